@@ -0,0 +1,365 @@
+//! Criterion benchmarks for the security module providers.
+//!
+//! Every provider that is compiled in through its feature flag is measured for key creation,
+//! signing, signature verification, encryption and decryption across several payload sizes.
+//! Besides criterion's own reports, a condensed `summary.json` with the mean, median and
+//! standard deviation of every benchmark is written to the criterion output directory, so
+//! results can be compared between runs or collected by CI.
+//!
+//! ```bash
+//! cargo bench --features macos
+//! ```
+//!
+//! Note that the benchmarks operate on real hardware and create persistent keys. Every key is
+//! deleted again after its benchmark, unless the provider cannot delete keys.
+use criterion::{criterion_group, BenchmarkId, Criterion, Throughput};
+use crypto_layer::common::traits::module_provider::Provider;
+use serde_json::{json, Value};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
+/// Payload sizes in bytes used for signing and verification.
+const SIGN_SIZES: [usize; 4] = [32, 1024, 16 * 1024, 64 * 1024];
+/// Payload sizes in bytes used for encryption and decryption.
+///
+/// Asymmetric encryption is bound to the key size, so sizes above
+/// `BenchTarget::max_encrypt_len` are skipped for the respective provider.
+const ENCRYPT_SIZES: [usize; 4] = [16, 62, 190, 446];
+
+/// The purpose a benchmark key is created for.
+#[derive(Clone, Copy, Debug)]
+enum KeyKind {
+    Signing,
+    Encryption,
+}
+
+/// A provider that takes part in the benchmarks.
+struct BenchTarget {
+    /// Name used as benchmark group prefix.
+    name: &'static str,
+    /// Creates a provider, generates a key with the given id and prepares it for `kind`.
+    /// Returns `None` if the provider does not support operations of that kind.
+    prepare: fn(key_id: &str, kind: KeyKind) -> Option<Box<dyn Provider>>,
+    /// Largest plaintext the encryption key is able to process.
+    max_encrypt_len: usize,
+}
+
+#[cfg(feature = "macos")]
+mod macos {
+    use super::KeyKind;
+    use crypto_layer::{
+        common::{
+            crypto::algorithms::{
+                encryption::{AsymmetricEncryption, EccCurves, EccSchemeAlgorithm},
+                hashes::{Hash, Sha2Bits},
+                KeyBits,
+            },
+            traits::module_provider::Provider,
+        },
+        tpm::macos::{SecureEnclaveConfig, SecureEnclaveProvider},
+    };
+
+    /// RSA-1024 with OAEP-SHA256 is able to encrypt at most 62 bytes.
+    pub const MAX_ENCRYPT_LEN: usize = 62;
+
+    fn config(kind: KeyKind) -> SecureEnclaveConfig {
+        let algorithm = match kind {
+            KeyKind::Signing => {
+                AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(EccCurves::P256))
+            }
+            KeyKind::Encryption => AsymmetricEncryption::Rsa(KeyBits::Bits1024),
+        };
+        SecureEnclaveConfig::new(Some(algorithm), Some(Hash::Sha2(Sha2Bits::Sha256)))
+    }
+
+    pub fn prepare(key_id: &str, kind: KeyKind) -> Option<Box<dyn Provider>> {
+        let mut provider = SecureEnclaveProvider::new(key_id.to_string());
+        provider.initialize_module().ok()?;
+        provider.create_key(key_id, Box::new(config(kind))).ok()?;
+        // The Secure Enclave provider only keeps its configuration after loading a key.
+        provider.load_key(key_id, Box::new(config(kind))).ok()?;
+        Some(Box::new(provider))
+    }
+}
+
+#[cfg(any(feature = "linux", feature = "win"))]
+mod tpm {
+    use super::KeyKind;
+    use crypto_layer::{
+        common::crypto::{
+            algorithms::{
+                encryption::{AsymmetricEncryption, BlockCiphers, SymmetricMode},
+                hashes::{Hash, Sha2Bits},
+                KeyBits,
+            },
+            KeyUsage,
+        },
+        tpm::TpmConfig,
+    };
+    use std::any::Any;
+
+    /// RSA-2048 with OAEP-SHA256 is able to encrypt at most 190 bytes.
+    pub const MAX_ENCRYPT_LEN: usize = 190;
+
+    pub fn config(kind: KeyKind) -> Box<dyn Any> {
+        let key_usages = match kind {
+            KeyKind::Signing => vec![KeyUsage::SignEncrypt],
+            KeyKind::Encryption => vec![KeyUsage::SignEncrypt, KeyUsage::Decrypt],
+        };
+        TpmConfig::new(
+            AsymmetricEncryption::Rsa(KeyBits::Bits2048),
+            BlockCiphers::Aes(SymmetricMode::Cfb, KeyBits::Bits128),
+            Hash::Sha2(Sha2Bits::Sha256),
+            key_usages,
+        )
+    }
+}
+
+#[cfg(feature = "linux")]
+mod linux {
+    use super::{tpm::config, KeyKind};
+    use crypto_layer::{common::traits::module_provider::Provider, tpm::linux::TpmProvider};
+
+    pub fn prepare(key_id: &str, kind: KeyKind) -> Option<Box<dyn Provider>> {
+        let mut provider = TpmProvider::new(key_id.to_string());
+        provider.initialize_module().ok()?;
+        provider.create_key(key_id, config(kind)).ok()?;
+        Some(Box::new(provider))
+    }
+}
+
+#[cfg(feature = "win")]
+mod win {
+    use super::{tpm::config, KeyKind};
+    use crypto_layer::{common::traits::module_provider::Provider, tpm::win::TpmProvider};
+
+    pub fn prepare(key_id: &str, kind: KeyKind) -> Option<Box<dyn Provider>> {
+        let mut provider = TpmProvider::new(key_id.to_string());
+        provider.initialize_module().ok()?;
+        provider.create_key(key_id, config(kind)).ok()?;
+        Some(Box::new(provider))
+    }
+}
+
+/// Collects all providers enabled through feature flags.
+#[allow(clippy::vec_init_then_push)]
+fn targets() -> Vec<BenchTarget> {
+    #[allow(unused_mut)]
+    let mut targets = Vec::new();
+    #[cfg(feature = "macos")]
+    targets.push(BenchTarget {
+        name: "secure_enclave",
+        prepare: macos::prepare,
+        max_encrypt_len: macos::MAX_ENCRYPT_LEN,
+    });
+    #[cfg(feature = "linux")]
+    targets.push(BenchTarget {
+        name: "tpm_linux",
+        prepare: linux::prepare,
+        max_encrypt_len: tpm::MAX_ENCRYPT_LEN,
+    });
+    #[cfg(feature = "win")]
+    targets.push(BenchTarget {
+        name: "tpm_windows",
+        prepare: win::prepare,
+        max_encrypt_len: tpm::MAX_ENCRYPT_LEN,
+    });
+    targets
+}
+
+/// Returns a key id that has not been used by a previous benchmark iteration.
+fn unique_key_id(prefix: &str) -> String {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    format!(
+        "bench_{}_{}_{}",
+        prefix,
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+/// Deletes a key created by a benchmark, so that repeated runs do not fill up the security module.
+fn delete_key(provider: &mut dyn Provider, key_id: &str) {
+    if let Err(e) = provider.delete_key(key_id) {
+        eprintln!("Failed to delete the benchmark key {}: {}", key_id, e);
+    }
+}
+
+/// Printable payload, as some providers only return ASCII plaintexts on decryption.
+fn payload(len: usize) -> Vec<u8> {
+    vec![b'a'; len]
+}
+
+fn bench_create(c: &mut Criterion, target: &BenchTarget) {
+    let mut group = c.benchmark_group(format!("{}/create_key", target.name));
+    // Every iteration creates and deletes a key in the security module, so keep the number of
+    // samples low.
+    group.sample_size(10);
+    for kind in [KeyKind::Signing, KeyKind::Encryption] {
+        group.bench_function(format!("{:?}", kind).to_lowercase(), |b| {
+            // Only the creation is measured, not the deletion of the key afterwards.
+            b.iter_custom(|iters| {
+                let mut elapsed = Duration::ZERO;
+                for _ in 0..iters {
+                    let key_id = unique_key_id(target.name);
+                    let start = Instant::now();
+                    let provider = (target.prepare)(&key_id, kind);
+                    elapsed += start.elapsed();
+                    if let Some(mut provider) = provider {
+                        delete_key(&mut *provider, &key_id);
+                    }
+                }
+                elapsed
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_sign_verify(c: &mut Criterion, target: &BenchTarget) {
+    let key_id = unique_key_id(target.name);
+    let Some(mut provider) = (target.prepare)(&key_id, KeyKind::Signing) else {
+        eprintln!("{}: signing not supported, skipping", target.name);
+        return;
+    };
+
+    let mut sign = c.benchmark_group(format!("{}/sign_data", target.name));
+    for size in SIGN_SIZES {
+        let data = payload(size);
+        sign.throughput(Throughput::Bytes(size as u64));
+        sign.bench_with_input(BenchmarkId::from_parameter(size), &data, |b, data| {
+            b.iter(|| provider.sign_data(data).expect("Failed to sign data"))
+        });
+    }
+    sign.finish();
+
+    let mut verify = c.benchmark_group(format!("{}/verify_signature", target.name));
+    for size in SIGN_SIZES {
+        let data = payload(size);
+        let signature = provider.sign_data(&data).expect("Failed to sign data");
+        verify.throughput(Throughput::Bytes(size as u64));
+        verify.bench_with_input(BenchmarkId::from_parameter(size), &data, |b, data| {
+            b.iter(|| {
+                provider
+                    .verify_signature(data, &signature)
+                    .expect("Failed to verify signature")
+            })
+        });
+    }
+    verify.finish();
+    delete_key(&mut *provider, &key_id);
+}
+
+fn bench_encrypt_decrypt(c: &mut Criterion, target: &BenchTarget) {
+    let key_id = unique_key_id(target.name);
+    let Some(mut provider) = (target.prepare)(&key_id, KeyKind::Encryption) else {
+        eprintln!("{}: encryption not supported, skipping", target.name);
+        return;
+    };
+    let sizes: Vec<usize> = ENCRYPT_SIZES
+        .into_iter()
+        .filter(|size| *size <= target.max_encrypt_len)
+        .collect();
+
+    let mut encrypt = c.benchmark_group(format!("{}/encrypt_data", target.name));
+    for size in &sizes {
+        let data = payload(*size);
+        encrypt.throughput(Throughput::Bytes(*size as u64));
+        encrypt.bench_with_input(BenchmarkId::from_parameter(size), &data, |b, data| {
+            b.iter(|| provider.encrypt_data(data).expect("Failed to encrypt data"))
+        });
+    }
+    encrypt.finish();
+
+    let mut decrypt = c.benchmark_group(format!("{}/decrypt_data", target.name));
+    for size in &sizes {
+        let ciphertext = provider
+            .encrypt_data(&payload(*size))
+            .expect("Failed to encrypt data");
+        decrypt.throughput(Throughput::Bytes(*size as u64));
+        decrypt.bench_with_input(BenchmarkId::from_parameter(size), &ciphertext, |b, data| {
+            b.iter(|| provider.decrypt_data(data).expect("Failed to decrypt data"))
+        });
+    }
+    decrypt.finish();
+    delete_key(&mut *provider, &key_id);
+}
+
+fn bench_providers(c: &mut Criterion) {
+    let targets = targets();
+    if targets.is_empty() {
+        eprintln!("No provider feature enabled, nothing to benchmark.");
+    }
+    for target in &targets {
+        bench_create(c, target);
+        bench_sign_verify(c, target);
+        bench_encrypt_decrypt(c, target);
+    }
+}
+
+/// Directory criterion writes its results to.
+fn criterion_dir() -> PathBuf {
+    if let Ok(home) = std::env::var("CRITERION_HOME") {
+        return PathBuf::from(home);
+    }
+    let target_dir = std::env::var("CARGO_TARGET_DIR").unwrap_or_else(|_| "target".to_string());
+    Path::new(&target_dir).join("criterion")
+}
+
+/// Recursively collects the `new` estimates of every benchmark below `dir`.
+fn collect_estimates(dir: &Path, results: &mut Vec<Value>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if !path.is_dir() {
+            continue;
+        }
+        if path.file_name().is_some_and(|name| name == "new") {
+            let read = |file: &str| -> Option<Value> {
+                serde_json::from_str(&fs::read_to_string(path.join(file)).ok()?).ok()
+            };
+            if let (Some(benchmark), Some(estimates)) =
+                (read("benchmark.json"), read("estimates.json"))
+            {
+                results.push(json!({
+                    "id": benchmark["full_id"],
+                    "throughput": benchmark["throughput"],
+                    "mean_ns": estimates["mean"]["point_estimate"],
+                    "median_ns": estimates["median"]["point_estimate"],
+                    "std_dev_ns": estimates["std_dev"]["point_estimate"],
+                }));
+            }
+        } else {
+            collect_estimates(&path, results);
+        }
+    }
+}
+
+/// Writes a condensed JSON summary of the latest benchmark run.
+fn write_summary() {
+    let dir = criterion_dir();
+    let mut results = Vec::new();
+    collect_estimates(&dir, &mut results);
+    results.sort_by(|a, b| a["id"].to_string().cmp(&b["id"].to_string()));
+
+    let path = dir.join("summary.json");
+    let summary = json!({ "benchmarks": results });
+    match fs::write(&path, serde_json::to_string_pretty(&summary).unwrap()) {
+        Ok(()) => println!("Benchmark summary written to {}", path.display()),
+        Err(e) => eprintln!("Failed to write benchmark summary: {}", e),
+    }
+}
+
+criterion_group!(benches, bench_providers);
+
+fn main() {
+    benches();
+    Criterion::default().configure_from_args().final_summary();
+    write_summary();
+}
@@ -0,0 +1,143 @@
+//! Generates the UniFFI scaffolding and checks the interface between the Secure Enclave provider
+//! and the Swift bindings.
+//!
+//! With the `uniffi` feature, the scaffolding of `src/crypto_layer.udl` is generated for
+//! `ffi::mobile`.
+//!
+//! With the `macos` feature, the identifiers of `src/tpm/macos/swift_interface.txt` are generated as
+//! constants for `tpm::macos::interface`, and the build fails if the Swift bindings do not handle
+//! one of them. Identifiers the Rust side passes therefore either exist in the Swift bindings or do
+//! not compile, instead of failing at runtime with an "unsupported algorithm" message.
+
+use std::{env, fmt::Write, fs, path::Path};
+
+const INTERFACE: &str = "src/tpm/macos/swift_interface.txt";
+const SWIFT_SOURCE: &str =
+    "src/tpm/macos/swift_rust_wrapper/swift-library/Sources/swift-library/SecureEnclaveManager.swift";
+const KINDS: [&str; 4] = ["algorithm", "hash", "access", "key_type"];
+#[cfg(feature = "uniffi")]
+const UDL: &str = "src/crypto_layer.udl";
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    #[cfg(feature = "uniffi")]
+    uniffi::generate_scaffolding(UDL)
+        .unwrap_or_else(|e| panic!("Cannot generate the scaffolding of {}: {}", UDL, e));
+    if env::var_os("CARGO_FEATURE_MACOS").is_none() {
+        return;
+    }
+    println!("cargo:rerun-if-changed={}", INTERFACE);
+    println!("cargo:rerun-if-changed={}", SWIFT_SOURCE);
+
+    let interface = read(INTERFACE);
+    let swift = read(SWIFT_SOURCE);
+    let identifiers = parse(&interface);
+
+    let mut drift = Vec::new();
+    for (kind, identifier) in &identifiers {
+        if let Some(problem) = check(kind, identifier, &identifiers, &swift) {
+            drift.push(format!("{} {}: {}", kind, identifier, problem));
+        }
+    }
+    if !drift.is_empty() {
+        panic!(
+            "The Swift bindings do not match {}:\n{}",
+            INTERFACE,
+            drift.join("\n")
+        );
+    }
+
+    let out = Path::new(&env::var_os("OUT_DIR").unwrap()).join("swift_interface.rs");
+    fs::write(out, generate(&identifiers)).unwrap();
+}
+
+fn read(path: &str) -> String {
+    fs::read_to_string(path).unwrap_or_else(|e| panic!("Cannot read {}: {}", path, e))
+}
+
+/// Returns the kind and identifier of every line that is neither empty nor a comment.
+fn parse(interface: &str) -> Vec<(String, String)> {
+    interface
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(
+            |line| match line.split_whitespace().collect::<Vec<_>>()[..] {
+                [kind, identifier] if KINDS.contains(&kind) => {
+                    (kind.to_owned(), identifier.to_owned())
+                }
+                _ => panic!("Invalid line in {}: '{}'", INTERFACE, line),
+            },
+        )
+        .collect()
+}
+
+/// Returns why the Swift bindings do not handle `identifier`, or `None` if they do.
+fn check(
+    kind: &str,
+    identifier: &str,
+    identifiers: &[(String, String)],
+    swift: &str,
+) -> Option<String> {
+    let matched = |identifier: &str| {
+        swift.contains(&format!("case \"{}\"", identifier))
+            || swift.contains(&format!("== \"{}\"", identifier))
+    };
+    match kind {
+        "key_type" => {
+            let Some((algorithm, size)) = identifier.split_once(';') else {
+                return Some("expected algorithm;size".to_owned());
+            };
+            if !identifiers.contains(&("algorithm".to_owned(), algorithm.to_owned())) {
+                Some(format!(
+                    "'{}' is not an algorithm of the interface",
+                    algorithm
+                ))
+            } else if size.parse::<u32>().is_err() {
+                Some(format!("'{}' is not a key size", size))
+            } else {
+                None
+            }
+        }
+        _ if matched(identifier) => None,
+        _ => Some(format!("not matched in {}", SWIFT_SOURCE)),
+    }
+}
+
+/// Generates a module per kind with a constant per identifier, e.g. `access::BIOMETRY_ANY`.
+fn generate(identifiers: &[(String, String)]) -> String {
+    let mut code = String::new();
+    for kind in KINDS {
+        writeln!(code, "pub mod {} {{", kind).unwrap();
+        for (_, identifier) in identifiers.iter().filter(|(k, _)| k == kind) {
+            writeln!(
+                code,
+                "    pub const {}: &str = {:?};",
+                constant_name(identifier),
+                identifier
+            )
+            .unwrap();
+        }
+        writeln!(code, "}}").unwrap();
+    }
+    code
+}
+
+/// Converts an identifier like `biometryAny` or `RSA;512` into a constant name like `BIOMETRY_ANY`
+/// or `RSA_512`.
+fn constant_name(identifier: &str) -> String {
+    let mut name = String::new();
+    let mut previous_lowercase = false;
+    for c in identifier.chars() {
+        if c.is_ascii_uppercase() && previous_lowercase {
+            name.push('_');
+        }
+        previous_lowercase = c.is_ascii_lowercase();
+        name.push(if c.is_ascii_alphanumeric() {
+            c.to_ascii_uppercase()
+        } else {
+            '_'
+        });
+    }
+    name
+}
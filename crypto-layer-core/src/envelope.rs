@@ -0,0 +1,419 @@
+//! The binary format of ciphertexts produced by the crypto layer.
+//!
+//! An envelope consists of a header describing how the ciphertext was produced, followed by the
+//! ciphertext including the authentication tag:
+//!
+//! ```text
+//! magic "CLEV" | version (u8) | AEAD algorithm (u8) | fields ... | 0x00 | ciphertext || tag
+//! ```
+//!
+//! Every field is encoded as `tag (u8) | length (u16, big endian) | value`. Fields with the high
+//! bit of the tag set are optional extensions and are ignored by parsers that do not know them,
+//! all other unknown fields are rejected. Optional fields follow all other fields, so that an
+//! envelope re-encoded by a parser that does not know them has the same header. Producers pass
+//! the encoded header as associated data to the AEAD, so that none of the header fields can be
+//! modified without detection.
+
+use crate::{
+    kdf::Kdf,
+    redact::{Redacted, RedactedOption},
+    CoreError,
+};
+use alloc::{string::String, vec::Vec};
+use core::fmt;
+
+/// The magic bytes every envelope starts with.
+pub const MAGIC: [u8; 4] = *b"CLEV";
+/// The version of the envelope format produced by this crate.
+pub const VERSION: u8 = 1;
+
+const END_OF_HEADER: u8 = 0x00;
+const KEY_ID: u8 = 0x01;
+const NONCE: u8 = 0x02;
+const WRAPPED_KEY: u8 = 0x03;
+const EPHEMERAL_PUBLIC_KEY: u8 = 0x04;
+const KDF: u8 = 0x05;
+const SALT: u8 = 0x06;
+const OPTIONAL_FIELD: u8 = 0x80;
+
+/// Authenticated encryption algorithms used for the payload of an envelope.
+///
+/// The numeric value of every variant is its identifier in encoded envelopes and must not change.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AeadAlgorithm {
+    /// AES-128 in Galois/Counter Mode.
+    Aes128Gcm = 1,
+    /// AES-256 in Galois/Counter Mode.
+    Aes256Gcm = 2,
+    /// ChaCha20-Poly1305 (RFC 8439).
+    ChaCha20Poly1305 = 3,
+}
+
+impl AeadAlgorithm {
+    /// Returns the identifier of the algorithm in encoded envelopes.
+    pub fn id(self) -> u8 {
+        self as u8
+    }
+
+    /// Returns the algorithm with the given identifier.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `AeadAlgorithm`, or `CoreError::UnknownAlgorithm` if the
+    /// identifier is not known.
+    pub fn from_id(id: u8) -> Result<Self, CoreError> {
+        match id {
+            1 => Ok(AeadAlgorithm::Aes128Gcm),
+            2 => Ok(AeadAlgorithm::Aes256Gcm),
+            3 => Ok(AeadAlgorithm::ChaCha20Poly1305),
+            _ => Err(CoreError::UnknownAlgorithm(id)),
+        }
+    }
+
+    /// Returns the key length in bytes.
+    pub fn key_len(self) -> usize {
+        match self {
+            AeadAlgorithm::Aes128Gcm => 16,
+            AeadAlgorithm::Aes256Gcm | AeadAlgorithm::ChaCha20Poly1305 => 32,
+        }
+    }
+
+    /// Returns the nonce length in bytes.
+    pub fn nonce_len(self) -> usize {
+        12
+    }
+
+    /// Returns the length of the authentication tag appended to the ciphertext in bytes.
+    pub fn tag_len(self) -> usize {
+        16
+    }
+}
+
+/// A parsed envelope borrowing from the encoded bytes.
+///
+/// Parsing does not allocate, which makes `EnvelopeRef` suitable for constrained targets.
+///
+/// The `Debug` representation redacts the wrapped key and the ciphertext, see `redact`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct EnvelopeRef<'a> {
+    /// The algorithm the payload is encrypted with.
+    pub aead: AeadAlgorithm,
+    /// The identifier of the key the payload (or the wrapped data key) is encrypted with.
+    pub key_id: &'a str,
+    /// The nonce of the AEAD.
+    pub nonce: &'a [u8],
+    /// The data key encrypted under the key identified by `key_id`, for envelope encryption.
+    pub wrapped_key: Option<&'a [u8]>,
+    /// The ephemeral public key of the sender, for hybrid public-key encryption.
+    pub ephemeral_public_key: Option<&'a [u8]>,
+    /// The KDF the payload key was derived with.
+    pub kdf: Option<Kdf>,
+    /// The salt passed to the KDF.
+    pub salt: Option<&'a [u8]>,
+    /// The encoded optional fields, which are not interpreted by this version of the format.
+    pub extensions: &'a [u8],
+    /// The encoded header, to be passed as associated data when decrypting.
+    pub header: &'a [u8],
+    /// The ciphertext including the authentication tag.
+    pub ciphertext: &'a [u8],
+}
+
+impl<'a> EnvelopeRef<'a> {
+    /// Parses an encoded envelope.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - The encoded envelope.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the parsed envelope, or a `CoreError` describing why `bytes` is
+    /// not a valid envelope.
+    pub fn parse(bytes: &'a [u8]) -> Result<Self, CoreError> {
+        let mut reader = Reader { input: bytes };
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(CoreError::InvalidMagic);
+        }
+        let version = reader.byte()?;
+        if version != VERSION {
+            return Err(CoreError::UnsupportedVersion(version));
+        }
+        let aead = AeadAlgorithm::from_id(reader.byte()?)?;
+
+        let mut key_id = None;
+        let mut nonce = None;
+        let mut wrapped_key = None;
+        let mut ephemeral_public_key = None;
+        let mut kdf = None;
+        let mut salt = None;
+        let mut extensions: Option<&[u8]> = None;
+
+        loop {
+            let rest = reader.input;
+            let tag = reader.byte()?;
+            if tag == END_OF_HEADER {
+                break;
+            }
+            let value = reader.value()?;
+            if tag & OPTIONAL_FIELD != 0 {
+                extensions.get_or_insert(rest);
+                continue;
+            }
+            if extensions.is_some() {
+                return Err(CoreError::MisplacedField(tag));
+            }
+            let slot = match tag {
+                KEY_ID => &mut key_id,
+                NONCE => &mut nonce,
+                WRAPPED_KEY => &mut wrapped_key,
+                EPHEMERAL_PUBLIC_KEY => &mut ephemeral_public_key,
+                KDF => &mut kdf,
+                SALT => &mut salt,
+                _ => return Err(CoreError::UnknownField(tag)),
+            };
+            if slot.replace(value).is_some() {
+                return Err(CoreError::DuplicateField(tag));
+            }
+        }
+
+        let key_id = key_id.ok_or(CoreError::MissingField("key_id"))?;
+        let key_id = core::str::from_utf8(key_id).map_err(|_| CoreError::InvalidField("key_id"))?;
+        let nonce = nonce.ok_or(CoreError::MissingField("nonce"))?;
+        if nonce.len() != aead.nonce_len() {
+            return Err(CoreError::InvalidField("nonce"));
+        }
+        let kdf = match kdf {
+            Some([id]) => Some(Kdf::from_id(*id)?),
+            Some(_) => return Err(CoreError::InvalidField("kdf")),
+            None => None,
+        };
+
+        // Everything from the first optional field up to the end of header marker.
+        let extensions =
+            extensions.map_or(&[][..], |rest| &rest[..rest.len() - reader.input.len() - 1]);
+        let header = &bytes[..bytes.len() - reader.input.len()];
+        let ciphertext = reader.input;
+        if ciphertext.len() < aead.tag_len() {
+            return Err(CoreError::Truncated);
+        }
+
+        Ok(Self {
+            aead,
+            key_id,
+            nonce,
+            wrapped_key,
+            ephemeral_public_key,
+            kdf,
+            salt,
+            extensions,
+            header,
+            ciphertext,
+        })
+    }
+
+    /// Copies the envelope into an owned `Envelope`.
+    ///
+    /// Optional fields are kept, so that the copy encodes to the same header.
+    pub fn to_envelope(&self) -> Envelope {
+        let mut reader = Reader {
+            input: self.extensions,
+        };
+        let mut extensions = Vec::new();
+        while let Ok(tag) = reader.byte() {
+            // `parse` has checked that the optional fields are complete.
+            let value = reader.value().unwrap_or_default();
+            extensions.push((tag, value.to_vec()));
+        }
+
+        Envelope {
+            aead: self.aead,
+            key_id: self.key_id.into(),
+            nonce: self.nonce.to_vec(),
+            wrapped_key: self.wrapped_key.map(<[u8]>::to_vec),
+            ephemeral_public_key: self.ephemeral_public_key.map(<[u8]>::to_vec),
+            kdf: self.kdf,
+            salt: self.salt.map(<[u8]>::to_vec),
+            extensions,
+            ciphertext: self.ciphertext.to_vec(),
+        }
+    }
+}
+
+impl fmt::Debug for EnvelopeRef<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EnvelopeRef")
+            .field("aead", &self.aead)
+            .field("key_id", &self.key_id)
+            .field("nonce", &self.nonce)
+            .field("wrapped_key", &RedactedOption(self.wrapped_key))
+            .field("ephemeral_public_key", &self.ephemeral_public_key)
+            .field("kdf", &self.kdf)
+            .field("salt", &self.salt)
+            .field("extensions", &self.extensions)
+            .field("ciphertext", &Redacted(self.ciphertext))
+            .finish_non_exhaustive()
+    }
+}
+
+/// An owned envelope, used to produce encoded envelopes.
+///
+/// The `Debug` representation redacts the wrapped key and the ciphertext, see `redact`.
+#[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Envelope {
+    /// The algorithm the payload is encrypted with.
+    pub aead: AeadAlgorithm,
+    /// The identifier of the key the payload (or the wrapped data key) is encrypted with.
+    pub key_id: String,
+    /// The nonce of the AEAD.
+    pub nonce: Vec<u8>,
+    /// The data key encrypted under the key identified by `key_id`, for envelope encryption.
+    pub wrapped_key: Option<Vec<u8>>,
+    /// The ephemeral public key of the sender, for hybrid public-key encryption.
+    pub ephemeral_public_key: Option<Vec<u8>>,
+    /// The KDF the payload key was derived with.
+    pub kdf: Option<Kdf>,
+    /// The salt passed to the KDF.
+    pub salt: Option<Vec<u8>>,
+    /// Optional fields as tag and value, encoded after all other fields in the given order.
+    ///
+    /// Every tag must have the high bit set. Parsing keeps the optional fields it does not
+    /// interpret, so that the header of a parsed envelope is encoded unchanged.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    pub extensions: Vec<(u8, Vec<u8>)>,
+    /// The ciphertext including the authentication tag.
+    pub ciphertext: Vec<u8>,
+}
+
+impl fmt::Debug for Envelope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Envelope")
+            .field("aead", &self.aead)
+            .field("key_id", &self.key_id)
+            .field("nonce", &self.nonce)
+            .field("wrapped_key", &RedactedOption(self.wrapped_key.as_deref()))
+            .field("ephemeral_public_key", &self.ephemeral_public_key)
+            .field("kdf", &self.kdf)
+            .field("salt", &self.salt)
+            .field("extensions", &self.extensions)
+            .field("ciphertext", &Redacted(&self.ciphertext))
+            .finish()
+    }
+}
+
+impl Envelope {
+    /// Creates an envelope without the optional fields and an empty ciphertext.
+    ///
+    /// The ciphertext is usually set after encrypting with `header` as associated data.
+    pub fn new(aead: AeadAlgorithm, key_id: impl Into<String>, nonce: Vec<u8>) -> Self {
+        Self {
+            aead,
+            key_id: key_id.into(),
+            nonce,
+            wrapped_key: None,
+            ephemeral_public_key: None,
+            kdf: None,
+            salt: None,
+            extensions: Vec::new(),
+            ciphertext: Vec::new(),
+        }
+    }
+
+    /// Parses an encoded envelope. See `EnvelopeRef::parse`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, CoreError> {
+        EnvelopeRef::parse(bytes).map(|envelope| envelope.to_envelope())
+    }
+
+    /// Encodes the header of the envelope, to be passed as associated data to the AEAD.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the encoded header, or a `CoreError` if a field is invalid or
+    /// longer than 65535 bytes, or an extension does not have the high bit of its tag set.
+    pub fn header(&self) -> Result<Vec<u8>, CoreError> {
+        if self.nonce.len() != self.aead.nonce_len() {
+            return Err(CoreError::InvalidField("nonce"));
+        }
+
+        let mut header = Vec::with_capacity(64 + self.key_id.len());
+        header.extend_from_slice(&MAGIC);
+        header.push(VERSION);
+        header.push(self.aead.id());
+        write_field(&mut header, KEY_ID, self.key_id.as_bytes())?;
+        write_field(&mut header, NONCE, &self.nonce)?;
+        if let Some(wrapped_key) = &self.wrapped_key {
+            write_field(&mut header, WRAPPED_KEY, wrapped_key)?;
+        }
+        if let Some(ephemeral_public_key) = &self.ephemeral_public_key {
+            write_field(&mut header, EPHEMERAL_PUBLIC_KEY, ephemeral_public_key)?;
+        }
+        if let Some(kdf) = self.kdf {
+            write_field(&mut header, KDF, &[kdf.id()])?;
+        }
+        if let Some(salt) = &self.salt {
+            write_field(&mut header, SALT, salt)?;
+        }
+        for (tag, value) in &self.extensions {
+            if tag & OPTIONAL_FIELD == 0 {
+                return Err(CoreError::InvalidField("extensions"));
+            }
+            write_field(&mut header, *tag, value)?;
+        }
+        header.push(END_OF_HEADER);
+        Ok(header)
+    }
+
+    /// Encodes the envelope.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the encoded envelope, or a `CoreError` if a field is invalid or
+    /// the ciphertext is shorter than the authentication tag.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, CoreError> {
+        if self.ciphertext.len() < self.aead.tag_len() {
+            return Err(CoreError::InvalidField("ciphertext"));
+        }
+        let mut bytes = self.header()?;
+        bytes.extend_from_slice(&self.ciphertext);
+        Ok(bytes)
+    }
+}
+
+fn write_field(output: &mut Vec<u8>, tag: u8, value: &[u8]) -> Result<(), CoreError> {
+    let len = u16::try_from(value.len()).map_err(|_| CoreError::FieldTooLong)?;
+    output.push(tag);
+    output.extend_from_slice(&len.to_be_bytes());
+    output.extend_from_slice(value);
+    Ok(())
+}
+
+struct Reader<'a> {
+    input: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn byte(&mut self) -> Result<u8, CoreError> {
+        let (&byte, rest) = self.input.split_first().ok_or(CoreError::Truncated)?;
+        self.input = rest;
+        Ok(byte)
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], CoreError> {
+        if self.input.len() < len {
+            return Err(CoreError::Truncated);
+        }
+        let (value, rest) = self.input.split_at(len);
+        self.input = rest;
+        Ok(value)
+    }
+
+    fn value(&mut self) -> Result<&'a [u8], CoreError> {
+        let len = u16::from_be_bytes([self.byte()?, self.byte()?]);
+        self.take(len as usize)
+    }
+}
@@ -0,0 +1,115 @@
+use core::fmt;
+
+/// Errors that can occur while encoding or decoding artifacts of the crypto layer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoreError {
+    /// The input ended before a complete structure was read.
+    Truncated,
+    /// The input does not start with the envelope magic bytes.
+    InvalidMagic,
+    /// The envelope was produced by an unsupported version of the format.
+    UnsupportedVersion(u8),
+    /// The algorithm identifier is not known.
+    UnknownAlgorithm(u8),
+    /// The envelope contains a critical field that is not known.
+    UnknownField(u8),
+    /// The envelope contains the same field more than once.
+    DuplicateField(u8),
+    /// A required field of the envelope is missing.
+    MissingField(&'static str),
+    /// A field is too long to be encoded.
+    FieldTooLong,
+    /// A field has an invalid value.
+    InvalidField(&'static str),
+    /// The signature is not a valid DER or raw encoded ECDSA signature.
+    InvalidSignatureEncoding,
+    /// The public key is not a valid encoded key.
+    InvalidPublicKey,
+    /// The requested output length is not supported.
+    InvalidLength,
+    /// The public key is valid, but its algorithm is not supported by the operation.
+    UnsupportedKeyAlgorithm,
+    /// The label of a signing context is empty, too long or not printable ASCII.
+    InvalidSigningContext,
+    /// The envelope contains a critical field after an optional field.
+    MisplacedField(u8),
+}
+
+impl CoreError {
+    /// Returns the stable numeric code of the error variant.
+    ///
+    /// Codes never change and are never reused, new variants get new codes.
+    pub fn code(&self) -> u16 {
+        match *self {
+            CoreError::Truncated => 1,
+            CoreError::InvalidMagic => 2,
+            CoreError::UnsupportedVersion(_) => 3,
+            CoreError::UnknownAlgorithm(_) => 4,
+            CoreError::UnknownField(_) => 5,
+            CoreError::DuplicateField(_) => 6,
+            CoreError::MissingField(_) => 7,
+            CoreError::FieldTooLong => 8,
+            CoreError::InvalidField(_) => 9,
+            CoreError::InvalidSignatureEncoding => 10,
+            CoreError::InvalidPublicKey => 11,
+            CoreError::InvalidLength => 12,
+            CoreError::UnsupportedKeyAlgorithm => 13,
+            CoreError::InvalidSigningContext => 14,
+            CoreError::MisplacedField(_) => 15,
+        }
+    }
+}
+
+impl fmt::Display for CoreError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CoreError::Truncated => write!(f, "Input is truncated"),
+            CoreError::InvalidMagic => write!(f, "Input is not an envelope"),
+            CoreError::UnsupportedVersion(version) => {
+                write!(f, "Unsupported envelope version: {}", version)
+            }
+            CoreError::UnknownAlgorithm(id) => write!(f, "Unknown algorithm: {}", id),
+            CoreError::UnknownField(tag) => write!(f, "Unknown envelope field: {}", tag),
+            CoreError::DuplicateField(tag) => write!(f, "Duplicate envelope field: {}", tag),
+            CoreError::MissingField(name) => write!(f, "Missing envelope field: {}", name),
+            CoreError::FieldTooLong => write!(f, "Envelope field is too long"),
+            CoreError::InvalidField(name) => write!(f, "Invalid envelope field: {}", name),
+            CoreError::InvalidSignatureEncoding => write!(f, "Invalid signature encoding"),
+            CoreError::InvalidPublicKey => write!(f, "Invalid public key"),
+            CoreError::InvalidLength => write!(f, "Invalid length"),
+            CoreError::UnsupportedKeyAlgorithm => write!(f, "Unsupported public key algorithm"),
+            CoreError::InvalidSigningContext => write!(f, "Invalid signing context"),
+            CoreError::MisplacedField(tag) => {
+                write!(f, "Envelope field after optional fields: {}", tag)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for CoreError {}
+
+/// Serializes the error as its stable code and message, e.g.
+/// `{"code": 1, "message": "Input is truncated"}`.
+#[cfg(feature = "serde")]
+impl serde::Serialize for CoreError {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut state = serializer.serialize_struct("CoreError", 2)?;
+        state.serialize_field("code", &self.code())?;
+        state.serialize_field("message", &Message(self))?;
+        state.end()
+    }
+}
+
+/// Serializes the `Display` representation of an error without allocating.
+#[cfg(feature = "serde")]
+struct Message<'a>(&'a CoreError);
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Message<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self.0)
+    }
+}
@@ -0,0 +1,92 @@
+use crate::{secret::SecretBytes, CoreError};
+use hkdf::Hkdf;
+use sha2::{Sha256, Sha384, Sha512};
+
+/// Key derivation functions used by the crypto layer.
+///
+/// The numeric value of every variant is its identifier in encoded artifacts and must not change.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Kdf {
+    /// HKDF (RFC 5869) with HMAC-SHA-256.
+    HkdfSha256 = 1,
+    /// HKDF (RFC 5869) with HMAC-SHA-384.
+    HkdfSha384 = 2,
+    /// HKDF (RFC 5869) with HMAC-SHA-512.
+    HkdfSha512 = 3,
+}
+
+impl Kdf {
+    /// Returns the identifier of the KDF in encoded artifacts.
+    pub fn id(self) -> u8 {
+        self as u8
+    }
+
+    /// Returns the KDF with the given identifier.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `Kdf`, or `CoreError::UnknownAlgorithm` if the identifier is not known.
+    pub fn from_id(id: u8) -> Result<Self, CoreError> {
+        match id {
+            1 => Ok(Kdf::HkdfSha256),
+            2 => Ok(Kdf::HkdfSha384),
+            3 => Ok(Kdf::HkdfSha512),
+            _ => Err(CoreError::UnknownAlgorithm(id)),
+        }
+    }
+
+    /// Returns the maximum number of bytes a single derivation can produce.
+    pub fn max_output_len(self) -> usize {
+        let hash_len = match self {
+            Kdf::HkdfSha256 => 32,
+            Kdf::HkdfSha384 => 48,
+            Kdf::HkdfSha512 => 64,
+        };
+        255 * hash_len
+    }
+
+    /// Derives key material into `output`.
+    ///
+    /// # Arguments
+    ///
+    /// * `ikm` - The input keying material.
+    /// * `salt` - An optional salt. HKDF uses a string of zeros if no salt is given.
+    /// * `info` - Context and application specific information binding the output to its purpose.
+    /// * `output` - The buffer to fill with derived key material.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` that is `Ok(())` on success, or `CoreError::InvalidLength` if `output` is
+    /// longer than `max_output_len`.
+    pub fn derive(
+        self,
+        ikm: &[u8],
+        salt: Option<&[u8]>,
+        info: &[u8],
+        output: &mut [u8],
+    ) -> Result<(), CoreError> {
+        let result = match self {
+            Kdf::HkdfSha256 => Hkdf::<Sha256>::new(salt, ikm).expand(info, output),
+            Kdf::HkdfSha384 => Hkdf::<Sha384>::new(salt, ikm).expand(info, output),
+            Kdf::HkdfSha512 => Hkdf::<Sha512>::new(salt, ikm).expand(info, output),
+        };
+        result.map_err(|_| CoreError::InvalidLength)
+    }
+
+    /// Derives `len` bytes of key material, which are zeroed when dropped.
+    ///
+    /// See `derive` for a description of the arguments.
+    pub fn derive_vec(
+        self,
+        ikm: &[u8],
+        salt: Option<&[u8]>,
+        info: &[u8],
+        len: usize,
+    ) -> Result<SecretBytes, CoreError> {
+        let mut output = SecretBytes::zeroed(len);
+        self.derive(ikm, salt, info, &mut output)?;
+        Ok(output)
+    }
+}
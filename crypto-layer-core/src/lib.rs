@@ -0,0 +1,35 @@
+//! The `no_std` core of the crypto layer.
+//!
+//! Providers need `std` and access to the platform's security module, but the artifacts they
+//! produce do not. This crate contains everything needed to parse and verify those artifacts
+//! and builds with `no_std + alloc`, so that embedded targets can consume them:
+//!
+//! - [`envelope`]: the binary format of ciphertexts produced by the crate.
+//! - [`signature_format`]: conversion between DER and raw (IEEE P1363) ECDSA signatures and,
+//!   with the `p256` and `p384` features, verification of P-256 and P-384 signatures.
+//! - [`spki`]: the DER and PEM encoded public keys exported by providers.
+//! - [`kdf`]: the key derivation functions referenced by envelopes.
+//! - [`redact`]: the redaction of sensitive bytes in `Debug` output.
+//! - [`secret`]: secret bytes that are zeroed when dropped.
+//! - [`signing_context`]: the domain separation of signatures with context labels.
+//!
+//! With the `wasm` feature the crate builds to a WebAssembly module for web frontends, see
+//! `wasm`.
+#![no_std]
+
+extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
+
+pub mod envelope;
+mod error;
+pub mod kdf;
+pub mod redact;
+pub mod secret;
+pub mod signature_format;
+pub mod signing_context;
+pub mod spki;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+pub use error::CoreError;
@@ -0,0 +1,106 @@
+//! Redaction of sensitive bytes in `Debug` output.
+//!
+//! Key material, plaintexts, ciphertexts and signatures are formatted through [`Redacted`], which
+//! shows as much of them as the crate-wide [`RedactionPolicy`] allows. By default only their
+//! length and a short hash prefix are shown, which is enough to tell two values apart in a log
+//! without revealing them.
+
+use core::{
+    fmt,
+    sync::atomic::{AtomicU8, Ordering},
+};
+use sha2::{Digest, Sha256};
+
+/// The number of bytes of the SHA-256 hash shown by `RedactionPolicy::Hashed`.
+const HASH_PREFIX_LEN: usize = 4;
+
+static POLICY: AtomicU8 = AtomicU8::new(RedactionPolicy::Hashed as u8);
+
+/// How much of a sensitive value `Debug` output shows.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RedactionPolicy {
+    /// The length and the first bytes of the SHA-256 hash, e.g. `<6 bytes, sha256:2bb80d53>`.
+    ///
+    /// The hash prefix allows to brute force short, guessable values such as PINs. Use
+    /// `LengthOnly` if such values are logged.
+    #[default]
+    Hashed = 0,
+    /// Only the length, e.g. `<6 bytes>`.
+    LengthOnly = 1,
+    /// The length and the value in hex, e.g. `<6 bytes: 736563726574>`.
+    ///
+    /// Only meant for debugging with test keys. Never enable this in production, as logs then
+    /// contain key material and plaintexts.
+    Reveal = 2,
+}
+
+impl RedactionPolicy {
+    /// Returns the policy currently applied by `Redacted`.
+    pub fn current() -> Self {
+        match POLICY.load(Ordering::Relaxed) {
+            1 => RedactionPolicy::LengthOnly,
+            2 => RedactionPolicy::Reveal,
+            _ => RedactionPolicy::Hashed,
+        }
+    }
+
+    /// Sets the policy applied by `Redacted` in the whole process.
+    pub fn set(policy: RedactionPolicy) {
+        POLICY.store(policy as u8, Ordering::Relaxed);
+    }
+
+    /// Formats `bytes` according to this policy.
+    pub fn format(self, bytes: &[u8], f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<{} bytes", bytes.len())?;
+        match self {
+            RedactionPolicy::Hashed => {
+                f.write_str(", sha256:")?;
+                write_hex(f, &Sha256::digest(bytes)[..HASH_PREFIX_LEN])?;
+            }
+            RedactionPolicy::LengthOnly => {}
+            RedactionPolicy::Reveal => {
+                f.write_str(": ")?;
+                write_hex(f, bytes)?;
+            }
+        }
+        f.write_str(">")
+    }
+}
+
+fn write_hex(f: &mut fmt::Formatter<'_>, bytes: &[u8]) -> fmt::Result {
+    bytes.iter().try_for_each(|byte| write!(f, "{:02x}", byte))
+}
+
+/// Formats sensitive bytes according to the current `RedactionPolicy`.
+///
+/// ```
+/// use crypto_layer_core::redact::Redacted;
+///
+/// let plaintext = b"secret";
+/// assert_eq!(
+///     format!("{:?}", Redacted(plaintext)),
+///     "<6 bytes, sha256:2bb80d53>"
+/// );
+/// ```
+#[derive(Clone, Copy)]
+pub struct Redacted<'a>(pub &'a [u8]);
+
+impl fmt::Debug for Redacted<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        RedactionPolicy::current().format(self.0, f)
+    }
+}
+
+/// Formats an optional sensitive value, e.g. a field of an envelope.
+pub(crate) struct RedactedOption<'a>(pub(crate) Option<&'a [u8]>);
+
+impl fmt::Debug for RedactedOption<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(bytes) => f.debug_tuple("Some").field(&Redacted(bytes)).finish(),
+            None => f.write_str("None"),
+        }
+    }
+}
@@ -0,0 +1,249 @@
+//! Secret bytes that are wiped when they are dropped.
+//!
+//! Plaintexts, derived keys, shared secrets and unwrapped key material are passed around as
+//! [`SecretBytes`]. Its bytes are zeroed with `zeroize` when the value is dropped, so freed
+//! memory keeps no copy of them, and its `Debug` output redacts them like [`Redacted`]:
+//!
+//! ```
+//! use crypto_layer_core::secret::SecretBytes;
+//!
+//! let secret = SecretBytes::from(&b"secret"[..]);
+//! assert_eq!(secret, b"secret");
+//! assert_eq!(format!("{:?}", secret), "SecretBytes(<6 bytes, sha256:2bb80d53>)");
+//! ```
+//!
+//! The `std` crate registers [`BufferHooks`] to lock the buffers into RAM, see
+//! [`set_buffer_hooks`].
+//!
+//! [`Redacted`]: crate::redact::Redacted
+
+use crate::redact::Redacted;
+use alloc::vec::Vec;
+use core::{
+    fmt,
+    ops::{Deref, DerefMut},
+    ptr,
+    sync::atomic::{AtomicPtr, Ordering},
+};
+use subtle::ConstantTimeEq;
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+static HOOKS: AtomicPtr<BufferHooks> = AtomicPtr::new(ptr::null_mut());
+
+/// Functions called with the buffer of every [`SecretBytes`], e.g. to lock it into RAM.
+///
+/// A buffer is passed as its start and capacity. Buffers of capacity 0 are not passed.
+#[derive(Debug, Clone, Copy)]
+pub struct BufferHooks {
+    /// Called after a buffer was allocated.
+    pub allocated: fn(*const u8, usize),
+    /// Called after a buffer was zeroed, before it is freed or handed out by `into_vec`. Buffers
+    /// allocated before the hooks were set are passed as well.
+    pub released: fn(*const u8, usize),
+}
+
+/// Sets the hooks called with the buffers of all `SecretBytes` allocated afterwards, in the
+/// whole process.
+pub fn set_buffer_hooks(hooks: &'static BufferHooks) {
+    HOOKS.store(
+        hooks as *const BufferHooks as *mut BufferHooks,
+        Ordering::Release,
+    );
+}
+
+fn hooks() -> Option<&'static BufferHooks> {
+    // SAFETY: The pointer is null or was created from a `&'static BufferHooks`.
+    unsafe { HOOKS.load(Ordering::Acquire).as_ref() }
+}
+
+fn allocated(buffer: &Vec<u8>) {
+    if let Some(hooks) = hooks().filter(|_| buffer.capacity() > 0) {
+        (hooks.allocated)(buffer.as_ptr(), buffer.capacity());
+    }
+}
+
+/// Zeroes `buffer` and passes it to the `released` hook.
+fn release(buffer: &mut Vec<u8>) {
+    buffer.zeroize();
+    if let Some(hooks) = hooks().filter(|_| buffer.capacity() > 0) {
+        (hooks.released)(buffer.as_ptr(), buffer.capacity());
+    }
+}
+
+/// Bytes of secret data, zeroed when dropped.
+///
+/// The bytes are accessed through `Deref` to `[u8]`. Comparisons with other byte strings run in
+/// constant time with `subtle`, only their lengths are not considered secret.
+#[derive(Default)]
+pub struct SecretBytes(Vec<u8>);
+
+impl SecretBytes {
+    /// Takes ownership of `bytes`, which are zeroed when the `SecretBytes` is dropped.
+    pub fn new(bytes: Vec<u8>) -> Self {
+        allocated(&bytes);
+        Self(bytes)
+    }
+
+    /// Returns `len` zero bytes, to be overwritten with a secret in place.
+    pub fn zeroed(len: usize) -> Self {
+        Self::new(alloc::vec![0; len])
+    }
+
+    /// Returns empty secret bytes that can hold `capacity` bytes without growing.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::new(Vec::with_capacity(capacity))
+    }
+
+    /// Appends `bytes`.
+    ///
+    /// When the capacity is exceeded the bytes are moved to a larger buffer and the old buffer
+    /// is zeroed before it is freed.
+    pub fn extend_from_slice(&mut self, bytes: &[u8]) {
+        let len = self.0.len() + bytes.len();
+        if len > self.0.capacity() {
+            let mut grown = Vec::with_capacity(len.max(2 * self.0.capacity()));
+            allocated(&grown);
+            grown.extend_from_slice(&self.0);
+            core::mem::swap(&mut self.0, &mut grown);
+            release(&mut grown);
+        }
+        self.0.extend_from_slice(bytes);
+    }
+
+    /// Shortens the bytes to `len`. The removed bytes stay in the buffer until it is zeroed on
+    /// drop.
+    pub fn truncate(&mut self, len: usize) {
+        self.0.truncate(len);
+    }
+
+    /// Returns the bytes as a `Vec` without zeroing them, for callers that wipe them
+    /// themselves, e.g. buffers of the C API that are zeroed when they are freed.
+    pub fn into_vec(mut self) -> Vec<u8> {
+        let bytes = core::mem::take(&mut self.0);
+        if let Some(hooks) = hooks().filter(|_| bytes.capacity() > 0) {
+            (hooks.released)(bytes.as_ptr(), bytes.capacity());
+        }
+        bytes
+    }
+}
+
+impl Clone for SecretBytes {
+    fn clone(&self) -> Self {
+        Self::new(self.0.clone())
+    }
+}
+
+impl Drop for SecretBytes {
+    fn drop(&mut self) {
+        release(&mut self.0);
+    }
+}
+
+impl Zeroize for SecretBytes {
+    fn zeroize(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for SecretBytes {}
+
+impl Deref for SecretBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl DerefMut for SecretBytes {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        &mut self.0
+    }
+}
+
+impl AsRef<[u8]> for SecretBytes {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl From<Vec<u8>> for SecretBytes {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self::new(bytes)
+    }
+}
+
+impl From<&[u8]> for SecretBytes {
+    fn from(bytes: &[u8]) -> Self {
+        Self::new(bytes.to_vec())
+    }
+}
+
+impl fmt::Debug for SecretBytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SecretBytes")
+            .field(&Redacted(&self.0))
+            .finish()
+    }
+}
+
+/// Returns whether `a` and `b` are equal, in time that only depends on their lengths.
+fn ct_eq(a: &[u8], b: &[u8]) -> bool {
+    a.ct_eq(b).into()
+}
+
+impl PartialEq for SecretBytes {
+    fn eq(&self, other: &SecretBytes) -> bool {
+        ct_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for SecretBytes {}
+
+impl PartialEq<[u8]> for SecretBytes {
+    fn eq(&self, other: &[u8]) -> bool {
+        ct_eq(&self.0, other)
+    }
+}
+
+impl PartialEq<&[u8]> for SecretBytes {
+    fn eq(&self, other: &&[u8]) -> bool {
+        ct_eq(&self.0, other)
+    }
+}
+
+impl PartialEq<Vec<u8>> for SecretBytes {
+    fn eq(&self, other: &Vec<u8>) -> bool {
+        ct_eq(&self.0, other)
+    }
+}
+
+impl<const N: usize> PartialEq<[u8; N]> for SecretBytes {
+    fn eq(&self, other: &[u8; N]) -> bool {
+        ct_eq(&self.0, other)
+    }
+}
+
+impl<const N: usize> PartialEq<&[u8; N]> for SecretBytes {
+    fn eq(&self, other: &&[u8; N]) -> bool {
+        ct_eq(&self.0, *other)
+    }
+}
+
+impl PartialEq<SecretBytes> for Vec<u8> {
+    fn eq(&self, other: &SecretBytes) -> bool {
+        ct_eq(self, &other.0)
+    }
+}
+
+impl PartialEq<SecretBytes> for [u8] {
+    fn eq(&self, other: &SecretBytes) -> bool {
+        ct_eq(self, &other.0)
+    }
+}
+
+impl PartialEq<SecretBytes> for &[u8] {
+    fn eq(&self, other: &SecretBytes) -> bool {
+        ct_eq(self, &other.0)
+    }
+}
@@ -0,0 +1,256 @@
+use crate::CoreError;
+use alloc::vec::Vec;
+
+/// Encodings of ECDSA signatures.
+///
+/// Apple's Security framework and OpenSSL produce DER encoded signatures, while TPMs, JOSE and
+/// most embedded libraries use the raw concatenation of the two scalars.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SignatureFormat {
+    /// ASN.1 DER encoded `ECDSA-Sig-Value` (RFC 3279).
+    Der,
+    /// The fixed-size concatenation `r || s` (IEEE P1363).
+    Raw,
+}
+
+const SEQUENCE: u8 = 0x30;
+const INTEGER: u8 = 0x02;
+
+/// The scalar length of the largest supported curve, P-521.
+pub const MAX_SCALAR_LEN: usize = 66;
+
+/// Converts a signature between formats.
+///
+/// # Arguments
+///
+/// * `signature` - The encoded signature.
+/// * `from` - The format of `signature`.
+/// * `to` - The requested format.
+/// * `scalar_len` - The length of a scalar of the curve in bytes, e.g. 32 for P-256.
+///
+/// # Returns
+///
+/// A `Result` containing the converted signature, or `CoreError::InvalidSignatureEncoding`
+/// if `signature` is not valid in the `from` format.
+pub fn convert(
+    signature: &[u8],
+    from: SignatureFormat,
+    to: SignatureFormat,
+    scalar_len: usize,
+) -> Result<Vec<u8>, CoreError> {
+    match (from, to) {
+        (SignatureFormat::Der, SignatureFormat::Raw) => der_to_raw(signature, scalar_len),
+        (SignatureFormat::Raw, SignatureFormat::Der) => {
+            if signature.len() != 2 * scalar_len {
+                return Err(CoreError::InvalidSignatureEncoding);
+            }
+            raw_to_der(signature)
+        }
+        (SignatureFormat::Der, SignatureFormat::Der) => {
+            der_to_raw(signature, scalar_len)?;
+            Ok(signature.to_vec())
+        }
+        (SignatureFormat::Raw, SignatureFormat::Raw) => {
+            if signature.len() != 2 * scalar_len {
+                return Err(CoreError::InvalidSignatureEncoding);
+            }
+            Ok(signature.to_vec())
+        }
+    }
+}
+
+/// Converts a DER encoded ECDSA signature to the raw `r || s` format.
+///
+/// The DER encoding is parsed strictly: lengths and integers must be minimally encoded,
+/// integers must be positive and no trailing data is allowed.
+///
+/// # Arguments
+///
+/// * `der` - The DER encoded signature.
+/// * `scalar_len` - The length of a scalar of the curve in bytes, e.g. 32 for P-256.
+///
+/// # Returns
+///
+/// A `Result` containing the raw signature of `2 * scalar_len` bytes, or
+/// `CoreError::InvalidSignatureEncoding` if `der` is malformed or a scalar is too large.
+pub fn der_to_raw(der: &[u8], scalar_len: usize) -> Result<Vec<u8>, CoreError> {
+    let (content, rest) = read_tlv(der, SEQUENCE)?;
+    if !rest.is_empty() {
+        return Err(CoreError::InvalidSignatureEncoding);
+    }
+    let (r, content) = read_tlv(content, INTEGER)?;
+    let (s, content) = read_tlv(content, INTEGER)?;
+    if !content.is_empty() {
+        return Err(CoreError::InvalidSignatureEncoding);
+    }
+
+    let mut raw = Vec::with_capacity(2 * scalar_len);
+    for scalar in [r, s] {
+        let scalar = unsigned_integer(scalar)?;
+        if scalar.len() > scalar_len {
+            return Err(CoreError::InvalidSignatureEncoding);
+        }
+        raw.resize(raw.len() + scalar_len - scalar.len(), 0);
+        raw.extend_from_slice(scalar);
+    }
+    Ok(raw)
+}
+
+/// Converts a raw `r || s` ECDSA signature to DER.
+///
+/// # Arguments
+///
+/// * `raw` - The raw signature, consisting of two scalars of equal length.
+///
+/// # Returns
+///
+/// A `Result` containing the DER encoded signature, or `CoreError::InvalidSignatureEncoding`
+/// if `raw` is empty, has an odd length or its scalars are longer than `MAX_SCALAR_LEN`.
+pub fn raw_to_der(raw: &[u8]) -> Result<Vec<u8>, CoreError> {
+    if raw.is_empty() || !raw.len().is_multiple_of(2) || raw.len() > 2 * MAX_SCALAR_LEN {
+        return Err(CoreError::InvalidSignatureEncoding);
+    }
+    let (r, s) = raw.split_at(raw.len() / 2);
+
+    let mut content = Vec::with_capacity(raw.len() + 6);
+    write_integer(&mut content, r);
+    write_integer(&mut content, s);
+
+    let mut der = Vec::with_capacity(content.len() + 3);
+    der.push(SEQUENCE);
+    write_length(&mut der, content.len());
+    der.extend_from_slice(&content);
+    Ok(der)
+}
+
+/// Verifies an ECDSA P-256 signature with SHA-256.
+///
+/// # Arguments
+///
+/// * `public_key` - The SEC1 encoded public key, compressed or uncompressed.
+/// * `data` - The data the signature was created over.
+/// * `signature` - The encoded signature.
+/// * `format` - The format of `signature`.
+///
+/// # Returns
+///
+/// A `Result` containing `true` if the signature is valid and `false` if it is not, or a
+/// `CoreError` if the public key or the signature is malformed.
+#[cfg(feature = "p256")]
+pub fn verify_p256(
+    public_key: &[u8],
+    data: &[u8],
+    signature: &[u8],
+    format: SignatureFormat,
+) -> Result<bool, CoreError> {
+    use p256::ecdsa::{signature::Verifier, Signature, VerifyingKey};
+
+    let verifying_key =
+        VerifyingKey::from_sec1_bytes(public_key).map_err(|_| CoreError::InvalidPublicKey)?;
+    let signature = match format {
+        SignatureFormat::Der => Signature::from_der(signature),
+        SignatureFormat::Raw => Signature::from_slice(signature),
+    }
+    .map_err(|_| CoreError::InvalidSignatureEncoding)?;
+    Ok(verifying_key.verify(data, &signature).is_ok())
+}
+
+/// Verifies an ECDSA P-384 signature with SHA-384.
+///
+/// # Arguments
+///
+/// * `public_key` - The SEC1 encoded public key, compressed or uncompressed.
+/// * `data` - The data the signature was created over.
+/// * `signature` - The encoded signature.
+/// * `format` - The format of `signature`.
+///
+/// # Returns
+///
+/// A `Result` containing `true` if the signature is valid and `false` if it is not, or a
+/// `CoreError` if the public key or the signature is malformed.
+#[cfg(feature = "p384")]
+pub fn verify_p384(
+    public_key: &[u8],
+    data: &[u8],
+    signature: &[u8],
+    format: SignatureFormat,
+) -> Result<bool, CoreError> {
+    use p384::ecdsa::{signature::Verifier, Signature, VerifyingKey};
+
+    let verifying_key =
+        VerifyingKey::from_sec1_bytes(public_key).map_err(|_| CoreError::InvalidPublicKey)?;
+    let signature = match format {
+        SignatureFormat::Der => Signature::from_der(signature),
+        SignatureFormat::Raw => Signature::from_slice(signature),
+    }
+    .map_err(|_| CoreError::InvalidSignatureEncoding)?;
+    Ok(verifying_key.verify(data, &signature).is_ok())
+}
+
+/// Reads a DER element with the expected tag, returning its content and the remaining input.
+fn read_tlv(input: &[u8], tag: u8) -> Result<(&[u8], &[u8]), CoreError> {
+    let (&actual_tag, input) = input
+        .split_first()
+        .ok_or(CoreError::InvalidSignatureEncoding)?;
+    if actual_tag != tag {
+        return Err(CoreError::InvalidSignatureEncoding);
+    }
+    let (&first, input) = input
+        .split_first()
+        .ok_or(CoreError::InvalidSignatureEncoding)?;
+    let (len, input) = match first {
+        0x00..=0x7f => (first as usize, input),
+        // ECDSA signatures are shorter than 256 bytes for all supported curves.
+        0x81 => {
+            let (&len, input) = input
+                .split_first()
+                .ok_or(CoreError::InvalidSignatureEncoding)?;
+            if len < 0x80 {
+                return Err(CoreError::InvalidSignatureEncoding);
+            }
+            (len as usize, input)
+        }
+        _ => return Err(CoreError::InvalidSignatureEncoding),
+    };
+    if input.len() < len {
+        return Err(CoreError::InvalidSignatureEncoding);
+    }
+    Ok(input.split_at(len))
+}
+
+/// Validates a minimally encoded positive DER integer and strips its sign byte.
+fn unsigned_integer(integer: &[u8]) -> Result<&[u8], CoreError> {
+    match integer {
+        [] => Err(CoreError::InvalidSignatureEncoding),
+        [first, ..] if first & 0x80 != 0 => Err(CoreError::InvalidSignatureEncoding),
+        [0, second, ..] if second & 0x80 == 0 => Err(CoreError::InvalidSignatureEncoding),
+        [0, rest @ ..] if !rest.is_empty() => Ok(rest),
+        _ => Ok(integer),
+    }
+}
+
+fn write_integer(output: &mut Vec<u8>, scalar: &[u8]) {
+    let first_nonzero = scalar.iter().position(|&byte| byte != 0);
+    let scalar = match first_nonzero {
+        Some(index) => &scalar[index..],
+        None => &[0],
+    };
+    let needs_sign_byte = scalar[0] & 0x80 != 0;
+
+    output.push(INTEGER);
+    write_length(output, scalar.len() + needs_sign_byte as usize);
+    if needs_sign_byte {
+        output.push(0);
+    }
+    output.extend_from_slice(scalar);
+}
+
+fn write_length(output: &mut Vec<u8>, len: usize) {
+    if len < 0x80 {
+        output.push(len as u8);
+    } else {
+        output.push(0x81);
+        output.push(len as u8);
+    }
+}
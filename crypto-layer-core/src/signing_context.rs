@@ -0,0 +1,53 @@
+//! Domain separation of signatures with context labels.
+//!
+//! A key that signs for several features of an application, e.g. login challenges and document
+//! approvals, must not have a signature for one feature accepted by another. A signature in a
+//! context covers a label naming the feature, so it only verifies in the same context:
+//!
+//! ```text
+//! "crypto-layer/signing-context/v1\n" || len(label) || label || data
+//! ```
+//!
+//! The length of the label is a single byte. Labels are 1 to 255 printable ASCII characters
+//! without spaces, e.g. `com.example.mail/login/v1`. Naming labels after the application, the
+//! feature and a version keeps them unique.
+
+use crate::CoreError;
+use alloc::vec::Vec;
+
+/// Prefixes every signed message, so that it differs from data signed without a context.
+pub const PREFIX: &[u8] = b"crypto-layer/signing-context/v1\n";
+
+/// The maximum length of a label in bytes.
+pub const MAX_LABEL_LEN: usize = 255;
+
+/// Checks that `label` is 1 to `MAX_LABEL_LEN` printable ASCII characters without spaces.
+///
+/// # Returns
+///
+/// A `Result` that is `Ok(())` on success, or `CoreError::InvalidSigningContext` otherwise.
+pub fn check_label(label: &str) -> Result<(), CoreError> {
+    if label.is_empty()
+        || label.len() > MAX_LABEL_LEN
+        || !label.bytes().all(|byte| byte.is_ascii_graphic())
+    {
+        return Err(CoreError::InvalidSigningContext);
+    }
+    Ok(())
+}
+
+/// Encodes the message that is signed for `data` in the context `label`.
+///
+/// # Returns
+///
+/// A `Result` containing the message on success, or `CoreError::InvalidSigningContext` if the
+/// label is invalid.
+pub fn encode(label: &str, data: &[u8]) -> Result<Vec<u8>, CoreError> {
+    check_label(label)?;
+    let mut message = Vec::with_capacity(PREFIX.len() + 1 + label.len() + data.len());
+    message.extend_from_slice(PREFIX);
+    message.push(label.len() as u8);
+    message.extend_from_slice(label.as_bytes());
+    message.extend_from_slice(data);
+    Ok(message)
+}
@@ -0,0 +1,252 @@
+//! Public keys in the `SubjectPublicKeyInfo` format (RFC 5280).
+//!
+//! Providers export public keys as DER encoded `SubjectPublicKeyInfo`, see
+//! `KeyMetadata::public_key_der`, and tools often pass them around PEM armored. Parsing only
+//! reads the structure and the algorithm; whether the key is a valid point is checked when a
+//! signature is verified.
+
+use crate::{signature_format::SignatureFormat, CoreError};
+use alloc::{string::String, vec::Vec};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use core::ops::Range;
+use sha2::{Digest, Sha256};
+
+const SEQUENCE: u8 = 0x30;
+const OBJECT_IDENTIFIER: u8 = 0x06;
+const NULL: u8 = 0x05;
+const BIT_STRING: u8 = 0x03;
+
+const PEM_BEGIN: &str = "-----BEGIN PUBLIC KEY-----";
+const PEM_END: &str = "-----END PUBLIC KEY-----";
+
+/// id-ecPublicKey (1.2.840.10045.2.1)
+const EC_PUBLIC_KEY: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01];
+/// prime256v1 (1.2.840.10045.3.1.7)
+const P256: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07];
+/// secp384r1 (1.3.132.0.34)
+const P384: &[u8] = &[0x2b, 0x81, 0x04, 0x00, 0x22];
+/// secp521r1 (1.3.132.0.35)
+const P521: &[u8] = &[0x2b, 0x81, 0x04, 0x00, 0x23];
+/// rsaEncryption (1.2.840.113549.1.1.1)
+const RSA_ENCRYPTION: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x01];
+/// id-Ed25519 (1.3.101.112)
+const ED25519: &[u8] = &[0x2b, 0x65, 0x70];
+/// id-X25519 (1.3.101.110)
+const X25519: &[u8] = &[0x2b, 0x65, 0x6e];
+
+/// The algorithms of public keys exported by the providers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PublicKeyAlgorithm {
+    /// An EC key on NIST P-256.
+    EcP256,
+    /// An EC key on NIST P-384.
+    EcP384,
+    /// An EC key on NIST P-521.
+    EcP521,
+    /// An RSA key of any size.
+    Rsa,
+    /// An Ed25519 signing key.
+    Ed25519,
+    /// An X25519 key agreement key.
+    X25519,
+}
+
+impl PublicKeyAlgorithm {
+    /// Returns the name of the algorithm, e.g. `"EC P-256"`.
+    pub fn name(self) -> &'static str {
+        match self {
+            PublicKeyAlgorithm::EcP256 => "EC P-256",
+            PublicKeyAlgorithm::EcP384 => "EC P-384",
+            PublicKeyAlgorithm::EcP521 => "EC P-521",
+            PublicKeyAlgorithm::Rsa => "RSA",
+            PublicKeyAlgorithm::Ed25519 => "Ed25519",
+            PublicKeyAlgorithm::X25519 => "X25519",
+        }
+    }
+}
+
+/// A parsed `SubjectPublicKeyInfo`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubjectPublicKeyInfo {
+    algorithm: PublicKeyAlgorithm,
+    der: Vec<u8>,
+    key: Range<usize>,
+}
+
+impl SubjectPublicKeyInfo {
+    /// Parses a DER encoded `SubjectPublicKeyInfo`.
+    ///
+    /// # Arguments
+    ///
+    /// * `der` - The DER encoded key.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the parsed key, `CoreError::InvalidPublicKey` if `der` is malformed,
+    /// or `CoreError::UnsupportedKeyAlgorithm` if the algorithm or curve is not known.
+    pub fn from_der(der: &[u8]) -> Result<Self, CoreError> {
+        let (info, rest) = read_tlv(der, SEQUENCE)?;
+        let (algorithm_identifier, info) = read_tlv(info, SEQUENCE)?;
+        let (key, info) = read_tlv(info, BIT_STRING)?;
+        if !rest.is_empty() || !info.is_empty() {
+            return Err(CoreError::InvalidPublicKey);
+        }
+        let algorithm = algorithm(algorithm_identifier)?;
+        // The number of unused bits, which is zero for all supported key formats.
+        if key.first() != Some(&0) {
+            return Err(CoreError::InvalidPublicKey);
+        }
+
+        let start = der.len() - key.len() + 1;
+        Ok(SubjectPublicKeyInfo {
+            algorithm,
+            der: der.to_vec(),
+            key: start..der.len(),
+        })
+    }
+
+    /// Parses a PEM encoded `SubjectPublicKeyInfo` (`-----BEGIN PUBLIC KEY-----`).
+    ///
+    /// Text before and after the PEM block is ignored.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the parsed key, or a `CoreError` as for `from_der`.
+    pub fn from_pem(pem: &str) -> Result<Self, CoreError> {
+        let (_, rest) = pem
+            .split_once(PEM_BEGIN)
+            .ok_or(CoreError::InvalidPublicKey)?;
+        let (base64, _) = rest
+            .split_once(PEM_END)
+            .ok_or(CoreError::InvalidPublicKey)?;
+        let base64: String = base64
+            .chars()
+            .filter(|c| !c.is_ascii_whitespace())
+            .collect();
+        let der = STANDARD
+            .decode(base64)
+            .map_err(|_| CoreError::InvalidPublicKey)?;
+        Self::from_der(&der)
+    }
+
+    /// Returns the algorithm of the key.
+    pub fn algorithm(&self) -> PublicKeyAlgorithm {
+        self.algorithm
+    }
+
+    /// Returns the DER encoding of the `SubjectPublicKeyInfo`.
+    pub fn der(&self) -> &[u8] {
+        &self.der
+    }
+
+    /// Returns the PEM encoding of the `SubjectPublicKeyInfo`.
+    pub fn to_pem(&self) -> String {
+        let base64 = STANDARD.encode(&self.der);
+        let mut pem = String::with_capacity(base64.len() + base64.len() / 64 + 60);
+        pem.push_str(PEM_BEGIN);
+        pem.push('\n');
+        for line in base64.as_bytes().chunks(64) {
+            pem.extend(line.iter().map(|&byte| char::from(byte)));
+            pem.push('\n');
+        }
+        pem.push_str(PEM_END);
+        pem.push('\n');
+        pem
+    }
+
+    /// Returns the encoded key without the algorithm identifier: the SEC1 point of EC keys, the
+    /// PKCS#1 `RSAPublicKey` of RSA keys and the raw 32 bytes of Ed25519 and X25519 keys.
+    pub fn key(&self) -> &[u8] {
+        &self.der[self.key.clone()]
+    }
+
+    /// Returns the SHA-256 digest of the DER encoding, the `pin-sha256` of RFC 7469.
+    pub fn fingerprint(&self) -> [u8; 32] {
+        Sha256::digest(&self.der).into()
+    }
+
+    /// Verifies an ECDSA signature with the hash of the curve, SHA-256 for P-256 and SHA-384
+    /// for P-384.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The data the signature was created over.
+    /// * `signature` - The encoded signature.
+    /// * `format` - The format of `signature`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing whether the signature is valid, or a `CoreError` if the key or the
+    /// signature is malformed. Keys other than P-256 with the `p256` feature and P-384 with the
+    /// `p384` feature fail with `CoreError::UnsupportedKeyAlgorithm`.
+    #[cfg_attr(not(any(feature = "p256", feature = "p384")), allow(unused_variables))]
+    pub fn verify(
+        &self,
+        data: &[u8],
+        signature: &[u8],
+        format: SignatureFormat,
+    ) -> Result<bool, CoreError> {
+        match self.algorithm {
+            #[cfg(feature = "p256")]
+            PublicKeyAlgorithm::EcP256 => {
+                crate::signature_format::verify_p256(self.key(), data, signature, format)
+            }
+            #[cfg(feature = "p384")]
+            PublicKeyAlgorithm::EcP384 => {
+                crate::signature_format::verify_p384(self.key(), data, signature, format)
+            }
+            _ => Err(CoreError::UnsupportedKeyAlgorithm),
+        }
+    }
+}
+
+/// Reads the algorithm from the content of an `AlgorithmIdentifier`.
+fn algorithm(identifier: &[u8]) -> Result<PublicKeyAlgorithm, CoreError> {
+    let (oid, parameters) = read_tlv(identifier, OBJECT_IDENTIFIER)?;
+    match oid {
+        EC_PUBLIC_KEY => {
+            let (curve, rest) = read_tlv(parameters, OBJECT_IDENTIFIER)?;
+            if !rest.is_empty() {
+                return Err(CoreError::InvalidPublicKey);
+            }
+            match curve {
+                P256 => Ok(PublicKeyAlgorithm::EcP256),
+                P384 => Ok(PublicKeyAlgorithm::EcP384),
+                P521 => Ok(PublicKeyAlgorithm::EcP521),
+                _ => Err(CoreError::UnsupportedKeyAlgorithm),
+            }
+        }
+        RSA_ENCRYPTION => match read_tlv(parameters, NULL)? {
+            ([], []) => Ok(PublicKeyAlgorithm::Rsa),
+            _ => Err(CoreError::InvalidPublicKey),
+        },
+        ED25519 | X25519 if !parameters.is_empty() => Err(CoreError::InvalidPublicKey),
+        ED25519 => Ok(PublicKeyAlgorithm::Ed25519),
+        X25519 => Ok(PublicKeyAlgorithm::X25519),
+        _ => Err(CoreError::UnsupportedKeyAlgorithm),
+    }
+}
+
+/// Reads a DER element with the expected tag, returning its content and the remaining input.
+///
+/// Lengths of up to two bytes are accepted, which covers RSA keys of up to 16384 bits.
+fn read_tlv(input: &[u8], tag: u8) -> Result<(&[u8], &[u8]), CoreError> {
+    let (&actual_tag, input) = input.split_first().ok_or(CoreError::InvalidPublicKey)?;
+    if actual_tag != tag {
+        return Err(CoreError::InvalidPublicKey);
+    }
+    let (&first, input) = input.split_first().ok_or(CoreError::InvalidPublicKey)?;
+    let (len, input) = match (first, input) {
+        (0x00..=0x7f, _) => (first as usize, input),
+        (0x81, [len, input @ ..]) if *len >= 0x80 => (*len as usize, input),
+        (0x82, [high, low, input @ ..]) if *high != 0 => {
+            (usize::from(*high) << 8 | usize::from(*low), input)
+        }
+        _ => return Err(CoreError::InvalidPublicKey),
+    };
+    if input.len() < len {
+        return Err(CoreError::InvalidPublicKey);
+    }
+    Ok(input.split_at(len))
+}
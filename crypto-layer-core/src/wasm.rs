@@ -0,0 +1,208 @@
+//! JavaScript bindings for web frontends, built with the `wasm` feature.
+//!
+//! The bindings cover what a frontend needs to check artifacts produced by devices: parsing
+//! public keys and envelopes, and verifying ECDSA signatures over P-256 and P-384. Keys never
+//! leave the security modules of the devices, so the provider layer of `crypto-layer` is not
+//! part of this crate. Build the module as a `cdylib` and generate the JavaScript glue with
+//! [`wasm-bindgen`](https://rustwasm.github.io/wasm-bindgen/):
+//!
+//! ```text
+//! cargo rustc -p crypto-layer-core --release --target wasm32-unknown-unknown --features wasm \
+//!     --crate-type cdylib
+//! wasm-bindgen --target web --out-dir pkg \
+//!     target/wasm32-unknown-unknown/release/crypto_layer_core.wasm
+//! ```
+//!
+//! ```js
+//! import init, { PublicKey } from "./pkg/crypto_layer_core.js";
+//!
+//! await init();
+//! const key = PublicKey.fromPem(pem);
+//! if (!key.verify(artifact, signature, "der")) throw new Error("Invalid signature");
+//! ```
+//!
+//! Errors are thrown as `Error` with the stable `CoreError::code` in their `code` property.
+
+use crate::{
+    envelope::EnvelopeRef, signature_format::SignatureFormat, signing_context,
+    spki::SubjectPublicKeyInfo, CoreError,
+};
+use alloc::{string::String, vec::Vec};
+use wasm_bindgen::prelude::*;
+
+/// Converts an error to a JavaScript `Error` with a `code` property.
+fn error(error: CoreError) -> JsValue {
+    let js_error = js_sys::Error::new(&alloc::format!("{}", error));
+    // Setting a property of a fresh object does not fail.
+    let _ = js_sys::Reflect::set(
+        &js_error,
+        &JsValue::from_str("code"),
+        &JsValue::from(error.code()),
+    );
+    js_error.into()
+}
+
+/// Reads `"der"` or `"raw"`.
+fn signature_format(format: &str) -> Result<SignatureFormat, JsValue> {
+    match format {
+        "der" => Ok(SignatureFormat::Der),
+        "raw" => Ok(SignatureFormat::Raw),
+        _ => Err(error(CoreError::InvalidSignatureEncoding)),
+    }
+}
+
+/// A public key exported by a security module.
+#[wasm_bindgen(js_name = PublicKey)]
+pub struct WasmPublicKey(SubjectPublicKeyInfo);
+
+#[wasm_bindgen(js_class = PublicKey)]
+impl WasmPublicKey {
+    /// Parses a DER encoded `SubjectPublicKeyInfo`.
+    #[wasm_bindgen(js_name = fromDer)]
+    pub fn from_der(der: &[u8]) -> Result<WasmPublicKey, JsValue> {
+        SubjectPublicKeyInfo::from_der(der)
+            .map(WasmPublicKey)
+            .map_err(error)
+    }
+
+    /// Parses a PEM encoded `SubjectPublicKeyInfo`.
+    #[wasm_bindgen(js_name = fromPem)]
+    pub fn from_pem(pem: &str) -> Result<WasmPublicKey, JsValue> {
+        SubjectPublicKeyInfo::from_pem(pem)
+            .map(WasmPublicKey)
+            .map_err(error)
+    }
+
+    /// The algorithm of the key, e.g. `"EC P-256"`.
+    #[wasm_bindgen(getter)]
+    pub fn algorithm(&self) -> String {
+        self.0.algorithm().name().into()
+    }
+
+    /// The SHA-256 digest of the DER encoding.
+    #[wasm_bindgen(getter)]
+    pub fn fingerprint(&self) -> Vec<u8> {
+        self.0.fingerprint().to_vec()
+    }
+
+    /// The DER encoding of the key.
+    #[wasm_bindgen(getter)]
+    pub fn der(&self) -> Vec<u8> {
+        self.0.der().to_vec()
+    }
+
+    /// The PEM encoding of the key.
+    #[wasm_bindgen(getter)]
+    pub fn pem(&self) -> String {
+        self.0.to_pem()
+    }
+
+    /// Verifies an ECDSA signature in the `"der"` or `"raw"` format.
+    pub fn verify(&self, data: &[u8], signature: &[u8], format: &str) -> Result<bool, JsValue> {
+        self.0
+            .verify(data, signature, signature_format(format)?)
+            .map_err(error)
+    }
+
+    /// Verifies an ECDSA signature created in the context `label`, see `signing_context`.
+    #[wasm_bindgen(js_name = verifyInContext)]
+    pub fn verify_in_context(
+        &self,
+        label: &str,
+        data: &[u8],
+        signature: &[u8],
+        format: &str,
+    ) -> Result<bool, JsValue> {
+        let message = signing_context::encode(label, data).map_err(error)?;
+        self.verify(&message, signature, format)
+    }
+}
+
+/// A parsed envelope, see `envelope`.
+///
+/// Optional fields are `undefined` when absent.
+#[wasm_bindgen(js_name = Envelope)]
+pub struct WasmEnvelope {
+    aead: u8,
+    key_id: String,
+    nonce: Vec<u8>,
+    wrapped_key: Option<Vec<u8>>,
+    ephemeral_public_key: Option<Vec<u8>>,
+    kdf: Option<u8>,
+    salt: Option<Vec<u8>>,
+    header: Vec<u8>,
+    ciphertext: Vec<u8>,
+}
+
+#[wasm_bindgen(js_class = Envelope)]
+impl WasmEnvelope {
+    /// Parses an encoded envelope.
+    pub fn parse(bytes: &[u8]) -> Result<WasmEnvelope, JsValue> {
+        let envelope = EnvelopeRef::parse(bytes).map_err(error)?;
+        Ok(WasmEnvelope {
+            aead: envelope.aead.id(),
+            key_id: envelope.key_id.into(),
+            nonce: envelope.nonce.to_vec(),
+            wrapped_key: envelope.wrapped_key.map(<[u8]>::to_vec),
+            ephemeral_public_key: envelope.ephemeral_public_key.map(<[u8]>::to_vec),
+            kdf: envelope.kdf.map(|kdf| kdf.id()),
+            salt: envelope.salt.map(<[u8]>::to_vec),
+            header: envelope.header.to_vec(),
+            ciphertext: envelope.ciphertext.to_vec(),
+        })
+    }
+
+    /// The identifier of the AEAD algorithm, see `AeadAlgorithm`.
+    #[wasm_bindgen(getter)]
+    pub fn aead(&self) -> u8 {
+        self.aead
+    }
+
+    /// The identifier of the key the envelope is encrypted for.
+    #[wasm_bindgen(getter, js_name = keyId)]
+    pub fn key_id(&self) -> String {
+        self.key_id.clone()
+    }
+
+    /// The nonce of the AEAD.
+    #[wasm_bindgen(getter)]
+    pub fn nonce(&self) -> Vec<u8> {
+        self.nonce.clone()
+    }
+
+    /// The wrapped data key.
+    #[wasm_bindgen(getter, js_name = wrappedKey)]
+    pub fn wrapped_key(&self) -> Option<Vec<u8>> {
+        self.wrapped_key.clone()
+    }
+
+    /// The ephemeral public key of the sender.
+    #[wasm_bindgen(getter, js_name = ephemeralPublicKey)]
+    pub fn ephemeral_public_key(&self) -> Option<Vec<u8>> {
+        self.ephemeral_public_key.clone()
+    }
+
+    /// The identifier of the KDF, see `Kdf`.
+    #[wasm_bindgen(getter)]
+    pub fn kdf(&self) -> Option<u8> {
+        self.kdf
+    }
+
+    /// The salt passed to the KDF.
+    #[wasm_bindgen(getter)]
+    pub fn salt(&self) -> Option<Vec<u8>> {
+        self.salt.clone()
+    }
+
+    /// The encoded header, the associated data of the AEAD.
+    #[wasm_bindgen(getter)]
+    pub fn header(&self) -> Vec<u8> {
+        self.header.clone()
+    }
+
+    /// The ciphertext including the authentication tag.
+    #[wasm_bindgen(getter)]
+    pub fn ciphertext(&self) -> Vec<u8> {
+        self.ciphertext.clone()
+    }
+}
@@ -0,0 +1,326 @@
+/// Hardware-backed keys of the Crypto Layer for Dart and Flutter.
+///
+/// The library calls the C API (`include/crypto_layer.h`) of the dynamic
+/// library of the crate, built with the `ffi` feature:
+///
+/// ```dart
+/// final provider = await Provider.open('TPM', 'ops-signing');
+/// await provider.createKey('ops-signing', Algorithm.ecP256, {Purpose.sign});
+/// final signature = await provider.sign(utf8.encode('release.tar.gz'));
+/// await provider.close();
+/// ```
+///
+/// `'TPM'` is the Secure Enclave on iOS and the Android Keystore on Android,
+/// so a Flutter app uses the same calls on both platforms. The calls run on a
+/// helper isolate, so a biometric prompt never blocks the UI isolate. Errors
+/// are thrown as [CryptoLayerException] with the error code of the C API.
+library crypto_layer;
+
+import 'dart:async';
+import 'dart:ffi';
+import 'dart:io';
+import 'dart:isolate';
+import 'dart:typed_data';
+
+import 'package:ffi/ffi.dart';
+
+import 'src/bindings.g.dart';
+
+/// The `CRYPTO_LAYER_ABI_VERSION` the package is written for.
+const int abiVersion = 1;
+
+/// The `CRYPTO_LAYER_ALGORITHM_*` constants.
+enum Algorithm {
+  ecP256(CRYPTO_LAYER_ALGORITHM_EC_P256),
+  ecP384(CRYPTO_LAYER_ALGORITHM_EC_P384),
+  ecP521(CRYPTO_LAYER_ALGORITHM_EC_P521),
+  rsa2048(CRYPTO_LAYER_ALGORITHM_RSA_2048),
+  rsa3072(CRYPTO_LAYER_ALGORITHM_RSA_3072),
+  rsa4096(CRYPTO_LAYER_ALGORITHM_RSA_4096),
+  aes128Gcm(CRYPTO_LAYER_ALGORITHM_AES_128_GCM),
+  aes256Gcm(CRYPTO_LAYER_ALGORITHM_AES_256_GCM);
+
+  const Algorithm(this.value);
+
+  /// The value of the constant.
+  final int value;
+}
+
+/// The `CRYPTO_LAYER_PURPOSE_*` flags.
+enum Purpose {
+  sign(CRYPTO_LAYER_PURPOSE_SIGN),
+  encrypt(CRYPTO_LAYER_PURPOSE_ENCRYPT),
+  keyAgreement(CRYPTO_LAYER_PURPOSE_KEY_AGREEMENT);
+
+  const Purpose(this.value);
+
+  /// The value of the flag.
+  final int value;
+}
+
+/// The `CRYPTO_LAYER_ACCESS_*` constants.
+enum Access {
+  none(CRYPTO_LAYER_ACCESS_NONE),
+  userPresence(CRYPTO_LAYER_ACCESS_USER_PRESENCE),
+  biometryAny(CRYPTO_LAYER_ACCESS_BIOMETRY_ANY),
+  biometryCurrentSet(CRYPTO_LAYER_ACCESS_BIOMETRY_CURRENT_SET),
+  devicePasscode(CRYPTO_LAYER_ACCESS_DEVICE_PASSCODE);
+
+  const Access(this.value);
+
+  /// The value of the constant.
+  final int value;
+}
+
+/// An error of the C API, with its [code] and [message].
+class CryptoLayerException implements Exception {
+  CryptoLayerException(this.code, this.message);
+
+  /// The stable code of the `SecurityModuleError`, or a `CRYPTO_LAYER_ERROR_*`
+  /// code for invalid arguments.
+  final int code;
+
+  /// The message of `crypto_layer_last_error_message`.
+  final String message;
+
+  @override
+  String toString() => 'CryptoLayerException: $message ($code)';
+}
+
+/// Opens the library from `CRYPTO_LAYER_LIBRARY`, or where the platform
+/// bundles it: iOS apps link it statically, Android apps ship it in their
+/// `jniLibs`.
+DynamicLibrary _openLibrary() {
+  final path = Platform.environment['CRYPTO_LAYER_LIBRARY'];
+  if (path != null && path.isNotEmpty) {
+    return DynamicLibrary.open(path);
+  }
+  if (Platform.isIOS) {
+    return DynamicLibrary.process();
+  }
+  if (Platform.isMacOS) {
+    return DynamicLibrary.open('libcrypto_layer.dylib');
+  }
+  if (Platform.isWindows) {
+    return DynamicLibrary.open('crypto_layer.dll');
+  }
+  return DynamicLibrary.open('libcrypto_layer.so');
+}
+
+CryptoLayerBindings _loadBindings() {
+  final bindings = CryptoLayerBindings(_openLibrary());
+  final version = bindings.crypto_layer_abi_version();
+  if (version != abiVersion) {
+    throw UnsupportedError(
+        'The crypto_layer library has ABI version $version, the package needs '
+        '$abiVersion');
+  }
+  return bindings;
+}
+
+/// The bindings of the isolate, loaded on first use.
+final CryptoLayerBindings _bindings = _loadBindings();
+
+void _check(int status) {
+  if (status != CRYPTO_LAYER_OK) {
+    // The message is kept per thread, and an isolate does not change its
+    // thread during a synchronous call.
+    final message = _bindings
+        .crypto_layer_last_error_message()
+        .cast<Utf8>()
+        .toDartString();
+    throw CryptoLayerException(status, message);
+  }
+}
+
+Pointer<Uint8> _bytes(Arena arena, Uint8List data) {
+  final pointer = arena<Uint8>(data.length);
+  pointer.asTypedList(data.length).setAll(0, data);
+  return pointer;
+}
+
+Uint8List _take(Pointer<CryptoLayerBuffer> buffer) {
+  try {
+    final len = buffer.ref.len;
+    return len == 0
+        ? Uint8List(0)
+        : Uint8List.fromList(buffer.ref.data.asTypedList(len));
+  } finally {
+    _bindings.crypto_layer_buffer_free(buffer);
+  }
+}
+
+Pointer<CryptoLayerKeySpec> _spec(Arena arena, Algorithm algorithm,
+    Set<Purpose> purposes, Access access, bool exportable) {
+  final spec = arena<CryptoLayerKeySpec>();
+  spec.ref
+    ..algorithm = algorithm.value
+    ..purposes = purposes.fold(0, (flags, purpose) => flags | purpose.value)
+    ..access = access.value
+    ..exportable = exportable;
+  return spec;
+}
+
+/// A function of the C API transforming bytes, e.g. `crypto_layer_sign`.
+typedef _Transform = int Function(Pointer<CryptoLayerProvider> provider,
+    Pointer<Uint8> data, int dataLen, Pointer<CryptoLayerBuffer> out);
+
+Uint8List _transform(Pointer<CryptoLayerProvider> provider, Uint8List data,
+    _Transform Function(CryptoLayerBindings bindings) function) {
+  return using((arena) {
+    final buffer = arena<CryptoLayerBuffer>();
+    _check(function(_bindings)(
+        provider, _bytes(arena, data), data.length, buffer));
+    return _take(buffer);
+  });
+}
+
+/// An open security module with its current key.
+///
+/// The calls of a provider run one after another, in the order they were
+/// made. [close] releases the provider after its pending calls, otherwise it
+/// is released when it is garbage collected.
+class Provider implements Finalizable {
+  Provider._(this._provider) {
+    _finalizer.attach(this, _provider!.cast(), detach: this);
+  }
+
+  static final _finalizer = NativeFinalizer(
+      _bindings.addresses.crypto_layer_provider_free.cast());
+
+  Pointer<CryptoLayerProvider>? _provider;
+  Future<void> _queue = Future.value();
+
+  /// Opens and initializes a security module for [keyId].
+  ///
+  /// [module] is `'TPM'` for the security module of the platform, or `'MOCK'`
+  /// for the in-memory provider of libraries built with `test-utils`.
+  static Future<Provider> open(String module, String keyId) async {
+    final address = await Isolate.run(() => using((arena) {
+          final out = arena<Pointer<CryptoLayerProvider>>();
+          _check(_bindings.crypto_layer_provider_open(
+              module.toNativeUtf8(allocator: arena).cast(),
+              keyId.toNativeUtf8(allocator: arena).cast(),
+              out));
+          return out.value.address;
+        }));
+    return Provider._(Pointer.fromAddress(address));
+  }
+
+  /// Releases the provider after its pending calls. Further calls throw a
+  /// [StateError].
+  Future<void> close() async {
+    final provider = _provider;
+    if (provider == null) {
+      return;
+    }
+    _provider = null;
+    _finalizer.detach(this);
+    await _queue;
+    _bindings.crypto_layer_provider_free(provider);
+  }
+
+  /// Creates a key and makes it the current key of the provider.
+  Future<void> createKey(
+      String keyId, Algorithm algorithm, Set<Purpose> purposes,
+      {Access access = Access.none, bool exportable = false}) {
+    return _run((provider) => using((arena) {
+          _check(_bindings.crypto_layer_create_key(
+              provider,
+              keyId.toNativeUtf8(allocator: arena).cast(),
+              _spec(arena, algorithm, purposes, access, exportable)));
+        }));
+  }
+
+  /// Loads an existing key and makes it the current key of the provider.
+  Future<void> loadKey(String keyId, Algorithm algorithm, Set<Purpose> purposes,
+      {Access access = Access.none, bool exportable = false}) {
+    return _run((provider) => using((arena) {
+          _check(_bindings.crypto_layer_load_key(
+              provider,
+              keyId.toNativeUtf8(allocator: arena).cast(),
+              _spec(arena, algorithm, purposes, access, exportable)));
+        }));
+  }
+
+  /// Returns the DER encoded SubjectPublicKeyInfo of the current key.
+  Future<Uint8List> publicKey() {
+    return _run((provider) => using((arena) {
+          final buffer = arena<CryptoLayerBuffer>();
+          _check(_bindings.crypto_layer_public_key(provider, buffer));
+          return _take(buffer);
+        }));
+  }
+
+  /// Signs [data] with the current key.
+  Future<Uint8List> sign(List<int> data) {
+    final input = Uint8List.fromList(data);
+    return _run((provider) =>
+        _transform(provider, input, (bindings) => bindings.crypto_layer_sign));
+  }
+
+  /// Returns whether [signature] over [data] is valid for the current key.
+  Future<bool> verify(List<int> data, List<int> signature) {
+    final input = Uint8List.fromList(data);
+    final signatureInput = Uint8List.fromList(signature);
+    return _run((provider) => using((arena) {
+          final valid = arena<Bool>();
+          _check(_bindings.crypto_layer_verify(
+              provider,
+              _bytes(arena, input),
+              input.length,
+              _bytes(arena, signatureInput),
+              signatureInput.length,
+              valid));
+          return valid.value;
+        }));
+  }
+
+  /// Encrypts [data] with the current key.
+  Future<Uint8List> encrypt(List<int> data) {
+    final input = Uint8List.fromList(data);
+    return _run((provider) => _transform(
+        provider, input, (bindings) => bindings.crypto_layer_encrypt));
+  }
+
+  /// Decrypts [data] with the current key.
+  Future<Uint8List> decrypt(List<int> data) {
+    final input = Uint8List.fromList(data);
+    return _run((provider) => _transform(
+        provider, input, (bindings) => bindings.crypto_layer_decrypt));
+  }
+
+  /// Encrypts [data] of any size with ECIES for the current EC key.
+  Future<Uint8List> envelopeEncrypt(List<int> data) {
+    final input = Uint8List.fromList(data);
+    return _run((provider) => _transform(
+        provider, input, (bindings) => bindings.crypto_layer_envelope_encrypt));
+  }
+
+  /// Decrypts an envelope of [envelopeEncrypt] with the current key.
+  Future<Uint8List> envelopeDecrypt(List<int> envelope) {
+    final input = Uint8List.fromList(envelope);
+    return _run((provider) => _transform(
+        provider, input, (bindings) => bindings.crypto_layer_envelope_decrypt));
+  }
+
+  /// Runs [body] with the provider on a helper isolate, after the pending
+  /// calls. The provider stays reachable, and thus open, until [body] returns.
+  Future<T> _run<T>(
+      T Function(Pointer<CryptoLayerProvider> provider) body) async {
+    final provider = _provider;
+    if (provider == null) {
+      throw StateError('The provider is closed');
+    }
+    final address = provider.address;
+    final previous = _queue;
+    final done = Completer<void>();
+    _queue = done.future;
+    try {
+      await previous;
+      return await Isolate.run(() => body(Pointer.fromAddress(address)));
+    } finally {
+      done.complete();
+    }
+  }
+}
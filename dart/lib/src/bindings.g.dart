@@ -0,0 +1,437 @@
+// Generated with ffigen from include/crypto_layer.h, do not edit.
+
+// AUTO GENERATED FILE, DO NOT EDIT.
+//
+// Generated by `package:ffigen`.
+// ignore_for_file: type=lint
+import 'dart:ffi' as ffi;
+
+/// Bindings of the C API of the Crypto Layer, see include/crypto_layer.h.
+class CryptoLayerBindings {
+  /// Holds the symbol lookup function.
+  final ffi.Pointer<T> Function<T extends ffi.NativeType>(String symbolName)
+      _lookup;
+
+  /// The symbols are looked up in [dynamicLibrary].
+  CryptoLayerBindings(ffi.DynamicLibrary dynamicLibrary)
+      : _lookup = dynamicLibrary.lookup;
+
+  /// The symbols are looked up with [lookup].
+  CryptoLayerBindings.fromLookup(
+      ffi.Pointer<T> Function<T extends ffi.NativeType>(String symbolName)
+          lookup)
+      : _lookup = lookup;
+
+  /// Returns `CRYPTO_LAYER_ABI_VERSION` of the library, which applications compare with the
+  /// version of the header they were compiled with.
+  int crypto_layer_abi_version() {
+    return _crypto_layer_abi_version();
+  }
+
+  late final _crypto_layer_abi_versionPtr =
+      _lookup<ffi.NativeFunction<ffi.Uint32 Function()>>('crypto_layer_abi_version');
+  late final _crypto_layer_abi_version =
+      _crypto_layer_abi_versionPtr.asFunction<int Function()>();
+
+  /// Opens and initializes a security module.
+  ///
+  /// # Arguments
+  ///
+  /// * `module` - `"TPM"` for the security module of the platform, or `"MOCK"` for the in-memory
+  ///   provider of builds with the `test-utils` feature.
+  /// * `key_id` - The id of the key the provider is created for.
+  /// * `out` - Receives the handle, which is released with `crypto_layer_provider_free`.
+  ///
+  /// # Safety
+  ///
+  /// `module` and `key_id` must be null or null-terminated strings, and `out` null or valid for
+  /// writes.
+  int crypto_layer_provider_open(
+    ffi.Pointer<ffi.Char> module,
+    ffi.Pointer<ffi.Char> key_id,
+    ffi.Pointer<ffi.Pointer<CryptoLayerProvider>> out,
+  ) {
+    return _crypto_layer_provider_open(
+      module,
+      key_id,
+      out,
+    );
+  }
+
+  late final _crypto_layer_provider_openPtr =
+      _lookup<ffi.NativeFunction<ffi.Int32 Function(ffi.Pointer<ffi.Char>, ffi.Pointer<ffi.Char>, ffi.Pointer<ffi.Pointer<CryptoLayerProvider>>)>>('crypto_layer_provider_open');
+  late final _crypto_layer_provider_open =
+      _crypto_layer_provider_openPtr.asFunction<int Function(ffi.Pointer<ffi.Char>, ffi.Pointer<ffi.Char>, ffi.Pointer<ffi.Pointer<CryptoLayerProvider>>)>();
+
+  /// Releases a provider handle of `crypto_layer_provider_open`. Null is ignored.
+  ///
+  /// # Safety
+  ///
+  /// `provider` must be null or a handle that was not released yet.
+  void crypto_layer_provider_free(
+    ffi.Pointer<CryptoLayerProvider> provider,
+  ) {
+    return _crypto_layer_provider_free(
+      provider,
+    );
+  }
+
+  late final _crypto_layer_provider_freePtr =
+      _lookup<ffi.NativeFunction<ffi.Void Function(ffi.Pointer<CryptoLayerProvider>)>>('crypto_layer_provider_free');
+  late final _crypto_layer_provider_free =
+      _crypto_layer_provider_freePtr.asFunction<void Function(ffi.Pointer<CryptoLayerProvider>)>();
+
+  /// Creates a key and makes it the current key of the provider.
+  ///
+  /// # Arguments
+  ///
+  /// * `provider` - The provider handle.
+  /// * `key_id` - The id of the key, which is also its label.
+  /// * `spec` - The algorithm, purposes and policy of the key.
+  ///
+  /// # Safety
+  ///
+  /// `provider` must be null or a valid handle, `key_id` null or a null-terminated string, and
+  /// `spec` null or valid for reads.
+  int crypto_layer_create_key(
+    ffi.Pointer<CryptoLayerProvider> provider,
+    ffi.Pointer<ffi.Char> key_id,
+    ffi.Pointer<CryptoLayerKeySpec> spec,
+  ) {
+    return _crypto_layer_create_key(
+      provider,
+      key_id,
+      spec,
+    );
+  }
+
+  late final _crypto_layer_create_keyPtr =
+      _lookup<ffi.NativeFunction<ffi.Int32 Function(ffi.Pointer<CryptoLayerProvider>, ffi.Pointer<ffi.Char>, ffi.Pointer<CryptoLayerKeySpec>)>>('crypto_layer_create_key');
+  late final _crypto_layer_create_key =
+      _crypto_layer_create_keyPtr.asFunction<int Function(ffi.Pointer<CryptoLayerProvider>, ffi.Pointer<ffi.Char>, ffi.Pointer<CryptoLayerKeySpec>)>();
+
+  /// Loads an existing key and makes it the current key of the provider, like
+  /// `crypto_layer_create_key`.
+  ///
+  /// # Safety
+  ///
+  /// See `crypto_layer_create_key`.
+  int crypto_layer_load_key(
+    ffi.Pointer<CryptoLayerProvider> provider,
+    ffi.Pointer<ffi.Char> key_id,
+    ffi.Pointer<CryptoLayerKeySpec> spec,
+  ) {
+    return _crypto_layer_load_key(
+      provider,
+      key_id,
+      spec,
+    );
+  }
+
+  late final _crypto_layer_load_keyPtr =
+      _lookup<ffi.NativeFunction<ffi.Int32 Function(ffi.Pointer<CryptoLayerProvider>, ffi.Pointer<ffi.Char>, ffi.Pointer<CryptoLayerKeySpec>)>>('crypto_layer_load_key');
+  late final _crypto_layer_load_key =
+      _crypto_layer_load_keyPtr.asFunction<int Function(ffi.Pointer<CryptoLayerProvider>, ffi.Pointer<ffi.Char>, ffi.Pointer<CryptoLayerKeySpec>)>();
+
+  /// Writes the DER encoded SubjectPublicKeyInfo of the current key to `out`.
+  ///
+  /// # Safety
+  ///
+  /// `provider` must be null or a valid handle and `out` null or valid for writes.
+  int crypto_layer_public_key(
+    ffi.Pointer<CryptoLayerProvider> provider,
+    ffi.Pointer<CryptoLayerBuffer> out,
+  ) {
+    return _crypto_layer_public_key(
+      provider,
+      out,
+    );
+  }
+
+  late final _crypto_layer_public_keyPtr =
+      _lookup<ffi.NativeFunction<ffi.Int32 Function(ffi.Pointer<CryptoLayerProvider>, ffi.Pointer<CryptoLayerBuffer>)>>('crypto_layer_public_key');
+  late final _crypto_layer_public_key =
+      _crypto_layer_public_keyPtr.asFunction<int Function(ffi.Pointer<CryptoLayerProvider>, ffi.Pointer<CryptoLayerBuffer>)>();
+
+  /// Signs `data` with the current key and writes the signature to `out`.
+  ///
+  /// # Safety
+  ///
+  /// `provider` must be null or a valid handle, `data` valid for `data_len` bytes unless
+  /// `data_len` is 0, and `out` null or valid for writes.
+  int crypto_layer_sign(
+    ffi.Pointer<CryptoLayerProvider> provider,
+    ffi.Pointer<ffi.Uint8> data,
+    int data_len,
+    ffi.Pointer<CryptoLayerBuffer> out,
+  ) {
+    return _crypto_layer_sign(
+      provider,
+      data,
+      data_len,
+      out,
+    );
+  }
+
+  late final _crypto_layer_signPtr =
+      _lookup<ffi.NativeFunction<ffi.Int32 Function(ffi.Pointer<CryptoLayerProvider>, ffi.Pointer<ffi.Uint8>, ffi.Size, ffi.Pointer<CryptoLayerBuffer>)>>('crypto_layer_sign');
+  late final _crypto_layer_sign =
+      _crypto_layer_signPtr.asFunction<int Function(ffi.Pointer<CryptoLayerProvider>, ffi.Pointer<ffi.Uint8>, int, ffi.Pointer<CryptoLayerBuffer>)>();
+
+  /// Verifies `signature` over `data` with the current key and writes the result to `valid`.
+  ///
+  /// # Safety
+  ///
+  /// `provider` must be null or a valid handle, `data` and `signature` valid for their lengths
+  /// unless they are 0, and `valid` null or valid for writes.
+  int crypto_layer_verify(
+    ffi.Pointer<CryptoLayerProvider> provider,
+    ffi.Pointer<ffi.Uint8> data,
+    int data_len,
+    ffi.Pointer<ffi.Uint8> signature,
+    int signature_len,
+    ffi.Pointer<ffi.Bool> valid,
+  ) {
+    return _crypto_layer_verify(
+      provider,
+      data,
+      data_len,
+      signature,
+      signature_len,
+      valid,
+    );
+  }
+
+  late final _crypto_layer_verifyPtr =
+      _lookup<ffi.NativeFunction<ffi.Int32 Function(ffi.Pointer<CryptoLayerProvider>, ffi.Pointer<ffi.Uint8>, ffi.Size, ffi.Pointer<ffi.Uint8>, ffi.Size, ffi.Pointer<ffi.Bool>)>>('crypto_layer_verify');
+  late final _crypto_layer_verify =
+      _crypto_layer_verifyPtr.asFunction<int Function(ffi.Pointer<CryptoLayerProvider>, ffi.Pointer<ffi.Uint8>, int, ffi.Pointer<ffi.Uint8>, int, ffi.Pointer<ffi.Bool>)>();
+
+  /// Encrypts `data` with the current key and writes the ciphertext to `out`.
+  ///
+  /// # Safety
+  ///
+  /// See `crypto_layer_sign`.
+  int crypto_layer_encrypt(
+    ffi.Pointer<CryptoLayerProvider> provider,
+    ffi.Pointer<ffi.Uint8> data,
+    int data_len,
+    ffi.Pointer<CryptoLayerBuffer> out,
+  ) {
+    return _crypto_layer_encrypt(
+      provider,
+      data,
+      data_len,
+      out,
+    );
+  }
+
+  late final _crypto_layer_encryptPtr =
+      _lookup<ffi.NativeFunction<ffi.Int32 Function(ffi.Pointer<CryptoLayerProvider>, ffi.Pointer<ffi.Uint8>, ffi.Size, ffi.Pointer<CryptoLayerBuffer>)>>('crypto_layer_encrypt');
+  late final _crypto_layer_encrypt =
+      _crypto_layer_encryptPtr.asFunction<int Function(ffi.Pointer<CryptoLayerProvider>, ffi.Pointer<ffi.Uint8>, int, ffi.Pointer<CryptoLayerBuffer>)>();
+
+  /// Decrypts `data` with the current key and writes the plaintext to `out`.
+  ///
+  /// # Safety
+  ///
+  /// See `crypto_layer_sign`.
+  int crypto_layer_decrypt(
+    ffi.Pointer<CryptoLayerProvider> provider,
+    ffi.Pointer<ffi.Uint8> data,
+    int data_len,
+    ffi.Pointer<CryptoLayerBuffer> out,
+  ) {
+    return _crypto_layer_decrypt(
+      provider,
+      data,
+      data_len,
+      out,
+    );
+  }
+
+  late final _crypto_layer_decryptPtr =
+      _lookup<ffi.NativeFunction<ffi.Int32 Function(ffi.Pointer<CryptoLayerProvider>, ffi.Pointer<ffi.Uint8>, ffi.Size, ffi.Pointer<CryptoLayerBuffer>)>>('crypto_layer_decrypt');
+  late final _crypto_layer_decrypt =
+      _crypto_layer_decryptPtr.asFunction<int Function(ffi.Pointer<CryptoLayerProvider>, ffi.Pointer<ffi.Uint8>, int, ffi.Pointer<CryptoLayerBuffer>)>();
+
+  /// Encrypts `data` with ECIES for the public key of the current EC key and writes the envelope
+  /// to `out`, see `ecies::encrypt_for`. Unlike `crypto_layer_encrypt`, the data may be of any
+  /// size.
+  ///
+  /// # Safety
+  ///
+  /// See `crypto_layer_sign`.
+  int crypto_layer_envelope_encrypt(
+    ffi.Pointer<CryptoLayerProvider> provider,
+    ffi.Pointer<ffi.Uint8> data,
+    int data_len,
+    ffi.Pointer<CryptoLayerBuffer> out,
+  ) {
+    return _crypto_layer_envelope_encrypt(
+      provider,
+      data,
+      data_len,
+      out,
+    );
+  }
+
+  late final _crypto_layer_envelope_encryptPtr =
+      _lookup<ffi.NativeFunction<ffi.Int32 Function(ffi.Pointer<CryptoLayerProvider>, ffi.Pointer<ffi.Uint8>, ffi.Size, ffi.Pointer<CryptoLayerBuffer>)>>('crypto_layer_envelope_encrypt');
+  late final _crypto_layer_envelope_encrypt =
+      _crypto_layer_envelope_encryptPtr.asFunction<int Function(ffi.Pointer<CryptoLayerProvider>, ffi.Pointer<ffi.Uint8>, int, ffi.Pointer<CryptoLayerBuffer>)>();
+
+  /// Decrypts an envelope of `crypto_layer_envelope_encrypt` with the current key and writes the
+  /// plaintext to `out`, see `ecies::decrypt`.
+  ///
+  /// # Safety
+  ///
+  /// See `crypto_layer_sign`.
+  int crypto_layer_envelope_decrypt(
+    ffi.Pointer<CryptoLayerProvider> provider,
+    ffi.Pointer<ffi.Uint8> data,
+    int data_len,
+    ffi.Pointer<CryptoLayerBuffer> out,
+  ) {
+    return _crypto_layer_envelope_decrypt(
+      provider,
+      data,
+      data_len,
+      out,
+    );
+  }
+
+  late final _crypto_layer_envelope_decryptPtr =
+      _lookup<ffi.NativeFunction<ffi.Int32 Function(ffi.Pointer<CryptoLayerProvider>, ffi.Pointer<ffi.Uint8>, ffi.Size, ffi.Pointer<CryptoLayerBuffer>)>>('crypto_layer_envelope_decrypt');
+  late final _crypto_layer_envelope_decrypt =
+      _crypto_layer_envelope_decryptPtr.asFunction<int Function(ffi.Pointer<CryptoLayerProvider>, ffi.Pointer<ffi.Uint8>, int, ffi.Pointer<CryptoLayerBuffer>)>();
+
+  /// Zeroes and releases a buffer of the library and resets it to null. Null pointers and empty
+  /// buffers are ignored.
+  ///
+  /// # Safety
+  ///
+  /// `buffer` must be null or point to a buffer that is empty or was written by the library and
+  /// not released yet.
+  void crypto_layer_buffer_free(
+    ffi.Pointer<CryptoLayerBuffer> buffer,
+  ) {
+    return _crypto_layer_buffer_free(
+      buffer,
+    );
+  }
+
+  late final _crypto_layer_buffer_freePtr =
+      _lookup<ffi.NativeFunction<ffi.Void Function(ffi.Pointer<CryptoLayerBuffer>)>>('crypto_layer_buffer_free');
+  late final _crypto_layer_buffer_free =
+      _crypto_layer_buffer_freePtr.asFunction<void Function(ffi.Pointer<CryptoLayerBuffer>)>();
+
+  late final addresses = _SymbolAddresses(this);
+}
+
+class _SymbolAddresses {
+  final CryptoLayerBindings _library;
+  _SymbolAddresses(this._library);
+  ffi.Pointer<
+          ffi.NativeFunction<ffi.Void Function(ffi.Pointer<CryptoLayerProvider>)>>
+      get crypto_layer_provider_free =>
+          _library._crypto_layer_provider_freePtr;
+}
+
+/// An open security module with its current key, see `crypto_layer_provider_open`.
+final class CryptoLayerProvider extends ffi.Opaque {}
+
+/// The algorithm, purposes and policy of a key, see `KeySpec`.
+final class CryptoLayerKeySpec extends ffi.Struct {
+  /// One of the `CRYPTO_LAYER_ALGORITHM_*` constants.
+  @ffi.Uint32()
+  external int algorithm;
+
+  /// A combination of the `CRYPTO_LAYER_PURPOSE_*` flags.
+  @ffi.Uint32()
+  external int purposes;
+
+  /// One of the `CRYPTO_LAYER_ACCESS_*` constants.
+  @ffi.Uint32()
+  external int access;
+
+  /// Whether the private key can be exported, see `KeySpec::exportable`.
+  @ffi.Bool()
+  external bool exportable;
+}
+
+/// Bytes allocated by the library, released with `crypto_layer_buffer_free`.
+final class CryptoLayerBuffer extends ffi.Struct {
+  /// The bytes, or null for an empty buffer.
+  external ffi.Pointer<ffi.Uint8> data;
+
+  /// The number of bytes.
+  @ffi.Size()
+  external int len;
+}
+
+/// The version of the C API, which changes with every incompatible change.
+const int CRYPTO_LAYER_ABI_VERSION = 1;
+
+/// The call succeeded.
+const int CRYPTO_LAYER_OK = 0;
+
+/// A required pointer argument is null.
+const int CRYPTO_LAYER_ERROR_NULL_POINTER = 200;
+
+/// An argument is not valid UTF-8 or not one of the constants of its field.
+const int CRYPTO_LAYER_ERROR_INVALID_ARGUMENT = 201;
+
+/// The security module is unknown or not compiled into the library.
+const int CRYPTO_LAYER_ERROR_UNSUPPORTED_MODULE = 202;
+
+/// The library panicked, which is a bug.
+const int CRYPTO_LAYER_ERROR_PANIC = 203;
+
+/// `CryptoLayerKeySpec::algorithm` of NIST P-256 keys.
+const int CRYPTO_LAYER_ALGORITHM_EC_P256 = 1;
+
+/// `CryptoLayerKeySpec::algorithm` of NIST P-384 keys.
+const int CRYPTO_LAYER_ALGORITHM_EC_P384 = 2;
+
+/// `CryptoLayerKeySpec::algorithm` of NIST P-521 keys.
+const int CRYPTO_LAYER_ALGORITHM_EC_P521 = 3;
+
+/// `CryptoLayerKeySpec::algorithm` of 2048 bit RSA keys.
+const int CRYPTO_LAYER_ALGORITHM_RSA_2048 = 4;
+
+/// `CryptoLayerKeySpec::algorithm` of 3072 bit RSA keys.
+const int CRYPTO_LAYER_ALGORITHM_RSA_3072 = 5;
+
+/// `CryptoLayerKeySpec::algorithm` of 4096 bit RSA keys.
+const int CRYPTO_LAYER_ALGORITHM_RSA_4096 = 6;
+
+/// `CryptoLayerKeySpec::algorithm` of 128 bit AES-GCM keys.
+const int CRYPTO_LAYER_ALGORITHM_AES_128_GCM = 7;
+
+/// `CryptoLayerKeySpec::algorithm` of 256 bit AES-GCM keys.
+const int CRYPTO_LAYER_ALGORITHM_AES_256_GCM = 8;
+
+/// `CryptoLayerKeySpec::purposes` flag for signing and verifying.
+const int CRYPTO_LAYER_PURPOSE_SIGN = 1;
+
+/// `CryptoLayerKeySpec::purposes` flag for encrypting and decrypting.
+const int CRYPTO_LAYER_PURPOSE_ENCRYPT = 2;
+
+/// `CryptoLayerKeySpec::purposes` flag for key agreements.
+const int CRYPTO_LAYER_PURPOSE_KEY_AGREEMENT = 4;
+
+/// `CryptoLayerKeySpec::access` of keys that can be used whenever the device is unlocked.
+const int CRYPTO_LAYER_ACCESS_NONE = 0;
+
+/// `CryptoLayerKeySpec::access` of keys that require any biometric or the device passcode.
+const int CRYPTO_LAYER_ACCESS_USER_PRESENCE = 1;
+
+/// `CryptoLayerKeySpec::access` of keys that require any enrolled biometric.
+const int CRYPTO_LAYER_ACCESS_BIOMETRY_ANY = 2;
+
+/// `CryptoLayerKeySpec::access` of keys that require a biometric enrolled at their creation.
+const int CRYPTO_LAYER_ACCESS_BIOMETRY_CURRENT_SET = 3;
+
+/// `CryptoLayerKeySpec::access` of keys that require the device passcode.
+const int CRYPTO_LAYER_ACCESS_DEVICE_PASSCODE = 4;
@@ -0,0 +1,74 @@
+// Tests of the Dart package against the mock provider.
+//
+// Build the library with the mock provider and run the tests from `dart/`:
+//
+//     cargo build --features ffi,test-utils,macos
+//     CRYPTO_LAYER_LIBRARY=../target/debug/libcrypto_layer.so dart test
+
+import 'dart:convert';
+import 'dart:math';
+import 'dart:typed_data';
+
+import 'package:crypto_layer/crypto_layer.dart';
+import 'package:test/test.dart';
+
+void main() {
+  test('signs and verifies', () async {
+    final provider = await Provider.open('MOCK', 'dart_sign');
+    await provider.createKey('dart_sign', Algorithm.ecP256, {Purpose.sign});
+
+    final signature = await provider.sign(utf8.encode('Hello, Dart!'));
+    expect(
+        await provider.verify(utf8.encode('Hello, Dart!'), signature), isTrue);
+    expect(
+        await provider.verify(utf8.encode('Hello, Rust!'), signature), isFalse);
+    expect((await provider.publicKey())[0], 0x30);
+    await provider.close();
+  });
+
+  test('encrypts envelopes', () async {
+    final provider = await Provider.open('MOCK', 'dart_envelope');
+    await provider.createKey(
+        'dart_envelope', Algorithm.ecP256, {Purpose.keyAgreement});
+    final random = Random.secure();
+    final secret = Uint8List.fromList(
+        List.generate(100000, (_) => random.nextInt(256)));
+
+    final envelope = await provider.envelopeEncrypt(secret);
+    expect(await provider.envelopeDecrypt(envelope), secret);
+    envelope[envelope.length - 1] ^= 1;
+    await expectLater(provider.envelopeDecrypt(envelope),
+        throwsA(isA<CryptoLayerException>()));
+    await provider.close();
+  });
+
+  test('runs the calls of a provider in order', () async {
+    final provider = await Provider.open('MOCK', 'dart_order');
+    // The calls do not wait for each other, and the provider closes after them.
+    final created =
+        provider.createKey('dart_order', Algorithm.rsa2048, {Purpose.encrypt});
+    final ciphertext = provider.encrypt(utf8.encode('secret'));
+    final closed = provider.close();
+
+    await created;
+    expect(await ciphertext, isNotEmpty);
+    await closed;
+    await expectLater(provider.sign([1]), throwsStateError);
+  });
+
+  test('throws the errors of the C API', () async {
+    await expectLater(
+        Provider.open('QUANTUM', 'dart_errors'),
+        throwsA(isA<CryptoLayerException>()
+            .having((e) => e.code, 'code', 202)
+            .having((e) => e.message, 'message', contains('QUANTUM'))));
+
+    final provider = await Provider.open('MOCK', 'dart_errors');
+    // The mock provider has no symmetric keys.
+    await expectLater(
+        provider
+            .createKey('dart_errors', Algorithm.aes256Gcm, {Purpose.encrypt}),
+        throwsA(isA<CryptoLayerException>().having((e) => e.code, 'code', 7)));
+    await provider.close();
+  });
+}
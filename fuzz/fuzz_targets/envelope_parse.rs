@@ -0,0 +1,19 @@
+#![no_main]
+
+use crypto_layer_core::envelope::{Envelope, EnvelopeRef};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(parsed) = EnvelopeRef::parse(data) else {
+        return;
+    };
+    assert!(data.ends_with(parsed.ciphertext));
+    assert!(data.starts_with(parsed.header));
+
+    // Re-encoding drops unknown optional fields, but must preserve everything that was parsed.
+    let envelope = parsed.to_envelope();
+    let encoded = envelope
+        .to_bytes()
+        .expect("parsed envelope cannot be encoded");
+    assert_eq!(Envelope::from_bytes(&encoded).unwrap(), envelope);
+});
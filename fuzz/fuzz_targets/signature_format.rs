@@ -0,0 +1,29 @@
+#![no_main]
+
+use crypto_layer_core::signature_format::{self, SignatureFormat, MAX_SCALAR_LEN};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Some((&selector, signature)) = data.split_first() else {
+        return;
+    };
+    let scalar_len = usize::from(selector) % (MAX_SCALAR_LEN + 1);
+
+    // DER is parsed strictly, so every accepted encoding is canonical and survives a round trip.
+    if let Ok(raw) = signature_format::der_to_raw(signature, scalar_len) {
+        assert_eq!(raw.len(), 2 * scalar_len);
+        assert_eq!(signature_format::raw_to_der(&raw).unwrap(), signature);
+    }
+
+    if let Ok(der) = signature_format::raw_to_der(signature) {
+        let raw = signature_format::der_to_raw(&der, signature.len() / 2).unwrap();
+        assert_eq!(raw, signature);
+    }
+
+    let _ = signature_format::convert(
+        signature,
+        SignatureFormat::Raw,
+        SignatureFormat::Der,
+        scalar_len,
+    );
+});
@@ -0,0 +1,52 @@
+#![no_main]
+
+use crypto_layer::{
+    common::crypto::algorithms::{
+        encryption::{AsymmetricEncryption, EccCurves, EccSchemeAlgorithm},
+        hashes::{Hash, Sha2Bits},
+        KeyBits,
+    },
+    tpm::macos::{
+        dispatch,
+        response::{self, SwiftError, SECURE_ENCLAVE_DOMAIN},
+    },
+    SecurityModuleError,
+};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    // The response bytes of `rustcall_dispatch` are parsed before they are decoded.
+    let _ = dispatch::decode_response(data);
+
+    let Some((&selector, message)) = data.split_first() else {
+        return;
+    };
+    // The Swift bridge hands over Swift strings, which are always valid UTF-8.
+    let Ok(message) = std::str::from_utf8(message) else {
+        return;
+    };
+    let failed = selector & 1 == 1;
+    let response = || {
+        if failed {
+            Err(SwiftError::new(
+                u32::from(selector >> 3),
+                SECURE_ENCLAVE_DOMAIN,
+                message,
+            ))
+        } else {
+            Ok(message.to_owned())
+        }
+    };
+
+    let _ = response::decode_status(response(), SecurityModuleError::InitializationError);
+    let _ = response::decode_bytes(response(), SecurityModuleError::SigningError);
+    let _ = response::decode_verification(response());
+
+    let algorithm = match selector >> 1 & 3 {
+        0 => AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(EccCurves::P256)),
+        1 => AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(EccCurves::P384)),
+        2 => AsymmetricEncryption::Rsa(KeyBits::Bits2048),
+        _ => AsymmetricEncryption::Rsa(KeyBits::Bits4096),
+    };
+    let _ = response::decode_public_key(response(), algorithm, Hash::Sha2(Sha2Bits::Sha256));
+});
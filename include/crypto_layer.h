@@ -0,0 +1,251 @@
+/* The C API of the Crypto Layer, see src/ffi/c_api.rs. */
+
+#ifndef CRYPTO_LAYER_H
+#define CRYPTO_LAYER_H
+
+/* Generated with cbindgen from src/ffi/c_api.rs, do not edit. */
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+// The version of the C API, which changes with every incompatible change.
+#define CRYPTO_LAYER_ABI_VERSION 1
+
+// The call succeeded.
+#define CRYPTO_LAYER_OK 0
+
+// A required pointer argument is null.
+#define CRYPTO_LAYER_ERROR_NULL_POINTER 200
+
+// An argument is not valid UTF-8 or not one of the constants of its field.
+#define CRYPTO_LAYER_ERROR_INVALID_ARGUMENT 201
+
+// The security module is unknown or not compiled into the library.
+#define CRYPTO_LAYER_ERROR_UNSUPPORTED_MODULE 202
+
+// The library panicked, which is a bug.
+#define CRYPTO_LAYER_ERROR_PANIC 203
+
+// `CryptoLayerKeySpec::algorithm` of NIST P-256 keys.
+#define CRYPTO_LAYER_ALGORITHM_EC_P256 1
+
+// `CryptoLayerKeySpec::algorithm` of NIST P-384 keys.
+#define CRYPTO_LAYER_ALGORITHM_EC_P384 2
+
+// `CryptoLayerKeySpec::algorithm` of NIST P-521 keys.
+#define CRYPTO_LAYER_ALGORITHM_EC_P521 3
+
+// `CryptoLayerKeySpec::algorithm` of 2048 bit RSA keys.
+#define CRYPTO_LAYER_ALGORITHM_RSA_2048 4
+
+// `CryptoLayerKeySpec::algorithm` of 3072 bit RSA keys.
+#define CRYPTO_LAYER_ALGORITHM_RSA_3072 5
+
+// `CryptoLayerKeySpec::algorithm` of 4096 bit RSA keys.
+#define CRYPTO_LAYER_ALGORITHM_RSA_4096 6
+
+// `CryptoLayerKeySpec::algorithm` of 128 bit AES-GCM keys.
+#define CRYPTO_LAYER_ALGORITHM_AES_128_GCM 7
+
+// `CryptoLayerKeySpec::algorithm` of 256 bit AES-GCM keys.
+#define CRYPTO_LAYER_ALGORITHM_AES_256_GCM 8
+
+// `CryptoLayerKeySpec::purposes` flag for signing and verifying.
+#define CRYPTO_LAYER_PURPOSE_SIGN 1
+
+// `CryptoLayerKeySpec::purposes` flag for encrypting and decrypting.
+#define CRYPTO_LAYER_PURPOSE_ENCRYPT 2
+
+// `CryptoLayerKeySpec::purposes` flag for key agreements.
+#define CRYPTO_LAYER_PURPOSE_KEY_AGREEMENT 4
+
+// `CryptoLayerKeySpec::access` of keys that can be used whenever the device is unlocked.
+#define CRYPTO_LAYER_ACCESS_NONE 0
+
+// `CryptoLayerKeySpec::access` of keys that require any biometric or the device passcode.
+#define CRYPTO_LAYER_ACCESS_USER_PRESENCE 1
+
+// `CryptoLayerKeySpec::access` of keys that require any enrolled biometric.
+#define CRYPTO_LAYER_ACCESS_BIOMETRY_ANY 2
+
+// `CryptoLayerKeySpec::access` of keys that require a biometric enrolled at their creation.
+#define CRYPTO_LAYER_ACCESS_BIOMETRY_CURRENT_SET 3
+
+// `CryptoLayerKeySpec::access` of keys that require the device passcode.
+#define CRYPTO_LAYER_ACCESS_DEVICE_PASSCODE 4
+
+// An open security module with its current key, see `crypto_layer_provider_open`.
+typedef struct CryptoLayerProvider CryptoLayerProvider;
+
+// The algorithm, purposes and policy of a key, see `KeySpec`.
+typedef struct CryptoLayerKeySpec {
+  // One of the `CRYPTO_LAYER_ALGORITHM_*` constants.
+  uint32_t algorithm;
+  // A combination of the `CRYPTO_LAYER_PURPOSE_*` flags.
+  uint32_t purposes;
+  // One of the `CRYPTO_LAYER_ACCESS_*` constants.
+  uint32_t access;
+  // Whether the private key can be exported, see `KeySpec::exportable`.
+  bool exportable;
+} CryptoLayerKeySpec;
+
+// Bytes allocated by the library, released with `crypto_layer_buffer_free`.
+typedef struct CryptoLayerBuffer {
+  // The bytes, or null for an empty buffer.
+  uint8_t *data;
+  // The number of bytes.
+  size_t len;
+} CryptoLayerBuffer;
+
+#ifdef __cplusplus
+extern "C" {
+#endif  // __cplusplus
+
+// Returns `CRYPTO_LAYER_ABI_VERSION` of the library, which applications compare with the
+// version of the header they were compiled with.
+uint32_t crypto_layer_abi_version(void);
+
+// Returns the message of the last error of the calling thread as null-terminated UTF-8, or an
+// empty string. The string is valid until the next failing call on the thread.
+const char *crypto_layer_last_error_message(void);
+
+// Opens and initializes a security module.
+//
+// # Arguments
+//
+// * `module` - `"TPM"` for the security module of the platform, or `"MOCK"` for the in-memory
+//   provider of builds with the `test-utils` feature.
+// * `key_id` - The id of the key the provider is created for.
+// * `out` - Receives the handle, which is released with `crypto_layer_provider_free`.
+//
+// # Safety
+//
+// `module` and `key_id` must be null or null-terminated strings, and `out` null or valid for
+// writes.
+int32_t crypto_layer_provider_open(const char *module,
+                                   const char *key_id,
+                                   CryptoLayerProvider **out);
+
+// Releases a provider handle of `crypto_layer_provider_open`. Null is ignored.
+//
+// # Safety
+//
+// `provider` must be null or a handle that was not released yet.
+void crypto_layer_provider_free(CryptoLayerProvider *provider);
+
+// Creates a key and makes it the current key of the provider.
+//
+// # Arguments
+//
+// * `provider` - The provider handle.
+// * `key_id` - The id of the key, which is also its label.
+// * `spec` - The algorithm, purposes and policy of the key.
+//
+// # Safety
+//
+// `provider` must be null or a valid handle, `key_id` null or a null-terminated string, and
+// `spec` null or valid for reads.
+int32_t crypto_layer_create_key(CryptoLayerProvider *provider,
+                                const char *key_id,
+                                const CryptoLayerKeySpec *spec);
+
+// Loads an existing key and makes it the current key of the provider, like
+// `crypto_layer_create_key`.
+//
+// # Safety
+//
+// See `crypto_layer_create_key`.
+int32_t crypto_layer_load_key(CryptoLayerProvider *provider,
+                              const char *key_id,
+                              const CryptoLayerKeySpec *spec);
+
+// Writes the DER encoded SubjectPublicKeyInfo of the current key to `out`.
+//
+// # Safety
+//
+// `provider` must be null or a valid handle and `out` null or valid for writes.
+int32_t crypto_layer_public_key(const CryptoLayerProvider *provider,
+                                CryptoLayerBuffer *out);
+
+// Signs `data` with the current key and writes the signature to `out`.
+//
+// # Safety
+//
+// `provider` must be null or a valid handle, `data` valid for `data_len` bytes unless
+// `data_len` is 0, and `out` null or valid for writes.
+int32_t crypto_layer_sign(const CryptoLayerProvider *provider,
+                          const uint8_t *data,
+                          size_t data_len,
+                          CryptoLayerBuffer *out);
+
+// Verifies `signature` over `data` with the current key and writes the result to `valid`.
+//
+// # Safety
+//
+// `provider` must be null or a valid handle, `data` and `signature` valid for their lengths
+// unless they are 0, and `valid` null or valid for writes.
+int32_t crypto_layer_verify(const CryptoLayerProvider *provider,
+                            const uint8_t *data,
+                            size_t data_len,
+                            const uint8_t *signature,
+                            size_t signature_len,
+                            bool *valid);
+
+// Encrypts `data` with the current key and writes the ciphertext to `out`.
+//
+// # Safety
+//
+// See `crypto_layer_sign`.
+int32_t crypto_layer_encrypt(const CryptoLayerProvider *provider,
+                             const uint8_t *data,
+                             size_t data_len,
+                             CryptoLayerBuffer *out);
+
+// Decrypts `data` with the current key and writes the plaintext to `out`.
+//
+// # Safety
+//
+// See `crypto_layer_sign`.
+int32_t crypto_layer_decrypt(const CryptoLayerProvider *provider,
+                             const uint8_t *data,
+                             size_t data_len,
+                             CryptoLayerBuffer *out);
+
+// Encrypts `data` with ECIES for the public key of the current EC key and writes the envelope
+// to `out`, see `ecies::encrypt_for`. Unlike `crypto_layer_encrypt`, the data may be of any
+// size.
+//
+// # Safety
+//
+// See `crypto_layer_sign`.
+int32_t crypto_layer_envelope_encrypt(const CryptoLayerProvider *provider,
+                                      const uint8_t *data,
+                                      size_t data_len,
+                                      CryptoLayerBuffer *out);
+
+// Decrypts an envelope of `crypto_layer_envelope_encrypt` with the current key and writes the
+// plaintext to `out`, see `ecies::decrypt`.
+//
+// # Safety
+//
+// See `crypto_layer_sign`.
+int32_t crypto_layer_envelope_decrypt(const CryptoLayerProvider *provider,
+                                      const uint8_t *data,
+                                      size_t data_len,
+                                      CryptoLayerBuffer *out);
+
+// Zeroes and releases a buffer of the library and resets it to null. Null pointers and empty
+// buffers are ignored.
+//
+// # Safety
+//
+// `buffer` must be null or point to a buffer that is empty or was written by the library and
+// not released yet.
+void crypto_layer_buffer_free(CryptoLayerBuffer *buffer);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* CRYPTO_LAYER_H */
@@ -0,0 +1,51 @@
+export declare const ABI_VERSION: number;
+
+export type Algorithm =
+  | 'EcP256'
+  | 'EcP384'
+  | 'EcP521'
+  | 'Rsa2048'
+  | 'Rsa3072'
+  | 'Rsa4096'
+  | 'Aes128Gcm'
+  | 'Aes256Gcm';
+
+export type Purpose = 'Sign' | 'Encrypt' | 'KeyAgreement';
+
+export type Access =
+  | 'None'
+  | 'UserPresence'
+  | 'BiometryAny'
+  | 'BiometryCurrentSet'
+  | 'DevicePasscode';
+
+/** The algorithm, purposes and policy of a key. */
+export interface KeySpec {
+  algorithm: Algorithm;
+  purposes: Purpose[];
+  access?: Access;
+  exportable?: boolean;
+}
+
+/** The errors of the addon carry the numeric error code of the C API. */
+export interface CryptoLayerError extends Error {
+  code: number;
+}
+
+/** An open security module with its current key. All operations run off the event loop. */
+export declare class Provider {
+  private constructor(handle: unknown);
+  static open(module: 'TPM' | 'MOCK', keyId: string): Promise<Provider>;
+  createKey(keyId: string, spec: KeySpec): Promise<void>;
+  loadKey(keyId: string, spec: KeySpec): Promise<void>;
+  /** The DER encoded SubjectPublicKeyInfo of the current key. */
+  publicKey(): Promise<Buffer>;
+  sign(data: Uint8Array): Promise<Buffer>;
+  verify(data: Uint8Array, signature: Uint8Array): Promise<boolean>;
+  encrypt(data: Uint8Array): Promise<Buffer>;
+  decrypt(data: Uint8Array): Promise<Buffer>;
+  /** Encrypts data of any size with ECIES for the current EC key. */
+  envelopeEncrypt(data: Uint8Array): Promise<Buffer>;
+  envelopeDecrypt(envelope: Uint8Array): Promise<Buffer>;
+  close(): void;
+}
@@ -0,0 +1,139 @@
+'use strict';
+
+// Loads the Node-API addon of the crate, built with the `node` feature, and wraps its functions
+// in the `Provider` class. The addon is looked up in CRYPTO_LAYER_ADDON and then next to this
+// file as crypto_layer.node, the renamed libcrypto_layer.so, .dylib or crypto_layer.dll.
+
+const path = require('path');
+
+const ABI_VERSION = 1;
+
+// The error code of the C API for invalid arguments.
+const INVALID_ARGUMENT = 201;
+
+const Algorithm = Object.freeze({
+  EcP256: 1,
+  EcP384: 2,
+  EcP521: 3,
+  Rsa2048: 4,
+  Rsa3072: 5,
+  Rsa4096: 6,
+  Aes128Gcm: 7,
+  Aes256Gcm: 8,
+});
+
+const Purpose = Object.freeze({
+  Sign: 1,
+  Encrypt: 2,
+  KeyAgreement: 4,
+});
+
+const Access = Object.freeze({
+  None: 0,
+  UserPresence: 1,
+  BiometryAny: 2,
+  BiometryCurrentSet: 3,
+  DevicePasscode: 4,
+});
+
+function loadAddon() {
+  const addon = { exports: {} };
+  const file = process.env.CRYPTO_LAYER_ADDON || path.join(__dirname, 'crypto_layer.node');
+  process.dlopen(addon, file);
+  if (addon.exports.abiVersion() !== ABI_VERSION) {
+    throw new Error(
+      `The addon has ABI version ${addon.exports.abiVersion()}, the package needs ${ABI_VERSION}`
+    );
+  }
+  return addon.exports;
+}
+
+const native = loadAddon();
+
+function constant(constants, name, field) {
+  if (!Object.prototype.hasOwnProperty.call(constants, name)) {
+    const error = new TypeError(`Unknown ${field} '${name}'`);
+    error.code = INVALID_ARGUMENT;
+    throw error;
+  }
+  return constants[name];
+}
+
+// Calls a function of the addon. napi-rs throws for arguments of the wrong type with the name of
+// a Node-API status as code, e.g. 'InvalidArg', which is reported as INVALID_ARGUMENT instead.
+function call(name, ...args) {
+  try {
+    return native[name](...args);
+  } catch (error) {
+    if (typeof error.code === 'string') {
+      error.code = INVALID_ARGUMENT;
+    }
+    throw error;
+  }
+}
+
+// Returns the arguments of createKey and loadKey of the addon for a key spec.
+function spec({ algorithm, purposes, access = 'None', exportable = false }) {
+  return [
+    constant(Algorithm, algorithm, 'algorithm'),
+    purposes.reduce((flags, purpose) => flags | constant(Purpose, purpose, 'purpose'), 0),
+    constant(Access, access, 'access'),
+    Boolean(exportable),
+  ];
+}
+
+// An open security module with its current key. All operations run off the event loop.
+class Provider {
+  constructor(handle) {
+    this._handle = handle;
+  }
+
+  // Opens "TPM", the security module of the platform, e.g. the Secure Enclave, or "MOCK", the
+  // in-memory provider of addons built with the test-utils feature.
+  static async open(module, keyId) {
+    return new Provider(await call('open', module, keyId));
+  }
+
+  async createKey(keyId, keySpec) {
+    await call('createKey', this._handle, keyId, ...spec(keySpec));
+  }
+
+  async loadKey(keyId, keySpec) {
+    await call('loadKey', this._handle, keyId, ...spec(keySpec));
+  }
+
+  async publicKey() {
+    return call('publicKey', this._handle);
+  }
+
+  async sign(data) {
+    return call('sign', this._handle, data);
+  }
+
+  async verify(data, signature) {
+    return call('verify', this._handle, data, signature);
+  }
+
+  async encrypt(data) {
+    return call('encrypt', this._handle, data);
+  }
+
+  async decrypt(data) {
+    return call('decrypt', this._handle, data);
+  }
+
+  async envelopeEncrypt(data) {
+    return call('envelopeEncrypt', this._handle, data);
+  }
+
+  async envelopeDecrypt(envelope) {
+    return call('envelopeDecrypt', this._handle, envelope);
+  }
+
+  // Releases the provider; operations that are still running complete.
+  close() {
+    call('close', this._handle);
+  }
+}
+
+module.exports = { ABI_VERSION, Access, Algorithm, Provider, Purpose };
@@ -0,0 +1,57 @@
+'use strict';
+
+// Tests of the package against the mock provider. Build the addon with the mock provider and run
+// the tests from the repository:
+//
+//     cargo build --features node,test-utils,macos
+//     CRYPTO_LAYER_ADDON=target/debug/libcrypto_layer.so node --test node/test/
+
+const assert = require('assert');
+const crypto = require('crypto');
+const test = require('node:test');
+const { Provider } = require('..');
+
+test('signs and verifies', async () => {
+  const provider = await Provider.open('MOCK', 'node_sign');
+  await provider.createKey('node_sign', { algorithm: 'EcP256', purposes: ['Sign'] });
+  const data = Buffer.from('Hello, Node!');
+  const signature = await provider.sign(data);
+  assert.strictEqual(await provider.verify(data, signature), true);
+
+  const publicKey = crypto.createPublicKey({
+    key: await provider.publicKey(),
+    format: 'der',
+    type: 'spki',
+  });
+  assert.ok(crypto.verify('sha256', data, publicKey, signature));
+  provider.close();
+});
+
+test('encrypts envelopes', async () => {
+  const provider = await Provider.open('MOCK', 'node_envelope');
+  await provider.createKey('node_envelope', { algorithm: 'EcP256', purposes: ['KeyAgreement'] });
+  const secret = crypto.randomBytes(100000);
+  const envelopes = await Promise.all([
+    provider.envelopeEncrypt(secret),
+    provider.envelopeEncrypt(new Uint8Array(0)),
+  ]);
+  assert.deepStrictEqual(await provider.envelopeDecrypt(envelopes[0]), secret);
+  assert.strictEqual((await provider.envelopeDecrypt(envelopes[1])).length, 0);
+
+  envelopes[0][envelopes[0].length - 1] ^= 1;
+  await assert.rejects(provider.envelopeDecrypt(envelopes[0]), { code: 2 });
+  provider.close();
+});
+
+test('rejects with error codes', async () => {
+  await assert.rejects(Provider.open('QUANTUM', 'node_errors'), { code: 202 });
+
+  const provider = await Provider.open('MOCK', 'node_errors');
+  await assert.rejects(provider.createKey('node_errors', { algorithm: 'Rsa1024', purposes: [] }), {
+    code: 201,
+  });
+  await assert.rejects(provider.sign('not bytes'), { code: 201 });
+  await assert.rejects(provider.sign(Buffer.from('no key')), (error) => error.code < 200);
+  provider.close();
+  await assert.rejects(provider.sign(Buffer.from('closed')), /closed/);
+});
@@ -0,0 +1,57 @@
+"""Hardware-backed keys of the Crypto Layer for Python.
+
+The extension module ``_crypto_layer`` is the crate built with the ``python`` feature by
+maturin, see ``src/ffi/python.rs``::
+
+    from crypto_layer import Algorithm, Provider, Purpose
+
+    with Provider("TPM", "ops-signing") as provider:
+        provider.create_key("ops-signing", Algorithm.EC_P256, Purpose.SIGN)
+        signature = provider.sign(b"release.tar.gz")
+
+Errors are raised as ``CryptoLayerError`` with the error code of the C API.
+"""
+
+import enum
+
+from ._crypto_layer import ABI_VERSION, CryptoLayerError, Provider
+
+__all__ = [
+    "ABI_VERSION",
+    "Access",
+    "Algorithm",
+    "CryptoLayerError",
+    "Provider",
+    "Purpose",
+]
+
+
+class Algorithm(enum.IntEnum):
+    """The ``CRYPTO_LAYER_ALGORITHM_*`` constants."""
+
+    EC_P256 = 1
+    EC_P384 = 2
+    EC_P521 = 3
+    RSA_2048 = 4
+    RSA_3072 = 5
+    RSA_4096 = 6
+    AES_128_GCM = 7
+    AES_256_GCM = 8
+
+
+class Purpose(enum.IntFlag):
+    """The ``CRYPTO_LAYER_PURPOSE_*`` flags."""
+
+    SIGN = 1
+    ENCRYPT = 2
+    KEY_AGREEMENT = 4
+
+
+class Access(enum.IntEnum):
+    """The ``CRYPTO_LAYER_ACCESS_*`` constants."""
+
+    NONE = 0
+    USER_PRESENCE = 1
+    BIOMETRY_ANY = 2
+    BIOMETRY_CURRENT_SET = 3
+    DEVICE_PASSCODE = 4
@@ -0,0 +1,54 @@
+"""Tests of the Python module against the mock provider.
+
+Build the module with the mock provider into the active virtual environment in `python/` and run
+the tests:
+
+    maturin develop --features python,macos,test-utils
+    python3 -m unittest discover tests
+
+`tests/python_module.rs` runs them with `cargo test --features python,test-utils,macos`.
+"""
+
+import os
+import unittest
+
+from crypto_layer import Algorithm, CryptoLayerError, Provider, Purpose
+
+
+class ProviderTest(unittest.TestCase):
+    def test_sign_and_verify(self):
+        with Provider("MOCK", "python_sign") as provider:
+            provider.create_key("python_sign", Algorithm.EC_P256, Purpose.SIGN)
+            signature = provider.sign(b"Hello, Python!")
+            self.assertTrue(provider.verify(b"Hello, Python!", signature))
+            self.assertFalse(provider.verify(b"Hello, Rust!", signature))
+            self.assertEqual(provider.public_key()[0], 0x30)
+
+    def test_envelope(self):
+        with Provider("MOCK", "python_envelope") as provider:
+            provider.create_key(
+                "python_envelope", Algorithm.EC_P256, Purpose.KEY_AGREEMENT
+            )
+            secret = os.urandom(100_000)
+            envelope = provider.envelope_encrypt(secret)
+            self.assertEqual(provider.envelope_decrypt(envelope), secret)
+            with self.assertRaises(CryptoLayerError):
+                provider.envelope_decrypt(envelope[:-1] + bytes([envelope[-1] ^ 1]))
+
+    def test_errors(self):
+        with self.assertRaises(CryptoLayerError) as error:
+            Provider("QUANTUM", "python_errors")
+        self.assertEqual(error.exception.code, 202)
+        self.assertIn("QUANTUM", error.exception.message)
+
+        provider = Provider("MOCK", "python_errors")
+        with self.assertRaises(CryptoLayerError) as error:
+            provider.create_key("python_errors", 99, Purpose.SIGN)
+        self.assertEqual(error.exception.code, 201)
+        provider.close()
+        with self.assertRaises(ValueError):
+            provider.sign(b"data")
+
+
+if __name__ == "__main__":
+    unittest.main()
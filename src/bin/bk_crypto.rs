@@ -0,0 +1,31 @@
+//! Manages the keys of the configured provider and uses them, see `crypto_layer::cli`.
+//!
+//! Usage: `bk-crypto <command>`, e.g. `bk-crypto key create release --algorithm ec-p256`. The
+//! provider is selected by the configuration of the crate, e.g. `CRYPTO_LAYER_PROVIDERS=linux`.
+
+use crypto_layer::{
+    cli::{self, Cli},
+    tpm::TpmConfig,
+    SecModules,
+};
+use std::{io, process::ExitCode};
+
+fn main() -> ExitCode {
+    let matches = cli::command().get_matches();
+    let provider = match SecModules::get_preferred_instance("bk-crypto".to_owned(), None) {
+        Ok(provider) => provider,
+        Err(e) => {
+            eprintln!("Failed to initialize the provider: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let cli = Cli::new(provider, TpmConfig::from_spec);
+    match cli.run(&matches, &mut io::stdin().lock(), &mut io::stdout().lock()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{e}");
+            ExitCode::FAILURE
+        }
+    }
+}
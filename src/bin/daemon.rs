@@ -0,0 +1,42 @@
+//! Serves the keys of the configured provider over JSON-RPC, see `crypto_layer::daemon`.
+//!
+//! Usage: `crypto-layer-daemon [socket]`. Without a socket, the daemon serves its stdin and
+//! stdout and exits when stdin is closed. A socket left over from an earlier run is replaced.
+//! The provider is selected by the configuration of the crate, e.g.
+//! `CRYPTO_LAYER_PROVIDERS=linux`.
+
+use crypto_layer::{daemon::Daemon, tpm::TpmConfig, SecModules};
+use std::{env, fs, io, os::unix::fs::FileTypeExt, process::ExitCode, sync::Arc};
+
+fn main() -> ExitCode {
+    let socket = env::args().nth(1);
+    let provider = match SecModules::get_preferred_instance("crypto-layer-daemon".to_owned(), None)
+    {
+        Ok(provider) => provider,
+        Err(e) => {
+            eprintln!("Failed to initialize the provider: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let daemon = Daemon::new(provider, TpmConfig::from_spec);
+
+    let Some(socket) = socket else {
+        if let Err(e) = daemon.serve(io::stdin().lock(), io::stdout().lock()) {
+            eprintln!("Failed to serve stdio: {e}");
+            return ExitCode::FAILURE;
+        }
+        return ExitCode::SUCCESS;
+    };
+    if fs::symlink_metadata(&socket).is_ok_and(|metadata| metadata.file_type().is_socket()) {
+        if let Err(e) = fs::remove_file(&socket) {
+            eprintln!("Failed to remove {socket}: {e}");
+            return ExitCode::FAILURE;
+        }
+    }
+    eprintln!("Serving on {socket}");
+    if let Err(e) = Arc::new(daemon).listen(&socket) {
+        eprintln!("Failed to serve {socket}: {e}");
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,27 @@
+//! Exports the canonical test vectors of the crypto layer as JSON.
+//!
+//! Usage: `export-test-vectors [path]`. Without a path the vectors are written to stdout.
+
+use crypto_layer::test_vectors;
+use std::{env, fs, process::ExitCode};
+
+fn main() -> ExitCode {
+    let json = match test_vectors::generate().and_then(|vectors| vectors.to_json()) {
+        Ok(json) => json,
+        Err(e) => {
+            eprintln!("Failed to generate test vectors: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match env::args().nth(1) {
+        Some(path) => {
+            if let Err(e) = fs::write(&path, json + "\n") {
+                eprintln!("Failed to write {path}: {e}");
+                return ExitCode::FAILURE;
+            }
+        }
+        None => println!("{json}"),
+    }
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,61 @@
+//! Serves a key of the configured provider as Kubernetes KMS v2 plugin.
+//!
+//! Usage: `kms-plugin <key-id> [socket]`. The socket defaults to
+//! `/var/run/kmsplugin/socket.sock` and is replaced if it is left over from an earlier run. The
+//! provider is selected by the configuration of the crate, e.g. `CRYPTO_LAYER_PROVIDERS=linux`.
+//! An RSA-3072 key is created under `key-id` on the first start and loaded on later starts.
+
+use crypto_layer::{
+    common::crypto::key_spec::{KeyAlgorithm, KeyPurpose, KeySpec},
+    kms_plugin::KmsPlugin,
+    tpm::TpmConfig,
+    SecModules, SecurityModuleError,
+};
+use std::{env, fs, os::unix::fs::FileTypeExt, process::ExitCode, sync::Arc};
+
+const DEFAULT_SOCKET: &str = "/var/run/kmsplugin/socket.sock";
+
+fn plugin(key_id: &str) -> Result<KmsPlugin, SecurityModuleError> {
+    let spec = KeySpec::builder()
+        .algorithm(KeyAlgorithm::Rsa3072)
+        .usage(KeyPurpose::Encrypt)
+        .label(key_id)
+        .build()?;
+    let provider = SecModules::get_preferred_instance(key_id.to_owned(), None)?;
+    {
+        let mut guard = provider.lock().unwrap();
+        if guard.load_key(key_id, TpmConfig::from_spec(&spec)?).is_err() {
+            guard.create_key(key_id, TpmConfig::from_spec(&spec)?)?;
+        }
+    }
+    KmsPlugin::new(provider)
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let Some(key_id) = env::args().nth(1) else {
+        eprintln!("Usage: kms-plugin <key-id> [socket]");
+        return ExitCode::FAILURE;
+    };
+    let socket = env::args().nth(2).unwrap_or_else(|| DEFAULT_SOCKET.to_owned());
+
+    let plugin = match plugin(&key_id) {
+        Ok(plugin) => plugin,
+        Err(e) => {
+            eprintln!("Failed to load the key {key_id}: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    if fs::symlink_metadata(&socket).is_ok_and(|metadata| metadata.file_type().is_socket()) {
+        if let Err(e) = fs::remove_file(&socket) {
+            eprintln!("Failed to remove {socket}: {e}");
+            return ExitCode::FAILURE;
+        }
+    }
+    eprintln!("Serving {} on {socket}", plugin.key_id());
+    if let Err(e) = Arc::new(plugin).listen(&socket).await {
+        eprintln!("Failed to serve {socket}: {e}");
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
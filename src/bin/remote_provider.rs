@@ -0,0 +1,63 @@
+//! Serves the configured provider to `RemoteProvider`s of other hosts.
+//!
+//! Usage: `remote-provider <address>`. The address is a TCP address such as `0.0.0.0:7443`, or
+//! the path of a Unix domain socket prefixed with `unix:`, which is replaced if it is left over
+//! from an earlier run. Calls must carry the bearer token of `CRYPTO_LAYER_REMOTE_TOKEN`, which
+//! is required for TCP addresses. The provider is selected by the configuration of the crate,
+//! e.g. `CRYPTO_LAYER_PROVIDERS=linux`.
+
+use crypto_layer::{remote::RemoteServer, tpm::TpmConfig, SecModules};
+use std::{env, fs, os::unix::fs::FileTypeExt, process::ExitCode, sync::Arc};
+
+const TOKEN_VARIABLE: &str = "CRYPTO_LAYER_REMOTE_TOKEN";
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let Some(address) = env::args().nth(1) else {
+        eprintln!("Usage: remote-provider <address>");
+        return ExitCode::FAILURE;
+    };
+    let token = env::var(TOKEN_VARIABLE)
+        .ok()
+        .filter(|token| !token.is_empty());
+    let socket = address.strip_prefix("unix:");
+    if token.is_none() && socket.is_none() {
+        eprintln!("Set {TOKEN_VARIABLE} to serve {address}");
+        return ExitCode::FAILURE;
+    }
+
+    let provider = match SecModules::get_preferred_instance("remote-provider".to_owned(), None) {
+        Ok(provider) => provider,
+        Err(e) => {
+            eprintln!("Failed to initialize the provider: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut server = RemoteServer::new(provider, TpmConfig::from_spec);
+    if let Some(token) = &token {
+        server = server.with_token(token);
+    }
+    let server = Arc::new(server);
+
+    let result = match socket {
+        Some(socket) => {
+            if fs::symlink_metadata(socket).is_ok_and(|metadata| metadata.file_type().is_socket()) {
+                if let Err(e) = fs::remove_file(socket) {
+                    eprintln!("Failed to remove {socket}: {e}");
+                    return ExitCode::FAILURE;
+                }
+            }
+            eprintln!("Serving on {socket}");
+            server.listen_unix(socket).await
+        }
+        None => {
+            eprintln!("Serving on {address}");
+            server.listen(address.as_str()).await
+        }
+    };
+    if let Err(e) = result {
+        eprintln!("Failed to serve {address}: {e}");
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
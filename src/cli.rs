@@ -0,0 +1,572 @@
+//! The `bk-crypto` command line tool, which gives admins and CI pipelines direct access to the
+//! keys of the security module.
+//!
+//! ```text
+//! bk-crypto key create release --algorithm ec-p256 > release.pem
+//! bk-crypto sign release --algorithm ec-p256 --context com.example.release/v1 \
+//!     --input artifact.tar --output artifact.sig
+//! bk-crypto verify release --algorithm ec-p256 --context com.example.release/v1 \
+//!     --input artifact.tar --signature artifact.sig
+//! bk-crypto key rotate release --algorithm ec-p256
+//! bk-crypto self-test
+//! ```
+//!
+//! | Command      | Does                                                                   |
+//! |--------------|------------------------------------------------------------------------|
+//! | `key create` | Creates a key and writes its public key as PEM                         |
+//! | `key list`   | Writes the ids of the keys of the security module, one per line        |
+//! | `key delete` | Deletes a key                                                          |
+//! | `key rotate` | Creates the next generation `<key-id>.<n>` and writes its id and PEM   |
+//! | `sign`       | Writes the signature of the input                                      |
+//! | `verify`     | Checks the signature of the input, failing if it is invalid            |
+//! | `encrypt`    | Writes the ciphertext of the input                                     |
+//! | `decrypt`    | Writes the plaintext of the input                                      |
+//! | `attest`     | Writes the attestation certificate chain of a key as PEM               |
+//! | `self-test`  | Creates temporary keys and checks that they sign, verify and encrypt   |
+//!
+//! Keys are named by their id, which is the label of their `KeySpec`, and loaded with the
+//! `--algorithm` given to every command. The input is read from `--input` or stdin and binary
+//! output written to `--output` or stdout, without any encoding. `sign` and `verify` take the
+//! label of a signing context with `--context`, see `crypto::signing_context`, which every new
+//! use of a key should set.
+//!
+//! Keys cannot be rotated in place, so `key rotate` creates a new key and keeps the previous
+//! generations, treating `<key-id>` itself as generation 0. Old generations are removed with
+//! `key delete` once nothing needs them.
+
+use crate::common::{
+    crypto::key_spec::{KeyAlgorithm, KeyPurpose, KeySpec},
+    error::SecurityModuleError,
+    traits::module_provider::Provider,
+    transaction::Transaction,
+};
+use clap::{builder::PossibleValuesParser, builder::TypedValueParser, Arg, ArgAction, ArgMatches};
+use openssl::{pkey::PKey, x509::X509};
+use std::{
+    any::Any,
+    error, fmt, fs,
+    io::{self, Read, Write},
+    path::PathBuf,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+/// The algorithms `self-test` checks unless others are given.
+pub const SELF_TEST_ALGORITHMS: [KeyAlgorithm; 2] = [KeyAlgorithm::EcP256, KeyAlgorithm::Rsa2048];
+
+const ALGORITHMS: [(&str, KeyAlgorithm); 8] = [
+    ("ec-p256", KeyAlgorithm::EcP256),
+    ("ec-p384", KeyAlgorithm::EcP384),
+    ("ec-p521", KeyAlgorithm::EcP521),
+    ("rsa-2048", KeyAlgorithm::Rsa2048),
+    ("rsa-3072", KeyAlgorithm::Rsa3072),
+    ("rsa-4096", KeyAlgorithm::Rsa4096),
+    ("aes-128-gcm", KeyAlgorithm::Aes128Gcm),
+    ("aes-256-gcm", KeyAlgorithm::Aes256Gcm),
+];
+
+const PURPOSES: [(&str, KeyPurpose); 3] = [
+    ("sign", KeyPurpose::Sign),
+    ("encrypt", KeyPurpose::Encrypt),
+    ("key-agreement", KeyPurpose::KeyAgreement),
+];
+
+const SELF_TEST_KEY_ID: &str = "bk-crypto-self-test";
+const SELF_TEST_DATA: &[u8] = b"bk-crypto self-test";
+
+/// An error of a `bk-crypto` command.
+#[derive(Debug)]
+pub enum CliError {
+    /// The security module failed.
+    SecurityModule(SecurityModuleError),
+    /// Reading the input or writing the output failed.
+    Io(io::Error),
+    /// `verify` found the signature to be invalid.
+    InvalidSignature,
+    /// `self-test` found the given number of algorithms not to work.
+    SelfTestFailed(usize),
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CliError::SecurityModule(e) => write!(f, "{}", e),
+            CliError::Io(e) => write!(f, "{}", e),
+            CliError::InvalidSignature => f.write_str("The signature is invalid"),
+            CliError::SelfTestFailed(failed) => {
+                write!(f, "{} algorithms failed the self-test", failed)
+            }
+        }
+    }
+}
+
+impl error::Error for CliError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            CliError::SecurityModule(e) => Some(e),
+            CliError::Io(e) => Some(e),
+            CliError::InvalidSignature | CliError::SelfTestFailed(_) => None,
+        }
+    }
+}
+
+impl From<SecurityModuleError> for CliError {
+    fn from(error: SecurityModuleError) -> Self {
+        CliError::SecurityModule(error)
+    }
+}
+
+impl From<io::Error> for CliError {
+    fn from(error: io::Error) -> Self {
+        CliError::Io(error)
+    }
+}
+
+/// Returns the arguments of `bk-crypto`, see the module documentation.
+///
+/// The arguments are parsed before the security module is initialized, so `--help` and usage
+/// errors work without one.
+pub fn command() -> clap::Command {
+    let key_id = || {
+        Arg::new("key-id")
+            .value_name("KEY_ID")
+            .required(true)
+            .help("The id of the key")
+    };
+    let algorithm = || {
+        Arg::new("algorithm")
+            .long("algorithm")
+            .short('a')
+            .required(true)
+            .value_parser(algorithm_parser())
+            .help("The algorithm of the key")
+    };
+    let purpose = || {
+        Arg::new("purpose")
+            .long("purpose")
+            .short('p')
+            .action(ArgAction::Append)
+            .value_parser(
+                PossibleValuesParser::new(PURPOSES.map(|(name, _)| name))
+                    .map(|name| lookup(&PURPOSES, &name)),
+            )
+            .help("What the key is used for, repeatable [default: sign, encrypt for AES]")
+    };
+    let input = || {
+        Arg::new("input")
+            .long("input")
+            .short('i')
+            .value_parser(clap::value_parser!(PathBuf))
+            .help("The file to read the data from [default: stdin]")
+    };
+    let output = || {
+        Arg::new("output")
+            .long("output")
+            .short('o')
+            .value_parser(clap::value_parser!(PathBuf))
+            .help("The file to write the result to [default: stdout]")
+    };
+    let context = || {
+        Arg::new("context")
+            .long("context")
+            .short('c')
+            .value_name("LABEL")
+            .help("The signing context, e.g. com.example.release/v1 [default: none]")
+    };
+    let data_command = |name: &'static str, about: &'static str| {
+        clap::Command::new(name)
+            .about(about)
+            .arg(key_id())
+            .arg(algorithm())
+            .arg(input())
+            .arg(output())
+    };
+
+    clap::Command::new("bk-crypto")
+        .about("Manages the keys of the security module and uses them")
+        .version(env!("CARGO_PKG_VERSION"))
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .subcommand(
+            clap::Command::new("key")
+                .about("Manages keys")
+                .subcommand_required(true)
+                .subcommand(
+                    clap::Command::new("create")
+                        .about("Creates a key and writes its public key as PEM")
+                        .arg(key_id())
+                        .arg(algorithm())
+                        .arg(purpose())
+                        .arg(
+                            Arg::new("exportable")
+                                .long("exportable")
+                                .action(ArgAction::SetTrue)
+                                .help("Allows the private key to be exported"),
+                        ),
+                )
+                .subcommand(clap::Command::new("list").about("Lists the ids of the keys"))
+                .subcommand(
+                    clap::Command::new("delete")
+                        .about("Deletes a key")
+                        .arg(key_id()),
+                )
+                .subcommand(
+                    clap::Command::new("rotate")
+                        .about("Creates the next generation of a key and writes its id and PEM")
+                        .arg(key_id())
+                        .arg(algorithm())
+                        .arg(purpose()),
+                ),
+        )
+        .subcommand(data_command("sign", "Signs the input").arg(context()))
+        .subcommand(
+            clap::Command::new("verify")
+                .about("Verifies the signature of the input")
+                .arg(key_id())
+                .arg(algorithm())
+                .arg(input())
+                .arg(context())
+                .arg(
+                    Arg::new("signature")
+                        .long("signature")
+                        .short('s')
+                        .required(true)
+                        .value_parser(clap::value_parser!(PathBuf))
+                        .help("The file holding the signature"),
+                ),
+        )
+        .subcommand(data_command("encrypt", "Encrypts the input"))
+        .subcommand(data_command("decrypt", "Decrypts the input"))
+        .subcommand(
+            clap::Command::new("attest")
+                .about("Writes the attestation certificate chain of a key as PEM")
+                .arg(key_id())
+                .arg(algorithm()),
+        )
+        .subcommand(
+            clap::Command::new("self-test")
+                .about("Checks that the security module creates and uses keys")
+                .arg(
+                    Arg::new("algorithm")
+                        .long("algorithm")
+                        .short('a')
+                        .action(ArgAction::Append)
+                        .value_parser(algorithm_parser())
+                        .help("An algorithm to check, repeatable [default: ec-p256, rsa-2048]"),
+                ),
+        )
+}
+
+/// Creates the configuration of a provider for the spec of a key, e.g. `TpmConfig::from_spec`.
+type ConfigFn = fn(&KeySpec) -> Result<Box<dyn Any>, SecurityModuleError>;
+
+/// Runs `bk-crypto` commands against one provider.
+pub struct Cli {
+    provider: Arc<Mutex<dyn Provider>>,
+    config: ConfigFn,
+}
+
+impl Cli {
+    /// Creates the tool for an initialized provider.
+    ///
+    /// # Arguments
+    ///
+    /// * `provider` - The provider holding the keys.
+    /// * `config` - Creates the configuration of the provider for the spec of a key, e.g.
+    ///   `TpmConfig::from_spec`.
+    pub fn new(provider: Arc<Mutex<dyn Provider>>, config: ConfigFn) -> Self {
+        Self { provider, config }
+    }
+
+    /// Runs the command of `matches`, which were parsed by `command`.
+    ///
+    /// # Arguments
+    ///
+    /// * `matches` - The parsed arguments.
+    /// * `input` - Read if the command has no `--input`, usually stdin.
+    /// * `output` - Written if the command has no `--output`, usually stdout.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` that is `Ok(())` if the command succeeded, or the `CliError` it failed with.
+    pub fn run(
+        &self,
+        matches: &ArgMatches,
+        input: &mut dyn Read,
+        output: &mut dyn Write,
+    ) -> Result<(), CliError> {
+        let Some((name, matches)) = matches.subcommand() else {
+            unreachable!("`command` requires a subcommand");
+        };
+        match (name, matches.subcommand()) {
+            ("key", Some(("create", matches))) => {
+                let spec = spec(matches, key_id(matches))?;
+                let mut provider = lock(&self.provider);
+                provider.create_key(spec.label(), (self.config)(&spec)?)?;
+                write_public_key(&*provider, &spec, output)
+            }
+            ("key", Some(("list", _))) => {
+                for key_id in lock(&self.provider).list_keys()? {
+                    writeln!(output, "{}", key_id)?;
+                }
+                Ok(())
+            }
+            ("key", Some(("delete", matches))) => {
+                Ok(lock(&self.provider).delete_key(key_id(matches))?)
+            }
+            ("key", Some(("rotate", matches))) => self.rotate(matches, output),
+            ("sign", None) => {
+                let provider = self.load(matches, KeyPurpose::Sign)?;
+                let data = read_input(matches, input)?;
+                let signature = match matches.get_one::<String>("context") {
+                    Some(label) => provider.sign_in_context(label, &data)?,
+                    None => provider.sign_data(&data)?,
+                };
+                write_output(matches, output, &signature)
+            }
+            ("verify", None) => {
+                let provider = self.load(matches, KeyPurpose::Sign)?;
+                let signature = fs::read(path(matches, "signature").expect("required"))?;
+                let data = read_input(matches, input)?;
+                let valid = match matches.get_one::<String>("context") {
+                    Some(label) => provider.verify_in_context(label, &data, &signature)?,
+                    None => provider.verify_signature(&data, &signature)?,
+                };
+                if valid {
+                    writeln!(output, "The signature is valid")?;
+                    Ok(())
+                } else {
+                    Err(CliError::InvalidSignature)
+                }
+            }
+            ("encrypt", None) => {
+                let provider = self.load(matches, KeyPurpose::Encrypt)?;
+                let ciphertext = provider.encrypt_data(&read_input(matches, input)?)?;
+                write_output(matches, output, &ciphertext)
+            }
+            ("decrypt", None) => {
+                let provider = self.load(matches, KeyPurpose::Encrypt)?;
+                let plaintext = provider.decrypt_data(&read_input(matches, input)?)?;
+                write_output(matches, output, &plaintext)
+            }
+            ("attest", None) => {
+                let provider = self.load(matches, default_purpose(algorithm(matches)))?;
+                let metadata = provider.key_metadata()?;
+                let chain = metadata.attestation().ok_or_else(|| {
+                    SecurityModuleError::UnsupportedOperation(
+                        "The security module does not attest its keys".to_owned(),
+                    )
+                })?;
+                for certificate in chain {
+                    let pem = X509::from_der(certificate)
+                        .and_then(|certificate| certificate.to_pem())
+                        .map_err(|_| SecurityModuleError::InvalidPublicKey)?;
+                    output.write_all(&pem)?;
+                }
+                Ok(())
+            }
+            ("self-test", None) => {
+                let algorithms: Vec<KeyAlgorithm> = match matches.get_many("algorithm") {
+                    Some(algorithms) => algorithms.copied().collect(),
+                    None => SELF_TEST_ALGORITHMS.to_vec(),
+                };
+                let mut failed = 0;
+                for algorithm in algorithms {
+                    let name = ALGORITHMS
+                        .iter()
+                        .find(|(_, value)| *value == algorithm)
+                        .map_or("unknown", |(name, _)| name);
+                    match self.self_test(algorithm) {
+                        Ok(()) => writeln!(output, "{}: ok", name)?,
+                        Err(e) => {
+                            failed += 1;
+                            writeln!(output, "{}: {}", name, e)?;
+                        }
+                    }
+                }
+                match failed {
+                    0 => Ok(()),
+                    failed => Err(CliError::SelfTestFailed(failed)),
+                }
+            }
+            _ => unreachable!("`command` only has these subcommands"),
+        }
+    }
+
+    /// Loads the key of a command that uses it for `purpose`.
+    fn load(
+        &self,
+        matches: &ArgMatches,
+        purpose: KeyPurpose,
+    ) -> Result<MutexGuard<'_, dyn Provider + 'static>, SecurityModuleError> {
+        let spec = KeySpec::builder()
+            .algorithm(algorithm(matches))
+            .usage(purpose)
+            .label(key_id(matches))
+            .build()?;
+        let mut provider = lock(&self.provider);
+        provider.load_key(spec.label(), (self.config)(&spec)?)?;
+        Ok(provider)
+    }
+
+    fn rotate(&self, matches: &ArgMatches, output: &mut dyn Write) -> Result<(), CliError> {
+        let key_id = key_id(matches);
+        let mut provider = lock(&self.provider);
+        let generation = provider
+            .list_keys()?
+            .iter()
+            .filter_map(|id| generation(key_id, id))
+            .max()
+            .map_or(1, |generation| generation + 1);
+        let spec = spec(matches, &format!("{}.{}", key_id, generation))?;
+        // The new generation is deleted again if its public key cannot be written, as nobody
+        // could register it otherwise.
+        let mut transaction = Transaction::begin("rotate key", &mut *provider);
+        transaction.create_key(spec.label(), (self.config)(&spec)?)?;
+        writeln!(output, "{}", spec.label())?;
+        write_public_key(transaction.target(), &spec, output)?;
+        transaction.commit();
+        Ok(())
+    }
+
+    /// Creates a temporary key of `algorithm`, uses it for all its purposes and deletes it.
+    fn self_test(&self, algorithm: KeyAlgorithm) -> Result<(), SecurityModuleError> {
+        let spec = KeySpec::builder()
+            .algorithm(algorithm)
+            .usage(default_purpose(algorithm))
+            .label(SELF_TEST_KEY_ID)
+            .build()?;
+        let mut provider = lock(&self.provider);
+        provider.create_key(SELF_TEST_KEY_ID, (self.config)(&spec)?)?;
+        let result = check(&*provider, &spec);
+        match provider.delete_key(SELF_TEST_KEY_ID) {
+            Ok(()) | Err(SecurityModuleError::UnsupportedOperation(_)) => result,
+            Err(e) => result.and(Err(e)),
+        }
+    }
+}
+
+impl fmt::Debug for Cli {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Cli").finish_non_exhaustive()
+    }
+}
+
+fn check(provider: &dyn Provider, spec: &KeySpec) -> Result<(), SecurityModuleError> {
+    if spec.allows(KeyPurpose::Sign) {
+        let signature = provider.sign_data(SELF_TEST_DATA)?;
+        if !provider.verify_signature(SELF_TEST_DATA, &signature)? {
+            return Err(SecurityModuleError::SignatureVerificationError(
+                "The signature of the self-test was not verified".to_owned(),
+            ));
+        }
+    }
+    if spec.allows(KeyPurpose::Encrypt) {
+        let ciphertext = provider.encrypt_data(SELF_TEST_DATA)?;
+        if provider.decrypt_data(&ciphertext)? != SELF_TEST_DATA {
+            return Err(SecurityModuleError::DecryptionError(
+                "The ciphertext of the self-test was not decrypted to itself".to_owned(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Returns `n` if `id` is the generation `<key_id>.<n>` of `key_id`, and 0 for `key_id` itself.
+fn generation(key_id: &str, id: &str) -> Option<u64> {
+    match id.strip_prefix(key_id)? {
+        "" => Some(0),
+        suffix => suffix
+            .strip_prefix('.')
+            .filter(|n| n.bytes().all(|b| b.is_ascii_digit()))?
+            .parse()
+            .ok(),
+    }
+}
+
+fn spec(matches: &ArgMatches, label: &str) -> Result<KeySpec, SecurityModuleError> {
+    let algorithm = algorithm(matches);
+    let mut builder = KeySpec::builder().algorithm(algorithm).label(label);
+    match matches.get_many::<KeyPurpose>("purpose") {
+        Some(purposes) => {
+            for &purpose in purposes {
+                builder = builder.usage(purpose);
+            }
+        }
+        None => builder = builder.usage(default_purpose(algorithm)),
+    }
+    if matches.try_get_one::<bool>("exportable").ok().flatten() == Some(&true) {
+        builder = builder.exportable(true);
+    }
+    builder.build()
+}
+
+fn write_public_key(
+    provider: &dyn Provider,
+    spec: &KeySpec,
+    output: &mut dyn Write,
+) -> Result<(), CliError> {
+    if spec.algorithm().is_symmetric() {
+        return Ok(());
+    }
+    let metadata = provider.key_metadata()?;
+    let pem = PKey::public_key_from_der(metadata.public_key_der())
+        .and_then(|key| key.public_key_to_pem())
+        .map_err(|_| SecurityModuleError::InvalidPublicKey)?;
+    Ok(output.write_all(&pem)?)
+}
+
+fn read_input(matches: &ArgMatches, input: &mut dyn Read) -> io::Result<Vec<u8>> {
+    match path(matches, "input") {
+        Some(path) => fs::read(path),
+        None => {
+            let mut data = Vec::new();
+            input.read_to_end(&mut data)?;
+            Ok(data)
+        }
+    }
+}
+
+fn write_output(matches: &ArgMatches, output: &mut dyn Write, data: &[u8]) -> Result<(), CliError> {
+    match path(matches, "output") {
+        Some(path) => fs::write(path, data)?,
+        None => output.write_all(data)?,
+    }
+    Ok(())
+}
+
+fn default_purpose(algorithm: KeyAlgorithm) -> KeyPurpose {
+    if algorithm.is_symmetric() {
+        KeyPurpose::Encrypt
+    } else {
+        KeyPurpose::Sign
+    }
+}
+
+fn algorithm_parser() -> impl TypedValueParser<Value = KeyAlgorithm> {
+    PossibleValuesParser::new(ALGORITHMS.map(|(name, _)| name))
+        .map(|name| lookup(&ALGORITHMS, &name))
+}
+
+fn lookup<T: Copy>(table: &[(&str, T)], name: &str) -> T {
+    table
+        .iter()
+        .find(|(entry, _)| *entry == name)
+        .map(|(_, value)| *value)
+        .expect("the parser only accepts names of the table")
+}
+
+fn key_id(matches: &ArgMatches) -> &str {
+    matches.get_one::<String>("key-id").expect("required")
+}
+
+fn algorithm(matches: &ArgMatches) -> KeyAlgorithm {
+    *matches.get_one("algorithm").expect("required")
+}
+
+fn path<'a>(matches: &'a ArgMatches, name: &str) -> Option<&'a PathBuf> {
+    matches.get_one(name)
+}
+
+fn lock<T: ?Sized>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
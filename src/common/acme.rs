@@ -0,0 +1,263 @@
+//! Helpers for obtaining certificates with ACME (RFC 8555) using keys of the security module.
+//!
+//! The account key signs the JWS of every ACME request, and the certificate key signs the CSR
+//! that finalizes an order, so neither has to be exportable. The HTTP client and the polling of
+//! the order are left to the application:
+//!
+//! ```rust,ignore
+//! use crypto_layer::common::acme::{self, Identifier, KeyReference};
+//!
+//! // The first request is signed with the JWK, all others with the account URL.
+//! let payload = json!({ "termsOfServiceAgreed": true });
+//! let jws = acme::sign_request(&account_key, KeyReference::Jwk, new_account_url, &nonce, Some(&payload))?;
+//! let response = client.post(new_account_url).header("Content-Type", "application/jose+json").body(jws.to_json()).send()?;
+//! let account_url = response.headers()["Location"].to_str()?;
+//!
+//! // Answer the http-01 challenge with the key authorization.
+//! let key_authorization = acme::key_authorization(&challenge.token, &account_public_key)?;
+//!
+//! // Finalize the order with a CSR signed by the certificate key.
+//! let identifiers = [Identifier::Dns("device-42.example.com".to_owned())];
+//! let csr = acme::create_csr(&certificate_key, &identifiers)?;
+//! let payload = acme::finalize_payload(&csr);
+//! let jws = acme::sign_request(&account_key, KeyReference::Account(account_url), finalize_url, &nonce, Some(&payload))?;
+//! ```
+//!
+//! The JWS algorithm follows from the account key as for capability tokens, e.g. `ES256` for
+//! P-256 keys with SHA-256, which every ACME server supports. CSRs are created by `csr`, which
+//! lists the keys that can sign them.
+
+use crate::common::{
+    capability_token::jws_algorithm,
+    crypto::{jwk, public_key::PublicKey, signature_format},
+    csr::{self, CsrSpec, MAX_COMMON_NAME_LEN},
+    error::SecurityModuleError,
+    traits::module_provider::Provider,
+};
+use base64::{prelude::BASE64_URL_SAFE_NO_PAD, Engine};
+use openssl::sha::sha256;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::net::IpAddr;
+
+/// How the account key is identified in the protected header of a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyReference<'a> {
+    /// The public key is embedded as JWK, required for `newAccount` and for revoking a
+    /// certificate with its own key.
+    Jwk,
+    /// The account is referenced by its URL, returned in the `Location` of `newAccount`.
+    Account(&'a str),
+}
+
+/// An ACME request in the flattened JSON serialization of JWS.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AcmeJws {
+    /// The protected header, base64url encoded.
+    pub protected: String,
+    /// The payload, base64url encoded, or empty for a POST-as-GET request.
+    pub payload: String,
+    /// The signature, base64url encoded.
+    pub signature: String,
+}
+
+impl AcmeJws {
+    /// Returns the request body, sent with the content type `application/jose+json`.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("requests are always serializable")
+    }
+}
+
+/// An identifier of an order, i.e. a name the certificate is issued for.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "lowercase")]
+pub enum Identifier {
+    /// A DNS name, e.g. `device-42.example.com`.
+    Dns(String),
+    /// An IP address, see RFC 8738.
+    Ip(IpAddr),
+}
+
+/// Returns `public_key` as JWK with the members required by RFC 7638.
+///
+/// # Returns
+///
+/// A `Result` containing the JWK, or a `SecurityModuleError::UnsupportedAlgorithm` if the key
+/// is neither an ECDSA key on P-256, P-384 or P-521, an RSA key nor an Ed25519 key.
+pub fn jwk(public_key: &PublicKey) -> Result<Value, SecurityModuleError> {
+    let members = jwk::required_members(public_key)?
+        .into_iter()
+        .map(|(name, value)| (name.to_owned(), Value::String(value)))
+        .collect::<Map<_, _>>();
+    Ok(Value::Object(members))
+}
+
+/// Returns the JWK thumbprint of `public_key` (RFC 7638), base64url encoded.
+///
+/// # Returns
+///
+/// A `Result` containing the thumbprint, or a `SecurityModuleError::UnsupportedAlgorithm` if
+/// the key has no JWK, see `jwk`.
+pub fn jwk_thumbprint(public_key: &PublicKey) -> Result<String, SecurityModuleError> {
+    // The thumbprint hashes the required members in lexicographic order without whitespace.
+    let members = jwk::required_members(public_key)?
+        .into_iter()
+        .map(|(name, value)| format!("\"{}\":{}", name, Value::String(value)))
+        .collect::<Vec<_>>();
+    let canonical = format!("{{{}}}", members.join(","));
+    Ok(BASE64_URL_SAFE_NO_PAD.encode(sha256(canonical.as_bytes())))
+}
+
+/// Returns the key authorization of a challenge `token` for the account key `public_key`.
+///
+/// The key authorization is served as is for `http-01` challenges. For `dns-01` challenges the
+/// TXT record contains `dns01_txt_value` of it.
+///
+/// # Returns
+///
+/// A `Result` containing the key authorization, or a
+/// `SecurityModuleError::UnsupportedAlgorithm` if the key has no JWK, see `jwk`.
+pub fn key_authorization(
+    token: &str,
+    public_key: &PublicKey,
+) -> Result<String, SecurityModuleError> {
+    Ok(format!("{}.{}", token, jwk_thumbprint(public_key)?))
+}
+
+/// Returns the value of the `_acme-challenge` TXT record for a `dns-01` challenge.
+pub fn dns01_txt_value(key_authorization: &str) -> String {
+    BASE64_URL_SAFE_NO_PAD.encode(sha256(key_authorization.as_bytes()))
+}
+
+/// Signs an ACME request with the loaded account key of `provider`.
+///
+/// # Arguments
+///
+/// * `provider` - The provider with the loaded account key.
+/// * `key` - How the account key is identified in the protected header.
+/// * `url` - The URL the request is sent to.
+/// * `nonce` - The `Replay-Nonce` of the previous response or of `newNonce`.
+/// * `payload` - The payload, or `None` for a POST-as-GET request.
+///
+/// # Returns
+///
+/// A `Result` containing the `AcmeJws`, a `SecurityModuleError::UnsupportedAlgorithm` if the
+/// key has no JWS algorithm, or the error of `provider`.
+#[tracing::instrument(skip(provider, nonce, payload))]
+pub fn sign_request(
+    provider: &(impl Provider + ?Sized),
+    key: KeyReference<'_>,
+    url: &str,
+    nonce: &str,
+    payload: Option<&Value>,
+) -> Result<AcmeJws, SecurityModuleError> {
+    let metadata = provider.key_metadata()?;
+    let public_key = metadata.public_key();
+    let mut protected = Map::new();
+    protected.insert("alg".to_owned(), jws_algorithm(public_key)?.name.into());
+    match key {
+        KeyReference::Jwk => protected.insert("jwk".to_owned(), jwk(public_key)?),
+        KeyReference::Account(account_url) => {
+            protected.insert("kid".to_owned(), account_url.into())
+        }
+    };
+    protected.insert("nonce".to_owned(), nonce.into());
+    protected.insert("url".to_owned(), url.into());
+    sign_jws(provider, public_key, &protected, payload)
+}
+
+/// Signs the inner JWS of an account key rollover with the loaded new account key of
+/// `provider`, see section 7.3.5 of RFC 8555.
+///
+/// The result is the payload of a request to the `keyChange` URL signed with the old key by
+/// `sign_request`.
+///
+/// # Returns
+///
+/// A `Result` containing the payload, a `SecurityModuleError::UnsupportedAlgorithm` if one of
+/// the keys has no JWK or the new key has no JWS algorithm, or the error of `provider`.
+#[tracing::instrument(skip(provider, old_public_key))]
+pub fn sign_key_change(
+    provider: &(impl Provider + ?Sized),
+    key_change_url: &str,
+    account_url: &str,
+    old_public_key: &PublicKey,
+) -> Result<Value, SecurityModuleError> {
+    let metadata = provider.key_metadata()?;
+    let public_key = metadata.public_key();
+    let mut protected = Map::new();
+    protected.insert("alg".to_owned(), jws_algorithm(public_key)?.name.into());
+    protected.insert("jwk".to_owned(), jwk(public_key)?);
+    protected.insert("url".to_owned(), key_change_url.into());
+    let payload = json!({ "account": account_url, "oldKey": jwk(old_public_key)? });
+    let jws = sign_jws(provider, public_key, &protected, Some(&payload))?;
+    Ok(serde_json::to_value(jws).expect("requests are always serializable"))
+}
+
+/// Creates a DER encoded PKCS#10 certificate signing request for `identifiers` with the loaded
+/// key of `provider`, e.g. the key the certificate is issued for.
+///
+/// The identifiers are requested as subject alternative names. The first DNS name is also the
+/// common name of the subject if it is short enough, otherwise the subject is empty.
+///
+/// # Returns
+///
+/// A `Result` containing the CSR, a `SecurityModuleError::SigningError` if `identifiers` is
+/// empty or a DNS name is not ASCII, a `SecurityModuleError::UnsupportedAlgorithm` if the key
+/// cannot sign certificates, or the error of `provider`.
+#[tracing::instrument(skip(provider))]
+pub fn create_csr(
+    provider: &(impl Provider + ?Sized),
+    identifiers: &[Identifier],
+) -> Result<Vec<u8>, SecurityModuleError> {
+    if identifiers.is_empty() {
+        return Err(SecurityModuleError::SigningError(
+            "At least one identifier is required".to_owned(),
+        ));
+    }
+    let mut spec = CsrSpec::new();
+    if let Some(name) = identifiers.iter().find_map(|identifier| match identifier {
+        Identifier::Dns(name) if name.len() <= MAX_COMMON_NAME_LEN => Some(name),
+        _ => None,
+    }) {
+        spec = spec.common_name(name.clone());
+    }
+    for identifier in identifiers {
+        spec = match identifier {
+            Identifier::Dns(name) => spec.dns_name(name.clone()),
+            Identifier::Ip(address) => spec.ip_address(*address),
+        };
+    }
+    csr::create_csr(provider, &spec)
+}
+
+/// Returns the payload of a request to the `finalize` URL of an order for the DER encoded `csr`.
+pub fn finalize_payload(csr: &[u8]) -> Value {
+    json!({ "csr": BASE64_URL_SAFE_NO_PAD.encode(csr) })
+}
+
+fn sign_jws(
+    provider: &(impl Provider + ?Sized),
+    public_key: &PublicKey,
+    protected: &Map<String, Value>,
+    payload: Option<&Value>,
+) -> Result<AcmeJws, SecurityModuleError> {
+    let algorithm = jws_algorithm(public_key)?;
+    let protected = BASE64_URL_SAFE_NO_PAD
+        .encode(serde_json::to_vec(protected).expect("headers are always serializable"));
+    // A POST-as-GET request has an empty payload, not an encoded empty string.
+    let payload = payload.map_or_else(String::new, |payload| {
+        BASE64_URL_SAFE_NO_PAD
+            .encode(serde_json::to_vec(payload).expect("payloads are always serializable"))
+    });
+    let signature = provider.sign_data(format!("{}.{}", protected, payload).as_bytes())?;
+    let signature = match algorithm.scalar_len {
+        Some(scalar_len) => signature_format::der_to_raw(&signature, scalar_len)?,
+        None => signature,
+    };
+    Ok(AcmeJws {
+        protected,
+        payload,
+        signature: BASE64_URL_SAFE_NO_PAD.encode(signature),
+    })
+}
@@ -0,0 +1,470 @@
+//! Detection of suspicious usage patterns.
+//!
+//! `AnomalyDetector` watches the outcomes of operations and calls the registered hooks when a
+//! pattern that may indicate an attack occurs within a sliding time window:
+//!
+//! * a burst of signatures that do not verify, e.g. forgery attempts,
+//! * repeated authentication failures, e.g. cancelled Touch ID prompts or wrong PINs,
+//! * decryption failures on many different ciphertexts, e.g. padding oracle or chosen ciphertext
+//!   attacks.
+//!
+//! Host applications forward the anomalies to their intrusion detection pipeline.
+//! `SecModules::get_instance` wraps every instance it creates in a `MonitoredProvider`, which
+//! reports to `AnomalyDetector::global`.
+
+use crate::common::{
+    crypto::{
+        key_metadata::KeyMetadata, key_spec::KeySpec, operation_context::OperationContext,
+        secret::SecretBytes,
+    },
+    error::SecurityModuleError,
+    latency::LatencySnapshot,
+    session_pool::SessionPoolMetrics,
+    traits::{key_handle::KeyHandle, module_provider::Provider},
+};
+use once_cell::sync::Lazy;
+use std::{
+    any::Any,
+    collections::{HashMap, HashSet, VecDeque},
+    fmt,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+    time::{Duration, Instant, SystemTime},
+};
+
+static GLOBAL: Lazy<Arc<AnomalyDetector>> =
+    Lazy::new(|| Arc::new(AnomalyDetector::new(AnomalyConfig::default())));
+
+/// A suspicious pattern.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum AnomalyKind {
+    /// Many signatures that do not verify.
+    FailedVerifications,
+    /// Many operations refused because the user or application could not be authenticated.
+    AuthFailures,
+    /// Decryption failed for many different ciphertexts.
+    DecryptFailures,
+}
+
+/// A detected anomaly, passed to the hooks of an `AnomalyDetector`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Anomaly {
+    pub kind: AnomalyKind,
+    /// The identifier of the key the operations were performed with.
+    pub key_id: String,
+    /// The number of occurrences within `window`, which reached the threshold of the kind.
+    pub count: usize,
+    pub window: Duration,
+    pub detected_at: SystemTime,
+}
+
+/// The thresholds of an `AnomalyDetector`.
+///
+/// An anomaly is reported when a threshold is reached within `window`. Afterwards the
+/// occurrences are forgotten, so that a burst is reported once and not for every further
+/// occurrence. A threshold of `0` disables the detection of the kind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AnomalyConfig {
+    pub window: Duration,
+    /// The number of signatures that do not verify.
+    pub failed_verifications: usize,
+    /// The number of authentication failures.
+    pub auth_failures: usize,
+    /// The number of distinct ciphertexts that fail to decrypt.
+    pub decrypt_failures: usize,
+}
+
+impl Default for AnomalyConfig {
+    fn default() -> Self {
+        Self {
+            window: Duration::from_secs(60),
+            failed_verifications: 20,
+            auth_failures: 5,
+            decrypt_failures: 10,
+        }
+    }
+}
+
+impl AnomalyConfig {
+    fn threshold(&self, kind: AnomalyKind) -> usize {
+        match kind {
+            AnomalyKind::FailedVerifications => self.failed_verifications,
+            AnomalyKind::AuthFailures => self.auth_failures,
+            AnomalyKind::DecryptFailures => self.decrypt_failures,
+        }
+    }
+}
+
+/// Identifies a hook registered with `AnomalyDetector::add_hook`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HookId(u64);
+
+type Hook = Arc<dyn Fn(&Anomaly) + Send + Sync>;
+
+/// The occurrences of a kind with a key within the window. Decryption failures are identified
+/// by the hash of the ciphertext, so that retries of the same ciphertext count once.
+#[derive(Default)]
+struct Occurrences(VecDeque<(Instant, Option<[u8; 32]>)>);
+
+impl Occurrences {
+    fn count(&self) -> usize {
+        let distinct: HashSet<_> = self.0.iter().filter_map(|(_, hash)| *hash).collect();
+        self.0.len() - self.0.iter().filter(|(_, hash)| hash.is_some()).count() + distinct.len()
+    }
+}
+
+/// Reports suspicious patterns in the outcomes of operations to hooks.
+pub struct AnomalyDetector {
+    config: Mutex<AnomalyConfig>,
+    occurrences: Mutex<HashMap<(String, AnomalyKind), Occurrences>>,
+    hooks: Mutex<Vec<(HookId, Hook)>>,
+    next_id: AtomicU64,
+}
+
+fn lock<T: ?Sized>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+impl AnomalyDetector {
+    /// Creates a detector without hooks.
+    pub fn new(config: AnomalyConfig) -> Self {
+        Self {
+            config: Mutex::new(config),
+            occurrences: Mutex::new(HashMap::new()),
+            hooks: Mutex::new(Vec::new()),
+            next_id: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns the detector the instances created by `SecModules::get_instance` report to.
+    pub fn global() -> Arc<AnomalyDetector> {
+        GLOBAL.clone()
+    }
+
+    /// Returns the thresholds of the detector.
+    pub fn config(&self) -> AnomalyConfig {
+        *lock(&self.config)
+    }
+
+    /// Replaces the thresholds of the detector. Occurrences recorded so far are kept.
+    pub fn set_config(&self, config: AnomalyConfig) {
+        *lock(&self.config) = config;
+    }
+
+    /// Registers a hook that is called with every anomaly detected afterwards.
+    ///
+    /// The hook is called on the thread that performed the operation completing the pattern,
+    /// while the operation waits, so it should return quickly.
+    pub fn add_hook(&self, hook: impl Fn(&Anomaly) + Send + Sync + 'static) -> HookId {
+        let id = HookId(self.next_id.fetch_add(1, Ordering::Relaxed));
+        lock(&self.hooks).push((id, Arc::new(hook)));
+        id
+    }
+
+    /// Removes a hook registered with `add_hook`.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the hook was registered, `false` if it was already removed.
+    pub fn remove_hook(&self, id: HookId) -> bool {
+        let mut hooks = lock(&self.hooks);
+        let count = hooks.len();
+        hooks.retain(|(hook, _)| *hook != id);
+        hooks.len() != count
+    }
+
+    /// Records the outcome of a signature verification.
+    pub fn record_verification(&self, key_id: &str, valid: bool) {
+        if !valid {
+            self.record(key_id, AnomalyKind::FailedVerifications, None);
+        }
+    }
+
+    /// Records an operation refused because of a failed authentication.
+    pub fn record_auth_failure(&self, key_id: &str) {
+        self.record(key_id, AnomalyKind::AuthFailures, None);
+    }
+
+    /// Records a ciphertext that failed to decrypt.
+    pub fn record_decrypt_failure(&self, key_id: &str, ciphertext: &[u8]) {
+        self.record(
+            key_id,
+            AnomalyKind::DecryptFailures,
+            Some(openssl::sha::sha256(ciphertext)),
+        );
+    }
+
+    fn record(&self, key_id: &str, kind: AnomalyKind, hash: Option<[u8; 32]>) {
+        let config = self.config();
+        let threshold = config.threshold(kind);
+        if threshold == 0 {
+            return;
+        }
+
+        let now = Instant::now();
+        let count = {
+            let mut occurrences = lock(&self.occurrences);
+            let window = occurrences.entry((key_id.to_owned(), kind)).or_default();
+            while window
+                .0
+                .front()
+                .is_some_and(|(time, _)| now.duration_since(*time) > config.window)
+            {
+                window.0.pop_front();
+            }
+            window.0.push_back((now, hash));
+            let count = window.count();
+            if count < threshold {
+                return;
+            }
+            window.0.clear();
+            count
+        };
+
+        let anomaly = Anomaly {
+            kind,
+            key_id: key_id.to_owned(),
+            count,
+            window: config.window,
+            detected_at: SystemTime::now(),
+        };
+        tracing::warn!(?kind, count, "Security anomaly detected");
+        let hooks: Vec<Hook> = lock(&self.hooks)
+            .iter()
+            .map(|(_, hook)| hook.clone())
+            .collect();
+        for hook in hooks {
+            hook(&anomaly);
+        }
+    }
+}
+
+impl fmt::Debug for AnomalyDetector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AnomalyDetector")
+            .field("config", &self.config())
+            .field("hooks", &lock(&self.hooks).len())
+            .finish_non_exhaustive()
+    }
+}
+
+/// A provider that reports the outcomes of calls to the wrapped provider to an
+/// `AnomalyDetector`.
+pub struct MonitoredProvider {
+    inner: Arc<Mutex<dyn Provider>>,
+    key_id: Mutex<String>,
+    detector: Arc<AnomalyDetector>,
+}
+
+impl MonitoredProvider {
+    /// Wraps a provider.
+    ///
+    /// # Arguments
+    ///
+    /// * `inner` - The provider whose calls are monitored.
+    /// * `key_id` - The id of the key the provider operates on, until `create_key` or
+    ///   `load_key` is called with another one.
+    /// * `detector` - The detector the outcomes are reported to.
+    pub fn new(
+        inner: Arc<Mutex<dyn Provider>>,
+        key_id: String,
+        detector: Arc<AnomalyDetector>,
+    ) -> Self {
+        Self {
+            inner,
+            key_id: Mutex::new(key_id),
+            detector,
+        }
+    }
+
+    fn inner(&self) -> MutexGuard<'_, dyn Provider + 'static> {
+        lock(&self.inner)
+    }
+
+    fn key_id(&self) -> String {
+        lock(&self.key_id).clone()
+    }
+
+    /// Reports authentication failures returned by `f`.
+    fn monitor<T>(
+        &self,
+        f: impl FnOnce() -> Result<T, SecurityModuleError>,
+    ) -> Result<T, SecurityModuleError> {
+        self.monitor_key(&self.key_id(), f)
+    }
+
+    /// Like `monitor`, but reports the failures for the key `key_id`, which need not be the
+    /// current key.
+    fn monitor_key<T>(
+        &self,
+        key_id: &str,
+        f: impl FnOnce() -> Result<T, SecurityModuleError>,
+    ) -> Result<T, SecurityModuleError> {
+        let result = f();
+        if let Err(error) = &result {
+            if error.is_authentication_failure() {
+                self.detector.record_auth_failure(key_id);
+            }
+        }
+        result
+    }
+
+    /// Runs `f`, which creates or loads the key `key_id`, and makes it the current key if `f`
+    /// succeeds. A failed call leaves the previous key loaded, so later outcomes are still
+    /// reported for it.
+    fn switch_key(
+        &self,
+        key_id: &str,
+        f: impl FnOnce() -> Result<(), SecurityModuleError>,
+    ) -> Result<(), SecurityModuleError> {
+        self.monitor_key(key_id, f)?;
+        *lock(&self.key_id) = key_id.to_owned();
+        Ok(())
+    }
+
+    fn monitor_verification(
+        &self,
+        f: impl FnOnce() -> Result<bool, SecurityModuleError>,
+    ) -> Result<bool, SecurityModuleError> {
+        let result = self.monitor(f);
+        if let Ok(valid) = result {
+            self.detector.record_verification(&self.key_id(), valid);
+        }
+        result
+    }
+}
+
+impl fmt::Debug for MonitoredProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MonitoredProvider")
+            .field("detector", &self.detector)
+            .finish_non_exhaustive()
+    }
+}
+
+impl KeyHandle for MonitoredProvider {
+    fn sign_data(&self, data: &[u8]) -> Result<Vec<u8>, SecurityModuleError> {
+        self.monitor(|| self.inner().sign_data(data))
+    }
+
+    fn decrypt_data(&self, encrypted_data: &[u8]) -> Result<SecretBytes, SecurityModuleError> {
+        let result = self.monitor(|| self.inner().decrypt_data(encrypted_data));
+        if matches!(&result, Err(error) if !error.is_authentication_failure()) {
+            self.detector
+                .record_decrypt_failure(&self.key_id(), encrypted_data);
+        }
+        result
+    }
+
+    fn encrypt_data(&self, data: &[u8]) -> Result<Vec<u8>, SecurityModuleError> {
+        self.monitor(|| self.inner().encrypt_data(data))
+    }
+
+    fn verify_signature(&self, data: &[u8], signature: &[u8]) -> Result<bool, SecurityModuleError> {
+        self.monitor_verification(|| self.inner().verify_signature(data, signature))
+    }
+
+    fn sign_data_into<'a>(
+        &self,
+        data: &[u8],
+        context: &'a mut OperationContext,
+    ) -> Result<&'a [u8], SecurityModuleError> {
+        self.monitor(|| self.inner().sign_data_into(data, context))
+    }
+
+    fn verify_signature_with(
+        &self,
+        data: &[u8],
+        signature: &[u8],
+        context: &mut OperationContext,
+    ) -> Result<bool, SecurityModuleError> {
+        self.monitor_verification(|| self.inner().verify_signature_with(data, signature, context))
+    }
+
+    fn verify_many(&self, items: &[(&[u8], &[u8])]) -> Result<Vec<bool>, SecurityModuleError> {
+        let result = self.monitor(|| self.inner().verify_many(items));
+        if let Ok(results) = &result {
+            let key_id = self.key_id();
+            for valid in results {
+                self.detector.record_verification(&key_id, *valid);
+            }
+        }
+        result
+    }
+
+    fn derive_shared_secret(
+        &self,
+        peer_public_key: &[u8],
+    ) -> Result<SecretBytes, SecurityModuleError> {
+        self.monitor(|| self.inner().derive_shared_secret(peer_public_key))
+    }
+}
+
+impl Provider for MonitoredProvider {
+    fn create_key(
+        &mut self,
+        key_id: &str,
+        config: Box<dyn Any>,
+    ) -> Result<(), SecurityModuleError> {
+        self.switch_key(key_id, || self.inner().create_key(key_id, config))
+    }
+
+    fn load_key(&mut self, key_id: &str, config: Box<dyn Any>) -> Result<(), SecurityModuleError> {
+        self.switch_key(key_id, || self.inner().load_key(key_id, config))
+    }
+
+    fn import_wrapped_key(
+        &mut self,
+        key_id: &str,
+        wrapped_key: &[u8],
+        wrapping_key_id: &str,
+        spec: &KeySpec,
+    ) -> Result<(), SecurityModuleError> {
+        self.switch_key(key_id, || {
+            self.inner()
+                .import_wrapped_key(key_id, wrapped_key, wrapping_key_id, spec)
+        })
+    }
+
+    fn export_private_key(&self) -> Result<SecretBytes, SecurityModuleError> {
+        self.monitor(|| self.inner().export_private_key())
+    }
+
+    fn initialize_module(&mut self) -> Result<(), SecurityModuleError> {
+        self.monitor(|| self.inner().initialize_module())
+    }
+
+    fn session_pool_metrics(&self) -> Option<SessionPoolMetrics> {
+        self.inner().session_pool_metrics()
+    }
+
+    fn latency_snapshot(&self) -> Option<LatencySnapshot> {
+        self.inner().latency_snapshot()
+    }
+
+    fn key_metadata(&self) -> Result<KeyMetadata, SecurityModuleError> {
+        self.inner().key_metadata()
+    }
+
+    fn refresh_key_metadata(&mut self) -> Result<KeyMetadata, SecurityModuleError> {
+        self.inner().refresh_key_metadata()
+    }
+
+    fn list_keys(&self) -> Result<Vec<String>, SecurityModuleError> {
+        self.inner().list_keys()
+    }
+
+    fn delete_key(&mut self, key_id: &str) -> Result<(), SecurityModuleError> {
+        self.inner().delete_key(key_id)
+    }
+
+    fn shutdown(&mut self) -> Result<(), SecurityModuleError> {
+        self.inner().shutdown()
+    }
+}
@@ -0,0 +1,309 @@
+//! Sealed data bound to a monotonic counter, so that rolled-back copies are rejected.
+//!
+//! Encrypting a license or a configuration under a device-bound key protects its
+//! confidentiality and integrity, but not its freshness: whoever can write the storage can put
+//! back an older ciphertext, e.g. a license before it was revoked. A `RollbackGuard` seals data
+//! together with the next value of a `MonotonicCounter`, and only opens data sealed with the
+//! current value:
+//!
+//! ```rust,ignore
+//! use crypto_layer::common::anti_rollback::{FileCounter, RollbackGuard};
+//!
+//! let counter = FileCounter::new("/var/lib/app/license.counter");
+//! let guard = RollbackGuard::new(provider, "license_key", counter);
+//! let sealed = guard.seal("license", b"valid until 2027-01-01")?;
+//! let license = guard.open("license", &sealed)?;
+//! ```
+//!
+//! The protection is as strong as the counter. `TpmNvCounter` of the Linux TPM keeps the counter
+//! in an NV index of the TPM, which cannot be decremented. `FileCounter` keeps it in a file for
+//! platforms without one, which only helps if the file is harder to roll back than the sealed
+//! data, e.g. because it lives on another volume or device.
+//!
+//! Sealing increments the counter before the data is encrypted, so every copy sealed before is
+//! rejected from then on, even if the new copy is never stored. The data is encrypted like a
+//! secret of `vault`: a fresh data key is encrypted with `encrypt_data` of the provider and kept
+//! in the envelope, and the payload key is derived from it with HKDF, using the name of the data
+//! as info. The plaintext is the big-endian u64 counter value followed by the data.
+
+use crate::common::{
+    crypto::{
+        aead,
+        envelope::{AeadAlgorithm, Envelope},
+        kdf::Kdf,
+        secret::SecretBytes,
+    },
+    error::SecurityModuleError,
+    input_limits,
+    traits::module_provider::Provider,
+    uniform_errors::{self, FailureTiming},
+};
+use openssl::rand::rand_bytes;
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+};
+
+/// The algorithm sealed data is encrypted with.
+const AEAD: AeadAlgorithm = AeadAlgorithm::Aes256Gcm;
+
+/// The length of the random salt of the payload key derivation in bytes.
+const SALT_LEN: usize = 16;
+
+/// Prefixes the name of the data in the info of the payload key derivation.
+const KDF_INFO_PREFIX: &[u8] = b"crypto-layer/anti-rollback/";
+
+/// The length of the counter value at the start of the plaintext in bytes.
+const VERSION_LEN: usize = 8;
+
+/// A counter that can only be incremented.
+pub trait MonotonicCounter: Send + Sync {
+    /// Returns the current value.
+    fn read(&self) -> Result<u64, SecurityModuleError>;
+
+    /// Increments the value and returns the new value.
+    fn increment(&self) -> Result<u64, SecurityModuleError>;
+}
+
+/// A `MonotonicCounter` in memory, e.g. for tests or data that only lives as long as the
+/// process.
+#[derive(Debug, Default)]
+pub struct MemoryCounter {
+    value: AtomicU64,
+}
+
+impl MemoryCounter {
+    /// Creates a counter starting at `value`.
+    pub fn new(value: u64) -> Self {
+        Self {
+            value: AtomicU64::new(value),
+        }
+    }
+}
+
+impl MonotonicCounter for MemoryCounter {
+    fn read(&self) -> Result<u64, SecurityModuleError> {
+        Ok(self.value.load(Ordering::SeqCst))
+    }
+
+    fn increment(&self) -> Result<u64, SecurityModuleError> {
+        let previous = self
+            .value
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |value| {
+                value.checked_add(1)
+            })
+            .map_err(|_| counter_exhausted())?;
+        Ok(previous + 1)
+    }
+}
+
+/// A `MonotonicCounter` kept as a decimal number in a file.
+///
+/// A missing file is the value 0. The new value is written to a temporary file first and then
+/// renamed, so a crash never leaves a partially written counter behind. Increments are
+/// serialized within the process, but not across processes sharing the file.
+#[derive(Debug)]
+pub struct FileCounter {
+    path: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl FileCounter {
+    /// Creates a counter kept in the file at `path`. The file is created on the first increment.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// Returns the path of the file holding the counter.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl MonotonicCounter for FileCounter {
+    fn read(&self) -> Result<u64, SecurityModuleError> {
+        let content = match fs::read_to_string(&self.path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(counter_error("read", &self.path, e)),
+        };
+        content.trim().parse().map_err(|_| {
+            SecurityModuleError::SecretStorage(format!(
+                "The counter '{}' does not hold a number",
+                self.path.display()
+            ))
+        })
+    }
+
+    fn increment(&self) -> Result<u64, SecurityModuleError> {
+        let _lock = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        let value = self.read()?.checked_add(1).ok_or_else(counter_exhausted)?;
+        let temporary = self.path.with_extension("tmp");
+        fs::write(&temporary, value.to_string())
+            .map_err(|e| counter_error("write", &temporary, e))?;
+        fs::rename(&temporary, &self.path).map_err(|e| counter_error("write", &self.path, e))?;
+        Ok(value)
+    }
+}
+
+fn counter_error(action: &str, path: &Path, e: io::Error) -> SecurityModuleError {
+    SecurityModuleError::SecretStorage(format!(
+        "Cannot {} the counter '{}': {}",
+        action,
+        path.display(),
+        e
+    ))
+}
+
+fn counter_exhausted() -> SecurityModuleError {
+    SecurityModuleError::SecretStorage("The counter cannot be incremented anymore".to_owned())
+}
+
+/// Seals data under a key of the security module, bound to the value of a `MonotonicCounter`.
+pub struct RollbackGuard {
+    provider: Arc<Mutex<dyn Provider>>,
+    key_id: String,
+    counter: Box<dyn MonotonicCounter>,
+    decryption_timing: FailureTiming,
+}
+
+impl RollbackGuard {
+    /// Creates a guard.
+    ///
+    /// # Arguments
+    ///
+    /// * `provider` - The provider encrypting and decrypting the data keys. The key `key_id` must
+    ///   be created or loaded and support `encrypt_data` and `decrypt_data`.
+    /// * `key_id` - The id of the key, which is recorded in every envelope.
+    /// * `counter` - The counter the sealed data is bound to. It must not be shared with other
+    ///   guards, as every seal invalidates the data sealed before.
+    pub fn new(
+        provider: Arc<Mutex<dyn Provider>>,
+        key_id: impl Into<String>,
+        counter: impl MonotonicCounter + 'static,
+    ) -> Self {
+        Self {
+            provider,
+            key_id: key_id.into(),
+            counter: Box::new(counter),
+            decryption_timing: FailureTiming::new(),
+        }
+    }
+
+    /// Returns the current value of the counter, which `open` expects in the sealed data.
+    pub fn version(&self) -> Result<u64, SecurityModuleError> {
+        self.counter.read()
+    }
+
+    /// Increments the counter and seals `data` under `name` with its new value.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the encoded envelope on success, a
+    /// `SecurityModuleError::SecretStorage` if the counter cannot be incremented, or the error of
+    /// the provider if the data key cannot be encrypted.
+    #[tracing::instrument(skip(self, data), fields(crypto.payload.size = data.len()))]
+    pub fn seal(&self, name: &str, data: &[u8]) -> Result<Vec<u8>, SecurityModuleError> {
+        let version = self.counter.increment()?;
+
+        let mut data_key = SecretBytes::zeroed(AEAD.key_len());
+        rand_bytes(&mut data_key)
+            .map_err(|e| SecurityModuleError::EncryptionError(e.to_string()))?;
+        let mut salt = vec![0; SALT_LEN];
+        rand_bytes(&mut salt).map_err(|e| SecurityModuleError::EncryptionError(e.to_string()))?;
+        let wrapped_key = self.provider().encrypt_data(&data_key)?;
+        let payload_key =
+            Kdf::HkdfSha256.derive_vec(&data_key, Some(&salt), &kdf_info(name), AEAD.key_len())?;
+
+        let mut plaintext = SecretBytes::with_capacity(VERSION_LEN + data.len());
+        plaintext.extend_from_slice(&version.to_be_bytes());
+        plaintext.extend_from_slice(data);
+        let mut envelope = Envelope::new(AEAD, self.key_id.as_str(), aead::random_nonce(AEAD)?);
+        envelope.wrapped_key = Some(wrapped_key);
+        envelope.kdf = Some(Kdf::HkdfSha256);
+        envelope.salt = Some(salt);
+        aead::seal(envelope, &payload_key, &plaintext)
+    }
+
+    /// Decrypts data of `seal` and checks that it was sealed under `name` with the current value
+    /// of the counter.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the data, which is zeroed when dropped, a
+    /// `SecurityModuleError::RollbackDetected` if it was sealed with another value of the counter,
+    /// a `SecurityModuleError::DecryptionError` if it was sealed under another key or name or was
+    /// modified, or the error of the provider if the data key cannot be decrypted for another
+    /// reason. Failures caused by the ciphertext are reported uniformly, see `uniform_errors`.
+    #[tracing::instrument(skip(self, sealed))]
+    pub fn open(&self, name: &str, sealed: &[u8]) -> Result<SecretBytes, SecurityModuleError> {
+        uniform_errors::decryption(&self.decryption_timing, || {
+            let envelope = input_limits::validate_envelope(sealed)?;
+            if envelope.key_id != self.key_id {
+                return Err(SecurityModuleError::DecryptionError(format!(
+                    "'{}' is sealed under the key '{}' instead of '{}'",
+                    name, envelope.key_id, self.key_id
+                )));
+            }
+            let (wrapped_key, kdf) = match (envelope.wrapped_key, envelope.kdf) {
+                (Some(wrapped_key), Some(kdf)) => (wrapped_key, kdf),
+                _ => {
+                    return Err(SecurityModuleError::DecryptionError(format!(
+                        "'{}' has no wrapped data key",
+                        name
+                    )))
+                }
+            };
+
+            let data_key = self.provider().decrypt_data(wrapped_key)?;
+            let payload_key = kdf.derive_vec(
+                &data_key,
+                envelope.salt,
+                &kdf_info(name),
+                envelope.aead.key_len(),
+            )?;
+            let plaintext = aead::open(&envelope, &payload_key)?;
+            let version = plaintext
+                .get(..VERSION_LEN)
+                .and_then(|version| version.try_into().ok())
+                .map(u64::from_be_bytes)
+                .ok_or_else(|| {
+                    SecurityModuleError::DecryptionError(format!("'{}' has no version", name))
+                })?;
+
+            let current = self.counter.read()?;
+            if version != current {
+                let rolled_back = if version < current { "data" } else { "counter" };
+                return Err(SecurityModuleError::RollbackDetected(format!(
+                    "'{}' was sealed with version {}, but the counter is at {}, so the {} was \
+                     rolled back",
+                    name, version, current, rolled_back
+                )));
+            }
+            Ok(SecretBytes::from(&plaintext[VERSION_LEN..]))
+        })
+    }
+
+    fn provider(&self) -> MutexGuard<'_, dyn Provider + 'static> {
+        self.provider.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl fmt::Debug for RollbackGuard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RollbackGuard")
+            .field("key_id", &self.key_id)
+            .finish_non_exhaustive()
+    }
+}
+
+fn kdf_info(name: &str) -> Vec<u8> {
+    [KDF_INFO_PREFIX, name.as_bytes()].concat()
+}
@@ -0,0 +1,652 @@
+//! An audit trail of key lifecycle events and cryptographic operations.
+//!
+//! An `AuditLog` turns every operation into an `AuditEvent` stating who performed which operation
+//! on which key, when, and with which result, and passes it to an `AuditSink`. Sinks write events
+//! to a file, to syslog, or to an application callback.
+//!
+//! Events are hash chained: the hash of every event covers its content and the hash of the
+//! previous event. Modifying, removing or reordering events therefore breaks the chain, which
+//! `verify_chain` detects. Applications that want to detect truncation or a rewritten log as
+//! well store `AuditLog::head` somewhere the log cannot be changed, e.g. a separate system.
+//!
+//! `SecModules::set_audit_log` audits every instance created by the factory afterwards.
+
+use crate::common::{
+    crypto::{
+        key_metadata::KeyMetadata, key_spec::KeySpec, operation_context::OperationContext,
+        secret::SecretBytes,
+    },
+    error::SecurityModuleError,
+    fips,
+    latency::{LatencySnapshot, ProviderOperation},
+    session_pool::SessionPoolMetrics,
+    traits::{key_handle::KeyHandle, module_provider::Provider},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    any::Any,
+    fmt,
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::Path,
+    sync::{Arc, Mutex, MutexGuard},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// The `previous_hash` of the first event of a chain.
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// The result of an audited operation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "result", rename_all = "snake_case")]
+pub enum AuditOutcome {
+    /// The operation succeeded.
+    Success,
+    /// The operation succeeded, but rejected its input, e.g. a signature that does not verify.
+    Rejected,
+    /// The operation failed with an error.
+    Failure {
+        /// The stable code of the error, see `SecurityModuleError::code`.
+        code: u32,
+        message: String,
+    },
+}
+
+impl From<&SecurityModuleError> for AuditOutcome {
+    fn from(error: &SecurityModuleError) -> Self {
+        AuditOutcome::Failure {
+            code: error.code(),
+            message: error.to_string(),
+        }
+    }
+}
+
+/// A single entry of the audit trail.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditEvent {
+    /// The position of the event in the chain, starting at 0.
+    pub sequence: u64,
+    /// Milliseconds since the Unix epoch.
+    pub timestamp: u64,
+    /// Who performed the operation, as configured on the `AuditLog`.
+    pub actor: String,
+    /// The id of the key the operation was performed on.
+    pub key_id: String,
+    pub operation: ProviderOperation,
+    #[serde(flatten)]
+    pub outcome: AuditOutcome,
+    /// Whether the operation ran in FIPS mode, see `fips`. Omitted when `false`, so events
+    /// recorded before the field existed keep their hash.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub fips_mode: bool,
+    /// The hash of the preceding event, or `GENESIS_HASH`.
+    pub previous_hash: String,
+    /// The SHA-256 hash of the event, in hex.
+    pub hash: String,
+}
+
+/// The hashed content of an event, everything except the hash itself.
+#[derive(Serialize)]
+struct HashedContent<'a> {
+    sequence: u64,
+    timestamp: u64,
+    actor: &'a str,
+    key_id: &'a str,
+    operation: ProviderOperation,
+    #[serde(flatten)]
+    outcome: &'a AuditOutcome,
+    #[serde(skip_serializing_if = "is_false")]
+    fips_mode: bool,
+    previous_hash: &'a str,
+}
+
+impl AuditEvent {
+    /// Computes the hash of the event from its content.
+    pub fn compute_hash(&self) -> String {
+        let content = HashedContent {
+            sequence: self.sequence,
+            timestamp: self.timestamp,
+            actor: &self.actor,
+            key_id: &self.key_id,
+            operation: self.operation,
+            outcome: &self.outcome,
+            fips_mode: self.fips_mode,
+            previous_hash: &self.previous_hash,
+        };
+        let json = serde_json::to_vec(&content).expect("audit events are always serializable");
+        hex(&openssl::sha::sha256(&json))
+    }
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// The position at which `verify_chain` found the chain to be broken.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChainBreak {
+    /// The index of the first event that does not belong to the chain.
+    pub index: usize,
+}
+
+impl fmt::Display for ChainBreak {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Audit chain is broken at event {}", self.index)
+    }
+}
+
+impl std::error::Error for ChainBreak {}
+
+/// Checks that `events` form an unbroken hash chain.
+///
+/// The first event may be any event of a chain, so that a log can be verified in parts. An
+/// event breaks the chain if its hash does not match its content, or if it does not directly
+/// follow the preceding event.
+pub fn verify_chain(events: &[AuditEvent]) -> Result<(), ChainBreak> {
+    for (index, event) in events.iter().enumerate() {
+        let linked = match index.checked_sub(1).map(|previous| &events[previous]) {
+            Some(previous) => {
+                event.previous_hash == previous.hash && event.sequence == previous.sequence + 1
+            }
+            None => event.sequence != 0 || event.previous_hash == GENESIS_HASH,
+        };
+        if !linked || event.hash != event.compute_hash() {
+            return Err(ChainBreak { index });
+        }
+    }
+    Ok(())
+}
+
+/// The destination of audit events.
+pub trait AuditSink: Send + Sync {
+    /// Writes an event. Events are written one at a time, in the order of the chain.
+    fn write(&self, event: &AuditEvent) -> Result<(), SecurityModuleError>;
+}
+
+/// Appends events to a file, one JSON object per line.
+#[derive(Debug)]
+pub struct FileSink {
+    file: Mutex<File>,
+}
+
+impl FileSink {
+    /// Opens `path` for appending, creating it if it does not exist.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `FileSink`, or a `SecurityModuleError::InitializationError` if
+    /// the file cannot be opened.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, SecurityModuleError> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| SecurityModuleError::InitializationError(e.to_string()))?;
+        Ok(Self {
+            file: Mutex::new(file),
+        })
+    }
+}
+
+impl AuditSink for FileSink {
+    fn write(&self, event: &AuditEvent) -> Result<(), SecurityModuleError> {
+        let mut line = serde_json::to_vec(event)
+            .map_err(|e| SecurityModuleError::InitializationError(e.to_string()))?;
+        line.push(b'\n');
+        let mut file = self
+            .file
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        file.write_all(&line)
+            .and_then(|_| file.flush())
+            .map_err(|e| SecurityModuleError::InitializationError(e.to_string()))
+    }
+}
+
+/// Reads the events written by a `FileSink`.
+///
+/// # Returns
+///
+/// A `Result` containing the events, or a `SecurityModuleError::InitializationError` if the file
+/// cannot be read or contains a line that is not an event.
+pub fn read_file(path: impl AsRef<Path>) -> Result<Vec<AuditEvent>, SecurityModuleError> {
+    let file =
+        File::open(path).map_err(|e| SecurityModuleError::InitializationError(e.to_string()))?;
+    BufReader::new(file)
+        .lines()
+        .map(|line| {
+            let line = line.map_err(|e| SecurityModuleError::InitializationError(e.to_string()))?;
+            serde_json::from_str(&line)
+                .map_err(|e| SecurityModuleError::InitializationError(e.to_string()))
+        })
+        .collect()
+}
+
+/// Sends events to the local syslog daemon, with the `auth` facility.
+///
+/// Successful operations are logged with severity `info`, rejected and failed ones with
+/// severity `warning`. The message is the event as JSON.
+#[cfg(unix)]
+#[derive(Debug)]
+pub struct SyslogSink {
+    socket: std::os::unix::net::UnixDatagram,
+}
+
+#[cfg(unix)]
+impl SyslogSink {
+    /// The socket of the syslog daemon on most Unix systems.
+    pub const DEFAULT_PATH: &'static str = "/dev/log";
+
+    /// Connects to the syslog daemon at `DEFAULT_PATH`.
+    pub fn new() -> Result<Self, SecurityModuleError> {
+        Self::with_path(Self::DEFAULT_PATH)
+    }
+
+    /// Connects to a syslog daemon listening on the datagram socket `path`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `SyslogSink`, or a `SecurityModuleError::InitializationError` if
+    /// the socket cannot be connected.
+    pub fn with_path(path: impl AsRef<Path>) -> Result<Self, SecurityModuleError> {
+        let socket = std::os::unix::net::UnixDatagram::unbound()
+            .and_then(|socket| socket.connect(path).map(|_| socket))
+            .map_err(|e| SecurityModuleError::InitializationError(e.to_string()))?;
+        Ok(Self { socket })
+    }
+}
+
+#[cfg(unix)]
+impl AuditSink for SyslogSink {
+    fn write(&self, event: &AuditEvent) -> Result<(), SecurityModuleError> {
+        const AUTH: u8 = 4 << 3;
+        let severity = match event.outcome {
+            AuditOutcome::Success => 6,
+            AuditOutcome::Rejected | AuditOutcome::Failure { .. } => 4,
+        };
+        let json = serde_json::to_string(event)
+            .map_err(|e| SecurityModuleError::InitializationError(e.to_string()))?;
+        let message = format!("<{}>crypto-layer: {}", AUTH | severity, json);
+        self.socket
+            .send(message.as_bytes())
+            .map(|_| ())
+            .map_err(|e| SecurityModuleError::InitializationError(e.to_string()))
+    }
+}
+
+/// Passes events to a function of the application.
+pub struct CallbackSink {
+    callback: Box<dyn Fn(&AuditEvent) + Send + Sync>,
+}
+
+impl CallbackSink {
+    pub fn new(callback: impl Fn(&AuditEvent) + Send + Sync + 'static) -> Self {
+        Self {
+            callback: Box::new(callback),
+        }
+    }
+}
+
+impl fmt::Debug for CallbackSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CallbackSink").finish_non_exhaustive()
+    }
+}
+
+impl AuditSink for CallbackSink {
+    fn write(&self, event: &AuditEvent) -> Result<(), SecurityModuleError> {
+        (self.callback)(event);
+        Ok(())
+    }
+}
+
+/// The end of the chain: the sequence number and hash of the next event's predecessor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainHead {
+    /// The sequence number of the next event.
+    pub next_sequence: u64,
+    /// The hash of the last event, or `GENESIS_HASH` if no event was recorded yet.
+    pub hash: String,
+}
+
+/// Records events into a hash chain and writes them to a sink.
+pub struct AuditLog {
+    actor: String,
+    sink: Box<dyn AuditSink>,
+    head: Mutex<ChainHead>,
+}
+
+impl AuditLog {
+    /// Creates a log starting a new chain.
+    ///
+    /// # Arguments
+    ///
+    /// * `actor` - Who performs the recorded operations, e.g. a user or service name.
+    /// * `sink` - Where events are written.
+    pub fn new(actor: impl Into<String>, sink: impl AuditSink + 'static) -> Self {
+        Self::resume(
+            actor,
+            sink,
+            ChainHead {
+                next_sequence: 0,
+                hash: GENESIS_HASH.to_owned(),
+            },
+        )
+    }
+
+    /// Creates a log continuing the chain ending at `head`, e.g. the chain of a file written by
+    /// an earlier run of the application.
+    pub fn resume(
+        actor: impl Into<String>,
+        sink: impl AuditSink + 'static,
+        head: ChainHead,
+    ) -> Self {
+        Self {
+            actor: actor.into(),
+            sink: Box::new(sink),
+            head: Mutex::new(head),
+        }
+    }
+
+    /// Returns the current end of the chain.
+    pub fn head(&self) -> ChainHead {
+        self.lock().clone()
+    }
+
+    /// Records an operation and writes it to the sink.
+    ///
+    /// An event that cannot be written still takes its place in the chain, so the gap it leaves
+    /// is detected by `verify_chain`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the recorded event, or the error of the sink.
+    pub fn record(
+        &self,
+        key_id: &str,
+        operation: ProviderOperation,
+        outcome: AuditOutcome,
+    ) -> Result<AuditEvent, SecurityModuleError> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_millis() as u64);
+        // The head stays locked while writing, so that events reach the sink in chain order.
+        let mut head = self.lock();
+        let mut event = AuditEvent {
+            sequence: head.next_sequence,
+            timestamp,
+            actor: self.actor.clone(),
+            key_id: key_id.to_owned(),
+            operation,
+            outcome,
+            fips_mode: fips::is_enabled(),
+            previous_hash: head.hash.clone(),
+            hash: String::new(),
+        };
+        event.hash = event.compute_hash();
+        head.next_sequence += 1;
+        head.hash = event.hash.clone();
+        self.sink.write(&event)?;
+        Ok(event)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, ChainHead> {
+        self.head
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl fmt::Debug for AuditLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AuditLog")
+            .field("actor", &self.actor)
+            .field("head", &self.head())
+            .finish_non_exhaustive()
+    }
+}
+
+/// A provider that records every call to the wrapped provider in an `AuditLog`.
+///
+/// Events that cannot be written to the sink are logged as an error; the result of the
+/// operation is returned unchanged.
+#[derive(Debug)]
+pub struct AuditedProvider {
+    inner: Arc<Mutex<dyn Provider>>,
+    key_id: Mutex<String>,
+    log: Arc<AuditLog>,
+}
+
+impl AuditedProvider {
+    /// Wraps a provider.
+    ///
+    /// # Arguments
+    ///
+    /// * `inner` - The provider whose calls are audited.
+    /// * `key_id` - The id of the key the provider operates on, until `create_key` or
+    ///   `load_key` is called with another one.
+    /// * `log` - The log the calls are recorded in, which may be shared between providers.
+    pub fn new(inner: Arc<Mutex<dyn Provider>>, key_id: String, log: Arc<AuditLog>) -> Self {
+        Self {
+            inner,
+            key_id: Mutex::new(key_id),
+            log,
+        }
+    }
+
+    fn inner(&self) -> MutexGuard<'_, dyn Provider + 'static> {
+        self.inner
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn key_id(&self) -> MutexGuard<'_, String> {
+        self.key_id
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn audit<T>(
+        &self,
+        operation: ProviderOperation,
+        f: impl FnOnce() -> Result<T, SecurityModuleError>,
+        outcome: impl FnOnce(&T) -> AuditOutcome,
+    ) -> Result<T, SecurityModuleError> {
+        let key_id = self.key_id().clone();
+        self.audit_key(&key_id, operation, f, outcome)
+    }
+
+    /// Runs `f` and records its outcome for the key `key_id`, which need not be the current key.
+    fn audit_key<T>(
+        &self,
+        key_id: &str,
+        operation: ProviderOperation,
+        f: impl FnOnce() -> Result<T, SecurityModuleError>,
+        outcome: impl FnOnce(&T) -> AuditOutcome,
+    ) -> Result<T, SecurityModuleError> {
+        let result = f();
+        let outcome = match &result {
+            Ok(value) => outcome(value),
+            Err(error) => error.into(),
+        };
+        if let Err(error) = self.log.record(key_id, operation, outcome) {
+            tracing::error!(%operation, %error, "Failed to write audit event");
+        }
+        result
+    }
+
+    /// Runs `f`, which creates or loads the key `key_id`, and makes it the current key if `f`
+    /// succeeds. A failed call leaves the previous key loaded, so it stays the current key.
+    fn switch_key(
+        &self,
+        key_id: &str,
+        operation: ProviderOperation,
+        f: impl FnOnce() -> Result<(), SecurityModuleError>,
+    ) -> Result<(), SecurityModuleError> {
+        self.audit_key(key_id, operation, f, |_| AuditOutcome::Success)?;
+        *self.key_id() = key_id.to_owned();
+        Ok(())
+    }
+
+    fn audit_status<T>(
+        &self,
+        operation: ProviderOperation,
+        f: impl FnOnce() -> Result<T, SecurityModuleError>,
+    ) -> Result<T, SecurityModuleError> {
+        self.audit(operation, f, |_| AuditOutcome::Success)
+    }
+}
+
+fn verification_outcome(valid: &bool) -> AuditOutcome {
+    if *valid {
+        AuditOutcome::Success
+    } else {
+        AuditOutcome::Rejected
+    }
+}
+
+impl KeyHandle for AuditedProvider {
+    fn sign_data(&self, data: &[u8]) -> Result<Vec<u8>, SecurityModuleError> {
+        self.audit_status(ProviderOperation::SignData, || self.inner().sign_data(data))
+    }
+
+    fn decrypt_data(&self, encrypted_data: &[u8]) -> Result<SecretBytes, SecurityModuleError> {
+        self.audit_status(ProviderOperation::DecryptData, || {
+            self.inner().decrypt_data(encrypted_data)
+        })
+    }
+
+    fn encrypt_data(&self, data: &[u8]) -> Result<Vec<u8>, SecurityModuleError> {
+        self.audit_status(ProviderOperation::EncryptData, || {
+            self.inner().encrypt_data(data)
+        })
+    }
+
+    fn verify_signature(&self, data: &[u8], signature: &[u8]) -> Result<bool, SecurityModuleError> {
+        self.audit(
+            ProviderOperation::VerifySignature,
+            || self.inner().verify_signature(data, signature),
+            verification_outcome,
+        )
+    }
+
+    fn sign_data_into<'a>(
+        &self,
+        data: &[u8],
+        context: &'a mut OperationContext,
+    ) -> Result<&'a [u8], SecurityModuleError> {
+        self.audit_status(ProviderOperation::SignData, || {
+            self.inner().sign_data_into(data, context)
+        })
+    }
+
+    fn verify_signature_with(
+        &self,
+        data: &[u8],
+        signature: &[u8],
+        context: &mut OperationContext,
+    ) -> Result<bool, SecurityModuleError> {
+        self.audit(
+            ProviderOperation::VerifySignature,
+            || self.inner().verify_signature_with(data, signature, context),
+            verification_outcome,
+        )
+    }
+
+    fn verify_many(&self, items: &[(&[u8], &[u8])]) -> Result<Vec<bool>, SecurityModuleError> {
+        self.audit(
+            ProviderOperation::VerifyMany,
+            || self.inner().verify_many(items),
+            |results| verification_outcome(&results.iter().all(|valid| *valid)),
+        )
+    }
+
+    fn derive_shared_secret(
+        &self,
+        peer_public_key: &[u8],
+    ) -> Result<SecretBytes, SecurityModuleError> {
+        self.audit_status(ProviderOperation::DeriveSharedSecret, || {
+            self.inner().derive_shared_secret(peer_public_key)
+        })
+    }
+}
+
+impl Provider for AuditedProvider {
+    fn create_key(
+        &mut self,
+        key_id: &str,
+        config: Box<dyn Any>,
+    ) -> Result<(), SecurityModuleError> {
+        self.switch_key(key_id, ProviderOperation::CreateKey, || {
+            self.inner().create_key(key_id, config)
+        })
+    }
+
+    fn load_key(&mut self, key_id: &str, config: Box<dyn Any>) -> Result<(), SecurityModuleError> {
+        self.switch_key(key_id, ProviderOperation::LoadKey, || {
+            self.inner().load_key(key_id, config)
+        })
+    }
+
+    fn import_wrapped_key(
+        &mut self,
+        key_id: &str,
+        wrapped_key: &[u8],
+        wrapping_key_id: &str,
+        spec: &KeySpec,
+    ) -> Result<(), SecurityModuleError> {
+        self.switch_key(key_id, ProviderOperation::ImportWrappedKey, || {
+            self.inner()
+                .import_wrapped_key(key_id, wrapped_key, wrapping_key_id, spec)
+        })
+    }
+
+    fn export_private_key(&self) -> Result<SecretBytes, SecurityModuleError> {
+        self.audit_status(ProviderOperation::ExportPrivateKey, || {
+            self.inner().export_private_key()
+        })
+    }
+
+    fn initialize_module(&mut self) -> Result<(), SecurityModuleError> {
+        self.audit_status(ProviderOperation::InitializeModule, || {
+            self.inner().initialize_module()
+        })
+    }
+
+    fn session_pool_metrics(&self) -> Option<SessionPoolMetrics> {
+        self.inner().session_pool_metrics()
+    }
+
+    fn latency_snapshot(&self) -> Option<LatencySnapshot> {
+        self.inner().latency_snapshot()
+    }
+
+    fn key_metadata(&self) -> Result<KeyMetadata, SecurityModuleError> {
+        self.inner().key_metadata()
+    }
+
+    fn refresh_key_metadata(&mut self) -> Result<KeyMetadata, SecurityModuleError> {
+        self.inner().refresh_key_metadata()
+    }
+
+    fn list_keys(&self) -> Result<Vec<String>, SecurityModuleError> {
+        self.inner().list_keys()
+    }
+
+    fn delete_key(&mut self, key_id: &str) -> Result<(), SecurityModuleError> {
+        self.audit_key(
+            key_id,
+            ProviderOperation::DeleteKey,
+            || self.inner().delete_key(key_id),
+            |_| AuditOutcome::Success,
+        )
+    }
+
+    fn shutdown(&mut self) -> Result<(), SecurityModuleError> {
+        self.inner().shutdown()
+    }
+}
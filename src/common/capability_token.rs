@@ -0,0 +1,421 @@
+//! Short-lived capability tokens signed by a device-bound key.
+//!
+//! A device mints a token with `mint_token` for the service it wants to call, naming the
+//! capabilities it requests in its claims. The service checks the token with `verify_token`
+//! against the public key it registered for the device, which it finds by the key id in the
+//! header:
+//!
+//! ```rust,ignore
+//! use crypto_layer::common::capability_token::{self, CapabilityToken, TokenPolicy, TokenSpec};
+//!
+//! // On the device.
+//! let spec = TokenSpec::new("https://storage.example.com", Duration::from_secs(300))
+//!     .claim("scope", "read:firmware".into());
+//! let token = capability_token::mint_token(&provider, &spec)?;
+//! request.header("Authorization", format!("Bearer {}", token));
+//!
+//! // On the service.
+//! let token: CapabilityToken = bearer.parse()?;
+//! let public_key = registered_keys.get(token.key_id())?;
+//! let audience = "https://storage.example.com";
+//! let claims = capability_token::verify_token(&token, public_key, audience, &TokenPolicy::default())?;
+//! ```
+//!
+//! Tokens are JSON Web Tokens (RFC 7519) in the compact JWS serialization, so services can also
+//! check them with any JWT library. The algorithm follows from the key: `ES256`, `ES384` and
+//! `ES512` for ECDSA keys on P-256, P-384 and P-521, `RS*` and `PS*` for RSA keys with PKCS#1
+//! v1.5 and PSS signatures and `EdDSA` for Ed25519 keys. CBOR Web Tokens are not supported.
+
+use crate::common::{
+    crypto::{
+        algorithms::{
+            encryption::{AsymmetricEncryption, EccCurves, EccSchemeAlgorithm},
+            hashes::{Hash, Sha2Bits},
+        },
+        public_key::{PublicKey, RsaSignaturePadding},
+        signature_format::{self, SignatureFormat},
+    },
+    error::SecurityModuleError,
+    traits::module_provider::Provider,
+};
+use base64::{prelude::BASE64_URL_SAFE_NO_PAD, Engine};
+use openssl::rand::rand_bytes;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::{
+    fmt,
+    str::FromStr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// The longest lifetime of a token.
+pub const MAX_LIFETIME: Duration = Duration::from_secs(60 * 60);
+
+/// The claims that are set by `mint_token` and cannot be specified by the caller.
+pub const RESERVED_CLAIMS: [&str; 6] = ["iss", "sub", "aud", "iat", "exp", "jti"];
+
+/// The type of the tokens in their header.
+const TOKEN_TYPE: &str = "JWT";
+
+/// The length of the random token id in bytes.
+const TOKEN_ID_LEN: usize = 16;
+
+/// The header of a token.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenHeader {
+    /// The JWS algorithm of the signature, e.g. `ES256`.
+    pub alg: String,
+    /// The type of the token, always `JWT`.
+    pub typ: String,
+    /// The id of the key the token was signed with.
+    pub kid: String,
+}
+
+/// The claims of a token.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TokenClaims {
+    /// The issuer of the token, the key id unless `TokenSpec::issuer` was set.
+    #[serde(rename = "iss")]
+    pub issuer: String,
+    /// The subject the token was issued for, e.g. a user of the device.
+    #[serde(rename = "sub", default, skip_serializing_if = "Option::is_none")]
+    pub subject: Option<String>,
+    /// The service the token is meant for.
+    #[serde(rename = "aud")]
+    pub audience: String,
+    /// The time the token was issued at in seconds since the Unix epoch.
+    #[serde(rename = "iat")]
+    pub issued_at: u64,
+    /// The time the token expires at in seconds since the Unix epoch.
+    #[serde(rename = "exp")]
+    pub expires_at: u64,
+    /// A random id of the token in unpadded base64url, which services can remember to reject
+    /// replayed tokens until they expire.
+    #[serde(rename = "jti")]
+    pub token_id: String,
+    /// The claims specified by the caller, e.g. the granted scopes.
+    #[serde(flatten)]
+    pub claims: Map<String, Value>,
+}
+
+/// What `mint_token` puts into a token.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenSpec {
+    issuer: Option<String>,
+    subject: Option<String>,
+    audience: String,
+    lifetime: Duration,
+    claims: Map<String, Value>,
+}
+
+impl TokenSpec {
+    /// Creates the specification of a token for `audience` that expires after `lifetime`, which
+    /// must not exceed `MAX_LIFETIME`.
+    pub fn new(audience: &str, lifetime: Duration) -> Self {
+        Self {
+            issuer: None,
+            subject: None,
+            audience: audience.to_owned(),
+            lifetime,
+            claims: Map::new(),
+        }
+    }
+
+    /// Sets the issuer, which defaults to the id of the signing key.
+    pub fn issuer(mut self, issuer: &str) -> Self {
+        self.issuer = Some(issuer.to_owned());
+        self
+    }
+
+    /// Sets the subject.
+    pub fn subject(mut self, subject: &str) -> Self {
+        self.subject = Some(subject.to_owned());
+        self
+    }
+
+    /// Adds a claim, which must not be one of `RESERVED_CLAIMS`.
+    pub fn claim(mut self, name: &str, value: Value) -> Self {
+        self.claims.insert(name.to_owned(), value);
+        self
+    }
+}
+
+/// A signed capability token, see the module documentation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CapabilityToken {
+    header: TokenHeader,
+    claims: TokenClaims,
+    signing_input: String,
+    signature: Vec<u8>,
+}
+
+impl CapabilityToken {
+    /// Returns the header of the token.
+    pub fn header(&self) -> &TokenHeader {
+        &self.header
+    }
+
+    /// Returns the id of the key the token claims to be signed with, to look up its public key.
+    pub fn key_id(&self) -> &str {
+        &self.header.kid
+    }
+
+    /// Returns the claims of the token, which are only trustworthy after `verify_token`.
+    pub fn claims(&self) -> &TokenClaims {
+        &self.claims
+    }
+
+    /// Returns the signature in the JWS encoding, raw `r || s` for ECDSA.
+    pub fn signature(&self) -> &[u8] {
+        &self.signature
+    }
+}
+
+impl fmt::Display for CapabilityToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}.{}",
+            self.signing_input,
+            BASE64_URL_SAFE_NO_PAD.encode(&self.signature)
+        )
+    }
+}
+
+impl FromStr for CapabilityToken {
+    type Err = SecurityModuleError;
+
+    /// Parses a compact token without verifying it.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `CapabilityToken`, or a `SecurityModuleError::InvalidToken` if
+    /// `s` is not a compact JWT.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (signing_input, signature) = s
+            .rsplit_once('.')
+            .ok_or_else(|| invalid_token("The token has no signature"))?;
+        let (header, claims) = signing_input
+            .split_once('.')
+            .ok_or_else(|| invalid_token("The token has no claims"))?;
+        let decode = |part: &str| {
+            BASE64_URL_SAFE_NO_PAD
+                .decode(part)
+                .map_err(|e| invalid_token(&e.to_string()))
+        };
+        let header: TokenHeader =
+            serde_json::from_slice(&decode(header)?).map_err(|e| invalid_token(&e.to_string()))?;
+        if header.typ != TOKEN_TYPE {
+            return Err(invalid_token("The token is not a JWT"));
+        }
+        Ok(Self {
+            header,
+            claims: serde_json::from_slice(&decode(claims)?)
+                .map_err(|e| invalid_token(&e.to_string()))?,
+            signing_input: signing_input.to_owned(),
+            signature: decode(signature)?,
+        })
+    }
+}
+
+/// The limits `verify_token` applies to the validity of tokens.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TokenPolicy {
+    /// The longest lifetime accepted, tokens valid for longer are rejected.
+    pub max_lifetime: Duration,
+    /// How far the clocks of the device and the service may differ.
+    pub clock_skew: Duration,
+}
+
+impl Default for TokenPolicy {
+    /// Accepts tokens with a lifetime of up to `MAX_LIFETIME` and clocks that differ by up to 30
+    /// seconds.
+    fn default() -> Self {
+        Self {
+            max_lifetime: MAX_LIFETIME,
+            clock_skew: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Mints a token with the loaded key of `provider`.
+///
+/// # Arguments
+///
+/// * `provider` - The provider with the loaded device key.
+/// * `spec` - The audience, lifetime and claims of the token.
+///
+/// # Returns
+///
+/// A `Result` containing the `CapabilityToken`, a `SecurityModuleError::InvalidToken` if the
+/// lifetime is zero or exceeds `MAX_LIFETIME` or a claim is reserved, a
+/// `SecurityModuleError::UnsupportedAlgorithm` if the key has no JWS algorithm, or the error of
+/// `provider` if the key metadata cannot be read or the token cannot be signed.
+#[tracing::instrument(skip_all, fields(crypto.audience = spec.audience))]
+pub fn mint_token(
+    provider: &(impl Provider + ?Sized),
+    spec: &TokenSpec,
+) -> Result<CapabilityToken, SecurityModuleError> {
+    if spec.lifetime.is_zero() || spec.lifetime > MAX_LIFETIME {
+        return Err(invalid_token(&format!(
+            "The lifetime must be between 1 and {} seconds",
+            MAX_LIFETIME.as_secs()
+        )));
+    }
+    if let Some(name) = RESERVED_CLAIMS
+        .iter()
+        .find(|name| spec.claims.contains_key(**name))
+    {
+        return Err(invalid_token(&format!("The claim {} is reserved", name)));
+    }
+
+    let metadata = provider.key_metadata()?;
+    let algorithm = jws_algorithm(metadata.public_key())?;
+    let mut token_id = [0; TOKEN_ID_LEN];
+    rand_bytes(&mut token_id).map_err(|e| SecurityModuleError::SigningError(e.to_string()))?;
+    let issued_at = unix_time(SystemTime::now());
+
+    let header = TokenHeader {
+        alg: algorithm.name.to_owned(),
+        typ: TOKEN_TYPE.to_owned(),
+        kid: metadata.key_id().to_owned(),
+    };
+    let claims = TokenClaims {
+        issuer: spec
+            .issuer
+            .clone()
+            .unwrap_or_else(|| metadata.key_id().to_owned()),
+        subject: spec.subject.clone(),
+        audience: spec.audience.clone(),
+        issued_at,
+        expires_at: issued_at + spec.lifetime.as_secs(),
+        token_id: BASE64_URL_SAFE_NO_PAD.encode(token_id),
+        claims: spec.claims.clone(),
+    };
+    let signing_input = format!("{}.{}", encode_part(&header), encode_part(&claims));
+
+    let signature = provider.sign_data(signing_input.as_bytes())?;
+    let signature = match algorithm.scalar_len {
+        Some(scalar_len) => signature_format::der_to_raw(&signature, scalar_len)?,
+        None => signature,
+    };
+    Ok(CapabilityToken {
+        header,
+        claims,
+        signing_input,
+        signature,
+    })
+}
+
+/// Verifies `token` against the registered public key of the device and the expected audience,
+/// at the current time.
+///
+/// # Returns
+///
+/// A `Result` containing the verified claims, a `SecurityModuleError::InvalidSignature` if the
+/// token was not signed by the owner of `public_key`, or a `SecurityModuleError::InvalidToken`
+/// if it is signed with another algorithm than the one of the key, is meant for another
+/// audience, is valid for longer than the policy allows, has expired or was issued in the
+/// future.
+pub fn verify_token<'a>(
+    token: &'a CapabilityToken,
+    public_key: &PublicKey,
+    audience: &str,
+    policy: &TokenPolicy,
+) -> Result<&'a TokenClaims, SecurityModuleError> {
+    verify_token_at(token, public_key, audience, policy, SystemTime::now())
+}
+
+/// Verifies `token` like `verify_token`, at the time `now`.
+#[tracing::instrument(skip(token, public_key))]
+pub fn verify_token_at<'a>(
+    token: &'a CapabilityToken,
+    public_key: &PublicKey,
+    audience: &str,
+    policy: &TokenPolicy,
+    now: SystemTime,
+) -> Result<&'a TokenClaims, SecurityModuleError> {
+    // The algorithm is taken from the key, never from the token, so a token cannot downgrade it.
+    let algorithm = jws_algorithm(public_key)?;
+    if token.header.alg != algorithm.name {
+        return Err(invalid_token("The token is signed with another algorithm"));
+    }
+    let format = match algorithm.scalar_len {
+        Some(scalar_len) if token.signature.len() != 2 * scalar_len => {
+            return Err(SecurityModuleError::InvalidSignature)
+        }
+        Some(_) => SignatureFormat::Raw,
+        None => SignatureFormat::Der,
+    };
+    if !public_key.verify_with_format(token.signing_input.as_bytes(), &token.signature, format)? {
+        return Err(SecurityModuleError::InvalidSignature);
+    }
+
+    let claims = &token.claims;
+    if claims.audience != audience {
+        return Err(invalid_token("The token is meant for another audience"));
+    }
+    if claims.expires_at.saturating_sub(claims.issued_at) > policy.max_lifetime.as_secs() {
+        return Err(invalid_token("The token is valid for too long"));
+    }
+    let now = unix_time(now);
+    let clock_skew = policy.clock_skew.as_secs();
+    if claims.issued_at > now.saturating_add(clock_skew) {
+        return Err(invalid_token("The token was issued in the future"));
+    }
+    if now >= claims.expires_at.saturating_add(clock_skew) {
+        return Err(invalid_token("The token has expired"));
+    }
+    Ok(claims)
+}
+
+/// The JWS algorithm of a key.
+pub(crate) struct JwsAlgorithm {
+    pub(crate) name: &'static str,
+    /// The length of a scalar of the curve for ECDSA, whose JWS signatures are raw `r || s`.
+    pub(crate) scalar_len: Option<usize>,
+}
+
+/// Returns the JWS algorithm of `public_key`, or a `SecurityModuleError::UnsupportedAlgorithm`
+/// if it has none.
+pub(crate) fn jws_algorithm(public_key: &PublicKey) -> Result<JwsAlgorithm, SecurityModuleError> {
+    let (name, scalar_len) = match (public_key.algorithm(), public_key.hash()) {
+        (AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(EccCurves::Curve25519)), _) => {
+            ("EdDSA", None)
+        }
+        (AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(curve)), Hash::Sha2(bits)) => {
+            match (curve, bits) {
+                (EccCurves::P256, Sha2Bits::Sha256) => ("ES256", Some(32)),
+                (EccCurves::P384, Sha2Bits::Sha384) => ("ES384", Some(48)),
+                (EccCurves::P521, Sha2Bits::Sha512) => ("ES512", Some(66)),
+                _ => return Err(SecurityModuleError::UnsupportedAlgorithm),
+            }
+        }
+        (AsymmetricEncryption::Rsa(_), Hash::Sha2(bits)) => {
+            match (public_key.rsa_padding(), bits) {
+                (RsaSignaturePadding::Pkcs1, Sha2Bits::Sha256) => ("RS256", None),
+                (RsaSignaturePadding::Pkcs1, Sha2Bits::Sha384) => ("RS384", None),
+                (RsaSignaturePadding::Pkcs1, Sha2Bits::Sha512) => ("RS512", None),
+                (RsaSignaturePadding::Pss, Sha2Bits::Sha256) => ("PS256", None),
+                (RsaSignaturePadding::Pss, Sha2Bits::Sha384) => ("PS384", None),
+                (RsaSignaturePadding::Pss, Sha2Bits::Sha512) => ("PS512", None),
+                _ => return Err(SecurityModuleError::UnsupportedAlgorithm),
+            }
+        }
+        _ => return Err(SecurityModuleError::UnsupportedAlgorithm),
+    };
+    Ok(JwsAlgorithm { name, scalar_len })
+}
+
+fn encode_part(part: &impl Serialize) -> String {
+    BASE64_URL_SAFE_NO_PAD.encode(serde_json::to_vec(part).expect("tokens are always serializable"))
+}
+
+fn unix_time(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+fn invalid_token(message: &str) -> SecurityModuleError {
+    SecurityModuleError::InvalidToken(message.to_owned())
+}
@@ -0,0 +1,398 @@
+//! Crate-wide configuration, tunable per deployment.
+//!
+//! A `CryptoConfig` selects the providers to use and in which order, what happens if the
+//! preferred one is unavailable, the namespace of the key ids, the timeouts of the providers, the
+//! log levels, whether secrets are locked into memory, the maximum sizes of inputs and whether
+//! only FIPS-approved algorithms may be used. It is built in code, parsed from a TOML file or
+//! resolved from the environment:
+//!
+//! ```toml
+//! providers = ["macos", "nks"]
+//! fallback = "next_provider"
+//! namespace = "com.example.mail"
+//! fips = true
+//!
+//! [deprecated]
+//! rsa1024 = "deny"
+//! sha1 = "warn"
+//!
+//! [timeouts]
+//! slow_operation_ms = 250
+//! session_acquire_ms = 5000
+//!
+//! [logging]
+//! level = "info"
+//! targets = { macos = "trace" }
+//!
+//! [memory]
+//! lock_secrets = true
+//!
+//! [limits]
+//! max_data_len = 1048576
+//! ```
+//!
+//! `CryptoConfig::resolve` starts from the defaults, reads the file named by `CRYPTO_LAYER_CONFIG`
+//! if set and applies the overrides of the environment variables below. `SecModules` resolves the
+//! configuration once when the first instance is created, unless `SecModules::configure` injected
+//! one before.
+//!
+//! | Variable | Overrides |
+//! |----------|-----------|
+//! | `CRYPTO_LAYER_PROVIDERS` | `providers`, comma separated |
+//! | `CRYPTO_LAYER_FALLBACK` | `fallback` |
+//! | `CRYPTO_LAYER_NAMESPACE` | `namespace` |
+//! | `CRYPTO_LAYER_FIPS` | `fips`, `true` or `false` |
+//! | `CRYPTO_LAYER_DEPRECATED` | `deprecated`, e.g. `rsa1024=deny,sha1=warn` |
+//! | `CRYPTO_LAYER_SLOW_OPERATION_MS` | `timeouts.slow_operation_ms`, `off` disables it |
+//! | `CRYPTO_LAYER_SESSION_ACQUIRE_MS` | `timeouts.session_acquire_ms`, `off` waits indefinitely |
+//! | `CRYPTO_LAYER_LOG_LEVEL` | `logging.level` |
+//! | `CRYPTO_LAYER_LOG_TARGETS` | `logging.targets`, e.g. `macos=trace,crypto_layer::common=debug` |
+//! | `CRYPTO_LAYER_LOCK_SECRETS` | `memory.lock_secrets`, `true` or `false` |
+//! | `CRYPTO_LAYER_MAX_DATA_LEN` | `limits.max_data_len`, in bytes |
+
+use crate::common::{
+    error::SecurityModuleError,
+    factory::SecurityModule,
+    input_limits::InputLimits,
+    latency::{LatencyConfig, DEFAULT_SLOW_THRESHOLD},
+    log_levels,
+    namespace::Namespace,
+    session_pool::SessionPoolConfig,
+    sunset::{SunsetAction, SunsetPolicy},
+};
+#[cfg(feature = "hsm")]
+use crate::hsm::core::instance::HsmType;
+#[cfg(feature = "android")]
+use crate::tpm::core::instance::AndroidTpmType;
+#[cfg(any(
+    feature = "win",
+    feature = "macos",
+    feature = "linux",
+    feature = "android"
+))]
+use crate::tpm::core::instance::TpmType;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, env, fs, path::Path, str::FromStr, time::Duration};
+use tracing::level_filters::LevelFilter;
+
+/// The environment variable naming the configuration file read by `CryptoConfig::resolve`.
+pub const CONFIG_FILE_VAR: &str = "CRYPTO_LAYER_CONFIG";
+
+/// The names of all providers, whether or not they are enabled in this build.
+pub const PROVIDER_NAMES: [&str; 8] = [
+    "windows",
+    "macos",
+    "linux",
+    "android",
+    "android_keystore",
+    "nitrokey",
+    "yubikey",
+    "nks",
+];
+
+/// The configuration of the crate, see the module documentation.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CryptoConfig {
+    /// The names of the providers to use, most preferred first, see `PROVIDER_NAMES`.
+    pub providers: Vec<String>,
+    pub fallback: FallbackPolicy,
+    /// The namespace the key ids of all instances are scoped to, see `Namespace`. `None` uses the
+    /// key ids unchanged.
+    pub namespace: Option<String>,
+    pub timeouts: Timeouts,
+    pub logging: Logging,
+    pub memory: Memory,
+    /// The maximum sizes of inputs, see `input_limits`.
+    pub limits: InputLimits,
+    /// The deprecated algorithms and what happens when a key is created with them, see `sunset`.
+    pub deprecated: BTreeMap<String, SunsetAction>,
+    /// Whether only FIPS-approved algorithms may be used, see `fips`. Defaults to `false`, and is
+    /// ignored with the `fips` feature, which always enables FIPS mode.
+    pub fips: bool,
+}
+
+/// What `SecModules::get_preferred_instance` does if a provider is unavailable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FallbackPolicy {
+    /// Fail if the most preferred provider is unavailable.
+    #[default]
+    Never,
+    /// Try the next provider in the order of preference.
+    NextProvider,
+}
+
+/// The timeouts of the providers, in milliseconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Timeouts {
+    /// Operations taking at least this long are logged as a warning. `None` disables the warning.
+    pub slow_operation_ms: Option<u64>,
+    /// How long an operation waits for a free TPM session. `None` waits indefinitely.
+    pub session_acquire_ms: Option<u64>,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Self {
+            slow_operation_ms: Some(DEFAULT_SLOW_THRESHOLD.as_millis() as u64),
+            session_acquire_ms: SessionPoolConfig::default()
+                .acquire_timeout
+                .map(|timeout| timeout.as_millis() as u64),
+        }
+    }
+}
+
+/// The log levels, see `log_levels`. Levels are names like `info` or `off`.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Logging {
+    /// The default level, or `None` to keep the level set through `log_levels`.
+    pub level: Option<String>,
+    /// The levels of providers, named as in `PROVIDER_NAMES`, or of module paths.
+    pub targets: BTreeMap<String, String>,
+}
+
+/// The handling of secrets in memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Memory {
+    /// Whether the buffers of secrets are locked into RAM and excluded from core dumps, see
+    /// `memory_lock`. Defaults to `false`.
+    pub lock_secrets: bool,
+}
+
+fn invalid(message: String) -> SecurityModuleError {
+    SecurityModuleError::InitializationError(message)
+}
+
+impl CryptoConfig {
+    /// Parses a configuration in TOML. Missing fields keep their defaults.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the validated configuration on success, or a
+    /// `SecurityModuleError::InitializationError` describing the invalid field.
+    pub fn from_toml(toml: &str) -> Result<Self, SecurityModuleError> {
+        let config: Self = toml::from_str(toml).map_err(|e| invalid(e.to_string()))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Reads and parses a TOML configuration file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SecurityModuleError> {
+        let path = path.as_ref();
+        let toml = fs::read_to_string(path)
+            .map_err(|e| invalid(format!("Cannot read {}: {}", path.display(), e)))?;
+        Self::from_toml(&toml)
+    }
+
+    /// Resolves the configuration of the process: the defaults, overridden by the file named by
+    /// `CRYPTO_LAYER_CONFIG` if set, overridden by the environment variables.
+    pub fn resolve() -> Result<Self, SecurityModuleError> {
+        let config = match env::var_os(CONFIG_FILE_VAR) {
+            Some(path) => Self::load(path)?,
+            None => Self::default(),
+        };
+        config.with_env_overrides(env::vars())
+    }
+
+    /// Applies the overrides of the `CRYPTO_LAYER_*` variables among `vars`, see the module
+    /// documentation. Other variables are ignored.
+    pub fn with_env_overrides(
+        mut self,
+        vars: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Self, SecurityModuleError> {
+        for (name, value) in vars {
+            let value = value.trim();
+            match name.as_str() {
+                "CRYPTO_LAYER_PROVIDERS" => {
+                    self.providers = value
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|name| !name.is_empty())
+                        .map(str::to_owned)
+                        .collect();
+                }
+                "CRYPTO_LAYER_FALLBACK" => {
+                    self.fallback = match value {
+                        "never" => FallbackPolicy::Never,
+                        "next_provider" => FallbackPolicy::NextProvider,
+                        _ => return Err(invalid(format!("Unknown fallback policy '{}'", value))),
+                    };
+                }
+                "CRYPTO_LAYER_NAMESPACE" => self.namespace = Some(value.to_owned()),
+                "CRYPTO_LAYER_FIPS" => {
+                    self.fips = value
+                        .parse()
+                        .map_err(|_| invalid(format!("{} must be true or false", name)))?;
+                }
+                "CRYPTO_LAYER_SLOW_OPERATION_MS" => {
+                    self.timeouts.slow_operation_ms = parse_millis(&name, value)?;
+                }
+                "CRYPTO_LAYER_SESSION_ACQUIRE_MS" => {
+                    self.timeouts.session_acquire_ms = parse_millis(&name, value)?;
+                }
+                "CRYPTO_LAYER_LOG_LEVEL" => self.logging.level = Some(value.to_owned()),
+                "CRYPTO_LAYER_LOCK_SECRETS" => {
+                    self.memory.lock_secrets = value
+                        .parse()
+                        .map_err(|_| invalid(format!("{} must be true or false", name)))?;
+                }
+                "CRYPTO_LAYER_MAX_DATA_LEN" => {
+                    self.limits.max_data_len = value
+                        .parse()
+                        .map_err(|_| invalid(format!("{} must be a number of bytes", name)))?;
+                }
+                "CRYPTO_LAYER_DEPRECATED" => {
+                    for entry in value.split(',').filter(|entry| !entry.trim().is_empty()) {
+                        let (algorithm, action) = entry.split_once('=').ok_or_else(|| {
+                            invalid(format!("Expected algorithm=action in {}", name))
+                        })?;
+                        let action = match action.trim() {
+                            "warn" => SunsetAction::Warn,
+                            "deny" => SunsetAction::Deny,
+                            action => {
+                                return Err(invalid(format!("Unknown sunset action '{}'", action)))
+                            }
+                        };
+                        self.deprecated.insert(algorithm.trim().to_owned(), action);
+                    }
+                }
+                "CRYPTO_LAYER_LOG_TARGETS" => {
+                    for target in value.split(',').filter(|target| !target.trim().is_empty()) {
+                        let (target, level) = target
+                            .split_once('=')
+                            .ok_or_else(|| invalid(format!("Expected target=level in {}", name)))?;
+                        self.logging
+                            .targets
+                            .insert(target.trim().to_owned(), level.trim().to_owned());
+                    }
+                }
+                _ => {}
+            }
+        }
+        self.validate()?;
+        Ok(self)
+    }
+
+    /// Checks that all providers, log levels and deprecated algorithms are known and that the
+    /// namespace and the input limits are valid.
+    pub fn validate(&self) -> Result<(), SecurityModuleError> {
+        for name in &self.providers {
+            if !PROVIDER_NAMES.contains(&name.as_str()) {
+                return Err(invalid(format!("Unknown provider '{}'", name)));
+            }
+        }
+        if let Some(name) = &self.namespace {
+            Namespace::new(name.as_str())?;
+        }
+        SunsetPolicy::from_map(&self.deprecated)?;
+        self.limits.validate()?;
+        for level in self
+            .logging
+            .level
+            .iter()
+            .chain(self.logging.targets.values())
+        {
+            parse_level(level)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the configured providers that are enabled in this build, most preferred first.
+    /// Providers whose feature is disabled are skipped, so one file can serve several builds.
+    pub fn security_modules(&self) -> Vec<SecurityModule> {
+        self.providers
+            .iter()
+            .filter_map(|name| security_module(name))
+            .collect()
+    }
+
+    /// Returns the namespace the key ids of new instances are scoped to, or `None` if it is not
+    /// set or invalid.
+    pub fn namespace(&self) -> Option<Namespace> {
+        Namespace::new(self.namespace.clone()?).ok()
+    }
+
+    /// Returns the policy for the deprecated algorithms of new instances, or an empty policy if
+    /// an algorithm is unknown, which `validate` reports.
+    pub fn sunset_policy(&self) -> SunsetPolicy {
+        SunsetPolicy::from_map(&self.deprecated).unwrap_or_default()
+    }
+
+    /// Returns the latency recording configuration of new instances.
+    pub fn latency_config(&self) -> LatencyConfig {
+        LatencyConfig {
+            slow_threshold: self.timeouts.slow_operation_ms.map(Duration::from_millis),
+        }
+    }
+
+    /// Returns the session pool configuration with the configured acquire timeout, to be passed
+    /// to `TpmConfig::new_with_session_pool`.
+    pub fn session_pool_config(&self) -> SessionPoolConfig {
+        SessionPoolConfig {
+            acquire_timeout: self.timeouts.session_acquire_ms.map(Duration::from_millis),
+            ..SessionPoolConfig::default()
+        }
+    }
+
+    /// Sets the configured log levels through `log_levels`. Levels that are not configured are
+    /// left unchanged.
+    pub(crate) fn apply_logging(&self) -> Result<(), SecurityModuleError> {
+        if let Some(level) = &self.logging.level {
+            log_levels::set_default_level(parse_level(level)?);
+        }
+        for (target, level) in &self.logging.targets {
+            let level = parse_level(level)?;
+            if PROVIDER_NAMES.contains(&target.as_str()) {
+                if let Some(module) = security_module(target) {
+                    log_levels::set_level(&module, level);
+                }
+            } else {
+                log_levels::set_target_level(target.clone(), level);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parse_millis(name: &str, value: &str) -> Result<Option<u64>, SecurityModuleError> {
+    if value == "off" {
+        return Ok(None);
+    }
+    value
+        .parse()
+        .map(Some)
+        .map_err(|_| invalid(format!("{} must be a number of milliseconds or off", name)))
+}
+
+fn parse_level(level: &str) -> Result<LevelFilter, SecurityModuleError> {
+    LevelFilter::from_str(level).map_err(|_| invalid(format!("Unknown log level '{}'", level)))
+}
+
+/// Returns the security module of a provider name, or `None` if it is disabled in this build.
+pub(crate) fn security_module(name: &str) -> Option<SecurityModule> {
+    match name {
+        #[cfg(feature = "win")]
+        "windows" => Some(SecurityModule::Tpm(TpmType::Windows)),
+        #[cfg(feature = "macos")]
+        "macos" => Some(SecurityModule::Tpm(TpmType::MacOs)),
+        #[cfg(feature = "linux")]
+        "linux" => Some(SecurityModule::Tpm(TpmType::Linux)),
+        #[cfg(feature = "android")]
+        "android" => Some(SecurityModule::Tpm(TpmType::Android(AndroidTpmType::Knox))),
+        #[cfg(feature = "android")]
+        "android_keystore" => Some(SecurityModule::Tpm(TpmType::Android(
+            AndroidTpmType::Keystore,
+        ))),
+        #[cfg(feature = "hsm")]
+        "nitrokey" => Some(SecurityModule::Hsm(HsmType::NitroKey)),
+        #[cfg(feature = "hsm")]
+        "yubikey" => Some(SecurityModule::Hsm(HsmType::YubiKey)),
+        #[cfg(feature = "nks")]
+        "nks" => Some(SecurityModule::Nks),
+        _ => None,
+    }
+}
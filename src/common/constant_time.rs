@@ -0,0 +1,15 @@
+//! Comparisons of MACs, authentication tags and other secrets in constant time.
+//!
+//! Comparing a received tag with the expected one using `==` stops at the first differing byte,
+//! so the time a verification takes reveals how many leading bytes of a forged tag are correct.
+//! [`eq`] compares every byte with `subtle` regardless of where the inputs differ.
+
+use subtle::ConstantTimeEq;
+
+/// Returns whether `a` and `b` are equal, in time that only depends on their lengths.
+///
+/// Inputs of different lengths are unequal. Their lengths are not considered secret, so this
+/// returns early if they differ.
+pub fn eq(a: &[u8], b: &[u8]) -> bool {
+    a.ct_eq(b).into()
+}
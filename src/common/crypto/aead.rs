@@ -0,0 +1,121 @@
+//! Authenticated encryption of envelope payloads.
+//!
+//! `seal` encrypts a payload into an envelope and `open` decrypts it again. The encoded header of
+//! the envelope is passed to the AEAD as associated data, so the algorithm, key id, nonce and all
+//! other header fields are authenticated together with the payload.
+
+use super::{
+    envelope::{AeadAlgorithm, Envelope, EnvelopeRef},
+    secret::SecretBytes,
+};
+use crate::common::{error::SecurityModuleError, fips};
+use openssl::{
+    rand::rand_bytes,
+    symm::{decrypt_aead, encrypt_aead, Cipher},
+};
+
+/// Returns a random nonce of the length required by `aead`.
+///
+/// # Returns
+///
+/// A `Result` containing the nonce, or a `SecurityModuleError::EncryptionError` if the random
+/// number generator fails.
+pub fn random_nonce(aead: AeadAlgorithm) -> Result<Vec<u8>, SecurityModuleError> {
+    let mut nonce = vec![0; aead.nonce_len()];
+    rand_bytes(&mut nonce).map_err(|e| SecurityModuleError::EncryptionError(e.to_string()))?;
+    Ok(nonce)
+}
+
+/// Encrypts `plaintext` into `envelope`.
+///
+/// The algorithm and nonce are taken from the envelope. A nonce must never be reused with the
+/// same key, use `random_nonce` unless the nonce is derived otherwise.
+///
+/// # Arguments
+///
+/// * `envelope` - The envelope describing the payload, its ciphertext is replaced.
+/// * `key` - The payload key, `envelope.aead.key_len()` bytes long.
+/// * `plaintext` - The data to be encrypted.
+///
+/// # Returns
+///
+/// A `Result` containing the encoded envelope, or a `SecurityModuleError` if the key has the
+/// wrong length, the envelope cannot be encoded or the algorithm is not approved in FIPS mode.
+pub fn seal(
+    mut envelope: Envelope,
+    key: &[u8],
+    plaintext: &[u8],
+) -> Result<Vec<u8>, SecurityModuleError> {
+    if fips::is_enabled() {
+        fips::check_aead(envelope.aead)?;
+    }
+    let cipher = cipher(envelope.aead, key)
+        .map_err(|e| SecurityModuleError::EncryptionError(e.to_owned()))?;
+    let header = envelope.header()?;
+
+    let mut tag = vec![0; envelope.aead.tag_len()];
+    let mut ciphertext = encrypt_aead(
+        cipher,
+        key,
+        Some(&envelope.nonce),
+        &header,
+        plaintext,
+        &mut tag,
+    )
+    .map_err(|e| SecurityModuleError::EncryptionError(e.to_string()))?;
+    ciphertext.extend_from_slice(&tag);
+
+    envelope.ciphertext = ciphertext;
+    Ok(envelope.to_bytes()?)
+}
+
+/// Decrypts the payload of `envelope`.
+///
+/// # Arguments
+///
+/// * `envelope` - The parsed envelope.
+/// * `key` - The payload key, `envelope.aead.key_len()` bytes long.
+///
+/// # Returns
+///
+/// A `Result` containing the plaintext, which is zeroed when dropped, or a
+/// `SecurityModuleError::DecryptionError` if the key has the wrong length or the payload or
+/// header were modified, or a `SecurityModuleError::NotFipsApproved` if the algorithm is not
+/// approved in FIPS mode.
+pub fn open(envelope: &EnvelopeRef<'_>, key: &[u8]) -> Result<SecretBytes, SecurityModuleError> {
+    if fips::is_enabled() {
+        fips::check_aead(envelope.aead)?;
+    }
+    let cipher = cipher(envelope.aead, key)
+        .map_err(|e| SecurityModuleError::DecryptionError(e.to_owned()))?;
+    let split = envelope
+        .ciphertext
+        .len()
+        .checked_sub(envelope.aead.tag_len())
+        .ok_or_else(|| {
+            SecurityModuleError::DecryptionError("Ciphertext is truncated".to_owned())
+        })?;
+    let (ciphertext, tag) = envelope.ciphertext.split_at(split);
+
+    decrypt_aead(
+        cipher,
+        key,
+        Some(envelope.nonce),
+        envelope.header,
+        ciphertext,
+        tag,
+    )
+    .map(SecretBytes::new)
+    .map_err(|_| SecurityModuleError::DecryptionError("Authentication failed".to_owned()))
+}
+
+fn cipher(aead: AeadAlgorithm, key: &[u8]) -> Result<Cipher, &'static str> {
+    if key.len() != aead.key_len() {
+        return Err("Invalid key length");
+    }
+    Ok(match aead {
+        AeadAlgorithm::Aes128Gcm => Cipher::aes_128_gcm(),
+        AeadAlgorithm::Aes256Gcm => Cipher::aes_256_gcm(),
+        AeadAlgorithm::ChaCha20Poly1305 => Cipher::chacha20_poly1305(),
+    })
+}
@@ -33,6 +33,7 @@ use serde::{Deserialize, Serialize};
 
 #[repr(C)]
 #[derive(Clone, Debug, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum AsymmetricEncryption {
     /// RSA encryption with selectable key sizes.
     ///
@@ -228,6 +229,7 @@ pub enum EccCurves {
 /// facilitating ABI compatibility and interfacing with C code.
 #[repr(C)]
 #[derive(Clone, Debug, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BlockCiphers {
     /// AES (Advanced Encryption Standard) block cipher with selectable key sizes and modes.
     Aes(SymmetricMode, KeyBits),
@@ -267,6 +269,7 @@ impl Default for BlockCiphers {
 /// `#[repr(C)]` attribute is used for C compatibility.
 #[repr(C)]
 #[derive(Clone, Debug, Default, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SymmetricMode {
     /// AES in Galois/Counter Mode (GCM) with selectable key sizes.
     /// GCM is preferred for its performance and security, providing both encryption and authentication.
@@ -318,6 +321,7 @@ pub enum SymmetricMode {
 /// Uses `#[repr(C)]` for C language compatibility.
 #[repr(C)]
 #[derive(Clone, Debug, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TripleDesNumKeys {
     /// Two-key Triple DES, using two different keys for encryption.
     Tdes2,
@@ -345,6 +349,7 @@ pub enum TripleDesNumKeys {
 /// Marked with `#[repr(C)]` to ensure compatibility with C-based environments.
 #[repr(C)]
 #[derive(Clone, Debug, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Rc2KeyBits {
     /// RC2 with a 40-bit key.
     Rc2_40,
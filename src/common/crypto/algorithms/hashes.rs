@@ -9,6 +9,7 @@
 /// Prefer using more secure algorithms like SHA-2 or SHA-3 for cryptographic purposes.
 #[repr(C)]
 #[derive(Clone, Debug, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Hash {
     /// SHA-1 hashing algorithm.
     ///
@@ -70,6 +71,7 @@ impl Default for Hash {
 /// `#[repr(C)]` attribute is used for C compatibility, facilitating interoperability with C-based systems.
 #[repr(C)]
 #[derive(Clone, Debug, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Sha2Bits {
     /// 224-bit digest size.
     Sha224,
@@ -142,6 +144,7 @@ impl From<Sha2Bits> for u32 {
 /// Uses `#[repr(C)]` for C language compatibility, important for interoperability with C-based systems.
 #[repr(C)]
 #[derive(Clone, Debug, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Sha3Bits {
     /// 224-bit digest size for SHA-3.
     Sha3_224,
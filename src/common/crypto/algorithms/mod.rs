@@ -21,6 +21,7 @@ pub mod hashes;
 /// This enum can be converted to and from `u32` values using the `From` trait implementations.
 #[repr(C)]
 #[derive(Clone, Debug, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum KeyBits {
     Bits128,
     Bits192,
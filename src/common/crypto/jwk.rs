@@ -0,0 +1,322 @@
+//! JSON Web Keys (RFC 7517) for exchanging public keys with web backends.
+//!
+//! Most web backends publish and accept public keys as JWKs rather than DER, often collected in
+//! a JWK Set served from a `jwks_uri`. `to_jwk` exports the public key of a security module key
+//! and `from_jwk` imports the key of a peer, e.g. to verify its signatures with a
+//! `VerifyingKey`:
+//!
+//! ```rust,ignore
+//! use crypto_layer::common::crypto::jwk::{self, JwkSet};
+//!
+//! // Publish the signing keys of the device.
+//! let jwks = JwkSet::from_metadata([&provider.key_metadata()?])?;
+//! let body = jwks.to_json()?.to_string();
+//!
+//! // Verify a token of the backend with the key named in its header.
+//! let jwks = JwkSet::from_json(&response_body)?;
+//! let key = jwks.verifying_key(kid).ok_or(SecurityModuleError::KeyError)?;
+//! ```
+//!
+//! Supported are `EC` keys on P-256, P-384 and P-521, `RSA` keys and `OKP` keys on Ed25519. The
+//! `alg` member carries the hash and, for RSA keys, the signature padding of the public key, so
+//! both survive the round trip. Without `alg`, EC keys use the hash matching the curve and RSA
+//! keys PKCS#1 v1.5 signatures with SHA-256.
+
+use super::{
+    algorithms::{
+        encryption::{AsymmetricEncryption, EccCurves, EccSchemeAlgorithm},
+        hashes::{Hash, Sha2Bits},
+    },
+    key_metadata::KeyMetadata,
+    public_key::{rsa_key_bits, PublicKey, RsaSignaturePadding},
+    verifying_key::VerifyingKey,
+};
+use crate::common::{capability_token::jws_algorithm, error::SecurityModuleError};
+use base64::{prelude::BASE64_URL_SAFE_NO_PAD, Engine};
+use crypto_layer_core::CoreError;
+use openssl::{
+    bn::BigNum,
+    pkey::{Id, PKey},
+};
+use serde_json::{Map, Value};
+
+/// Returns `public_key` as JWK with its `alg` and, if given, the key id `kid`.
+///
+/// # Returns
+///
+/// A `Result` containing the JWK, or a `SecurityModuleError::UnsupportedAlgorithm` if the key
+/// is neither an ECDSA key on P-256, P-384 or P-521, an RSA key nor an Ed25519 key.
+pub fn to_jwk(public_key: &PublicKey, kid: Option<&str>) -> Result<Value, SecurityModuleError> {
+    let mut members = required_members(public_key)?
+        .into_iter()
+        .map(|(name, value)| (name.to_owned(), Value::String(value)))
+        .collect::<Map<_, _>>();
+    // Keys whose hash does not fit the curve have no JWS algorithm and are exported without.
+    if let Ok(alg) = jws_algorithm(public_key) {
+        members.insert("alg".to_owned(), alg.name.into());
+    }
+    if let Some(kid) = kid {
+        members.insert("kid".to_owned(), kid.into());
+    }
+    Ok(Value::Object(members))
+}
+
+/// Parses a public key from a JWK, see the module documentation for the supported keys.
+///
+/// # Returns
+///
+/// A `Result` containing the `PublicKey` on success, a `SecurityModuleError::Encoding` if a
+/// member is missing or malformed, a `SecurityModuleError::InvalidPublicKey` if the key is
+/// invalid or does not fit its `alg`, or a `SecurityModuleError::UnsupportedAlgorithm` if its
+/// type, curve, size or `alg` is not supported.
+pub fn from_jwk(jwk: &Value) -> Result<PublicKey, SecurityModuleError> {
+    let alg = jwk
+        .get("alg")
+        .map(|alg| alg.as_str().ok_or(CoreError::InvalidField("alg")))
+        .transpose()?;
+
+    let public_key = match member(jwk, "kty")? {
+        "EC" => {
+            let (curve, hash) = match member(jwk, "crv")? {
+                "P-256" => (EccCurves::P256, Sha2Bits::Sha256),
+                "P-384" => (EccCurves::P384, Sha2Bits::Sha384),
+                "P-521" => (EccCurves::P521, Sha2Bits::Sha512),
+                _ => return Err(SecurityModuleError::UnsupportedAlgorithm),
+            };
+            PublicKey::from_ec_coordinates(
+                &decoded_member(jwk, "x")?,
+                &decoded_member(jwk, "y")?,
+                AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(curve)),
+                Hash::Sha2(hash),
+            )?
+        }
+        "RSA" => {
+            let modulus = decoded_member(jwk, "n")?;
+            let (hash, padding) = match alg {
+                Some(alg) => rsa_alg(alg)?,
+                None => (Sha2Bits::Sha256, RsaSignaturePadding::Pkcs1),
+            };
+            let bits = BigNum::from_slice(&modulus)
+                .map_err(|_| SecurityModuleError::InvalidPublicKey)?
+                .num_bits();
+            PublicKey::from_rsa_components(
+                &modulus,
+                &decoded_member(jwk, "e")?,
+                AsymmetricEncryption::Rsa(rsa_key_bits(bits as usize)?),
+                Hash::Sha2(hash),
+            )?
+            .with_rsa_padding(padding)
+        }
+        "OKP" => {
+            if member(jwk, "crv")? != "Ed25519" {
+                return Err(SecurityModuleError::UnsupportedAlgorithm);
+            }
+            let der = PKey::public_key_from_raw_bytes(&decoded_member(jwk, "x")?, Id::ED25519)
+                .and_then(|key| key.public_key_to_der())
+                .map_err(|_| SecurityModuleError::InvalidPublicKey)?;
+            PublicKey::from_der(
+                &der,
+                AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(EccCurves::Curve25519)),
+                Hash::Sha2(Sha2Bits::Sha512),
+            )?
+        }
+        _ => return Err(SecurityModuleError::UnsupportedAlgorithm),
+    };
+
+    if alg.is_some_and(|alg| jws_algorithm(&public_key).map_or(true, |jws| jws.name != alg)) {
+        return Err(SecurityModuleError::InvalidPublicKey);
+    }
+    Ok(public_key)
+}
+
+/// A JWK Set (RFC 7517, section 5) of public keys, optionally named by a key id.
+#[derive(Clone, Debug, Default)]
+pub struct JwkSet {
+    keys: Vec<(Option<String>, PublicKey)>,
+}
+
+impl JwkSet {
+    /// Creates an empty JWK Set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a JWK Set of the public keys of `keys`, named by their key ids.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the JWK Set, or a `SecurityModuleError::UnsupportedAlgorithm` if a
+    /// key has no JWK, see `to_jwk`.
+    pub fn from_metadata<'a>(
+        keys: impl IntoIterator<Item = &'a KeyMetadata>,
+    ) -> Result<Self, SecurityModuleError> {
+        let mut jwks = Self::new();
+        for metadata in keys {
+            // Fail early instead of when the set is serialized.
+            required_members(metadata.public_key())?;
+            jwks.insert(metadata.key_id(), metadata.public_key().clone());
+        }
+        Ok(jwks)
+    }
+
+    /// Parses a JWK Set.
+    ///
+    /// Keys that are malformed or not supported are ignored, as RFC 7517 recommends, since JWK
+    /// Sets often contain keys for other purposes.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the JWK Set, or a `SecurityModuleError::Encoding` if `json` is no
+    /// JSON object with a `keys` array.
+    pub fn from_json(json: &str) -> Result<Self, SecurityModuleError> {
+        let jwks: Value =
+            serde_json::from_str(json).map_err(|_| CoreError::InvalidField("jwks"))?;
+        let keys = jwks
+            .get("keys")
+            .ok_or(CoreError::MissingField("keys"))?
+            .as_array()
+            .ok_or(CoreError::InvalidField("keys"))?;
+        let keys = keys
+            .iter()
+            .filter_map(|jwk| match from_jwk(jwk) {
+                Ok(public_key) => {
+                    let kid = jwk.get("kid").and_then(Value::as_str).map(str::to_owned);
+                    Some((kid, public_key))
+                }
+                Err(error) => {
+                    tracing::debug!(%error, "Ignoring a key of the JWK Set");
+                    None
+                }
+            })
+            .collect();
+        Ok(Self { keys })
+    }
+
+    /// Returns the JWK Set as JSON object with a `keys` array.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the JWK Set, or a `SecurityModuleError::UnsupportedAlgorithm` if a
+    /// key has no JWK, see `to_jwk`.
+    pub fn to_json(&self) -> Result<Value, SecurityModuleError> {
+        let keys = self
+            .keys
+            .iter()
+            .map(|(kid, public_key)| to_jwk(public_key, kid.as_deref()))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(serde_json::json!({ "keys": keys }))
+    }
+
+    /// Adds a public key named `kid`, replacing the key of the same name.
+    pub fn insert(&mut self, kid: impl Into<String>, public_key: PublicKey) {
+        let kid = Some(kid.into());
+        match self.keys.iter_mut().find(|(name, _)| *name == kid) {
+            Some(entry) => entry.1 = public_key,
+            None => self.keys.push((kid, public_key)),
+        }
+    }
+
+    /// Returns the public key named `kid`.
+    pub fn get(&self, kid: &str) -> Option<&PublicKey> {
+        self.keys
+            .iter()
+            .find(|(name, _)| name.as_deref() == Some(kid))
+            .map(|(_, public_key)| public_key)
+    }
+
+    /// Returns a `VerifyingKey` for the public key named `kid`.
+    pub fn verifying_key(&self, kid: &str) -> Option<VerifyingKey> {
+        self.get(kid).cloned().map(VerifyingKey::new)
+    }
+
+    /// Returns the key ids and public keys in the order of the set.
+    pub fn iter(&self) -> impl Iterator<Item = (Option<&str>, &PublicKey)> {
+        self.keys
+            .iter()
+            .map(|(kid, public_key)| (kid.as_deref(), public_key))
+    }
+
+    /// Returns the number of keys.
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Returns whether the set contains no keys.
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+}
+
+/// Returns the required members of the JWK of `public_key` in lexicographic order, from which
+/// the JWK thumbprint (RFC 7638) is computed.
+pub(crate) fn required_members(
+    public_key: &PublicKey,
+) -> Result<Vec<(&'static str, String)>, SecurityModuleError> {
+    let encode = |bytes: &[u8]| BASE64_URL_SAFE_NO_PAD.encode(bytes);
+    match public_key.algorithm() {
+        AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(EccCurves::Curve25519)) => {
+            let raw = PKey::public_key_from_der(&public_key.to_der()?)
+                .and_then(|key| key.raw_public_key())
+                .map_err(|_| SecurityModuleError::InvalidPublicKey)?;
+            Ok(vec![
+                ("crv", "Ed25519".to_owned()),
+                ("kty", "OKP".to_owned()),
+                ("x", encode(&raw)),
+            ])
+        }
+        AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(curve)) => {
+            let (name, scalar_len) = match curve {
+                EccCurves::P256 => ("P-256", 32),
+                EccCurves::P384 => ("P-384", 48),
+                EccCurves::P521 => ("P-521", 66),
+                _ => return Err(SecurityModuleError::UnsupportedAlgorithm),
+            };
+            // The uncompressed point is 0x04 || x || y.
+            let point = public_key.to_ec_point()?;
+            let (x, y) = point[1..].split_at(scalar_len);
+            Ok(vec![
+                ("crv", name.to_owned()),
+                ("kty", "EC".to_owned()),
+                ("x", encode(x)),
+                ("y", encode(y)),
+            ])
+        }
+        AsymmetricEncryption::Rsa(_) => {
+            let rsa = PKey::public_key_from_der(&public_key.to_der()?)
+                .and_then(|key| key.rsa())
+                .map_err(|_| SecurityModuleError::InvalidPublicKey)?;
+            Ok(vec![
+                ("e", encode(&rsa.e().to_vec())),
+                ("kty", "RSA".to_owned()),
+                ("n", encode(&rsa.n().to_vec())),
+            ])
+        }
+        _ => Err(SecurityModuleError::UnsupportedAlgorithm),
+    }
+}
+
+/// Returns the hash and padding of an RSA JWS algorithm.
+fn rsa_alg(alg: &str) -> Result<(Sha2Bits, RsaSignaturePadding), SecurityModuleError> {
+    match alg {
+        "RS256" => Ok((Sha2Bits::Sha256, RsaSignaturePadding::Pkcs1)),
+        "RS384" => Ok((Sha2Bits::Sha384, RsaSignaturePadding::Pkcs1)),
+        "RS512" => Ok((Sha2Bits::Sha512, RsaSignaturePadding::Pkcs1)),
+        "PS256" => Ok((Sha2Bits::Sha256, RsaSignaturePadding::Pss)),
+        "PS384" => Ok((Sha2Bits::Sha384, RsaSignaturePadding::Pss)),
+        "PS512" => Ok((Sha2Bits::Sha512, RsaSignaturePadding::Pss)),
+        _ => Err(SecurityModuleError::UnsupportedAlgorithm),
+    }
+}
+
+fn member<'a>(jwk: &'a Value, name: &'static str) -> Result<&'a str, CoreError> {
+    jwk.get(name)
+        .ok_or(CoreError::MissingField(name))?
+        .as_str()
+        .ok_or(CoreError::InvalidField(name))
+}
+
+fn decoded_member(jwk: &Value, name: &'static str) -> Result<Vec<u8>, CoreError> {
+    BASE64_URL_SAFE_NO_PAD
+        .decode(member(jwk, name)?)
+        .map_err(|_| CoreError::InvalidField(name))
+}
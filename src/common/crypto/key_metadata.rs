@@ -0,0 +1,158 @@
+use super::public_key::PublicKey;
+#[cfg(feature = "serde")]
+use super::{
+    algorithms::{encryption::AsymmetricEncryption, hashes::Hash},
+    public_key::RsaSignaturePadding,
+};
+use crate::common::error::SecurityModuleError;
+use std::time::SystemTime;
+
+/// Information about a key pair that is expensive to fetch from the security module.
+///
+/// Providers compute the metadata once when a key is created or loaded and serve it from memory
+/// afterwards, so that verifications and enrollments do not round-trip through the security
+/// module. `Provider::refresh_key_metadata` fetches it again, e.g. after the attestation
+/// certificates of a key were renewed.
+///
+/// With the `serde` feature, the public key is serialized as DER together with its algorithms.
+#[derive(Clone, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "KeyMetadataFields", into = "KeyMetadataFields")
+)]
+pub struct KeyMetadata {
+    key_id: String,
+    public_key: PublicKey,
+    public_key_der: Vec<u8>,
+    attestation: Option<Vec<Vec<u8>>>,
+    exportable: bool,
+    fetched_at: SystemTime,
+}
+
+impl KeyMetadata {
+    /// Creates the metadata of a key pair without attestation data.
+    ///
+    /// # Arguments
+    ///
+    /// * `key_id` - The identifier of the key pair.
+    /// * `public_key` - The public key exported from the security module.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `KeyMetadata` on success, or a
+    /// `SecurityModuleError::InvalidPublicKey` if the public key cannot be DER encoded.
+    pub fn new(
+        key_id: impl Into<String>,
+        public_key: PublicKey,
+    ) -> Result<Self, SecurityModuleError> {
+        let public_key_der = public_key.to_der()?;
+        Ok(Self {
+            key_id: key_id.into(),
+            public_key,
+            public_key_der,
+            attestation: None,
+            exportable: false,
+            fetched_at: SystemTime::now(),
+        })
+    }
+
+    /// Adds the attestation certificate chain of the key pair.
+    ///
+    /// # Arguments
+    ///
+    /// * `chain` - The DER encoded certificates, starting with the certificate of the key pair.
+    pub fn with_attestation(mut self, chain: Vec<Vec<u8>>) -> Self {
+        self.attestation = Some(chain);
+        self
+    }
+
+    /// Records that the private key was created exportable, see `KeySpec::exportable`.
+    pub fn with_exportable(mut self, exportable: bool) -> Self {
+        self.exportable = exportable;
+        self
+    }
+
+    /// Returns the identifier of the key pair.
+    pub fn key_id(&self) -> &str {
+        &self.key_id
+    }
+
+    /// Returns the public key of the key pair.
+    pub fn public_key(&self) -> &PublicKey {
+        &self.public_key
+    }
+
+    /// Returns the public key as DER encoded `SubjectPublicKeyInfo` structure.
+    pub fn public_key_der(&self) -> &[u8] {
+        &self.public_key_der
+    }
+
+    /// Returns the DER encoded attestation certificate chain, starting with the certificate of
+    /// the key pair, or `None` if the security module does not attest its keys.
+    pub fn attestation(&self) -> Option<&[Vec<u8>]> {
+        self.attestation.as_deref()
+    }
+
+    /// Returns whether the private key can be exported with `Provider::export_private_key`.
+    pub fn exportable(&self) -> bool {
+        self.exportable
+    }
+
+    /// Returns when the metadata was fetched from the security module.
+    pub fn fetched_at(&self) -> SystemTime {
+        self.fetched_at
+    }
+}
+
+/// The serialized representation of `KeyMetadata`.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct KeyMetadataFields {
+    key_id: String,
+    algorithm: AsymmetricEncryption,
+    hash: Hash,
+    #[serde(default)]
+    rsa_padding: RsaSignaturePadding,
+    public_key_der: Vec<u8>,
+    attestation: Option<Vec<Vec<u8>>>,
+    #[serde(default)]
+    exportable: bool,
+    fetched_at: SystemTime,
+}
+
+#[cfg(feature = "serde")]
+impl From<KeyMetadata> for KeyMetadataFields {
+    fn from(metadata: KeyMetadata) -> Self {
+        Self {
+            key_id: metadata.key_id,
+            algorithm: metadata.public_key.algorithm(),
+            hash: metadata.public_key.hash(),
+            rsa_padding: metadata.public_key.rsa_padding(),
+            public_key_der: metadata.public_key_der,
+            attestation: metadata.attestation,
+            exportable: metadata.exportable,
+            fetched_at: metadata.fetched_at,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<KeyMetadataFields> for KeyMetadata {
+    type Error = SecurityModuleError;
+
+    fn try_from(fields: KeyMetadataFields) -> Result<Self, Self::Error> {
+        let public_key =
+            PublicKey::from_der(&fields.public_key_der, fields.algorithm, fields.hash)?
+                .with_rsa_padding(fields.rsa_padding);
+        Ok(Self {
+            key_id: fields.key_id,
+            public_key,
+            public_key_der: fields.public_key_der,
+            attestation: fields.attestation,
+            exportable: fields.exportable,
+            fetched_at: fields.fetched_at,
+        })
+    }
+}
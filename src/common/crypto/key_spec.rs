@@ -0,0 +1,379 @@
+//! Provider-independent specification of a key.
+//!
+//! A `KeySpec` describes what a key is and what it may be used for. `KeySpec::builder` checks
+//! that the fields fit together before any security module is touched, e.g. that an AES key is
+//! not meant for signing, and the provider configurations are derived from the validated spec:
+//!
+//! ```rust,ignore
+//! use crypto_layer::common::crypto::key_spec::{AccessControl::*, KeyAlgorithm::*, KeyPurpose::*, KeySpec};
+//!
+//! let spec = KeySpec::builder()
+//!     .algorithm(EcP256)
+//!     .usage(Sign)
+//!     .access(BiometryAny)
+//!     .label("device-identity")
+//!     .build()?;
+//! provider.create_key(spec.label(), Box::new(SecureEnclaveConfig::from_spec(&spec)?))?;
+//! ```
+
+use super::{
+    algorithms::{
+        encryption::{
+            AsymmetricEncryption, BlockCiphers, EccCurves, EccSchemeAlgorithm, SymmetricMode,
+        },
+        hashes::{Hash, Sha2Bits},
+        KeyBits,
+    },
+    KeyUsage,
+};
+use crate::common::{
+    error::SecurityModuleError,
+    factory::SecModules,
+    key_id::{KeyId, MAX_KEY_ID_LEN},
+    plan::KeyPlan,
+};
+use std::collections::BTreeSet;
+
+/// The maximum length of a label in bytes.
+pub const MAX_LABEL_LEN: usize = MAX_KEY_ID_LEN;
+
+/// The algorithm and size of a key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum KeyAlgorithm {
+    /// An elliptic curve key on NIST P-256.
+    EcP256,
+    /// An elliptic curve key on NIST P-384.
+    EcP384,
+    /// An elliptic curve key on NIST P-521.
+    EcP521,
+    Rsa2048,
+    Rsa3072,
+    Rsa4096,
+    /// A 128 bit AES key used in GCM mode.
+    Aes128Gcm,
+    /// A 256 bit AES key used in GCM mode.
+    Aes256Gcm,
+}
+
+impl KeyAlgorithm {
+    /// Returns whether the algorithm uses a single secret key instead of a key pair.
+    pub fn is_symmetric(self) -> bool {
+        matches!(self, KeyAlgorithm::Aes128Gcm | KeyAlgorithm::Aes256Gcm)
+    }
+
+    fn curve(self) -> Option<EccCurves> {
+        match self {
+            KeyAlgorithm::EcP256 => Some(EccCurves::P256),
+            KeyAlgorithm::EcP384 => Some(EccCurves::P384),
+            KeyAlgorithm::EcP521 => Some(EccCurves::P521),
+            _ => None,
+        }
+    }
+
+    /// The hash that matches the security level of the key.
+    fn default_hash(self) -> Hash {
+        match self {
+            KeyAlgorithm::EcP384 | KeyAlgorithm::Rsa3072 => Hash::Sha2(Sha2Bits::Sha384),
+            KeyAlgorithm::EcP521 | KeyAlgorithm::Rsa4096 => Hash::Sha2(Sha2Bits::Sha512),
+            _ => Hash::Sha2(Sha2Bits::Sha256),
+        }
+    }
+}
+
+/// An operation a key may be used for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum KeyPurpose {
+    /// Signing data and verifying signatures.
+    Sign,
+    /// Encrypting and decrypting data.
+    Encrypt,
+    /// Deriving shared secrets with the public keys of other parties, e.g. with ECDH.
+    KeyAgreement,
+}
+
+/// The user authentication the security module requires before it uses a key.
+///
+/// Providers that cannot enforce the requested access control reject the key when it is
+/// created instead of silently creating a weaker one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum AccessControl {
+    /// The key can be used whenever the device is unlocked.
+    #[default]
+    None,
+    /// Any biometric or the device passcode.
+    UserPresence,
+    /// Any enrolled biometric, including biometrics enrolled after the key was created.
+    BiometryAny,
+    /// A biometric enrolled when the key was created. Enrolling another one invalidates the key.
+    BiometryCurrentSet,
+    /// The device passcode.
+    DevicePasscode,
+}
+
+/// A validated specification of a key, created with `KeySpec::builder`.
+///
+/// With the `serde` feature, deserialized specs are validated like built ones.
+#[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "KeySpecBuilder")
+)]
+pub struct KeySpec {
+    algorithm: KeyAlgorithm,
+    purposes: BTreeSet<KeyPurpose>,
+    access: AccessControl,
+    label: KeyId,
+    hash: Hash,
+    exportable: bool,
+}
+
+impl KeySpec {
+    /// Returns a builder without any field set.
+    pub fn builder() -> KeySpecBuilder {
+        KeySpecBuilder::default()
+    }
+
+    pub fn algorithm(&self) -> KeyAlgorithm {
+        self.algorithm
+    }
+
+    /// Returns the operations the key may be used for, in a stable order.
+    pub fn purposes(&self) -> impl Iterator<Item = KeyPurpose> + '_ {
+        self.purposes.iter().copied()
+    }
+
+    /// Returns whether the key may be used for `purpose`.
+    pub fn allows(&self, purpose: KeyPurpose) -> bool {
+        self.purposes.contains(&purpose)
+    }
+
+    pub fn access(&self) -> AccessControl {
+        self.access
+    }
+
+    /// Returns the label of the key, which is used as the key id by the providers.
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    /// Returns the label of the key as a validated `KeyId`.
+    pub fn key_id(&self) -> &KeyId {
+        &self.label
+    }
+
+    /// Returns the hash used for signing, which defaults to the SHA-2 variant matching the
+    /// security level of the key.
+    pub fn hash(&self) -> Hash {
+        self.hash
+    }
+
+    /// Returns whether the private key may leave the security module through
+    /// `Provider::export_private_key`. Keys are not exportable unless requested.
+    pub fn exportable(&self) -> bool {
+        self.exportable
+    }
+
+    /// Returns the asymmetric algorithm of the key, or `None` for symmetric keys.
+    ///
+    /// Elliptic curve keys map to ECDSA if they are used for signing and to ECDH if they are used
+    /// for key agreement.
+    pub fn asymmetric_algorithm(&self) -> Option<AsymmetricEncryption> {
+        let bits = match self.algorithm {
+            KeyAlgorithm::Rsa2048 => KeyBits::Bits2048,
+            KeyAlgorithm::Rsa3072 => KeyBits::Bits3072,
+            KeyAlgorithm::Rsa4096 => KeyBits::Bits4096,
+            algorithm => {
+                let curve = algorithm.curve()?;
+                let scheme = if self.allows(KeyPurpose::KeyAgreement) {
+                    EccSchemeAlgorithm::EcDh(curve)
+                } else {
+                    EccSchemeAlgorithm::EcDsa(curve)
+                };
+                return Some(AsymmetricEncryption::Ecc(scheme));
+            }
+        };
+        Some(AsymmetricEncryption::Rsa(bits))
+    }
+
+    /// Returns the symmetric algorithm of the key, or `None` for key pairs.
+    pub fn symmetric_algorithm(&self) -> Option<BlockCiphers> {
+        match self.algorithm {
+            KeyAlgorithm::Aes128Gcm => {
+                Some(BlockCiphers::Aes(SymmetricMode::Gcm, KeyBits::Bits128))
+            }
+            KeyAlgorithm::Aes256Gcm => {
+                Some(BlockCiphers::Aes(SymmetricMode::Gcm, KeyBits::Bits256))
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the usages of the key in the terms of the TPM.
+    pub fn key_usages(&self) -> Vec<KeyUsage> {
+        let mut usages = Vec::new();
+        for purpose in self.purposes() {
+            let usage = match purpose {
+                KeyPurpose::Sign => KeyUsage::SignEncrypt,
+                KeyPurpose::Encrypt if self.algorithm.is_symmetric() => KeyUsage::SignEncrypt,
+                KeyPurpose::Encrypt | KeyPurpose::KeyAgreement => KeyUsage::Decrypt,
+            };
+            if !usages.contains(&usage) {
+                usages.push(usage);
+            }
+        }
+        usages
+    }
+}
+
+/// Collects the fields of a `KeySpec`, see `KeySpec::builder`.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(default, deny_unknown_fields)
+)]
+pub struct KeySpecBuilder {
+    algorithm: Option<KeyAlgorithm>,
+    purposes: BTreeSet<KeyPurpose>,
+    access: AccessControl,
+    label: Option<String>,
+    hash: Option<Hash>,
+    exportable: bool,
+}
+
+impl KeySpecBuilder {
+    /// Sets the algorithm of the key. Required.
+    pub fn algorithm(mut self, algorithm: KeyAlgorithm) -> Self {
+        self.algorithm = Some(algorithm);
+        self
+    }
+
+    /// Allows the key to be used for `purpose`. At least one purpose is required; calling this
+    /// again adds another one.
+    pub fn usage(mut self, purpose: KeyPurpose) -> Self {
+        self.purposes.insert(purpose);
+        self
+    }
+
+    /// Sets the user authentication required to use the key. Defaults to `AccessControl::None`.
+    pub fn access(mut self, access: AccessControl) -> Self {
+        self.access = access;
+        self
+    }
+
+    /// Sets the label of the key, which is used as the key id. Required.
+    pub fn label(mut self, label: impl Into<String>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    /// Sets the hash used for signing.
+    pub fn hash(mut self, hash: Hash) -> Self {
+        self.hash = Some(hash);
+        self
+    }
+
+    /// Sets whether the private key may be exported with `Provider::export_private_key`.
+    /// Defaults to `false`. Providers that cannot export keys reject exportable specs.
+    pub fn exportable(mut self, exportable: bool) -> Self {
+        self.exportable = exportable;
+        self
+    }
+
+    /// Validates the fields and creates the spec.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `KeySpec` on success, or a `SecurityModuleError::InvalidKeySpec`
+    /// describing the first field that is missing or does not fit the others.
+    pub fn build(self) -> Result<KeySpec, SecurityModuleError> {
+        let invalid = |message: String| Err(SecurityModuleError::InvalidKeySpec(message));
+
+        let Some(algorithm) = self.algorithm else {
+            return invalid("No algorithm given".to_owned());
+        };
+        if self.purposes.is_empty() {
+            return invalid("No usage given".to_owned());
+        }
+        let label = match self.label {
+            Some(label) if !label.is_empty() => label,
+            _ => return invalid("No label given".to_owned()),
+        };
+        if label.len() > MAX_LABEL_LEN {
+            return invalid(format!("Label is longer than {} bytes", MAX_LABEL_LEN));
+        }
+        if label.chars().any(char::is_control) {
+            return invalid("Label contains control characters".to_owned());
+        }
+        let label = match KeyId::new(label) {
+            Ok(label) => label,
+            Err(SecurityModuleError::InvalidKeyId(message)) => {
+                return invalid(format!("Label is not a valid key id: {}", message))
+            }
+            Err(e) => return Err(e),
+        };
+
+        for purpose in &self.purposes {
+            let supported = match purpose {
+                KeyPurpose::Sign => !algorithm.is_symmetric(),
+                KeyPurpose::Encrypt => algorithm.curve().is_none(),
+                KeyPurpose::KeyAgreement => algorithm.curve().is_some(),
+            };
+            if !supported {
+                return invalid(format!(
+                    "{:?} keys cannot be used for {:?}",
+                    algorithm, purpose
+                ));
+            }
+        }
+        if self.purposes.contains(&KeyPurpose::Sign)
+            && self.purposes.contains(&KeyPurpose::KeyAgreement)
+        {
+            return invalid(format!(
+                "{:?} keys cannot be used for both Sign and KeyAgreement",
+                algorithm
+            ));
+        }
+
+        let hash = self.hash.unwrap_or_else(|| algorithm.default_hash());
+        if self.purposes.contains(&KeyPurpose::Sign)
+            && !matches!(hash, Hash::Sha2(_) | Hash::Sha3(_))
+        {
+            return invalid(format!("{:?} is too weak for signing", hash));
+        }
+
+        Ok(KeySpec {
+            algorithm,
+            purposes: self.purposes,
+            access: self.access,
+            label,
+            hash,
+            exportable: self.exportable,
+        })
+    }
+
+    /// Validates the fields and reports what creating the key would do with the configuration
+    /// of the crate, without touching a security module.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `KeyPlan` on success, or the error of `build`.
+    pub fn plan(self) -> Result<KeyPlan, SecurityModuleError> {
+        KeyPlan::for_config(&SecModules::config(), self.build()?)
+    }
+}
+
+impl TryFrom<KeySpecBuilder> for KeySpec {
+    type Error = SecurityModuleError;
+
+    fn try_from(builder: KeySpecBuilder) -> Result<Self, Self::Error> {
+        builder.build()
+    }
+}
@@ -1,8 +1,20 @@
+pub mod aead;
 pub mod algorithms;
+pub mod jwk;
+pub mod key_metadata;
+pub mod key_spec;
+pub mod operation_context;
 pub mod pkcs;
+pub mod public_key;
+pub mod verifying_key;
+
+pub use crypto_layer_core::{
+    envelope, kdf, redact, secret, signature_format, signing_context, spki,
+};
 
 #[repr(C)]
 #[derive(Eq, Hash, PartialEq, Clone, Debug, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum KeyUsage {
     ClientAuth,
     Decrypt,
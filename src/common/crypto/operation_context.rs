@@ -0,0 +1,140 @@
+use crate::common::{crypto::redact::Redacted, error::SecurityModuleError};
+use base64::{prelude::BASE64_STANDARD, Engine};
+use std::fmt;
+
+/// Reusable buffers for high-frequency cryptographic operations.
+///
+/// Every call to `KeyHandle::sign_data` returns a freshly allocated `Vec<u8>`, and providers
+/// that exchange base64 encoded data with the security module allocate further intermediate
+/// buffers. For services performing thousands of operations per second this allocator traffic
+/// quickly dominates. An `OperationContext` owns an output buffer and a scratch buffer for
+/// encoding and keeps their capacity between operations, so that once the buffers have grown to
+/// the size of the largest operation, subsequent operations do not allocate on the Rust side.
+///
+/// A context is not tied to a key or a provider and can be reused across both. Operations take
+/// the context by mutable reference, so use one context per worker thread.
+///
+/// # Examples
+///
+/// ```rust
+/// use crypto_layer::common::{crypto::operation_context::OperationContext, traits::key_handle::KeyHandle};
+///
+/// # fn sign_all(key: &dyn KeyHandle, messages: &[&[u8]]) -> Result<(), crypto_layer::SecurityModuleError> {
+/// let mut context = OperationContext::with_capacity(512);
+/// for message in messages {
+///     let signature = key.sign_data_into(message, &mut context)?;
+///     // `signature` borrows from `context` and stays valid until the next operation.
+///     println!("{} byte signature", signature.len());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Default)]
+pub struct OperationContext {
+    output: Vec<u8>,
+    scratch: Vec<u8>,
+}
+
+/// Redacts the result of the last operation, which may be a signature or a plaintext.
+impl fmt::Debug for OperationContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OperationContext")
+            .field("output", &Redacted(&self.output))
+            .field("capacity", &self.capacity())
+            .finish_non_exhaustive()
+    }
+}
+
+impl OperationContext {
+    /// Creates an empty context. The buffers grow on first use.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a context whose output and scratch buffers can each hold `capacity` bytes
+    /// without reallocating.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            output: Vec::with_capacity(capacity),
+            scratch: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Returns the result of the last operation that wrote to the output buffer.
+    pub fn output(&self) -> &[u8] {
+        &self.output
+    }
+
+    /// Returns the number of bytes the output and scratch buffers can hold without reallocating.
+    pub fn capacity(&self) -> usize {
+        self.output.capacity().min(self.scratch.capacity())
+    }
+
+    /// Empties both buffers while keeping their capacity.
+    pub fn clear(&mut self) {
+        self.output.clear();
+        self.scratch.clear();
+    }
+
+    /// Releases the memory held by the buffers, e.g. after an unusually large operation.
+    pub fn shrink_to(&mut self, capacity: usize) {
+        self.output.shrink_to(capacity);
+        self.scratch.shrink_to(capacity);
+    }
+
+    /// Replaces the content of the output buffer with `data`.
+    ///
+    /// # Returns
+    ///
+    /// The output buffer.
+    pub fn set_output(&mut self, data: &[u8]) -> &[u8] {
+        self.output.clear();
+        self.output.extend_from_slice(data);
+        &self.output
+    }
+
+    /// Gives mutable access to the output buffer, cleared, for providers writing their result
+    /// directly into the context.
+    pub fn output_mut(&mut self) -> &mut Vec<u8> {
+        self.output.clear();
+        &mut self.output
+    }
+
+    /// Decodes base64 encoded data into the scratch buffer.
+    ///
+    /// # Arguments
+    ///
+    /// * `encoded` - The base64 (standard alphabet, padded) encoded data.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the decoded data on success, or a `SecurityModuleError::InvalidSignature`
+    /// if `encoded` is not valid base64.
+    pub fn decode_base64(&mut self, encoded: &[u8]) -> Result<&[u8], SecurityModuleError> {
+        self.scratch.clear();
+        BASE64_STANDARD
+            .decode_vec(encoded, &mut self.scratch)
+            .map_err(|_| SecurityModuleError::InvalidSignature)?;
+        Ok(&self.scratch)
+    }
+
+    /// Encodes data as base64 into the scratch buffer.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The data to be encoded.
+    ///
+    /// # Returns
+    ///
+    /// The base64 (standard alphabet, padded) encoded data.
+    pub fn encode_base64(&mut self, data: &[u8]) -> &[u8] {
+        let encoded_len =
+            base64::encoded_len(data.len(), true).expect("Overflow when calculating base64 length");
+        self.scratch.clear();
+        self.scratch.resize(encoded_len, 0);
+        BASE64_STANDARD
+            .encode_slice(data, &mut self.scratch)
+            .expect("Scratch buffer is sized exactly");
+        &self.scratch
+    }
+}
@@ -0,0 +1,416 @@
+use super::{
+    algorithms::{
+        encryption::{AsymmetricEncryption, EccCurves},
+        hashes::{Hash, Sha2Bits, Sha3Bits},
+        KeyBits,
+    },
+    signature_format::{self, SignatureFormat},
+};
+use crate::common::error::SecurityModuleError;
+use openssl::{
+    bn::{BigNum, BigNumContext},
+    ec::{EcGroup, EcKey, EcPoint, PointConversionForm},
+    hash::MessageDigest,
+    nid::Nid,
+    pkey::{Id, PKey, Public},
+    rsa::{Padding, Rsa},
+    sign::{RsaPssSaltlen, Verifier},
+};
+use rayon::prelude::*;
+
+/// Specifies the padding scheme used for RSA signatures.
+///
+/// The Secure Enclave provider signs with RSA-PSS, while most other providers use
+/// PKCS#1 v1.5 signatures.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RsaSignaturePadding {
+    /// RSASSA-PKCS1-v1_5 signatures.
+    #[default]
+    Pkcs1,
+    /// RSASSA-PSS signatures with MGF1 and a salt length equal to the digest length.
+    Pss,
+}
+
+/// A public key exported from a security module.
+///
+/// Signature verification does not require the private key, so once the public key of a key
+/// pair has been exported, signatures can be verified in pure Rust without round-tripping
+/// through the security module. This also allows verifying large batches in parallel.
+///
+/// # Examples
+///
+/// ```rust
+/// use crypto_layer::common::crypto::{
+///     algorithms::{encryption::{AsymmetricEncryption, EccCurves, EccSchemeAlgorithm}, hashes::{Hash, Sha2Bits}},
+///     public_key::PublicKey,
+/// };
+///
+/// # fn verify(point: &[u8], data: &[u8], signature: &[u8]) -> Result<bool, crypto_layer::SecurityModuleError> {
+/// let public_key = PublicKey::from_ec_point(
+///     point,
+///     AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(EccCurves::P256)),
+///     Hash::Sha2(Sha2Bits::Sha256),
+/// )?;
+/// public_key.verify(data, signature)
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct PublicKey {
+    algorithm: AsymmetricEncryption,
+    hash: Hash,
+    rsa_padding: RsaSignaturePadding,
+    key: PKey<Public>,
+}
+
+impl PublicKey {
+    /// Creates a `PublicKey` from a DER encoded `SubjectPublicKeyInfo` structure.
+    ///
+    /// # Arguments
+    ///
+    /// * `der` - The DER encoded public key.
+    /// * `algorithm` - The asymmetric algorithm of the key pair.
+    /// * `hash` - The hash algorithm used for signing.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `PublicKey` on success, or a `SecurityModuleError::InvalidPublicKey` on failure.
+    pub fn from_der(
+        der: &[u8],
+        algorithm: AsymmetricEncryption,
+        hash: Hash,
+    ) -> Result<Self, SecurityModuleError> {
+        let key =
+            PKey::public_key_from_der(der).map_err(|_| SecurityModuleError::InvalidPublicKey)?;
+        Ok(Self::new(key, algorithm, hash))
+    }
+
+    /// Creates a `PublicKey` from a DER encoded PKCS#1 `RSAPublicKey` structure.
+    ///
+    /// This is the representation Apple's Security framework exports RSA public keys in.
+    ///
+    /// # Arguments
+    ///
+    /// * `der` - The DER encoded PKCS#1 public key.
+    /// * `algorithm` - The asymmetric algorithm of the key pair.
+    /// * `hash` - The hash algorithm used for signing.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `PublicKey` on success, or a `SecurityModuleError::InvalidPublicKey` on failure.
+    pub fn from_pkcs1_der(
+        der: &[u8],
+        algorithm: AsymmetricEncryption,
+        hash: Hash,
+    ) -> Result<Self, SecurityModuleError> {
+        let key = Rsa::public_key_from_der_pkcs1(der)
+            .and_then(PKey::from_rsa)
+            .map_err(|_| SecurityModuleError::InvalidPublicKey)?;
+        Ok(Self::new(key, algorithm, hash))
+    }
+
+    /// Creates a `PublicKey` from an uncompressed elliptic curve point (SEC1 / ANSI X9.63).
+    ///
+    /// This is the representation Apple's Security framework exports EC public keys in.
+    ///
+    /// # Arguments
+    ///
+    /// * `point` - The encoded curve point (`0x04 || X || Y`).
+    /// * `algorithm` - The asymmetric algorithm of the key pair, which determines the curve.
+    /// * `hash` - The hash algorithm used for signing.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `PublicKey` on success, or a `SecurityModuleError` if the
+    /// curve is not supported or the point is invalid.
+    pub fn from_ec_point(
+        point: &[u8],
+        algorithm: AsymmetricEncryption,
+        hash: Hash,
+    ) -> Result<Self, SecurityModuleError> {
+        let nid = algorithm
+            .ecc_curve()
+            .map(curve_nid)
+            .ok_or(SecurityModuleError::UnsupportedAlgorithm)??;
+        let group =
+            EcGroup::from_curve_name(nid).map_err(|_| SecurityModuleError::UnsupportedAlgorithm)?;
+        let mut ctx = BigNumContext::new().map_err(|_| SecurityModuleError::InvalidPublicKey)?;
+        let key = EcPoint::from_bytes(&group, point, &mut ctx)
+            .and_then(|point| EcKey::from_public_key(&group, &point))
+            .and_then(PKey::from_ec_key)
+            .map_err(|_| SecurityModuleError::InvalidPublicKey)?;
+        Ok(Self::new(key, algorithm, hash))
+    }
+
+    /// Creates a `PublicKey` from the affine coordinates of an elliptic curve point.
+    ///
+    /// This is the representation TPMs return public ECC keys in.
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - The big-endian x coordinate.
+    /// * `y` - The big-endian y coordinate.
+    /// * `algorithm` - The asymmetric algorithm of the key pair, which determines the curve.
+    /// * `hash` - The hash algorithm used for signing.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `PublicKey` on success, or a `SecurityModuleError` if the
+    /// curve is not supported or the point is invalid.
+    pub fn from_ec_coordinates(
+        x: &[u8],
+        y: &[u8],
+        algorithm: AsymmetricEncryption,
+        hash: Hash,
+    ) -> Result<Self, SecurityModuleError> {
+        let nid = algorithm
+            .ecc_curve()
+            .map(curve_nid)
+            .ok_or(SecurityModuleError::UnsupportedAlgorithm)??;
+        let group =
+            EcGroup::from_curve_name(nid).map_err(|_| SecurityModuleError::UnsupportedAlgorithm)?;
+        let key = BigNum::from_slice(x)
+            .and_then(|x| Ok((x, BigNum::from_slice(y)?)))
+            .and_then(|(x, y)| EcKey::from_public_key_affine_coordinates(&group, &x, &y))
+            .and_then(PKey::from_ec_key)
+            .map_err(|_| SecurityModuleError::InvalidPublicKey)?;
+        Ok(Self::new(key, algorithm, hash))
+    }
+
+    /// Creates an RSA `PublicKey` from its modulus and public exponent.
+    ///
+    /// # Arguments
+    ///
+    /// * `modulus` - The big-endian modulus.
+    /// * `exponent` - The big-endian public exponent.
+    /// * `algorithm` - The asymmetric algorithm of the key pair.
+    /// * `hash` - The hash algorithm used for signing.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `PublicKey` on success, or a `SecurityModuleError::InvalidPublicKey` on failure.
+    pub fn from_rsa_components(
+        modulus: &[u8],
+        exponent: &[u8],
+        algorithm: AsymmetricEncryption,
+        hash: Hash,
+    ) -> Result<Self, SecurityModuleError> {
+        let key = BigNum::from_slice(modulus)
+            .and_then(|modulus| Ok((modulus, BigNum::from_slice(exponent)?)))
+            .and_then(|(modulus, exponent)| Rsa::from_public_components(modulus, exponent))
+            .and_then(PKey::from_rsa)
+            .map_err(|_| SecurityModuleError::InvalidPublicKey)?;
+        Ok(Self::new(key, algorithm, hash))
+    }
+
+    fn new(key: PKey<Public>, algorithm: AsymmetricEncryption, hash: Hash) -> Self {
+        Self {
+            algorithm,
+            hash,
+            rsa_padding: RsaSignaturePadding::default(),
+            key,
+        }
+    }
+
+    /// Sets the padding scheme expected for RSA signatures.
+    pub fn with_rsa_padding(mut self, padding: RsaSignaturePadding) -> Self {
+        self.rsa_padding = padding;
+        self
+    }
+
+    /// Returns the asymmetric algorithm of the key pair.
+    pub fn algorithm(&self) -> AsymmetricEncryption {
+        self.algorithm
+    }
+
+    /// Returns the hash algorithm used for signing.
+    pub fn hash(&self) -> Hash {
+        self.hash
+    }
+
+    /// Returns the padding scheme expected for RSA signatures.
+    pub fn rsa_padding(&self) -> RsaSignaturePadding {
+        self.rsa_padding
+    }
+
+    /// Encodes the public key as DER `SubjectPublicKeyInfo` structure.
+    pub fn to_der(&self) -> Result<Vec<u8>, SecurityModuleError> {
+        self.key
+            .public_key_to_der()
+            .map_err(|_| SecurityModuleError::InvalidPublicKey)
+    }
+
+    /// Encodes an EC public key as uncompressed SEC1 point, the format of `from_ec_point` and of
+    /// the Secure Enclave.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the point, or a `SecurityModuleError::InvalidPublicKey` if the key
+    /// is not an EC key.
+    pub fn to_ec_point(&self) -> Result<Vec<u8>, SecurityModuleError> {
+        let ec_key = self
+            .key
+            .ec_key()
+            .map_err(|_| SecurityModuleError::InvalidPublicKey)?;
+        BigNumContext::new()
+            .and_then(|mut ctx| {
+                ec_key.public_key().to_bytes(
+                    ec_key.group(),
+                    PointConversionForm::UNCOMPRESSED,
+                    &mut ctx,
+                )
+            })
+            .map_err(|_| SecurityModuleError::InvalidPublicKey)
+    }
+
+    /// Returns the key for key agreements and other operations on the raw key.
+    pub(crate) fn pkey(&self) -> &PKey<Public> {
+        &self.key
+    }
+
+    /// Verifies a single signature.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The data the signature was created over.
+    /// * `signature` - The raw signature, DER encoded for ECDSA.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing `true` if the signature is valid, `false` if it is not,
+    /// or a `SecurityModuleError` if the verification could not be performed.
+    pub fn verify(&self, data: &[u8], signature: &[u8]) -> Result<bool, SecurityModuleError> {
+        if self.key.id() == Id::ED25519 {
+            let mut verifier = Verifier::new_without_digest(&self.key)
+                .map_err(|e| SecurityModuleError::SignatureVerificationError(e.to_string()))?;
+            return verifier
+                .verify_oneshot(signature, data)
+                .map_err(|e| SecurityModuleError::SignatureVerificationError(e.to_string()));
+        }
+
+        let mut verifier = Verifier::new(message_digest(self.hash)?, &self.key)
+            .map_err(|e| SecurityModuleError::SignatureVerificationError(e.to_string()))?;
+        if self.key.id() == Id::RSA && self.rsa_padding == RsaSignaturePadding::Pss {
+            verifier
+                .set_rsa_padding(Padding::PKCS1_PSS)
+                .and_then(|_| verifier.set_rsa_pss_saltlen(RsaPssSaltlen::DIGEST_LENGTH))
+                .map_err(|e| SecurityModuleError::SignatureVerificationError(e.to_string()))?;
+        }
+        verifier
+            .update(data)
+            .map_err(|e| SecurityModuleError::SignatureVerificationError(e.to_string()))?;
+        verifier
+            .verify(signature)
+            .map_err(|e| SecurityModuleError::SignatureVerificationError(e.to_string()))
+    }
+
+    /// Verifies a single signature given in the specified format.
+    ///
+    /// RSA and Ed25519 signatures have a single encoding, `format` only affects ECDSA signatures.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The data the signature was created over.
+    /// * `signature` - The encoded signature.
+    /// * `format` - The format of `signature`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing `true` if the signature is valid, `false` if it is not,
+    /// or a `SecurityModuleError` if the verification could not be performed.
+    pub fn verify_with_format(
+        &self,
+        data: &[u8],
+        signature: &[u8],
+        format: SignatureFormat,
+    ) -> Result<bool, SecurityModuleError> {
+        match (format, self.key.id()) {
+            (SignatureFormat::Raw, Id::EC) => {
+                self.verify(data, &signature_format::raw_to_der(signature)?)
+            }
+            _ => self.verify(data, signature),
+        }
+    }
+
+    /// Verifies a batch of signatures in parallel.
+    ///
+    /// The batch is distributed over the rayon thread pool. Signatures that cannot be
+    /// verified, e.g. because they are malformed, are reported as invalid.
+    ///
+    /// # Arguments
+    ///
+    /// * `items` - Pairs of data and the signature created over it.
+    ///
+    /// # Returns
+    ///
+    /// A `Vec<bool>` with one entry per item, in the same order as `items`.
+    pub fn verify_many(&self, items: &[(&[u8], &[u8])]) -> Vec<bool> {
+        items
+            .par_iter()
+            .map(|(data, signature)| self.verify(data, signature).unwrap_or(false))
+            .collect()
+    }
+}
+
+/// Maps an elliptic curve to the corresponding OpenSSL curve identifier.
+pub(crate) fn curve_nid(curve: EccCurves) -> Result<Nid, SecurityModuleError> {
+    match curve {
+        EccCurves::P256 => Ok(Nid::X9_62_PRIME256V1),
+        EccCurves::P384 => Ok(Nid::SECP384R1),
+        EccCurves::P521 => Ok(Nid::SECP521R1),
+        EccCurves::Secp256k1 => Ok(Nid::SECP256K1),
+        EccCurves::BrainpoolP256r1 => Ok(Nid::BRAINPOOL_P256R1),
+        EccCurves::BrainpoolP384r1 => Ok(Nid::BRAINPOOL_P384R1),
+        EccCurves::BrainpoolP512r1 => Ok(Nid::BRAINPOOL_P512R1),
+        _ => Err(SecurityModuleError::UnsupportedAlgorithm),
+    }
+}
+
+/// Maps an OpenSSL curve identifier to the corresponding elliptic curve, the inverse of
+/// `curve_nid`.
+pub(crate) fn curve_from_nid(nid: Nid) -> Option<EccCurves> {
+    match nid {
+        Nid::X9_62_PRIME256V1 => Some(EccCurves::P256),
+        Nid::SECP384R1 => Some(EccCurves::P384),
+        Nid::SECP521R1 => Some(EccCurves::P521),
+        Nid::SECP256K1 => Some(EccCurves::Secp256k1),
+        Nid::BRAINPOOL_P256R1 => Some(EccCurves::BrainpoolP256r1),
+        Nid::BRAINPOOL_P384R1 => Some(EccCurves::BrainpoolP384r1),
+        Nid::BRAINPOOL_P512R1 => Some(EccCurves::BrainpoolP512r1),
+        _ => None,
+    }
+}
+
+/// Returns the size of an RSA key, or a `SecurityModuleError::UnsupportedAlgorithm` for sizes
+/// that `KeyBits` does not represent.
+pub(crate) fn rsa_key_bits(bits: usize) -> Result<KeyBits, SecurityModuleError> {
+    match bits {
+        1024 => Ok(KeyBits::Bits1024),
+        2048 => Ok(KeyBits::Bits2048),
+        3072 => Ok(KeyBits::Bits3072),
+        4096 => Ok(KeyBits::Bits4096),
+        8192 => Ok(KeyBits::Bits8192),
+        _ => Err(SecurityModuleError::UnsupportedAlgorithm),
+    }
+}
+
+/// Maps a hash algorithm to the corresponding OpenSSL message digest.
+///
+/// MD2, MD4 and the truncated SHA-512 variants are not supported by the openssl crate.
+pub(crate) fn message_digest(hash: Hash) -> Result<MessageDigest, SecurityModuleError> {
+    match hash {
+        Hash::Sha1 => Ok(MessageDigest::sha1()),
+        Hash::Sha2(Sha2Bits::Sha224) => Ok(MessageDigest::sha224()),
+        Hash::Sha2(Sha2Bits::Sha256) => Ok(MessageDigest::sha256()),
+        Hash::Sha2(Sha2Bits::Sha384) => Ok(MessageDigest::sha384()),
+        Hash::Sha2(Sha2Bits::Sha512) => Ok(MessageDigest::sha512()),
+        Hash::Sha3(Sha3Bits::Sha3_224) => Ok(MessageDigest::sha3_224()),
+        Hash::Sha3(Sha3Bits::Sha3_256) => Ok(MessageDigest::sha3_256()),
+        Hash::Sha3(Sha3Bits::Sha3_384) => Ok(MessageDigest::sha3_384()),
+        Hash::Sha3(Sha3Bits::Sha3_512) => Ok(MessageDigest::sha3_512()),
+        Hash::Md5 => Ok(MessageDigest::md5()),
+        Hash::Ripemd160 => Ok(MessageDigest::ripemd160()),
+        _ => Err(SecurityModuleError::UnsupportedAlgorithm),
+    }
+}
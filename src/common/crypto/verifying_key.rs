@@ -0,0 +1,196 @@
+//! Verify-only key handles for the public keys of other parties.
+//!
+//! Signatures of peers are verified against their public key, which is not held by any local
+//! security module. A `VerifyingKey` wraps such a key in a `KeyHandle`, so that code written
+//! against `verify_signature` works for local keys and for peers alike:
+//!
+//! ```rust,ignore
+//! use crypto_layer::common::crypto::verifying_key::VerifyingKey;
+//!
+//! let peer = VerifyingKey::from_jwk(r#"{"kty":"EC","crv":"P-256","x":"...","y":"..."}"#)?;
+//! let valid = peer.verify_signature(data, signature)?;
+//! ```
+//!
+//! The algorithm is read from the key. PEM and DER keys do not name a hash, which is passed
+//! explicitly, while JWKs carry it in their `alg` member, see `jwk`.
+//!
+//! Signing, decryption and key agreement need the private key and fail with
+//! `SecurityModuleError::UnsupportedOperation`.
+
+use super::{
+    algorithms::{
+        encryption::{AsymmetricEncryption, EccCurves, EccSchemeAlgorithm},
+        hashes::Hash,
+    },
+    jwk,
+    public_key::{curve_from_nid, rsa_key_bits, PublicKey},
+    signature_format::SignatureFormat,
+};
+use crate::common::{
+    crypto::secret::SecretBytes, error::SecurityModuleError, traits::key_handle::KeyHandle,
+};
+use crypto_layer_core::CoreError;
+use openssl::{
+    pkey::{Id, PKey, Public},
+    rsa::Rsa,
+};
+use serde_json::Value;
+
+/// A `KeyHandle` holding only a public key, which verifies signatures without a security module.
+#[derive(Clone, Debug)]
+pub struct VerifyingKey {
+    public_key: PublicKey,
+    format: SignatureFormat,
+}
+
+impl VerifyingKey {
+    /// Creates a verifying key that expects DER encoded ECDSA signatures, like the providers
+    /// create them.
+    pub fn new(public_key: PublicKey) -> Self {
+        Self {
+            public_key,
+            format: SignatureFormat::Der,
+        }
+    }
+
+    /// Creates a verifying key from a DER encoded `SubjectPublicKeyInfo` structure.
+    ///
+    /// # Arguments
+    ///
+    /// * `der` - The DER encoded public key.
+    /// * `hash` - The hash algorithm the peer signs with.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `VerifyingKey` on success, a
+    /// `SecurityModuleError::InvalidPublicKey` if the key cannot be parsed, or a
+    /// `SecurityModuleError::UnsupportedAlgorithm` if its algorithm, curve or size is not supported.
+    pub fn from_der(der: &[u8], hash: Hash) -> Result<Self, SecurityModuleError> {
+        let key =
+            PKey::public_key_from_der(der).map_err(|_| SecurityModuleError::InvalidPublicKey)?;
+        Self::from_pkey(&key, hash)
+    }
+
+    /// Creates a verifying key from a PEM encoded `SubjectPublicKeyInfo` (`PUBLIC KEY`) or
+    /// PKCS#1 (`RSA PUBLIC KEY`) structure.
+    ///
+    /// # Arguments
+    ///
+    /// * `pem` - The PEM encoded public key.
+    /// * `hash` - The hash algorithm the peer signs with.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `VerifyingKey` on success, or a `SecurityModuleError` as for
+    /// `from_der`.
+    pub fn from_pem(pem: &[u8], hash: Hash) -> Result<Self, SecurityModuleError> {
+        let key = PKey::public_key_from_pem(pem)
+            .or_else(|_| Rsa::public_key_from_pem_pkcs1(pem).and_then(PKey::from_rsa))
+            .map_err(|_| SecurityModuleError::InvalidPublicKey)?;
+        Self::from_pkey(&key, hash)
+    }
+
+    /// Creates a verifying key from a JSON Web Key, see `jwk::from_jwk`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `VerifyingKey` on success, a `SecurityModuleError::Encoding` if
+    /// `jwk` is no JSON object, or a `SecurityModuleError` as for `jwk::from_jwk`.
+    pub fn from_jwk(jwk: &str) -> Result<Self, SecurityModuleError> {
+        let jwk: Value = serde_json::from_str(jwk).map_err(|_| CoreError::InvalidField("jwk"))?;
+        Ok(Self::new(jwk::from_jwk(&jwk)?))
+    }
+
+    fn from_pkey(key: &PKey<Public>, hash: Hash) -> Result<Self, SecurityModuleError> {
+        let algorithm = match key.id() {
+            Id::RSA => {
+                let bits = key.bits() as usize;
+                AsymmetricEncryption::Rsa(rsa_key_bits(bits)?)
+            }
+            Id::EC => {
+                let curve = key
+                    .ec_key()
+                    .ok()
+                    .and_then(|ec_key| ec_key.group().curve_name())
+                    .and_then(curve_from_nid)
+                    .ok_or(SecurityModuleError::UnsupportedAlgorithm)?;
+                AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(curve))
+            }
+            Id::ED25519 => {
+                AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(EccCurves::Curve25519))
+            }
+            _ => return Err(SecurityModuleError::UnsupportedAlgorithm),
+        };
+        let der = key
+            .public_key_to_der()
+            .map_err(|_| SecurityModuleError::InvalidPublicKey)?;
+        Ok(Self::new(PublicKey::from_der(&der, algorithm, hash)?))
+    }
+
+    /// Sets the encoding of the ECDSA signatures to be verified, e.g. `SignatureFormat::Raw` for
+    /// JWS or WebCrypto signatures. RSA and Ed25519 signatures have a single encoding.
+    pub fn with_signature_format(mut self, format: SignatureFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Returns the public key.
+    pub fn public_key(&self) -> &PublicKey {
+        &self.public_key
+    }
+
+    /// Returns the encoding of the ECDSA signatures to be verified.
+    pub fn signature_format(&self) -> SignatureFormat {
+        self.format
+    }
+}
+
+impl From<PublicKey> for VerifyingKey {
+    fn from(public_key: PublicKey) -> Self {
+        Self::new(public_key)
+    }
+}
+
+impl KeyHandle for VerifyingKey {
+    fn sign_data(&self, _data: &[u8]) -> Result<Vec<u8>, SecurityModuleError> {
+        Err(verify_only())
+    }
+
+    fn decrypt_data(&self, _encrypted_data: &[u8]) -> Result<SecretBytes, SecurityModuleError> {
+        Err(verify_only())
+    }
+
+    fn encrypt_data(&self, _data: &[u8]) -> Result<Vec<u8>, SecurityModuleError> {
+        Err(verify_only())
+    }
+
+    /// Verifies a signature in the format of `signature_format` against the public key.
+    fn verify_signature(&self, data: &[u8], signature: &[u8]) -> Result<bool, SecurityModuleError> {
+        self.public_key
+            .verify_with_format(data, signature, self.format)
+    }
+
+    /// Verifies the batch in parallel, see `PublicKey::verify_many`.
+    fn verify_many(&self, items: &[(&[u8], &[u8])]) -> Result<Vec<bool>, SecurityModuleError> {
+        match self.format {
+            SignatureFormat::Der => Ok(self.public_key.verify_many(items)),
+            SignatureFormat::Raw => Ok(items
+                .iter()
+                .map(|(data, signature)| self.verify_signature(data, signature).unwrap_or(false))
+                .collect()),
+        }
+    }
+
+    fn derive_shared_secret(
+        &self,
+        _peer_public_key: &[u8],
+    ) -> Result<SecretBytes, SecurityModuleError> {
+        Err(verify_only())
+    }
+}
+
+fn verify_only() -> SecurityModuleError {
+    SecurityModuleError::UnsupportedOperation(
+        "A verifying key holds no private key and only verifies signatures".to_owned(),
+    )
+}
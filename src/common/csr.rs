@@ -0,0 +1,262 @@
+//! PKCS#10 certificate signing requests (RFC 2986) signed by keys of the security module.
+//!
+//! Certificate authorities issue certificates for keys that never leave the security module
+//! from a CSR, which proves that the requester holds the key:
+//!
+//! ```rust,ignore
+//! use crypto_layer::common::csr::{self, CsrSpec};
+//!
+//! let spec = CsrSpec::new()
+//!     .common_name("device-42")
+//!     .dns_name("device-42.example.com");
+//! let csr = csr::create_csr(&provider, &spec)?;
+//! ```
+//!
+//! CSRs can be signed with ECDSA keys with SHA-256, SHA-384 or SHA-512, RSA keys with PKCS#1
+//! v1.5 signatures and Ed25519 keys. The `acme` and `iot` helpers create their CSRs here.
+
+use crate::common::{
+    crypto::{
+        algorithms::{
+            encryption::{AsymmetricEncryption, EccCurves, EccSchemeAlgorithm},
+            hashes::{Hash, Sha2Bits},
+        },
+        public_key::{PublicKey, RsaSignaturePadding},
+    },
+    error::SecurityModuleError,
+    traits::module_provider::Provider,
+};
+use std::net::IpAddr;
+
+/// The longest common name a certificate can have, see RFC 5280.
+pub const MAX_COMMON_NAME_LEN: usize = 64;
+
+/// A subject alternative name requested for the certificate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SubjectAltName {
+    /// A DNS name in ASCII, i.e. with internationalized labels encoded as A-labels.
+    Dns(String),
+    /// An IPv4 or IPv6 address.
+    Ip(IpAddr),
+}
+
+/// The subject and the subject alternative names of a CSR.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CsrSpec {
+    common_name: Option<String>,
+    subject_alt_names: Vec<SubjectAltName>,
+}
+
+impl CsrSpec {
+    /// Creates a spec with an empty subject and no subject alternative names.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the common name of the subject, e.g. the device id.
+    pub fn common_name(mut self, common_name: impl Into<String>) -> Self {
+        self.common_name = Some(common_name.into());
+        self
+    }
+
+    /// Adds a DNS name as subject alternative name.
+    pub fn dns_name(mut self, name: impl Into<String>) -> Self {
+        self.subject_alt_names
+            .push(SubjectAltName::Dns(name.into()));
+        self
+    }
+
+    /// Adds an IP address as subject alternative name.
+    pub fn ip_address(mut self, address: IpAddr) -> Self {
+        self.subject_alt_names.push(SubjectAltName::Ip(address));
+        self
+    }
+}
+
+/// Creates a DER encoded CSR for `spec` with the loaded key of `provider`.
+///
+/// # Returns
+///
+/// A `Result` containing the CSR, a `SecurityModuleError::SigningError` if `spec` has neither
+/// a common name nor a subject alternative name, the common name is empty or longer than
+/// `MAX_COMMON_NAME_LEN` or a DNS name is not ASCII, a
+/// `SecurityModuleError::UnsupportedAlgorithm` if the key cannot sign certificates, or the
+/// error of `provider`.
+#[tracing::instrument(skip(provider))]
+pub fn create_csr(
+    provider: &(impl Provider + ?Sized),
+    spec: &CsrSpec,
+) -> Result<Vec<u8>, SecurityModuleError> {
+    let metadata = provider.key_metadata()?;
+    sign_csr(metadata.public_key(), spec, |data| provider.sign_data(data))
+}
+
+/// Creates a DER encoded CSR for `spec` and `public_key`, signed by `sign`, see `create_csr`.
+pub(crate) fn sign_csr(
+    public_key: &PublicKey,
+    spec: &CsrSpec,
+    sign: impl FnOnce(&[u8]) -> Result<Vec<u8>, SecurityModuleError>,
+) -> Result<Vec<u8>, SecurityModuleError> {
+    if spec.common_name.is_none() && spec.subject_alt_names.is_empty() {
+        return Err(SecurityModuleError::SigningError(
+            "A common name or a subject alternative name is required".to_owned(),
+        ));
+    }
+    let signature_algorithm = signature_algorithm(public_key)?;
+
+    let subject = match &spec.common_name {
+        Some(name) if name.is_empty() || name.len() > MAX_COMMON_NAME_LEN => {
+            return Err(SecurityModuleError::SigningError(format!(
+                "The common name must have 1 to {} bytes",
+                MAX_COMMON_NAME_LEN
+            )));
+        }
+        Some(name) => der(
+            SET,
+            &der(
+                SEQUENCE,
+                &[
+                    der(OBJECT_IDENTIFIER, OID_COMMON_NAME),
+                    der(UTF8_STRING, name.as_bytes()),
+                ]
+                .concat(),
+            ),
+        ),
+        None => Vec::new(),
+    };
+
+    let mut names = Vec::new();
+    for name in &spec.subject_alt_names {
+        match name {
+            SubjectAltName::Dns(name) if name.is_ascii() => {
+                names.extend(der(DNS_NAME, name.as_bytes()))
+            }
+            SubjectAltName::Dns(name) => {
+                return Err(SecurityModuleError::SigningError(format!(
+                    "The DNS name {} is not ASCII, it must be encoded as A-label",
+                    name
+                )));
+            }
+            SubjectAltName::Ip(IpAddr::V4(address)) => {
+                names.extend(der(IP_ADDRESS, &address.octets()))
+            }
+            SubjectAltName::Ip(IpAddr::V6(address)) => {
+                names.extend(der(IP_ADDRESS, &address.octets()))
+            }
+        }
+    }
+    // The subject alternative names are requested in an extension request attribute.
+    let attributes = if names.is_empty() {
+        Vec::new()
+    } else {
+        let subject_alt_name = der(
+            SEQUENCE,
+            &[
+                der(OBJECT_IDENTIFIER, OID_SUBJECT_ALT_NAME),
+                der(OCTET_STRING, &der(SEQUENCE, &names)),
+            ]
+            .concat(),
+        );
+        der(
+            SEQUENCE,
+            &[
+                der(OBJECT_IDENTIFIER, OID_EXTENSION_REQUEST),
+                der(SET, &der(SEQUENCE, &subject_alt_name)),
+            ]
+            .concat(),
+        )
+    };
+
+    let request_info = der(
+        SEQUENCE,
+        &[
+            der(INTEGER, &[0]),
+            der(SEQUENCE, &subject),
+            public_key.to_der()?,
+            der(ATTRIBUTES, &attributes),
+        ]
+        .concat(),
+    );
+    let signature = sign(&request_info)?;
+    Ok(der(
+        SEQUENCE,
+        &[
+            request_info,
+            signature_algorithm,
+            der(BIT_STRING, &[&[0][..], &signature].concat()),
+        ]
+        .concat(),
+    ))
+}
+
+const INTEGER: u8 = 0x02;
+const BIT_STRING: u8 = 0x03;
+const OCTET_STRING: u8 = 0x04;
+const NULL: u8 = 0x05;
+const OBJECT_IDENTIFIER: u8 = 0x06;
+const UTF8_STRING: u8 = 0x0c;
+const SEQUENCE: u8 = 0x30;
+const SET: u8 = 0x31;
+/// The `[0] IMPLICIT` attributes of a `CertificationRequestInfo`.
+const ATTRIBUTES: u8 = 0xa0;
+/// The `dNSName` and `iPAddress` choices of a `GeneralName`.
+const DNS_NAME: u8 = 0x82;
+const IP_ADDRESS: u8 = 0x87;
+
+const OID_COMMON_NAME: &[u8] = &[0x55, 0x04, 0x03];
+const OID_SUBJECT_ALT_NAME: &[u8] = &[0x55, 0x1d, 0x11];
+const OID_EXTENSION_REQUEST: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x0e];
+const OID_ECDSA_WITH_SHA256: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02];
+const OID_ECDSA_WITH_SHA384: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x03];
+const OID_ECDSA_WITH_SHA512: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x04];
+const OID_SHA256_WITH_RSA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0b];
+const OID_SHA384_WITH_RSA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0c];
+const OID_SHA512_WITH_RSA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0d];
+const OID_ED25519: &[u8] = &[0x2b, 0x65, 0x70];
+
+/// Returns the DER encoded `AlgorithmIdentifier` of the signatures of `public_key`.
+fn signature_algorithm(public_key: &PublicKey) -> Result<Vec<u8>, SecurityModuleError> {
+    let identifier = match (public_key.algorithm(), public_key.hash()) {
+        (AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(EccCurves::Curve25519)), _) => {
+            der(OBJECT_IDENTIFIER, OID_ED25519)
+        }
+        (AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(_)), Hash::Sha2(bits)) => {
+            let oid = match bits {
+                Sha2Bits::Sha256 => OID_ECDSA_WITH_SHA256,
+                Sha2Bits::Sha384 => OID_ECDSA_WITH_SHA384,
+                Sha2Bits::Sha512 => OID_ECDSA_WITH_SHA512,
+                _ => return Err(SecurityModuleError::UnsupportedAlgorithm),
+            };
+            der(OBJECT_IDENTIFIER, oid)
+        }
+        (AsymmetricEncryption::Rsa(_), Hash::Sha2(bits))
+            if public_key.rsa_padding() == RsaSignaturePadding::Pkcs1 =>
+        {
+            let oid = match bits {
+                Sha2Bits::Sha256 => OID_SHA256_WITH_RSA,
+                Sha2Bits::Sha384 => OID_SHA384_WITH_RSA,
+                Sha2Bits::Sha512 => OID_SHA512_WITH_RSA,
+                _ => return Err(SecurityModuleError::UnsupportedAlgorithm),
+            };
+            [der(OBJECT_IDENTIFIER, oid), der(NULL, &[])].concat()
+        }
+        _ => return Err(SecurityModuleError::UnsupportedAlgorithm),
+    };
+    Ok(der(SEQUENCE, &identifier))
+}
+
+/// Encodes `contents` with `tag` and the DER length.
+fn der(tag: u8, contents: &[u8]) -> Vec<u8> {
+    let len = contents.len();
+    let mut encoded = vec![tag];
+    if len < 0x80 {
+        encoded.push(len as u8);
+    } else {
+        let bytes = len.to_be_bytes();
+        let start = bytes.iter().position(|&byte| byte != 0).unwrap_or(0);
+        encoded.push(0x80 | (bytes.len() - start) as u8);
+        encoded.extend_from_slice(&bytes[start..]);
+    }
+    encoded.extend_from_slice(contents);
+    encoded
+}
@@ -0,0 +1,312 @@
+//! One well-known identity key per device.
+//!
+//! Most applications of this crate need a key that identifies the device, e.g. towards the
+//! backend of an IoT or mobile fleet. A `DeviceIdentity` manages that key: it creates it in the
+//! security module on first use, finds it again on every start, exposes its public key and
+//! attestation and signs with it.
+//!
+//! ```rust,ignore
+//! use crypto_layer::common::device_identity::DeviceIdentity;
+//!
+//! let config = SecureEnclaveConfig::from_spec(&spec)?;
+//! let identity = DeviceIdentity::open(provider, move || Box::new(config.clone()))?;
+//! register_device(identity.public_key(), identity.attestation());
+//! let signature = identity.sign(b"telemetry")?;
+//! ```
+//!
+//! Keys cannot be exported or rotated in place, so `renew` creates the key of the next
+//! generation. The keys are stored as `<prefix>.<generation>`, e.g. `device-identity.1`, and the
+//! identity always uses the highest generation. The `Renewal` of `renew` carries an endorsement
+//! of the new public key by the previous key, which lets the backend trust the new key based on
+//! the key it registered before.
+
+use crate::common::{
+    crypto::{key_metadata::KeyMetadata, public_key::PublicKey},
+    csr::{self, CsrSpec},
+    error::SecurityModuleError,
+    key_id::KeyId,
+    traits::module_provider::Provider,
+    transaction,
+};
+use openssl::sha::sha256;
+use std::{
+    any::Any,
+    fmt,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+/// The prefix of the identity keys used by `DeviceIdentity::open`.
+pub const DEFAULT_KEY_PREFIX: &str = "device-identity";
+
+/// Separates endorsements of renewed keys from other signatures of the identity keys.
+pub const RENEWAL_DOMAIN: &[u8] = b"crypto-layer/device-identity/renewal/v1\n";
+
+/// The highest generation looked for when the provider cannot list its keys.
+const MAX_PROBED_GENERATIONS: u32 = 1024;
+
+/// Creates the provider-specific configuration of the identity keys.
+type ConfigFn = dyn Fn() -> Box<dyn Any> + Send + Sync;
+
+/// The identity key of the device, see the module documentation.
+pub struct DeviceIdentity {
+    provider: Arc<Mutex<dyn Provider>>,
+    prefix: String,
+    config: Box<ConfigFn>,
+    generation: u32,
+    metadata: KeyMetadata,
+}
+
+/// The result of `DeviceIdentity::renew`, to be sent to the backend.
+#[derive(Debug, Clone)]
+pub struct Renewal {
+    /// The id of the key that was replaced.
+    pub previous_key_id: String,
+    /// The public key that was replaced.
+    pub previous_public_key: PublicKey,
+    /// The id of the new key.
+    pub key_id: String,
+    /// The new public key.
+    pub public_key: PublicKey,
+    /// The signature of the previous key over `RENEWAL_DOMAIN`, the SHA-256 hash of the DER
+    /// encoded previous public key and the DER encoded new public key.
+    pub endorsement: Vec<u8>,
+}
+
+impl Renewal {
+    /// Verifies that the key the backend trusts endorsed the new key.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` that is `Ok(())` if `trusted` is the previous public key and its signature is
+    /// valid, or a `SecurityModuleError::InvalidSignature` otherwise.
+    pub fn verify(&self, trusted: &PublicKey) -> Result<(), SecurityModuleError> {
+        let previous = self.previous_public_key.to_der()?;
+        if trusted.to_der()? != previous {
+            return Err(SecurityModuleError::InvalidSignature);
+        }
+        let endorsed = endorsed_data(&previous, &self.public_key.to_der()?);
+        match trusted.verify(&endorsed, &self.endorsement)? {
+            true => Ok(()),
+            false => Err(SecurityModuleError::InvalidSignature),
+        }
+    }
+}
+
+impl DeviceIdentity {
+    /// Opens the identity with the keys `device-identity.<generation>`, see `open_with_prefix`.
+    pub fn open(
+        provider: Arc<Mutex<dyn Provider>>,
+        config: impl Fn() -> Box<dyn Any> + Send + Sync + 'static,
+    ) -> Result<Self, SecurityModuleError> {
+        Self::open_with_prefix(provider, DEFAULT_KEY_PREFIX, config)
+    }
+
+    /// Opens the identity with the keys `<prefix>.<generation>`, creating the key of the first
+    /// generation if there is none.
+    ///
+    /// # Arguments
+    ///
+    /// * `provider` - The initialized provider holding the identity keys.
+    /// * `prefix` - The prefix of the key ids, which must form valid `KeyId`s.
+    /// * `config` - Creates the configuration passed to `create_key` and `load_key`, e.g. a
+    ///   `SecureEnclaveConfig` for a P-256 signing key.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `DeviceIdentity`, a `SecurityModuleError::InvalidKeyId` if the
+    /// prefix does not form valid key ids, or the error of `provider` if the key cannot be
+    /// loaded or created.
+    #[tracing::instrument(skip(provider, config))]
+    pub fn open_with_prefix(
+        provider: Arc<Mutex<dyn Provider>>,
+        prefix: &str,
+        config: impl Fn() -> Box<dyn Any> + Send + Sync + 'static,
+    ) -> Result<Self, SecurityModuleError> {
+        KeyId::new(key_id(prefix, MAX_PROBED_GENERATIONS))?;
+        let generation = {
+            let mut guard = lock(&provider);
+            match current_generation(&mut *guard, prefix, &config)? {
+                Some(generation) => {
+                    guard.load_key(&key_id(prefix, generation), config())?;
+                    generation
+                }
+                None => {
+                    guard.create_key(&key_id(prefix, 1), config())?;
+                    1
+                }
+            }
+        };
+        let metadata = lock(&provider).key_metadata()?;
+        Ok(Self {
+            provider,
+            prefix: prefix.to_owned(),
+            config: Box::new(config),
+            generation,
+            metadata,
+        })
+    }
+
+    /// Returns the id of the current identity key.
+    pub fn key_id(&self) -> String {
+        key_id(&self.prefix, self.generation)
+    }
+
+    /// Returns the generation of the current identity key, starting at 1.
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+
+    /// Returns the metadata of the current identity key.
+    pub fn metadata(&self) -> &KeyMetadata {
+        &self.metadata
+    }
+
+    /// Returns the public key of the current identity key.
+    pub fn public_key(&self) -> &PublicKey {
+        self.metadata.public_key()
+    }
+
+    /// Returns the DER encoded attestation certificate chain of the current identity key, or
+    /// `None` if the security module does not attest its keys.
+    pub fn attestation(&self) -> Option<&[Vec<u8>]> {
+        self.metadata.attestation()
+    }
+
+    /// Signs `data` with the current identity key.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the signature, or the error of the provider.
+    pub fn sign(&self, data: &[u8]) -> Result<Vec<u8>, SecurityModuleError> {
+        self.loaded()?.sign_data(data)
+    }
+
+    /// Creates a CSR for the current identity key, e.g. to enroll a client certificate with the
+    /// backend, see `csr::create_csr`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the DER encoded CSR, or the error of `csr::create_csr`.
+    pub fn create_csr(&self, spec: &CsrSpec) -> Result<Vec<u8>, SecurityModuleError> {
+        csr::sign_csr(self.public_key(), spec, |data| self.sign(data))
+    }
+
+    /// Creates the identity key of the next generation and endorses it with the current key.
+    ///
+    /// The previous key is kept in the security module, so signatures it created can still be
+    /// verified, but the identity only uses the new key from now on.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `Renewal` for the backend, or the error of the provider. If the
+    /// new key was created but could not be endorsed, it is deleted again and the previous key
+    /// reloaded, see `transaction`, so the identity keeps using the previous key.
+    #[tracing::instrument(skip(self), fields(crypto.generation = self.generation))]
+    pub fn renew(&mut self) -> Result<Renewal, SecurityModuleError> {
+        let previous_key_id = self.key_id();
+        let next_key_id = key_id(&self.prefix, self.generation + 1);
+
+        let config = &self.config;
+        let previous_metadata = &self.metadata;
+        let mut provider = lock(&self.provider);
+        let (metadata, endorsement) =
+            transaction::run("renew device identity", &mut *provider, |transaction| {
+                // Runs last on rollback, after the new key was deleted.
+                let reloaded = previous_key_id.clone();
+                transaction.on_rollback("reload the previous key", move |provider| {
+                    provider.load_key(&reloaded, config())
+                });
+                transaction.create_key(&next_key_id, config())?;
+                let provider = transaction.target();
+                let metadata = provider.key_metadata()?;
+                provider.load_key(&previous_key_id, config())?;
+                let endorsement = provider.sign_data(&endorsed_data(
+                    previous_metadata.public_key_der(),
+                    metadata.public_key_der(),
+                ))?;
+                provider.load_key(&next_key_id, config())?;
+                Ok((metadata, endorsement))
+            })?;
+        drop(provider);
+
+        let renewal = Renewal {
+            previous_key_id,
+            previous_public_key: self.metadata.public_key().clone(),
+            key_id: next_key_id,
+            public_key: metadata.public_key().clone(),
+            endorsement,
+        };
+        self.generation += 1;
+        self.metadata = metadata;
+        Ok(renewal)
+    }
+
+    /// Locks the provider and loads the current identity key if another key is loaded.
+    fn loaded(&self) -> Result<MutexGuard<'_, dyn Provider + 'static>, SecurityModuleError> {
+        let mut provider = lock(&self.provider);
+        let is_loaded = provider
+            .key_metadata()
+            .is_ok_and(|metadata| metadata.public_key_der() == self.metadata.public_key_der());
+        if !is_loaded {
+            provider.load_key(&self.key_id(), (self.config)())?;
+        }
+        Ok(provider)
+    }
+}
+
+impl fmt::Debug for DeviceIdentity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DeviceIdentity")
+            .field("key_id", &self.key_id())
+            .field("metadata", &self.metadata)
+            .finish_non_exhaustive()
+    }
+}
+
+fn key_id(prefix: &str, generation: u32) -> String {
+    format!("{}.{}", prefix, generation)
+}
+
+/// Returns the highest generation of the identity keys, or `None` if there is none yet.
+///
+/// Providers that cannot list their keys are probed with `load_key`, starting at the first
+/// generation.
+fn current_generation(
+    provider: &mut dyn Provider,
+    prefix: &str,
+    config: &ConfigFn,
+) -> Result<Option<u32>, SecurityModuleError> {
+    if let Ok(key_ids) = provider.list_keys() {
+        return Ok(key_ids
+            .iter()
+            .filter_map(|key_id| key_id.strip_prefix(prefix)?.strip_prefix('.'))
+            .filter(|generation| !generation.starts_with('0'))
+            .filter_map(|generation| generation.parse().ok())
+            .max());
+    }
+
+    let mut current = None;
+    for generation in 1..=MAX_PROBED_GENERATIONS {
+        if provider
+            .load_key(&key_id(prefix, generation), config())
+            .is_err()
+        {
+            break;
+        }
+        current = Some(generation);
+    }
+    Ok(current)
+}
+
+fn endorsed_data(previous_public_key_der: &[u8], public_key_der: &[u8]) -> Vec<u8> {
+    [
+        RENEWAL_DOMAIN,
+        &sha256(previous_public_key_der),
+        public_key_der,
+    ]
+    .concat()
+}
+
+fn lock<'a>(provider: &'a Mutex<dyn Provider + 'static>) -> MutexGuard<'a, dyn Provider + 'static> {
+    provider.lock().unwrap_or_else(|e| e.into_inner())
+}
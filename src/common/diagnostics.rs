@@ -0,0 +1,346 @@
+//! Support bundles for bug reports.
+//!
+//! `support_bundle` collects everything needed to reproduce a hardware-specific issue in a single
+//! JSON document: the crate version and enabled features, the operating system and hardware, the
+//! configuration of the factory, every security module instance with its key and latencies, and
+//! the most recent errors. Key ids are replaced by `latency::key_id_hash` and only public key
+//! fingerprints are included, so the bundle can be attached to a public bug report.
+
+use crate::common::{
+    crypto::{key_metadata::KeyMetadata, redact::RedactionPolicy},
+    error::SecurityModuleError,
+    factory::SecModules,
+    fips,
+    latency::{key_id_hash, LatencySnapshot, ProviderOperation},
+    session_pool::SessionPoolMetrics,
+    telemetry::CorrelationId,
+};
+use serde::Serialize;
+use std::{
+    collections::VecDeque,
+    fs,
+    path::Path,
+    sync::{Mutex, MutexGuard},
+    thread,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// The number of errors kept for `SupportBundle::recent_errors`.
+pub const RECENT_ERRORS: usize = 32;
+
+static ERRORS: Mutex<VecDeque<ErrorRecord>> = Mutex::new(VecDeque::new());
+
+/// A sanitized snapshot of the state of the crate, see the module documentation.
+#[derive(Debug, Clone, Serialize)]
+pub struct SupportBundle {
+    /// When the bundle was created, in milliseconds since the Unix epoch.
+    pub created_at: u64,
+    /// The version of the crate.
+    pub version: &'static str,
+    /// The enabled cargo features.
+    pub features: Vec<&'static str>,
+    pub system: SystemInfo,
+    pub config: ConfigInfo,
+    /// The instances created by `SecModules::get_instance`.
+    pub modules: Vec<ModuleInfo>,
+    /// The last `RECENT_ERRORS` errors returned by instances of the factory, oldest first.
+    pub recent_errors: Vec<ErrorRecord>,
+}
+
+/// The operating system and hardware the crate runs on.
+#[derive(Debug, Clone, Serialize)]
+pub struct SystemInfo {
+    pub os: &'static str,
+    pub os_version: Option<String>,
+    pub family: &'static str,
+    pub arch: &'static str,
+    /// The hardware model, e.g. `MacBookPro18,1`, if the operating system reports it.
+    pub hardware_model: Option<String>,
+    pub cpus: Option<usize>,
+}
+
+/// The configuration of the factory and the crate.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfigInfo {
+    /// The slow-operation threshold of new instances, in milliseconds.
+    pub slow_threshold_ms: Option<u128>,
+    pub audit_enabled: bool,
+    /// Whether chaos mode is enabled, which is only possible with the `test-utils` feature.
+    pub chaos_enabled: bool,
+    pub redaction_policy: String,
+    /// Whether FIPS mode is enabled, see `fips`.
+    pub fips_mode: bool,
+}
+
+/// A security module instance and its key.
+#[derive(Debug, Clone, Serialize)]
+pub struct ModuleInfo {
+    /// The `Debug` representation of the `SecurityModule`, e.g. `Tpm(MacOs)`.
+    pub module: String,
+    /// The key the instance operates on, or `None` if no key was created or loaded.
+    pub key: Option<KeyInfo>,
+    /// The latency of every operation called at least once.
+    pub operations: Vec<OperationInfo>,
+    pub slow_operations: u64,
+    pub session_pool: Option<SessionPoolMetrics>,
+}
+
+/// The public part of a key.
+#[derive(Debug, Clone, Serialize)]
+pub struct KeyInfo {
+    pub key_id_hash: String,
+    /// The `Debug` representation of the algorithm of the key.
+    pub algorithm: String,
+    /// The hex encoded SHA-256 hash of the DER encoded public key.
+    pub public_key_fingerprint: String,
+    pub attested: bool,
+    /// When the metadata was fetched from the security module, in milliseconds since the Unix
+    /// epoch.
+    pub fetched_at: u64,
+}
+
+/// The latency of an operation.
+#[derive(Debug, Clone, Serialize)]
+pub struct OperationInfo {
+    pub operation: ProviderOperation,
+    pub count: u64,
+    pub mean_us: Option<u128>,
+    pub p99_us: Option<u128>,
+    pub max_us: Option<u128>,
+}
+
+/// An error returned by an instance of the factory.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ErrorRecord {
+    /// When the error was returned, in milliseconds since the Unix epoch.
+    pub timestamp: u64,
+    pub operation: ProviderOperation,
+    pub key_id_hash: String,
+    /// The correlation id of the failed operation, see `telemetry::CorrelationId`.
+    pub correlation_id: Option<String>,
+    /// The stable code of the error, see `SecurityModuleError::code`.
+    pub code: u32,
+    /// The error message, with the key id replaced by its hash.
+    pub message: String,
+}
+
+impl SupportBundle {
+    /// Returns the bundle as pretty-printed JSON.
+    pub fn to_json(&self) -> Result<String, SecurityModuleError> {
+        serde_json::to_string_pretty(self)
+            .map_err(|e| SecurityModuleError::InitializationError(e.to_string()))
+    }
+
+    /// Writes the bundle as JSON to a file, which can be attached to a bug report.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` that is `Ok(())` on success, or a `SecurityModuleError::InitializationError`
+    /// if the file cannot be written.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SecurityModuleError> {
+        fs::write(path, self.to_json()?)
+            .map_err(|e| SecurityModuleError::InitializationError(e.to_string()))
+    }
+}
+
+/// Collects a support bundle.
+///
+/// Fetches the key metadata of every instance, which may take a round trip through the
+/// security module if a provider does not cache it.
+pub fn support_bundle() -> SupportBundle {
+    let modules = SecModules::instances()
+        .into_iter()
+        .map(|(module, instance)| {
+            let instance = instance
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            let latency = instance.latency_snapshot().unwrap_or_default();
+            ModuleInfo {
+                module: format!("{:?}", module),
+                key: instance.key_metadata().ok().as_ref().map(key_info),
+                operations: operations(&latency),
+                slow_operations: latency.slow_operations,
+                session_pool: instance.session_pool_metrics(),
+            }
+        })
+        .collect();
+
+    let latency = SecModules::latency_config();
+    SupportBundle {
+        created_at: millis(SystemTime::now()),
+        version: env!("CARGO_PKG_VERSION"),
+        features: features(),
+        system: system_info(),
+        config: ConfigInfo {
+            slow_threshold_ms: latency
+                .slow_threshold
+                .map(|threshold| threshold.as_millis()),
+            audit_enabled: SecModules::audit_enabled(),
+            chaos_enabled: SecModules::chaos_enabled(),
+            redaction_policy: format!("{:?}", RedactionPolicy::current()),
+            fips_mode: fips::is_enabled(),
+        },
+        modules,
+        recent_errors: lock_errors().iter().cloned().collect(),
+    }
+}
+
+/// Remembers an error for `SupportBundle::recent_errors`.
+pub(crate) fn record_error(
+    operation: ProviderOperation,
+    key_id: &str,
+    error: &SecurityModuleError,
+) {
+    let key_id_hash = key_id_hash(key_id);
+    let mut message = error.to_string();
+    if !key_id.is_empty() {
+        message = message.replace(key_id, &key_id_hash);
+    }
+    let record = ErrorRecord {
+        timestamp: millis(SystemTime::now()),
+        operation,
+        key_id_hash,
+        correlation_id: CorrelationId::current().map(|id| id.to_string()),
+        code: error.code(),
+        message,
+    };
+
+    let mut errors = lock_errors();
+    if errors.len() == RECENT_ERRORS {
+        errors.pop_front();
+    }
+    errors.push_back(record);
+}
+
+fn lock_errors() -> MutexGuard<'static, VecDeque<ErrorRecord>> {
+    ERRORS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn key_info(metadata: &KeyMetadata) -> KeyInfo {
+    KeyInfo {
+        key_id_hash: key_id_hash(metadata.key_id()),
+        algorithm: format!("{:?}", metadata.public_key().algorithm()),
+        public_key_fingerprint: openssl::sha::sha256(metadata.public_key_der())
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect(),
+        attested: metadata.attestation().is_some(),
+        fetched_at: millis(metadata.fetched_at()),
+    }
+}
+
+fn operations(latency: &LatencySnapshot) -> Vec<OperationInfo> {
+    latency
+        .operations
+        .iter()
+        .map(|(operation, histogram)| OperationInfo {
+            operation: *operation,
+            count: histogram.count(),
+            mean_us: histogram.mean().map(|mean| mean.as_micros()),
+            p99_us: histogram.percentile(99.0).map(|p99| p99.as_micros()),
+            max_us: histogram.max().map(|max| max.as_micros()),
+        })
+        .collect()
+}
+
+fn millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+fn features() -> Vec<&'static str> {
+    [
+        ("android", cfg!(feature = "android")),
+        ("ffi", cfg!(feature = "ffi")),
+        ("fips", cfg!(feature = "fips")),
+        ("hcvault", cfg!(feature = "hcvault")),
+        ("hsm", cfg!(feature = "hsm")),
+        ("iot", cfg!(feature = "iot")),
+        ("linux", cfg!(feature = "linux")),
+        ("macos", cfg!(feature = "macos")),
+        ("messaging", cfg!(feature = "messaging")),
+        ("metrics", cfg!(feature = "metrics")),
+        ("nitro", cfg!(feature = "nitro")),
+        ("nks", cfg!(feature = "nks")),
+        ("sqlite", cfg!(feature = "sqlite")),
+        ("ssh-agent", cfg!(feature = "ssh-agent")),
+        ("test-utils", cfg!(feature = "test-utils")),
+        ("tpm", cfg!(feature = "tpm")),
+        ("win", cfg!(feature = "win")),
+        ("yubi", cfg!(feature = "yubi")),
+    ]
+    .into_iter()
+    .filter_map(|(feature, enabled)| enabled.then_some(feature))
+    .collect()
+}
+
+fn system_info() -> SystemInfo {
+    SystemInfo {
+        os: std::env::consts::OS,
+        os_version: os_version(),
+        family: std::env::consts::FAMILY,
+        arch: std::env::consts::ARCH,
+        hardware_model: hardware_model(),
+        cpus: thread::available_parallelism().map(usize::from).ok(),
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn os_version() -> Option<String> {
+    let release = fs::read_to_string("/etc/os-release")
+        .ok()
+        .and_then(|release| {
+            release.lines().find_map(|line| {
+                line.strip_prefix("PRETTY_NAME=")
+                    .map(|name| name.trim_matches('"').to_owned())
+            })
+        });
+    let kernel = fs::read_to_string("/proc/sys/kernel/osrelease")
+        .ok()
+        .map(|kernel| kernel.trim().to_owned());
+    match (release, kernel) {
+        (Some(release), Some(kernel)) => Some(format!("{} (kernel {})", release, kernel)),
+        (release, kernel) => release.or(kernel),
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn os_version() -> Option<String> {
+    command_output("sw_vers", &["-productVersion"])
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn os_version() -> Option<String> {
+    None
+}
+
+#[cfg(target_os = "linux")]
+fn hardware_model() -> Option<String> {
+    fs::read_to_string("/sys/devices/virtual/dmi/id/product_name")
+        .ok()
+        .map(|model| model.trim().to_owned())
+        .filter(|model| !model.is_empty())
+}
+
+#[cfg(target_os = "macos")]
+fn hardware_model() -> Option<String> {
+    command_output("sysctl", &["-n", "hw.model"])
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn hardware_model() -> Option<String> {
+    None
+}
+
+#[cfg(target_os = "macos")]
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = std::process::Command::new(program)
+        .args(args)
+        .output()
+        .ok()?;
+    let output = String::from_utf8(output.stdout).ok()?;
+    Some(output.trim().to_owned()).filter(|output| !output.is_empty())
+}
@@ -0,0 +1,664 @@
+//! DNSSEC signatures (RFC 4034) of resource record sets with zone keys of the security module.
+//!
+//! Small authoritative servers can keep their zone keys in a TPM or HSM and sign every RRset
+//! when the zone changes. All encodings are canonical, so the same RRset and validity always
+//! give the same data to sign:
+//!
+//! ```rust,ignore
+//! use crypto_layer::common::dnssec::{self, KeyRole, RrSet, ZoneKey};
+//!
+//! let key = ZoneKey::new("example.net.", provider.key_metadata()?.public_key(), KeyRole::ZoneSigning)?;
+//! println!("{}", key.dnskey_record(3600));
+//!
+//! let rrset = RrSet::new("www.example.net.", dnssec::TYPE_A, 3600, vec![vec![192, 0, 2, 1]])?;
+//! let rrsig = dnssec::sign_rrset(&provider, &key, &rrset, now, now + Duration::from_secs(30 * 86400))?;
+//! ```
+//!
+//! Only the algorithms RFC 8624 recommends for signing are supported: ECDSA with P-256 and
+//! SHA-256 (13) and with P-384 and SHA-384 (14), Ed25519 (15), and RSA with PKCS#1 v1.5
+//! signatures, 2048 to 4096 bits and SHA-256 (8) or SHA-512 (10). Names are given in ASCII
+//! without escapes, and RDATA in its canonical wire format, i.e. with names in RDATA of the
+//! types of RFC 4034, section 6.2, in lower case.
+
+use crate::common::{
+    crypto::{
+        algorithms::{
+            encryption::{AsymmetricEncryption, EccCurves, EccSchemeAlgorithm},
+            hashes::{Hash, Sha2Bits},
+            KeyBits,
+        },
+        public_key::{PublicKey, RsaSignaturePadding},
+        signature_format,
+    },
+    error::SecurityModuleError,
+    traits::module_provider::Provider,
+};
+use base64::{prelude::BASE64_STANDARD, Engine};
+use openssl::{
+    pkey::PKey,
+    sha::{sha256, sha384},
+};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The type of address records.
+pub const TYPE_A: u16 = 1;
+/// The type of IPv6 address records.
+pub const TYPE_AAAA: u16 = 28;
+/// The type of text records.
+pub const TYPE_TXT: u16 = 16;
+/// The type of DNSKEY records.
+pub const TYPE_DNSKEY: u16 = 48;
+/// The Internet class.
+pub const CLASS_IN: u16 = 1;
+
+const MAX_LABEL_LEN: usize = 63;
+const MAX_NAME_LEN: usize = 255;
+const PROTOCOL: u8 = 3;
+const FLAG_ZONE: u16 = 0x0100;
+const FLAG_SECURE_ENTRY_POINT: u16 = 0x0001;
+
+/// A DNSSEC algorithm supported for signing, see the module documentation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DnssecAlgorithm {
+    /// RSA/SHA-256 (8).
+    RsaSha256,
+    /// RSA/SHA-512 (10).
+    RsaSha512,
+    /// ECDSA P-256 with SHA-256 (13).
+    EcdsaP256Sha256,
+    /// ECDSA P-384 with SHA-384 (14).
+    EcdsaP384Sha384,
+    /// Ed25519 (15).
+    Ed25519,
+}
+
+impl DnssecAlgorithm {
+    /// Returns the algorithm `public_key` signs with.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `DnssecAlgorithm`, or a
+    /// `SecurityModuleError::UnsupportedAlgorithm` if the key is not supported for signing.
+    pub fn of(public_key: &PublicKey) -> Result<Self, SecurityModuleError> {
+        match (public_key.algorithm(), public_key.hash()) {
+            (AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(EccCurves::Curve25519)), _) => {
+                Ok(DnssecAlgorithm::Ed25519)
+            }
+            (
+                AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(EccCurves::P256)),
+                Hash::Sha2(Sha2Bits::Sha256),
+            ) => Ok(DnssecAlgorithm::EcdsaP256Sha256),
+            (
+                AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(EccCurves::P384)),
+                Hash::Sha2(Sha2Bits::Sha384),
+            ) => Ok(DnssecAlgorithm::EcdsaP384Sha384),
+            (
+                AsymmetricEncryption::Rsa(
+                    KeyBits::Bits2048 | KeyBits::Bits3072 | KeyBits::Bits4096,
+                ),
+                Hash::Sha2(hash),
+            ) if public_key.rsa_padding() == RsaSignaturePadding::Pkcs1 => match hash {
+                Sha2Bits::Sha256 => Ok(DnssecAlgorithm::RsaSha256),
+                Sha2Bits::Sha512 => Ok(DnssecAlgorithm::RsaSha512),
+                _ => Err(SecurityModuleError::UnsupportedAlgorithm),
+            },
+            _ => Err(SecurityModuleError::UnsupportedAlgorithm),
+        }
+    }
+
+    /// Returns the number of the algorithm in DNSKEY, DS and RRSIG records.
+    pub fn number(self) -> u8 {
+        match self {
+            DnssecAlgorithm::RsaSha256 => 8,
+            DnssecAlgorithm::RsaSha512 => 10,
+            DnssecAlgorithm::EcdsaP256Sha256 => 13,
+            DnssecAlgorithm::EcdsaP384Sha384 => 14,
+            DnssecAlgorithm::Ed25519 => 15,
+        }
+    }
+
+    /// Returns the length of `r` and `s` of ECDSA signatures.
+    fn scalar_len(self) -> Option<usize> {
+        match self {
+            DnssecAlgorithm::EcdsaP256Sha256 => Some(32),
+            DnssecAlgorithm::EcdsaP384Sha384 => Some(48),
+            _ => None,
+        }
+    }
+}
+
+/// What a zone key signs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyRole {
+    /// A key signing key, which signs the DNSKEY RRset and is referenced by the DS record in
+    /// the parent zone.
+    KeySigning,
+    /// A zone signing key, which signs all other RRsets.
+    ZoneSigning,
+}
+
+/// The digest of a DS record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DsDigest {
+    /// SHA-256 (2), which every resolver supports.
+    Sha256,
+    /// SHA-384 (4).
+    Sha384,
+}
+
+/// A key of the security module as DNSKEY of a zone.
+#[derive(Debug, Clone)]
+pub struct ZoneKey {
+    zone: Vec<u8>,
+    public_key: PublicKey,
+    algorithm: DnssecAlgorithm,
+    flags: u16,
+    /// The public key in the encoding of the DNSKEY record of the algorithm.
+    key_data: Vec<u8>,
+}
+
+impl ZoneKey {
+    /// Creates the DNSKEY of `public_key` for `zone`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `ZoneKey`, a `SecurityModuleError::UnsupportedAlgorithm` if
+    /// the key is not supported for signing, or a `SecurityModuleError::SigningError` if
+    /// `zone` is not a valid name.
+    pub fn new(
+        zone: &str,
+        public_key: &PublicKey,
+        role: KeyRole,
+    ) -> Result<Self, SecurityModuleError> {
+        let algorithm = DnssecAlgorithm::of(public_key)?;
+        let zone = name_to_wire(zone)?;
+        let key_data = match algorithm {
+            DnssecAlgorithm::EcdsaP256Sha256 | DnssecAlgorithm::EcdsaP384Sha384 => {
+                // RFC 6605 encodes the point without the prefix of the uncompressed form.
+                public_key.to_ec_point()?[1..].to_vec()
+            }
+            DnssecAlgorithm::Ed25519 => PKey::public_key_from_der(&public_key.to_der()?)
+                .and_then(|key| key.raw_public_key())
+                .map_err(|_| SecurityModuleError::InvalidPublicKey)?,
+            DnssecAlgorithm::RsaSha256 | DnssecAlgorithm::RsaSha512 => {
+                let rsa = PKey::public_key_from_der(&public_key.to_der()?)
+                    .and_then(|key| key.rsa())
+                    .map_err(|_| SecurityModuleError::InvalidPublicKey)?;
+                let exponent = rsa.e().to_vec();
+                // RFC 3110 prefixes the exponent with its length in one or three bytes.
+                let mut key_data = match u8::try_from(exponent.len()) {
+                    Ok(len) => vec![len],
+                    Err(_) => [&[0][..], &(exponent.len() as u16).to_be_bytes()].concat(),
+                };
+                key_data.extend_from_slice(&exponent);
+                key_data.extend_from_slice(&rsa.n().to_vec());
+                key_data
+            }
+        };
+        let flags = match role {
+            KeyRole::KeySigning => FLAG_ZONE | FLAG_SECURE_ENTRY_POINT,
+            KeyRole::ZoneSigning => FLAG_ZONE,
+        };
+        Ok(Self {
+            zone,
+            public_key: public_key.clone(),
+            algorithm,
+            flags,
+            key_data,
+        })
+    }
+
+    /// Returns the algorithm of the key.
+    pub fn algorithm(&self) -> DnssecAlgorithm {
+        self.algorithm
+    }
+
+    /// Returns the public key of the security module.
+    pub fn public_key(&self) -> &PublicKey {
+        &self.public_key
+    }
+
+    /// Returns the RDATA of the DNSKEY record.
+    pub fn dnskey_rdata(&self) -> Vec<u8> {
+        let mut rdata = self.flags.to_be_bytes().to_vec();
+        rdata.push(PROTOCOL);
+        rdata.push(self.algorithm.number());
+        rdata.extend_from_slice(&self.key_data);
+        rdata
+    }
+
+    /// Returns the key tag of RFC 4034, appendix B, which RRSIG and DS records reference the
+    /// key with.
+    pub fn key_tag(&self) -> u16 {
+        let mut sum: u32 = 0;
+        for (i, &byte) in self.dnskey_rdata().iter().enumerate() {
+            sum += match i % 2 {
+                0 => (byte as u32) << 8,
+                _ => byte as u32,
+            };
+        }
+        sum += (sum >> 16) & 0xffff;
+        sum as u16
+    }
+
+    /// Returns the RDATA of the DS record of the key for the parent zone.
+    pub fn ds_rdata(&self, digest: DsDigest) -> Vec<u8> {
+        let signed = [&self.zone[..], &self.dnskey_rdata()].concat();
+        let (digest_type, digest) = match digest {
+            DsDigest::Sha256 => (2, sha256(&signed).to_vec()),
+            DsDigest::Sha384 => (4, sha384(&signed).to_vec()),
+        };
+        let mut rdata = self.key_tag().to_be_bytes().to_vec();
+        rdata.push(self.algorithm.number());
+        rdata.push(digest_type);
+        rdata.extend_from_slice(&digest);
+        rdata
+    }
+
+    /// Returns the DNSKEY record in the presentation format of zone files.
+    pub fn dnskey_record(&self, ttl: u32) -> String {
+        format!(
+            "{} {} IN DNSKEY {} {} {} {}",
+            name_to_string(&self.zone),
+            ttl,
+            self.flags,
+            PROTOCOL,
+            self.algorithm.number(),
+            BASE64_STANDARD.encode(&self.key_data)
+        )
+    }
+
+    /// Returns the DS record in the presentation format of zone files.
+    pub fn ds_record(&self, ttl: u32, digest: DsDigest) -> String {
+        let rdata = self.ds_rdata(digest);
+        let digest: String = rdata[4..]
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        format!(
+            "{} {} IN DS {} {} {} {}",
+            name_to_string(&self.zone),
+            ttl,
+            self.key_tag(),
+            rdata[2],
+            rdata[3],
+            digest
+        )
+    }
+}
+
+/// The records of one name, type and class, in canonical form.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RrSet {
+    name: Vec<u8>,
+    rtype: u16,
+    class: u16,
+    ttl: u32,
+    rdatas: Vec<Vec<u8>>,
+}
+
+impl RrSet {
+    /// Creates an RRset of the class `IN`.
+    ///
+    /// The records are sorted and duplicates are removed, as RFC 4034, section 6.3 requires
+    /// for signing.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `RrSet`, or a `SecurityModuleError::SigningError` if `name`
+    /// is not a valid name, `rdatas` is empty or an RDATA is longer than 65535 bytes.
+    pub fn new(
+        name: &str,
+        rtype: u16,
+        ttl: u32,
+        rdatas: Vec<Vec<u8>>,
+    ) -> Result<Self, SecurityModuleError> {
+        Self::with_class(name, rtype, CLASS_IN, ttl, rdatas)
+    }
+
+    /// Creates an RRset of `class`, see `new`.
+    pub fn with_class(
+        name: &str,
+        rtype: u16,
+        class: u16,
+        ttl: u32,
+        mut rdatas: Vec<Vec<u8>>,
+    ) -> Result<Self, SecurityModuleError> {
+        let name = name_to_wire(name)?;
+        if rdatas.is_empty() {
+            return Err(invalid("An RRset must have at least one record"));
+        }
+        if rdatas.iter().any(|rdata| rdata.len() > u16::MAX as usize) {
+            return Err(invalid("RDATA must not be longer than 65535 bytes"));
+        }
+        rdatas.sort();
+        rdatas.dedup();
+        Ok(Self {
+            name,
+            rtype,
+            class,
+            ttl,
+            rdatas,
+        })
+    }
+
+    /// Returns the records in canonical order.
+    pub fn rdatas(&self) -> &[Vec<u8>] {
+        &self.rdatas
+    }
+}
+
+/// The RDATA of an RRSIG record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rrsig {
+    type_covered: u16,
+    algorithm: u8,
+    labels: u8,
+    original_ttl: u32,
+    expiration: u32,
+    inception: u32,
+    key_tag: u16,
+    signer_name: Vec<u8>,
+    signature: Vec<u8>,
+}
+
+impl Rrsig {
+    /// Returns the type of the signed RRset.
+    pub fn type_covered(&self) -> u16 {
+        self.type_covered
+    }
+
+    /// Returns the key tag of the key the signature claims to be created with.
+    pub fn key_tag(&self) -> u16 {
+        self.key_tag
+    }
+
+    /// Returns the zone of the key, e.g. `example.net.`.
+    pub fn signer_name(&self) -> String {
+        name_to_string(&self.signer_name)
+    }
+
+    /// Returns the start of the validity of the signature.
+    pub fn inception(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(self.inception.into())
+    }
+
+    /// Returns the end of the validity of the signature.
+    pub fn expiration(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(self.expiration.into())
+    }
+
+    /// Returns the signature, `r || s` for ECDSA.
+    pub fn signature(&self) -> &[u8] {
+        &self.signature
+    }
+
+    /// Returns the wire format of the RRSIG RDATA.
+    pub fn to_rdata(&self) -> Vec<u8> {
+        [self.signed_fields(), self.signature.clone()].concat()
+    }
+
+    /// Parses the wire format of an RRSIG RDATA without verifying it.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `Rrsig`, or a
+    /// `SecurityModuleError::SignatureVerificationError` if `rdata` is malformed.
+    pub fn from_rdata(rdata: &[u8]) -> Result<Self, SecurityModuleError> {
+        let (fields, rest) = rdata
+            .split_first_chunk::<18>()
+            .ok_or_else(|| malformed("The RRSIG is truncated"))?;
+        let name_len =
+            wire_name_len(rest).ok_or_else(|| malformed("The signer name is invalid"))?;
+        let (signer_name, signature) = rest.split_at(name_len);
+        let u32_at =
+            |i: usize| u32::from_be_bytes([fields[i], fields[i + 1], fields[i + 2], fields[i + 3]]);
+        Ok(Self {
+            type_covered: u16::from_be_bytes([fields[0], fields[1]]),
+            algorithm: fields[2],
+            labels: fields[3],
+            original_ttl: u32_at(4),
+            expiration: u32_at(8),
+            inception: u32_at(12),
+            key_tag: u16::from_be_bytes([fields[16], fields[17]]),
+            signer_name: signer_name.to_ascii_lowercase(),
+            signature: signature.to_vec(),
+        })
+    }
+
+    /// Returns the RDATA without the signature, which precedes the RRset in the signed data.
+    fn signed_fields(&self) -> Vec<u8> {
+        let mut fields = self.type_covered.to_be_bytes().to_vec();
+        fields.push(self.algorithm);
+        fields.push(self.labels);
+        fields.extend_from_slice(&self.original_ttl.to_be_bytes());
+        fields.extend_from_slice(&self.expiration.to_be_bytes());
+        fields.extend_from_slice(&self.inception.to_be_bytes());
+        fields.extend_from_slice(&self.key_tag.to_be_bytes());
+        fields.extend_from_slice(&self.signer_name);
+        fields
+    }
+}
+
+/// Signs `rrset` with `key`, the loaded key of `provider`, valid from `inception` to
+/// `expiration`.
+///
+/// # Returns
+///
+/// A `Result` containing the `Rrsig`, a `SecurityModuleError::SigningError` if `rrset` is not
+/// in the zone of `key`, the validity is empty or not between 1970 and 2106, a
+/// `SecurityModuleError::InvalidPublicKey` if `provider` has another key loaded, or the error of
+/// `provider`.
+#[tracing::instrument(skip(provider, key, rrset), fields(dns.rrtype = rrset.rtype))]
+pub fn sign_rrset(
+    provider: &(impl Provider + ?Sized),
+    key: &ZoneKey,
+    rrset: &RrSet,
+    inception: SystemTime,
+    expiration: SystemTime,
+) -> Result<Rrsig, SecurityModuleError> {
+    if provider.key_metadata()?.public_key_der() != key.public_key.to_der()? {
+        return Err(SecurityModuleError::InvalidPublicKey);
+    }
+    if !is_subdomain(&rrset.name, &key.zone) {
+        return Err(invalid("The RRset is not in the zone of the key"));
+    }
+    let (inception, expiration) = (timestamp(inception)?, timestamp(expiration)?);
+    if inception >= expiration {
+        return Err(invalid("The signature must expire after its inception"));
+    }
+    let mut rrsig = Rrsig {
+        type_covered: rrset.rtype,
+        algorithm: key.algorithm.number(),
+        labels: labels(&rrset.name),
+        original_ttl: rrset.ttl,
+        expiration,
+        inception,
+        key_tag: key.key_tag(),
+        signer_name: key.zone.clone(),
+        signature: Vec::new(),
+    };
+    let signature = provider.sign_data(&signed_data(&rrsig, rrset, &rrset.name))?;
+    rrsig.signature = match key.algorithm.scalar_len() {
+        Some(scalar_len) => signature_format::der_to_raw(&signature, scalar_len)?,
+        None => signature,
+    };
+    Ok(rrsig)
+}
+
+/// Verifies that `rrsig` was created over `rrset` with `key` and is valid at `now`.
+///
+/// RRsets synthesized from a wildcard are accepted if `rrsig` covers the wildcard.
+///
+/// # Returns
+///
+/// A `Result` that is `Ok(())` if the signature is valid, or a
+/// `SecurityModuleError::InvalidSignature` if it was created by another key, over another
+/// RRset, or is not valid at `now`.
+#[tracing::instrument(skip_all, fields(dns.rrtype = rrset.rtype))]
+pub fn verify_rrset(
+    rrsig: &Rrsig,
+    key: &ZoneKey,
+    rrset: &RrSet,
+    now: SystemTime,
+) -> Result<(), SecurityModuleError> {
+    let now = timestamp(now).map_err(|_| SecurityModuleError::InvalidSignature)?;
+    if rrsig.type_covered != rrset.rtype
+        || rrsig.algorithm != key.algorithm.number()
+        || rrsig.key_tag != key.key_tag()
+        || rrsig.signer_name != key.zone
+        || !is_subdomain(&rrset.name, &key.zone)
+        || now < rrsig.inception
+        || now > rrsig.expiration
+    {
+        return Err(SecurityModuleError::InvalidSignature);
+    }
+    let owner = match labels(&rrset.name).checked_sub(rrsig.labels) {
+        Some(0) => rrset.name.clone(),
+        // The RRset was expanded from the wildcard with the rightmost labels of its name.
+        Some(extra) => {
+            let mut rest = &rrset.name[..];
+            for _ in 0..extra {
+                rest = &rest[1 + rest[0] as usize..];
+            }
+            [&[1, b'*'][..], rest].concat()
+        }
+        None => return Err(SecurityModuleError::InvalidSignature),
+    };
+    let signature = match key.algorithm.scalar_len() {
+        Some(scalar_len) if rrsig.signature.len() == 2 * scalar_len => {
+            signature_format::raw_to_der(&rrsig.signature)
+                .map_err(|_| SecurityModuleError::InvalidSignature)?
+        }
+        Some(_) => return Err(SecurityModuleError::InvalidSignature),
+        None => rrsig.signature.clone(),
+    };
+    match key
+        .public_key
+        .verify(&signed_data(rrsig, rrset, &owner), &signature)?
+    {
+        true => Ok(()),
+        false => Err(SecurityModuleError::InvalidSignature),
+    }
+}
+
+/// Returns the data an RRSIG is created over, RFC 4034, section 3.1.8.1.
+fn signed_data(rrsig: &Rrsig, rrset: &RrSet, owner: &[u8]) -> Vec<u8> {
+    let mut data = rrsig.signed_fields();
+    for rdata in &rrset.rdatas {
+        data.extend_from_slice(owner);
+        data.extend_from_slice(&rrset.rtype.to_be_bytes());
+        data.extend_from_slice(&rrset.class.to_be_bytes());
+        data.extend_from_slice(&rrsig.original_ttl.to_be_bytes());
+        data.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+        data.extend_from_slice(rdata);
+    }
+    data
+}
+
+/// Encodes `name` in the canonical wire format, in lower case and uncompressed.
+fn name_to_wire(name: &str) -> Result<Vec<u8>, SecurityModuleError> {
+    let name = name.strip_suffix('.').unwrap_or(name);
+    let mut wire = Vec::with_capacity(name.len() + 2);
+    if !name.is_empty() {
+        for label in name.split('.') {
+            if label.is_empty() || label.len() > MAX_LABEL_LEN {
+                return Err(invalid("Labels must have 1 to 63 bytes"));
+            }
+            if !label
+                .bytes()
+                .all(|byte| byte.is_ascii_graphic() && byte != b'\\')
+            {
+                return Err(invalid("Names must be printable ASCII without escapes"));
+            }
+            wire.push(label.len() as u8);
+            wire.extend_from_slice(label.to_ascii_lowercase().as_bytes());
+        }
+    }
+    wire.push(0);
+    if wire.len() > MAX_NAME_LEN {
+        return Err(invalid("Names must not be longer than 255 bytes"));
+    }
+    Ok(wire)
+}
+
+fn name_to_string(wire: &[u8]) -> String {
+    let mut name = String::new();
+    let mut rest = wire;
+    while let Some((&len, tail)) = rest.split_first() {
+        if len == 0 || tail.len() < len as usize {
+            break;
+        }
+        name.push_str(&String::from_utf8_lossy(&tail[..len as usize]));
+        name.push('.');
+        rest = &tail[len as usize..];
+    }
+    if name.is_empty() {
+        name.push('.');
+    }
+    name
+}
+
+/// Returns the length of the uncompressed name at the start of `bytes`.
+fn wire_name_len(bytes: &[u8]) -> Option<usize> {
+    let mut len = 0;
+    loop {
+        let &label_len = bytes.get(len)?;
+        if label_len as usize > MAX_LABEL_LEN {
+            return None;
+        }
+        len += 1 + label_len as usize;
+        if len > MAX_NAME_LEN || len > bytes.len() {
+            return None;
+        }
+        if label_len == 0 {
+            return Some(len);
+        }
+    }
+}
+
+/// Returns the labels of the RRSIG of `name`, which do not count the root and a wildcard.
+fn labels(name: &[u8]) -> u8 {
+    let mut count = 0;
+    let mut rest = name;
+    while let Some((&len, tail)) = rest.split_first() {
+        if len == 0 {
+            break;
+        }
+        count += 1;
+        rest = &tail[len as usize..];
+    }
+    match name.starts_with(&[1, b'*']) {
+        true => count - 1,
+        false => count,
+    }
+}
+
+/// Returns whether `name` is `zone` or below it.
+fn is_subdomain(name: &[u8], zone: &[u8]) -> bool {
+    let mut rest = name;
+    loop {
+        if rest == zone {
+            return true;
+        }
+        match rest.split_first() {
+            Some((&len, tail)) if len > 0 => rest = &tail[len as usize..],
+            _ => return false,
+        }
+    }
+}
+
+fn timestamp(time: SystemTime) -> Result<u32, SecurityModuleError> {
+    time.duration_since(UNIX_EPOCH)
+        .ok()
+        .and_then(|since| u32::try_from(since.as_secs()).ok())
+        .ok_or_else(|| invalid("DNSSEC times must be between 1970 and 2106"))
+}
+
+fn invalid(message: &str) -> SecurityModuleError {
+    SecurityModuleError::SigningError(message.to_owned())
+}
+
+fn malformed(message: &str) -> SecurityModuleError {
+    SecurityModuleError::SignatureVerificationError(message.to_owned())
+}
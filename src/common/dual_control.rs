@@ -0,0 +1,517 @@
+//! Dual control: signing with designated keys only after two independent approvals.
+//!
+//! Keys that sign releases, payments or certificates should not be usable by a single person or
+//! a single compromised process. A `DualControlProvider` asks its `Approver`s before every
+//! signature with a key of its `DualControlPolicy`, and only signs once enough distinct approvers
+//! agreed, e.g. two people confirming with their fingerprint or an approval service:
+//!
+//! ```rust,ignore
+//! use crypto_layer::common::dual_control::{
+//!     CallbackApprover, DualControlPolicy, DualControlProvider,
+//! };
+//!
+//! let policy = DualControlPolicy::builder(["release-signing"])
+//!     .with_approver(CallbackApprover::new("alice", |request| prompt_biometry("alice", request)))
+//!     .with_approver(CallbackApprover::new("bob", |request| approval_service.ask(request)))
+//!     .build()?;
+//! let provider = DualControlProvider::new(provider, "release-signing".to_owned(), policy)
+//!     .with_record_sink(|record| store_approval(record));
+//! provider.sign_data(&release)?; // asks alice and bob first
+//! ```
+//!
+//! Approvers are asked in order until `required` of them approved. An approver that declines or
+//! fails is skipped, so a single unavailable approver does not block signing as long as enough
+//! others approve. Exporting the private key of a designated key requires the same approvals,
+//! since it would bypass the control. Keys that are not designated are used without asking.
+//!
+//! Every decision is recorded in an `ApprovalRecord` naming the key, the operation, the SHA-256
+//! hash of the data and the decision of every approver asked, which is passed to the record sink,
+//! e.g. to store it next to the `audit` log.
+
+use crate::common::{
+    crypto::{
+        key_metadata::KeyMetadata, key_spec::KeySpec, operation_context::OperationContext,
+        secret::SecretBytes,
+    },
+    error::SecurityModuleError,
+    latency::{LatencySnapshot, ProviderOperation},
+    session_pool::SessionPoolMetrics,
+    traits::{key_handle::KeyHandle, module_provider::Provider},
+};
+use openssl::sha::sha256;
+use serde::{Deserialize, Serialize};
+use std::{
+    any::Any,
+    collections::BTreeSet,
+    fmt,
+    sync::{Arc, Mutex, MutexGuard},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// The number of approvals required by default.
+pub const DEFAULT_REQUIRED_APPROVALS: usize = 2;
+
+/// Decides on a request, see `CallbackApprover`.
+type ApproveFn = dyn Fn(&ApprovalRequest<'_>) -> Result<bool, SecurityModuleError> + Send + Sync;
+
+/// Receives the approval records of a `DualControlProvider`.
+type RecordSink = dyn Fn(&ApprovalRecord) + Send + Sync;
+
+/// An operation waiting for approval.
+#[derive(Debug, Clone, Copy)]
+pub struct ApprovalRequest<'a> {
+    /// The id of the designated key.
+    pub key_id: &'a str,
+    pub operation: ProviderOperation,
+    /// The data to be signed, empty for `ExportPrivateKey`.
+    pub data: &'a [u8],
+    /// The SHA-256 hash of `data` in hex, e.g. to be shown to the approver.
+    pub data_hash: &'a str,
+}
+
+/// Decides whether an operation may proceed, e.g. by asking a person for a biometric
+/// confirmation or by calling an external approval service.
+pub trait Approver: Send + Sync {
+    /// Returns the identity of the approver. The approvals of one operation must come from
+    /// approvers with different identities.
+    fn id(&self) -> &str;
+
+    /// Returns whether the approver approves `request`.
+    fn approve(&self, request: &ApprovalRequest<'_>) -> Result<bool, SecurityModuleError>;
+}
+
+/// An `Approver` calling a closure.
+pub struct CallbackApprover {
+    id: String,
+    callback: Box<ApproveFn>,
+}
+
+impl CallbackApprover {
+    /// Creates an approver with the identity `id` that decides by calling `callback`.
+    pub fn new(
+        id: impl Into<String>,
+        callback: impl Fn(&ApprovalRequest<'_>) -> Result<bool, SecurityModuleError>
+            + Send
+            + Sync
+            + 'static,
+    ) -> Self {
+        Self {
+            id: id.into(),
+            callback: Box::new(callback),
+        }
+    }
+}
+
+impl fmt::Debug for CallbackApprover {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CallbackApprover")
+            .field("id", &self.id)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Approver for CallbackApprover {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn approve(&self, request: &ApprovalRequest<'_>) -> Result<bool, SecurityModuleError> {
+        (self.callback)(request)
+    }
+}
+
+/// The decision of one approver.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "decision", rename_all = "snake_case")]
+pub enum ApprovalDecision {
+    Approved,
+    Declined,
+    /// The approver failed, e.g. because the user cancelled the prompt.
+    Failed {
+        /// The stable code of the error, see `SecurityModuleError::code`.
+        code: u32,
+        message: String,
+    },
+}
+
+/// An approver asked for an operation and its decision.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApproverDecision {
+    pub approver: String,
+    #[serde(flatten)]
+    pub decision: ApprovalDecision,
+}
+
+/// The record of the approval of one operation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApprovalRecord {
+    /// Milliseconds since the Unix epoch.
+    pub timestamp: u64,
+    pub key_id: String,
+    pub operation: ProviderOperation,
+    /// The SHA-256 hash of the data in hex.
+    pub data_hash: String,
+    /// The approvers asked, in order.
+    pub decisions: Vec<ApproverDecision>,
+    /// Whether the operation was allowed to proceed.
+    pub granted: bool,
+}
+
+impl ApprovalRecord {
+    /// Returns the approvers that approved the operation.
+    pub fn approvers(&self) -> impl Iterator<Item = &str> {
+        self.decisions
+            .iter()
+            .filter(|decision| decision.decision == ApprovalDecision::Approved)
+            .map(|decision| decision.approver.as_str())
+    }
+}
+
+/// The keys under dual control and their approvers.
+pub struct DualControlPolicy {
+    keys: BTreeSet<String>,
+    approvers: Vec<Arc<dyn Approver>>,
+    required: usize,
+}
+
+impl DualControlPolicy {
+    /// Starts a policy for the keys `key_ids`, requiring `DEFAULT_REQUIRED_APPROVALS` approvals.
+    pub fn builder<I>(key_ids: I) -> DualControlPolicyBuilder
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        DualControlPolicyBuilder {
+            keys: key_ids.into_iter().map(Into::into).collect(),
+            approvers: Vec::new(),
+            required: DEFAULT_REQUIRED_APPROVALS,
+        }
+    }
+
+    /// Returns whether `key_id` is under dual control.
+    pub fn covers(&self, key_id: &str) -> bool {
+        self.keys.contains(key_id)
+    }
+
+    /// Returns the number of approvals an operation requires.
+    pub fn required(&self) -> usize {
+        self.required
+    }
+
+    /// Asks the approvers until `required` of them approved.
+    fn request(&self, key_id: &str, operation: ProviderOperation, data: &[u8]) -> ApprovalRecord {
+        let data_hash = hex(&sha256(data));
+        let request = ApprovalRequest {
+            key_id,
+            operation,
+            data,
+            data_hash: &data_hash,
+        };
+        let mut decisions = Vec::new();
+        let mut approvals = 0;
+        for approver in &self.approvers {
+            if approvals == self.required {
+                break;
+            }
+            let decision = match approver.approve(&request) {
+                Ok(true) => {
+                    approvals += 1;
+                    ApprovalDecision::Approved
+                }
+                Ok(false) => ApprovalDecision::Declined,
+                Err(e) => ApprovalDecision::Failed {
+                    code: e.code(),
+                    message: e.to_string(),
+                },
+            };
+            decisions.push(ApproverDecision {
+                approver: approver.id().to_owned(),
+                decision,
+            });
+        }
+
+        ApprovalRecord {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |duration| duration.as_millis() as u64),
+            key_id: key_id.to_owned(),
+            operation,
+            data_hash,
+            decisions,
+            granted: approvals == self.required,
+        }
+    }
+}
+
+impl fmt::Debug for DualControlPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DualControlPolicy")
+            .field("keys", &self.keys)
+            .field(
+                "approvers",
+                &self.approvers.iter().map(|a| a.id()).collect::<Vec<_>>(),
+            )
+            .field("required", &self.required)
+            .finish()
+    }
+}
+
+/// Builds a `DualControlPolicy`, see `DualControlPolicy::builder`.
+pub struct DualControlPolicyBuilder {
+    keys: BTreeSet<String>,
+    approvers: Vec<Arc<dyn Approver>>,
+    required: usize,
+}
+
+impl DualControlPolicyBuilder {
+    /// Adds an approver, which is asked after the approvers added before.
+    pub fn with_approver(mut self, approver: impl Approver + 'static) -> Self {
+        self.approvers.push(Arc::new(approver));
+        self
+    }
+
+    /// Sets the number of approvals an operation requires, at least 2.
+    pub fn required(mut self, required: usize) -> Self {
+        self.required = required;
+        self
+    }
+
+    /// Checks the policy and creates it.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the policy on success, or a
+    /// `SecurityModuleError::InitializationError` if fewer than 2 approvals are required, two
+    /// approvers share an id or there are fewer approvers than required approvals.
+    pub fn build(self) -> Result<DualControlPolicy, SecurityModuleError> {
+        let invalid = |message: String| Err(SecurityModuleError::InitializationError(message));
+        if self.required < DEFAULT_REQUIRED_APPROVALS {
+            return invalid(format!(
+                "Dual control requires at least {} approvals",
+                DEFAULT_REQUIRED_APPROVALS
+            ));
+        }
+        let mut ids = BTreeSet::new();
+        for approver in &self.approvers {
+            if !ids.insert(approver.id()) {
+                return invalid(format!("The approver '{}' is added twice", approver.id()));
+            }
+        }
+        if self.approvers.len() < self.required {
+            return invalid(format!(
+                "{} approvals are required, but there are only {} approvers",
+                self.required,
+                self.approvers.len()
+            ));
+        }
+        Ok(DualControlPolicy {
+            keys: self.keys,
+            approvers: self.approvers,
+            required: self.required,
+        })
+    }
+}
+
+/// A provider that only signs with the keys of its `DualControlPolicy` after the approvers
+/// approved, see the module documentation.
+pub struct DualControlProvider {
+    inner: Arc<Mutex<dyn Provider>>,
+    key_id: Mutex<String>,
+    policy: DualControlPolicy,
+    record_sink: Option<Box<RecordSink>>,
+}
+
+impl DualControlProvider {
+    /// Wraps a provider.
+    ///
+    /// # Arguments
+    ///
+    /// * `inner` - The provider holding the keys.
+    /// * `key_id` - The id of the key the provider operates on, until `create_key` or `load_key`
+    ///   is called with another one.
+    /// * `policy` - The keys under dual control and their approvers.
+    pub fn new(inner: Arc<Mutex<dyn Provider>>, key_id: String, policy: DualControlPolicy) -> Self {
+        Self {
+            inner,
+            key_id: Mutex::new(key_id),
+            policy,
+            record_sink: None,
+        }
+    }
+
+    /// Passes the record of every approval to `sink`, whether it was granted or not.
+    pub fn with_record_sink(
+        mut self,
+        sink: impl Fn(&ApprovalRecord) + Send + Sync + 'static,
+    ) -> Self {
+        self.record_sink = Some(Box::new(sink));
+        self
+    }
+
+    fn inner(&self) -> MutexGuard<'_, dyn Provider + 'static> {
+        self.inner
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn key_id(&self) -> MutexGuard<'_, String> {
+        self.key_id
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Obtains the approvals for `operation` if the current key is under dual control.
+    fn authorize(
+        &self,
+        operation: ProviderOperation,
+        data: &[u8],
+    ) -> Result<(), SecurityModuleError> {
+        let key_id = self.key_id().clone();
+        if !self.policy.covers(&key_id) {
+            return Ok(());
+        }
+        let record = self.policy.request(&key_id, operation, data);
+        if let Some(sink) = &self.record_sink {
+            sink(&record);
+        }
+        if record.granted {
+            return Ok(());
+        }
+        Err(SecurityModuleError::AuthenticationFailed(format!(
+            "The key '{}' requires {} approvals for {}, but only {} approved",
+            key_id,
+            self.policy.required,
+            operation,
+            record.approvers().count()
+        )))
+    }
+}
+
+impl fmt::Debug for DualControlProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DualControlProvider")
+            .field("key_id", &*self.key_id())
+            .field("policy", &self.policy)
+            .finish_non_exhaustive()
+    }
+}
+
+impl KeyHandle for DualControlProvider {
+    fn sign_data(&self, data: &[u8]) -> Result<Vec<u8>, SecurityModuleError> {
+        self.authorize(ProviderOperation::SignData, data)?;
+        self.inner().sign_data(data)
+    }
+
+    fn decrypt_data(&self, encrypted_data: &[u8]) -> Result<SecretBytes, SecurityModuleError> {
+        self.inner().decrypt_data(encrypted_data)
+    }
+
+    fn encrypt_data(&self, data: &[u8]) -> Result<Vec<u8>, SecurityModuleError> {
+        self.inner().encrypt_data(data)
+    }
+
+    fn verify_signature(&self, data: &[u8], signature: &[u8]) -> Result<bool, SecurityModuleError> {
+        self.inner().verify_signature(data, signature)
+    }
+
+    fn sign_data_into<'a>(
+        &self,
+        data: &[u8],
+        context: &'a mut OperationContext,
+    ) -> Result<&'a [u8], SecurityModuleError> {
+        self.authorize(ProviderOperation::SignData, data)?;
+        self.inner().sign_data_into(data, context)
+    }
+
+    fn verify_signature_with(
+        &self,
+        data: &[u8],
+        signature: &[u8],
+        context: &mut OperationContext,
+    ) -> Result<bool, SecurityModuleError> {
+        self.inner().verify_signature_with(data, signature, context)
+    }
+
+    fn verify_many(&self, items: &[(&[u8], &[u8])]) -> Result<Vec<bool>, SecurityModuleError> {
+        self.inner().verify_many(items)
+    }
+
+    fn derive_shared_secret(
+        &self,
+        peer_public_key: &[u8],
+    ) -> Result<SecretBytes, SecurityModuleError> {
+        self.inner().derive_shared_secret(peer_public_key)
+    }
+}
+
+impl Provider for DualControlProvider {
+    fn create_key(
+        &mut self,
+        key_id: &str,
+        config: Box<dyn Any>,
+    ) -> Result<(), SecurityModuleError> {
+        self.inner().create_key(key_id, config)?;
+        *self.key_id() = key_id.to_owned();
+        Ok(())
+    }
+
+    fn load_key(&mut self, key_id: &str, config: Box<dyn Any>) -> Result<(), SecurityModuleError> {
+        self.inner().load_key(key_id, config)?;
+        *self.key_id() = key_id.to_owned();
+        Ok(())
+    }
+
+    fn import_wrapped_key(
+        &mut self,
+        key_id: &str,
+        wrapped_key: &[u8],
+        wrapping_key_id: &str,
+        spec: &KeySpec,
+    ) -> Result<(), SecurityModuleError> {
+        self.inner()
+            .import_wrapped_key(key_id, wrapped_key, wrapping_key_id, spec)?;
+        *self.key_id() = key_id.to_owned();
+        Ok(())
+    }
+
+    fn export_private_key(&self) -> Result<SecretBytes, SecurityModuleError> {
+        self.authorize(ProviderOperation::ExportPrivateKey, &[])?;
+        self.inner().export_private_key()
+    }
+
+    fn initialize_module(&mut self) -> Result<(), SecurityModuleError> {
+        self.inner().initialize_module()
+    }
+
+    fn session_pool_metrics(&self) -> Option<SessionPoolMetrics> {
+        self.inner().session_pool_metrics()
+    }
+
+    fn latency_snapshot(&self) -> Option<LatencySnapshot> {
+        self.inner().latency_snapshot()
+    }
+
+    fn key_metadata(&self) -> Result<KeyMetadata, SecurityModuleError> {
+        self.inner().key_metadata()
+    }
+
+    fn refresh_key_metadata(&mut self) -> Result<KeyMetadata, SecurityModuleError> {
+        self.inner().refresh_key_metadata()
+    }
+
+    fn list_keys(&self) -> Result<Vec<String>, SecurityModuleError> {
+        self.inner().list_keys()
+    }
+
+    fn delete_key(&mut self, key_id: &str) -> Result<(), SecurityModuleError> {
+        self.inner().delete_key(key_id)
+    }
+
+    fn shutdown(&mut self) -> Result<(), SecurityModuleError> {
+        self.inner().shutdown()
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
@@ -0,0 +1,199 @@
+//! Hybrid public-key encryption of messages with ECIES.
+//!
+//! `encrypt_for` encrypts a message for the owner of an EC public key, e.g. a P-256 key of the
+//! Secure Enclave of another device, and `decrypt` decrypts it with the private key of the
+//! security module. Two devices using this crate can thus exchange messages by sharing only
+//! their public keys:
+//!
+//! ```rust,ignore
+//! use crypto_layer::common::ecies;
+//!
+//! // On the sender, with the public key of the recipient.
+//! let ciphertext = ecies::encrypt_for(&recipient_public_key, b"meet at noon")?;
+//! // On the recipient, whose provider holds the private key.
+//! let plaintext = ecies::decrypt(&provider, &ciphertext)?;
+//! ```
+//!
+//! The ciphertext is an envelope, see `crypto::envelope`, which records every parameter the
+//! recipient needs:
+//!
+//! - `ephemeral_public_key` - The uncompressed SEC1 point of a fresh key pair on the curve of the
+//!   recipient, generated for every message.
+//! - `kdf` and `salt` - The payload key is derived from the x-coordinate of the ECDH shared point
+//!   with the KDF, the random salt and the info `crypto-layer/ecies/v1` followed by the
+//!   ephemeral public key.
+//! - `aead` and `nonce` - The algorithm and nonce the payload is encrypted with, with the encoded
+//!   header as associated data.
+//! - `key_id` - The `recipient_id` of the public key the message is encrypted for.
+//!
+//! `encrypt_for` uses HKDF-SHA-256 and AES-256-GCM, `encrypt_for_with` selects other algorithms.
+//! `decrypt` accepts every algorithm of the envelope format.
+
+use crate::common::{
+    crypto::{
+        aead,
+        algorithms::encryption::AsymmetricEncryption,
+        envelope::{AeadAlgorithm, Envelope},
+        kdf::Kdf,
+        public_key::PublicKey,
+        secret::SecretBytes,
+    },
+    error::SecurityModuleError,
+    input_limits,
+    traits::key_handle::KeyHandle,
+    uniform_errors::{self, FailureTiming},
+};
+use openssl::{
+    bn::BigNumContext,
+    derive::Deriver,
+    ec::{EcKey, PointConversionForm},
+    error::ErrorStack,
+    pkey::PKey,
+    rand::rand_bytes,
+    sha::sha256,
+};
+
+/// The AEAD `encrypt_for` encrypts with.
+pub const DEFAULT_AEAD: AeadAlgorithm = AeadAlgorithm::Aes256Gcm;
+
+/// The KDF `encrypt_for` derives the payload key with.
+pub const DEFAULT_KDF: Kdf = Kdf::HkdfSha256;
+
+/// The slowest failure of `decrypt`, see `uniform_errors`.
+static DECRYPTION_TIMING: FailureTiming = FailureTiming::new();
+
+/// Prefixes the ephemeral public key in the info of the key derivation.
+pub const KDF_INFO_PREFIX: &[u8] = b"crypto-layer/ecies/v1";
+
+/// The length of the random salt of the key derivation in bytes.
+const SALT_LEN: usize = 32;
+
+/// Encrypts `plaintext` for the owner of `public_key` with HKDF-SHA-256 and AES-256-GCM.
+///
+/// # Arguments
+///
+/// * `public_key` - The EC public key of the recipient.
+/// * `plaintext` - The message to be encrypted.
+///
+/// # Returns
+///
+/// A `Result` containing the encoded envelope, or a `SecurityModuleError::InvalidPublicKey` if
+/// `public_key` is not an EC key.
+pub fn encrypt_for(
+    public_key: &PublicKey,
+    plaintext: &[u8],
+) -> Result<Vec<u8>, SecurityModuleError> {
+    encrypt_for_with(public_key, plaintext, DEFAULT_AEAD, DEFAULT_KDF)
+}
+
+/// Encrypts `plaintext` for the owner of `public_key` like `encrypt_for`, with the given
+/// algorithms.
+pub fn encrypt_for_with(
+    public_key: &PublicKey,
+    plaintext: &[u8],
+    aead: AeadAlgorithm,
+    kdf: Kdf,
+) -> Result<Vec<u8>, SecurityModuleError> {
+    if !matches!(public_key.algorithm(), AsymmetricEncryption::Ecc(_)) {
+        return Err(SecurityModuleError::InvalidPublicKey);
+    }
+    let recipient = public_key
+        .pkey()
+        .ec_key()
+        .map_err(|_| SecurityModuleError::InvalidPublicKey)?;
+
+    let encryption_error = |e: ErrorStack| SecurityModuleError::EncryptionError(e.to_string());
+    let ephemeral = EcKey::generate(recipient.group()).map_err(encryption_error)?;
+    let ephemeral_public_key = BigNumContext::new()
+        .and_then(|mut ctx| {
+            ephemeral.public_key().to_bytes(
+                recipient.group(),
+                PointConversionForm::UNCOMPRESSED,
+                &mut ctx,
+            )
+        })
+        .map_err(encryption_error)?;
+    let shared_secret = PKey::from_ec_key(ephemeral)
+        .and_then(|ephemeral| {
+            let mut deriver = Deriver::new(&ephemeral)?;
+            deriver.set_peer(public_key.pkey())?;
+            deriver.derive_to_vec()
+        })
+        .map(SecretBytes::new)
+        .map_err(encryption_error)?;
+
+    let mut salt = vec![0; SALT_LEN];
+    rand_bytes(&mut salt).map_err(encryption_error)?;
+    let payload_key = kdf.derive_vec(
+        &shared_secret,
+        Some(&salt),
+        &kdf_info(&ephemeral_public_key),
+        aead.key_len(),
+    )?;
+
+    let mut envelope = Envelope::new(aead, recipient_id(public_key)?, aead::random_nonce(aead)?);
+    envelope.ephemeral_public_key = Some(ephemeral_public_key);
+    envelope.kdf = Some(kdf);
+    envelope.salt = Some(salt);
+    aead::seal(envelope, &payload_key, plaintext)
+}
+
+/// Decrypts a message of `encrypt_for` with the private key of `key_handle`.
+///
+/// # Arguments
+///
+/// * `key_handle` - Derives the shared secret with `derive_shared_secret`, e.g. a provider with
+///   the loaded key the message was encrypted for.
+/// * `ciphertext` - The encoded envelope.
+///
+/// # Returns
+///
+/// A `Result` containing the plaintext, which is zeroed when dropped, a
+/// `SecurityModuleError::InputTooLarge` if the envelope exceeds the `InputLimits` of the process,
+/// a `SecurityModuleError::DecryptionError` if the envelope is not an ECIES message, was
+/// encrypted for another key or was modified, or the error of `key_handle` if the shared secret
+/// cannot be derived for another reason. Failures caused by the ciphertext are reported
+/// uniformly, see `uniform_errors`.
+#[tracing::instrument(skip_all, fields(crypto.payload.size = ciphertext.len()))]
+pub fn decrypt(
+    key_handle: &(impl KeyHandle + ?Sized),
+    ciphertext: &[u8],
+) -> Result<SecretBytes, SecurityModuleError> {
+    uniform_errors::decryption(&DECRYPTION_TIMING, || {
+        let envelope = input_limits::validate_envelope(ciphertext)?;
+        let (ephemeral_public_key, kdf) =
+            match (envelope.ephemeral_public_key, envelope.kdf) {
+                (Some(ephemeral_public_key), Some(kdf)) => (ephemeral_public_key, kdf),
+                _ => return Err(SecurityModuleError::DecryptionError(
+                    "The envelope has no ephemeral public key or KDF, it is not an ECIES message"
+                        .to_owned(),
+                )),
+            };
+
+        let shared_secret = key_handle.derive_shared_secret(ephemeral_public_key)?;
+        let payload_key = kdf.derive_vec(
+            &shared_secret,
+            envelope.salt,
+            &kdf_info(ephemeral_public_key),
+            envelope.aead.key_len(),
+        )?;
+        aead::open(&envelope, &payload_key)
+    })
+}
+
+/// Returns the id `encrypt_for` records as key id of the envelope: `ecies:` followed by the
+/// first 16 bytes of the SHA-256 hash of the uncompressed point of `public_key` in hex.
+///
+/// Recipients with several keys can use it to select the key to decrypt with.
+pub fn recipient_id(public_key: &PublicKey) -> Result<String, SecurityModuleError> {
+    let hash = sha256(&public_key.to_ec_point()?);
+    let hex: String = hash[..16]
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    Ok(format!("ecies:{}", hex))
+}
+
+fn kdf_info(ephemeral_public_key: &[u8]) -> Vec<u8> {
+    [KDF_INFO_PREFIX, ephemeral_public_key].concat()
+}
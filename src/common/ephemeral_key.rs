@@ -0,0 +1,145 @@
+//! Short-lived keys of the security module that are deleted after use.
+//!
+//! Some protocols need a hardware-backed key only for the duration of one exchange, e.g. the
+//! ephemeral key of an attested key agreement. `with_ephemeral_key` creates such a key, runs a
+//! closure with it and deletes it again, however the closure ends:
+//!
+//! ```rust,ignore
+//! use crypto_layer::common::ephemeral_key::with_ephemeral_key;
+//!
+//! let shared_secret = with_ephemeral_key(&mut provider, &spec, TpmConfig::from_spec, |key| {
+//!     send_public_key(key.public_key()?);
+//!     key.derive_shared_secret(&receive_public_key())
+//! })?;
+//! ```
+//!
+//! The key is deleted when the closure returns, returns early with an error or panics. A failed
+//! deletion after a successful closure is returned as error, since the caller relies on the key
+//! being gone; after a failed closure or a panic it is logged as a warning. Every key gets a
+//! unique id of the label of the spec and 16 random bytes in hex, so that concurrent uses with
+//! the same spec do not collide.
+
+use crate::common::{
+    crypto::{key_spec::KeySpec, public_key::PublicKey},
+    error::SecurityModuleError,
+    key_id::MAX_KEY_ID_LEN,
+    latency::key_id_hash,
+    traits::module_provider::Provider,
+};
+use openssl::rand::rand_bytes;
+use std::{any::Any, fmt, ops::Deref};
+
+/// The number of random bytes in the id of an ephemeral key.
+const RANDOM_ID_LEN: usize = 16;
+
+/// A key created by `with_ephemeral_key`, which dereferences to the provider holding it as the
+/// loaded key.
+pub struct EphemeralKey<'a, P: Provider + ?Sized> {
+    provider: &'a mut P,
+    key_id: String,
+    deleted: bool,
+}
+
+impl<P: Provider + ?Sized> EphemeralKey<'_, P> {
+    /// Returns the id the key was created with.
+    pub fn key_id(&self) -> &str {
+        &self.key_id
+    }
+
+    /// Returns the public key, for asymmetric keys.
+    pub fn public_key(&self) -> Result<PublicKey, SecurityModuleError> {
+        Ok(self.provider.key_metadata()?.public_key().clone())
+    }
+
+    fn delete(&mut self) -> Result<(), SecurityModuleError> {
+        self.deleted = true;
+        self.provider.delete_key(&self.key_id)
+    }
+}
+
+impl<P: Provider + ?Sized> Deref for EphemeralKey<'_, P> {
+    type Target = P;
+
+    fn deref(&self) -> &P {
+        self.provider
+    }
+}
+
+impl<P: Provider + ?Sized> Drop for EphemeralKey<'_, P> {
+    fn drop(&mut self) {
+        if self.deleted {
+            return;
+        }
+        if let Err(e) = self.delete() {
+            tracing::warn!(
+                key_id_hash = %key_id_hash(&self.key_id),
+                error = %e,
+                "Failed to delete an ephemeral key"
+            );
+        }
+    }
+}
+
+impl<P: Provider + ?Sized> fmt::Debug for EphemeralKey<'_, P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EphemeralKey")
+            .field("key_id", &self.key_id)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Creates a key for `spec`, runs `f` with it and deletes the key afterwards.
+///
+/// # Arguments
+///
+/// * `provider` - The provider creating the key. It has no loaded key afterwards.
+/// * `spec` - The spec of the key, whose label prefixes the id of the key.
+/// * `config` - Creates the configuration of the provider for `spec`, e.g. `TpmConfig::from_spec`.
+/// * `f` - Uses the key, which is the loaded key of the provider while it runs.
+///
+/// # Returns
+///
+/// A `Result` containing the result of `f`, the error of `f`, or the error of the provider if the
+/// key cannot be created or, after `f` succeeded, cannot be deleted.
+#[tracing::instrument(skip_all)]
+pub fn with_ephemeral_key<P, T>(
+    provider: &mut P,
+    spec: &KeySpec,
+    config: impl FnOnce(&KeySpec) -> Result<Box<dyn Any>, SecurityModuleError>,
+    f: impl FnOnce(&EphemeralKey<'_, P>) -> Result<T, SecurityModuleError>,
+) -> Result<T, SecurityModuleError>
+where
+    P: Provider + ?Sized,
+{
+    let key_id = ephemeral_key_id(spec.label())?;
+    provider.create_key(&key_id, config(spec)?)?;
+    let mut key = EphemeralKey {
+        provider,
+        key_id,
+        deleted: false,
+    };
+
+    let result = f(&key);
+    let deleted = key.delete();
+    match (result, deleted) {
+        (Ok(_), Err(e)) => Err(e),
+        (result, Err(e)) => {
+            tracing::warn!(
+                key_id_hash = %key_id_hash(key.key_id()),
+                error = %e,
+                "Failed to delete an ephemeral key"
+            );
+            result
+        }
+        (result, Ok(())) => result,
+    }
+}
+
+/// Returns the label, shortened to fit, followed by `.` and `RANDOM_ID_LEN` random bytes in hex.
+fn ephemeral_key_id(label: &str) -> Result<String, SecurityModuleError> {
+    let mut random = [0; RANDOM_ID_LEN];
+    rand_bytes(&mut random).map_err(|e| SecurityModuleError::InitializationError(e.to_string()))?;
+    let suffix: String = random.iter().map(|byte| format!("{:02x}", byte)).collect();
+    let prefix = &label[..label.len().min(MAX_KEY_ID_LEN - 1 - suffix.len())];
+    Ok(format!("{}.{}", prefix, suffix))
+}
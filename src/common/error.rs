@@ -3,6 +3,7 @@ use crate::tpm::core::error::TpmError;
 
 #[cfg(feature = "hsm")]
 use crate::hsm::core::error::HsmError;
+use crypto_layer_core::CoreError;
 use std::fmt;
 
 /// Represents errors that can occur within a security module.
@@ -49,6 +50,146 @@ pub enum SecurityModuleError {
     InvalidSignature,
     InvalidPublicKey,
     SigningFailed,
+    /// No session became available within the acquire timeout of a session pool.
+    SessionPoolTimeout,
+    /// Error that occurred while encoding or decoding an envelope, signature or derived key.
+    Encoding(CoreError),
+    /// The security module refused to use a key because the user or application could not be
+    /// authenticated, e.g. a wrong PIN or a cancelled biometric prompt.
+    ///
+    /// This variant contains a descriptive error message.
+    AuthenticationFailed(String),
+    /// A key specification was rejected before any security module was used, e.g. because its
+    /// algorithm does not support the requested usage.
+    ///
+    /// This variant contains a descriptive error message.
+    InvalidKeySpec(String),
+    /// A key id does not meet the rules of `KeyId`, which every security module accepts.
+    ///
+    /// This variant contains a descriptive error message.
+    InvalidKeyId(String),
+    /// A key was to be created with an algorithm that the configuration no longer allows for new
+    /// keys, see `sunset`.
+    ///
+    /// This variant contains a descriptive error message.
+    DeprecatedAlgorithm(String),
+    /// The storage of a `SecretsVault` failed to read, write or delete a secret, see `vault`, a
+    /// `MonotonicCounter` failed to read or increment its value, see `anti_rollback`, or a
+    /// `MetadataStore` failed to read, write or delete a record, see `metadata_store`.
+    ///
+    /// This variant contains a descriptive error message.
+    SecretStorage(String),
+    /// A proof of possession was rejected, e.g. because it was created for another audience or
+    /// challenge or has expired, see `proof_of_possession`.
+    ///
+    /// This variant contains a descriptive error message.
+    InvalidProof(String),
+    /// A capability token was rejected, e.g. because it was issued for another audience or has
+    /// expired, see `capability_token`.
+    ///
+    /// This variant contains a descriptive error message.
+    InvalidToken(String),
+    /// The security module does not support an operation at all, e.g. importing keys into a
+    /// module that only generates keys itself.
+    ///
+    /// This variant contains a descriptive error message.
+    UnsupportedOperation(String),
+    /// An input exceeds the `InputLimits` of the process and was rejected before any security
+    /// module was used, see `input_limits`.
+    ///
+    /// This variant contains a descriptive error message.
+    InputTooLarge(String),
+    /// Sealed data was rejected because it is older or newer than the state of its monotonic
+    /// counter, i.e. it or the counter was rolled back, see `anti_rollback`.
+    ///
+    /// This variant contains a descriptive error message.
+    RollbackDetected(String),
+    /// A key, cipher or hash was rejected because it is not approved for FIPS mode, see `fips`.
+    ///
+    /// This variant contains a descriptive error message.
+    NotFipsApproved(String),
+}
+
+impl SecurityModuleError {
+    /// Returns the stable numeric code of the error variant.
+    ///
+    /// Codes never change and are never reused, so applications can persist them or pass them
+    /// across language boundaries. New variants get new codes. Codes, messages and the codes of
+    /// `CoreError` are pinned by the golden files in `src/tests/common/golden/`.
+    pub fn code(&self) -> u32 {
+        match *self {
+            SecurityModuleError::SigningError(_) => 1,
+            SecurityModuleError::DecryptionError(_) => 2,
+            SecurityModuleError::EncryptionError(_) => 3,
+            SecurityModuleError::SignatureVerificationError(_) => 4,
+            SecurityModuleError::InitializationError(_) => 5,
+            SecurityModuleError::KeyError => 6,
+            SecurityModuleError::UnsupportedAlgorithm => 7,
+            SecurityModuleError::VerificationFailed => 8,
+            SecurityModuleError::InvalidSignature => 9,
+            SecurityModuleError::InvalidPublicKey => 10,
+            SecurityModuleError::SigningFailed => 11,
+            SecurityModuleError::SessionPoolTimeout => 12,
+            SecurityModuleError::Encoding(_) => 13,
+            SecurityModuleError::AuthenticationFailed(_) => 14,
+            SecurityModuleError::InvalidKeySpec(_) => 15,
+            SecurityModuleError::InvalidKeyId(_) => 16,
+            SecurityModuleError::DeprecatedAlgorithm(_) => 17,
+            SecurityModuleError::SecretStorage(_) => 18,
+            SecurityModuleError::InvalidProof(_) => 19,
+            SecurityModuleError::InvalidToken(_) => 20,
+            SecurityModuleError::UnsupportedOperation(_) => 21,
+            SecurityModuleError::InputTooLarge(_) => 22,
+            SecurityModuleError::RollbackDetected(_) => 23,
+            SecurityModuleError::NotFipsApproved(_) => 24,
+            #[cfg(feature = "hsm")]
+            SecurityModuleError::Hsm(_) => 100,
+            #[cfg(feature = "tpm")]
+            SecurityModuleError::Tpm(_) => 101,
+            #[cfg(feature = "nks")]
+            SecurityModuleError::NksError => 102,
+        }
+    }
+
+    /// Returns whether the error reports a failed authentication to the security module.
+    ///
+    /// Besides `AuthenticationFailed`, this includes the authentication errors of HSMs.
+    pub fn is_authentication_failure(&self) -> bool {
+        #[cfg(feature = "hsm")]
+        if let SecurityModuleError::Hsm(HsmError::Authentication(_)) = self {
+            return true;
+        }
+        matches!(self, SecurityModuleError::AuthenticationFailed(_))
+    }
+}
+
+/// The stable code and the message of an error, e.g. to return it from a service or to store it.
+///
+/// With the `serde` feature, `SecurityModuleError` serializes as its report, e.g.
+/// `{"code": 7, "message": "Unsupported algorithm"}`, and reports can be deserialized again.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ErrorReport {
+    /// The code of the error variant, see `SecurityModuleError::code`.
+    pub code: u32,
+    /// The `Display` representation of the error.
+    pub message: String,
+}
+
+impl From<&SecurityModuleError> for ErrorReport {
+    fn from(err: &SecurityModuleError) -> Self {
+        ErrorReport {
+            code: err.code(),
+            message: err.to_string(),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for SecurityModuleError {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        ErrorReport::from(self).serialize(serializer)
+    }
 }
 
 impl fmt::Display for SecurityModuleError {
@@ -86,7 +227,44 @@ impl fmt::Display for SecurityModuleError {
             SecurityModuleError::VerificationFailed => write!(f, "Verification failed"),
             SecurityModuleError::InvalidSignature => write!(f, "Invalid signature"),
             SecurityModuleError::InvalidPublicKey => write!(f, "Invalid public key"),
-            SecurityModuleError::SigningFailed => write!(f, "Invalid public key"),
+            SecurityModuleError::SigningFailed => write!(f, "Signing failed"),
+            SecurityModuleError::SessionPoolTimeout => {
+                write!(f, "Timed out waiting for a free session")
+            }
+            SecurityModuleError::Encoding(ref err) => write!(f, "Encoding error: {}", err),
+            SecurityModuleError::AuthenticationFailed(ref error_msg) => {
+                write!(f, "Authentication failed: {}", error_msg)
+            }
+            SecurityModuleError::InvalidKeySpec(ref error_msg) => {
+                write!(f, "Invalid key spec: {}", error_msg)
+            }
+            SecurityModuleError::InvalidKeyId(ref error_msg) => {
+                write!(f, "Invalid key id: {}", error_msg)
+            }
+            SecurityModuleError::DeprecatedAlgorithm(ref error_msg) => {
+                write!(f, "Deprecated algorithm: {}", error_msg)
+            }
+            SecurityModuleError::SecretStorage(ref error_msg) => {
+                write!(f, "Secret storage error: {}", error_msg)
+            }
+            SecurityModuleError::InvalidProof(ref error_msg) => {
+                write!(f, "Invalid proof of possession: {}", error_msg)
+            }
+            SecurityModuleError::InvalidToken(ref error_msg) => {
+                write!(f, "Invalid capability token: {}", error_msg)
+            }
+            SecurityModuleError::UnsupportedOperation(ref error_msg) => {
+                write!(f, "Unsupported operation: {}", error_msg)
+            }
+            SecurityModuleError::InputTooLarge(ref error_msg) => {
+                write!(f, "Input too large: {}", error_msg)
+            }
+            SecurityModuleError::RollbackDetected(ref error_msg) => {
+                write!(f, "Rollback detected: {}", error_msg)
+            }
+            SecurityModuleError::NotFipsApproved(ref error_msg) => {
+                write!(f, "Not FIPS approved: {}", error_msg)
+            }
             #[cfg(feature = "nks")]
             SecurityModuleError::NksError => write!(f, "Key error"),
         }
@@ -99,7 +277,7 @@ impl std::error::Error for SecurityModuleError {
     /// This method helps in understanding and diagnosing the underlying cause of the error,
     /// particularly useful when debugging or logging error information.
     ///
-    /// For errors originating from an HSM or TPM, the source error is returned.
+    /// For errors originating from an HSM or TPM, or from the encoding layer, the source error is returned.
     /// For other error variants, `None` is returned, as they do not have an underlying source error.
     #[tracing::instrument]
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
@@ -121,10 +299,31 @@ impl std::error::Error for SecurityModuleError {
             #[cfg(feature = "nks")]
             SecurityModuleError::NksError => None,
             SecurityModuleError::SigningFailed => None,
+            SecurityModuleError::SessionPoolTimeout => None,
+            SecurityModuleError::Encoding(ref err) => Some(err),
+            SecurityModuleError::AuthenticationFailed(_) => None,
+            SecurityModuleError::InvalidKeySpec(_) => None,
+            SecurityModuleError::InvalidKeyId(_) => None,
+            SecurityModuleError::DeprecatedAlgorithm(_) => None,
+            SecurityModuleError::SecretStorage(_) => None,
+            SecurityModuleError::InvalidProof(_) => None,
+            SecurityModuleError::InvalidToken(_) => None,
+            SecurityModuleError::UnsupportedOperation(_) => None,
+            SecurityModuleError::InputTooLarge(_) => None,
+            SecurityModuleError::RollbackDetected(_) => None,
+            SecurityModuleError::NotFipsApproved(_) => None,
         }
     }
 }
 
+impl From<CoreError> for SecurityModuleError {
+    /// Converts a `CoreError` into a `SecurityModuleError`.
+    #[tracing::instrument]
+    fn from(err: CoreError) -> SecurityModuleError {
+        SecurityModuleError::Encoding(err)
+    }
+}
+
 #[cfg(feature = "hsm")]
 impl From<HsmError> for SecurityModuleError {
     /// Converts an `HsmError` into a `SecurityModuleError`.
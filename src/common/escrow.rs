@@ -0,0 +1,276 @@
+//! Backup and restore of application data keys.
+//!
+//! Keys of the Secure Enclave or the TPM never leave the device, so application data that is
+//! only encrypted under them is lost with the device. Applications that need to restore their
+//! data on a new device keep their data keys wrapped under a device key and escrow them in an
+//! encrypted backup bundle:
+//!
+//! ```rust,ignore
+//! use crypto_layer::common::escrow::{self, Recovery, RecoveryKey};
+//!
+//! // On the old device, the data keys are encrypted with `encrypt_data` of `provider`.
+//! let recovery_key = RecoveryKey::PublicKey(&recovery_public_key);
+//! let bundle = escrow::export_wrapped_keys(&provider, &wrapped_keys, &recovery_key)?;
+//!
+//! // On the new device, with the private recovery key in another provider, e.g. a smart card.
+//! let recovery = Recovery::KeyHandle(&recovery_provider);
+//! let wrapped_keys = escrow::import_wrapped_keys(&new_provider, &bundle, recovery)?;
+//! ```
+//!
+//! The bundle is an envelope, see `crypto::envelope`, encrypted for the recovery key:
+//!
+//! - `RecoveryKey::PublicKey` encrypts it for an EC public key with `ecies`, the key id of the
+//!   envelope is the `ecies::recipient_id` of the key.
+//! - `RecoveryKey::Passphrase` encrypts it with AES-256-GCM under a key derived from the
+//!   passphrase with Argon2id. The salt is kept in the envelope and the key id records the
+//!   Argon2id parameters as `escrow-passphrase:m=<KiB>,t=<passes>`. Argon2id is not approved,
+//!   so passphrases are refused in FIPS mode, see `fips`.
+//!
+//! The payload holds the number of keys as u32, followed by every key as the u16 length of its
+//! name, the name, the u32 length of the key and the key, in the order of the names. All lengths
+//! are big-endian.
+
+use crate::common::{
+    crypto::{
+        aead,
+        envelope::{AeadAlgorithm, Envelope},
+        public_key::PublicKey,
+        secret::SecretBytes,
+    },
+    ecies,
+    error::SecurityModuleError,
+    input_limits,
+    password::{self, PasswordParams},
+    traits::key_handle::KeyHandle,
+};
+use openssl::rand::rand_bytes;
+use sodiumoxide::crypto::pwhash::argon2id13;
+use std::collections::BTreeMap;
+
+/// The prefix of the key id of bundles encrypted under a passphrase.
+const PASSPHRASE_KEY_ID_PREFIX: &str = "escrow-passphrase:";
+
+/// The algorithm bundles are encrypted with under a passphrase.
+const PASSPHRASE_AEAD: AeadAlgorithm = AeadAlgorithm::Aes256Gcm;
+
+/// The maximum length of the name of a key in bytes.
+pub const MAX_KEY_NAME_LEN: usize = 255;
+
+/// The key a bundle is encrypted for.
+#[derive(Debug, Clone, Copy)]
+pub enum RecoveryKey<'a> {
+    /// An EC public key, e.g. of an offline recovery key pair or a smart card of the user.
+    PublicKey(&'a PublicKey),
+    /// A passphrase chosen by the user, stretched with Argon2id.
+    Passphrase {
+        /// The passphrase.
+        passphrase: &'a [u8],
+        /// The Argon2id parameters, recorded in the bundle.
+        params: PasswordParams,
+    },
+}
+
+/// The secret a bundle is decrypted with.
+#[derive(Clone, Copy)]
+pub enum Recovery<'a> {
+    /// Holds the private key of `RecoveryKey::PublicKey` and derives the shared secret with
+    /// `derive_shared_secret`.
+    KeyHandle(&'a dyn KeyHandle),
+    /// The passphrase of `RecoveryKey::Passphrase`, the parameters are read from the bundle.
+    Passphrase(&'a [u8]),
+}
+
+/// Encrypts `keys` into a bundle for `recovery_key`.
+///
+/// # Arguments
+///
+/// * `keys` - The data keys by name, which are zeroed when dropped.
+/// * `recovery_key` - The key the bundle is encrypted for.
+///
+/// # Returns
+///
+/// A `Result` containing the encoded bundle, or a `SecurityModuleError::EncryptionError` if a
+/// name is empty or longer than `MAX_KEY_NAME_LEN`, a `SecurityModuleError::InvalidPublicKey` if
+/// the public key is not an EC key or a `SecurityModuleError::Encoding` if the Argon2id
+/// parameters are out of range.
+#[tracing::instrument(skip_all, fields(crypto.keys = keys.len()))]
+pub fn export_keys(
+    keys: &BTreeMap<String, SecretBytes>,
+    recovery_key: &RecoveryKey<'_>,
+) -> Result<Vec<u8>, SecurityModuleError> {
+    let payload = encode_keys(keys)?;
+    match *recovery_key {
+        RecoveryKey::PublicKey(public_key) => ecies::encrypt_for(public_key, &payload),
+        RecoveryKey::Passphrase { passphrase, params } => {
+            params.validate()?;
+            let mut salt = vec![0; argon2id13::SALTBYTES];
+            rand_bytes(&mut salt)
+                .map_err(|e| SecurityModuleError::EncryptionError(e.to_string()))?;
+            let key = password::stretch(
+                passphrase,
+                &salt,
+                params,
+                SecurityModuleError::EncryptionError,
+            )?;
+
+            let key_id = format!(
+                "{}m={},t={}",
+                PASSPHRASE_KEY_ID_PREFIX, params.memory_kib, params.passes
+            );
+            let mut envelope = Envelope::new(
+                PASSPHRASE_AEAD,
+                key_id,
+                aead::random_nonce(PASSPHRASE_AEAD)?,
+            );
+            envelope.salt = Some(salt);
+            aead::seal(envelope, &key, &payload)
+        }
+    }
+}
+
+/// Decrypts a bundle of `export_keys`.
+///
+/// # Returns
+///
+/// A `Result` containing the data keys by name, which are zeroed when dropped, or a
+/// `SecurityModuleError::DecryptionError` if the bundle was encrypted for another recovery key
+/// or modified.
+#[tracing::instrument(skip_all, fields(crypto.payload.size = bundle.len()))]
+pub fn import_keys(
+    bundle: &[u8],
+    recovery: Recovery<'_>,
+) -> Result<BTreeMap<String, SecretBytes>, SecurityModuleError> {
+    let payload = match recovery {
+        Recovery::KeyHandle(key_handle) => ecies::decrypt(key_handle, bundle)?,
+        Recovery::Passphrase(passphrase) => {
+            let envelope = input_limits::validate_envelope(bundle)?;
+            let params = envelope
+                .key_id
+                .strip_prefix(PASSPHRASE_KEY_ID_PREFIX)
+                .and_then(parse_params)
+                .ok_or_else(|| {
+                    decryption_error("The bundle is not encrypted under a passphrase")
+                })?;
+            params.validate()?;
+            let salt = envelope
+                .salt
+                .ok_or_else(|| decryption_error("The bundle has no salt"))?;
+            let key = password::stretch(
+                passphrase,
+                salt,
+                params,
+                SecurityModuleError::DecryptionError,
+            )?;
+            aead::open(&envelope, &key)?
+        }
+    };
+    decode_keys(&payload)
+}
+
+/// Exports data keys that are encrypted with `encrypt_data` of `device`, e.g. the provider of
+/// the old device, into a bundle for `recovery_key`.
+///
+/// # Returns
+///
+/// A `Result` containing the encoded bundle, the error of `device` if a key cannot be decrypted
+/// or an error of `export_keys`.
+pub fn export_wrapped_keys(
+    device: &(impl KeyHandle + ?Sized),
+    wrapped_keys: &BTreeMap<String, Vec<u8>>,
+    recovery_key: &RecoveryKey<'_>,
+) -> Result<Vec<u8>, SecurityModuleError> {
+    let keys = wrapped_keys
+        .iter()
+        .map(|(name, wrapped_key)| Ok((name.clone(), device.decrypt_data(wrapped_key)?)))
+        .collect::<Result<_, SecurityModuleError>>()?;
+    export_keys(&keys, recovery_key)
+}
+
+/// Imports the data keys of a bundle and encrypts them with `encrypt_data` of `device`, e.g.
+/// the provider of the new device.
+///
+/// # Returns
+///
+/// A `Result` containing the data keys by name encrypted under `device`, an error of
+/// `import_keys` or the error of `device` if a key cannot be encrypted.
+pub fn import_wrapped_keys(
+    device: &(impl KeyHandle + ?Sized),
+    bundle: &[u8],
+    recovery: Recovery<'_>,
+) -> Result<BTreeMap<String, Vec<u8>>, SecurityModuleError> {
+    import_keys(bundle, recovery)?
+        .into_iter()
+        .map(|(name, key)| Ok((name, device.encrypt_data(&key)?)))
+        .collect()
+}
+
+fn parse_params(params: &str) -> Option<PasswordParams> {
+    let (memory_kib, passes) = params.strip_prefix("m=")?.split_once(",t=")?;
+    Some(PasswordParams {
+        passes: passes.parse().ok()?,
+        memory_kib: memory_kib.parse().ok()?,
+    })
+}
+
+pub(crate) fn encode_keys(
+    keys: &BTreeMap<String, SecretBytes>,
+) -> Result<SecretBytes, SecurityModuleError> {
+    let count = u32::try_from(keys.len())
+        .map_err(|_| SecurityModuleError::EncryptionError("Too many keys".to_owned()))?;
+    let len = keys
+        .iter()
+        .map(|(name, key)| 6 + name.len() + key.len())
+        .sum::<usize>();
+    let mut payload = SecretBytes::with_capacity(4 + len);
+    payload.extend_from_slice(&count.to_be_bytes());
+    for (name, key) in keys {
+        if name.is_empty() || name.len() > MAX_KEY_NAME_LEN {
+            return Err(SecurityModuleError::EncryptionError(format!(
+                "Key names must have 1 to {} bytes",
+                MAX_KEY_NAME_LEN
+            )));
+        }
+        let key_len = u32::try_from(key.len()).map_err(|_| {
+            SecurityModuleError::EncryptionError(format!("The key {} is too long", name))
+        })?;
+        payload.extend_from_slice(&(name.len() as u16).to_be_bytes());
+        payload.extend_from_slice(name.as_bytes());
+        payload.extend_from_slice(&key_len.to_be_bytes());
+        payload.extend_from_slice(key);
+    }
+    Ok(payload)
+}
+
+pub(crate) fn decode_keys(
+    mut payload: &[u8],
+) -> Result<BTreeMap<String, SecretBytes>, SecurityModuleError> {
+    let mut take = |len: usize| {
+        if payload.len() < len {
+            return Err(decryption_error("The bundle is truncated"));
+        }
+        let (field, rest) = payload.split_at(len);
+        payload = rest;
+        Ok(field)
+    };
+
+    let count = u32::from_be_bytes(take(4)?.try_into().unwrap());
+    let mut keys = BTreeMap::new();
+    for _ in 0..count {
+        let name_len = u16::from_be_bytes(take(2)?.try_into().unwrap()) as usize;
+        let name = String::from_utf8(take(name_len)?.to_vec())
+            .map_err(|_| decryption_error("The bundle holds an invalid key name"))?;
+        let key_len = u32::from_be_bytes(take(4)?.try_into().unwrap()) as usize;
+        let key = SecretBytes::from(take(key_len)?);
+        if keys.insert(name, key).is_some() {
+            return Err(decryption_error("The bundle holds a key name twice"));
+        }
+    }
+    if !payload.is_empty() {
+        return Err(decryption_error("The bundle has trailing data"));
+    }
+    Ok(keys)
+}
+
+fn decryption_error(message: &str) -> SecurityModuleError {
+    SecurityModuleError::DecryptionError(message.to_owned())
+}
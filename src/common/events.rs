@@ -0,0 +1,420 @@
+//! Notifications about key lifecycle changes.
+//!
+//! `KeyEvents` delivers a `KeyEvent` to every subscriber as soon as a key changes, so that
+//! applications can invalidate caches or raise alerts without polling the security module.
+//! Subscribers either register a callback with `KeyEvents::subscribe`, which is called on the
+//! thread that caused the event, or consume a `KeyEventStream` returned by `KeyEvents::stream`
+//! from an async task.
+//!
+//! `SecModules::get_instance` wraps every instance it creates in an `EventedProvider`, which
+//! publishes `Created` and `AuthFailed` events to `KeyEvents::global`. Code that rotates,
+//! deletes or disables keys publishes the corresponding events with `KeyEvents::publish`.
+
+use crate::common::{
+    crypto::{
+        key_metadata::KeyMetadata, key_spec::KeySpec, operation_context::OperationContext,
+        secret::SecretBytes,
+    },
+    error::SecurityModuleError,
+    latency::{LatencySnapshot, ProviderOperation},
+    session_pool::SessionPoolMetrics,
+    traits::{key_handle::KeyHandle, module_provider::Provider},
+};
+use futures::{
+    channel::mpsc::{self, UnboundedReceiver, UnboundedSender},
+    Stream,
+};
+use once_cell::sync::Lazy;
+use std::{
+    any::Any,
+    fmt,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+    task::{Context, Poll},
+    time::SystemTime,
+};
+
+static GLOBAL: Lazy<Arc<KeyEvents>> = Lazy::new(|| Arc::new(KeyEvents::new()));
+
+/// What happened to a key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum KeyEventKind {
+    /// A key was created in the security module.
+    Created,
+    /// A key was replaced by a new version, e.g. by a rotation policy.
+    Rotated,
+    /// A key was deleted from the security module.
+    Deleted,
+    /// A key was disabled and can no longer be used until it is enabled again.
+    Disabled,
+    /// An operation was refused because the user or application could not be authenticated.
+    AuthFailed {
+        /// The operation that was refused.
+        operation: ProviderOperation,
+    },
+}
+
+/// A change of a key, delivered to the subscribers of `KeyEvents`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyEvent {
+    /// The identifier of the key.
+    pub key_id: String,
+    /// What happened to the key.
+    pub kind: KeyEventKind,
+    /// When the event was published.
+    pub timestamp: SystemTime,
+}
+
+impl KeyEvent {
+    /// Creates an event that happened now.
+    pub fn new(key_id: impl Into<String>, kind: KeyEventKind) -> Self {
+        Self {
+            key_id: key_id.into(),
+            kind,
+            timestamp: SystemTime::now(),
+        }
+    }
+}
+
+/// Identifies a callback registered with `KeyEvents::subscribe`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SubscriptionId(u64);
+
+type Callback = Arc<dyn Fn(&KeyEvent) + Send + Sync>;
+
+#[derive(Default)]
+struct Subscribers {
+    callbacks: Vec<(SubscriptionId, Callback)>,
+    streams: Vec<UnboundedSender<KeyEvent>>,
+}
+
+/// Delivers key events to callbacks and streams.
+#[derive(Default)]
+pub struct KeyEvents {
+    subscribers: Mutex<Subscribers>,
+    next_id: AtomicU64,
+}
+
+impl KeyEvents {
+    /// Creates a publisher without subscribers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the publisher the instances created by `SecModules::get_instance` publish to.
+    pub fn global() -> Arc<KeyEvents> {
+        GLOBAL.clone()
+    }
+
+    fn subscribers(&self) -> MutexGuard<'_, Subscribers> {
+        self.subscribers
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Registers a callback that is called with every event published afterwards.
+    ///
+    /// The callback is called on the thread that publishes the event, while the operation that
+    /// caused it waits, so it should return quickly. It may subscribe or unsubscribe callbacks.
+    pub fn subscribe(
+        &self,
+        callback: impl Fn(&KeyEvent) + Send + Sync + 'static,
+    ) -> SubscriptionId {
+        let id = SubscriptionId(self.next_id.fetch_add(1, Ordering::Relaxed));
+        self.subscribers().callbacks.push((id, Arc::new(callback)));
+        id
+    }
+
+    /// Removes a callback registered with `subscribe`.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the callback was registered, `false` if it was already removed.
+    pub fn unsubscribe(&self, id: SubscriptionId) -> bool {
+        let mut subscribers = self.subscribers();
+        let count = subscribers.callbacks.len();
+        subscribers
+            .callbacks
+            .retain(|(subscription, _)| *subscription != id);
+        subscribers.callbacks.len() != count
+    }
+
+    /// Returns a stream of the events published afterwards.
+    ///
+    /// Events are buffered until the stream is polled. Dropping the stream unsubscribes it.
+    pub fn stream(&self) -> KeyEventStream {
+        let (sender, receiver) = mpsc::unbounded();
+        self.subscribers().streams.push(sender);
+        KeyEventStream(receiver)
+    }
+
+    /// Delivers an event to all subscribers.
+    pub fn publish(&self, event: KeyEvent) {
+        let callbacks: Vec<Callback> = {
+            let mut subscribers = self.subscribers();
+            subscribers
+                .streams
+                .retain(|sender| sender.unbounded_send(event.clone()).is_ok());
+            subscribers
+                .callbacks
+                .iter()
+                .map(|(_, callback)| callback.clone())
+                .collect()
+        };
+        for callback in callbacks {
+            callback(&event);
+        }
+    }
+}
+
+impl fmt::Debug for KeyEvents {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let subscribers = self.subscribers();
+        f.debug_struct("KeyEvents")
+            .field("callbacks", &subscribers.callbacks.len())
+            .field("streams", &subscribers.streams.len())
+            .finish()
+    }
+}
+
+/// A stream of the events published by `KeyEvents`, returned by `KeyEvents::stream`.
+#[derive(Debug)]
+pub struct KeyEventStream(UnboundedReceiver<KeyEvent>);
+
+impl Stream for KeyEventStream {
+    type Item = KeyEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<KeyEvent>> {
+        Pin::new(&mut self.0).poll_next(cx)
+    }
+}
+
+/// A provider that publishes the key events caused by calls to the wrapped provider.
+pub struct EventedProvider {
+    inner: Arc<Mutex<dyn Provider>>,
+    key_id: Mutex<String>,
+    events: Arc<KeyEvents>,
+}
+
+impl EventedProvider {
+    /// Wraps a provider.
+    ///
+    /// # Arguments
+    ///
+    /// * `inner` - The provider whose calls may cause events.
+    /// * `key_id` - The id of the key the provider operates on, until `create_key` or
+    ///   `load_key` is called with another one.
+    /// * `events` - The publisher the events are delivered through.
+    pub fn new(inner: Arc<Mutex<dyn Provider>>, key_id: String, events: Arc<KeyEvents>) -> Self {
+        Self {
+            inner,
+            key_id: Mutex::new(key_id),
+            events,
+        }
+    }
+
+    fn inner(&self) -> MutexGuard<'_, dyn Provider + 'static> {
+        self.inner
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn key_id(&self) -> MutexGuard<'_, String> {
+        self.key_id
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn publish<T>(
+        &self,
+        operation: ProviderOperation,
+        f: impl FnOnce() -> Result<T, SecurityModuleError>,
+    ) -> Result<T, SecurityModuleError> {
+        let key_id = self.key_id().clone();
+        self.publish_for(&key_id, operation, f)
+    }
+
+    /// Runs `f` and publishes its event for the key `key_id`, which need not be the current key.
+    fn publish_for<T>(
+        &self,
+        key_id: &str,
+        operation: ProviderOperation,
+        f: impl FnOnce() -> Result<T, SecurityModuleError>,
+    ) -> Result<T, SecurityModuleError> {
+        let result = f();
+        let kind = match &result {
+            Ok(_) if operation == ProviderOperation::CreateKey => KeyEventKind::Created,
+            Err(error) if error.is_authentication_failure() => {
+                KeyEventKind::AuthFailed { operation }
+            }
+            _ => return result,
+        };
+        self.events.publish(KeyEvent::new(key_id.to_owned(), kind));
+        result
+    }
+
+    /// Runs `f`, which creates or loads the key `key_id`, and makes it the current key if `f`
+    /// succeeds. A failed call leaves the previous key loaded, so it stays the current key.
+    fn switch_key(
+        &self,
+        key_id: &str,
+        operation: ProviderOperation,
+        f: impl FnOnce() -> Result<(), SecurityModuleError>,
+    ) -> Result<(), SecurityModuleError> {
+        self.publish_for(key_id, operation, f)?;
+        *self.key_id() = key_id.to_owned();
+        Ok(())
+    }
+}
+
+impl fmt::Debug for EventedProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EventedProvider")
+            .field("events", &self.events)
+            .finish_non_exhaustive()
+    }
+}
+
+impl KeyHandle for EventedProvider {
+    fn sign_data(&self, data: &[u8]) -> Result<Vec<u8>, SecurityModuleError> {
+        self.publish(ProviderOperation::SignData, || self.inner().sign_data(data))
+    }
+
+    fn decrypt_data(&self, encrypted_data: &[u8]) -> Result<SecretBytes, SecurityModuleError> {
+        self.publish(ProviderOperation::DecryptData, || {
+            self.inner().decrypt_data(encrypted_data)
+        })
+    }
+
+    fn encrypt_data(&self, data: &[u8]) -> Result<Vec<u8>, SecurityModuleError> {
+        self.publish(ProviderOperation::EncryptData, || {
+            self.inner().encrypt_data(data)
+        })
+    }
+
+    fn verify_signature(&self, data: &[u8], signature: &[u8]) -> Result<bool, SecurityModuleError> {
+        self.publish(ProviderOperation::VerifySignature, || {
+            self.inner().verify_signature(data, signature)
+        })
+    }
+
+    fn sign_data_into<'a>(
+        &self,
+        data: &[u8],
+        context: &'a mut OperationContext,
+    ) -> Result<&'a [u8], SecurityModuleError> {
+        self.publish(ProviderOperation::SignData, || {
+            self.inner().sign_data_into(data, context)
+        })
+    }
+
+    fn verify_signature_with(
+        &self,
+        data: &[u8],
+        signature: &[u8],
+        context: &mut OperationContext,
+    ) -> Result<bool, SecurityModuleError> {
+        self.publish(ProviderOperation::VerifySignature, || {
+            self.inner().verify_signature_with(data, signature, context)
+        })
+    }
+
+    fn verify_many(&self, items: &[(&[u8], &[u8])]) -> Result<Vec<bool>, SecurityModuleError> {
+        self.publish(ProviderOperation::VerifyMany, || {
+            self.inner().verify_many(items)
+        })
+    }
+
+    fn derive_shared_secret(
+        &self,
+        peer_public_key: &[u8],
+    ) -> Result<SecretBytes, SecurityModuleError> {
+        self.publish(ProviderOperation::DeriveSharedSecret, || {
+            self.inner().derive_shared_secret(peer_public_key)
+        })
+    }
+}
+
+impl Provider for EventedProvider {
+    fn create_key(
+        &mut self,
+        key_id: &str,
+        config: Box<dyn Any>,
+    ) -> Result<(), SecurityModuleError> {
+        self.switch_key(key_id, ProviderOperation::CreateKey, || {
+            self.inner().create_key(key_id, config)
+        })
+    }
+
+    fn load_key(&mut self, key_id: &str, config: Box<dyn Any>) -> Result<(), SecurityModuleError> {
+        self.switch_key(key_id, ProviderOperation::LoadKey, || {
+            self.inner().load_key(key_id, config)
+        })
+    }
+
+    fn import_wrapped_key(
+        &mut self,
+        key_id: &str,
+        wrapped_key: &[u8],
+        wrapping_key_id: &str,
+        spec: &KeySpec,
+    ) -> Result<(), SecurityModuleError> {
+        self.switch_key(key_id, ProviderOperation::ImportWrappedKey, || {
+            self.inner()
+                .import_wrapped_key(key_id, wrapped_key, wrapping_key_id, spec)
+        })
+    }
+
+    fn export_private_key(&self) -> Result<SecretBytes, SecurityModuleError> {
+        self.publish(ProviderOperation::ExportPrivateKey, || {
+            self.inner().export_private_key()
+        })
+    }
+
+    fn initialize_module(&mut self) -> Result<(), SecurityModuleError> {
+        self.publish(ProviderOperation::InitializeModule, || {
+            self.inner().initialize_module()
+        })
+    }
+
+    fn session_pool_metrics(&self) -> Option<SessionPoolMetrics> {
+        self.inner().session_pool_metrics()
+    }
+
+    fn latency_snapshot(&self) -> Option<LatencySnapshot> {
+        self.inner().latency_snapshot()
+    }
+
+    fn key_metadata(&self) -> Result<KeyMetadata, SecurityModuleError> {
+        self.inner().key_metadata()
+    }
+
+    fn refresh_key_metadata(&mut self) -> Result<KeyMetadata, SecurityModuleError> {
+        self.inner().refresh_key_metadata()
+    }
+
+    fn list_keys(&self) -> Result<Vec<String>, SecurityModuleError> {
+        self.inner().list_keys()
+    }
+
+    fn delete_key(&mut self, key_id: &str) -> Result<(), SecurityModuleError> {
+        let result = self.inner().delete_key(key_id);
+        let kind = match &result {
+            Ok(()) => KeyEventKind::Deleted,
+            Err(error) if error.is_authentication_failure() => KeyEventKind::AuthFailed {
+                operation: ProviderOperation::DeleteKey,
+            },
+            Err(_) => return result,
+        };
+        self.events.publish(KeyEvent::new(key_id.to_owned(), kind));
+        result
+    }
+
+    fn shutdown(&mut self) -> Result<(), SecurityModuleError> {
+        self.inner().shutdown()
+    }
+}
@@ -1,8 +1,27 @@
-use super::traits::{log_config::LogConfig, module_provider::Provider};
+use super::{
+    anomaly::{AnomalyDetector, MonitoredProvider},
+    audit::{AuditLog, AuditedProvider},
+    config::{CryptoConfig, FallbackPolicy},
+    error::SecurityModuleError,
+    events::{EventedProvider, KeyEvents},
+    fips,
+    input_limits::InputLimits,
+    key_id::{KeyId, ValidatedProvider},
+    latency::{LatencyConfig, TimedProvider, DEFAULT_SLOW_THRESHOLD},
+    memory_lock,
+    namespace::NamespacedProvider,
+    plan::InstancePlan,
+    profile::Profile,
+    traits::{log_config::LogConfig, module_provider::Provider},
+};
 #[cfg(feature = "hsm")]
 use crate::hsm::core::instance::{HsmInstance, HsmType};
 #[cfg(feature = "tpm")]
 use crate::tpm::core::instance::{TpmInstance, TpmType};
+#[cfg(feature = "metrics")]
+use super::metrics::MeteredProvider;
+#[cfg(feature = "test-utils")]
+use crate::mock::chaos::{ChaosConfig, ChaosProvider};
 use once_cell::sync::Lazy;
 use std::{
     collections::HashMap,
@@ -52,6 +71,13 @@ impl From<&str> for SecurityModule {
 /// are unique and accessible across the application.
 static INSTANCES: SecurityModuleInstances = Lazy::new(|| Mutex::new(HashMap::new()));
 static LOGGING_INITIALIZED: Mutex<bool> = Mutex::new(false);
+static LATENCY_CONFIG: Mutex<LatencyConfig> = Mutex::new(LatencyConfig {
+    slow_threshold: Some(DEFAULT_SLOW_THRESHOLD),
+});
+static AUDIT_LOG: Mutex<Option<Arc<AuditLog>>> = Mutex::new(None);
+static CONFIG: Mutex<Option<CryptoConfig>> = Mutex::new(None);
+#[cfg(feature = "test-utils")]
+static CHAOS_CONFIG: Mutex<Option<ChaosConfig>> = Mutex::new(None);
 
 /// A container struct for security module-related functionality.
 ///
@@ -75,12 +101,21 @@ impl SecModules {
     /// # Returns
     ///
     /// An `Option` containing an `Arc<Mutex<dyn Provider>>` to the requested module instance,
-    /// or `None` if the module type is not supported or an error occurs during instance creation.
+    /// or `None` if the module type is not supported, `key_id` is not a valid `KeyId` or an error
+    /// occurs during instance creation.
     pub fn get_instance(
         key_id: String,
         module: SecurityModule,
         log: Option<Box<dyn LogConfig>>,
     ) -> Option<Arc<Mutex<dyn Provider>>> {
+        // Resolve the configuration before logging is set up, so that its log levels apply
+        let config = Self::config();
+        let namespace = config.namespace();
+        if let Err(e) = KeyId::new(key_id.as_str()) {
+            tracing::warn!(error = %e, "Rejected key id");
+            return None;
+        }
+
         // Initialize logging once
         if !*LOGGING_INITIALIZED.lock().unwrap() {
             if let Some(log_inst) = log {
@@ -92,12 +127,260 @@ impl SecModules {
         // Check if requested instance is in cache. If not, create a new instance
         let mut instances = INSTANCES.lock().unwrap();
         if !instances.contains_key(&module) {
-            let instance = SecModule::create_instance(key_id, &module);
-            instances.insert(module.clone(), instance?);
+            let scoped_key_id = match &namespace {
+                Some(namespace) => namespace.scope(&key_id),
+                None => key_id.clone(),
+            };
+            let instance = SecModule::create_instance(scoped_key_id, &module)?;
+            let instance: ProviderArc = match namespace {
+                Some(namespace) => {
+                    Arc::new(Mutex::new(NamespacedProvider::new(instance, namespace)))
+                }
+                None => instance,
+            };
+            let instance: ProviderArc = Arc::new(Mutex::new(
+                ValidatedProvider::new(instance)
+                    .with_sunset_policy(config.sunset_policy())
+                    .with_input_limits(config.limits)
+                    .with_fips_mode(fips::is_enabled()),
+            ));
+            #[cfg(feature = "test-utils")]
+            let instance = match *CHAOS_CONFIG.lock().unwrap() {
+                Some(chaos) => Arc::new(Mutex::new(ChaosProvider::new(instance, chaos))),
+                None => instance,
+            };
+            let instance: ProviderArc = match AUDIT_LOG.lock().unwrap().clone() {
+                Some(log) => Arc::new(Mutex::new(AuditedProvider::new(
+                    instance,
+                    key_id.clone(),
+                    log,
+                ))),
+                None => instance,
+            };
+            let instance: ProviderArc = Arc::new(Mutex::new(EventedProvider::new(
+                instance,
+                key_id.clone(),
+                KeyEvents::global(),
+            )));
+            let instance: ProviderArc = Arc::new(Mutex::new(MonitoredProvider::new(
+                instance,
+                key_id.clone(),
+                AnomalyDetector::global(),
+            )));
+            #[cfg(feature = "metrics")]
+            let instance: ProviderArc = Arc::new(Mutex::new(MeteredProvider::new(
+                instance,
+                format!("{:?}", module),
+            )));
+            let config = *LATENCY_CONFIG.lock().unwrap();
+            let instance = TimedProvider::new(instance, key_id, config);
+            instances.insert(module.clone(), Arc::new(Mutex::new(instance)));
         }
 
         instances.get(&module).cloned()
     }
+
+    /// Returns an initialized instance of the most preferred provider of the configuration.
+    ///
+    /// The providers are tried in the order of `CryptoConfig::providers`. If an instance cannot
+    /// be created or its `initialize_module` fails, the next provider is tried if the fallback
+    /// policy is `FallbackPolicy::NextProvider`.
+    ///
+    /// # Arguments
+    ///
+    /// * `key_id` - A `String` identifier for the security module instance.
+    /// * `log` - The logger set up with the first instance.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the instance on success, or the error of the last provider tried.
+    /// If no configured provider is enabled in this build, a
+    /// `SecurityModuleError::InitializationError` is returned, and if `key_id` is not a valid
+    /// `KeyId`, a `SecurityModuleError::InvalidKeyId`.
+    pub fn get_preferred_instance(
+        key_id: String,
+        mut log: Option<Box<dyn LogConfig>>,
+    ) -> Result<Arc<Mutex<dyn Provider>>, SecurityModuleError> {
+        KeyId::new(key_id.as_str())?;
+        let config = Self::config();
+        let mut error = SecurityModuleError::InitializationError(
+            "No configured provider is enabled in this build".to_owned(),
+        );
+        for module in config.security_modules() {
+            let result = match Self::get_instance(key_id.clone(), module.clone(), log.take()) {
+                Some(instance) => {
+                    let initialized = instance.lock().unwrap().initialize_module();
+                    initialized.map(|()| instance)
+                }
+                None => Err(SecurityModuleError::InitializationError(format!(
+                    "Cannot create an instance of {:?}",
+                    module
+                ))),
+            };
+            match result {
+                Ok(instance) => return Ok(instance),
+                Err(e) if config.fallback == FallbackPolicy::NextProvider => {
+                    tracing::warn!(?module, error = %e, "Provider unavailable, falling back");
+                    error = e;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Err(error)
+    }
+
+    /// Reports what `get_instance` and `get_preferred_instance` would create for `key_id` with the
+    /// configuration of the crate, without creating an instance.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the plan on success, or a `SecurityModuleError::InvalidKeyId` if
+    /// `key_id` is not a valid `KeyId`.
+    pub fn plan(key_id: &str) -> Result<InstancePlan, SecurityModuleError> {
+        InstancePlan::for_config(&Self::config(), key_id)
+    }
+
+    /// Injects the configuration of the crate.
+    ///
+    /// Applies the timeouts, log levels, memory locking, input limits and FIPS mode of `config`.
+    /// Instances that already exist keep their latency configuration, input limits and FIPS mode. Without a call, the configuration is resolved from the environment
+    /// with `CryptoConfig::resolve` when the first instance is created.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` that is `Ok(())` on success, or a `SecurityModuleError::InitializationError`
+    /// if the configuration is invalid.
+    pub fn configure(config: CryptoConfig) -> Result<(), SecurityModuleError> {
+        config.validate()?;
+        Self::apply(&config)?;
+        *CONFIG.lock().unwrap() = Some(config);
+        Ok(())
+    }
+
+    /// Selects the providers and the fallback policy of `profile`, keeping the rest of the
+    /// current configuration. Use `Profile::key_spec` for the keys.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` that is `Ok(())` on success, or a `SecurityModuleError::InitializationError`
+    /// if the current configuration is invalid.
+    pub fn use_profile(profile: Profile) -> Result<(), SecurityModuleError> {
+        Self::configure(profile.apply(Self::config()))
+    }
+
+    /// Returns the configuration of the crate, resolving it from the environment on the first
+    /// call unless `configure` was called before.
+    ///
+    /// An invalid environment is logged and the defaults are used instead, so that a typo in a
+    /// deployment does not prevent the crate from working.
+    pub fn config() -> CryptoConfig {
+        let mut config = CONFIG.lock().unwrap();
+        if let Some(config) = &*config {
+            return config.clone();
+        }
+        let resolved = CryptoConfig::resolve()
+            .and_then(|resolved| Self::apply(&resolved).map(|()| resolved))
+            .unwrap_or_else(|e| {
+                tracing::warn!(error = %e, "Invalid configuration, using the defaults");
+                CryptoConfig::default()
+            });
+        *config = Some(resolved.clone());
+        resolved
+    }
+
+    fn apply(config: &CryptoConfig) -> Result<(), SecurityModuleError> {
+        config.apply_logging()?;
+        memory_lock::set_enabled(config.memory.lock_secrets);
+        fips::set_enabled(config.fips);
+        InputLimits::set(config.limits);
+        *LATENCY_CONFIG.lock().unwrap() = config.latency_config();
+        Ok(())
+    }
+
+    /// Sets the latency recording configuration of instances created afterwards.
+    ///
+    /// Every instance returned by `get_instance` records the latency of its calls, which can be
+    /// retrieved through `Provider::latency_snapshot`. Calls slower than
+    /// `LatencyConfig::slow_threshold` are logged as a warning.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The `LatencyConfig` to apply to new instances.
+    pub fn set_latency_config(config: LatencyConfig) {
+        *LATENCY_CONFIG.lock().unwrap() = config;
+    }
+
+    /// Enables or disables auditing of instances created afterwards.
+    ///
+    /// While enabled, every instance returned by `get_instance` records its key lifecycle events
+    /// and cryptographic operations in `log`. Instances that already exist are not affected.
+    ///
+    /// # Arguments
+    ///
+    /// * `log` - The `AuditLog` to record into, or `None` to disable auditing.
+    pub fn set_audit_log(log: Option<Arc<AuditLog>>) {
+        *AUDIT_LOG.lock().unwrap() = log;
+    }
+
+    /// Enables or disables chaos mode for instances created afterwards.
+    ///
+    /// In chaos mode every instance returned by `get_instance` is wrapped in a `ChaosProvider`,
+    /// which randomly delays, reorders or fails calls as configured. Instances that already
+    /// exist are not affected. Only available with the `test-utils` feature.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The misbehavior to inject, or `None` to disable chaos mode.
+    #[cfg(feature = "test-utils")]
+    pub fn set_chaos_config(config: Option<ChaosConfig>) {
+        *CHAOS_CONFIG.lock().unwrap() = config;
+    }
+
+    /// Shuts down the instance of `module` and removes it from the registry, so that the next call
+    /// of `get_instance` creates a new one. Does nothing if no instance of `module` exists.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` that is `Ok(())` on success, or the error of `Provider::shutdown`. The instance
+    /// is removed either way.
+    pub fn shutdown(module: &SecurityModule) -> Result<(), SecurityModuleError> {
+        let instance = INSTANCES.lock().unwrap().remove(module);
+        match instance {
+            Some(instance) => instance
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .shutdown(),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns all instances created so far, for `diagnostics::support_bundle`.
+    pub(crate) fn instances() -> Vec<(SecurityModule, ProviderArc)> {
+        INSTANCES
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(module, instance)| (module.clone(), instance.clone()))
+            .collect()
+    }
+
+    /// Returns the latency recording configuration of new instances.
+    pub(crate) fn latency_config() -> LatencyConfig {
+        *LATENCY_CONFIG.lock().unwrap()
+    }
+
+    /// Returns whether new instances are audited.
+    pub(crate) fn audit_enabled() -> bool {
+        AUDIT_LOG.lock().unwrap().is_some()
+    }
+
+    /// Returns whether new instances are wrapped in a `ChaosProvider`.
+    pub(crate) fn chaos_enabled() -> bool {
+        #[cfg(feature = "test-utils")]
+        return CHAOS_CONFIG.lock().unwrap().is_some();
+        #[cfg(not(feature = "test-utils"))]
+        false
+    }
 }
 
 /// Represents a specific instance of a security module.
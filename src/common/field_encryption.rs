@@ -0,0 +1,309 @@
+//! Encryption of database fields under a key of the security module.
+//!
+//! A `FieldEncryptor` holds a root key that is generated once and stored next to the database,
+//! encrypted with `encrypt_data` of the security module. Every field is encrypted under a key
+//! derived from the root key and its `FieldContext`, the table, column and row it belongs to, so
+//! a ciphertext copied to another field cannot be decrypted:
+//!
+//! ```rust,ignore
+//! use crypto_layer::common::field_encryption::{FieldContext, FieldEncryptor};
+//!
+//! let (fields, wrapped_root_key) = FieldEncryptor::generate(&provider, "db_key")?;
+//! // Later, after reading `wrapped_root_key` back.
+//! let fields = FieldEncryptor::unwrap(&provider, "db_key", &wrapped_root_key)?;
+//!
+//! let address = FieldContext::new("users", "address").row("42");
+//! let ciphertext = fields.encrypt(&address, b"Main Street 1")?;
+//!
+//! // Searchable columns are encrypted deterministically and cannot be bound to a row.
+//! let email = FieldContext::new("users", "email");
+//! let query = fields.encrypt_deterministic(&email, b"alice@example.com")?;
+//! ```
+//!
+//! Fields are envelopes, see `crypto::envelope`, encrypted with AES-256-GCM:
+//!
+//! - `encrypt` derives the field key with HKDF-SHA-256 from the root key, a random salt and the
+//!   context, and uses a random nonce. Equal plaintexts have different ciphertexts.
+//! - `encrypt_deterministic` derives the field key without salt and the nonce from an HMAC of the
+//!   plaintext, like AES-SIV. Equal plaintexts of a column have equal ciphertexts, so the column
+//!   can be searched for equality, which also reveals which rows are equal. It should only be
+//!   used for columns that need to be searched.
+//!
+//! The info of the key derivation is `crypto-layer/field/` followed by the mode and the table,
+//! the column and the row, each prefixed with its u16 big-endian length.
+
+use crate::common::{
+    constant_time,
+    crypto::{
+        aead,
+        envelope::{AeadAlgorithm, Envelope},
+        kdf::Kdf,
+        secret::SecretBytes,
+    },
+    error::SecurityModuleError,
+    input_limits,
+    traits::key_handle::KeyHandle,
+};
+use openssl::{hash::MessageDigest, pkey::PKey, rand::rand_bytes, sign::Signer};
+use std::fmt;
+
+/// The algorithm fields are encrypted with.
+const AEAD: AeadAlgorithm = AeadAlgorithm::Aes256Gcm;
+
+/// The KDF field keys are derived with.
+const KDF: Kdf = Kdf::HkdfSha256;
+
+/// The length of the root key and the derived keys in bytes.
+const KEY_LEN: usize = 32;
+
+/// The length of the random salt of randomized fields in bytes.
+const SALT_LEN: usize = 16;
+
+const KDF_INFO_PREFIX: &[u8] = b"crypto-layer/field/";
+const RANDOMIZED: &[u8] = b"randomized";
+const DETERMINISTIC: &[u8] = b"deterministic";
+const DETERMINISTIC_NONCE: &[u8] = b"deterministic-nonce";
+
+/// The field a value belongs to, which every ciphertext is bound to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FieldContext<'a> {
+    table: &'a str,
+    column: &'a str,
+    row: Option<&'a str>,
+}
+
+impl<'a> FieldContext<'a> {
+    /// Creates the context of a column.
+    pub fn new(table: &'a str, column: &'a str) -> Self {
+        Self {
+            table,
+            column,
+            row: None,
+        }
+    }
+
+    /// Binds the context to a row, e.g. to its primary key.
+    pub fn row(mut self, row: &'a str) -> Self {
+        self.row = Some(row);
+        self
+    }
+
+    fn kdf_info(&self, mode: &[u8]) -> Result<Vec<u8>, SecurityModuleError> {
+        let mut info = [KDF_INFO_PREFIX, mode].concat();
+        for part in [self.table, self.column, self.row.unwrap_or_default()] {
+            let len = u16::try_from(part.len()).map_err(|_| {
+                SecurityModuleError::EncryptionError(
+                    "Table, column and row ids must be shorter than 64 KiB".to_owned(),
+                )
+            })?;
+            info.extend_from_slice(&len.to_be_bytes());
+            info.extend_from_slice(part.as_bytes());
+        }
+        Ok(info)
+    }
+}
+
+/// Encrypts and decrypts database fields, see the module documentation.
+#[derive(Clone)]
+pub struct FieldEncryptor {
+    key_id: String,
+    root_key: SecretBytes,
+}
+
+impl FieldEncryptor {
+    /// Generates a new root key.
+    ///
+    /// # Arguments
+    ///
+    /// * `key_handle` - Encrypts the root key with `encrypt_data`, e.g. a provider with the loaded
+    ///   key `key_id`.
+    /// * `key_id` - The id of the key, recorded in every field.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `FieldEncryptor` and the encrypted root key, which has to be
+    /// stored to decrypt the fields again, or the error of `key_handle`.
+    pub fn generate(
+        key_handle: &(impl KeyHandle + ?Sized),
+        key_id: &str,
+    ) -> Result<(Self, Vec<u8>), SecurityModuleError> {
+        let mut root_key = SecretBytes::zeroed(KEY_LEN);
+        rand_bytes(&mut root_key)
+            .map_err(|e| SecurityModuleError::EncryptionError(e.to_string()))?;
+        let wrapped_root_key = key_handle.encrypt_data(&root_key)?;
+        Ok((
+            Self {
+                key_id: key_id.to_owned(),
+                root_key,
+            },
+            wrapped_root_key,
+        ))
+    }
+
+    /// Decrypts a root key of `generate`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `FieldEncryptor`, the error of `key_handle` if the root key
+    /// cannot be decrypted, or a `SecurityModuleError::DecryptionError` if it has the wrong length.
+    pub fn unwrap(
+        key_handle: &(impl KeyHandle + ?Sized),
+        key_id: &str,
+        wrapped_root_key: &[u8],
+    ) -> Result<Self, SecurityModuleError> {
+        let root_key = key_handle.decrypt_data(wrapped_root_key)?;
+        if root_key.len() != KEY_LEN {
+            return Err(SecurityModuleError::DecryptionError(
+                "The root key has the wrong length".to_owned(),
+            ));
+        }
+        Ok(Self {
+            key_id: key_id.to_owned(),
+            root_key,
+        })
+    }
+
+    /// Returns the id of the key the root key is encrypted with.
+    pub fn key_id(&self) -> &str {
+        &self.key_id
+    }
+
+    /// Encrypts `plaintext` for the field `context` with a random salt and nonce.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the encoded envelope, or a `SecurityModuleError::EncryptionError`
+    /// if a part of the context is 64 KiB or longer.
+    pub fn encrypt(
+        &self,
+        context: &FieldContext<'_>,
+        plaintext: &[u8],
+    ) -> Result<Vec<u8>, SecurityModuleError> {
+        let mut salt = vec![0; SALT_LEN];
+        rand_bytes(&mut salt).map_err(|e| SecurityModuleError::EncryptionError(e.to_string()))?;
+        let key = KDF.derive_vec(
+            &self.root_key,
+            Some(&salt),
+            &context.kdf_info(RANDOMIZED)?,
+            KEY_LEN,
+        )?;
+
+        let mut envelope = Envelope::new(AEAD, self.key_id.as_str(), aead::random_nonce(AEAD)?);
+        envelope.kdf = Some(KDF);
+        envelope.salt = Some(salt);
+        aead::seal(envelope, &key, plaintext)
+    }
+
+    /// Encrypts `plaintext` for the column of `context` so that equal plaintexts have equal
+    /// ciphertexts.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the encoded envelope, or a `SecurityModuleError::EncryptionError`
+    /// if the context is bound to a row, which would make the ciphertexts unsearchable, or a part
+    /// of it is 64 KiB or longer.
+    pub fn encrypt_deterministic(
+        &self,
+        context: &FieldContext<'_>,
+        plaintext: &[u8],
+    ) -> Result<Vec<u8>, SecurityModuleError> {
+        if context.row.is_some() {
+            return Err(SecurityModuleError::EncryptionError(
+                "Deterministically encrypted fields cannot be bound to a row".to_owned(),
+            ));
+        }
+        let (key, nonce_key) = self.deterministic_keys(context)?;
+        let nonce = synthetic_nonce(&nonce_key, plaintext)
+            .map_err(|e| SecurityModuleError::EncryptionError(e.to_string()))?;
+
+        let mut envelope = Envelope::new(AEAD, self.key_id.as_str(), nonce);
+        envelope.kdf = Some(KDF);
+        aead::seal(envelope, &key, plaintext)
+    }
+
+    /// Decrypts a field of `encrypt` or `encrypt_deterministic` with the context it was encrypted
+    /// with.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the plaintext, which is zeroed when dropped, or a
+    /// `SecurityModuleError::DecryptionError` if the field was encrypted for another context or
+    /// under another root key or was modified.
+    pub fn decrypt(
+        &self,
+        context: &FieldContext<'_>,
+        ciphertext: &[u8],
+    ) -> Result<SecretBytes, SecurityModuleError> {
+        let envelope = input_limits::validate_envelope(ciphertext)?;
+        if envelope.aead != AEAD || envelope.kdf != Some(KDF) {
+            return Err(SecurityModuleError::DecryptionError(
+                "The envelope is not an encrypted field".to_owned(),
+            ));
+        }
+
+        match envelope.salt {
+            Some(salt) => {
+                let key = KDF.derive_vec(
+                    &self.root_key,
+                    Some(salt),
+                    &context.kdf_info(RANDOMIZED)?,
+                    KEY_LEN,
+                )?;
+                aead::open(&envelope, &key)
+            }
+            None => {
+                let (key, nonce_key) = self.deterministic_keys(context)?;
+                let plaintext = aead::open(&envelope, &key)?;
+                // The nonce is authenticated, checking it only guards against a broken encryptor.
+                let nonce = synthetic_nonce(&nonce_key, &plaintext)
+                    .map_err(|e| SecurityModuleError::DecryptionError(e.to_string()))?;
+                if !constant_time::eq(&nonce, envelope.nonce) {
+                    return Err(SecurityModuleError::DecryptionError(
+                        "The nonce of the field does not match its plaintext".to_owned(),
+                    ));
+                }
+                Ok(plaintext)
+            }
+        }
+    }
+
+    fn deterministic_keys(
+        &self,
+        context: &FieldContext<'_>,
+    ) -> Result<(SecretBytes, SecretBytes), SecurityModuleError> {
+        let key = KDF.derive_vec(
+            &self.root_key,
+            None,
+            &context.kdf_info(DETERMINISTIC)?,
+            KEY_LEN,
+        )?;
+        let nonce_key = KDF.derive_vec(
+            &self.root_key,
+            None,
+            &context.kdf_info(DETERMINISTIC_NONCE)?,
+            KEY_LEN,
+        )?;
+        Ok((key, nonce_key))
+    }
+}
+
+impl fmt::Debug for FieldEncryptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FieldEncryptor")
+            .field("key_id", &self.key_id)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Derives the nonce of a deterministically encrypted field from its plaintext.
+fn synthetic_nonce(
+    nonce_key: &[u8],
+    plaintext: &[u8],
+) -> Result<Vec<u8>, openssl::error::ErrorStack> {
+    let key = PKey::hmac(nonce_key)?;
+    let mut signer = Signer::new(MessageDigest::sha256(), &key)?;
+    signer.update(plaintext)?;
+    let mut nonce = signer.sign_to_vec()?;
+    nonce.truncate(AEAD.nonce_len());
+    Ok(nonce)
+}
@@ -0,0 +1,496 @@
+//! Encryption of files of any size under a key of the security module.
+//!
+//! `encrypt_file` streams a file through AES-256-GCM in chunks of fixed size, so files are never
+//! held in memory as a whole, and `decrypt_file` restores it:
+//!
+//! ```rust,ignore
+//! use crypto_layer::common::file_encryption::{decrypt_file, encrypt_file};
+//!
+//! encrypt_file(&provider, "backup_key", Path::new("db.sqlite"), Path::new("db.sqlite.enc"))?;
+//! decrypt_file(&provider, Path::new("db.sqlite.enc"), Path::new("db.sqlite"))?;
+//! ```
+//!
+//! An encrypted file starts with the length of its header as a 32-bit big-endian integer and the
+//! header, an envelope (see `crypto::envelope`) holding the file key wrapped with `encrypt_data`
+//! of the provider and the chunk size as its payload. The frames follow, each a kind byte, the
+//! length of its ciphertext as a 32-bit big-endian integer and the ciphertext:
+//!
+//! - A chunk frame (`0`) holds one chunk of the plaintext. Its nonce is derived from the nonce of
+//!   the header and the index of the chunk, and the index and a hash of the header are passed as
+//!   associated data, so chunks cannot be reordered or moved to another file.
+//! - The manifest frame (`1`) is always the last frame. It holds the number of chunks, the length
+//!   of the plaintext and a hash over the chunk ciphertexts in their order. A file missing its
+//!   last chunks or its manifest therefore fails to decrypt.
+//!
+//! The chunk and header keys are derived from the file key with HKDF, so every file uses its own
+//! keys and nonces never repeat under a key.
+
+use crate::common::{
+    constant_time,
+    crypto::{
+        aead,
+        envelope::{AeadAlgorithm, Envelope, EnvelopeRef},
+        kdf::Kdf,
+        secret::SecretBytes,
+    },
+    error::SecurityModuleError,
+    traits::key_handle::KeyHandle,
+};
+use openssl::{
+    rand::rand_bytes,
+    sha::{sha256, Sha256},
+    symm::{decrypt_aead, encrypt_aead, Cipher},
+};
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, ErrorKind, Read, Write},
+    path::{Path, PathBuf},
+};
+
+/// The size of the plaintext chunks `encrypt_file` uses, 64 KiB.
+pub const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+
+/// The largest supported chunk size, 16 MiB. Decryption rejects headers with larger chunks, so a
+/// modified file cannot make it allocate arbitrarily large buffers.
+pub const MAX_CHUNK_SIZE: usize = 16 * 1024 * 1024;
+
+/// The algorithm chunks are encrypted with.
+const AEAD: AeadAlgorithm = AeadAlgorithm::Aes256Gcm;
+
+/// The length of the random salt of the key derivation in bytes.
+const SALT_LEN: usize = 16;
+
+/// The largest header `decrypt_stream` reads. Headers hold a wrapped key, which is at most a
+/// few kilobytes long.
+const MAX_HEADER_LEN: usize = 64 * 1024;
+
+const HEADER_INFO: &[u8] = b"crypto-layer/file/header";
+const CHUNK_INFO: &[u8] = b"crypto-layer/file/chunk";
+
+const CHUNK_FRAME: u8 = 0;
+const MANIFEST_FRAME: u8 = 1;
+
+/// The length of the plaintext of the manifest frame: the number of chunks, the length of the
+/// plaintext and the hash of the chunk ciphertexts.
+const MANIFEST_LEN: usize = 8 + 8 + 32;
+
+/// Describes an encrypted file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileManifest {
+    /// The id of the key the file key is wrapped with.
+    pub key_id: String,
+    /// The size of the plaintext chunks in bytes.
+    pub chunk_size: usize,
+    /// The number of chunks.
+    pub chunks: u64,
+    /// The length of the plaintext in bytes.
+    pub plaintext_len: u64,
+}
+
+/// Encrypts the file `input` into the file `output` in chunks of `DEFAULT_CHUNK_SIZE` bytes.
+///
+/// `output` is written to a temporary file next to it first and only replaced once encryption
+/// succeeded.
+///
+/// # Arguments
+///
+/// * `key_handle` - Wraps the file key with `encrypt_data`, e.g. a provider with a loaded RSA
+///   key or P-256 key of the Secure Enclave.
+/// * `key_id` - The id of the key of `key_handle`, which is recorded in the header.
+/// * `input` - The file to be encrypted.
+/// * `output` - The encrypted file to be written.
+///
+/// # Returns
+///
+/// A `Result` containing the `FileManifest` of the encrypted file, or a
+/// `SecurityModuleError::EncryptionError` if a file cannot be read or written, or the error of
+/// `key_handle` if the file key cannot be wrapped.
+#[tracing::instrument(skip_all)]
+pub fn encrypt_file(
+    key_handle: &(impl KeyHandle + ?Sized),
+    key_id: &str,
+    input: &Path,
+    output: &Path,
+) -> Result<FileManifest, SecurityModuleError> {
+    let reader = File::open(input).map_err(|e| encryption_error("read", input, e))?;
+    write_atomically(output, encryption_error, |writer| {
+        encrypt_stream(
+            key_handle,
+            key_id,
+            BufReader::new(reader),
+            writer,
+            DEFAULT_CHUNK_SIZE,
+        )
+    })
+}
+
+/// Decrypts the file `input` written by `encrypt_file` into the file `output`.
+///
+/// The plaintext is written to a temporary file next to `output` first, which is only renamed to
+/// `output` once the manifest has been verified, so a truncated or modified file never leaves a
+/// partial plaintext behind.
+///
+/// # Returns
+///
+/// A `Result` containing the `FileManifest` of the encrypted file, or a
+/// `SecurityModuleError::DecryptionError` if a file cannot be read or written or the encrypted
+/// file was truncated, reordered or modified, or the error of `key_handle` if the file key cannot
+/// be unwrapped.
+#[tracing::instrument(skip_all)]
+pub fn decrypt_file(
+    key_handle: &(impl KeyHandle + ?Sized),
+    input: &Path,
+    output: &Path,
+) -> Result<FileManifest, SecurityModuleError> {
+    let reader = File::open(input).map_err(|e| decryption_error("read", input, e))?;
+    write_atomically(output, decryption_error, |writer| {
+        decrypt_stream(key_handle, BufReader::new(reader), writer)
+    })
+}
+
+/// Encrypts everything read from `reader` into `writer` in chunks of `chunk_size` bytes.
+///
+/// # Returns
+///
+/// A `Result` containing the `FileManifest` of the encrypted data, or a
+/// `SecurityModuleError::EncryptionError` if `chunk_size` is 0 or larger than `MAX_CHUNK_SIZE`
+/// or reading or writing fails, or the error of `key_handle` if the file key cannot be wrapped.
+pub fn encrypt_stream(
+    key_handle: &(impl KeyHandle + ?Sized),
+    key_id: &str,
+    mut reader: impl Read,
+    mut writer: impl Write,
+    chunk_size: usize,
+) -> Result<FileManifest, SecurityModuleError> {
+    if chunk_size == 0 || chunk_size > MAX_CHUNK_SIZE {
+        return Err(SecurityModuleError::EncryptionError(format!(
+            "The chunk size must be between 1 and {} bytes, but is {}",
+            MAX_CHUNK_SIZE, chunk_size
+        )));
+    }
+    let io_error = |e: io::Error| SecurityModuleError::EncryptionError(e.to_string());
+
+    let mut file_key = SecretBytes::zeroed(AEAD.key_len());
+    rand_bytes(&mut file_key).map_err(|e| SecurityModuleError::EncryptionError(e.to_string()))?;
+    let salt = random_bytes(SALT_LEN)?;
+    let mut envelope = Envelope::new(AEAD, key_id, aead::random_nonce(AEAD)?);
+    envelope.wrapped_key = Some(key_handle.encrypt_data(&file_key)?);
+    envelope.kdf = Some(Kdf::HkdfSha256);
+    envelope.salt = Some(salt);
+    let keys = FileKeys::derive(&file_key, &envelope.salt, &envelope.nonce)?;
+    let header = aead::seal(envelope, &keys.header, &(chunk_size as u32).to_be_bytes())?;
+    writer
+        .write_all(&(header.len() as u32).to_be_bytes())
+        .and_then(|()| writer.write_all(&header))
+        .map_err(io_error)?;
+
+    let header_hash = sha256(&header);
+    let mut order = Sha256::new();
+    let mut chunk = vec![0; chunk_size];
+    let mut chunks = 0u64;
+    let mut plaintext_len = 0u64;
+    loop {
+        let len = read_full(&mut reader, &mut chunk).map_err(io_error)?;
+        if len == 0 {
+            break;
+        }
+        let ciphertext = keys
+            .seal(CHUNK_FRAME, chunks, &header_hash, &chunk[..len])
+            .map_err(SecurityModuleError::EncryptionError)?;
+        order.update(&ciphertext);
+        write_frame(&mut writer, CHUNK_FRAME, &ciphertext).map_err(io_error)?;
+        chunks += 1;
+        plaintext_len += len as u64;
+        if len < chunk_size {
+            break;
+        }
+    }
+
+    let mut manifest = Vec::with_capacity(MANIFEST_LEN);
+    manifest.extend_from_slice(&chunks.to_be_bytes());
+    manifest.extend_from_slice(&plaintext_len.to_be_bytes());
+    manifest.extend_from_slice(&order.finish());
+    let ciphertext = keys
+        .seal(MANIFEST_FRAME, chunks, &header_hash, &manifest)
+        .map_err(SecurityModuleError::EncryptionError)?;
+    write_frame(&mut writer, MANIFEST_FRAME, &ciphertext).map_err(io_error)?;
+    writer.flush().map_err(io_error)?;
+
+    Ok(FileManifest {
+        key_id: key_id.to_owned(),
+        chunk_size,
+        chunks,
+        plaintext_len,
+    })
+}
+
+/// Decrypts the data written by `encrypt_stream` from `reader` into `writer`.
+///
+/// Every chunk is authenticated before it is written, but whether chunks are missing is only
+/// known once the manifest has been read. If this function fails, the data written so far must
+/// be discarded.
+///
+/// # Returns
+///
+/// A `Result` containing the `FileManifest` of the encrypted data, or a
+/// `SecurityModuleError::DecryptionError` if reading or writing fails or the data was truncated,
+/// reordered or modified, or the error of `key_handle` if the file key cannot be unwrapped.
+pub fn decrypt_stream(
+    key_handle: &(impl KeyHandle + ?Sized),
+    mut reader: impl Read,
+    mut writer: impl Write,
+) -> Result<FileManifest, SecurityModuleError> {
+    let io_error = |e: io::Error| match e.kind() {
+        ErrorKind::UnexpectedEof => invalid("The encrypted file is truncated"),
+        _ => SecurityModuleError::DecryptionError(e.to_string()),
+    };
+
+    let mut len = [0; 4];
+    reader.read_exact(&mut len).map_err(io_error)?;
+    let len = u32::from_be_bytes(len) as usize;
+    if len > MAX_HEADER_LEN {
+        return Err(invalid("The header of the encrypted file is too long"));
+    }
+    let mut header = vec![0; len];
+    reader.read_exact(&mut header).map_err(io_error)?;
+
+    let envelope = EnvelopeRef::parse(&header)?;
+    let wrapped_key = envelope
+        .wrapped_key
+        .ok_or_else(|| invalid("The header of the encrypted file has no wrapped file key"))?;
+    if envelope.aead != AEAD || envelope.kdf != Some(Kdf::HkdfSha256) {
+        return Err(SecurityModuleError::UnsupportedAlgorithm);
+    }
+    let file_key = key_handle.decrypt_data(wrapped_key)?;
+    let keys = FileKeys::derive(
+        &file_key,
+        &envelope.salt.map(<[u8]>::to_vec),
+        envelope.nonce,
+    )?;
+    let chunk_size = match aead::open(&envelope, &keys.header)?[..].try_into() {
+        Ok(bytes) => u32::from_be_bytes(bytes) as usize,
+        Err(_) => return Err(invalid("The header of the encrypted file is invalid")),
+    };
+    if chunk_size == 0 || chunk_size > MAX_CHUNK_SIZE {
+        return Err(invalid("The chunk size of the encrypted file is invalid"));
+    }
+
+    let header_hash = sha256(&header);
+    let mut order = Sha256::new();
+    let mut chunks = 0u64;
+    let mut plaintext_len = 0u64;
+    let mut last_chunk_full = true;
+    loop {
+        let mut frame = [0; 5];
+        reader.read_exact(&mut frame).map_err(io_error)?;
+        let len = u32::from_be_bytes([frame[1], frame[2], frame[3], frame[4]]) as usize;
+        if len > chunk_size.max(MANIFEST_LEN) + AEAD.tag_len() {
+            return Err(invalid("A frame of the encrypted file is too long"));
+        }
+        let mut ciphertext = vec![0; len];
+        reader.read_exact(&mut ciphertext).map_err(io_error)?;
+
+        match frame[0] {
+            CHUNK_FRAME if last_chunk_full => {
+                let chunk = keys
+                    .open(CHUNK_FRAME, chunks, &header_hash, &ciphertext)
+                    .ok_or_else(|| invalid("A chunk of the encrypted file was modified"))?;
+                order.update(&ciphertext);
+                writer
+                    .write_all(&chunk)
+                    .map_err(|e| SecurityModuleError::DecryptionError(e.to_string()))?;
+                last_chunk_full = chunk.len() == chunk_size;
+                chunks += 1;
+                plaintext_len += chunk.len() as u64;
+            }
+            MANIFEST_FRAME => {
+                let manifest = keys
+                    .open(MANIFEST_FRAME, chunks, &header_hash, &ciphertext)
+                    .ok_or_else(|| {
+                        invalid("Chunks of the encrypted file are missing or were reordered")
+                    })?;
+                let expected = [
+                    &chunks.to_be_bytes()[..],
+                    &plaintext_len.to_be_bytes(),
+                    &order.finish(),
+                ]
+                .concat();
+                if !constant_time::eq(&manifest, &expected) {
+                    return Err(invalid(
+                        "The chunks of the encrypted file do not match its manifest",
+                    ));
+                }
+                break;
+            }
+            _ => return Err(invalid("The encrypted file has an invalid frame")),
+        }
+    }
+
+    if reader.read(&mut [0]).map_err(io_error)? != 0 {
+        return Err(invalid("The encrypted file has data after its manifest"));
+    }
+    writer
+        .flush()
+        .map_err(|e| SecurityModuleError::DecryptionError(e.to_string()))?;
+
+    Ok(FileManifest {
+        key_id: envelope.key_id.to_owned(),
+        chunk_size,
+        chunks,
+        plaintext_len,
+    })
+}
+
+/// The keys derived from the file key.
+struct FileKeys {
+    header: SecretBytes,
+    chunk: SecretBytes,
+    base_nonce: Vec<u8>,
+}
+
+impl FileKeys {
+    fn derive(
+        file_key: &[u8],
+        salt: &Option<Vec<u8>>,
+        base_nonce: &[u8],
+    ) -> Result<Self, SecurityModuleError> {
+        let derive =
+            |info| Kdf::HkdfSha256.derive_vec(file_key, salt.as_deref(), info, AEAD.key_len());
+        Ok(Self {
+            header: derive(HEADER_INFO)?,
+            chunk: derive(CHUNK_INFO)?,
+            base_nonce: base_nonce.to_vec(),
+        })
+    }
+
+    /// Returns the nonce of the frame at `index`: the base nonce with the index XORed into its
+    /// last 8 bytes.
+    fn nonce(&self, index: u64) -> Vec<u8> {
+        let mut nonce = self.base_nonce.clone();
+        let offset = nonce.len() - 8;
+        for (byte, index) in nonce[offset..].iter_mut().zip(index.to_be_bytes()) {
+            *byte ^= index;
+        }
+        nonce
+    }
+
+    /// Returns the associated data of a frame, its kind and index and the hash of the header.
+    fn aad(kind: u8, index: u64, header_hash: &[u8; 32]) -> Vec<u8> {
+        [&[kind][..], &index.to_be_bytes(), header_hash].concat()
+    }
+
+    fn seal(
+        &self,
+        kind: u8,
+        index: u64,
+        header_hash: &[u8; 32],
+        plaintext: &[u8],
+    ) -> Result<Vec<u8>, String> {
+        let mut tag = vec![0; AEAD.tag_len()];
+        let mut ciphertext = encrypt_aead(
+            Cipher::aes_256_gcm(),
+            &self.chunk,
+            Some(&self.nonce(index)),
+            &Self::aad(kind, index, header_hash),
+            plaintext,
+            &mut tag,
+        )
+        .map_err(|e| e.to_string())?;
+        ciphertext.extend_from_slice(&tag);
+        Ok(ciphertext)
+    }
+
+    /// Decrypts a frame, or returns `None` if it was modified or belongs to another position.
+    fn open(
+        &self,
+        kind: u8,
+        index: u64,
+        header_hash: &[u8; 32],
+        ciphertext: &[u8],
+    ) -> Option<SecretBytes> {
+        let split = ciphertext.len().checked_sub(AEAD.tag_len())?;
+        let (ciphertext, tag) = ciphertext.split_at(split);
+        decrypt_aead(
+            Cipher::aes_256_gcm(),
+            &self.chunk,
+            Some(&self.nonce(index)),
+            &Self::aad(kind, index, header_hash),
+            ciphertext,
+            tag,
+        )
+        .ok()
+        .map(SecretBytes::new)
+    }
+}
+
+fn write_frame(writer: &mut impl Write, kind: u8, ciphertext: &[u8]) -> io::Result<()> {
+    writer.write_all(&[kind])?;
+    writer.write_all(&(ciphertext.len() as u32).to_be_bytes())?;
+    writer.write_all(ciphertext)
+}
+
+/// Reads until `buffer` is full or the end of `reader` is reached and returns the number of
+/// bytes read.
+fn read_full(reader: &mut impl Read, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut len = 0;
+    while len < buffer.len() {
+        match reader.read(&mut buffer[len..]) {
+            Ok(0) => break,
+            Ok(read) => len += read,
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(len)
+}
+
+/// Writes `path` through a temporary file next to it, which is renamed to `path` if `write`
+/// succeeds and removed otherwise.
+fn write_atomically<T>(
+    path: &Path,
+    error: fn(&str, &Path, io::Error) -> SecurityModuleError,
+    write: impl FnOnce(&mut BufWriter<File>) -> Result<T, SecurityModuleError>,
+) -> Result<T, SecurityModuleError> {
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".partial");
+    let temporary = PathBuf::from(temporary);
+
+    let result = File::create(&temporary)
+        .map_err(|e| error("write", &temporary, e))
+        .and_then(|file| {
+            let mut writer = BufWriter::new(file);
+            let value = write(&mut writer)?;
+            writer
+                .into_inner()
+                .map_err(|e| error("write", &temporary, e.into_error()))?
+                .sync_all()
+                .map_err(|e| error("write", &temporary, e))?;
+            Ok(value)
+        })
+        .and_then(|value| {
+            fs::rename(&temporary, path).map_err(|e| error("write", path, e))?;
+            Ok(value)
+        });
+    if result.is_err() {
+        let _ = fs::remove_file(&temporary);
+    }
+    result
+}
+
+fn encryption_error(action: &str, path: &Path, e: io::Error) -> SecurityModuleError {
+    SecurityModuleError::EncryptionError(format!("Cannot {} '{}': {}", action, path.display(), e))
+}
+
+fn decryption_error(action: &str, path: &Path, e: io::Error) -> SecurityModuleError {
+    SecurityModuleError::DecryptionError(format!("Cannot {} '{}': {}", action, path.display(), e))
+}
+
+fn invalid(message: &str) -> SecurityModuleError {
+    SecurityModuleError::DecryptionError(message.to_owned())
+}
+
+fn random_bytes(len: usize) -> Result<Vec<u8>, SecurityModuleError> {
+    let mut bytes = vec![0; len];
+    rand_bytes(&mut bytes).map_err(|e| SecurityModuleError::EncryptionError(e.to_string()))?;
+    Ok(bytes)
+}
@@ -0,0 +1,144 @@
+//! FIPS mode, which restricts the crate to FIPS-approved algorithms for regulated deployments.
+//!
+//! In FIPS mode the `ValidatedProvider` of every instance created by `SecModules` refuses to
+//! create, import or load keys whose algorithm or hash is not in `APPROVED_ALGORITHMS`, e.g. RSA
+//! keys shorter than 2048 bits, Curve25519, secp256k1 and Brainpool curves, or SHA-1 and MD5.
+//! Of the block ciphers only AES with 128, 192 or 256 bits is approved, and configurations whose
+//! algorithms cannot be determined, e.g. of unknown types, are refused. Envelopes are only
+//! sealed and opened with AES-GCM. Age files and libsodium sealed boxes, which are built on
+//! ChaCha20 and XSalsa20, the X25519 sessions of `messaging`, and the Argon2id password hashing
+//! of `password` and `escrow` are refused. Refusals fail with
+//! `SecurityModuleError::NotFipsApproved` naming the algorithm.
+//!
+//! The mode is enabled with `fips = true` in the configuration or `CRYPTO_LAYER_FIPS=true`, or
+//! at build time with the `fips` feature, which cannot be turned off at runtime. Instances keep
+//! the mode they were created in. The mode is recorded in every `AuditEvent`, in the
+//! `ProofClaims` of proofs of possession and in support bundles, so auditors can tell which
+//! operations ran in FIPS mode.
+//!
+//! FIPS mode only restricts the algorithms the crate asks for. Whether the implementations are
+//! validated depends on the security module and on the OpenSSL build the crate is linked with.
+
+use crate::common::{
+    crypto::{
+        algorithms::{
+            encryption::{AsymmetricEncryption, BlockCiphers},
+            hashes::Hash,
+            KeyBits,
+        },
+        envelope::AeadAlgorithm,
+    },
+    error::SecurityModuleError,
+    sunset::{algorithm_name, hash_name, requested_algorithms},
+};
+use std::{
+    any::Any,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+/// The names of the approved algorithms among `sunset::ALGORITHM_NAMES`.
+pub const APPROVED_ALGORITHMS: [&str; 17] = [
+    "rsa2048",
+    "rsa3072",
+    "rsa4096",
+    "rsa8192",
+    "p256",
+    "p384",
+    "p521",
+    "sha224",
+    "sha256",
+    "sha384",
+    "sha512",
+    "sha512_224",
+    "sha512_256",
+    "sha3_224",
+    "sha3_256",
+    "sha3_384",
+    "sha3_512",
+];
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Enables or disables FIPS mode. Disabling has no effect with the `fips` feature.
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Returns whether FIPS mode is enabled, at build time or at runtime.
+pub fn is_enabled() -> bool {
+    cfg!(feature = "fips") || ENABLED.load(Ordering::Relaxed)
+}
+
+/// Returns whether the algorithm named as in `sunset::ALGORITHM_NAMES` is approved.
+pub fn is_approved(name: &str) -> bool {
+    APPROVED_ALGORITHMS.contains(&name)
+}
+
+/// Checks that the algorithm named `name` is approved.
+///
+/// # Returns
+///
+/// A `Result` that is `Ok(())` if the algorithm is in `APPROVED_ALGORITHMS`, or a
+/// `SecurityModuleError::NotFipsApproved` naming it.
+pub fn check_name(name: &str) -> Result<(), SecurityModuleError> {
+    if is_approved(name) {
+        return Ok(());
+    }
+    Err(SecurityModuleError::NotFipsApproved(format!(
+        "{} is not approved in FIPS mode",
+        name
+    )))
+}
+
+/// Checks that `algorithm` and `hash` are approved, see `check_name`.
+pub fn check(
+    algorithm: Option<AsymmetricEncryption>,
+    hash: Option<Hash>,
+) -> Result<(), SecurityModuleError> {
+    algorithm
+        .and_then(algorithm_name)
+        .into_iter()
+        .chain(hash.map(hash_name))
+        .try_for_each(|name| check_name(&name))
+}
+
+/// Checks the algorithms of a key about to be created or loaded with `config`, which is the
+/// configuration passed to `Provider::create_key` or `Provider::load_key` or the `KeySpec` passed
+/// to `Provider::import_wrapped_key`.
+///
+/// # Returns
+///
+/// A `Result` that is `Ok(())` if the algorithms are approved, or a
+/// `SecurityModuleError::NotFipsApproved` naming the first one that is not, or if `config` is of
+/// an unknown type or names neither an asymmetric nor a symmetric algorithm.
+pub fn check_key(config: &dyn Any) -> Result<(), SecurityModuleError> {
+    let requested = requested_algorithms(config)
+        .filter(|requested| requested.asymmetric.is_some() || requested.symmetric.is_some())
+        .ok_or_else(|| {
+            SecurityModuleError::NotFipsApproved(
+                "The algorithms of the key configuration cannot be checked in FIPS mode".to_owned(),
+            )
+        })?;
+    check(requested.asymmetric, requested.hash)?;
+    requested.symmetric.map_or(Ok(()), check_block_cipher)
+}
+
+/// Checks that `cipher` is approved. Only AES with 128, 192 or 256 bits is, in any mode.
+pub fn check_block_cipher(cipher: BlockCiphers) -> Result<(), SecurityModuleError> {
+    match cipher {
+        BlockCiphers::Aes(_, KeyBits::Bits128 | KeyBits::Bits192 | KeyBits::Bits256) => Ok(()),
+        BlockCiphers::Aes(_, bits) => check_name(&format!("aes{}", u32::from(bits))),
+        BlockCiphers::TripleDes(_) => check_name("tdea"),
+        BlockCiphers::Des => check_name("des"),
+        BlockCiphers::Rc2(_) => check_name("rc2"),
+        BlockCiphers::Camellia(_, bits) => check_name(&format!("camellia{}", u32::from(bits))),
+    }
+}
+
+/// Checks that `aead` is approved. Only AES-GCM is.
+pub fn check_aead(aead: AeadAlgorithm) -> Result<(), SecurityModuleError> {
+    match aead {
+        AeadAlgorithm::Aes128Gcm | AeadAlgorithm::Aes256Gcm => Ok(()),
+        AeadAlgorithm::ChaCha20Poly1305 => check_name("chacha20_poly1305"),
+    }
+}
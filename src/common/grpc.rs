@@ -0,0 +1,466 @@
+//! The gRPC plumbing shared by the KMS plugin and the remote provider, see `kms_plugin` and
+//! `remote`.
+//!
+//! Both only need unary calls, which are small enough to implement on `h2` directly: a
+//! request carries a single length-prefixed message, and a response a single message followed
+//! by the `grpc-status` trailer. Messages are protocol buffers, encoded by hand with the
+//! helpers at the end of this module.
+
+use crate::common::error::SecurityModuleError;
+use bytes::Bytes;
+use crypto_layer_core::CoreError;
+#[cfg(feature = "remote")]
+use h2::client::SendRequest;
+use h2::{server::SendResponse, RecvStream};
+use http::{header::CONTENT_TYPE, HeaderMap, HeaderValue, Method, Request, Response, StatusCode};
+use std::{io, sync::Arc};
+use tokio::io::{AsyncRead, AsyncWrite};
+
+pub(crate) const GRPC_OK: u32 = 0;
+pub(crate) const GRPC_INVALID_ARGUMENT: u32 = 3;
+#[cfg(feature = "remote")]
+pub(crate) const GRPC_DEADLINE_EXCEEDED: u32 = 4;
+pub(crate) const GRPC_NOT_FOUND: u32 = 5;
+pub(crate) const GRPC_UNIMPLEMENTED: u32 = 12;
+pub(crate) const GRPC_INTERNAL: u32 = 13;
+pub(crate) const GRPC_UNAVAILABLE: u32 = 14;
+#[cfg(feature = "remote")]
+pub(crate) const GRPC_UNAUTHENTICATED: u32 = 16;
+
+/// The trailer with the `SecurityModuleError::code` of a failed call.
+pub(crate) const ERROR_CODE: &str = "crypto-layer-error-code";
+
+/// A gRPC service with unary methods.
+pub(crate) trait Service: Send + Sync + 'static {
+    /// The prefix of the paths of the methods, e.g. `/v2.KeyManagementService/`.
+    const PATH: &'static str;
+    /// The name of the service in logs.
+    const NAME: &'static str;
+    /// The longest request message the service accepts in bytes.
+    const MAX_MESSAGE_LEN: usize;
+
+    /// Checks the headers of a call before its message is read.
+    fn authorize(&self, _headers: &HeaderMap) -> Result<(), GrpcStatus> {
+        Ok(())
+    }
+
+    /// Answers a call of `method` with the encoded request. Runs on a blocking thread.
+    fn call(&self, method: &str, request: &[u8]) -> Result<Vec<u8>, GrpcStatus>;
+}
+
+/// The status of a gRPC call, sent in the trailers of the response.
+#[derive(Debug)]
+pub(crate) struct GrpcStatus {
+    pub(crate) code: u32,
+    pub(crate) message: String,
+    /// The `SecurityModuleError::code` of the error that failed the call.
+    pub(crate) error_code: Option<u32>,
+}
+
+impl GrpcStatus {
+    pub(crate) fn new(code: u32, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            error_code: None,
+        }
+    }
+
+    fn ok() -> Self {
+        Self::new(GRPC_OK, "")
+    }
+
+    fn trailers(&self) -> HeaderMap {
+        let mut trailers = HeaderMap::new();
+        trailers.insert("grpc-status", HeaderValue::from(self.code));
+        if !self.message.is_empty() {
+            let message = percent_encode(&self.message);
+            if let Ok(value) = HeaderValue::from_str(&message) {
+                trailers.insert("grpc-message", value);
+            }
+        }
+        if let Some(error_code) = self.error_code {
+            trailers.insert(ERROR_CODE, HeaderValue::from(error_code));
+        }
+        trailers
+    }
+
+    /// Reads the status from the headers or trailers of a response.
+    #[cfg(feature = "remote")]
+    fn from_trailers(trailers: &HeaderMap) -> Option<Self> {
+        let number = |name: &str| trailers.get(name)?.to_str().ok()?.parse().ok();
+        Some(Self {
+            code: number("grpc-status")?,
+            message: trailers
+                .get("grpc-message")
+                .and_then(|value| value.to_str().ok())
+                .map(percent_decode)
+                .unwrap_or_default(),
+            error_code: number(ERROR_CODE),
+        })
+    }
+}
+
+impl From<SecurityModuleError> for GrpcStatus {
+    fn from(error: SecurityModuleError) -> Self {
+        let code = match error {
+            SecurityModuleError::Encoding(_)
+            | SecurityModuleError::InvalidKeySpec(_)
+            | SecurityModuleError::InvalidKeyId(_)
+            | SecurityModuleError::InputTooLarge(_) => GRPC_INVALID_ARGUMENT,
+            SecurityModuleError::KeyError => GRPC_NOT_FOUND,
+            SecurityModuleError::SessionPoolTimeout => GRPC_UNAVAILABLE,
+            _ => GRPC_INTERNAL,
+        };
+        Self {
+            code,
+            message: error.to_string(),
+            error_code: Some(error.code()),
+        }
+    }
+}
+
+/// Serves the calls of one connected client until it disconnects.
+///
+/// Every call is answered on its own task and `Service::call` runs on the blocking threads of
+/// the runtime, so `serve` must be called on a Tokio runtime.
+pub(crate) async fn serve<S: Service>(
+    service: Arc<S>,
+    io: impl AsyncRead + AsyncWrite + Unpin,
+) -> io::Result<()> {
+    let mut connection = h2::server::handshake(io).await.map_err(io_error)?;
+    while let Some(result) = connection.accept().await {
+        let (request, respond) = result.map_err(io_error)?;
+        let service = Arc::clone(&service);
+        tokio::spawn(async move {
+            if let Err(e) = self::respond(service, request, respond).await {
+                tracing::debug!(error = %e, service = S::NAME, "grpc call failed");
+            }
+        });
+    }
+    Ok(())
+}
+
+async fn respond<S: Service>(
+    service: Arc<S>,
+    request: Request<RecvStream>,
+    mut respond: SendResponse<Bytes>,
+) -> Result<(), h2::Error> {
+    let (parts, mut body) = request.into_parts();
+    let grpc = parts
+        .headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/grpc"));
+    if parts.method != Method::POST || !grpc {
+        let response = Response::builder()
+            .status(StatusCode::UNSUPPORTED_MEDIA_TYPE)
+            .body(())
+            .expect("the response is valid");
+        respond.send_response(response, true)?;
+        return Ok(());
+    }
+
+    let result = match service.authorize(&parts.headers) {
+        Err(status) => Err(status),
+        Ok(()) => match (
+            parts.uri.path().strip_prefix(S::PATH),
+            read_message(&mut body, S::MAX_MESSAGE_LEN).await,
+        ) {
+            (_, Err(status)) => Err(status),
+            (None, Ok(_)) => Err(GrpcStatus::new(
+                GRPC_UNIMPLEMENTED,
+                format!("Unknown service of {}", parts.uri.path()),
+            )),
+            (Some(method), Ok(message)) => {
+                let method = method.to_owned();
+                tokio::task::spawn_blocking(move || service.call(&method, &message))
+                    .await
+                    .unwrap_or_else(|_| Err(GrpcStatus::new(GRPC_INTERNAL, "The call panicked")))
+            }
+        },
+    };
+
+    let response = Response::builder()
+        .header(CONTENT_TYPE, "application/grpc")
+        .body(())
+        .expect("the response is valid");
+    match result {
+        Ok(message) => {
+            let mut stream = respond.send_response(response, false)?;
+            stream.send_data(frame(&message).into(), false)?;
+            stream.send_trailers(GrpcStatus::ok().trailers())?;
+        }
+        Err(status) => {
+            // A response without a message carries the status in its headers.
+            let (mut parts, ()) = response.into_parts();
+            parts.headers.extend(status.trailers());
+            respond.send_response(Response::from_parts(parts, ()), true)?;
+        }
+    }
+    Ok(())
+}
+
+/// Calls the unary method `path` and returns the response message.
+///
+/// # Arguments
+///
+/// * `client` - The HTTP/2 connection.
+/// * `path` - The path of the method, e.g. `/v2.KeyManagementService/Status`.
+/// * `headers` - Additional request headers, e.g. `authorization`.
+/// * `message` - The encoded request.
+/// * `max_len` - The longest response message to accept in bytes.
+///
+/// # Returns
+///
+/// A `Result` containing the encoded response, or the `GrpcStatus` of the failed call. Failures
+/// of the connection have the status `GRPC_UNAVAILABLE`.
+#[cfg(feature = "remote")]
+pub(crate) async fn call(
+    client: &SendRequest<Bytes>,
+    path: &str,
+    headers: &HeaderMap,
+    message: &[u8],
+    max_len: usize,
+) -> Result<Vec<u8>, GrpcStatus> {
+    let unavailable = |e: h2::Error| GrpcStatus::new(GRPC_UNAVAILABLE, e.to_string());
+    let mut request = Request::post(format!("http://localhost{}", path))
+        .header(CONTENT_TYPE, "application/grpc")
+        .header("te", "trailers")
+        .body(())
+        .expect("the request is valid");
+    request.headers_mut().extend(headers.clone());
+    let (response, mut stream) = client
+        .clone()
+        .ready()
+        .await
+        .map_err(unavailable)?
+        .send_request(request, false)
+        .map_err(unavailable)?;
+    stream
+        .send_data(frame(message).into(), true)
+        .map_err(unavailable)?;
+
+    let response = response.await.map_err(unavailable)?;
+    if let Some(status) = GrpcStatus::from_trailers(response.headers()) {
+        return Err(status);
+    }
+    let mut body = response.into_body();
+    let message = read_message(&mut body, max_len).await?;
+    let trailers = body.trailers().await.map_err(unavailable)?;
+    match trailers.as_ref().and_then(GrpcStatus::from_trailers) {
+        Some(status) if status.code == GRPC_OK => Ok(message),
+        Some(status) => Err(status),
+        None => Err(GrpcStatus::new(
+            GRPC_INTERNAL,
+            "The response has no grpc-status",
+        )),
+    }
+}
+
+/// Reads the single message of a unary call.
+pub(crate) async fn read_message(
+    body: &mut RecvStream,
+    max_len: usize,
+) -> Result<Vec<u8>, GrpcStatus> {
+    let invalid = |message: &str| GrpcStatus::new(GRPC_INVALID_ARGUMENT, message);
+    let mut framed = Vec::new();
+    while let Some(data) = body.data().await {
+        let data = data.map_err(|e| GrpcStatus::new(GRPC_INTERNAL, e.to_string()))?;
+        let _ = body.flow_control().release_capacity(data.len());
+        if framed.len() + data.len() > 5 + max_len {
+            return Err(invalid("The message is too long"));
+        }
+        framed.extend_from_slice(&data);
+    }
+
+    let Some((&compressed, rest)) = framed.split_first() else {
+        return Err(invalid("The call has no message"));
+    };
+    if compressed != 0 {
+        return Err(GrpcStatus::new(
+            GRPC_UNIMPLEMENTED,
+            "Compressed messages are not supported",
+        ));
+    }
+    match rest.split_first_chunk::<4>() {
+        Some((len, message)) if u32::from_be_bytes(*len) as usize == message.len() => {
+            Ok(message.to_vec())
+        }
+        _ => Err(invalid("The call does not have a single message")),
+    }
+}
+
+/// Prefixes an uncompressed message with its length.
+fn frame(message: &[u8]) -> Vec<u8> {
+    let mut framed = Vec::with_capacity(5 + message.len());
+    framed.push(0);
+    framed.extend_from_slice(&(message.len() as u32).to_be_bytes());
+    framed.extend_from_slice(message);
+    framed
+}
+
+pub(crate) fn io_error(error: h2::Error) -> io::Error {
+    if error.is_io() {
+        error.into_io().expect("the error is an I/O error")
+    } else {
+        io::Error::new(io::ErrorKind::InvalidData, error)
+    }
+}
+
+/// Encodes `message` as `grpc-message` header.
+fn percent_encode(message: &str) -> String {
+    message
+        .bytes()
+        .map(|byte| match byte {
+            b' '..=b'~' if byte != b'%' => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// Decodes a `grpc-message` header, keeping invalid escapes as they are.
+#[cfg(feature = "remote")]
+fn percent_decode(message: &str) -> String {
+    let bytes = message.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .filter(|_| bytes[i] == b'%')
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// The value of a protocol buffer field.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Value<'a> {
+    /// A varint, e.g. an integer, a `bool` or an enum.
+    Varint(#[cfg_attr(not(feature = "remote"), allow(dead_code))] u64),
+    /// A length-delimited value, e.g. `bytes`, a `string` or an embedded message.
+    Bytes(&'a [u8]),
+}
+
+fn put_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+/// Writes a length-delimited field, unless it is empty and thus has the default value.
+pub(crate) fn put_field(bytes: &mut Vec<u8>, field: u64, value: &[u8]) {
+    if !value.is_empty() {
+        put_entry(bytes, field, value);
+    }
+}
+
+/// Writes a length-delimited field, even if it is empty, e.g. an entry of a repeated field.
+pub(crate) fn put_entry(bytes: &mut Vec<u8>, field: u64, value: &[u8]) {
+    put_varint(bytes, field << 3 | 2);
+    put_varint(bytes, value.len() as u64);
+    bytes.extend_from_slice(value);
+}
+
+/// Writes a varint field, unless it is zero and thus has the default value.
+#[cfg(feature = "remote")]
+pub(crate) fn put_uint(bytes: &mut Vec<u8>, field: u64, value: u64) {
+    if value != 0 {
+        put_varint(bytes, field << 3);
+        put_varint(bytes, value);
+    }
+}
+
+fn get_varint(bytes: &mut &[u8]) -> Result<u64, SecurityModuleError> {
+    let mut value = 0;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = bytes
+            .split_first()
+            .ok_or(SecurityModuleError::Encoding(CoreError::Truncated))?;
+        *bytes = rest;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(SecurityModuleError::Encoding(CoreError::InvalidField(
+        "varint",
+    )))
+}
+
+fn get_slice<'a>(bytes: &mut &'a [u8], len: u64) -> Result<&'a [u8], SecurityModuleError> {
+    let len = usize::try_from(len)
+        .ok()
+        .filter(|&len| len <= bytes.len())
+        .ok_or(SecurityModuleError::Encoding(CoreError::Truncated))?;
+    let (value, rest) = bytes.split_at(len);
+    *bytes = rest;
+    Ok(value)
+}
+
+/// Returns the varint and length-delimited fields of a message, skipping the fixed-size fields,
+/// which none of the messages of the services has.
+pub(crate) fn fields(mut bytes: &[u8]) -> Result<Vec<(u64, Value<'_>)>, SecurityModuleError> {
+    let mut fields = Vec::new();
+    while !bytes.is_empty() {
+        let key = get_varint(&mut bytes)?;
+        match key & 7 {
+            0 => fields.push((key >> 3, Value::Varint(get_varint(&mut bytes)?))),
+            1 => {
+                get_slice(&mut bytes, 8)?;
+            }
+            2 => {
+                let len = get_varint(&mut bytes)?;
+                fields.push((key >> 3, Value::Bytes(get_slice(&mut bytes, len)?)));
+            }
+            5 => {
+                get_slice(&mut bytes, 4)?;
+            }
+            _ => {
+                return Err(SecurityModuleError::Encoding(CoreError::InvalidField(
+                    "wire type",
+                )))
+            }
+        }
+    }
+    Ok(fields)
+}
+
+/// Reads a `bytes` field or an embedded message.
+pub(crate) fn bytes(value: Value<'_>) -> Result<&[u8], SecurityModuleError> {
+    match value {
+        Value::Bytes(bytes) => Ok(bytes),
+        Value::Varint(_) => Err(SecurityModuleError::Encoding(CoreError::InvalidField(
+            "wire type",
+        ))),
+    }
+}
+
+pub(crate) fn string(value: Value<'_>) -> Result<String, SecurityModuleError> {
+    String::from_utf8(bytes(value)?.to_vec())
+        .map_err(|_| SecurityModuleError::Encoding(CoreError::InvalidField("string")))
+}
+
+#[cfg(feature = "remote")]
+pub(crate) fn uint(value: Value<'_>) -> Result<u64, SecurityModuleError> {
+    match value {
+        Value::Varint(value) => Ok(value),
+        Value::Bytes(_) => Err(SecurityModuleError::Encoding(CoreError::InvalidField(
+            "wire type",
+        ))),
+    }
+}
@@ -0,0 +1,175 @@
+//! Size limits and structural validation of inputs.
+//!
+//! Data passed to a security module is copied into JNI arrays, CNG buffers, Swift values and TPM
+//! structures, and parsers allocate for the fields of envelopes. `InputLimits` bounds these
+//! inputs before any of this happens, so that a broken or malicious caller cannot exhaust the
+//! memory of the process, and rejects them with a `SecurityModuleError::InputTooLarge`:
+//!
+//! ```toml
+//! [limits]
+//! max_data_len = 16777216
+//! max_field_len = 16384
+//! max_batch_len = 4096
+//! ```
+//!
+//! `SecModules::configure` sets the limits of the process. The `ValidatedProvider` of every
+//! instance created by `SecModules` checks the inputs of its operations, and the C API checks
+//! its arguments before it reads them. `validate_envelope` additionally checks the structure of
+//! an envelope, i.e. its nonce and tag lengths and the bounds of its header fields, before it is
+//! decrypted, reporting malformed envelopes with a `SecurityModuleError::Encoding`.
+
+use crate::common::{crypto::envelope::EnvelopeRef, error::SecurityModuleError};
+use serde::{Deserialize, Serialize};
+use std::sync::RwLock;
+
+/// The default maximum length of data, 16 MiB.
+pub const DEFAULT_MAX_DATA_LEN: usize = 16 * 1024 * 1024;
+
+/// The default maximum length of signatures, public keys, wrapped keys and envelope header
+/// fields, 16 KiB.
+pub const DEFAULT_MAX_FIELD_LEN: usize = 16 * 1024;
+
+/// The default maximum number of items of a batch.
+pub const DEFAULT_MAX_BATCH_LEN: usize = 4096;
+
+static LIMITS: RwLock<InputLimits> = RwLock::new(InputLimits::DEFAULT);
+
+/// The maximum sizes of inputs, see the module documentation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct InputLimits {
+    /// The maximum length of data to be signed, verified, encrypted or decrypted in bytes,
+    /// including envelopes.
+    pub max_data_len: usize,
+    /// The maximum length of signatures, public keys, wrapped keys and the header fields of
+    /// envelopes in bytes.
+    pub max_field_len: usize,
+    /// The maximum number of items verified by one call of `verify_many`.
+    pub max_batch_len: usize,
+}
+
+impl Default for InputLimits {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+impl InputLimits {
+    /// The default limits.
+    pub const DEFAULT: InputLimits = InputLimits {
+        max_data_len: DEFAULT_MAX_DATA_LEN,
+        max_field_len: DEFAULT_MAX_FIELD_LEN,
+        max_batch_len: DEFAULT_MAX_BATCH_LEN,
+    };
+
+    /// Returns the limits of the process.
+    pub fn current() -> Self {
+        *LIMITS
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Sets the limits of the process.
+    pub fn set(limits: InputLimits) {
+        *LIMITS
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = limits;
+    }
+
+    /// Checks that no limit is 0, which would reject every input.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` that is `Ok(())` on success, or a `SecurityModuleError::InitializationError`
+    /// naming the limit.
+    pub fn validate(&self) -> Result<(), SecurityModuleError> {
+        for (name, limit) in [
+            ("max_data_len", self.max_data_len),
+            ("max_field_len", self.max_field_len),
+            ("max_batch_len", self.max_batch_len),
+        ] {
+            if limit == 0 {
+                return Err(SecurityModuleError::InitializationError(format!(
+                    "The input limit {} must not be 0",
+                    name
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the length of data, e.g. a payload or an envelope.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the input in the error message.
+    /// * `len` - The length of the input in bytes.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` that is `Ok(())` on success, or a `SecurityModuleError::InputTooLarge` if
+    /// `len` exceeds `max_data_len`.
+    pub fn check_data(&self, name: &str, len: usize) -> Result<(), SecurityModuleError> {
+        check(name, len, self.max_data_len, "bytes")
+    }
+
+    /// Checks the length of a field, e.g. a signature or a public key.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` that is `Ok(())` on success, or a `SecurityModuleError::InputTooLarge` if
+    /// `len` exceeds `max_field_len`.
+    pub fn check_field(&self, name: &str, len: usize) -> Result<(), SecurityModuleError> {
+        check(name, len, self.max_field_len, "bytes")
+    }
+
+    /// Checks the number of items of a batch.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` that is `Ok(())` on success, or a `SecurityModuleError::InputTooLarge` if
+    /// `len` exceeds `max_batch_len`.
+    pub fn check_batch(&self, name: &str, len: usize) -> Result<(), SecurityModuleError> {
+        check(name, len, self.max_batch_len, "items")
+    }
+}
+
+fn check(name: &str, len: usize, max: usize, unit: &str) -> Result<(), SecurityModuleError> {
+    if len > max {
+        return Err(SecurityModuleError::InputTooLarge(format!(
+            "The {} has {} {}, at most {} are allowed",
+            name, len, unit, max
+        )));
+    }
+    Ok(())
+}
+
+/// Checks the size and structure of an envelope with the limits of the process and parses it.
+///
+/// Parsing rejects nonces of the wrong length for the algorithm and ciphertexts shorter than its
+/// tag. The key id, wrapped key, ephemeral public key and salt must not exceed `max_field_len`.
+///
+/// # Returns
+///
+/// A `Result` containing the parsed envelope on success, a `SecurityModuleError::InputTooLarge`
+/// if the envelope or one of its fields is too long, or a `SecurityModuleError::Encoding` if it
+/// is malformed.
+pub fn validate_envelope(bytes: &[u8]) -> Result<EnvelopeRef<'_>, SecurityModuleError> {
+    let limits = InputLimits::current();
+    limits.check_data("envelope", bytes.len())?;
+    let envelope = EnvelopeRef::parse(bytes)?;
+    for (name, field) in [
+        ("key id of the envelope", Some(envelope.key_id.as_bytes())),
+        ("wrapped key of the envelope", envelope.wrapped_key),
+        (
+            "ephemeral public key of the envelope",
+            envelope.ephemeral_public_key,
+        ),
+        ("salt of the envelope", envelope.salt),
+    ] {
+        if let Some(field) = field {
+            limits.check_field(name, field.len())?;
+        }
+    }
+    Ok(envelope)
+}
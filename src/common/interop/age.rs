@@ -0,0 +1,362 @@
+//! Files of the age encryption tool (age-encryption.org/v1).
+//!
+//! An age file starts with a text header that lists one stanza per recipient, each wrapping the 16
+//! byte file key for the key of the recipient, and ends with an HMAC-SHA-256 of the header under a
+//! key derived from the file key. The payload follows as a 16 byte nonce and the STREAM
+//! encryption of the plaintext in ChaCha20-Poly1305 chunks of 64 KiB. Files in the ASCII armor of
+//! `age --armor` are also accepted.
+//!
+//! The file key is unwrapped with the first stanza of the recipient of `decrypt`:
+//!
+//! - `X25519` - The native stanza of `age1...` recipients. The wrapping key is derived with
+//!   HKDF-SHA-256 from the X25519 shared secret of the ephemeral key of the stanza, with the
+//!   ephemeral and the recipient public key as salt and the info `age-encryption.org/v1/X25519`.
+//! - `piv-p256` - The stanza of `age-plugin-yubikey` and other PIV plugins for P-256 keys, e.g.
+//!   of a Secure Enclave or TPM. It carries the first 4 bytes of the SHA-256 hash of the
+//!   compressed recipient point as tag, and derives the wrapping key like `X25519` from the ECDH
+//!   shared secret with the compressed points as salt and the info `piv-p256`.
+//!
+//! Stanzas of other recipients, e.g. `scrypt` or `ssh-ed25519`, are skipped.
+
+use super::{check_x25519_shared_secret, decryption_error};
+use crate::common::{
+    constant_time,
+    crypto::{kdf::Kdf, public_key::PublicKey, secret::SecretBytes},
+    error::SecurityModuleError,
+    fips,
+    traits::key_handle::KeyHandle,
+};
+use base64::{
+    prelude::{BASE64_STANDARD, BASE64_STANDARD_NO_PAD},
+    Engine,
+};
+use crypto_layer_core::CoreError;
+use openssl::{
+    bn::BigNumContext,
+    ec::{EcGroup, EcPoint, PointConversionForm},
+    hash::MessageDigest,
+    nid::Nid,
+    pkey::PKey,
+    sha::sha256,
+    sign::Signer,
+    symm::{decrypt_aead, Cipher},
+};
+
+const VERSION_LINE: &str = "age-encryption.org/v1";
+const ARMOR_BEGIN: &str = "-----BEGIN AGE ENCRYPTED FILE-----";
+const ARMOR_END: &str = "-----END AGE ENCRYPTED FILE-----";
+
+const X25519_INFO: &[u8] = b"age-encryption.org/v1/X25519";
+const PIV_P256_INFO: &[u8] = b"piv-p256";
+
+/// The length of the file key in bytes.
+const FILE_KEY_LEN: usize = 16;
+
+/// The length of a plaintext chunk of the payload in bytes.
+const CHUNK_LEN: usize = 64 * 1024;
+
+const TAG_LEN: usize = 16;
+const PAYLOAD_NONCE_LEN: usize = 16;
+
+/// The key of the recipient a file is decrypted for, held by the security module.
+#[derive(Debug, Clone)]
+pub enum Recipient {
+    /// An X25519 key with its raw public key, whose shared secret `derive_shared_secret` derives
+    /// from the raw ephemeral public key.
+    X25519([u8; 32]),
+    /// A P-256 key, whose shared secret `derive_shared_secret` derives from the uncompressed
+    /// ephemeral point.
+    PivP256(PublicKey),
+}
+
+/// One recipient stanza of the header.
+struct Stanza<'a> {
+    kind: &'a str,
+    args: Vec<&'a str>,
+    body: Vec<u8>,
+}
+
+/// The parsed header of a file and the payload that follows it.
+struct Header<'a> {
+    stanzas: Vec<Stanza<'a>>,
+    /// The header up to and including `---`, which the MAC covers.
+    authenticated: &'a [u8],
+    mac: Vec<u8>,
+    payload: &'a [u8],
+}
+
+/// Decrypts an age file for `recipient`.
+///
+/// # Arguments
+///
+/// * `key_handle` - Derives the shared secret of the stanza with `derive_shared_secret`, e.g. a
+///   provider with the loaded key of `recipient`.
+/// * `recipient` - The key of `key_handle`.
+/// * `file` - The age file, binary or armored.
+///
+/// # Returns
+///
+/// A `Result` containing the plaintext, which is zeroed when dropped, a
+/// `SecurityModuleError::Encoding` if the file is malformed, a
+/// `SecurityModuleError::DecryptionError` if it has no stanza for `recipient` or was modified,
+/// the error of `key_handle` if the shared secret cannot be derived, or a
+/// `SecurityModuleError::NotFipsApproved` in FIPS mode.
+#[tracing::instrument(skip_all, fields(crypto.payload.size = file.len()))]
+pub fn decrypt(
+    key_handle: &(impl KeyHandle + ?Sized),
+    recipient: &Recipient,
+    file: &[u8],
+) -> Result<SecretBytes, SecurityModuleError> {
+    if fips::is_enabled() {
+        fips::check_name("chacha20_poly1305")?;
+    }
+    let dearmored;
+    let file = if file.trim_ascii_start().starts_with(ARMOR_BEGIN.as_bytes()) {
+        dearmored = dearmor(file)?;
+        &dearmored[..]
+    } else {
+        file
+    };
+    let header = parse_header(file)?;
+
+    let mut file_key = None;
+    for stanza in &header.stanzas {
+        file_key = unwrap_file_key(key_handle, recipient, stanza)?;
+        if file_key.is_some() {
+            break;
+        }
+    }
+    let file_key = file_key
+        .ok_or_else(|| decryption_error("The file has no stanza for the key of the recipient"))?;
+
+    verify_header(&file_key, &header)?;
+    let (nonce, payload) = header
+        .payload
+        .split_at_checked(PAYLOAD_NONCE_LEN)
+        .ok_or(CoreError::Truncated)?;
+    let payload_key = Kdf::HkdfSha256.derive_vec(&file_key, Some(nonce), b"payload", 32)?;
+    decrypt_payload(&payload_key, payload)
+}
+
+/// Splits a binary file into its header and payload.
+fn parse_header(file: &[u8]) -> Result<Header<'_>, CoreError> {
+    let mut lines = Lines { file, pos: 0 };
+    if lines.next()? != VERSION_LINE {
+        return Err(CoreError::InvalidMagic);
+    }
+    let mut stanzas = Vec::new();
+    loop {
+        let start = lines.pos;
+        let line = lines.next()?;
+        if let Some(mac) = line.strip_prefix("--- ") {
+            return Ok(Header {
+                stanzas,
+                authenticated: &file[..start + 3],
+                mac: decode(mac)?,
+                payload: &file[lines.pos..],
+            });
+        }
+        let mut args = line
+            .strip_prefix("-> ")
+            .ok_or(CoreError::InvalidField("stanza"))?
+            .split(' ');
+        let kind = args.next().filter(|kind| !kind.is_empty());
+        let kind = kind.ok_or(CoreError::InvalidField("stanza"))?;
+        let args: Vec<&str> = args.collect();
+        if args.iter().any(|arg| arg.is_empty()) {
+            return Err(CoreError::InvalidField("stanza"));
+        }
+
+        // The body is wrapped at 64 columns and ends with a shorter line, which may be empty.
+        let mut body = String::new();
+        loop {
+            let line = lines.next()?;
+            if line.len() > 64 {
+                return Err(CoreError::InvalidField("stanza body"));
+            }
+            body.push_str(line);
+            if line.len() < 64 {
+                break;
+            }
+        }
+        stanzas.push(Stanza {
+            kind,
+            args,
+            body: decode(&body)?,
+        });
+    }
+}
+
+/// Iterates over the lines of the header, which end with `\n`.
+struct Lines<'a> {
+    file: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Lines<'a> {
+    fn next(&mut self) -> Result<&'a str, CoreError> {
+        let rest = &self.file[self.pos..];
+        let len = rest
+            .iter()
+            .position(|&byte| byte == b'\n')
+            .ok_or(CoreError::Truncated)?;
+        self.pos += len + 1;
+        let line =
+            std::str::from_utf8(&rest[..len]).map_err(|_| CoreError::InvalidField("header"))?;
+        if !line.bytes().all(|byte| (0x20..0x7f).contains(&byte)) {
+            return Err(CoreError::InvalidField("header"));
+        }
+        Ok(line)
+    }
+}
+
+/// Decodes canonical unpadded base64 of the header.
+fn decode(data: &str) -> Result<Vec<u8>, CoreError> {
+    BASE64_STANDARD_NO_PAD
+        .decode(data)
+        .map_err(|_| CoreError::InvalidField("base64"))
+}
+
+/// Tries to unwrap the file key with `stanza`, returning `None` for stanzas of other recipients.
+fn unwrap_file_key(
+    key_handle: &(impl KeyHandle + ?Sized),
+    recipient: &Recipient,
+    stanza: &Stanza<'_>,
+) -> Result<Option<SecretBytes>, SecurityModuleError> {
+    let (salt, shared_secret, info) = match (recipient, stanza.kind) {
+        (Recipient::X25519(public_key), "X25519") => {
+            let [ephemeral_public_key] = stanza.args[..] else {
+                return Err(CoreError::InvalidField("X25519 stanza").into());
+            };
+            let ephemeral_public_key = decode(ephemeral_public_key)?;
+            if ephemeral_public_key.len() != 32 {
+                return Err(CoreError::InvalidField("X25519 stanza").into());
+            }
+            let shared_secret = key_handle.derive_shared_secret(&ephemeral_public_key)?;
+            check_x25519_shared_secret(&shared_secret)?;
+            let salt = [&ephemeral_public_key[..], public_key].concat();
+            (salt, shared_secret, X25519_INFO)
+        }
+        (Recipient::PivP256(public_key), "piv-p256") => {
+            let [tag, ephemeral_public_key] = stanza.args[..] else {
+                return Err(CoreError::InvalidField("piv-p256 stanza").into());
+            };
+            let recipient_point = compressed_point(&public_key.to_ec_point()?)?;
+            if decode(tag)? != sha256(&recipient_point)[..4] {
+                return Ok(None);
+            }
+            let ephemeral_public_key = decode(ephemeral_public_key)?;
+            if ephemeral_public_key.len() != 33 {
+                return Err(CoreError::InvalidField("piv-p256 stanza").into());
+            }
+            let shared_secret =
+                key_handle.derive_shared_secret(&uncompressed_point(&ephemeral_public_key)?)?;
+            let salt = [ephemeral_public_key, recipient_point].concat();
+            (salt, shared_secret, PIV_P256_INFO)
+        }
+        _ => return Ok(None),
+    };
+
+    if stanza.body.len() != FILE_KEY_LEN + TAG_LEN {
+        return Err(CoreError::InvalidField("stanza body").into());
+    }
+    let wrap_key = Kdf::HkdfSha256.derive_vec(&shared_secret, Some(&salt), info, 32)?;
+    let (wrapped_key, tag) = stanza.body.split_at(FILE_KEY_LEN);
+    let file_key = decrypt_aead(
+        Cipher::chacha20_poly1305(),
+        &wrap_key,
+        Some(&[0; 12]),
+        &[],
+        wrapped_key,
+        tag,
+    );
+    // A stanza of the same type for another key fails to authenticate.
+    Ok(file_key.ok().map(SecretBytes::new))
+}
+
+fn verify_header(file_key: &[u8], header: &Header<'_>) -> Result<(), SecurityModuleError> {
+    let mac_key = Kdf::HkdfSha256.derive_vec(file_key, None, b"header", 32)?;
+    let expected = PKey::hmac(&mac_key)
+        .and_then(|key| {
+            let mut signer = Signer::new(MessageDigest::sha256(), &key)?;
+            signer.update(header.authenticated)?;
+            signer.sign_to_vec()
+        })
+        .map_err(|e| SecurityModuleError::DecryptionError(e.to_string()))?;
+    if !constant_time::eq(&header.mac, &expected) {
+        return Err(decryption_error("The header of the file was modified"));
+    }
+    Ok(())
+}
+
+/// Decrypts the STREAM chunks of the payload, whose nonce is the big-endian 11 byte index of the
+/// chunk followed by 1 for the last chunk and 0 for all others.
+fn decrypt_payload(payload_key: &[u8], payload: &[u8]) -> Result<SecretBytes, SecurityModuleError> {
+    let modified = || decryption_error("The payload of the file was modified or truncated");
+    let mut plaintext = SecretBytes::with_capacity(payload.len());
+    let mut chunks = payload.chunks(CHUNK_LEN + TAG_LEN).enumerate().peekable();
+    if payload.is_empty() {
+        return Err(modified());
+    }
+    while let Some((index, chunk)) = chunks.next() {
+        let last = chunks.peek().is_none();
+        if chunk.len() < TAG_LEN || (chunk.len() == TAG_LEN && index > 0) {
+            return Err(modified());
+        }
+        let mut nonce = [0; 12];
+        nonce[3..11].copy_from_slice(&(index as u64).to_be_bytes());
+        nonce[11] = last as u8;
+        let (ciphertext, tag) = chunk.split_at(chunk.len() - TAG_LEN);
+        let chunk = decrypt_aead(
+            Cipher::chacha20_poly1305(),
+            payload_key,
+            Some(&nonce),
+            &[],
+            ciphertext,
+            tag,
+        )
+        .map(SecretBytes::new)
+        .map_err(|_| modified())?;
+        plaintext.extend_from_slice(&chunk);
+    }
+    Ok(plaintext)
+}
+
+/// Decodes the ASCII armor of `age --armor`, padded base64 in lines of 64 columns.
+fn dearmor(file: &[u8]) -> Result<Vec<u8>, SecurityModuleError> {
+    let text = std::str::from_utf8(file).map_err(|_| CoreError::InvalidField("armor"))?;
+    let mut lines = text.trim().lines().map(|line| line.trim_end_matches('\r'));
+    if lines.next() != Some(ARMOR_BEGIN) {
+        return Err(CoreError::InvalidField("armor").into());
+    }
+    let mut base64 = String::new();
+    for line in lines.by_ref() {
+        if line == ARMOR_END {
+            return BASE64_STANDARD
+                .decode(&base64)
+                .map_err(|_| CoreError::InvalidField("armor").into());
+        }
+        if line.len() > 64 {
+            return Err(CoreError::InvalidField("armor").into());
+        }
+        base64.push_str(line);
+    }
+    Err(CoreError::Truncated.into())
+}
+
+fn compressed_point(point: &[u8]) -> Result<Vec<u8>, SecurityModuleError> {
+    convert_point(point, PointConversionForm::COMPRESSED)
+}
+
+fn uncompressed_point(point: &[u8]) -> Result<Vec<u8>, SecurityModuleError> {
+    convert_point(point, PointConversionForm::UNCOMPRESSED)
+}
+
+fn convert_point(point: &[u8], form: PointConversionForm) -> Result<Vec<u8>, SecurityModuleError> {
+    EcGroup::from_curve_name(Nid::X9_62_PRIME256V1)
+        .and_then(|group| {
+            let mut ctx = BigNumContext::new()?;
+            EcPoint::from_bytes(&group, point, &mut ctx)?.to_bytes(&group, form, &mut ctx)
+        })
+        .map_err(|_| SecurityModuleError::InvalidPublicKey)
+}
@@ -0,0 +1,366 @@
+//! JSON Web Encryption (RFC 7516) in the compact and the JSON serialization.
+//!
+//! The content encryption key of a JWE is encrypted for the recipient with the `alg` of its
+//! header, and the content is encrypted under it with the `enc` of the header. Supported are the
+//! key management algorithms (RFC 7518) for keys of the security module:
+//!
+//! - `RSA-OAEP` - The key is decrypted with `decrypt_data`, which must decrypt RSA-OAEP with
+//!   SHA-1 as the mock provider and most modules do.
+//! - `ECDH-ES` - The key is derived with the Concat KDF from the ECDH shared secret of `epk`,
+//!   computed with `derive_shared_secret`, and the `apu` and `apv` of the header.
+//! - `ECDH-ES+A128KW`, `ECDH-ES+A192KW` and `ECDH-ES+A256KW` - The key derived as for `ECDH-ES`
+//!   unwraps the encrypted key with AES Key Wrap (RFC 3394).
+//!
+//! and the content encryption algorithms `A128GCM`, `A192GCM`, `A256GCM`, `A128CBC-HS256`,
+//! `A192CBC-HS384` and `A256CBC-HS512`. Compressed (`zip`) JWEs and JWEs with critical header
+//! parameters (`crit`) are rejected.
+//!
+//! A JWE in the JSON serialization may have several recipients, of which the first that can be
+//! decrypted with the key is used.
+
+use super::decryption_error;
+use crate::common::{
+    constant_time,
+    crypto::{jwk, secret::SecretBytes},
+    error::SecurityModuleError,
+    traits::key_handle::KeyHandle,
+};
+use base64::{prelude::BASE64_URL_SAFE_NO_PAD, Engine};
+use crypto_layer_core::CoreError;
+use openssl::{
+    aes::{unwrap_key, AesKey},
+    hash::MessageDigest,
+    pkey::PKey,
+    sha::sha256,
+    sign::Signer,
+    symm::{self, decrypt_aead, Cipher},
+};
+use serde_json::{Map, Value};
+
+/// The decrypted content of a JWE.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JweContent {
+    /// The union of the protected, the shared unprotected and the per-recipient header, e.g. with
+    /// the `kid`, `cty` or `typ` of the content.
+    pub header: Map<String, Value>,
+    /// The decrypted content, which is zeroed when dropped.
+    pub plaintext: SecretBytes,
+}
+
+/// The parts of a JWE that are shared by all recipients.
+struct Parts<'a> {
+    protected: &'a str,
+    unprotected: Option<&'a Map<String, Value>>,
+    aad: Option<&'a str>,
+    iv: Vec<u8>,
+    ciphertext: Vec<u8>,
+    tag: Vec<u8>,
+}
+
+/// Decrypts a JWE with the key of `key_handle`.
+///
+/// # Arguments
+///
+/// * `key_handle` - Decrypts or derives the content encryption key, e.g. a provider with the
+///   loaded key of the recipient.
+/// * `jwe` - The JWE in the compact serialization or in the general or flattened JSON
+///   serialization.
+///
+/// # Returns
+///
+/// A `Result` containing the header and the content, a `SecurityModuleError::Encoding` if the
+/// JWE is malformed, a `SecurityModuleError::UnsupportedAlgorithm` if it uses an algorithm, `zip`
+/// or `crit`, a `SecurityModuleError::DecryptionError` if it was encrypted for another key or
+/// modified, or the error of `key_handle`.
+#[tracing::instrument(skip_all, fields(crypto.payload.size = jwe.len()))]
+pub fn decrypt(
+    key_handle: &(impl KeyHandle + ?Sized),
+    jwe: &str,
+) -> Result<JweContent, SecurityModuleError> {
+    let jwe = jwe.trim();
+    if !jwe.starts_with('{') {
+        let [protected, encrypted_key, iv, ciphertext, tag] = jwe
+            .split('.')
+            .collect::<Vec<_>>()
+            .try_into()
+            .map_err(|_| CoreError::InvalidField("jwe"))?;
+        let parts = Parts {
+            protected,
+            unprotected: None,
+            aad: None,
+            iv: decode(iv, "iv")?,
+            ciphertext: decode(ciphertext, "ciphertext")?,
+            tag: decode(tag, "tag")?,
+        };
+        return decrypt_recipient(key_handle, &parts, None, encrypted_key);
+    }
+
+    let json: Value = serde_json::from_str(jwe).map_err(|_| CoreError::InvalidField("jwe"))?;
+    let string = |name: &'static str| -> Result<Option<&str>, CoreError> {
+        match json.get(name) {
+            None => Ok(None),
+            Some(value) => value
+                .as_str()
+                .map(Some)
+                .ok_or(CoreError::InvalidField(name)),
+        }
+    };
+    let required = |name: &'static str| -> Result<Vec<u8>, CoreError> {
+        decode(string(name)?.ok_or(CoreError::MissingField(name))?, name)
+    };
+    let parts = Parts {
+        protected: string("protected")?.unwrap_or(""),
+        unprotected: object(json.get("unprotected"), "unprotected")?,
+        aad: string("aad")?,
+        iv: required("iv")?,
+        ciphertext: required("ciphertext")?,
+        tag: required("tag")?,
+    };
+
+    let flattened = [json.clone()];
+    let recipients = match json.get("recipients") {
+        Some(Value::Array(recipients)) if !recipients.is_empty() => &recipients[..],
+        Some(_) => return Err(CoreError::InvalidField("recipients").into()),
+        None => &flattened[..],
+    };
+    let mut result = Err(CoreError::MissingField("recipients").into());
+    for recipient in recipients {
+        let header = object(recipient.get("header"), "header")?;
+        let encrypted_key = match recipient.get("encrypted_key") {
+            None => "",
+            Some(value) => value
+                .as_str()
+                .ok_or(CoreError::InvalidField("encrypted_key"))?,
+        };
+        result = decrypt_recipient(key_handle, &parts, header, encrypted_key);
+        if result.is_ok() {
+            break;
+        }
+    }
+    result
+}
+
+fn decrypt_recipient(
+    key_handle: &(impl KeyHandle + ?Sized),
+    parts: &Parts<'_>,
+    recipient_header: Option<&Map<String, Value>>,
+    encrypted_key: &str,
+) -> Result<JweContent, SecurityModuleError> {
+    let header = joint_header(parts, recipient_header)?;
+    if header.contains_key("zip") || header.contains_key("crit") {
+        return Err(SecurityModuleError::UnsupportedAlgorithm);
+    }
+    let parameter = |name: &'static str| -> Result<&str, CoreError> {
+        header
+            .get(name)
+            .ok_or(CoreError::MissingField(name))?
+            .as_str()
+            .ok_or(CoreError::InvalidField(name))
+    };
+    let enc = ContentEncryption::from_name(parameter("enc")?)?;
+    let alg = parameter("alg")?;
+    let encrypted_key = decode(encrypted_key, "encrypted_key")?;
+
+    let cek = match alg {
+        "RSA-OAEP" => key_handle.decrypt_data(&encrypted_key)?,
+        "ECDH-ES" => {
+            if !encrypted_key.is_empty() {
+                return Err(CoreError::InvalidField("encrypted_key").into());
+            }
+            ecdh_es(key_handle, &header, parameter("enc")?, enc.key_len())?
+        }
+        "ECDH-ES+A128KW" | "ECDH-ES+A192KW" | "ECDH-ES+A256KW" => {
+            let kek_len = match alg {
+                "ECDH-ES+A128KW" => 16,
+                "ECDH-ES+A192KW" => 24,
+                _ => 32,
+            };
+            let kek = ecdh_es(key_handle, &header, alg, kek_len)?;
+            aes_unwrap(&kek, &encrypted_key)?
+        }
+        _ => return Err(SecurityModuleError::UnsupportedAlgorithm),
+    };
+
+    let mut aad = parts.protected.as_bytes().to_vec();
+    if let Some(extra) = parts.aad {
+        aad.push(b'.');
+        aad.extend(extra.as_bytes());
+    }
+    Ok(JweContent {
+        header,
+        plaintext: enc.decrypt(&cek, parts, &aad)?,
+    })
+}
+
+/// Merges the headers of the JWE, which must not share parameters (RFC 7516, section 7.2.1).
+fn joint_header(
+    parts: &Parts<'_>,
+    recipient_header: Option<&Map<String, Value>>,
+) -> Result<Map<String, Value>, CoreError> {
+    let mut header = if parts.protected.is_empty() {
+        Map::new()
+    } else {
+        let protected = decode(parts.protected, "protected")?;
+        match serde_json::from_slice(&protected) {
+            Ok(Value::Object(header)) => header,
+            _ => return Err(CoreError::InvalidField("protected")),
+        }
+    };
+    for other in [parts.unprotected, recipient_header].into_iter().flatten() {
+        for (name, value) in other {
+            if header.insert(name.clone(), value.clone()).is_some() {
+                return Err(CoreError::InvalidField("header"));
+            }
+        }
+    }
+    Ok(header)
+}
+
+/// Derives a key of `len` bytes for `algorithm_id` from the `epk`, `apu` and `apv` of `header`.
+fn ecdh_es(
+    key_handle: &(impl KeyHandle + ?Sized),
+    header: &Map<String, Value>,
+    algorithm_id: &str,
+    len: usize,
+) -> Result<SecretBytes, SecurityModuleError> {
+    let epk = header.get("epk").ok_or(CoreError::MissingField("epk"))?;
+    let ephemeral_public_key = jwk::from_jwk(epk)?.to_ec_point()?;
+    let party_info = |name: &'static str| match header.get(name) {
+        None => Ok(Vec::new()),
+        Some(Value::String(value)) => decode(value, name),
+        Some(_) => Err(CoreError::InvalidField(name)),
+    };
+    let (apu, apv) = (party_info("apu")?, party_info("apv")?);
+    let shared_secret = key_handle.derive_shared_secret(&ephemeral_public_key)?;
+    Ok(concat_kdf(&shared_secret, algorithm_id, &apu, &apv, len))
+}
+
+/// The Concat KDF of NIST SP 800-56A with SHA-256 as specified for ECDH-ES (RFC 7518, section
+/// 4.6.2).
+pub(crate) fn concat_kdf(
+    shared_secret: &[u8],
+    algorithm_id: &str,
+    apu: &[u8],
+    apv: &[u8],
+    len: usize,
+) -> SecretBytes {
+    let mut other_info = Vec::new();
+    for field in [algorithm_id.as_bytes(), apu, apv] {
+        other_info.extend((field.len() as u32).to_be_bytes());
+        other_info.extend(field);
+    }
+    other_info.extend(((len * 8) as u32).to_be_bytes());
+
+    let mut key = SecretBytes::with_capacity(len.next_multiple_of(32));
+    for counter in 1..=len.div_ceil(32) as u32 {
+        key.extend_from_slice(&sha256(
+            &[&counter.to_be_bytes(), shared_secret, &other_info].concat(),
+        ));
+    }
+    key.truncate(len);
+    key
+}
+
+fn aes_unwrap(kek: &[u8], wrapped_key: &[u8]) -> Result<SecretBytes, SecurityModuleError> {
+    if wrapped_key.len() < 24 || !wrapped_key.len().is_multiple_of(8) {
+        return Err(CoreError::InvalidField("encrypted_key").into());
+    }
+    let kek = AesKey::new_decrypt(kek).map_err(|_| decryption_error("Invalid key wrapping key"))?;
+    let mut key = SecretBytes::zeroed(wrapped_key.len() - 8);
+    unwrap_key(&kek, None, &mut key, wrapped_key)
+        .map_err(|_| decryption_error("The content encryption key cannot be unwrapped"))?;
+    Ok(key)
+}
+
+/// The content encryption algorithms (RFC 7518, section 5.1).
+#[derive(Clone, Copy)]
+enum ContentEncryption {
+    Gcm(Cipher, usize),
+    CbcHmac(Cipher, MessageDigest, usize),
+}
+
+impl ContentEncryption {
+    fn from_name(enc: &str) -> Result<Self, SecurityModuleError> {
+        Ok(match enc {
+            "A128GCM" => Self::Gcm(Cipher::aes_128_gcm(), 16),
+            "A192GCM" => Self::Gcm(Cipher::aes_192_gcm(), 24),
+            "A256GCM" => Self::Gcm(Cipher::aes_256_gcm(), 32),
+            "A128CBC-HS256" => Self::CbcHmac(Cipher::aes_128_cbc(), MessageDigest::sha256(), 32),
+            "A192CBC-HS384" => Self::CbcHmac(Cipher::aes_192_cbc(), MessageDigest::sha384(), 48),
+            "A256CBC-HS512" => Self::CbcHmac(Cipher::aes_256_cbc(), MessageDigest::sha512(), 64),
+            _ => return Err(SecurityModuleError::UnsupportedAlgorithm),
+        })
+    }
+
+    fn key_len(self) -> usize {
+        match self {
+            Self::Gcm(_, key_len) | Self::CbcHmac(_, _, key_len) => key_len,
+        }
+    }
+
+    fn decrypt(
+        self,
+        cek: &[u8],
+        parts: &Parts<'_>,
+        aad: &[u8],
+    ) -> Result<SecretBytes, SecurityModuleError> {
+        let modified = || decryption_error("The JWE was modified or encrypted for another key");
+        if cek.len() != self.key_len() {
+            return Err(modified());
+        }
+        match self {
+            Self::Gcm(cipher, _) => {
+                if parts.iv.len() != 12 || parts.tag.len() != 16 {
+                    return Err(modified());
+                }
+                decrypt_aead(
+                    cipher,
+                    cek,
+                    Some(&parts.iv),
+                    aad,
+                    &parts.ciphertext,
+                    &parts.tag,
+                )
+                .map(SecretBytes::new)
+                .map_err(|_| modified())
+            }
+            Self::CbcHmac(cipher, digest, key_len) => {
+                // The first half of the key authenticates, the second half encrypts.
+                let (mac_key, enc_key) = cek.split_at(key_len / 2);
+                let al = ((aad.len() as u64) * 8).to_be_bytes();
+                let tag = PKey::hmac(mac_key)
+                    .and_then(|key| {
+                        let mut signer = Signer::new(digest, &key)?;
+                        for data in [aad, &parts.iv, &parts.ciphertext, &al] {
+                            signer.update(data)?;
+                        }
+                        signer.sign_to_vec()
+                    })
+                    .map_err(|_| modified())?;
+                if !constant_time::eq(&tag[..key_len / 2], &parts.tag) {
+                    return Err(modified());
+                }
+                symm::decrypt(cipher, enc_key, Some(&parts.iv), &parts.ciphertext)
+                    .map(SecretBytes::new)
+                    .map_err(|_| modified())
+            }
+        }
+    }
+}
+
+fn object<'a>(
+    value: Option<&'a Value>,
+    name: &'static str,
+) -> Result<Option<&'a Map<String, Value>>, CoreError> {
+    match value {
+        None => Ok(None),
+        Some(Value::Object(object)) => Ok(Some(object)),
+        Some(_) => Err(CoreError::InvalidField(name)),
+    }
+}
+
+fn decode(data: &str, name: &'static str) -> Result<Vec<u8>, CoreError> {
+    BASE64_URL_SAFE_NO_PAD
+        .decode(data)
+        .map_err(|_| CoreError::InvalidField(name))
+}
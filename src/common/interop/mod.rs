@@ -0,0 +1,42 @@
+//! Decryption of ciphertexts of other tools with keys of the security module.
+//!
+//! Other software rarely produces the envelopes of `ecies` or `file_encryption`, but often one of
+//! a few common formats. The submodules decrypt them when the private key of the recipient is in
+//! the security module, which only computes the key agreement or unwraps the content key:
+//!
+//! - `jwe` - JSON Web Encryption (RFC 7516) in the compact and the JSON serialization, as
+//!   produced by JOSE libraries and web backends, for RSA and EC keys.
+//! - `age` - Files of the age encryption tool (age-encryption.org/v1), for X25519 keys and, with
+//!   the `piv-p256` stanza of PIV plugins such as `age-plugin-yubikey`, P-256 keys.
+//! - `sealed_box` - Anonymous sealed boxes of libsodium (`crypto_box_seal`), for X25519 keys.
+//!
+//! ```rust,ignore
+//! use crypto_layer::common::interop::{age, jwe};
+//!
+//! let content = jwe::decrypt(&provider, &token)?;
+//! let recipient = age::Recipient::PivP256(provider.key_metadata()?.public_key().clone());
+//! let plaintext = age::decrypt(&provider, &recipient, &file)?;
+//! ```
+//!
+//! Every format authenticates the ciphertext, and all of them fail with a
+//! `SecurityModuleError::DecryptionError` if it was modified or encrypted for another key,
+//! malformed input fails with a `SecurityModuleError::Encoding`, and algorithms of the formats
+//! that are not supported with a `SecurityModuleError::UnsupportedAlgorithm`.
+
+pub mod age;
+pub mod jwe;
+pub mod sealed_box;
+
+use crate::common::error::SecurityModuleError;
+
+fn decryption_error(message: &str) -> SecurityModuleError {
+    SecurityModuleError::DecryptionError(message.to_owned())
+}
+
+/// Rejects the all-zero X25519 shared secret of low-order public keys, as age and libsodium do.
+fn check_x25519_shared_secret(shared_secret: &[u8]) -> Result<(), SecurityModuleError> {
+    if shared_secret.iter().fold(0, |acc, byte| acc | byte) == 0 {
+        return Err(SecurityModuleError::InvalidPublicKey);
+    }
+    Ok(())
+}
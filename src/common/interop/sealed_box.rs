@@ -0,0 +1,119 @@
+//! Anonymous sealed boxes of libsodium (`crypto_box_seal`).
+//!
+//! A sealed box is the 32 byte X25519 public key of an ephemeral key pair followed by the
+//! XSalsa20-Poly1305 `crypto_box` of the message from the ephemeral key to the recipient. The
+//! nonce is the 24 byte BLAKE2b hash of the ephemeral and the recipient public key, and the key
+//! is the HSalsa20 hash of the X25519 shared secret, as `crypto_box_beforenm` computes it. The
+//! security module only computes the shared secret with `derive_shared_secret`.
+
+use super::{check_x25519_shared_secret, decryption_error};
+use crate::common::{
+    crypto::secret::SecretBytes, error::SecurityModuleError, fips, traits::key_handle::KeyHandle,
+};
+use crypto_layer_core::CoreError;
+use sodiumoxide::crypto::{generichash, secretbox};
+use zeroize::Zeroize;
+
+/// The length of X25519 public keys in bytes.
+pub const PUBLIC_KEY_LEN: usize = 32;
+
+/// The length a sealed box adds to the message in bytes.
+pub const OVERHEAD: usize = PUBLIC_KEY_LEN + secretbox::MACBYTES;
+
+/// Opens a sealed box for the X25519 key of `key_handle`.
+///
+/// # Arguments
+///
+/// * `key_handle` - Derives the X25519 shared secret with `derive_shared_secret` from the raw
+///   ephemeral public key.
+/// * `recipient_public_key` - The raw X25519 public key of `key_handle`, which the nonce covers.
+/// * `sealed_box` - The sealed box.
+///
+/// # Returns
+///
+/// A `Result` containing the message, which is zeroed when dropped, a
+/// `SecurityModuleError::Encoding` if the box is shorter than `OVERHEAD`, a
+/// `SecurityModuleError::DecryptionError` if it was sealed for another key or modified, the
+/// error of `key_handle` if the shared secret cannot be derived, or a
+/// `SecurityModuleError::NotFipsApproved` in FIPS mode.
+#[tracing::instrument(skip_all, fields(crypto.payload.size = sealed_box.len()))]
+pub fn open(
+    key_handle: &(impl KeyHandle + ?Sized),
+    recipient_public_key: &[u8; PUBLIC_KEY_LEN],
+    sealed_box: &[u8],
+) -> Result<SecretBytes, SecurityModuleError> {
+    if fips::is_enabled() {
+        fips::check_name("xsalsa20_poly1305")?;
+    }
+    if sealed_box.len() < OVERHEAD {
+        return Err(CoreError::Truncated.into());
+    }
+    sodiumoxide::init().map_err(|_| decryption_error("libsodium cannot be initialized"))?;
+    let (ephemeral_public_key, ciphertext) = sealed_box.split_at(PUBLIC_KEY_LEN);
+
+    let shared_secret = key_handle.derive_shared_secret(ephemeral_public_key)?;
+    if shared_secret.len() != 32 {
+        return Err(SecurityModuleError::UnsupportedAlgorithm);
+    }
+    check_x25519_shared_secret(&shared_secret)?;
+    // `secretbox::Key` zeroes itself when dropped.
+    let key = secretbox::Key(hsalsa20(shared_secret[..].try_into().unwrap(), &[0; 16]));
+
+    let nonce = nonce(ephemeral_public_key, recipient_public_key)?;
+    secretbox::open(ciphertext, &nonce, &key)
+        .map(SecretBytes::new)
+        .map_err(|_| decryption_error("The sealed box was modified or sealed for another key"))
+}
+
+/// The BLAKE2b hash with 24 bytes of output of the ephemeral and the recipient public key.
+fn nonce(
+    ephemeral_public_key: &[u8],
+    recipient_public_key: &[u8],
+) -> Result<secretbox::Nonce, SecurityModuleError> {
+    let unavailable = |_| decryption_error("BLAKE2b is not available");
+    let mut state =
+        generichash::State::new(Some(secretbox::NONCEBYTES), None).map_err(unavailable)?;
+    state.update(ephemeral_public_key).map_err(unavailable)?;
+    state.update(recipient_public_key).map_err(unavailable)?;
+    let digest = state.finalize().map_err(unavailable)?;
+    Ok(secretbox::Nonce::from_slice(digest.as_ref()).unwrap())
+}
+
+/// HSalsa20, which derives a key from a key and a 16 byte input, see the XSalsa20 paper.
+fn hsalsa20(key: &[u8; 32], input: &[u8; 16]) -> [u8; 32] {
+    // "expand 32-byte k"
+    const SIGMA: [u32; 4] = [0x6170_7865, 0x3320_646e, 0x7962_2d32, 0x6b20_6574];
+    let word = |bytes: &[u8]| u32::from_le_bytes(bytes.try_into().unwrap());
+    let mut x = [0; 16];
+    for i in 0..4 {
+        x[i * 5] = SIGMA[i];
+        x[1 + i] = word(&key[i * 4..i * 4 + 4]);
+        x[11 + i] = word(&key[16 + i * 4..20 + i * 4]);
+        x[6 + i] = word(&input[i * 4..i * 4 + 4]);
+    }
+
+    fn quarter_round(x: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+        x[b] ^= x[a].wrapping_add(x[d]).rotate_left(7);
+        x[c] ^= x[b].wrapping_add(x[a]).rotate_left(9);
+        x[d] ^= x[c].wrapping_add(x[b]).rotate_left(13);
+        x[a] ^= x[d].wrapping_add(x[c]).rotate_left(18);
+    }
+    for _ in 0..10 {
+        // The column round, then the row round.
+        quarter_round(&mut x, 0, 4, 8, 12);
+        quarter_round(&mut x, 5, 9, 13, 1);
+        quarter_round(&mut x, 10, 14, 2, 6);
+        quarter_round(&mut x, 15, 3, 7, 11);
+        quarter_round(&mut x, 0, 1, 2, 3);
+        quarter_round(&mut x, 5, 6, 7, 4);
+        quarter_round(&mut x, 10, 11, 8, 9);
+        quarter_round(&mut x, 15, 12, 13, 14);
+    }
+
+    let mut output = [0; 32];
+    for (chunk, i) in output.chunks_mut(4).zip([0, 5, 10, 15, 6, 7, 8, 9]) {
+        chunk.copy_from_slice(&x[i].to_le_bytes());
+    }
+    x.zeroize();
+    output
+}
@@ -0,0 +1,249 @@
+//! Purpose-scoped subkeys derived from one master secret under a key of the security module.
+//!
+//! Applications that need many symmetric keys, e.g. one per feature and version, would need as
+//! many keys of the security module, which the Secure Enclave and TPMs only hold few of. A
+//! `KeyHierarchy` holds a master secret that is generated once and stored encrypted with
+//! `encrypt_data` of the security module, and deterministically derives subkeys from it by path:
+//!
+//! ```rust,ignore
+//! use crypto_layer::common::key_hierarchy::KeyHierarchy;
+//!
+//! let (hierarchy, wrapped_master) = KeyHierarchy::generate(&provider, "master_kek")?;
+//! // Later, after reading `wrapped_master` back.
+//! let hierarchy = KeyHierarchy::unwrap(&provider, "master_kek", &wrapped_master)?;
+//!
+//! let payments_key = hierarchy.derive_subkey("payments/v1")?;
+//! // A component that only needs its own subtree never sees the master secret.
+//! let payments = hierarchy.subtree("payments")?;
+//! assert_eq!(payments.derive_subkey("v1")?, payments_key);
+//! ```
+//!
+//! A path consists of up to `MAX_DEPTH` segments separated by `/`. Every segment has 1 to
+//! `MAX_SEGMENT_LEN` ASCII letters, digits, `.`, `-` and `_` and starts with a letter or digit,
+//! like key ids. The key of a node is derived from the key of its parent with HKDF-SHA-256 and the
+//! info `crypto-layer/subkey/v1/node` followed by the u8 length of the segment and the segment,
+//! with the master secret as key of the root. A subkey of `len` bytes is derived from the key of
+//! the node of its path with the info `crypto-layer/subkey/v1/key` followed by the big-endian u16
+//! `len`, so subkeys of different lengths are unrelated.
+
+use crate::common::{
+    crypto::{kdf::Kdf, secret::SecretBytes},
+    error::SecurityModuleError,
+    traits::key_handle::KeyHandle,
+};
+use crypto_layer_core::CoreError;
+use openssl::rand::rand_bytes;
+use std::fmt;
+
+/// The KDF subkeys are derived with.
+const KDF: Kdf = Kdf::HkdfSha256;
+
+/// The length of the master secret and the keys of the nodes in bytes.
+const NODE_KEY_LEN: usize = 32;
+
+/// The length of the subkeys of `derive_subkey` in bytes.
+pub const DEFAULT_SUBKEY_LEN: usize = 32;
+
+/// The maximum length of a segment of a path in bytes.
+pub const MAX_SEGMENT_LEN: usize = 64;
+
+/// The maximum number of segments of a path below the root.
+pub const MAX_DEPTH: usize = 16;
+
+const NODE_INFO: &[u8] = b"crypto-layer/subkey/v1/node";
+const KEY_INFO: &[u8] = b"crypto-layer/subkey/v1/key";
+
+/// Derives subkeys from a master secret, see the module documentation.
+#[derive(Clone)]
+pub struct KeyHierarchy {
+    key_id: String,
+    path: String,
+    depth: usize,
+    node_key: SecretBytes,
+}
+
+impl KeyHierarchy {
+    /// Generates a new master secret.
+    ///
+    /// # Arguments
+    ///
+    /// * `key_handle` - Encrypts the master secret with `encrypt_data`, e.g. a provider with the
+    ///   loaded key `key_id`.
+    /// * `key_id` - The id of the key the master secret is encrypted with.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `KeyHierarchy` and the encrypted master secret, which has to be
+    /// stored to derive the same subkeys again, or the error of `key_handle`.
+    pub fn generate(
+        key_handle: &(impl KeyHandle + ?Sized),
+        key_id: &str,
+    ) -> Result<(Self, Vec<u8>), SecurityModuleError> {
+        let mut master = SecretBytes::zeroed(NODE_KEY_LEN);
+        rand_bytes(&mut master).map_err(|e| SecurityModuleError::EncryptionError(e.to_string()))?;
+        let wrapped_master = key_handle.encrypt_data(&master)?;
+        Ok((Self::root(key_id, master), wrapped_master))
+    }
+
+    /// Decrypts a master secret of `generate`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `KeyHierarchy`, the error of `key_handle` if the master secret
+    /// cannot be decrypted, or a `SecurityModuleError::DecryptionError` if it has the wrong
+    /// length.
+    pub fn unwrap(
+        key_handle: &(impl KeyHandle + ?Sized),
+        key_id: &str,
+        wrapped_master: &[u8],
+    ) -> Result<Self, SecurityModuleError> {
+        let master = key_handle.decrypt_data(wrapped_master)?;
+        if master.len() != NODE_KEY_LEN {
+            return Err(SecurityModuleError::DecryptionError(
+                "The master secret has the wrong length".to_owned(),
+            ));
+        }
+        Ok(Self::root(key_id, master))
+    }
+
+    fn root(key_id: &str, master: SecretBytes) -> Self {
+        Self {
+            key_id: key_id.to_owned(),
+            path: String::new(),
+            depth: 0,
+            node_key: master,
+        }
+    }
+
+    /// Returns the id of the key the master secret is encrypted with.
+    pub fn key_id(&self) -> &str {
+        &self.key_id
+    }
+
+    /// Returns the path of the node this hierarchy derives from, which is empty for the root.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Derives the `DEFAULT_SUBKEY_LEN` byte subkey of `path`, relative to the node of this
+    /// hierarchy.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the subkey, which is zeroed when dropped, or a
+    /// `SecurityModuleError::InvalidKeyId` if `path` is not a valid path.
+    pub fn derive_subkey(&self, path: &str) -> Result<SecretBytes, SecurityModuleError> {
+        self.derive_subkey_with_len(path, DEFAULT_SUBKEY_LEN)
+    }
+
+    /// Derives the subkey of `path` with `len` bytes, like `derive_subkey`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the subkey, a `SecurityModuleError::InvalidKeyId` if `path` is not a
+    /// valid path, or a `SecurityModuleError::Encoding` if `len` is 0 or longer than
+    /// `Kdf::max_output_len` of HKDF-SHA-256.
+    pub fn derive_subkey_with_len(
+        &self,
+        path: &str,
+        len: usize,
+    ) -> Result<SecretBytes, SecurityModuleError> {
+        let len_field = u16::try_from(len)
+            .ok()
+            .filter(|&len| len > 0)
+            .ok_or(CoreError::InvalidLength)?;
+        let node_key = self.node_key(path)?;
+        let info = [KEY_INFO, &len_field.to_be_bytes()].concat();
+        Ok(KDF.derive_vec(&node_key, None, &info, len)?)
+    }
+
+    /// Returns the hierarchy of the node of `path`, which derives the subkeys below it without
+    /// holding the master secret.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the hierarchy, or a `SecurityModuleError::InvalidKeyId` if `path` is
+    /// not a valid path.
+    pub fn subtree(&self, path: &str) -> Result<Self, SecurityModuleError> {
+        let node_key = self.node_key(path)?;
+        Ok(Self {
+            key_id: self.key_id.clone(),
+            path: if self.path.is_empty() {
+                path.to_owned()
+            } else {
+                format!("{}/{}", self.path, path)
+            },
+            depth: self.depth + path.split('/').count(),
+            node_key,
+        })
+    }
+
+    /// Derives the key of the node of `path`, relative to the node of this hierarchy.
+    fn node_key(&self, path: &str) -> Result<SecretBytes, SecurityModuleError> {
+        let segments = segments(path)?;
+        if self.depth + segments.len() > MAX_DEPTH {
+            return Err(invalid_path(
+                path,
+                &format!("is deeper than {} segments below the root", MAX_DEPTH),
+            ));
+        }
+        let mut node_key = self.node_key.clone();
+        for segment in segments {
+            let info = [NODE_INFO, &[segment.len() as u8], segment.as_bytes()].concat();
+            node_key = KDF.derive_vec(&node_key, None, &info, NODE_KEY_LEN)?;
+        }
+        Ok(node_key)
+    }
+}
+
+impl fmt::Debug for KeyHierarchy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyHierarchy")
+            .field("key_id", &self.key_id)
+            .field("path", &self.path)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Splits a path into its segments and validates them.
+fn segments(path: &str) -> Result<Vec<&str>, SecurityModuleError> {
+    let segments: Vec<&str> = path.split('/').collect();
+    for segment in &segments {
+        if segment.is_empty() || segment.len() > MAX_SEGMENT_LEN {
+            return Err(invalid_path(
+                path,
+                &format!(
+                    "has a segment that is empty or longer than {} bytes",
+                    MAX_SEGMENT_LEN
+                ),
+            ));
+        }
+        if !segment.starts_with(|c: char| c.is_ascii_alphanumeric()) {
+            return Err(invalid_path(
+                path,
+                "has a segment that does not start with a letter or digit",
+            ));
+        }
+        if let Some(c) = segment
+            .chars()
+            .find(|c| !c.is_ascii_alphanumeric() && !matches!(c, '.' | '-' | '_'))
+        {
+            return Err(invalid_path(
+                path,
+                &format!(
+                    "contains {:?}, only ASCII letters, digits, '.', '-', '_' and '/' are allowed",
+                    c
+                ),
+            ));
+        }
+    }
+    Ok(segments)
+}
+
+fn invalid_path(path: &str, rule: &str) -> SecurityModuleError {
+    SecurityModuleError::InvalidKeyId(format!(
+        "The subkey path '{}' {}",
+        path.escape_debug(),
+        rule
+    ))
+}
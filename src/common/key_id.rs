@@ -0,0 +1,371 @@
+//! Validated key ids.
+//!
+//! Every security module has its own rules for the names of keys: keychain application tags,
+//! the names of persistent TPM and CNG keys, Android Keystore aliases and the paths of the NKS
+//! vault. A `KeyId` only allows ids that all of them accept, so that a malformed id is rejected
+//! before any security module is touched instead of failing on one platform only.
+//!
+//! `SecModules::get_instance` wraps every instance it creates in a `ValidatedProvider`, which
+//! rejects invalid ids passed to `create_key` and `load_key`, rejects keys that are not approved in
+//! FIPS mode, see `fips`, applies the `SunsetPolicy` of the configuration to new keys and rejects
+//! inputs exceeding its `InputLimits`. It reports failed
+//! decryptions and malformed signatures uniformly, see `uniform_errors`.
+
+use crate::common::{
+    crypto::{
+        key_metadata::KeyMetadata, key_spec::KeySpec, operation_context::OperationContext,
+        secret::SecretBytes,
+    },
+    error::SecurityModuleError,
+    fips,
+    input_limits::InputLimits,
+    latency::LatencySnapshot,
+    session_pool::SessionPoolMetrics,
+    sunset::SunsetPolicy,
+    traits::{key_handle::KeyHandle, module_provider::Provider},
+    uniform_errors::{self, FailureTiming},
+};
+use std::{
+    any::Any,
+    fmt,
+    ops::Deref,
+    str::FromStr,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+/// The maximum length of a key id in bytes.
+pub const MAX_KEY_ID_LEN: usize = 128;
+
+/// The id of a key: 1 to `MAX_KEY_ID_LEN` ASCII letters, digits, `.`, `-` and `_`, starting with
+/// a letter or digit.
+///
+/// # Examples
+///
+/// ```rust
+/// use crypto_layer::common::key_id::KeyId;
+///
+/// let key_id = KeyId::new("device-identity").unwrap();
+/// assert_eq!(key_id.as_str(), "device-identity");
+/// assert!(KeyId::new("../other-key").is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "String", into = "String")
+)]
+pub struct KeyId(String);
+
+impl KeyId {
+    /// Validates a key id.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `KeyId` on success, or a `SecurityModuleError::InvalidKeyId`
+    /// describing the rule the id breaks.
+    pub fn new(key_id: impl Into<String>) -> Result<Self, SecurityModuleError> {
+        let key_id = key_id.into();
+        validate(&key_id)?;
+        Ok(Self(key_id))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+fn validate(key_id: &str) -> Result<(), SecurityModuleError> {
+    let invalid = |message: String| Err(SecurityModuleError::InvalidKeyId(message));
+
+    let Some(first) = key_id.chars().next() else {
+        return invalid("The key id is empty".to_owned());
+    };
+    if key_id.len() > MAX_KEY_ID_LEN {
+        return invalid(format!(
+            "The key id is longer than {} bytes",
+            MAX_KEY_ID_LEN
+        ));
+    }
+    if !first.is_ascii_alphanumeric() {
+        return invalid(format!(
+            "'{}' does not start with a letter or digit",
+            key_id.escape_debug()
+        ));
+    }
+    if let Some(c) = key_id
+        .chars()
+        .find(|c| !c.is_ascii_alphanumeric() && !matches!(c, '.' | '-' | '_'))
+    {
+        return invalid(format!(
+            "'{}' contains {:?}, only ASCII letters, digits, '.', '-' and '_' are allowed",
+            key_id.escape_debug(),
+            c
+        ));
+    }
+    Ok(())
+}
+
+impl Deref for KeyId {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<str> for KeyId {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for KeyId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl FromStr for KeyId {
+    type Err = SecurityModuleError;
+
+    fn from_str(key_id: &str) -> Result<Self, Self::Err> {
+        Self::new(key_id)
+    }
+}
+
+impl TryFrom<String> for KeyId {
+    type Error = SecurityModuleError;
+
+    fn try_from(key_id: String) -> Result<Self, Self::Error> {
+        Self::new(key_id)
+    }
+}
+
+impl TryFrom<&str> for KeyId {
+    type Error = SecurityModuleError;
+
+    fn try_from(key_id: &str) -> Result<Self, Self::Error> {
+        Self::new(key_id)
+    }
+}
+
+impl From<KeyId> for String {
+    fn from(key_id: KeyId) -> Self {
+        key_id.0
+    }
+}
+
+impl PartialEq<str> for KeyId {
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other
+    }
+}
+
+impl PartialEq<&str> for KeyId {
+    fn eq(&self, other: &&str) -> bool {
+        self.0 == *other
+    }
+}
+
+/// A provider that rejects key ids that are not a valid `KeyId`, new keys with algorithms
+/// denied by its `SunsetPolicy` and inputs exceeding its `InputLimits`, before they reach the
+/// wrapped provider.
+pub struct ValidatedProvider {
+    inner: Arc<Mutex<dyn Provider>>,
+    sunset: SunsetPolicy,
+    limits: InputLimits,
+    fips: bool,
+    decryption_timing: FailureTiming,
+}
+
+impl ValidatedProvider {
+    /// Wraps a provider without deprecating any algorithm, outside of FIPS mode, with the default
+    /// `InputLimits`.
+    pub fn new(inner: Arc<Mutex<dyn Provider>>) -> Self {
+        Self {
+            inner,
+            sunset: SunsetPolicy::new(),
+            limits: InputLimits::default(),
+            fips: false,
+            decryption_timing: FailureTiming::new(),
+        }
+    }
+
+    /// Applies `policy` to the keys created afterwards.
+    pub fn with_sunset_policy(mut self, policy: SunsetPolicy) -> Self {
+        self.sunset = policy;
+        self
+    }
+
+    /// Rejects inputs exceeding `limits` with a `SecurityModuleError::InputTooLarge`.
+    pub fn with_input_limits(mut self, limits: InputLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Rejects keys whose algorithms are not approved for FIPS mode, see `fips::check_key`.
+    pub fn with_fips_mode(mut self, enabled: bool) -> Self {
+        self.fips = enabled;
+        self
+    }
+
+    fn inner(&self) -> MutexGuard<'_, dyn Provider + 'static> {
+        self.inner
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn check_signed(&self, data: &[u8], signature: &[u8]) -> Result<(), SecurityModuleError> {
+        self.limits.check_data("data", data.len())?;
+        self.limits.check_field("signature", signature.len())
+    }
+}
+
+impl fmt::Debug for ValidatedProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ValidatedProvider")
+            .field("sunset", &self.sunset)
+            .field("limits", &self.limits)
+            .field("fips", &self.fips)
+            .finish_non_exhaustive()
+    }
+}
+
+impl KeyHandle for ValidatedProvider {
+    fn sign_data(&self, data: &[u8]) -> Result<Vec<u8>, SecurityModuleError> {
+        self.limits.check_data("data", data.len())?;
+        self.inner().sign_data(data)
+    }
+
+    fn decrypt_data(&self, encrypted_data: &[u8]) -> Result<SecretBytes, SecurityModuleError> {
+        self.limits
+            .check_data("encrypted data", encrypted_data.len())?;
+        uniform_errors::decryption(&self.decryption_timing, || {
+            self.inner().decrypt_data(encrypted_data)
+        })
+    }
+
+    fn encrypt_data(&self, data: &[u8]) -> Result<Vec<u8>, SecurityModuleError> {
+        self.limits.check_data("data", data.len())?;
+        self.inner().encrypt_data(data)
+    }
+
+    fn verify_signature(&self, data: &[u8], signature: &[u8]) -> Result<bool, SecurityModuleError> {
+        self.check_signed(data, signature)?;
+        uniform_errors::verification(|| self.inner().verify_signature(data, signature))
+    }
+
+    fn sign_data_into<'a>(
+        &self,
+        data: &[u8],
+        context: &'a mut OperationContext,
+    ) -> Result<&'a [u8], SecurityModuleError> {
+        self.limits.check_data("data", data.len())?;
+        self.inner().sign_data_into(data, context)
+    }
+
+    fn verify_signature_with(
+        &self,
+        data: &[u8],
+        signature: &[u8],
+        context: &mut OperationContext,
+    ) -> Result<bool, SecurityModuleError> {
+        self.check_signed(data, signature)?;
+        self.inner().verify_signature_with(data, signature, context)
+    }
+
+    fn verify_many(&self, items: &[(&[u8], &[u8])]) -> Result<Vec<bool>, SecurityModuleError> {
+        self.limits.check_batch("batch", items.len())?;
+        for (data, signature) in items {
+            self.check_signed(data, signature)?;
+        }
+        self.inner().verify_many(items)
+    }
+
+    fn derive_shared_secret(
+        &self,
+        peer_public_key: &[u8],
+    ) -> Result<SecretBytes, SecurityModuleError> {
+        self.limits
+            .check_field("peer public key", peer_public_key.len())?;
+        self.inner().derive_shared_secret(peer_public_key)
+    }
+}
+
+impl Provider for ValidatedProvider {
+    fn create_key(
+        &mut self,
+        key_id: &str,
+        config: Box<dyn Any>,
+    ) -> Result<(), SecurityModuleError> {
+        validate(key_id)?;
+        if self.fips {
+            fips::check_key(config.as_ref())?;
+        }
+        self.sunset.check_new_key(key_id, config.as_ref())?;
+        self.inner().create_key(key_id, config)
+    }
+
+    fn load_key(&mut self, key_id: &str, config: Box<dyn Any>) -> Result<(), SecurityModuleError> {
+        validate(key_id)?;
+        if self.fips {
+            fips::check_key(config.as_ref())?;
+        }
+        self.inner().load_key(key_id, config)
+    }
+
+    fn import_wrapped_key(
+        &mut self,
+        key_id: &str,
+        wrapped_key: &[u8],
+        wrapping_key_id: &str,
+        spec: &KeySpec,
+    ) -> Result<(), SecurityModuleError> {
+        validate(key_id)?;
+        validate(wrapping_key_id)?;
+        self.limits.check_field("wrapped key", wrapped_key.len())?;
+        if self.fips {
+            fips::check_key(spec)?;
+        }
+        self.sunset.check_new_key(key_id, spec)?;
+        self.inner()
+            .import_wrapped_key(key_id, wrapped_key, wrapping_key_id, spec)
+    }
+
+    fn export_private_key(&self) -> Result<SecretBytes, SecurityModuleError> {
+        self.inner().export_private_key()
+    }
+
+    fn initialize_module(&mut self) -> Result<(), SecurityModuleError> {
+        self.inner().initialize_module()
+    }
+
+    fn session_pool_metrics(&self) -> Option<SessionPoolMetrics> {
+        self.inner().session_pool_metrics()
+    }
+
+    fn latency_snapshot(&self) -> Option<LatencySnapshot> {
+        self.inner().latency_snapshot()
+    }
+
+    fn key_metadata(&self) -> Result<KeyMetadata, SecurityModuleError> {
+        self.inner().key_metadata()
+    }
+
+    fn refresh_key_metadata(&mut self) -> Result<KeyMetadata, SecurityModuleError> {
+        self.inner().refresh_key_metadata()
+    }
+
+    fn list_keys(&self) -> Result<Vec<String>, SecurityModuleError> {
+        self.inner().list_keys()
+    }
+
+    fn delete_key(&mut self, key_id: &str) -> Result<(), SecurityModuleError> {
+        validate(key_id)?;
+        self.inner().delete_key(key_id)
+    }
+
+    fn shutdown(&mut self) -> Result<(), SecurityModuleError> {
+        self.inner().shutdown()
+    }
+}
@@ -0,0 +1,164 @@
+//! Import of keys that were generated outside the security module ("bring your own key").
+//!
+//! The key travels to the security module wrapped under a wrapping key that the module already
+//! holds, so it never exists in plaintext on the device. `Provider::import_wrapped_key` unwraps
+//! it inside the module. The format of the wrapped key depends on the module:
+//!
+//! - The TPM of `tpm::linux` imports a duplicate created with `TPM2_Duplicate` or
+//!   `tpm2_duplicate` for the current key as parent. The wrapped key is the `TPM2B_PUBLIC` of the
+//!   key, followed by the `TPM2B_PRIVATE` duplicate and the `TPM2B_ENCRYPTED_SECRET` seed.
+//! - The Android Keystore imports the DER encoded `SecureKeyWrapper` of `WrappedKeyEntry`.
+//! - PKCS#11 tokens and the mock provider unwrap `CKM_RSA_AES_KEY_WRAP` blobs, which `wrap_rsa_aes`
+//!   creates: an ephemeral AES-256 key encrypted with RSA-OAEP with SHA-256 and MGF1 with
+//!   SHA-256, followed by the PKCS#8 DER private key wrapped under the AES key with AES-KWP
+//!   (RFC 5649).
+//! - The Secure Enclave only uses keys it generated itself and rejects imports with
+//!   `SecurityModuleError::UnsupportedOperation`.
+//!
+//! ```rust,ignore
+//! use crypto_layer::common::key_import;
+//!
+//! // On the key server, for the public key of the wrapping key of the device.
+//! let wrapped_key = key_import::wrap_rsa_aes(&wrapping_public_key, &pkcs8_der)?;
+//!
+//! // On the device.
+//! provider.import_wrapped_key("imported", &wrapped_key, "wrapping", &spec)?;
+//! ```
+
+use crate::common::{
+    crypto::{
+        algorithms::encryption::AsymmetricEncryption, public_key::PublicKey, secret::SecretBytes,
+    },
+    error::SecurityModuleError,
+};
+use crypto_layer_core::CoreError;
+use openssl::{
+    cipher::Cipher,
+    cipher_ctx::{CipherCtx, CipherCtxFlags},
+    encrypt::Encrypter,
+    error::ErrorStack,
+    hash::MessageDigest,
+    pkey::PKey,
+    rand::rand_bytes,
+    rsa::Padding,
+};
+#[cfg(feature = "test-utils")]
+use openssl::{encrypt::Decrypter, pkey::Private};
+
+/// The length of the ephemeral AES key of `CKM_RSA_AES_KEY_WRAP` blobs in bytes.
+const AES_KEY_LEN: usize = 32;
+
+/// Wraps a private key for import into a module holding the private key of
+/// `wrapping_public_key`, in the format of `CKM_RSA_AES_KEY_WRAP`.
+///
+/// # Arguments
+///
+/// * `wrapping_public_key` - The RSA public key of the wrapping key.
+/// * `pkcs8_der` - The private key to be imported, as PKCS#8 DER.
+///
+/// # Returns
+///
+/// A `Result` containing the wrapped key on success, a
+/// `SecurityModuleError::UnsupportedAlgorithm` if the wrapping key is not an RSA key, or a
+/// `SecurityModuleError::EncryptionError` if the key cannot be wrapped.
+pub fn wrap_rsa_aes(
+    wrapping_public_key: &PublicKey,
+    pkcs8_der: &[u8],
+) -> Result<Vec<u8>, SecurityModuleError> {
+    if !matches!(
+        wrapping_public_key.algorithm(),
+        AsymmetricEncryption::Rsa(_)
+    ) {
+        return Err(SecurityModuleError::UnsupportedAlgorithm);
+    }
+    if pkcs8_der.is_empty() {
+        return Err(CoreError::MissingField("private key").into());
+    }
+    let public_key = PKey::public_key_from_der(&wrapping_public_key.to_der()?)
+        .map_err(|_| SecurityModuleError::InvalidPublicKey)?;
+
+    let mut aes_key = SecretBytes::zeroed(AES_KEY_LEN);
+    rand_bytes(&mut aes_key).map_err(encryption_error)?;
+    Encrypter::new(&public_key)
+        .and_then(|mut encrypter| {
+            encrypter.set_rsa_padding(Padding::PKCS1_OAEP)?;
+            encrypter.set_rsa_oaep_md(MessageDigest::sha256())?;
+            encrypter.set_rsa_mgf1_md(MessageDigest::sha256())?;
+            let mut wrapped = vec![0; encrypter.encrypt_len(&aes_key)?];
+            let len = encrypter.encrypt(&aes_key, &mut wrapped)?;
+            wrapped.truncate(len);
+            wrapped.extend(aes_kwp_wrap(&aes_key, pkcs8_der)?);
+            Ok(wrapped)
+        })
+        .map_err(encryption_error)
+}
+
+/// Unwraps a `CKM_RSA_AES_KEY_WRAP` blob created by `wrap_rsa_aes` with the private wrapping
+/// key and returns the PKCS#8 DER private key. Only the mock provider unwraps in software.
+#[cfg(feature = "test-utils")]
+pub(crate) fn unwrap_rsa_aes(
+    wrapping_private_key: &PKey<Private>,
+    wrapped_key: &[u8],
+) -> Result<SecretBytes, SecurityModuleError> {
+    let rsa = wrapping_private_key
+        .rsa()
+        .map_err(|_| SecurityModuleError::UnsupportedAlgorithm)?;
+    let modulus_len = rsa.size() as usize;
+    if wrapped_key.len() <= modulus_len {
+        return Err(CoreError::Truncated.into());
+    }
+    let (encrypted_key, wrapped_private_key) = wrapped_key.split_at(modulus_len);
+
+    let aes_key = Decrypter::new(wrapping_private_key)
+        .and_then(|mut decrypter| {
+            decrypter.set_rsa_padding(Padding::PKCS1_OAEP)?;
+            decrypter.set_rsa_oaep_md(MessageDigest::sha256())?;
+            decrypter.set_rsa_mgf1_md(MessageDigest::sha256())?;
+            let mut aes_key = SecretBytes::zeroed(decrypter.decrypt_len(encrypted_key)?);
+            let len = decrypter.decrypt(encrypted_key, &mut aes_key)?;
+            aes_key.truncate(len);
+            Ok(aes_key)
+        })
+        .map_err(|_| decryption_error("The AES key cannot be decrypted"))?;
+    if aes_key.len() != AES_KEY_LEN {
+        return Err(decryption_error("The AES key has the wrong length"));
+    }
+    aes_kwp_unwrap(&aes_key, wrapped_private_key)
+        .map_err(|_| decryption_error("The private key cannot be unwrapped"))
+}
+
+/// Wraps `data` under the AES-256 key `key` with AES-KWP (RFC 5649).
+pub(crate) fn aes_kwp_wrap(key: &[u8], data: &[u8]) -> Result<Vec<u8>, ErrorStack> {
+    let mut ctx = CipherCtx::new()?;
+    ctx.set_flags(CipherCtxFlags::FLAG_WRAP_ALLOW);
+    ctx.encrypt_init(Some(Cipher::aes_256_wrap_pad()), Some(key), None)?;
+    apply(&mut ctx, data).map(SecretBytes::into_vec)
+}
+
+/// Unwraps data wrapped by `aes_kwp_wrap`, checking its integrity.
+pub(crate) fn aes_kwp_unwrap(key: &[u8], wrapped: &[u8]) -> Result<SecretBytes, ErrorStack> {
+    let mut ctx = CipherCtx::new()?;
+    ctx.set_flags(CipherCtxFlags::FLAG_WRAP_ALLOW);
+    ctx.decrypt_init(Some(Cipher::aes_256_wrap_pad()), Some(key), None)?;
+    apply(&mut ctx, wrapped)
+}
+
+/// Runs the key wrap cipher of `ctx` over `input`.
+fn apply(ctx: &mut CipherCtx, input: &[u8]) -> Result<SecretBytes, ErrorStack> {
+    // AES-KWP pads to 8 bytes and adds an 8 byte integrity check value in a single update,
+    // finalizing needs another block of room.
+    let mut output = SecretBytes::zeroed(input.len() + 3 * ctx.block_size());
+    let len = ctx.cipher_update(input, Some(&mut output))?;
+    let rest = ctx.cipher_final(&mut output[len..])?;
+    output.truncate(len + rest);
+    Ok(output)
+}
+
+fn encryption_error(err: ErrorStack) -> SecurityModuleError {
+    SecurityModuleError::EncryptionError(err.to_string())
+}
+
+#[cfg(feature = "test-utils")]
+fn decryption_error(message: &str) -> SecurityModuleError {
+    SecurityModuleError::DecryptionError(message.to_owned())
+}
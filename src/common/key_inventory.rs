@@ -0,0 +1,206 @@
+//! Signed manifests of the keys of a device for fleet management.
+//!
+//! A `KeyInventory` lists the public keys of a device with their metadata and, if a
+//! `MetadataStore` is used, their records: labels, usage policy, version and usage statistics.
+//! It never holds key material. The device signs the inventory with a designated key, e.g. its
+//! device identity, and the fleet management verifies it against the registered public key of
+//! that key before it trusts the listed keys:
+//!
+//! ```rust,ignore
+//! use crypto_layer::common::key_inventory::KeyInventory;
+//!
+//! // On the device.
+//! let inventory = KeyInventory::collect("sensor-17", &mut provider, Some(&*store), config)?;
+//! provider.load_key("device-identity.1", config("device-identity.1"))?;
+//! std::fs::write("inventory.dsse.json", inventory.sign(&provider)?.to_json())?;
+//!
+//! // In the fleet management.
+//! let envelope = DsseEnvelope::from_json(&std::fs::read_to_string("inventory.dsse.json")?)?;
+//! let inventory = KeyInventory::verify(&envelope, &registered_public_key)?;
+//! inventory.import(&fleet_store)?;
+//! ```
+//!
+//! Manifests are DSSE envelopes of the JSON encoding of the inventory with the payload type
+//! `INVENTORY_PAYLOAD_TYPE`, see `sigstore::DsseEnvelope`. Unlike Sigstore signatures, any key
+//! that can sign may sign an inventory.
+
+use crate::common::{
+    crypto::{key_metadata::KeyMetadata, public_key::PublicKey},
+    error::SecurityModuleError,
+    metadata_store::{KeyRecord, MetadataStore},
+    sigstore::{pae, DsseEnvelope, DsseSignature},
+    traits::module_provider::Provider,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    any::Any,
+    collections::BTreeMap,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// The payload type of signed key inventories.
+pub const INVENTORY_PAYLOAD_TYPE: &str = "application/vnd.crypto-layer.key-inventory+json";
+
+/// The version of the inventory format written by this version of the crate.
+pub const INVENTORY_VERSION: u32 = 1;
+
+/// A key of an inventory.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InventoryEntry {
+    /// The public key and attestation of the key.
+    pub metadata: KeyMetadata,
+    /// The record of the key, if the device keeps one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub record: Option<KeyRecord>,
+}
+
+/// The keys of a device, see the module documentation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct KeyInventory {
+    /// The version of the inventory format, `INVENTORY_VERSION` for new inventories.
+    pub version: u32,
+    /// The device the keys belong to, e.g. its serial number.
+    pub device: String,
+    /// The time the inventory was created at in milliseconds since the Unix epoch.
+    pub created_at: u64,
+    /// The keys by their ids.
+    pub keys: BTreeMap<String, InventoryEntry>,
+}
+
+impl KeyInventory {
+    /// Creates an empty inventory of `device`.
+    pub fn new(device: &str) -> Self {
+        Self {
+            version: INVENTORY_VERSION,
+            device: device.to_owned(),
+            created_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |duration| duration.as_millis() as u64),
+            keys: BTreeMap::new(),
+        }
+    }
+
+    /// Adds a key, replacing an entry of the same key id.
+    pub fn insert(&mut self, metadata: KeyMetadata, record: Option<KeyRecord>) {
+        self.keys.insert(
+            metadata.key_id().to_owned(),
+            InventoryEntry { metadata, record },
+        );
+    }
+
+    /// Creates the inventory of all keys of `provider`.
+    ///
+    /// Every key is loaded to read its metadata, so afterwards the last key of `list_keys` is
+    /// loaded, not the key loaded before.
+    ///
+    /// # Arguments
+    ///
+    /// * `device` - The device the keys belong to.
+    /// * `provider` - The provider holding the keys, which must be able to list them.
+    /// * `store` - The store of the records of the keys, if the device keeps them.
+    /// * `config` - Returns the configuration to load a key with for its key id.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `KeyInventory`, or the error of `provider` or `store`.
+    #[tracing::instrument(skip(provider, store, config))]
+    pub fn collect(
+        device: &str,
+        provider: &mut (impl Provider + ?Sized),
+        store: Option<&dyn MetadataStore>,
+        mut config: impl FnMut(&str) -> Box<dyn Any>,
+    ) -> Result<Self, SecurityModuleError> {
+        let mut inventory = Self::new(device);
+        for key_id in provider.list_keys()? {
+            provider.load_key(&key_id, config(&key_id))?;
+            let record = match store {
+                Some(store) => store.load(&key_id)?,
+                None => None,
+            };
+            inventory.insert(provider.key_metadata()?, record);
+        }
+        Ok(inventory)
+    }
+
+    /// Signs the inventory with the loaded key of `provider`, the designated key of the device.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the signed manifest, whose signature names the id of the signing
+    /// key, or the error of `provider`.
+    #[tracing::instrument(skip_all, fields(crypto.keys = self.keys.len()))]
+    pub fn sign(
+        &self,
+        provider: &(impl Provider + ?Sized),
+    ) -> Result<DsseEnvelope, SecurityModuleError> {
+        let payload = serde_json::to_vec(self).expect("inventories can be serialized");
+        let mut envelope = DsseEnvelope::new(INVENTORY_PAYLOAD_TYPE, payload);
+        let sig = provider.sign_data(&pae(&envelope.payload_type, &envelope.payload))?;
+        envelope.signatures.push(DsseSignature {
+            keyid: Some(provider.key_metadata()?.key_id().to_owned()),
+            sig,
+        });
+        Ok(envelope)
+    }
+
+    /// Verifies a signed manifest against the public key of the designated key of the device.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the verified `KeyInventory`, a
+    /// `SecurityModuleError::InvalidSignature` if the manifest was not signed by the owner of
+    /// `public_key`, or a `SecurityModuleError::SignatureVerificationError` if the envelope does
+    /// not hold an inventory, the inventory is of a newer version or lists a key under another
+    /// key id than its own.
+    pub fn verify(
+        envelope: &DsseEnvelope,
+        public_key: &PublicKey,
+    ) -> Result<Self, SecurityModuleError> {
+        if envelope.payload_type != INVENTORY_PAYLOAD_TYPE {
+            return Err(malformed("The envelope does not hold a key inventory"));
+        }
+        envelope.verify(public_key)?;
+
+        let inventory: Self =
+            serde_json::from_slice(&envelope.payload).map_err(|e| malformed(&e.to_string()))?;
+        if inventory.version > INVENTORY_VERSION {
+            return Err(malformed(&format!(
+                "The inventory has the unsupported version {}",
+                inventory.version
+            )));
+        }
+        for (key_id, entry) in &inventory.keys {
+            let record_key_id = entry.record.as_ref().map(|record| record.key_id.as_str());
+            if entry.metadata.key_id() != key_id || record_key_id.is_some_and(|id| id != key_id) {
+                return Err(malformed(&format!(
+                    "The inventory lists another key as '{}'",
+                    key_id
+                )));
+            }
+        }
+        Ok(inventory)
+    }
+
+    /// Saves the records of the keys to `store`, so that it lists every key of the inventory.
+    ///
+    /// Keys without a record get a new one. Records are saved under the ids of the keys, so
+    /// inventories of several devices are imported into a store per device.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the number of saved records, or the error of `store`.
+    pub fn import(&self, store: &dyn MetadataStore) -> Result<usize, SecurityModuleError> {
+        for (key_id, entry) in &self.keys {
+            match &entry.record {
+                Some(record) => store.save(record)?,
+                None => store.save(&KeyRecord::new(key_id))?,
+            }
+        }
+        Ok(self.keys.len())
+    }
+}
+
+fn malformed(message: &str) -> SecurityModuleError {
+    SecurityModuleError::SignatureVerificationError(message.to_owned())
+}
@@ -0,0 +1,114 @@
+//! Serves keys of a single provider to clients that each name their key, see `remote` and
+//! `daemon`.
+//!
+//! Providers of the factory are shared by all key ids and hold one loaded key at a time, so the
+//! router remembers the spec of every key a client created or loaded, and loads the key a call
+//! names into the provider before the call uses it.
+
+use crate::common::{
+    crypto::key_spec::KeySpec, error::SecurityModuleError, traits::module_provider::Provider,
+};
+use std::{
+    any::Any,
+    collections::HashMap,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+/// Creates the configuration of a provider for the spec of a key, e.g. `TpmConfig::from_spec`.
+pub(crate) type ConfigFn = fn(&KeySpec) -> Result<Box<dyn Any>, SecurityModuleError>;
+
+pub(crate) struct KeyRouter {
+    provider: Arc<Mutex<dyn Provider>>,
+    config: ConfigFn,
+    keys: Mutex<Keys>,
+}
+
+#[derive(Default)]
+struct Keys {
+    specs: HashMap<String, KeySpec>,
+    /// The key id of the key the provider has loaded.
+    loaded: Option<String>,
+}
+
+impl KeyRouter {
+    pub(crate) fn new(provider: Arc<Mutex<dyn Provider>>, config: ConfigFn) -> Self {
+        Self {
+            provider,
+            config,
+            keys: Mutex::default(),
+        }
+    }
+
+    /// Creates or loads the key labeled by `spec` and remembers its spec.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the DER encoded public key, `None` for symmetric keys, or the
+    /// error of the provider.
+    pub(crate) fn open_key(
+        &self,
+        spec: &KeySpec,
+        create: bool,
+    ) -> Result<Option<Vec<u8>>, SecurityModuleError> {
+        let key_id = spec.label();
+        let mut keys = lock(&self.keys);
+        let mut provider = lock(&self.provider);
+        keys.loaded = None;
+        let config = (self.config)(spec)?;
+        if create {
+            provider.create_key(key_id, config)?;
+        } else {
+            provider.load_key(key_id, config)?;
+        }
+        keys.loaded = Some(key_id.to_owned());
+        let public_key = match spec.asymmetric_algorithm() {
+            Some(_) => Some(provider.key_metadata()?.public_key_der().to_vec()),
+            None => None,
+        };
+        keys.specs.insert(key_id.to_owned(), spec.clone());
+        Ok(public_key)
+    }
+
+    /// Runs `operation` with the key `key_id` loaded into the provider.
+    ///
+    /// # Returns
+    ///
+    /// The result of `operation`, a `SecurityModuleError::KeyError` if no client created or
+    /// loaded the key, or the error of `load_key`.
+    pub(crate) fn with_key<T>(
+        &self,
+        key_id: &str,
+        operation: impl FnOnce(&dyn Provider) -> Result<T, SecurityModuleError>,
+    ) -> Result<T, SecurityModuleError> {
+        let mut keys = lock(&self.keys);
+        let mut provider = lock(&self.provider);
+        if keys.loaded.as_deref() != Some(key_id) {
+            let spec = keys
+                .specs
+                .get(key_id)
+                .ok_or(SecurityModuleError::KeyError)?;
+            let config = (self.config)(spec)?;
+            keys.loaded = None;
+            provider.load_key(key_id, config)?;
+            keys.loaded = Some(key_id.to_owned());
+        }
+        operation(&*provider)
+    }
+
+    /// Returns the ids of the keys the security module holds, see `Provider::list_keys`.
+    #[cfg_attr(not(feature = "daemon"), allow(dead_code))]
+    pub(crate) fn list_keys(&self) -> Result<Vec<String>, SecurityModuleError> {
+        lock(&self.provider).list_keys()
+    }
+
+    /// Returns the ids of the keys clients created or loaded, sorted.
+    pub(crate) fn key_ids(&self) -> Vec<String> {
+        let mut key_ids: Vec<String> = lock(&self.keys).specs.keys().cloned().collect();
+        key_ids.sort_unstable();
+        key_ids
+    }
+}
+
+fn lock<T: ?Sized>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
@@ -0,0 +1,84 @@
+//! Usage statistics of keys.
+//!
+//! Every instance created by `SecModules::get_instance` counts the operations performed with its
+//! key and remembers when the key was last used, so that unused keys can be identified and
+//! retired. Statistics are kept in memory since the start of the process.
+
+use crate::common::latency::ProviderOperation;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Mutex, MutexGuard},
+    time::SystemTime,
+};
+
+static STATS: Lazy<Mutex<HashMap<String, KeyStats>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// The usage of a key since the start of the process.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct KeyStats {
+    /// The identifier of the key.
+    pub key_id: String,
+    /// When the key was first used, i.e. created, loaded or used for an operation.
+    pub first_used: SystemTime,
+    /// When the key was last used successfully, or `None` if every operation failed.
+    pub last_used: Option<SystemTime>,
+    /// The number of calls of every operation performed at least once, including failed ones.
+    pub operations: BTreeMap<ProviderOperation, u64>,
+    /// The number of failed calls.
+    pub failures: u64,
+}
+
+impl KeyStats {
+    fn new(key_id: &str, now: SystemTime) -> Self {
+        Self {
+            key_id: key_id.to_owned(),
+            first_used: now,
+            last_used: None,
+            operations: BTreeMap::new(),
+            failures: 0,
+        }
+    }
+
+    /// Returns the number of calls of all operations.
+    pub fn total_operations(&self) -> u64 {
+        self.operations.values().sum()
+    }
+}
+
+fn lock() -> MutexGuard<'static, HashMap<String, KeyStats>> {
+    STATS.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Returns the usage statistics of a key, or `None` if it was not used since the start of the
+/// process.
+pub fn key_stats(key_id: &str) -> Option<KeyStats> {
+    lock().get(key_id).cloned()
+}
+
+/// Returns the usage statistics of all keys used since the start of the process, in no
+/// particular order.
+pub fn all_key_stats() -> Vec<KeyStats> {
+    lock().values().cloned().collect()
+}
+
+/// Counts a call of `operation` with the key `key_id`.
+///
+/// `InitializeModule` does not use a key and is not counted.
+pub(crate) fn record(key_id: &str, operation: ProviderOperation, succeeded: bool) {
+    if operation == ProviderOperation::InitializeModule || key_id.is_empty() {
+        return;
+    }
+    let now = SystemTime::now();
+    let mut stats = lock();
+    let stats = stats
+        .entry(key_id.to_owned())
+        .or_insert_with(|| KeyStats::new(key_id, now));
+    *stats.operations.entry(operation).or_default() += 1;
+    if succeeded {
+        stats.last_used = Some(now);
+    } else {
+        stats.failures += 1;
+    }
+}
@@ -0,0 +1,206 @@
+//! Wrapping of application keys under key encryption keys (KEKs) of the security module.
+//!
+//! Applications that keep their own software keys, e.g. database encryption keys or the keys of
+//! another protocol, protect them with a hardware root by wrapping them under a KEK. `encrypt_data`
+//! is not suited for this: RSA-OAEP only encrypts a few dozen bytes and EC keys do not encrypt at
+//! all. A `KeyWrapper` selects a construction that fits the type of the KEK:
+//!
+//! ```rust,ignore
+//! use crypto_layer::common::key_wrapping::KeyWrapper;
+//!
+//! let wrapper = KeyWrapper::new().with_kek("database_kek", provider);
+//! let blob = wrapper.wrap("database_kek", &database_key)?;
+//! // Later, possibly after a restart, with the same KEK loaded.
+//! let database_key = wrapper.unwrap("database_kek", &blob)?;
+//! ```
+//!
+//! The blob starts with a byte naming the construction:
+//!
+//! - `0x01` for RSA and symmetric KEKs: a fresh AES-256 key is encrypted with `encrypt_data` of
+//!   the KEK, i.e. with RSA-OAEP for RSA keys, and the key material is wrapped under it with
+//!   AES-KWP (RFC 5649). The tag is followed by the big-endian u16 length of the encrypted AES
+//!   key, the encrypted AES key and the wrapped key material.
+//! - `0x02` for EC KEKs: the key material is encrypted for the public key of the KEK with
+//!   `ecies`. The tag is followed by the ECIES envelope.
+//!
+//! The type of the KEK is read from `Provider::key_metadata`: EC keys use ECIES, all other keys,
+//! including symmetric keys without metadata, use AES-KWP.
+//!
+//! The AES keys and the unwrapped key material are `SecretBytes`, which are zeroed when dropped
+//! and locked into RAM if `memory_lock` is enabled.
+
+use crate::common::{
+    crypto::{
+        algorithms::encryption::AsymmetricEncryption, public_key::PublicKey, secret::SecretBytes,
+    },
+    ecies,
+    error::SecurityModuleError,
+    key_import::{aes_kwp_unwrap, aes_kwp_wrap},
+    traits::module_provider::Provider,
+    uniform_errors::{self, FailureTiming},
+};
+use crypto_layer_core::CoreError;
+use openssl::rand::rand_bytes;
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+/// The maximum length of the key material in bytes. Larger data should be encrypted with
+/// `crypto::aead` under a wrapped key.
+pub const MAX_KEY_MATERIAL_LEN: usize = 64 * 1024;
+
+/// The tag of blobs with an AES key encrypted by the KEK and AES-KWP wrapped key material.
+const TAG_AES_KWP: u8 = 0x01;
+
+/// The tag of blobs with ECIES encrypted key material.
+const TAG_ECIES: u8 = 0x02;
+
+/// The length of the AES key of `TAG_AES_KWP` blobs in bytes.
+const AES_KEY_LEN: usize = 32;
+
+/// Wraps and unwraps key material under the KEKs of the security module, see the module
+/// documentation.
+#[derive(Debug, Default)]
+pub struct KeyWrapper {
+    keks: BTreeMap<String, Arc<Mutex<dyn Provider>>>,
+    unwrap_timing: FailureTiming,
+}
+
+impl KeyWrapper {
+    /// Creates a key wrapper without KEKs.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the KEK `kek_id`, replacing a previous KEK of that id.
+    ///
+    /// # Arguments
+    ///
+    /// * `kek_id` - The id the KEK is selected with in `wrap` and `unwrap`.
+    /// * `provider` - The provider with the KEK created or loaded. RSA and symmetric keys must
+    ///   support `encrypt_data` and `decrypt_data`, EC keys `derive_shared_secret`.
+    pub fn with_kek(
+        mut self,
+        kek_id: impl Into<String>,
+        provider: Arc<Mutex<dyn Provider>>,
+    ) -> Self {
+        self.keks.insert(kek_id.into(), provider);
+        self
+    }
+
+    /// Returns the ids of the KEKs in sorted order.
+    pub fn kek_ids(&self) -> impl Iterator<Item = &str> {
+        self.keks.keys().map(String::as_str)
+    }
+
+    /// Wraps `key_material` under the KEK `kek_id`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the blob on success, a `SecurityModuleError::KeyError` if there is
+    /// no KEK `kek_id`, a `SecurityModuleError::Encoding` if the key material is empty or longer
+    /// than `MAX_KEY_MATERIAL_LEN`, or the error of the provider if the KEK cannot encrypt.
+    #[tracing::instrument(skip(self, key_material), fields(crypto.payload.size = key_material.len()))]
+    pub fn wrap(&self, kek_id: &str, key_material: &[u8]) -> Result<Vec<u8>, SecurityModuleError> {
+        if key_material.is_empty() {
+            return Err(CoreError::MissingField("key material").into());
+        }
+        if key_material.len() > MAX_KEY_MATERIAL_LEN {
+            return Err(CoreError::InvalidField("key material").into());
+        }
+        let provider = self.kek(kek_id)?;
+
+        if let Some(public_key) = ecies_public_key(&*provider) {
+            let mut blob = vec![TAG_ECIES];
+            blob.extend(ecies::encrypt_for(&public_key, key_material)?);
+            return Ok(blob);
+        }
+
+        let mut aes_key = SecretBytes::zeroed(AES_KEY_LEN);
+        rand_bytes(&mut aes_key)
+            .map_err(|e| SecurityModuleError::EncryptionError(e.to_string()))?;
+        provider.encrypt_data(&aes_key).and_then(|encrypted_key| {
+            let len = u16::try_from(encrypted_key.len()).map_err(|_| {
+                SecurityModuleError::EncryptionError(format!(
+                    "The encrypted AES key is {} bytes long",
+                    encrypted_key.len()
+                ))
+            })?;
+            let wrapped = aes_kwp_wrap(&aes_key, key_material)
+                .map_err(|e| SecurityModuleError::EncryptionError(e.to_string()))?;
+            let mut blob = Vec::with_capacity(3 + encrypted_key.len() + wrapped.len());
+            blob.push(TAG_AES_KWP);
+            blob.extend(len.to_be_bytes());
+            blob.extend(encrypted_key);
+            blob.extend(wrapped);
+            Ok(blob)
+        })
+    }
+
+    /// Unwraps a blob of `wrap` with the KEK `kek_id` and returns the key material.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the key material, which is zeroed when dropped, on success, a
+    /// `SecurityModuleError::KeyError` if there is no KEK `kek_id`, a
+    /// `SecurityModuleError::DecryptionError` if the blob is truncated, was wrapped under another
+    /// KEK or was modified, or the error of the provider if the KEK cannot decrypt for another
+    /// reason. Failures caused by the blob are reported uniformly, see `uniform_errors`.
+    #[tracing::instrument(skip(self, blob), fields(crypto.payload.size = blob.len()))]
+    pub fn unwrap(&self, kek_id: &str, blob: &[u8]) -> Result<SecretBytes, SecurityModuleError> {
+        let provider = self.kek(kek_id)?;
+        uniform_errors::decryption(&self.unwrap_timing, || {
+            let (&tag, rest) = blob.split_first().ok_or(CoreError::Truncated)?;
+            let expected = match ecies_public_key(&*provider) {
+                Some(_) => TAG_ECIES,
+                None => TAG_AES_KWP,
+            };
+            if tag != expected {
+                return Err(SecurityModuleError::DecryptionError(format!(
+                    "The key material was not wrapped under the KEK '{}'",
+                    kek_id
+                )));
+            }
+            if tag == TAG_ECIES {
+                return ecies::decrypt(&*provider, rest);
+            }
+
+            let (len, rest) = rest.split_first_chunk::<2>().ok_or(CoreError::Truncated)?;
+            let len = u16::from_be_bytes(*len) as usize;
+            if rest.len() <= len {
+                return Err(CoreError::Truncated.into());
+            }
+            let (encrypted_key, wrapped) = rest.split_at(len);
+            let aes_key = provider.decrypt_data(encrypted_key)?;
+            if aes_key.len() != AES_KEY_LEN {
+                return Err(SecurityModuleError::DecryptionError(
+                    "The AES key has the wrong length".to_owned(),
+                ));
+            }
+            aes_kwp_unwrap(&aes_key, wrapped).map_err(|_| {
+                SecurityModuleError::DecryptionError(
+                    "The key material cannot be unwrapped".to_owned(),
+                )
+            })
+        })
+    }
+
+    fn kek(
+        &self,
+        kek_id: &str,
+    ) -> Result<MutexGuard<'_, dyn Provider + 'static>, SecurityModuleError> {
+        let provider = self.keks.get(kek_id).ok_or(SecurityModuleError::KeyError)?;
+        Ok(provider.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+}
+
+/// Returns the public key of an EC KEK, whose key material is encrypted with ECIES.
+fn ecies_public_key(provider: &dyn Provider) -> Option<PublicKey> {
+    let metadata = provider.key_metadata().ok()?;
+    matches!(
+        metadata.public_key().algorithm(),
+        AsymmetricEncryption::Ecc(_)
+    )
+    .then(|| metadata.public_key().clone())
+}
@@ -0,0 +1,508 @@
+use crate::common::{
+    crypto::{
+        key_metadata::KeyMetadata, key_spec::KeySpec, operation_context::OperationContext,
+        secret::SecretBytes,
+    },
+    diagnostics,
+    error::SecurityModuleError,
+    key_stats,
+    session_pool::SessionPoolMetrics,
+    telemetry::CorrelationId,
+    traits::{key_handle::KeyHandle, module_provider::Provider},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    any::Any,
+    collections::BTreeMap,
+    fmt,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+    time::{Duration, Instant},
+};
+
+/// The number of histogram buckets. Bucket `i` counts durations below `2^i` microseconds that
+/// do not fit into a lower bucket, the last bucket counts everything above.
+const BUCKETS: usize = 32;
+
+/// The slow-operation threshold used unless configured otherwise.
+pub const DEFAULT_SLOW_THRESHOLD: Duration = Duration::from_millis(500);
+
+/// Configuration of the latency recording of a provider.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LatencyConfig {
+    /// Operations taking at least this long are logged as a warning.
+    /// `None` disables slow-operation logging.
+    pub slow_threshold: Option<Duration>,
+}
+
+impl Default for LatencyConfig {
+    fn default() -> Self {
+        Self {
+            slow_threshold: Some(DEFAULT_SLOW_THRESHOLD),
+        }
+    }
+}
+
+/// The provider calls whose latency is recorded.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProviderOperation {
+    CreateKey,
+    LoadKey,
+    ImportWrappedKey,
+    ExportPrivateKey,
+    InitializeModule,
+    SignData,
+    DecryptData,
+    EncryptData,
+    VerifySignature,
+    VerifyMany,
+    DeriveSharedSecret,
+    DeleteKey,
+}
+
+impl fmt::Display for ProviderOperation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ProviderOperation::CreateKey => "create_key",
+            ProviderOperation::LoadKey => "load_key",
+            ProviderOperation::ImportWrappedKey => "import_wrapped_key",
+            ProviderOperation::ExportPrivateKey => "export_private_key",
+            ProviderOperation::InitializeModule => "initialize_module",
+            ProviderOperation::SignData => "sign_data",
+            ProviderOperation::DecryptData => "decrypt_data",
+            ProviderOperation::EncryptData => "encrypt_data",
+            ProviderOperation::VerifySignature => "verify_signature",
+            ProviderOperation::VerifyMany => "verify_many",
+            ProviderOperation::DeriveSharedSecret => "derive_shared_secret",
+            ProviderOperation::DeleteKey => "delete_key",
+        };
+        f.write_str(name)
+    }
+}
+
+/// A histogram of durations with exponentially growing buckets.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LatencyHistogram {
+    buckets: [u64; BUCKETS],
+    count: u64,
+    total: Duration,
+    min: Duration,
+    max: Duration,
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        Self {
+            buckets: [0; BUCKETS],
+            count: 0,
+            total: Duration::ZERO,
+            min: Duration::MAX,
+            max: Duration::ZERO,
+        }
+    }
+}
+
+impl LatencyHistogram {
+    /// Adds a duration to the histogram.
+    pub fn record(&mut self, duration: Duration) {
+        let micros = duration.as_micros();
+        let bucket = if micros == 0 {
+            0
+        } else {
+            (u128::BITS - micros.leading_zeros()) as usize
+        };
+        self.buckets[bucket.min(BUCKETS - 1)] += 1;
+        self.count += 1;
+        self.total = self.total.saturating_add(duration);
+        self.min = self.min.min(duration);
+        self.max = self.max.max(duration);
+    }
+
+    /// Returns the number of recorded durations.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Returns the sum of all recorded durations.
+    pub fn total(&self) -> Duration {
+        self.total
+    }
+
+    /// Returns the shortest recorded duration, or `None` if the histogram is empty.
+    pub fn min(&self) -> Option<Duration> {
+        (self.count > 0).then_some(self.min)
+    }
+
+    /// Returns the longest recorded duration, or `None` if the histogram is empty.
+    pub fn max(&self) -> Option<Duration> {
+        (self.count > 0).then_some(self.max)
+    }
+
+    /// Returns the mean of all recorded durations, or `None` if the histogram is empty.
+    pub fn mean(&self) -> Option<Duration> {
+        (self.count > 0)
+            .then(|| Duration::from_nanos((self.total.as_nanos() / u128::from(self.count)) as u64))
+    }
+
+    /// Returns an upper bound of the given percentile.
+    ///
+    /// The result is the upper bound of the bucket containing the percentile, capped at the
+    /// longest recorded duration, so it overestimates the exact value by less than a factor of 2.
+    ///
+    /// # Arguments
+    ///
+    /// * `percentile` - The percentile between `0.0` and `100.0`, e.g. `99.0`.
+    ///
+    /// # Returns
+    ///
+    /// The estimated duration, or `None` if the histogram is empty.
+    pub fn percentile(&self, percentile: f64) -> Option<Duration> {
+        if self.count == 0 {
+            return None;
+        }
+        let rank = ((percentile.clamp(0.0, 100.0) / 100.0) * self.count as f64).ceil() as u64;
+        let mut seen = 0;
+        for (upper_bound, count) in self.buckets() {
+            seen += count;
+            if seen >= rank.max(1) {
+                return Some(upper_bound.min(self.max));
+            }
+        }
+        Some(self.max)
+    }
+
+    /// Returns the upper bound and count of every bucket, in ascending order.
+    ///
+    /// The upper bound of the last bucket is `Duration::MAX`.
+    pub fn buckets(&self) -> Vec<(Duration, u64)> {
+        bucket_upper_bounds().zip(self.buckets).collect()
+    }
+}
+
+fn bucket_upper_bounds() -> impl Iterator<Item = Duration> {
+    (0..BUCKETS).map(|bucket| match bucket {
+        _ if bucket == BUCKETS - 1 => Duration::MAX,
+        _ => Duration::from_micros(1 << bucket),
+    })
+}
+
+/// A snapshot of the latencies recorded by a provider.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LatencySnapshot {
+    /// The latency histogram of every operation that was called at least once.
+    pub operations: BTreeMap<ProviderOperation, LatencyHistogram>,
+    /// The number of operations that exceeded the slow-operation threshold.
+    pub slow_operations: u64,
+}
+
+impl LatencySnapshot {
+    /// Returns the histogram of the given operation, or `None` if it was never called.
+    pub fn get(&self, operation: ProviderOperation) -> Option<&LatencyHistogram> {
+        self.operations.get(&operation)
+    }
+}
+
+/// Records the latency of operations and logs slow ones.
+#[derive(Debug, Default)]
+pub struct LatencyRecorder {
+    config: LatencyConfig,
+    histograms: Mutex<BTreeMap<ProviderOperation, LatencyHistogram>>,
+    slow_operations: AtomicU64,
+}
+
+impl LatencyRecorder {
+    /// Creates a recorder without any recorded latencies.
+    pub fn new(config: LatencyConfig) -> Self {
+        Self {
+            config,
+            ..Default::default()
+        }
+    }
+
+    /// Returns the configuration of the recorder.
+    pub fn config(&self) -> &LatencyConfig {
+        &self.config
+    }
+
+    /// Records the duration of an operation.
+    ///
+    /// If the duration reaches the slow-operation threshold, a warning containing the operation,
+    /// a hash of the key id and the duration is logged. The key id itself is not logged.
+    pub fn record(&self, operation: ProviderOperation, key_id: &str, duration: Duration) {
+        self.lock().entry(operation).or_default().record(duration);
+
+        if self
+            .config
+            .slow_threshold
+            .is_some_and(|threshold| duration >= threshold)
+        {
+            self.slow_operations.fetch_add(1, Ordering::Relaxed);
+            tracing::warn!(
+                %operation,
+                key_id_hash = %key_id_hash(key_id),
+                duration_ms = duration.as_secs_f64() * 1000.0,
+                "Slow security module operation"
+            );
+        }
+    }
+
+    /// Runs `f` and records how long it took.
+    pub fn time<T>(&self, operation: ProviderOperation, key_id: &str, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        self.record(operation, key_id, start.elapsed());
+        result
+    }
+
+    /// Returns a copy of all recorded latencies.
+    pub fn snapshot(&self) -> LatencySnapshot {
+        LatencySnapshot {
+            operations: self.lock().clone(),
+            slow_operations: self.slow_operations.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Discards all recorded latencies.
+    pub fn reset(&self) {
+        self.lock().clear();
+        self.slow_operations.store(0, Ordering::Relaxed);
+    }
+
+    fn lock(&self) -> MutexGuard<'_, BTreeMap<ProviderOperation, LatencyHistogram>> {
+        self.histograms
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Returns a short, stable hash of a key id, used to correlate log entries without revealing
+/// the key id.
+pub fn key_id_hash(key_id: &str) -> String {
+    openssl::sha::sha256(key_id.as_bytes())[..8]
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// A provider that records the latency of every call to the wrapped provider.
+///
+/// `SecModules::get_instance` wraps every provider it creates, so that latencies are available
+/// through `Provider::latency_snapshot` without any support from the provider itself. Every call
+/// runs with a `CorrelationId`, see `common::telemetry`.
+#[derive(Debug)]
+pub struct TimedProvider {
+    inner: Arc<Mutex<dyn Provider>>,
+    key_id: Mutex<String>,
+    recorder: LatencyRecorder,
+}
+
+impl TimedProvider {
+    /// Wraps a provider.
+    ///
+    /// # Arguments
+    ///
+    /// * `inner` - The provider whose calls are timed.
+    /// * `key_id` - The id of the key the provider operates on, until `create_key` or
+    ///   `load_key` is called with another one.
+    /// * `config` - The slow-operation threshold.
+    pub fn new(inner: Arc<Mutex<dyn Provider>>, key_id: String, config: LatencyConfig) -> Self {
+        Self {
+            inner,
+            key_id: Mutex::new(key_id),
+            recorder: LatencyRecorder::new(config),
+        }
+    }
+
+    fn inner(&self) -> MutexGuard<'_, dyn Provider + 'static> {
+        self.inner
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn key_id(&self) -> MutexGuard<'_, String> {
+        self.key_id
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Runs `f` with a correlation id, records how long it took, counts it in the usage
+    /// statistics of the current key and remembers a returned error for
+    /// `diagnostics::support_bundle`.
+    fn time<T>(
+        &self,
+        operation: ProviderOperation,
+        f: impl FnOnce() -> Result<T, SecurityModuleError>,
+    ) -> Result<T, SecurityModuleError> {
+        let key_id = self.key_id().clone();
+        self.time_key(&key_id, operation, f)
+    }
+
+    /// Like `time`, but charges the call to the key `key_id`, which need not be the current key.
+    fn time_key<T>(
+        &self,
+        key_id: &str,
+        operation: ProviderOperation,
+        f: impl FnOnce() -> Result<T, SecurityModuleError>,
+    ) -> Result<T, SecurityModuleError> {
+        CorrelationId::scope(|_| {
+            let result = self.recorder.time(operation, key_id, f);
+            key_stats::record(key_id, operation, result.is_ok());
+            if let Err(error) = &result {
+                diagnostics::record_error(operation, key_id, error);
+            }
+            result
+        })
+    }
+
+    /// Runs `f`, which creates or loads the key `key_id`, and makes it the current key if `f`
+    /// succeeds. A failed call leaves the previous key loaded, so later calls are still charged
+    /// to it.
+    fn switch_key(
+        &self,
+        key_id: &str,
+        operation: ProviderOperation,
+        f: impl FnOnce() -> Result<(), SecurityModuleError>,
+    ) -> Result<(), SecurityModuleError> {
+        self.time_key(key_id, operation, f)?;
+        *self.key_id() = key_id.to_owned();
+        Ok(())
+    }
+}
+
+impl KeyHandle for TimedProvider {
+    fn sign_data(&self, data: &[u8]) -> Result<Vec<u8>, SecurityModuleError> {
+        self.time(ProviderOperation::SignData, || self.inner().sign_data(data))
+    }
+
+    fn decrypt_data(&self, encrypted_data: &[u8]) -> Result<SecretBytes, SecurityModuleError> {
+        self.time(ProviderOperation::DecryptData, || {
+            self.inner().decrypt_data(encrypted_data)
+        })
+    }
+
+    fn encrypt_data(&self, data: &[u8]) -> Result<Vec<u8>, SecurityModuleError> {
+        self.time(ProviderOperation::EncryptData, || {
+            self.inner().encrypt_data(data)
+        })
+    }
+
+    fn verify_signature(&self, data: &[u8], signature: &[u8]) -> Result<bool, SecurityModuleError> {
+        self.time(ProviderOperation::VerifySignature, || {
+            self.inner().verify_signature(data, signature)
+        })
+    }
+
+    fn sign_data_into<'a>(
+        &self,
+        data: &[u8],
+        context: &'a mut OperationContext,
+    ) -> Result<&'a [u8], SecurityModuleError> {
+        self.time(ProviderOperation::SignData, || {
+            self.inner().sign_data_into(data, context)
+        })
+    }
+
+    fn verify_signature_with(
+        &self,
+        data: &[u8],
+        signature: &[u8],
+        context: &mut OperationContext,
+    ) -> Result<bool, SecurityModuleError> {
+        self.time(ProviderOperation::VerifySignature, || {
+            self.inner().verify_signature_with(data, signature, context)
+        })
+    }
+
+    fn verify_many(&self, items: &[(&[u8], &[u8])]) -> Result<Vec<bool>, SecurityModuleError> {
+        self.time(ProviderOperation::VerifyMany, || {
+            self.inner().verify_many(items)
+        })
+    }
+
+    fn derive_shared_secret(
+        &self,
+        peer_public_key: &[u8],
+    ) -> Result<SecretBytes, SecurityModuleError> {
+        self.time(ProviderOperation::DeriveSharedSecret, || {
+            self.inner().derive_shared_secret(peer_public_key)
+        })
+    }
+}
+
+impl Provider for TimedProvider {
+    fn create_key(
+        &mut self,
+        key_id: &str,
+        config: Box<dyn Any>,
+    ) -> Result<(), SecurityModuleError> {
+        self.switch_key(key_id, ProviderOperation::CreateKey, || {
+            self.inner().create_key(key_id, config)
+        })
+    }
+
+    fn load_key(&mut self, key_id: &str, config: Box<dyn Any>) -> Result<(), SecurityModuleError> {
+        self.switch_key(key_id, ProviderOperation::LoadKey, || {
+            self.inner().load_key(key_id, config)
+        })
+    }
+
+    fn import_wrapped_key(
+        &mut self,
+        key_id: &str,
+        wrapped_key: &[u8],
+        wrapping_key_id: &str,
+        spec: &KeySpec,
+    ) -> Result<(), SecurityModuleError> {
+        self.switch_key(key_id, ProviderOperation::ImportWrappedKey, || {
+            self.inner()
+                .import_wrapped_key(key_id, wrapped_key, wrapping_key_id, spec)
+        })
+    }
+
+    fn export_private_key(&self) -> Result<SecretBytes, SecurityModuleError> {
+        self.time(ProviderOperation::ExportPrivateKey, || {
+            self.inner().export_private_key()
+        })
+    }
+
+    fn initialize_module(&mut self) -> Result<(), SecurityModuleError> {
+        self.time(ProviderOperation::InitializeModule, || {
+            self.inner().initialize_module()
+        })
+    }
+
+    fn session_pool_metrics(&self) -> Option<SessionPoolMetrics> {
+        self.inner().session_pool_metrics()
+    }
+
+    fn latency_snapshot(&self) -> Option<LatencySnapshot> {
+        Some(self.recorder.snapshot())
+    }
+
+    fn key_metadata(&self) -> Result<KeyMetadata, SecurityModuleError> {
+        self.inner().key_metadata()
+    }
+
+    fn refresh_key_metadata(&mut self) -> Result<KeyMetadata, SecurityModuleError> {
+        self.inner().refresh_key_metadata()
+    }
+
+    fn list_keys(&self) -> Result<Vec<String>, SecurityModuleError> {
+        self.inner().list_keys()
+    }
+
+    fn delete_key(&mut self, key_id: &str) -> Result<(), SecurityModuleError> {
+        self.time(ProviderOperation::DeleteKey, || {
+            self.inner().delete_key(key_id)
+        })
+    }
+
+    fn shutdown(&mut self) -> Result<(), SecurityModuleError> {
+        self.inner().shutdown()
+    }
+}
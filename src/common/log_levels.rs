@@ -0,0 +1,142 @@
+//! Log verbosity per provider, changeable at runtime.
+//!
+//! `ProviderLogFilter` is a `tracing_subscriber` layer that filters spans and events by the
+//! levels configured here. Levels can be changed at any time, e.g. to turn on verbose logging of
+//! the Secure Enclave provider during an incident, without restarting the process:
+//!
+//! ```rust,ignore
+//! use crypto_layer::common::log_levels::{self, ProviderLogFilter};
+//! use tracing::level_filters::LevelFilter;
+//! use tracing_subscriber::prelude::*;
+//!
+//! tracing_subscriber::registry()
+//!     .with(ProviderLogFilter)
+//!     .with(tracing_subscriber::fmt::layer())
+//!     .init();
+//! log_levels::set_default_level(LevelFilter::INFO);
+//!
+//! // During the incident:
+//! log_levels::set_level(&SecurityModule::Tpm(TpmType::MacOs), LevelFilter::TRACE);
+//! // Afterwards:
+//! log_levels::clear_level(&SecurityModule::Tpm(TpmType::MacOs));
+//! ```
+//!
+//! The loggers set up by the `LogConfig` implementations of the providers include the filter.
+//! Until a level is set, everything passes the filter.
+
+use crate::common::factory::SecurityModule;
+#[cfg(feature = "tpm")]
+use crate::tpm::core::instance::TpmType;
+use std::{
+    collections::BTreeMap,
+    sync::{RwLock, RwLockReadGuard, RwLockWriteGuard},
+};
+use tracing::{level_filters::LevelFilter, subscriber::Interest, Metadata, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+
+static LEVELS: RwLock<Levels> = RwLock::new(Levels {
+    default: LevelFilter::TRACE,
+    targets: BTreeMap::new(),
+});
+
+#[derive(Debug, Clone)]
+struct Levels {
+    default: LevelFilter,
+    targets: BTreeMap<String, LevelFilter>,
+}
+
+impl Levels {
+    /// Returns the level of the longest configured target `target` belongs to.
+    fn level(&self, target: &str) -> LevelFilter {
+        self.targets
+            .iter()
+            .filter(|(prefix, _)| {
+                target
+                    .strip_prefix(prefix.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+            })
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map_or(self.default, |(_, level)| *level)
+    }
+}
+
+fn read() -> RwLockReadGuard<'static, Levels> {
+    LEVELS
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn write() -> RwLockWriteGuard<'static, Levels> {
+    LEVELS
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Returns the module path the spans and events of a provider are logged under.
+pub fn target(module: &SecurityModule) -> &'static str {
+    match module {
+        #[cfg(feature = "hsm")]
+        SecurityModule::Hsm(_) => "crypto_layer::hsm",
+        #[cfg(feature = "tpm")]
+        SecurityModule::Tpm(tpm_type) => match tpm_type {
+            #[cfg(feature = "win")]
+            TpmType::Windows => "crypto_layer::tpm::win",
+            #[cfg(feature = "macos")]
+            TpmType::MacOs => "crypto_layer::tpm::macos",
+            #[cfg(feature = "linux")]
+            TpmType::Linux => "crypto_layer::tpm::linux",
+            #[cfg(feature = "android")]
+            TpmType::Android(_) => "crypto_layer::tpm::android",
+            TpmType::None => "crypto_layer::tpm",
+        },
+        #[cfg(feature = "nks")]
+        SecurityModule::Nks => "crypto_layer::nks",
+    }
+}
+
+/// Sets the level of a provider, overriding the default level.
+pub fn set_level(module: &SecurityModule, level: LevelFilter) {
+    set_target_level(target(module), level);
+}
+
+/// Removes the level of a provider, so that the default level applies again.
+pub fn clear_level(module: &SecurityModule) {
+    clear_target_level(target(module));
+}
+
+/// Sets the level of all spans and events whose target is `target` or one of its submodules,
+/// e.g. `crypto_layer::common::session_pool`.
+pub fn set_target_level(target: impl Into<String>, level: LevelFilter) {
+    write().targets.insert(target.into(), level);
+}
+
+/// Removes the level of a target set by `set_target_level`.
+pub fn clear_target_level(target: &str) {
+    write().targets.remove(target);
+}
+
+/// Sets the level of all targets without a level of their own.
+pub fn set_default_level(level: LevelFilter) {
+    write().default = level;
+}
+
+/// Returns the level of the default and of every target with a level of its own.
+pub fn levels() -> (LevelFilter, BTreeMap<String, LevelFilter>) {
+    let levels = read();
+    (levels.default, levels.targets.clone())
+}
+
+/// A layer filtering spans and events by the levels configured in this module.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProviderLogFilter;
+
+impl<S: Subscriber> Layer<S> for ProviderLogFilter {
+    fn register_callsite(&self, _metadata: &'static Metadata<'static>) -> Interest {
+        // The levels can change at any time, so every span and event is checked when it occurs.
+        Interest::sometimes()
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>, _ctx: Context<'_, S>) -> bool {
+        *metadata.level() <= read().level(metadata.target())
+    }
+}
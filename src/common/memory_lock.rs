@@ -0,0 +1,217 @@
+//! Locking of secret buffers into RAM.
+//!
+//! Plaintexts, unwrapped keys and the keys exported by the software provider are held in
+//! `SecretBytes`, which are zeroed when dropped. While they are alive, the OS may still write
+//! them to swap or include them in a core dump. When locking is enabled, the buffers of all
+//! `SecretBytes` allocated afterwards are locked into RAM with `mlock` or `VirtualLock`, and on
+//! Linux and Android excluded from core dumps with `MADV_DONTDUMP`:
+//!
+//! ```toml
+//! [memory]
+//! lock_secrets = true
+//! ```
+//!
+//! Locking is best effort. If the OS denies it, e.g. because `RLIMIT_MEMLOCK` is exhausted, a
+//! warning is logged once and the buffers are used unlocked. Locks apply to whole pages, so the
+//! buffers sharing a page are counted and the page is unlocked with the last of them.
+
+use crate::common::crypto::secret::{self, BufferHooks};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex, MutexGuard, OnceLock,
+    },
+};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Whether a failure to lock was logged.
+static WARNED: AtomicBool = AtomicBool::new(false);
+
+static HOOKS: BufferHooks = BufferHooks {
+    allocated: lock,
+    released: unlock,
+};
+
+static LOCKS: Mutex<Locks> = Mutex::new(Locks {
+    buffers: BTreeSet::new(),
+    pages: BTreeMap::new(),
+});
+
+#[derive(Debug)]
+struct Locks {
+    /// The start and length of the locked buffers.
+    buffers: BTreeSet<(usize, usize)>,
+    /// The number of locked buffers on each page, by page address.
+    pages: BTreeMap<usize, usize>,
+}
+
+fn locks() -> MutexGuard<'static, Locks> {
+    LOCKS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Enables or disables locking of the `SecretBytes` allocated afterwards, in the whole process.
+/// Buffers that are already locked stay locked until they are dropped.
+pub fn set_enabled(enabled: bool) {
+    if enabled {
+        secret::set_buffer_hooks(&HOOKS);
+    }
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Returns whether new `SecretBytes` are locked into RAM.
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Returns whether the buffer starting at `bytes` was locked when it was allocated, i.e. whether
+/// the OS was asked to lock it. Locking may still have been denied, which is logged.
+pub fn is_locked(bytes: &[u8]) -> bool {
+    let start = bytes.as_ptr() as usize;
+    locks()
+        .buffers
+        .range((start, 0)..=(start, usize::MAX))
+        .next()
+        .is_some()
+}
+
+fn page_size() -> usize {
+    static PAGE_SIZE: OnceLock<usize> = OnceLock::new();
+    *PAGE_SIZE.get_or_init(os::page_size)
+}
+
+/// Returns the addresses of the pages `len` bytes at `start` lie on.
+fn pages(start: usize, len: usize) -> impl Iterator<Item = usize> {
+    let page_size = page_size();
+    let first = start / page_size * page_size;
+    (first..start + len).step_by(page_size)
+}
+
+fn lock(ptr: *const u8, len: usize) {
+    if !is_enabled() {
+        return;
+    }
+    let start = ptr as usize;
+    let mut locks = locks();
+    locks.buffers.insert((start, len));
+    for page in pages(start, len) {
+        let count = locks.pages.entry(page).or_insert(0);
+        *count += 1;
+        if *count == 1 {
+            if let Err(e) = os::lock(page, page_size()) {
+                if !WARNED.swap(true, Ordering::Relaxed) {
+                    tracing::warn!(error = %e, "Cannot lock secrets into RAM, using them unlocked");
+                }
+            }
+        }
+    }
+}
+
+fn unlock(ptr: *const u8, len: usize) {
+    let start = ptr as usize;
+    let mut locks = locks();
+    if !locks.buffers.remove(&(start, len)) {
+        return;
+    }
+    for page in pages(start, len) {
+        let count = locks
+            .pages
+            .get_mut(&page)
+            .expect("locked pages are counted");
+        *count -= 1;
+        if *count == 0 {
+            locks.pages.remove(&page);
+            // The page is unlocked anyway when it is unmapped.
+            let _ = os::unlock(page, page_size());
+        }
+    }
+}
+
+#[cfg(unix)]
+mod os {
+    use std::io;
+
+    pub(super) fn page_size() -> usize {
+        // SAFETY: `sysconf` has no preconditions.
+        match unsafe { libc::sysconf(libc::_SC_PAGESIZE) } {
+            size if size > 0 => size as usize,
+            _ => 4096,
+        }
+    }
+
+    pub(super) fn lock(page: usize, len: usize) -> io::Result<()> {
+        // SAFETY: Locking and advising pages does not change their content.
+        unsafe {
+            if libc::mlock(page as *const libc::c_void, len) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            libc::madvise(page as *mut libc::c_void, len, libc::MADV_DONTDUMP);
+        }
+        Ok(())
+    }
+
+    pub(super) fn unlock(page: usize, len: usize) -> io::Result<()> {
+        // SAFETY: See `lock`.
+        unsafe {
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            libc::madvise(page as *mut libc::c_void, len, libc::MADV_DODUMP);
+            if libc::munlock(page as *const libc::c_void, len) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(windows)]
+mod os {
+    use std::{ffi::c_void, io};
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn VirtualLock(address: *const c_void, size: usize) -> i32;
+        fn VirtualUnlock(address: *const c_void, size: usize) -> i32;
+    }
+
+    /// The page size of all architectures Windows runs on.
+    pub(super) fn page_size() -> usize {
+        4096
+    }
+
+    pub(super) fn lock(page: usize, len: usize) -> io::Result<()> {
+        // SAFETY: Locking pages does not change their content.
+        match unsafe { VirtualLock(page as *const c_void, len) } {
+            0 => Err(io::Error::last_os_error()),
+            _ => Ok(()),
+        }
+    }
+
+    pub(super) fn unlock(page: usize, len: usize) -> io::Result<()> {
+        // SAFETY: See `lock`.
+        match unsafe { VirtualUnlock(page as *const c_void, len) } {
+            0 => Err(io::Error::last_os_error()),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+mod os {
+    use std::io;
+
+    pub(super) fn page_size() -> usize {
+        4096
+    }
+
+    pub(super) fn lock(_page: usize, _len: usize) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    pub(super) fn unlock(_page: usize, _len: usize) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,573 @@
+//! Durable metadata of keys that survives process restarts.
+//!
+//! Security modules only remember the key material, while features like key rotation, usage
+//! restrictions or retiring unused keys need to know more about a key: its labels, which
+//! operations it may be used for, its version and how it was used. A `MetadataStore` keeps this
+//! metadata as one `KeyRecord` per key id, independently of the provider holding the key, so the
+//! same record is seen whichever provider the key is used through:
+//!
+//! ```rust,ignore
+//! use crypto_layer::common::metadata_store::{FileMetadataStore, MetadataProvider, MetadataStore};
+//!
+//! let store = Arc::new(FileMetadataStore::new("/var/lib/app/metadata")?);
+//! let mut provider = MetadataProvider::new(provider, "device".to_owned(), store.clone());
+//! provider.create_key("device", config)?; // creates version 1 of the record
+//! store.update("device", &mut |record| {
+//!     record.labels.insert("owner".to_owned(), "telemetry".to_owned());
+//!     record.usage = Some([ProviderOperation::SignData].into());
+//! })?;
+//! provider.encrypt_data(b"data")?; // fails, the key may only sign
+//! ```
+//!
+//! `MemoryMetadataStore` keeps the records in memory, `FileMetadataStore` in one JSON file per
+//! key, and on macOS `tpm::macos::metadata_store::KeychainMetadataStore` in attributes of keychain
+//! items. Records only hold metadata, never key material, so the stores need not be confidential.
+
+use crate::common::{
+    crypto::{
+        key_metadata::KeyMetadata, key_spec::KeySpec, operation_context::OperationContext,
+        secret::SecretBytes,
+    },
+    error::SecurityModuleError,
+    latency::{key_id_hash, LatencySnapshot, ProviderOperation},
+    session_pool::SessionPoolMetrics,
+    traits::{key_handle::KeyHandle, module_provider::Provider},
+    vault::{decode_file_name, encode_file_name},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    any::Any,
+    collections::{BTreeMap, BTreeSet},
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// The usage of a key recorded by a `MetadataProvider`, unlike `key_stats::KeyStats` kept across
+/// process restarts.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UsageStats {
+    /// The number of calls of every operation performed at least once, including failed ones.
+    pub operations: BTreeMap<ProviderOperation, u64>,
+    /// The number of failed calls.
+    pub failures: u64,
+    /// When the key was last used successfully, in milliseconds since the Unix epoch, or `None`
+    /// if every operation failed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_used: Option<u64>,
+}
+
+/// The metadata of one key.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeyRecord {
+    pub key_id: String,
+    /// Free-form labels, e.g. the owner or purpose of the key.
+    pub labels: BTreeMap<String, String>,
+    /// The operations the key may be used for, or `None` if it may be used for all of them.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usage: Option<BTreeSet<ProviderOperation>>,
+    /// The version of the key, starting at 1 and incremented whenever a key is created or
+    /// imported under an id that already has a record.
+    pub version: u32,
+    /// When the first version of the key was created, in milliseconds since the Unix epoch.
+    pub created_at: u64,
+    pub stats: UsageStats,
+}
+
+impl KeyRecord {
+    /// Creates the record of version 1 of a key, created now.
+    pub fn new(key_id: &str) -> Self {
+        Self {
+            key_id: key_id.to_owned(),
+            labels: BTreeMap::new(),
+            usage: None,
+            version: 1,
+            created_at: now(),
+            stats: UsageStats::default(),
+        }
+    }
+
+    /// Returns whether the usage policy of the key allows `operation`.
+    pub fn allows(&self, operation: ProviderOperation) -> bool {
+        self.usage
+            .as_ref()
+            .is_none_or(|usage| usage.contains(&operation))
+    }
+
+    /// Counts a call of `operation`.
+    pub fn record_use(&mut self, operation: ProviderOperation, succeeded: bool) {
+        *self.stats.operations.entry(operation).or_default() += 1;
+        if succeeded {
+            self.stats.last_used = Some(now());
+        } else {
+            self.stats.failures += 1;
+        }
+    }
+}
+
+impl Default for KeyRecord {
+    fn default() -> Self {
+        Self::new("")
+    }
+}
+
+/// Stores the `KeyRecord`s of keys.
+pub trait MetadataStore: Send + Sync {
+    /// Returns the record of `key_id`, or `None` if there is none.
+    fn load(&self, key_id: &str) -> Result<Option<KeyRecord>, SecurityModuleError>;
+
+    /// Stores `record` under its key id, replacing any previous record.
+    fn save(&self, record: &KeyRecord) -> Result<(), SecurityModuleError>;
+
+    /// Removes the record of `key_id` and returns whether there was one.
+    fn delete(&self, key_id: &str) -> Result<bool, SecurityModuleError>;
+
+    /// Returns the ids of all keys with a record in sorted order.
+    fn key_ids(&self) -> Result<Vec<String>, SecurityModuleError>;
+
+    /// Applies `f` to the record of `key_id`, or to a new record if there is none, and stores
+    /// the result.
+    ///
+    /// The default implementation loads and saves the record, stores that are shared between
+    /// threads override it to do both under a lock.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the stored record on success, or the error of the store.
+    fn update(
+        &self,
+        key_id: &str,
+        f: &mut dyn FnMut(&mut KeyRecord),
+    ) -> Result<KeyRecord, SecurityModuleError> {
+        let mut record = self.load(key_id)?.unwrap_or_else(|| KeyRecord::new(key_id));
+        f(&mut record);
+        self.save(&record)?;
+        Ok(record)
+    }
+}
+
+/// A `MetadataStore` keeping the records in memory, e.g. for tests.
+#[derive(Debug, Default)]
+pub struct MemoryMetadataStore {
+    records: Mutex<BTreeMap<String, KeyRecord>>,
+}
+
+impl MemoryMetadataStore {
+    /// Creates an empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn records(&self) -> MutexGuard<'_, BTreeMap<String, KeyRecord>> {
+        self.records.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl MetadataStore for MemoryMetadataStore {
+    fn load(&self, key_id: &str) -> Result<Option<KeyRecord>, SecurityModuleError> {
+        Ok(self.records().get(key_id).cloned())
+    }
+
+    fn save(&self, record: &KeyRecord) -> Result<(), SecurityModuleError> {
+        self.records().insert(record.key_id.clone(), record.clone());
+        Ok(())
+    }
+
+    fn delete(&self, key_id: &str) -> Result<bool, SecurityModuleError> {
+        Ok(self.records().remove(key_id).is_some())
+    }
+
+    fn key_ids(&self) -> Result<Vec<String>, SecurityModuleError> {
+        Ok(self.records().keys().cloned().collect())
+    }
+
+    fn update(
+        &self,
+        key_id: &str,
+        f: &mut dyn FnMut(&mut KeyRecord),
+    ) -> Result<KeyRecord, SecurityModuleError> {
+        let mut records = self.records();
+        let record = records
+            .entry(key_id.to_owned())
+            .or_insert_with(|| KeyRecord::new(key_id));
+        f(record);
+        Ok(record.clone())
+    }
+}
+
+/// A `MetadataStore` keeping every record in a JSON file of a directory.
+///
+/// The file name is the hex encoded key id with the extension `.json`, like the files of
+/// `vault::FileStorage`. Records are written to a temporary file first and then renamed, so a
+/// crash never leaves a partially written record behind.
+#[derive(Debug)]
+pub struct FileMetadataStore {
+    dir: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl FileMetadataStore {
+    /// The extension of the files holding records.
+    const EXTENSION: &'static str = "json";
+
+    /// Creates a store in `dir`, creating the directory if it does not exist.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `FileMetadataStore` on success, or a
+    /// `SecurityModuleError::SecretStorage` if the directory cannot be created.
+    pub fn new(dir: impl Into<PathBuf>) -> Result<Self, SecurityModuleError> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir).map_err(|e| store_error("create", &dir, e))?;
+        Ok(Self {
+            dir,
+            lock: Mutex::new(()),
+        })
+    }
+
+    /// Returns the directory holding the records.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn path(&self, key_id: &str) -> PathBuf {
+        self.dir
+            .join(encode_file_name(key_id))
+            .with_extension(Self::EXTENSION)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, ()> {
+        self.lock.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write(&self, record: &KeyRecord) -> Result<(), SecurityModuleError> {
+        let path = self.path(&record.key_id);
+        let temporary = path.with_extension("tmp");
+        // A `KeyRecord` only holds strings, numbers and maps with string keys.
+        let json = serde_json::to_vec_pretty(record).expect("A key record serializes to JSON");
+        fs::write(&temporary, json).map_err(|e| store_error("write", &temporary, e))?;
+        fs::rename(&temporary, &path).map_err(|e| store_error("write", &path, e))
+    }
+}
+
+impl MetadataStore for FileMetadataStore {
+    fn load(&self, key_id: &str) -> Result<Option<KeyRecord>, SecurityModuleError> {
+        let path = self.path(key_id);
+        let json = match fs::read(&path) {
+            Ok(json) => json,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(store_error("read", &path, e)),
+        };
+        serde_json::from_slice(&json).map(Some).map_err(|e| {
+            SecurityModuleError::SecretStorage(format!(
+                "The metadata file '{}' is invalid: {}",
+                path.display(),
+                e
+            ))
+        })
+    }
+
+    fn save(&self, record: &KeyRecord) -> Result<(), SecurityModuleError> {
+        let _lock = self.lock();
+        self.write(record)
+    }
+
+    fn delete(&self, key_id: &str) -> Result<bool, SecurityModuleError> {
+        let path = self.path(key_id);
+        let _lock = self.lock();
+        match fs::remove_file(&path) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(store_error("delete", &path, e)),
+        }
+    }
+
+    fn key_ids(&self) -> Result<Vec<String>, SecurityModuleError> {
+        let entries = fs::read_dir(&self.dir).map_err(|e| store_error("list", &self.dir, e))?;
+        let mut key_ids = Vec::new();
+        for entry in entries {
+            let path = entry.map_err(|e| store_error("list", &self.dir, e))?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some(Self::EXTENSION) {
+                continue;
+            }
+            // Files that do not hold a record are skipped.
+            if let Some(key_id) = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(decode_file_name)
+                .and_then(|key_id| String::from_utf8(key_id).ok())
+            {
+                key_ids.push(key_id);
+            }
+        }
+        key_ids.sort();
+        Ok(key_ids)
+    }
+
+    fn update(
+        &self,
+        key_id: &str,
+        f: &mut dyn FnMut(&mut KeyRecord),
+    ) -> Result<KeyRecord, SecurityModuleError> {
+        let _lock = self.lock();
+        let mut record = self.load(key_id)?.unwrap_or_else(|| KeyRecord::new(key_id));
+        f(&mut record);
+        self.write(&record)?;
+        Ok(record)
+    }
+}
+
+fn store_error(action: &str, path: &Path, e: io::Error) -> SecurityModuleError {
+    SecurityModuleError::SecretStorage(format!(
+        "Cannot {} the metadata '{}': {}",
+        action,
+        path.display(),
+        e
+    ))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_millis() as u64)
+}
+
+/// A provider that keeps the `KeyRecord`s of its keys in a `MetadataStore`.
+///
+/// Creating, importing or loading a key creates its record if there is none, and creating or
+/// importing a key under an id that already has a record increments its version. Deleting a key
+/// deletes its record. Every operation is checked against the usage policy of the record and
+/// fails with `SecurityModuleError::UnsupportedOperation` if the policy does not allow it, and
+/// counted in the `UsageStats` of the record. Failing to store the statistics is logged, but does
+/// not fail the operation.
+pub struct MetadataProvider {
+    inner: Arc<Mutex<dyn Provider>>,
+    key_id: Mutex<String>,
+    store: Arc<dyn MetadataStore>,
+}
+
+impl MetadataProvider {
+    /// Wraps a provider.
+    ///
+    /// # Arguments
+    ///
+    /// * `inner` - The provider holding the keys.
+    /// * `key_id` - The id of the key the provider operates on, until `create_key` or `load_key`
+    ///   is called with another one.
+    /// * `store` - The store keeping the records.
+    pub fn new(
+        inner: Arc<Mutex<dyn Provider>>,
+        key_id: String,
+        store: Arc<dyn MetadataStore>,
+    ) -> Self {
+        Self {
+            inner,
+            key_id: Mutex::new(key_id),
+            store,
+        }
+    }
+
+    /// Returns the record of the current key, or `None` if it has none.
+    pub fn record(&self) -> Result<Option<KeyRecord>, SecurityModuleError> {
+        self.store.load(&self.key_id())
+    }
+
+    fn inner(&self) -> MutexGuard<'_, dyn Provider + 'static> {
+        self.inner
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn key_id(&self) -> MutexGuard<'_, String> {
+        self.key_id
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Runs `f` if the usage policy of the current key allows `operation`, and counts it.
+    fn run<T>(
+        &self,
+        operation: ProviderOperation,
+        f: impl FnOnce() -> Result<T, SecurityModuleError>,
+    ) -> Result<T, SecurityModuleError> {
+        let key_id = self.key_id().clone();
+        if let Some(record) = self.store.load(&key_id)? {
+            if !record.allows(operation) {
+                return Err(SecurityModuleError::UnsupportedOperation(format!(
+                    "The usage policy of the key '{}' does not allow {}",
+                    key_id, operation
+                )));
+            }
+        }
+        let result = f();
+        if let Err(e) = self.store.update(&key_id, &mut |record| {
+            record.record_use(operation, result.is_ok())
+        }) {
+            tracing::warn!(
+                key_id_hash = %key_id_hash(&key_id),
+                error = %e,
+                "Failed to store the usage of a key"
+            );
+        }
+        result
+    }
+
+    /// Runs `f`, which creates or loads the key `key_id`, and creates or updates its record.
+    fn track(
+        &self,
+        key_id: &str,
+        new_version: bool,
+        f: impl FnOnce() -> Result<(), SecurityModuleError>,
+    ) -> Result<(), SecurityModuleError> {
+        f()?;
+        *self.key_id() = key_id.to_owned();
+        let exists = self.store.load(key_id)?.is_some();
+        if exists && !new_version {
+            return Ok(());
+        }
+        self.store
+            .update(key_id, &mut |record| {
+                if exists {
+                    record.version += 1;
+                }
+            })
+            .map(|_| ())
+    }
+}
+
+impl fmt::Debug for MetadataProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MetadataProvider")
+            .field("key_id", &*self.key_id())
+            .finish_non_exhaustive()
+    }
+}
+
+impl KeyHandle for MetadataProvider {
+    fn sign_data(&self, data: &[u8]) -> Result<Vec<u8>, SecurityModuleError> {
+        self.run(ProviderOperation::SignData, || self.inner().sign_data(data))
+    }
+
+    fn decrypt_data(&self, encrypted_data: &[u8]) -> Result<SecretBytes, SecurityModuleError> {
+        self.run(ProviderOperation::DecryptData, || {
+            self.inner().decrypt_data(encrypted_data)
+        })
+    }
+
+    fn encrypt_data(&self, data: &[u8]) -> Result<Vec<u8>, SecurityModuleError> {
+        self.run(ProviderOperation::EncryptData, || {
+            self.inner().encrypt_data(data)
+        })
+    }
+
+    fn verify_signature(&self, data: &[u8], signature: &[u8]) -> Result<bool, SecurityModuleError> {
+        self.run(ProviderOperation::VerifySignature, || {
+            self.inner().verify_signature(data, signature)
+        })
+    }
+
+    fn sign_data_into<'a>(
+        &self,
+        data: &[u8],
+        context: &'a mut OperationContext,
+    ) -> Result<&'a [u8], SecurityModuleError> {
+        self.run(ProviderOperation::SignData, || {
+            self.inner().sign_data_into(data, context)
+        })
+    }
+
+    fn verify_signature_with(
+        &self,
+        data: &[u8],
+        signature: &[u8],
+        context: &mut OperationContext,
+    ) -> Result<bool, SecurityModuleError> {
+        self.run(ProviderOperation::VerifySignature, || {
+            self.inner().verify_signature_with(data, signature, context)
+        })
+    }
+
+    fn verify_many(&self, items: &[(&[u8], &[u8])]) -> Result<Vec<bool>, SecurityModuleError> {
+        self.run(ProviderOperation::VerifyMany, || {
+            self.inner().verify_many(items)
+        })
+    }
+
+    fn derive_shared_secret(
+        &self,
+        peer_public_key: &[u8],
+    ) -> Result<SecretBytes, SecurityModuleError> {
+        self.run(ProviderOperation::DeriveSharedSecret, || {
+            self.inner().derive_shared_secret(peer_public_key)
+        })
+    }
+}
+
+impl Provider for MetadataProvider {
+    fn create_key(
+        &mut self,
+        key_id: &str,
+        config: Box<dyn Any>,
+    ) -> Result<(), SecurityModuleError> {
+        self.track(key_id, true, || self.inner().create_key(key_id, config))
+    }
+
+    fn load_key(&mut self, key_id: &str, config: Box<dyn Any>) -> Result<(), SecurityModuleError> {
+        self.track(key_id, false, || self.inner().load_key(key_id, config))
+    }
+
+    fn import_wrapped_key(
+        &mut self,
+        key_id: &str,
+        wrapped_key: &[u8],
+        wrapping_key_id: &str,
+        spec: &KeySpec,
+    ) -> Result<(), SecurityModuleError> {
+        self.track(key_id, true, || {
+            self.inner()
+                .import_wrapped_key(key_id, wrapped_key, wrapping_key_id, spec)
+        })
+    }
+
+    fn export_private_key(&self) -> Result<SecretBytes, SecurityModuleError> {
+        self.run(ProviderOperation::ExportPrivateKey, || {
+            self.inner().export_private_key()
+        })
+    }
+
+    fn initialize_module(&mut self) -> Result<(), SecurityModuleError> {
+        self.inner().initialize_module()
+    }
+
+    fn session_pool_metrics(&self) -> Option<SessionPoolMetrics> {
+        self.inner().session_pool_metrics()
+    }
+
+    fn latency_snapshot(&self) -> Option<LatencySnapshot> {
+        self.inner().latency_snapshot()
+    }
+
+    fn key_metadata(&self) -> Result<KeyMetadata, SecurityModuleError> {
+        self.inner().key_metadata()
+    }
+
+    fn refresh_key_metadata(&mut self) -> Result<KeyMetadata, SecurityModuleError> {
+        self.inner().refresh_key_metadata()
+    }
+
+    fn list_keys(&self) -> Result<Vec<String>, SecurityModuleError> {
+        self.inner().list_keys()
+    }
+
+    fn delete_key(&mut self, key_id: &str) -> Result<(), SecurityModuleError> {
+        self.inner().delete_key(key_id)?;
+        self.store.delete(key_id).map(|_| ())
+    }
+
+    fn shutdown(&mut self) -> Result<(), SecurityModuleError> {
+        self.inner().shutdown()
+    }
+}
@@ -0,0 +1,345 @@
+//! Operation metrics reported through the `metrics` facade.
+//!
+//! `MeteredProvider` reports every call of the wrapped provider to the recorder installed by the
+//! application, e.g. `metrics-exporter-prometheus`. Without an installed recorder, reporting does
+//! nothing. The following metrics are reported:
+//!
+//! * `crypto_layer_operations_total` - A counter of calls, labeled with `operation`, `provider`
+//!   and `result`, which is `success`, `rejected` (a signature that does not verify) or
+//!   `failure`.
+//! * `crypto_layer_operation_duration_seconds` - A histogram of call durations, labeled with
+//!   `operation` and `provider`.
+//! * `crypto_layer_keys` - A gauge of the distinct keys created or loaded through a provider,
+//!   labeled with `provider`.
+//! * `crypto_layer_ffi_bytes_total` - A counter of the bytes passed to and returned by the Swift
+//!   bindings of the Secure Enclave provider, labeled with `method` and `direction`, which is
+//!   `request` or `response`.
+//! * `crypto_layer_ffi_marshalling_duration_seconds` - A histogram of the time spent encoding
+//!   requests for and decoding responses of the Swift bindings, labeled with `method`. Together
+//!   with the bytes it quantifies the overhead of the boundary, apart from the time the bindings
+//!   take.
+//!
+//! `SecModules::get_instance` wraps every instance it creates if the `metrics` feature is
+//! enabled.
+
+use crate::common::{
+    crypto::{
+        key_metadata::KeyMetadata, key_spec::KeySpec, operation_context::OperationContext,
+        secret::SecretBytes,
+    },
+    error::SecurityModuleError,
+    latency::{LatencySnapshot, ProviderOperation},
+    session_pool::SessionPoolMetrics,
+    traits::{key_handle::KeyHandle, module_provider::Provider},
+};
+use ::metrics::{
+    counter, describe_counter, describe_gauge, describe_histogram, gauge, histogram, Unit,
+};
+use std::{
+    any::Any,
+    collections::HashSet,
+    fmt,
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+/// The name of the counter of calls.
+pub const OPERATIONS_TOTAL: &str = "crypto_layer_operations_total";
+/// The name of the histogram of call durations.
+pub const OPERATION_DURATION_SECONDS: &str = "crypto_layer_operation_duration_seconds";
+/// The name of the gauge of keys.
+pub const KEYS: &str = "crypto_layer_keys";
+/// The name of the counter of bytes crossing the boundary to the Swift bindings.
+pub const FFI_BYTES_TOTAL: &str = "crypto_layer_ffi_bytes_total";
+/// The name of the histogram of marshalling durations of calls into the Swift bindings.
+pub const FFI_MARSHALLING_DURATION_SECONDS: &str = "crypto_layer_ffi_marshalling_duration_seconds";
+
+/// Registers the descriptions and units of the metrics with the installed recorder.
+///
+/// `MeteredProvider::new` calls this as well, so it only needs to be called if the recorder is
+/// installed after the providers were created.
+pub fn describe() {
+    describe_counter!(OPERATIONS_TOTAL, "Calls of security module operations.");
+    describe_histogram!(
+        OPERATION_DURATION_SECONDS,
+        Unit::Seconds,
+        "Duration of security module operations."
+    );
+    describe_gauge!(KEYS, "Keys created or loaded through a security module.");
+    describe_counter!(
+        FFI_BYTES_TOTAL,
+        Unit::Bytes,
+        "Bytes passed to and returned by the Swift bindings."
+    );
+    describe_histogram!(
+        FFI_MARSHALLING_DURATION_SECONDS,
+        Unit::Seconds,
+        "Time spent encoding requests for and decoding responses of the Swift bindings."
+    );
+}
+
+/// Reports one call into the Swift bindings.
+///
+/// # Arguments
+///
+/// * `method` - The value of the `method` label, the called function of the bindings.
+/// * `request_bytes` - The length of the request passed to the bindings.
+/// * `response_bytes` - The length of the response returned by the bindings.
+/// * `marshalling` - The time spent encoding the request and decoding the response.
+pub fn record_ffi_call(
+    method: &'static str,
+    request_bytes: usize,
+    response_bytes: usize,
+    marshalling: Duration,
+) {
+    counter!(FFI_BYTES_TOTAL, "method" => method, "direction" => "request")
+        .increment(request_bytes as u64);
+    counter!(FFI_BYTES_TOTAL, "method" => method, "direction" => "response")
+        .increment(response_bytes as u64);
+    histogram!(FFI_MARSHALLING_DURATION_SECONDS, "method" => method).record(marshalling);
+}
+
+/// A provider that reports every call to the wrapped provider through the `metrics` facade.
+pub struct MeteredProvider {
+    inner: Arc<Mutex<dyn Provider>>,
+    provider: String,
+    keys: Mutex<HashSet<String>>,
+}
+
+impl MeteredProvider {
+    /// Wraps a provider.
+    ///
+    /// # Arguments
+    ///
+    /// * `inner` - The provider whose calls are reported.
+    /// * `provider` - The value of the `provider` label, e.g. the name of the security module.
+    pub fn new(inner: Arc<Mutex<dyn Provider>>, provider: String) -> Self {
+        describe();
+        Self {
+            inner,
+            provider,
+            keys: Mutex::new(HashSet::new()),
+        }
+    }
+
+    fn inner(&self) -> MutexGuard<'_, dyn Provider + 'static> {
+        self.inner
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn report<T>(
+        &self,
+        operation: ProviderOperation,
+        f: impl FnOnce() -> Result<T, SecurityModuleError>,
+        accepted: impl FnOnce(&T) -> bool,
+    ) -> Result<T, SecurityModuleError> {
+        let start = Instant::now();
+        let result = f();
+        let duration = start.elapsed();
+
+        let outcome = match &result {
+            Ok(value) if accepted(value) => "success",
+            Ok(_) => "rejected",
+            Err(_) => "failure",
+        };
+        let operation = operation.to_string();
+        counter!(
+            OPERATIONS_TOTAL,
+            "operation" => operation.clone(),
+            "provider" => self.provider.clone(),
+            "result" => outcome,
+        )
+        .increment(1);
+        histogram!(
+            OPERATION_DURATION_SECONDS,
+            "operation" => operation,
+            "provider" => self.provider.clone(),
+        )
+        .record(duration);
+        result
+    }
+
+    fn report_status<T>(
+        &self,
+        operation: ProviderOperation,
+        f: impl FnOnce() -> Result<T, SecurityModuleError>,
+    ) -> Result<T, SecurityModuleError> {
+        self.report(operation, f, |_| true)
+    }
+
+    fn add_key(&self, key_id: &str) {
+        let mut keys = self
+            .keys
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if keys.insert(key_id.to_owned()) {
+            gauge!(KEYS, "provider" => self.provider.clone()).set(keys.len() as f64);
+        }
+    }
+
+    fn remove_key(&self, key_id: &str) {
+        let mut keys = self
+            .keys
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if keys.remove(key_id) {
+            gauge!(KEYS, "provider" => self.provider.clone()).set(keys.len() as f64);
+        }
+    }
+}
+
+impl fmt::Debug for MeteredProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MeteredProvider")
+            .field("provider", &self.provider)
+            .finish_non_exhaustive()
+    }
+}
+
+impl KeyHandle for MeteredProvider {
+    fn sign_data(&self, data: &[u8]) -> Result<Vec<u8>, SecurityModuleError> {
+        self.report_status(ProviderOperation::SignData, || self.inner().sign_data(data))
+    }
+
+    fn decrypt_data(&self, encrypted_data: &[u8]) -> Result<SecretBytes, SecurityModuleError> {
+        self.report_status(ProviderOperation::DecryptData, || {
+            self.inner().decrypt_data(encrypted_data)
+        })
+    }
+
+    fn encrypt_data(&self, data: &[u8]) -> Result<Vec<u8>, SecurityModuleError> {
+        self.report_status(ProviderOperation::EncryptData, || {
+            self.inner().encrypt_data(data)
+        })
+    }
+
+    fn verify_signature(&self, data: &[u8], signature: &[u8]) -> Result<bool, SecurityModuleError> {
+        self.report(
+            ProviderOperation::VerifySignature,
+            || self.inner().verify_signature(data, signature),
+            |valid| *valid,
+        )
+    }
+
+    fn sign_data_into<'a>(
+        &self,
+        data: &[u8],
+        context: &'a mut OperationContext,
+    ) -> Result<&'a [u8], SecurityModuleError> {
+        self.report_status(ProviderOperation::SignData, || {
+            self.inner().sign_data_into(data, context)
+        })
+    }
+
+    fn verify_signature_with(
+        &self,
+        data: &[u8],
+        signature: &[u8],
+        context: &mut OperationContext,
+    ) -> Result<bool, SecurityModuleError> {
+        self.report(
+            ProviderOperation::VerifySignature,
+            || self.inner().verify_signature_with(data, signature, context),
+            |valid| *valid,
+        )
+    }
+
+    fn verify_many(&self, items: &[(&[u8], &[u8])]) -> Result<Vec<bool>, SecurityModuleError> {
+        self.report(
+            ProviderOperation::VerifyMany,
+            || self.inner().verify_many(items),
+            |results| results.iter().all(|valid| *valid),
+        )
+    }
+
+    fn derive_shared_secret(
+        &self,
+        peer_public_key: &[u8],
+    ) -> Result<SecretBytes, SecurityModuleError> {
+        self.report_status(ProviderOperation::DeriveSharedSecret, || {
+            self.inner().derive_shared_secret(peer_public_key)
+        })
+    }
+}
+
+impl Provider for MeteredProvider {
+    fn create_key(
+        &mut self,
+        key_id: &str,
+        config: Box<dyn Any>,
+    ) -> Result<(), SecurityModuleError> {
+        self.report_status(ProviderOperation::CreateKey, || {
+            self.inner().create_key(key_id, config)
+        })?;
+        self.add_key(key_id);
+        Ok(())
+    }
+
+    fn load_key(&mut self, key_id: &str, config: Box<dyn Any>) -> Result<(), SecurityModuleError> {
+        self.report_status(ProviderOperation::LoadKey, || {
+            self.inner().load_key(key_id, config)
+        })?;
+        self.add_key(key_id);
+        Ok(())
+    }
+
+    fn import_wrapped_key(
+        &mut self,
+        key_id: &str,
+        wrapped_key: &[u8],
+        wrapping_key_id: &str,
+        spec: &KeySpec,
+    ) -> Result<(), SecurityModuleError> {
+        self.report_status(ProviderOperation::ImportWrappedKey, || {
+            self.inner()
+                .import_wrapped_key(key_id, wrapped_key, wrapping_key_id, spec)
+        })?;
+        self.add_key(key_id);
+        Ok(())
+    }
+
+    fn export_private_key(&self) -> Result<SecretBytes, SecurityModuleError> {
+        self.report_status(ProviderOperation::ExportPrivateKey, || {
+            self.inner().export_private_key()
+        })
+    }
+
+    fn initialize_module(&mut self) -> Result<(), SecurityModuleError> {
+        self.report_status(ProviderOperation::InitializeModule, || {
+            self.inner().initialize_module()
+        })
+    }
+
+    fn session_pool_metrics(&self) -> Option<SessionPoolMetrics> {
+        self.inner().session_pool_metrics()
+    }
+
+    fn latency_snapshot(&self) -> Option<LatencySnapshot> {
+        self.inner().latency_snapshot()
+    }
+
+    fn key_metadata(&self) -> Result<KeyMetadata, SecurityModuleError> {
+        self.inner().key_metadata()
+    }
+
+    fn refresh_key_metadata(&mut self) -> Result<KeyMetadata, SecurityModuleError> {
+        self.inner().refresh_key_metadata()
+    }
+
+    fn list_keys(&self) -> Result<Vec<String>, SecurityModuleError> {
+        self.inner().list_keys()
+    }
+
+    fn delete_key(&mut self, key_id: &str) -> Result<(), SecurityModuleError> {
+        self.report_status(ProviderOperation::DeleteKey, || {
+            self.inner().delete_key(key_id)
+        })?;
+        self.remove_key(key_id);
+        Ok(())
+    }
+
+    fn shutdown(&mut self) -> Result<(), SecurityModuleError> {
+        self.inner().shutdown()
+    }
+}
@@ -0,0 +1,376 @@
+//! Migration of exportable keys and data keys to a new device.
+//!
+//! When the user upgrades their device, keys created exportable on the old device (the source)
+//! and application data keys move to the new device (the target) without ever being readable
+//! in transit. The target holds a transport key, which it proves possession of and whose
+//! attestation the source checks before it wraps anything under it:
+//!
+//! ```rust,ignore
+//! use crypto_layer::common::migration::{self, MigrationPolicy, MigrationRequest};
+//!
+//! // On the source, the challenge is shown to the target, e.g. as a QR code.
+//! let challenge = migration::challenge()?;
+//!
+//! // On the target, with the transport key loaded.
+//! let request = MigrationRequest::create(&target, &challenge)?;
+//!
+//! // On the source, with the exportable key loaded.
+//! let verified = migration::verify_request(&request, &challenge, &MigrationPolicy::default())?;
+//! let package = migration::export_key(&source, &verified)?;
+//!
+//! // On the target.
+//! migration::import_key(&mut target, &request, &package, "migrated", &spec)?;
+//! ```
+//!
+//! The request carries the public transport key as a JWK, its attestation chain and a proof of
+//! possession, see `proof_of_possession`, over the challenge with the audience
+//! `MIGRATION_AUDIENCE`. The proof binds the attestation and the exportability of the transport
+//! key, and its client nonce identifies the request. Every package repeats that nonce, so the
+//! target rejects packages that answer another request.
+//!
+//! - `export_key` wraps the exportable private key of the source with `key_import::wrap_rsa_aes`
+//!   for an RSA transport key, which the target imports with `Provider::import_wrapped_key`.
+//!   The private key thus only exists in plaintext inside the security modules.
+//! - `export_data_keys` encrypts data keys with `ecies` for an EC transport key. The nonce of the
+//!   request is encrypted together with the keys, in the payload format of `escrow`.
+//!
+//! The transport key signs the proof, so it must be a signing key. Targets should create a fresh
+//! transport key for every migration and delete it afterwards.
+
+use crate::common::{
+    crypto::{jwk, key_spec::KeySpec, public_key::PublicKey, secret::SecretBytes},
+    ecies,
+    error::SecurityModuleError,
+    escrow, key_import,
+    proof_of_possession::{self, hex, Proof, ProofClaims, ProofPolicy},
+    traits::{key_handle::KeyHandle, module_provider::Provider},
+};
+use base64::{prelude::BASE64_URL_SAFE_NO_PAD, Engine};
+use crypto_layer_core::CoreError;
+use openssl::{rand::rand_bytes, sha::sha256};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, time::Duration};
+
+/// The audience of the proofs of migration requests.
+pub const MIGRATION_AUDIENCE: &str = "crypto-layer/migration/v1";
+
+/// The length of the challenges of `challenge` in bytes.
+pub const CHALLENGE_LEN: usize = 32;
+
+/// A request of the target device for a migration, see the module documentation.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MigrationRequest {
+    /// The public transport key as a JWK.
+    pub transport_key: serde_json::Value,
+    /// The DER encoded attestation chain of the transport key in unpadded base64url, leaf first,
+    /// or empty if the security module does not attest its keys.
+    #[serde(default)]
+    pub attestation: Vec<String>,
+    /// The encoded proof of possession of the transport key over the challenge of the source.
+    pub proof: String,
+}
+
+/// What a `MigrationPackage` holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PayloadKind {
+    /// A private key wrapped with `key_import::wrap_rsa_aes`.
+    PrivateKey,
+    /// Data keys encrypted with `ecies`.
+    DataKeys,
+}
+
+/// The keys the source sends to the target, see the module documentation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MigrationPackage {
+    /// The client nonce of the proof of the request the package answers.
+    pub request_nonce: String,
+    pub kind: PayloadKind,
+    /// The wrapped or encrypted keys in unpadded base64url.
+    pub payload: String,
+}
+
+/// The checks `verify_request` applies to the target device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MigrationPolicy {
+    /// The limits of the creation time of the proof.
+    pub proof: ProofPolicy,
+    /// Rejects transport keys without an attestation chain.
+    pub require_attestation: bool,
+    /// Rejects transport keys that could leave the security module of the target.
+    pub require_hardware_key: bool,
+}
+
+impl Default for MigrationPolicy {
+    /// Requires an attested, non-exportable transport key and accepts requests for 10 minutes,
+    /// which leaves the user time to confirm the migration on the source.
+    fn default() -> Self {
+        Self {
+            proof: ProofPolicy {
+                max_age: Duration::from_secs(10 * 60),
+                ..ProofPolicy::default()
+            },
+            require_attestation: true,
+            require_hardware_key: true,
+        }
+    }
+}
+
+/// A request that passed `verify_request`, to be passed to `export_key` and `export_data_keys`.
+#[derive(Debug, Clone)]
+pub struct VerifiedRequest {
+    transport_key: PublicKey,
+    attestation: Vec<Vec<u8>>,
+    nonce: String,
+}
+
+impl VerifiedRequest {
+    pub fn transport_key(&self) -> &PublicKey {
+        &self.transport_key
+    }
+
+    /// Returns the DER encoded attestation chain of the transport key, leaf first, whose leaf
+    /// the proof is bound to. Applications check it against the roots of the vendors they trust.
+    pub fn attestation(&self) -> &[Vec<u8>] {
+        &self.attestation
+    }
+}
+
+/// Returns a random challenge for `MigrationRequest::create`.
+pub fn challenge() -> Result<Vec<u8>, SecurityModuleError> {
+    let mut challenge = vec![0; CHALLENGE_LEN];
+    rand_bytes(&mut challenge).map_err(|e| SecurityModuleError::SigningError(e.to_string()))?;
+    Ok(challenge)
+}
+
+impl MigrationRequest {
+    /// Creates a request of the target device, with the loaded key of `provider` as transport
+    /// key.
+    ///
+    /// # Arguments
+    ///
+    /// * `provider` - The provider of the target with the loaded transport key.
+    /// * `challenge` - The challenge of the source.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the request, a `SecurityModuleError::UnsupportedAlgorithm` if the
+    /// transport key is no signing key, or the error of `proof_of_possession::create_proof`.
+    #[tracing::instrument(skip_all)]
+    pub fn create(
+        provider: &(impl Provider + ?Sized),
+        challenge: &[u8],
+    ) -> Result<Self, SecurityModuleError> {
+        let metadata = provider.key_metadata()?;
+        let transport_key = jwk::to_jwk(metadata.public_key(), Some(metadata.key_id()))?;
+        let proof = proof_of_possession::create_proof(provider, MIGRATION_AUDIENCE, challenge)?;
+        Ok(Self {
+            transport_key,
+            attestation: metadata
+                .attestation()
+                .unwrap_or_default()
+                .iter()
+                .map(|certificate| BASE64_URL_SAFE_NO_PAD.encode(certificate))
+                .collect(),
+            proof: proof.to_string(),
+        })
+    }
+
+    /// Returns the claims of the proof without verifying it.
+    fn claims(&self) -> Result<ProofClaims, SecurityModuleError> {
+        Ok(self.proof.parse::<Proof>()?.claims().clone())
+    }
+}
+
+/// Verifies a request on the source device, at the current time.
+///
+/// # Arguments
+///
+/// * `request` - The request of the target.
+/// * `challenge` - The challenge the source gave to the target.
+/// * `policy` - The checks applied to the transport key.
+///
+/// # Returns
+///
+/// A `Result` containing the verified request, a `SecurityModuleError::InvalidPublicKey` or
+/// `SecurityModuleError::Encoding` if the transport key or attestation cannot be decoded, an
+/// error of `proof_of_possession::verify_proof`, or a `SecurityModuleError::InvalidProof` if the
+/// attestation does not match the proof or the transport key does not meet `policy`.
+#[tracing::instrument(skip_all)]
+pub fn verify_request(
+    request: &MigrationRequest,
+    challenge: &[u8],
+    policy: &MigrationPolicy,
+) -> Result<VerifiedRequest, SecurityModuleError> {
+    let transport_key = jwk::from_jwk(&request.transport_key)?;
+    let attestation = request
+        .attestation
+        .iter()
+        .map(|certificate| BASE64_URL_SAFE_NO_PAD.decode(certificate))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| CoreError::InvalidField("attestation"))?;
+    let proof: Proof = request.proof.parse()?;
+    let claims = proof_of_possession::verify_proof(
+        &proof,
+        &transport_key,
+        MIGRATION_AUDIENCE,
+        challenge,
+        &policy.proof,
+    )?;
+
+    if claims.attestation != attestation.first().map(|leaf| hex(&sha256(leaf))) {
+        return Err(invalid_request(
+            "The attestation does not belong to the proof",
+        ));
+    }
+    if policy.require_attestation && attestation.is_empty() {
+        return Err(invalid_request("The transport key is not attested"));
+    }
+    if policy.require_hardware_key && claims.exportable {
+        return Err(invalid_request("The transport key is exportable"));
+    }
+    Ok(VerifiedRequest {
+        transport_key,
+        attestation,
+        nonce: claims.nonce.clone(),
+    })
+}
+
+/// Wraps the loaded private key of the source for the target of `request`.
+///
+/// # Returns
+///
+/// A `Result` containing the package, the error of `Provider::export_private_key` if the key
+/// is not exportable, or a `SecurityModuleError::UnsupportedAlgorithm` if the transport key is
+/// not an RSA key.
+#[tracing::instrument(skip_all)]
+pub fn export_key(
+    source: &(impl Provider + ?Sized),
+    request: &VerifiedRequest,
+) -> Result<MigrationPackage, SecurityModuleError> {
+    let pkcs8_der = source.export_private_key()?;
+    let wrapped_key = key_import::wrap_rsa_aes(&request.transport_key, &pkcs8_der)?;
+    Ok(package(request, PayloadKind::PrivateKey, &wrapped_key))
+}
+
+/// Encrypts `keys` for the target of `request`.
+///
+/// # Returns
+///
+/// A `Result` containing the package, a `SecurityModuleError::InvalidPublicKey` if the
+/// transport key is not an EC key, or a `SecurityModuleError::EncryptionError` if a name is
+/// empty or longer than `escrow::MAX_KEY_NAME_LEN`.
+#[tracing::instrument(skip_all, fields(crypto.keys = keys.len()))]
+pub fn export_data_keys(
+    keys: &BTreeMap<String, SecretBytes>,
+    request: &VerifiedRequest,
+) -> Result<MigrationPackage, SecurityModuleError> {
+    let nonce = bound_nonce(&request.nonce)?;
+    let encoded_keys = escrow::encode_keys(keys)?;
+    let mut plaintext = SecretBytes::with_capacity(nonce.len() + encoded_keys.len());
+    plaintext.extend_from_slice(&nonce);
+    plaintext.extend_from_slice(&encoded_keys);
+    let ciphertext = ecies::encrypt_for(&request.transport_key, &plaintext)?;
+    Ok(package(request, PayloadKind::DataKeys, &ciphertext))
+}
+
+/// Imports the private key of a package on the target, unwrapping it with the transport key of
+/// `request`.
+///
+/// # Arguments
+///
+/// * `target` - The provider of the target holding the transport key.
+/// * `request` - The request the target sent for this migration.
+/// * `package` - The package of `export_key`.
+/// * `key_id` - The id of the imported key.
+/// * `spec` - The algorithm and usages of the imported key, which decide whether it stays
+///   exportable on the target.
+///
+/// # Returns
+///
+/// A `Result` that, on success, contains `Ok(())`, a `SecurityModuleError::InvalidProof` if the
+/// package answers another request or holds data keys, or the error of
+/// `Provider::import_wrapped_key`.
+#[tracing::instrument(skip_all)]
+pub fn import_key(
+    target: &mut (impl Provider + ?Sized),
+    request: &MigrationRequest,
+    package: &MigrationPackage,
+    key_id: &str,
+    spec: &KeySpec,
+) -> Result<(), SecurityModuleError> {
+    let claims = request.claims()?;
+    let wrapped_key = open_package(&claims, package, PayloadKind::PrivateKey)?;
+    target.import_wrapped_key(key_id, &wrapped_key, &claims.key_id, spec)
+}
+
+/// Decrypts the data keys of a package on the target with the transport key of `request`.
+///
+/// # Arguments
+///
+/// * `target` - Holds the transport key and derives the shared secret with
+///   `derive_shared_secret`, e.g. the provider of the target with the loaded transport key.
+/// * `request` - The request the target sent for this migration.
+/// * `package` - The package of `export_data_keys`.
+///
+/// # Returns
+///
+/// A `Result` containing the data keys by name, which are zeroed when dropped, a
+/// `SecurityModuleError::InvalidProof` if the package answers another request or holds a private
+/// key, or an error of `ecies::decrypt`.
+#[tracing::instrument(skip_all)]
+pub fn import_data_keys(
+    target: &(impl KeyHandle + ?Sized),
+    request: &MigrationRequest,
+    package: &MigrationPackage,
+) -> Result<BTreeMap<String, SecretBytes>, SecurityModuleError> {
+    let claims = request.claims()?;
+    let ciphertext = open_package(&claims, package, PayloadKind::DataKeys)?;
+    let plaintext = ecies::decrypt(target, &ciphertext)?;
+    let nonce = bound_nonce(&claims.nonce)?;
+    match plaintext.strip_prefix(nonce.as_slice()) {
+        Some(encoded_keys) => escrow::decode_keys(encoded_keys),
+        None => Err(invalid_request("The package answers another request")),
+    }
+}
+
+fn package(request: &VerifiedRequest, kind: PayloadKind, payload: &[u8]) -> MigrationPackage {
+    MigrationPackage {
+        request_nonce: request.nonce.clone(),
+        kind,
+        payload: BASE64_URL_SAFE_NO_PAD.encode(payload),
+    }
+}
+
+/// Checks that `package` answers the request of `claims` with a payload of `kind` and decodes
+/// the payload.
+fn open_package(
+    claims: &ProofClaims,
+    package: &MigrationPackage,
+    kind: PayloadKind,
+) -> Result<Vec<u8>, SecurityModuleError> {
+    if package.request_nonce != claims.nonce {
+        return Err(invalid_request("The package answers another request"));
+    }
+    if package.kind != kind {
+        return Err(invalid_request(&format!(
+            "The package holds {:?} instead of {:?}",
+            package.kind, kind
+        )));
+    }
+    Ok(BASE64_URL_SAFE_NO_PAD
+        .decode(&package.payload)
+        .map_err(|_| CoreError::InvalidField("payload"))?)
+}
+
+/// Returns the length-prefixed nonce that starts the plaintext of data key packages.
+fn bound_nonce(nonce: &str) -> Result<Vec<u8>, SecurityModuleError> {
+    let len = u8::try_from(nonce.len()).map_err(|_| invalid_request("The nonce is too long"))?;
+    let mut bound = vec![len];
+    bound.extend_from_slice(nonce.as_bytes());
+    Ok(bound)
+}
+
+fn invalid_request(message: &str) -> SecurityModuleError {
+    SecurityModuleError::InvalidProof(message.to_owned())
+}
@@ -1,4 +1,64 @@
+pub mod acme;
+pub mod anomaly;
+pub mod anti_rollback;
+pub mod audit;
+pub mod capability_token;
+pub mod config;
+pub mod constant_time;
+pub mod csr;
 pub mod crypto;
+pub mod device_identity;
+pub mod diagnostics;
+pub mod dnssec;
+pub mod dual_control;
+pub mod ecies;
+pub mod ephemeral_key;
 pub mod error;
+pub mod escrow;
+pub mod events;
 pub mod factory;
+pub mod field_encryption;
+pub mod file_encryption;
+pub mod fips;
+#[cfg(any(feature = "kms-plugin", feature = "remote"))]
+pub(crate) mod grpc;
+pub mod input_limits;
+pub mod interop;
+pub mod key_hierarchy;
+pub mod key_id;
+pub mod key_import;
+#[cfg(feature = "serde")]
+pub mod key_inventory;
+#[cfg(any(feature = "daemon", feature = "remote"))]
+pub(crate) mod key_router;
+pub mod key_stats;
+pub mod key_wrapping;
+pub mod latency;
+pub mod log_levels;
+pub mod memory_lock;
+pub mod metadata_store;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+pub mod migration;
+pub mod namespace;
+pub mod openpgp;
+pub mod password;
+pub mod pkcs12;
+pub mod plan;
+pub mod profile;
+pub mod proof_of_possession;
+pub mod recovery;
+pub mod sealed_message;
+pub mod session_pool;
+pub mod sigstore;
+#[cfg(feature = "sqlite")]
+pub mod sqlite_store;
+pub(crate) mod ssh_wire;
+pub mod sshsig;
+pub mod sunset;
+pub mod telemetry;
 pub mod traits;
+pub mod transaction;
+pub mod uniform_errors;
+pub mod vault;
+pub mod webauthn;
@@ -0,0 +1,261 @@
+//! Scoping of key ids per application or tenant.
+//!
+//! Several applications or tenants using the crate on one device share the key store of the
+//! security module, e.g. the keychain on macOS. A `Namespace` prefixes every key id with its
+//! name, so that two tenants creating a key named `identity` get two different keys:
+//!
+//! ```rust,ignore
+//! use crypto_layer::common::namespace::{Namespace, NamespacedProvider};
+//!
+//! let provider = NamespacedProvider::new(provider, Namespace::new("com.example.mail")?);
+//! provider.create_key("identity", config)?; // creates "com.example.mail/identity"
+//! provider.list_keys()?; // ["identity"], keys of other namespaces are not listed
+//! ```
+//!
+//! `SecModules::get_instance` wraps every instance it creates in a `NamespacedProvider` if
+//! `CryptoConfig::namespace` is set.
+
+use crate::common::{
+    crypto::{
+        key_metadata::KeyMetadata, key_spec::KeySpec, operation_context::OperationContext,
+        secret::SecretBytes,
+    },
+    error::SecurityModuleError,
+    latency::LatencySnapshot,
+    session_pool::SessionPoolMetrics,
+    traits::{key_handle::KeyHandle, module_provider::Provider},
+};
+use std::{
+    any::Any,
+    fmt,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+/// Separates the name of a namespace from the key id in scoped key ids.
+pub const NAMESPACE_SEPARATOR: char = '/';
+
+/// The maximum length of the name of a namespace in bytes.
+pub const MAX_NAMESPACE_LEN: usize = 64;
+
+/// The name of an application or tenant whose key ids are kept apart from all others.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Namespace(String);
+
+impl Namespace {
+    /// Creates a namespace.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the namespace, e.g. a reverse domain name like `com.example.mail`.
+    ///   It consists of ASCII letters, digits, `.`, `-` and `_` and is at most
+    ///   `MAX_NAMESPACE_LEN` bytes long.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `Namespace` on success, or a
+    /// `SecurityModuleError::InitializationError` if the name is invalid.
+    pub fn new(name: impl Into<String>) -> Result<Self, SecurityModuleError> {
+        let name = name.into();
+        let invalid = |reason: &str| {
+            Err(SecurityModuleError::InitializationError(format!(
+                "Invalid namespace '{}': {}",
+                name, reason
+            )))
+        };
+
+        if name.is_empty() {
+            return invalid("the name is empty");
+        }
+        if name.len() > MAX_NAMESPACE_LEN {
+            return invalid("the name is too long");
+        }
+        if !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'))
+        {
+            return invalid("only ASCII letters, digits, '.', '-' and '_' are allowed");
+        }
+        Ok(Self(name))
+    }
+
+    /// Returns the name of the namespace.
+    pub fn name(&self) -> &str {
+        &self.0
+    }
+
+    /// Returns the id the security module stores the key `key_id` of this namespace under.
+    pub fn scope(&self, key_id: &str) -> String {
+        format!("{}{}{}", self.0, NAMESPACE_SEPARATOR, key_id)
+    }
+
+    /// Returns the key id within this namespace of a scoped key id, or `None` if the key belongs
+    /// to another namespace or to none.
+    pub fn unscope<'a>(&self, scoped_key_id: &'a str) -> Option<&'a str> {
+        scoped_key_id
+            .strip_prefix(self.0.as_str())?
+            .strip_prefix(NAMESPACE_SEPARATOR)
+    }
+}
+
+impl fmt::Display for Namespace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// A provider that scopes the key ids passed to the wrapped provider to a `Namespace`.
+///
+/// Keys are created and loaded under their scoped id, so keys of other namespaces cannot be
+/// loaded, and `list_keys` only returns the keys of the namespace, without the prefix.
+pub struct NamespacedProvider {
+    inner: Arc<Mutex<dyn Provider>>,
+    namespace: Namespace,
+}
+
+impl NamespacedProvider {
+    /// Wraps a provider.
+    ///
+    /// # Arguments
+    ///
+    /// * `inner` - The provider whose key ids are scoped.
+    /// * `namespace` - The namespace the key ids are scoped to.
+    pub fn new(inner: Arc<Mutex<dyn Provider>>, namespace: Namespace) -> Self {
+        Self { inner, namespace }
+    }
+
+    /// Returns the namespace the key ids are scoped to.
+    pub fn namespace(&self) -> &Namespace {
+        &self.namespace
+    }
+
+    fn inner(&self) -> MutexGuard<'_, dyn Provider + 'static> {
+        self.inner
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl fmt::Debug for NamespacedProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NamespacedProvider")
+            .field("namespace", &self.namespace)
+            .finish_non_exhaustive()
+    }
+}
+
+impl KeyHandle for NamespacedProvider {
+    fn sign_data(&self, data: &[u8]) -> Result<Vec<u8>, SecurityModuleError> {
+        self.inner().sign_data(data)
+    }
+
+    fn decrypt_data(&self, encrypted_data: &[u8]) -> Result<SecretBytes, SecurityModuleError> {
+        self.inner().decrypt_data(encrypted_data)
+    }
+
+    fn encrypt_data(&self, data: &[u8]) -> Result<Vec<u8>, SecurityModuleError> {
+        self.inner().encrypt_data(data)
+    }
+
+    fn verify_signature(&self, data: &[u8], signature: &[u8]) -> Result<bool, SecurityModuleError> {
+        self.inner().verify_signature(data, signature)
+    }
+
+    fn sign_data_into<'a>(
+        &self,
+        data: &[u8],
+        context: &'a mut OperationContext,
+    ) -> Result<&'a [u8], SecurityModuleError> {
+        self.inner().sign_data_into(data, context)
+    }
+
+    fn verify_signature_with(
+        &self,
+        data: &[u8],
+        signature: &[u8],
+        context: &mut OperationContext,
+    ) -> Result<bool, SecurityModuleError> {
+        self.inner().verify_signature_with(data, signature, context)
+    }
+
+    fn verify_many(&self, items: &[(&[u8], &[u8])]) -> Result<Vec<bool>, SecurityModuleError> {
+        self.inner().verify_many(items)
+    }
+
+    fn derive_shared_secret(
+        &self,
+        peer_public_key: &[u8],
+    ) -> Result<SecretBytes, SecurityModuleError> {
+        self.inner().derive_shared_secret(peer_public_key)
+    }
+}
+
+impl Provider for NamespacedProvider {
+    fn create_key(
+        &mut self,
+        key_id: &str,
+        config: Box<dyn Any>,
+    ) -> Result<(), SecurityModuleError> {
+        let key_id = self.namespace.scope(key_id);
+        self.inner().create_key(&key_id, config)
+    }
+
+    fn load_key(&mut self, key_id: &str, config: Box<dyn Any>) -> Result<(), SecurityModuleError> {
+        let key_id = self.namespace.scope(key_id);
+        self.inner().load_key(&key_id, config)
+    }
+
+    fn import_wrapped_key(
+        &mut self,
+        key_id: &str,
+        wrapped_key: &[u8],
+        wrapping_key_id: &str,
+        spec: &KeySpec,
+    ) -> Result<(), SecurityModuleError> {
+        let key_id = self.namespace.scope(key_id);
+        let wrapping_key_id = self.namespace.scope(wrapping_key_id);
+        self.inner()
+            .import_wrapped_key(&key_id, wrapped_key, &wrapping_key_id, spec)
+    }
+
+    fn export_private_key(&self) -> Result<SecretBytes, SecurityModuleError> {
+        self.inner().export_private_key()
+    }
+
+    fn initialize_module(&mut self) -> Result<(), SecurityModuleError> {
+        self.inner().initialize_module()
+    }
+
+    fn session_pool_metrics(&self) -> Option<SessionPoolMetrics> {
+        self.inner().session_pool_metrics()
+    }
+
+    fn latency_snapshot(&self) -> Option<LatencySnapshot> {
+        self.inner().latency_snapshot()
+    }
+
+    fn key_metadata(&self) -> Result<KeyMetadata, SecurityModuleError> {
+        self.inner().key_metadata()
+    }
+
+    fn refresh_key_metadata(&mut self) -> Result<KeyMetadata, SecurityModuleError> {
+        self.inner().refresh_key_metadata()
+    }
+
+    fn list_keys(&self) -> Result<Vec<String>, SecurityModuleError> {
+        let key_ids = self.inner().list_keys()?;
+        Ok(key_ids
+            .iter()
+            .filter_map(|key_id| self.namespace.unscope(key_id))
+            .map(str::to_owned)
+            .collect())
+    }
+
+    fn delete_key(&mut self, key_id: &str) -> Result<(), SecurityModuleError> {
+        let key_id = self.namespace.scope(key_id);
+        self.inner().delete_key(&key_id)
+    }
+
+    fn shutdown(&mut self) -> Result<(), SecurityModuleError> {
+        self.inner().shutdown()
+    }
+}
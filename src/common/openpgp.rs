@@ -0,0 +1,865 @@
+//! OpenPGP signatures (RFC 4880) created with keys of the security module.
+//!
+//! The key of the security module becomes an OpenPGP v4 key, either as primary key of a
+//! certificate or as signing subkey of a primary key, which may itself be held in another
+//! security module. The certificate is imported into GnuPG or published on a key server, and
+//! detached signatures of releases or emails are checked with `gpg --verify`:
+//!
+//! ```rust,ignore
+//! use crypto_layer::common::openpgp::{self, PgpKey, SignatureType};
+//!
+//! // The creation time is part of the fingerprint and must be kept with the key.
+//! let key = PgpKey::new(provider.key_metadata()?.public_key(), created)?;
+//! let certificate = openpgp::certificate(&provider, &key, "Release Signing <release@example.com>", None)?;
+//! std::fs::write("release.asc", certificate.to_string())?;
+//!
+//! let signature = openpgp::sign(&provider, &key, &tarball, SignatureType::Binary)?;
+//! std::fs::write("app-1.0.tar.gz.asc", signature.to_string())?;
+//! ```
+//!
+//! ECDSA keys on P-256, P-384 and P-521, Ed25519 keys as EdDSA keys and RSA keys with PKCS#1
+//! v1.5 signatures are supported, which GnuPG 2.2 and later accept. Signatures use the hash of
+//! the key, which must be SHA-256, SHA-384 or SHA-512, and ECDSA keys the hash of their curve.
+//!
+//! The packets are encoded here instead of with the builders of `sequoia-openpgp`, whose
+//! `crypto::Signer` is handed the digest of a signature. `Provider::sign_data` signs the data,
+//! which the security module hashes itself, and no provider signs a given digest, so the
+//! signature is created from the hashed data. The tests check the encoding against GnuPG in
+//! both directions: signatures of GnuPG verify with `verify`, and GnuPG imports the
+//! certificates of `certificate` and verifies the signatures of `sign`.
+
+use crate::common::{
+    crypto::{
+        algorithms::{
+            encryption::{AsymmetricEncryption, EccCurves, EccSchemeAlgorithm},
+            hashes::{Hash, Sha2Bits},
+        },
+        public_key::{PublicKey, RsaSignaturePadding},
+        signature_format,
+    },
+    error::SecurityModuleError,
+    traits::module_provider::Provider,
+};
+use base64::{prelude::BASE64_STANDARD, Engine};
+use openssl::{
+    pkey::PKey,
+    sha::{sha1, sha256, sha384, sha512},
+};
+use std::{
+    fmt,
+    str::FromStr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+const KEY_VERSION: u8 = 4;
+const SIGNATURE_VERSION: u8 = 4;
+
+const TAG_SIGNATURE: u8 = 2;
+const TAG_PUBLIC_KEY: u8 = 6;
+const TAG_USER_ID: u8 = 13;
+const TAG_PUBLIC_SUBKEY: u8 = 14;
+
+const ALGORITHM_RSA: u8 = 1;
+const ALGORITHM_ECDSA: u8 = 19;
+const ALGORITHM_EDDSA: u8 = 22;
+
+const POSITIVE_CERTIFICATION: u8 = 0x13;
+const SUBKEY_BINDING: u8 = 0x18;
+const PRIMARY_KEY_BINDING: u8 = 0x19;
+
+const SUBPACKET_CREATION_TIME: u8 = 2;
+const SUBPACKET_ISSUER: u8 = 16;
+const SUBPACKET_PREFERRED_HASHES: u8 = 21;
+const SUBPACKET_KEY_FLAGS: u8 = 27;
+const SUBPACKET_EMBEDDED_SIGNATURE: u8 = 32;
+const SUBPACKET_ISSUER_FINGERPRINT: u8 = 33;
+
+const KEY_FLAG_CERTIFY: u8 = 0x01;
+const KEY_FLAG_SIGN: u8 = 0x02;
+
+const OID_P256: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07];
+const OID_P384: &[u8] = &[0x2b, 0x81, 0x04, 0x00, 0x22];
+const OID_P521: &[u8] = &[0x2b, 0x81, 0x04, 0x00, 0x23];
+const OID_ED25519: &[u8] = &[0x2b, 0x06, 0x01, 0x04, 0x01, 0xda, 0x47, 0x0f, 0x01];
+
+/// The length of the lines of the armored encoding, as GnuPG writes it.
+const LINE_LEN: usize = 64;
+
+/// What a signature is created over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureType {
+    /// The data as is, e.g. a release archive.
+    Binary,
+    /// Text, whose line endings are converted to CRLF before it is signed and verified, so the
+    /// signature survives conversions of the line endings.
+    Text,
+}
+
+impl SignatureType {
+    fn id(self) -> u8 {
+        match self {
+            SignatureType::Binary => 0x00,
+            SignatureType::Text => 0x01,
+        }
+    }
+}
+
+/// The key material of a `PgpKey`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum KeyAlgorithm {
+    Rsa { modulus_len: usize },
+    Ecdsa { scalar_len: usize },
+    EdDsa,
+}
+
+/// A key of the security module as OpenPGP v4 key.
+#[derive(Debug, Clone)]
+pub struct PgpKey {
+    public_key: PublicKey,
+    algorithm: KeyAlgorithm,
+    hash: Sha2Bits,
+    created: u32,
+    /// The body of the public key packet, which the fingerprint is calculated over.
+    packet_body: Vec<u8>,
+}
+
+impl PgpKey {
+    /// Creates the OpenPGP key of `public_key`, created at `created`.
+    ///
+    /// # Arguments
+    ///
+    /// * `public_key` - The public key of the security module.
+    /// * `created` - The creation time of the OpenPGP key, which is part of its fingerprint, so
+    ///   the same time must be passed whenever the key is used.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `PgpKey`, a `SecurityModuleError::UnsupportedAlgorithm` if the
+    /// key cannot be used with OpenPGP, see the module documentation, or a
+    /// `SecurityModuleError::SigningError` if `created` is not between 1970 and 2106.
+    pub fn new(public_key: &PublicKey, created: SystemTime) -> Result<Self, SecurityModuleError> {
+        let (algorithm, hash) = key_algorithm(public_key)?;
+        let created = timestamp(created)?;
+        let mut packet_body = vec![KEY_VERSION];
+        packet_body.extend_from_slice(&created.to_be_bytes());
+        match algorithm {
+            KeyAlgorithm::Rsa { .. } => {
+                let rsa = PKey::public_key_from_der(&public_key.to_der()?)
+                    .and_then(|key| key.rsa())
+                    .map_err(|_| SecurityModuleError::InvalidPublicKey)?;
+                packet_body.push(ALGORITHM_RSA);
+                put_mpi(&mut packet_body, &rsa.n().to_vec());
+                put_mpi(&mut packet_body, &rsa.e().to_vec());
+            }
+            KeyAlgorithm::Ecdsa { scalar_len } => {
+                packet_body.push(ALGORITHM_ECDSA);
+                let oid = match scalar_len {
+                    32 => OID_P256,
+                    48 => OID_P384,
+                    _ => OID_P521,
+                };
+                packet_body.push(oid.len() as u8);
+                packet_body.extend_from_slice(oid);
+                put_mpi(&mut packet_body, &public_key.to_ec_point()?);
+            }
+            KeyAlgorithm::EdDsa => {
+                let raw = PKey::public_key_from_der(&public_key.to_der()?)
+                    .and_then(|key| key.raw_public_key())
+                    .map_err(|_| SecurityModuleError::InvalidPublicKey)?;
+                packet_body.push(ALGORITHM_EDDSA);
+                packet_body.push(OID_ED25519.len() as u8);
+                packet_body.extend_from_slice(OID_ED25519);
+                // The point is prefixed with 0x40 to mark its native encoding.
+                put_mpi(&mut packet_body, &[&[0x40][..], &raw].concat());
+            }
+        }
+        Ok(Self {
+            public_key: public_key.clone(),
+            algorithm,
+            hash,
+            created,
+            packet_body,
+        })
+    }
+
+    /// Returns the v4 fingerprint of the key.
+    pub fn fingerprint(&self) -> [u8; 20] {
+        sha1(&self.hashed_key())
+    }
+
+    /// Returns the fingerprint in upper case hex, as GnuPG shows it.
+    pub fn fingerprint_hex(&self) -> String {
+        self.fingerprint()
+            .iter()
+            .map(|byte| format!("{:02X}", byte))
+            .collect()
+    }
+
+    /// Returns the key id, the last 8 bytes of the fingerprint.
+    pub fn key_id(&self) -> [u8; 8] {
+        let fingerprint = self.fingerprint();
+        let mut key_id = [0; 8];
+        key_id.copy_from_slice(&fingerprint[12..]);
+        key_id
+    }
+
+    /// Returns the creation time of the key.
+    pub fn created(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(self.created.into())
+    }
+
+    /// Returns the public key of the security module.
+    pub fn public_key(&self) -> &PublicKey {
+        &self.public_key
+    }
+
+    fn public_key_algorithm(&self) -> u8 {
+        match self.algorithm {
+            KeyAlgorithm::Rsa { .. } => ALGORITHM_RSA,
+            KeyAlgorithm::Ecdsa { .. } => ALGORITHM_ECDSA,
+            KeyAlgorithm::EdDsa => ALGORITHM_EDDSA,
+        }
+    }
+
+    /// Returns the key as it is hashed into fingerprints and certifications.
+    fn hashed_key(&self) -> Vec<u8> {
+        let mut hashed = vec![0x99];
+        hashed.extend_from_slice(&(self.packet_body.len() as u16).to_be_bytes());
+        hashed.extend_from_slice(&self.packet_body);
+        hashed
+    }
+
+    fn check_provider(
+        &self,
+        provider: &(impl Provider + ?Sized),
+    ) -> Result<(), SecurityModuleError> {
+        if provider.key_metadata()?.public_key_der() != self.public_key.to_der()? {
+            return Err(SecurityModuleError::InvalidPublicKey);
+        }
+        Ok(())
+    }
+}
+
+/// A transferable public key, i.e. an OpenPGP certificate.
+///
+/// `Display` uses the armored encoding, as `gpg --export --armor` writes it, `to_bytes` the
+/// binary one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PgpCertificate {
+    packets: Vec<u8>,
+}
+
+impl PgpCertificate {
+    /// Returns the binary encoding of the certificate.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.packets.clone()
+    }
+}
+
+impl fmt::Display for PgpCertificate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        armor(f, "PGP PUBLIC KEY BLOCK", &self.packets)
+    }
+}
+
+/// A detached OpenPGP v4 signature.
+///
+/// `Display` and `FromStr` use the armored encoding, as `gpg --detach-sign --armor` writes it,
+/// `to_bytes` and `from_bytes` the binary one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PgpSignature {
+    /// The body of the signature packet.
+    body: Vec<u8>,
+}
+
+impl PgpSignature {
+    /// Returns the binary encoding of the signature.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        packet(TAG_SIGNATURE, &self.body)
+    }
+
+    /// Parses the binary encoding of a signature without verifying it.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `PgpSignature`, or a
+    /// `SecurityModuleError::SignatureVerificationError` if `bytes` is not a single signature
+    /// packet of version 4.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SecurityModuleError> {
+        let (tag, body, rest) = read_packet(bytes)?;
+        if tag != TAG_SIGNATURE || !rest.is_empty() {
+            return Err(malformed("The data is not a single signature packet"));
+        }
+        parse_signature(body)?;
+        Ok(Self {
+            body: body.to_vec(),
+        })
+    }
+
+    /// Returns the id of the key the signature claims to be created with.
+    pub fn issuer_key_id(&self) -> Option<[u8; 8]> {
+        let parsed = parse_signature(&self.body).ok()?;
+        [parsed.hashed_subpackets, parsed.unhashed_subpackets]
+            .into_iter()
+            .flat_map(subpackets)
+            .find_map(|(kind, data)| match kind {
+                SUBPACKET_ISSUER => data.try_into().ok(),
+                SUBPACKET_ISSUER_FINGERPRINT if data.len() == 21 => data[13..].try_into().ok(),
+                _ => None,
+            })
+    }
+}
+
+impl fmt::Display for PgpSignature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        armor(f, "PGP SIGNATURE", &self.to_bytes())
+    }
+}
+
+impl FromStr for PgpSignature {
+    type Err = SecurityModuleError;
+
+    /// Parses an armored signature without verifying it.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `PgpSignature`, or a
+    /// `SecurityModuleError::SignatureVerificationError` if `s` is not an armored signature or
+    /// its checksum is wrong.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_bytes(&dearmor(s, "PGP SIGNATURE")?)
+    }
+}
+
+/// Creates the certificate of `primary` with `user_id`, certified by the loaded key of
+/// `provider`, and optionally with a signing subkey.
+///
+/// The primary key may certify and sign. A signing subkey is bound to the primary key by a
+/// signature of the primary key and signs a binding of the primary key back, so its provider
+/// is needed as well.
+///
+/// # Arguments
+///
+/// * `provider` - The provider with the primary key loaded.
+/// * `primary` - The primary key.
+/// * `user_id` - The user id, e.g. `Alice <alice@example.com>`.
+/// * `signing_subkey` - The provider with the subkey loaded, and the subkey.
+///
+/// # Returns
+///
+/// A `Result` containing the `PgpCertificate`, a `SecurityModuleError::SigningError` if
+/// `user_id` is empty, a `SecurityModuleError::InvalidPublicKey` if a provider has another key
+/// loaded, or the error of a provider.
+#[tracing::instrument(skip(provider, primary, signing_subkey))]
+pub fn certificate(
+    provider: &(impl Provider + ?Sized),
+    primary: &PgpKey,
+    user_id: &str,
+    signing_subkey: Option<(&dyn Provider, &PgpKey)>,
+) -> Result<PgpCertificate, SecurityModuleError> {
+    if user_id.is_empty() {
+        return Err(SecurityModuleError::SigningError(
+            "The user id must not be empty".to_owned(),
+        ));
+    }
+    primary.check_provider(provider)?;
+    let created = timestamp(SystemTime::now())?;
+
+    let mut hashed = primary.hashed_key();
+    hashed.push(0xb4);
+    hashed.extend_from_slice(&(user_id.len() as u32).to_be_bytes());
+    hashed.extend_from_slice(user_id.as_bytes());
+    let preferred_hashes = subpacket(SUBPACKET_PREFERRED_HASHES, &[hash_id(primary.hash)]);
+    let flags = subpacket(SUBPACKET_KEY_FLAGS, &[KEY_FLAG_CERTIFY | KEY_FLAG_SIGN]);
+    let certification = signature(
+        provider,
+        primary,
+        POSITIVE_CERTIFICATION,
+        &hashed,
+        &[flags, preferred_hashes].concat(),
+        created,
+    )?;
+
+    let mut packets = packet(TAG_PUBLIC_KEY, &primary.packet_body);
+    packets.extend(packet(TAG_USER_ID, user_id.as_bytes()));
+    packets.extend(packet(TAG_SIGNATURE, &certification));
+
+    if let Some((subkey_provider, subkey)) = signing_subkey {
+        subkey.check_provider(subkey_provider)?;
+        let hashed = [primary.hashed_key(), subkey.hashed_key()].concat();
+        let back_signature = signature(
+            subkey_provider,
+            subkey,
+            PRIMARY_KEY_BINDING,
+            &hashed,
+            &[],
+            created,
+        )?;
+        let subpackets = [
+            subpacket(SUBPACKET_KEY_FLAGS, &[KEY_FLAG_SIGN]),
+            subpacket(SUBPACKET_EMBEDDED_SIGNATURE, &back_signature),
+        ]
+        .concat();
+        let binding = signature(
+            provider,
+            primary,
+            SUBKEY_BINDING,
+            &hashed,
+            &subpackets,
+            created,
+        )?;
+        packets.extend(packet(TAG_PUBLIC_SUBKEY, &subkey.packet_body));
+        packets.extend(packet(TAG_SIGNATURE, &binding));
+    }
+    Ok(PgpCertificate { packets })
+}
+
+/// Signs `data` with `key`, the loaded key of `provider`, in a detached signature.
+///
+/// # Returns
+///
+/// A `Result` containing the `PgpSignature`, a `SecurityModuleError::InvalidPublicKey` if
+/// `provider` has another key loaded, or the error of `provider`.
+#[tracing::instrument(skip(provider, key, data), fields(crypto.payload.size = data.len()))]
+pub fn sign(
+    provider: &(impl Provider + ?Sized),
+    key: &PgpKey,
+    data: &[u8],
+    signature_type: SignatureType,
+) -> Result<PgpSignature, SecurityModuleError> {
+    key.check_provider(provider)?;
+    let body = signature(
+        provider,
+        key,
+        signature_type.id(),
+        &signed_document(data, signature_type),
+        &[],
+        timestamp(SystemTime::now())?,
+    )?;
+    Ok(PgpSignature { body })
+}
+
+/// Verifies that `signature` was created over `data` with `key`.
+///
+/// # Returns
+///
+/// A `Result` that is `Ok(())` if the signature is valid, or a
+/// `SecurityModuleError::InvalidSignature` if it was created by another key, over other data
+/// or is no signature of a document.
+#[tracing::instrument(skip(signature, key, data), fields(crypto.payload.size = data.len()))]
+pub fn verify(
+    signature: &PgpSignature,
+    key: &PgpKey,
+    data: &[u8],
+) -> Result<(), SecurityModuleError> {
+    let parsed = parse_signature(&signature.body)?;
+    let signature_type = match parsed.signature_type {
+        0x00 => SignatureType::Binary,
+        0x01 => SignatureType::Text,
+        _ => return Err(SecurityModuleError::InvalidSignature),
+    };
+    if parsed.public_key_algorithm != key.public_key_algorithm()
+        || parsed.hash_algorithm != hash_id(key.hash)
+    {
+        return Err(SecurityModuleError::InvalidSignature);
+    }
+    let hashed = [
+        &signed_document(data, signature_type)[..],
+        parsed.hashed_part,
+        &trailer(parsed.hashed_part.len()),
+    ]
+    .concat();
+    let digest = digest(key.hash, &hashed);
+    if digest[..2] != *parsed.left_bits {
+        return Err(SecurityModuleError::InvalidSignature);
+    }
+    let decoded = decode_signature(key.algorithm, parsed.mpis)
+        .ok_or(SecurityModuleError::InvalidSignature)?;
+    let signed = match key.algorithm {
+        KeyAlgorithm::EdDsa => digest,
+        _ => hashed,
+    };
+    match key.public_key.verify(&signed, &decoded)? {
+        true => Ok(()),
+        false => Err(SecurityModuleError::InvalidSignature),
+    }
+}
+
+/// Returns the algorithm of `public_key` and the hash it signs with.
+fn key_algorithm(public_key: &PublicKey) -> Result<(KeyAlgorithm, Sha2Bits), SecurityModuleError> {
+    let hash = match public_key.hash() {
+        Hash::Sha2(hash @ (Sha2Bits::Sha256 | Sha2Bits::Sha384 | Sha2Bits::Sha512)) => hash,
+        _ => return Err(SecurityModuleError::UnsupportedAlgorithm),
+    };
+    let algorithm = match public_key.algorithm() {
+        AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(curve)) => match (curve, hash) {
+            (EccCurves::Curve25519, _) => KeyAlgorithm::EdDsa,
+            (EccCurves::P256, Sha2Bits::Sha256) => KeyAlgorithm::Ecdsa { scalar_len: 32 },
+            (EccCurves::P384, Sha2Bits::Sha384) => KeyAlgorithm::Ecdsa { scalar_len: 48 },
+            (EccCurves::P521, Sha2Bits::Sha512) => KeyAlgorithm::Ecdsa { scalar_len: 66 },
+            _ => return Err(SecurityModuleError::UnsupportedAlgorithm),
+        },
+        AsymmetricEncryption::Rsa(bits)
+            if public_key.rsa_padding() == RsaSignaturePadding::Pkcs1 =>
+        {
+            KeyAlgorithm::Rsa {
+                modulus_len: u32::from(bits) as usize / 8,
+            }
+        }
+        _ => return Err(SecurityModuleError::UnsupportedAlgorithm),
+    };
+    Ok((algorithm, hash))
+}
+
+/// Creates the body of a signature packet of `signature_type` by `key` over `hashed`, the data
+/// that precedes the signature fields in the hash.
+fn signature(
+    provider: &(impl Provider + ?Sized),
+    key: &PgpKey,
+    signature_type: u8,
+    hashed: &[u8],
+    subpackets: &[u8],
+    created: u32,
+) -> Result<Vec<u8>, SecurityModuleError> {
+    let mut issuer_fingerprint = vec![KEY_VERSION];
+    issuer_fingerprint.extend_from_slice(&key.fingerprint());
+    let hashed_subpackets = [
+        subpacket(SUBPACKET_CREATION_TIME, &created.to_be_bytes()),
+        subpacket(SUBPACKET_ISSUER_FINGERPRINT, &issuer_fingerprint),
+        subpackets.to_vec(),
+    ]
+    .concat();
+    let mut body = vec![
+        SIGNATURE_VERSION,
+        signature_type,
+        key.public_key_algorithm(),
+        hash_id(key.hash),
+    ];
+    body.extend_from_slice(&(hashed_subpackets.len() as u16).to_be_bytes());
+    body.extend_from_slice(&hashed_subpackets);
+
+    let message = [hashed, &body, &trailer(body.len())].concat();
+    let digest = digest(key.hash, &message);
+    // EdDSA signs the digest, the other algorithms hash the message themselves.
+    let signature = match key.algorithm {
+        KeyAlgorithm::EdDsa => provider.sign_data(&digest)?,
+        _ => provider.sign_data(&message)?,
+    };
+
+    let unhashed_subpackets = subpacket(SUBPACKET_ISSUER, &key.key_id());
+    body.extend_from_slice(&(unhashed_subpackets.len() as u16).to_be_bytes());
+    body.extend_from_slice(&unhashed_subpackets);
+    body.extend_from_slice(&digest[..2]);
+    match key.algorithm {
+        KeyAlgorithm::Rsa { .. } => put_mpi(&mut body, &signature),
+        KeyAlgorithm::Ecdsa { scalar_len } => {
+            let raw = signature_format::der_to_raw(&signature, scalar_len)?;
+            let (r, s) = raw.split_at(scalar_len);
+            put_mpi(&mut body, r);
+            put_mpi(&mut body, s);
+        }
+        KeyAlgorithm::EdDsa => {
+            if signature.len() != 64 {
+                return Err(SecurityModuleError::SigningError(
+                    "The Ed25519 signature has an invalid length".to_owned(),
+                ));
+            }
+            let (r, s) = signature.split_at(32);
+            put_mpi(&mut body, r);
+            put_mpi(&mut body, s);
+        }
+    }
+    Ok(body)
+}
+
+/// Decodes the MPIs of a signature into the encoding of `PublicKey::verify`.
+fn decode_signature(algorithm: KeyAlgorithm, mut mpis: &[u8]) -> Option<Vec<u8>> {
+    let (count, len) = match algorithm {
+        KeyAlgorithm::Rsa { modulus_len } => (1, modulus_len),
+        KeyAlgorithm::Ecdsa { scalar_len } => (2, scalar_len),
+        KeyAlgorithm::EdDsa => (2, 32),
+    };
+    let mut raw = Vec::with_capacity(count * len);
+    for _ in 0..count {
+        let value = get_mpi(&mut mpis)?;
+        if value.len() > len {
+            return None;
+        }
+        raw.resize(raw.len() + len - value.len(), 0);
+        raw.extend_from_slice(value);
+    }
+    if !mpis.is_empty() {
+        return None;
+    }
+    match algorithm {
+        KeyAlgorithm::Ecdsa { .. } => signature_format::raw_to_der(&raw).ok(),
+        _ => Some(raw),
+    }
+}
+
+/// The fields of a v4 signature packet.
+struct ParsedSignature<'a> {
+    signature_type: u8,
+    public_key_algorithm: u8,
+    hash_algorithm: u8,
+    /// The fields from the version to the hashed subpackets, which are hashed.
+    hashed_part: &'a [u8],
+    hashed_subpackets: &'a [u8],
+    unhashed_subpackets: &'a [u8],
+    left_bits: &'a [u8],
+    mpis: &'a [u8],
+}
+
+fn parse_signature(body: &[u8]) -> Result<ParsedSignature<'_>, SecurityModuleError> {
+    let truncated = || malformed("The signature is truncated");
+    let (header, rest) = body.split_first_chunk::<6>().ok_or_else(truncated)?;
+    if header[0] != SIGNATURE_VERSION {
+        return Err(malformed("The signature has an unsupported version"));
+    }
+    let hashed_len = u16::from_be_bytes([header[4], header[5]]) as usize;
+    let (hashed_subpackets, rest) = rest.split_at_checked(hashed_len).ok_or_else(truncated)?;
+    let (unhashed_len, rest) = rest.split_first_chunk::<2>().ok_or_else(truncated)?;
+    let (unhashed_subpackets, rest) = rest
+        .split_at_checked(u16::from_be_bytes(*unhashed_len) as usize)
+        .ok_or_else(truncated)?;
+    let (left_bits, mpis) = rest.split_at_checked(2).ok_or_else(truncated)?;
+    Ok(ParsedSignature {
+        signature_type: header[1],
+        public_key_algorithm: header[2],
+        hash_algorithm: header[3],
+        hashed_part: &body[..6 + hashed_len],
+        hashed_subpackets,
+        unhashed_subpackets,
+        left_bits,
+        mpis,
+    })
+}
+
+/// Returns the document as it is hashed for `signature_type`.
+fn signed_document(data: &[u8], signature_type: SignatureType) -> Vec<u8> {
+    match signature_type {
+        SignatureType::Binary => data.to_vec(),
+        SignatureType::Text => {
+            let mut canonical = Vec::with_capacity(data.len());
+            for (i, &byte) in data.iter().enumerate() {
+                if byte == b'\n' && (i == 0 || data[i - 1] != b'\r') {
+                    canonical.push(b'\r');
+                }
+                canonical.push(byte);
+            }
+            canonical
+        }
+    }
+}
+
+fn trailer(hashed_len: usize) -> [u8; 6] {
+    let len = (hashed_len as u32).to_be_bytes();
+    [SIGNATURE_VERSION, 0xff, len[0], len[1], len[2], len[3]]
+}
+
+fn hash_id(hash: Sha2Bits) -> u8 {
+    match hash {
+        Sha2Bits::Sha384 => 9,
+        Sha2Bits::Sha512 => 10,
+        _ => 8,
+    }
+}
+
+fn digest(hash: Sha2Bits, data: &[u8]) -> Vec<u8> {
+    match hash {
+        Sha2Bits::Sha384 => sha384(data).to_vec(),
+        Sha2Bits::Sha512 => sha512(data).to_vec(),
+        _ => sha256(data).to_vec(),
+    }
+}
+
+fn timestamp(time: SystemTime) -> Result<u32, SecurityModuleError> {
+    time.duration_since(UNIX_EPOCH)
+        .ok()
+        .and_then(|since| u32::try_from(since.as_secs()).ok())
+        .ok_or_else(|| {
+            SecurityModuleError::SigningError(
+                "OpenPGP times must be between 1970 and 2106".to_owned(),
+            )
+        })
+}
+
+/// Encodes a packet with the new format header.
+fn packet(tag: u8, body: &[u8]) -> Vec<u8> {
+    let mut encoded = vec![0xc0 | tag];
+    put_length(&mut encoded, body.len());
+    encoded.extend_from_slice(body);
+    encoded
+}
+
+/// Reads a packet with an old or new format header, returning its tag, body and the rest.
+fn read_packet(bytes: &[u8]) -> Result<(u8, &[u8], &[u8]), SecurityModuleError> {
+    let truncated = || malformed("The packet is truncated");
+    let (&header, rest) = bytes.split_first().ok_or_else(truncated)?;
+    if header & 0x80 == 0 {
+        return Err(malformed("The data is not an OpenPGP packet"));
+    }
+    let (tag, len, rest) = if header & 0x40 != 0 {
+        let (len, rest) = get_length(rest).ok_or_else(truncated)?;
+        (header & 0x3f, len, rest)
+    } else {
+        let len_bytes = match header & 0x03 {
+            0 => 1,
+            1 => 2,
+            2 => 4,
+            _ => {
+                return Err(malformed(
+                    "Packets of indeterminate length are not supported",
+                ))
+            }
+        };
+        let (len, rest) = rest.split_at_checked(len_bytes).ok_or_else(truncated)?;
+        let len = len.iter().fold(0, |len, &byte| (len << 8) | byte as usize);
+        ((header >> 2) & 0x0f, len, rest)
+    };
+    let (body, rest) = rest.split_at_checked(len).ok_or_else(truncated)?;
+    Ok((tag, body, rest))
+}
+
+fn subpacket(kind: u8, data: &[u8]) -> Vec<u8> {
+    let mut encoded = Vec::new();
+    put_length(&mut encoded, data.len() + 1);
+    encoded.push(kind);
+    encoded.extend_from_slice(data);
+    encoded
+}
+
+/// Iterates over the type and data of subpackets, stopping at malformed ones.
+fn subpackets(mut area: &[u8]) -> impl Iterator<Item = (u8, &[u8])> {
+    std::iter::from_fn(move || {
+        let (len, rest) = get_length(area)?;
+        let (packet, rest) = rest.split_at_checked(len)?;
+        area = rest;
+        let (&kind, data) = packet.split_first()?;
+        // The high bit marks critical subpackets.
+        Some((kind & 0x7f, data))
+    })
+}
+
+/// Writes a length of a new format packet or subpacket.
+fn put_length(buffer: &mut Vec<u8>, len: usize) {
+    match len {
+        0..=191 => buffer.push(len as u8),
+        192..=8383 => {
+            let len = len - 192;
+            buffer.push((len >> 8) as u8 + 192);
+            buffer.push(len as u8);
+        }
+        _ => {
+            buffer.push(0xff);
+            buffer.extend_from_slice(&(len as u32).to_be_bytes());
+        }
+    }
+}
+
+fn get_length(bytes: &[u8]) -> Option<(usize, &[u8])> {
+    let (&first, rest) = bytes.split_first()?;
+    match first {
+        0..=191 => Some((first as usize, rest)),
+        192..=223 => {
+            let (&second, rest) = rest.split_first()?;
+            Some((
+                (((first - 192) as usize) << 8) + second as usize + 192,
+                rest,
+            ))
+        }
+        255 => {
+            let (len, rest) = rest.split_first_chunk::<4>()?;
+            Some((u32::from_be_bytes(*len) as usize, rest))
+        }
+        // Partial body lengths are only allowed for data packets.
+        _ => None,
+    }
+}
+
+/// Writes a multiprecision integer, the big endian `value` with its length in bits.
+fn put_mpi(buffer: &mut Vec<u8>, value: &[u8]) {
+    let start = value
+        .iter()
+        .position(|&byte| byte != 0)
+        .unwrap_or(value.len());
+    let value = &value[start..];
+    let bits = match value.first() {
+        Some(first) => (value.len() - 1) * 8 + (8 - first.leading_zeros() as usize),
+        None => 0,
+    };
+    buffer.extend_from_slice(&(bits as u16).to_be_bytes());
+    buffer.extend_from_slice(value);
+}
+
+fn get_mpi<'a>(bytes: &mut &'a [u8]) -> Option<&'a [u8]> {
+    let (bits, rest) = bytes.split_first_chunk::<2>()?;
+    let (value, rest) = rest.split_at_checked((u16::from_be_bytes(*bits) as usize).div_ceil(8))?;
+    *bytes = rest;
+    Some(value)
+}
+
+/// Writes the ASCII armor of `data` with the CRC-24 checksum GnuPG expects.
+fn armor(f: &mut fmt::Formatter<'_>, label: &str, data: &[u8]) -> fmt::Result {
+    writeln!(f, "-----BEGIN {}-----", label)?;
+    writeln!(f)?;
+    let encoded = BASE64_STANDARD.encode(data);
+    for line in encoded.as_bytes().chunks(LINE_LEN) {
+        writeln!(f, "{}", String::from_utf8_lossy(line))?;
+    }
+    writeln!(
+        f,
+        "={}",
+        BASE64_STANDARD.encode(&crc24(data).to_be_bytes()[1..])
+    )?;
+    writeln!(f, "-----END {}-----", label)
+}
+
+fn dearmor(s: &str, label: &str) -> Result<Vec<u8>, SecurityModuleError> {
+    let not_armored = || malformed("The signature is not armored");
+    let body = s
+        .trim()
+        .strip_prefix(&format!("-----BEGIN {}-----", label))
+        .and_then(|s| s.strip_suffix(&format!("-----END {}-----", label)))
+        .ok_or_else(not_armored)?;
+    let mut encoded = String::new();
+    let mut checksum = None;
+    for line in body.lines().map(str::trim) {
+        match line.strip_prefix('=') {
+            Some(crc) if crc.len() == 4 => checksum = Some(crc),
+            // Armor headers, e.g. `Comment: ...`, are skipped.
+            _ if line.contains(':') => {}
+            _ => encoded.push_str(line),
+        }
+    }
+    let data = BASE64_STANDARD
+        .decode(encoded)
+        .map_err(|e| malformed(&e.to_string()))?;
+    if let Some(checksum) = checksum {
+        if BASE64_STANDARD.decode(checksum).ok().as_deref()
+            != Some(&crc24(&data).to_be_bytes()[1..])
+        {
+            return Err(malformed("The armor checksum is wrong"));
+        }
+    }
+    Ok(data)
+}
+
+/// The CRC-24 of RFC 4880, section 6.1.
+fn crc24(data: &[u8]) -> u32 {
+    let mut crc: u32 = 0xb704ce;
+    for &byte in data {
+        crc ^= (byte as u32) << 16;
+        for _ in 0..8 {
+            crc <<= 1;
+            if crc & 0x1000000 != 0 {
+                crc ^= 0x1864cfb;
+            }
+        }
+    }
+    crc & 0xffffff
+}
+
+fn malformed(message: &str) -> SecurityModuleError {
+    SecurityModuleError::SignatureVerificationError(message.to_owned())
+}
@@ -0,0 +1,340 @@
+//! Password verifiers bound to a key of the security module.
+//!
+//! `harden_password` stretches a password with Argon2id and authenticates the result with
+//! HMAC-SHA-256 under a random key, which is encrypted with `encrypt_data` of the security module
+//! and kept in the `Verifier`. `verify_password` decrypts that key again to check a password:
+//!
+//! ```rust,ignore
+//! use crypto_layer::common::password::{harden_password, verify_password, Verifier};
+//!
+//! let verifier = harden_password(&provider, "password_key", b"correct horse")?;
+//! database.store(user, verifier.to_string());
+//!
+//! let verifier: Verifier = database.load(user).parse()?;
+//! assert!(verify_password(&provider, &verifier, b"correct horse")?);
+//! ```
+//!
+//! Without the key of the security module the HMAC key cannot be decrypted, so a stolen database
+//! of verifiers does not allow guessing passwords off the device, no matter how weak they are.
+//! Every guess on the device costs an Argon2id computation and a decryption by the security
+//! module.
+//!
+//! Argon2id is not approved, so both functions fail with `SecurityModuleError::NotFipsApproved`
+//! in FIPS mode, see `fips`.
+//!
+//! Verifiers are encoded as `$crypto-layer-argon2id$v=1$m=<KiB>,t=<passes>$` followed by the key
+//! id, the salt, the encrypted HMAC key and the tag in unpadded base64, separated by `$`.
+
+use crate::common::{
+    constant_time, crypto::secret::SecretBytes, error::SecurityModuleError, fips,
+    traits::key_handle::KeyHandle,
+};
+use base64::{prelude::BASE64_STANDARD_NO_PAD, Engine};
+use crypto_layer_core::CoreError;
+use openssl::{hash::MessageDigest, pkey::PKey, rand::rand_bytes, sign::Signer};
+use sodiumoxide::crypto::pwhash::argon2id13;
+use std::{fmt, str::FromStr};
+
+/// The identifier at the start of an encoded `Verifier`.
+const IDENTIFIER: &str = "crypto-layer-argon2id";
+
+/// The version of the verifier format.
+const VERSION: u32 = 1;
+
+/// The length of the HMAC key in bytes.
+const HMAC_KEY_LEN: usize = 32;
+
+/// The length of the Argon2id output in bytes.
+const HASH_LEN: usize = 32;
+
+/// The cost parameters of Argon2id.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PasswordParams {
+    /// The number of passes over the memory.
+    pub passes: u32,
+    /// The memory in KiB.
+    pub memory_kib: u32,
+}
+
+impl PasswordParams {
+    /// The parameters for interactive logins: 2 passes over 64 MiB.
+    pub const INTERACTIVE: Self = Self {
+        passes: 2,
+        memory_kib: 64 * 1024,
+    };
+
+    /// The parameters for less frequent operations, e.g. unlocking a backup: 3 passes over
+    /// 256 MiB.
+    pub const MODERATE: Self = Self {
+        passes: 3,
+        memory_kib: 256 * 1024,
+    };
+
+    /// The smallest memory Argon2id accepts in KiB.
+    pub const MIN_MEMORY_KIB: u32 = 8;
+
+    /// The largest memory accepted in KiB, which keeps a manipulated verifier from exhausting
+    /// the memory of the device.
+    pub const MAX_MEMORY_KIB: u32 = 4 * 1024 * 1024;
+
+    /// The largest number of passes accepted.
+    pub const MAX_PASSES: u32 = 64;
+
+    pub(crate) fn validate(&self) -> Result<(), CoreError> {
+        if !(1..=Self::MAX_PASSES).contains(&self.passes) {
+            return Err(CoreError::InvalidField("t"));
+        }
+        if !(Self::MIN_MEMORY_KIB..=Self::MAX_MEMORY_KIB).contains(&self.memory_kib) {
+            return Err(CoreError::InvalidField("m"));
+        }
+        Ok(())
+    }
+}
+
+impl Default for PasswordParams {
+    fn default() -> Self {
+        Self::INTERACTIVE
+    }
+}
+
+/// The stored form of a password, see the module documentation.
+///
+/// `Display` and `FromStr` convert it from and to its encoding, which is safe to store in a
+/// database.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Verifier {
+    key_id: String,
+    params: PasswordParams,
+    salt: Vec<u8>,
+    wrapped_key: Vec<u8>,
+    tag: Vec<u8>,
+}
+
+impl Verifier {
+    /// Returns the id of the key the HMAC key is encrypted with.
+    pub fn key_id(&self) -> &str {
+        &self.key_id
+    }
+
+    /// Returns the Argon2id parameters the password was hardened with.
+    ///
+    /// Applications can compare them with their current parameters after a successful
+    /// `verify_password` and harden the password again if they are weaker.
+    pub fn params(&self) -> PasswordParams {
+        self.params
+    }
+}
+
+impl fmt::Debug for Verifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Verifier")
+            .field("key_id", &self.key_id)
+            .field("params", &self.params)
+            .finish_non_exhaustive()
+    }
+}
+
+impl fmt::Display for Verifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "${}$v={}$m={},t={}",
+            IDENTIFIER, VERSION, self.params.memory_kib, self.params.passes
+        )?;
+        for field in [
+            self.key_id.as_bytes(),
+            &self.salt,
+            &self.wrapped_key,
+            &self.tag,
+        ] {
+            write!(f, "${}", BASE64_STANDARD_NO_PAD.encode(field))?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Verifier {
+    type Err = SecurityModuleError;
+
+    /// Parses an encoded verifier.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `Verifier`, or a `SecurityModuleError::Encoding` if `s` is not a
+    /// verifier of this version or its parameters are out of range.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut fields = s.split('$');
+        if fields.next() != Some("") || fields.next() != Some(IDENTIFIER) {
+            return Err(CoreError::InvalidMagic.into());
+        }
+        let version = fields
+            .next()
+            .and_then(|field| field.strip_prefix("v="))
+            .ok_or(CoreError::MissingField("v"))?;
+        if version != VERSION.to_string() {
+            return Err(CoreError::UnsupportedVersion(version.parse().unwrap_or(u8::MAX)).into());
+        }
+
+        let params = fields.next().ok_or(CoreError::MissingField("m"))?;
+        let (memory_kib, passes) = params
+            .strip_prefix("m=")
+            .and_then(|params| params.split_once(",t="))
+            .ok_or(CoreError::InvalidField("m"))?;
+        let params = PasswordParams {
+            passes: passes.parse().map_err(|_| CoreError::InvalidField("t"))?,
+            memory_kib: memory_kib
+                .parse()
+                .map_err(|_| CoreError::InvalidField("m"))?,
+        };
+        params.validate()?;
+
+        let mut decode = |name: &'static str| {
+            let field = fields.next().ok_or(CoreError::MissingField(name))?;
+            BASE64_STANDARD_NO_PAD
+                .decode(field)
+                .map_err(|_| CoreError::InvalidField(name))
+        };
+        let key_id =
+            String::from_utf8(decode("key_id")?).map_err(|_| CoreError::InvalidField("key_id"))?;
+        let salt = decode("salt")?;
+        let wrapped_key = decode("wrapped_key")?;
+        let tag = decode("tag")?;
+        if salt.len() != argon2id13::SALTBYTES {
+            return Err(CoreError::InvalidField("salt").into());
+        }
+        if tag.len() != HASH_LEN {
+            return Err(CoreError::InvalidField("tag").into());
+        }
+        if fields.next().is_some() {
+            return Err(CoreError::InvalidLength.into());
+        }
+
+        Ok(Self {
+            key_id,
+            params,
+            salt,
+            wrapped_key,
+            tag,
+        })
+    }
+}
+
+/// Hardens `password` with `PasswordParams::INTERACTIVE`.
+///
+/// # Arguments
+///
+/// * `key_handle` - Encrypts the HMAC key with `encrypt_data`, e.g. a provider with the loaded
+///   key `key_id`.
+/// * `key_id` - The id of the key, recorded in the verifier.
+/// * `password` - The password to be hardened.
+///
+/// # Returns
+///
+/// A `Result` containing the `Verifier`, or a `SecurityModuleError::EncryptionError` if Argon2id
+/// cannot allocate its memory or the error of `key_handle` if the HMAC key cannot be encrypted.
+pub fn harden_password(
+    key_handle: &(impl KeyHandle + ?Sized),
+    key_id: &str,
+    password: &[u8],
+) -> Result<Verifier, SecurityModuleError> {
+    harden_password_with(key_handle, key_id, password, PasswordParams::INTERACTIVE)
+}
+
+/// Hardens `password` like `harden_password`, with the given Argon2id parameters.
+#[tracing::instrument(skip(key_handle, password))]
+pub fn harden_password_with(
+    key_handle: &(impl KeyHandle + ?Sized),
+    key_id: &str,
+    password: &[u8],
+    params: PasswordParams,
+) -> Result<Verifier, SecurityModuleError> {
+    params.validate()?;
+    let encryption_error =
+        |e: openssl::error::ErrorStack| SecurityModuleError::EncryptionError(e.to_string());
+    let mut salt = vec![0; argon2id13::SALTBYTES];
+    let mut hmac_key = SecretBytes::zeroed(HMAC_KEY_LEN);
+    rand_bytes(&mut salt).map_err(encryption_error)?;
+    rand_bytes(&mut hmac_key).map_err(encryption_error)?;
+
+    let hash = stretch(
+        password,
+        &salt,
+        params,
+        SecurityModuleError::EncryptionError,
+    )?;
+    let tag = hmac(&hmac_key, &hash).map_err(encryption_error)?;
+    Ok(Verifier {
+        key_id: key_id.to_owned(),
+        params,
+        salt,
+        wrapped_key: key_handle.encrypt_data(&hmac_key)?,
+        tag,
+    })
+}
+
+/// Checks `password` against `verifier`.
+///
+/// # Arguments
+///
+/// * `key_handle` - Decrypts the HMAC key with `decrypt_data`, e.g. a provider with the loaded key
+///   of `verifier.key_id()`.
+/// * `verifier` - The verifier of `harden_password`.
+/// * `password` - The password to be checked.
+///
+/// # Returns
+///
+/// A `Result` containing whether the password matches, or the error of `key_handle` if the HMAC
+/// key cannot be decrypted, e.g. because the verifier was created with another key or copied from
+/// another device, or a `SecurityModuleError::DecryptionError` if Argon2id cannot allocate its
+/// memory.
+#[tracing::instrument(skip(key_handle, password))]
+pub fn verify_password(
+    key_handle: &(impl KeyHandle + ?Sized),
+    verifier: &Verifier,
+    password: &[u8],
+) -> Result<bool, SecurityModuleError> {
+    verifier.params.validate()?;
+    let hmac_key = key_handle.decrypt_data(&verifier.wrapped_key)?;
+    let hash = stretch(
+        password,
+        &verifier.salt,
+        verifier.params,
+        SecurityModuleError::DecryptionError,
+    )?;
+    let tag =
+        hmac(&hmac_key, &hash).map_err(|e| SecurityModuleError::DecryptionError(e.to_string()))?;
+    Ok(constant_time::eq(&tag, &verifier.tag))
+}
+
+/// Stretches `password` with Argon2id, reporting failures with `error`. Fails with
+/// `SecurityModuleError::NotFipsApproved` in FIPS mode.
+pub(crate) fn stretch(
+    password: &[u8],
+    salt: &[u8],
+    params: PasswordParams,
+    error: fn(String) -> SecurityModuleError,
+) -> Result<SecretBytes, SecurityModuleError> {
+    if fips::is_enabled() {
+        fips::check_name("argon2id")?;
+    }
+    let salt = argon2id13::Salt::from_slice(salt)
+        .ok_or_else(|| error("The salt has an invalid length".to_owned()))?;
+    sodiumoxide::init().map_err(|_| error("libsodium cannot be initialized".to_owned()))?;
+    let mut hash = SecretBytes::zeroed(HASH_LEN);
+    argon2id13::derive_key(
+        &mut hash,
+        password,
+        &salt,
+        argon2id13::OpsLimit(params.passes as usize),
+        argon2id13::MemLimit(params.memory_kib as usize * 1024),
+    )
+    .map_err(|_| error("Argon2id cannot allocate its memory".to_owned()))?;
+    Ok(hash)
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Result<Vec<u8>, openssl::error::ErrorStack> {
+    let key = PKey::hmac(key)?;
+    let mut signer = Signer::new(MessageDigest::sha256(), &key)?;
+    signer.update(data)?;
+    signer.sign_to_vec()
+}
@@ -0,0 +1,152 @@
+//! Export of software-backed identities as password-protected PKCS#12 bundles.
+//!
+//! Legacy tooling such as browsers, mail clients, Java key stores and Windows certificate stores
+//! imports identities as PKCS#12 (`.p12` or `.pfx`) files. Keys of providers that keep them in
+//! software, e.g. the `MockProvider`, can be exported together with their certificate chain if
+//! they were created exportable, see `KeySpec::exportable`. Keys of the Secure Enclave and the
+//! TPM never leave the device and cannot be exported:
+//!
+//! ```rust,ignore
+//! use crypto_layer::common::pkcs12::{self, Pkcs12Options};
+//!
+//! let options = Pkcs12Options::default().friendly_name("Device identity");
+//! let p12 = pkcs12::export_identity(&provider, &[certificate, intermediate], password, &options)?;
+//! std::fs::write("identity.p12", p12)?;
+//! ```
+//!
+//! By default the key and the certificates are encrypted with AES-256-CBC under a key derived
+//! with PBKDF2 and the bundle is authenticated with HMAC-SHA-256, as OpenSSL 3 does.
+//! `Pkcs12Options::legacy` selects the `PBE-SHA1-3DES` and HMAC-SHA-1 scheme for tools that
+//! predate it, e.g. Java 8 and Windows Server 2016.
+
+use crate::common::{error::SecurityModuleError, traits::module_provider::Provider};
+use crypto_layer_core::CoreError;
+use openssl::{
+    error::ErrorStack, hash::MessageDigest, nid::Nid, pkcs12::Pkcs12, pkey::PKey, stack::Stack,
+    x509::X509,
+};
+
+/// The iteration count of the key derivation and the MAC used unless configured otherwise.
+pub const DEFAULT_ITERATIONS: u32 = 100_000;
+
+/// The iteration count of legacy bundles, which old tools expect.
+const LEGACY_ITERATIONS: u32 = 2048;
+
+/// The encryption and naming of a PKCS#12 bundle.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pkcs12Options {
+    friendly_name: Option<String>,
+    legacy: bool,
+    iterations: u32,
+}
+
+impl Default for Pkcs12Options {
+    fn default() -> Self {
+        Self {
+            friendly_name: None,
+            legacy: false,
+            iterations: DEFAULT_ITERATIONS,
+        }
+    }
+}
+
+impl Pkcs12Options {
+    /// Sets the name that importing tools show for the identity.
+    pub fn friendly_name(mut self, friendly_name: impl Into<String>) -> Self {
+        self.friendly_name = Some(friendly_name.into());
+        self
+    }
+
+    /// Encrypts with `PBE-SHA1-3DES` and authenticates with HMAC-SHA-1 with 2048 iterations,
+    /// which tools that predate OpenSSL 3 support.
+    pub fn legacy(mut self) -> Self {
+        self.legacy = true;
+        self.iterations = LEGACY_ITERATIONS;
+        self
+    }
+
+    /// Sets the iteration count of the key derivation and the MAC.
+    pub fn iterations(mut self, iterations: u32) -> Self {
+        self.iterations = iterations;
+        self
+    }
+}
+
+/// Exports the loaded key of `provider` and its certificate chain as PKCS#12 bundle.
+///
+/// # Arguments
+///
+/// * `provider` - The provider with the loaded key, which has to be exportable.
+/// * `certificate_chain` - The DER encoded certificates of the key, leaf first.
+/// * `password` - The password the bundle is encrypted with.
+/// * `options` - The encryption and naming of the bundle.
+///
+/// # Returns
+///
+/// A `Result` containing the DER encoded bundle, the error of `Provider::export_private_key` if
+/// the key cannot be exported, a `SecurityModuleError::Encoding` if the password or the chain is
+/// empty or a certificate cannot be parsed, a `SecurityModuleError::InvalidPublicKey` if the
+/// leaf certificate belongs to another key, or a `SecurityModuleError::EncryptionError` if the
+/// bundle cannot be created.
+#[tracing::instrument(skip_all, fields(crypto.certificates = certificate_chain.len()))]
+pub fn export_identity(
+    provider: &(impl Provider + ?Sized),
+    certificate_chain: &[Vec<u8>],
+    password: &str,
+    options: &Pkcs12Options,
+) -> Result<Vec<u8>, SecurityModuleError> {
+    if password.is_empty() {
+        return Err(CoreError::MissingField("password").into());
+    }
+    let (leaf, intermediates) = certificate_chain
+        .split_first()
+        .ok_or(CoreError::MissingField("certificate"))?;
+    let parse =
+        |der: &Vec<u8>| X509::from_der(der).map_err(|_| CoreError::InvalidField("certificate"));
+    let leaf = parse(leaf)?;
+    let mut ca = Stack::new().map_err(encryption_error)?;
+    for intermediate in intermediates {
+        ca.push(parse(intermediate)?).map_err(encryption_error)?;
+    }
+
+    let pkcs8_der = provider.export_private_key()?;
+    let private_key =
+        PKey::private_key_from_pkcs8(&pkcs8_der).map_err(|_| SecurityModuleError::KeyError)?;
+    let matches = leaf
+        .public_key()
+        .map(|public_key| public_key.public_eq(&private_key))
+        .unwrap_or(false);
+    if !matches {
+        return Err(SecurityModuleError::InvalidPublicKey);
+    }
+
+    let mut builder = Pkcs12::builder();
+    builder
+        .pkey(&private_key)
+        .cert(&leaf)
+        .ca(ca)
+        .key_iter(options.iterations)
+        .mac_iter(options.iterations);
+    if let Some(friendly_name) = &options.friendly_name {
+        builder.name(friendly_name);
+    }
+    if options.legacy {
+        builder
+            .key_algorithm(Nid::PBE_WITHSHA1AND3_KEY_TRIPLEDES_CBC)
+            .cert_algorithm(Nid::PBE_WITHSHA1AND3_KEY_TRIPLEDES_CBC)
+            .mac_md(MessageDigest::sha1());
+    } else {
+        builder
+            .key_algorithm(Nid::AES_256_CBC)
+            .cert_algorithm(Nid::AES_256_CBC)
+            .mac_md(MessageDigest::sha256());
+    }
+    builder
+        .build2(password)
+        .and_then(|pkcs12| pkcs12.to_der())
+        .map_err(encryption_error)
+}
+
+fn encryption_error(e: ErrorStack) -> SecurityModuleError {
+    SecurityModuleError::EncryptionError(e.to_string())
+}
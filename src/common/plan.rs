@@ -0,0 +1,161 @@
+//! Dry runs of instance and key creation.
+//!
+//! A plan reports what `SecModules::get_instance` and `Provider::create_key` would do with the
+//! current configuration, without creating an instance or touching a security module, so that
+//! infrastructure-as-code tools can compare the desired state with the actual one:
+//!
+//! ```rust,ignore
+//! use crypto_layer::common::crypto::key_spec::{KeyAlgorithm::*, KeyPurpose::*, KeySpec};
+//!
+//! let plan = KeySpec::builder()
+//!     .algorithm(EcP256)
+//!     .usage(Sign)
+//!     .label("device-identity")
+//!     .plan()?;
+//! assert!(plan.allowed());
+//! println!("{} in {}", plan.instance.stored_as, plan.instance.providers[0].storage);
+//! ```
+//!
+//! With the `serde` feature, plans can be serialized, e.g. to JSON.
+
+use crate::common::{
+    config::{CryptoConfig, FallbackPolicy},
+    crypto::key_spec::KeySpec,
+    error::SecurityModuleError,
+    factory::SecModules,
+    key_id::KeyId,
+    sunset::SunsetAction,
+};
+use std::{collections::BTreeMap, time::Duration};
+
+/// A provider an instance would be created with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ProviderPlan {
+    /// The name of the provider, see `config::PROVIDER_NAMES`.
+    pub provider: String,
+    /// Where the provider keeps the key.
+    pub storage: String,
+    /// Whether `SecModules` already holds an instance of the provider, which is returned instead
+    /// of a new one.
+    pub cached: bool,
+}
+
+/// What `SecModules::get_instance` and `SecModules::get_preferred_instance` would create.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct InstancePlan {
+    pub key_id: KeyId,
+    /// The id the security module stores the key under, which includes the namespace.
+    pub stored_as: String,
+    pub namespace: Option<String>,
+    /// The providers that are enabled in this build, in the order they would be tried. Unless
+    /// the fallback policy is `FallbackPolicy::NextProvider`, only the first one is used.
+    pub providers: Vec<ProviderPlan>,
+    pub fallback: FallbackPolicy,
+    /// Whether the calls of the instance would be recorded in the audit log.
+    pub audited: bool,
+    /// Calls taking at least this long would be logged as a warning.
+    pub slow_operation_threshold: Option<Duration>,
+    /// The deprecated algorithms, see `sunset`.
+    pub deprecated: BTreeMap<String, SunsetAction>,
+}
+
+impl InstancePlan {
+    /// Plans an instance for `key_id` with `config` instead of the configuration of the crate.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the plan on success, or a `SecurityModuleError::InvalidKeyId` if
+    /// `key_id` is not a valid `KeyId`.
+    pub fn for_config(config: &CryptoConfig, key_id: &str) -> Result<Self, SecurityModuleError> {
+        let key_id = KeyId::new(key_id)?;
+        let namespace = config.namespace();
+        let stored_as = match &namespace {
+            Some(namespace) => namespace.scope(&key_id),
+            None => key_id.to_string(),
+        };
+        let cached = SecModules::instances();
+        let providers = config
+            .providers
+            .iter()
+            .filter_map(|name| {
+                let module = crate::common::config::security_module(name)?;
+                Some(ProviderPlan {
+                    provider: name.clone(),
+                    storage: storage(name, &stored_as),
+                    cached: cached.iter().any(|(cached, _)| *cached == module),
+                })
+            })
+            .collect();
+        Ok(Self {
+            key_id,
+            stored_as,
+            namespace: namespace.map(|namespace| namespace.name().to_owned()),
+            providers,
+            fallback: config.fallback,
+            audited: SecModules::audit_enabled(),
+            slow_operation_threshold: SecModules::latency_config().slow_threshold,
+            deprecated: config.deprecated.clone(),
+        })
+    }
+}
+
+/// What creating a key with a `KeySpec` would do, returned by `KeySpecBuilder::plan`.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct KeyPlan {
+    pub spec: KeySpec,
+    pub instance: InstancePlan,
+    /// The deprecated algorithm of the key with the strictest action, or `None` if the key does
+    /// not use a deprecated algorithm.
+    pub deprecation: Option<(String, SunsetAction)>,
+}
+
+impl KeyPlan {
+    /// Plans the creation of the key described by `spec` with `config` instead of the
+    /// configuration of the crate.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the plan on success, or a `SecurityModuleError::InvalidKeyId` if
+    /// the label of `spec` is not a valid `KeyId`.
+    pub fn for_config(config: &CryptoConfig, spec: KeySpec) -> Result<Self, SecurityModuleError> {
+        let instance = InstancePlan::for_config(config, spec.label())?;
+        let deprecation = config
+            .sunset_policy()
+            .check(spec.asymmetric_algorithm(), Some(spec.hash()))
+            .map(|(name, action)| (name.to_owned(), action));
+        Ok(Self {
+            spec,
+            instance,
+            deprecation,
+        })
+    }
+
+    /// Returns whether the key would be created, i.e. whether none of its algorithms is denied.
+    pub fn allowed(&self) -> bool {
+        !matches!(self.deprecation, Some((_, SunsetAction::Deny)))
+    }
+}
+
+/// Describes where a provider keeps the key stored as `stored_as`.
+fn storage(provider: &str, stored_as: &str) -> String {
+    match provider {
+        "windows" => format!(
+            "TPM through the Microsoft Platform Crypto Provider, persisted key '{}'",
+            stored_as
+        ),
+        "macos" => format!(
+            "Secure Enclave, keychain item with the application tag '{}'",
+            stored_as
+        ),
+        "linux" => format!("TPM 2.0, key '{}'", stored_as),
+        "android" => format!("Samsung Knox Vault, alias '{}'", stored_as),
+        "android_keystore" => format!("Android Keystore, alias '{}'", stored_as),
+        "nitrokey" => format!("Nitrokey HSM, key '{}'", stored_as),
+        "yubikey" => format!("YubiKey, key '{}'", stored_as),
+        "nks" => format!("Network key storage, secret '{}'", stored_as),
+        _ => format!("'{}'", stored_as),
+    }
+}
@@ -0,0 +1,106 @@
+//! Named sets of safe defaults.
+//!
+//! A `Profile` pre-selects the algorithm and access control of new keys and the providers and
+//! fallback policy of the crate, so that applications get a sound configuration without choosing
+//! every parameter themselves:
+//!
+//! ```rust,ignore
+//! use crypto_layer::{common::profile::Profile, SecModules};
+//!
+//! SecModules::use_profile(Profile::HighSecurity)?;
+//! let spec = Profile::HighSecurity.key_spec("device-identity").build()?;
+//! ```
+//!
+//! The key spec builder returned by `Profile::key_spec` can still be adjusted, e.g. to add a
+//! usage, and is validated as usual when it is built.
+
+use crate::common::{
+    config::{CryptoConfig, FallbackPolicy},
+    crypto::key_spec::{AccessControl, KeyAlgorithm, KeyPurpose, KeySpec, KeySpecBuilder},
+};
+
+/// The providers keeping keys in hardware on the device, most preferred first.
+const HARDWARE_PROVIDERS: [&str; 7] = [
+    "macos",
+    "windows",
+    "linux",
+    "android",
+    "android_keystore",
+    "yubikey",
+    "nitrokey",
+];
+
+/// A named set of defaults, see the module documentation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Profile {
+    /// ECDSA P-256 signing keys that require a biometric enrolled when the key was created, in
+    /// hardware only. If the preferred provider is unavailable, no other one is used.
+    HighSecurity,
+    /// RSA-2048 signing keys without user authentication. Every provider may be used, including
+    /// the network key storage, and unavailable providers fall back to the next one.
+    Compatibility,
+}
+
+impl Profile {
+    /// Returns whether the profile only allows providers keeping keys in hardware on the device.
+    pub fn requires_hardware(self) -> bool {
+        matches!(self, Profile::HighSecurity)
+    }
+
+    /// Returns the algorithm of new keys.
+    pub fn key_algorithm(self) -> KeyAlgorithm {
+        match self {
+            Profile::HighSecurity => KeyAlgorithm::EcP256,
+            Profile::Compatibility => KeyAlgorithm::Rsa2048,
+        }
+    }
+
+    /// Returns the user authentication required to use new keys.
+    pub fn access(self) -> AccessControl {
+        match self {
+            Profile::HighSecurity => AccessControl::BiometryCurrentSet,
+            Profile::Compatibility => AccessControl::None,
+        }
+    }
+
+    /// Returns the providers the profile allows, most preferred first, see
+    /// `config::PROVIDER_NAMES`.
+    pub fn providers(self) -> Vec<String> {
+        let mut providers: Vec<String> = HARDWARE_PROVIDERS.map(str::to_owned).to_vec();
+        if !self.requires_hardware() {
+            providers.push("nks".to_owned());
+        }
+        providers
+    }
+
+    pub fn fallback(self) -> FallbackPolicy {
+        match self {
+            Profile::HighSecurity => FallbackPolicy::Never,
+            Profile::Compatibility => FallbackPolicy::NextProvider,
+        }
+    }
+
+    /// Returns a builder for a signing key with the algorithm and access control of the profile.
+    ///
+    /// # Arguments
+    ///
+    /// * `label` - The label of the key, which is used as the key id.
+    pub fn key_spec(self, label: impl Into<String>) -> KeySpecBuilder {
+        KeySpec::builder()
+            .algorithm(self.key_algorithm())
+            .usage(KeyPurpose::Sign)
+            .access(self.access())
+            .label(label)
+    }
+
+    /// Replaces the providers and the fallback policy of `config` with the ones of the profile.
+    /// The namespace, timeouts and log levels are kept.
+    pub fn apply(self, config: CryptoConfig) -> CryptoConfig {
+        CryptoConfig {
+            providers: self.providers(),
+            fallback: self.fallback(),
+            ..config
+        }
+    }
+}
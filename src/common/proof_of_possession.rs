@@ -0,0 +1,285 @@
+//! Proofs that a client holds a device-bound key, e.g. to bind a session to a device.
+//!
+//! The server sends a random challenge, the client answers with `create_proof` and the server
+//! checks the answer with `verify_proof` against the public key it registered for the device:
+//!
+//! ```rust,ignore
+//! use crypto_layer::common::proof_of_possession::{self, Proof, ProofPolicy};
+//!
+//! // On the device.
+//! let proof = proof_of_possession::create_proof(&provider, "https://api.example.com", &challenge)?;
+//! request.header("Proof-Of-Possession", proof.to_string());
+//!
+//! // On the server.
+//! let proof: Proof = header.parse()?;
+//! let audience = "https://api.example.com";
+//! proof_of_possession::verify_proof(&proof, &public_key, audience, &challenge, &ProofPolicy::default())?;
+//! ```
+//!
+//! A proof holds `ProofClaims` encoded as JSON and a signature over `SIGNATURE_DOMAIN` followed by
+//! the encoded claims, which is created with `sign_data` of the provider. `Proof` is encoded as
+//! the unpadded base64url encoding of the claims, a dot and the unpadded base64url encoding of the
+//! signature, so it fits into HTTP headers.
+
+use crate::common::{
+    crypto::public_key::PublicKey, error::SecurityModuleError, fips,
+    traits::module_provider::Provider,
+};
+use base64::{prelude::BASE64_URL_SAFE_NO_PAD, Engine};
+use openssl::{rand::rand_bytes, sha::sha256};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    str::FromStr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Separates the signatures of proofs from other signatures of the same key.
+pub const SIGNATURE_DOMAIN: &[u8] = b"crypto-layer/proof-of-possession/v1\n";
+
+/// The shortest challenge accepted in bytes.
+pub const MIN_CHALLENGE_LEN: usize = 16;
+
+/// The longest challenge accepted in bytes.
+pub const MAX_CHALLENGE_LEN: usize = 256;
+
+/// The length of the nonce of the client in bytes.
+const NONCE_LEN: usize = 16;
+
+/// The claims a proof is signed over.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProofClaims {
+    /// The service the proof is meant for, e.g. its origin.
+    pub audience: String,
+    /// The challenge of the server in unpadded base64url.
+    pub challenge: String,
+    /// A random nonce of the client in unpadded base64url, which servers can remember to reject
+    /// replayed proofs within their maximum age.
+    pub nonce: String,
+    /// The time the proof was created at in seconds since the Unix epoch.
+    pub issued_at: u64,
+    /// The id of the key the proof was signed with.
+    pub key_id: String,
+    /// The SHA-256 hash of the DER encoded public key in hex.
+    pub key_thumbprint: String,
+    /// The SHA-256 hash of the attestation certificate of the key in hex, or `None` if the
+    /// security module does not attest its keys.
+    pub attestation: Option<String>,
+    /// Whether the private key can leave the security module, see `KeySpec::exportable`.
+    /// Servers that require hardware-bound keys reject proofs of exportable keys.
+    #[serde(default)]
+    pub exportable: bool,
+    /// Whether the client ran in FIPS mode, see `fips`. Servers of regulated deployments reject
+    /// proofs of clients that do not.
+    #[serde(default)]
+    pub fips_mode: bool,
+}
+
+/// A signed proof of possession, see the module documentation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Proof {
+    claims: ProofClaims,
+    encoded_claims: Vec<u8>,
+    signature: Vec<u8>,
+}
+
+impl Proof {
+    /// Returns the claims of the proof, which are only trustworthy after `verify_proof`.
+    pub fn claims(&self) -> &ProofClaims {
+        &self.claims
+    }
+
+    /// Returns the signature over the claims.
+    pub fn signature(&self) -> &[u8] {
+        &self.signature
+    }
+}
+
+impl fmt::Display for Proof {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}.{}",
+            BASE64_URL_SAFE_NO_PAD.encode(&self.encoded_claims),
+            BASE64_URL_SAFE_NO_PAD.encode(&self.signature)
+        )
+    }
+}
+
+impl FromStr for Proof {
+    type Err = SecurityModuleError;
+
+    /// Parses an encoded proof without verifying it.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `Proof`, or a `SecurityModuleError::InvalidProof` if `s` is not
+    /// an encoded proof.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (claims, signature) = s
+            .split_once('.')
+            .ok_or_else(|| invalid_proof("The proof has no signature"))?;
+        let decode = |field: &str| {
+            BASE64_URL_SAFE_NO_PAD
+                .decode(field)
+                .map_err(|e| invalid_proof(&e.to_string()))
+        };
+        let encoded_claims = decode(claims)?;
+        Ok(Self {
+            claims: serde_json::from_slice(&encoded_claims)
+                .map_err(|e| invalid_proof(&e.to_string()))?,
+            encoded_claims,
+            signature: decode(signature)?,
+        })
+    }
+}
+
+/// The limits `verify_proof` applies to the creation time of proofs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProofPolicy {
+    /// How long a proof is accepted after its creation.
+    pub max_age: Duration,
+    /// How far the clock of the client may be ahead of the clock of the server.
+    pub clock_skew: Duration,
+}
+
+impl Default for ProofPolicy {
+    /// Accepts proofs for 5 minutes and clocks that are up to 30 seconds ahead.
+    fn default() -> Self {
+        Self {
+            max_age: Duration::from_secs(5 * 60),
+            clock_skew: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Creates a proof that the loaded key of `provider` answered `challenge` for `audience`.
+///
+/// # Arguments
+///
+/// * `provider` - The provider with the loaded device key.
+/// * `audience` - The service the proof is meant for.
+/// * `challenge` - The random challenge of the service.
+///
+/// # Returns
+///
+/// A `Result` containing the `Proof`, a `SecurityModuleError::InvalidProof` if the challenge is
+/// shorter than `MIN_CHALLENGE_LEN` or longer than `MAX_CHALLENGE_LEN`, or the error of
+/// `provider` if the key metadata cannot be read or the claims cannot be signed.
+#[tracing::instrument(skip(provider, challenge))]
+pub fn create_proof(
+    provider: &(impl Provider + ?Sized),
+    audience: &str,
+    challenge: &[u8],
+) -> Result<Proof, SecurityModuleError> {
+    check_challenge(challenge)?;
+    let metadata = provider.key_metadata()?;
+    let mut nonce = [0; NONCE_LEN];
+    rand_bytes(&mut nonce).map_err(|e| SecurityModuleError::SigningError(e.to_string()))?;
+
+    let claims = ProofClaims {
+        audience: audience.to_owned(),
+        challenge: BASE64_URL_SAFE_NO_PAD.encode(challenge),
+        nonce: BASE64_URL_SAFE_NO_PAD.encode(nonce),
+        issued_at: unix_time(SystemTime::now()),
+        key_id: metadata.key_id().to_owned(),
+        key_thumbprint: hex(&sha256(metadata.public_key_der())),
+        attestation: metadata
+            .attestation()
+            .and_then(|chain| chain.first())
+            .map(|certificate| hex(&sha256(certificate))),
+        exportable: metadata.exportable(),
+        fips_mode: fips::is_enabled(),
+    };
+    let encoded_claims = serde_json::to_vec(&claims).expect("claims are always serializable");
+    let signature = provider.sign_data(&[SIGNATURE_DOMAIN, &encoded_claims].concat())?;
+    Ok(Proof {
+        claims,
+        encoded_claims,
+        signature,
+    })
+}
+
+/// Verifies `proof` against the registered public key of the device, the expected audience and
+/// the challenge, at the current time.
+///
+/// # Returns
+///
+/// A `Result` containing the verified claims, a `SecurityModuleError::InvalidSignature` if the
+/// proof was not signed by the owner of `public_key`, or a `SecurityModuleError::InvalidProof` if
+/// it belongs to another key, audience or challenge, has expired or was created in the future.
+pub fn verify_proof<'a>(
+    proof: &'a Proof,
+    public_key: &PublicKey,
+    audience: &str,
+    challenge: &[u8],
+    policy: &ProofPolicy,
+) -> Result<&'a ProofClaims, SecurityModuleError> {
+    verify_proof_at(
+        proof,
+        public_key,
+        audience,
+        challenge,
+        policy,
+        SystemTime::now(),
+    )
+}
+
+/// Verifies `proof` like `verify_proof`, at the time `now`.
+#[tracing::instrument(skip(proof, public_key, challenge))]
+pub fn verify_proof_at<'a>(
+    proof: &'a Proof,
+    public_key: &PublicKey,
+    audience: &str,
+    challenge: &[u8],
+    policy: &ProofPolicy,
+    now: SystemTime,
+) -> Result<&'a ProofClaims, SecurityModuleError> {
+    check_challenge(challenge)?;
+    let signed = [SIGNATURE_DOMAIN, &proof.encoded_claims].concat();
+    if !public_key.verify(&signed, &proof.signature)? {
+        return Err(SecurityModuleError::InvalidSignature);
+    }
+
+    let claims = &proof.claims;
+    if claims.key_thumbprint != hex(&sha256(&public_key.to_der()?)) {
+        return Err(invalid_proof("The proof belongs to another key"));
+    }
+    if claims.audience != audience {
+        return Err(invalid_proof("The proof is meant for another audience"));
+    }
+    if claims.challenge != BASE64_URL_SAFE_NO_PAD.encode(challenge) {
+        return Err(invalid_proof("The proof answers another challenge"));
+    }
+    let now = unix_time(now);
+    if claims.issued_at > now.saturating_add(policy.clock_skew.as_secs()) {
+        return Err(invalid_proof("The proof was created in the future"));
+    }
+    if now.saturating_sub(claims.issued_at) > policy.max_age.as_secs() {
+        return Err(invalid_proof("The proof has expired"));
+    }
+    Ok(claims)
+}
+
+fn check_challenge(challenge: &[u8]) -> Result<(), SecurityModuleError> {
+    if !(MIN_CHALLENGE_LEN..=MAX_CHALLENGE_LEN).contains(&challenge.len()) {
+        return Err(invalid_proof(&format!(
+            "Challenges must have {} to {} bytes",
+            MIN_CHALLENGE_LEN, MAX_CHALLENGE_LEN
+        )));
+    }
+    Ok(())
+}
+
+fn unix_time(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn invalid_proof(message: &str) -> SecurityModuleError {
+    SecurityModuleError::InvalidProof(message.to_owned())
+}
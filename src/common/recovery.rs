@@ -0,0 +1,309 @@
+//! M-of-N recovery of wrapped master secrets with Shamir's secret sharing.
+//!
+//! Enterprise key escrow often requires that no single custodian can recover a master secret.
+//! `split` splits a secret, e.g. the wrapped master secret of a `KeyHierarchy` or a backup key
+//! of `escrow`, into `N` shares of which any `M` reconstruct it with `combine`, while fewer than
+//! `M` shares reveal nothing about it:
+//!
+//! ```rust,ignore
+//! use crypto_layer::common::recovery::{self, Share};
+//!
+//! // Three custodians receive one share each, any two of them can recover the secret.
+//! let shares = recovery::split(&wrapped_master, 2, 3)?;
+//! let encoded: Vec<String> = shares.iter().map(Share::to_string).collect();
+//!
+//! // Later, with the shares of two custodians.
+//! let shares = [encoded[0].parse()?, encoded[2].parse()?];
+//! let wrapped_master = recovery::combine(&shares)?;
+//! ```
+//!
+//! Every byte of the secret is the constant term of a random polynomial of degree `M - 1` over
+//! GF(2^8) with the AES polynomial, and share `x` holds the values of the polynomials at `x`.
+//!
+//! A share is encoded as the version `1`, the threshold `M`, the index `x`, the 16 byte id of the
+//! split, a 16 byte check value, the big-endian u16 length of the share value, the value and the
+//! first 4 bytes of the SHA-256 hash of all preceding bytes. The hash detects corrupted shares,
+//! the id rejects shares of different splits, and the check value, the first 16 bytes of the
+//! SHA-256 hash of the id and the secret, detects reconstructions from modified shares. Since the
+//! check value lets share holders test guesses of the secret, only secrets with full entropy such
+//! as keys and wrapped keys should be split. `Share` is displayed as the unpadded base64url
+//! encoding of the share.
+
+use crate::common::{constant_time, crypto::secret::SecretBytes, error::SecurityModuleError};
+use base64::{prelude::BASE64_URL_SAFE_NO_PAD, Engine};
+use crypto_layer_core::CoreError;
+use openssl::{
+    rand::rand_bytes,
+    sha::{sha256, Sha256},
+};
+use std::{fmt, str::FromStr};
+
+/// The version of the share encoding.
+const VERSION: u8 = 1;
+
+/// The length of the id of a split in bytes.
+const SPLIT_ID_LEN: usize = 16;
+
+/// The length of the check value of the secret in bytes.
+const CHECK_LEN: usize = 16;
+
+/// The length of the hash that ends an encoded share in bytes.
+const CHECKSUM_LEN: usize = 4;
+
+/// The length of an encoded share without its value in bytes.
+const HEADER_LEN: usize = 3 + SPLIT_ID_LEN + CHECK_LEN + 2;
+
+/// The maximum length of a secret in bytes.
+pub const MAX_SECRET_LEN: usize = 4096;
+
+/// One share of a split secret, see the module documentation.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Share {
+    threshold: u8,
+    index: u8,
+    split_id: [u8; SPLIT_ID_LEN],
+    check: [u8; CHECK_LEN],
+    value: Vec<u8>,
+}
+
+impl Share {
+    /// Returns the number of shares needed to reconstruct the secret.
+    pub fn threshold(&self) -> u8 {
+        self.threshold
+    }
+
+    /// Returns the index of the share, from 1 to the number of shares.
+    pub fn index(&self) -> u8 {
+        self.index
+    }
+
+    /// Encodes the share.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_LEN + self.value.len() + CHECKSUM_LEN);
+        bytes.extend([VERSION, self.threshold, self.index]);
+        bytes.extend(self.split_id);
+        bytes.extend(self.check);
+        bytes.extend((self.value.len() as u16).to_be_bytes());
+        bytes.extend(&self.value);
+        let checksum = sha256(&bytes);
+        bytes.extend(&checksum[..CHECKSUM_LEN]);
+        bytes
+    }
+
+    /// Decodes a share of `to_bytes`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the share, or a `SecurityModuleError::Encoding` if the share is
+    /// truncated, has another version, an invalid threshold or index, or is corrupted.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SecurityModuleError> {
+        if bytes.len() < HEADER_LEN + CHECKSUM_LEN {
+            return Err(CoreError::Truncated.into());
+        }
+        let (content, checksum) = bytes.split_at(bytes.len() - CHECKSUM_LEN);
+        if !constant_time::eq(&sha256(content)[..CHECKSUM_LEN], checksum) {
+            return Err(CoreError::InvalidField("share checksum").into());
+        }
+        let (header, value) = content.split_at(HEADER_LEN);
+        if header[0] != VERSION {
+            return Err(CoreError::UnsupportedVersion(header[0]).into());
+        }
+        let (threshold, index) = (header[1], header[2]);
+        if threshold < 2 || index == 0 {
+            return Err(CoreError::InvalidField("share index").into());
+        }
+        let len = u16::from_be_bytes([header[HEADER_LEN - 2], header[HEADER_LEN - 1]]) as usize;
+        if value.len() != len {
+            return Err(CoreError::InvalidField("share value").into());
+        }
+        Ok(Self {
+            threshold,
+            index,
+            split_id: header[3..3 + SPLIT_ID_LEN].try_into().unwrap(),
+            check: header[3 + SPLIT_ID_LEN..3 + SPLIT_ID_LEN + CHECK_LEN]
+                .try_into()
+                .unwrap(),
+            value: value.to_vec(),
+        })
+    }
+}
+
+impl fmt::Display for Share {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&BASE64_URL_SAFE_NO_PAD.encode(self.to_bytes()))
+    }
+}
+
+impl FromStr for Share {
+    type Err = SecurityModuleError;
+
+    /// Parses a share displayed with `to_string`, see `Share::from_bytes`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes = BASE64_URL_SAFE_NO_PAD
+            .decode(s.trim())
+            .map_err(|_| CoreError::InvalidField("share"))?;
+        Self::from_bytes(&bytes)
+    }
+}
+
+impl fmt::Debug for Share {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Share")
+            .field("threshold", &self.threshold)
+            .field("index", &self.index)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Splits `secret` into `shares` shares of which `threshold` reconstruct it.
+///
+/// # Arguments
+///
+/// * `secret` - The secret, 1 to `MAX_SECRET_LEN` bytes.
+/// * `threshold` - The number of shares needed to reconstruct the secret, at least 2.
+/// * `shares` - The number of shares, at least `threshold`.
+///
+/// # Returns
+///
+/// A `Result` containing the shares with the indexes 1 to `shares`, or a
+/// `SecurityModuleError::Encoding` if the secret is empty or too long or the threshold is out
+/// of range.
+#[tracing::instrument(skip(secret), fields(crypto.payload.size = secret.len()))]
+pub fn split(secret: &[u8], threshold: u8, shares: u8) -> Result<Vec<Share>, SecurityModuleError> {
+    if secret.is_empty() {
+        return Err(CoreError::MissingField("secret").into());
+    }
+    if secret.len() > MAX_SECRET_LEN {
+        return Err(CoreError::InvalidField("secret").into());
+    }
+    if threshold < 2 || shares < threshold {
+        return Err(CoreError::InvalidField("threshold").into());
+    }
+    let random_error =
+        |e: openssl::error::ErrorStack| SecurityModuleError::EncryptionError(e.to_string());
+    let mut split_id = [0; SPLIT_ID_LEN];
+    rand_bytes(&mut split_id).map_err(random_error)?;
+    let check = check_value(&split_id, secret);
+
+    // The coefficients of degree 1 to `threshold - 1` of the polynomial of every byte.
+    let mut coefficients = SecretBytes::zeroed(secret.len() * (threshold as usize - 1));
+    rand_bytes(&mut coefficients).map_err(random_error)?;
+    let shares = (1..=shares)
+        .map(|index| Share {
+            threshold,
+            index,
+            split_id,
+            check,
+            value: secret
+                .iter()
+                .zip(coefficients.chunks(threshold as usize - 1))
+                .map(|(&constant, coefficients)| {
+                    // Horner's method, starting with the coefficient of the highest degree.
+                    let value = coefficients
+                        .iter()
+                        .rev()
+                        .fold(0, |value, &coefficient| gf_mul(value, index) ^ coefficient);
+                    gf_mul(value, index) ^ constant
+                })
+                .collect(),
+        })
+        .collect();
+    Ok(shares)
+}
+
+/// Reconstructs the secret from at least `threshold` shares of one split.
+///
+/// # Returns
+///
+/// A `Result` containing the secret, which is zeroed when dropped, or a
+/// `SecurityModuleError::DecryptionError` if there are fewer shares than the threshold, the
+/// shares belong to different splits, an index occurs twice, or the shares were modified.
+#[tracing::instrument(skip_all, fields(crypto.shares = shares.len()))]
+pub fn combine(shares: &[Share]) -> Result<SecretBytes, SecurityModuleError> {
+    let decryption_error = |message: &str| SecurityModuleError::DecryptionError(message.to_owned());
+    let first = shares
+        .first()
+        .ok_or_else(|| decryption_error("No shares were given"))?;
+    if shares.iter().any(|share| {
+        share.split_id != first.split_id
+            || share.threshold != first.threshold
+            || !constant_time::eq(&share.check, &first.check)
+            || share.value.len() != first.value.len()
+    }) {
+        return Err(decryption_error("The shares belong to different splits"));
+    }
+    let shares = &shares[..shares.len().min(first.threshold as usize)];
+    if shares.len() < first.threshold as usize {
+        return Err(SecurityModuleError::DecryptionError(format!(
+            "{} of the shares are required, {} were given",
+            first.threshold,
+            shares.len()
+        )));
+    }
+    for (i, share) in shares.iter().enumerate() {
+        if shares[..i].iter().any(|other| other.index == share.index) {
+            return Err(decryption_error("A share was given twice"));
+        }
+    }
+
+    // The Lagrange basis polynomials of the indexes, evaluated at 0.
+    let basis: Vec<u8> = shares
+        .iter()
+        .map(|share| {
+            shares
+                .iter()
+                .filter(|other| other.index != share.index)
+                .fold(1, |product, other| {
+                    gf_mul(product, gf_div(other.index, other.index ^ share.index))
+                })
+        })
+        .collect();
+    let secret: SecretBytes = (0..first.value.len())
+        .map(|i| {
+            shares
+                .iter()
+                .zip(&basis)
+                .fold(0, |secret, (share, &basis)| {
+                    secret ^ gf_mul(share.value[i], basis)
+                })
+        })
+        .collect::<Vec<u8>>()
+        .into();
+    if !constant_time::eq(&check_value(&first.split_id, &secret), &first.check) {
+        return Err(decryption_error("The shares do not reconstruct the secret"));
+    }
+    Ok(secret)
+}
+
+fn check_value(split_id: &[u8], secret: &[u8]) -> [u8; CHECK_LEN] {
+    let mut hasher = Sha256::new();
+    hasher.update(split_id);
+    hasher.update(secret);
+    hasher.finish()[..CHECK_LEN].try_into().unwrap()
+}
+
+/// Multiplies in GF(2^8) modulo the AES polynomial, without branches on the operands.
+fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0;
+    for _ in 0..8 {
+        product ^= a & 0u8.wrapping_sub(b & 1);
+        let carry = 0u8.wrapping_sub(a >> 7);
+        a = (a << 1) ^ (carry & 0x1b);
+        b >>= 1;
+    }
+    product
+}
+
+/// Divides in GF(2^8), `b` must not be 0.
+fn gf_div(a: u8, b: u8) -> u8 {
+    // b^254 is the inverse of b, since the multiplicative group has 255 elements.
+    let mut inverse = 1;
+    let mut power = b;
+    for bit in 0..8 {
+        if (254 >> bit) & 1 == 1 {
+            inverse = gf_mul(inverse, power);
+        }
+        power = gf_mul(power, power);
+    }
+    gf_mul(a, inverse)
+}
@@ -0,0 +1,132 @@
+//! Messages that are signed by the sender and encrypted for the recipient.
+//!
+//! `seal_message` signs a message with the loaded key of the sender's provider and encrypts the
+//! message and the signature for the recipient with `ecies`. `open_message` decrypts it with the
+//! loaded key of the recipient's provider and verifies the signature with the public key of the
+//! expected sender:
+//!
+//! ```rust,ignore
+//! use crypto_layer::common::sealed_message::{open_message, seal_message};
+//!
+//! let sealed = seal_message(&sender_provider, &recipient_public_key, b"transfer 10 EUR")?;
+//! let data = open_message(&recipient_provider, &sender_public_key, &sealed)?;
+//! ```
+//!
+//! Combining signatures and encryption naively is prone to known pitfalls, which are avoided as
+//! follows:
+//!
+//! - Signed messages could be forwarded to a third party, who would believe they were addressed
+//!   to them. The signature therefore covers the `ecies::recipient_id` of the recipient, and
+//!   `open_message` compares it with the id of its own key.
+//! - Signatures outside the encryption could be stripped and replaced by an attacker, making
+//!   them the apparent sender, and would reveal who sent the message. The signature is therefore
+//!   created first and encrypted together with the message.
+//! - The signature could be confused with signatures over other data of the same key. The signed
+//!   data is therefore prefixed with the domain `crypto-layer/sealed-message/v1` and also covers
+//!   the public key of the sender.
+//!
+//! The signed data is the domain, the recipient id and the SHA-256 hash of the uncompressed point
+//! or DER encoding of the sender's public key, each prefixed with its u16 length, followed by the
+//! message. The plaintext of the ECIES envelope is the u16 length of the signature, the signature
+//! and the message. All lengths are big-endian.
+
+use crate::common::{
+    crypto::{algorithms::encryption::AsymmetricEncryption, public_key::PublicKey},
+    ecies,
+    error::SecurityModuleError,
+    traits::module_provider::Provider,
+};
+use openssl::sha::sha256;
+
+/// Separates the signatures of sealed messages from other signatures of the same key.
+pub const SIGNATURE_DOMAIN: &[u8] = b"crypto-layer/sealed-message/v1";
+
+/// Signs `data` with the loaded key of `sender` and encrypts it for the owner of
+/// `recipient_public_key`.
+///
+/// # Arguments
+///
+/// * `sender` - The provider of the sender with a loaded signing key.
+/// * `recipient_public_key` - The EC public key of the recipient.
+/// * `data` - The message to be sealed.
+///
+/// # Returns
+///
+/// A `Result` containing the encoded ECIES envelope, the error of `sender` if the message cannot
+/// be signed, or a `SecurityModuleError::InvalidPublicKey` if `recipient_public_key` is not an EC
+/// key.
+#[tracing::instrument(skip_all, fields(crypto.payload.size = data.len()))]
+pub fn seal_message(
+    sender: &(impl Provider + ?Sized),
+    recipient_public_key: &PublicKey,
+    data: &[u8],
+) -> Result<Vec<u8>, SecurityModuleError> {
+    let sender_public_key = sender.key_metadata()?.public_key().clone();
+    let recipient_id = ecies::recipient_id(recipient_public_key)?;
+    let signature = sender.sign_data(&signed_data(&recipient_id, &sender_public_key, data)?)?;
+    let signature_len = u16::try_from(signature.len())
+        .map_err(|_| SecurityModuleError::SigningError("The signature is too long".to_owned()))?;
+
+    let plaintext = [&signature_len.to_be_bytes()[..], &signature, data].concat();
+    ecies::encrypt_for(recipient_public_key, &plaintext)
+}
+
+/// Decrypts a message of `seal_message` with the loaded key of `recipient` and verifies that it
+/// was signed by the owner of `sender_public_key` for this recipient.
+///
+/// # Arguments
+///
+/// * `recipient` - The provider of the recipient with the loaded key the message was sealed for.
+/// * `sender_public_key` - The public key of the expected sender.
+/// * `sealed` - The encoded envelope.
+///
+/// # Returns
+///
+/// A `Result` containing the message, a `SecurityModuleError::DecryptionError` if the message
+/// was sealed for another key or modified, or a `SecurityModuleError::InvalidSignature` if it was
+/// not signed by the expected sender for this recipient.
+#[tracing::instrument(skip_all, fields(crypto.payload.size = sealed.len()))]
+pub fn open_message(
+    recipient: &(impl Provider + ?Sized),
+    sender_public_key: &PublicKey,
+    sealed: &[u8],
+) -> Result<Vec<u8>, SecurityModuleError> {
+    let plaintext = ecies::decrypt(recipient, sealed)?;
+    let (signature, data) = plaintext
+        .get(..2)
+        .map(|len| u16::from_be_bytes([len[0], len[1]]) as usize)
+        .and_then(|len| plaintext[2..].split_at_checked(len))
+        .ok_or_else(|| {
+            SecurityModuleError::DecryptionError("The sealed message is truncated".to_owned())
+        })?;
+
+    // The id of the recipient's own key, not the key id of the envelope, which the sender
+    // chooses.
+    let recipient_id = ecies::recipient_id(recipient.key_metadata()?.public_key())?;
+    if !sender_public_key.verify(
+        &signed_data(&recipient_id, sender_public_key, data)?,
+        signature,
+    )? {
+        return Err(SecurityModuleError::InvalidSignature);
+    }
+    Ok(data.to_vec())
+}
+
+fn signed_data(
+    recipient_id: &str,
+    sender_public_key: &PublicKey,
+    data: &[u8],
+) -> Result<Vec<u8>, SecurityModuleError> {
+    let sender = match sender_public_key.algorithm() {
+        AsymmetricEncryption::Ecc(_) => sender_public_key.to_ec_point()?,
+        _ => sender_public_key.to_der()?,
+    };
+
+    let mut signed = Vec::new();
+    for field in [SIGNATURE_DOMAIN, recipient_id.as_bytes(), &sha256(&sender)] {
+        signed.extend_from_slice(&(field.len() as u16).to_be_bytes());
+        signed.extend_from_slice(field);
+    }
+    signed.extend_from_slice(data);
+    Ok(signed)
+}
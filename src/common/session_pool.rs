@@ -0,0 +1,375 @@
+use crate::common::error::SecurityModuleError;
+use serde::Serialize;
+// The pool is model checked with loom, see `src/tests/common/session_pool_loom.rs`.
+#[cfg(crypto_layer_loom)]
+use loom::sync::{Condvar, Mutex, MutexGuard};
+#[cfg(not(crypto_layer_loom))]
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::{
+    fmt,
+    ops::{Deref, DerefMut},
+    time::{Duration, Instant},
+};
+
+/// Configuration of a `SessionPool`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SessionPoolConfig {
+    /// The maximum number of sessions that are open at the same time.
+    pub max_size: usize,
+    /// Idle sessions are closed once they have not been used for this long.
+    /// `None` keeps idle sessions open until the pool is dropped.
+    pub idle_timeout: Option<Duration>,
+    /// How long `SessionPool::acquire` waits for a session if all sessions are in use.
+    /// `None` waits indefinitely.
+    pub acquire_timeout: Option<Duration>,
+}
+
+impl Default for SessionPoolConfig {
+    fn default() -> Self {
+        Self {
+            max_size: 4,
+            idle_timeout: Some(Duration::from_secs(300)),
+            acquire_timeout: Some(Duration::from_secs(30)),
+        }
+    }
+}
+
+/// A snapshot of the utilization of a `SessionPool`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+pub struct SessionPoolMetrics {
+    /// The maximum number of sessions the pool opens.
+    pub max_size: usize,
+    /// Sessions currently handed out to callers.
+    pub in_use: usize,
+    /// Sessions currently open but not in use.
+    pub idle: usize,
+    /// Callers currently waiting for a session.
+    pub waiting: usize,
+    /// Sessions opened over the lifetime of the pool.
+    pub opened: u64,
+    /// Sessions closed because they were idle for longer than the idle timeout.
+    pub closed_idle: u64,
+    /// Sessions closed because the caller discarded them.
+    pub discarded: u64,
+    /// Acquisitions served by an already open session.
+    pub reused: u64,
+    /// Acquisitions that had to wait for a session to be released.
+    pub waited: u64,
+    /// Acquisitions that failed because no session became available in time.
+    pub timeouts: u64,
+}
+
+impl SessionPoolMetrics {
+    /// Returns the fraction of the pool that is in use, between `0.0` and `1.0`.
+    pub fn utilization(&self) -> f64 {
+        if self.max_size == 0 {
+            return 0.0;
+        }
+        self.in_use as f64 / self.max_size as f64
+    }
+}
+
+struct IdleSession<S> {
+    session: S,
+    since: Instant,
+}
+
+struct PoolState<S> {
+    /// Idle sessions, the most recently released last so that it is reused first and the
+    /// others are given a chance to expire.
+    idle: Vec<IdleSession<S>>,
+    /// Sessions handed out or currently being opened.
+    in_use: usize,
+    /// Expired sessions that are currently being closed. They still count towards the maximum
+    /// size, so that a replacement is only opened once they are closed.
+    closing: usize,
+    metrics: SessionPoolMetrics,
+}
+
+type OpenSession<S> = dyn Fn() -> Result<S, SecurityModuleError> + Send + Sync;
+
+/// A pool of sessions to a security module.
+///
+/// Opening a session to an HSM or TPM is expensive, and sharing a single session serializes
+/// all operations. A `SessionPool` keeps up to `max_size` sessions open, hands them out to
+/// concurrent callers and closes sessions that have been idle for longer than the idle timeout.
+///
+/// Sessions are opened lazily by the function passed to `SessionPool::new`; the pool never
+/// holds its lock while opening or closing a session.
+///
+/// # Examples
+///
+/// ```rust
+/// use crypto_layer::common::session_pool::{SessionPool, SessionPoolConfig};
+///
+/// let pool = SessionPool::new(SessionPoolConfig::default(), || Ok(String::from("session")));
+/// {
+///     let session = pool.acquire().unwrap();
+///     assert_eq!(session.as_str(), "session");
+///     assert_eq!(pool.metrics().in_use, 1);
+/// }
+/// assert_eq!(pool.metrics().idle, 1);
+/// ```
+pub struct SessionPool<S> {
+    config: SessionPoolConfig,
+    open: Box<OpenSession<S>>,
+    state: Mutex<PoolState<S>>,
+    released: Condvar,
+}
+
+impl<S> SessionPool<S> {
+    /// Creates an empty pool.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The size and timeouts of the pool.
+    /// * `open` - Opens a new session, called whenever no idle session is available and the
+    ///   pool has not reached its maximum size.
+    pub fn new(
+        config: SessionPoolConfig,
+        open: impl Fn() -> Result<S, SecurityModuleError> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            config,
+            open: Box::new(open),
+            state: Mutex::new(PoolState {
+                idle: Vec::new(),
+                in_use: 0,
+                closing: 0,
+                metrics: SessionPoolMetrics {
+                    max_size: config.max_size,
+                    ..Default::default()
+                },
+            }),
+            released: Condvar::new(),
+        }
+    }
+
+    /// Returns the configuration of the pool.
+    pub fn config(&self) -> &SessionPoolConfig {
+        &self.config
+    }
+
+    /// Takes a session from the pool, opening a new one if none is idle.
+    ///
+    /// If all sessions are in use, waits for one to be released for at most the acquire timeout.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the session on success. The session is returned to the pool when it
+    /// is dropped. On failure, returns the error of opening the session, or
+    /// `SecurityModuleError::SessionPoolTimeout` if no session became available in time.
+    pub fn acquire(&self) -> Result<PooledSession<'_, S>, SecurityModuleError> {
+        let deadline = self
+            .config
+            .acquire_timeout
+            .map(|timeout| Instant::now() + timeout);
+        let mut state = self.lock();
+        let mut waited = false;
+
+        loop {
+            let expired = self.take_expired(&mut state);
+            if !expired.is_empty() {
+                drop(state);
+                self.close(expired);
+                state = self.lock();
+                continue;
+            }
+
+            if let Some(idle) = state.idle.pop() {
+                state.in_use += 1;
+                state.metrics.reused += 1;
+                drop(state);
+                return Ok(self.hand_out(idle.session));
+            }
+
+            if state.in_use + state.closing < self.config.max_size {
+                state.in_use += 1;
+                drop(state);
+                return match (self.open)() {
+                    Ok(session) => {
+                        self.lock().metrics.opened += 1;
+                        Ok(self.hand_out(session))
+                    }
+                    Err(error) => {
+                        self.lock().in_use -= 1;
+                        self.released.notify_one();
+                        Err(error)
+                    }
+                };
+            }
+
+            if !waited {
+                waited = true;
+                state.metrics.waited += 1;
+            }
+            state.metrics.waiting += 1;
+            state = match deadline {
+                Some(deadline) => {
+                    let remaining = deadline.saturating_duration_since(Instant::now());
+                    let (mut guard, result) = self
+                        .released
+                        .wait_timeout(state, remaining)
+                        .unwrap_or_else(|poisoned| poisoned.into_inner());
+                    guard.metrics.waiting -= 1;
+                    if result.timed_out()
+                        && guard.idle.is_empty()
+                        && guard.in_use + guard.closing >= self.config.max_size
+                    {
+                        guard.metrics.timeouts += 1;
+                        return Err(SecurityModuleError::SessionPoolTimeout);
+                    }
+                    guard
+                }
+                None => {
+                    let mut guard = self
+                        .released
+                        .wait(state)
+                        .unwrap_or_else(|poisoned| poisoned.into_inner());
+                    guard.metrics.waiting -= 1;
+                    guard
+                }
+            };
+        }
+    }
+
+    /// Closes all idle sessions that exceeded the idle timeout.
+    ///
+    /// Expired sessions are also closed on every `acquire`, calling this method periodically
+    /// additionally releases sessions of pools that are no longer used.
+    pub fn close_idle(&self) {
+        let expired = {
+            let mut state = self.lock();
+            self.take_expired(&mut state)
+        };
+        self.close(expired);
+    }
+
+    /// Returns a snapshot of the utilization of the pool.
+    pub fn metrics(&self) -> SessionPoolMetrics {
+        let state = self.lock();
+        SessionPoolMetrics {
+            in_use: state.in_use,
+            idle: state.idle.len(),
+            ..state.metrics
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, PoolState<S>> {
+        // The state is consistent after every mutation, so a poisoned lock can be recovered.
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn hand_out(&self, session: S) -> PooledSession<'_, S> {
+        PooledSession {
+            pool: self,
+            session: Some(session),
+        }
+    }
+
+    /// Removes the expired idle sessions from `state`, so that the caller can `close` them
+    /// after releasing the lock.
+    fn take_expired(&self, state: &mut PoolState<S>) -> Vec<IdleSession<S>> {
+        let Some(idle_timeout) = self.config.idle_timeout else {
+            return Vec::new();
+        };
+        let now = Instant::now();
+        let (expired, idle) = std::mem::take(&mut state.idle)
+            .into_iter()
+            .partition(|idle| now.duration_since(idle.since) >= idle_timeout);
+        state.idle = idle;
+        state.closing += expired.len();
+        state.metrics.closed_idle += expired.len() as u64;
+        expired
+    }
+
+    /// Closes sessions returned by `take_expired` and frees their slots.
+    fn close(&self, expired: Vec<IdleSession<S>>) {
+        if expired.is_empty() {
+            return;
+        }
+        let count = expired.len();
+        drop(expired);
+        self.lock().closing -= count;
+        self.released.notify_all();
+    }
+
+    fn release(&self, session: S) {
+        let mut state = self.lock();
+        state.in_use -= 1;
+        state.idle.push(IdleSession {
+            session,
+            since: Instant::now(),
+        });
+        drop(state);
+        self.released.notify_one();
+    }
+
+    fn discard(&self) {
+        let mut state = self.lock();
+        state.in_use -= 1;
+        state.metrics.discarded += 1;
+        drop(state);
+        self.released.notify_one();
+    }
+}
+
+impl<S> fmt::Debug for SessionPool<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SessionPool")
+            .field("config", &self.config)
+            .field("metrics", &self.metrics())
+            .finish()
+    }
+}
+
+/// A session taken from a `SessionPool`.
+///
+/// Dereferences to the underlying session and returns it to the pool when dropped.
+pub struct PooledSession<'a, S> {
+    pool: &'a SessionPool<S>,
+    session: Option<S>,
+}
+
+impl<S> PooledSession<'_, S> {
+    /// Closes the session instead of returning it to the pool, e.g. because it is broken.
+    pub fn discard(mut self) {
+        drop(self.session.take());
+        self.pool.discard();
+    }
+}
+
+impl<S> Deref for PooledSession<'_, S> {
+    type Target = S;
+
+    fn deref(&self) -> &S {
+        self.session
+            .as_ref()
+            .expect("Session is present until dropped")
+    }
+}
+
+impl<S> DerefMut for PooledSession<'_, S> {
+    fn deref_mut(&mut self) -> &mut S {
+        self.session
+            .as_mut()
+            .expect("Session is present until dropped")
+    }
+}
+
+impl<S> Drop for PooledSession<'_, S> {
+    fn drop(&mut self) {
+        if let Some(session) = self.session.take() {
+            self.pool.release(session);
+        }
+    }
+}
+
+impl<S: fmt::Debug> fmt::Debug for PooledSession<'_, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("PooledSession").field(&self.session).finish()
+    }
+}
@@ -0,0 +1,367 @@
+//! Signing for Sigstore with keys of the security module.
+//!
+//! Artifacts and attestations are signed in DSSE envelopes, and the key is certified for the
+//! OIDC identity of the signer by Fulcio, so neither cosign nor the Sigstore client has to hold
+//! the private key. The HTTP client and the Rekor upload are left to the application:
+//!
+//! ```rust,ignore
+//! use crypto_layer::common::sigstore;
+//!
+//! // Request a short-lived certificate for the key and the identity of the OIDC token.
+//! let request = sigstore::certificate_request(&provider, &identity_token)?;
+//! let response = client.post(format!("{fulcio_url}/api/v2/signingCert")).json(&request).send()?.json()?;
+//! let certificate_chain = sigstore::certificate_chain(&response, &public_key)?;
+//!
+//! // Sign an in-toto statement.
+//! let envelope = sigstore::sign_envelope(&provider, sigstore::IN_TOTO_PAYLOAD_TYPE, &statement)?;
+//! std::fs::write("provenance.dsse.json", envelope.to_json())?;
+//! ```
+//!
+//! Keys must have one of the key details of Sigstore, see `key_details`. ECDSA signatures are
+//! DER encoded, as Sigstore expects.
+//!
+//! `sigstore-rs` cannot sign with these keys: its `Signer` trait returns a `KeyPair`, which
+//! exports the private key, `SigStoreSigner` only wraps its own software keys, and its
+//! `SigningSession` generates an ephemeral key. So the envelopes and the Fulcio request are
+//! created here, and the tests verify them with the verification keys of `sigstore-rs`.
+
+use crate::common::{
+    crypto::{
+        algorithms::{
+            encryption::{AsymmetricEncryption, EccCurves, EccSchemeAlgorithm},
+            hashes::{Hash, Sha2Bits},
+            KeyBits,
+        },
+        public_key::{PublicKey, RsaSignaturePadding},
+    },
+    error::SecurityModuleError,
+    traits::module_provider::Provider,
+};
+use base64::{
+    prelude::{BASE64_STANDARD, BASE64_URL_SAFE_NO_PAD},
+    Engine,
+};
+use openssl::{pkey::PKey, x509::X509};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+/// The payload type of in-toto statements, e.g. SLSA provenance.
+pub const IN_TOTO_PAYLOAD_TYPE: &str = "application/vnd.in-toto+json";
+
+const PAE_PREFIX: &str = "DSSEv1";
+
+/// A signature of a DSSE envelope.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DsseSignature {
+    /// An optional hint which key created the signature, not covered by it.
+    pub keyid: Option<String>,
+    /// The signature over the pre-authentication encoding of the envelope.
+    pub sig: Vec<u8>,
+}
+
+/// A payload and its signatures in the Dead Simple Signing Envelope format.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DsseEnvelope {
+    /// The type of `payload`, e.g. `IN_TOTO_PAYLOAD_TYPE`.
+    pub payload_type: String,
+    /// The signed payload.
+    pub payload: Vec<u8>,
+    /// The signatures, each over the type and the payload.
+    pub signatures: Vec<DsseSignature>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct EnvelopeJson {
+    #[serde(rename = "payloadType")]
+    payload_type: String,
+    payload: String,
+    signatures: Vec<SignatureJson>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SignatureJson {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    keyid: String,
+    sig: String,
+}
+
+impl DsseEnvelope {
+    /// Creates an envelope without signatures.
+    pub fn new(payload_type: impl Into<String>, payload: impl Into<Vec<u8>>) -> Self {
+        Self {
+            payload_type: payload_type.into(),
+            payload: payload.into(),
+            signatures: Vec::new(),
+        }
+    }
+
+    /// Signs the envelope with the loaded key of `provider`, in addition to its signatures.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` that is `Ok(())` if the signature was added, a
+    /// `SecurityModuleError::UnsupportedAlgorithm` if the key is not supported by Sigstore, or
+    /// the error of `provider`.
+    pub fn add_signature(
+        &mut self,
+        provider: &(impl Provider + ?Sized),
+        keyid: Option<&str>,
+    ) -> Result<(), SecurityModuleError> {
+        key_details(provider.key_metadata()?.public_key())?;
+        let sig = provider.sign_data(&pae(&self.payload_type, &self.payload))?;
+        self.signatures.push(DsseSignature {
+            keyid: keyid.map(str::to_owned),
+            sig,
+        });
+        Ok(())
+    }
+
+    /// Verifies that one of the signatures was created by the owner of `public_key`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` that is `Ok(())` if a signature is valid, or a
+    /// `SecurityModuleError::InvalidSignature` if none is.
+    pub fn verify(&self, public_key: &PublicKey) -> Result<(), SecurityModuleError> {
+        let signed = pae(&self.payload_type, &self.payload);
+        for signature in &self.signatures {
+            if public_key.verify(&signed, &signature.sig).unwrap_or(false) {
+                return Ok(());
+            }
+        }
+        Err(SecurityModuleError::InvalidSignature)
+    }
+
+    /// Returns the JSON encoding of the envelope.
+    pub fn to_json(&self) -> String {
+        let envelope = EnvelopeJson {
+            payload_type: self.payload_type.clone(),
+            payload: BASE64_STANDARD.encode(&self.payload),
+            signatures: self
+                .signatures
+                .iter()
+                .map(|signature| SignatureJson {
+                    keyid: signature.keyid.clone().unwrap_or_default(),
+                    sig: BASE64_STANDARD.encode(&signature.sig),
+                })
+                .collect(),
+        };
+        serde_json::to_string(&envelope).expect("envelopes can be serialized")
+    }
+
+    /// Parses the JSON encoding of an envelope without verifying it.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `DsseEnvelope`, or a
+    /// `SecurityModuleError::SignatureVerificationError` if `json` is malformed.
+    pub fn from_json(json: &str) -> Result<Self, SecurityModuleError> {
+        let envelope: EnvelopeJson =
+            serde_json::from_str(json).map_err(|e| malformed(&e.to_string()))?;
+        let decode = |value: &str| {
+            BASE64_STANDARD
+                .decode(value)
+                .map_err(|_| malformed("The envelope is not base64 encoded"))
+        };
+        Ok(Self {
+            payload: decode(&envelope.payload)?,
+            payload_type: envelope.payload_type,
+            signatures: envelope
+                .signatures
+                .into_iter()
+                .map(|signature| {
+                    Ok(DsseSignature {
+                        sig: decode(&signature.sig)?,
+                        keyid: Some(signature.keyid).filter(|keyid| !keyid.is_empty()),
+                    })
+                })
+                .collect::<Result<_, SecurityModuleError>>()?,
+        })
+    }
+}
+
+/// Returns the pre-authentication encoding of DSSE, which signatures of envelopes are created
+/// over.
+pub fn pae(payload_type: &str, payload: &[u8]) -> Vec<u8> {
+    [
+        format!(
+            "{} {} {} {} ",
+            PAE_PREFIX,
+            payload_type.len(),
+            payload_type,
+            payload.len()
+        )
+        .as_bytes(),
+        payload,
+    ]
+    .concat()
+}
+
+/// Signs `payload` in a DSSE envelope with the loaded key of `provider`.
+///
+/// # Returns
+///
+/// A `Result` containing the `DsseEnvelope`, or the error of `DsseEnvelope::add_signature`.
+#[tracing::instrument(skip(provider, payload), fields(crypto.payload.size = payload.len()))]
+pub fn sign_envelope(
+    provider: &(impl Provider + ?Sized),
+    payload_type: &str,
+    payload: &[u8],
+) -> Result<DsseEnvelope, SecurityModuleError> {
+    let mut envelope = DsseEnvelope::new(payload_type, payload);
+    envelope.add_signature(provider, None)?;
+    Ok(envelope)
+}
+
+/// Returns the `PublicKeyDetails` of Sigstore for `public_key`, e.g. `PKIX_ECDSA_P256_SHA_256`,
+/// as in the verification material of bundles.
+///
+/// # Returns
+///
+/// A `Result` containing the name, or a `SecurityModuleError::UnsupportedAlgorithm` if Sigstore
+/// does not support the key. ECDSA keys must use the hash of their curve, e.g. SHA-256 for
+/// P-256, and RSA keys with 2048, 3072 or 4096 bits SHA-256.
+pub fn key_details(public_key: &PublicKey) -> Result<&'static str, SecurityModuleError> {
+    let details = match (public_key.algorithm(), public_key.hash()) {
+        (AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(EccCurves::Curve25519)), _) => {
+            "PKIX_ED25519"
+        }
+        (AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(curve)), Hash::Sha2(bits)) => {
+            match (curve, bits) {
+                (EccCurves::P256, Sha2Bits::Sha256) => "PKIX_ECDSA_P256_SHA_256",
+                (EccCurves::P384, Sha2Bits::Sha384) => "PKIX_ECDSA_P384_SHA_384",
+                (EccCurves::P521, Sha2Bits::Sha512) => "PKIX_ECDSA_P521_SHA_512",
+                _ => return Err(SecurityModuleError::UnsupportedAlgorithm),
+            }
+        }
+        (AsymmetricEncryption::Rsa(bits), Hash::Sha2(Sha2Bits::Sha256)) => {
+            match (public_key.rsa_padding(), bits) {
+                (RsaSignaturePadding::Pkcs1, KeyBits::Bits2048) => "PKIX_RSA_PKCS1V15_2048_SHA256",
+                (RsaSignaturePadding::Pkcs1, KeyBits::Bits3072) => "PKIX_RSA_PKCS1V15_3072_SHA256",
+                (RsaSignaturePadding::Pkcs1, KeyBits::Bits4096) => "PKIX_RSA_PKCS1V15_4096_SHA256",
+                (RsaSignaturePadding::Pss, KeyBits::Bits2048) => "PKIX_RSA_PSS_2048_SHA256",
+                (RsaSignaturePadding::Pss, KeyBits::Bits3072) => "PKIX_RSA_PSS_3072_SHA256",
+                (RsaSignaturePadding::Pss, KeyBits::Bits4096) => "PKIX_RSA_PSS_4096_SHA256",
+                _ => return Err(SecurityModuleError::UnsupportedAlgorithm),
+            }
+        }
+        _ => return Err(SecurityModuleError::UnsupportedAlgorithm),
+    };
+    Ok(details)
+}
+
+/// Creates the body of a `signingCert` request of the Fulcio v2 API for the loaded key of
+/// `provider` and the identity of `identity_token`.
+///
+/// The proof of possession is a signature over the subject of the token as cosign creates it,
+/// that is the `email` claim if the token has one and the `sub` claim otherwise. The token is
+/// only decoded, Fulcio verifies it.
+///
+/// # Returns
+///
+/// A `Result` containing the JSON body, a `SecurityModuleError::InvalidToken` if
+/// `identity_token` is not a JWT with a subject, a `SecurityModuleError::UnsupportedAlgorithm`
+/// if the key is not supported by Sigstore, or the error of `provider`.
+#[tracing::instrument(skip_all)]
+pub fn certificate_request(
+    provider: &(impl Provider + ?Sized),
+    identity_token: &str,
+) -> Result<Value, SecurityModuleError> {
+    let subject = token_subject(identity_token)?;
+    let metadata = provider.key_metadata()?;
+    let public_key = metadata.public_key();
+    key_details(public_key)?;
+    let pem = PKey::public_key_from_der(metadata.public_key_der())
+        .and_then(|key| key.public_key_to_pem())
+        .map_err(|_| SecurityModuleError::InvalidPublicKey)?;
+    let proof_of_possession = provider.sign_data(subject.as_bytes())?;
+
+    let mut key = json!({ "content": String::from_utf8_lossy(&pem) });
+    let algorithm = match public_key.algorithm() {
+        AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(EccCurves::Curve25519)) => {
+            Some("ED25519")
+        }
+        AsymmetricEncryption::Ecc(_) => Some("ECDSA"),
+        AsymmetricEncryption::Rsa(_) if public_key.rsa_padding() == RsaSignaturePadding::Pss => {
+            Some("RSA_PSS")
+        }
+        // Fulcio detects the algorithm from the key, PKCS#1 v1.5 has no name in the API.
+        AsymmetricEncryption::Rsa(_) => None,
+    };
+    if let Some(algorithm) = algorithm {
+        key["algorithm"] = json!(algorithm);
+    }
+    Ok(json!({
+        "credentials": { "oidcIdentityToken": identity_token },
+        "publicKeyRequest": {
+            "publicKey": key,
+            "proofOfPossession": BASE64_STANDARD.encode(proof_of_possession),
+        },
+    }))
+}
+
+/// Returns the DER encoded certificate chain of a `signingCert` response of the Fulcio v2
+/// API, leaf first, after checking that the leaf certifies `public_key`.
+///
+/// Responses with an embedded and with a detached SCT are accepted. The SCT is not verified.
+///
+/// # Returns
+///
+/// A `Result` containing the certificates, or a `SecurityModuleError::InvalidPublicKey` if the
+/// response has no certificates or the leaf certifies another key.
+pub fn certificate_chain(
+    response: &Value,
+    public_key: &PublicKey,
+) -> Result<Vec<Vec<u8>>, SecurityModuleError> {
+    let certificates = [
+        "signedCertificateEmbeddedSct",
+        "signedCertificateDetachedSct",
+    ]
+    .iter()
+    .find_map(|field| response[field]["chain"]["certificates"].as_array())
+    .ok_or(SecurityModuleError::InvalidPublicKey)?;
+    let certificate_chain = certificates
+        .iter()
+        .map(|pem| {
+            pem.as_str()
+                .and_then(|pem| X509::from_pem(pem.as_bytes()).ok())
+                .and_then(|certificate| certificate.to_der().ok())
+                .ok_or(SecurityModuleError::InvalidPublicKey)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let leaf = certificate_chain
+        .first()
+        .ok_or(SecurityModuleError::InvalidPublicKey)?;
+    let leaf_public_key = X509::from_der(leaf)
+        .and_then(|certificate| certificate.public_key())
+        .and_then(|key| key.public_key_to_der())
+        .map_err(|_| SecurityModuleError::InvalidPublicKey)?;
+    if leaf_public_key != public_key.to_der()? {
+        return Err(SecurityModuleError::InvalidPublicKey);
+    }
+    Ok(certificate_chain)
+}
+
+/// Returns the subject cosign signs as proof of possession for `identity_token`.
+fn token_subject(identity_token: &str) -> Result<String, SecurityModuleError> {
+    let invalid =
+        || SecurityModuleError::InvalidToken("The identity token is not a JWT".to_owned());
+    let claims = identity_token.split('.').nth(1).ok_or_else(invalid)?;
+    let claims: Value = BASE64_URL_SAFE_NO_PAD
+        .decode(claims)
+        .ok()
+        .and_then(|claims| serde_json::from_slice(&claims).ok())
+        .ok_or_else(invalid)?;
+    claims["email"]
+        .as_str()
+        .or_else(|| claims["sub"].as_str())
+        .filter(|subject| !subject.is_empty())
+        .map(str::to_owned)
+        .ok_or_else(|| {
+            SecurityModuleError::InvalidToken("The identity token has no subject".to_owned())
+        })
+}
+
+fn malformed(message: &str) -> SecurityModuleError {
+    SecurityModuleError::SignatureVerificationError(message.to_owned())
+}
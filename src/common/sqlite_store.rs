@@ -0,0 +1,341 @@
+//! A `MetadataStore` in a SQLite database, with an index of ciphertexts by key version.
+//!
+//! Rotating a key at scale means re-encrypting every blob still encrypted under an older version
+//! of it, which requires knowing which key and version each blob was encrypted with. Next to the
+//! `KeyRecord`s, a `SqliteMetadataStore` therefore maps the ids of ciphertexts to the key and
+//! version that decrypts them:
+//!
+//! ```rust,ignore
+//! use crypto_layer::common::sqlite_store::{ciphertext_id, SqliteMetadataStore};
+//!
+//! let store = SqliteMetadataStore::open("/var/lib/app/metadata.db")?;
+//! let blob = provider.encrypt_data(b"data")?;
+//! store.index_current(&ciphertext_id(&blob), "records")?;
+//!
+//! // After rotating "records":
+//! for entry in store.stale_ciphertexts("records")? {
+//!     // re-encrypt the blob of `entry.ciphertext_id` under the current version
+//! }
+//! ```
+//!
+//! Ciphertext ids are chosen by the application, e.g. the id of the row holding the blob, or the
+//! SHA-256 hash of the blob returned by `ciphertext_id`. Only ids are stored, never ciphertexts.
+//! Deleting the record of a key keeps its ciphertexts in the index, since the blobs still exist.
+//!
+//! The store is available with the `sqlite` feature. SQLite is bundled, so no system library is
+//! required.
+
+use crate::common::{
+    error::SecurityModuleError,
+    metadata_store::{KeyRecord, MetadataStore},
+};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::{
+    path::Path,
+    sync::{Mutex, MutexGuard},
+    time::Duration,
+};
+
+/// The version of the database schema, stored as `user_version`.
+const SCHEMA_VERSION: u32 = 1;
+
+/// How long a call waits for another process writing the database.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS key_records (
+        key_id TEXT PRIMARY KEY NOT NULL,
+        version INTEGER NOT NULL,
+        record TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS ciphertexts (
+        ciphertext_id TEXT PRIMARY KEY NOT NULL,
+        key_id TEXT NOT NULL,
+        version INTEGER NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS ciphertexts_by_key ON ciphertexts (key_id, version);
+";
+
+/// A ciphertext in the index and the key version that decrypts it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CiphertextEntry {
+    pub ciphertext_id: String,
+    pub key_id: String,
+    /// The `KeyRecord::version` of the key the ciphertext was encrypted with.
+    pub version: u32,
+}
+
+/// Returns the id of a ciphertext derived from its content, the SHA-256 hash in hex.
+pub fn ciphertext_id(ciphertext: &[u8]) -> String {
+    openssl::sha::sha256(ciphertext)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// A `MetadataStore` keeping the records and the ciphertext index in a SQLite database, see the
+/// module documentation.
+#[derive(Debug)]
+pub struct SqliteMetadataStore {
+    connection: Mutex<Connection>,
+}
+
+impl SqliteMetadataStore {
+    /// Opens the database at `path`, creating it if it does not exist.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `SqliteMetadataStore` on success, or a
+    /// `SecurityModuleError::SecretStorage` if the database cannot be opened or was created by a
+    /// newer version of the crate.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, SecurityModuleError> {
+        let connection = Connection::open(path).map_err(|e| database_error("open", e))?;
+        Self::init(connection)
+    }
+
+    /// Opens a database in memory, e.g. for tests.
+    pub fn open_in_memory() -> Result<Self, SecurityModuleError> {
+        let connection = Connection::open_in_memory().map_err(|e| database_error("open", e))?;
+        Self::init(connection)
+    }
+
+    fn init(connection: Connection) -> Result<Self, SecurityModuleError> {
+        connection
+            .busy_timeout(BUSY_TIMEOUT)
+            .map_err(|e| database_error("open", e))?;
+        let version: u32 = connection
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .map_err(|e| database_error("open", e))?;
+        if version > SCHEMA_VERSION {
+            return Err(SecurityModuleError::SecretStorage(format!(
+                "The metadata database has schema version {}, but crypto-layer supports up to version {}",
+                version, SCHEMA_VERSION
+            )));
+        }
+        connection
+            .execute_batch(SCHEMA)
+            .and_then(|_| connection.pragma_update(None, "user_version", SCHEMA_VERSION))
+            .map_err(|e| database_error("create", e))?;
+        Ok(Self {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    fn connection(&self) -> MutexGuard<'_, Connection> {
+        self.connection.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Records that the ciphertext `ciphertext_id` is decrypted by version `version` of the key
+    /// `key_id`, replacing any previous entry of the ciphertext.
+    pub fn index_ciphertext(
+        &self,
+        ciphertext_id: &str,
+        key_id: &str,
+        version: u32,
+    ) -> Result<(), SecurityModuleError> {
+        self.connection()
+            .execute(
+                "INSERT OR REPLACE INTO ciphertexts (ciphertext_id, key_id, version) VALUES (?1, ?2, ?3)",
+                params![ciphertext_id, key_id, version],
+            )
+            .map(|_| ())
+            .map_err(|e| database_error("write", e))
+    }
+
+    /// Records that the ciphertext `ciphertext_id` is decrypted by the current version of the key
+    /// `key_id`, see `index_ciphertext`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the version on success, or a `SecurityModuleError::SecretStorage` if
+    /// the key has no record.
+    pub fn index_current(
+        &self,
+        ciphertext_id: &str,
+        key_id: &str,
+    ) -> Result<u32, SecurityModuleError> {
+        let version = self
+            .load(key_id)?
+            .ok_or_else(|| {
+                SecurityModuleError::SecretStorage(format!("The key '{}' has no record", key_id))
+            })?
+            .version;
+        self.index_ciphertext(ciphertext_id, key_id, version)?;
+        Ok(version)
+    }
+
+    /// Returns the key version that decrypts the ciphertext `ciphertext_id`, or `None` if it is
+    /// not in the index.
+    pub fn lookup_ciphertext(
+        &self,
+        ciphertext_id: &str,
+    ) -> Result<Option<CiphertextEntry>, SecurityModuleError> {
+        self.connection()
+            .query_row(
+                "SELECT ciphertext_id, key_id, version FROM ciphertexts WHERE ciphertext_id = ?1",
+                [ciphertext_id],
+                entry,
+            )
+            .optional()
+            .map_err(|e| database_error("read", e))
+    }
+
+    /// Returns the ciphertexts encrypted with the key `key_id`, with any version if `version` is
+    /// `None`, ordered by version and id.
+    pub fn ciphertexts(
+        &self,
+        key_id: &str,
+        version: Option<u32>,
+    ) -> Result<Vec<CiphertextEntry>, SecurityModuleError> {
+        self.query(
+            "SELECT ciphertext_id, key_id, version FROM ciphertexts
+             WHERE key_id = ?1 AND (?2 IS NULL OR version = ?2)
+             ORDER BY version, ciphertext_id",
+            params![key_id, version],
+        )
+    }
+
+    /// Returns the ciphertexts encrypted with an older version of the key `key_id` than the
+    /// version of its record, i.e. those to re-encrypt after a rotation, ordered by version and
+    /// id.
+    pub fn stale_ciphertexts(
+        &self,
+        key_id: &str,
+    ) -> Result<Vec<CiphertextEntry>, SecurityModuleError> {
+        self.query(
+            "SELECT c.ciphertext_id, c.key_id, c.version FROM ciphertexts c
+             JOIN key_records r ON r.key_id = c.key_id
+             WHERE c.key_id = ?1 AND c.version < r.version
+             ORDER BY c.version, c.ciphertext_id",
+            params![key_id],
+        )
+    }
+
+    /// Removes the ciphertext `ciphertext_id` from the index and returns whether it was in it.
+    pub fn remove_ciphertext(&self, ciphertext_id: &str) -> Result<bool, SecurityModuleError> {
+        self.connection()
+            .execute(
+                "DELETE FROM ciphertexts WHERE ciphertext_id = ?1",
+                [ciphertext_id],
+            )
+            .map(|rows| rows > 0)
+            .map_err(|e| database_error("delete", e))
+    }
+
+    fn query(
+        &self,
+        sql: &str,
+        params: impl rusqlite::Params,
+    ) -> Result<Vec<CiphertextEntry>, SecurityModuleError> {
+        let connection = self.connection();
+        let mut statement = connection
+            .prepare_cached(sql)
+            .map_err(|e| database_error("read", e))?;
+        let entries = statement
+            .query_map(params, entry)
+            .and_then(|rows| rows.collect())
+            .map_err(|e| database_error("read", e));
+        entries
+    }
+}
+
+impl MetadataStore for SqliteMetadataStore {
+    fn load(&self, key_id: &str) -> Result<Option<KeyRecord>, SecurityModuleError> {
+        let json: Option<String> = self
+            .connection()
+            .query_row(
+                "SELECT record FROM key_records WHERE key_id = ?1",
+                [key_id],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| database_error("read", e))?;
+        json.map(|json| parse_record(key_id, &json)).transpose()
+    }
+
+    fn save(&self, record: &KeyRecord) -> Result<(), SecurityModuleError> {
+        write_record(&self.connection(), record)
+    }
+
+    fn delete(&self, key_id: &str) -> Result<bool, SecurityModuleError> {
+        self.connection()
+            .execute("DELETE FROM key_records WHERE key_id = ?1", [key_id])
+            .map(|rows| rows > 0)
+            .map_err(|e| database_error("delete", e))
+    }
+
+    fn key_ids(&self) -> Result<Vec<String>, SecurityModuleError> {
+        let connection = self.connection();
+        let mut statement = connection
+            .prepare_cached("SELECT key_id FROM key_records ORDER BY key_id")
+            .map_err(|e| database_error("list", e))?;
+        let key_ids = statement
+            .query_map([], |row| row.get(0))
+            .and_then(|rows| rows.collect())
+            .map_err(|e| database_error("list", e));
+        key_ids
+    }
+
+    fn update(
+        &self,
+        key_id: &str,
+        f: &mut dyn FnMut(&mut KeyRecord),
+    ) -> Result<KeyRecord, SecurityModuleError> {
+        let mut connection = self.connection();
+        // The transaction keeps other processes from writing the record in between.
+        let transaction = connection
+            .transaction_with_behavior(rusqlite::TransactionBehavior::Immediate)
+            .map_err(|e| database_error("write", e))?;
+        let json: Option<String> = transaction
+            .query_row(
+                "SELECT record FROM key_records WHERE key_id = ?1",
+                [key_id],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| database_error("read", e))?;
+        let mut record = match json {
+            Some(json) => parse_record(key_id, &json)?,
+            None => KeyRecord::new(key_id),
+        };
+        f(&mut record);
+        write_record(&transaction, &record)?;
+        transaction
+            .commit()
+            .map_err(|e| database_error("write", e))?;
+        Ok(record)
+    }
+}
+
+fn write_record(connection: &Connection, record: &KeyRecord) -> Result<(), SecurityModuleError> {
+    // A `KeyRecord` only holds strings, numbers and maps with string keys.
+    let json = serde_json::to_string(record).expect("A key record serializes to JSON");
+    connection
+        .execute(
+            "INSERT OR REPLACE INTO key_records (key_id, version, record) VALUES (?1, ?2, ?3)",
+            params![record.key_id, record.version, json],
+        )
+        .map(|_| ())
+        .map_err(|e| database_error("write", e))
+}
+
+fn parse_record(key_id: &str, json: &str) -> Result<KeyRecord, SecurityModuleError> {
+    serde_json::from_str(json).map_err(|e| {
+        SecurityModuleError::SecretStorage(format!(
+            "The metadata of '{}' in the database is invalid: {}",
+            key_id, e
+        ))
+    })
+}
+
+fn entry(row: &rusqlite::Row<'_>) -> rusqlite::Result<CiphertextEntry> {
+    Ok(CiphertextEntry {
+        ciphertext_id: row.get(0)?,
+        key_id: row.get(1)?,
+        version: row.get(2)?,
+    })
+}
+
+fn database_error(action: &str, e: rusqlite::Error) -> SecurityModuleError {
+    SecurityModuleError::SecretStorage(format!("Cannot {} the metadata database: {}", action, e))
+}
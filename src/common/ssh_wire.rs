@@ -0,0 +1,218 @@
+//! The SSH wire encoding of public keys and signatures, shared by `sshsig` and the SSH agent.
+//!
+//! Public keys are encoded as in RFC 4253, RFC 5656 and RFC 8709, signatures as in RFC 5656,
+//! RFC 8332 and RFC 8709. Strings and integers are `string` and `mpint` of RFC 4251.
+
+use crate::common::{
+    crypto::{
+        algorithms::{
+            encryption::{AsymmetricEncryption, EccCurves, EccSchemeAlgorithm},
+            hashes::{Hash, Sha2Bits},
+        },
+        public_key::{PublicKey, RsaSignaturePadding},
+        signature_format,
+    },
+    error::SecurityModuleError,
+};
+use base64::{prelude::BASE64_STANDARD, Engine};
+use openssl::pkey::PKey;
+
+/// The SSH key type of a public key.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum KeyType {
+    Ecdsa {
+        curve: &'static str,
+        scalar_len: usize,
+    },
+    Ed25519,
+    Rsa {
+        hash: Sha2Bits,
+    },
+}
+
+impl KeyType {
+    /// Returns the key type of `public_key`, or a `SecurityModuleError::UnsupportedAlgorithm` if
+    /// it cannot be used with SSH.
+    ///
+    /// ECDSA keys must use the hash of their curve, and RSA keys PKCS#1 v1.5 signatures with
+    /// SHA-256 or SHA-512, since SSH fixes the hash of every signature algorithm.
+    pub(crate) fn of(public_key: &PublicKey) -> Result<Self, SecurityModuleError> {
+        match (public_key.algorithm(), public_key.hash()) {
+            (AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(EccCurves::Curve25519)), _) => {
+                Ok(KeyType::Ed25519)
+            }
+            (AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(curve)), Hash::Sha2(bits)) => {
+                match (curve, bits) {
+                    (EccCurves::P256, Sha2Bits::Sha256) => Ok(KeyType::Ecdsa {
+                        curve: "nistp256",
+                        scalar_len: 32,
+                    }),
+                    (EccCurves::P384, Sha2Bits::Sha384) => Ok(KeyType::Ecdsa {
+                        curve: "nistp384",
+                        scalar_len: 48,
+                    }),
+                    (EccCurves::P521, Sha2Bits::Sha512) => Ok(KeyType::Ecdsa {
+                        curve: "nistp521",
+                        scalar_len: 66,
+                    }),
+                    _ => Err(SecurityModuleError::UnsupportedAlgorithm),
+                }
+            }
+            (
+                AsymmetricEncryption::Rsa(_),
+                Hash::Sha2(hash @ (Sha2Bits::Sha256 | Sha2Bits::Sha512)),
+            ) if public_key.rsa_padding() == RsaSignaturePadding::Pkcs1 => {
+                Ok(KeyType::Rsa { hash })
+            }
+            _ => Err(SecurityModuleError::UnsupportedAlgorithm),
+        }
+    }
+
+    /// Returns the name of the key type, e.g. `ecdsa-sha2-nistp256`.
+    pub(crate) fn name(self) -> String {
+        match self {
+            KeyType::Ecdsa { curve, .. } => format!("ecdsa-sha2-{}", curve),
+            KeyType::Ed25519 => "ssh-ed25519".to_owned(),
+            KeyType::Rsa { .. } => "ssh-rsa".to_owned(),
+        }
+    }
+
+    /// Returns the name of the signature algorithm the key signs with, which differs from the
+    /// key type for RSA keys.
+    pub(crate) fn signature_algorithm(self) -> String {
+        match self {
+            KeyType::Rsa {
+                hash: Sha2Bits::Sha512,
+            } => "rsa-sha2-512".to_owned(),
+            KeyType::Rsa { .. } => "rsa-sha2-256".to_owned(),
+            key_type => key_type.name(),
+        }
+    }
+}
+
+/// Encodes `public_key` of the type `key_type`.
+pub(crate) fn public_key_blob(
+    key_type: KeyType,
+    public_key: &PublicKey,
+) -> Result<Vec<u8>, SecurityModuleError> {
+    let mut blob = Vec::new();
+    put_bytes(&mut blob, key_type.name().as_bytes());
+    match key_type {
+        KeyType::Ecdsa { curve, .. } => {
+            put_bytes(&mut blob, curve.as_bytes());
+            put_bytes(&mut blob, &public_key.to_ec_point()?);
+        }
+        KeyType::Ed25519 => {
+            let raw = PKey::public_key_from_der(&public_key.to_der()?)
+                .and_then(|key| key.raw_public_key())
+                .map_err(|_| SecurityModuleError::InvalidPublicKey)?;
+            put_bytes(&mut blob, &raw);
+        }
+        KeyType::Rsa { .. } => {
+            let rsa = PKey::public_key_from_der(&public_key.to_der()?)
+                .and_then(|key| key.rsa())
+                .map_err(|_| SecurityModuleError::InvalidPublicKey)?;
+            put_mpint(&mut blob, &rsa.e().to_vec());
+            put_mpint(&mut blob, &rsa.n().to_vec());
+        }
+    }
+    Ok(blob)
+}
+
+/// Formats a public key blob as a line of `authorized_keys`, e.g. `ssh-ed25519 AAAA... comment`.
+pub(crate) fn public_key_line(key_type: KeyType, blob: &[u8], comment: &str) -> String {
+    let line = format!("{} {}", key_type.name(), BASE64_STANDARD.encode(blob));
+    match comment {
+        "" => line,
+        comment => format!("{} {}", line, comment),
+    }
+}
+
+/// Encodes a signature of `sign_data`, DER encoded for ECDSA, as an SSH signature.
+pub(crate) fn encode_signature(
+    key_type: KeyType,
+    signature: &[u8],
+) -> Result<Vec<u8>, SecurityModuleError> {
+    let signature = match key_type {
+        KeyType::Ecdsa { scalar_len, .. } => {
+            let raw = signature_format::der_to_raw(signature, scalar_len)?;
+            let (r, s) = raw.split_at(scalar_len);
+            let mut scalars = Vec::new();
+            put_mpint(&mut scalars, r);
+            put_mpint(&mut scalars, s);
+            scalars
+        }
+        KeyType::Ed25519 | KeyType::Rsa { .. } => signature.to_vec(),
+    };
+    let mut encoded = Vec::new();
+    put_bytes(&mut encoded, key_type.signature_algorithm().as_bytes());
+    put_bytes(&mut encoded, &signature);
+    Ok(encoded)
+}
+
+/// Decodes an SSH signature by a key of the type `key_type` into the encoding of
+/// `PublicKey::verify`.
+///
+/// Returns `None` if `encoded` is malformed or was created with another signature algorithm.
+pub(crate) fn decode_signature(key_type: KeyType, mut encoded: &[u8]) -> Option<Vec<u8>> {
+    let algorithm = get_bytes(&mut encoded)?;
+    let signature = get_bytes(&mut encoded)?;
+    if algorithm != key_type.signature_algorithm().as_bytes() || !encoded.is_empty() {
+        return None;
+    }
+    match key_type {
+        KeyType::Ecdsa { scalar_len, .. } => {
+            let mut scalars = signature;
+            let mut raw = Vec::with_capacity(2 * scalar_len);
+            for _ in 0..2 {
+                let scalar = get_bytes(&mut scalars)?;
+                let scalar = scalar.strip_prefix(&[0]).unwrap_or(scalar);
+                if scalar.len() > scalar_len {
+                    return None;
+                }
+                raw.resize(raw.len() + scalar_len - scalar.len(), 0);
+                raw.extend_from_slice(scalar);
+            }
+            if !scalars.is_empty() {
+                return None;
+            }
+            signature_format::raw_to_der(&raw).ok()
+        }
+        KeyType::Ed25519 | KeyType::Rsa { .. } => Some(signature.to_vec()),
+    }
+}
+
+/// Appends `bytes` as a `string`.
+pub(crate) fn put_bytes(buffer: &mut Vec<u8>, bytes: &[u8]) {
+    buffer.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+    buffer.extend_from_slice(bytes);
+}
+
+/// Appends an unsigned big-endian integer as an `mpint`.
+pub(crate) fn put_mpint(buffer: &mut Vec<u8>, integer: &[u8]) {
+    let start = integer
+        .iter()
+        .position(|&byte| byte != 0)
+        .unwrap_or(integer.len());
+    let integer = &integer[start..];
+    if integer.first().is_some_and(|&byte| byte & 0x80 != 0) {
+        put_bytes(buffer, &[&[0], integer].concat());
+    } else {
+        put_bytes(buffer, integer);
+    }
+}
+
+/// Takes a `uint32` from the front of `buffer`, or returns `None` if it is truncated.
+pub(crate) fn get_u32(buffer: &mut &[u8]) -> Option<u32> {
+    let (value, rest) = buffer.split_first_chunk::<4>()?;
+    *buffer = rest;
+    Some(u32::from_be_bytes(*value))
+}
+
+/// Takes a `string` from the front of `buffer`, or returns `None` if it is truncated.
+pub(crate) fn get_bytes<'a>(buffer: &mut &'a [u8]) -> Option<&'a [u8]> {
+    let len = get_u32(buffer)? as usize;
+    let (bytes, rest) = buffer.split_at_checked(len)?;
+    *buffer = rest;
+    Some(bytes)
+}
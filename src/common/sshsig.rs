@@ -0,0 +1,255 @@
+//! SSH signatures of arbitrary data, as created by `ssh-keygen -Y sign`.
+//!
+//! The format is specified in `PROTOCOL.sshsig` of OpenSSH. Git signs commits and tags with it
+//! when `gpg.format` is `ssh`, and `ssh-keygen -Y verify` checks signatures against a file of
+//! allowed signers, so signatures of this module can be checked with standard tooling and vice
+//! versa:
+//!
+//! ```rust,ignore
+//! use crypto_layer::common::sshsig::{self, SshSignature};
+//!
+//! let signature = sshsig::sign(&provider, sshsig::GIT_NAMESPACE, commit)?;
+//! std::fs::write("commit.sig", signature.to_string())?;
+//!
+//! let signature: SshSignature = std::fs::read_to_string("commit.sig")?.parse()?;
+//! sshsig::verify(&signature, &public_key, sshsig::GIT_NAMESPACE, commit)?;
+//! ```
+//!
+//! The namespace separates signatures for different purposes, e.g. `git` for commits and `file`
+//! for files, so a signature made for one purpose is rejected for another. The data is hashed with
+//! SHA-512 and the hash is signed together with the namespace with `sign_data`. Keys must have
+//! an SSH key type, see `public_key_line`.
+
+use crate::common::{
+    crypto::public_key::PublicKey,
+    error::SecurityModuleError,
+    ssh_wire::{self, KeyType},
+    traits::module_provider::Provider,
+};
+use base64::{prelude::BASE64_STANDARD, Engine};
+use openssl::sha::{sha256, sha512};
+use std::{fmt, str::FromStr};
+
+/// The namespace Git signs commits and tags in.
+pub const GIT_NAMESPACE: &str = "git";
+
+/// The namespace `ssh-keygen -Y sign` uses for files.
+pub const FILE_NAMESPACE: &str = "file";
+
+const MAGIC: &[u8] = b"SSHSIG";
+const VERSION: u32 = 1;
+const HASH_ALGORITHM: &str = "sha512";
+const BEGIN: &str = "-----BEGIN SSH SIGNATURE-----";
+const END: &str = "-----END SSH SIGNATURE-----";
+
+/// The length of the lines of the armored encoding.
+const LINE_LEN: usize = 70;
+
+/// An SSH signature, see the module documentation.
+///
+/// `Display` and `FromStr` use the armored encoding of `ssh-keygen`, `to_bytes` and
+/// `from_bytes` the binary one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SshSignature {
+    public_key: Vec<u8>,
+    namespace: String,
+    hash_algorithm: String,
+    signature: Vec<u8>,
+}
+
+impl SshSignature {
+    /// Returns the SSH encoding of the public key the signature claims to be created with.
+    pub fn public_key_blob(&self) -> &[u8] {
+        &self.public_key
+    }
+
+    /// Returns the namespace of the signature.
+    pub fn namespace(&self) -> &str {
+        &self.namespace
+    }
+
+    /// Returns the binary encoding of the signature.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut encoded = MAGIC.to_vec();
+        encoded.extend_from_slice(&VERSION.to_be_bytes());
+        ssh_wire::put_bytes(&mut encoded, &self.public_key);
+        ssh_wire::put_bytes(&mut encoded, self.namespace.as_bytes());
+        ssh_wire::put_bytes(&mut encoded, b"");
+        ssh_wire::put_bytes(&mut encoded, self.hash_algorithm.as_bytes());
+        ssh_wire::put_bytes(&mut encoded, &self.signature);
+        encoded
+    }
+
+    /// Parses the binary encoding of a signature without verifying it.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `SshSignature`, or a
+    /// `SecurityModuleError::SignatureVerificationError` if `bytes` is not an SSH signature of
+    /// version 1.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SecurityModuleError> {
+        let mut rest = bytes
+            .strip_prefix(MAGIC)
+            .ok_or_else(|| malformed("The signature does not start with SSHSIG"))?;
+        if ssh_wire::get_u32(&mut rest) != Some(VERSION) {
+            return Err(malformed("The signature has an unsupported version"));
+        }
+        let mut field = || {
+            ssh_wire::get_bytes(&mut rest).ok_or_else(|| malformed("The signature is truncated"))
+        };
+        let public_key = field()?.to_vec();
+        let namespace = String::from_utf8(field()?.to_vec())
+            .map_err(|_| malformed("The namespace is not UTF-8"))?;
+        field()?;
+        let hash_algorithm = String::from_utf8(field()?.to_vec())
+            .map_err(|_| malformed("The hash algorithm is not UTF-8"))?;
+        let signature = field()?.to_vec();
+        if !rest.is_empty() {
+            return Err(malformed("The signature has trailing data"));
+        }
+        Ok(Self {
+            public_key,
+            namespace,
+            hash_algorithm,
+            signature,
+        })
+    }
+}
+
+impl fmt::Display for SshSignature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", BEGIN)?;
+        let encoded = BASE64_STANDARD.encode(self.to_bytes());
+        for line in encoded.as_bytes().chunks(LINE_LEN) {
+            writeln!(f, "{}", String::from_utf8_lossy(line))?;
+        }
+        writeln!(f, "{}", END)
+    }
+}
+
+impl FromStr for SshSignature {
+    type Err = SecurityModuleError;
+
+    /// Parses an armored signature without verifying it.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `SshSignature`, or a
+    /// `SecurityModuleError::SignatureVerificationError` if `s` is not an armored SSH signature.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let body = s
+            .trim()
+            .strip_prefix(BEGIN)
+            .and_then(|s| s.strip_suffix(END))
+            .ok_or_else(|| malformed("The signature is not armored"))?;
+        let encoded: String = body.split_whitespace().collect();
+        let bytes = BASE64_STANDARD
+            .decode(encoded)
+            .map_err(|e| malformed(&e.to_string()))?;
+        Self::from_bytes(&bytes)
+    }
+}
+
+/// Returns `public_key` as a line of `authorized_keys` or `allowed_signers`, e.g. for the
+/// `user.signingkey` of Git.
+///
+/// # Returns
+///
+/// A `Result` containing the line, e.g. `ecdsa-sha2-nistp256 AAAA... comment`, or a
+/// `SecurityModuleError::UnsupportedAlgorithm` if the key has no SSH key type. ECDSA keys must
+/// use the hash of their curve, e.g. SHA-256 for P-256, and RSA keys PKCS#1 v1.5 signatures with
+/// SHA-256 or SHA-512.
+pub fn public_key_line(
+    public_key: &PublicKey,
+    comment: &str,
+) -> Result<String, SecurityModuleError> {
+    let key_type = KeyType::of(public_key)?;
+    let blob = ssh_wire::public_key_blob(key_type, public_key)?;
+    Ok(ssh_wire::public_key_line(key_type, &blob, comment))
+}
+
+/// Signs `data` in `namespace` with the loaded key of `provider`.
+///
+/// # Returns
+///
+/// A `Result` containing the `SshSignature`, a `SecurityModuleError::SigningError` if the
+/// namespace is empty, a `SecurityModuleError::UnsupportedAlgorithm` if the key has no SSH key
+/// type, or the error of `provider`.
+#[tracing::instrument(skip(provider, data), fields(crypto.payload.size = data.len()))]
+pub fn sign(
+    provider: &(impl Provider + ?Sized),
+    namespace: &str,
+    data: &[u8],
+) -> Result<SshSignature, SecurityModuleError> {
+    if namespace.is_empty() {
+        return Err(SecurityModuleError::SigningError(
+            "The namespace must not be empty".to_owned(),
+        ));
+    }
+    let metadata = provider.key_metadata()?;
+    let key_type = KeyType::of(metadata.public_key())?;
+    let signature = provider.sign_data(&signed_data(namespace, HASH_ALGORITHM, data)?)?;
+    Ok(SshSignature {
+        public_key: ssh_wire::public_key_blob(key_type, metadata.public_key())?,
+        namespace: namespace.to_owned(),
+        hash_algorithm: HASH_ALGORITHM.to_owned(),
+        signature: ssh_wire::encode_signature(key_type, &signature)?,
+    })
+}
+
+/// Verifies that `signature` was created over `data` in `namespace` by the owner of
+/// `public_key`.
+///
+/// Signatures with SHA-256 and SHA-512 hashes are accepted. RSA signatures must use the hash of
+/// `public_key`.
+///
+/// # Returns
+///
+/// A `Result` that is `Ok(())` if the signature is valid, a
+/// `SecurityModuleError::InvalidSignature` if it was created by another key, in another
+/// namespace or over other data, or a `SecurityModuleError::UnsupportedAlgorithm` if
+/// `public_key` has no SSH key type.
+#[tracing::instrument(skip(signature, public_key, data), fields(crypto.payload.size = data.len()))]
+pub fn verify(
+    signature: &SshSignature,
+    public_key: &PublicKey,
+    namespace: &str,
+    data: &[u8],
+) -> Result<(), SecurityModuleError> {
+    let key_type = KeyType::of(public_key)?;
+    if signature.public_key != ssh_wire::public_key_blob(key_type, public_key)?
+        || signature.namespace != namespace
+    {
+        return Err(SecurityModuleError::InvalidSignature);
+    }
+    let decoded = ssh_wire::decode_signature(key_type, &signature.signature)
+        .ok_or(SecurityModuleError::InvalidSignature)?;
+    let signed = signed_data(namespace, &signature.hash_algorithm, data)
+        .map_err(|_| SecurityModuleError::InvalidSignature)?;
+    match public_key.verify(&signed, &decoded)? {
+        true => Ok(()),
+        false => Err(SecurityModuleError::InvalidSignature),
+    }
+}
+
+fn signed_data(
+    namespace: &str,
+    hash_algorithm: &str,
+    data: &[u8],
+) -> Result<Vec<u8>, SecurityModuleError> {
+    let hash = match hash_algorithm {
+        "sha256" => sha256(data).to_vec(),
+        "sha512" => sha512(data).to_vec(),
+        _ => return Err(SecurityModuleError::UnsupportedAlgorithm),
+    };
+    let mut signed = MAGIC.to_vec();
+    ssh_wire::put_bytes(&mut signed, namespace.as_bytes());
+    ssh_wire::put_bytes(&mut signed, b"");
+    ssh_wire::put_bytes(&mut signed, hash_algorithm.as_bytes());
+    ssh_wire::put_bytes(&mut signed, &hash);
+    Ok(signed)
+}
+
+fn malformed(message: &str) -> SecurityModuleError {
+    SecurityModuleError::SignatureVerificationError(message.to_owned())
+}
@@ -0,0 +1,315 @@
+//! Deprecation of algorithms and key sizes.
+//!
+//! Organizational crypto-agility policies retire algorithms in two steps: creating keys with
+//! them first causes a warning and is later denied, while existing keys keep working until they
+//! are migrated. A `SunsetPolicy` maps algorithm names to a `SunsetAction` and is read from the
+//! `deprecated` table of the configuration:
+//!
+//! ```toml
+//! [deprecated]
+//! rsa1024 = "deny"
+//! rsa2048 = "warn"
+//! sha1 = "deny"
+//! ```
+//!
+//! The names are those of `ALGORITHM_NAMES`: RSA key sizes like `rsa2048`, elliptic curves like
+//! `p256` and hashes like `sha1` or `sha3_256`. `SecModules::get_instance` applies the policy of
+//! the configuration to `create_key` and `import_wrapped_key` through `ValidatedProvider`;
+//! `load_key` is never affected.
+//! `SunsetPolicy::keys_to_migrate` lists the keys still using deprecated algorithms.
+
+use crate::common::crypto::{
+    algorithms::{
+        encryption::{AsymmetricEncryption, BlockCiphers, EccSchemeAlgorithm},
+        hashes::Hash,
+    },
+    key_metadata::KeyMetadata,
+    key_spec::KeySpec,
+};
+use crate::common::error::SecurityModuleError;
+use serde::{Deserialize, Serialize};
+use std::{any::Any, collections::BTreeMap};
+
+/// The names of all algorithms that can be deprecated.
+pub const ALGORITHM_NAMES: [&str; 32] = [
+    "rsa512",
+    "rsa1024",
+    "rsa2048",
+    "rsa3072",
+    "rsa4096",
+    "rsa8192",
+    "p256",
+    "p384",
+    "p521",
+    "secp256k1",
+    "brainpoolp256r1",
+    "brainpoolp384r1",
+    "brainpoolp512r1",
+    "brainpoolp638",
+    "curve25519",
+    "curve448",
+    "frp256v1",
+    "sha1",
+    "sha224",
+    "sha256",
+    "sha384",
+    "sha512",
+    "sha512_224",
+    "sha512_256",
+    "sha3_224",
+    "sha3_256",
+    "sha3_384",
+    "sha3_512",
+    "md2",
+    "md4",
+    "md5",
+    "ripemd160",
+];
+
+/// What happens when a key is created with a deprecated algorithm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SunsetAction {
+    /// The key is created and a warning is logged.
+    Warn,
+    /// Creating the key fails with `SecurityModuleError::DeprecatedAlgorithm`.
+    Deny,
+}
+
+/// A key that uses a deprecated algorithm, returned by `SunsetPolicy::keys_to_migrate`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyMigration {
+    pub key_id: String,
+    /// The name of the deprecated algorithm, see `ALGORITHM_NAMES`.
+    pub algorithm: String,
+    pub action: SunsetAction,
+}
+
+/// The deprecated algorithms, see the module documentation.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SunsetPolicy {
+    algorithms: BTreeMap<String, SunsetAction>,
+}
+
+impl SunsetPolicy {
+    /// Creates a policy that does not deprecate any algorithm.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a policy from the `deprecated` table of the configuration.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the policy on success, or a
+    /// `SecurityModuleError::InitializationError` naming the first unknown algorithm.
+    pub fn from_map(
+        algorithms: &BTreeMap<String, SunsetAction>,
+    ) -> Result<Self, SecurityModuleError> {
+        algorithms
+            .iter()
+            .try_fold(Self::new(), |policy, (name, action)| {
+                policy.deprecate(name, *action)
+            })
+    }
+
+    /// Deprecates an algorithm, replacing its previous action.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the policy on success, or a
+    /// `SecurityModuleError::InitializationError` if `name` is not one of `ALGORITHM_NAMES`.
+    pub fn deprecate(
+        mut self,
+        name: &str,
+        action: SunsetAction,
+    ) -> Result<Self, SecurityModuleError> {
+        if !ALGORITHM_NAMES.contains(&name) {
+            return Err(SecurityModuleError::InitializationError(format!(
+                "Unknown algorithm '{}'",
+                name
+            )));
+        }
+        self.algorithms.insert(name.to_owned(), action);
+        Ok(self)
+    }
+
+    /// Returns whether no algorithm is deprecated.
+    pub fn is_empty(&self) -> bool {
+        self.algorithms.is_empty()
+    }
+
+    /// Returns the deprecated algorithm among `algorithm` and `hash` with the strictest action,
+    /// or `None` if neither is deprecated.
+    pub fn check(
+        &self,
+        algorithm: Option<AsymmetricEncryption>,
+        hash: Option<Hash>,
+    ) -> Option<(&str, SunsetAction)> {
+        algorithm
+            .and_then(algorithm_name)
+            .into_iter()
+            .chain(hash.map(hash_name))
+            .filter_map(|name| self.algorithms.get_key_value(&name))
+            .map(|(name, action)| (name.as_str(), *action))
+            .max_by_key(|(_, action)| *action)
+    }
+
+    /// Checks the algorithm of a key about to be created with `config`, which is the
+    /// configuration passed to `Provider::create_key` or the `KeySpec` passed to
+    /// `Provider::import_wrapped_key`.
+    ///
+    /// Logs a warning for algorithms deprecated with `SunsetAction::Warn`. Configurations of
+    /// unknown types are not checked.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` that is `Ok(())` if the key may be created, or a
+    /// `SecurityModuleError::DeprecatedAlgorithm` if its algorithm is denied.
+    pub fn check_new_key(&self, key_id: &str, config: &dyn Any) -> Result<(), SecurityModuleError> {
+        if self.is_empty() {
+            return Ok(());
+        }
+        let Some(requested) = requested_algorithms(config) else {
+            return Ok(());
+        };
+        match self.check(requested.asymmetric, requested.hash) {
+            Some((name, SunsetAction::Deny)) => Err(SecurityModuleError::DeprecatedAlgorithm(
+                format!("{} may no longer be used for new keys", name),
+            )),
+            Some((name, SunsetAction::Warn)) => {
+                tracing::warn!(
+                    key_id_hash = %crate::common::latency::key_id_hash(key_id),
+                    algorithm = name,
+                    "Creating a key with a deprecated algorithm"
+                );
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the keys among `keys` that use a deprecated algorithm and need to be migrated.
+    pub fn keys_to_migrate<'a>(
+        &self,
+        keys: impl IntoIterator<Item = &'a KeyMetadata>,
+    ) -> Vec<KeyMigration> {
+        keys.into_iter()
+            .filter_map(|metadata| {
+                let public_key = metadata.public_key();
+                let (algorithm, action) =
+                    self.check(Some(public_key.algorithm()), Some(public_key.hash()))?;
+                Some(KeyMigration {
+                    key_id: metadata.key_id().to_owned(),
+                    algorithm: algorithm.to_owned(),
+                    action,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Returns the name of an asymmetric algorithm in `ALGORITHM_NAMES`, e.g. `rsa2048` or `p256`.
+pub fn algorithm_name(algorithm: AsymmetricEncryption) -> Option<String> {
+    match algorithm {
+        AsymmetricEncryption::Rsa(bits) => Some(format!("rsa{}", u32::from(bits))),
+        AsymmetricEncryption::Ecc(scheme) => match scheme {
+            EccSchemeAlgorithm::EcDsa(curve)
+            | EccSchemeAlgorithm::EcDh(curve)
+            | EccSchemeAlgorithm::EcDaa(curve)
+            | EccSchemeAlgorithm::Sm2(curve)
+            | EccSchemeAlgorithm::EcSchnorr(curve)
+            | EccSchemeAlgorithm::EcMqv(curve) => Some(format!("{:?}", curve).to_lowercase()),
+            EccSchemeAlgorithm::Null => None,
+        },
+    }
+}
+
+/// Returns the name of a hash in `ALGORITHM_NAMES`, e.g. `sha256` or `sha3_256`.
+pub fn hash_name(hash: Hash) -> String {
+    match hash {
+        Hash::Sha2(bits) => format!("{:?}", bits),
+        Hash::Sha3(bits) => format!("{:?}", bits),
+        hash => format!("{:?}", hash),
+    }
+    .to_lowercase()
+}
+
+/// The algorithms requested by a provider configuration, see `requested_algorithms`.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct RequestedAlgorithms {
+    /// The algorithm of a key pair.
+    pub(crate) asymmetric: Option<AsymmetricEncryption>,
+    /// The algorithm of a symmetric key, or the cipher protecting a key pair in the TPM.
+    pub(crate) symmetric: Option<BlockCiphers>,
+    pub(crate) hash: Option<Hash>,
+}
+
+/// Returns the algorithms requested by a known provider configuration, or `None` for
+/// configurations of unknown types.
+pub(crate) fn requested_algorithms(config: &dyn Any) -> Option<RequestedAlgorithms> {
+    if let Some(spec) = config.downcast_ref::<KeySpec>() {
+        return Some(spec_algorithms(spec));
+    }
+    #[cfg(feature = "remote")]
+    if let Some(config) = config.downcast_ref::<crate::remote::RemoteConfig>() {
+        return Some(spec_algorithms(&config.spec));
+    }
+    #[cfg(feature = "tpm")]
+    if let Some(config) = config.downcast_ref::<crate::tpm::TpmConfig>() {
+        return Some(RequestedAlgorithms {
+            asymmetric: Some(config.key_algorithm),
+            symmetric: Some(config.sym_algorithm),
+            hash: Some(config.hash),
+        });
+    }
+    #[cfg(feature = "macos")]
+    if let Some(config) = config.downcast_ref::<crate::tpm::macos::SecureEnclaveConfig>() {
+        return Some(RequestedAlgorithms {
+            asymmetric: config.asym_algorithm,
+            symmetric: None,
+            hash: config.hash,
+        });
+    }
+    #[cfg(feature = "android")]
+    if let Some(config) = config.downcast_ref::<crate::tpm::android::config::AndroidConfig>() {
+        use crate::tpm::android::config::EncryptionMode;
+        return Some(match config.mode {
+            EncryptionMode::ASym { algo, digest } => RequestedAlgorithms {
+                asymmetric: Some(algo),
+                symmetric: None,
+                hash: Some(digest),
+            },
+            EncryptionMode::Sym(cipher) => RequestedAlgorithms {
+                symmetric: Some(cipher),
+                ..Default::default()
+            },
+        });
+    }
+    #[cfg(feature = "nks")]
+    if let Some(config) = config.downcast_ref::<crate::nks::NksConfig>() {
+        return Some(RequestedAlgorithms {
+            asymmetric: config.key_algorithm,
+            symmetric: config.key_algorithm_sym,
+            hash: Some(config.hash),
+        });
+    }
+    #[cfg(feature = "test-utils")]
+    if let Some(config) = config.downcast_ref::<crate::mock::MockConfig>() {
+        return Some(RequestedAlgorithms {
+            asymmetric: Some(config.key_algorithm),
+            symmetric: None,
+            hash: Some(config.hash),
+        });
+    }
+    let _ = config;
+    None
+}
+
+fn spec_algorithms(spec: &KeySpec) -> RequestedAlgorithms {
+    RequestedAlgorithms {
+        asymmetric: spec.asymmetric_algorithm(),
+        symmetric: spec.symmetric_algorithm(),
+        hash: Some(spec.hash()),
+    }
+}
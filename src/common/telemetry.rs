@@ -0,0 +1,84 @@
+//! Conventions for the tracing spans of provider calls, and correlation ids of operations.
+//!
+//! Span fields are named after OpenTelemetry attributes, so that `tracing-opentelemetry` exports
+//! them unchanged:
+//!
+//! * `crypto.key_id_hash` - `latency::key_id_hash` of the key id. Key ids are never recorded.
+//! * `crypto.payload.size`, `crypto.signature.size`, `crypto.batch.size` - The length of the
+//!   input. Payloads, plaintexts, ciphertexts and signatures are never recorded.
+//! * `otel.kind`, `rpc.system`, `rpc.method` - Set on the `secure_enclave.call` span of every
+//!   call into the Swift bindings, which is a child of the span of the provider call.
+//! * `ffi.duration_us` - The time the Swift bindings took to answer, in microseconds. Replayed
+//!   calls report the recorded time.
+//! * `ffi.request.size`, `ffi.response.size`, `ffi.marshalling_us` - The length of the request
+//!   and response bytes of a live call into the Swift bindings, and the time in microseconds spent
+//!   encoding and decoding them.
+//! * `otel.status_code`, `otel.status_description` - `"ERROR"` and the error message if a call
+//!   into the Swift bindings failed.
+//! * `crypto.correlation_id` - The `CorrelationId` of the operation the call into the Swift
+//!   bindings belongs to. The bindings include it in their `os_log` messages, so that the log
+//!   lines of both languages for a single operation can be matched.
+//!
+//! Values that must not be recorded are wrapped in `crypto::redact::Redacted` where a `Debug`
+//! representation is needed.
+
+use std::{cell::Cell, fmt};
+
+thread_local! {
+    static CURRENT: Cell<Option<CorrelationId>> = const { Cell::new(None) };
+}
+
+/// A random identifier of a single provider operation, e.g. one `sign_data` call.
+///
+/// Displayed as 16 hex digits. Providers created by `SecModules::get_instance` assign an id to
+/// every call, which is then the current id on the calling thread until the call returns.
+/// Calls into the Swift bindings outside of such a call get an id of their own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CorrelationId(u64);
+
+impl CorrelationId {
+    /// Creates a random id.
+    pub fn new() -> Self {
+        Self(rand::random())
+    }
+
+    /// Returns the id of the operation running on the current thread, if any.
+    pub fn current() -> Option<Self> {
+        CURRENT.with(Cell::get)
+    }
+
+    /// Runs `f` as an operation with a correlation id.
+    ///
+    /// Nested calls keep the id of the outermost operation, so that an operation keeps its id
+    /// through all wrappers of a provider.
+    pub fn scope<T>(f: impl FnOnce(CorrelationId) -> T) -> T {
+        if let Some(id) = Self::current() {
+            return f(id);
+        }
+
+        /// Clears the current id when the operation returns or panics.
+        struct Reset;
+        impl Drop for Reset {
+            fn drop(&mut self) {
+                CURRENT.with(|current| current.set(None));
+            }
+        }
+
+        let id = Self::new();
+        CURRENT.with(|current| current.set(Some(id)));
+        let _reset = Reset;
+        f(id)
+    }
+}
+
+impl Default for CorrelationId {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Display for CorrelationId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
@@ -0,0 +1,41 @@
+use crate::common::{
+    crypto::secret::SecretBytes, error::SecurityModuleError, traits::key_handle::KeyHandle,
+};
+use futures::future::BoxFuture;
+
+/// Defines asynchronous variants of the key operations that may wait for the user.
+///
+/// Signing and decrypting with a key that requires user authentication, e.g. Touch ID, wait until
+/// the user confirms the prompt. Providers that can wait without blocking, like the Secure Enclave
+/// provider, implement these methods natively, so that async applications do not need to move the
+/// calls onto a blocking thread. Providers whose operations never wait for the user can keep the
+/// default implementations, which perform the synchronous operation when the future is polled.
+pub trait AsyncKeyHandle: KeyHandle {
+    /// Signs the given data using the cryptographic key, see `KeyHandle::sign_data`.
+    ///
+    /// # Arguments
+    /// * `data` - A byte slice representing the data to be signed.
+    ///
+    /// # Returns
+    /// A future resolving to the signature as a `Vec<u8>` on success, or a `SecurityModuleError` on failure.
+    fn sign_data_async<'a>(
+        &'a self,
+        data: &'a [u8],
+    ) -> BoxFuture<'a, Result<Vec<u8>, SecurityModuleError>> {
+        Box::pin(async move { self.sign_data(data) })
+    }
+
+    /// Decrypts the given encrypted data using the cryptographic key, see `KeyHandle::decrypt_data`.
+    ///
+    /// # Arguments
+    /// * `encrypted_data` - A byte slice representing the data to be decrypted.
+    ///
+    /// # Returns
+    /// A future resolving to the decrypted data, which is zeroed when dropped, on success, or a `SecurityModuleError` on failure.
+    fn decrypt_data_async<'a>(
+        &'a self,
+        encrypted_data: &'a [u8],
+    ) -> BoxFuture<'a, Result<SecretBytes, SecurityModuleError>> {
+        Box::pin(async move { self.decrypt_data(encrypted_data) })
+    }
+}
@@ -1,4 +1,7 @@
-use crate::common::error::SecurityModuleError;
+use crate::common::{
+    crypto::{operation_context::OperationContext, secret::SecretBytes, signing_context},
+    error::SecurityModuleError,
+};
 use std::fmt::Debug;
 #[cfg(feature = "linux")]
 use tss_esapi::handles::KeyHandle as TssKeyHandle;
@@ -50,9 +53,9 @@ pub trait KeyHandle: Send + Sync + Debug {
     /// * `encrypted_data` - A byte slice representing the data to be decrypted.
     ///
     /// # Returns
-    /// A `Result` containing the decrypted data as a `Vec<u8>` on success, or a `SecurityModuleError` on failure.
+    /// A `Result` containing the decrypted data, which is zeroed when dropped, on success, or a `SecurityModuleError` on failure.
     #[tracing::instrument]
-    fn decrypt_data(&self, _encrypted_data: &[u8]) -> Result<Vec<u8>, SecurityModuleError> {
+    fn decrypt_data(&self, _encrypted_data: &[u8]) -> Result<SecretBytes, SecurityModuleError> {
         Err(SecurityModuleError::InitializationError(
             "Method not implemented".to_owned(),
         ))
@@ -89,4 +92,121 @@ pub trait KeyHandle: Send + Sync + Debug {
             "Method not implemented".to_owned(),
         ))
     }
+    /// Signs the given data, writing the signature into the output buffer of `context`.
+    ///
+    /// The default implementation copies the result of `sign_data` into the context. Implementors
+    /// can override this method to avoid the intermediate allocation.
+    ///
+    /// # Arguments
+    /// * `data` - A byte slice representing the data to be signed.
+    /// * `context` - The `OperationContext` whose buffers are reused.
+    ///
+    /// # Returns
+    /// A `Result` containing the signature, borrowed from `context`, on success, or a `SecurityModuleError` on failure.
+    #[tracing::instrument(skip(context))]
+    fn sign_data_into<'a>(
+        &self,
+        data: &[u8],
+        context: &'a mut OperationContext,
+    ) -> Result<&'a [u8], SecurityModuleError> {
+        let signature = self.sign_data(data)?;
+        Ok(context.set_output(&signature))
+    }
+    /// Verifies the signature of the given data, using the buffers of `context` as scratch space.
+    ///
+    /// The default implementation calls `verify_signature`. Implementors that need to decode or
+    /// convert the signature before verifying it can override this method to reuse the buffers.
+    ///
+    /// # Arguments
+    /// * `data` - A byte slice representing the data whose signature is to be verified.
+    /// * `signature` - A byte slice representing the signature to be verified against the data.
+    /// * `context` - The `OperationContext` whose buffers are reused.
+    ///
+    /// # Returns
+    /// A `Result` containing a boolean indicating whether the signature is valid (`true`) or not (`false`),
+    /// or a `SecurityModuleError` on failure.
+    #[tracing::instrument(skip(_context))]
+    fn verify_signature_with(
+        &self,
+        data: &[u8],
+        signature: &[u8],
+        _context: &mut OperationContext,
+    ) -> Result<bool, SecurityModuleError> {
+        self.verify_signature(data, signature)
+    }
+    /// Signs the given data in the context `label`, so that the signature is only accepted by
+    /// `verify_in_context` with the same label, see `crypto::signing_context`.
+    ///
+    /// Prefer this over `sign_data` whenever a key signs for more than one purpose, e.g. with the
+    /// name of the feature and protocol version as label, so that a signature created for one
+    /// feature cannot be replayed to another.
+    ///
+    /// # Arguments
+    /// * `label` - The context, e.g. `com.example.mail/login/v1`.
+    /// * `data` - A byte slice representing the data to be signed.
+    ///
+    /// # Returns
+    /// A `Result` containing the signature as a `Vec<u8>` on success, a `SecurityModuleError::Encoding` if the label is invalid, or a `SecurityModuleError` on failure.
+    #[tracing::instrument(skip(data))]
+    fn sign_in_context(&self, label: &str, data: &[u8]) -> Result<Vec<u8>, SecurityModuleError> {
+        self.sign_data(&signing_context::encode(label, data)?)
+    }
+    /// Verifies a signature of `sign_in_context` created in the context `label`.
+    ///
+    /// # Arguments
+    /// * `label` - The context the signature must have been created in.
+    /// * `data` - A byte slice representing the data whose signature is to be verified.
+    /// * `signature` - A byte slice representing the signature to be verified against the data.
+    ///
+    /// # Returns
+    /// A `Result` containing a boolean indicating whether the signature is valid for the data in this context,
+    /// a `SecurityModuleError::Encoding` if the label is invalid, or a `SecurityModuleError` on failure.
+    #[tracing::instrument(skip(data, signature))]
+    fn verify_in_context(
+        &self,
+        label: &str,
+        data: &[u8],
+        signature: &[u8],
+    ) -> Result<bool, SecurityModuleError> {
+        self.verify_signature(&signing_context::encode(label, data)?, signature)
+    }
+    /// Verifies a batch of signatures using the cryptographic key.
+    ///
+    /// The default implementation calls `verify_signature` for every item. Implementors that
+    /// have access to the public key can override this method to verify the batch in parallel
+    /// without involving the security module.
+    ///
+    /// # Arguments
+    /// * `items` - Pairs of data and the signature to be verified against it.
+    ///
+    /// # Returns
+    /// A `Result` containing one boolean per item, in the same order as `items`, indicating whether
+    /// the respective signature is valid, or a `SecurityModuleError` on failure.
+    #[tracing::instrument(skip(items), fields(count = items.len()))]
+    fn verify_many(&self, items: &[(&[u8], &[u8])]) -> Result<Vec<bool>, SecurityModuleError> {
+        items
+            .iter()
+            .map(|(data, signature)| self.verify_signature(data, signature))
+            .collect()
+    }
+    /// Derives a shared secret from the private key and the public key of another party with
+    /// ECDH, e.g. to decrypt a message of `common::ecies`.
+    ///
+    /// The raw shared secret must not be used as a key directly, derive keys from it with a KDF.
+    ///
+    /// # Arguments
+    /// * `peer_public_key` - The public key of the other party as an uncompressed SEC1 point,
+    ///   on the curve of the key, or as the raw 32 bytes for X25519 keys.
+    ///
+    /// # Returns
+    /// A `Result` containing the x-coordinate of the shared point, which is zeroed when dropped, on success, or a `SecurityModuleError` on failure.
+    #[tracing::instrument(skip_all)]
+    fn derive_shared_secret(
+        &self,
+        _peer_public_key: &[u8],
+    ) -> Result<SecretBytes, SecurityModuleError> {
+        Err(SecurityModuleError::InitializationError(
+            "Method not implemented".to_owned(),
+        ))
+    }
 }
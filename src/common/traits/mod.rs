@@ -1,3 +1,4 @@
+pub mod async_key_handle;
 pub mod key_handle;
 pub mod module_provider;
 pub mod module_provider_config;
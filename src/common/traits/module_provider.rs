@@ -1,5 +1,10 @@
 use super::key_handle::KeyHandle;
-use crate::common::error::SecurityModuleError;
+use crate::common::{
+    crypto::{key_metadata::KeyMetadata, key_spec::KeySpec, secret::SecretBytes},
+    error::SecurityModuleError,
+    latency::LatencySnapshot,
+    session_pool::SessionPoolMetrics,
+};
 use std::{any::Any, fmt::Debug};
 
 /// Defines the interface for a security module provider.
@@ -45,6 +50,54 @@ pub trait Provider: Send + Sync + KeyHandle + Debug {
     /// On failure, it returns a `SecurityModuleError`.
     fn load_key(&mut self, key_id: &str, config: Box<dyn Any>) -> Result<(), SecurityModuleError>;
 
+    /// Imports a key that was generated outside the security module and wrapped under a key the
+    /// module holds, and makes it the current key like `create_key` does.
+    ///
+    /// The format of `wrapped_key` depends on the security module, see `key_import`.
+    ///
+    /// # Arguments
+    ///
+    /// * `key_id` - A string slice that uniquely identifies the imported key.
+    /// * `wrapped_key` - The wrapped key in the import format of the security module.
+    /// * `wrapping_key_id` - The id of the key of the security module `wrapped_key` is wrapped under.
+    /// * `spec` - The algorithm and usages of the imported key.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` that, on success, contains `Ok(())`, indicating that the key was imported
+    /// successfully. On failure, it returns a `SecurityModuleError`, which is
+    /// `SecurityModuleError::UnsupportedOperation` if the security module cannot import keys.
+    #[tracing::instrument(skip_all)]
+    fn import_wrapped_key(
+        &mut self,
+        _key_id: &str,
+        _wrapped_key: &[u8],
+        _wrapping_key_id: &str,
+        _spec: &KeySpec,
+    ) -> Result<(), SecurityModuleError> {
+        Err(SecurityModuleError::UnsupportedOperation(
+            "The security module does not support importing wrapped keys".to_owned(),
+        ))
+    }
+
+    /// Exports the private key of the loaded key as PKCS#8 DER.
+    ///
+    /// Keys are only exported if they were created from a `KeySpec` marked exportable, see
+    /// `KeySpec::exportable`. Security modules that keep keys in hardware never export them.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` that, on success, contains the PKCS#8 DER encoded private key, which is zeroed
+    /// when dropped. On failure, it returns a `SecurityModuleError`, which is
+    /// `SecurityModuleError::UnsupportedOperation` if the security module does not export keys
+    /// or the key is not exportable.
+    #[tracing::instrument(skip_all)]
+    fn export_private_key(&self) -> Result<SecretBytes, SecurityModuleError> {
+        Err(SecurityModuleError::UnsupportedOperation(
+            "The security module does not export private keys".to_owned(),
+        ))
+    }
+
     /// Initializes the security module and returns a handle for further operations.
     ///
     /// This method should be called before performing any other operations with the security module.
@@ -55,4 +108,104 @@ pub trait Provider: Send + Sync + KeyHandle + Debug {
     /// A `Result` that, on success, contains `Ok(())`, indicating that the module was initialized successfully.
     /// On failure, it returns a `SecurityModuleError`.
     fn initialize_module(&mut self) -> Result<(), SecurityModuleError>;
+
+    /// Returns the utilization of the session pool of the security module.
+    ///
+    /// # Returns
+    ///
+    /// The current `SessionPoolMetrics`, or `None` if the provider does not pool sessions.
+    fn session_pool_metrics(&self) -> Option<SessionPoolMetrics> {
+        None
+    }
+
+    /// Returns the latencies of the calls made to the provider.
+    ///
+    /// Providers created through `SecModules::get_instance` are wrapped in a `TimedProvider`,
+    /// which records the latency of every call.
+    ///
+    /// # Returns
+    ///
+    /// A `LatencySnapshot` of all recorded calls, or `None` if latencies are not recorded.
+    fn latency_snapshot(&self) -> Option<LatencySnapshot> {
+        None
+    }
+
+    /// Returns the metadata of the current key, such as its DER encoded public key and
+    /// attestation data.
+    ///
+    /// Providers compute the metadata when a key is created or loaded, so this method is served
+    /// from memory and does not access the security module.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the cached `KeyMetadata` on success, or a `SecurityModuleError` if no
+    /// key has been created or loaded or the provider does not support key metadata.
+    #[tracing::instrument(skip_all)]
+    fn key_metadata(&self) -> Result<KeyMetadata, SecurityModuleError> {
+        Err(SecurityModuleError::InitializationError(
+            "Method not implemented".to_owned(),
+        ))
+    }
+
+    /// Fetches the metadata of the current key from the security module and replaces the cached
+    /// metadata with it.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the fetched `KeyMetadata` on success, or a `SecurityModuleError` on failure.
+    /// On failure, the previously cached metadata is kept.
+    #[tracing::instrument(skip_all)]
+    fn refresh_key_metadata(&mut self) -> Result<KeyMetadata, SecurityModuleError> {
+        Err(SecurityModuleError::InitializationError(
+            "Method not implemented".to_owned(),
+        ))
+    }
+    /// Returns the ids of the keys the security module holds, in a stable order.
+    ///
+    /// A `NamespacedProvider` only returns the keys of its namespace.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the key ids on success, or a `SecurityModuleError` if the
+    /// provider cannot enumerate its keys.
+    #[tracing::instrument(skip_all)]
+    fn list_keys(&self) -> Result<Vec<String>, SecurityModuleError> {
+        Err(SecurityModuleError::InitializationError(
+            "Method not implemented".to_owned(),
+        ))
+    }
+
+    /// Deletes the key identified by `key_id` from the security module. If it is the loaded key,
+    /// the provider forgets it.
+    ///
+    /// # Arguments
+    ///
+    /// * `key_id` - A string slice that identifies the key to be deleted.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` that is `Ok(())` on success. On failure, it returns a `SecurityModuleError`,
+    /// which is `SecurityModuleError::KeyError` if the key does not exist, or
+    /// `SecurityModuleError::UnsupportedOperation` if the security module cannot delete keys.
+    #[tracing::instrument(skip_all)]
+    fn delete_key(&mut self, _key_id: &str) -> Result<(), SecurityModuleError> {
+        Err(SecurityModuleError::UnsupportedOperation(
+            "The security module does not delete keys".to_owned(),
+        ))
+    }
+
+    /// Releases the resources the provider holds in the security module and forgets the loaded
+    /// key. The provider has to be initialized again before further operations.
+    ///
+    /// Calling it on a provider that is not initialized, or calling it repeatedly, does nothing.
+    /// Providers that hold no resources do nothing.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` that is `Ok(())` on success, or a `SecurityModuleError` if the security module
+    /// failed to release its resources. The provider is shut down either way.
+    #[tracing::instrument(skip_all)]
+    fn shutdown(&mut self) -> Result<(), SecurityModuleError> {
+        Ok(())
+    }
 }
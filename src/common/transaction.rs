@@ -0,0 +1,206 @@
+//! Multi-step operations that are rolled back if a step fails.
+//!
+//! Rotating a key takes several calls to the security module, any of which can fail, e.g.
+//! creating the new key succeeds but endorsing it with the previous key does not. Stopping there
+//! would leave the new key behind, and the next start would pick it up without an endorsement. A
+//! `Transaction` remembers how to undo every step that succeeded and undoes them in reverse order
+//! if a later step fails:
+//!
+//! ```rust,ignore
+//! use crypto_layer::common::transaction;
+//!
+//! transaction::run("rotate", &mut *provider, |transaction| {
+//!     transaction.create_key("records.2", config())?; // deleted again if a later step fails
+//!     let metadata = transaction.target().key_metadata()?;
+//!     register_key(&metadata)?;
+//!     Ok(metadata)
+//! })?;
+//! ```
+//!
+//! A transaction that is dropped without being committed, e.g. while unwinding from a panic, is
+//! rolled back as well. Undo steps that fail are logged and the remaining ones still run, so a
+//! rollback gets as close to the initial state as it can. Transactions only protect against
+//! failed steps: undo steps are not persisted, so a crash in the middle of an operation still
+//! leaves the steps done so far behind.
+//!
+//! `DeviceIdentity::renew` and the `key rotate` command of the CLI run their rotations as
+//! transactions. Imports, e.g. of `migration`, are a single call to the security module and need
+//! none.
+
+use crate::common::{error::SecurityModuleError, traits::module_provider::Provider};
+use std::{any::Any, fmt};
+
+/// Undoes a step, see `Transaction::step`.
+type UndoFn<'a, C> = Box<dyn FnOnce(&mut C) -> Result<(), SecurityModuleError> + 'a>;
+
+struct UndoStep<'a, C: ?Sized> {
+    description: String,
+    undo: UndoFn<'a, C>,
+}
+
+/// A multi-step operation on `target`, see the module documentation.
+pub struct Transaction<'a, C: ?Sized> {
+    name: String,
+    target: &'a mut C,
+    undo_steps: Vec<UndoStep<'a, C>>,
+    committed: bool,
+}
+
+impl<'a, C: ?Sized> Transaction<'a, C> {
+    /// Begins a transaction on `target`, e.g. a provider.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Names the operation in the log messages of a rollback.
+    /// * `target` - The object the steps operate on.
+    pub fn begin(name: &str, target: &'a mut C) -> Self {
+        Self {
+            name: name.to_owned(),
+            target,
+            undo_steps: Vec::new(),
+            committed: false,
+        }
+    }
+
+    /// Returns the target for steps that need not be undone, e.g. reading metadata or signing.
+    pub fn target(&mut self) -> &mut C {
+        self.target
+    }
+
+    /// Performs a step and remembers how to undo it.
+    ///
+    /// # Arguments
+    ///
+    /// * `description` - Describes the step in the log messages of a rollback.
+    /// * `action` - Performs the step.
+    /// * `undo` - Undoes the step on rollback. It is only kept if `action` succeeded.
+    ///
+    /// # Returns
+    ///
+    /// The result of `action`. The transaction is not rolled back by a failed step, the caller
+    /// returns the error and `run` or dropping the transaction rolls it back.
+    pub fn step<T>(
+        &mut self,
+        description: &str,
+        action: impl FnOnce(&mut C) -> Result<T, SecurityModuleError>,
+        undo: impl FnOnce(&mut C) -> Result<(), SecurityModuleError> + 'a,
+    ) -> Result<T, SecurityModuleError> {
+        let value = action(self.target)?;
+        self.on_rollback(description, undo);
+        Ok(value)
+    }
+
+    /// Remembers to run `undo` on rollback, e.g. to restore state changed before the transaction
+    /// began.
+    pub fn on_rollback(
+        &mut self,
+        description: &str,
+        undo: impl FnOnce(&mut C) -> Result<(), SecurityModuleError> + 'a,
+    ) {
+        self.undo_steps.push(UndoStep {
+            description: description.to_owned(),
+            undo: Box::new(undo),
+        });
+    }
+
+    /// Ends the transaction, keeping all steps.
+    pub fn commit(mut self) {
+        self.committed = true;
+    }
+
+    /// Undoes all steps in reverse order.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` that is `Ok(())` if every step was undone, or the error of the first undo step
+    /// that failed. The remaining steps are undone either way.
+    pub fn rollback(mut self) -> Result<(), SecurityModuleError> {
+        self.undo_all()
+    }
+
+    fn undo_all(&mut self) -> Result<(), SecurityModuleError> {
+        let mut result = Ok(());
+        while let Some(step) = self.undo_steps.pop() {
+            if let Err(e) = (step.undo)(self.target) {
+                tracing::error!(
+                    transaction = %self.name,
+                    step = %step.description,
+                    error = %e,
+                    "Failed to undo a step of a transaction"
+                );
+                if result.is_ok() {
+                    result = Err(e);
+                }
+            }
+        }
+        result
+    }
+}
+
+impl<C: Provider + ?Sized> Transaction<'_, C> {
+    /// Creates a key, which is deleted again on rollback.
+    pub fn create_key(
+        &mut self,
+        key_id: &str,
+        config: Box<dyn Any>,
+    ) -> Result<(), SecurityModuleError> {
+        let created = key_id.to_owned();
+        self.step(
+            &format!("create key '{}'", key_id),
+            |target| target.create_key(key_id, config),
+            move |target| target.delete_key(&created),
+        )
+    }
+}
+
+impl<C: ?Sized> Drop for Transaction<'_, C> {
+    fn drop(&mut self) {
+        if !self.committed {
+            // Failures are logged by `undo_all`.
+            let _ = self.undo_all();
+        }
+    }
+}
+
+impl<C: ?Sized> fmt::Debug for Transaction<'_, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Transaction")
+            .field("name", &self.name)
+            .field(
+                "steps",
+                &self
+                    .undo_steps
+                    .iter()
+                    .map(|step| step.description.as_str())
+                    .collect::<Vec<_>>(),
+            )
+            .field("committed", &self.committed)
+            .finish()
+    }
+}
+
+/// Runs `f` in a transaction on `target`, which is committed if `f` succeeds and rolled back if
+/// it fails.
+///
+/// # Returns
+///
+/// The result of `f`. If `f` failed, its error is returned even if undoing a step failed as
+/// well, since it names the cause. Failed undo steps are logged.
+pub fn run<'a, C: ?Sized, T>(
+    name: &str,
+    target: &'a mut C,
+    f: impl FnOnce(&mut Transaction<'a, C>) -> Result<T, SecurityModuleError>,
+) -> Result<T, SecurityModuleError> {
+    let mut transaction = Transaction::begin(name, target);
+    match f(&mut transaction) {
+        Ok(value) => {
+            transaction.commit();
+            Ok(value)
+        }
+        Err(e) => {
+            // Failures are logged by `undo_all`.
+            let _ = transaction.rollback();
+            Err(e)
+        }
+    }
+}
@@ -0,0 +1,145 @@
+//! Uniform responses to failed decryptions and verifications.
+//!
+//! A service that decrypts ciphertexts of its clients and reports why a decryption failed, or
+//! fails faster for some ciphertexts than for others, is a decryption oracle: a client can tell
+//! a malformed envelope from a wrong tag and, for RSA, a bad padding from a good one, and learn
+//! the plaintext of a ciphertext by submitting modified copies of it. `decryption` therefore
+//! reports every failure that depends on the ciphertext as the same
+//! `SecurityModuleError::DecryptionError`, and delays it to the slowest failure of the same
+//! operation seen so far:
+//!
+//! ```rust,ignore
+//! use crypto_layer::common::uniform_errors::{self, FailureTiming};
+//!
+//! static TIMING: FailureTiming = FailureTiming::new();
+//!
+//! let plaintext = uniform_errors::decryption(&TIMING, || parse_and_decrypt(ciphertext))?;
+//! ```
+//!
+//! The original error is logged at debug level. Errors that do not depend on the content of the
+//! ciphertext pass through unchanged, e.g. a missing key, a locked security module or a
+//! ciphertext exceeding the `InputLimits`, as does `SecurityModuleError::RollbackDetected`, which
+//! is only reported for authentic data. `verification` likewise reports malformed signatures as
+//! invalid instead of failing.
+//!
+//! The `ValidatedProvider` of every instance created by `SecModules` applies this to
+//! `decrypt_data` and `verify_signature`, and `ecies`, `vault`, `key_wrapping` and
+//! `anti_rollback` to the ciphertexts they open.
+
+use crate::common::error::SecurityModuleError;
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// The message of the error every failed decryption is reported with.
+pub const DECRYPTION_FAILED: &str = "Decryption failed";
+
+/// The longest a failure is delayed to, so that a single slow call, e.g. while a remote security
+/// module reconnects, does not slow down every later failure.
+pub const MAX_FAILURE_DELAY: Duration = Duration::from_millis(100);
+
+/// The slowest failure of an operation, which later failures are delayed to.
+#[derive(Debug, Default)]
+pub struct FailureTiming {
+    slowest_nanos: AtomicU64,
+}
+
+impl FailureTiming {
+    /// Creates a timing without any failure yet.
+    pub const fn new() -> Self {
+        Self {
+            slowest_nanos: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns the duration of the slowest failure so far, at most `MAX_FAILURE_DELAY`.
+    pub fn slowest(&self) -> Duration {
+        Duration::from_nanos(self.slowest_nanos.load(Ordering::Relaxed))
+    }
+
+    /// Records a failure that started at `start` and sleeps until it took as long as the slowest
+    /// failure so far.
+    fn pad(&self, start: Instant) {
+        let elapsed = start.elapsed().min(MAX_FAILURE_DELAY);
+        let nanos = elapsed.as_nanos() as u64;
+        let slowest = Duration::from_nanos(
+            self.slowest_nanos
+                .fetch_max(nanos, Ordering::Relaxed)
+                .max(nanos),
+        );
+        if let Some(remaining) = slowest.checked_sub(start.elapsed()) {
+            thread::sleep(remaining);
+        }
+    }
+}
+
+/// Runs the decryption `op` and reports every failure that depends on the ciphertext as a
+/// `SecurityModuleError::DecryptionError` with the message `DECRYPTION_FAILED`, delayed by
+/// `timing`.
+///
+/// # Returns
+///
+/// A `Result` containing the result of `op` on success, the uniform error if it failed because of
+/// the ciphertext, or its error otherwise.
+pub fn decryption<T>(
+    timing: &FailureTiming,
+    op: impl FnOnce() -> Result<T, SecurityModuleError>,
+) -> Result<T, SecurityModuleError> {
+    let start = Instant::now();
+    let error = match op() {
+        Ok(value) => return Ok(value),
+        Err(error) if passes_through(&error) => return Err(error),
+        Err(error) => error,
+    };
+    tracing::debug!(error = %error, "decryption failed");
+    timing.pad(start);
+    Err(SecurityModuleError::DecryptionError(
+        DECRYPTION_FAILED.to_owned(),
+    ))
+}
+
+/// Runs the verification `op` and reports a signature it fails to parse as invalid.
+///
+/// # Returns
+///
+/// A `Result` containing the result of `op`, `false` if it failed because of the signature, or
+/// its error otherwise.
+pub fn verification(
+    op: impl FnOnce() -> Result<bool, SecurityModuleError>,
+) -> Result<bool, SecurityModuleError> {
+    match op() {
+        Err(
+            error @ (SecurityModuleError::SignatureVerificationError(_)
+            | SecurityModuleError::InvalidSignature
+            | SecurityModuleError::VerificationFailed
+            | SecurityModuleError::Encoding(_)),
+        ) => {
+            tracing::debug!(error = %error, "verification failed");
+            Ok(false)
+        }
+        result => result,
+    }
+}
+
+/// Returns whether `error` is independent of the content of the ciphertext.
+///
+/// `SecurityModuleError::UnsupportedAlgorithm` does not pass through, since envelopes name the
+/// algorithm they were encrypted with.
+fn passes_through(error: &SecurityModuleError) -> bool {
+    error.is_authentication_failure()
+        || matches!(
+            error,
+            SecurityModuleError::InitializationError(_)
+                | SecurityModuleError::KeyError
+                | SecurityModuleError::SessionPoolTimeout
+                | SecurityModuleError::InvalidKeyId(_)
+                | SecurityModuleError::DeprecatedAlgorithm(_)
+                | SecurityModuleError::SecretStorage(_)
+                | SecurityModuleError::UnsupportedOperation(_)
+                | SecurityModuleError::InputTooLarge(_)
+                | SecurityModuleError::RollbackDetected(_)
+                | SecurityModuleError::NotFipsApproved(_)
+        )
+}
@@ -0,0 +1,383 @@
+//! Small secrets stored encrypted under a key of the security module.
+//!
+//! A `SecretsVault` keeps secrets like API tokens, passwords or database credentials of an
+//! application. It envelope-encrypts every secret under a device-bound key, e.g. a key of the
+//! Secure Enclave or the TPM, and hands only the ciphertext to a `SecretStorage`:
+//!
+//! ```rust,ignore
+//! use crypto_layer::common::vault::{FileStorage, SecretsVault};
+//!
+//! let vault = SecretsVault::new(provider, "vault_key", FileStorage::new("/var/lib/app/secrets")?);
+//! vault.put_secret("db_password", b"hunter2")?;
+//! assert_eq!(vault.get_secret("db_password")?.unwrap(), b"hunter2");
+//! ```
+//!
+//! Each secret is encrypted with a fresh random data key. The data key is encrypted with
+//! `encrypt_data` of the provider and stored as the wrapped key of the envelope, see
+//! `crypto::envelope`. The payload key is derived from the data key with HKDF, using the name of
+//! the secret as info, so a ciphertext copied to another name cannot be decrypted. The storage
+//! can therefore be untrusted, e.g. a file on disk or a cloud key-value store: reading a secret
+//! always requires the key of the security module.
+
+use crate::common::{
+    crypto::{
+        aead,
+        envelope::{AeadAlgorithm, Envelope},
+        kdf::Kdf,
+        secret::SecretBytes,
+    },
+    error::SecurityModuleError,
+    input_limits,
+    traits::module_provider::Provider,
+    uniform_errors::{self, FailureTiming},
+};
+use openssl::rand::rand_bytes;
+use std::{
+    collections::BTreeMap,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+/// The maximum length of the name of a secret in bytes, which keeps the file names of
+/// `FileStorage` within the 255 bytes most file systems allow.
+pub const MAX_SECRET_NAME_LEN: usize = 120;
+
+/// The maximum length of a secret in bytes. The vault is meant for small secrets, larger data
+/// should be encrypted with `crypto::aead` directly.
+pub const MAX_SECRET_LEN: usize = 64 * 1024;
+
+/// The algorithm secrets are encrypted with.
+const AEAD: AeadAlgorithm = AeadAlgorithm::Aes256Gcm;
+
+/// The length of the random salt of the payload key derivation in bytes.
+const SALT_LEN: usize = 16;
+
+/// Prefixes the name of a secret in the info of the payload key derivation.
+const KDF_INFO_PREFIX: &[u8] = b"crypto-layer/vault/";
+
+/// Stores the encrypted secrets of a `SecretsVault`.
+///
+/// The storage only ever sees encoded envelopes, it does not need to protect their
+/// confidentiality or integrity. Names are checked by the vault before they are passed on.
+pub trait SecretStorage: Send + Sync {
+    /// Returns the ciphertext stored under `name`, or `None` if there is none.
+    fn read(&self, name: &str) -> Result<Option<Vec<u8>>, SecurityModuleError>;
+
+    /// Stores `ciphertext` under `name`, replacing any previous ciphertext.
+    fn write(&self, name: &str, ciphertext: &[u8]) -> Result<(), SecurityModuleError>;
+
+    /// Removes the ciphertext stored under `name` and returns whether there was one.
+    fn delete(&self, name: &str) -> Result<bool, SecurityModuleError>;
+
+    /// Returns the names of all stored ciphertexts in sorted order.
+    fn names(&self) -> Result<Vec<String>, SecurityModuleError>;
+}
+
+/// A `SecretStorage` keeping the ciphertexts in memory, e.g. for tests or secrets that only live
+/// as long as the process.
+#[derive(Debug, Default)]
+pub struct MemoryStorage {
+    secrets: Mutex<BTreeMap<String, Vec<u8>>>,
+}
+
+impl MemoryStorage {
+    /// Creates an empty storage.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn secrets(&self) -> MutexGuard<'_, BTreeMap<String, Vec<u8>>> {
+        self.secrets.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl SecretStorage for MemoryStorage {
+    fn read(&self, name: &str) -> Result<Option<Vec<u8>>, SecurityModuleError> {
+        Ok(self.secrets().get(name).cloned())
+    }
+
+    fn write(&self, name: &str, ciphertext: &[u8]) -> Result<(), SecurityModuleError> {
+        self.secrets().insert(name.to_owned(), ciphertext.to_vec());
+        Ok(())
+    }
+
+    fn delete(&self, name: &str) -> Result<bool, SecurityModuleError> {
+        Ok(self.secrets().remove(name).is_some())
+    }
+
+    fn names(&self) -> Result<Vec<String>, SecurityModuleError> {
+        Ok(self.secrets().keys().cloned().collect())
+    }
+}
+
+/// A `SecretStorage` keeping every ciphertext in a file of a directory.
+///
+/// The file name is the hex encoded name of the secret with the extension `.secret`, so names
+/// cannot escape the directory. Ciphertexts are written to a temporary file first and then
+/// renamed, so a crash never leaves a partially written secret behind.
+#[derive(Debug, Clone)]
+pub struct FileStorage {
+    dir: PathBuf,
+}
+
+impl FileStorage {
+    /// The extension of the files holding ciphertexts.
+    const EXTENSION: &'static str = "secret";
+
+    /// Creates a storage in `dir`, creating the directory if it does not exist.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `FileStorage` on success, or a
+    /// `SecurityModuleError::SecretStorage` if the directory cannot be created.
+    pub fn new(dir: impl Into<PathBuf>) -> Result<Self, SecurityModuleError> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir).map_err(|e| storage_error("create", &dir, e))?;
+        Ok(Self { dir })
+    }
+
+    /// Returns the directory holding the ciphertexts.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.dir
+            .join(encode_file_name(name))
+            .with_extension(Self::EXTENSION)
+    }
+}
+
+impl SecretStorage for FileStorage {
+    fn read(&self, name: &str) -> Result<Option<Vec<u8>>, SecurityModuleError> {
+        let path = self.path(name);
+        match fs::read(&path) {
+            Ok(ciphertext) => Ok(Some(ciphertext)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(storage_error("read", &path, e)),
+        }
+    }
+
+    fn write(&self, name: &str, ciphertext: &[u8]) -> Result<(), SecurityModuleError> {
+        let path = self.path(name);
+        let temporary = path.with_extension("tmp");
+        fs::write(&temporary, ciphertext).map_err(|e| storage_error("write", &temporary, e))?;
+        fs::rename(&temporary, &path).map_err(|e| storage_error("write", &path, e))
+    }
+
+    fn delete(&self, name: &str) -> Result<bool, SecurityModuleError> {
+        let path = self.path(name);
+        match fs::remove_file(&path) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(storage_error("delete", &path, e)),
+        }
+    }
+
+    fn names(&self) -> Result<Vec<String>, SecurityModuleError> {
+        let entries = fs::read_dir(&self.dir).map_err(|e| storage_error("list", &self.dir, e))?;
+        let mut names = Vec::new();
+        for entry in entries {
+            let path = entry
+                .map_err(|e| storage_error("list", &self.dir, e))?
+                .path();
+            if path.extension().and_then(|e| e.to_str()) != Some(Self::EXTENSION) {
+                continue;
+            }
+            // Files that do not hold a secret of the vault are skipped.
+            if let Some(name) = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(decode_file_name)
+                .and_then(|name| String::from_utf8(name).ok())
+            {
+                names.push(name);
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+}
+
+pub(crate) fn encode_file_name(name: &str) -> String {
+    name.bytes().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+pub(crate) fn decode_file_name(stem: &str) -> Option<Vec<u8>> {
+    if !stem.len().is_multiple_of(2) || !stem.is_ascii() {
+        return None;
+    }
+    (0..stem.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&stem[i..i + 2], 16).ok())
+        .collect()
+}
+
+fn storage_error(action: &str, path: &Path, e: io::Error) -> SecurityModuleError {
+    SecurityModuleError::SecretStorage(format!("Cannot {} '{}': {}", action, path.display(), e))
+}
+
+/// Stores small secrets envelope-encrypted under a key of the security module.
+pub struct SecretsVault {
+    provider: Arc<Mutex<dyn Provider>>,
+    key_id: String,
+    storage: Box<dyn SecretStorage>,
+    decryption_timing: FailureTiming,
+}
+
+impl SecretsVault {
+    /// Creates a vault.
+    ///
+    /// # Arguments
+    ///
+    /// * `provider` - The provider encrypting and decrypting the data keys. The key `key_id` must
+    ///   be created or loaded and support `encrypt_data` and `decrypt_data`, e.g. an RSA key or a
+    ///   P-256 key of the Secure Enclave.
+    /// * `key_id` - The id of the key, which is recorded in every envelope.
+    /// * `storage` - Stores the encrypted secrets.
+    pub fn new(
+        provider: Arc<Mutex<dyn Provider>>,
+        key_id: impl Into<String>,
+        storage: impl SecretStorage + 'static,
+    ) -> Self {
+        Self {
+            provider,
+            key_id: key_id.into(),
+            storage: Box::new(storage),
+            decryption_timing: FailureTiming::new(),
+        }
+    }
+
+    /// Returns the id of the key the secrets are encrypted under.
+    pub fn key_id(&self) -> &str {
+        &self.key_id
+    }
+
+    /// Encrypts `secret` and stores it under `name`, replacing any previous secret of that name.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` that is `Ok` on success, or a `SecurityModuleError::SecretStorage` if the name or
+    /// secret is invalid or the storage fails, or the error of the provider if the data key cannot
+    /// be encrypted.
+    #[tracing::instrument(skip(self, secret), fields(crypto.payload.size = secret.len()))]
+    pub fn put_secret(&self, name: &str, secret: &[u8]) -> Result<(), SecurityModuleError> {
+        check_name(name)?;
+        if secret.len() > MAX_SECRET_LEN {
+            return Err(SecurityModuleError::SecretStorage(format!(
+                "The secret '{}' is {} bytes long, at most {} bytes are supported",
+                name,
+                secret.len(),
+                MAX_SECRET_LEN
+            )));
+        }
+
+        let mut data_key = SecretBytes::zeroed(AEAD.key_len());
+        rand_bytes(&mut data_key)
+            .map_err(|e| SecurityModuleError::EncryptionError(e.to_string()))?;
+        let salt = random_bytes(SALT_LEN)?;
+        let wrapped_key = self.provider().encrypt_data(&data_key)?;
+        let payload_key =
+            Kdf::HkdfSha256.derive_vec(&data_key, Some(&salt), &kdf_info(name), AEAD.key_len())?;
+
+        let mut envelope = Envelope::new(AEAD, self.key_id.as_str(), aead::random_nonce(AEAD)?);
+        envelope.wrapped_key = Some(wrapped_key);
+        envelope.kdf = Some(Kdf::HkdfSha256);
+        envelope.salt = Some(salt);
+        let ciphertext = aead::seal(envelope, &payload_key, secret)?;
+        self.storage.write(name, &ciphertext)
+    }
+
+    /// Reads and decrypts the secret stored under `name`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the secret, which is zeroed when dropped, or `None` if there is no
+    /// secret of that name. Fails with a `SecurityModuleError::DecryptionError` if the secret was
+    /// encrypted under another key or name or was modified, and with the error of the provider if
+    /// the data key cannot be decrypted for another reason. Failures caused by the ciphertext are
+    /// reported uniformly, see `uniform_errors`.
+    #[tracing::instrument(skip(self))]
+    pub fn get_secret(&self, name: &str) -> Result<Option<SecretBytes>, SecurityModuleError> {
+        check_name(name)?;
+        let ciphertext = match self.storage.read(name)? {
+            Some(ciphertext) => ciphertext,
+            None => return Ok(None),
+        };
+
+        uniform_errors::decryption(&self.decryption_timing, || {
+            let envelope = input_limits::validate_envelope(&ciphertext)?;
+            if envelope.key_id != self.key_id {
+                return Err(SecurityModuleError::DecryptionError(format!(
+                    "The secret '{}' is encrypted under the key '{}' instead of '{}'",
+                    name, envelope.key_id, self.key_id
+                )));
+            }
+            let (wrapped_key, kdf) = match (envelope.wrapped_key, envelope.kdf) {
+                (Some(wrapped_key), Some(kdf)) => (wrapped_key, kdf),
+                _ => {
+                    return Err(SecurityModuleError::DecryptionError(format!(
+                        "The secret '{}' has no wrapped data key",
+                        name
+                    )))
+                }
+            };
+
+            let data_key = self.provider().decrypt_data(wrapped_key)?;
+            let payload_key = kdf.derive_vec(
+                &data_key,
+                envelope.salt,
+                &kdf_info(name),
+                envelope.aead.key_len(),
+            )?;
+            aead::open(&envelope, &payload_key).map(Some)
+        })
+    }
+
+    /// Removes the secret stored under `name` and returns whether there was one.
+    #[tracing::instrument(skip(self))]
+    pub fn delete_secret(&self, name: &str) -> Result<bool, SecurityModuleError> {
+        check_name(name)?;
+        self.storage.delete(name)
+    }
+
+    /// Returns the names of all stored secrets in sorted order, without decrypting them.
+    pub fn list_secrets(&self) -> Result<Vec<String>, SecurityModuleError> {
+        self.storage.names()
+    }
+
+    fn provider(&self) -> MutexGuard<'_, dyn Provider + 'static> {
+        self.provider.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl fmt::Debug for SecretsVault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SecretsVault")
+            .field("key_id", &self.key_id)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Checks that `name` is a non-empty name of at most `MAX_SECRET_NAME_LEN` bytes without control
+/// characters.
+fn check_name(name: &str) -> Result<(), SecurityModuleError> {
+    if name.is_empty() || name.len() > MAX_SECRET_NAME_LEN || name.chars().any(char::is_control) {
+        return Err(SecurityModuleError::SecretStorage(format!(
+            "Invalid secret name {:?}: it must be 1 to {} bytes long without control characters",
+            name, MAX_SECRET_NAME_LEN
+        )));
+    }
+    Ok(())
+}
+
+fn kdf_info(name: &str) -> Vec<u8> {
+    [KDF_INFO_PREFIX, name.as_bytes()].concat()
+}
+
+fn random_bytes(len: usize) -> Result<Vec<u8>, SecurityModuleError> {
+    let mut bytes = vec![0; len];
+    rand_bytes(&mut bytes).map_err(|e| SecurityModuleError::EncryptionError(e.to_string()))?;
+    Ok(bytes)
+}
@@ -0,0 +1,66 @@
+//! The subset of CBOR (RFC 8949) needed for authenticator data and attestation objects.
+//!
+//! Only definite lengths are written, and callers list map entries in the canonical order of
+//! CTAP2: integer keys before text keys, shorter keys before longer ones, equal lengths
+//! bytewise.
+
+/// A CBOR value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Value {
+    Int(i64),
+    Bytes(Vec<u8>),
+    Text(String),
+    Map(Vec<(Value, Value)>),
+}
+
+impl Value {
+    /// Returns the CBOR encoding of the value.
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        let mut encoded = Vec::new();
+        self.encode_into(&mut encoded);
+        encoded
+    }
+
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        match self {
+            Value::Int(value) if *value >= 0 => head(out, 0, *value as u64),
+            Value::Int(value) => head(out, 1, !(*value) as u64),
+            Value::Bytes(bytes) => {
+                head(out, 2, bytes.len() as u64);
+                out.extend_from_slice(bytes);
+            }
+            Value::Text(text) => {
+                head(out, 3, text.len() as u64);
+                out.extend_from_slice(text.as_bytes());
+            }
+            Value::Map(entries) => {
+                head(out, 5, entries.len() as u64);
+                for (key, value) in entries {
+                    key.encode_into(out);
+                    value.encode_into(out);
+                }
+            }
+        }
+    }
+}
+
+/// Writes the initial byte of a data item of the major type `major` with its argument.
+fn head(out: &mut Vec<u8>, major: u8, argument: u64) {
+    let major = major << 5;
+    match argument {
+        0..=23 => out.push(major | argument as u8),
+        24..=0xff => out.extend_from_slice(&[major | 24, argument as u8]),
+        0x100..=0xffff => {
+            out.push(major | 25);
+            out.extend_from_slice(&(argument as u16).to_be_bytes());
+        }
+        0x1_0000..=0xffff_ffff => {
+            out.push(major | 26);
+            out.extend_from_slice(&(argument as u32).to_be_bytes());
+        }
+        _ => {
+            out.push(major | 27);
+            out.extend_from_slice(&argument.to_be_bytes());
+        }
+    }
+}
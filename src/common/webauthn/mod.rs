@@ -0,0 +1,506 @@
+//! The building blocks of a WebAuthn platform authenticator, to implement passkeys.
+//!
+//! An `Authenticator` creates a key in the security module for every credential, keeps the
+//! credentials in a `CredentialStore` by relying party and signs assertions with their keys. It
+//! implements the authenticator side of the `authenticatorMakeCredential` and
+//! `authenticatorGetAssertion` operations of WebAuthn Level 2, while the application handles
+//! the client side: it builds and hashes the client data, checks the origin against the RP ID and
+//! obtains the consent of the user.
+//!
+//! ```rust,ignore
+//! use crypto_layer::common::webauthn::{
+//!     AttestationFormat, Authenticator, GetAssertionRequest, MakeCredentialRequest,
+//!     MemoryCredentialStore,
+//! };
+//!
+//! let authenticator = Authenticator::new(provider, move || Box::new(config.clone()), AAGUID,
+//!     MemoryCredentialStore::new());
+//!
+//! let credential = authenticator.make_credential(&MakeCredentialRequest {
+//!     rp_id: "example.com",
+//!     user_id: b"user-4711",
+//!     user_name: "alice@example.com",
+//!     client_data_hash: &sha256(client_data_json),
+//!     user_verified: true,
+//!     attestation: AttestationFormat::Packed,
+//! })?;
+//! send_to_server(credential.credential_id, credential.attestation_object);
+//!
+//! let assertion = authenticator.get_assertion(&GetAssertionRequest {
+//!     rp_id: "example.com",
+//!     client_data_hash: &sha256(client_data_json),
+//!     allow_credentials: &[],
+//!     user_verified: true,
+//! })?;
+//! ```
+//!
+//! Credentials use ES256 for P-256 keys with SHA-256, EdDSA for Ed25519 keys and RS256 for RSA
+//! keys with PKCS#1 v1.5 signatures and SHA-256. The `packed` attestation is a self attestation
+//! with the credential key. Credentials are device-bound, so the backup flags are never set.
+
+pub(crate) mod cbor;
+
+use crate::common::{
+    crypto::{
+        algorithms::{
+            encryption::{AsymmetricEncryption, EccCurves, EccSchemeAlgorithm},
+            hashes::{Hash, Sha2Bits},
+        },
+        public_key::{PublicKey, RsaSignaturePadding},
+    },
+    error::SecurityModuleError,
+    traits::module_provider::Provider,
+};
+use cbor::Value;
+use openssl::{pkey::PKey, rand::rand_bytes, sha::sha256};
+use std::{
+    any::Any,
+    collections::BTreeMap,
+    fmt,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+/// The prefix of the ids of the credential keys, followed by the credential id in hex.
+pub const KEY_ID_PREFIX: &str = "webauthn.";
+
+/// The length of the random credential ids in bytes.
+pub const CREDENTIAL_ID_LEN: usize = 16;
+
+/// The flag of authenticator data that the user was present.
+pub const FLAG_USER_PRESENT: u8 = 0x01;
+
+/// The flag of authenticator data that the user was verified.
+pub const FLAG_USER_VERIFIED: u8 = 0x04;
+
+/// The flag of authenticator data that attested credential data is included.
+pub const FLAG_ATTESTED_CREDENTIAL_DATA: u8 = 0x40;
+
+const COSE_ALG_ES256: i64 = -7;
+const COSE_ALG_EDDSA: i64 = -8;
+const COSE_ALG_RS256: i64 = -257;
+
+/// Creates the provider-specific configuration of the credential keys.
+type ConfigFn = dyn Fn() -> Box<dyn Any> + Send + Sync;
+
+/// A credential of the authenticator.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Credential {
+    /// The id of the credential, sent to the relying party.
+    pub credential_id: Vec<u8>,
+    /// The id of the relying party the credential belongs to, e.g. `example.com`.
+    pub rp_id: String,
+    /// The user handle of the relying party.
+    pub user_id: Vec<u8>,
+    /// The name of the user, e.g. to let the user choose a credential.
+    pub user_name: String,
+    /// The id of the key of the credential in the security module.
+    pub key_id: String,
+    /// The COSE algorithm of the credential, e.g. -7 for ES256.
+    pub algorithm: i64,
+    /// The number of assertions signed with the credential.
+    pub sign_count: u32,
+}
+
+/// Stores the credentials of an `Authenticator`.
+///
+/// The credentials only hold the ids of their keys, not the keys themselves, so a store can be
+/// kept in a plain file or database.
+pub trait CredentialStore: Send + Sync {
+    /// Returns the credential `credential_id` of `rp_id`, or `None` if there is none.
+    fn get(
+        &self,
+        rp_id: &str,
+        credential_id: &[u8],
+    ) -> Result<Option<Credential>, SecurityModuleError>;
+
+    /// Returns all credentials of `rp_id` in the order of their ids.
+    fn list(&self, rp_id: &str) -> Result<Vec<Credential>, SecurityModuleError>;
+
+    /// Stores `credential`, replacing any previous credential with its RP ID and id.
+    fn put(&self, credential: &Credential) -> Result<(), SecurityModuleError>;
+
+    /// Removes the credential `credential_id` of `rp_id` and returns whether there was one.
+    fn delete(&self, rp_id: &str, credential_id: &[u8]) -> Result<bool, SecurityModuleError>;
+}
+
+/// A `CredentialStore` keeping the credentials in memory.
+#[derive(Debug, Default)]
+pub struct MemoryCredentialStore {
+    credentials: Mutex<BTreeMap<(String, Vec<u8>), Credential>>,
+}
+
+impl MemoryCredentialStore {
+    /// Creates an empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn credentials(&self) -> MutexGuard<'_, BTreeMap<(String, Vec<u8>), Credential>> {
+        self.credentials.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl CredentialStore for MemoryCredentialStore {
+    fn get(
+        &self,
+        rp_id: &str,
+        credential_id: &[u8],
+    ) -> Result<Option<Credential>, SecurityModuleError> {
+        Ok(self
+            .credentials()
+            .get(&(rp_id.to_owned(), credential_id.to_vec()))
+            .cloned())
+    }
+
+    fn list(&self, rp_id: &str) -> Result<Vec<Credential>, SecurityModuleError> {
+        Ok(self
+            .credentials()
+            .values()
+            .filter(|credential| credential.rp_id == rp_id)
+            .cloned()
+            .collect())
+    }
+
+    fn put(&self, credential: &Credential) -> Result<(), SecurityModuleError> {
+        self.credentials().insert(
+            (credential.rp_id.clone(), credential.credential_id.clone()),
+            credential.clone(),
+        );
+        Ok(())
+    }
+
+    fn delete(&self, rp_id: &str, credential_id: &[u8]) -> Result<bool, SecurityModuleError> {
+        Ok(self
+            .credentials()
+            .remove(&(rp_id.to_owned(), credential_id.to_vec()))
+            .is_some())
+    }
+}
+
+/// The attestation statement of a new credential.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttestationFormat {
+    /// No attestation, the format `none`.
+    None,
+    /// A self attestation signed with the credential key, the format `packed` without `x5c`.
+    Packed,
+}
+
+/// The parameters of `Authenticator::make_credential`.
+#[derive(Debug, Clone, Copy)]
+pub struct MakeCredentialRequest<'a> {
+    /// The id of the relying party, which the application checked against the origin.
+    pub rp_id: &'a str,
+    /// The user handle of the relying party.
+    pub user_id: &'a [u8],
+    /// The name of the user.
+    pub user_name: &'a str,
+    /// The SHA-256 hash of the client data JSON.
+    pub client_data_hash: &'a [u8; 32],
+    /// Whether the application verified the user, e.g. with biometrics.
+    pub user_verified: bool,
+    /// The attestation statement to create.
+    pub attestation: AttestationFormat,
+}
+
+/// The parameters of `Authenticator::get_assertion`.
+#[derive(Debug, Clone, Copy)]
+pub struct GetAssertionRequest<'a> {
+    /// The id of the relying party, which the application checked against the origin.
+    pub rp_id: &'a str,
+    /// The SHA-256 hash of the client data JSON.
+    pub client_data_hash: &'a [u8; 32],
+    /// The ids of the credentials the relying party accepts, or none for a discoverable
+    /// credential.
+    pub allow_credentials: &'a [Vec<u8>],
+    /// Whether the application verified the user, e.g. with biometrics.
+    pub user_verified: bool,
+}
+
+/// The result of `Authenticator::make_credential`.
+#[derive(Debug, Clone)]
+pub struct AttestedCredential {
+    /// The new credential, as stored in the `CredentialStore`.
+    pub credential: Credential,
+    /// The public key of the credential.
+    pub public_key: PublicKey,
+    /// The public key of the credential as a COSE key.
+    pub cose_public_key: Vec<u8>,
+    /// The authenticator data with the attested credential data.
+    pub authenticator_data: Vec<u8>,
+    /// The CBOR encoded attestation object, sent to the relying party.
+    pub attestation_object: Vec<u8>,
+}
+
+/// The result of `Authenticator::get_assertion`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Assertion {
+    /// The id of the credential that signed the assertion.
+    pub credential_id: Vec<u8>,
+    /// The authenticator data.
+    pub authenticator_data: Vec<u8>,
+    /// The signature over the authenticator data and the client data hash, DER encoded for
+    /// ECDSA.
+    pub signature: Vec<u8>,
+    /// The user handle of the credential.
+    pub user_id: Vec<u8>,
+}
+
+/// A platform authenticator, see the module documentation.
+pub struct Authenticator<S> {
+    provider: Arc<Mutex<dyn Provider>>,
+    config: Box<ConfigFn>,
+    aaguid: [u8; 16],
+    store: S,
+}
+
+impl<S: CredentialStore> Authenticator<S> {
+    /// Creates an authenticator.
+    ///
+    /// # Arguments
+    ///
+    /// * `provider` - The initialized provider holding the credential keys.
+    /// * `config` - Creates the configuration passed to `create_key` and `load_key`, e.g. a
+    ///   `SecureEnclaveConfig` for a P-256 signing key.
+    /// * `aaguid` - The AAGUID identifying the model of the authenticator, or zeros.
+    /// * `store` - Stores the credentials.
+    pub fn new(
+        provider: Arc<Mutex<dyn Provider>>,
+        config: impl Fn() -> Box<dyn Any> + Send + Sync + 'static,
+        aaguid: [u8; 16],
+        store: S,
+    ) -> Self {
+        Self {
+            provider,
+            config: Box::new(config),
+            aaguid,
+            store,
+        }
+    }
+
+    /// Returns the credential store, e.g. to list the credentials of a relying party.
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    /// Creates a credential for a relying party.
+    ///
+    /// The application is responsible for checking the exclude list of the relying party
+    /// against `store`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `AttestedCredential`, a
+    /// `SecurityModuleError::UnsupportedAlgorithm` if the configured key has no COSE algorithm,
+    /// in which case the key is left in the security module, or the error of the provider or of
+    /// the store.
+    #[tracing::instrument(skip_all, fields(webauthn.rp_id = request.rp_id))]
+    pub fn make_credential(
+        &self,
+        request: &MakeCredentialRequest<'_>,
+    ) -> Result<AttestedCredential, SecurityModuleError> {
+        let mut credential_id = vec![0; CREDENTIAL_ID_LEN];
+        rand_bytes(&mut credential_id)
+            .map_err(|e| SecurityModuleError::SigningError(e.to_string()))?;
+        let key_id = format!("{}{}", KEY_ID_PREFIX, hex(&credential_id));
+
+        let mut provider = lock(&self.provider);
+        provider.create_key(&key_id, (self.config)())?;
+        let public_key = provider.key_metadata()?.public_key().clone();
+        let (algorithm, cose_public_key) = cose_key(&public_key)?;
+
+        let mut authenticator_data = authenticator_data(
+            request.rp_id,
+            flags(request.user_verified) | FLAG_ATTESTED_CREDENTIAL_DATA,
+            0,
+        );
+        authenticator_data.extend_from_slice(&self.aaguid);
+        authenticator_data.extend_from_slice(&(credential_id.len() as u16).to_be_bytes());
+        authenticator_data.extend_from_slice(&credential_id);
+        authenticator_data.extend_from_slice(&cose_public_key);
+
+        let (format, statement) = match request.attestation {
+            AttestationFormat::None => ("none", Vec::new()),
+            AttestationFormat::Packed => {
+                let signature = provider
+                    .sign_data(&[&authenticator_data[..], request.client_data_hash].concat())?;
+                (
+                    "packed",
+                    vec![
+                        (Value::Text("alg".to_owned()), Value::Int(algorithm)),
+                        (Value::Text("sig".to_owned()), Value::Bytes(signature)),
+                    ],
+                )
+            }
+        };
+        drop(provider);
+
+        let attestation_object = Value::Map(vec![
+            (
+                Value::Text("fmt".to_owned()),
+                Value::Text(format.to_owned()),
+            ),
+            (Value::Text("attStmt".to_owned()), Value::Map(statement)),
+            (
+                Value::Text("authData".to_owned()),
+                Value::Bytes(authenticator_data.clone()),
+            ),
+        ])
+        .encode();
+
+        let credential = Credential {
+            credential_id,
+            rp_id: request.rp_id.to_owned(),
+            user_id: request.user_id.to_vec(),
+            user_name: request.user_name.to_owned(),
+            key_id,
+            algorithm,
+            sign_count: 0,
+        };
+        self.store.put(&credential)?;
+        Ok(AttestedCredential {
+            credential,
+            public_key,
+            cose_public_key,
+            authenticator_data,
+            attestation_object,
+        })
+    }
+
+    /// Signs an assertion with a credential of a relying party and increments its signature
+    /// counter.
+    ///
+    /// Without `allow_credentials`, the first discoverable credential of the relying party is
+    /// used. Applications that let the user choose between several credentials pass the chosen
+    /// one.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `Assertion`, a `SecurityModuleError::KeyError` if the relying
+    /// party has no matching credential, or the error of the provider or of the store.
+    #[tracing::instrument(skip_all, fields(webauthn.rp_id = request.rp_id))]
+    pub fn get_assertion(
+        &self,
+        request: &GetAssertionRequest<'_>,
+    ) -> Result<Assertion, SecurityModuleError> {
+        let credential = match request.allow_credentials {
+            [] => self.store.list(request.rp_id)?.into_iter().next(),
+            allowed => allowed
+                .iter()
+                .map(|credential_id| self.store.get(request.rp_id, credential_id))
+                .find_map(Result::transpose)
+                .transpose()?,
+        };
+        let mut credential = credential.ok_or(SecurityModuleError::KeyError)?;
+        credential.sign_count = credential.sign_count.wrapping_add(1);
+
+        let authenticator_data = authenticator_data(
+            request.rp_id,
+            flags(request.user_verified),
+            credential.sign_count,
+        );
+        let signature = {
+            let mut provider = lock(&self.provider);
+            provider.load_key(&credential.key_id, (self.config)())?;
+            provider.sign_data(&[&authenticator_data[..], request.client_data_hash].concat())?
+        };
+        self.store.put(&credential)?;
+        Ok(Assertion {
+            credential_id: credential.credential_id,
+            authenticator_data,
+            signature,
+            user_id: credential.user_id,
+        })
+    }
+}
+
+impl<S> fmt::Debug for Authenticator<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Authenticator")
+            .field("aaguid", &hex(&self.aaguid))
+            .finish_non_exhaustive()
+    }
+}
+
+/// Returns the authenticator data without attested credential data.
+fn authenticator_data(rp_id: &str, flags: u8, sign_count: u32) -> Vec<u8> {
+    let mut data = sha256(rp_id.as_bytes()).to_vec();
+    data.push(flags);
+    data.extend_from_slice(&sign_count.to_be_bytes());
+    data
+}
+
+fn flags(user_verified: bool) -> u8 {
+    match user_verified {
+        true => FLAG_USER_PRESENT | FLAG_USER_VERIFIED,
+        false => FLAG_USER_PRESENT,
+    }
+}
+
+/// Returns the COSE algorithm and the encoded COSE key (RFC 9053) of `public_key`.
+fn cose_key(public_key: &PublicKey) -> Result<(i64, Vec<u8>), SecurityModuleError> {
+    // The key type, the algorithm and the parameters of the key type.
+    let (key_type, algorithm, parameters) = match (public_key.algorithm(), public_key.hash()) {
+        (AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(EccCurves::Curve25519)), _) => {
+            let x = PKey::public_key_from_der(&public_key.to_der()?)
+                .and_then(|key| key.raw_public_key())
+                .map_err(|_| SecurityModuleError::InvalidPublicKey)?;
+            // OKP on Ed25519.
+            (1, COSE_ALG_EDDSA, vec![Value::Int(6), Value::Bytes(x)])
+        }
+        (
+            AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(EccCurves::P256)),
+            Hash::Sha2(Sha2Bits::Sha256),
+        ) => {
+            let point = public_key.to_ec_point()?;
+            let (x, y) = point[1..].split_at(32);
+            // EC2 on P-256.
+            (
+                2,
+                COSE_ALG_ES256,
+                vec![
+                    Value::Int(1),
+                    Value::Bytes(x.to_vec()),
+                    Value::Bytes(y.to_vec()),
+                ],
+            )
+        }
+        (AsymmetricEncryption::Rsa(_), Hash::Sha2(Sha2Bits::Sha256))
+            if public_key.rsa_padding() == RsaSignaturePadding::Pkcs1 =>
+        {
+            let rsa = PKey::public_key_from_der(&public_key.to_der()?)
+                .and_then(|key| key.rsa())
+                .map_err(|_| SecurityModuleError::InvalidPublicKey)?;
+            (
+                3,
+                COSE_ALG_RS256,
+                vec![
+                    Value::Bytes(rsa.n().to_vec()),
+                    Value::Bytes(rsa.e().to_vec()),
+                ],
+            )
+        }
+        _ => return Err(SecurityModuleError::UnsupportedAlgorithm),
+    };
+
+    // The parameters of the key type have the labels -1, -2 and so on.
+    let mut entries = vec![
+        (Value::Int(1), Value::Int(key_type)),
+        (Value::Int(3), Value::Int(algorithm)),
+    ];
+    entries.extend(
+        (1..)
+            .zip(parameters)
+            .map(|(label, value)| (Value::Int(-label), value)),
+    );
+    Ok((algorithm, Value::Map(entries).encode()))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn lock<'a>(provider: &'a Mutex<dyn Provider + 'static>) -> MutexGuard<'a, dyn Provider + 'static> {
+    provider.lock().unwrap_or_else(|e| e.into_inner())
+}
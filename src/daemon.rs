@@ -0,0 +1,386 @@
+//! A daemon that serves the keys of the security module over JSON-RPC.
+//!
+//! Tools written in any language on the same machine use the keys without native bindings:
+//! they start the `crypto-layer-daemon` binary and talk to it over its stdin and stdout, or
+//! connect to its Unix domain socket. Every line is a JSON-RPC 2.0 request, a notification or
+//! a batch, and every request is answered with a line:
+//!
+//! ```text
+//! --> {"jsonrpc": "2.0", "id": 1, "method": "createKey", "params": {"spec": {"algorithm": "EcP256", "purposes": ["Sign"], "label": "release"}}}
+//! <-- {"jsonrpc": "2.0", "id": 1, "result": {"keyId": "release", "publicKey": "MFkwEwYHKoZIzj0CAQYIKoZIzj0DAQcDQgAE..."}}
+//! --> {"jsonrpc": "2.0", "id": 2, "method": "sign", "params": {"keyId": "release", "data": "SGVsbG8="}}
+//! <-- {"jsonrpc": "2.0", "id": 2, "result": {"signature": "MEUCIQDx..."}}
+//! ```
+//!
+//! Binary data is Base64 with padding. The methods are:
+//!
+//! | Method               | Params                            | Result                      |
+//! |----------------------|-----------------------------------|-----------------------------|
+//! | `createKey`          | `spec`                            | `keyId`, `publicKey`        |
+//! | `loadKey`            | `spec`                            | `keyId`, `publicKey`        |
+//! | `listKeys`           |                                   | `keyIds`                    |
+//! | `sign`               | `keyId`, `data`                   | `signature`                 |
+//! | `verify`             | `keyId`, `data`, `signature`      | `valid`                     |
+//! | `encrypt`            | `keyId`, `data`                   | `ciphertext`                |
+//! | `decrypt`            | `keyId`, `data`                   | `plaintext`                 |
+//! | `deriveSharedSecret` | `keyId`, `data` (the peer's key)  | `sharedSecret`              |
+//!
+//! The `spec` is a `KeySpec` in its `serde` representation, whose label is the key id, and
+//! `publicKey` is the DER encoded `SubjectPublicKeyInfo`, `null` for symmetric keys. A key has
+//! to be created or loaded before other methods name it. Errors of the security module have
+//! the code `SECURITY_MODULE_ERROR` and its `ErrorReport` as data, e.g.
+//! `{"code": -32000, "message": "Key error", "data": {"code": 6, "message": "Key error"}}`.
+//!
+//! The daemon does not authenticate its clients. On the socket, which is only accessible to the
+//! user running the daemon, every process of the user can use the keys.
+
+use crate::common::{
+    crypto::key_spec::KeySpec,
+    error::{ErrorReport, SecurityModuleError},
+    key_router::{ConfigFn, KeyRouter},
+    traits::module_provider::Provider,
+};
+use base64::{prelude::BASE64_STANDARD, Engine};
+use serde::{de::DeserializeOwned, Deserialize, Deserializer};
+use serde_json::{json, Value};
+use std::{
+    fmt,
+    io::{self, BufRead, Read, Write},
+    sync::{Arc, Mutex},
+};
+
+/// The longest line the daemon accepts in bytes.
+pub const MAX_LINE_LEN: usize = 4 * 1024 * 1024;
+
+/// The JSON-RPC error code of errors of the security module, see the module documentation.
+pub const SECURITY_MODULE_ERROR: i64 = -32000;
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// A JSON-RPC daemon, see the module documentation.
+pub struct Daemon {
+    router: KeyRouter,
+}
+
+/// The error object of a JSON-RPC response.
+struct RpcError {
+    code: i64,
+    message: String,
+    data: Option<Value>,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+
+    fn to_value(&self) -> Value {
+        let mut error = json!({ "code": self.code, "message": self.message });
+        if let Some(data) = &self.data {
+            error["data"] = data.clone();
+        }
+        error
+    }
+}
+
+impl From<SecurityModuleError> for RpcError {
+    fn from(error: SecurityModuleError) -> Self {
+        let report = ErrorReport::from(&error);
+        Self {
+            code: SECURITY_MODULE_ERROR,
+            message: report.message.clone(),
+            data: Some(serde_json::to_value(report).expect("reports are always serializable")),
+        }
+    }
+}
+
+/// Binary data, Base64 encoded in JSON.
+struct Base64(Vec<u8>);
+
+impl<'de> Deserialize<'de> for Base64 {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        BASE64_STANDARD
+            .decode(encoded)
+            .map(Base64)
+            .map_err(|_| serde::de::Error::custom("invalid Base64"))
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct KeyParams {
+    spec: KeySpec,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+struct DataParams {
+    key_id: String,
+    data: Base64,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+struct VerifyParams {
+    key_id: String,
+    data: Base64,
+    signature: Base64,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NoParams {}
+
+impl Daemon {
+    /// Creates a daemon for an initialized provider.
+    ///
+    /// # Arguments
+    ///
+    /// * `provider` - The provider, which should not be used to load keys while the daemon
+    ///   runs.
+    /// * `config` - Creates the configuration of the provider for the spec of a key, e.g.
+    ///   `TpmConfig::from_spec`.
+    pub fn new(provider: Arc<Mutex<dyn Provider>>, config: ConfigFn) -> Self {
+        Self {
+            router: KeyRouter::new(provider, config),
+        }
+    }
+
+    /// Answers a single line.
+    ///
+    /// # Returns
+    ///
+    /// The response line without its line break, or `None` if the line only holds
+    /// notifications.
+    pub fn handle_line(&self, line: &str) -> Option<String> {
+        let response = match serde_json::from_str::<Value>(line) {
+            Ok(Value::Array(requests)) if !requests.is_empty() => {
+                let responses: Vec<Value> = requests
+                    .into_iter()
+                    .filter_map(|request| self.handle(request))
+                    .collect();
+                if responses.is_empty() {
+                    return None;
+                }
+                Value::Array(responses)
+            }
+            Ok(Value::Array(_)) => response(
+                Value::Null,
+                Err(RpcError::new(INVALID_REQUEST, "The batch is empty")),
+            ),
+            Ok(request) => self.handle(request)?,
+            Err(e) => response(Value::Null, Err(RpcError::new(PARSE_ERROR, e.to_string()))),
+        };
+        Some(response.to_string())
+    }
+
+    /// Serves the lines of `input` until it ends, writing the responses to `output`.
+    ///
+    /// # Returns
+    ///
+    /// An `io::Result` that is `Ok(())` when `input` ended, or the error of `input` or
+    /// `output`. A line longer than `MAX_LINE_LEN` is an `io::ErrorKind::InvalidData` error.
+    pub fn serve(&self, mut input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            let limit = MAX_LINE_LEN as u64 + 1;
+            if (&mut input).take(limit).read_until(b'\n', &mut line)? == 0 {
+                return Ok(());
+            }
+            if line.last() == Some(&b'\n') {
+                line.pop();
+            } else if line.len() > MAX_LINE_LEN {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "The line is too long",
+                ));
+            }
+            let response = match std::str::from_utf8(&line) {
+                Ok(text) if text.trim().is_empty() => continue,
+                Ok(text) => self.handle_line(text),
+                Err(_) => Some(
+                    response(
+                        Value::Null,
+                        Err(RpcError::new(PARSE_ERROR, "The line is not UTF-8")),
+                    )
+                    .to_string(),
+                ),
+            };
+            if let Some(response) = response {
+                output.write_all(response.as_bytes())?;
+                output.write_all(b"\n")?;
+                output.flush()?;
+            }
+        }
+    }
+
+    /// Listens on the Unix domain socket `path`, which must not exist, and serves every client
+    /// on its own thread.
+    ///
+    /// The socket is made accessible to the current user only, but should also be created in a
+    /// directory that only the user can access, since other users could connect before its
+    /// permissions are set. This function only returns when the socket cannot be created or
+    /// accepting a connection fails.
+    #[cfg(unix)]
+    pub fn listen(self: Arc<Self>, path: impl AsRef<std::path::Path>) -> io::Result<()> {
+        use std::{
+            fs, io::BufReader, os::unix::fs::PermissionsExt, os::unix::net::UnixListener, thread,
+        };
+
+        let listener = UnixListener::bind(&path)?;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600))?;
+        for stream in listener.incoming() {
+            let stream = stream?;
+            let daemon = Arc::clone(&self);
+            thread::spawn(move || {
+                let result = stream
+                    .try_clone()
+                    .and_then(|reader| daemon.serve(BufReader::new(reader), stream));
+                if let Err(e) = result {
+                    tracing::warn!(error = %e, "daemon connection failed");
+                }
+            });
+        }
+        Ok(())
+    }
+
+    /// Answers a single request, or returns `None` for a notification.
+    fn handle(&self, request: Value) -> Option<Value> {
+        let Value::Object(mut request) = request else {
+            return Some(response(
+                Value::Null,
+                Err(RpcError::new(
+                    INVALID_REQUEST,
+                    "The request is not an object",
+                )),
+            ));
+        };
+        let id = match request.remove("id") {
+            Some(id @ (Value::Null | Value::Number(_) | Value::String(_))) => Some(id),
+            Some(_) => {
+                return Some(response(
+                    Value::Null,
+                    Err(RpcError::new(INVALID_REQUEST, "The id is invalid")),
+                ))
+            }
+            None => None,
+        };
+        let version = request.remove("jsonrpc");
+        let method = match (version, request.remove("method")) {
+            (Some(Value::String(version)), Some(Value::String(method))) if version == "2.0" => {
+                method
+            }
+            _ => {
+                return Some(response(
+                    id.unwrap_or(Value::Null),
+                    Err(RpcError::new(
+                        INVALID_REQUEST,
+                        "The request needs a jsonrpc of 2.0 and a method",
+                    )),
+                ))
+            }
+        };
+        let params = match request.remove("params") {
+            None => Value::Object(Default::default()),
+            Some(params @ Value::Object(_)) => params,
+            Some(_) => {
+                return Some(response(
+                    id.unwrap_or(Value::Null),
+                    Err(RpcError::new(
+                        INVALID_PARAMS,
+                        "The params are not an object",
+                    )),
+                ))
+            }
+        };
+
+        tracing::debug!(method, "daemon call");
+        let result = self.call(&method, params);
+        if let Err(e) = &result {
+            tracing::debug!(method, error = %e.message, "daemon call failed");
+        }
+        // Notifications are never answered, not even with errors.
+        Some(response(id?, result))
+    }
+
+    fn call(&self, method: &str, params: Value) -> Result<Value, RpcError> {
+        match method {
+            "createKey" | "loadKey" => {
+                let KeyParams { spec } = parse(params)?;
+                let public_key = self.router.open_key(&spec, method == "createKey")?;
+                Ok(json!({
+                    "keyId": spec.label(),
+                    "publicKey": public_key.map(|der| BASE64_STANDARD.encode(der)),
+                }))
+            }
+            "listKeys" => {
+                let NoParams {} = parse(params)?;
+                Ok(json!({ "keyIds": self.router.list_keys()? }))
+            }
+            "sign" => self.data(params, "signature", |provider, data| {
+                provider.sign_data(data)
+            }),
+            "encrypt" => self.data(params, "ciphertext", |provider, data| {
+                provider.encrypt_data(data)
+            }),
+            "decrypt" => self.data(params, "plaintext", |provider, data| {
+                provider.decrypt_data(data)
+            }),
+            "deriveSharedSecret" => self.data(params, "sharedSecret", |provider, data| {
+                provider.derive_shared_secret(data)
+            }),
+            "verify" => {
+                let params: VerifyParams = parse(params)?;
+                let valid = self.router.with_key(&params.key_id, |provider| {
+                    provider.verify_signature(&params.data.0, &params.signature.0)
+                })?;
+                Ok(json!({ "valid": valid }))
+            }
+            _ => Err(RpcError::new(
+                METHOD_NOT_FOUND,
+                format!("Unknown method {}", method),
+            )),
+        }
+    }
+
+    fn data<T: AsRef<[u8]>>(
+        &self,
+        params: Value,
+        field: &str,
+        operation: impl FnOnce(&dyn Provider, &[u8]) -> Result<T, SecurityModuleError>,
+    ) -> Result<Value, RpcError> {
+        let params: DataParams = parse(params)?;
+        let output = self.router.with_key(&params.key_id, |provider| {
+            operation(provider, &params.data.0)
+        })?;
+        Ok(json!({ field: BASE64_STANDARD.encode(output) }))
+    }
+}
+
+impl fmt::Debug for Daemon {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Daemon")
+            .field("keys", &self.router.key_ids())
+            .finish_non_exhaustive()
+    }
+}
+
+fn parse<T: DeserializeOwned>(params: Value) -> Result<T, RpcError> {
+    serde_json::from_value(params).map_err(|e| RpcError::new(INVALID_PARAMS, e.to_string()))
+}
+
+fn response(id: Value, result: Result<Value, RpcError>) -> Value {
+    match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(error) => json!({ "jsonrpc": "2.0", "id": id, "error": error.to_value() }),
+    }
+}
@@ -0,0 +1,651 @@
+//! A stable C API for applications that cannot link the crate as a Rust library.
+//!
+//! C, C++, Dart, Swift without the Swift bindings, and other languages with a C FFI use the crate
+//! through the `cdylib` of the `ffi` feature and the header `include/crypto_layer.h`, which
+//! cbindgen generates from this module with the `cbindgen.toml` of the repository:
+//!
+//! ```c
+//! CryptoLayerProvider *provider = NULL;
+//! int32_t status = crypto_layer_provider_open("TPM", "signing_key", &provider);
+//! CryptoLayerKeySpec spec = {CRYPTO_LAYER_ALGORITHM_EC_P256, CRYPTO_LAYER_PURPOSE_SIGN,
+//!                            CRYPTO_LAYER_ACCESS_NONE, false};
+//! if (status == CRYPTO_LAYER_OK) {
+//!     status = crypto_layer_create_key(provider, "signing_key", &spec);
+//! }
+//! CryptoLayerBuffer signature = {0};
+//! if (status == CRYPTO_LAYER_OK) {
+//!     status = crypto_layer_sign(provider, data, data_len, &signature);
+//! }
+//! if (status != CRYPTO_LAYER_OK) {
+//!     fprintf(stderr, "%s\n", crypto_layer_last_error_message());
+//! }
+//! crypto_layer_buffer_free(&signature);
+//! crypto_layer_provider_free(provider);
+//! ```
+//!
+//! The API consists of an opaque `CryptoLayerProvider` handle, plain structs and integer
+//! constants, and never changes incompatibly within an `CRYPTO_LAYER_ABI_VERSION`:
+//!
+//! - Every function that can fail returns `CRYPTO_LAYER_OK` or an error code: the stable
+//!   `SecurityModuleError::code` of the error, or one of the `CRYPTO_LAYER_ERROR_*` codes of this
+//!   module for invalid arguments. `crypto_layer_last_error_message` describes the last error of
+//!   the calling thread.
+//! - Byte outputs are returned in a `CryptoLayerBuffer` allocated by the library, which the caller
+//!   releases with `crypto_layer_buffer_free`. Buffers are zeroed before they are freed, since
+//!   they may hold plaintexts.
+//! - Strings are null-terminated UTF-8. Input pointers are only borrowed for the call.
+//! - Panics do not unwind into the caller but fail with `CRYPTO_LAYER_ERROR_PANIC`.
+//!
+//! A provider handle may be used from several threads, its calls are serialized.
+
+use crate::common::{
+    crypto::key_spec::{AccessControl, KeyAlgorithm, KeyPurpose, KeySpec},
+    ecies,
+    error::SecurityModuleError,
+    input_limits::InputLimits,
+    traits::module_provider::Provider,
+};
+use std::{
+    any::Any,
+    cell::RefCell,
+    ffi::{c_char, CStr, CString},
+    panic::{self, AssertUnwindSafe},
+    ptr, slice,
+    sync::{Arc, Mutex, MutexGuard},
+};
+use zeroize::Zeroize;
+
+/// The version of the C API, which changes with every incompatible change.
+pub const CRYPTO_LAYER_ABI_VERSION: u32 = 1;
+
+/// The call succeeded.
+pub const CRYPTO_LAYER_OK: i32 = 0;
+/// A required pointer argument is null.
+pub const CRYPTO_LAYER_ERROR_NULL_POINTER: i32 = 200;
+/// An argument is not valid UTF-8 or not one of the constants of its field.
+pub const CRYPTO_LAYER_ERROR_INVALID_ARGUMENT: i32 = 201;
+/// The security module is unknown or not compiled into the library.
+pub const CRYPTO_LAYER_ERROR_UNSUPPORTED_MODULE: i32 = 202;
+/// The library panicked, which is a bug.
+pub const CRYPTO_LAYER_ERROR_PANIC: i32 = 203;
+
+/// `CryptoLayerKeySpec::algorithm` of NIST P-256 keys.
+pub const CRYPTO_LAYER_ALGORITHM_EC_P256: u32 = 1;
+/// `CryptoLayerKeySpec::algorithm` of NIST P-384 keys.
+pub const CRYPTO_LAYER_ALGORITHM_EC_P384: u32 = 2;
+/// `CryptoLayerKeySpec::algorithm` of NIST P-521 keys.
+pub const CRYPTO_LAYER_ALGORITHM_EC_P521: u32 = 3;
+/// `CryptoLayerKeySpec::algorithm` of 2048 bit RSA keys.
+pub const CRYPTO_LAYER_ALGORITHM_RSA_2048: u32 = 4;
+/// `CryptoLayerKeySpec::algorithm` of 3072 bit RSA keys.
+pub const CRYPTO_LAYER_ALGORITHM_RSA_3072: u32 = 5;
+/// `CryptoLayerKeySpec::algorithm` of 4096 bit RSA keys.
+pub const CRYPTO_LAYER_ALGORITHM_RSA_4096: u32 = 6;
+/// `CryptoLayerKeySpec::algorithm` of 128 bit AES-GCM keys.
+pub const CRYPTO_LAYER_ALGORITHM_AES_128_GCM: u32 = 7;
+/// `CryptoLayerKeySpec::algorithm` of 256 bit AES-GCM keys.
+pub const CRYPTO_LAYER_ALGORITHM_AES_256_GCM: u32 = 8;
+
+/// `CryptoLayerKeySpec::purposes` flag for signing and verifying.
+pub const CRYPTO_LAYER_PURPOSE_SIGN: u32 = 1;
+/// `CryptoLayerKeySpec::purposes` flag for encrypting and decrypting.
+pub const CRYPTO_LAYER_PURPOSE_ENCRYPT: u32 = 2;
+/// `CryptoLayerKeySpec::purposes` flag for key agreements.
+pub const CRYPTO_LAYER_PURPOSE_KEY_AGREEMENT: u32 = 4;
+
+/// `CryptoLayerKeySpec::access` of keys that can be used whenever the device is unlocked.
+pub const CRYPTO_LAYER_ACCESS_NONE: u32 = 0;
+/// `CryptoLayerKeySpec::access` of keys that require any biometric or the device passcode.
+pub const CRYPTO_LAYER_ACCESS_USER_PRESENCE: u32 = 1;
+/// `CryptoLayerKeySpec::access` of keys that require any enrolled biometric.
+pub const CRYPTO_LAYER_ACCESS_BIOMETRY_ANY: u32 = 2;
+/// `CryptoLayerKeySpec::access` of keys that require a biometric enrolled at their creation.
+pub const CRYPTO_LAYER_ACCESS_BIOMETRY_CURRENT_SET: u32 = 3;
+/// `CryptoLayerKeySpec::access` of keys that require the device passcode.
+pub const CRYPTO_LAYER_ACCESS_DEVICE_PASSCODE: u32 = 4;
+
+/// An open security module with its current key, see `crypto_layer_provider_open`.
+pub struct CryptoLayerProvider {
+    provider: Arc<Mutex<dyn Provider>>,
+    module: Module,
+}
+
+/// The security modules the C API opens.
+#[derive(Debug, Clone, Copy)]
+pub(super) enum Module {
+    /// The security module of the platform, e.g. the Secure Enclave or the TPM.
+    #[cfg(feature = "tpm")]
+    Tpm,
+    /// The in-memory `MockProvider`.
+    #[cfg(feature = "test-utils")]
+    Mock,
+}
+
+/// The algorithm, purposes and policy of a key, see `KeySpec`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct CryptoLayerKeySpec {
+    /// One of the `CRYPTO_LAYER_ALGORITHM_*` constants.
+    pub algorithm: u32,
+    /// A combination of the `CRYPTO_LAYER_PURPOSE_*` flags.
+    pub purposes: u32,
+    /// One of the `CRYPTO_LAYER_ACCESS_*` constants.
+    pub access: u32,
+    /// Whether the private key can be exported, see `KeySpec::exportable`.
+    pub exportable: bool,
+}
+
+/// Bytes allocated by the library, released with `crypto_layer_buffer_free`.
+#[repr(C)]
+#[derive(Debug)]
+pub struct CryptoLayerBuffer {
+    /// The bytes, or null for an empty buffer.
+    pub data: *mut u8,
+    /// The number of bytes.
+    pub len: usize,
+}
+
+/// An error of a call, with the code it returns and the message of
+/// `crypto_layer_last_error_message`.
+pub(super) struct Error {
+    pub(super) code: i32,
+    pub(super) message: String,
+}
+
+impl Error {
+    pub(super) fn new(code: i32, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+
+    fn null(name: &str) -> Self {
+        Self::new(
+            CRYPTO_LAYER_ERROR_NULL_POINTER,
+            format!("'{}' is null", name),
+        )
+    }
+
+    pub(super) fn invalid(message: impl Into<String>) -> Self {
+        Self::new(CRYPTO_LAYER_ERROR_INVALID_ARGUMENT, message)
+    }
+}
+
+impl From<SecurityModuleError> for Error {
+    fn from(e: SecurityModuleError) -> Self {
+        Self::new(e.code() as i32, e.to_string())
+    }
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<CString> = RefCell::new(CString::default());
+}
+
+/// Runs the body of an exported function, recording its error and catching its panics.
+fn run(body: impl FnOnce() -> Result<(), Error>) -> i32 {
+    let error = match panic::catch_unwind(AssertUnwindSafe(body)) {
+        Ok(Ok(())) => return CRYPTO_LAYER_OK,
+        Ok(Err(error)) => error,
+        Err(_) => Error::new(CRYPTO_LAYER_ERROR_PANIC, "The library panicked"),
+    };
+    let message = CString::new(error.message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last_error| *last_error.borrow_mut() = message);
+    error.code
+}
+
+/// Borrows a null-terminated UTF-8 string argument.
+unsafe fn string<'a>(ptr: *const c_char, name: &str) -> Result<&'a str, Error> {
+    if ptr.is_null() {
+        return Err(Error::null(name));
+    }
+    CStr::from_ptr(ptr)
+        .to_str()
+        .map_err(|_| Error::invalid(format!("'{}' is not valid UTF-8", name)))
+}
+
+/// Borrows a byte argument, which may be null if `len` is 0. `len` is checked against the
+/// `InputLimits` of the process before the argument is read.
+unsafe fn bytes<'a>(ptr: *const u8, len: usize, name: &str) -> Result<&'a [u8], Error> {
+    InputLimits::current().check_data(name, len)?;
+    match (ptr.is_null(), len) {
+        (true, 0) => Ok(&[]),
+        (true, _) => Err(Error::null(name)),
+        (false, _) => Ok(slice::from_raw_parts(ptr, len)),
+    }
+}
+
+/// Borrows a provider handle and locks its provider.
+unsafe fn lock<'a>(
+    provider: *const CryptoLayerProvider,
+) -> Result<
+    (
+        &'a CryptoLayerProvider,
+        MutexGuard<'a, dyn Provider + 'static>,
+    ),
+    Error,
+> {
+    let provider = provider.as_ref().ok_or_else(|| Error::null("provider"))?;
+    let guard = provider.provider.lock().map_err(|_| {
+        Error::new(
+            CRYPTO_LAYER_ERROR_PANIC,
+            "The provider was poisoned by an earlier panic",
+        )
+    })?;
+    Ok((provider, guard))
+}
+
+/// Moves `data` into the buffer `out`.
+unsafe fn write_buffer(out: *mut CryptoLayerBuffer, data: Vec<u8>) -> Result<(), Error> {
+    let out = out.as_mut().ok_or_else(|| Error::null("out"))?;
+    *out = if data.is_empty() {
+        CryptoLayerBuffer {
+            data: ptr::null_mut(),
+            len: 0,
+        }
+    } else {
+        let len = data.len();
+        CryptoLayerBuffer {
+            data: Box::into_raw(data.into_boxed_slice()) as *mut u8,
+            len,
+        }
+    };
+    Ok(())
+}
+
+impl Module {
+    pub(super) fn from_name(name: &str) -> Result<Self, Error> {
+        match name {
+            #[cfg(feature = "tpm")]
+            "TPM" => Ok(Module::Tpm),
+            #[cfg(feature = "test-utils")]
+            "MOCK" => Ok(Module::Mock),
+            _ => Err(Error::new(
+                CRYPTO_LAYER_ERROR_UNSUPPORTED_MODULE,
+                format!("The security module '{}' is not supported", name),
+            )),
+        }
+    }
+
+    /// Opens and initializes the provider of the module for `key_id`.
+    pub(super) fn open(self, key_id: &str) -> Result<Arc<Mutex<dyn Provider>>, Error> {
+        let provider = self.provider(key_id)?;
+        provider
+            .lock()
+            .map_err(|_| Error::new(CRYPTO_LAYER_ERROR_PANIC, "The provider was poisoned"))?
+            .initialize_module()?;
+        Ok(provider)
+    }
+
+    fn provider(self, key_id: &str) -> Result<Arc<Mutex<dyn Provider>>, Error> {
+        match self {
+            #[cfg(feature = "tpm")]
+            Module::Tpm => {
+                use crate::{
+                    common::factory::{SecModules, SecurityModule},
+                    tpm::core::instance::TpmType,
+                };
+                let tpm_type = match TpmType::default() {
+                    // Knox, the default on Android, is not implemented.
+                    #[cfg(feature = "android")]
+                    TpmType::Android(_) => {
+                        TpmType::Android(crate::tpm::core::instance::AndroidTpmType::Keystore)
+                    }
+                    tpm_type => tpm_type,
+                };
+                SecModules::get_instance(key_id.to_owned(), SecurityModule::Tpm(tpm_type), None)
+                    .ok_or_else(|| {
+                        Error::new(
+                            CRYPTO_LAYER_ERROR_UNSUPPORTED_MODULE,
+                            "The security module of the platform cannot be opened",
+                        )
+                    })
+            }
+            #[cfg(feature = "test-utils")]
+            Module::Mock => Ok(Arc::new(Mutex::new(crate::mock::MockProvider::new(
+                key_id.to_owned(),
+            )))),
+        }
+    }
+
+    /// Returns the configuration of the module for `spec`, for `create_key` and `load_key`.
+    pub(super) fn config(self, spec: &KeySpec) -> Result<Box<dyn Any>, SecurityModuleError> {
+        match self {
+            #[cfg(feature = "tpm")]
+            Module::Tpm => {
+                #[cfg(feature = "macos")]
+                if crate::tpm::core::instance::TpmType::default()
+                    == crate::tpm::core::instance::TpmType::MacOs
+                {
+                    return Ok(Box::new(crate::tpm::macos::SecureEnclaveConfig::from_spec(
+                        spec,
+                    )?));
+                }
+                #[cfg(feature = "android")]
+                if matches!(
+                    crate::tpm::core::instance::TpmType::default(),
+                    crate::tpm::core::instance::TpmType::Android(_)
+                ) {
+                    return crate::tpm::android::config::AndroidConfig::from_spec(spec);
+                }
+                crate::tpm::TpmConfig::from_spec(spec)
+            }
+            #[cfg(feature = "test-utils")]
+            Module::Mock => crate::mock::MockConfig::from_spec(spec),
+        }
+    }
+}
+
+impl CryptoLayerKeySpec {
+    pub(super) fn to_key_spec(self, label: &str) -> Result<KeySpec, Error> {
+        let algorithm = match self.algorithm {
+            CRYPTO_LAYER_ALGORITHM_EC_P256 => KeyAlgorithm::EcP256,
+            CRYPTO_LAYER_ALGORITHM_EC_P384 => KeyAlgorithm::EcP384,
+            CRYPTO_LAYER_ALGORITHM_EC_P521 => KeyAlgorithm::EcP521,
+            CRYPTO_LAYER_ALGORITHM_RSA_2048 => KeyAlgorithm::Rsa2048,
+            CRYPTO_LAYER_ALGORITHM_RSA_3072 => KeyAlgorithm::Rsa3072,
+            CRYPTO_LAYER_ALGORITHM_RSA_4096 => KeyAlgorithm::Rsa4096,
+            CRYPTO_LAYER_ALGORITHM_AES_128_GCM => KeyAlgorithm::Aes128Gcm,
+            CRYPTO_LAYER_ALGORITHM_AES_256_GCM => KeyAlgorithm::Aes256Gcm,
+            other => return Err(Error::invalid(format!("Unknown algorithm {}", other))),
+        };
+        let access = match self.access {
+            CRYPTO_LAYER_ACCESS_NONE => AccessControl::None,
+            CRYPTO_LAYER_ACCESS_USER_PRESENCE => AccessControl::UserPresence,
+            CRYPTO_LAYER_ACCESS_BIOMETRY_ANY => AccessControl::BiometryAny,
+            CRYPTO_LAYER_ACCESS_BIOMETRY_CURRENT_SET => AccessControl::BiometryCurrentSet,
+            CRYPTO_LAYER_ACCESS_DEVICE_PASSCODE => AccessControl::DevicePasscode,
+            other => return Err(Error::invalid(format!("Unknown access control {}", other))),
+        };
+        let purposes = [
+            (CRYPTO_LAYER_PURPOSE_SIGN, KeyPurpose::Sign),
+            (CRYPTO_LAYER_PURPOSE_ENCRYPT, KeyPurpose::Encrypt),
+            (CRYPTO_LAYER_PURPOSE_KEY_AGREEMENT, KeyPurpose::KeyAgreement),
+        ];
+        let known = purposes.iter().fold(0, |known, (flag, _)| known | flag);
+        if self.purposes & !known != 0 {
+            return Err(Error::invalid(format!(
+                "Unknown purposes {:#x}",
+                self.purposes & !known
+            )));
+        }
+
+        let mut builder = KeySpec::builder()
+            .algorithm(algorithm)
+            .access(access)
+            .label(label)
+            .exportable(self.exportable);
+        for (flag, purpose) in purposes {
+            if self.purposes & flag != 0 {
+                builder = builder.usage(purpose);
+            }
+        }
+        Ok(builder.build()?)
+    }
+}
+
+/// Returns `CRYPTO_LAYER_ABI_VERSION` of the library, which applications compare with the
+/// version of the header they were compiled with.
+#[no_mangle]
+pub extern "C" fn crypto_layer_abi_version() -> u32 {
+    CRYPTO_LAYER_ABI_VERSION
+}
+
+/// Returns the message of the last error of the calling thread as null-terminated UTF-8, or an
+/// empty string. The string is valid until the next failing call on the thread.
+#[no_mangle]
+pub extern "C" fn crypto_layer_last_error_message() -> *const c_char {
+    LAST_ERROR.with(|last_error| last_error.borrow().as_ptr())
+}
+
+/// Opens and initializes a security module.
+///
+/// # Arguments
+///
+/// * `module` - `"TPM"` for the security module of the platform, or `"MOCK"` for the in-memory
+///   provider of builds with the `test-utils` feature.
+/// * `key_id` - The id of the key the provider is created for.
+/// * `out` - Receives the handle, which is released with `crypto_layer_provider_free`.
+///
+/// # Safety
+///
+/// `module` and `key_id` must be null or null-terminated strings, and `out` null or valid for
+/// writes.
+#[no_mangle]
+pub unsafe extern "C" fn crypto_layer_provider_open(
+    module: *const c_char,
+    key_id: *const c_char,
+    out: *mut *mut CryptoLayerProvider,
+) -> i32 {
+    run(|| {
+        let module = Module::from_name(string(module, "module")?)?;
+        let key_id = string(key_id, "key_id")?;
+        let out = out.as_mut().ok_or_else(|| Error::null("out"))?;
+        let provider = module.open(key_id)?;
+        *out = Box::into_raw(Box::new(CryptoLayerProvider { provider, module }));
+        Ok(())
+    })
+}
+
+/// Releases a provider handle of `crypto_layer_provider_open`. Null is ignored.
+///
+/// # Safety
+///
+/// `provider` must be null or a handle that was not released yet.
+#[no_mangle]
+pub unsafe extern "C" fn crypto_layer_provider_free(provider: *mut CryptoLayerProvider) {
+    if !provider.is_null() {
+        drop(Box::from_raw(provider));
+    }
+}
+
+/// Creates a key and makes it the current key of the provider.
+///
+/// # Arguments
+///
+/// * `provider` - The provider handle.
+/// * `key_id` - The id of the key, which is also its label.
+/// * `spec` - The algorithm, purposes and policy of the key.
+///
+/// # Safety
+///
+/// `provider` must be null or a valid handle, `key_id` null or a null-terminated string, and
+/// `spec` null or valid for reads.
+#[no_mangle]
+pub unsafe extern "C" fn crypto_layer_create_key(
+    provider: *mut CryptoLayerProvider,
+    key_id: *const c_char,
+    spec: *const CryptoLayerKeySpec,
+) -> i32 {
+    run(|| {
+        let (handle, mut provider) = lock(provider)?;
+        let key_id = string(key_id, "key_id")?;
+        let spec = spec.as_ref().ok_or_else(|| Error::null("spec"))?;
+        let config = handle.module.config(&spec.to_key_spec(key_id)?)?;
+        Ok(provider.create_key(key_id, config)?)
+    })
+}
+
+/// Loads an existing key and makes it the current key of the provider, like
+/// `crypto_layer_create_key`.
+///
+/// # Safety
+///
+/// See `crypto_layer_create_key`.
+#[no_mangle]
+pub unsafe extern "C" fn crypto_layer_load_key(
+    provider: *mut CryptoLayerProvider,
+    key_id: *const c_char,
+    spec: *const CryptoLayerKeySpec,
+) -> i32 {
+    run(|| {
+        let (handle, mut provider) = lock(provider)?;
+        let key_id = string(key_id, "key_id")?;
+        let spec = spec.as_ref().ok_or_else(|| Error::null("spec"))?;
+        let config = handle.module.config(&spec.to_key_spec(key_id)?)?;
+        Ok(provider.load_key(key_id, config)?)
+    })
+}
+
+/// Writes the DER encoded SubjectPublicKeyInfo of the current key to `out`.
+///
+/// # Safety
+///
+/// `provider` must be null or a valid handle and `out` null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn crypto_layer_public_key(
+    provider: *const CryptoLayerProvider,
+    out: *mut CryptoLayerBuffer,
+) -> i32 {
+    run(|| {
+        let (_, provider) = lock(provider)?;
+        let der = provider.key_metadata()?.public_key_der().to_vec();
+        write_buffer(out, der)
+    })
+}
+
+/// Signs `data` with the current key and writes the signature to `out`.
+///
+/// # Safety
+///
+/// `provider` must be null or a valid handle, `data` valid for `data_len` bytes unless
+/// `data_len` is 0, and `out` null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn crypto_layer_sign(
+    provider: *const CryptoLayerProvider,
+    data: *const u8,
+    data_len: usize,
+    out: *mut CryptoLayerBuffer,
+) -> i32 {
+    run(|| {
+        let (_, provider) = lock(provider)?;
+        let signature = provider.sign_data(bytes(data, data_len, "data")?)?;
+        write_buffer(out, signature)
+    })
+}
+
+/// Verifies `signature` over `data` with the current key and writes the result to `valid`.
+///
+/// # Safety
+///
+/// `provider` must be null or a valid handle, `data` and `signature` valid for their lengths
+/// unless they are 0, and `valid` null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn crypto_layer_verify(
+    provider: *const CryptoLayerProvider,
+    data: *const u8,
+    data_len: usize,
+    signature: *const u8,
+    signature_len: usize,
+    valid: *mut bool,
+) -> i32 {
+    run(|| {
+        let (_, provider) = lock(provider)?;
+        let valid = valid.as_mut().ok_or_else(|| Error::null("valid"))?;
+        *valid = provider.verify_signature(
+            bytes(data, data_len, "data")?,
+            bytes(signature, signature_len, "signature")?,
+        )?;
+        Ok(())
+    })
+}
+
+/// Encrypts `data` with the current key and writes the ciphertext to `out`.
+///
+/// # Safety
+///
+/// See `crypto_layer_sign`.
+#[no_mangle]
+pub unsafe extern "C" fn crypto_layer_encrypt(
+    provider: *const CryptoLayerProvider,
+    data: *const u8,
+    data_len: usize,
+    out: *mut CryptoLayerBuffer,
+) -> i32 {
+    run(|| {
+        let (_, provider) = lock(provider)?;
+        let ciphertext = provider.encrypt_data(bytes(data, data_len, "data")?)?;
+        write_buffer(out, ciphertext)
+    })
+}
+
+/// Decrypts `data` with the current key and writes the plaintext to `out`.
+///
+/// # Safety
+///
+/// See `crypto_layer_sign`.
+#[no_mangle]
+pub unsafe extern "C" fn crypto_layer_decrypt(
+    provider: *const CryptoLayerProvider,
+    data: *const u8,
+    data_len: usize,
+    out: *mut CryptoLayerBuffer,
+) -> i32 {
+    run(|| {
+        let (_, provider) = lock(provider)?;
+        let plaintext = provider.decrypt_data(bytes(data, data_len, "data")?)?;
+        write_buffer(out, plaintext.into_vec())
+    })
+}
+
+/// Encrypts `data` with ECIES for the public key of the current EC key and writes the envelope
+/// to `out`, see `ecies::encrypt_for`. Unlike `crypto_layer_encrypt`, the data may be of any
+/// size.
+///
+/// # Safety
+///
+/// See `crypto_layer_sign`.
+#[no_mangle]
+pub unsafe extern "C" fn crypto_layer_envelope_encrypt(
+    provider: *const CryptoLayerProvider,
+    data: *const u8,
+    data_len: usize,
+    out: *mut CryptoLayerBuffer,
+) -> i32 {
+    run(|| {
+        let (_, provider) = lock(provider)?;
+        let metadata = provider.key_metadata()?;
+        let envelope = ecies::encrypt_for(metadata.public_key(), bytes(data, data_len, "data")?)?;
+        write_buffer(out, envelope)
+    })
+}
+
+/// Decrypts an envelope of `crypto_layer_envelope_encrypt` with the current key and writes the
+/// plaintext to `out`, see `ecies::decrypt`.
+///
+/// # Safety
+///
+/// See `crypto_layer_sign`.
+#[no_mangle]
+pub unsafe extern "C" fn crypto_layer_envelope_decrypt(
+    provider: *const CryptoLayerProvider,
+    data: *const u8,
+    data_len: usize,
+    out: *mut CryptoLayerBuffer,
+) -> i32 {
+    run(|| {
+        let (_, provider) = lock(provider)?;
+        let plaintext = ecies::decrypt(&*provider, bytes(data, data_len, "data")?)?;
+        write_buffer(out, plaintext.into_vec())
+    })
+}
+
+/// Zeroes and releases a buffer of the library and resets it to null. Null pointers and empty
+/// buffers are ignored.
+///
+/// # Safety
+///
+/// `buffer` must be null or point to a buffer that is empty or was written by the library and
+/// not released yet.
+#[no_mangle]
+pub unsafe extern "C" fn crypto_layer_buffer_free(buffer: *mut CryptoLayerBuffer) {
+    let Some(buffer) = buffer.as_mut() else {
+        return;
+    };
+    if !buffer.data.is_null() {
+        let mut data = Box::from_raw(ptr::slice_from_raw_parts_mut(buffer.data, buffer.len));
+        data.zeroize();
+    }
+    buffer.data = ptr::null_mut();
+    buffer.len = 0;
+}
@@ -0,0 +1,180 @@
+//! The UniFFI interface of the crate for Android (Kotlin) and iOS (Swift) apps.
+//!
+//! `src/crypto_layer.udl` declares the interface and this module implements it: a
+//! `CryptoProvider` object with the operations of the current key, the `KeySpec` dictionary with
+//! the enums of `key_spec`, and `CryptoLayerError`, which the bindings raise as exceptions. It
+//! opens the same security modules as the C API.
+//!
+//! `build.rs` generates the scaffolding that exports the module from the UDL file, which the root
+//! of the crate includes, and the bindings are generated with the `uniffi.toml` of the repository:
+//!
+//! ```sh
+//! cargo build --release --features uniffi,macos
+//! uniffi-bindgen generate src/crypto_layer.udl --language kotlin --out-dir bindings/kotlin
+//! uniffi-bindgen generate src/crypto_layer.udl --language swift --out-dir bindings/swift
+//! ```
+//!
+//! In Kotlin, for example:
+//!
+//! ```kotlin
+//! val provider = CryptoProvider.open("TPM", "device-identity")
+//! provider.createKey("device-identity", KeySpec(KeyAlgorithm.EC_P256, listOf(KeyPurpose.SIGN)))
+//! val signature = provider.sign(data)
+//! ```
+//!
+//! `tests/uniffi_bindings.rs` runs the Python bindings against the mock provider.
+
+use super::c_api::{
+    self, Module, CRYPTO_LAYER_ABI_VERSION, CRYPTO_LAYER_ERROR_INVALID_ARGUMENT,
+    CRYPTO_LAYER_ERROR_PANIC, CRYPTO_LAYER_ERROR_UNSUPPORTED_MODULE,
+};
+pub use crate::common::crypto::key_spec::{AccessControl, KeyAlgorithm, KeyPurpose};
+use crate::common::{
+    crypto::key_spec, error::SecurityModuleError, traits::module_provider::Provider,
+};
+use std::{
+    fmt,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+/// Returns the version of the interface, the `CRYPTO_LAYER_ABI_VERSION` of the C API.
+pub fn abi_version() -> u32 {
+    CRYPTO_LAYER_ABI_VERSION
+}
+
+/// The algorithm, purposes and policy of a key, see `key_spec::KeySpec`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeySpec {
+    pub algorithm: KeyAlgorithm,
+    pub purposes: Vec<KeyPurpose>,
+    pub access: AccessControl,
+    pub exportable: bool,
+}
+
+impl KeySpec {
+    fn to_key_spec(&self, label: &str) -> Result<key_spec::KeySpec, CryptoLayerError> {
+        let builder = key_spec::KeySpec::builder()
+            .algorithm(self.algorithm)
+            .access(self.access)
+            .label(label)
+            .exportable(self.exportable);
+        self.purposes
+            .iter()
+            .fold(builder, |builder, purpose| builder.usage(*purpose))
+            .build()
+            .map_err(|e| CryptoLayerError::InvalidArgument {
+                message: e.to_string(),
+            })
+    }
+}
+
+/// The errors of the interface.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CryptoLayerError {
+    /// An error of the security module, with the stable `SecurityModuleError::code`.
+    Module { code: i32, message: String },
+    /// An argument is not valid, e.g. a key id with unsupported characters.
+    InvalidArgument { message: String },
+    /// The security module is unknown or not compiled into the library.
+    UnsupportedModule { message: String },
+    /// An earlier call panicked while it used the provider.
+    Poisoned { message: String },
+}
+
+impl fmt::Display for CryptoLayerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CryptoLayerError::Module { code, message } => write!(f, "{} ({})", message, code),
+            CryptoLayerError::InvalidArgument { message }
+            | CryptoLayerError::UnsupportedModule { message }
+            | CryptoLayerError::Poisoned { message } => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for CryptoLayerError {}
+
+impl From<SecurityModuleError> for CryptoLayerError {
+    fn from(e: SecurityModuleError) -> Self {
+        CryptoLayerError::Module {
+            code: e.code() as i32,
+            message: e.to_string(),
+        }
+    }
+}
+
+impl From<c_api::Error> for CryptoLayerError {
+    fn from(e: c_api::Error) -> Self {
+        let message = e.message;
+        match e.code {
+            CRYPTO_LAYER_ERROR_INVALID_ARGUMENT => CryptoLayerError::InvalidArgument { message },
+            CRYPTO_LAYER_ERROR_UNSUPPORTED_MODULE => {
+                CryptoLayerError::UnsupportedModule { message }
+            }
+            CRYPTO_LAYER_ERROR_PANIC => CryptoLayerError::Poisoned { message },
+            code => CryptoLayerError::Module { code, message },
+        }
+    }
+}
+
+/// An open security module with its current key.
+#[derive(Debug)]
+pub struct CryptoProvider {
+    provider: Arc<Mutex<dyn Provider>>,
+    module: Module,
+}
+
+impl CryptoProvider {
+    /// Opens and initializes a security module, `"TPM"` for the security module of the platform
+    /// or `"MOCK"` for the in-memory provider of builds with the `test-utils` feature.
+    pub fn open(module: String, key_id: String) -> Result<Self, CryptoLayerError> {
+        let module = Module::from_name(&module)?;
+        let provider = module.open(&key_id)?;
+        Ok(Self { provider, module })
+    }
+
+    /// Creates a key and makes it the current key of the provider.
+    pub fn create_key(&self, key_id: String, spec: KeySpec) -> Result<(), CryptoLayerError> {
+        let config = self.module.config(&spec.to_key_spec(&key_id)?)?;
+        Ok(self.lock()?.create_key(&key_id, config)?)
+    }
+
+    /// Loads an existing key and makes it the current key of the provider.
+    pub fn load_key(&self, key_id: String, spec: KeySpec) -> Result<(), CryptoLayerError> {
+        let config = self.module.config(&spec.to_key_spec(&key_id)?)?;
+        Ok(self.lock()?.load_key(&key_id, config)?)
+    }
+
+    /// Returns the DER encoded SubjectPublicKeyInfo of the current key.
+    pub fn public_key(&self) -> Result<Vec<u8>, CryptoLayerError> {
+        Ok(self.lock()?.key_metadata()?.public_key_der().to_vec())
+    }
+
+    /// Signs `data` with the current key.
+    pub fn sign(&self, data: Vec<u8>) -> Result<Vec<u8>, CryptoLayerError> {
+        Ok(self.lock()?.sign_data(&data)?)
+    }
+
+    /// Verifies `signature` over `data` with the current key.
+    pub fn verify(&self, data: Vec<u8>, signature: Vec<u8>) -> Result<bool, CryptoLayerError> {
+        Ok(self.lock()?.verify_signature(&data, &signature)?)
+    }
+
+    /// Encrypts `data` with the current key.
+    pub fn encrypt(&self, data: Vec<u8>) -> Result<Vec<u8>, CryptoLayerError> {
+        Ok(self.lock()?.encrypt_data(&data)?)
+    }
+
+    /// Decrypts `data` with the current key.
+    pub fn decrypt(&self, data: Vec<u8>) -> Result<Vec<u8>, CryptoLayerError> {
+        Ok(self.lock()?.decrypt_data(&data)?.into_vec())
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, dyn Provider + 'static>, CryptoLayerError> {
+        self.provider
+            .lock()
+            .map_err(|_| CryptoLayerError::Poisoned {
+                message: "The provider was poisoned by an earlier panic".to_owned(),
+            })
+    }
+}
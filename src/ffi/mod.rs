@@ -1,2 +1,9 @@
+pub mod c_api;
 pub mod factory;
+#[cfg(feature = "uniffi")]
+pub mod mobile;
+#[cfg(feature = "node")]
+pub mod node;
 mod provider;
+#[cfg(feature = "python")]
+pub mod python;
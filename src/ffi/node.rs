@@ -0,0 +1,310 @@
+//! A Node-API addon for Node.js and Electron applications.
+//!
+//! The `node` feature makes the `cdylib` of the crate a Node-API addon built with napi-rs.
+//! Node-API is the stable C ABI of Node.js, so one build of the library runs on every Node.js and
+//! Electron version with Node-API 3 without being rebuilt. The JavaScript package in `node/`
+//! loads it and wraps the functions of this module in a `Provider` class:
+//!
+//! ```js
+//! const { Provider } = require('crypto-layer');
+//!
+//! const provider = await Provider.open('TPM', 'app-secrets');
+//! await provider.createKey('app-secrets', { algorithm: 'EcP256', purposes: ['KeyAgreement'] });
+//! const envelope = await provider.envelopeEncrypt(Buffer.from(secret));
+//! ```
+//!
+//! The addon uses the numeric constants of the C API and opens the same security modules. Every
+//! operation returns a promise and runs on the thread pool of libuv as an `AsyncTask`, so a Touch
+//! ID prompt of the Secure Enclave does not block the event loop, and the calls of a provider are
+//! serialized. Errors reject the promise with an `Error` whose `code` is the error code of the C
+//! API.
+//!
+//! napi-rs resolves the Node-API functions in the running process when the addon is loaded
+//! (`dyn-symbols`), so the library does not link against Node.js.
+
+// napi-rs registers the exported functions only outside of unit tests.
+#![cfg_attr(test, allow(dead_code))]
+
+use super::c_api::{self, CryptoLayerKeySpec, Module, CRYPTO_LAYER_ABI_VERSION};
+use crate::common::{
+    crypto::secret::SecretBytes, ecies, error::SecurityModuleError,
+    traits::module_provider::Provider,
+};
+use napi::{
+    bindgen_prelude::{AsyncTask, Buffer, External, ToNapiValue, Uint8Array},
+    Env, JsUnknown, NapiRaw, NapiValue, Task,
+};
+use napi_derive::napi;
+use std::{
+    panic::{self, AssertUnwindSafe},
+    sync::{Arc, Mutex},
+};
+use zeroize::Zeroize;
+
+type Error = c_api::Error;
+
+/// A provider opened by `open`, owned by a JavaScript external.
+struct NodeProvider {
+    provider: Mutex<Option<Arc<Mutex<dyn Provider>>>>,
+    module: Module,
+}
+
+impl NodeProvider {
+    /// Runs `f` with the provider, which fails after `close`.
+    fn with<T>(
+        &self,
+        f: impl FnOnce(&mut dyn Provider) -> Result<T, SecurityModuleError>,
+    ) -> Result<T, Error> {
+        let provider = self
+            .provider
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+            .ok_or_else(|| Error::invalid("The provider is closed"))?;
+        let mut provider = provider.lock().map_err(|_| {
+            Error::new(
+                c_api::CRYPTO_LAYER_ERROR_PANIC,
+                "The provider was poisoned by an earlier panic",
+            )
+        })?;
+        Ok(f(&mut *provider)?)
+    }
+}
+
+/// The result of an operation, converted to a JavaScript value on the main thread.
+enum Output {
+    Undefined,
+    Bool(bool),
+    Bytes(Vec<u8>),
+    Provider(Arc<NodeProvider>),
+}
+
+/// An operation run on the thread pool, which settles the promise of its `AsyncTask`.
+struct Job(Option<Box<dyn FnOnce() -> Result<Output, Error> + Send>>);
+
+impl Job {
+    fn new(job: impl FnOnce() -> Result<Output, Error> + Send + 'static) -> AsyncTask<Self> {
+        AsyncTask::new(Self(Some(Box::new(job))))
+    }
+}
+
+impl Task for Job {
+    type Output = Result<Output, Error>;
+    type JsValue = JsUnknown;
+
+    fn compute(&mut self) -> napi::Result<Self::Output> {
+        let job = self
+            .0
+            .take()
+            .ok_or_else(|| napi::Error::from_reason("The operation already ran"))?;
+        Ok(
+            panic::catch_unwind(AssertUnwindSafe(job)).unwrap_or_else(|_| {
+                Err(Error::new(
+                    c_api::CRYPTO_LAYER_ERROR_PANIC,
+                    "The library panicked",
+                ))
+            }),
+        )
+    }
+
+    fn resolve(&mut self, env: Env, output: Self::Output) -> napi::Result<JsUnknown> {
+        match output {
+            Ok(Output::Undefined) => js_value(env, ()),
+            Ok(Output::Bool(value)) => js_value(env, value),
+            // The `Buffer` takes over the bytes instead of copying them.
+            Ok(Output::Bytes(bytes)) => js_value(env, Buffer::from(bytes)),
+            Ok(Output::Provider(provider)) => js_value(env, External::new(provider)),
+            Err(error) => Err(js_error(env, &error)),
+        }
+    }
+}
+
+/// Converts `value` into a JavaScript value.
+fn js_value(env: Env, value: impl ToNapiValue) -> napi::Result<JsUnknown> {
+    // Safety: the environment is the one of the current call.
+    unsafe { JsUnknown::from_raw(env.raw(), ToNapiValue::to_napi_value(env.raw(), value)?) }
+}
+
+/// Creates an `Error` with the message and the numeric `code` of `error`, which rejects the
+/// promise of the operation.
+fn js_error(env: Env, error: &Error) -> napi::Error {
+    let create = || {
+        let mut object = env.create_error(napi::Error::from_reason(error.message.clone()))?;
+        object.set_named_property("code", env.create_int32(error.code)?)?;
+        // Safety: the object is a value of `env`.
+        Ok(unsafe { JsUnknown::from_raw_unchecked(env.raw(), object.raw()) })
+    };
+    create().map_or_else(|e| e, napi::Error::from)
+}
+
+/// An operation on bytes, e.g. `KeyHandle::sign_data`.
+type Operation = fn(&dyn Provider, &[u8]) -> Result<Vec<u8>, SecurityModuleError>;
+
+/// Runs an operation on `data` with the provider.
+fn operation(
+    provider: &External<Arc<NodeProvider>>,
+    data: Uint8Array,
+    op: Operation,
+) -> AsyncTask<Job> {
+    let provider = Arc::clone(provider);
+    // The thread pool cannot access the `Uint8Array`.
+    let mut data = data.to_vec();
+    Job::new(move || {
+        let result = provider.with(|provider| op(provider, &data));
+        data.zeroize();
+        result.map(Output::Bytes)
+    })
+}
+
+/// `abiVersion(): number`
+#[napi]
+fn abi_version() -> u32 {
+    CRYPTO_LAYER_ABI_VERSION
+}
+
+/// `open(module: string, keyId: string): Promise<external>`
+#[napi(ts_return_type = "Promise<ExternalObject<NodeProvider>>")]
+fn open(module: String, key_id: String) -> AsyncTask<Job> {
+    Job::new(move || {
+        let module = Module::from_name(&module)?;
+        let provider = module.open(&key_id)?;
+        Ok(Output::Provider(Arc::new(NodeProvider {
+            provider: Mutex::new(Some(provider)),
+            module,
+        })))
+    })
+}
+
+/// `close(provider: external): undefined`
+#[napi]
+fn close(provider: External<Arc<NodeProvider>>) {
+    provider
+        .provider
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .take();
+}
+
+/// `createKey(provider, keyId, algorithm, purposes, access, exportable): Promise<undefined>`
+/// and `loadKey` with the same arguments.
+fn key(
+    provider: &External<Arc<NodeProvider>>,
+    key_id: String,
+    spec: CryptoLayerKeySpec,
+    create: bool,
+) -> AsyncTask<Job> {
+    let provider = Arc::clone(provider);
+    Job::new(move || {
+        let spec = spec.to_key_spec(&key_id)?;
+        provider.with(|inner| {
+            let config = provider.module.config(&spec)?;
+            if create {
+                inner.create_key(&key_id, config)
+            } else {
+                inner.load_key(&key_id, config)
+            }
+        })?;
+        Ok(Output::Undefined)
+    })
+}
+
+#[napi(ts_return_type = "Promise<void>")]
+fn create_key(
+    provider: External<Arc<NodeProvider>>,
+    key_id: String,
+    algorithm: u32,
+    purposes: u32,
+    access: u32,
+    exportable: bool,
+) -> AsyncTask<Job> {
+    let spec = CryptoLayerKeySpec {
+        algorithm,
+        purposes,
+        access,
+        exportable,
+    };
+    key(&provider, key_id, spec, true)
+}
+
+#[napi(ts_return_type = "Promise<void>")]
+fn load_key(
+    provider: External<Arc<NodeProvider>>,
+    key_id: String,
+    algorithm: u32,
+    purposes: u32,
+    access: u32,
+    exportable: bool,
+) -> AsyncTask<Job> {
+    let spec = CryptoLayerKeySpec {
+        algorithm,
+        purposes,
+        access,
+        exportable,
+    };
+    key(&provider, key_id, spec, false)
+}
+
+/// `publicKey(provider): Promise<Buffer>`
+#[napi(ts_return_type = "Promise<Buffer>")]
+fn public_key(provider: External<Arc<NodeProvider>>) -> AsyncTask<Job> {
+    let provider = Arc::clone(&provider);
+    Job::new(move || {
+        let der =
+            provider.with(|provider| Ok(provider.key_metadata()?.public_key_der().to_vec()))?;
+        Ok(Output::Bytes(der))
+    })
+}
+
+/// `verify(provider, data, signature): Promise<boolean>`
+#[napi(ts_return_type = "Promise<boolean>")]
+fn verify(
+    provider: External<Arc<NodeProvider>>,
+    data: Uint8Array,
+    signature: Uint8Array,
+) -> AsyncTask<Job> {
+    let provider = Arc::clone(&provider);
+    let data = data.to_vec();
+    let signature = signature.to_vec();
+    Job::new(move || {
+        let valid = provider.with(|provider| provider.verify_signature(&data, &signature))?;
+        Ok(Output::Bool(valid))
+    })
+}
+
+/// `sign(provider, data): Promise<Buffer>`
+#[napi(ts_return_type = "Promise<Buffer>")]
+fn sign(provider: External<Arc<NodeProvider>>, data: Uint8Array) -> AsyncTask<Job> {
+    operation(&provider, data, |provider, data| provider.sign_data(data))
+}
+
+/// `encrypt(provider, data): Promise<Buffer>`
+#[napi(ts_return_type = "Promise<Buffer>")]
+fn encrypt(provider: External<Arc<NodeProvider>>, data: Uint8Array) -> AsyncTask<Job> {
+    operation(&provider, data, |provider, data| {
+        provider.encrypt_data(data)
+    })
+}
+
+/// `decrypt(provider, data): Promise<Buffer>`
+#[napi(ts_return_type = "Promise<Buffer>")]
+fn decrypt(provider: External<Arc<NodeProvider>>, data: Uint8Array) -> AsyncTask<Job> {
+    operation(&provider, data, |provider, data| {
+        provider.decrypt_data(data).map(SecretBytes::into_vec)
+    })
+}
+
+/// `envelopeEncrypt(provider, data): Promise<Buffer>`, see `crypto_layer_envelope_encrypt`.
+#[napi(ts_return_type = "Promise<Buffer>")]
+fn envelope_encrypt(provider: External<Arc<NodeProvider>>, data: Uint8Array) -> AsyncTask<Job> {
+    operation(&provider, data, |provider, data| {
+        ecies::encrypt_for(provider.key_metadata()?.public_key(), data)
+    })
+}
+
+/// `envelopeDecrypt(provider, envelope): Promise<Buffer>`
+#[napi(ts_return_type = "Promise<Buffer>")]
+fn envelope_decrypt(provider: External<Arc<NodeProvider>>, envelope: Uint8Array) -> AsyncTask<Job> {
+    operation(&provider, envelope, |provider, data| {
+        ecies::decrypt(provider, data).map(SecretBytes::into_vec)
+    })
+}
@@ -0,0 +1,263 @@
+//! A Python extension module for scripts of data-science and ops teams.
+//!
+//! The `python` feature adds the extension module `crypto_layer._crypto_layer` to the `cdylib` of
+//! the crate, built with PyO3 for the stable ABI of Python 3.8, so one wheel runs on every later
+//! Python version. maturin builds the wheel from `python/pyproject.toml`, whose package adds the
+//! constants of the C API as enums:
+//!
+//! ```python
+//! from crypto_layer import Algorithm, Provider, Purpose
+//!
+//! with Provider("TPM", "ops-signing") as provider:
+//!     provider.create_key("ops-signing", Algorithm.EC_P256, Purpose.SIGN)
+//!     signature = provider.sign(b"release.tar.gz")
+//! ```
+//!
+//! The module uses the numeric constants of the C API and opens the same security modules.
+//! Operations release the GIL, so a Touch ID prompt of the Secure Enclave does not block other
+//! Python threads, and the calls of a provider are serialized. Errors are raised as
+//! `CryptoLayerError` with the `code` and `message` of the C API, and calls of a closed provider
+//! raise `ValueError`.
+
+use super::c_api::{self, CryptoLayerKeySpec, Module, CRYPTO_LAYER_ABI_VERSION};
+use crate::common::{ecies, traits::module_provider::Provider};
+use pyo3::{
+    create_exception,
+    exceptions::{PyException, PyValueError},
+    prelude::*,
+    types::{PyBytes, PyTuple},
+};
+use std::{
+    panic::{self, AssertUnwindSafe},
+    sync::{Arc, Mutex},
+};
+
+type Error = c_api::Error;
+
+create_exception!(
+    crypto_layer,
+    CryptoLayerError,
+    PyException,
+    "An error of the C API, with its `code` and `message`."
+);
+
+/// Converts `error` into a `CryptoLayerError`.
+fn py_error(py: Python<'_>, error: Error) -> PyErr {
+    let err = CryptoLayerError::new_err(format!("{} ({})", error.message, error.code));
+    let value = err.value(py);
+    let result = value
+        .setattr("code", error.code)
+        .and_then(|()| value.setattr("message", error.message));
+    match result {
+        Ok(()) => err,
+        Err(e) => e,
+    }
+}
+
+/// Runs `f` without the GIL, converting its error and catching its panics.
+fn run<T: Send>(py: Python<'_>, f: impl FnOnce() -> Result<T, Error> + Send) -> PyResult<T> {
+    py.detach(|| {
+        panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|_| {
+            Err(Error::new(
+                c_api::CRYPTO_LAYER_ERROR_PANIC,
+                "The library panicked",
+            ))
+        })
+    })
+    .map_err(|e| py_error(py, e))
+}
+
+/// An open security module with its current key.
+///
+/// `module` is `"TPM"` for the security module of the platform, e.g. the Secure Enclave of a
+/// Mac, or `"MOCK"` for the in-memory provider of libraries built with `test-utils`.
+#[pyclass(name = "Provider", module = "crypto_layer", frozen)]
+struct PyProvider {
+    provider: Mutex<Option<Arc<Mutex<dyn Provider>>>>,
+    module: Module,
+}
+
+impl PyProvider {
+    /// Runs `f` with the provider without the GIL, which fails with `ValueError` after `close`.
+    fn with<T: Send>(
+        &self,
+        py: Python<'_>,
+        f: impl FnOnce(&mut dyn Provider) -> Result<T, Error> + Send,
+    ) -> PyResult<T> {
+        let provider = self
+            .provider
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+            .ok_or_else(|| PyValueError::new_err("The provider is closed"))?;
+        run(py, move || {
+            let mut provider = provider.lock().map_err(|_| {
+                Error::new(
+                    c_api::CRYPTO_LAYER_ERROR_PANIC,
+                    "The provider was poisoned by an earlier panic",
+                )
+            })?;
+            f(&mut *provider)
+        })
+    }
+
+    /// Creates or loads the key `key_id` of `spec`.
+    fn key(
+        &self,
+        py: Python<'_>,
+        key_id: &str,
+        spec: CryptoLayerKeySpec,
+        create: bool,
+    ) -> PyResult<()> {
+        let module = self.module;
+        self.with(py, |provider| {
+            let config = module.config(&spec.to_key_spec(key_id)?)?;
+            if create {
+                provider.create_key(key_id, config)?;
+            } else {
+                provider.load_key(key_id, config)?;
+            }
+            Ok(())
+        })
+    }
+}
+
+#[pymethods]
+impl PyProvider {
+    #[new]
+    fn open(py: Python<'_>, module: &str, key_id: &str) -> PyResult<Self> {
+        run(py, || {
+            let module = Module::from_name(module)?;
+            Ok(Self {
+                provider: Mutex::new(Some(module.open(key_id)?)),
+                module,
+            })
+        })
+    }
+
+    /// Releases the provider. Further calls raise `ValueError`.
+    fn close(&self) {
+        self.provider
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take();
+    }
+
+    fn __enter__(slf: Py<Self>) -> Py<Self> {
+        slf
+    }
+
+    #[pyo3(signature = (*_exc_info))]
+    fn __exit__(&self, _exc_info: &Bound<'_, PyTuple>) {
+        self.close();
+    }
+
+    /// Creates a key and makes it the current key of the provider.
+    #[pyo3(signature = (key_id, algorithm, purposes, access = 0, exportable = false))]
+    fn create_key(
+        &self,
+        py: Python<'_>,
+        key_id: &str,
+        algorithm: u32,
+        purposes: u32,
+        access: u32,
+        exportable: bool,
+    ) -> PyResult<()> {
+        let spec = CryptoLayerKeySpec {
+            algorithm,
+            purposes,
+            access,
+            exportable,
+        };
+        self.key(py, key_id, spec, true)
+    }
+
+    /// Loads an existing key and makes it the current key of the provider.
+    #[pyo3(signature = (key_id, algorithm, purposes, access = 0, exportable = false))]
+    fn load_key(
+        &self,
+        py: Python<'_>,
+        key_id: &str,
+        algorithm: u32,
+        purposes: u32,
+        access: u32,
+        exportable: bool,
+    ) -> PyResult<()> {
+        let spec = CryptoLayerKeySpec {
+            algorithm,
+            purposes,
+            access,
+            exportable,
+        };
+        self.key(py, key_id, spec, false)
+    }
+
+    /// Returns the DER encoded SubjectPublicKeyInfo of the current key.
+    fn public_key<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyBytes>> {
+        let der = self.with(py, |provider| {
+            Ok(provider.key_metadata()?.public_key_der().to_vec())
+        })?;
+        Ok(PyBytes::new(py, &der))
+    }
+
+    /// Signs `data` with the current key.
+    fn sign<'py>(&self, py: Python<'py>, data: &[u8]) -> PyResult<Bound<'py, PyBytes>> {
+        let signature = self.with(py, |provider| Ok(provider.sign_data(data)?))?;
+        Ok(PyBytes::new(py, &signature))
+    }
+
+    /// Returns whether `signature` over `data` is valid for the current key.
+    fn verify(&self, py: Python<'_>, data: &[u8], signature: &[u8]) -> PyResult<bool> {
+        self.with(py, |provider| {
+            Ok(provider.verify_signature(data, signature)?)
+        })
+    }
+
+    /// Encrypts `data` with the current key.
+    fn encrypt<'py>(&self, py: Python<'py>, data: &[u8]) -> PyResult<Bound<'py, PyBytes>> {
+        let ciphertext = self.with(py, |provider| Ok(provider.encrypt_data(data)?))?;
+        Ok(PyBytes::new(py, &ciphertext))
+    }
+
+    /// Decrypts `data` with the current key.
+    fn decrypt<'py>(&self, py: Python<'py>, data: &[u8]) -> PyResult<Bound<'py, PyBytes>> {
+        // The plaintext is zeroed when dropped, after it was copied into the `bytes`.
+        let plaintext = self.with(py, |provider| Ok(provider.decrypt_data(data)?))?;
+        Ok(PyBytes::new(py, &plaintext))
+    }
+
+    /// Encrypts `data` of any size with ECIES for the current EC key, see
+    /// `crypto_layer_envelope_encrypt`.
+    fn envelope_encrypt<'py>(&self, py: Python<'py>, data: &[u8]) -> PyResult<Bound<'py, PyBytes>> {
+        let envelope = self.with(py, |provider| {
+            Ok(ecies::encrypt_for(
+                provider.key_metadata()?.public_key(),
+                data,
+            )?)
+        })?;
+        Ok(PyBytes::new(py, &envelope))
+    }
+
+    /// Decrypts an envelope of `envelope_encrypt` with the current key.
+    fn envelope_decrypt<'py>(
+        &self,
+        py: Python<'py>,
+        envelope: &[u8],
+    ) -> PyResult<Bound<'py, PyBytes>> {
+        let plaintext = self.with(py, |provider| Ok(ecies::decrypt(provider, envelope)?))?;
+        Ok(PyBytes::new(py, &plaintext))
+    }
+}
+
+/// The extension module `crypto_layer._crypto_layer`.
+#[pymodule]
+#[pyo3(name = "_crypto_layer")]
+fn crypto_layer_module(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add("ABI_VERSION", CRYPTO_LAYER_ABI_VERSION)?;
+    module.add(
+        "CryptoLayerError",
+        module.py().get_type::<CryptoLayerError>(),
+    )?;
+    module.add_class::<PyProvider>()?;
+    Ok(())
+}
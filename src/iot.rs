@@ -0,0 +1,352 @@
+//! Mutual-TLS provisioning of devices for AWS IoT Core and Azure IoT Hub.
+//!
+//! Both hubs authenticate devices by X.509 client certificates over MQTT. An `IotDevice` ties
+//! the steps together for a `DeviceIdentity`, whose key never leaves the security module:
+//!
+//! ```rust,ignore
+//! use crypto_layer::iot::{IotDevice, IotHub};
+//!
+//! let identity = Arc::new(DeviceIdentity::open(provider, config)?);
+//! let hub = IotHub::AzureIotHub { host_name: "my-hub.azure-devices.net".to_owned() };
+//! let mut device = IotDevice::new(identity, "device-42", hub)?;
+//!
+//! // Enrollment: the backend or the CA of the fleet issues a certificate for the CSR.
+//! let certificate = enroll(&device.enrollment_csr()?)?;
+//! device.set_certificate_chain_pem(&certificate)?;
+//!
+//! // Connection: the TLS handshake is signed by the identity key.
+//! let config = device.client_config(ClientConfig::builder().with_root_certificates(roots))?;
+//! let connection = device.mqtt_connection();
+//! let stream = TcpStream::connect((connection.host.as_str(), connection.port))?;
+//! ```
+//!
+//! The MQTT client is left to the application, e.g. `rumqttc` with `Transport::tls_with_config`.
+//! The certificate is issued for the current key of the identity, so a device that renewed its
+//! identity has to enroll again.
+//!
+//! TLS 1.3 only allows RSA keys with PSS signatures. Keys with PKCS#1 v1.5 signatures can only
+//! be used if TLS 1.2 is enabled in rustls.
+
+use crate::common::{
+    crypto::algorithms::{
+        encryption::{AsymmetricEncryption, EccCurves, EccSchemeAlgorithm},
+        hashes::{Hash, Sha2Bits},
+    },
+    crypto::public_key::RsaSignaturePadding,
+    csr::{CsrSpec, MAX_COMMON_NAME_LEN},
+    device_identity::DeviceIdentity,
+    error::SecurityModuleError,
+};
+use openssl::x509::X509;
+use rustls::{
+    client::WantsClientCert,
+    pki_types::{CertificateDer, SubjectPublicKeyInfoDer},
+    sign::{CertifiedKey, Signer, SigningKey, SingleCertAndKey},
+    ClientConfig, ConfigBuilder, SignatureAlgorithm, SignatureScheme,
+};
+use std::sync::Arc;
+
+/// The port of MQTT over TLS.
+pub const MQTT_PORT: u16 = 8883;
+
+/// The API version of Azure IoT Hub in the MQTT user name.
+const AZURE_API_VERSION: &str = "2021-04-12";
+
+/// The hub a device connects to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IotHub {
+    /// AWS IoT Core, with the device data endpoint of the account, e.g.
+    /// `a1b2c3d4e5f6g7-ats.iot.eu-central-1.amazonaws.com`. The device id is the thing name.
+    AwsIot {
+        /// The device data endpoint.
+        endpoint: String,
+    },
+    /// Azure IoT Hub, with the host name of the hub, e.g. `my-hub.azure-devices.net`. The
+    /// device id is the id of the device registered with X.509 authentication.
+    AzureIotHub {
+        /// The host name of the hub.
+        host_name: String,
+    },
+}
+
+/// The parameters of the MQTT connection of a device to its hub.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MqttConnection {
+    /// The host name, which is also the server name of the TLS handshake.
+    pub host: String,
+    /// The port, always `MQTT_PORT`.
+    pub port: u16,
+    /// The client id of the MQTT `CONNECT` packet.
+    pub client_id: String,
+    /// The user name of the MQTT `CONNECT` packet, required by Azure IoT Hub.
+    pub username: Option<String>,
+}
+
+/// A device connecting to an IoT hub with its identity key, see the module documentation.
+#[derive(Debug)]
+pub struct IotDevice {
+    identity: Arc<DeviceIdentity>,
+    device_id: String,
+    hub: IotHub,
+    certificate_chain: Vec<CertificateDer<'static>>,
+}
+
+impl IotDevice {
+    /// Creates a device without a certificate.
+    ///
+    /// # Arguments
+    ///
+    /// * `identity` - The identity whose key authenticates the device.
+    /// * `device_id` - The id of the device at the hub, which is also the common name of its
+    ///   certificate.
+    /// * `hub` - The hub the device connects to.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `IotDevice`, a `SecurityModuleError::InitializationError` if
+    /// the device id is empty, longer than `MAX_COMMON_NAME_LEN` or contains characters other
+    /// than ASCII letters, digits, `-`, `_` and `:`, which both hubs accept, or a
+    /// `SecurityModuleError::UnsupportedAlgorithm` if the identity key cannot sign TLS
+    /// handshakes.
+    pub fn new(
+        identity: Arc<DeviceIdentity>,
+        device_id: &str,
+        hub: IotHub,
+    ) -> Result<Self, SecurityModuleError> {
+        let is_valid = |c: char| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | ':');
+        if device_id.is_empty()
+            || device_id.len() > MAX_COMMON_NAME_LEN
+            || !device_id.chars().all(is_valid)
+        {
+            return Err(SecurityModuleError::InitializationError(format!(
+                "The device id {:?} is not valid for IoT hubs",
+                device_id
+            )));
+        }
+        signature_scheme(&identity)?;
+        Ok(Self {
+            identity,
+            device_id: device_id.to_owned(),
+            hub,
+            certificate_chain: Vec::new(),
+        })
+    }
+
+    /// Returns the id of the device at the hub.
+    pub fn device_id(&self) -> &str {
+        &self.device_id
+    }
+
+    /// Returns the identity of the device.
+    pub fn identity(&self) -> &Arc<DeviceIdentity> {
+        &self.identity
+    }
+
+    /// Returns the DER encoded certificate chain of the device, starting with its certificate,
+    /// or an empty chain before enrollment.
+    pub fn certificate_chain(&self) -> &[CertificateDer<'static>] {
+        &self.certificate_chain
+    }
+
+    /// Creates the CSR for the certificate of the device, with the device id as common name.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the DER encoded CSR, or the error of `DeviceIdentity::create_csr`.
+    pub fn enrollment_csr(&self) -> Result<Vec<u8>, SecurityModuleError> {
+        self.identity
+            .create_csr(&CsrSpec::new().common_name(self.device_id.clone()))
+    }
+
+    /// Sets the certificate chain issued for the device, starting with its certificate and
+    /// optionally followed by intermediate certificates.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` that is `Ok(())` if the chain was set, a
+    /// `SecurityModuleError::InvalidPublicKey` if the chain is empty or its first certificate is
+    /// malformed or was issued for another key than the identity key.
+    pub fn set_certificate_chain(
+        &mut self,
+        certificate_chain: Vec<Vec<u8>>,
+    ) -> Result<(), SecurityModuleError> {
+        let leaf = certificate_chain
+            .first()
+            .ok_or(SecurityModuleError::InvalidPublicKey)?;
+        let public_key = X509::from_der(leaf)
+            .and_then(|certificate| certificate.public_key())
+            .and_then(|public_key| public_key.public_key_to_der())
+            .map_err(|_| SecurityModuleError::InvalidPublicKey)?;
+        if public_key != self.identity.metadata().public_key_der() {
+            return Err(SecurityModuleError::InvalidPublicKey);
+        }
+        self.certificate_chain = certificate_chain
+            .into_iter()
+            .map(CertificateDer::from)
+            .collect();
+        Ok(())
+    }
+
+    /// Sets the certificate chain from PEM, e.g. as returned by AWS IoT Core or a CA, see
+    /// `set_certificate_chain`.
+    pub fn set_certificate_chain_pem(&mut self, pem: &[u8]) -> Result<(), SecurityModuleError> {
+        let certificate_chain = X509::stack_from_pem(pem)
+            .and_then(|certificates| certificates.iter().map(|c| c.to_der()).collect())
+            .map_err(|_| SecurityModuleError::InvalidPublicKey)?;
+        self.set_certificate_chain(certificate_chain)
+    }
+
+    /// Returns the certificate chain and the identity key for rustls, e.g. for a custom
+    /// `ResolvesClientCert`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `CertifiedKey`, or a
+    /// `SecurityModuleError::InitializationError` if no certificate chain was set.
+    pub fn certified_key(&self) -> Result<CertifiedKey, SecurityModuleError> {
+        if self.certificate_chain.is_empty() {
+            return Err(SecurityModuleError::InitializationError(
+                "The device has no certificate, enroll it first".to_owned(),
+            ));
+        }
+        let signing_key = IdentitySigningKey::new(Arc::clone(&self.identity))?;
+        Ok(CertifiedKey::new(
+            self.certificate_chain.clone(),
+            Arc::new(signing_key),
+        ))
+    }
+
+    /// Completes `builder`, which sets the crypto provider and the root certificates of the
+    /// hub, with the client certificate of the device.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `ClientConfig`, or the error of `certified_key`.
+    pub fn client_config(
+        &self,
+        builder: ConfigBuilder<ClientConfig, WantsClientCert>,
+    ) -> Result<ClientConfig, SecurityModuleError> {
+        let resolver = SingleCertAndKey::from(self.certified_key()?);
+        Ok(builder.with_client_cert_resolver(Arc::new(resolver)))
+    }
+
+    /// Returns the parameters of the MQTT connection to the hub.
+    pub fn mqtt_connection(&self) -> MqttConnection {
+        match &self.hub {
+            IotHub::AwsIot { endpoint } => MqttConnection {
+                host: endpoint.clone(),
+                port: MQTT_PORT,
+                client_id: self.device_id.clone(),
+                username: None,
+            },
+            IotHub::AzureIotHub { host_name } => MqttConnection {
+                host: host_name.clone(),
+                port: MQTT_PORT,
+                client_id: self.device_id.clone(),
+                username: Some(format!(
+                    "{}/{}/?api-version={}",
+                    host_name, self.device_id, AZURE_API_VERSION
+                )),
+            },
+        }
+    }
+}
+
+/// A rustls `SigningKey` that signs TLS handshakes with the current key of a `DeviceIdentity`.
+#[derive(Debug)]
+pub struct IdentitySigningKey {
+    identity: Arc<DeviceIdentity>,
+    scheme: SignatureScheme,
+    algorithm: SignatureAlgorithm,
+}
+
+impl IdentitySigningKey {
+    /// Wraps the identity key.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `IdentitySigningKey`, or a
+    /// `SecurityModuleError::UnsupportedAlgorithm` if the key has no TLS signature scheme.
+    /// ECDSA keys must use the hash of their curve, since TLS 1.3 fixes it.
+    pub fn new(identity: Arc<DeviceIdentity>) -> Result<Self, SecurityModuleError> {
+        let (scheme, algorithm) = signature_scheme(&identity)?;
+        Ok(Self {
+            identity,
+            scheme,
+            algorithm,
+        })
+    }
+}
+
+impl SigningKey for IdentitySigningKey {
+    fn choose_scheme(&self, offered: &[SignatureScheme]) -> Option<Box<dyn Signer>> {
+        offered.contains(&self.scheme).then(|| {
+            Box::new(IdentitySigner {
+                identity: Arc::clone(&self.identity),
+                scheme: self.scheme,
+            }) as Box<dyn Signer>
+        })
+    }
+
+    fn public_key(&self) -> Option<SubjectPublicKeyInfoDer<'_>> {
+        Some(SubjectPublicKeyInfoDer::from(
+            self.identity.metadata().public_key_der(),
+        ))
+    }
+
+    fn algorithm(&self) -> SignatureAlgorithm {
+        self.algorithm
+    }
+}
+
+#[derive(Debug)]
+struct IdentitySigner {
+    identity: Arc<DeviceIdentity>,
+    scheme: SignatureScheme,
+}
+
+impl Signer for IdentitySigner {
+    fn sign(&self, message: &[u8]) -> Result<Vec<u8>, rustls::Error> {
+        self.identity.sign(message).map_err(|e| {
+            tracing::warn!(error = %e, "tls handshake signature failed");
+            rustls::Error::General(e.to_string())
+        })
+    }
+
+    fn scheme(&self) -> SignatureScheme {
+        self.scheme
+    }
+}
+
+fn signature_scheme(
+    identity: &DeviceIdentity,
+) -> Result<(SignatureScheme, SignatureAlgorithm), SecurityModuleError> {
+    let public_key = identity.public_key();
+    match (public_key.algorithm(), public_key.hash()) {
+        (AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(EccCurves::Curve25519)), _) => {
+            Ok((SignatureScheme::ED25519, SignatureAlgorithm::ED25519))
+        }
+        (AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(curve)), Hash::Sha2(bits)) => {
+            let scheme = match (curve, bits) {
+                (EccCurves::P256, Sha2Bits::Sha256) => SignatureScheme::ECDSA_NISTP256_SHA256,
+                (EccCurves::P384, Sha2Bits::Sha384) => SignatureScheme::ECDSA_NISTP384_SHA384,
+                (EccCurves::P521, Sha2Bits::Sha512) => SignatureScheme::ECDSA_NISTP521_SHA512,
+                _ => return Err(SecurityModuleError::UnsupportedAlgorithm),
+            };
+            Ok((scheme, SignatureAlgorithm::ECDSA))
+        }
+        (AsymmetricEncryption::Rsa(_), Hash::Sha2(bits)) => {
+            let scheme = match (public_key.rsa_padding(), bits) {
+                (RsaSignaturePadding::Pss, Sha2Bits::Sha256) => SignatureScheme::RSA_PSS_SHA256,
+                (RsaSignaturePadding::Pss, Sha2Bits::Sha384) => SignatureScheme::RSA_PSS_SHA384,
+                (RsaSignaturePadding::Pss, Sha2Bits::Sha512) => SignatureScheme::RSA_PSS_SHA512,
+                (RsaSignaturePadding::Pkcs1, Sha2Bits::Sha256) => SignatureScheme::RSA_PKCS1_SHA256,
+                (RsaSignaturePadding::Pkcs1, Sha2Bits::Sha384) => SignatureScheme::RSA_PKCS1_SHA384,
+                (RsaSignaturePadding::Pkcs1, Sha2Bits::Sha512) => SignatureScheme::RSA_PKCS1_SHA512,
+                _ => return Err(SecurityModuleError::UnsupportedAlgorithm),
+            };
+            Ok((scheme, SignatureAlgorithm::RSA))
+        }
+        _ => Err(SecurityModuleError::UnsupportedAlgorithm),
+    }
+}
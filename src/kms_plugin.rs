@@ -0,0 +1,545 @@
+//! A Kubernetes KMS v2 plugin that encrypts with keys of the security module.
+//!
+//! The kube-apiserver encrypts secrets at rest under data encryption keys, which it has a KMS
+//! plugin encrypt through the gRPC service `v2.KeyManagementService` on a Unix domain socket.
+//! `KmsPlugin` implements this service with a key of the TPM or an HSM, so the secrets of the
+//! cluster can only be decrypted by a node that holds the key in its hardware:
+//!
+//! ```rust,ignore
+//! use crypto_layer::kms_plugin::KmsPlugin;
+//!
+//! let plugin = KmsPlugin::new(Arc::new(Mutex::new(provider)))?;
+//! Arc::new(plugin).listen("/var/run/kmsplugin/socket.sock").await?;
+//! ```
+//!
+//! The `EncryptionConfiguration` of the kube-apiserver then names the socket:
+//!
+//! ```yaml
+//! resources:
+//!   - resources: ["secrets"]
+//!     providers:
+//!       - kms:
+//!           apiVersion: v2
+//!           name: crypto-layer
+//!           endpoint: unix:///var/run/kmsplugin/socket.sock
+//!       - identity: {}
+//! ```
+//!
+//! The data encryption keys are encrypted with `encrypt_data` of the provider, which needs an
+//! RSA key on the TPM. The key id reported to the kube-apiserver combines the id of the key with
+//! a fingerprint of its public key, so the kube-apiserver notices when the key was replaced and
+//! encrypts new data encryption keys. Replaced keys are added with `add_previous_key` to decrypt
+//! the secrets that were written under them until the secrets are rewritten. The `kms-plugin`
+//! binary serves a key of the configured provider.
+
+use crate::common::{
+    crypto::secret::SecretBytes,
+    error::SecurityModuleError,
+    grpc::{
+        self, fields, put_entry, put_field, string, GrpcStatus, Service,
+        GRPC_UNIMPLEMENTED,
+    },
+    traits::module_provider::Provider,
+};
+use crypto_layer_core::CoreError;
+use openssl::sha::sha256;
+use std::{
+    collections::BTreeMap,
+    fmt, io,
+    sync::{Arc, Mutex, MutexGuard},
+};
+use tokio::io::{AsyncRead, AsyncWrite};
+
+/// The version of the KMS API, reported by `Status`.
+pub const API_VERSION: &str = "v2";
+
+/// The longest gRPC message the plugin accepts in bytes.
+pub const MAX_MESSAGE_LEN: usize = 64 * 1024;
+
+/// The kube-apiserver rejects longer key ids and ciphertexts.
+const MAX_KEY_ID_LEN: usize = 1024;
+const MAX_CIPHERTEXT_LEN: usize = 1024;
+
+const SERVICE_PATH: &str = "/v2.KeyManagementService/";
+const HEALTH_PROBE: &[u8] = b"crypto-layer kms plugin health probe";
+
+/// The response of `Status`, which the kube-apiserver polls to check the health of the plugin
+/// and to learn the current key id.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StatusResponse {
+    pub version: String,
+    /// `ok` if the plugin is healthy, otherwise the reason why it is not.
+    pub healthz: String,
+    pub key_id: String,
+}
+
+/// A request to encrypt a data encryption key.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct EncryptRequest {
+    pub plaintext: Vec<u8>,
+    /// A unique id of the request, for logging.
+    pub uid: String,
+}
+
+/// The encrypted data encryption key and the id of the key that encrypted it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EncryptResponse {
+    pub ciphertext: Vec<u8>,
+    pub key_id: String,
+    /// Stored by the kube-apiserver next to the ciphertext and passed back to `Decrypt`.
+    pub annotations: BTreeMap<String, Vec<u8>>,
+}
+
+/// A request to decrypt a data encryption key, with the key id and annotations of its
+/// `EncryptResponse`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DecryptRequest {
+    pub ciphertext: Vec<u8>,
+    /// A unique id of the request, for logging.
+    pub uid: String,
+    pub key_id: String,
+    pub annotations: BTreeMap<String, Vec<u8>>,
+}
+
+/// The decrypted data encryption key.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct DecryptResponse {
+    pub plaintext: Vec<u8>,
+}
+
+impl StatusResponse {
+    /// Encodes the response as protocol buffer.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        put_field(&mut bytes, 1, self.version.as_bytes());
+        put_field(&mut bytes, 2, self.healthz.as_bytes());
+        put_field(&mut bytes, 3, self.key_id.as_bytes());
+        bytes
+    }
+
+    /// Decodes a response encoded as protocol buffer.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the response, or a `SecurityModuleError::Encoding` if `bytes` is
+    /// not a valid message.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SecurityModuleError> {
+        let mut response = Self::default();
+        for (field, value) in fields(bytes)? {
+            match field {
+                1 => response.version = string(value)?,
+                2 => response.healthz = string(value)?,
+                3 => response.key_id = string(value)?,
+                _ => {}
+            }
+        }
+        Ok(response)
+    }
+}
+
+impl EncryptRequest {
+    /// Encodes the request as protocol buffer.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        put_field(&mut bytes, 1, &self.plaintext);
+        put_field(&mut bytes, 2, self.uid.as_bytes());
+        bytes
+    }
+
+    /// Decodes a request encoded as protocol buffer.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the request, or a `SecurityModuleError::Encoding` if `bytes` is
+    /// not a valid message.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SecurityModuleError> {
+        let mut request = Self::default();
+        for (field, value) in fields(bytes)? {
+            match field {
+                1 => request.plaintext = grpc::bytes(value)?.to_vec(),
+                2 => request.uid = string(value)?,
+                _ => {}
+            }
+        }
+        Ok(request)
+    }
+}
+
+impl EncryptResponse {
+    /// Encodes the response as protocol buffer.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        put_field(&mut bytes, 1, &self.ciphertext);
+        put_field(&mut bytes, 2, self.key_id.as_bytes());
+        put_map(&mut bytes, 3, &self.annotations);
+        bytes
+    }
+
+    /// Decodes a response encoded as protocol buffer.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the response, or a `SecurityModuleError::Encoding` if `bytes` is
+    /// not a valid message.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SecurityModuleError> {
+        let mut response = Self::default();
+        for (field, value) in fields(bytes)? {
+            match field {
+                1 => response.ciphertext = grpc::bytes(value)?.to_vec(),
+                2 => response.key_id = string(value)?,
+                3 => map_entry(&mut response.annotations, grpc::bytes(value)?)?,
+                _ => {}
+            }
+        }
+        Ok(response)
+    }
+}
+
+impl DecryptRequest {
+    /// Encodes the request as protocol buffer.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        put_field(&mut bytes, 1, &self.ciphertext);
+        put_field(&mut bytes, 2, self.uid.as_bytes());
+        put_field(&mut bytes, 3, self.key_id.as_bytes());
+        put_map(&mut bytes, 4, &self.annotations);
+        bytes
+    }
+
+    /// Decodes a request encoded as protocol buffer.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the request, or a `SecurityModuleError::Encoding` if `bytes` is
+    /// not a valid message.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SecurityModuleError> {
+        let mut request = Self::default();
+        for (field, value) in fields(bytes)? {
+            match field {
+                1 => request.ciphertext = grpc::bytes(value)?.to_vec(),
+                2 => request.uid = string(value)?,
+                3 => request.key_id = string(value)?,
+                4 => map_entry(&mut request.annotations, grpc::bytes(value)?)?,
+                _ => {}
+            }
+        }
+        Ok(request)
+    }
+}
+
+impl DecryptResponse {
+    /// Encodes the response as protocol buffer.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        put_field(&mut bytes, 1, &self.plaintext);
+        bytes
+    }
+
+    /// Decodes a response encoded as protocol buffer.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the response, or a `SecurityModuleError::Encoding` if `bytes` is
+    /// not a valid message.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SecurityModuleError> {
+        let mut response = Self::default();
+        for (field, value) in fields(bytes)? {
+            if field == 1 {
+                response.plaintext = grpc::bytes(value)?.to_vec();
+            }
+        }
+        Ok(response)
+    }
+}
+
+// The plaintexts are data encryption keys and must not end up in logs.
+impl fmt::Debug for EncryptRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EncryptRequest")
+            .field("uid", &self.uid)
+            .finish_non_exhaustive()
+    }
+}
+
+impl fmt::Debug for DecryptResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DecryptResponse").finish_non_exhaustive()
+    }
+}
+
+/// A Kubernetes KMS v2 plugin, see the module documentation.
+pub struct KmsPlugin {
+    current: PluginKey,
+    previous: Vec<PluginKey>,
+}
+
+struct PluginKey {
+    provider: Arc<Mutex<dyn Provider>>,
+    key_id: String,
+    public_key_der: Vec<u8>,
+}
+
+impl KmsPlugin {
+    /// Creates a plugin that encrypts with the loaded key of `provider`.
+    ///
+    /// # Arguments
+    ///
+    /// * `provider` - The provider with the loaded key, which should not be used to load other
+    ///   keys while the plugin runs.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the plugin, or the error of `provider` if its key metadata cannot
+    /// be read.
+    pub fn new(provider: Arc<Mutex<dyn Provider>>) -> Result<Self, SecurityModuleError> {
+        Ok(Self {
+            current: PluginKey::new(provider)?,
+            previous: Vec::new(),
+        })
+    }
+
+    /// Adds the loaded key of `provider`, which the plugin used before its current key, to
+    /// decrypt data encryption keys that were encrypted under it.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` that is `Ok(())` if the key was added, or the error of `provider` if its key
+    /// metadata cannot be read.
+    pub fn add_previous_key(
+        &mut self,
+        provider: Arc<Mutex<dyn Provider>>,
+    ) -> Result<(), SecurityModuleError> {
+        self.previous.push(PluginKey::new(provider)?);
+        Ok(())
+    }
+
+    /// Returns the key id of the current key, e.g. `kms-key@4f1c...`.
+    pub fn key_id(&self) -> &str {
+        &self.current.key_id
+    }
+
+    /// Answers `Status`, after encrypting and decrypting a probe with the current key.
+    pub fn status(&self) -> StatusResponse {
+        let healthz = match self.health_check() {
+            Ok(()) => "ok".to_owned(),
+            Err(e) => {
+                tracing::warn!(error = %e, "kms plugin health check failed");
+                e.to_string()
+            }
+        };
+        StatusResponse {
+            version: API_VERSION.to_owned(),
+            healthz,
+            key_id: self.current.key_id.clone(),
+        }
+    }
+
+    /// Answers `Encrypt` with the current key.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the response, a `SecurityModuleError::Encoding` if the plaintext is
+    /// empty, a `SecurityModuleError::KeyError` if the provider has loaded another key, or the
+    /// error of `encrypt_data`.
+    pub fn encrypt(
+        &self,
+        request: &EncryptRequest,
+    ) -> Result<EncryptResponse, SecurityModuleError> {
+        if request.plaintext.is_empty() {
+            return Err(SecurityModuleError::Encoding(CoreError::MissingField(
+                "plaintext",
+            )));
+        }
+        let ciphertext = self.current.encrypt(&request.plaintext)?;
+        if ciphertext.len() > MAX_CIPHERTEXT_LEN {
+            return Err(SecurityModuleError::EncryptionError(format!(
+                "The ciphertext is longer than {} bytes",
+                MAX_CIPHERTEXT_LEN
+            )));
+        }
+        Ok(EncryptResponse {
+            ciphertext,
+            key_id: self.current.key_id.clone(),
+            annotations: BTreeMap::new(),
+        })
+    }
+
+    /// Answers `Decrypt` with the current or a previous key, whichever has the key id of the
+    /// request.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the response, a `SecurityModuleError::Encoding` if the ciphertext
+    /// is empty, a `SecurityModuleError::KeyError` if no key has the key id or the provider has
+    /// loaded another key, or the error of `decrypt_data`.
+    pub fn decrypt(
+        &self,
+        request: &DecryptRequest,
+    ) -> Result<DecryptResponse, SecurityModuleError> {
+        if request.ciphertext.is_empty() {
+            return Err(SecurityModuleError::Encoding(CoreError::MissingField(
+                "ciphertext",
+            )));
+        }
+        let key = std::iter::once(&self.current)
+            .chain(&self.previous)
+            .find(|key| key.key_id == request.key_id)
+            .ok_or(SecurityModuleError::KeyError)?;
+        Ok(DecryptResponse {
+            plaintext: key.decrypt(&request.ciphertext)?,
+        })
+    }
+
+    /// Serves the calls of one connected kube-apiserver until it disconnects.
+    ///
+    /// Every call is answered on its own task, and the security module is used on the blocking
+    /// threads of the runtime, so `serve` must be called on a Tokio runtime.
+    ///
+    /// # Returns
+    ///
+    /// An `io::Result` that is `Ok(())` when the client disconnected, or the error of the
+    /// HTTP/2 connection.
+    pub async fn serve(self: Arc<Self>, io: impl AsyncRead + AsyncWrite + Unpin) -> io::Result<()> {
+        grpc::serve(self, io).await
+    }
+
+    /// Listens on the Unix domain socket `path`, which must not exist, and serves every client
+    /// on its own task.
+    ///
+    /// The socket is made accessible to the current user only. This function only returns when
+    /// the socket cannot be created or accepting a connection fails.
+    #[cfg(unix)]
+    pub async fn listen(self: Arc<Self>, path: impl AsRef<std::path::Path>) -> io::Result<()> {
+        use std::{fs, os::unix::fs::PermissionsExt};
+
+        let listener = tokio::net::UnixListener::bind(&path)?;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600))?;
+        loop {
+            let (stream, _) = listener.accept().await?;
+            let plugin = Arc::clone(&self);
+            tokio::spawn(async move {
+                if let Err(e) = plugin.serve(stream).await {
+                    tracing::warn!(error = %e, "kms plugin connection failed");
+                }
+            });
+        }
+    }
+
+    fn health_check(&self) -> Result<(), SecurityModuleError> {
+        let ciphertext = self.current.encrypt(HEALTH_PROBE)?;
+        if self.current.decrypt(&ciphertext)? != HEALTH_PROBE {
+            return Err(SecurityModuleError::DecryptionError(
+                "The health probe was not decrypted to itself".to_owned(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Service for KmsPlugin {
+    const PATH: &'static str = SERVICE_PATH;
+    const NAME: &'static str = "kms plugin";
+    const MAX_MESSAGE_LEN: usize = MAX_MESSAGE_LEN;
+
+    fn call(&self, method: &str, request: &[u8]) -> Result<Vec<u8>, GrpcStatus> {
+        match method {
+            "Status" => Ok(self.status().to_bytes()),
+            "Encrypt" => {
+                let request = EncryptRequest::from_bytes(request)?;
+                tracing::debug!(uid = %request.uid, "kms plugin encrypt");
+                Ok(self.encrypt(&request)?.to_bytes())
+            }
+            "Decrypt" => {
+                let request = DecryptRequest::from_bytes(request)?;
+                tracing::debug!(uid = %request.uid, key_id = %request.key_id, "kms plugin decrypt");
+                Ok(self.decrypt(&request)?.to_bytes())
+            }
+            _ => Err(GrpcStatus::new(
+                GRPC_UNIMPLEMENTED,
+                format!("Unknown method {}", method),
+            )),
+        }
+    }
+}
+
+impl fmt::Debug for KmsPlugin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let previous: Vec<&str> = self
+            .previous
+            .iter()
+            .map(|key| key.key_id.as_str())
+            .collect();
+        f.debug_struct("KmsPlugin")
+            .field("key_id", &self.current.key_id)
+            .field("previous", &previous)
+            .finish()
+    }
+}
+
+impl PluginKey {
+    fn new(provider: Arc<Mutex<dyn Provider>>) -> Result<Self, SecurityModuleError> {
+        let metadata = lock(&provider).key_metadata()?;
+        let fingerprint: String = sha256(metadata.public_key_der())[..16]
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        let key_id = format!("{}@{}", metadata.key_id(), fingerprint);
+        if key_id.len() > MAX_KEY_ID_LEN {
+            return Err(SecurityModuleError::InvalidKeyId(format!(
+                "The key id is longer than {} bytes",
+                MAX_KEY_ID_LEN
+            )));
+        }
+        Ok(Self {
+            provider,
+            key_id,
+            public_key_der: metadata.public_key_der().to_vec(),
+        })
+    }
+
+    fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>, SecurityModuleError> {
+        self.loaded()?.encrypt_data(plaintext)
+    }
+
+    fn decrypt(&self, ciphertext: &[u8]) -> Result<Vec<u8>, SecurityModuleError> {
+        self.loaded()?
+            .decrypt_data(ciphertext)
+            .map(SecretBytes::into_vec)
+    }
+
+    /// Locks the provider, which may have loaded another key since it was added.
+    fn loaded(&self) -> Result<MutexGuard<'_, dyn Provider + 'static>, SecurityModuleError> {
+        let provider = lock(&self.provider);
+        if provider.key_metadata()?.public_key_der() != self.public_key_der.as_slice() {
+            return Err(SecurityModuleError::KeyError);
+        }
+        Ok(provider)
+    }
+}
+
+/// Writes a `map<string, bytes>` field as its repeated entries.
+fn put_map(bytes: &mut Vec<u8>, field: u64, map: &BTreeMap<String, Vec<u8>>) {
+    for (key, value) in map {
+        let mut entry = Vec::new();
+        put_field(&mut entry, 1, key.as_bytes());
+        put_field(&mut entry, 2, value);
+        put_entry(bytes, field, &entry);
+    }
+}
+
+fn map_entry(map: &mut BTreeMap<String, Vec<u8>>, entry: &[u8]) -> Result<(), SecurityModuleError> {
+    let (mut key, mut value) = (String::new(), Vec::new());
+    for (field, field_value) in fields(entry)? {
+        match field {
+            1 => key = string(field_value)?,
+            2 => value = grpc::bytes(field_value)?.to_vec(),
+            _ => {}
+        }
+    }
+    map.insert(key, value);
+    Ok(())
+}
+
+fn lock<'a>(provider: &'a Mutex<dyn Provider + 'static>) -> MutexGuard<'a, dyn Provider + 'static> {
+    provider.lock().unwrap_or_else(|e| e.into_inner())
+}
@@ -1,8 +1,33 @@
+// The providers need `std` and a security module, web frontends verify their artifacts with
+// the `wasm` feature of `crypto-layer-core`.
+#[cfg(target_arch = "wasm32")]
+compile_error!("crypto-layer does not support wasm32, use crypto-layer-core with the `wasm` feature");
+
+#[cfg(feature = "cli")]
+pub mod cli;
 pub mod common;
+#[cfg(feature = "daemon")]
+pub mod daemon;
 #[cfg(feature = "ffi")]
 pub mod ffi;
 #[cfg(feature = "hsm")]
 pub mod hsm;
+#[cfg(feature = "iot")]
+pub mod iot;
+#[cfg(feature = "kms-plugin")]
+pub mod kms_plugin;
+#[cfg(feature = "messaging")]
+pub mod messaging;
+#[cfg(feature = "test-utils")]
+pub mod mock;
+#[cfg(feature = "test-utils")]
+pub mod provider_conformance;
+#[cfg(feature = "remote")]
+pub mod remote;
+#[cfg(feature = "ssh-agent")]
+pub mod ssh_agent;
+#[cfg(feature = "test-utils")]
+pub mod test_vectors;
 #[cfg(test)]
 mod tests;
 #[cfg(feature = "tpm")]
@@ -13,3 +38,11 @@ pub mod nks;
 pub use common::{error::SecurityModuleError, factory::SecModules};
 #[cfg(feature = "ffi")]
 pub use ffi::factory::{secmodules_free_instance, secmodules_get_instance};
+
+// The scaffolding of `src/crypto_layer.udl`, which UniFFI expects at the root of the crate.
+#[cfg(feature = "uniffi")]
+use ffi::mobile::{
+    abi_version, AccessControl, CryptoLayerError, CryptoProvider, KeyAlgorithm, KeyPurpose, KeySpec,
+};
+#[cfg(feature = "uniffi")]
+uniffi::include_scaffolding!("crypto_layer");
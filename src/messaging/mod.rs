@@ -0,0 +1,449 @@
+//! End-to-end encrypted sessions between devices with the Double Ratchet of Signal.
+//!
+//! The long-term identity key of every device stays in the security module and only signs: the
+//! responder signs a prekey, and the initiator signs the handshake. All X25519 keys of the key
+//! agreement and the ratchet are ephemeral software keys, so every signing key of the security
+//! module, e.g. a P-256 key of the Secure Enclave, can serve as identity key:
+//!
+//! ```rust,ignore
+//! use crypto_layer::messaging::{Prekey, Session};
+//!
+//! // Bob publishes a prekey signed by his identity key.
+//! let prekey = Prekey::generate(&bob_provider)?;
+//! publish(prekey.bundle().to_bytes());
+//!
+//! // Alice starts a session with the bundle and the identity key she trusts for Bob.
+//! let (mut alice, init) = Session::initiate(&alice_provider, &bundle, &bob_identity)?;
+//! let message = alice.encrypt(b"hello", b"")?;
+//! send(init.to_bytes(), message);
+//!
+//! // Bob accepts the session with the identity key he trusts for Alice.
+//! let mut bob = Session::accept(&prekey, &init, &alice_identity)?;
+//! let plaintext = bob.decrypt(&message, b"")?;
+//! ```
+//!
+//! The key agreement derives the first root key from the X25519 shared secret of an ephemeral
+//! key of the initiator and the prekey, bound to both identity keys. The Double Ratchet then
+//! derives a new key for every message, so a compromised session key reveals neither earlier
+//! nor, after the next reply, later messages. Messages may arrive out of order; the keys of up
+//! to `MAX_SKIPPED_MESSAGES` missing messages are kept.
+//!
+//! X25519 is not approved, so prekeys and sessions are refused in FIPS mode, see `fips`.
+//!
+//! Sessions and prekeys are kept in memory. A prekey may be used by several initiators, so it
+//! should be replaced regularly, e.g. daily.
+
+mod ratchet;
+
+use crate::common::{
+    crypto::{kdf::Kdf, public_key::PublicKey},
+    error::SecurityModuleError,
+    fips,
+    traits::module_provider::Provider,
+};
+use openssl::{
+    pkey::{PKey, Private},
+    sha::sha256,
+};
+use ratchet::{Ratchet, KEY_LEN};
+use std::fmt;
+
+pub use ratchet::MAX_SKIPPED_MESSAGES;
+
+/// Separates signatures of prekeys from other signatures of the identity keys.
+pub const PREKEY_DOMAIN: &[u8] = b"crypto-layer/messaging/prekey/v1\n";
+
+/// Separates signatures of handshakes from other signatures of the identity keys.
+pub const HANDSHAKE_DOMAIN: &[u8] = b"crypto-layer/messaging/handshake/v1\n";
+
+const VERSION: u8 = 1;
+const SESSION_INFO: &[u8] = b"crypto-layer/messaging/session/v1";
+
+/// A prekey of the responder, signed by its identity key, which initiators start sessions with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrekeyBundle {
+    identity_public_key: Vec<u8>,
+    prekey: [u8; KEY_LEN],
+    signature: Vec<u8>,
+}
+
+impl PrekeyBundle {
+    /// Returns the DER encoded identity key the bundle claims to be signed with, e.g. to look up
+    /// the trusted key of the responder.
+    pub fn identity_public_key_der(&self) -> &[u8] {
+        &self.identity_public_key
+    }
+
+    /// Returns the encoding of the bundle.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut encoded = vec![VERSION];
+        put_bytes(&mut encoded, &self.identity_public_key);
+        encoded.extend_from_slice(&self.prekey);
+        put_bytes(&mut encoded, &self.signature);
+        encoded
+    }
+
+    /// Parses a bundle without verifying it.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `PrekeyBundle`, or a
+    /// `SecurityModuleError::SignatureVerificationError` if `bytes` is malformed.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SecurityModuleError> {
+        let mut reader = Reader::new(bytes)?;
+        let bundle = Self {
+            identity_public_key: reader.bytes()?.to_vec(),
+            prekey: reader.key()?,
+            signature: reader.bytes()?.to_vec(),
+        };
+        reader.finish()?;
+        Ok(bundle)
+    }
+}
+
+/// The private half of a prekey, kept by the responder to accept sessions.
+pub struct Prekey {
+    key: PKey<Private>,
+    bundle: PrekeyBundle,
+}
+
+impl Prekey {
+    /// Generates a prekey and signs it with the loaded identity key of `provider`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `Prekey`, a `SecurityModuleError::EncryptionError` if the key
+    /// cannot be generated, or the error of `provider`.
+    #[tracing::instrument(skip(provider))]
+    pub fn generate(provider: &(impl Provider + ?Sized)) -> Result<Self, SecurityModuleError> {
+        check_fips_mode()?;
+        let key = ratchet::generate_key()
+            .map_err(|e| SecurityModuleError::EncryptionError(e.to_string()))?;
+        let prekey = ratchet::public_key(&key)
+            .map_err(|e| SecurityModuleError::EncryptionError(e.to_string()))?;
+        let identity_public_key = provider.key_metadata()?.public_key_der().to_vec();
+        let signature = provider.sign_data(&[PREKEY_DOMAIN, &prekey].concat())?;
+        Ok(Self {
+            key,
+            bundle: PrekeyBundle {
+                identity_public_key,
+                prekey,
+                signature,
+            },
+        })
+    }
+
+    /// Returns the bundle to publish for initiators.
+    pub fn bundle(&self) -> &PrekeyBundle {
+        &self.bundle
+    }
+}
+
+impl fmt::Debug for Prekey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Prekey")
+            .field("bundle", &self.bundle)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The first message of a session, sent by the initiator together with its first messages.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionInit {
+    identity_public_key: Vec<u8>,
+    prekey: [u8; KEY_LEN],
+    ephemeral_key: [u8; KEY_LEN],
+    signature: Vec<u8>,
+}
+
+impl SessionInit {
+    /// Returns the DER encoded identity key of the initiator, e.g. to look up its trusted key.
+    pub fn identity_public_key_der(&self) -> &[u8] {
+        &self.identity_public_key
+    }
+
+    /// Returns the encoding of the handshake.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut encoded = vec![VERSION];
+        put_bytes(&mut encoded, &self.identity_public_key);
+        encoded.extend_from_slice(&self.prekey);
+        encoded.extend_from_slice(&self.ephemeral_key);
+        put_bytes(&mut encoded, &self.signature);
+        encoded
+    }
+
+    /// Parses a handshake without verifying it.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `SessionInit`, or a
+    /// `SecurityModuleError::SignatureVerificationError` if `bytes` is malformed.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SecurityModuleError> {
+        let mut reader = Reader::new(bytes)?;
+        let init = Self {
+            identity_public_key: reader.bytes()?.to_vec(),
+            prekey: reader.key()?,
+            ephemeral_key: reader.key()?,
+            signature: reader.bytes()?.to_vec(),
+        };
+        reader.finish()?;
+        Ok(init)
+    }
+}
+
+/// An end-to-end encrypted session with another device, see the module documentation.
+pub struct Session {
+    peer_identity_public_key: Vec<u8>,
+    ratchet: Ratchet,
+}
+
+impl Session {
+    /// Starts a session with the owner of `bundle`, signing the handshake with the loaded
+    /// identity key of `provider`.
+    ///
+    /// # Arguments
+    ///
+    /// * `provider` - The provider with the loaded identity key of the initiator.
+    /// * `bundle` - The prekey bundle published by the responder.
+    /// * `peer_identity` - The identity key the initiator trusts for the responder.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `Session` and the `SessionInit` for the responder, a
+    /// `SecurityModuleError::InvalidSignature` if the bundle was not signed by
+    /// `peer_identity`, or the error of `provider`.
+    #[tracing::instrument(skip_all)]
+    pub fn initiate(
+        provider: &(impl Provider + ?Sized),
+        bundle: &PrekeyBundle,
+        peer_identity: &PublicKey,
+    ) -> Result<(Self, SessionInit), SecurityModuleError> {
+        check_fips_mode()?;
+        let peer_identity_public_key = peer_identity.to_der()?;
+        if bundle.identity_public_key != peer_identity_public_key
+            || !peer_identity
+                .verify(&[PREKEY_DOMAIN, &bundle.prekey].concat(), &bundle.signature)?
+        {
+            return Err(SecurityModuleError::InvalidSignature);
+        }
+
+        let encryption_error =
+            |e: openssl::error::ErrorStack| SecurityModuleError::EncryptionError(e.to_string());
+        let ephemeral = ratchet::generate_key().map_err(encryption_error)?;
+        let ephemeral_key = ratchet::public_key(&ephemeral).map_err(encryption_error)?;
+        let identity_public_key = provider.key_metadata()?.public_key_der().to_vec();
+        let signature = provider.sign_data(&handshake_data(
+            &peer_identity_public_key,
+            &bundle.prekey,
+            &ephemeral_key,
+        ))?;
+        let shared = ratchet::dh(&ephemeral, &bundle.prekey).map_err(encryption_error)?;
+
+        let associated_data = associated_data(&identity_public_key, &peer_identity_public_key);
+        let shared_secret =
+            session_secret(&shared, &associated_data, &bundle.prekey, &ephemeral_key)?;
+        let ratchet = Ratchet::initiator(&shared_secret, bundle.prekey, associated_data)?;
+        let init = SessionInit {
+            identity_public_key,
+            prekey: bundle.prekey,
+            ephemeral_key,
+            signature,
+        };
+        Ok((
+            Self {
+                peer_identity_public_key,
+                ratchet,
+            },
+            init,
+        ))
+    }
+
+    /// Accepts a session started with `prekey`.
+    ///
+    /// The responder can only send after it decrypted the first message of the initiator.
+    ///
+    /// # Arguments
+    ///
+    /// * `prekey` - The prekey the initiator used.
+    /// * `init` - The handshake of the initiator.
+    /// * `peer_identity` - The identity key the responder trusts for the initiator.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `Session`, a `SecurityModuleError::InvalidSignature` if the
+    /// handshake was not signed by `peer_identity` for this prekey and responder, or a
+    /// `SecurityModuleError::DecryptionError` if its ephemeral key is invalid.
+    #[tracing::instrument(skip_all)]
+    pub fn accept(
+        prekey: &Prekey,
+        init: &SessionInit,
+        peer_identity: &PublicKey,
+    ) -> Result<Self, SecurityModuleError> {
+        check_fips_mode()?;
+        let peer_identity_public_key = peer_identity.to_der()?;
+        let identity_public_key = &prekey.bundle.identity_public_key;
+        let signed = handshake_data(identity_public_key, &init.prekey, &init.ephemeral_key);
+        if init.identity_public_key != peer_identity_public_key
+            || init.prekey != prekey.bundle.prekey
+            || !peer_identity.verify(&signed, &init.signature)?
+        {
+            return Err(SecurityModuleError::InvalidSignature);
+        }
+
+        let shared = ratchet::dh(&prekey.key, &init.ephemeral_key).map_err(|_| {
+            SecurityModuleError::DecryptionError("The ephemeral key is invalid".to_owned())
+        })?;
+        let associated_data = associated_data(&peer_identity_public_key, identity_public_key);
+        let shared_secret =
+            session_secret(&shared, &associated_data, &init.prekey, &init.ephemeral_key)?;
+        Ok(Self {
+            peer_identity_public_key,
+            ratchet: Ratchet::responder(&shared_secret, prekey.key.clone(), associated_data),
+        })
+    }
+
+    /// Returns the DER encoded identity key of the other device.
+    pub fn peer_identity_public_key_der(&self) -> &[u8] {
+        &self.peer_identity_public_key
+    }
+
+    /// Returns the number of message keys kept for messages that have not arrived yet.
+    pub fn skipped_messages(&self) -> usize {
+        self.ratchet.skipped_messages()
+    }
+
+    /// Encrypts the next message of the session.
+    ///
+    /// # Arguments
+    ///
+    /// * `plaintext` - The message.
+    /// * `associated_data` - Data authenticated but not encrypted, e.g. the id of the
+    ///   conversation, which must be passed to `decrypt` again.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the encrypted message, or a `SecurityModuleError::EncryptionError`
+    /// if the responder has not received a message yet.
+    pub fn encrypt(
+        &mut self,
+        plaintext: &[u8],
+        associated_data: &[u8],
+    ) -> Result<Vec<u8>, SecurityModuleError> {
+        self.ratchet.encrypt(plaintext, associated_data)
+    }
+
+    /// Decrypts a message of the other device.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the plaintext, or a `SecurityModuleError::DecryptionError` if the
+    /// message is malformed, was modified, was already received or more than
+    /// `MAX_SKIPPED_MESSAGES` messages are missing before it. The session is unchanged if
+    /// decryption fails.
+    pub fn decrypt(
+        &mut self,
+        message: &[u8],
+        associated_data: &[u8],
+    ) -> Result<Vec<u8>, SecurityModuleError> {
+        self.ratchet.decrypt(message, associated_data)
+    }
+}
+
+impl fmt::Debug for Session {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Session")
+            .field("skipped_messages", &self.skipped_messages())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Returns the data the initiator signs, which binds the handshake to the responder and its
+/// prekey.
+/// Refuses prekeys and sessions in FIPS mode, since X25519 is not approved.
+fn check_fips_mode() -> Result<(), SecurityModuleError> {
+    if fips::is_enabled() {
+        fips::check_name("curve25519")?;
+    }
+    Ok(())
+}
+
+fn handshake_data(
+    responder_identity_public_key: &[u8],
+    prekey: &[u8; KEY_LEN],
+    ephemeral_key: &[u8; KEY_LEN],
+) -> Vec<u8> {
+    [
+        HANDSHAKE_DOMAIN,
+        &sha256(responder_identity_public_key),
+        prekey,
+        ephemeral_key,
+    ]
+    .concat()
+}
+
+/// Returns the hashes of the identity keys of the initiator and the responder, which are
+/// authenticated with every message.
+fn associated_data(initiator: &[u8], responder: &[u8]) -> Vec<u8> {
+    [sha256(initiator), sha256(responder)].concat()
+}
+
+fn session_secret(
+    shared: &[u8; KEY_LEN],
+    associated_data: &[u8],
+    prekey: &[u8; KEY_LEN],
+    ephemeral_key: &[u8; KEY_LEN],
+) -> Result<[u8; KEY_LEN], SecurityModuleError> {
+    let info = [SESSION_INFO, associated_data, prekey, ephemeral_key].concat();
+    let mut secret = [0; KEY_LEN];
+    Kdf::HkdfSha256.derive(shared, None, &info, &mut secret)?;
+    Ok(secret)
+}
+
+fn put_bytes(buffer: &mut Vec<u8>, bytes: &[u8]) {
+    buffer.extend_from_slice(&(bytes.len() as u16).to_be_bytes());
+    buffer.extend_from_slice(bytes);
+}
+
+/// Reads the fields of prekey bundles and handshakes.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Result<Self, SecurityModuleError> {
+        match bytes.split_first() {
+            Some((&VERSION, rest)) => Ok(Self(rest)),
+            _ => Err(malformed("The version is not supported")),
+        }
+    }
+
+    fn bytes(&mut self) -> Result<&'a [u8], SecurityModuleError> {
+        let (len, rest) = self
+            .0
+            .split_first_chunk::<2>()
+            .ok_or_else(|| malformed("The encoding is truncated"))?;
+        let (bytes, rest) = rest
+            .split_at_checked(u16::from_be_bytes(*len) as usize)
+            .ok_or_else(|| malformed("The encoding is truncated"))?;
+        self.0 = rest;
+        Ok(bytes)
+    }
+
+    fn key(&mut self) -> Result<[u8; KEY_LEN], SecurityModuleError> {
+        let (key, rest) = self
+            .0
+            .split_first_chunk::<KEY_LEN>()
+            .ok_or_else(|| malformed("The encoding is truncated"))?;
+        self.0 = rest;
+        Ok(*key)
+    }
+
+    fn finish(self) -> Result<(), SecurityModuleError> {
+        match self.0.is_empty() {
+            true => Ok(()),
+            false => Err(malformed("The encoding has trailing data")),
+        }
+    }
+}
+
+fn malformed(message: &str) -> SecurityModuleError {
+    SecurityModuleError::SignatureVerificationError(message.to_owned())
+}
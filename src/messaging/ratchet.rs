@@ -0,0 +1,347 @@
+//! The Double Ratchet of Signal, with X25519, HKDF-SHA-256, HMAC-SHA-256 and AES-256-GCM.
+//!
+//! The functions follow section 3 of the specification
+//! (<https://signal.org/docs/specifications/doubleratchet/>). The root chain is advanced by
+//! `KDF_RK`, HKDF with the root key as salt, the sending and receiving chains by `KDF_CK`, HMAC
+//! with the chain key over `0x01` for the message key and `0x02` for the next chain key. Every
+//! message key is expanded by HKDF into an AES-256-GCM key and nonce, so no nonce is stored.
+
+use crate::common::{crypto::kdf::Kdf, error::SecurityModuleError};
+use openssl::{
+    derive::Deriver,
+    error::ErrorStack,
+    hash::MessageDigest,
+    pkey::{Id, PKey, Private},
+    sign::Signer,
+    symm::{decrypt_aead, encrypt_aead, Cipher},
+};
+use std::collections::{HashMap, VecDeque};
+
+/// The length of X25519 public keys, chain keys and message keys in bytes.
+pub(crate) const KEY_LEN: usize = 32;
+
+/// The most message keys skipped in one chain, or stored for out-of-order messages.
+pub const MAX_SKIPPED_MESSAGES: u32 = 1000;
+
+const VERSION: u8 = 1;
+const HEADER_LEN: usize = 1 + KEY_LEN + 4 + 4;
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+const ROOT_INFO: &[u8] = b"crypto-layer/messaging/root/v1";
+const MESSAGE_INFO: &[u8] = b"crypto-layer/messaging/message/v1";
+
+type Key = [u8; KEY_LEN];
+
+/// The state of one side of a session.
+#[derive(Clone)]
+pub(crate) struct Ratchet {
+    /// Authenticates the identities of both sides with every message.
+    associated_data: Vec<u8>,
+    sending_key: PKey<Private>,
+    receiving_key: Option<Key>,
+    root_key: Key,
+    sending_chain: Option<Key>,
+    receiving_chain: Option<Key>,
+    sent: u32,
+    received: u32,
+    previous_sent: u32,
+    skipped: HashMap<(Key, u32), Key>,
+    /// The skipped keys in the order they were stored, to drop the oldest first.
+    skipped_order: VecDeque<(Key, u32)>,
+}
+
+/// The header of a message, sent in the clear and authenticated with its ciphertext.
+struct Header {
+    public_key: Key,
+    previous_sent: u32,
+    number: u32,
+}
+
+impl Header {
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut header = Vec::with_capacity(HEADER_LEN);
+        header.push(VERSION);
+        header.extend_from_slice(&self.public_key);
+        header.extend_from_slice(&self.previous_sent.to_be_bytes());
+        header.extend_from_slice(&self.number.to_be_bytes());
+        header
+    }
+
+    fn from_bytes(header: &[u8]) -> Option<Self> {
+        let (&version, rest) = header.split_first()?;
+        let (public_key, rest) = rest.split_first_chunk::<KEY_LEN>()?;
+        let (previous_sent, rest) = rest.split_first_chunk::<4>()?;
+        let number = rest.first_chunk::<4>()?;
+        (version == VERSION).then(|| Self {
+            public_key: *public_key,
+            previous_sent: u32::from_be_bytes(*previous_sent),
+            number: u32::from_be_bytes(*number),
+        })
+    }
+}
+
+impl Ratchet {
+    /// Initializes the side that sends the first message to the ratchet key of the other side.
+    pub(crate) fn initiator(
+        shared_secret: &Key,
+        remote_key: Key,
+        associated_data: Vec<u8>,
+    ) -> Result<Self, SecurityModuleError> {
+        let sending_key = generate_key().map_err(encryption_error)?;
+        let (root_key, sending_chain) = kdf_rk(
+            shared_secret,
+            &dh(&sending_key, &remote_key).map_err(encryption_error)?,
+        )?;
+        Ok(Self {
+            associated_data,
+            sending_key,
+            receiving_key: Some(remote_key),
+            root_key,
+            sending_chain: Some(sending_chain),
+            receiving_chain: None,
+            sent: 0,
+            received: 0,
+            previous_sent: 0,
+            skipped: HashMap::new(),
+            skipped_order: VecDeque::new(),
+        })
+    }
+
+    /// Initializes the side whose ratchet key the first message is sent to.
+    pub(crate) fn responder(
+        shared_secret: &Key,
+        ratchet_key: PKey<Private>,
+        associated_data: Vec<u8>,
+    ) -> Self {
+        Self {
+            associated_data,
+            sending_key: ratchet_key,
+            receiving_key: None,
+            root_key: *shared_secret,
+            sending_chain: None,
+            receiving_chain: None,
+            sent: 0,
+            received: 0,
+            previous_sent: 0,
+            skipped: HashMap::new(),
+            skipped_order: VecDeque::new(),
+        }
+    }
+
+    /// Returns the number of message keys stored for messages that have not arrived yet.
+    pub(crate) fn skipped_messages(&self) -> usize {
+        self.skipped.len()
+    }
+
+    pub(crate) fn encrypt(
+        &mut self,
+        plaintext: &[u8],
+        associated_data: &[u8],
+    ) -> Result<Vec<u8>, SecurityModuleError> {
+        let chain = self.sending_chain.ok_or_else(|| {
+            SecurityModuleError::EncryptionError(
+                "The session can only send after it received a message".to_owned(),
+            )
+        })?;
+        let (next_chain, message_key) = kdf_ck(&chain)?;
+        let header = Header {
+            public_key: public_key(&self.sending_key).map_err(encryption_error)?,
+            previous_sent: self.previous_sent,
+            number: self.sent,
+        }
+        .to_bytes();
+        let (key, nonce) = message_cipher(&message_key)?;
+        let mut tag = [0; TAG_LEN];
+        let ciphertext = encrypt_aead(
+            Cipher::aes_256_gcm(),
+            &key,
+            Some(&nonce),
+            &self.aead_associated_data(&header, associated_data),
+            plaintext,
+            &mut tag,
+        )
+        .map_err(encryption_error)?;
+
+        self.sending_chain = Some(next_chain);
+        self.sent = self.sent.checked_add(1).ok_or_else(|| {
+            SecurityModuleError::EncryptionError("The sending chain is exhausted".to_owned())
+        })?;
+        Ok([header, ciphertext, tag.to_vec()].concat())
+    }
+
+    /// Decrypts `message`, leaving the state unchanged if it fails.
+    pub(crate) fn decrypt(
+        &mut self,
+        message: &[u8],
+        associated_data: &[u8],
+    ) -> Result<Vec<u8>, SecurityModuleError> {
+        if message.len() < HEADER_LEN + TAG_LEN {
+            return Err(decryption_error("The message is truncated"));
+        }
+        let (header_bytes, rest) = message.split_at(HEADER_LEN);
+        let (ciphertext, tag) = rest.split_at(rest.len() - TAG_LEN);
+        let header = Header::from_bytes(header_bytes)
+            .ok_or_else(|| decryption_error("The message has an unsupported version"))?;
+        let aead_associated_data = self.aead_associated_data(header_bytes, associated_data);
+        let open = |message_key: &Key| {
+            let (key, nonce) = message_cipher(message_key)?;
+            decrypt_aead(
+                Cipher::aes_256_gcm(),
+                &key,
+                Some(&nonce),
+                &aead_associated_data,
+                ciphertext,
+                tag,
+            )
+            .map_err(|_| decryption_error("Authentication failed"))
+        };
+
+        let index = (header.public_key, header.number);
+        if let Some(message_key) = self.skipped.get(&index) {
+            let plaintext = open(message_key)?;
+            self.skipped.remove(&index);
+            self.skipped_order.retain(|skipped| *skipped != index);
+            return Ok(plaintext);
+        }
+
+        let mut next = self.clone();
+        if next.receiving_key != Some(header.public_key) {
+            next.skip_message_keys(header.previous_sent)?;
+            next.dh_ratchet(header.public_key)?;
+        }
+        next.skip_message_keys(header.number)?;
+        let chain = next
+            .receiving_chain
+            .ok_or_else(|| decryption_error("The session has no receiving chain"))?;
+        let (next_chain, message_key) = kdf_ck(&chain)?;
+        next.receiving_chain = Some(next_chain);
+        next.received = header
+            .number
+            .checked_add(1)
+            .ok_or_else(|| decryption_error("The receiving chain is exhausted"))?;
+        let plaintext = open(&message_key)?;
+        *self = next;
+        Ok(plaintext)
+    }
+
+    fn aead_associated_data(&self, header: &[u8], associated_data: &[u8]) -> Vec<u8> {
+        [&self.associated_data, header, associated_data].concat()
+    }
+
+    /// Stores the keys of the messages of the receiving chain up to `until`, which were not
+    /// received yet.
+    fn skip_message_keys(&mut self, until: u32) -> Result<(), SecurityModuleError> {
+        let Some(mut chain) = self.receiving_chain else {
+            return Ok(());
+        };
+        let Some(receiving_key) = self.receiving_key else {
+            return Ok(());
+        };
+        if until < self.received {
+            // An old message whose key was not stored, e.g. a replayed one.
+            return Err(decryption_error("The message was already received"));
+        }
+        if until - self.received > MAX_SKIPPED_MESSAGES {
+            return Err(decryption_error("Too many messages were skipped"));
+        }
+        while self.received < until {
+            let (next_chain, message_key) = kdf_ck(&chain)?;
+            let index = (receiving_key, self.received);
+            self.skipped.insert(index, message_key);
+            self.skipped_order.push_back(index);
+            if self.skipped_order.len() > MAX_SKIPPED_MESSAGES as usize {
+                if let Some(oldest) = self.skipped_order.pop_front() {
+                    self.skipped.remove(&oldest);
+                }
+            }
+            chain = next_chain;
+            self.received += 1;
+        }
+        self.receiving_chain = Some(chain);
+        Ok(())
+    }
+
+    fn dh_ratchet(&mut self, remote_key: Key) -> Result<(), SecurityModuleError> {
+        self.previous_sent = self.sent;
+        self.sent = 0;
+        self.received = 0;
+        self.receiving_key = Some(remote_key);
+        let shared = dh(&self.sending_key, &remote_key)
+            .map_err(|_| decryption_error("The ratchet key is invalid"))?;
+        let (root_key, receiving_chain) = kdf_rk(&self.root_key, &shared)?;
+        self.sending_key = generate_key().map_err(encryption_error)?;
+        let shared = dh(&self.sending_key, &remote_key).map_err(encryption_error)?;
+        let (root_key, sending_chain) = kdf_rk(&root_key, &shared)?;
+        self.root_key = root_key;
+        self.receiving_chain = Some(receiving_chain);
+        self.sending_chain = Some(sending_chain);
+        Ok(())
+    }
+}
+
+/// Generates an X25519 key pair.
+pub(crate) fn generate_key() -> Result<PKey<Private>, ErrorStack> {
+    PKey::generate_x25519()
+}
+
+/// Returns the raw X25519 public key of `key`.
+pub(crate) fn public_key(key: &PKey<Private>) -> Result<Key, ErrorStack> {
+    let raw = key.raw_public_key()?;
+    let mut public_key = [0; KEY_LEN];
+    public_key.copy_from_slice(&raw);
+    Ok(public_key)
+}
+
+/// Computes the X25519 shared secret of `key` and `remote_key`.
+pub(crate) fn dh(key: &PKey<Private>, remote_key: &Key) -> Result<Key, ErrorStack> {
+    let remote_key = PKey::public_key_from_raw_bytes(remote_key, Id::X25519)?;
+    let mut deriver = Deriver::new(key)?;
+    deriver.set_peer(&remote_key)?;
+    let mut shared = [0; KEY_LEN];
+    deriver.derive(&mut shared)?;
+    Ok(shared)
+}
+
+fn kdf_rk(root_key: &Key, dh_output: &Key) -> Result<(Key, Key), SecurityModuleError> {
+    let mut output = [0; 2 * KEY_LEN];
+    Kdf::HkdfSha256.derive(dh_output, Some(root_key), ROOT_INFO, &mut output)?;
+    let (root_key, chain_key) = output.split_at(KEY_LEN);
+    Ok((
+        root_key.try_into().expect("split at the key length"),
+        chain_key.try_into().expect("split at the key length"),
+    ))
+}
+
+/// Returns the next chain key and the message key of `chain_key`.
+fn kdf_ck(chain_key: &Key) -> Result<(Key, Key), SecurityModuleError> {
+    let hmac = |input: u8| -> Result<Key, ErrorStack> {
+        let key = PKey::hmac(chain_key)?;
+        let mut signer = Signer::new(MessageDigest::sha256(), &key)?;
+        signer.update(&[input])?;
+        let mut output = [0; KEY_LEN];
+        signer.sign(&mut output)?;
+        Ok(output)
+    };
+    let message_key = hmac(0x01).map_err(encryption_error)?;
+    let next_chain_key = hmac(0x02).map_err(encryption_error)?;
+    Ok((next_chain_key, message_key))
+}
+
+/// Expands a message key into the AES-256-GCM key and nonce of its message.
+fn message_cipher(message_key: &Key) -> Result<(Key, [u8; NONCE_LEN]), SecurityModuleError> {
+    let mut output = [0; KEY_LEN + NONCE_LEN];
+    Kdf::HkdfSha256.derive(message_key, None, MESSAGE_INFO, &mut output)?;
+    let (key, nonce) = output.split_at(KEY_LEN);
+    Ok((
+        key.try_into().expect("split at the key length"),
+        nonce.try_into().expect("split at the key length"),
+    ))
+}
+
+fn encryption_error(e: ErrorStack) -> SecurityModuleError {
+    SecurityModuleError::EncryptionError(e.to_string())
+}
+
+fn decryption_error(message: &str) -> SecurityModuleError {
+    SecurityModuleError::DecryptionError(message.to_owned())
+}
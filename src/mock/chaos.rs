@@ -0,0 +1,351 @@
+//! A provider wrapper that misbehaves on purpose.
+//!
+//! `ChaosProvider` forwards every call to a wrapped provider, but randomly delays calls, holds
+//! back their results or fails them before they reach the provider. Concurrent calls whose
+//! results are held back complete in a different order than they were issued. This exercises the
+//! error handling of applications the way a busy or flaky security module would.
+//!
+//! `SecModules::set_chaos_config` wraps every instance created by the factory afterwards.
+
+use crate::common::{
+    crypto::{
+        key_metadata::KeyMetadata, key_spec::KeySpec, operation_context::OperationContext,
+        secret::SecretBytes,
+    },
+    error::SecurityModuleError,
+    latency::{LatencySnapshot, ProviderOperation},
+    session_pool::SessionPoolMetrics,
+    traits::{key_handle::KeyHandle, module_provider::Provider},
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::{
+    any::Any,
+    fmt,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+    thread,
+    time::Duration,
+};
+
+/// Configuration of a `ChaosProvider`.
+///
+/// All rates are probabilities between `0.0` and `1.0` and are applied to every call
+/// independently. The default configuration forwards all calls unchanged.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChaosConfig {
+    /// The fraction of calls that fail without reaching the wrapped provider.
+    pub failure_rate: f64,
+    /// The fraction of calls that are delayed before reaching the wrapped provider.
+    pub delay_rate: f64,
+    /// The fraction of calls whose result is held back after the wrapped provider returned.
+    pub reorder_rate: f64,
+    /// The maximum duration of a delay or hold, the actual duration is chosen uniformly.
+    pub max_delay: Duration,
+    /// The seed of the random number generator, so that a test injects the same sequence of
+    /// misbehavior on every run.
+    pub seed: u64,
+}
+
+impl Default for ChaosConfig {
+    fn default() -> Self {
+        Self {
+            failure_rate: 0.0,
+            delay_rate: 0.0,
+            reorder_rate: 0.0,
+            max_delay: Duration::from_millis(10),
+            seed: 0,
+        }
+    }
+}
+
+/// The misbehavior injected by a `ChaosProvider` so far.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ChaosStats {
+    /// Calls made through the provider.
+    pub calls: u64,
+    /// Calls that failed with an injected error.
+    pub failures: u64,
+    /// Calls that were delayed before reaching the wrapped provider.
+    pub delays: u64,
+    /// Calls whose result was held back.
+    pub reorders: u64,
+}
+
+/// What happens to a single call.
+struct Plan {
+    fail: bool,
+    delay: Option<Duration>,
+    hold: Option<Duration>,
+}
+
+/// A provider that randomly delays, reorders the completion of, or fails calls to the wrapped
+/// provider.
+///
+/// Injected failures return the error the operation would report itself, e.g. a
+/// `SecurityModuleError::SigningError` for `sign_data`, with the message `"Injected failure"`.
+pub struct ChaosProvider {
+    inner: Arc<Mutex<dyn Provider>>,
+    config: ChaosConfig,
+    rng: Mutex<StdRng>,
+    calls: AtomicU64,
+    failures: AtomicU64,
+    delays: AtomicU64,
+    reorders: AtomicU64,
+}
+
+impl ChaosProvider {
+    /// Wraps a provider.
+    ///
+    /// # Arguments
+    ///
+    /// * `inner` - The provider the calls are forwarded to.
+    /// * `config` - The rates of the injected misbehavior. Rates outside of `0.0..=1.0` are
+    ///   clamped.
+    pub fn new(inner: Arc<Mutex<dyn Provider>>, config: ChaosConfig) -> Self {
+        let config = ChaosConfig {
+            failure_rate: config.failure_rate.clamp(0.0, 1.0),
+            delay_rate: config.delay_rate.clamp(0.0, 1.0),
+            reorder_rate: config.reorder_rate.clamp(0.0, 1.0),
+            ..config
+        };
+        Self {
+            inner,
+            rng: Mutex::new(StdRng::seed_from_u64(config.seed)),
+            config,
+            calls: AtomicU64::new(0),
+            failures: AtomicU64::new(0),
+            delays: AtomicU64::new(0),
+            reorders: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns the configuration of the provider.
+    pub fn config(&self) -> &ChaosConfig {
+        &self.config
+    }
+
+    /// Returns the misbehavior injected so far.
+    pub fn stats(&self) -> ChaosStats {
+        ChaosStats {
+            calls: self.calls.load(Ordering::Relaxed),
+            failures: self.failures.load(Ordering::Relaxed),
+            delays: self.delays.load(Ordering::Relaxed),
+            reorders: self.reorders.load(Ordering::Relaxed),
+        }
+    }
+
+    fn inner(&self) -> MutexGuard<'_, dyn Provider + 'static> {
+        self.inner
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn plan(&self) -> Plan {
+        let mut rng = self
+            .rng
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut duration = |rate: f64| {
+            rng.gen_bool(rate)
+                .then(|| rng.gen_range(Duration::ZERO..=self.config.max_delay))
+        };
+        let delay = duration(self.config.delay_rate);
+        let hold = duration(self.config.reorder_rate);
+        Plan {
+            fail: rng.gen_bool(self.config.failure_rate),
+            delay,
+            hold,
+        }
+    }
+
+    /// Performs `f` according to a freshly drawn plan. Delays happen outside of the lock of the
+    /// wrapped provider, so that concurrent calls can overtake each other.
+    fn call<T>(
+        &self,
+        operation: ProviderOperation,
+        f: impl FnOnce() -> Result<T, SecurityModuleError>,
+    ) -> Result<T, SecurityModuleError> {
+        let plan = self.plan();
+        self.calls.fetch_add(1, Ordering::Relaxed);
+
+        if let Some(delay) = plan.delay {
+            self.delays.fetch_add(1, Ordering::Relaxed);
+            thread::sleep(delay);
+        }
+        let result = if plan.fail {
+            self.failures.fetch_add(1, Ordering::Relaxed);
+            Err(injected_error(operation))
+        } else {
+            f()
+        };
+        if let Some(hold) = plan.hold {
+            self.reorders.fetch_add(1, Ordering::Relaxed);
+            thread::sleep(hold);
+        }
+        result
+    }
+}
+
+impl fmt::Debug for ChaosProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ChaosProvider")
+            .field("config", &self.config)
+            .field("stats", &self.stats())
+            .finish_non_exhaustive()
+    }
+}
+
+fn injected_error(operation: ProviderOperation) -> SecurityModuleError {
+    let message = "Injected failure".to_owned();
+    match operation {
+        ProviderOperation::CreateKey
+        | ProviderOperation::LoadKey
+        | ProviderOperation::ImportWrappedKey
+        | ProviderOperation::ExportPrivateKey
+        | ProviderOperation::DeleteKey
+        | ProviderOperation::InitializeModule => SecurityModuleError::InitializationError(message),
+        ProviderOperation::SignData => SecurityModuleError::SigningError(message),
+        ProviderOperation::DecryptData | ProviderOperation::DeriveSharedSecret => {
+            SecurityModuleError::DecryptionError(message)
+        }
+        ProviderOperation::EncryptData => SecurityModuleError::EncryptionError(message),
+        ProviderOperation::VerifySignature | ProviderOperation::VerifyMany => {
+            SecurityModuleError::SignatureVerificationError(message)
+        }
+    }
+}
+
+impl KeyHandle for ChaosProvider {
+    fn sign_data(&self, data: &[u8]) -> Result<Vec<u8>, SecurityModuleError> {
+        self.call(ProviderOperation::SignData, || self.inner().sign_data(data))
+    }
+
+    fn decrypt_data(&self, encrypted_data: &[u8]) -> Result<SecretBytes, SecurityModuleError> {
+        self.call(ProviderOperation::DecryptData, || {
+            self.inner().decrypt_data(encrypted_data)
+        })
+    }
+
+    fn encrypt_data(&self, data: &[u8]) -> Result<Vec<u8>, SecurityModuleError> {
+        self.call(ProviderOperation::EncryptData, || {
+            self.inner().encrypt_data(data)
+        })
+    }
+
+    fn verify_signature(&self, data: &[u8], signature: &[u8]) -> Result<bool, SecurityModuleError> {
+        self.call(ProviderOperation::VerifySignature, || {
+            self.inner().verify_signature(data, signature)
+        })
+    }
+
+    fn sign_data_into<'a>(
+        &self,
+        data: &[u8],
+        context: &'a mut OperationContext,
+    ) -> Result<&'a [u8], SecurityModuleError> {
+        self.call(ProviderOperation::SignData, || {
+            self.inner().sign_data_into(data, context)
+        })
+    }
+
+    fn verify_signature_with(
+        &self,
+        data: &[u8],
+        signature: &[u8],
+        context: &mut OperationContext,
+    ) -> Result<bool, SecurityModuleError> {
+        self.call(ProviderOperation::VerifySignature, || {
+            self.inner().verify_signature_with(data, signature, context)
+        })
+    }
+
+    fn verify_many(&self, items: &[(&[u8], &[u8])]) -> Result<Vec<bool>, SecurityModuleError> {
+        self.call(ProviderOperation::VerifyMany, || {
+            self.inner().verify_many(items)
+        })
+    }
+
+    fn derive_shared_secret(
+        &self,
+        peer_public_key: &[u8],
+    ) -> Result<SecretBytes, SecurityModuleError> {
+        self.call(ProviderOperation::DeriveSharedSecret, || {
+            self.inner().derive_shared_secret(peer_public_key)
+        })
+    }
+}
+
+impl Provider for ChaosProvider {
+    fn create_key(
+        &mut self,
+        key_id: &str,
+        config: Box<dyn Any>,
+    ) -> Result<(), SecurityModuleError> {
+        self.call(ProviderOperation::CreateKey, || {
+            self.inner().create_key(key_id, config)
+        })
+    }
+
+    fn load_key(&mut self, key_id: &str, config: Box<dyn Any>) -> Result<(), SecurityModuleError> {
+        self.call(ProviderOperation::LoadKey, || {
+            self.inner().load_key(key_id, config)
+        })
+    }
+
+    fn import_wrapped_key(
+        &mut self,
+        key_id: &str,
+        wrapped_key: &[u8],
+        wrapping_key_id: &str,
+        spec: &KeySpec,
+    ) -> Result<(), SecurityModuleError> {
+        self.call(ProviderOperation::ImportWrappedKey, || {
+            self.inner()
+                .import_wrapped_key(key_id, wrapped_key, wrapping_key_id, spec)
+        })
+    }
+
+    fn export_private_key(&self) -> Result<SecretBytes, SecurityModuleError> {
+        self.call(ProviderOperation::ExportPrivateKey, || {
+            self.inner().export_private_key()
+        })
+    }
+
+    fn initialize_module(&mut self) -> Result<(), SecurityModuleError> {
+        self.call(ProviderOperation::InitializeModule, || {
+            self.inner().initialize_module()
+        })
+    }
+
+    fn session_pool_metrics(&self) -> Option<SessionPoolMetrics> {
+        self.inner().session_pool_metrics()
+    }
+
+    fn latency_snapshot(&self) -> Option<LatencySnapshot> {
+        self.inner().latency_snapshot()
+    }
+
+    fn key_metadata(&self) -> Result<KeyMetadata, SecurityModuleError> {
+        self.inner().key_metadata()
+    }
+
+    fn refresh_key_metadata(&mut self) -> Result<KeyMetadata, SecurityModuleError> {
+        self.inner().refresh_key_metadata()
+    }
+
+    fn list_keys(&self) -> Result<Vec<String>, SecurityModuleError> {
+        self.inner().list_keys()
+    }
+
+    fn delete_key(&mut self, key_id: &str) -> Result<(), SecurityModuleError> {
+        self.call(ProviderOperation::DeleteKey, || {
+            self.inner().delete_key(key_id)
+        })
+    }
+
+    fn shutdown(&mut self) -> Result<(), SecurityModuleError> {
+        self.inner().shutdown()
+    }
+}
@@ -0,0 +1,176 @@
+use super::MockProvider;
+use crate::common::{
+    crypto::{
+        algorithms::encryption::AsymmetricEncryption, public_key::message_digest,
+        secret::SecretBytes,
+    },
+    error::SecurityModuleError,
+    latency::{key_id_hash, ProviderOperation},
+    traits::{async_key_handle::AsyncKeyHandle, key_handle::KeyHandle},
+};
+use openssl::{
+    bn::BigNumContext,
+    derive::Deriver,
+    ec::{EcKey, EcPoint},
+    encrypt::{Decrypter, Encrypter},
+    pkey::PKey,
+    rsa::Padding,
+    sign::Signer,
+};
+use tracing::instrument;
+
+/// Implements the `KeyHandle` trait with software keys.
+///
+/// ECDSA signatures are DER encoded, RSA keys sign with PKCS#1 v1.5 and encrypt with OAEP.
+impl KeyHandle for MockProvider {
+    /// Signs the given data with the current key.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - A byte slice representing the data to be signed.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the signature as a `Vec<u8>` on success, or a `SecurityModuleError` on failure.
+    #[instrument(skip_all, fields(crypto.key_id_hash = %key_id_hash(&self.key_id), crypto.payload.size = data.len()))]
+    fn sign_data(&self, data: &[u8]) -> Result<Vec<u8>, SecurityModuleError> {
+        let key = self.enter(ProviderOperation::SignData)?;
+        let digest = message_digest(key.config.hash)?;
+
+        Signer::new(digest, &key.private_key)
+            .and_then(|mut signer| {
+                signer.update(data)?;
+                signer.sign_to_vec()
+            })
+            .map_err(|e| SecurityModuleError::SigningError(e.to_string()))
+    }
+
+    /// Decrypts data encrypted with `encrypt_data`. Only RSA keys support encryption.
+    ///
+    /// # Arguments
+    ///
+    /// * `encrypted_data` - A byte slice representing the data to be decrypted.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the decrypted data as a `Vec<u8>` on success, or a `SecurityModuleError` on failure.
+    #[instrument(skip_all, fields(crypto.key_id_hash = %key_id_hash(&self.key_id), crypto.payload.size = encrypted_data.len()))]
+    fn decrypt_data(&self, encrypted_data: &[u8]) -> Result<SecretBytes, SecurityModuleError> {
+        let key = self.enter(ProviderOperation::DecryptData)?;
+        if !matches!(key.config.key_algorithm, AsymmetricEncryption::Rsa(_)) {
+            return Err(SecurityModuleError::UnsupportedAlgorithm);
+        }
+
+        Decrypter::new(&key.private_key)
+            .and_then(|mut decrypter| {
+                decrypter.set_rsa_padding(Padding::PKCS1_OAEP)?;
+                let mut decrypted = SecretBytes::zeroed(decrypter.decrypt_len(encrypted_data)?);
+                let len = decrypter.decrypt(encrypted_data, &mut decrypted)?;
+                decrypted.truncate(len);
+                Ok(decrypted)
+            })
+            .map_err(|e| SecurityModuleError::DecryptionError(e.to_string()))
+    }
+
+    /// Encrypts data with the public key of the current key. Only RSA keys support encryption.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - A byte slice representing the data to be encrypted.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the encrypted data as a `Vec<u8>` on success, or a `SecurityModuleError` on failure.
+    #[instrument(skip_all, fields(crypto.key_id_hash = %key_id_hash(&self.key_id), crypto.payload.size = data.len()))]
+    fn encrypt_data(&self, data: &[u8]) -> Result<Vec<u8>, SecurityModuleError> {
+        let key = self.enter(ProviderOperation::EncryptData)?;
+        if !matches!(key.config.key_algorithm, AsymmetricEncryption::Rsa(_)) {
+            return Err(SecurityModuleError::UnsupportedAlgorithm);
+        }
+
+        Encrypter::new(&key.private_key)
+            .and_then(|mut encrypter| {
+                encrypter.set_rsa_padding(Padding::PKCS1_OAEP)?;
+                let mut encrypted = vec![0; encrypter.encrypt_len(data)?];
+                let len = encrypter.encrypt(data, &mut encrypted)?;
+                encrypted.truncate(len);
+                Ok(encrypted)
+            })
+            .map_err(|e| SecurityModuleError::EncryptionError(e.to_string()))
+    }
+
+    /// Verifies a signature created by `sign_data`.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - A byte slice representing the data whose signature is to be verified.
+    /// * `signature` - A byte slice representing the signature to be verified against the data.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing a boolean indicating whether the signature is valid (`true`) or not (`false`),
+    /// or a `SecurityModuleError` on failure.
+    #[instrument(skip_all, fields(crypto.key_id_hash = %key_id_hash(&self.key_id), crypto.payload.size = data.len(), crypto.signature.size = signature.len()))]
+    fn verify_signature(&self, data: &[u8], signature: &[u8]) -> Result<bool, SecurityModuleError> {
+        let key = self.enter(ProviderOperation::VerifySignature)?;
+        key.metadata.public_key().verify(data, signature)
+    }
+
+    /// Verifies a batch of signatures in parallel.
+    ///
+    /// # Arguments
+    ///
+    /// * `items` - Pairs of data and the signature to be verified against it.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing one boolean per item indicating whether the respective signature is valid,
+    /// or a `SecurityModuleError` on failure.
+    #[instrument(skip_all, fields(crypto.key_id_hash = %key_id_hash(&self.key_id), crypto.batch.size = items.len()))]
+    fn verify_many(&self, items: &[(&[u8], &[u8])]) -> Result<Vec<bool>, SecurityModuleError> {
+        let key = self.enter(ProviderOperation::VerifyMany)?;
+        Ok(key.metadata.public_key().verify_many(items))
+    }
+
+    /// Derives a shared secret with ECDH. Only ECC keys support key agreement.
+    ///
+    /// # Arguments
+    ///
+    /// * `peer_public_key` - The public key of the other party as a SEC1 point on the curve of the key.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the shared secret on success, or a `SecurityModuleError::InvalidPublicKey`
+    /// if the public key is not a point on the curve of the key.
+    #[instrument(skip_all, fields(crypto.key_id_hash = %key_id_hash(&self.key_id)))]
+    fn derive_shared_secret(
+        &self,
+        peer_public_key: &[u8],
+    ) -> Result<SecretBytes, SecurityModuleError> {
+        let key = self.enter(ProviderOperation::DeriveSharedSecret)?;
+        if !matches!(key.config.key_algorithm, AsymmetricEncryption::Ecc(_)) {
+            return Err(SecurityModuleError::UnsupportedAlgorithm);
+        }
+
+        let ec_key = key
+            .private_key
+            .ec_key()
+            .map_err(|e| SecurityModuleError::DecryptionError(e.to_string()))?;
+        let peer = BigNumContext::new()
+            .and_then(|mut ctx| EcPoint::from_bytes(ec_key.group(), peer_public_key, &mut ctx))
+            .and_then(|point| EcKey::from_public_key(ec_key.group(), &point))
+            .and_then(PKey::from_ec_key)
+            .map_err(|_| SecurityModuleError::InvalidPublicKey)?;
+
+        Deriver::new(&key.private_key)
+            .and_then(|mut deriver| {
+                deriver.set_peer(&peer)?;
+                deriver.derive_to_vec()
+            })
+            .map(SecretBytes::new)
+            .map_err(|e| SecurityModuleError::DecryptionError(e.to_string()))
+    }
+}
+
+/// Software keys never wait for the user, so the asynchronous operations are the synchronous ones.
+impl AsyncKeyHandle for MockProvider {}
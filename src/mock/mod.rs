@@ -0,0 +1,391 @@
+//! An in-memory provider for testing applications without a security module.
+//!
+//! The `MockProvider` implements all provider traits with software keys and can be scripted to
+//! fail, slow down or behave flakily for individual operations, which makes it possible to
+//! unit-test the error handling of applications built on the crypto layer.
+
+use crate::common::{
+    crypto::{
+        algorithms::{
+            encryption::{AsymmetricEncryption, EccCurves, EccSchemeAlgorithm},
+            hashes::{Hash, Sha2Bits},
+        },
+        key_metadata::KeyMetadata,
+        key_spec::KeySpec,
+    },
+    error::SecurityModuleError,
+    latency::ProviderOperation,
+    traits::{module_provider::Provider, module_provider_config::ProviderConfig},
+};
+use openssl::pkey::{PKey, Private};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::{
+    any::Any,
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex, MutexGuard},
+    thread,
+    time::Duration,
+};
+
+pub mod chaos;
+pub mod key_handle;
+pub mod provider;
+
+/// The configuration of a key created by the `MockProvider`.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MockConfig {
+    /// The algorithm of the key pair. RSA and ECDSA keys are supported.
+    pub key_algorithm: AsymmetricEncryption,
+    /// The hash algorithm used for signing.
+    pub hash: Hash,
+    /// Whether `export_private_key` may export the key. Defaults to `false`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub exportable: bool,
+}
+
+impl MockConfig {
+    /// Creates a boxed `MockConfig` to be passed to `create_key` and `load_key`.
+    ///
+    /// # Arguments
+    ///
+    /// * `key_algorithm` - The algorithm of the key pair.
+    /// * `hash` - The hash algorithm used for signing.
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(key_algorithm: AsymmetricEncryption, hash: Hash) -> Box<dyn Any> {
+        Box::new(Self {
+            key_algorithm,
+            hash,
+            exportable: false,
+        })
+    }
+
+    /// Creates a boxed `MockConfig` from a validated `KeySpec`. The access control of the spec
+    /// is accepted but not enforced, so that applications requiring biometry can be tested.
+    /// Exportable specs create keys `export_private_key` exports.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the config on success, or a
+    /// `SecurityModuleError::UnsupportedAlgorithm` for symmetric keys.
+    pub fn from_spec(spec: &KeySpec) -> Result<Box<dyn Any>, SecurityModuleError> {
+        let key_algorithm = spec
+            .asymmetric_algorithm()
+            .ok_or(SecurityModuleError::UnsupportedAlgorithm)?;
+        Ok(Box::new(Self {
+            key_algorithm,
+            hash: spec.hash(),
+            exportable: spec.exportable(),
+        }))
+    }
+}
+
+impl Default for MockConfig {
+    fn default() -> Self {
+        Self {
+            key_algorithm: AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(EccCurves::P256)),
+            hash: Hash::Sha2(Sha2Bits::Sha256),
+            exportable: false,
+        }
+    }
+}
+
+impl ProviderConfig for MockConfig {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+type ErrorFactory = Arc<dyn Fn() -> SecurityModuleError + Send + Sync>;
+
+/// An error injected into an operation.
+struct Fault {
+    error: ErrorFactory,
+    /// The number of calls that still fail, `None` fails all calls.
+    remaining: Option<usize>,
+    /// The probability of a call failing.
+    probability: f64,
+}
+
+#[derive(Default)]
+struct OperationScript {
+    fault: Option<Fault>,
+    latency: Duration,
+    calls: usize,
+}
+
+struct ScriptState {
+    operations: HashMap<ProviderOperation, OperationScript>,
+    rng: StdRng,
+}
+
+/// Scripts the behavior of a `MockProvider`.
+///
+/// The controller is shared with the provider it was obtained from, so the behavior can still be
+/// changed after the provider has been handed to the code under test.
+///
+/// # Examples
+///
+/// ```rust
+/// use crypto_layer::{
+///     common::{
+///         latency::ProviderOperation,
+///         traits::{key_handle::KeyHandle, module_provider::Provider},
+///     },
+///     mock::{MockConfig, MockProvider},
+///     SecurityModuleError,
+/// };
+///
+/// let mut provider = MockProvider::new("test_key".to_owned());
+/// let controller = provider.controller();
+/// provider.initialize_module().unwrap();
+/// provider.create_key("test_key", Box::new(MockConfig::default())).unwrap();
+///
+/// controller.fail_times(ProviderOperation::SignData, 1, || SecurityModuleError::SigningFailed);
+/// assert!(provider.sign_data(b"data").is_err());
+/// assert!(provider.sign_data(b"data").is_ok());
+/// assert_eq!(controller.calls(ProviderOperation::SignData), 2);
+/// ```
+#[derive(Clone)]
+pub struct MockController {
+    state: Arc<Mutex<ScriptState>>,
+}
+
+impl MockController {
+    fn new() -> Self {
+        Self {
+            state: Arc::new(Mutex::new(ScriptState {
+                operations: HashMap::new(),
+                rng: StdRng::seed_from_u64(0),
+            })),
+        }
+    }
+
+    /// Makes every call of `operation` fail with the error returned by `error`.
+    pub fn fail(
+        &self,
+        operation: ProviderOperation,
+        error: impl Fn() -> SecurityModuleError + Send + Sync + 'static,
+    ) {
+        self.set_fault(operation, Arc::new(error), None, 1.0);
+    }
+
+    /// Makes the next `times` calls of `operation` fail with the error returned by `error`.
+    pub fn fail_times(
+        &self,
+        operation: ProviderOperation,
+        times: usize,
+        error: impl Fn() -> SecurityModuleError + Send + Sync + 'static,
+    ) {
+        self.set_fault(operation, Arc::new(error), Some(times), 1.0);
+    }
+
+    /// Makes calls of `operation` fail randomly with the given probability.
+    ///
+    /// The random number generator is seeded, so a test produces the same sequence of failures
+    /// on every run. Use `seed` to choose another sequence.
+    ///
+    /// # Arguments
+    ///
+    /// * `operation` - The operation to make flaky.
+    /// * `probability` - The probability of a call failing, between `0.0` and `1.0`.
+    /// * `error` - Returns the error a failing call returns.
+    pub fn fail_randomly(
+        &self,
+        operation: ProviderOperation,
+        probability: f64,
+        error: impl Fn() -> SecurityModuleError + Send + Sync + 'static,
+    ) {
+        self.set_fault(
+            operation,
+            Arc::new(error),
+            None,
+            probability.clamp(0.0, 1.0),
+        );
+    }
+
+    /// Delays every call of `operation`, whether it succeeds or not.
+    pub fn set_latency(&self, operation: ProviderOperation, latency: Duration) {
+        self.lock().operations.entry(operation).or_default().latency = latency;
+    }
+
+    /// Reseeds the random number generator used by `fail_randomly`.
+    pub fn seed(&self, seed: u64) {
+        self.lock().rng = StdRng::seed_from_u64(seed);
+    }
+
+    /// Removes the injected error and latency of `operation`.
+    pub fn clear(&self, operation: ProviderOperation) {
+        if let Some(script) = self.lock().operations.get_mut(&operation) {
+            script.fault = None;
+            script.latency = Duration::ZERO;
+        }
+    }
+
+    /// Removes all injected errors and latencies and resets the call counters.
+    pub fn reset(&self) {
+        self.lock().operations.clear();
+    }
+
+    /// Returns how often `operation` was called, including failed calls.
+    pub fn calls(&self, operation: ProviderOperation) -> usize {
+        self.lock()
+            .operations
+            .get(&operation)
+            .map_or(0, |script| script.calls)
+    }
+
+    fn set_fault(
+        &self,
+        operation: ProviderOperation,
+        error: ErrorFactory,
+        remaining: Option<usize>,
+        probability: f64,
+    ) {
+        self.lock().operations.entry(operation).or_default().fault = Some(Fault {
+            error,
+            remaining,
+            probability,
+        });
+    }
+
+    /// Records a call of `operation`, applies its latency and returns the injected error, if any.
+    pub(crate) fn enter(&self, operation: ProviderOperation) -> Result<(), SecurityModuleError> {
+        let (latency, error) = {
+            let mut state = self.lock();
+            let state = &mut *state;
+            let script = state.operations.entry(operation).or_default();
+            script.calls += 1;
+
+            let error = match &mut script.fault {
+                Some(fault) if state.rng.gen_bool(fault.probability) => {
+                    let error = fault.error.clone();
+                    if let Some(remaining) = &mut fault.remaining {
+                        *remaining -= 1;
+                        if *remaining == 0 {
+                            script.fault = None;
+                        }
+                    }
+                    Some(error)
+                }
+                _ => None,
+            };
+            (script.latency, error)
+        };
+
+        if !latency.is_zero() {
+            thread::sleep(latency);
+        }
+        match error {
+            Some(error) => Err(error()),
+            None => Ok(()),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, ScriptState> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl fmt::Debug for MockController {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.lock();
+        let mut scripted: Vec<_> = state.operations.keys().collect();
+        scripted.sort();
+        f.debug_struct("MockController")
+            .field("scripted", &scripted)
+            .finish()
+    }
+}
+
+/// A key pair held in memory by the `MockProvider`.
+#[derive(Clone)]
+pub(crate) struct MockKey {
+    pub(crate) config: MockConfig,
+    pub(crate) private_key: PKey<Private>,
+    pub(crate) metadata: KeyMetadata,
+}
+
+impl fmt::Debug for MockKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MockKey")
+            .field("config", &self.config)
+            .field("metadata", &self.metadata)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A provider that keeps its keys in memory, for testing applications without a security module.
+///
+/// Keys created with `create_key` can be loaded again with `load_key` for the lifetime of the
+/// provider. Every operation can be scripted with the `MockController` returned by `controller`.
+#[derive(Debug)]
+pub struct MockProvider {
+    pub(super) key_id: String,
+    pub(super) initialized: bool,
+    pub(super) keys: HashMap<String, MockKey>,
+    pub(super) key: Option<MockKey>,
+    pub(super) controller: MockController,
+}
+
+impl MockProvider {
+    /// Constructs a new `MockProvider` without any keys.
+    ///
+    /// # Arguments
+    ///
+    /// * `key_id` - A string identifier for the cryptographic key to be managed by this provider.
+    pub fn new(key_id: String) -> Self {
+        Self {
+            key_id,
+            initialized: false,
+            keys: HashMap::new(),
+            key: None,
+            controller: MockController::new(),
+        }
+    }
+
+    /// Constructs an initialized `MockProvider` that has created and loaded the key `key_id`,
+    /// the usual starting point of a test.
+    ///
+    /// # Arguments
+    ///
+    /// * `key_id` - The id of the key to create.
+    /// * `config` - The `MockConfig` of the key, e.g. `MockConfig::default()` boxed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `config` is not a `MockConfig` of an algorithm the provider supports.
+    pub fn with_key(key_id: &str, config: Box<dyn Any>) -> Self {
+        let mut provider = Self::new(key_id.to_owned());
+        provider
+            .initialize_module()
+            .expect("a new mock provider can be initialized");
+        provider
+            .create_key(key_id, config)
+            .expect("the mock provider supports the configuration");
+        provider
+    }
+
+    /// Returns the controller scripting the behavior of this provider.
+    pub fn controller(&self) -> MockController {
+        self.controller.clone()
+    }
+
+    /// Records a call of `operation` and returns the current key, failing if the call was
+    /// scripted to fail or no key has been created or loaded.
+    pub(super) fn enter(
+        &self,
+        operation: ProviderOperation,
+    ) -> Result<&MockKey, SecurityModuleError> {
+        self.controller.enter(operation)?;
+        if !self.initialized {
+            return Err(SecurityModuleError::InitializationError(
+                "Module not initialized".to_owned(),
+            ));
+        }
+        self.key.as_ref().ok_or(SecurityModuleError::KeyError)
+    }
+}
@@ -0,0 +1,319 @@
+use super::{MockConfig, MockKey, MockProvider};
+use crate::common::{
+    crypto::{
+        algorithms::encryption::{AsymmetricEncryption, EccSchemeAlgorithm},
+        key_metadata::KeyMetadata,
+        key_spec::KeySpec,
+        public_key::{curve_nid, PublicKey},
+        secret::SecretBytes,
+    },
+    error::SecurityModuleError,
+    key_import,
+    latency::{key_id_hash, ProviderOperation},
+    traits::module_provider::Provider,
+};
+use openssl::{
+    ec::{EcGroup, EcKey},
+    pkey::{PKey, Private},
+    rsa::Rsa,
+};
+use std::any::Any;
+use tracing::instrument;
+
+/// Implements the `Provider` trait with keys held in memory.
+impl Provider for MockProvider {
+    /// Creates a new key pair in memory identified by `key_id`.
+    ///
+    /// # Arguments
+    ///
+    /// * `key_id` - A string slice that uniquely identifies the key to be created.
+    /// * `config` - A boxed `MockConfig` specifying the algorithm of the key.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` that, on success, contains `Ok(())`, indicating that the key was created successfully.
+    /// On failure, it returns a `SecurityModuleError`.
+    #[instrument(skip_all, fields(crypto.key_id_hash = %key_id_hash(key_id)))]
+    fn create_key(
+        &mut self,
+        key_id: &str,
+        config: Box<dyn Any>,
+    ) -> Result<(), SecurityModuleError> {
+        self.controller.enter(ProviderOperation::CreateKey)?;
+        self.ensure_initialized()?;
+        let config = *config.downcast::<MockConfig>().map_err(|_| {
+            SecurityModuleError::InitializationError("Failed to initialize config".to_owned())
+        })?;
+
+        let private_key = generate_key(config.key_algorithm)?;
+        self.insert_key(key_id, config, private_key)
+    }
+
+    /// Loads a key pair previously created by this provider.
+    ///
+    /// # Arguments
+    ///
+    /// * `key_id` - A string slice that uniquely identifies the key to be loaded.
+    /// * `config` - Ignored, the provider remembers the configuration of its keys.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` that, on success, contains `Ok(())`, indicating that the key was loaded successfully.
+    /// On failure, it returns a `SecurityModuleError::KeyError` if no key with this id was created.
+    #[instrument(skip_all, fields(crypto.key_id_hash = %key_id_hash(key_id)))]
+    fn load_key(&mut self, key_id: &str, _config: Box<dyn Any>) -> Result<(), SecurityModuleError> {
+        self.controller.enter(ProviderOperation::LoadKey)?;
+        self.ensure_initialized()?;
+
+        let key = self
+            .keys
+            .get(key_id)
+            .cloned()
+            .ok_or(SecurityModuleError::KeyError)?;
+        self.key = Some(key);
+        self.key_id = key_id.to_owned();
+        Ok(())
+    }
+
+    /// Imports a private key that `key_import::wrap_rsa_aes` wrapped under the RSA key
+    /// `wrapping_key_id` of this provider and makes it the current key.
+    ///
+    /// # Arguments
+    ///
+    /// * `key_id` - A string slice that uniquely identifies the imported key.
+    /// * `wrapped_key` - The `CKM_RSA_AES_KEY_WRAP` blob of the PKCS#8 private key.
+    /// * `wrapping_key_id` - The id of an RSA key created or imported by this provider.
+    /// * `spec` - The algorithm of the key, which has to match the private key.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` that, on success, contains `Ok(())`. On failure, it returns a
+    /// `SecurityModuleError::KeyError` if the wrapping key does not exist or the unwrapped key does
+    /// not match `spec`, or a `SecurityModuleError::DecryptionError` if it cannot be unwrapped.
+    #[instrument(skip_all, fields(crypto.key_id_hash = %key_id_hash(key_id)))]
+    fn import_wrapped_key(
+        &mut self,
+        key_id: &str,
+        wrapped_key: &[u8],
+        wrapping_key_id: &str,
+        spec: &KeySpec,
+    ) -> Result<(), SecurityModuleError> {
+        self.controller.enter(ProviderOperation::ImportWrappedKey)?;
+        self.ensure_initialized()?;
+        let config = MockConfig {
+            key_algorithm: spec
+                .asymmetric_algorithm()
+                .ok_or(SecurityModuleError::UnsupportedAlgorithm)?,
+            hash: spec.hash(),
+            exportable: spec.exportable(),
+        };
+
+        let wrapping_key = self
+            .keys
+            .get(wrapping_key_id)
+            .ok_or(SecurityModuleError::KeyError)?;
+        let pkcs8_der = key_import::unwrap_rsa_aes(&wrapping_key.private_key, wrapped_key)?;
+        let private_key =
+            PKey::private_key_from_pkcs8(&pkcs8_der).map_err(|_| SecurityModuleError::KeyError)?;
+        if !matches_algorithm(&private_key, config.key_algorithm)? {
+            return Err(SecurityModuleError::KeyError);
+        }
+        self.insert_key(key_id, config, private_key)
+    }
+
+    /// Exports the private key of the current key as PKCS#8 DER.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the private key on success, or a
+    /// `SecurityModuleError::UnsupportedOperation` if the key was not created exportable.
+    #[instrument(skip_all, fields(crypto.key_id_hash = %key_id_hash(&self.key_id)))]
+    fn export_private_key(&self) -> Result<SecretBytes, SecurityModuleError> {
+        let key = self.enter(ProviderOperation::ExportPrivateKey)?;
+        if !key.config.exportable {
+            return Err(SecurityModuleError::UnsupportedOperation(format!(
+                "The key '{}' was not created exportable",
+                self.key_id
+            )));
+        }
+        key.private_key
+            .private_key_to_pkcs8()
+            .map(SecretBytes::new)
+            .map_err(|_| SecurityModuleError::KeyError)
+    }
+
+    /// Initializes the provider. Creating, loading and using keys fails until this is called.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` that, on success, contains `Ok(())`, indicating that the module was initialized successfully.
+    /// On failure, it returns the error injected through the `MockController`.
+    #[instrument(skip_all, fields(crypto.key_id_hash = %key_id_hash(&self.key_id)))]
+    fn initialize_module(&mut self) -> Result<(), SecurityModuleError> {
+        self.controller.enter(ProviderOperation::InitializeModule)?;
+        self.initialized = true;
+        Ok(())
+    }
+
+    /// Returns the metadata of the current key. The mock provider does not attest its keys.
+    #[instrument(skip_all, fields(crypto.key_id_hash = %key_id_hash(&self.key_id)))]
+    fn key_metadata(&self) -> Result<KeyMetadata, SecurityModuleError> {
+        self.key
+            .as_ref()
+            .map(|key| key.metadata.clone())
+            .ok_or(SecurityModuleError::KeyError)
+    }
+
+    /// Recomputes the metadata of the current key.
+    #[instrument(skip_all, fields(crypto.key_id_hash = %key_id_hash(&self.key_id)))]
+    fn refresh_key_metadata(&mut self) -> Result<KeyMetadata, SecurityModuleError> {
+        let key = self.key.as_mut().ok_or(SecurityModuleError::KeyError)?;
+        key.metadata = key_metadata(&self.key_id, &key.config, &key.private_key)?;
+        self.keys.insert(self.key_id.clone(), key.clone());
+        Ok(key.metadata.clone())
+    }
+    /// Returns the ids of all keys created or imported so far, sorted.
+    #[instrument(skip_all, fields(crypto.key_id_hash = %key_id_hash(&self.key_id)))]
+    fn list_keys(&self) -> Result<Vec<String>, SecurityModuleError> {
+        self.ensure_initialized()?;
+        let mut key_ids: Vec<String> = self.keys.keys().cloned().collect();
+        key_ids.sort();
+        Ok(key_ids)
+    }
+
+    /// Deletes a key created or imported by this provider, and forgets it if it is the current
+    /// key.
+    #[instrument(skip_all, fields(crypto.key_id_hash = %key_id_hash(key_id)))]
+    fn delete_key(&mut self, key_id: &str) -> Result<(), SecurityModuleError> {
+        self.controller.enter(ProviderOperation::DeleteKey)?;
+        self.ensure_initialized()?;
+        self.keys
+            .remove(key_id)
+            .ok_or(SecurityModuleError::KeyError)?;
+        if self.key_id == key_id {
+            self.key = None;
+        }
+        Ok(())
+    }
+
+    /// Forgets the current key and returns to the uninitialized state. The keys created so far
+    /// are kept and can be loaded again after `initialize_module`.
+    #[instrument(skip_all, fields(crypto.key_id_hash = %key_id_hash(&self.key_id)))]
+    fn shutdown(&mut self) -> Result<(), SecurityModuleError> {
+        self.initialized = false;
+        self.key = None;
+        Ok(())
+    }
+}
+
+impl MockProvider {
+    /// Imports an existing private key and makes it the current key, like `create_key` does for
+    /// generated keys.
+    ///
+    /// Fixed keys make the output of deterministic operations reproducible, e.g. for test vectors.
+    ///
+    /// # Arguments
+    ///
+    /// * `key_id` - A string slice that uniquely identifies the imported key.
+    /// * `config` - The algorithm of the key, which has to match the private key.
+    /// * `private_key_pem` - The PEM encoded private key, in PKCS#8 or the traditional format.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` that, on success, contains `Ok(())`. On failure, it returns a
+    /// `SecurityModuleError::KeyError` if the key cannot be parsed or does not match `config`.
+    #[instrument(skip_all, fields(crypto.key_id_hash = %key_id_hash(key_id)))]
+    pub fn import_key(
+        &mut self,
+        key_id: &str,
+        config: MockConfig,
+        private_key_pem: &[u8],
+    ) -> Result<(), SecurityModuleError> {
+        self.controller.enter(ProviderOperation::CreateKey)?;
+        self.ensure_initialized()?;
+
+        let private_key = PKey::private_key_from_pem(private_key_pem)
+            .map_err(|_| SecurityModuleError::KeyError)?;
+        if !matches_algorithm(&private_key, config.key_algorithm)? {
+            return Err(SecurityModuleError::KeyError);
+        }
+        self.insert_key(key_id, config, private_key)
+    }
+
+    /// Stores a new key under `key_id` and makes it the current key.
+    fn insert_key(
+        &mut self,
+        key_id: &str,
+        config: MockConfig,
+        private_key: PKey<Private>,
+    ) -> Result<(), SecurityModuleError> {
+        let key = MockKey {
+            metadata: key_metadata(key_id, &config, &private_key)?,
+            config,
+            private_key,
+        };
+
+        self.keys.insert(key_id.to_owned(), key.clone());
+        self.key = Some(key);
+        self.key_id = key_id.to_owned();
+        Ok(())
+    }
+
+    fn ensure_initialized(&self) -> Result<(), SecurityModuleError> {
+        if self.initialized {
+            Ok(())
+        } else {
+            Err(SecurityModuleError::InitializationError(
+                "Module not initialized".to_owned(),
+            ))
+        }
+    }
+}
+
+/// Generates a software key pair for the given algorithm.
+fn generate_key(algorithm: AsymmetricEncryption) -> Result<PKey<Private>, SecurityModuleError> {
+    let key = match algorithm {
+        AsymmetricEncryption::Rsa(key_bits) => {
+            Rsa::generate(u32::from(key_bits)).and_then(PKey::from_rsa)
+        }
+        AsymmetricEncryption::Ecc(
+            EccSchemeAlgorithm::EcDsa(curve) | EccSchemeAlgorithm::EcDh(curve),
+        ) => EcGroup::from_curve_name(curve_nid(curve)?)
+            .and_then(|group| EcKey::generate(&group))
+            .and_then(PKey::from_ec_key),
+        _ => return Err(SecurityModuleError::UnsupportedAlgorithm),
+    };
+    key.map_err(|e| SecurityModuleError::InitializationError(e.to_string()))
+}
+
+/// Returns whether `key` is a key pair of the given algorithm, including its size or curve.
+fn matches_algorithm(
+    key: &PKey<Private>,
+    algorithm: AsymmetricEncryption,
+) -> Result<bool, SecurityModuleError> {
+    Ok(match algorithm {
+        AsymmetricEncryption::Rsa(key_bits) => key
+            .rsa()
+            .is_ok_and(|rsa| rsa.size() * 8 == u32::from(key_bits)),
+        AsymmetricEncryption::Ecc(
+            EccSchemeAlgorithm::EcDsa(curve) | EccSchemeAlgorithm::EcDh(curve),
+        ) => {
+            let nid = curve_nid(curve)?;
+            key.ec_key()
+                .is_ok_and(|ec_key| ec_key.group().curve_name() == Some(nid))
+        }
+        _ => return Err(SecurityModuleError::UnsupportedAlgorithm),
+    })
+}
+
+fn key_metadata(
+    key_id: &str,
+    config: &MockConfig,
+    private_key: &PKey<Private>,
+) -> Result<KeyMetadata, SecurityModuleError> {
+    let der = private_key
+        .public_key_to_der()
+        .map_err(|_| SecurityModuleError::InvalidPublicKey)?;
+    let public_key = PublicKey::from_der(&der, config.key_algorithm, config.hash)?;
+    Ok(KeyMetadata::new(key_id, public_key)?.with_exportable(config.exportable))
+}
@@ -17,8 +17,10 @@ use crate::common::crypto::algorithms::encryption::{
 };
 use crate::common::crypto::algorithms::hashes::*;
 use crate::common::crypto::algorithms::KeyBits;
+use crate::common::latency::key_id_hash;
 use crate::common::{
-    crypto::algorithms::encryption::AsymmetricEncryption, error::SecurityModuleError,
+    crypto::{algorithms::encryption::AsymmetricEncryption, secret::SecretBytes},
+    error::SecurityModuleError,
     traits::key_handle::KeyHandle,
 };
 use crate::nks::NksConfig;
@@ -36,7 +38,7 @@ impl KeyHandle for NksProvider {
     /// # Returns
     ///
     /// A `Result` containing the signature as a `Vec<u8>` on success, or a `SecurityModuleError` on failure.
-    #[tracing::instrument]
+    #[tracing::instrument(skip_all, fields(crypto.key_id_hash = %key_id_hash(&self.key_id), crypto.payload.size = _data.len()))]
     fn sign_data(&self, _data: &[u8]) -> Result<Vec<u8>, SecurityModuleError> {
         if let Some(nks_config) = self
             .config
@@ -113,7 +115,7 @@ impl KeyHandle for NksProvider {
                 }
             }
         } else {
-            println!("Failed to downcast to NksConfig");
+            tracing::error!("Failed to downcast to NksConfig");
             Err(SecurityModuleError::NksError)
         }
     }
@@ -127,8 +129,8 @@ impl KeyHandle for NksProvider {
     /// # Returns
     ///
     /// A `Result` containing the decrypted data as a `Vec<u8>` on success, or a `SecurityModuleError` on failure.
-    #[tracing::instrument]
-    fn decrypt_data(&self, _encrypted_data: &[u8]) -> Result<Vec<u8>, SecurityModuleError> {
+    #[tracing::instrument(skip_all, fields(crypto.key_id_hash = %key_id_hash(&self.key_id), crypto.payload.size = _encrypted_data.len()))]
+    fn decrypt_data(&self, _encrypted_data: &[u8]) -> Result<SecretBytes, SecurityModuleError> {
         let config = self
             .config
             .as_ref()
@@ -151,7 +153,7 @@ impl KeyHandle for NksProvider {
                     // RSA decryption method
                     let rsa = Rsa::private_key_from_pem(self.private_key.as_bytes())
                         .map_err(|_| SecurityModuleError::KeyError)?;
-                    let mut decrypted_data = vec![0; rsa.size() as usize];
+                    let mut decrypted_data = SecretBytes::zeroed(rsa.size() as usize);
                     rsa.private_decrypt(encrypted_data, &mut decrypted_data, Padding::PKCS1)
                         .map_err(|_| {
                             SecurityModuleError::DecryptionError(
@@ -161,9 +163,9 @@ impl KeyHandle for NksProvider {
                     let last_non_zero_pos =
                         decrypted_data.iter().rposition(|&x| x != 0).unwrap_or(0) + 1;
 
-                    let (decrypted_data, _) = decrypted_data.split_at(last_non_zero_pos);
+                    decrypted_data.truncate(last_non_zero_pos);
 
-                    Ok(decrypted_data.to_vec())
+                    Ok(decrypted_data)
                 }
                 Some(AsymmetricEncryption::Ecc(..)) => {
                     let public_key_bytes = BASE64_STANDARD
@@ -193,7 +195,7 @@ impl KeyHandle for NksProvider {
                             },
                         )?;
 
-                    Ok(decrypted_message)
+                    Ok(decrypted_message.into())
                 }
                 None => match &key_algorithm_sym {
                     Some(BlockCiphers::Aes(mode, length)) => {
@@ -235,7 +237,7 @@ impl KeyHandle for NksProvider {
                                 // Truncate the decrypted data to remove any extra bytes
                                 decrypted_data.truncate(count + rest);
 
-                                Ok(decrypted_data)
+                                Ok(decrypted_data.into())
                             }
                             SymmetricMode::Ecb => {
                                 // AES ECB decryption
@@ -256,7 +258,7 @@ impl KeyHandle for NksProvider {
                                     .unwrap();
                                 let rest = crypter.finalize(&mut decrypted_data[count..]).unwrap();
                                 decrypted_data.truncate(count + rest);
-                                Ok(decrypted_data)
+                                Ok(decrypted_data.into())
                             }
                             SymmetricMode::Cbc => {
                                 let cipher = match length {
@@ -277,7 +279,7 @@ impl KeyHandle for NksProvider {
                                     crypter.update(encrypted_data, &mut decrypted_data).unwrap();
                                 let rest = crypter.finalize(&mut decrypted_data[count..]).unwrap();
                                 decrypted_data.truncate(count + rest);
-                                Ok(decrypted_data)
+                                Ok(decrypted_data.into())
                             }
                             SymmetricMode::Cfb => {
                                 let cipher = match length {
@@ -297,7 +299,7 @@ impl KeyHandle for NksProvider {
                                     crypter.update(encrypted_data, &mut decrypted_data).unwrap();
                                 let rest = crypter.finalize(&mut decrypted_data[count..]).unwrap();
                                 decrypted_data.truncate(count + rest);
-                                Ok(decrypted_data)
+                                Ok(decrypted_data.into())
                             }
                             SymmetricMode::Ofb => {
                                 let cipher = match length {
@@ -317,7 +319,7 @@ impl KeyHandle for NksProvider {
                                     crypter.update(encrypted_data, &mut decrypted_data).unwrap();
                                 let rest = crypter.finalize(&mut decrypted_data[count..]).unwrap();
                                 decrypted_data.truncate(count + rest);
-                                Ok(decrypted_data)
+                                Ok(decrypted_data.into())
                             }
                             SymmetricMode::Ctr => {
                                 let cipher = match length {
@@ -337,7 +339,7 @@ impl KeyHandle for NksProvider {
                                     crypter.update(encrypted_data, &mut decrypted_data).unwrap();
                                 let rest = crypter.finalize(&mut decrypted_data[count..]).unwrap();
                                 decrypted_data.truncate(count + rest);
-                                Ok(decrypted_data)
+                                Ok(decrypted_data.into())
                             }
                             _ => Err(SecurityModuleError::UnsupportedAlgorithm),
                         }
@@ -357,7 +359,7 @@ impl KeyHandle for NksProvider {
     /// # Returns
     ///
     /// A `Result` containing the encrypted data as a `Vec<u8>` on success, or a `SecurityModuleError` on failure.
-    #[tracing::instrument]
+    #[tracing::instrument(skip_all, fields(crypto.key_id_hash = %key_id_hash(&self.key_id), crypto.payload.size = _data.len()))]
     fn encrypt_data(&self, _data: &[u8]) -> Result<Vec<u8>, SecurityModuleError> {
         let config = self
             .config
@@ -580,7 +582,7 @@ impl KeyHandle for NksProvider {
     /// # Returns
     ///
     /// A `Result` containing `true` if the signature is valid, `false` if it is invalid, or a `SecurityModuleError` on failure.
-    #[tracing::instrument]
+    #[tracing::instrument(skip_all, fields(crypto.key_id_hash = %key_id_hash(&self.key_id), crypto.payload.size = _data.len(), crypto.signature.size = _signature.len()))]
     fn verify_signature(
         &self,
         _data: &[u8],
@@ -672,7 +674,7 @@ impl KeyHandle for NksProvider {
                 }
             }
         } else {
-            println!("Failed to downcast to NksConfig");
+            tracing::error!("Failed to downcast to NksConfig");
             Err(SecurityModuleError::NksError)
         }
     }
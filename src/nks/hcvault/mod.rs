@@ -9,8 +9,10 @@
 //     },
 //     KeyUsage,
 // };
+use std::fmt;
 use std::sync::Arc;
 
+use crate::common::crypto::redact::Redacted;
 use crate::common::traits::module_provider_config::ProviderConfig;
 
 pub mod key_handle;
@@ -22,7 +24,9 @@ pub mod provider;
 /// This provider leverages the Network Key Storage (nks) to interact with a network
 /// module for operations like signing, encryption, and decryption. It provides a secure and
 /// network-backed implementation of cryptographic operations.
-#[derive(Clone, Debug)]
+///
+/// The `Debug` representation redacts the private key and the secrets, see `crypto::redact`.
+#[derive(Clone)]
 #[repr(C)]
 pub struct NksProvider {
     //TODO implement NksProvider struct
@@ -35,6 +39,19 @@ pub struct NksProvider {
     private_key: String,
 }
 
+impl fmt::Debug for NksProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let secrets = self.secrets_json.as_ref().map(|secrets| secrets.to_string());
+        f.debug_struct("NksProvider")
+            .field("key_id", &self.key_id)
+            .field("config", &self.config)
+            .field("secrets_json", &secrets.as_deref().map(|s| Redacted(s.as_bytes())))
+            .field("public_key", &self.public_key)
+            .field("private_key", &Redacted(self.private_key.as_bytes()))
+            .finish()
+    }
+}
+
 impl NksProvider {
     /// Constructs a new `NksProvider`.
     ///
@@ -9,12 +9,14 @@ use std::path::Path;
 use std::str::FromStr;
 use std::string::String;
 use tokio::runtime::Runtime;
-use tracing::instrument;
+use tracing::{debug, error, info, instrument, warn};
 
 use crate::common::crypto::algorithms::encryption::{
     BlockCiphers, EccCurves, EccSchemeAlgorithm, SymmetricMode,
 };
 use crate::common::crypto::algorithms::KeyBits;
+use crate::common::crypto::redact::Redacted;
+use crate::common::latency::key_id_hash;
 use crate::common::traits::module_provider_config::ProviderConfig;
 use crate::common::{
     crypto::algorithms::encryption::AsymmetricEncryption, error::SecurityModuleError,
@@ -45,7 +47,7 @@ impl Provider for NksProvider {
     /// let config = get_config("rsa").unwrap();
     /// provider.create_key("test_rsa_key", Box::new(config.clone())).expect("Failed to create RSA key");
     /// ```
-    #[instrument]
+    #[instrument(skip_all, fields(crypto.key_id_hash = %key_id_hash(key_id)))]
     fn create_key(
         &mut self,
         key_id: &str,
@@ -91,13 +93,10 @@ impl Provider for NksProvider {
                 Url::parse(&nks_config.nks_address).unwrap(),
                 cyphertype,
             ));
-            match key_length {
-                Some(kb) => println!("XXXXX Key length: {}", u32::from(kb)),
-                None => println!("XXXXX Key length: None"),
-            }
+            debug!(key_length = ?key_length.map(u32::from), "Generating key pair");
             match get_and_save_keypair_result {
                 Ok((result_string, new_token)) => {
-                    println!("Key pair generated and saved successfully.");
+                    info!("Key pair generated and saved successfully");
                     self.secrets_json = Some(result_string.parse().unwrap());
                     //safe token to config
                     let config = NksConfig::new(
@@ -118,12 +117,12 @@ impl Provider for NksProvider {
                     Ok(())
                 }
                 Err(err) => {
-                    println!("Failed to generate and save key pair: {}", err);
+                    error!(%err, "Failed to generate and save key pair");
                     Err(SecurityModuleError::NksError)
                 }
             }
         } else {
-            println!("Failed to downcast to NksConfig");
+            error!("Failed to downcast to NksConfig");
             Err(SecurityModuleError::NksError)
         }
     }
@@ -149,7 +148,7 @@ impl Provider for NksProvider {
     /// let config = get_config("rsa").unwrap();
     /// provider.load_key("test_rsa_key", Box::new(config.clone())).expect("Failed to load RSA key");
     /// ```
-    #[instrument]
+    #[instrument(skip_all, fields(crypto.key_id_hash = %key_id_hash(key_id)))]
     fn load_key(&mut self, key_id: &str, _config: Box<dyn Any>) -> Result<(), SecurityModuleError> {
         // Check if secrets_json is None
         if let Some(secrets_json) = &self.secrets_json {
@@ -163,19 +162,22 @@ impl Provider for NksProvider {
                             key.get("publicKey").unwrap().as_str().unwrap().to_string();
                         self.private_key =
                             key.get("privateKey").unwrap().as_str().unwrap().to_string();
-                        println!("Public Key: {}", self.public_key);
-                        println!("Private Key: {}", self.private_key);
+                        debug!(
+                            public_key = %self.public_key,
+                            private_key = ?Redacted(self.private_key.as_bytes()),
+                            "Key loaded"
+                        );
                         return Ok(());
                     }
                 }
             }
         } else {
-            println!("Secrets JSON is empty");
+            warn!("Secrets JSON is empty");
             return Err(SecurityModuleError::NksError);
         }
 
         // If no matching key is found, return an error
-        println!("Key '{}' not found in secrets_json", key_id);
+        warn!(key_id_hash = %key_id_hash(key_id), "Key not found in secrets_json");
         Err(SecurityModuleError::NksError)
     }
 
@@ -194,7 +196,7 @@ impl Provider for NksProvider {
     /// ```ignore
     /// provider.initialize_module().expect("Failed to initialize module");
     /// ```
-    #[instrument]
+    #[instrument(skip_all, fields(crypto.key_id_hash = %key_id_hash(&self.key_id)))]
     fn initialize_module(&mut self) -> Result<(), SecurityModuleError> {
         if let Some(nks_config) = self
             .config
@@ -208,14 +210,14 @@ impl Provider for NksProvider {
             let nks_address = Some(Url::from_str(nks_address_str.as_str()).unwrap());
             let mut nks_token = nks_config.nks_token.clone();
             if nks_token.is_empty() {
-                println!("Token field in config is empty. checking for token.json...");
+                debug!("Token field in config is empty, checking for token.json");
                 // Check if token file exists
                 let tokens_file_path = Box::new(Path::new("token.json")); // Adjust the path as needed
                 if Path::new(&*tokens_file_path).exists() {
-                    println!("Tokens file exists.");
+                    debug!("Tokens file exists");
                     nks_token = get_user_token_from_file().unwrap();
                 } else {
-                    println!("Token file does not exist. Generating token...");
+                    debug!("Token file does not exist, generating token");
                     // Token field empty and no token in token.json, generate token using API
                     let runtime = Runtime::new().unwrap();
                     let nks_address = nks_address.clone().ok_or(SecurityModuleError::NksError)?;
@@ -224,7 +226,7 @@ impl Provider for NksProvider {
                             nks_token = token;
                         }
                         Err(err) => {
-                            println!("Failed to get tokens from API: {}", err);
+                            error!(%err, "Failed to get tokens from API");
                             return Err(SecurityModuleError::NksError);
                         }
                     }
@@ -238,7 +240,7 @@ impl Provider for NksProvider {
                     nks_token = new_token;
                 }
                 Err(err) => {
-                    println!("Failed to get secrets: {}", err);
+                    error!(%err, "Failed to get secrets");
                     return Err(SecurityModuleError::NksError);
                 }
             }
@@ -257,10 +259,10 @@ impl Provider for NksProvider {
                 "user_token": nks_token.clone()
             });
             fs::write("token.json", token_data.to_string()).expect("Error writing to token.json");
-            println!("Nks initialized successfully.");
+            info!("Nks initialized successfully");
             Ok(())
         } else {
-            println!("Failed to downcast to NksConfig");
+            error!("Failed to downcast to NksConfig");
             Err(SecurityModuleError::NksError)
         }
     }
@@ -295,7 +297,7 @@ fn get_user_token_from_file() -> Option<String> {
     if let Some(user_token) = json["user_token"].as_str() {
         Some(user_token.to_string())
     } else {
-        println!("user_token not found or invalid format.");
+        warn!("user_token not found or invalid format");
         Some("no valid token".to_string())
     }
 }
@@ -342,7 +344,7 @@ async fn get_token(nks_address: Url) -> anyhow::Result<String, Box<dyn std::erro
             return Ok(user_token_str.to_string());
         }
     }
-    println!("The response does not contain a 'token' field");
+    error!("The response does not contain a 'token' field");
     Ok(String::new())
 }
 
@@ -400,7 +402,10 @@ async fn get_and_save_key_pair(
     let response_text = response.text().await?;
     if !status.is_success() {
         let response_json: Value = serde_json::from_str(&response_text)?;
-        println!("Response JSON: {}", response_json);
+        debug!(
+            response = ?Redacted(response_text.as_bytes()),
+            "Key pair request failed"
+        );
         if let Some(message) = response_json.get("message") {
             if message.as_str().unwrap() == format!("Key with ID {} already exists.", key_name) {
                 return Err(format!("A key with name {} already exists.", key_name).into());
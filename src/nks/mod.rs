@@ -1,8 +1,10 @@
 use crate::common::crypto::algorithms::encryption::{AsymmetricEncryption, BlockCiphers};
 use crate::common::crypto::algorithms::hashes::Hash;
+use crate::common::crypto::redact::Redacted;
 use crate::common::crypto::KeyUsage;
 use crate::common::traits::module_provider_config::ProviderConfig;
 use std::any::Any;
+use std::fmt;
 use std::sync::Arc;
 
 #[cfg(feature = "core")]
@@ -11,7 +13,9 @@ pub mod core;
 pub mod hcvault;
 
 /// Configuration for NKS (Network Key Storage).
-#[derive(Debug, Clone)]
+///
+/// The `Debug` representation redacts the token, see `crypto::redact`.
+#[derive(Clone)]
 pub struct NksConfig {
     /// The NKS token used for authentication.
     pub nks_token: String,
@@ -26,6 +30,19 @@ pub struct NksConfig {
     pub key_algorithm_sym: Option<BlockCiphers>,
 }
 
+impl fmt::Debug for NksConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NksConfig")
+            .field("nks_token", &Redacted(self.nks_token.as_bytes()))
+            .field("nks_address", &self.nks_address)
+            .field("key_algorithm", &self.key_algorithm)
+            .field("hash", &self.hash)
+            .field("key_usages", &self.key_usages)
+            .field("key_algorithm_sym", &self.key_algorithm_sym)
+            .finish()
+    }
+}
+
 impl ProviderConfig for NksConfig {
     /// Returns a reference to `self` as a trait object.
     fn as_any(&self) -> &dyn Any {
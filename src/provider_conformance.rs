@@ -0,0 +1,241 @@
+//! A conformance suite for the provider traits.
+//!
+//! Every backend is expected to behave identically from the perspective of an application, no
+//! matter whether its keys live in a TPM, a Secure Enclave or in memory. The checks in this module
+//! exercise the contract of `KeyHandle` on a provider that has a key created or loaded, and panic
+//! with a description of the violated part of the contract otherwise, so they can be called from
+//! the tests of any backend:
+//!
+//! ```rust,ignore
+//! provider.initialize_module()?;
+//! provider.create_key("conformance_key", config)?;
+//! crypto_layer::provider_conformance::run_all(&provider);
+//! ```
+//!
+//! Encryption is only checked if the key supports it. Providers signal keys that cannot encrypt
+//! by returning `SecurityModuleError::UnsupportedAlgorithm` from `encrypt_data`.
+
+use crate::common::{
+    crypto::secret::SecretBytes, error::SecurityModuleError, traits::module_provider::Provider,
+};
+use std::thread;
+
+/// The size of the input used by `large_input`.
+pub const LARGE_INPUT_SIZE: usize = 1024 * 1024;
+
+/// The number of threads used by `concurrent_access`.
+pub const CONCURRENT_THREADS: usize = 8;
+
+const UNICODE_INPUT: &str = "Grüße, 世界! Привет 👋🔐";
+
+/// Bytes that commonly break text based handling: NUL, line endings, escape, DEL and the high bit.
+const BINARY_INPUT: &[u8] = &[
+    0x00, 0xff, 0x00, 0x0a, 0x0d, 0x1b, 0x7f, 0x80, 0xfe, 0x01, 0x00, 0x00,
+];
+
+/// Runs all conformance checks against the current key of `provider`.
+///
+/// # Arguments
+///
+/// * `provider` - An initialized provider with a created or loaded key.
+///
+/// # Panics
+///
+/// Panics if the provider violates any part of the contract.
+pub fn run_all(provider: &dyn Provider) {
+    sign_verify_round_trip(provider);
+    encryption_round_trip(provider);
+    tamper_detection(provider);
+    binary_data(provider);
+    empty_input(provider);
+    large_input(provider);
+    unicode(provider);
+    concurrent_access(provider);
+}
+
+/// Checks that signatures created by `sign_data` are accepted by `verify_signature` and
+/// `verify_many`, and that signing the same data twice yields valid signatures.
+pub fn sign_verify_round_trip(provider: &dyn Provider) {
+    let data = b"Hello, World!";
+    assert_sign_verify(provider, data, "sign_verify_round_trip");
+
+    let first = sign(provider, data, "sign_verify_round_trip");
+    let second = sign(provider, data, "sign_verify_round_trip");
+    let results = provider
+        .verify_many(&[(data, &first), (data, &second), (b"Hello, World?", &first)])
+        .unwrap_or_else(|e| panic!("sign_verify_round_trip: verify_many failed: {e}"));
+    assert_eq!(
+        results,
+        vec![true, true, false],
+        "sign_verify_round_trip: verify_many disagrees with verify_signature"
+    );
+}
+
+/// Checks that `decrypt_data` restores the data passed to `encrypt_data`.
+pub fn encryption_round_trip(provider: &dyn Provider) {
+    if supports_encryption(provider) {
+        assert_encrypt_decrypt(provider, b"Hello, World!", "encryption_round_trip");
+    }
+}
+
+/// Checks that modified data, modified signatures and modified ciphertexts are rejected.
+///
+/// A rejected signature may either be reported as `Ok(false)` or as an error, a modified
+/// ciphertext must not decrypt to the original data.
+pub fn tamper_detection(provider: &dyn Provider) {
+    let data = b"Transfer 100 EUR to Alice";
+    let signature = sign(provider, data, "tamper_detection");
+
+    let mut tampered_data = data.to_vec();
+    tampered_data[9] ^= 0x01;
+    assert!(
+        !matches!(
+            provider.verify_signature(&tampered_data, &signature),
+            Ok(true)
+        ),
+        "tamper_detection: signature accepted for modified data"
+    );
+
+    let mut tampered_signature = signature.clone();
+    let middle = tampered_signature.len() / 2;
+    tampered_signature[middle] ^= 0x01;
+    assert!(
+        !matches!(
+            provider.verify_signature(data, &tampered_signature),
+            Ok(true)
+        ),
+        "tamper_detection: modified signature accepted"
+    );
+
+    assert!(
+        !matches!(provider.verify_signature(data, &[]), Ok(true)),
+        "tamper_detection: empty signature accepted"
+    );
+
+    if supports_encryption(provider) {
+        let mut ciphertext = encrypt(provider, data, "tamper_detection");
+        let middle = ciphertext.len() / 2;
+        ciphertext[middle] ^= 0x01;
+        assert!(
+            !matches!(provider.decrypt_data(&ciphertext), Ok(decrypted) if decrypted == data),
+            "tamper_detection: modified ciphertext decrypted to the original data"
+        );
+    }
+}
+
+/// Checks that arbitrary bytes, including NUL bytes and bytes above `0x7f`, are handled unchanged.
+pub fn binary_data(provider: &dyn Provider) {
+    assert_sign_verify(provider, BINARY_INPUT, "binary_data");
+    if supports_encryption(provider) {
+        assert_encrypt_decrypt(provider, BINARY_INPUT, "binary_data");
+    }
+}
+
+/// Checks that empty data can be signed and encrypted.
+pub fn empty_input(provider: &dyn Provider) {
+    assert_sign_verify(provider, &[], "empty_input");
+    if supports_encryption(provider) {
+        assert_encrypt_decrypt(provider, &[], "empty_input");
+    }
+}
+
+/// Checks that `LARGE_INPUT_SIZE` bytes can be signed.
+///
+/// Encryption is not checked, as asymmetric encryption is limited to a few hundred bytes.
+pub fn large_input(provider: &dyn Provider) {
+    let data: Vec<u8> = (0..LARGE_INPUT_SIZE).map(|i| (i % 251) as u8).collect();
+    assert_sign_verify(provider, &data, "large_input");
+}
+
+/// Checks that UTF-8 encoded text with multi-byte characters survives signing and encryption.
+pub fn unicode(provider: &dyn Provider) {
+    assert_sign_verify(provider, UNICODE_INPUT.as_bytes(), "unicode");
+    if supports_encryption(provider) {
+        let encrypted = encrypt(provider, UNICODE_INPUT.as_bytes(), "unicode");
+        let decrypted = provider
+            .decrypt_data(&encrypted)
+            .unwrap_or_else(|e| panic!("unicode: decrypt_data failed: {e}"));
+        assert_eq!(
+            std::str::from_utf8(&decrypted),
+            Ok(UNICODE_INPUT),
+            "unicode: decrypted text differs"
+        );
+    }
+}
+
+/// Checks that `CONCURRENT_THREADS` threads can sign, verify and encrypt with the same provider
+/// at the same time, and that every thread gets the results for its own data.
+pub fn concurrent_access(provider: &dyn Provider) {
+    let encryption = supports_encryption(provider);
+    thread::scope(|scope| {
+        for thread in 0..CONCURRENT_THREADS {
+            scope.spawn(move || {
+                for round in 0..4 {
+                    let data = format!("thread {thread}, round {round}").into_bytes();
+                    let signature = sign(provider, &data, "concurrent_access");
+                    assert!(
+                        verify(provider, &data, &signature, "concurrent_access"),
+                        "concurrent_access: signature of thread {thread} rejected"
+                    );
+                    if encryption {
+                        let encrypted = encrypt(provider, &data, "concurrent_access");
+                        assert_eq!(
+                            decrypt(provider, &encrypted, "concurrent_access"),
+                            data,
+                            "concurrent_access: thread {thread} decrypted foreign data"
+                        );
+                    }
+                }
+            });
+        }
+    });
+}
+
+fn supports_encryption(provider: &dyn Provider) -> bool {
+    match provider.encrypt_data(b"probe") {
+        Ok(_) => true,
+        Err(SecurityModuleError::UnsupportedAlgorithm) => false,
+        Err(e) => panic!("encrypt_data failed with an error other than UnsupportedAlgorithm: {e}"),
+    }
+}
+
+fn assert_sign_verify(provider: &dyn Provider, data: &[u8], check: &str) {
+    let signature = sign(provider, data, check);
+    assert!(
+        verify(provider, data, &signature, check),
+        "{check}: valid signature rejected"
+    );
+}
+
+fn assert_encrypt_decrypt(provider: &dyn Provider, data: &[u8], check: &str) {
+    let encrypted = encrypt(provider, data, check);
+    assert_eq!(
+        decrypt(provider, &encrypted, check),
+        data,
+        "{check}: decrypted data differs"
+    );
+}
+
+fn sign(provider: &dyn Provider, data: &[u8], check: &str) -> Vec<u8> {
+    provider
+        .sign_data(data)
+        .unwrap_or_else(|e| panic!("{check}: sign_data failed: {e}"))
+}
+
+fn verify(provider: &dyn Provider, data: &[u8], signature: &[u8], check: &str) -> bool {
+    provider
+        .verify_signature(data, signature)
+        .unwrap_or_else(|e| panic!("{check}: verify_signature failed: {e}"))
+}
+
+fn encrypt(provider: &dyn Provider, data: &[u8], check: &str) -> Vec<u8> {
+    provider
+        .encrypt_data(data)
+        .unwrap_or_else(|e| panic!("{check}: encrypt_data failed: {e}"))
+}
+
+fn decrypt(provider: &dyn Provider, encrypted: &[u8], check: &str) -> SecretBytes {
+    provider
+        .decrypt_data(encrypted)
+        .unwrap_or_else(|e| panic!("{check}: decrypt_data failed: {e}"))
+}
@@ -0,0 +1,452 @@
+use super::{
+    error, DataRequest, DataResponse, KeyRequest, KeyResponse, RemoteConfig, VerifyRequest,
+    VerifyResponse, MAX_MESSAGE_LEN, SERVICE_PATH,
+};
+use crate::common::{
+    crypto::{
+        key_metadata::KeyMetadata, key_spec::KeySpec, public_key::PublicKey, secret::SecretBytes,
+    },
+    error::SecurityModuleError,
+    grpc::{self, GrpcStatus, GRPC_DEADLINE_EXCEEDED, GRPC_UNAVAILABLE},
+    traits::{key_handle::KeyHandle, module_provider::Provider},
+};
+use bytes::Bytes;
+use h2::client::SendRequest;
+use http::{header::AUTHORIZATION, HeaderMap, HeaderValue};
+use std::{
+    any::Any,
+    fmt,
+    future::Future,
+    sync::{mpsc, Mutex},
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    runtime::{self, Runtime},
+};
+use tracing::instrument;
+
+/// The time a call may take before it fails, unless changed with `with_timeout`.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A provider whose keys live in the security module behind a `RemoteServer`, see the module
+/// documentation.
+///
+/// The provider runs its connection on a runtime of its own, so it can be used from
+/// synchronous code as well as from within a Tokio runtime. A connection that broke, e.g.
+/// because the server restarted, is replaced on the next call, and the key is loaded again if
+/// the server forgot it.
+pub struct RemoteProvider {
+    endpoint: String,
+    token: Option<String>,
+    timeout: Duration,
+    connection: Option<Connection>,
+    key: Option<RemoteKey>,
+}
+
+struct Connection {
+    /// Only `None` while the connection is dropped.
+    runtime: Option<Runtime>,
+    headers: HeaderMap,
+    client: Mutex<Option<SendRequest<Bytes>>>,
+}
+
+struct RemoteKey {
+    request: KeyRequest,
+    spec: KeySpec,
+    metadata: Option<KeyMetadata>,
+}
+
+impl RemoteProvider {
+    /// Creates a provider for the server at `endpoint`. The provider connects in
+    /// `initialize_module`.
+    ///
+    /// # Arguments
+    ///
+    /// * `endpoint` - A TCP address such as `signing-host:7443`, or the path of a Unix domain
+    ///   socket prefixed with `unix:`.
+    pub fn new(endpoint: impl Into<String>) -> Self {
+        Self {
+            endpoint: endpoint.into(),
+            token: None,
+            timeout: DEFAULT_TIMEOUT,
+            connection: None,
+            key: None,
+        }
+    }
+
+    /// Sends `token` as bearer token with every call.
+    pub fn with_token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    /// Fails calls that take longer than `timeout`, which defaults to 30 seconds.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    fn connection(&self) -> Result<&Connection, SecurityModuleError> {
+        self.connection.as_ref().ok_or_else(|| {
+            SecurityModuleError::InitializationError("Module not initialized".to_owned())
+        })
+    }
+
+    fn key(&self) -> Result<&RemoteKey, SecurityModuleError> {
+        self.key.as_ref().ok_or(SecurityModuleError::KeyError)
+    }
+
+    /// Calls `method` of the server, reconnecting once if the connection broke.
+    fn unary(&self, method: &str, request: &[u8]) -> Result<Vec<u8>, SecurityModuleError> {
+        let connection = self.connection()?;
+        let path = format!("{}{}", SERVICE_PATH, method);
+        let mut reconnected = false;
+        loop {
+            let cached = lock(&connection.client).clone();
+            let client = match cached {
+                Some(client) => client,
+                None => {
+                    reconnected = true;
+                    let client = connection.run(connect(self.endpoint.clone()), self.timeout);
+                    let client = client.map_err(error)?;
+                    *lock(&connection.client) = Some(client.clone());
+                    client
+                }
+            };
+            let headers = connection.headers.clone();
+            let message = request.to_vec();
+            let path = path.clone();
+            let result = connection.run(
+                async move { grpc::call(&client, &path, &headers, &message, MAX_MESSAGE_LEN).await },
+                self.timeout,
+            );
+            match result {
+                // Statuses with an error code come from the server, not from a broken connection.
+                Err(status)
+                    if status.code == GRPC_UNAVAILABLE
+                        && status.error_code.is_none()
+                        && !reconnected =>
+                {
+                    tracing::debug!(message = %status.message, "remote provider reconnects");
+                    *lock(&connection.client) = None;
+                }
+                result => return result.map_err(error),
+            }
+        }
+    }
+
+    /// Calls `method` with a request for the current key, and loads the key again if the
+    /// server does not know it, e.g. because it restarted.
+    fn key_call(
+        &self,
+        method: &str,
+        request: impl Fn(String) -> Vec<u8>,
+    ) -> Result<Vec<u8>, SecurityModuleError> {
+        let key = self.key()?;
+        let message = request(key.request.key_id.clone());
+        match self.unary(method, &message) {
+            Err(SecurityModuleError::KeyError) => {
+                tracing::debug!("remote provider loads its key again");
+                self.unary("LoadKey", &key.request.to_bytes())?;
+                self.unary(method, &message)
+            }
+            result => result,
+        }
+    }
+
+    fn data_call(&self, method: &str, data: &[u8]) -> Result<Vec<u8>, SecurityModuleError> {
+        let response = self.key_call(method, |key_id| {
+            DataRequest {
+                key_id,
+                data: data.to_vec(),
+            }
+            .to_bytes()
+        })?;
+        Ok(DataResponse::from_bytes(&response)?.data)
+    }
+
+    /// Sends `CreateKey` or `LoadKey` and makes the key the current key.
+    fn open_key(
+        &mut self,
+        method: &str,
+        key_id: &str,
+        config: Box<dyn Any>,
+    ) -> Result<(), SecurityModuleError> {
+        let config = config.downcast::<RemoteConfig>().map_err(|_| {
+            SecurityModuleError::InitializationError("Failed to initialize config".to_owned())
+        })?;
+        let request = KeyRequest::new(key_id, &config.spec);
+        let remote_spec = request.spec()?;
+        // `Hash` has no `PartialEq`, its variants are told apart by their names.
+        if format!("{:?}", remote_spec.hash()) != format!("{:?}", config.spec.hash()) {
+            return Err(SecurityModuleError::InvalidKeySpec(format!(
+                "The remote provider only supports the hash {:?} for this algorithm",
+                remote_spec.hash()
+            )));
+        }
+
+        // A failed call leaves the previous key loaded, as with local providers.
+        let response = KeyResponse::from_bytes(&self.unary(method, &request.to_bytes())?)?;
+        let metadata = metadata(key_id, &config.spec, &response)?;
+        self.key = Some(RemoteKey {
+            request,
+            spec: config.spec,
+            metadata,
+        });
+        Ok(())
+    }
+}
+
+impl Provider for RemoteProvider {
+    /// Creates a key in the security module of the server.
+    ///
+    /// # Arguments
+    ///
+    /// * `key_id` - A string slice that uniquely identifies the key to be created.
+    /// * `config` - A boxed `RemoteConfig` with the spec of the key.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` that, on success, contains `Ok(())`. On failure, it returns the error of the
+    /// server, or a `SecurityModuleError::InvalidKeySpec` if the spec has a hash other than the
+    /// default of its algorithm.
+    #[instrument(skip(self, config))]
+    fn create_key(
+        &mut self,
+        key_id: &str,
+        config: Box<dyn Any>,
+    ) -> Result<(), SecurityModuleError> {
+        self.open_key("CreateKey", key_id, config)
+    }
+
+    /// Loads a key from the security module of the server.
+    ///
+    /// # Arguments
+    ///
+    /// * `key_id` - A string slice that uniquely identifies the key to be loaded.
+    /// * `config` - A boxed `RemoteConfig` with the spec the key was created with.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` that, on success, contains `Ok(())`. On failure, it returns the error of the
+    /// server, e.g. a `SecurityModuleError::KeyError` if the key does not exist.
+    #[instrument(skip(self, config))]
+    fn load_key(&mut self, key_id: &str, config: Box<dyn Any>) -> Result<(), SecurityModuleError> {
+        self.open_key("LoadKey", key_id, config)
+    }
+
+    /// Connects to the server.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` that, on success, contains `Ok(())`. On failure, it returns a
+    /// `SecurityModuleError::InitializationError` if the server is unreachable, or a
+    /// `SecurityModuleError::InvalidToken` if the token cannot be sent in a header.
+    #[instrument(skip(self), fields(endpoint = %self.endpoint))]
+    fn initialize_module(&mut self) -> Result<(), SecurityModuleError> {
+        let mut headers = HeaderMap::new();
+        if let Some(token) = &self.token {
+            let value = HeaderValue::from_str(&format!("Bearer {}", token)).map_err(|_| {
+                SecurityModuleError::InvalidToken(
+                    "The token contains characters that are not allowed in a header".to_owned(),
+                )
+            })?;
+            headers.insert(AUTHORIZATION, value);
+        }
+        let runtime = runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("crypto-layer-remote")
+            .enable_all()
+            .build()
+            .map_err(|e| SecurityModuleError::InitializationError(e.to_string()))?;
+        let connection = Connection {
+            runtime: Some(runtime),
+            headers,
+            client: Mutex::new(None),
+        };
+        let client = connection
+            .run(connect(self.endpoint.clone()), self.timeout)
+            .map_err(error)?;
+        *lock(&connection.client) = Some(client);
+        self.connection = Some(connection);
+        Ok(())
+    }
+
+    /// Returns the metadata of the current key, fetched when it was created or loaded.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `KeyMetadata`, a `SecurityModuleError::KeyError` if no key is
+    /// loaded, or a `SecurityModuleError::UnsupportedOperation` for symmetric keys.
+    fn key_metadata(&self) -> Result<KeyMetadata, SecurityModuleError> {
+        self.key()?.metadata.clone().ok_or_else(|| {
+            SecurityModuleError::UnsupportedOperation("Symmetric keys have no metadata".to_owned())
+        })
+    }
+
+    /// Loads the current key again to fetch its metadata from the server.
+    fn refresh_key_metadata(&mut self) -> Result<KeyMetadata, SecurityModuleError> {
+        let key = self.key()?;
+        let response = KeyResponse::from_bytes(&self.unary("LoadKey", &key.request.to_bytes())?)?;
+        let metadata = metadata(&key.request.key_id, &key.spec, &response)?;
+        let key = self.key.as_mut().ok_or(SecurityModuleError::KeyError)?;
+        key.metadata = metadata;
+        self.key_metadata()
+    }
+
+    /// Closes the connection and forgets the current key. Keys stay on the server.
+    fn shutdown(&mut self) -> Result<(), SecurityModuleError> {
+        self.key = None;
+        self.connection = None;
+        Ok(())
+    }
+}
+
+impl KeyHandle for RemoteProvider {
+    /// Signs `data` with the current key on the server.
+    #[instrument(skip_all)]
+    fn sign_data(&self, data: &[u8]) -> Result<Vec<u8>, SecurityModuleError> {
+        self.data_call("Sign", data)
+    }
+
+    /// Decrypts `encrypted_data` with the current key on the server.
+    #[instrument(skip_all)]
+    fn decrypt_data(&self, encrypted_data: &[u8]) -> Result<SecretBytes, SecurityModuleError> {
+        self.data_call("Decrypt", encrypted_data)
+            .map(SecretBytes::new)
+    }
+
+    /// Encrypts `data` with the current key on the server.
+    #[instrument(skip_all)]
+    fn encrypt_data(&self, data: &[u8]) -> Result<Vec<u8>, SecurityModuleError> {
+        self.data_call("Encrypt", data)
+    }
+
+    /// Verifies `signature` with the current key on the server.
+    #[instrument(skip_all)]
+    fn verify_signature(&self, data: &[u8], signature: &[u8]) -> Result<bool, SecurityModuleError> {
+        let response = self.key_call("Verify", |key_id| {
+            VerifyRequest {
+                key_id,
+                data: data.to_vec(),
+                signature: signature.to_vec(),
+            }
+            .to_bytes()
+        })?;
+        Ok(VerifyResponse::from_bytes(&response)?.valid)
+    }
+
+    /// Derives a shared secret with the current key on the server.
+    #[instrument(skip_all)]
+    fn derive_shared_secret(
+        &self,
+        peer_public_key: &[u8],
+    ) -> Result<SecretBytes, SecurityModuleError> {
+        self.data_call("DeriveSharedSecret", peer_public_key)
+            .map(SecretBytes::new)
+    }
+}
+
+impl fmt::Debug for RemoteProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RemoteProvider")
+            .field("endpoint", &self.endpoint)
+            .field("key_id", &self.key.as_ref().map(|key| &key.request.key_id))
+            .field("connected", &self.connection.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Connection {
+    /// Runs `future` on the runtime of the connection and waits for its result.
+    ///
+    /// Waiting on a channel instead of `Runtime::block_on` keeps the provider usable from
+    /// within other runtimes.
+    fn run<T: Send + 'static>(
+        &self,
+        future: impl Future<Output = Result<T, GrpcStatus>> + Send + 'static,
+        timeout: Duration,
+    ) -> Result<T, GrpcStatus> {
+        let runtime = self
+            .runtime
+            .as_ref()
+            .expect("the runtime lives as long as the connection");
+        let (sender, receiver) = mpsc::channel();
+        runtime.spawn(async move {
+            let result = tokio::time::timeout(timeout, future)
+                .await
+                .unwrap_or_else(|_| {
+                    Err(GrpcStatus::new(
+                        GRPC_DEADLINE_EXCEEDED,
+                        format!("The call did not finish within {:?}", timeout),
+                    ))
+                });
+            let _ = sender.send(result);
+        });
+        receiver.recv().unwrap_or_else(|_| {
+            Err(GrpcStatus::new(
+                GRPC_UNAVAILABLE,
+                "The runtime of the connection stopped",
+            ))
+        })
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        // Dropping a runtime blocks, which panics within another runtime.
+        if let Some(runtime) = self.runtime.take() {
+            runtime.shutdown_background();
+        }
+    }
+}
+
+/// Opens an HTTP/2 connection to `endpoint` and drives it on a task of the current runtime.
+async fn connect(endpoint: String) -> Result<SendRequest<Bytes>, GrpcStatus> {
+    let unavailable =
+        |e: &dyn fmt::Display| GrpcStatus::new(GRPC_UNAVAILABLE, format!("{}: {}", endpoint, e));
+    #[cfg(unix)]
+    if let Some(path) = endpoint.strip_prefix("unix:") {
+        let stream = tokio::net::UnixStream::connect(path)
+            .await
+            .map_err(|e| unavailable(&e))?;
+        return handshake(stream).await.map_err(|e| unavailable(&e));
+    }
+    let stream = tokio::net::TcpStream::connect(endpoint.as_str())
+        .await
+        .map_err(|e| unavailable(&e))?;
+    let _ = stream.set_nodelay(true);
+    handshake(stream).await.map_err(|e| unavailable(&e))
+}
+
+async fn handshake(
+    io: impl AsyncRead + AsyncWrite + Unpin + Send + 'static,
+) -> Result<SendRequest<Bytes>, h2::Error> {
+    let (client, connection) = h2::client::handshake(io).await?;
+    tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            tracing::debug!(error = %e, "remote provider connection closed");
+        }
+    });
+    Ok(client)
+}
+
+/// Builds the metadata of a key from the response of `CreateKey` or `LoadKey`.
+fn metadata(
+    key_id: &str,
+    spec: &KeySpec,
+    response: &KeyResponse,
+) -> Result<Option<KeyMetadata>, SecurityModuleError> {
+    let Some(algorithm) = spec.asymmetric_algorithm() else {
+        return Ok(None);
+    };
+    let public_key = PublicKey::from_der(&response.public_key, algorithm, spec.hash())?;
+    Ok(Some(
+        KeyMetadata::new(key_id, public_key)?.with_exportable(spec.exportable()),
+    ))
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
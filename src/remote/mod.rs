@@ -0,0 +1,420 @@
+//! A provider that forwards its operations over gRPC to the security module of another host.
+//!
+//! Services without a security module of their own, e.g. a fleet service on a Linux VM, use a
+//! `RemoteProvider` like any other provider, while the keys stay in the TPM of a signing host
+//! that runs a `RemoteServer` in front of its local provider:
+//!
+//! ```rust,ignore
+//! use crypto_layer::remote::{RemoteConfig, RemoteProvider};
+//!
+//! let mut provider = RemoteProvider::new("signing-host:7443").with_token(token);
+//! provider.initialize_module()?;
+//! provider.load_key("release-signing", RemoteConfig::from_spec(&spec)?)?;
+//! let signature = provider.sign_data(artifact)?;
+//! ```
+//!
+//! On the signing host, the `remote-provider` binary or
+//!
+//! ```rust,ignore
+//! use crypto_layer::remote::RemoteServer;
+//!
+//! let server = RemoteServer::new(provider, TpmConfig::from_spec).with_token(token);
+//! Arc::new(server).listen("0.0.0.0:7443").await?;
+//! ```
+//!
+//! serves the gRPC service `cryptolayer.remote.v1.RemoteProvider` of `remote.proto`. Keys
+//! are created and loaded by their `KeySpec`, which the server turns into the configuration of
+//! its provider; the hash of the spec is the default hash of its algorithm. Errors of the
+//! server arrive at the client as the same `SecurityModuleError` variant, except for errors
+//! that cannot be rebuilt from their code and message, e.g. TPM errors, which arrive as
+//! `SecurityModuleError::InitializationError`.
+//!
+//! Calls are authenticated with a bearer token when one is configured. The connection itself
+//! is plaintext HTTP/2, so across hosts it must run inside a tunnel that encrypts and
+//! authenticates the hosts, e.g. WireGuard, SSH port forwarding of the Unix socket or a TLS
+//! terminating proxy.
+
+use crate::common::{
+    crypto::key_spec::{AccessControl, KeyAlgorithm, KeyPurpose, KeySpec},
+    error::SecurityModuleError,
+    grpc::{self, fields, put_field, put_uint, string, uint, GrpcStatus},
+    traits::module_provider_config::ProviderConfig,
+};
+use std::any::Any;
+
+mod client;
+mod server;
+
+pub use client::RemoteProvider;
+pub use server::RemoteServer;
+
+/// The longest gRPC message the client and the server accept in bytes.
+pub const MAX_MESSAGE_LEN: usize = 4 * 1024 * 1024;
+
+const SERVICE_PATH: &str = "/cryptolayer.remote.v1.RemoteProvider/";
+
+/// The configuration of a key of a `RemoteProvider`, passed to `create_key` and `load_key`.
+#[derive(Debug, Clone)]
+pub struct RemoteConfig {
+    /// The spec the server creates or loads the key with.
+    pub spec: KeySpec,
+}
+
+impl RemoteConfig {
+    /// Creates a boxed `RemoteConfig` from a validated `KeySpec`. Whether the security module
+    /// of the server supports the spec is only known when the key is created or loaded.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the config, which is always `Ok` and only a `Result` to match the
+    /// `from_spec` of the other providers.
+    pub fn from_spec(spec: &KeySpec) -> Result<Box<dyn Any>, SecurityModuleError> {
+        Ok(Box::new(Self { spec: spec.clone() }))
+    }
+}
+
+impl ProviderConfig for RemoteConfig {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// The request of `CreateKey` and `LoadKey`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct KeyRequest {
+    key_id: String,
+    /// A `KeyAlgorithm` of `remote.proto`, numbered as the `CRYPTO_LAYER_ALGORITHM_*`
+    /// constants of the C API.
+    algorithm: u64,
+    /// The `CRYPTO_LAYER_PURPOSE_*` flags of the C API.
+    purposes: u64,
+    /// An `AccessControl` of `remote.proto`.
+    access: u64,
+    exportable: bool,
+}
+
+/// The response of `CreateKey` and `LoadKey`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct KeyResponse {
+    /// The DER encoded `SubjectPublicKeyInfo`, empty for symmetric keys.
+    public_key: Vec<u8>,
+}
+
+/// The request of `Sign`, `Encrypt`, `Decrypt` and `DeriveSharedSecret`.
+#[derive(Clone, Default, PartialEq, Eq)]
+struct DataRequest {
+    key_id: String,
+    data: Vec<u8>,
+}
+
+/// The response of `Sign`, `Encrypt`, `Decrypt` and `DeriveSharedSecret`.
+#[derive(Clone, Default, PartialEq, Eq)]
+struct DataResponse {
+    data: Vec<u8>,
+}
+
+/// The request of `Verify`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct VerifyRequest {
+    key_id: String,
+    data: Vec<u8>,
+    signature: Vec<u8>,
+}
+
+/// The response of `Verify`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct VerifyResponse {
+    valid: bool,
+}
+
+const ALGORITHMS: [(u64, KeyAlgorithm); 8] = [
+    (1, KeyAlgorithm::EcP256),
+    (2, KeyAlgorithm::EcP384),
+    (3, KeyAlgorithm::EcP521),
+    (4, KeyAlgorithm::Rsa2048),
+    (5, KeyAlgorithm::Rsa3072),
+    (6, KeyAlgorithm::Rsa4096),
+    (7, KeyAlgorithm::Aes128Gcm),
+    (8, KeyAlgorithm::Aes256Gcm),
+];
+
+const PURPOSES: [(u64, KeyPurpose); 3] = [
+    (1, KeyPurpose::Sign),
+    (2, KeyPurpose::Encrypt),
+    (4, KeyPurpose::KeyAgreement),
+];
+
+const ACCESS: [(u64, AccessControl); 5] = [
+    (0, AccessControl::None),
+    (1, AccessControl::UserPresence),
+    (2, AccessControl::BiometryAny),
+    (3, AccessControl::BiometryCurrentSet),
+    (4, AccessControl::DevicePasscode),
+];
+
+/// Returns the id of `value` in `table`.
+fn id<T: Copy + PartialEq>(table: &[(u64, T)], value: T) -> u64 {
+    table
+        .iter()
+        .find(|(_, entry)| *entry == value)
+        .map_or(0, |(id, _)| *id)
+}
+
+/// Returns the entry of `table` with the id `id`.
+fn entry<T: Copy>(table: &[(u64, T)], id: u64) -> Option<T> {
+    table
+        .iter()
+        .find(|(entry_id, _)| *entry_id == id)
+        .map(|(_, entry)| *entry)
+}
+
+impl KeyRequest {
+    fn new(key_id: &str, spec: &KeySpec) -> Self {
+        Self {
+            key_id: key_id.to_owned(),
+            algorithm: id(&ALGORITHMS, spec.algorithm()),
+            purposes: spec.purposes().map(|purpose| id(&PURPOSES, purpose)).sum(),
+            access: id(&ACCESS, spec.access()),
+            exportable: spec.exportable(),
+        }
+    }
+
+    /// Returns the spec of the request, labeled with its key id.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the spec, or a `SecurityModuleError::InvalidKeySpec` if a field
+    /// has an unknown value or the spec is invalid.
+    fn spec(&self) -> Result<KeySpec, SecurityModuleError> {
+        let unknown = |field: &str, value: u64| {
+            SecurityModuleError::InvalidKeySpec(format!("Unknown {} {}", field, value))
+        };
+        let algorithm = entry(&ALGORITHMS, self.algorithm)
+            .ok_or_else(|| unknown("algorithm", self.algorithm))?;
+        let access =
+            entry(&ACCESS, self.access).ok_or_else(|| unknown("access control", self.access))?;
+        let known = PURPOSES.iter().fold(0, |flags, (flag, _)| flags | flag);
+        if self.purposes & !known != 0 {
+            return Err(unknown("purposes", self.purposes));
+        }
+
+        let mut builder = KeySpec::builder()
+            .algorithm(algorithm)
+            .access(access)
+            .exportable(self.exportable)
+            .label(self.key_id.as_str());
+        for (flag, purpose) in PURPOSES {
+            if self.purposes & flag != 0 {
+                builder = builder.usage(purpose);
+            }
+        }
+        builder.build()
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        put_field(&mut bytes, 1, self.key_id.as_bytes());
+        put_uint(&mut bytes, 2, self.algorithm);
+        put_uint(&mut bytes, 3, self.purposes);
+        put_uint(&mut bytes, 4, self.access);
+        put_uint(&mut bytes, 5, self.exportable.into());
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self, SecurityModuleError> {
+        let mut request = Self::default();
+        for (field, value) in fields(bytes)? {
+            match field {
+                1 => request.key_id = string(value)?,
+                2 => request.algorithm = uint(value)?,
+                3 => request.purposes = uint(value)?,
+                4 => request.access = uint(value)?,
+                5 => request.exportable = uint(value)? != 0,
+                _ => {}
+            }
+        }
+        Ok(request)
+    }
+}
+
+impl KeyResponse {
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        put_field(&mut bytes, 1, &self.public_key);
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self, SecurityModuleError> {
+        let mut response = Self::default();
+        for (field, value) in fields(bytes)? {
+            if field == 1 {
+                response.public_key = grpc::bytes(value)?.to_vec();
+            }
+        }
+        Ok(response)
+    }
+}
+
+impl DataRequest {
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        put_field(&mut bytes, 1, self.key_id.as_bytes());
+        put_field(&mut bytes, 2, &self.data);
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self, SecurityModuleError> {
+        let mut request = Self::default();
+        for (field, value) in fields(bytes)? {
+            match field {
+                1 => request.key_id = string(value)?,
+                2 => request.data = grpc::bytes(value)?.to_vec(),
+                _ => {}
+            }
+        }
+        Ok(request)
+    }
+}
+
+impl DataResponse {
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        put_field(&mut bytes, 1, &self.data);
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self, SecurityModuleError> {
+        let mut response = Self::default();
+        for (field, value) in fields(bytes)? {
+            if field == 1 {
+                response.data = grpc::bytes(value)?.to_vec();
+            }
+        }
+        Ok(response)
+    }
+}
+
+impl VerifyRequest {
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        put_field(&mut bytes, 1, self.key_id.as_bytes());
+        put_field(&mut bytes, 2, &self.data);
+        put_field(&mut bytes, 3, &self.signature);
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self, SecurityModuleError> {
+        let mut request = Self::default();
+        for (field, value) in fields(bytes)? {
+            match field {
+                1 => request.key_id = string(value)?,
+                2 => request.data = grpc::bytes(value)?.to_vec(),
+                3 => request.signature = grpc::bytes(value)?.to_vec(),
+                _ => {}
+            }
+        }
+        Ok(request)
+    }
+}
+
+impl VerifyResponse {
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        put_uint(&mut bytes, 1, self.valid.into());
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self, SecurityModuleError> {
+        let mut response = Self::default();
+        for (field, value) in fields(bytes)? {
+            if field == 1 {
+                response.valid = uint(value)? != 0;
+            }
+        }
+        Ok(response)
+    }
+}
+
+// The data of requests and responses may be plaintexts and must not end up in logs.
+impl std::fmt::Debug for DataRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DataRequest")
+            .field("key_id", &self.key_id)
+            .finish_non_exhaustive()
+    }
+}
+
+impl std::fmt::Debug for DataResponse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DataResponse").finish_non_exhaustive()
+    }
+}
+
+/// Converts an error of the server to the status of the call, with the message of the error
+/// without the prefix of its variant, so that the client can rebuild the error.
+fn status(error: SecurityModuleError) -> GrpcStatus {
+    let message = match &error {
+        SecurityModuleError::SigningError(message)
+        | SecurityModuleError::DecryptionError(message)
+        | SecurityModuleError::EncryptionError(message)
+        | SecurityModuleError::SignatureVerificationError(message)
+        | SecurityModuleError::InitializationError(message)
+        | SecurityModuleError::AuthenticationFailed(message)
+        | SecurityModuleError::InvalidKeySpec(message)
+        | SecurityModuleError::InvalidKeyId(message)
+        | SecurityModuleError::DeprecatedAlgorithm(message)
+        | SecurityModuleError::SecretStorage(message)
+        | SecurityModuleError::InvalidProof(message)
+        | SecurityModuleError::InvalidToken(message)
+        | SecurityModuleError::UnsupportedOperation(message)
+        | SecurityModuleError::InputTooLarge(message)
+        | SecurityModuleError::RollbackDetected(message)
+        | SecurityModuleError::NotFipsApproved(message) => message.clone(),
+        _ => error.to_string(),
+    };
+    GrpcStatus {
+        message,
+        ..error.into()
+    }
+}
+
+/// Rebuilds the error of the server from the status of a failed call.
+fn error(status: GrpcStatus) -> SecurityModuleError {
+    let GrpcStatus {
+        code,
+        message,
+        error_code,
+    } = status;
+    match error_code {
+        Some(1) => SecurityModuleError::SigningError(message),
+        Some(2) => SecurityModuleError::DecryptionError(message),
+        Some(3) => SecurityModuleError::EncryptionError(message),
+        Some(4) => SecurityModuleError::SignatureVerificationError(message),
+        Some(5) => SecurityModuleError::InitializationError(message),
+        Some(6) => SecurityModuleError::KeyError,
+        Some(7) => SecurityModuleError::UnsupportedAlgorithm,
+        Some(8) => SecurityModuleError::VerificationFailed,
+        Some(9) => SecurityModuleError::InvalidSignature,
+        Some(10) => SecurityModuleError::InvalidPublicKey,
+        Some(11) => SecurityModuleError::SigningFailed,
+        Some(12) => SecurityModuleError::SessionPoolTimeout,
+        Some(14) => SecurityModuleError::AuthenticationFailed(message),
+        Some(15) => SecurityModuleError::InvalidKeySpec(message),
+        Some(16) => SecurityModuleError::InvalidKeyId(message),
+        Some(17) => SecurityModuleError::DeprecatedAlgorithm(message),
+        Some(18) => SecurityModuleError::SecretStorage(message),
+        Some(19) => SecurityModuleError::InvalidProof(message),
+        Some(20) => SecurityModuleError::InvalidToken(message),
+        Some(21) => SecurityModuleError::UnsupportedOperation(message),
+        Some(22) => SecurityModuleError::InputTooLarge(message),
+        Some(23) => SecurityModuleError::RollbackDetected(message),
+        Some(24) => SecurityModuleError::NotFipsApproved(message),
+        _ if code == grpc::GRPC_UNAUTHENTICATED => SecurityModuleError::AuthenticationFailed(
+            format!("The remote provider rejected the token: {}", message),
+        ),
+        _ => SecurityModuleError::InitializationError(format!(
+            "The remote provider failed with gRPC status {}: {}",
+            code, message
+        )),
+    }
+}
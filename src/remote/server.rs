@@ -0,0 +1,208 @@
+use super::{
+    status, DataRequest, DataResponse, KeyRequest, KeyResponse, VerifyRequest, VerifyResponse,
+    MAX_MESSAGE_LEN, SERVICE_PATH,
+};
+use crate::common::{
+    constant_time,
+    crypto::secret::SecretBytes,
+    error::SecurityModuleError,
+    grpc::{self, GrpcStatus, Service, GRPC_UNAUTHENTICATED, GRPC_UNIMPLEMENTED},
+    key_router::{ConfigFn, KeyRouter},
+    traits::module_provider::Provider,
+};
+use http::{header::AUTHORIZATION, HeaderMap};
+use openssl::sha::sha256;
+use std::{
+    fmt, io,
+    sync::{Arc, Mutex},
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::{TcpListener, ToSocketAddrs},
+};
+
+type DataOperation = fn(&dyn Provider, &[u8]) -> Result<Vec<u8>, SecurityModuleError>;
+
+/// Serves a local provider to `RemoteProvider`s, see the module documentation.
+///
+/// Providers of the factory are shared by all key ids, so the server remembers the spec of
+/// every key a client created or loaded, and loads the key of a call into the provider before
+/// it is used.
+pub struct RemoteServer {
+    router: KeyRouter,
+    token: Option<[u8; 32]>,
+}
+
+impl RemoteServer {
+    /// Creates a server for an initialized provider.
+    ///
+    /// # Arguments
+    ///
+    /// * `provider` - The provider, which should not be used to load keys while the server
+    ///   runs.
+    /// * `config` - Creates the configuration of the provider for the spec of a key, e.g.
+    ///   `TpmConfig::from_spec`.
+    pub fn new(provider: Arc<Mutex<dyn Provider>>, config: ConfigFn) -> Self {
+        Self {
+            router: KeyRouter::new(provider, config),
+            token: None,
+        }
+    }
+
+    /// Requires every call to carry `token` as bearer token in its `authorization` header.
+    pub fn with_token(mut self, token: &str) -> Self {
+        self.token = Some(sha256(token.as_bytes()));
+        self
+    }
+
+    /// Serves the calls of one connected client until it disconnects.
+    ///
+    /// The security module is used on the blocking threads of the runtime, so `serve` must be
+    /// called on a Tokio runtime. Any transport works, e.g. a TLS stream.
+    ///
+    /// # Returns
+    ///
+    /// An `io::Result` that is `Ok(())` when the client disconnected, or the error of the
+    /// HTTP/2 connection.
+    pub async fn serve(self: Arc<Self>, io: impl AsyncRead + AsyncWrite + Unpin) -> io::Result<()> {
+        grpc::serve(self, io).await
+    }
+
+    /// Listens on the TCP address `address` and serves every client on its own task.
+    ///
+    /// This function only returns when the address cannot be bound or accepting a connection
+    /// fails.
+    pub async fn listen(self: Arc<Self>, address: impl ToSocketAddrs) -> io::Result<()> {
+        let listener = TcpListener::bind(address).await?;
+        loop {
+            let (stream, peer) = listener.accept().await?;
+            let _ = stream.set_nodelay(true);
+            let server = Arc::clone(&self);
+            tokio::spawn(async move {
+                if let Err(e) = server.serve(stream).await {
+                    tracing::warn!(error = %e, %peer, "remote provider connection failed");
+                }
+            });
+        }
+    }
+
+    /// Listens on the Unix domain socket `path`, which must not exist, and serves every client
+    /// on its own task.
+    ///
+    /// The socket is made accessible to the current user only. This function only returns when
+    /// the socket cannot be created or accepting a connection fails.
+    #[cfg(unix)]
+    pub async fn listen_unix(self: Arc<Self>, path: impl AsRef<std::path::Path>) -> io::Result<()> {
+        use std::{fs, os::unix::fs::PermissionsExt};
+
+        let listener = tokio::net::UnixListener::bind(&path)?;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600))?;
+        loop {
+            let (stream, _) = listener.accept().await?;
+            let server = Arc::clone(&self);
+            tokio::spawn(async move {
+                if let Err(e) = server.serve(stream).await {
+                    tracing::warn!(error = %e, "remote provider connection failed");
+                }
+            });
+        }
+    }
+
+    fn open_key(&self, request: &[u8], create: bool) -> Result<Vec<u8>, SecurityModuleError> {
+        let spec = KeyRequest::from_bytes(request)?.spec()?;
+        let public_key = self.router.open_key(&spec, create)?;
+        Ok(KeyResponse {
+            public_key: public_key.unwrap_or_default(),
+        }
+        .to_bytes())
+    }
+
+    fn data(
+        &self,
+        request: &[u8],
+        operation: DataOperation,
+    ) -> Result<Vec<u8>, SecurityModuleError> {
+        let request = DataRequest::from_bytes(request)?;
+        let data = self.router.with_key(&request.key_id, |provider| {
+            operation(provider, &request.data)
+        })?;
+        Ok(DataResponse { data }.to_bytes())
+    }
+
+    fn dispatch(&self, method: &str, request: &[u8]) -> Result<Vec<u8>, SecurityModuleError> {
+        match method {
+            "CreateKey" => self.open_key(request, true),
+            "LoadKey" => self.open_key(request, false),
+            "Sign" => self.data(request, |provider, data| provider.sign_data(data)),
+            "Encrypt" => self.data(request, |provider, data| provider.encrypt_data(data)),
+            "Decrypt" => self.data(request, |provider, data| {
+                provider.decrypt_data(data).map(SecretBytes::into_vec)
+            }),
+            "DeriveSharedSecret" => self.data(request, |provider, peer_public_key| {
+                provider
+                    .derive_shared_secret(peer_public_key)
+                    .map(SecretBytes::into_vec)
+            }),
+            "Verify" => {
+                let request = VerifyRequest::from_bytes(request)?;
+                let valid = self.router.with_key(&request.key_id, |provider| {
+                    provider.verify_signature(&request.data, &request.signature)
+                })?;
+                Ok(VerifyResponse { valid }.to_bytes())
+            }
+            _ => unreachable!("unknown methods are rejected by `call`"),
+        }
+    }
+}
+
+impl Service for RemoteServer {
+    const PATH: &'static str = SERVICE_PATH;
+    const NAME: &'static str = "remote provider";
+    const MAX_MESSAGE_LEN: usize = MAX_MESSAGE_LEN;
+
+    fn authorize(&self, headers: &HeaderMap) -> Result<(), GrpcStatus> {
+        let Some(expected) = self.token else {
+            return Ok(());
+        };
+        let token = headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+        match token {
+            Some(token) if constant_time::eq(&sha256(token.as_bytes()), &expected) => Ok(()),
+            _ => Err(GrpcStatus::new(
+                GRPC_UNAUTHENTICATED,
+                "The call has no valid bearer token",
+            )),
+        }
+    }
+
+    fn call(&self, method: &str, request: &[u8]) -> Result<Vec<u8>, GrpcStatus> {
+        const METHODS: [&str; 7] = [
+            "CreateKey",
+            "LoadKey",
+            "Sign",
+            "Verify",
+            "Encrypt",
+            "Decrypt",
+            "DeriveSharedSecret",
+        ];
+        if !METHODS.contains(&method) {
+            return Err(GrpcStatus::new(
+                GRPC_UNIMPLEMENTED,
+                format!("Unknown method {}", method),
+            ));
+        }
+        tracing::debug!(method, "remote provider call");
+        self.dispatch(method, request).map_err(status)
+    }
+}
+
+impl fmt::Debug for RemoteServer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RemoteServer")
+            .field("keys", &self.router.key_ids())
+            .field("token", &self.token.is_some())
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,250 @@
+//! An SSH agent that signs with keys of the security module.
+//!
+//! The agent speaks the SSH agent protocol (draft-miller-ssh-agent), so `ssh`, `git` and every
+//! other OpenSSH client can authenticate with keys that never leave the Secure Enclave or the
+//! TPM:
+//!
+//! ```rust,ignore
+//! use crypto_layer::ssh_agent::SshAgent;
+//!
+//! let mut agent = SshAgent::new();
+//! agent.add_key(Arc::new(Mutex::new(provider)), "secure-enclave")?;
+//! println!("{}", agent.public_keys()[0]); // For ~/.ssh/authorized_keys.
+//! Arc::new(agent).listen("/tmp/crypto-layer-agent.sock")?;
+//! // SSH_AUTH_SOCK=/tmp/crypto-layer-agent.sock ssh host
+//! ```
+//!
+//! `listen` serves a Unix domain socket. On Windows, `serve` can be called with a connected
+//! named pipe, e.g. `\\.\pipe\openssh-ssh-agent`.
+//!
+//! Every key is held by its own provider, which must have the key loaded. The agent lists the
+//! keys and signs with them, but does not support adding, removing or locking keys, which are
+//! managed by the application. The following keys are supported:
+//!
+//! - ECDSA keys on P-256, P-384 and P-521 with SHA-256, SHA-384 and SHA-512, as
+//!   `ecdsa-sha2-nistp256`, `ecdsa-sha2-nistp384` and `ecdsa-sha2-nistp521`.
+//! - Ed25519 keys as `ssh-ed25519`.
+//! - RSA keys with PKCS#1 v1.5 signatures, as `ssh-rsa`. They only sign `rsa-sha2-256` or
+//!   `rsa-sha2-512` requests matching their hash, SHA-1 is not supported.
+
+use crate::common::{
+    error::SecurityModuleError,
+    ssh_wire::{self, KeyType},
+    traits::module_provider::Provider,
+};
+use std::{
+    fmt,
+    io::{self, Read, Write},
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+/// The longest message the agent accepts in bytes.
+pub const MAX_MESSAGE_LEN: usize = 256 * 1024;
+
+const SSH_AGENT_FAILURE: u8 = 5;
+const SSH_AGENTC_REQUEST_IDENTITIES: u8 = 11;
+const SSH_AGENT_IDENTITIES_ANSWER: u8 = 12;
+const SSH_AGENTC_SIGN_REQUEST: u8 = 13;
+const SSH_AGENT_SIGN_RESPONSE: u8 = 14;
+
+const SSH_AGENT_RSA_SHA2_256: u32 = 2;
+const SSH_AGENT_RSA_SHA2_512: u32 = 4;
+
+/// An SSH agent, see the module documentation.
+#[derive(Default)]
+pub struct SshAgent {
+    keys: Vec<AgentKey>,
+}
+
+struct AgentKey {
+    provider: Arc<Mutex<dyn Provider>>,
+    key_type: KeyType,
+    public_key_der: Vec<u8>,
+    blob: Vec<u8>,
+    comment: String,
+}
+
+impl SshAgent {
+    /// Creates an agent without keys.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the loaded key of `provider`.
+    ///
+    /// # Arguments
+    ///
+    /// * `provider` - The provider with the loaded key, which should not be used to load other
+    ///   keys while the agent runs.
+    /// * `comment` - The comment shown by `ssh-add -l`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` that is `Ok(())` if the key was added, a
+    /// `SecurityModuleError::UnsupportedAlgorithm` if it has no SSH key type, or the error of
+    /// `provider` if its key metadata cannot be read.
+    pub fn add_key(
+        &mut self,
+        provider: Arc<Mutex<dyn Provider>>,
+        comment: &str,
+    ) -> Result<(), SecurityModuleError> {
+        let metadata = lock(&provider).key_metadata()?;
+        let public_key = metadata.public_key();
+        let key_type = KeyType::of(public_key)?;
+        let blob = ssh_wire::public_key_blob(key_type, public_key)?;
+        self.keys.push(AgentKey {
+            provider,
+            key_type,
+            public_key_der: metadata.public_key_der().to_vec(),
+            blob,
+            comment: comment.to_owned(),
+        });
+        Ok(())
+    }
+
+    /// Returns the keys in the OpenSSH public key format, e.g. for `authorized_keys`.
+    pub fn public_keys(&self) -> Vec<String> {
+        self.keys
+            .iter()
+            .map(|key| ssh_wire::public_key_line(key.key_type, &key.blob, &key.comment))
+            .collect()
+    }
+
+    /// Answers a single request, without its length prefix.
+    ///
+    /// Requests the agent does not support or cannot fulfil are answered with
+    /// `SSH_AGENT_FAILURE`, as the protocol requires.
+    pub fn handle_message(&self, request: &[u8]) -> Vec<u8> {
+        let result = match request.split_first() {
+            Some((&SSH_AGENTC_REQUEST_IDENTITIES, [])) => Ok(self.identities()),
+            Some((&SSH_AGENTC_SIGN_REQUEST, body)) => self.sign(body),
+            _ => Err(SecurityModuleError::UnsupportedAlgorithm),
+        };
+        result.unwrap_or_else(|e| {
+            tracing::debug!(error = %e, "ssh agent request failed");
+            vec![SSH_AGENT_FAILURE]
+        })
+    }
+
+    /// Serves the requests of one connected client until it disconnects.
+    ///
+    /// # Returns
+    ///
+    /// An `io::Result` that is `Ok(())` when the client disconnected, or the error of `stream`.
+    /// A message longer than `MAX_MESSAGE_LEN` is an `io::ErrorKind::InvalidData` error.
+    pub fn serve(&self, stream: &mut (impl Read + Write)) -> io::Result<()> {
+        loop {
+            let mut len = [0; 4];
+            match stream.read_exact(&mut len) {
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+                result => result?,
+            }
+            let len = u32::from_be_bytes(len) as usize;
+            if len > MAX_MESSAGE_LEN {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "The message is too long",
+                ));
+            }
+            let mut request = vec![0; len];
+            stream.read_exact(&mut request)?;
+
+            let response = self.handle_message(&request);
+            let mut framed = Vec::with_capacity(4 + response.len());
+            ssh_wire::put_bytes(&mut framed, &response);
+            stream.write_all(&framed)?;
+            stream.flush()?;
+        }
+    }
+
+    /// Listens on the Unix domain socket `path`, which must not exist, and serves every client
+    /// on its own thread.
+    ///
+    /// The socket is made accessible to the current user only, but should also be created in a
+    /// directory that only the user can access, since other users could connect before its
+    /// permissions are set. This function only returns when the socket cannot be created or
+    /// accepting a connection fails.
+    #[cfg(unix)]
+    pub fn listen(self: Arc<Self>, path: impl AsRef<std::path::Path>) -> io::Result<()> {
+        use std::{fs, os::unix::fs::PermissionsExt, os::unix::net::UnixListener, thread};
+
+        let listener = UnixListener::bind(&path)?;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600))?;
+        for stream in listener.incoming() {
+            let mut stream = stream?;
+            let agent = Arc::clone(&self);
+            thread::spawn(move || {
+                if let Err(e) = agent.serve(&mut stream) {
+                    tracing::warn!(error = %e, "ssh agent connection failed");
+                }
+            });
+        }
+        Ok(())
+    }
+
+    fn identities(&self) -> Vec<u8> {
+        let mut response = vec![SSH_AGENT_IDENTITIES_ANSWER];
+        response.extend_from_slice(&(self.keys.len() as u32).to_be_bytes());
+        for key in &self.keys {
+            ssh_wire::put_bytes(&mut response, &key.blob);
+            ssh_wire::put_bytes(&mut response, key.comment.as_bytes());
+        }
+        response
+    }
+
+    fn sign(&self, mut body: &[u8]) -> Result<Vec<u8>, SecurityModuleError> {
+        let truncated = || SecurityModuleError::SigningError("The request is truncated".to_owned());
+        let blob = ssh_wire::get_bytes(&mut body).ok_or_else(truncated)?;
+        let data = ssh_wire::get_bytes(&mut body).ok_or_else(truncated)?;
+        let flags = ssh_wire::get_u32(&mut body).ok_or_else(truncated)?;
+        if !body.is_empty() {
+            return Err(SecurityModuleError::SigningError(
+                "The sign request has trailing data".to_owned(),
+            ));
+        }
+        let key = self
+            .keys
+            .iter()
+            .find(|key| key.blob == blob)
+            .ok_or(SecurityModuleError::KeyError)?;
+
+        // RSA keys only sign with the hash they were created with, never with SHA-1.
+        if let KeyType::Rsa { .. } = key.key_type {
+            let requested = match flags {
+                SSH_AGENT_RSA_SHA2_256 => "rsa-sha2-256",
+                SSH_AGENT_RSA_SHA2_512 => "rsa-sha2-512",
+                _ => "ssh-rsa",
+            };
+            if requested != key.key_type.signature_algorithm() {
+                return Err(SecurityModuleError::UnsupportedAlgorithm);
+            }
+        }
+
+        let signature = {
+            let provider = lock(&key.provider);
+            // The provider may have loaded another key since it was added.
+            if provider.key_metadata()?.public_key_der() != key.public_key_der.as_slice() {
+                return Err(SecurityModuleError::KeyError);
+            }
+            provider.sign_data(data)?
+        };
+        let mut response = vec![SSH_AGENT_SIGN_RESPONSE];
+        ssh_wire::put_bytes(
+            &mut response,
+            &ssh_wire::encode_signature(key.key_type, &signature)?,
+        );
+        Ok(response)
+    }
+}
+
+impl fmt::Debug for SshAgent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SshAgent")
+            .field("keys", &self.public_keys())
+            .finish()
+    }
+}
+
+fn lock<'a>(provider: &'a Mutex<dyn Provider + 'static>) -> MutexGuard<'a, dyn Provider + 'static> {
+    provider.lock().unwrap_or_else(|e| e.into_inner())
+}
@@ -0,0 +1,397 @@
+//! Canonical test vectors for validating other implementations against the crypto layer.
+//!
+//! `generate` produces signatures, RSA ciphertexts and envelopes with the fixed keys in `keys/`,
+//! using the `MockProvider` as software provider. The result is exported to JSON by the
+//! `export-test-vectors` binary:
+//!
+//! ```bash
+//! cargo run --features test-utils --bin export-test-vectors -- vectors.json
+//! ```
+//!
+//! Binary values are base64 encoded. Envelopes and RSA PKCS#1 v1.5 signatures are reproducible
+//! byte for byte, ECDSA signatures and OAEP ciphertexts are randomized and can only be verified or
+//! decrypted. `check` performs these validations, so vectors exported by an earlier version can
+//! be checked against the current one.
+
+use crate::{
+    common::{
+        crypto::{
+            aead,
+            algorithms::{
+                encryption::{AsymmetricEncryption, EccCurves, EccSchemeAlgorithm},
+                hashes::{Hash, Sha2Bits},
+                KeyBits,
+            },
+            envelope::{AeadAlgorithm, Envelope, EnvelopeRef},
+            kdf::Kdf,
+        },
+        error::SecurityModuleError,
+        traits::{key_handle::KeyHandle, module_provider::Provider},
+    },
+    mock::{MockConfig, MockProvider},
+};
+use serde::{Deserialize, Serialize};
+
+/// The version of the test vector format, increased on incompatible changes.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// The signature scheme of RSA keys.
+pub const RSA_SIGNATURE_SCHEME: &str = "RSASSA-PKCS1-v1_5";
+/// The signature scheme of ECDSA keys, signatures are DER encoded.
+pub const ECDSA_SIGNATURE_SCHEME: &str = "ECDSA-DER";
+/// The encryption scheme of RSA keys, OAEP with SHA-1 and MGF1 with SHA-1.
+pub const RSA_ENCRYPTION_SCHEME: &str = "RSAES-OAEP-SHA1";
+
+/// A fixed key pair of the test vectors.
+struct FixedKey {
+    key_id: &'static str,
+    algorithm: &'static str,
+    hash: &'static str,
+    private_key_pem: &'static str,
+}
+
+const FIXED_KEYS: [FixedKey; 3] = [
+    FixedKey {
+        key_id: "vector-rsa-2048",
+        algorithm: "RSA-2048",
+        hash: "SHA-256",
+        private_key_pem: include_str!("keys/rsa2048.pem"),
+    },
+    FixedKey {
+        key_id: "vector-p256",
+        algorithm: "ECDSA-P256",
+        hash: "SHA-256",
+        private_key_pem: include_str!("keys/p256.pem"),
+    },
+    FixedKey {
+        key_id: "vector-p384",
+        algorithm: "ECDSA-P384",
+        hash: "SHA-384",
+        private_key_pem: include_str!("keys/p384.pem"),
+    },
+];
+
+/// The messages signed with every key.
+const MESSAGES: [&[u8]; 4] = [
+    b"",
+    b"abc",
+    b"\x00\xff\x00\x0a\x0d\x1b\x7f\x80",
+    "Grüße, 世界! 🔐".as_bytes(),
+];
+
+/// The plaintexts encrypted with every RSA key.
+const PLAINTEXTS: [&[u8]; 3] = [
+    b"",
+    b"attack at dawn",
+    b"\x00\x01\x02\x03\x04\x05\x06\x07\x08\x09\x0a\x0b\x0c\x0d\x0e\x0f\x10\x11\x12\x13\x14\x15\x16\x17\x18\x19\x1a\x1b\x1c\x1d\x1e\x1f",
+];
+
+/// A complete set of test vectors.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TestVectors {
+    /// The `FORMAT_VERSION` the vectors were exported with.
+    pub version: u32,
+    pub keys: Vec<KeyVector>,
+    pub signatures: Vec<SignatureVector>,
+    pub ciphertexts: Vec<CiphertextVector>,
+    pub envelopes: Vec<EnvelopeVector>,
+}
+
+/// A fixed key pair.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyVector {
+    pub key_id: String,
+    /// `RSA-2048`, `ECDSA-P256` or `ECDSA-P384`.
+    pub algorithm: String,
+    /// The hash algorithm used for signing, `SHA-256` or `SHA-384`.
+    pub hash: String,
+    /// The private key in PKCS#8 PEM format.
+    pub private_key_pem: String,
+    /// The DER encoded `SubjectPublicKeyInfo` of the key pair.
+    #[serde(with = "base64_bytes")]
+    pub public_key_der: Vec<u8>,
+}
+
+/// A signature created by one of the keys.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignatureVector {
+    pub key_id: String,
+    /// `RSA_SIGNATURE_SCHEME` or `ECDSA_SIGNATURE_SCHEME`.
+    pub scheme: String,
+    #[serde(with = "base64_bytes")]
+    pub message: Vec<u8>,
+    #[serde(with = "base64_bytes")]
+    pub signature: Vec<u8>,
+    /// Whether signing the message again yields the same signature.
+    pub deterministic: bool,
+}
+
+/// A ciphertext encrypted with one of the RSA keys.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CiphertextVector {
+    pub key_id: String,
+    /// `RSA_ENCRYPTION_SCHEME`.
+    pub scheme: String,
+    #[serde(with = "base64_bytes")]
+    pub plaintext: Vec<u8>,
+    #[serde(with = "base64_bytes")]
+    pub ciphertext: Vec<u8>,
+}
+
+/// An encoded envelope with the key of its payload.
+///
+/// The payload key is given directly, header fields such as the wrapped key or the KDF are only
+/// part of the associated data.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EnvelopeVector {
+    pub name: String,
+    /// The identifier of the AEAD algorithm, as in the envelope header.
+    pub aead: u8,
+    #[serde(with = "base64_bytes")]
+    pub key: Vec<u8>,
+    #[serde(with = "base64_bytes")]
+    pub plaintext: Vec<u8>,
+    #[serde(with = "base64_bytes")]
+    pub envelope: Vec<u8>,
+}
+
+impl TestVectors {
+    /// Serializes the vectors to pretty-printed JSON.
+    pub fn to_json(&self) -> Result<String, SecurityModuleError> {
+        serde_json::to_string_pretty(self)
+            .map_err(|e| SecurityModuleError::InitializationError(e.to_string()))
+    }
+
+    /// Deserializes vectors from JSON.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `TestVectors`, or a `SecurityModuleError::InitializationError`
+    /// if the JSON is invalid or has an unsupported `version`.
+    pub fn from_json(json: &str) -> Result<Self, SecurityModuleError> {
+        let vectors: Self = serde_json::from_str(json)
+            .map_err(|e| SecurityModuleError::InitializationError(e.to_string()))?;
+        if vectors.version != FORMAT_VERSION {
+            return Err(SecurityModuleError::InitializationError(format!(
+                "Unsupported test vector version {}",
+                vectors.version
+            )));
+        }
+        Ok(vectors)
+    }
+}
+
+/// Generates the canonical test vectors.
+///
+/// # Returns
+///
+/// A `Result` containing the `TestVectors`, or a `SecurityModuleError` if an operation of the
+/// software provider fails.
+pub fn generate() -> Result<TestVectors, SecurityModuleError> {
+    let mut provider = MockProvider::new(String::new());
+    provider.initialize_module()?;
+
+    let mut vectors = TestVectors {
+        version: FORMAT_VERSION,
+        keys: Vec::new(),
+        signatures: Vec::new(),
+        ciphertexts: Vec::new(),
+        envelopes: envelopes()?,
+    };
+
+    for key in &FIXED_KEYS {
+        let config = config(key.algorithm, key.hash)?;
+        provider.import_key(key.key_id, config, key.private_key_pem.as_bytes())?;
+        vectors.keys.push(KeyVector {
+            key_id: key.key_id.to_owned(),
+            algorithm: key.algorithm.to_owned(),
+            hash: key.hash.to_owned(),
+            private_key_pem: key.private_key_pem.to_owned(),
+            public_key_der: provider.key_metadata()?.public_key().to_der()?,
+        });
+
+        let rsa = matches!(config.key_algorithm, AsymmetricEncryption::Rsa(_));
+        for message in MESSAGES {
+            vectors.signatures.push(SignatureVector {
+                key_id: key.key_id.to_owned(),
+                scheme: signature_scheme(rsa).to_owned(),
+                message: message.to_vec(),
+                signature: provider.sign_data(message)?,
+                deterministic: rsa,
+            });
+        }
+        if rsa {
+            for plaintext in PLAINTEXTS {
+                vectors.ciphertexts.push(CiphertextVector {
+                    key_id: key.key_id.to_owned(),
+                    scheme: RSA_ENCRYPTION_SCHEME.to_owned(),
+                    plaintext: plaintext.to_vec(),
+                    ciphertext: provider.encrypt_data(plaintext)?,
+                });
+            }
+        }
+    }
+    Ok(vectors)
+}
+
+/// Validates test vectors against this implementation.
+///
+/// Every public key has to match its private key, every signature has to verify and every
+/// ciphertext and envelope has to decrypt to its plaintext. Deterministic signatures and
+/// envelopes additionally have to be reproduced byte for byte.
+///
+/// # Returns
+///
+/// A `Result` that is `Ok(())` if all vectors are valid, or a `SecurityModuleError` naming the
+/// first invalid vector.
+pub fn check(vectors: &TestVectors) -> Result<(), SecurityModuleError> {
+    let mut provider = MockProvider::new(String::new());
+    provider.initialize_module()?;
+
+    for key in &vectors.keys {
+        let config = config(&key.algorithm, &key.hash)?;
+        provider.import_key(&key.key_id, config, key.private_key_pem.as_bytes())?;
+        if provider.key_metadata()?.public_key().to_der()? != key.public_key_der {
+            return Err(SecurityModuleError::InitializationError(format!(
+                "Public key of {} does not match its private key",
+                key.key_id
+            )));
+        }
+    }
+    // The mock provider remembers the configuration of imported keys.
+    for (index, vector) in vectors.signatures.iter().enumerate() {
+        provider.load_key(&vector.key_id, Box::new(()))?;
+        let valid = provider.verify_signature(&vector.message, &vector.signature)?;
+        let reproduced =
+            !vector.deterministic || provider.sign_data(&vector.message)? == vector.signature;
+        if !valid || !reproduced {
+            return Err(SecurityModuleError::SignatureVerificationError(format!(
+                "Signature {index} of {} is invalid",
+                vector.key_id
+            )));
+        }
+    }
+
+    for (index, vector) in vectors.ciphertexts.iter().enumerate() {
+        provider.load_key(&vector.key_id, Box::new(()))?;
+        if provider.decrypt_data(&vector.ciphertext)? != vector.plaintext {
+            return Err(SecurityModuleError::DecryptionError(format!(
+                "Ciphertext {index} of {} is invalid",
+                vector.key_id
+            )));
+        }
+    }
+
+    for vector in &vectors.envelopes {
+        let envelope = EnvelopeRef::parse(&vector.envelope)?;
+        let resealed = aead::seal(envelope.to_envelope(), &vector.key, &vector.plaintext)?;
+        if envelope.aead.id() != vector.aead
+            || aead::open(&envelope, &vector.key)? != vector.plaintext
+            || resealed != vector.envelope
+        {
+            return Err(SecurityModuleError::DecryptionError(format!(
+                "Envelope {} is invalid",
+                vector.name
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Seals the envelope vectors. Keys and nonces are counting byte sequences, which keeps the
+/// envelopes reproducible.
+fn envelopes() -> Result<Vec<EnvelopeVector>, SecurityModuleError> {
+    let counting = |start: u8, len: usize| -> Vec<u8> {
+        (0..len).map(|i| start.wrapping_add(i as u8)).collect()
+    };
+    let binary: Vec<u8> = (0..=255).collect();
+
+    let mut wrapped = Envelope::new(
+        AeadAlgorithm::Aes256Gcm,
+        "vector-rsa-2048",
+        counting(0xc0, 12),
+    );
+    wrapped.wrapped_key = Some(counting(0x40, 32));
+    wrapped.kdf = Some(Kdf::HkdfSha256);
+    wrapped.salt = Some(counting(0x70, 16));
+
+    let cases = [
+        (
+            "aes-128-gcm",
+            Envelope::new(AeadAlgorithm::Aes128Gcm, "vector-aes", counting(0xa0, 12)),
+            &b"The quick brown fox jumps over the lazy dog"[..],
+        ),
+        (
+            "aes-256-gcm-empty",
+            Envelope::new(AeadAlgorithm::Aes256Gcm, "vector-aes", counting(0xb0, 12)),
+            &b""[..],
+        ),
+        (
+            "chacha20-poly1305-binary",
+            Envelope::new(
+                AeadAlgorithm::ChaCha20Poly1305,
+                "vector-chacha",
+                counting(0xd0, 12),
+            ),
+            &binary[..],
+        ),
+        ("aes-256-gcm-wrapped-key", wrapped, &b"attack at dawn"[..]),
+    ];
+
+    cases
+        .into_iter()
+        .map(|(name, envelope, plaintext)| {
+            let aead = envelope.aead;
+            let key = counting(0x00, aead.key_len());
+            Ok(EnvelopeVector {
+                name: name.to_owned(),
+                aead: aead.id(),
+                envelope: aead::seal(envelope, &key, plaintext)?,
+                key,
+                plaintext: plaintext.to_vec(),
+            })
+        })
+        .collect()
+}
+
+fn config(algorithm: &str, hash: &str) -> Result<MockConfig, SecurityModuleError> {
+    let key_algorithm = match algorithm {
+        "RSA-2048" => AsymmetricEncryption::Rsa(KeyBits::Bits2048),
+        "ECDSA-P256" => AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(EccCurves::P256)),
+        "ECDSA-P384" => AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(EccCurves::P384)),
+        _ => return Err(SecurityModuleError::UnsupportedAlgorithm),
+    };
+    let hash = match hash {
+        "SHA-256" => Hash::Sha2(Sha2Bits::Sha256),
+        "SHA-384" => Hash::Sha2(Sha2Bits::Sha384),
+        _ => return Err(SecurityModuleError::UnsupportedAlgorithm),
+    };
+    Ok(MockConfig {
+        key_algorithm,
+        hash,
+        exportable: false,
+    })
+}
+
+fn signature_scheme(rsa: bool) -> &'static str {
+    if rsa {
+        RSA_SIGNATURE_SCHEME
+    } else {
+        ECDSA_SIGNATURE_SCHEME
+    }
+}
+
+/// Stores binary values base64 encoded.
+mod base64_bytes {
+    use base64::{prelude::BASE64_STANDARD, Engine};
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&BASE64_STANDARD.encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        BASE64_STANDARD.decode(encoded).map_err(D::Error::custom)
+    }
+}
@@ -0,0 +1,274 @@
+use crate::{
+    cli::{self, Cli, CliError},
+    common::traits::module_provider::Provider,
+    mock::{MockConfig, MockProvider},
+    SecurityModuleError,
+};
+use std::{
+    fs,
+    io::{self, Write},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+fn cli() -> Cli {
+    let mut provider = MockProvider::new("bk-crypto".to_owned());
+    provider.initialize_module().unwrap();
+    Cli::new(Arc::new(Mutex::new(provider)), MockConfig::from_spec)
+}
+
+/// Runs `bk-crypto` with `args` and `input` on stdin, and returns its stdout.
+fn run(cli: &Cli, args: &[&str], input: &[u8]) -> Result<Vec<u8>, CliError> {
+    let matches = cli::command()
+        .try_get_matches_from(["bk-crypto"].iter().chain(args))
+        .unwrap();
+    let mut output = Vec::new();
+    cli.run(&matches, &mut &input[..], &mut output)?;
+    Ok(output)
+}
+
+fn run_ok(cli: &Cli, args: &[&str]) -> String {
+    String::from_utf8(run(cli, args, b"").unwrap()).unwrap()
+}
+
+fn temp_file(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("bk-crypto-{}-{}", std::process::id(), name))
+}
+
+#[test]
+fn test_command() {
+    cli::command().debug_assert();
+
+    for args in [
+        &["key", "create", "release"][..],
+        &["key", "create", "release", "--algorithm", "ec-p999"],
+        &[
+            "sign",
+            "release",
+            "--algorithm",
+            "ec-p256",
+            "--purpose",
+            "sign",
+        ],
+        &["verify", "release", "--algorithm", "ec-p256"],
+        &["key"],
+    ] {
+        assert!(
+            cli::command()
+                .try_get_matches_from(["bk-crypto"].iter().chain(args))
+                .is_err(),
+            "{args:?}"
+        );
+    }
+}
+
+#[test]
+fn test_sign_and_verify() {
+    let cli = cli();
+    let public_key = run_ok(&cli, &["key", "create", "release", "-a", "ec-p256"]);
+    assert!(public_key.starts_with("-----BEGIN PUBLIC KEY-----\n"));
+    let (input, signature) = (temp_file("sign.in"), temp_file("sign.sig"));
+    fs::write(&input, b"artifact").unwrap();
+
+    let sign = [
+        "sign",
+        "release",
+        "-a",
+        "ec-p256",
+        "--input",
+        input.to_str().unwrap(),
+        "--output",
+        signature.to_str().unwrap(),
+    ];
+    assert!(run_ok(&cli, &sign).is_empty());
+    let verify = [
+        "verify",
+        "release",
+        "-a",
+        "ec-p256",
+        "--signature",
+        signature.to_str().unwrap(),
+    ];
+
+    assert_eq!(
+        run(&cli, &verify, b"artifact").unwrap(),
+        b"The signature is valid\n"
+    );
+    assert!(matches!(
+        run(&cli, &verify, b"tampered"),
+        Err(CliError::InvalidSignature)
+    ));
+    fs::remove_file(input).unwrap();
+    fs::remove_file(signature).unwrap();
+}
+
+#[test]
+fn test_sign_and_verify_in_context() {
+    let cli = cli();
+    run_ok(&cli, &["key", "create", "release", "-a", "ec-p256"]);
+    let signature = temp_file("context.sig");
+
+    let sign = [
+        "sign",
+        "release",
+        "-a",
+        "ec-p256",
+        "--context",
+        "com.example.release/v1",
+    ];
+    fs::write(&signature, run(&cli, &sign, b"artifact").unwrap()).unwrap();
+    let verify = |context: Option<&str>| {
+        let mut args = vec!["verify", "release", "-a", "ec-p256", "-s"];
+        args.push(signature.to_str().unwrap());
+        args.extend(context.iter().flat_map(|context| ["-c", context]));
+        run(&cli, &args, b"artifact")
+    };
+
+    assert!(verify(Some("com.example.release/v1")).is_ok());
+    assert!(matches!(
+        verify(Some("com.example.login/v1")),
+        Err(CliError::InvalidSignature)
+    ));
+    assert!(matches!(verify(None), Err(CliError::InvalidSignature)));
+    assert!(matches!(
+        verify(Some("release v1")),
+        Err(CliError::SecurityModule(SecurityModuleError::Encoding(_)))
+    ));
+    fs::remove_file(signature).unwrap();
+}
+
+#[test]
+fn test_encrypt_and_decrypt() {
+    let cli = cli();
+    run_ok(
+        &cli,
+        &["key", "create", "backup", "-a", "rsa-2048", "-p", "encrypt"],
+    );
+
+    let ciphertext = run(&cli, &["encrypt", "backup", "-a", "rsa-2048"], b"secret").unwrap();
+    let plaintext = run(&cli, &["decrypt", "backup", "-a", "rsa-2048"], &ciphertext).unwrap();
+
+    assert_eq!(plaintext, b"secret");
+}
+
+#[test]
+fn test_list_and_delete() {
+    let cli = cli();
+    run_ok(&cli, &["key", "create", "first", "-a", "ec-p256"]);
+    run_ok(&cli, &["key", "create", "second", "-a", "ec-p384"]);
+
+    assert_eq!(run_ok(&cli, &["key", "list"]), "first\nsecond\n");
+    assert!(run_ok(&cli, &["key", "delete", "first"]).is_empty());
+
+    assert_eq!(run_ok(&cli, &["key", "list"]), "second\n");
+    assert!(matches!(
+        run(&cli, &["sign", "first", "-a", "ec-p256"], b"data"),
+        Err(CliError::SecurityModule(SecurityModuleError::KeyError))
+    ));
+}
+
+#[test]
+fn test_rotate() {
+    let cli = cli();
+    run_ok(&cli, &["key", "create", "release", "-a", "ec-p256"]);
+    run_ok(
+        &cli,
+        &["key", "create", "release-candidate", "-a", "ec-p256"],
+    );
+
+    let rotated = run_ok(&cli, &["key", "rotate", "release", "-a", "ec-p256"]);
+    assert!(rotated.starts_with("release.1\n-----BEGIN PUBLIC KEY-----\n"));
+    run_ok(&cli, &["key", "delete", "release"]);
+    let rotated = run_ok(&cli, &["key", "rotate", "release", "-a", "ec-p256"]);
+    assert!(rotated.starts_with("release.2\n"));
+
+    assert_eq!(
+        run_ok(&cli, &["key", "list"]),
+        "release-candidate\nrelease.1\nrelease.2\n"
+    );
+    assert!(!run(&cli, &["sign", "release.2", "-a", "ec-p256"], b"data")
+        .unwrap()
+        .is_empty());
+    assert!(run_ok(&cli, &["key", "rotate", "new", "-a", "ec-p256"]).starts_with("new.1\n"));
+}
+
+/// An output that cannot be written, e.g. a closed pipe.
+struct ClosedPipe;
+
+impl Write for ClosedPipe {
+    fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+        Err(io::ErrorKind::BrokenPipe.into())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_failed_rotations_delete_the_new_generation() {
+    let cli = cli();
+    run_ok(&cli, &["key", "create", "release", "-a", "ec-p256"]);
+
+    let matches = cli::command()
+        .try_get_matches_from(["bk-crypto", "key", "rotate", "release", "-a", "ec-p256"])
+        .unwrap();
+    assert!(matches!(
+        cli.run(&matches, &mut &b""[..], &mut ClosedPipe),
+        Err(CliError::Io(_))
+    ));
+    assert_eq!(run_ok(&cli, &["key", "list"]), "release\n");
+    assert!(run_ok(&cli, &["key", "rotate", "release", "-a", "ec-p256"]).starts_with("release.1\n"));
+}
+
+#[test]
+fn test_invalid_specs() {
+    let cli = cli();
+
+    for args in [
+        &["key", "create", "aes", "-a", "ec-p256", "-p", "encrypt"][..],
+        &["key", "create", "../escape", "-a", "ec-p256"],
+        &["encrypt", "release", "-a", "ec-p256"],
+    ] {
+        assert!(
+            matches!(
+                run(&cli, args, b""),
+                Err(CliError::SecurityModule(
+                    SecurityModuleError::InvalidKeySpec(_)
+                ))
+            ),
+            "{args:?}"
+        );
+    }
+}
+
+#[test]
+fn test_attest() {
+    let cli = cli();
+    run_ok(&cli, &["key", "create", "device", "-a", "ec-p256"]);
+
+    // The mock provider does not attest its keys.
+    assert!(matches!(
+        run(&cli, &["attest", "device", "-a", "ec-p256"], b""),
+        Err(CliError::SecurityModule(
+            SecurityModuleError::UnsupportedOperation(_)
+        ))
+    ));
+}
+
+#[test]
+fn test_self_test() {
+    let cli = cli();
+
+    assert_eq!(run_ok(&cli, &["self-test"]), "ec-p256: ok\nrsa-2048: ok\n");
+    assert_eq!(run_ok(&cli, &["key", "list"]), "");
+
+    let error = run(
+        &cli,
+        &["self-test", "-a", "ec-p384", "-a", "aes-256-gcm"],
+        b"",
+    )
+    .unwrap_err();
+    assert!(matches!(error, CliError::SelfTestFailed(1)));
+    assert_eq!(error.to_string(), "1 algorithms failed the self-test");
+}
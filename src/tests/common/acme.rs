@@ -0,0 +1,223 @@
+use crate::{
+    common::{
+        acme::{self, Identifier, KeyReference},
+        crypto::{
+            algorithms::{
+                encryption::{AsymmetricEncryption, EccCurves, EccSchemeAlgorithm},
+                hashes::{Hash, Sha2Bits},
+                KeyBits,
+            },
+            public_key::PublicKey,
+            signature_format::SignatureFormat,
+        },
+        traits::module_provider::Provider,
+    },
+    mock::{MockConfig, MockProvider},
+    SecurityModuleError,
+};
+use base64::{prelude::BASE64_URL_SAFE_NO_PAD, Engine};
+use openssl::{pkey::PKey, x509::X509Req};
+use serde_json::{json, Value};
+
+const NEW_ACCOUNT_URL: &str = "https://acme.example.com/acme/new-account";
+const ACCOUNT_URL: &str = "https://acme.example.com/acme/acct/4711";
+const NONCE: &str = "6S8IqOGY7eL2lsGoTZYifg";
+
+fn provider(algorithm: AsymmetricEncryption) -> MockProvider {
+    MockProvider::with_key(
+        "acme",
+        MockConfig::new(algorithm, Hash::Sha2(Sha2Bits::Sha256)),
+    )
+}
+
+fn p256() -> AsymmetricEncryption {
+    AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(EccCurves::P256))
+}
+
+fn decode(part: &str) -> Value {
+    serde_json::from_slice(&BASE64_URL_SAFE_NO_PAD.decode(part).unwrap()).unwrap()
+}
+
+fn verify(jws: &Value, public_key: &PublicKey) -> bool {
+    let signing_input = format!(
+        "{}.{}",
+        jws["protected"].as_str().unwrap(),
+        jws["payload"].as_str().unwrap()
+    );
+    let signature = BASE64_URL_SAFE_NO_PAD
+        .decode(jws["signature"].as_str().unwrap())
+        .unwrap();
+    public_key
+        .verify_with_format(signing_input.as_bytes(), &signature, SignatureFormat::Raw)
+        .unwrap()
+}
+
+#[test]
+fn test_sign_request() {
+    let account_key = provider(p256());
+    let metadata = account_key.key_metadata().unwrap();
+    let public_key = metadata.public_key();
+    let point = public_key.to_ec_point().unwrap();
+
+    let payload = json!({ "termsOfServiceAgreed": true });
+    let jws = acme::sign_request(
+        &account_key,
+        KeyReference::Jwk,
+        NEW_ACCOUNT_URL,
+        NONCE,
+        Some(&payload),
+    )
+    .unwrap();
+    let body: Value = serde_json::from_str(&jws.to_json()).unwrap();
+    assert!(verify(&body, public_key));
+    assert_eq!(decode(&jws.payload), payload);
+    assert_eq!(
+        decode(&jws.protected),
+        json!({
+            "alg": "ES256",
+            "jwk": {
+                "crv": "P-256",
+                "kty": "EC",
+                "x": BASE64_URL_SAFE_NO_PAD.encode(&point[1..33]),
+                "y": BASE64_URL_SAFE_NO_PAD.encode(&point[33..]),
+            },
+            "nonce": NONCE,
+            "url": NEW_ACCOUNT_URL,
+        })
+    );
+
+    // A POST-as-GET request has an empty payload and references the account.
+    let jws = acme::sign_request(
+        &account_key,
+        KeyReference::Account(ACCOUNT_URL),
+        ACCOUNT_URL,
+        NONCE,
+        None,
+    )
+    .unwrap();
+    assert_eq!(jws.payload, "");
+    assert!(verify(&serde_json::to_value(&jws).unwrap(), public_key));
+    assert_eq!(
+        decode(&jws.protected),
+        json!({ "alg": "ES256", "kid": ACCOUNT_URL, "nonce": NONCE, "url": ACCOUNT_URL })
+    );
+
+    // The inner JWS of a key rollover is signed by the new key and carries no nonce.
+    let new_account_key = provider(p256());
+    let new_metadata = new_account_key.key_metadata().unwrap();
+    let key_change_url = "https://acme.example.com/acme/key-change";
+    let inner =
+        acme::sign_key_change(&new_account_key, key_change_url, ACCOUNT_URL, public_key).unwrap();
+    assert!(verify(&inner, new_metadata.public_key()));
+    let protected = decode(inner["protected"].as_str().unwrap());
+    assert_eq!(
+        protected["jwk"],
+        acme::jwk(new_metadata.public_key()).unwrap()
+    );
+    assert_eq!(protected["url"], key_change_url);
+    assert!(protected.get("nonce").is_none());
+    assert_eq!(
+        decode(inner["payload"].as_str().unwrap()),
+        json!({ "account": ACCOUNT_URL, "oldKey": acme::jwk(public_key).unwrap() })
+    );
+}
+
+#[test]
+fn test_key_authorization() {
+    // The example of section 3.1 of RFC 7638.
+    let modulus = BASE64_URL_SAFE_NO_PAD
+        .decode(concat!(
+            "0vx7agoebGcQSuuPiLJXZptN9nndrQmbXEps2aiAFbWhM78LhWx4cbbfAAtVT86zwu1RK7aPFFxuhDR1L6tSoc_B",
+            "JECPebWKRXjBZCiFV4n3oknjhMstn64tZ_2W-5JsGY4Hc5n9yBXArwl93lqt7_RN5w6Cf0h4QyQ5v-65YGjQR0_F",
+            "DW2QvzqY368QQMicAtaSqzs8KJZgnYb9c7d0zgdAZHzu6qMQvRL5hajrn1n91CbOpbISD08qNLyrdkt-bFTWhAI4",
+            "vMQFh6WeZu0fM4lFd2NcRwr3XPksINHaQ-G_xBniIqbw0Ls1jF44-csFCur-kEgU8awapJzKnqDKgw",
+        ))
+        .unwrap();
+    let public_key = PublicKey::from_rsa_components(
+        &modulus,
+        &[1, 0, 1],
+        AsymmetricEncryption::Rsa(KeyBits::Bits2048),
+        Hash::Sha2(Sha2Bits::Sha256),
+    )
+    .unwrap();
+    let thumbprint = "NzbLsXh8uDCcd-6MNwXF4W_7noWXFZAfHkxZsRGC9Xs";
+    assert_eq!(acme::jwk_thumbprint(&public_key).unwrap(), thumbprint);
+
+    let key_authorization =
+        acme::key_authorization("evaGxfADs6pSRb2LAv9IZf17", &public_key).unwrap();
+    assert_eq!(
+        key_authorization,
+        format!("evaGxfADs6pSRb2LAv9IZf17.{}", thumbprint)
+    );
+    assert_eq!(acme::dns01_txt_value(&key_authorization).len(), 43);
+    assert_eq!(
+        serde_json::to_value(Identifier::Ip("192.0.2.1".parse().unwrap())).unwrap(),
+        json!({ "type": "ip", "value": "192.0.2.1" })
+    );
+}
+
+#[test]
+fn test_create_csr() {
+    for (algorithm, signature_algorithm) in [
+        (p256(), "ecdsa-with-SHA256"),
+        (
+            AsymmetricEncryption::Rsa(KeyBits::Bits2048),
+            "sha256WithRSAEncryption",
+        ),
+    ] {
+        let provider = provider(algorithm);
+        let identifiers = [
+            Identifier::Dns("device-42.example.com".to_owned()),
+            Identifier::Dns("device-42.local".to_owned()),
+            Identifier::Ip("192.0.2.1".parse().unwrap()),
+            Identifier::Ip("2001:db8::1".parse().unwrap()),
+        ];
+        let csr = acme::create_csr(&provider, &identifiers).unwrap();
+
+        let request = X509Req::from_der(&csr).unwrap();
+        let public_key = provider
+            .key_metadata()
+            .unwrap()
+            .public_key()
+            .to_der()
+            .unwrap();
+        let public_key = PKey::public_key_from_der(&public_key).unwrap();
+        assert!(request.verify(&public_key).unwrap());
+        let text = String::from_utf8(request.to_text().unwrap()).unwrap();
+        assert!(text.contains("CN=device-42.example.com"), "{}", text);
+        assert!(text.contains(signature_algorithm), "{}", text);
+        assert!(
+            text.contains(
+                "DNS:device-42.example.com, DNS:device-42.local, IP Address:192.0.2.1, IP Address:2001:DB8:0:0:0:0:0:1"
+            ),
+            "{}",
+            text
+        );
+
+        let payload = acme::finalize_payload(&csr);
+        assert_eq!(
+            BASE64_URL_SAFE_NO_PAD
+                .decode(payload["csr"].as_str().unwrap())
+                .unwrap(),
+            csr
+        );
+    }
+
+    // Without a DNS name short enough for the common name, the subject is empty.
+    let provider = provider(p256());
+    let long_name = format!("{}.example.com", "a".repeat(60));
+    let csr = acme::create_csr(&provider, &[Identifier::Dns(long_name.clone())]).unwrap();
+    let request = X509Req::from_der(&csr).unwrap();
+    assert_eq!(request.subject_name().entries().count(), 0);
+    let text = String::from_utf8(request.to_text().unwrap()).unwrap();
+    assert!(text.contains(&format!("DNS:{}", long_name)), "{}", text);
+
+    assert!(matches!(
+        acme::create_csr(&provider, &[]),
+        Err(SecurityModuleError::SigningError(_))
+    ));
+    assert!(matches!(
+        acme::create_csr(&provider, &[Identifier::Dns("bücher.example".to_owned())]),
+        Err(SecurityModuleError::SigningError(_))
+    ));
+}
@@ -0,0 +1,233 @@
+use crate::{
+    common::{
+        anomaly::{Anomaly, AnomalyConfig, AnomalyDetector, AnomalyKind, MonitoredProvider},
+        latency::ProviderOperation,
+        traits::{key_handle::KeyHandle, module_provider::Provider},
+    },
+    mock::{MockConfig, MockProvider},
+    SecurityModuleError,
+};
+use std::{
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+fn config(threshold: usize) -> AnomalyConfig {
+    AnomalyConfig {
+        window: Duration::from_secs(60),
+        failed_verifications: threshold,
+        auth_failures: threshold,
+        decrypt_failures: threshold,
+    }
+}
+
+/// Registers a hook collecting the anomalies in memory.
+fn collect(detector: &AnomalyDetector) -> Arc<Mutex<Vec<Anomaly>>> {
+    let collected = Arc::new(Mutex::new(Vec::new()));
+    let hook_collected = collected.clone();
+    detector.add_hook(move |anomaly| hook_collected.lock().unwrap().push(anomaly.clone()));
+    collected
+}
+
+fn kinds(collected: &Mutex<Vec<Anomaly>>) -> Vec<(String, AnomalyKind, usize)> {
+    collected
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|anomaly| (anomaly.key_id.clone(), anomaly.kind, anomaly.count))
+        .collect()
+}
+
+#[test]
+fn test_reports_anomaly_once_per_burst() {
+    let detector = AnomalyDetector::new(config(3));
+    let collected = collect(&detector);
+
+    for _ in 0..2 {
+        detector.record_auth_failure("key");
+    }
+    assert!(collected.lock().unwrap().is_empty());
+    detector.record_auth_failure("key");
+    detector.record_auth_failure("key");
+
+    assert_eq!(
+        kinds(&collected),
+        [("key".to_owned(), AnomalyKind::AuthFailures, 3)]
+    );
+}
+
+#[test]
+fn test_counts_per_key_and_kind() {
+    let detector = AnomalyDetector::new(config(2));
+    let collected = collect(&detector);
+
+    detector.record_auth_failure("first");
+    detector.record_auth_failure("second");
+    detector.record_verification("first", false);
+    detector.record_verification("first", true);
+    assert!(collected.lock().unwrap().is_empty());
+
+    detector.record_verification("first", false);
+    assert_eq!(
+        kinds(&collected),
+        [("first".to_owned(), AnomalyKind::FailedVerifications, 2)]
+    );
+}
+
+#[test]
+fn test_decrypt_failures_count_distinct_ciphertexts() {
+    let detector = AnomalyDetector::new(config(3));
+    let collected = collect(&detector);
+
+    for _ in 0..5 {
+        detector.record_decrypt_failure("key", b"retried");
+    }
+    detector.record_decrypt_failure("key", b"second");
+    assert!(collected.lock().unwrap().is_empty());
+
+    detector.record_decrypt_failure("key", b"third");
+    assert_eq!(
+        kinds(&collected),
+        [("key".to_owned(), AnomalyKind::DecryptFailures, 3)]
+    );
+}
+
+#[test]
+fn test_occurrences_expire_after_window() {
+    let detector = AnomalyDetector::new(AnomalyConfig {
+        window: Duration::from_millis(20),
+        ..config(2)
+    });
+    let collected = collect(&detector);
+
+    detector.record_auth_failure("key");
+    thread::sleep(Duration::from_millis(50));
+    detector.record_auth_failure("key");
+
+    assert!(collected.lock().unwrap().is_empty());
+}
+
+#[test]
+fn test_zero_threshold_disables_detection() {
+    let detector = AnomalyDetector::new(AnomalyConfig {
+        auth_failures: 0,
+        ..config(1)
+    });
+    let collected = collect(&detector);
+
+    detector.record_auth_failure("key");
+    detector.record_verification("key", false);
+
+    assert_eq!(
+        kinds(&collected),
+        [("key".to_owned(), AnomalyKind::FailedVerifications, 1)]
+    );
+}
+
+#[test]
+fn test_remove_hook() {
+    let detector = AnomalyDetector::new(config(1));
+    let collected = Arc::new(Mutex::new(0));
+    let hook_collected = collected.clone();
+    let id = detector.add_hook(move |_| *hook_collected.lock().unwrap() += 1);
+
+    detector.record_auth_failure("key");
+    assert!(detector.remove_hook(id));
+    assert!(!detector.remove_hook(id));
+    detector.record_auth_failure("key");
+
+    assert_eq!(*collected.lock().unwrap(), 1);
+}
+
+#[test]
+fn test_provider_reports_outcomes() {
+    let detector = Arc::new(AnomalyDetector::new(config(2)));
+    let collected = collect(&detector);
+    let mock = MockProvider::new("anomaly_key".to_owned());
+    let controller = mock.controller();
+    let mut provider = MonitoredProvider::new(
+        Arc::new(Mutex::new(mock)),
+        "anomaly_key".to_owned(),
+        detector.clone(),
+    );
+
+    provider.initialize_module().unwrap();
+    provider
+        .create_key("anomaly_key", Box::new(MockConfig::default()))
+        .unwrap();
+    let signature = provider.sign_data(b"data").unwrap();
+    assert!(provider.verify_signature(b"data", &signature).unwrap());
+    assert_eq!(
+        provider
+            .verify_many(&[
+                (&b"forged"[..], &signature[..]),
+                (b"forged again", &signature)
+            ])
+            .unwrap(),
+        [false, false]
+    );
+
+    controller.fail(ProviderOperation::SignData, || {
+        SecurityModuleError::AuthenticationFailed("Cancelled".to_owned())
+    });
+    assert!(provider.sign_data(b"data").is_err());
+    assert!(provider.sign_data(b"data").is_err());
+
+    controller.fail(ProviderOperation::DecryptData, || {
+        SecurityModuleError::DecryptionError("Invalid padding".to_owned())
+    });
+    assert!(provider.decrypt_data(b"first").is_err());
+    assert!(provider.decrypt_data(b"second").is_err());
+
+    assert_eq!(
+        kinds(&collected),
+        [
+            (
+                "anomaly_key".to_owned(),
+                AnomalyKind::FailedVerifications,
+                2
+            ),
+            ("anomaly_key".to_owned(), AnomalyKind::AuthFailures, 2),
+            ("anomaly_key".to_owned(), AnomalyKind::DecryptFailures, 2),
+        ]
+    );
+}
+
+#[test]
+fn test_failed_loads_keep_the_current_key() {
+    let detector = Arc::new(AnomalyDetector::new(config(2)));
+    let collected = collect(&detector);
+    let mock = MockProvider::with_key("anomaly_key", Box::new(MockConfig::default()));
+    let controller = mock.controller();
+    let mut provider = MonitoredProvider::new(
+        Arc::new(Mutex::new(mock)),
+        "anomaly_key".to_owned(),
+        detector.clone(),
+    );
+
+    controller.fail(ProviderOperation::LoadKey, || {
+        SecurityModuleError::AuthenticationFailed("Cancelled".to_owned())
+    });
+    for _ in 0..2 {
+        assert!(provider
+            .load_key("other", Box::new(MockConfig::default()))
+            .is_err());
+    }
+    let signature = provider.sign_data(b"data").unwrap();
+    for _ in 0..2 {
+        assert!(!provider.verify_signature(b"forged", &signature).unwrap());
+    }
+
+    assert_eq!(
+        kinds(&collected),
+        [
+            ("other".to_owned(), AnomalyKind::AuthFailures, 2),
+            (
+                "anomaly_key".to_owned(),
+                AnomalyKind::FailedVerifications,
+                2
+            ),
+        ]
+    );
+}
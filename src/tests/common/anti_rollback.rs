@@ -0,0 +1,92 @@
+use crate::{
+    common::{
+        anti_rollback::{FileCounter, MemoryCounter, MonotonicCounter, RollbackGuard},
+        crypto::algorithms::{
+            encryption::AsymmetricEncryption,
+            hashes::{Hash, Sha2Bits},
+            KeyBits,
+        },
+        traits::module_provider::Provider,
+    },
+    mock::{MockConfig, MockProvider},
+    SecurityModuleError,
+};
+use std::sync::{Arc, Mutex};
+
+fn guard_provider() -> Arc<Mutex<dyn Provider>> {
+    Arc::new(Mutex::new(MockProvider::with_key(
+        "license_key",
+        MockConfig::new(
+            AsymmetricEncryption::Rsa(KeyBits::Bits2048),
+            Hash::Sha2(Sha2Bits::Sha256),
+        ),
+    )))
+}
+
+#[test]
+fn test_seal_and_open() {
+    let guard = RollbackGuard::new(guard_provider(), "license_key", MemoryCounter::new(7));
+
+    let sealed = guard.seal("license", b"valid until 2027").unwrap();
+    assert_eq!(guard.version().unwrap(), 8);
+    assert_eq!(guard.open("license", &sealed).unwrap(), b"valid until 2027");
+    assert_eq!(guard.open("license", &sealed).unwrap(), b"valid until 2027");
+
+    // The name is bound into the key derivation.
+    assert!(matches!(
+        guard.open("config", &sealed),
+        Err(SecurityModuleError::DecryptionError(_))
+    ));
+}
+
+#[test]
+fn test_rejects_rolled_back_data() {
+    let guard = RollbackGuard::new(guard_provider(), "license_key", MemoryCounter::default());
+
+    let revoked = guard.seal("license", b"valid until 2027").unwrap();
+    let current = guard.seal("license", b"revoked").unwrap();
+    assert_eq!(guard.open("license", &current).unwrap(), b"revoked");
+    assert!(matches!(
+        guard.open("license", &revoked),
+        Err(SecurityModuleError::RollbackDetected(message))
+            if message.contains("version 1, but the counter is at 2")
+    ));
+}
+
+#[test]
+fn test_rejects_rolled_back_counter() {
+    let path = std::env::temp_dir().join(format!("anti_rollback_{}", std::process::id()));
+    let guard = RollbackGuard::new(guard_provider(), "license_key", FileCounter::new(&path));
+
+    let sealed = guard.seal("config", b"debug = false").unwrap();
+    std::fs::write(&path, "0").unwrap();
+    assert!(matches!(
+        guard.open("config", &sealed),
+        Err(SecurityModuleError::RollbackDetected(message))
+            if message.ends_with("the counter was rolled back")
+    ));
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_file_counter() {
+    let path = std::env::temp_dir().join(format!("file_counter_{}", std::process::id()));
+    let counter = FileCounter::new(&path);
+
+    assert_eq!(counter.read().unwrap(), 0);
+    assert_eq!(counter.increment().unwrap(), 1);
+    assert_eq!(counter.increment().unwrap(), 2);
+    assert_eq!(FileCounter::new(&path).read().unwrap(), 2);
+
+    std::fs::write(&path, "two").unwrap();
+    assert!(matches!(
+        counter.increment(),
+        Err(SecurityModuleError::SecretStorage(_))
+    ));
+    std::fs::remove_file(&path).unwrap();
+
+    let exhausted = MemoryCounter::new(u64::MAX);
+    assert!(exhausted.increment().is_err());
+    assert_eq!(exhausted.read().unwrap(), u64::MAX);
+}
@@ -0,0 +1,272 @@
+use crate::{
+    common::{
+        audit::{
+            read_file, verify_chain, AuditEvent, AuditLog, AuditOutcome, AuditSink,
+            AuditedProvider, CallbackSink, ChainBreak, FileSink, GENESIS_HASH,
+        },
+        latency::ProviderOperation,
+        traits::{key_handle::KeyHandle, module_provider::Provider},
+    },
+    mock::{MockConfig, MockProvider},
+    SecurityModuleError,
+};
+use std::sync::{Arc, Mutex};
+
+/// A sink collecting events in memory.
+fn collecting_sink() -> (CallbackSink, Arc<Mutex<Vec<AuditEvent>>>) {
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let sink_events = events.clone();
+    let sink = CallbackSink::new(move |event| sink_events.lock().unwrap().push(event.clone()));
+    (sink, events)
+}
+
+fn record_events(count: usize) -> Vec<AuditEvent> {
+    let (sink, events) = collecting_sink();
+    let log = AuditLog::new("tester", sink);
+    for _ in 0..count {
+        log.record("key", ProviderOperation::SignData, AuditOutcome::Success)
+            .unwrap();
+    }
+    let events = events.lock().unwrap().clone();
+    events
+}
+
+#[test]
+fn test_provider_operations_are_audited() {
+    let (sink, events) = collecting_sink();
+    let log = Arc::new(AuditLog::new("alice", sink));
+    let mock = MockProvider::new("audit_key".to_owned());
+    let controller = mock.controller();
+    let mut provider = AuditedProvider::new(
+        Arc::new(Mutex::new(mock)),
+        "audit_key".to_owned(),
+        log.clone(),
+    );
+
+    provider.initialize_module().unwrap();
+    provider
+        .create_key("audit_key", Box::new(MockConfig::default()))
+        .unwrap();
+    let signature = provider.sign_data(b"data").unwrap();
+    assert!(provider.verify_signature(b"data", &signature).unwrap());
+    assert!(!provider.verify_signature(b"other", &signature).unwrap());
+    controller.fail(ProviderOperation::DecryptData, || {
+        SecurityModuleError::DecryptionError("Device removed".to_owned())
+    });
+    assert!(provider.decrypt_data(b"ciphertext").is_err());
+
+    let events = events.lock().unwrap().clone();
+    let summary: Vec<_> = events
+        .iter()
+        .map(|event| (event.operation, event.outcome.clone()))
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            (ProviderOperation::InitializeModule, AuditOutcome::Success),
+            (ProviderOperation::CreateKey, AuditOutcome::Success),
+            (ProviderOperation::SignData, AuditOutcome::Success),
+            (ProviderOperation::VerifySignature, AuditOutcome::Success),
+            (ProviderOperation::VerifySignature, AuditOutcome::Rejected),
+            (
+                ProviderOperation::DecryptData,
+                AuditOutcome::Failure {
+                    code: 2,
+                    message: "Decryption error: Device removed".to_owned()
+                }
+            ),
+        ]
+    );
+    assert!(events
+        .iter()
+        .all(|event| event.actor == "alice" && event.key_id == "audit_key"));
+    assert_eq!(verify_chain(&events), Ok(()));
+    assert_eq!(log.head().next_sequence, 6);
+    assert_eq!(log.head().hash, events[5].hash);
+}
+
+#[test]
+fn test_failed_loads_keep_the_current_key() {
+    let (sink, events) = collecting_sink();
+    let log = Arc::new(AuditLog::new("alice", sink));
+    let mut mock = MockProvider::new("audit_key".to_owned());
+    mock.initialize_module().unwrap();
+    let mut provider =
+        AuditedProvider::new(Arc::new(Mutex::new(mock)), "audit_key".to_owned(), log);
+    provider
+        .create_key("audit_key", Box::new(MockConfig::default()))
+        .unwrap();
+
+    assert!(provider
+        .load_key("other", Box::new(MockConfig::default()))
+        .is_err());
+    provider.sign_data(b"data").unwrap();
+
+    let events = events.lock().unwrap();
+    let summary: Vec<_> = events
+        .iter()
+        .map(|event| (event.key_id.as_str(), event.operation))
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            ("audit_key", ProviderOperation::CreateKey),
+            ("other", ProviderOperation::LoadKey),
+            ("audit_key", ProviderOperation::SignData),
+        ]
+    );
+    assert!(matches!(events[1].outcome, AuditOutcome::Failure { .. }));
+}
+
+#[test]
+fn test_chain_links_events() {
+    let events = record_events(3);
+
+    assert_eq!(events[0].sequence, 0);
+    assert_eq!(events[0].previous_hash, GENESIS_HASH);
+    assert_eq!(events[1].previous_hash, events[0].hash);
+    assert_eq!(events[2].previous_hash, events[1].hash);
+    assert_eq!(verify_chain(&events), Ok(()));
+    // A part of a chain verifies on its own.
+    assert_eq!(verify_chain(&events[1..]), Ok(()));
+    assert_eq!(verify_chain(&[]), Ok(()));
+}
+
+#[test]
+fn test_tampering_breaks_chain() {
+    let events = record_events(4);
+
+    let mut modified = events.clone();
+    modified[2].outcome = AuditOutcome::Failure {
+        code: 1,
+        message: "Signing error: forged".to_owned(),
+    };
+    assert_eq!(verify_chain(&modified), Err(ChainBreak { index: 2 }));
+
+    // Recomputing the hash of a modified event breaks the link of its successor.
+    modified[2].hash = modified[2].compute_hash();
+    assert_eq!(verify_chain(&modified), Err(ChainBreak { index: 3 }));
+
+    let mut removed = events.clone();
+    removed.remove(1);
+    assert_eq!(verify_chain(&removed), Err(ChainBreak { index: 1 }));
+
+    let mut reordered = events.clone();
+    reordered.swap(1, 2);
+    assert_eq!(verify_chain(&reordered), Err(ChainBreak { index: 1 }));
+
+    let mut forged_genesis = events;
+    forged_genesis[0].previous_hash = forged_genesis[1].hash.clone();
+    forged_genesis[0].hash = forged_genesis[0].compute_hash();
+    assert_eq!(verify_chain(&forged_genesis), Err(ChainBreak { index: 0 }));
+}
+
+#[test]
+fn test_fips_mode_is_hashed() {
+    let events = record_events(2);
+    // Events outside of FIPS mode omit the field and keep the hash they had before it existed.
+    let json = serde_json::to_string(&events[0]).unwrap();
+    assert!(!json.contains("fips_mode"));
+    assert_eq!(
+        serde_json::from_str::<AuditEvent>(&json).unwrap(),
+        events[0]
+    );
+
+    let mut fips = events.clone();
+    fips[1].fips_mode = true;
+    assert_eq!(verify_chain(&fips), Err(ChainBreak { index: 1 }));
+    fips[1].hash = fips[1].compute_hash();
+    assert_eq!(verify_chain(&fips), Ok(()));
+    let json = serde_json::to_string(&fips[1]).unwrap();
+    assert!(json.contains(r#""fips_mode":true"#));
+    assert_eq!(serde_json::from_str::<AuditEvent>(&json).unwrap(), fips[1]);
+}
+
+#[test]
+fn test_file_sink_round_trip() {
+    let path = std::env::temp_dir().join(format!("audit_{}.jsonl", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let log = AuditLog::new("service", FileSink::open(&path).unwrap());
+    log.record("key", ProviderOperation::CreateKey, AuditOutcome::Success)
+        .unwrap();
+    let head = log.head();
+    drop(log);
+    // A later run continues the chain in the same file.
+    let log = AuditLog::resume("service", FileSink::open(&path).unwrap(), head);
+    log.record(
+        "key",
+        ProviderOperation::SignData,
+        (&SecurityModuleError::SigningFailed).into(),
+    )
+    .unwrap();
+
+    let events = read_file(&path).unwrap();
+    assert_eq!(events.len(), 2);
+    assert_eq!(verify_chain(&events), Ok(()));
+    assert_eq!(events[1].sequence, 1);
+    let line = std::fs::read_to_string(&path).unwrap();
+    assert!(line.contains(r#""operation":"sign_data","result":"failure","code":11"#));
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_failed_writes_leave_gap() {
+    struct FailingSink;
+
+    impl AuditSink for FailingSink {
+        fn write(&self, event: &AuditEvent) -> Result<(), SecurityModuleError> {
+            match event.sequence {
+                1 => Err(SecurityModuleError::InitializationError(
+                    "Disk full".to_owned(),
+                )),
+                _ => Ok(()),
+            }
+        }
+    }
+
+    let log = AuditLog::new("tester", FailingSink);
+    let first = log
+        .record("key", ProviderOperation::SignData, AuditOutcome::Success)
+        .unwrap();
+    assert!(log
+        .record("key", ProviderOperation::SignData, AuditOutcome::Success)
+        .is_err());
+    let third = log
+        .record("key", ProviderOperation::SignData, AuditOutcome::Success)
+        .unwrap();
+
+    assert_eq!(verify_chain(&[first, third]), Err(ChainBreak { index: 1 }));
+}
+
+#[test]
+#[cfg(unix)]
+fn test_syslog_sink() {
+    use crate::common::audit::SyslogSink;
+    use std::os::unix::net::UnixDatagram;
+
+    let path = std::env::temp_dir().join(format!("audit_syslog_{}.sock", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let daemon = UnixDatagram::bind(&path).unwrap();
+
+    let log = AuditLog::new("tester", SyslogSink::with_path(&path).unwrap());
+    log.record("key", ProviderOperation::LoadKey, AuditOutcome::Success)
+        .unwrap();
+    log.record(
+        "key",
+        ProviderOperation::VerifySignature,
+        AuditOutcome::Rejected,
+    )
+    .unwrap();
+
+    let mut buffer = [0; 1024];
+    let length = daemon.recv(&mut buffer).unwrap();
+    let message = std::str::from_utf8(&buffer[..length]).unwrap();
+    assert!(message.starts_with("<38>crypto-layer: {"));
+    assert!(message.contains(r#""operation":"load_key""#));
+    let length = daemon.recv(&mut buffer).unwrap();
+    assert!(buffer[..length].starts_with(b"<36>crypto-layer: "));
+
+    std::fs::remove_file(&path).unwrap();
+}
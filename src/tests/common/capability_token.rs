@@ -0,0 +1,171 @@
+use crate::{
+    common::{
+        capability_token::{
+            mint_token, verify_token, verify_token_at, CapabilityToken, TokenPolicy, TokenSpec,
+            MAX_LIFETIME,
+        },
+        crypto::{
+            algorithms::{
+                encryption::{AsymmetricEncryption, EccCurves, EccSchemeAlgorithm},
+                hashes::{Hash, Sha2Bits},
+                KeyBits,
+            },
+            public_key::PublicKey,
+        },
+        traits::module_provider::Provider,
+    },
+    mock::{MockConfig, MockProvider},
+    SecurityModuleError,
+};
+use base64::{prelude::BASE64_URL_SAFE_NO_PAD, Engine};
+use std::time::{Duration, SystemTime};
+
+const AUDIENCE: &str = "https://storage.example.com";
+
+fn device(algorithm: AsymmetricEncryption) -> MockProvider {
+    MockProvider::with_key(
+        "device_key",
+        MockConfig::new(algorithm, Hash::Sha2(Sha2Bits::Sha256)),
+    )
+}
+
+fn ec_device() -> MockProvider {
+    device(AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(
+        EccCurves::P256,
+    )))
+}
+
+fn public_key(provider: &MockProvider) -> PublicKey {
+    provider.key_metadata().unwrap().public_key().clone()
+}
+
+fn spec() -> TokenSpec {
+    TokenSpec::new(AUDIENCE, Duration::from_secs(300))
+        .subject("alice")
+        .claim("scope", "read:firmware".into())
+}
+
+#[test]
+fn test_mint_and_verify() {
+    for algorithm in [
+        AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(EccCurves::P256)),
+        AsymmetricEncryption::Rsa(KeyBits::Bits2048),
+    ] {
+        let provider = device(algorithm);
+        let token = mint_token(&provider, &spec()).unwrap();
+        let token: CapabilityToken = token.to_string().parse().unwrap();
+        assert_eq!(token.key_id(), "device_key");
+
+        let claims = verify_token(
+            &token,
+            &public_key(&provider),
+            AUDIENCE,
+            &TokenPolicy::default(),
+        )
+        .unwrap();
+        assert_eq!(claims.issuer, "device_key");
+        assert_eq!(claims.subject.as_deref(), Some("alice"));
+        assert_eq!(claims.expires_at - claims.issued_at, 300);
+        assert_eq!(claims.claims["scope"], "read:firmware");
+    }
+
+    let token = mint_token(&ec_device(), &spec().issuer("sensor-7")).unwrap();
+    assert_eq!(token.header().alg, "ES256");
+    assert_eq!(token.signature().len(), 64);
+    assert_eq!(token.claims().issuer, "sensor-7");
+}
+
+#[test]
+fn test_rejects_invalid_tokens() {
+    let provider = ec_device();
+    let public_key = public_key(&provider);
+    let token = mint_token(&provider, &spec()).unwrap();
+    let policy = TokenPolicy::default();
+
+    assert!(matches!(
+        verify_token(&token, &self::public_key(&ec_device()), AUDIENCE, &policy),
+        Err(SecurityModuleError::InvalidSignature)
+    ));
+    assert!(matches!(
+        verify_token(&token, &public_key, "https://other.example.com", &policy),
+        Err(SecurityModuleError::InvalidToken(_))
+    ));
+
+    // A modified claim invalidates the signature.
+    let encoded = token.to_string();
+    let parts: Vec<&str> = encoded.split('.').collect();
+    let claims = String::from_utf8(BASE64_URL_SAFE_NO_PAD.decode(parts[1]).unwrap()).unwrap();
+    let claims = claims.replace("read:firmware", "write:firmware");
+    let modified = format!(
+        "{}.{}.{}",
+        parts[0],
+        BASE64_URL_SAFE_NO_PAD.encode(claims),
+        parts[2]
+    );
+    let modified: CapabilityToken = modified.parse().unwrap();
+    assert!(matches!(
+        verify_token(&modified, &public_key, AUDIENCE, &policy),
+        Err(SecurityModuleError::InvalidSignature)
+    ));
+
+    // The algorithm is taken from the key, not from the token.
+    let header = BASE64_URL_SAFE_NO_PAD.encode(r#"{"alg":"none","typ":"JWT","kid":"device_key"}"#);
+    let unsigned: CapabilityToken = format!("{}.{}.", header, parts[1]).parse().unwrap();
+    assert!(matches!(
+        verify_token(&unsigned, &public_key, AUDIENCE, &policy),
+        Err(SecurityModuleError::InvalidToken(_))
+    ));
+
+    assert!("not a token".parse::<CapabilityToken>().is_err());
+    assert!("a.b.c".parse::<CapabilityToken>().is_err());
+}
+
+#[test]
+fn test_token_validity() {
+    let provider = ec_device();
+    let public_key = public_key(&provider);
+    let token = mint_token(&provider, &spec()).unwrap();
+    let policy = TokenPolicy::default();
+    let now = SystemTime::now();
+    let verify_at = |now| verify_token_at(&token, &public_key, AUDIENCE, &policy, now);
+
+    assert!(verify_at(now + Duration::from_secs(290)).is_ok());
+    assert!(matches!(
+        verify_at(now + Duration::from_secs(300 + 31)),
+        Err(SecurityModuleError::InvalidToken(_))
+    ));
+    assert!(verify_at(now - Duration::from_secs(10)).is_ok());
+    assert!(matches!(
+        verify_at(now - Duration::from_secs(60)),
+        Err(SecurityModuleError::InvalidToken(_))
+    ));
+
+    let strict = TokenPolicy {
+        max_lifetime: Duration::from_secs(60),
+        ..policy
+    };
+    assert!(matches!(
+        verify_token(&token, &public_key, AUDIENCE, &strict),
+        Err(SecurityModuleError::InvalidToken(_))
+    ));
+}
+
+#[test]
+fn test_mint_rejects_invalid_specs() {
+    let provider = ec_device();
+    for spec in [
+        TokenSpec::new(AUDIENCE, Duration::ZERO),
+        TokenSpec::new(AUDIENCE, MAX_LIFETIME + Duration::from_secs(1)),
+        spec().claim("exp", u64::MAX.into()),
+        spec().claim("aud", "https://other.example.com".into()),
+    ] {
+        assert!(
+            matches!(
+                mint_token(&provider, &spec),
+                Err(SecurityModuleError::InvalidToken(_))
+            ),
+            "{:?} was accepted",
+            spec
+        );
+    }
+}
@@ -0,0 +1,305 @@
+use crate::{
+    common::{
+        config::{CryptoConfig, FallbackPolicy, Memory},
+        crypto::secret::SecretBytes,
+        factory::SecModules,
+        input_limits::{InputLimits, DEFAULT_MAX_DATA_LEN, DEFAULT_MAX_FIELD_LEN},
+        log_levels, memory_lock,
+        profile::Profile,
+        sunset::{SunsetAction, SunsetPolicy},
+    },
+    SecurityModuleError,
+};
+use std::time::Duration;
+use tracing::level_filters::LevelFilter;
+
+const TOML: &str = r#"
+providers = ["macos", "nks"]
+fallback = "next_provider"
+namespace = "com.example.mail"
+fips = true
+
+[timeouts]
+slow_operation_ms = 250
+session_acquire_ms = 5000
+
+[logging]
+level = "info"
+targets = { macos = "trace" }
+
+[deprecated]
+rsa1024 = "deny"
+sha1 = "warn"
+
+[memory]
+lock_secrets = true
+
+[limits]
+max_data_len = 1048576
+"#;
+
+fn vars(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+    vars.iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect()
+}
+
+fn rejection(result: Result<CryptoConfig, SecurityModuleError>) -> String {
+    match result {
+        Err(SecurityModuleError::InitializationError(message)) => message,
+        result => panic!("Expected InitializationError, got {:?}", result),
+    }
+}
+
+#[test]
+fn test_from_toml() {
+    let config = CryptoConfig::from_toml(TOML).unwrap();
+
+    assert_eq!(config.providers, ["macos", "nks"]);
+    assert_eq!(config.fallback, FallbackPolicy::NextProvider);
+    assert_eq!(config.namespace().unwrap().name(), "com.example.mail");
+    assert_eq!(
+        config.latency_config().slow_threshold,
+        Some(Duration::from_millis(250))
+    );
+    assert_eq!(
+        config.session_pool_config().acquire_timeout,
+        Some(Duration::from_secs(5))
+    );
+    assert_eq!(config.logging.level.as_deref(), Some("info"));
+    assert_eq!(config.logging.targets["macos"], "trace");
+    assert_eq!(config.deprecated["rsa1024"], SunsetAction::Deny);
+    assert_eq!(
+        config.sunset_policy(),
+        SunsetPolicy::new()
+            .deprecate("rsa1024", SunsetAction::Deny)
+            .unwrap()
+            .deprecate("sha1", SunsetAction::Warn)
+            .unwrap()
+    );
+    assert!(config.memory.lock_secrets);
+    assert_eq!(config.limits.max_data_len, 1024 * 1024);
+    assert_eq!(config.limits.max_field_len, DEFAULT_MAX_FIELD_LEN);
+    assert!(config.fips);
+}
+
+#[test]
+fn test_missing_fields_keep_defaults() {
+    assert_eq!(
+        CryptoConfig::from_toml("").unwrap(),
+        CryptoConfig::default()
+    );
+    let config = CryptoConfig::from_toml("[timeouts]\nslow_operation_ms = 1").unwrap();
+    assert_eq!(
+        config.timeouts.session_acquire_ms,
+        CryptoConfig::default().timeouts.session_acquire_ms
+    );
+}
+
+#[test]
+fn test_rejects_invalid_toml() {
+    assert!(rejection(CryptoConfig::from_toml("provider = [\"macos\"]")).contains("provider"));
+    assert_eq!(
+        rejection(CryptoConfig::from_toml("providers = [\"tpm2\"]")),
+        "Unknown provider 'tpm2'"
+    );
+    assert_eq!(
+        rejection(CryptoConfig::from_toml("[logging]\nlevel = \"loud\"")),
+        "Unknown log level 'loud'"
+    );
+    assert_eq!(
+        rejection(CryptoConfig::from_toml("[deprecated]\nrsa1000 = \"deny\"")),
+        "Unknown algorithm 'rsa1000'"
+    );
+    assert!(CryptoConfig::from_toml("fallback = \"sometimes\"").is_err());
+    assert_eq!(
+        rejection(CryptoConfig::from_toml("[limits]\nmax_batch_len = 0")),
+        "The input limit max_batch_len must not be 0"
+    );
+    assert!(rejection(CryptoConfig::from_toml("namespace = \"tenant/a\"")).contains("tenant/a"));
+}
+
+#[test]
+fn test_env_overrides() {
+    let config = CryptoConfig::from_toml(TOML)
+        .unwrap()
+        .with_env_overrides(vars(&[
+            ("CRYPTO_LAYER_PROVIDERS", "linux, windows"),
+            ("CRYPTO_LAYER_FALLBACK", "never"),
+            ("CRYPTO_LAYER_NAMESPACE", "tenant-a"),
+            ("CRYPTO_LAYER_SLOW_OPERATION_MS", "off"),
+            ("CRYPTO_LAYER_SESSION_ACQUIRE_MS", "100"),
+            ("CRYPTO_LAYER_LOG_LEVEL", "warn"),
+            ("CRYPTO_LAYER_LOCK_SECRETS", "false"),
+            ("CRYPTO_LAYER_FIPS", "false"),
+            ("CRYPTO_LAYER_MAX_DATA_LEN", "2048"),
+            ("CRYPTO_LAYER_DEPRECATED", "sha1=deny, rsa2048=warn"),
+            (
+                "CRYPTO_LAYER_LOG_TARGETS",
+                "macos=debug,crypto_layer::common=error",
+            ),
+            ("PATH", "/usr/bin"),
+        ]))
+        .unwrap();
+
+    assert_eq!(config.providers, ["linux", "windows"]);
+    assert_eq!(config.fallback, FallbackPolicy::Never);
+    assert_eq!(config.namespace.as_deref(), Some("tenant-a"));
+    assert_eq!(config.latency_config().slow_threshold, None);
+    assert_eq!(
+        config.session_pool_config().acquire_timeout,
+        Some(Duration::from_millis(100))
+    );
+    assert_eq!(config.logging.level.as_deref(), Some("warn"));
+    assert_eq!(config.logging.targets["macos"], "debug");
+    assert_eq!(config.logging.targets["crypto_layer::common"], "error");
+    assert_eq!(config.deprecated["rsa1024"], SunsetAction::Deny);
+    assert_eq!(config.deprecated["rsa2048"], SunsetAction::Warn);
+    assert_eq!(config.deprecated["sha1"], SunsetAction::Deny);
+    assert!(!config.memory.lock_secrets);
+    assert!(!config.fips);
+    assert_eq!(config.limits.max_data_len, 2048);
+}
+
+#[test]
+fn test_rejects_invalid_env_overrides() {
+    let override_with = |name: &str, value: &str| {
+        rejection(CryptoConfig::default().with_env_overrides(vars(&[(name, value)])))
+    };
+
+    assert_eq!(
+        override_with("CRYPTO_LAYER_PROVIDERS", "macos,tpm2"),
+        "Unknown provider 'tpm2'"
+    );
+    assert_eq!(
+        override_with("CRYPTO_LAYER_FALLBACK", "always"),
+        "Unknown fallback policy 'always'"
+    );
+    assert_eq!(
+        override_with("CRYPTO_LAYER_SLOW_OPERATION_MS", "fast"),
+        "CRYPTO_LAYER_SLOW_OPERATION_MS must be a number of milliseconds or off"
+    );
+    assert_eq!(
+        override_with("CRYPTO_LAYER_LOG_TARGETS", "macos"),
+        "Expected target=level in CRYPTO_LAYER_LOG_TARGETS"
+    );
+    assert_eq!(
+        override_with("CRYPTO_LAYER_DEPRECATED", "sha1=forbid"),
+        "Unknown sunset action 'forbid'"
+    );
+    assert_eq!(
+        override_with("CRYPTO_LAYER_LOCK_SECRETS", "yes"),
+        "CRYPTO_LAYER_LOCK_SECRETS must be true or false"
+    );
+    assert_eq!(
+        override_with("CRYPTO_LAYER_FIPS", "on"),
+        "CRYPTO_LAYER_FIPS must be true or false"
+    );
+    assert_eq!(
+        override_with("CRYPTO_LAYER_MAX_DATA_LEN", "16M"),
+        "CRYPTO_LAYER_MAX_DATA_LEN must be a number of bytes"
+    );
+    assert_eq!(
+        override_with("CRYPTO_LAYER_MAX_DATA_LEN", "0"),
+        "The input limit max_data_len must not be 0"
+    );
+}
+
+#[test]
+fn test_load_file() {
+    let path = std::env::temp_dir().join(format!("crypto_config_{}.toml", std::process::id()));
+    std::fs::write(&path, TOML).unwrap();
+    let config = CryptoConfig::load(&path);
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(config.unwrap(), CryptoConfig::from_toml(TOML).unwrap());
+    assert!(CryptoConfig::load(&path).is_err());
+}
+
+#[test]
+fn test_security_modules_skip_disabled_providers() {
+    let config = CryptoConfig::from_toml(r#"providers = ["yubikey", "macos"]"#).unwrap();
+    let modules = config.security_modules();
+
+    #[cfg(all(feature = "macos", not(feature = "hsm")))]
+    assert_eq!(
+        modules,
+        [crate::common::factory::SecurityModule::Tpm(
+            crate::tpm::core::instance::TpmType::MacOs
+        )]
+    );
+    #[cfg(feature = "hsm")]
+    assert_eq!(modules.len(), 2);
+    #[cfg(not(any(feature = "macos", feature = "hsm")))]
+    assert!(modules.is_empty());
+}
+
+/// `SecModules` holds a single configuration, so everything that injects one is tested together.
+#[test]
+fn test_configure() {
+    let mut config = SecModules::config();
+    config.providers.clear();
+    config
+        .logging
+        .targets
+        .insert("crypto_layer::tests::config".to_owned(), "debug".to_owned());
+    SecModules::configure(config.clone()).unwrap();
+
+    assert_eq!(SecModules::config(), config);
+    assert_eq!(
+        log_levels::levels().1.get("crypto_layer::tests::config"),
+        Some(&LevelFilter::DEBUG)
+    );
+    log_levels::clear_target_level("crypto_layer::tests::config");
+
+    // Locking is best effort, so only the request to lock is checked.
+    let unlocked = SecretBytes::from(&b"secret"[..]);
+    SecModules::configure(CryptoConfig {
+        memory: Memory { lock_secrets: true },
+        ..config.clone()
+    })
+    .unwrap();
+    assert!(memory_lock::is_enabled());
+    let locked = SecretBytes::from(&b"secret"[..]);
+    assert!(memory_lock::is_locked(&locked));
+    assert!(!memory_lock::is_locked(&unlocked));
+    SecModules::configure(config.clone()).unwrap();
+    assert!(!memory_lock::is_enabled());
+    assert!(memory_lock::is_locked(&locked));
+    drop(locked);
+
+    // Raising the limit keeps the other tests, which use the process limits, unaffected.
+    let limits = InputLimits {
+        max_data_len: 2 * DEFAULT_MAX_DATA_LEN,
+        ..config.limits
+    };
+    SecModules::configure(CryptoConfig {
+        limits,
+        ..config.clone()
+    })
+    .unwrap();
+    assert_eq!(InputLimits::current(), limits);
+    SecModules::configure(config.clone()).unwrap();
+    assert_eq!(InputLimits::current(), config.limits);
+
+    assert!(matches!(
+        SecModules::get_preferred_instance("config_key".to_owned(), None),
+        Err(SecurityModuleError::InitializationError(message))
+            if message == "No configured provider is enabled in this build"
+    ));
+    let plan = SecModules::plan("config_key").unwrap();
+    assert!(plan.providers.is_empty());
+    assert_eq!(plan.fallback, config.fallback);
+
+    let mut invalid = config.clone();
+    invalid.providers.push("tpm2".to_owned());
+    assert!(SecModules::configure(invalid).is_err());
+    assert_eq!(SecModules::config(), config);
+
+    SecModules::use_profile(Profile::Compatibility).unwrap();
+    assert_eq!(
+        SecModules::config(),
+        Profile::Compatibility.apply(config.clone())
+    );
+    SecModules::configure(config).unwrap();
+}
@@ -0,0 +1,10 @@
+use crate::common::constant_time;
+
+#[test]
+fn test_eq() {
+    assert!(constant_time::eq(b"tag", b"tag"));
+    assert!(constant_time::eq(b"", b""));
+    assert!(!constant_time::eq(b"tag", b"tab"));
+    assert!(!constant_time::eq(b"tag", b"tags"));
+    assert!(!constant_time::eq(b"", b"tag"));
+}
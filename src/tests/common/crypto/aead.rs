@@ -0,0 +1,145 @@
+use crate::{
+    common::crypto::{
+        aead,
+        envelope::{AeadAlgorithm, Envelope, EnvelopeRef},
+    },
+    SecurityModuleError,
+};
+use proptest::{collection::vec, prelude::*};
+use test_case::test_case;
+
+const PLAINTEXT: &[u8] = b"The quick brown fox jumps over the lazy dog";
+
+fn hex(data: &str) -> Vec<u8> {
+    (0..data.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&data[i..i + 2], 16).unwrap())
+        .collect()
+}
+
+fn kat_key(aead: AeadAlgorithm) -> Vec<u8> {
+    (0..aead.key_len() as u8).collect()
+}
+
+fn kat_envelope(aead: AeadAlgorithm) -> Envelope {
+    Envelope::new(aead, "kat-key", (0xa0..0xac).collect())
+}
+
+// Envelopes computed independently from the format specification with the Python
+// `cryptography` package, key 00 01 02 ..., nonce a0 a1 ... ab and key id "kat-key".
+#[test_case(AeadAlgorithm::Aes128Gcm, "434c455601010100076b61742d6b657902000ca0a1a2a3a4a5a6a7a8a9aaab00feee5d9b0ffc5a69e158d7722965de4035815b28e96250df56301eed27ffafdcf2c6655d6e90954d1bb185c41d148866bc0d12693144e20257bbad" ; "aes128gcm")]
+#[test_case(AeadAlgorithm::Aes256Gcm, "434c455601020100076b61742d6b657902000ca0a1a2a3a4a5a6a7a8a9aaab00b270190d34be6bdc0945e5a1680daefe16c32130f8c22f1cef2e49f01ad95575ba136793ce582a1d3bf363d5c6e565c3e07f53678efcf87c0d1dfc" ; "aes256gcm")]
+#[test_case(AeadAlgorithm::ChaCha20Poly1305, "434c455601030100076b61742d6b657902000ca0a1a2a3a4a5a6a7a8a9aaab0058c31d7f3c93abcecb2f9166938d93dbfb31ab9f291f0dd3c7f8b4c71410e37804e536e6cfb386aaa2a392f193851353fd087abe7183e6e227d8f2" ; "chacha20poly1305")]
+fn test_known_answer(aead: AeadAlgorithm, expected: &str) {
+    let expected = hex(expected);
+    let key = kat_key(aead);
+
+    let sealed = aead::seal(kat_envelope(aead), &key, PLAINTEXT).unwrap();
+    assert_eq!(sealed, expected);
+
+    let envelope = EnvelopeRef::parse(&expected).unwrap();
+    assert_eq!(aead::open(&envelope, &key).unwrap(), PLAINTEXT);
+}
+
+#[test_case(AeadAlgorithm::Aes128Gcm ; "aes128gcm")]
+#[test_case(AeadAlgorithm::Aes256Gcm ; "aes256gcm")]
+#[test_case(AeadAlgorithm::ChaCha20Poly1305 ; "chacha20poly1305")]
+fn test_seal_open_random_nonce(aead: AeadAlgorithm) {
+    let key = kat_key(aead);
+    let envelope = Envelope::new(aead, "key", aead::random_nonce(aead).unwrap());
+
+    let sealed = aead::seal(envelope, &key, b"").unwrap();
+    let opened = aead::open(&EnvelopeRef::parse(&sealed).unwrap(), &key).unwrap();
+    assert!(opened.is_empty());
+    assert_ne!(
+        aead::random_nonce(aead).unwrap(),
+        aead::random_nonce(aead).unwrap()
+    );
+}
+
+#[test]
+fn test_open_rejects_modified_header() {
+    let key = kat_key(AeadAlgorithm::Aes256Gcm);
+    let mut envelope = kat_envelope(AeadAlgorithm::Aes256Gcm);
+    let sealed = aead::seal(envelope.clone(), &key, PLAINTEXT).unwrap();
+
+    // Move the ciphertext under another key id, which changes the associated data.
+    envelope.key_id = "other-key".to_owned();
+    envelope.ciphertext = EnvelopeRef::parse(&sealed).unwrap().ciphertext.to_vec();
+    let moved = envelope.to_bytes().unwrap();
+
+    assert!(matches!(
+        aead::open(&EnvelopeRef::parse(&moved).unwrap(), &key),
+        Err(SecurityModuleError::DecryptionError(_))
+    ));
+}
+
+#[test]
+fn test_open_rejects_modified_ciphertext() {
+    let key = kat_key(AeadAlgorithm::Aes256Gcm);
+    let mut sealed = aead::seal(kat_envelope(AeadAlgorithm::Aes256Gcm), &key, PLAINTEXT).unwrap();
+    let last = sealed.len() - 1;
+    sealed[last] ^= 0x01;
+
+    assert!(matches!(
+        aead::open(&EnvelopeRef::parse(&sealed).unwrap(), &key),
+        Err(SecurityModuleError::DecryptionError(_))
+    ));
+}
+
+#[test]
+fn test_invalid_key_length() {
+    let key = kat_key(AeadAlgorithm::Aes128Gcm);
+    assert!(matches!(
+        aead::seal(kat_envelope(AeadAlgorithm::Aes256Gcm), &key, PLAINTEXT),
+        Err(SecurityModuleError::EncryptionError(_))
+    ));
+}
+
+fn aead_algorithm() -> impl Strategy<Value = AeadAlgorithm> {
+    prop_oneof![
+        Just(AeadAlgorithm::Aes128Gcm),
+        Just(AeadAlgorithm::Aes256Gcm),
+        Just(AeadAlgorithm::ChaCha20Poly1305),
+    ]
+}
+
+prop_compose! {
+    fn key_and_envelope()(aead in aead_algorithm())(
+        key in vec(any::<u8>(), aead.key_len()),
+        nonce in vec(any::<u8>(), aead.nonce_len()),
+        key_id in "\\PC{0,32}",
+        aead in Just(aead),
+    ) -> (Vec<u8>, Envelope) {
+        (key, Envelope::new(aead, key_id, nonce))
+    }
+}
+
+proptest! {
+    #[test]
+    fn prop_seal_open_is_identity(
+        (key, envelope) in key_and_envelope(),
+        plaintext in vec(any::<u8>(), 0..4096),
+    ) {
+        let sealed = aead::seal(envelope, &key, &plaintext).unwrap();
+        let opened = aead::open(&EnvelopeRef::parse(&sealed).unwrap(), &key).unwrap();
+        prop_assert_eq!(opened, plaintext);
+    }
+
+    #[test]
+    fn prop_modified_envelope_is_rejected(
+        (key, envelope) in key_and_envelope(),
+        plaintext in vec(any::<u8>(), 0..256),
+        position in any::<prop::sample::Index>(),
+        bit in 0..8u8,
+    ) {
+        let mut sealed = aead::seal(envelope, &key, &plaintext).unwrap();
+        let position = position.index(sealed.len());
+        sealed[position] ^= 1 << bit;
+
+        // Modifications either break the encoding or the authentication.
+        if let Ok(envelope) = EnvelopeRef::parse(&sealed) {
+            prop_assert!(aead::open(&envelope, &key).is_err());
+        }
+    }
+}
@@ -0,0 +1,266 @@
+use crate::common::crypto::{
+    aead,
+    envelope::{AeadAlgorithm, Envelope, EnvelopeRef, MAGIC, VERSION},
+    kdf::Kdf,
+};
+use crypto_layer_core::CoreError;
+use proptest::{collection::vec, option, prelude::*};
+use test_case::test_case;
+
+fn envelope() -> Envelope {
+    let mut envelope = Envelope::new(AeadAlgorithm::Aes256Gcm, "test_key", vec![7; 12]);
+    envelope.ciphertext = vec![0xaa; 32];
+    envelope
+}
+
+/// Encodes a header by hand, so that tests can produce envelopes `Envelope` refuses to encode.
+fn raw_envelope(fields: &[(u8, &[u8])]) -> Vec<u8> {
+    let mut bytes = MAGIC.to_vec();
+    bytes.push(VERSION);
+    bytes.push(AeadAlgorithm::Aes256Gcm.id());
+    for (tag, value) in fields {
+        bytes.push(*tag);
+        bytes.extend_from_slice(&(value.len() as u16).to_be_bytes());
+        bytes.extend_from_slice(value);
+    }
+    bytes.push(0);
+    bytes.extend_from_slice(&[0xaa; 16]);
+    bytes
+}
+
+#[test]
+fn test_round_trip() {
+    let envelope = envelope();
+
+    let bytes = envelope.to_bytes().unwrap();
+
+    assert_eq!(Envelope::from_bytes(&bytes).unwrap(), envelope);
+}
+
+#[test]
+fn test_round_trip_with_optional_fields() {
+    let mut envelope = envelope();
+    envelope.aead = AeadAlgorithm::ChaCha20Poly1305;
+    envelope.wrapped_key = Some(vec![1; 40]);
+    envelope.ephemeral_public_key = Some(vec![4; 65]);
+    envelope.kdf = Some(Kdf::HkdfSha384);
+    envelope.salt = Some(vec![2; 16]);
+
+    let bytes = envelope.to_bytes().unwrap();
+
+    assert_eq!(Envelope::from_bytes(&bytes).unwrap(), envelope);
+}
+
+#[test]
+fn test_parse_borrows_header_and_ciphertext() {
+    let envelope = envelope();
+    let bytes = envelope.to_bytes().unwrap();
+
+    let parsed = EnvelopeRef::parse(&bytes).unwrap();
+
+    assert_eq!(parsed.header, envelope.header().unwrap().as_slice());
+    assert_eq!(parsed.ciphertext, envelope.ciphertext.as_slice());
+    assert_eq!(parsed.key_id, "test_key");
+    assert_eq!(bytes.len(), parsed.header.len() + parsed.ciphertext.len());
+}
+
+#[test]
+fn test_debug_redacts_payload() {
+    let mut envelope = envelope();
+    envelope.wrapped_key = Some(vec![0xbb; 40]);
+    let bytes = envelope.to_bytes().unwrap();
+
+    for debug in [
+        format!("{:?}", envelope),
+        format!("{:?}", EnvelopeRef::parse(&bytes).unwrap()),
+    ] {
+        assert!(debug.contains("key_id: \"test_key\""), "{}", debug);
+        assert!(debug.contains("wrapped_key: Some(<40 bytes"), "{}", debug);
+        assert!(debug.contains("ciphertext: <32 bytes"), "{}", debug);
+        assert!(!debug.contains("170") && !debug.contains("187"), "{}", debug);
+    }
+}
+
+#[test]
+fn test_skips_unknown_optional_field() {
+    let bytes = raw_envelope(&[(0x01, b"test_key"), (0x02, &[7; 12]), (0x90, b"extension")]);
+
+    let parsed = EnvelopeRef::parse(&bytes).unwrap();
+
+    assert_eq!(parsed.key_id, "test_key");
+    assert_eq!(parsed.header.len(), bytes.len() - 16);
+}
+
+#[test]
+fn test_keeps_unknown_optional_fields() {
+    let bytes = raw_envelope(&[
+        (0x01, b"test_key"),
+        (0x02, &[7; 12]),
+        (0x90, b"extension"),
+        (0x81, b""),
+    ]);
+
+    let envelope = Envelope::from_bytes(&bytes).unwrap();
+
+    assert_eq!(
+        envelope.extensions,
+        [(0x90, b"extension".to_vec()), (0x81, Vec::new())]
+    );
+    assert_eq!(envelope.to_bytes().unwrap(), bytes);
+}
+
+#[test]
+fn test_re_encoded_envelope_decrypts() {
+    let key = [3; 32];
+    let mut envelope = envelope();
+    envelope.extensions = vec![(0x90, b"extension".to_vec())];
+    let sealed = aead::seal(envelope, &key, b"plaintext").unwrap();
+
+    let re_encoded = Envelope::from_bytes(&sealed).unwrap().to_bytes().unwrap();
+
+    assert_eq!(re_encoded, sealed);
+    let parsed = EnvelopeRef::parse(&re_encoded).unwrap();
+    assert_eq!(&*aead::open(&parsed, &key).unwrap(), b"plaintext");
+}
+
+#[test_case(&[(0x01, b"test_key"), (0x02, &[7; 12]), (0x10, b"extension")], CoreError::UnknownField(0x10) ; "unknown critical field")]
+#[test_case(&[(0x01, b"test_key"), (0x01, b"test_key"), (0x02, &[7; 12])], CoreError::DuplicateField(0x01) ; "duplicate field")]
+#[test_case(&[(0x01, b"test_key"), (0x90, b"extension"), (0x02, &[7; 12])], CoreError::MisplacedField(0x02) ; "field after optional field")]
+#[test_case(&[(0x02, &[7; 12])], CoreError::MissingField("key_id") ; "missing key id")]
+#[test_case(&[(0x01, b"test_key")], CoreError::MissingField("nonce") ; "missing nonce")]
+#[test_case(&[(0x01, &[0xff, 0xfe]), (0x02, &[7; 12])], CoreError::InvalidField("key_id") ; "key id not utf8")]
+#[test_case(&[(0x01, b"test_key"), (0x02, &[7; 8])], CoreError::InvalidField("nonce") ; "nonce length")]
+#[test_case(&[(0x01, b"test_key"), (0x02, &[7; 12]), (0x05, &[1, 1])], CoreError::InvalidField("kdf") ; "kdf length")]
+#[test_case(&[(0x01, b"test_key"), (0x02, &[7; 12]), (0x05, &[9])], CoreError::UnknownAlgorithm(9) ; "unknown kdf")]
+fn test_rejects_invalid_fields(fields: &[(u8, &[u8])], error: CoreError) {
+    assert_eq!(EnvelopeRef::parse(&raw_envelope(fields)), Err(error));
+}
+
+#[test]
+fn test_rejects_invalid_preamble() {
+    let bytes = envelope().to_bytes().unwrap();
+
+    let mut invalid = bytes.clone();
+    invalid[0] = b'X';
+    assert_eq!(EnvelopeRef::parse(&invalid), Err(CoreError::InvalidMagic));
+
+    let mut invalid = bytes.clone();
+    invalid[4] = VERSION + 1;
+    assert_eq!(
+        EnvelopeRef::parse(&invalid),
+        Err(CoreError::UnsupportedVersion(VERSION + 1))
+    );
+
+    let mut invalid = bytes;
+    invalid[5] = 0xee;
+    assert_eq!(
+        EnvelopeRef::parse(&invalid),
+        Err(CoreError::UnknownAlgorithm(0xee))
+    );
+}
+
+#[test]
+fn test_rejects_truncated_input() {
+    let bytes = envelope().to_bytes().unwrap();
+
+    for len in 0..bytes.len() - 32 + 16 {
+        assert_eq!(
+            EnvelopeRef::parse(&bytes[..len]),
+            Err(CoreError::Truncated),
+            "length {len}"
+        );
+    }
+}
+
+#[test]
+fn test_encoding_validates_fields() {
+    let mut envelope = envelope();
+    envelope.ciphertext.truncate(15);
+    assert_eq!(
+        envelope.to_bytes(),
+        Err(CoreError::InvalidField("ciphertext"))
+    );
+
+    let mut envelope = self::envelope();
+    envelope.nonce.pop();
+    assert_eq!(envelope.header(), Err(CoreError::InvalidField("nonce")));
+
+    let mut envelope = self::envelope();
+    envelope.salt = Some(vec![0; u16::MAX as usize + 1]);
+    assert_eq!(envelope.header(), Err(CoreError::FieldTooLong));
+
+    let mut envelope = self::envelope();
+    envelope.extensions = vec![(0x10, b"extension".to_vec())];
+    assert_eq!(
+        envelope.header(),
+        Err(CoreError::InvalidField("extensions"))
+    );
+}
+
+#[test_case(AeadAlgorithm::Aes128Gcm, 16)]
+#[test_case(AeadAlgorithm::Aes256Gcm, 32)]
+#[test_case(AeadAlgorithm::ChaCha20Poly1305, 32)]
+fn test_aead_parameters(aead: AeadAlgorithm, key_len: usize) {
+    assert_eq!(aead.key_len(), key_len);
+    assert_eq!(AeadAlgorithm::from_id(aead.id()), Ok(aead));
+}
+
+fn aead_algorithm() -> impl Strategy<Value = AeadAlgorithm> {
+    prop_oneof![
+        Just(AeadAlgorithm::Aes128Gcm),
+        Just(AeadAlgorithm::Aes256Gcm),
+        Just(AeadAlgorithm::ChaCha20Poly1305),
+    ]
+}
+
+fn kdf() -> impl Strategy<Value = Kdf> {
+    prop_oneof![
+        Just(Kdf::HkdfSha256),
+        Just(Kdf::HkdfSha384),
+        Just(Kdf::HkdfSha512),
+    ]
+}
+
+prop_compose! {
+    fn arbitrary_envelope()(aead in aead_algorithm())(
+        nonce in vec(any::<u8>(), aead.nonce_len()),
+        key_id in "\\PC{0,64}",
+        wrapped_key in option::of(vec(any::<u8>(), 0..512)),
+        ephemeral_public_key in option::of(vec(any::<u8>(), 0..133)),
+        kdf in option::of(kdf()),
+        salt in option::of(vec(any::<u8>(), 0..64)),
+        extensions in vec((0x80..=0xffu8, vec(any::<u8>(), 0..32)), 0..3),
+        ciphertext in vec(any::<u8>(), aead.tag_len()..1024),
+        aead in Just(aead),
+    ) -> Envelope {
+        let mut envelope = Envelope::new(aead, key_id, nonce);
+        envelope.wrapped_key = wrapped_key;
+        envelope.ephemeral_public_key = ephemeral_public_key;
+        envelope.kdf = kdf;
+        envelope.salt = salt;
+        envelope.extensions = extensions;
+        envelope.ciphertext = ciphertext;
+        envelope
+    }
+}
+
+proptest! {
+    #[test]
+    fn prop_encode_decode_is_identity(envelope in arbitrary_envelope()) {
+        let bytes = envelope.to_bytes().unwrap();
+        let parsed = EnvelopeRef::parse(&bytes).unwrap();
+
+        let header = envelope.header().unwrap();
+        prop_assert_eq!(parsed.header, header.as_slice());
+        prop_assert_eq!(parsed.ciphertext, envelope.ciphertext.as_slice());
+        prop_assert_eq!(parsed.to_envelope(), envelope);
+    }
+
+    #[test]
+    fn prop_truncated_envelope_is_rejected(envelope in arbitrary_envelope(), cut in any::<prop::sample::Index>()) {
+        let header_len = envelope.header().unwrap().len();
+        let bytes = envelope.to_bytes().unwrap();
+        let truncated = &bytes[..cut.index(header_len)];
+        prop_assert!(EnvelopeRef::parse(truncated).is_err());
+    }
+}
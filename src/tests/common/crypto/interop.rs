@@ -0,0 +1,209 @@
+//! Cross-verification of crate-produced artifacts with independent implementations.
+//!
+//! ECDSA signatures are checked against the RustCrypto `p256` crate and envelopes against the
+//! RustCrypto `aes-gcm` crate, in both directions.
+
+use crate::common::crypto::{
+    aead,
+    algorithms::{
+        encryption::{AsymmetricEncryption, EccCurves, EccSchemeAlgorithm},
+        hashes::{Hash, Sha2Bits},
+    },
+    envelope::{AeadAlgorithm, Envelope, EnvelopeRef},
+    public_key::PublicKey,
+    signature_format::{self, SignatureFormat},
+};
+use aes_gcm::{
+    aead::{Aead, KeyInit, Payload},
+    Aes128Gcm, Aes256Gcm, Nonce,
+};
+use p256::{
+    ecdsa::{signature::Signer, signature::Verifier, Signature, SigningKey, VerifyingKey},
+    pkcs8::DecodePublicKey,
+};
+use test_case::test_case;
+
+fn hex(data: &str) -> Vec<u8> {
+    (0..data.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&data[i..i + 2], 16).unwrap())
+        .collect()
+}
+
+const P256_SHA256: (AsymmetricEncryption, Hash) = (
+    AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(EccCurves::P256)),
+    Hash::Sha2(Sha2Bits::Sha256),
+);
+
+/// The P-256 key pair of RFC 6979, appendix A.2.5.
+fn rfc6979_signing_key() -> SigningKey {
+    SigningKey::from_slice(&hex(
+        "c9afa9d845ba75166b5c215767b1d6934e50c3db36e89b127b8a622b120f6721",
+    ))
+    .unwrap()
+}
+
+fn rfc6979_public_key() -> PublicKey {
+    PublicKey::from_ec_coordinates(
+        &hex("60fed4ba255a9d31c961eb74c6356d68c049b8923b61fa6ce669622e60f29fb6"),
+        &hex("7903fe1008b8bc99a41ae9e95628bc64f2f1b20c2d7e9f5177a3c294d4462299"),
+        P256_SHA256.0,
+        P256_SHA256.1,
+    )
+    .unwrap()
+}
+
+/// The deterministic signature over "sample" with SHA-256 of RFC 6979, appendix A.2.5.
+fn rfc6979_sample_signature() -> Vec<u8> {
+    hex(concat!(
+        "efd48b2aacb6a8fd1140dd9cd45e81d69d2c877b56aaf991c34d0ea84eaf3716",
+        "f7cb1c942d657c41d436c7a1b6e29f65f3e900dbb9aff4064dc4ab2f843acda8",
+    ))
+}
+
+#[test]
+fn test_rfc6979_known_answer() {
+    let public_key = rfc6979_public_key();
+    let raw = rfc6979_sample_signature();
+
+    assert!(public_key
+        .verify_with_format(b"sample", &raw, SignatureFormat::Raw)
+        .unwrap());
+    assert!(public_key
+        .verify(b"sample", &signature_format::raw_to_der(&raw).unwrap())
+        .unwrap());
+    assert!(!public_key
+        .verify_with_format(b"samples", &raw, SignatureFormat::Raw)
+        .unwrap());
+
+    let signature: Signature = rfc6979_signing_key().sign(b"sample");
+    assert_eq!(signature.to_bytes().as_slice(), raw.as_slice());
+}
+
+#[test]
+fn test_p256_signature_verified_by_crate() {
+    let public_key = rfc6979_public_key();
+    let signature: Signature = rfc6979_signing_key().sign(b"signed by RustCrypto");
+
+    assert!(public_key
+        .verify(b"signed by RustCrypto", signature.to_der().as_bytes())
+        .unwrap());
+    assert!(public_key
+        .verify_with_format(
+            b"signed by RustCrypto",
+            &signature.to_bytes(),
+            SignatureFormat::Raw
+        )
+        .unwrap());
+}
+
+#[test]
+fn test_crate_public_key_and_signature_format_accepted_by_p256() {
+    let der = rfc6979_public_key().to_der().unwrap();
+    let verifying_key = VerifyingKey::from_public_key_der(&der).unwrap();
+    assert_eq!(&verifying_key, rfc6979_signing_key().verifying_key());
+
+    let raw = rfc6979_sample_signature();
+    let der_signature =
+        signature_format::convert(&raw, SignatureFormat::Raw, SignatureFormat::Der, 32).unwrap();
+    let signature = Signature::from_der(&der_signature).unwrap();
+    assert!(verifying_key.verify(b"sample", &signature).is_ok());
+}
+
+#[cfg(feature = "test-utils")]
+#[test]
+fn test_provider_signature_verified_by_p256() {
+    use crate::{
+        common::traits::{key_handle::KeyHandle, module_provider::Provider},
+        mock::{MockConfig, MockProvider},
+    };
+
+    let provider = MockProvider::with_key("interop", MockConfig::new(P256_SHA256.0, P256_SHA256.1));
+    let signature = provider.sign_data(b"signed by the crate").unwrap();
+
+    let metadata = provider.key_metadata().unwrap();
+    let verifying_key = VerifyingKey::from_public_key_der(metadata.public_key_der()).unwrap();
+    let signature = Signature::from_der(&signature).unwrap();
+    assert!(verifying_key
+        .verify(b"signed by the crate", &signature)
+        .is_ok());
+    assert!(verifying_key
+        .verify(b"signed by someone", &signature)
+        .is_err());
+}
+
+fn rustcrypto_encrypt(
+    aead: AeadAlgorithm,
+    key: &[u8],
+    nonce: &[u8],
+    aad: &[u8],
+    msg: &[u8],
+) -> Vec<u8> {
+    let nonce = Nonce::from_slice(nonce);
+    let payload = Payload { msg, aad };
+    match aead {
+        AeadAlgorithm::Aes128Gcm => Aes128Gcm::new_from_slice(key)
+            .unwrap()
+            .encrypt(nonce, payload),
+        AeadAlgorithm::Aes256Gcm => Aes256Gcm::new_from_slice(key)
+            .unwrap()
+            .encrypt(nonce, payload),
+        AeadAlgorithm::ChaCha20Poly1305 => unreachable!(),
+    }
+    .unwrap()
+}
+
+fn rustcrypto_decrypt(
+    aead: AeadAlgorithm,
+    key: &[u8],
+    nonce: &[u8],
+    aad: &[u8],
+    msg: &[u8],
+) -> Result<Vec<u8>, aes_gcm::Error> {
+    let nonce = Nonce::from_slice(nonce);
+    let payload = Payload { msg, aad };
+    match aead {
+        AeadAlgorithm::Aes128Gcm => Aes128Gcm::new_from_slice(key)
+            .unwrap()
+            .decrypt(nonce, payload),
+        AeadAlgorithm::Aes256Gcm => Aes256Gcm::new_from_slice(key)
+            .unwrap()
+            .decrypt(nonce, payload),
+        AeadAlgorithm::ChaCha20Poly1305 => unreachable!(),
+    }
+}
+
+#[test_case(AeadAlgorithm::Aes128Gcm ; "aes128gcm")]
+#[test_case(AeadAlgorithm::Aes256Gcm ; "aes256gcm")]
+fn test_crate_envelope_decrypted_by_rustcrypto(aead: AeadAlgorithm) {
+    let key: Vec<u8> = (0..aead.key_len() as u8).rev().collect();
+    let envelope = Envelope::new(aead, "interop", aead::random_nonce(aead).unwrap());
+    let sealed = aead::seal(envelope, &key, b"sealed by the crate").unwrap();
+
+    let parsed = EnvelopeRef::parse(&sealed).unwrap();
+    let plaintext =
+        rustcrypto_decrypt(aead, &key, parsed.nonce, parsed.header, parsed.ciphertext).unwrap();
+    assert_eq!(plaintext, b"sealed by the crate");
+
+    // The header is authenticated, decrypting without it as associated data fails.
+    assert!(rustcrypto_decrypt(aead, &key, parsed.nonce, &[], parsed.ciphertext).is_err());
+}
+
+#[test_case(AeadAlgorithm::Aes128Gcm ; "aes128gcm")]
+#[test_case(AeadAlgorithm::Aes256Gcm ; "aes256gcm")]
+fn test_rustcrypto_envelope_opened_by_crate(aead: AeadAlgorithm) {
+    let key: Vec<u8> = (0..aead.key_len() as u8).collect();
+    let mut envelope = Envelope::new(aead, "interop", vec![0x42; aead.nonce_len()]);
+    let header = envelope.header().unwrap();
+    envelope.ciphertext = rustcrypto_encrypt(
+        aead,
+        &key,
+        &envelope.nonce,
+        &header,
+        b"sealed by RustCrypto",
+    );
+    let encoded = envelope.to_bytes().unwrap();
+
+    let parsed = EnvelopeRef::parse(&encoded).unwrap();
+    assert_eq!(aead::open(&parsed, &key).unwrap(), b"sealed by RustCrypto");
+}
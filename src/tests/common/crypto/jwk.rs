@@ -0,0 +1,176 @@
+use crate::{
+    common::{
+        crypto::{
+            algorithms::{
+                encryption::{AsymmetricEncryption, EccCurves, EccSchemeAlgorithm},
+                hashes::{Hash, Sha2Bits},
+                KeyBits,
+            },
+            jwk::{self, JwkSet},
+            key_metadata::KeyMetadata,
+            public_key::{PublicKey, RsaSignaturePadding},
+        },
+        traits::{key_handle::KeyHandle, module_provider::Provider},
+    },
+    mock::{MockConfig, MockProvider},
+    SecurityModuleError,
+};
+use crypto_layer_core::CoreError;
+use openssl::{pkey::PKey, sign::Signer};
+use serde_json::json;
+use std::any::Any;
+
+/// Returns a provider holding a new key `key_id` with `config` and the metadata of it.
+fn provider(key_id: &str, config: Box<dyn Any>) -> (MockProvider, KeyMetadata) {
+    let provider = MockProvider::with_key(key_id, config);
+    let metadata = provider.key_metadata().unwrap();
+    (provider, metadata)
+}
+
+fn rsa_config() -> Box<dyn Any> {
+    MockConfig::new(
+        AsymmetricEncryption::Rsa(KeyBits::Bits2048),
+        Hash::Sha2(Sha2Bits::Sha256),
+    )
+}
+
+#[test]
+fn test_ec_round_trip() {
+    let (provider, metadata) = provider("signer", Box::new(MockConfig::default()));
+    let public_key = metadata.public_key();
+
+    let exported = jwk::to_jwk(public_key, Some("signer")).unwrap();
+    assert_eq!(exported["kty"], "EC");
+    assert_eq!(exported["crv"], "P-256");
+    assert_eq!(exported["alg"], "ES256");
+    assert_eq!(exported["kid"], "signer");
+
+    let imported = jwk::from_jwk(&exported).unwrap();
+    assert_eq!(imported.to_der().unwrap(), public_key.to_der().unwrap());
+    let signature = provider.sign_data(b"data").unwrap();
+    assert!(imported.verify(b"data", &signature).unwrap());
+
+    // Keys whose hash does not fit the curve have no `alg`.
+    let der = public_key.to_der().unwrap();
+    let sha512 = PublicKey::from_der(
+        &der,
+        AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(EccCurves::P256)),
+        Hash::Sha2(Sha2Bits::Sha512),
+    )
+    .unwrap();
+    assert!(jwk::to_jwk(&sha512, None).unwrap().get("alg").is_none());
+}
+
+#[test]
+fn test_rsa_and_ed25519_round_trips() {
+    let (_, metadata) = provider("signer", rsa_config());
+    let pss = metadata
+        .public_key()
+        .clone()
+        .with_rsa_padding(RsaSignaturePadding::Pss);
+    let exported = jwk::to_jwk(&pss, None).unwrap();
+    assert_eq!(exported["alg"], "PS256");
+    let imported = jwk::from_jwk(&exported).unwrap();
+    assert_eq!(imported.rsa_padding(), RsaSignaturePadding::Pss);
+    assert!(matches!(
+        imported.algorithm(),
+        AsymmetricEncryption::Rsa(KeyBits::Bits2048)
+    ));
+
+    let key = PKey::generate_ed25519().unwrap();
+    let public_key = PublicKey::from_der(
+        &key.public_key_to_der().unwrap(),
+        AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(EccCurves::Curve25519)),
+        Hash::Sha2(Sha2Bits::Sha512),
+    )
+    .unwrap();
+    let exported = jwk::to_jwk(&public_key, None).unwrap();
+    assert_eq!(exported["kty"], "OKP");
+    assert_eq!(exported["alg"], "EdDSA");
+    let signature = Signer::new_without_digest(&key)
+        .unwrap()
+        .sign_oneshot_to_vec(b"data")
+        .unwrap();
+    assert!(jwk::from_jwk(&exported)
+        .unwrap()
+        .verify(b"data", &signature)
+        .unwrap());
+}
+
+#[test]
+fn test_jwk_set() {
+    let (signer, ec_metadata) = provider("ec", Box::new(MockConfig::default()));
+    let (_, rsa_metadata) = provider("rsa", rsa_config());
+    let jwks = JwkSet::from_metadata([&ec_metadata, &rsa_metadata]).unwrap();
+    assert_eq!(jwks.len(), 2);
+
+    let mut json = jwks.to_json().unwrap();
+    assert_eq!(json["keys"][0]["kid"], "ec");
+    assert_eq!(json["keys"][1]["kid"], "rsa");
+    assert_eq!(json["keys"][1]["alg"], "RS256");
+
+    // Unsupported and malformed keys of other parties are ignored.
+    let keys = json["keys"].as_array_mut().unwrap();
+    keys.push(json!({ "kty": "oct", "k": "c2VjcmV0", "kid": "symmetric" }));
+    keys.push(json!({ "kty": "EC", "crv": "P-256", "kid": "broken" }));
+    let parsed = JwkSet::from_json(&json.to_string()).unwrap();
+    assert_eq!(
+        parsed.iter().map(|(kid, _)| kid).collect::<Vec<_>>(),
+        [Some("ec"), Some("rsa")]
+    );
+    assert!(parsed.get("symmetric").is_none());
+    let signature = signer.sign_data(b"data").unwrap();
+    let key = parsed.verifying_key("ec").unwrap();
+    assert!(key.verify_signature(b"data", &signature).unwrap());
+    assert!(!parsed
+        .verifying_key("rsa")
+        .unwrap()
+        .verify_signature(b"data", &signature)
+        .unwrap());
+
+    // Inserting a key under an existing id replaces it.
+    let mut jwks = parsed;
+    jwks.insert("rsa", ec_metadata.public_key().clone());
+    assert_eq!(jwks.len(), 2);
+    assert_eq!(
+        jwks.get("rsa").unwrap().to_der().unwrap(),
+        ec_metadata.public_key().to_der().unwrap()
+    );
+    assert!(JwkSet::new().is_empty());
+}
+
+#[test]
+fn test_rejected_jwk_sets() {
+    assert!(matches!(
+        JwkSet::from_json("[]"),
+        Err(SecurityModuleError::Encoding(CoreError::MissingField(
+            "keys"
+        )))
+    ));
+    assert!(matches!(
+        JwkSet::from_json(r#"{"keys":{}}"#),
+        Err(SecurityModuleError::Encoding(CoreError::InvalidField(
+            "keys"
+        )))
+    ));
+    assert!(matches!(
+        JwkSet::from_json("{"),
+        Err(SecurityModuleError::Encoding(CoreError::InvalidField(
+            "jwks"
+        )))
+    ));
+    let unsupported = PublicKey::from_der(
+        &provider("ec", Box::new(MockConfig::default()))
+            .1
+            .public_key()
+            .to_der()
+            .unwrap(),
+        AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDh(EccCurves::P256)),
+        Hash::Sha2(Sha2Bits::Sha256),
+    )
+    .unwrap();
+    assert!(matches!(
+        jwk::to_jwk(&unsupported, None),
+        Err(SecurityModuleError::UnsupportedAlgorithm)
+    ));
+}
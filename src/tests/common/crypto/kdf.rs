@@ -0,0 +1,66 @@
+use crate::common::crypto::kdf::Kdf;
+use crypto_layer_core::CoreError;
+use test_case::test_case;
+
+fn hex(data: &str) -> Vec<u8> {
+    (0..data.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&data[i..i + 2], 16).unwrap())
+        .collect()
+}
+
+/// Test case 1 of RFC 5869.
+#[test]
+fn test_hkdf_sha256_rfc5869() {
+    let ikm = [0x0b; 22];
+    let salt = hex("000102030405060708090a0b0c");
+    let info = hex("f0f1f2f3f4f5f6f7f8f9");
+
+    let okm = Kdf::HkdfSha256
+        .derive_vec(&ikm, Some(&salt), &info, 42)
+        .unwrap();
+
+    assert_eq!(
+        okm,
+        hex("3cb25f25faacd57a90434f64d0362f2a2d2d0a90cf1a5a4c5db02d56ecc4c5bf34007208d5b887185865")
+    );
+}
+
+/// Test case 3 of RFC 5869, without salt and info.
+#[test]
+fn test_hkdf_sha256_without_salt() {
+    let ikm = [0x0b; 22];
+
+    let okm = Kdf::HkdfSha256.derive_vec(&ikm, None, &[], 42).unwrap();
+
+    assert_eq!(
+        okm,
+        hex("8da4e775a563c18f715f802a063c5a31b8a11f5c5ee1879ec3454e5f3c738d2d9d201395faa4b61a96c8")
+    );
+}
+
+#[test_case(Kdf::HkdfSha256)]
+#[test_case(Kdf::HkdfSha384)]
+#[test_case(Kdf::HkdfSha512)]
+fn test_output_length_limit(kdf: Kdf) {
+    let mut output = vec![0u8; kdf.max_output_len()];
+    assert!(kdf.derive(b"secret", None, b"info", &mut output).is_ok());
+
+    let mut output = vec![0u8; kdf.max_output_len() + 1];
+    assert_eq!(
+        kdf.derive(b"secret", None, b"info", &mut output),
+        Err(CoreError::InvalidLength)
+    );
+}
+
+#[test_case(Kdf::HkdfSha256)]
+#[test_case(Kdf::HkdfSha384)]
+#[test_case(Kdf::HkdfSha512)]
+fn test_id_round_trip(kdf: Kdf) {
+    assert_eq!(Kdf::from_id(kdf.id()), Ok(kdf));
+}
+
+#[test]
+fn test_unknown_id() {
+    assert_eq!(Kdf::from_id(0), Err(CoreError::UnknownAlgorithm(0)));
+}
@@ -0,0 +1,141 @@
+use crate::common::{
+    crypto::{
+        algorithms::{
+            encryption::{AsymmetricEncryption, EccCurves, EccSchemeAlgorithm},
+            hashes::{Hash, Sha2Bits},
+            KeyBits,
+        },
+        key_metadata::KeyMetadata,
+        public_key::PublicKey,
+    },
+    error::SecurityModuleError,
+    traits::{key_handle::KeyHandle, module_provider::Provider},
+};
+use openssl::{
+    bn::{BigNum, BigNumContext},
+    ec::{EcGroup, EcKey},
+    nid::Nid,
+    pkey::PKey,
+    rsa::Rsa,
+};
+use std::{any::Any, time::SystemTime};
+
+const P256: AsymmetricEncryption =
+    AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(EccCurves::P256));
+const SHA256: Hash = Hash::Sha2(Sha2Bits::Sha256);
+
+#[derive(Debug)]
+struct NoMetadataProvider;
+
+impl KeyHandle for NoMetadataProvider {}
+
+impl Provider for NoMetadataProvider {
+    fn create_key(
+        &mut self,
+        _key_id: &str,
+        _config: Box<dyn Any>,
+    ) -> Result<(), SecurityModuleError> {
+        Ok(())
+    }
+
+    fn load_key(
+        &mut self,
+        _key_id: &str,
+        _config: Box<dyn Any>,
+    ) -> Result<(), SecurityModuleError> {
+        Ok(())
+    }
+
+    fn initialize_module(&mut self) -> Result<(), SecurityModuleError> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_metadata_caches_der() {
+    let ec_key =
+        EcKey::generate(&EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap()).unwrap();
+    let expected_der = ec_key.public_key_to_der().unwrap();
+    let public_key = PublicKey::from_der(&expected_der, P256, SHA256).unwrap();
+    let before = SystemTime::now();
+
+    let metadata = KeyMetadata::new("test_key", public_key).unwrap();
+
+    assert_eq!(metadata.key_id(), "test_key");
+    assert_eq!(metadata.public_key_der(), expected_der.as_slice());
+    assert_eq!(metadata.public_key().to_der().unwrap(), expected_der);
+    assert!(metadata.attestation().is_none());
+    assert!(metadata.fetched_at() >= before);
+}
+
+#[test]
+fn test_metadata_with_attestation() {
+    let ec_key =
+        EcKey::generate(&EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap()).unwrap();
+    let public_key =
+        PublicKey::from_der(&ec_key.public_key_to_der().unwrap(), P256, SHA256).unwrap();
+    let chain = vec![vec![0x30, 0x01], vec![0x30, 0x02]];
+
+    let metadata = KeyMetadata::new("test_key", public_key)
+        .unwrap()
+        .with_attestation(chain.clone());
+
+    assert_eq!(metadata.attestation(), Some(chain.as_slice()));
+}
+
+#[test]
+fn test_public_key_from_ec_coordinates() {
+    let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+    let ec_key = EcKey::generate(&group).unwrap();
+    let mut ctx = BigNumContext::new().unwrap();
+    let mut x = BigNum::new().unwrap();
+    let mut y = BigNum::new().unwrap();
+    ec_key
+        .public_key()
+        .affine_coordinates(&group, &mut x, &mut y, &mut ctx)
+        .unwrap();
+
+    let public_key =
+        PublicKey::from_ec_coordinates(&x.to_vec(), &y.to_vec(), P256, SHA256).unwrap();
+
+    assert_eq!(
+        public_key.to_der().unwrap(),
+        ec_key.public_key_to_der().unwrap()
+    );
+    assert!(matches!(
+        PublicKey::from_ec_coordinates(&x.to_vec(), &x.to_vec(), P256, SHA256),
+        Err(SecurityModuleError::InvalidPublicKey)
+    ));
+}
+
+#[test]
+fn test_public_key_from_rsa_components() {
+    let rsa = Rsa::generate(2048).unwrap();
+
+    let public_key = PublicKey::from_rsa_components(
+        &rsa.n().to_vec(),
+        &[0, 1, 0, 1],
+        AsymmetricEncryption::Rsa(KeyBits::Bits2048),
+        SHA256,
+    )
+    .unwrap();
+
+    assert_eq!(
+        public_key.to_der().unwrap(),
+        PKey::from_rsa(rsa).unwrap().public_key_to_der().unwrap()
+    );
+}
+
+#[test]
+fn test_providers_without_metadata() {
+    let mut provider = NoMetadataProvider;
+
+    assert!(matches!(
+        provider.key_metadata(),
+        Err(SecurityModuleError::InitializationError(_))
+    ));
+    assert!(matches!(
+        provider.refresh_key_metadata(),
+        Err(SecurityModuleError::InitializationError(_))
+    ));
+}
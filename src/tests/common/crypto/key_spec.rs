@@ -0,0 +1,248 @@
+use crate::{
+    common::crypto::{
+        algorithms::{
+            encryption::{AsymmetricEncryption, BlockCiphers, EccCurves, EccSchemeAlgorithm},
+            hashes::{Hash, Sha2Bits},
+        },
+        key_spec::{
+            AccessControl, KeyAlgorithm, KeyPurpose, KeySpec, KeySpecBuilder, MAX_LABEL_LEN,
+        },
+        KeyUsage,
+    },
+    SecurityModuleError,
+};
+
+fn builder(algorithm: KeyAlgorithm, purpose: KeyPurpose) -> KeySpecBuilder {
+    KeySpec::builder()
+        .algorithm(algorithm)
+        .usage(purpose)
+        .label("device-identity")
+}
+
+fn rejection(builder: KeySpecBuilder) -> String {
+    match builder.build() {
+        Err(SecurityModuleError::InvalidKeySpec(message)) => message,
+        result => panic!("Expected InvalidKeySpec, got {:?}", result),
+    }
+}
+
+#[test]
+fn test_build_signing_key() {
+    let spec = builder(KeyAlgorithm::EcP256, KeyPurpose::Sign)
+        .access(AccessControl::BiometryAny)
+        .build()
+        .unwrap();
+
+    assert_eq!(spec.label(), "device-identity");
+    assert_eq!(spec.access(), AccessControl::BiometryAny);
+    assert!(spec.allows(KeyPurpose::Sign));
+    assert!(!spec.allows(KeyPurpose::Encrypt));
+    assert!(matches!(spec.hash(), Hash::Sha2(Sha2Bits::Sha256)));
+    assert!(matches!(
+        spec.asymmetric_algorithm(),
+        Some(AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(
+            EccCurves::P256
+        )))
+    ));
+    assert!(spec.symmetric_algorithm().is_none());
+    assert_eq!(spec.key_usages(), [KeyUsage::SignEncrypt]);
+    assert!(!spec.exportable());
+}
+
+#[test]
+fn test_hash_defaults_to_security_level_of_key() {
+    let spec = builder(KeyAlgorithm::EcP384, KeyPurpose::Sign)
+        .build()
+        .unwrap();
+    assert!(matches!(spec.hash(), Hash::Sha2(Sha2Bits::Sha384)));
+
+    let spec = builder(KeyAlgorithm::EcP384, KeyPurpose::Sign)
+        .hash(Hash::Sha2(Sha2Bits::Sha512))
+        .build()
+        .unwrap();
+    assert!(matches!(spec.hash(), Hash::Sha2(Sha2Bits::Sha512)));
+}
+
+#[test]
+fn test_key_agreement_maps_to_ecdh() {
+    let spec = builder(KeyAlgorithm::EcP256, KeyPurpose::KeyAgreement)
+        .build()
+        .unwrap();
+
+    assert!(matches!(
+        spec.asymmetric_algorithm(),
+        Some(AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDh(
+            EccCurves::P256
+        )))
+    ));
+    assert_eq!(spec.key_usages(), [KeyUsage::Decrypt]);
+}
+
+#[test]
+fn test_rsa_key_for_signing_and_encryption() {
+    let spec = builder(KeyAlgorithm::Rsa3072, KeyPurpose::Encrypt)
+        .usage(KeyPurpose::Sign)
+        .build()
+        .unwrap();
+
+    assert_eq!(
+        spec.purposes().collect::<Vec<_>>(),
+        [KeyPurpose::Sign, KeyPurpose::Encrypt]
+    );
+    assert_eq!(
+        spec.key_usages(),
+        [KeyUsage::SignEncrypt, KeyUsage::Decrypt]
+    );
+}
+
+#[test]
+fn test_aes_key() {
+    let spec = builder(KeyAlgorithm::Aes256Gcm, KeyPurpose::Encrypt)
+        .build()
+        .unwrap();
+
+    assert!(spec.algorithm().is_symmetric());
+    assert!(spec.asymmetric_algorithm().is_none());
+    assert!(matches!(
+        spec.symmetric_algorithm(),
+        Some(BlockCiphers::Aes(_, _))
+    ));
+}
+
+#[test]
+fn test_rejects_missing_fields() {
+    assert_eq!(
+        rejection(KeySpec::builder().usage(KeyPurpose::Sign).label("key")),
+        "No algorithm given"
+    );
+    assert_eq!(
+        rejection(
+            KeySpec::builder()
+                .algorithm(KeyAlgorithm::EcP256)
+                .label("key")
+        ),
+        "No usage given"
+    );
+    assert_eq!(
+        rejection(
+            KeySpec::builder()
+                .algorithm(KeyAlgorithm::EcP256)
+                .usage(KeyPurpose::Sign)
+        ),
+        "No label given"
+    );
+    assert_eq!(
+        rejection(builder(KeyAlgorithm::EcP256, KeyPurpose::Sign).label("")),
+        "No label given"
+    );
+}
+
+#[test]
+fn test_rejects_invalid_labels() {
+    let long_label = "a".repeat(MAX_LABEL_LEN + 1);
+    assert!(
+        rejection(builder(KeyAlgorithm::EcP256, KeyPurpose::Sign).label(long_label))
+            .starts_with("Label is longer than")
+    );
+    assert!(builder(KeyAlgorithm::EcP256, KeyPurpose::Sign)
+        .label("a".repeat(MAX_LABEL_LEN))
+        .build()
+        .is_ok());
+    assert_eq!(
+        rejection(builder(KeyAlgorithm::EcP256, KeyPurpose::Sign).label("key\n")),
+        "Label contains control characters"
+    );
+}
+
+#[test]
+fn test_rejects_usages_the_algorithm_does_not_support() {
+    assert_eq!(
+        rejection(builder(KeyAlgorithm::Aes128Gcm, KeyPurpose::Sign)),
+        "Aes128Gcm keys cannot be used for Sign"
+    );
+    assert_eq!(
+        rejection(builder(KeyAlgorithm::Aes256Gcm, KeyPurpose::KeyAgreement)),
+        "Aes256Gcm keys cannot be used for KeyAgreement"
+    );
+    assert_eq!(
+        rejection(builder(KeyAlgorithm::Rsa2048, KeyPurpose::KeyAgreement)),
+        "Rsa2048 keys cannot be used for KeyAgreement"
+    );
+    assert_eq!(
+        rejection(builder(KeyAlgorithm::EcP256, KeyPurpose::Encrypt)),
+        "EcP256 keys cannot be used for Encrypt"
+    );
+    assert_eq!(
+        rejection(builder(KeyAlgorithm::EcP256, KeyPurpose::Sign).usage(KeyPurpose::KeyAgreement)),
+        "EcP256 keys cannot be used for both Sign and KeyAgreement"
+    );
+}
+
+#[test]
+fn test_rejects_weak_signing_hash() {
+    assert_eq!(
+        rejection(builder(KeyAlgorithm::Rsa2048, KeyPurpose::Sign).hash(Hash::Sha1)),
+        "Sha1 is too weak for signing"
+    );
+    assert!(builder(KeyAlgorithm::Rsa2048, KeyPurpose::Encrypt)
+        .hash(Hash::Sha1)
+        .build()
+        .is_ok());
+}
+
+#[cfg(feature = "tpm")]
+#[test]
+fn test_tpm_config_from_spec() {
+    use crate::tpm::TpmConfig;
+
+    let spec = builder(KeyAlgorithm::Rsa2048, KeyPurpose::Sign)
+        .build()
+        .unwrap();
+    let config = TpmConfig::from_spec(&spec).unwrap();
+    let config = config.downcast_ref::<TpmConfig>().unwrap();
+    assert!(matches!(config.key_algorithm, AsymmetricEncryption::Rsa(_)));
+    assert_eq!(config.key_usages, [KeyUsage::SignEncrypt]);
+
+    let spec = builder(KeyAlgorithm::EcP256, KeyPurpose::Sign)
+        .access(AccessControl::BiometryCurrentSet)
+        .build()
+        .unwrap();
+    assert!(matches!(
+        TpmConfig::from_spec(&spec),
+        Err(SecurityModuleError::InvalidKeySpec(_))
+    ));
+
+    let spec = builder(KeyAlgorithm::EcP256, KeyPurpose::Sign)
+        .exportable(true)
+        .build()
+        .unwrap();
+    assert!(matches!(
+        TpmConfig::from_spec(&spec),
+        Err(SecurityModuleError::InvalidKeySpec(_))
+    ));
+}
+
+#[cfg(feature = "test-utils")]
+#[test]
+fn test_mock_config_from_spec() {
+    use crate::{
+        common::traits::key_handle::KeyHandle,
+        mock::{MockConfig, MockProvider},
+    };
+
+    let spec = builder(KeyAlgorithm::EcP256, KeyPurpose::Sign)
+        .access(AccessControl::BiometryAny)
+        .build()
+        .unwrap();
+    let provider = MockProvider::with_key(spec.label(), MockConfig::from_spec(&spec).unwrap());
+    let signature = provider.sign_data(b"data").unwrap();
+    assert!(provider.verify_signature(b"data", &signature).unwrap());
+
+    let spec = builder(KeyAlgorithm::Aes128Gcm, KeyPurpose::Encrypt)
+        .build()
+        .unwrap();
+    assert!(matches!(
+        MockConfig::from_spec(&spec),
+        Err(SecurityModuleError::UnsupportedAlgorithm)
+    ));
+}
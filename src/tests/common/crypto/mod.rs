@@ -0,0 +1,14 @@
+pub mod aead;
+pub mod envelope;
+pub mod interop;
+pub mod jwk;
+pub mod kdf;
+pub mod key_metadata;
+pub mod key_spec;
+pub mod operation_context;
+pub mod redact;
+pub mod secret;
+pub mod signature_format;
+pub mod signing_context;
+pub mod spki;
+pub mod verifying_key;
@@ -0,0 +1,158 @@
+use crate::{
+    common::{
+        crypto::{
+            algorithms::{
+                encryption::{AsymmetricEncryption, EccCurves, EccSchemeAlgorithm},
+                hashes::{Hash, Sha2Bits},
+            },
+            operation_context::OperationContext,
+            public_key::PublicKey,
+        },
+        error::SecurityModuleError,
+        traits::key_handle::KeyHandle,
+    },
+    tests::allocations::count_allocations,
+};
+use base64::{prelude::BASE64_STANDARD, Engine};
+use openssl::{
+    bn::BigNumContext,
+    ec::{EcGroup, EcKey, PointConversionForm},
+    hash::MessageDigest,
+    nid::Nid,
+    pkey::PKey,
+    sign::Signer,
+};
+
+const ITERATIONS: usize = 1_000;
+
+/// A key handle returning a fixed signature, to exercise the default `sign_data_into`.
+#[derive(Debug)]
+struct FixedSignature(Vec<u8>);
+
+impl KeyHandle for FixedSignature {
+    fn sign_data(&self, _data: &[u8]) -> Result<Vec<u8>, SecurityModuleError> {
+        Ok(self.0.clone())
+    }
+}
+
+/// Creates a P-256 public key together with a base64 encoded signature over `data`.
+fn signed_p256(data: &[u8]) -> (PublicKey, Vec<u8>) {
+    let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+    let ec_key = EcKey::generate(&group).unwrap();
+    let mut ctx = BigNumContext::new().unwrap();
+    let point = ec_key
+        .public_key()
+        .to_bytes(&group, PointConversionForm::UNCOMPRESSED, &mut ctx)
+        .unwrap();
+
+    let private_key = PKey::from_ec_key(ec_key).unwrap();
+    let mut signer = Signer::new(MessageDigest::sha256(), &private_key).unwrap();
+    signer.update(data).unwrap();
+    let signature = signer.sign_to_vec().unwrap();
+
+    let public_key = PublicKey::from_ec_point(
+        &point,
+        AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(EccCurves::P256)),
+        Hash::Sha2(Sha2Bits::Sha256),
+    )
+    .unwrap();
+    (public_key, BASE64_STANDARD.encode(signature).into_bytes())
+}
+
+#[test]
+fn test_set_output_reuses_buffer() {
+    let mut context = OperationContext::with_capacity(64);
+    let data = [0x42u8; 64];
+
+    let (_, allocations) = count_allocations(|| {
+        for _ in 0..ITERATIONS {
+            assert_eq!(context.set_output(&data), &data);
+        }
+    });
+    assert_eq!(allocations, 0);
+}
+
+#[test]
+fn test_buffers_grow_once() {
+    let mut context = OperationContext::new();
+    let data = vec![0x42u8; 256];
+
+    let (_, allocations) = count_allocations(|| {
+        for _ in 0..ITERATIONS {
+            context.set_output(&data);
+            context.encode_base64(&data);
+        }
+    });
+    assert_eq!(allocations, 2);
+}
+
+#[test]
+fn test_base64_round_trip_without_allocations() {
+    let mut context = OperationContext::with_capacity(512);
+    let data = [0x17u8; 256];
+    let encoded = BASE64_STANDARD.encode(data);
+
+    let (_, allocations) = count_allocations(|| {
+        for _ in 0..ITERATIONS {
+            assert_eq!(context.encode_base64(&data), encoded.as_bytes());
+            assert_eq!(context.decode_base64(encoded.as_bytes()).unwrap(), &data);
+        }
+    });
+    assert_eq!(allocations, 0);
+}
+
+#[test]
+fn test_decode_invalid_base64() {
+    let mut context = OperationContext::new();
+
+    assert!(matches!(
+        context.decode_base64(b"not base64!"),
+        Err(SecurityModuleError::InvalidSignature)
+    ));
+}
+
+#[test]
+fn test_verify_without_allocations() {
+    let data = b"Hello, World!";
+    let (public_key, signature) = signed_p256(data);
+    let mut context = OperationContext::with_capacity(128);
+
+    // Warm up: the first decode may grow the scratch buffer.
+    let signature_der = context.decode_base64(&signature).unwrap().to_vec();
+    assert!(public_key.verify(data, &signature_der).unwrap());
+
+    let (_, allocations) = count_allocations(|| {
+        for _ in 0..ITERATIONS {
+            let signature = context.decode_base64(&signature).unwrap();
+            assert!(public_key.verify(data, signature).unwrap());
+        }
+    });
+    assert_eq!(allocations, 0);
+}
+
+#[test]
+fn test_default_sign_data_into() {
+    let key = FixedSignature(vec![0x01, 0x02, 0x03]);
+    let mut context = OperationContext::with_capacity(16);
+
+    let signature = key.sign_data_into(b"Hello, World!", &mut context).unwrap();
+    assert_eq!(signature, &[0x01, 0x02, 0x03]);
+    assert_eq!(context.output(), &[0x01, 0x02, 0x03]);
+
+    // `sign_data` allocates on every call, but the buffer of the context is reused.
+    let buffer = context.output().as_ptr();
+    for _ in 0..ITERATIONS {
+        key.sign_data_into(b"Hello, World!", &mut context).unwrap();
+    }
+    assert_eq!(context.output().as_ptr(), buffer);
+}
+
+#[test]
+fn test_debug_redacts_output() {
+    let mut context = OperationContext::with_capacity(64);
+    context.set_output(&[0x42; 32]);
+
+    let debug = format!("{:?}", context);
+    assert!(debug.contains("output: <32 bytes"), "{}", debug);
+    assert!(!debug.contains("66"), "{}", debug);
+}
@@ -0,0 +1,43 @@
+use crate::common::crypto::redact::{Redacted, RedactionPolicy};
+use std::fmt;
+use test_case::test_case;
+
+/// Formats bytes with a fixed policy, so that tests do not depend on the global policy.
+struct WithPolicy<'a>(RedactionPolicy, &'a [u8]);
+
+impl fmt::Debug for WithPolicy<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.format(self.1, f)
+    }
+}
+
+#[test_case(RedactionPolicy::Hashed, "<6 bytes, sha256:2bb80d53>")]
+#[test_case(RedactionPolicy::LengthOnly, "<6 bytes>")]
+#[test_case(RedactionPolicy::Reveal, "<6 bytes: 736563726574>")]
+fn test_policy_format(policy: RedactionPolicy, expected: &str) {
+    assert_eq!(format!("{:?}", WithPolicy(policy, b"secret")), expected);
+}
+
+#[test]
+fn test_empty_value() {
+    assert_eq!(
+        format!("{:?}", WithPolicy(RedactionPolicy::Hashed, b"")),
+        "<0 bytes, sha256:e3b0c442>"
+    );
+}
+
+#[test]
+fn test_default_policy_is_hashed() {
+    assert_eq!(RedactionPolicy::default(), RedactionPolicy::Hashed);
+}
+
+#[test]
+fn test_set_policy() {
+    // Other tests only rely on values being redacted, which holds for both policies used here.
+    RedactionPolicy::set(RedactionPolicy::LengthOnly);
+    assert_eq!(RedactionPolicy::current(), RedactionPolicy::LengthOnly);
+    assert_eq!(format!("{:?}", Redacted(b"secret")), "<6 bytes>");
+
+    RedactionPolicy::set(RedactionPolicy::Hashed);
+    assert_eq!(RedactionPolicy::current(), RedactionPolicy::Hashed);
+}
@@ -0,0 +1,49 @@
+use crate::common::crypto::secret::SecretBytes;
+use zeroize::Zeroize;
+
+#[test]
+fn test_extend_beyond_capacity() {
+    let mut secret = SecretBytes::with_capacity(4);
+    secret.extend_from_slice(b"sec");
+    secret.extend_from_slice(b"ret");
+    secret.extend_from_slice(&[0; 100]);
+
+    assert_eq!(secret.len(), 106);
+    assert_eq!(&secret[..6], b"secret");
+}
+
+#[test]
+fn test_zeroize() {
+    let mut secret = SecretBytes::from(&b"secret"[..]);
+    secret.zeroize();
+    assert!(secret.is_empty());
+}
+
+#[test]
+fn test_truncate_and_into_vec() {
+    let mut secret = SecretBytes::zeroed(8);
+    secret[..6].copy_from_slice(b"secret");
+    secret.truncate(6);
+    assert_eq!(secret.into_vec(), b"secret");
+}
+
+#[test]
+fn test_debug_is_redacted() {
+    let debug = format!("{:?}", SecretBytes::from(&b"secret"[..]));
+    // Other tests may switch the global policy between hashed and length-only redaction.
+    assert!(debug.starts_with("SecretBytes(<6 bytes"));
+    assert!(!debug.contains("736563726574"));
+}
+
+#[test]
+fn test_comparisons() {
+    let secret = SecretBytes::from(&b"secret"[..]);
+
+    assert_eq!(secret, SecretBytes::from(b"secret".to_vec()));
+    assert_ne!(secret, SecretBytes::from(&b"secreT"[..]));
+    assert_eq!(secret, b"secret");
+    assert_ne!(secret, b"secrets");
+    assert_eq!(secret, b"secret".to_vec());
+    assert_eq!(&b"secret"[..], secret);
+    assert_ne!(b"public".to_vec(), secret);
+}
@@ -0,0 +1,167 @@
+use crate::common::crypto::{
+    algorithms::{
+        encryption::{AsymmetricEncryption, EccCurves, EccSchemeAlgorithm},
+        hashes::{Hash, Sha2Bits},
+    },
+    public_key::PublicKey,
+    signature_format::{self, SignatureFormat},
+};
+use crypto_layer_core::CoreError;
+use openssl::{
+    bn::BigNumContext,
+    ec::{EcGroup, EcKey, PointConversionForm},
+    hash::MessageDigest,
+    nid::Nid,
+    pkey::PKey,
+    sign::Signer,
+};
+use proptest::{collection::vec, prelude::*};
+use test_case::test_case;
+
+/// Signs `data` with a fresh key on `curve`, returning the uncompressed public point and the
+/// DER encoded signature.
+fn sign(curve: Nid, digest: MessageDigest, data: &[u8]) -> (Vec<u8>, Vec<u8>) {
+    let group = EcGroup::from_curve_name(curve).unwrap();
+    let ec_key = EcKey::generate(&group).unwrap();
+    let mut ctx = BigNumContext::new().unwrap();
+    let point = ec_key
+        .public_key()
+        .to_bytes(&group, PointConversionForm::UNCOMPRESSED, &mut ctx)
+        .unwrap();
+
+    let private_key = PKey::from_ec_key(ec_key).unwrap();
+    let mut signer = Signer::new(digest, &private_key).unwrap();
+    signer.update(data).unwrap();
+    (point, signer.sign_to_vec().unwrap())
+}
+
+#[test_case(Nid::X9_62_PRIME256V1, MessageDigest::sha256(), 32 ; "p256")]
+#[test_case(Nid::SECP384R1, MessageDigest::sha384(), 48 ; "p384")]
+#[test_case(Nid::SECP521R1, MessageDigest::sha512(), 66 ; "p521")]
+fn test_der_raw_round_trip(curve: Nid, digest: MessageDigest, scalar_len: usize) {
+    for _ in 0..16 {
+        let (_, der) = sign(curve, digest, b"Hello, World!");
+
+        let raw = signature_format::der_to_raw(&der, scalar_len).unwrap();
+        assert_eq!(raw.len(), 2 * scalar_len);
+        assert_eq!(signature_format::raw_to_der(&raw).unwrap(), der);
+        assert_eq!(
+            signature_format::convert(&raw, SignatureFormat::Raw, SignatureFormat::Der, scalar_len)
+                .unwrap(),
+            der
+        );
+    }
+}
+
+#[test]
+fn test_verify_p256() {
+    let data = b"Hello, World!";
+    let (point, der) = sign(Nid::X9_62_PRIME256V1, MessageDigest::sha256(), data);
+    let raw = signature_format::der_to_raw(&der, 32).unwrap();
+
+    assert!(signature_format::verify_p256(&point, data, &der, SignatureFormat::Der).unwrap());
+    assert!(signature_format::verify_p256(&point, data, &raw, SignatureFormat::Raw).unwrap());
+    assert!(
+        !signature_format::verify_p256(&point, b"tampered", &raw, SignatureFormat::Raw).unwrap()
+    );
+    assert_eq!(
+        signature_format::verify_p256(&point[1..], data, &raw, SignatureFormat::Raw),
+        Err(CoreError::InvalidPublicKey)
+    );
+}
+
+#[test]
+fn test_verify_p384() {
+    let data = b"Hello, World!";
+    let (point, der) = sign(Nid::SECP384R1, MessageDigest::sha384(), data);
+    let raw = signature_format::der_to_raw(&der, 48).unwrap();
+
+    assert!(signature_format::verify_p384(&point, data, &der, SignatureFormat::Der).unwrap());
+    assert!(signature_format::verify_p384(&point, data, &raw, SignatureFormat::Raw).unwrap());
+    assert!(
+        !signature_format::verify_p384(&point, b"tampered", &raw, SignatureFormat::Raw).unwrap()
+    );
+    assert_eq!(
+        signature_format::verify_p384(&point, data, &raw[1..], SignatureFormat::Raw),
+        Err(CoreError::InvalidSignatureEncoding)
+    );
+}
+
+#[test]
+fn test_public_key_verify_raw() {
+    let data = b"Hello, World!";
+    let (point, der) = sign(Nid::X9_62_PRIME256V1, MessageDigest::sha256(), data);
+    let raw = signature_format::der_to_raw(&der, 32).unwrap();
+    let public_key = PublicKey::from_ec_point(
+        &point,
+        AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(EccCurves::P256)),
+        Hash::Sha2(Sha2Bits::Sha256),
+    )
+    .unwrap();
+
+    assert!(public_key
+        .verify_with_format(data, &raw, SignatureFormat::Raw)
+        .unwrap());
+    assert!(public_key
+        .verify_with_format(data, &der, SignatureFormat::Der)
+        .unwrap());
+}
+
+#[test]
+fn test_small_scalars_are_padded() {
+    let der = [0x30, 0x06, 0x02, 0x01, 0x01, 0x02, 0x01, 0x7f];
+
+    let raw = signature_format::der_to_raw(&der, 4).unwrap();
+
+    assert_eq!(raw, [0, 0, 0, 1, 0, 0, 0, 0x7f]);
+    assert_eq!(signature_format::raw_to_der(&raw).unwrap(), der);
+}
+
+#[test_case(&[] ; "empty")]
+#[test_case(&[0x30, 0x06, 0x02, 0x01, 0x01, 0x02, 0x01, 0x01, 0x00] ; "trailing data")]
+#[test_case(&[0x30, 0x06, 0x02, 0x01, 0x81, 0x02, 0x01, 0x01] ; "negative integer")]
+#[test_case(&[0x30, 0x07, 0x02, 0x02, 0x00, 0x01, 0x02, 0x01, 0x01] ; "non-minimal integer")]
+#[test_case(&[0x30, 0x81, 0x06, 0x02, 0x01, 0x01, 0x02, 0x01, 0x01] ; "non-minimal length")]
+#[test_case(&[0x30, 0x06, 0x02, 0x01, 0x01, 0x02, 0x02, 0x01] ; "truncated")]
+#[test_case(&[0x31, 0x06, 0x02, 0x01, 0x01, 0x02, 0x01, 0x01] ; "wrong tag")]
+#[test_case(&[0x30, 0x07, 0x02, 0x01, 0x01, 0x02, 0x02, 0x01, 0x01] ; "scalar too long")]
+fn test_invalid_der(der: &[u8]) {
+    assert_eq!(
+        signature_format::der_to_raw(der, 1),
+        Err(CoreError::InvalidSignatureEncoding)
+    );
+}
+
+#[test_case(&[] ; "empty")]
+#[test_case(&[0x01, 0x02, 0x03] ; "odd length")]
+#[test_case(&[0x01; 2 * signature_format::MAX_SCALAR_LEN + 2] ; "too long")]
+fn test_invalid_raw(raw: &[u8]) {
+    assert_eq!(
+        signature_format::raw_to_der(raw),
+        Err(CoreError::InvalidSignatureEncoding)
+    );
+}
+
+prop_compose! {
+    fn raw_signature()(scalar_len in prop_oneof![Just(32usize), Just(48), Just(66)])(
+        raw in vec(any::<u8>(), 2 * scalar_len),
+    ) -> Vec<u8> {
+        raw
+    }
+}
+
+proptest! {
+    #[test]
+    fn prop_raw_der_raw_is_identity(raw in raw_signature()) {
+        let der = signature_format::convert(&raw, SignatureFormat::Raw, SignatureFormat::Der, raw.len() / 2).unwrap();
+        let converted = signature_format::convert(&der, SignatureFormat::Der, SignatureFormat::Raw, raw.len() / 2).unwrap();
+        prop_assert_eq!(converted, raw);
+    }
+
+    #[test]
+    fn prop_der_raw_der_is_identity(raw in raw_signature()) {
+        let der = signature_format::raw_to_der(&raw).unwrap();
+        let converted = signature_format::der_to_raw(&der, raw.len() / 2).unwrap();
+        prop_assert_eq!(signature_format::raw_to_der(&converted).unwrap(), der);
+    }
+}
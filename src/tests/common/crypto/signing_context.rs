@@ -0,0 +1,79 @@
+use crate::{
+    common::{
+        crypto::{
+            signing_context::{self, MAX_LABEL_LEN, PREFIX},
+            verifying_key::VerifyingKey,
+        },
+        traits::{key_handle::KeyHandle, module_provider::Provider},
+    },
+    mock::{MockConfig, MockProvider},
+    SecurityModuleError,
+};
+use crypto_layer_core::CoreError;
+
+#[test]
+fn test_encode() {
+    let message = signing_context::encode("com.example.mail/login/v1", b"challenge").unwrap();
+    assert_eq!(
+        message,
+        [PREFIX, &[25], b"com.example.mail/login/v1", b"challenge"].concat()
+    );
+    assert_eq!(
+        signing_context::encode("a", b"").unwrap(),
+        [PREFIX, &[1], b"a"].concat()
+    );
+
+    // The length prefix keeps the label and the data apart.
+    assert_ne!(
+        signing_context::encode("login", b"/v1data").unwrap(),
+        signing_context::encode("login/v1", b"data").unwrap()
+    );
+}
+
+#[test]
+fn test_rejects_invalid_labels() {
+    assert!(signing_context::check_label(&"a".repeat(MAX_LABEL_LEN)).is_ok());
+    for label in [
+        "",
+        "login v1",
+        "login\n",
+        "anmeldung/überweisung",
+        &"a".repeat(MAX_LABEL_LEN + 1),
+    ] {
+        assert_eq!(
+            signing_context::encode(label, b"data"),
+            Err(CoreError::InvalidSigningContext)
+        );
+    }
+}
+
+#[test]
+fn test_sign_and_verify_in_context() {
+    let provider = MockProvider::with_key("signer", Box::new(MockConfig::default()));
+    let verifying_key = VerifyingKey::new(provider.key_metadata().unwrap().public_key().clone());
+
+    let signature = provider
+        .sign_in_context("com.example.mail/login/v1", b"challenge")
+        .unwrap();
+    for key in [&provider as &dyn KeyHandle, &verifying_key] {
+        assert!(key
+            .verify_in_context("com.example.mail/login/v1", b"challenge", &signature)
+            .unwrap());
+        assert!(!key
+            .verify_in_context("com.example.mail/approve/v1", b"challenge", &signature)
+            .unwrap());
+        assert!(!key.verify_signature(b"challenge", &signature).unwrap());
+    }
+
+    // A plain signature is not accepted in a context either.
+    let plain = provider.sign_data(b"challenge").unwrap();
+    assert!(!provider
+        .verify_in_context("com.example.mail/login/v1", b"challenge", &plain)
+        .unwrap());
+    assert!(matches!(
+        provider.sign_in_context("", b"challenge"),
+        Err(SecurityModuleError::Encoding(
+            CoreError::InvalidSigningContext
+        ))
+    ));
+}
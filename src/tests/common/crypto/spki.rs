@@ -0,0 +1,131 @@
+use crate::common::crypto::{
+    signature_format::{self, SignatureFormat},
+    spki::{PublicKeyAlgorithm, SubjectPublicKeyInfo},
+};
+use crypto_layer_core::CoreError;
+use openssl::{
+    ec::{EcGroup, EcKey},
+    hash::MessageDigest,
+    nid::Nid,
+    pkey::{PKey, Private},
+    rsa::Rsa,
+    sha::sha256,
+    sign::Signer,
+};
+use test_case::test_case;
+
+fn ec_key(curve: Nid) -> PKey<Private> {
+    let group = EcGroup::from_curve_name(curve).unwrap();
+    PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap()
+}
+
+fn sign(key: &PKey<Private>, digest: MessageDigest, data: &[u8]) -> Vec<u8> {
+    let mut signer = Signer::new(digest, key).unwrap();
+    signer.update(data).unwrap();
+    signer.sign_to_vec().unwrap()
+}
+
+#[test_case(ec_key(Nid::X9_62_PRIME256V1), PublicKeyAlgorithm::EcP256, 65 ; "p256")]
+#[test_case(ec_key(Nid::SECP384R1), PublicKeyAlgorithm::EcP384, 97 ; "p384")]
+#[test_case(ec_key(Nid::SECP521R1), PublicKeyAlgorithm::EcP521, 133 ; "p521")]
+#[test_case(PKey::generate_ed25519().unwrap(), PublicKeyAlgorithm::Ed25519, 32 ; "ed25519")]
+#[test_case(PKey::generate_x25519().unwrap(), PublicKeyAlgorithm::X25519, 32 ; "x25519")]
+fn test_from_der(key: PKey<Private>, algorithm: PublicKeyAlgorithm, key_len: usize) {
+    let der = key.public_key_to_der().unwrap();
+
+    let info = SubjectPublicKeyInfo::from_der(&der).unwrap();
+
+    assert_eq!(info.algorithm(), algorithm);
+    assert_eq!(info.der(), der);
+    assert_eq!(info.key().len(), key_len);
+    assert!(der.ends_with(info.key()));
+}
+
+#[test]
+fn test_rsa() {
+    let key = PKey::from_rsa(Rsa::generate(3072).unwrap()).unwrap();
+    let der = key.public_key_to_der().unwrap();
+
+    let info = SubjectPublicKeyInfo::from_der(&der).unwrap();
+
+    assert_eq!(info.algorithm(), PublicKeyAlgorithm::Rsa);
+    assert_eq!(
+        info.key(),
+        key.rsa().unwrap().public_key_to_der_pkcs1().unwrap()
+    );
+    let signature = sign(&key, MessageDigest::sha256(), b"data");
+    assert_eq!(
+        info.verify(b"data", &signature, SignatureFormat::Der),
+        Err(CoreError::UnsupportedKeyAlgorithm)
+    );
+}
+
+#[test]
+fn test_pem() {
+    let key = ec_key(Nid::X9_62_PRIME256V1);
+    let pem = String::from_utf8(key.public_key_to_pem().unwrap()).unwrap();
+
+    let info = SubjectPublicKeyInfo::from_pem(&format!("Device key:\n{pem}\n")).unwrap();
+
+    assert_eq!(info.der(), key.public_key_to_der().unwrap());
+    assert_eq!(info.to_pem(), pem);
+    assert_eq!(info.fingerprint(), sha256(info.der()));
+}
+
+#[test_case(Nid::X9_62_PRIME256V1, MessageDigest::sha256(), 32 ; "p256")]
+#[test_case(Nid::SECP384R1, MessageDigest::sha384(), 48 ; "p384")]
+fn test_verify(curve: Nid, digest: MessageDigest, scalar_len: usize) {
+    let key = ec_key(curve);
+    let info = SubjectPublicKeyInfo::from_der(&key.public_key_to_der().unwrap()).unwrap();
+    let der = sign(&key, digest, b"Hello, World!");
+    let raw = signature_format::der_to_raw(&der, scalar_len).unwrap();
+
+    assert!(info
+        .verify(b"Hello, World!", &der, SignatureFormat::Der)
+        .unwrap());
+    assert!(info
+        .verify(b"Hello, World!", &raw, SignatureFormat::Raw)
+        .unwrap());
+    assert!(!info
+        .verify(b"tampered", &raw, SignatureFormat::Raw)
+        .unwrap());
+}
+
+#[test]
+fn test_unsupported_curve() {
+    let der = ec_key(Nid::SECP256K1).public_key_to_der().unwrap();
+
+    assert_eq!(
+        SubjectPublicKeyInfo::from_der(&der),
+        Err(CoreError::UnsupportedKeyAlgorithm)
+    );
+}
+
+#[test]
+fn test_invalid() {
+    let der = ec_key(Nid::X9_62_PRIME256V1).public_key_to_der().unwrap();
+    let mut trailing = der.clone();
+    trailing.push(0);
+    let mut invalid_point = der.clone();
+    let last = invalid_point.len() - 1;
+    invalid_point[last] ^= 1;
+
+    for der in [&der[..der.len() - 1], &trailing, &[]] {
+        assert_eq!(
+            SubjectPublicKeyInfo::from_der(der),
+            Err(CoreError::InvalidPublicKey)
+        );
+    }
+    assert_eq!(
+        SubjectPublicKeyInfo::from_pem(
+            "-----BEGIN PUBLIC KEY-----\n!!!!\n-----END PUBLIC KEY-----"
+        ),
+        Err(CoreError::InvalidPublicKey)
+    );
+    assert_eq!(
+        SubjectPublicKeyInfo::from_der(&invalid_point)
+            .unwrap()
+            .verify(b"data", &[1; 64], SignatureFormat::Raw),
+        Err(CoreError::InvalidPublicKey)
+    );
+}
@@ -0,0 +1,180 @@
+use crate::{
+    common::{
+        acme,
+        crypto::{
+            algorithms::{
+                encryption::{AsymmetricEncryption, EccCurves, EccSchemeAlgorithm},
+                hashes::{Hash, Sha2Bits},
+                KeyBits,
+            },
+            public_key::{PublicKey, RsaSignaturePadding},
+            signature_format::{self, SignatureFormat},
+            verifying_key::VerifyingKey,
+        },
+        traits::{key_handle::KeyHandle, module_provider::Provider},
+    },
+    mock::{MockConfig, MockProvider},
+    SecurityModuleError,
+};
+use crypto_layer_core::CoreError;
+use openssl::{pkey::PKey, rsa::Rsa};
+use std::any::Any;
+
+/// Returns a provider holding a new key with `config` and the public key of it.
+fn provider(config: Box<dyn Any>) -> (MockProvider, PublicKey) {
+    let provider = MockProvider::with_key("signer", config);
+    let public_key = provider.key_metadata().unwrap().public_key().clone();
+    (provider, public_key)
+}
+
+#[test]
+fn test_verify_external_keys() {
+    let (provider, public_key) = provider(Box::new(MockConfig::default()));
+    let der = public_key.to_der().unwrap();
+    let pem = PKey::public_key_from_der(&der)
+        .unwrap()
+        .public_key_to_pem()
+        .unwrap();
+    let jwk = acme::jwk(&public_key).unwrap().to_string();
+    let signature = provider.sign_data(b"data").unwrap();
+
+    let sha256 = Hash::Sha2(Sha2Bits::Sha256);
+    for key in [
+        VerifyingKey::from_der(&der, sha256).unwrap(),
+        VerifyingKey::from_pem(&pem, sha256).unwrap(),
+        VerifyingKey::from_jwk(&jwk).unwrap(),
+    ] {
+        assert!(matches!(
+            key.public_key().algorithm(),
+            AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(EccCurves::P256))
+        ));
+        assert_eq!(key.public_key().to_der().unwrap(), der);
+        assert!(key.verify_signature(b"data", &signature).unwrap());
+        assert!(!key.verify_signature(b"other", &signature).unwrap());
+        assert_eq!(
+            key.verify_many(&[(b"data", &signature), (b"other", &signature)])
+                .unwrap(),
+            [true, false]
+        );
+    }
+
+    // JWS signatures concatenate the scalars.
+    let raw = signature_format::der_to_raw(&signature, 32).unwrap();
+    let key = VerifyingKey::from_jwk(&jwk)
+        .unwrap()
+        .with_signature_format(SignatureFormat::Raw);
+    assert!(key.verify_signature(b"data", &raw).unwrap());
+    assert_eq!(
+        key.verify_many(&[(b"data", &raw), (b"data", &signature)])
+            .unwrap(),
+        [true, false]
+    );
+}
+
+#[test]
+fn test_verify_rsa_keys() {
+    let (provider, public_key) = provider(MockConfig::new(
+        AsymmetricEncryption::Rsa(KeyBits::Bits2048),
+        Hash::Sha2(Sha2Bits::Sha256),
+    ));
+    let der = public_key.to_der().unwrap();
+    let signature = provider.sign_data(b"data").unwrap();
+
+    let pkcs1_pem = PKey::public_key_from_der(&der)
+        .unwrap()
+        .rsa()
+        .unwrap()
+        .public_key_to_pem_pkcs1()
+        .unwrap();
+    let key = VerifyingKey::from_pem(&pkcs1_pem, Hash::Sha2(Sha2Bits::Sha256)).unwrap();
+    assert!(matches!(
+        key.public_key().algorithm(),
+        AsymmetricEncryption::Rsa(KeyBits::Bits2048)
+    ));
+    assert!(key.verify_signature(b"data", &signature).unwrap());
+
+    let mut jwk = acme::jwk(&public_key).unwrap();
+    let key = VerifyingKey::from_jwk(&jwk.to_string()).unwrap();
+    assert_eq!(key.public_key().rsa_padding(), RsaSignaturePadding::Pkcs1);
+    assert!(key.verify_signature(b"data", &signature).unwrap());
+
+    // `alg` selects the padding and the hash.
+    jwk["alg"] = "PS384".into();
+    let key = VerifyingKey::from_jwk(&jwk.to_string()).unwrap();
+    assert_eq!(key.public_key().rsa_padding(), RsaSignaturePadding::Pss);
+    assert!(matches!(
+        key.public_key().hash(),
+        Hash::Sha2(Sha2Bits::Sha384)
+    ));
+    assert!(!key.verify_signature(b"data", &signature).unwrap());
+
+    // Sizes without a `KeyBits` are rejected instead of guessed.
+    let odd = Rsa::generate(1536).unwrap().public_key_to_der().unwrap();
+    assert!(matches!(
+        VerifyingKey::from_der(&odd, Hash::Sha2(Sha2Bits::Sha256)),
+        Err(SecurityModuleError::UnsupportedAlgorithm)
+    ));
+}
+
+#[test]
+fn test_rejected_jwks() {
+    let (_, public_key) = provider(Box::new(MockConfig::default()));
+    let jwk = acme::jwk(&public_key).unwrap();
+
+    let mut mismatch = jwk.clone();
+    mismatch["alg"] = "ES384".into();
+    assert!(matches!(
+        VerifyingKey::from_jwk(&mismatch.to_string()),
+        Err(SecurityModuleError::InvalidPublicKey)
+    ));
+    let mut missing = jwk.clone();
+    missing.as_object_mut().unwrap().remove("y");
+    assert!(matches!(
+        VerifyingKey::from_jwk(&missing.to_string()),
+        Err(SecurityModuleError::Encoding(CoreError::MissingField("y")))
+    ));
+    let mut invalid = jwk.clone();
+    invalid["x"] = "not base64!".into();
+    assert!(matches!(
+        VerifyingKey::from_jwk(&invalid.to_string()),
+        Err(SecurityModuleError::Encoding(CoreError::InvalidField("x")))
+    ));
+    let mut unknown = jwk;
+    unknown["crv"] = "P-192".into();
+    assert!(matches!(
+        VerifyingKey::from_jwk(&unknown.to_string()),
+        Err(SecurityModuleError::UnsupportedAlgorithm)
+    ));
+    assert!(matches!(
+        VerifyingKey::from_jwk("{"),
+        Err(SecurityModuleError::Encoding(CoreError::InvalidField(
+            "jwk"
+        )))
+    ));
+    assert!(matches!(
+        VerifyingKey::from_der(b"not a key", Hash::Sha2(Sha2Bits::Sha256)),
+        Err(SecurityModuleError::InvalidPublicKey)
+    ));
+}
+
+#[test]
+fn test_verify_only() {
+    let (_, public_key) = provider(Box::new(MockConfig::default()));
+    let key = VerifyingKey::from(public_key);
+    assert!(matches!(
+        key.sign_data(b"data"),
+        Err(SecurityModuleError::UnsupportedOperation(_))
+    ));
+    assert!(matches!(
+        key.encrypt_data(b"data"),
+        Err(SecurityModuleError::UnsupportedOperation(_))
+    ));
+    assert!(matches!(
+        key.decrypt_data(b"data"),
+        Err(SecurityModuleError::UnsupportedOperation(_))
+    ));
+    assert!(matches!(
+        key.derive_shared_secret(b"peer"),
+        Err(SecurityModuleError::UnsupportedOperation(_))
+    ));
+}
@@ -0,0 +1,66 @@
+use crate::{
+    common::{
+        crypto::algorithms::{
+            encryption::{AsymmetricEncryption, EccCurves, EccSchemeAlgorithm},
+            hashes::{Hash, Sha2Bits},
+        },
+        csr::{self, CsrSpec, MAX_COMMON_NAME_LEN},
+        traits::module_provider::Provider,
+    },
+    mock::{MockConfig, MockProvider},
+    SecurityModuleError,
+};
+use openssl::{nid::Nid, pkey::PKey, x509::X509Req};
+
+fn provider(hash: Sha2Bits) -> MockProvider {
+    MockProvider::with_key(
+        "csr",
+        MockConfig::new(
+            AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(EccCurves::P256)),
+            Hash::Sha2(hash),
+        ),
+    )
+}
+
+#[test]
+fn test_create_csr() {
+    for (hash, signature_algorithm) in [
+        (Sha2Bits::Sha256, "ecdsa-with-SHA256"),
+        (Sha2Bits::Sha384, "ecdsa-with-SHA384"),
+    ] {
+        let provider = provider(hash);
+        let csr = csr::create_csr(&provider, &CsrSpec::new().common_name("device-42")).unwrap();
+
+        let request = X509Req::from_der(&csr).unwrap();
+        let public_key = provider.key_metadata().unwrap().public_key_der().to_vec();
+        assert!(request
+            .verify(&PKey::public_key_from_der(&public_key).unwrap())
+            .unwrap());
+        let common_name = request
+            .subject_name()
+            .entries_by_nid(Nid::COMMONNAME)
+            .next()
+            .unwrap();
+        assert_eq!(common_name.data().as_slice(), b"device-42");
+        // Without subject alternative names, no extensions are requested.
+        assert_eq!(request.extensions().unwrap().len(), 0);
+        let text = String::from_utf8(request.to_text().unwrap()).unwrap();
+        assert!(text.contains(signature_algorithm), "{}", text);
+    }
+}
+
+#[test]
+fn test_invalid_spec() {
+    let provider = provider(Sha2Bits::Sha256);
+    for spec in [
+        CsrSpec::new(),
+        CsrSpec::new().common_name(""),
+        CsrSpec::new().common_name("a".repeat(MAX_COMMON_NAME_LEN + 1)),
+        CsrSpec::new().dns_name("bücher.example"),
+    ] {
+        assert!(matches!(
+            csr::create_csr(&provider, &spec),
+            Err(SecurityModuleError::SigningError(_))
+        ));
+    }
+}
@@ -0,0 +1,196 @@
+use crate::{
+    common::{
+        crypto::{
+            algorithms::{
+                encryption::{AsymmetricEncryption, EccCurves, EccSchemeAlgorithm},
+                hashes::{Hash, Sha2Bits},
+            },
+            key_metadata::KeyMetadata,
+        },
+        device_identity::{DeviceIdentity, DEFAULT_KEY_PREFIX},
+        latency::ProviderOperation,
+        traits::{key_handle::KeyHandle, module_provider::Provider},
+    },
+    mock::{MockConfig, MockController, MockProvider},
+    SecurityModuleError,
+};
+use std::{
+    any::Any,
+    sync::{Arc, Mutex},
+};
+
+fn config() -> Box<dyn Any> {
+    MockConfig::new(
+        AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(EccCurves::P256)),
+        Hash::Sha2(Sha2Bits::Sha256),
+    )
+}
+
+fn mock_provider() -> MockProvider {
+    let mut provider = MockProvider::new(String::new());
+    provider.initialize_module().unwrap();
+    provider
+}
+
+/// A provider that cannot list its keys, like the Secure Enclave.
+#[derive(Debug)]
+struct UnlistedProvider(MockProvider);
+
+impl KeyHandle for UnlistedProvider {
+    fn sign_data(&self, data: &[u8]) -> Result<Vec<u8>, SecurityModuleError> {
+        self.0.sign_data(data)
+    }
+}
+
+impl Provider for UnlistedProvider {
+    fn create_key(
+        &mut self,
+        key_id: &str,
+        config: Box<dyn Any>,
+    ) -> Result<(), SecurityModuleError> {
+        self.0.create_key(key_id, config)
+    }
+
+    fn load_key(&mut self, key_id: &str, config: Box<dyn Any>) -> Result<(), SecurityModuleError> {
+        self.0.load_key(key_id, config)
+    }
+
+    fn initialize_module(&mut self) -> Result<(), SecurityModuleError> {
+        self.0.initialize_module()
+    }
+
+    fn key_metadata(&self) -> Result<KeyMetadata, SecurityModuleError> {
+        self.0.key_metadata()
+    }
+}
+
+#[test]
+fn test_open_creates_and_finds_the_key() {
+    let provider: Arc<Mutex<dyn Provider>> = Arc::new(Mutex::new(mock_provider()));
+    let identity = DeviceIdentity::open(provider.clone(), config).unwrap();
+    assert_eq!(identity.key_id(), format!("{}.1", DEFAULT_KEY_PREFIX));
+    assert_eq!(identity.generation(), 1);
+    assert_eq!(identity.attestation(), None);
+
+    let signature = identity.sign(b"telemetry").unwrap();
+    assert!(identity
+        .public_key()
+        .verify(b"telemetry", &signature)
+        .unwrap());
+
+    let reopened = DeviceIdentity::open(provider.clone(), config).unwrap();
+    assert_eq!(reopened.key_id(), identity.key_id());
+    assert_eq!(
+        reopened.metadata().public_key_der(),
+        identity.metadata().public_key_der()
+    );
+    assert_eq!(
+        provider.lock().unwrap().list_keys().unwrap(),
+        ["device-identity.1"]
+    );
+
+    assert!(matches!(
+        DeviceIdentity::open_with_prefix(provider, "../identity", config),
+        Err(SecurityModuleError::InvalidKeyId(_))
+    ));
+}
+
+#[test]
+fn test_renew() {
+    let provider: Arc<Mutex<dyn Provider>> = Arc::new(Mutex::new(mock_provider()));
+    let mut identity = DeviceIdentity::open(provider.clone(), config).unwrap();
+    let registered = identity.public_key().clone();
+
+    let renewal = identity.renew().unwrap();
+    assert_eq!(renewal.previous_key_id, "device-identity.1");
+    assert_eq!(renewal.key_id, "device-identity.2");
+    assert_eq!(identity.generation(), 2);
+    assert_eq!(
+        renewal.public_key.to_der().unwrap(),
+        identity.metadata().public_key_der()
+    );
+    renewal.verify(&registered).unwrap();
+
+    // The backend only accepts endorsements by the key it registered.
+    assert!(matches!(
+        renewal.verify(&renewal.public_key),
+        Err(SecurityModuleError::InvalidSignature)
+    ));
+    let mut forged = renewal.clone();
+    forged.public_key = registered.clone();
+    assert!(forged.verify(&registered).is_err());
+
+    let signature = identity.sign(b"telemetry").unwrap();
+    assert!(renewal.public_key.verify(b"telemetry", &signature).unwrap());
+    assert!(!registered.verify(b"telemetry", &signature).unwrap());
+
+    let reopened = DeviceIdentity::open(provider, config).unwrap();
+    assert_eq!(reopened.key_id(), "device-identity.2");
+}
+
+#[test]
+fn test_shared_provider() {
+    let mut mock = mock_provider();
+    let controller: MockController = mock.controller();
+    mock.create_key("other_key", config()).unwrap();
+    let provider: Arc<Mutex<dyn Provider>> = Arc::new(Mutex::new(mock));
+    let identity = DeviceIdentity::open(provider.clone(), config).unwrap();
+
+    // The identity key is only loaded again if another key was loaded in between.
+    let loads = controller.calls(ProviderOperation::LoadKey);
+    identity.sign(b"first").unwrap();
+    assert_eq!(controller.calls(ProviderOperation::LoadKey), loads);
+    provider
+        .lock()
+        .unwrap()
+        .load_key("other_key", config())
+        .unwrap();
+    let signature = identity.sign(b"second").unwrap();
+    // One load of the other key and one of the identity key.
+    assert_eq!(controller.calls(ProviderOperation::LoadKey), loads + 2);
+    assert!(identity.public_key().verify(b"second", &signature).unwrap());
+}
+
+#[test]
+fn test_providers_that_cannot_list_keys_are_probed() {
+    let provider: Arc<Mutex<dyn Provider>> =
+        Arc::new(Mutex::new(UnlistedProvider(mock_provider())));
+    let mut identity = DeviceIdentity::open(provider.clone(), config).unwrap();
+    identity.renew().unwrap();
+    identity.renew().unwrap();
+
+    let reopened = DeviceIdentity::open(provider, config).unwrap();
+    assert_eq!(reopened.key_id(), "device-identity.3");
+    assert_eq!(
+        reopened.metadata().public_key_der(),
+        identity.metadata().public_key_der()
+    );
+}
+
+#[test]
+fn test_failed_renewal_is_rolled_back() {
+    let mock = mock_provider();
+    let controller = mock.controller();
+    let provider: Arc<Mutex<dyn Provider>> = Arc::new(Mutex::new(mock));
+    let mut identity = DeviceIdentity::open(provider.clone(), config).unwrap();
+    let metadata = identity.metadata().clone();
+
+    controller.fail_times(ProviderOperation::SignData, 1, || {
+        SecurityModuleError::SigningFailed
+    });
+    assert!(identity.renew().is_err());
+    assert_eq!(
+        provider.lock().unwrap().list_keys().unwrap(),
+        ["device-identity.1"]
+    );
+    assert_eq!(identity.key_id(), "device-identity.1");
+    let signature = identity.sign(b"data").unwrap();
+    assert!(identity.public_key().verify(b"data", &signature).unwrap());
+
+    let reopened = DeviceIdentity::open(provider, config).unwrap();
+    assert_eq!(reopened.key_id(), "device-identity.1");
+    assert_eq!(
+        reopened.metadata().public_key_der(),
+        metadata.public_key_der()
+    );
+}
@@ -0,0 +1,86 @@
+use crate::{
+    common::{
+        diagnostics::{support_bundle, RECENT_ERRORS},
+        latency::{key_id_hash, LatencyConfig, ProviderOperation, TimedProvider},
+        traits::{key_handle::KeyHandle, module_provider::Provider},
+    },
+    mock::{MockConfig, MockProvider},
+    SecurityModuleError,
+};
+use std::sync::{Arc, Mutex};
+
+/// Creates a provider for `key_id` whose signing operations fail with a message naming the key.
+fn failing_provider(key_id: &str) -> TimedProvider {
+    let mock = MockProvider::new(key_id.to_owned());
+    let message = format!("Key {} is locked", key_id);
+    mock.controller()
+        .fail(ProviderOperation::SignData, move || {
+            SecurityModuleError::SigningError(message.clone())
+        });
+    let mut provider = TimedProvider::new(
+        Arc::new(Mutex::new(mock)),
+        key_id.to_owned(),
+        LatencyConfig::default(),
+    );
+    provider.initialize_module().unwrap();
+    provider
+        .create_key(key_id, Box::new(MockConfig::default()))
+        .unwrap();
+    provider
+}
+
+#[test]
+fn test_errors_are_recorded_sanitized() {
+    let key_id = "diagnostics_sanitized_key";
+    let provider = failing_provider(key_id);
+
+    assert!(provider.sign_data(b"data").is_err());
+
+    let bundle = support_bundle();
+    let hash = key_id_hash(key_id);
+    let record = bundle
+        .recent_errors
+        .iter()
+        .find(|record| record.key_id_hash == hash)
+        .unwrap();
+    assert_eq!(record.operation, ProviderOperation::SignData);
+    assert_eq!(record.code, 1);
+    assert_eq!(
+        record.message,
+        format!("Signing error: Key {} is locked", hash)
+    );
+    assert_eq!(record.correlation_id.as_ref().map(String::len), Some(16));
+    assert!(!bundle.to_json().unwrap().contains(key_id));
+}
+
+#[test]
+fn test_recent_errors_are_bounded() {
+    let key_id = "diagnostics_bounded_key";
+    let provider = failing_provider(key_id);
+
+    for _ in 0..RECENT_ERRORS + 5 {
+        assert!(provider.sign_data(b"data").is_err());
+    }
+
+    let bundle = support_bundle();
+    assert_eq!(bundle.recent_errors.len(), RECENT_ERRORS);
+    assert!(bundle
+        .recent_errors
+        .iter()
+        .any(|record| record.key_id_hash == key_id_hash(key_id)));
+}
+
+#[test]
+fn test_bundle_describes_build_and_system() {
+    let bundle = support_bundle();
+
+    assert_eq!(bundle.version, env!("CARGO_PKG_VERSION"));
+    assert!(bundle.features.contains(&"test-utils"));
+    assert_eq!(bundle.system.os, std::env::consts::OS);
+    assert_eq!(bundle.system.arch, std::env::consts::ARCH);
+    assert_eq!(bundle.config.redaction_policy, "Hashed");
+
+    let json: serde_json::Value = serde_json::from_str(&bundle.to_json().unwrap()).unwrap();
+    assert!(json["modules"].is_array());
+    assert!(json["recent_errors"].is_array());
+}
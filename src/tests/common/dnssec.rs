@@ -0,0 +1,296 @@
+use crate::{
+    common::{
+        crypto::{
+            algorithms::{
+                encryption::{AsymmetricEncryption, EccCurves, EccSchemeAlgorithm},
+                hashes::{Hash, Sha2Bits},
+                KeyBits,
+            },
+            public_key::PublicKey,
+        },
+        dnssec::{
+            self, DnssecAlgorithm, DsDigest, KeyRole, RrSet, Rrsig, ZoneKey, TYPE_A, TYPE_TXT,
+        },
+        traits::module_provider::Provider,
+    },
+    mock::{MockConfig, MockProvider},
+    SecurityModuleError,
+};
+use base64::{prelude::BASE64_STANDARD, Engine};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The MX record type, whose RDATA contains a name.
+const TYPE_MX: u16 = 15;
+
+fn provider(algorithm: AsymmetricEncryption, hash: Sha2Bits) -> MockProvider {
+    MockProvider::with_key("zone", MockConfig::new(algorithm, Hash::Sha2(hash)))
+}
+
+fn p256() -> MockProvider {
+    provider(
+        AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(EccCurves::P256)),
+        Sha2Bits::Sha256,
+    )
+}
+
+fn zone_key(provider: &MockProvider) -> ZoneKey {
+    let public_key = provider.key_metadata().unwrap().public_key().clone();
+    ZoneKey::new("Example.NET.", &public_key, KeyRole::ZoneSigning).unwrap()
+}
+
+fn time(secs: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(secs)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Builds the RRSIG RDATA of an example of an RFC.
+fn rrsig(fields: (u16, u8, u8, u32, u32, u32, u16), signer: &[u8], signature: &str) -> Rrsig {
+    let (type_covered, algorithm, labels, ttl, expiration, inception, key_tag) = fields;
+    let mut rdata = type_covered.to_be_bytes().to_vec();
+    rdata.extend_from_slice(&[algorithm, labels]);
+    for field in [ttl, expiration, inception] {
+        rdata.extend_from_slice(&field.to_be_bytes());
+    }
+    rdata.extend_from_slice(&key_tag.to_be_bytes());
+    rdata.extend_from_slice(signer);
+    rdata.extend_from_slice(&BASE64_STANDARD.decode(signature).unwrap());
+    Rrsig::from_rdata(&rdata).unwrap()
+}
+
+#[test]
+fn test_rfc6605_example() {
+    let point = BASE64_STANDARD
+        .decode("GojIhhXUN/u4v54ZQqGSnyhWJwaubCvTmeexv7bR6edbkrSqQpF64cYbcB7wNcP+e+MAnLr+Wi9xMWyQLc8NAA==")
+        .unwrap();
+    let public_key = PublicKey::from_ec_point(
+        &[&[0x04][..], &point].concat(),
+        AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(EccCurves::P256)),
+        Hash::Sha2(Sha2Bits::Sha256),
+    )
+    .unwrap();
+    let key = ZoneKey::new("example.net.", &public_key, KeyRole::KeySigning).unwrap();
+    assert_eq!(key.algorithm(), DnssecAlgorithm::EcdsaP256Sha256);
+    assert_eq!(key.key_tag(), 55648);
+    assert_eq!(
+        hex(&key.ds_rdata(DsDigest::Sha256)[4..]),
+        "b4c8c1fe2e7477127b27115656ad6256f424625bf5c1e2770ce6d6e37df61d17"
+    );
+    assert_eq!(
+        key.dnskey_record(3600),
+        format!(
+            "example.net. 3600 IN DNSKEY 257 3 13 {}",
+            BASE64_STANDARD.encode(&point)
+        )
+    );
+    assert!(key
+        .ds_record(3600, DsDigest::Sha256)
+        .starts_with("example.net. 3600 IN DS 55648 13 2 b4c8c1fe"));
+
+    let rrset = RrSet::new("www.example.net.", TYPE_A, 3600, vec![vec![192, 0, 2, 1]]).unwrap();
+    let rrsig = rrsig(
+        (TYPE_A, 13, 3, 3600, 1284026679, 1281607479, 55648),
+        b"\x07example\x03net\x00",
+        "qx6wLYqmh+l9oCKTN6qIc+bw6ya+KJ8oMz0YP107epXAyGmt+3SNruPFKG7tZoLBLlUzGGus7ZwmwWep666VCw==",
+    );
+    assert_eq!(rrsig.signer_name(), "example.net.");
+    dnssec::verify_rrset(&rrsig, &key, &rrset, time(1282000000)).unwrap();
+    for now in [time(1281607478), time(1284026680)] {
+        assert!(matches!(
+            dnssec::verify_rrset(&rrsig, &key, &rrset, now),
+            Err(SecurityModuleError::InvalidSignature)
+        ));
+    }
+}
+
+#[test]
+fn test_rfc8080_example() {
+    let raw = BASE64_STANDARD
+        .decode("l02Woi0iS8Aa25FQkUd9RMzZHJpBoRQwAQEX1SxZJA4=")
+        .unwrap();
+    let spki = [
+        &[
+            0x30, 0x2a, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x03, 0x21, 0x00,
+        ][..],
+        &raw,
+    ]
+    .concat();
+    let public_key = PublicKey::from_der(
+        &spki,
+        AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(EccCurves::Curve25519)),
+        Hash::Sha2(Sha2Bits::Sha512),
+    )
+    .unwrap();
+    let key = ZoneKey::new("example.com.", &public_key, KeyRole::KeySigning).unwrap();
+    assert_eq!(key.algorithm(), DnssecAlgorithm::Ed25519);
+    assert_eq!(key.key_tag(), 3613);
+    assert_eq!(
+        hex(&key.ds_rdata(DsDigest::Sha256)[4..]),
+        "3aa5ab37efce57f737fc1627013fee07bdf241bd10f3b1964ab55c78e79a304b"
+    );
+
+    let mx = [&[0, 10][..], b"\x04mail\x07example\x03com\x00"].concat();
+    let rrset = RrSet::new("example.com.", TYPE_MX, 3600, vec![mx]).unwrap();
+    let rrsig = rrsig(
+        (TYPE_MX, 15, 2, 3600, 1440021600, 1438207200, 3613),
+        b"\x07example\x03com\x00",
+        "oL9krJun7xfBOIWcGHi7mag5/hdZrKWw15jPGrHpjQeRAvTdszaPD+QLs3fx8A4M3e23mRZ9VrbpMngwcrqNAg==",
+    );
+    dnssec::verify_rrset(&rrsig, &key, &rrset, time(1439000000)).unwrap();
+}
+
+#[test]
+fn test_sign_rrset() {
+    let provider = p256();
+    let key = zone_key(&provider);
+    let rrset = RrSet::new(
+        "WWW.example.net",
+        TYPE_A,
+        300,
+        vec![vec![192, 0, 2, 2], vec![192, 0, 2, 1], vec![192, 0, 2, 2]],
+    )
+    .unwrap();
+    assert_eq!(rrset.rdatas(), [vec![192, 0, 2, 1], vec![192, 0, 2, 2]]);
+
+    let rrsig = dnssec::sign_rrset(
+        &provider,
+        &key,
+        &rrset,
+        time(1_800_000_000),
+        time(1_802_592_000),
+    )
+    .unwrap();
+    assert_eq!(rrsig.type_covered(), TYPE_A);
+    assert_eq!(rrsig.key_tag(), key.key_tag());
+    assert_eq!(rrsig.inception(), time(1_800_000_000));
+    assert_eq!(rrsig.expiration(), time(1_802_592_000));
+    assert_eq!(rrsig.signature().len(), 64);
+    assert_eq!(Rrsig::from_rdata(&rrsig.to_rdata()).unwrap(), rrsig);
+    dnssec::verify_rrset(&rrsig, &key, &rrset, time(1_801_000_000)).unwrap();
+
+    // The order of the records and the case of the name do not change the signed data.
+    let reordered = RrSet::new(
+        "www.EXAMPLE.net.",
+        TYPE_A,
+        300,
+        vec![vec![192, 0, 2, 1], vec![192, 0, 2, 2]],
+    )
+    .unwrap();
+    dnssec::verify_rrset(&rrsig, &key, &reordered, time(1_801_000_000)).unwrap();
+
+    for other in [
+        RrSet::new("www.example.net.", TYPE_A, 300, vec![vec![192, 0, 2, 1]]).unwrap(),
+        RrSet::new("ftp.example.net.", TYPE_A, 300, rrset.rdatas().to_vec()).unwrap(),
+        RrSet::new("www.example.net.", TYPE_TXT, 300, rrset.rdatas().to_vec()).unwrap(),
+    ] {
+        assert!(matches!(
+            dnssec::verify_rrset(&rrsig, &key, &other, time(1_801_000_000)),
+            Err(SecurityModuleError::InvalidSignature)
+        ));
+    }
+    let other_key = zone_key(&p256());
+    assert!(matches!(
+        dnssec::verify_rrset(&rrsig, &other_key, &rrset, time(1_801_000_000)),
+        Err(SecurityModuleError::InvalidSignature)
+    ));
+}
+
+#[test]
+fn test_wildcards() {
+    let provider = p256();
+    let key = zone_key(&provider);
+    let rdatas = vec![b"\x05hello".to_vec()];
+    let wildcard = RrSet::new("*.example.net.", TYPE_TXT, 300, rdatas.clone()).unwrap();
+    let rrsig = dnssec::sign_rrset(
+        &provider,
+        &key,
+        &wildcard,
+        time(1_800_000_000),
+        time(1_802_592_000),
+    )
+    .unwrap();
+    assert_eq!(rrsig.to_rdata()[3], 2);
+
+    let expanded = RrSet::new("a.b.example.net.", TYPE_TXT, 300, rdatas).unwrap();
+    dnssec::verify_rrset(&rrsig, &key, &expanded, time(1_801_000_000)).unwrap();
+    let apex = RrSet::new("example.net.", TYPE_TXT, 300, vec![b"\x05hello".to_vec()]).unwrap();
+    assert!(dnssec::verify_rrset(&rrsig, &key, &apex, time(1_801_000_000)).is_err());
+}
+
+#[test]
+fn test_constraints() {
+    let provider = p256();
+    let key = zone_key(&provider);
+    let rrset = RrSet::new("www.example.org.", TYPE_A, 300, vec![vec![192, 0, 2, 1]]).unwrap();
+    let rrset_in_zone =
+        RrSet::new("www.example.net.", TYPE_A, 300, vec![vec![192, 0, 2, 1]]).unwrap();
+    for (rrset, inception, expiration) in [
+        (&rrset, time(1_800_000_000), time(1_802_592_000)),
+        (&rrset_in_zone, time(1_800_000_000), time(1_800_000_000)),
+        (&rrset_in_zone, time(1_800_000_000), time(1 << 32)),
+    ] {
+        assert!(matches!(
+            dnssec::sign_rrset(&provider, &key, rrset, inception, expiration),
+            Err(SecurityModuleError::SigningError(_))
+        ));
+    }
+    assert!(matches!(
+        dnssec::sign_rrset(&p256(), &key, &rrset_in_zone, time(0), time(1)),
+        Err(SecurityModuleError::InvalidPublicKey)
+    ));
+
+    let long_label = format!("{}.example.net.", "a".repeat(64));
+    let long_name = format!("{}example.net.", "abcdefgh.".repeat(28));
+    for name in [
+        "www..example.net.",
+        "www example.net.",
+        &long_label,
+        &long_name,
+    ] {
+        assert!(matches!(
+            RrSet::new(name, TYPE_A, 300, vec![vec![192, 0, 2, 1]]),
+            Err(SecurityModuleError::SigningError(_))
+        ));
+    }
+    assert!(RrSet::new("www.example.net.", TYPE_A, 300, Vec::new()).is_err());
+    assert!(RrSet::new("www.example.net.", TYPE_A, 300, vec![vec![0; 65536]]).is_err());
+
+    for (algorithm, hash) in [
+        (
+            AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(EccCurves::P256)),
+            Sha2Bits::Sha384,
+        ),
+        (
+            AsymmetricEncryption::Rsa(KeyBits::Bits1024),
+            Sha2Bits::Sha256,
+        ),
+        (
+            AsymmetricEncryption::Rsa(KeyBits::Bits2048),
+            Sha2Bits::Sha384,
+        ),
+    ] {
+        let provider = self::provider(algorithm, hash);
+        let public_key = provider.key_metadata().unwrap().public_key().clone();
+        assert!(matches!(
+            ZoneKey::new("example.net.", &public_key, KeyRole::ZoneSigning),
+            Err(SecurityModuleError::UnsupportedAlgorithm)
+        ));
+    }
+    let rsa = self::provider(
+        AsymmetricEncryption::Rsa(KeyBits::Bits2048),
+        Sha2Bits::Sha256,
+    );
+    let rsa_key = zone_key(&rsa);
+    assert_eq!(rsa_key.algorithm(), DnssecAlgorithm::RsaSha256);
+    let rrsig = dnssec::sign_rrset(
+        &rsa,
+        &rsa_key,
+        &rrset_in_zone,
+        time(1_800_000_000),
+        time(1_802_592_000),
+    )
+    .unwrap();
+    dnssec::verify_rrset(&rrsig, &rsa_key, &rrset_in_zone, time(1_801_000_000)).unwrap();
+}
@@ -0,0 +1,205 @@
+use crate::{
+    common::{
+        crypto::algorithms::{
+            encryption::AsymmetricEncryption,
+            hashes::{Hash, Sha2Bits},
+            KeyBits,
+        },
+        dual_control::{
+            ApprovalDecision, ApprovalRecord, CallbackApprover, DualControlPolicy,
+            DualControlProvider,
+        },
+        latency::ProviderOperation,
+        traits::{key_handle::KeyHandle, module_provider::Provider},
+    },
+    mock::{MockConfig, MockProvider},
+    SecurityModuleError,
+};
+use std::sync::{
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+    Arc, Mutex,
+};
+
+fn approver(id: &'static str, approve: bool) -> CallbackApprover {
+    CallbackApprover::new(id, move |_| Ok(approve))
+}
+
+fn dual_control(
+    policy: DualControlPolicy,
+) -> (DualControlProvider, Arc<Mutex<Vec<ApprovalRecord>>>) {
+    let mut mock = MockProvider::new("release-signing".to_owned());
+    mock.initialize_module().unwrap();
+    let records = Arc::new(Mutex::new(Vec::new()));
+    let sink = records.clone();
+    let mut provider = DualControlProvider::new(
+        Arc::new(Mutex::new(mock)),
+        "release-signing".to_owned(),
+        policy,
+    )
+    .with_record_sink(move |record| sink.lock().unwrap().push(record.clone()));
+    provider
+        .create_key("release-signing", Box::new(MockConfig::default()))
+        .unwrap();
+    (provider, records)
+}
+
+#[test]
+fn test_signs_after_two_approvals() {
+    let asked = Arc::new(AtomicUsize::new(0));
+    let carol_asked = asked.clone();
+    let policy = DualControlPolicy::builder(["release-signing"])
+        .with_approver(approver("alice", true))
+        .with_approver(CallbackApprover::new("bob", |request| {
+            assert_eq!(request.key_id, "release-signing");
+            assert_eq!(request.operation, ProviderOperation::SignData);
+            Ok(request.data == b"release 1.0")
+        }))
+        .with_approver(CallbackApprover::new("carol", move |_| {
+            carol_asked.fetch_add(1, Ordering::SeqCst);
+            Ok(true)
+        }))
+        .build()
+        .unwrap();
+    let (provider, records) = dual_control(policy);
+
+    let signature = provider.sign_data(b"release 1.0").unwrap();
+    assert!(provider
+        .verify_signature(b"release 1.0", &signature)
+        .unwrap());
+    // Two approvals suffice, the third approver is not asked.
+    assert_eq!(asked.load(Ordering::SeqCst), 0);
+
+    let records = records.lock().unwrap();
+    assert_eq!(records.len(), 1);
+    assert!(records[0].granted);
+    assert_eq!(records[0].approvers().collect::<Vec<_>>(), ["alice", "bob"]);
+    assert_eq!(records[0].data_hash.len(), 64);
+}
+
+#[test]
+fn test_skips_declining_and_failing_approvers() {
+    let policy = DualControlPolicy::builder(["release-signing"])
+        .with_approver(approver("alice", true))
+        .with_approver(CallbackApprover::new("bob", |_| {
+            Err(SecurityModuleError::AuthenticationFailed(
+                "Prompt cancelled".to_owned(),
+            ))
+        }))
+        .with_approver(approver("carol", false))
+        .build()
+        .unwrap();
+    let (provider, records) = dual_control(policy);
+
+    assert!(matches!(
+        provider.sign_data(b"release 1.0"),
+        Err(SecurityModuleError::AuthenticationFailed(message))
+            if message.ends_with("but only 1 approved")
+    ));
+    // Signing in a context and exporting the key are controlled as well.
+    assert!(provider.sign_in_context("release", b"1.0").is_err());
+    assert!(matches!(
+        provider.export_private_key(),
+        Err(SecurityModuleError::AuthenticationFailed(_))
+    ));
+
+    let records = records.lock().unwrap();
+    assert_eq!(records.len(), 3);
+    assert!(!records[0].granted);
+    assert_eq!(records[0].decisions[0].decision, ApprovalDecision::Approved);
+    assert!(matches!(
+        records[0].decisions[1].decision,
+        ApprovalDecision::Failed { code: 14, .. }
+    ));
+    assert_eq!(records[0].decisions[2].decision, ApprovalDecision::Declined);
+    assert_eq!(records[2].operation, ProviderOperation::ExportPrivateKey);
+}
+
+#[test]
+fn test_other_keys_are_not_controlled() {
+    let asked = Arc::new(AtomicBool::new(false));
+    let flag = asked.clone();
+    let policy = DualControlPolicy::builder(["release-signing"])
+        .with_approver(CallbackApprover::new("alice", move |_| {
+            flag.store(true, Ordering::SeqCst);
+            Ok(false)
+        }))
+        .with_approver(approver("bob", false))
+        .build()
+        .unwrap();
+    let (mut provider, records) = dual_control(policy);
+
+    provider
+        .create_key(
+            "telemetry",
+            MockConfig::new(
+                AsymmetricEncryption::Rsa(KeyBits::Bits2048),
+                Hash::Sha2(Sha2Bits::Sha256),
+            ),
+        )
+        .unwrap();
+    provider.sign_data(b"telemetry").unwrap();
+    provider.sign_in_context("telemetry", b"data").unwrap();
+    assert!(!asked.load(Ordering::SeqCst));
+    assert!(records.lock().unwrap().is_empty());
+}
+
+#[test]
+fn test_failed_loads_keep_the_key_controlled() {
+    let policy = DualControlPolicy::builder(["release-signing"])
+        .with_approver(approver("alice", false))
+        .with_approver(approver("bob", false))
+        .build()
+        .unwrap();
+    let (mut provider, records) = dual_control(policy);
+
+    // The designated key stays loaded, so it must not sign without approvals.
+    assert!(provider
+        .load_key("telemetry", Box::new(MockConfig::default()))
+        .is_err());
+    assert!(matches!(
+        provider.sign_data(b"release 1.0"),
+        Err(SecurityModuleError::AuthenticationFailed(_))
+    ));
+    assert_eq!(records.lock().unwrap().len(), 1);
+}
+
+#[test]
+fn test_rejects_invalid_policies() {
+    let invalid = [
+        DualControlPolicy::builder(["key"])
+            .with_approver(approver("alice", true))
+            .required(1),
+        DualControlPolicy::builder(["key"])
+            .with_approver(approver("alice", true))
+            .with_approver(approver("alice", true)),
+        DualControlPolicy::builder(["key"])
+            .with_approver(approver("alice", true))
+            .with_approver(approver("bob", true))
+            .required(3),
+    ];
+    for builder in invalid {
+        assert!(matches!(
+            builder.build(),
+            Err(SecurityModuleError::InitializationError(_))
+        ));
+    }
+}
+
+#[test]
+fn test_record_serialization() {
+    let policy = DualControlPolicy::builder(["release-signing"])
+        .with_approver(approver("alice", true))
+        .with_approver(approver("bob", true))
+        .build()
+        .unwrap();
+    let (provider, records) = dual_control(policy);
+    provider.sign_data(b"release 1.0").unwrap();
+
+    let record = records.lock().unwrap()[0].clone();
+    let json = serde_json::to_string(&record).unwrap();
+    assert!(json.contains(r#"{"approver":"alice","decision":"approved"}"#));
+    assert_eq!(
+        serde_json::from_str::<ApprovalRecord>(&json).unwrap(),
+        record
+    );
+}
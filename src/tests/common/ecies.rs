@@ -0,0 +1,143 @@
+use crate::{
+    common::{
+        crypto::{
+            aead,
+            algorithms::{
+                encryption::{AsymmetricEncryption, EccCurves, EccSchemeAlgorithm},
+                hashes::{Hash, Sha2Bits},
+                KeyBits,
+            },
+            envelope::{AeadAlgorithm, Envelope, EnvelopeRef},
+            kdf::Kdf,
+            public_key::PublicKey,
+        },
+        ecies::{self, encrypt_for, encrypt_for_with, recipient_id},
+        traits::{key_handle::KeyHandle, module_provider::Provider},
+    },
+    mock::{MockConfig, MockProvider},
+    SecurityModuleError,
+};
+
+fn device(key_algorithm: AsymmetricEncryption) -> MockProvider {
+    MockProvider::with_key(
+        "device_key",
+        MockConfig::new(key_algorithm, Hash::Sha2(Sha2Bits::Sha256)),
+    )
+}
+
+fn p256_device() -> MockProvider {
+    device(AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(
+        EccCurves::P256,
+    )))
+}
+
+fn public_key(provider: &MockProvider) -> PublicKey {
+    provider.key_metadata().unwrap().public_key().clone()
+}
+
+#[test]
+fn test_derive_shared_secret() {
+    let (alice, bob) = (p256_device(), p256_device());
+    let alice_point = public_key(&alice).to_ec_point().unwrap();
+    let bob_point = public_key(&bob).to_ec_point().unwrap();
+    assert_eq!(alice_point.len(), 65);
+
+    let secret = alice.derive_shared_secret(&bob_point).unwrap();
+    assert_eq!(secret.len(), 32);
+    assert_eq!(bob.derive_shared_secret(&alice_point).unwrap(), secret);
+
+    assert!(matches!(
+        alice.derive_shared_secret(&alice_point[..64]),
+        Err(SecurityModuleError::InvalidPublicKey)
+    ));
+    assert!(matches!(
+        device(AsymmetricEncryption::Rsa(KeyBits::Bits2048)).derive_shared_secret(&bob_point),
+        Err(SecurityModuleError::UnsupportedAlgorithm)
+    ));
+}
+
+#[test]
+fn test_exchange_messages_with_public_keys() {
+    let (alice, bob) = (p256_device(), p256_device());
+
+    let ciphertext = encrypt_for(&public_key(&bob), b"meet at noon").unwrap();
+    assert_eq!(ecies::decrypt(&bob, &ciphertext).unwrap(), b"meet at noon");
+    let reply = encrypt_for(&public_key(&alice), b"see you").unwrap();
+    assert_eq!(ecies::decrypt(&alice, &reply).unwrap(), b"see you");
+
+    // Only the recipient can decrypt.
+    assert!(matches!(
+        ecies::decrypt(&alice, &ciphertext),
+        Err(SecurityModuleError::DecryptionError(_))
+    ));
+
+    // Every message uses a fresh ephemeral key.
+    let again = encrypt_for(&public_key(&bob), b"meet at noon").unwrap();
+    assert_ne!(
+        EnvelopeRef::parse(&again).unwrap().ephemeral_public_key,
+        EnvelopeRef::parse(&ciphertext)
+            .unwrap()
+            .ephemeral_public_key
+    );
+}
+
+#[test]
+fn test_parameters_are_recorded_in_the_envelope() {
+    let bob = p256_device();
+    let ciphertext = encrypt_for(&public_key(&bob), b"message").unwrap();
+    let envelope = EnvelopeRef::parse(&ciphertext).unwrap();
+    assert_eq!(envelope.aead, ecies::DEFAULT_AEAD);
+    assert_eq!(envelope.kdf, Some(ecies::DEFAULT_KDF));
+    assert_eq!(envelope.salt.map(<[u8]>::len), Some(32));
+    assert_eq!(envelope.ephemeral_public_key.map(<[u8]>::len), Some(65));
+    assert_eq!(envelope.key_id, recipient_id(&public_key(&bob)).unwrap());
+    assert!(envelope.key_id.starts_with("ecies:"));
+
+    let ciphertext = encrypt_for_with(
+        &public_key(&bob),
+        b"message",
+        AeadAlgorithm::ChaCha20Poly1305,
+        Kdf::HkdfSha512,
+    )
+    .unwrap();
+    let envelope = EnvelopeRef::parse(&ciphertext).unwrap();
+    assert_eq!(envelope.aead, AeadAlgorithm::ChaCha20Poly1305);
+    assert_eq!(envelope.kdf, Some(Kdf::HkdfSha512));
+    assert_eq!(ecies::decrypt(&bob, &ciphertext).unwrap(), b"message");
+}
+
+#[test]
+fn test_invalid_messages_and_keys() {
+    let bob = p256_device();
+    let ciphertext = encrypt_for(&public_key(&bob), b"message").unwrap();
+
+    let mut modified = ciphertext.clone();
+    *modified.last_mut().unwrap() ^= 1;
+    assert!(ecies::decrypt(&bob, &modified).is_err());
+
+    // The parameters are authenticated as well.
+    let mut envelope = Envelope::from_bytes(&ciphertext).unwrap();
+    envelope.kdf = Some(Kdf::HkdfSha384);
+    assert!(ecies::decrypt(&bob, &envelope.to_bytes().unwrap()).is_err());
+
+    let plain = aead::seal(
+        Envelope::new(
+            AeadAlgorithm::Aes256Gcm,
+            "device_key",
+            aead::random_nonce(AeadAlgorithm::Aes256Gcm).unwrap(),
+        ),
+        &[0; 32],
+        b"message",
+    )
+    .unwrap();
+    assert!(matches!(
+        ecies::decrypt(&bob, &plain),
+        Err(SecurityModuleError::DecryptionError(_))
+    ));
+
+    let rsa = device(AsymmetricEncryption::Rsa(KeyBits::Bits2048));
+    assert!(matches!(
+        encrypt_for(&public_key(&rsa), b"message"),
+        Err(SecurityModuleError::InvalidPublicKey)
+    ));
+}
@@ -0,0 +1,123 @@
+use crate::{
+    common::{
+        crypto::key_spec::{KeyAlgorithm, KeyPurpose, KeySpec},
+        ephemeral_key::with_ephemeral_key,
+        key_id::MAX_KEY_ID_LEN,
+        latency::ProviderOperation,
+        traits::{key_handle::KeyHandle, module_provider::Provider},
+    },
+    mock::{MockConfig, MockProvider},
+    SecurityModuleError,
+};
+use std::panic::{self, AssertUnwindSafe};
+
+fn spec(label: &str) -> KeySpec {
+    KeySpec::builder()
+        .algorithm(KeyAlgorithm::EcP256)
+        .usage(KeyPurpose::Sign)
+        .label(label)
+        .build()
+        .unwrap()
+}
+
+fn provider() -> MockProvider {
+    let mut provider = MockProvider::new("ephemeral".to_owned());
+    provider.initialize_module().unwrap();
+    provider
+}
+
+#[test]
+fn test_deletes_key_after_use() {
+    let mut provider = provider();
+    let (key_id, signature, public_key) = with_ephemeral_key(
+        &mut provider,
+        &spec("handshake"),
+        MockConfig::from_spec,
+        |key| {
+            assert_eq!(key.list_keys().unwrap(), [key.key_id()]);
+            Ok((
+                key.key_id().to_owned(),
+                key.sign_data(b"transcript")?,
+                key.public_key()?,
+            ))
+        },
+    )
+    .unwrap();
+
+    assert!(key_id.starts_with("handshake."));
+    assert_eq!(key_id.len(), "handshake.".len() + 32);
+    assert!(public_key.verify(b"transcript", &signature).unwrap());
+    assert!(provider.list_keys().unwrap().is_empty());
+    assert!(matches!(
+        provider.sign_data(b"transcript"),
+        Err(SecurityModuleError::KeyError)
+    ));
+}
+
+#[test]
+fn test_deletes_key_on_error_and_panic() {
+    let mut provider = provider();
+    let result: Result<(), _> = with_ephemeral_key(
+        &mut provider,
+        &spec("handshake"),
+        MockConfig::from_spec,
+        |_| Err(SecurityModuleError::SigningFailed),
+    );
+    assert!(matches!(result, Err(SecurityModuleError::SigningFailed)));
+    assert!(provider.list_keys().unwrap().is_empty());
+
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        with_ephemeral_key(
+            &mut provider,
+            &spec("handshake"),
+            MockConfig::from_spec,
+            |_| -> Result<(), SecurityModuleError> { panic!("protocol aborted") },
+        )
+    }));
+    assert!(result.is_err());
+    assert!(provider.list_keys().unwrap().is_empty());
+}
+
+#[test]
+fn test_reports_failed_deletion() {
+    let mut provider = provider();
+    let controller = provider.controller();
+    controller.fail_times(ProviderOperation::DeleteKey, 1, || {
+        SecurityModuleError::InitializationError("Device removed".to_owned())
+    });
+    let result = with_ephemeral_key(
+        &mut provider,
+        &spec("handshake"),
+        MockConfig::from_spec,
+        |key| key.sign_data(b"transcript"),
+    );
+    assert!(matches!(
+        result,
+        Err(SecurityModuleError::InitializationError(_))
+    ));
+
+    // Keys whose creation fails are not deleted.
+    controller.fail_times(ProviderOperation::CreateKey, 1, || {
+        SecurityModuleError::InitializationError("Device removed".to_owned())
+    });
+    let result = with_ephemeral_key(
+        &mut provider,
+        &spec("handshake"),
+        MockConfig::from_spec,
+        |_| Ok(()),
+    );
+    assert!(result.is_err());
+    assert_eq!(controller.calls(ProviderOperation::DeleteKey), 1);
+}
+
+#[test]
+fn test_shortens_long_labels() {
+    let mut provider = provider();
+    let label = "a".repeat(MAX_KEY_ID_LEN);
+    let key_id = with_ephemeral_key(&mut provider, &spec(&label), MockConfig::from_spec, |key| {
+        Ok(key.key_id().to_owned())
+    })
+    .unwrap();
+    assert_eq!(key_id.len(), MAX_KEY_ID_LEN);
+    assert!(key_id.starts_with("aaaa"));
+}
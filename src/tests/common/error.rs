@@ -0,0 +1,126 @@
+//! Golden-file tests pinning the codes and messages of errors.
+//!
+//! Applications match on error codes and sometimes on messages, so changing either is a breaking
+//! change. If a change is intended, regenerate the golden files with
+//! `UPDATE_GOLDEN=1 cargo test --features macos tests::common::error` and review the diff.
+
+use crate::{common::crypto::envelope::AeadAlgorithm, SecurityModuleError};
+use crypto_layer_core::CoreError;
+use std::{collections::HashSet, env, fs, path::Path};
+
+/// An instance of every variant that exists without optional features.
+fn errors() -> Vec<SecurityModuleError> {
+    vec![
+        SecurityModuleError::SigningError("message".to_owned()),
+        SecurityModuleError::DecryptionError("message".to_owned()),
+        SecurityModuleError::EncryptionError("message".to_owned()),
+        SecurityModuleError::SignatureVerificationError("message".to_owned()),
+        SecurityModuleError::InitializationError("message".to_owned()),
+        SecurityModuleError::KeyError,
+        SecurityModuleError::UnsupportedAlgorithm,
+        SecurityModuleError::VerificationFailed,
+        SecurityModuleError::InvalidSignature,
+        SecurityModuleError::InvalidPublicKey,
+        SecurityModuleError::SigningFailed,
+        SecurityModuleError::SessionPoolTimeout,
+        SecurityModuleError::Encoding(CoreError::Truncated),
+        SecurityModuleError::AuthenticationFailed("message".to_owned()),
+        SecurityModuleError::InvalidKeySpec("message".to_owned()),
+        SecurityModuleError::InvalidKeyId("message".to_owned()),
+        SecurityModuleError::DeprecatedAlgorithm("message".to_owned()),
+        SecurityModuleError::SecretStorage("message".to_owned()),
+        SecurityModuleError::InvalidProof("message".to_owned()),
+        SecurityModuleError::InvalidToken("message".to_owned()),
+        SecurityModuleError::UnsupportedOperation("message".to_owned()),
+        SecurityModuleError::InputTooLarge("message".to_owned()),
+        SecurityModuleError::RollbackDetected("message".to_owned()),
+        SecurityModuleError::NotFipsApproved("message".to_owned()),
+    ]
+}
+
+/// An instance of every variant of `CoreError`.
+fn core_errors() -> Vec<CoreError> {
+    vec![
+        CoreError::Truncated,
+        CoreError::InvalidMagic,
+        CoreError::UnsupportedVersion(2),
+        CoreError::UnknownAlgorithm(AeadAlgorithm::ChaCha20Poly1305.id() + 1),
+        CoreError::UnknownField(0x07),
+        CoreError::DuplicateField(0x01),
+        CoreError::MissingField("nonce"),
+        CoreError::FieldTooLong,
+        CoreError::InvalidField("nonce"),
+        CoreError::InvalidSignatureEncoding,
+        CoreError::InvalidPublicKey,
+        CoreError::InvalidLength,
+        CoreError::UnsupportedKeyAlgorithm,
+        CoreError::InvalidSigningContext,
+        CoreError::MisplacedField(0x02),
+    ]
+}
+
+fn render<E: std::fmt::Debug + std::fmt::Display>(
+    errors: &[E],
+    code: impl Fn(&E) -> u32,
+) -> String {
+    errors
+        .iter()
+        .map(|error| format!("{}\t{:?}\t{}\n", code(error), error, error))
+        .collect()
+}
+
+/// Compares `actual` with the golden file `name`, or rewrites the file if `UPDATE_GOLDEN` is set.
+fn assert_golden(name: &str, actual: &str) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("src/tests/common/golden")
+        .join(name);
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(&path, actual).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(&path).unwrap_or_default();
+    assert!(
+        expected == actual,
+        "{} is outdated, error codes or messages changed:\n--- expected\n{expected}\n--- actual\n{actual}",
+        path.display()
+    );
+}
+
+fn assert_unique(codes: impl IntoIterator<Item = u32>) {
+    let mut seen = HashSet::new();
+    for code in codes {
+        assert!(seen.insert(code), "code {code} is used twice");
+    }
+}
+
+#[test]
+fn test_error_codes_and_messages() {
+    let errors = errors();
+    assert_unique(errors.iter().map(SecurityModuleError::code));
+    assert_golden("errors.txt", &render(&errors, SecurityModuleError::code));
+}
+
+#[test]
+fn test_core_error_codes_and_messages() {
+    let errors = core_errors();
+    assert_unique(errors.iter().map(|error| u32::from(error.code())));
+    assert_golden(
+        "core_errors.txt",
+        &render(&errors, |error| u32::from(error.code())),
+    );
+}
+
+#[test]
+#[cfg(feature = "tpm")]
+fn test_tpm_error_codes_and_messages() {
+    use crate::tpm::core::error::TpmError;
+
+    let errors = [
+        SecurityModuleError::Tpm(TpmError::InitializationError("message".to_owned())),
+        SecurityModuleError::Tpm(TpmError::UnsupportedOperation("message".to_owned())),
+    ];
+    assert_golden(
+        "tpm_errors.txt",
+        &render(&errors, SecurityModuleError::code),
+    );
+}
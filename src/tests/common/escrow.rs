@@ -0,0 +1,157 @@
+use crate::{
+    common::{
+        crypto::{
+            algorithms::{
+                encryption::{AsymmetricEncryption, EccCurves, EccSchemeAlgorithm},
+                hashes::{Hash, Sha2Bits},
+                KeyBits,
+            },
+            secret::SecretBytes,
+        },
+        escrow::{
+            export_keys, export_wrapped_keys, import_keys, import_wrapped_keys, Recovery,
+            RecoveryKey,
+        },
+        password::PasswordParams,
+        traits::{key_handle::KeyHandle, module_provider::Provider},
+    },
+    mock::{MockConfig, MockProvider},
+    SecurityModuleError,
+};
+use std::collections::BTreeMap;
+
+/// Keeps the tests fast, real applications should use `PasswordParams::MODERATE` or stronger.
+const PARAMS: PasswordParams = PasswordParams {
+    passes: 1,
+    memory_kib: PasswordParams::MIN_MEMORY_KIB,
+};
+
+fn provider(key_algorithm: AsymmetricEncryption) -> MockProvider {
+    MockProvider::with_key(
+        "key",
+        MockConfig::new(key_algorithm, Hash::Sha2(Sha2Bits::Sha256)),
+    )
+}
+
+fn device() -> MockProvider {
+    provider(AsymmetricEncryption::Rsa(KeyBits::Bits2048))
+}
+
+fn recovery_provider() -> MockProvider {
+    provider(AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(
+        EccCurves::P256,
+    )))
+}
+
+fn keys() -> BTreeMap<String, SecretBytes> {
+    BTreeMap::from([
+        ("backup".to_owned(), vec![1; 32].into()),
+        ("database".to_owned(), vec![2; 16].into()),
+        ("empty".to_owned(), SecretBytes::default()),
+    ])
+}
+
+#[test]
+fn test_public_key_recovery() {
+    let recovery = recovery_provider();
+    let public_key = recovery.key_metadata().unwrap().public_key().clone();
+
+    let bundle = export_keys(&keys(), &RecoveryKey::PublicKey(&public_key)).unwrap();
+    assert_eq!(
+        import_keys(&bundle, Recovery::KeyHandle(&recovery)).unwrap(),
+        keys()
+    );
+    assert!(import_keys(&bundle, Recovery::KeyHandle(&recovery_provider())).is_err());
+    assert!(import_keys(&bundle, Recovery::Passphrase(b"passphrase")).is_err());
+
+    let rsa = device().key_metadata().unwrap().public_key().clone();
+    assert!(matches!(
+        export_keys(&keys(), &RecoveryKey::PublicKey(&rsa)),
+        Err(SecurityModuleError::InvalidPublicKey)
+    ));
+}
+
+#[test]
+fn test_passphrase_recovery() {
+    let recovery_key = RecoveryKey::Passphrase {
+        passphrase: b"correct horse battery staple",
+        params: PARAMS,
+    };
+    let bundle = export_keys(&keys(), &recovery_key).unwrap();
+    assert_eq!(
+        import_keys(
+            &bundle,
+            Recovery::Passphrase(b"correct horse battery staple")
+        )
+        .unwrap(),
+        keys()
+    );
+    assert!(matches!(
+        import_keys(&bundle, Recovery::Passphrase(b"wrong")),
+        Err(SecurityModuleError::DecryptionError(_))
+    ));
+
+    let mut modified = bundle.clone();
+    *modified.last_mut().unwrap() ^= 1;
+    assert!(import_keys(
+        &modified,
+        Recovery::Passphrase(b"correct horse battery staple")
+    )
+    .is_err());
+
+    assert!(matches!(
+        export_keys(
+            &keys(),
+            &RecoveryKey::Passphrase {
+                passphrase: b"passphrase",
+                params: PasswordParams {
+                    passes: 0,
+                    ..PARAMS
+                },
+            }
+        ),
+        Err(SecurityModuleError::Encoding(_))
+    ));
+}
+
+#[test]
+fn test_restore_on_new_device() {
+    let (old_device, new_device, recovery) = (device(), device(), recovery_provider());
+    let public_key = recovery.key_metadata().unwrap().public_key().clone();
+    let wrapped_keys: BTreeMap<String, Vec<u8>> = keys()
+        .into_iter()
+        .map(|(name, key)| (name, old_device.encrypt_data(&key).unwrap()))
+        .collect();
+
+    let bundle = export_wrapped_keys(
+        &old_device,
+        &wrapped_keys,
+        &RecoveryKey::PublicKey(&public_key),
+    )
+    .unwrap();
+    let restored =
+        import_wrapped_keys(&new_device, &bundle, Recovery::KeyHandle(&recovery)).unwrap();
+
+    assert_eq!(restored.len(), 3);
+    for (name, key) in keys() {
+        assert_eq!(new_device.decrypt_data(&restored[&name]).unwrap(), key);
+        // The old device cannot decrypt the restored keys.
+        assert!(old_device.decrypt_data(&restored[&name]).is_err());
+    }
+}
+
+#[test]
+fn test_invalid_key_names() {
+    let public_key = recovery_provider()
+        .key_metadata()
+        .unwrap()
+        .public_key()
+        .clone();
+    for name in [String::new(), "a".repeat(256)] {
+        let keys = BTreeMap::from([(name, SecretBytes::zeroed(32))]);
+        assert!(matches!(
+            export_keys(&keys, &RecoveryKey::PublicKey(&public_key)),
+            Err(SecurityModuleError::EncryptionError(_))
+        ));
+    }
+}
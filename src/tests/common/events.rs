@@ -0,0 +1,195 @@
+use crate::{
+    common::{
+        events::{EventedProvider, KeyEvent, KeyEventKind, KeyEvents},
+        latency::ProviderOperation,
+        traits::{key_handle::KeyHandle, module_provider::Provider},
+    },
+    mock::{MockConfig, MockProvider},
+    SecurityModuleError,
+};
+use futures::{executor::block_on, StreamExt};
+use std::sync::{Arc, Mutex};
+
+/// Subscribes a callback collecting the kinds of events in memory.
+fn collect(events: &KeyEvents) -> Arc<Mutex<Vec<(String, KeyEventKind)>>> {
+    let collected = Arc::new(Mutex::new(Vec::new()));
+    let callback_collected = collected.clone();
+    events.subscribe(move |event| {
+        callback_collected
+            .lock()
+            .unwrap()
+            .push((event.key_id.clone(), event.kind))
+    });
+    collected
+}
+
+#[test]
+fn test_provider_publishes_created_and_auth_failed() {
+    let events = Arc::new(KeyEvents::new());
+    let collected = collect(&events);
+    let mock = MockProvider::new("event_key".to_owned());
+    let controller = mock.controller();
+    let mut provider = EventedProvider::new(
+        Arc::new(Mutex::new(mock)),
+        "event_key".to_owned(),
+        events.clone(),
+    );
+
+    provider.initialize_module().unwrap();
+    provider
+        .create_key("event_key", Box::new(MockConfig::default()))
+        .unwrap();
+    provider.sign_data(b"data").unwrap();
+    controller.fail(ProviderOperation::DecryptData, || {
+        SecurityModuleError::DecryptionError("Device removed".to_owned())
+    });
+    assert!(provider.decrypt_data(b"ciphertext").is_err());
+    controller.fail(ProviderOperation::SignData, || {
+        SecurityModuleError::AuthenticationFailed("Wrong PIN".to_owned())
+    });
+    assert!(provider.sign_data(b"data").is_err());
+
+    assert_eq!(
+        *collected.lock().unwrap(),
+        [
+            ("event_key".to_owned(), KeyEventKind::Created),
+            (
+                "event_key".to_owned(),
+                KeyEventKind::AuthFailed {
+                    operation: ProviderOperation::SignData
+                }
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_provider_publishes_deleted() {
+    let events = Arc::new(KeyEvents::new());
+    let collected = collect(&events);
+    let mut provider = EventedProvider::new(
+        Arc::new(Mutex::new(MockProvider::new("current".to_owned()))),
+        "current".to_owned(),
+        events.clone(),
+    );
+    provider.initialize_module().unwrap();
+    for key_id in ["old", "current"] {
+        provider
+            .create_key(key_id, Box::new(MockConfig::default()))
+            .unwrap();
+    }
+
+    provider.delete_key("old").unwrap();
+    assert!(matches!(
+        provider.delete_key("old"),
+        Err(SecurityModuleError::KeyError)
+    ));
+
+    assert_eq!(
+        collected.lock().unwrap()[2..],
+        [("old".to_owned(), KeyEventKind::Deleted)]
+    );
+    assert!(provider.sign_data(b"data").is_ok());
+}
+
+#[test]
+fn test_failed_loads_keep_the_current_key() {
+    let events = Arc::new(KeyEvents::new());
+    let collected = collect(&events);
+    let mock = MockProvider::new("event_key".to_owned());
+    let controller = mock.controller();
+    let mut provider = EventedProvider::new(
+        Arc::new(Mutex::new(mock)),
+        "event_key".to_owned(),
+        events.clone(),
+    );
+    provider.initialize_module().unwrap();
+    provider
+        .create_key("event_key", Box::new(MockConfig::default()))
+        .unwrap();
+
+    let wrong_pin = || SecurityModuleError::AuthenticationFailed("Wrong PIN".to_owned());
+    controller.fail(ProviderOperation::LoadKey, wrong_pin);
+    assert!(provider
+        .load_key("other", Box::new(MockConfig::default()))
+        .is_err());
+    controller.fail(ProviderOperation::SignData, wrong_pin);
+    assert!(provider.sign_data(b"data").is_err());
+
+    let auth_failed = |operation| KeyEventKind::AuthFailed { operation };
+    assert_eq!(
+        collected.lock().unwrap()[1..],
+        [
+            ("other".to_owned(), auth_failed(ProviderOperation::LoadKey)),
+            (
+                "event_key".to_owned(),
+                auth_failed(ProviderOperation::SignData)
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_stream_receives_published_events() {
+    let events = KeyEvents::new();
+    let mut stream = events.stream();
+
+    events.publish(KeyEvent::new("key_1", KeyEventKind::Rotated));
+    events.publish(KeyEvent::new("key_2", KeyEventKind::Deleted));
+
+    let first = block_on(stream.next()).unwrap();
+    let second = block_on(stream.next()).unwrap();
+    assert_eq!(
+        (first.key_id.as_str(), first.kind),
+        ("key_1", KeyEventKind::Rotated)
+    );
+    assert_eq!(
+        (second.key_id.as_str(), second.kind),
+        ("key_2", KeyEventKind::Deleted)
+    );
+}
+
+#[test]
+fn test_dropped_stream_is_unsubscribed() {
+    let events = KeyEvents::new();
+    let stream = events.stream();
+    drop(stream);
+
+    events.publish(KeyEvent::new("key", KeyEventKind::Disabled));
+
+    assert_eq!(
+        format!("{:?}", events),
+        "KeyEvents { callbacks: 0, streams: 0 }"
+    );
+}
+
+#[test]
+fn test_unsubscribe() {
+    let events = KeyEvents::new();
+    let collected = Arc::new(Mutex::new(0));
+    let callback_collected = collected.clone();
+    let id = events.subscribe(move |_| *callback_collected.lock().unwrap() += 1);
+
+    events.publish(KeyEvent::new("key", KeyEventKind::Created));
+    assert!(events.unsubscribe(id));
+    assert!(!events.unsubscribe(id));
+    events.publish(KeyEvent::new("key", KeyEventKind::Created));
+
+    assert_eq!(*collected.lock().unwrap(), 1);
+}
+
+#[test]
+fn test_callback_may_subscribe() {
+    let events = Arc::new(KeyEvents::new());
+    let callback_events = events.clone();
+    events.subscribe(move |_| {
+        callback_events.subscribe(|_| {});
+    });
+
+    events.publish(KeyEvent::new("key", KeyEventKind::Created));
+
+    assert_eq!(
+        format!("{:?}", events),
+        "KeyEvents { callbacks: 2, streams: 0 }"
+    );
+}
@@ -0,0 +1,125 @@
+use crate::{
+    common::{
+        crypto::{
+            algorithms::{
+                encryption::AsymmetricEncryption,
+                hashes::{Hash, Sha2Bits},
+                KeyBits,
+            },
+            envelope::EnvelopeRef,
+        },
+        field_encryption::{FieldContext, FieldEncryptor},
+    },
+    mock::{MockConfig, MockProvider},
+    SecurityModuleError,
+};
+
+fn provider() -> MockProvider {
+    MockProvider::with_key(
+        "db_key",
+        MockConfig::new(
+            AsymmetricEncryption::Rsa(KeyBits::Bits2048),
+            Hash::Sha2(Sha2Bits::Sha256),
+        ),
+    )
+}
+
+#[test]
+fn test_randomized_fields() {
+    let provider = provider();
+    let (fields, wrapped_root_key) = FieldEncryptor::generate(&provider, "db_key").unwrap();
+    let address = FieldContext::new("users", "address").row("42");
+
+    let ciphertext = fields.encrypt(&address, b"Main Street 1").unwrap();
+    assert_eq!(
+        fields.decrypt(&address, &ciphertext).unwrap(),
+        b"Main Street 1"
+    );
+    assert_ne!(
+        fields.encrypt(&address, b"Main Street 1").unwrap(),
+        ciphertext
+    );
+    assert_eq!(EnvelopeRef::parse(&ciphertext).unwrap().key_id, "db_key");
+
+    // The root key can be restored from its encrypted form.
+    let restored = FieldEncryptor::unwrap(&provider, "db_key", &wrapped_root_key).unwrap();
+    assert_eq!(
+        restored.decrypt(&address, &ciphertext).unwrap(),
+        b"Main Street 1"
+    );
+    assert!(FieldEncryptor::unwrap(&self::provider(), "db_key", &wrapped_root_key).is_err());
+    assert!(!format!("{:?}", fields).contains("root_key"));
+}
+
+#[test]
+fn test_fields_are_bound_to_their_context() {
+    let (fields, _) = FieldEncryptor::generate(&provider(), "db_key").unwrap();
+    let address = FieldContext::new("users", "address").row("42");
+    let ciphertext = fields.encrypt(&address, b"Main Street 1").unwrap();
+
+    for other in [
+        FieldContext::new("users", "address").row("43"),
+        FieldContext::new("users", "address"),
+        FieldContext::new("users", "city").row("42"),
+        FieldContext::new("admins", "address").row("42"),
+        // Parts are length-prefixed, so moving bytes between them changes the context.
+        FieldContext::new("usersa", "ddress").row("42"),
+    ] {
+        assert!(
+            matches!(
+                fields.decrypt(&other, &ciphertext),
+                Err(SecurityModuleError::DecryptionError(_))
+            ),
+            "{:?} was accepted",
+            other
+        );
+    }
+
+    let (other_fields, _) = FieldEncryptor::generate(&provider(), "db_key").unwrap();
+    assert!(other_fields.decrypt(&address, &ciphertext).is_err());
+
+    let mut modified = ciphertext.clone();
+    *modified.last_mut().unwrap() ^= 1;
+    assert!(fields.decrypt(&address, &modified).is_err());
+}
+
+#[test]
+fn test_deterministic_fields() {
+    let (fields, _) = FieldEncryptor::generate(&provider(), "db_key").unwrap();
+    let email = FieldContext::new("users", "email");
+
+    let ciphertext = fields
+        .encrypt_deterministic(&email, b"alice@example.com")
+        .unwrap();
+    assert_eq!(
+        fields
+            .encrypt_deterministic(&email, b"alice@example.com")
+            .unwrap(),
+        ciphertext
+    );
+    assert_ne!(
+        fields
+            .encrypt_deterministic(&email, b"bob@example.com")
+            .unwrap(),
+        ciphertext
+    );
+    assert_eq!(
+        fields.decrypt(&email, &ciphertext).unwrap(),
+        b"alice@example.com"
+    );
+
+    // Equal plaintexts of other columns have other ciphertexts.
+    let backup_email = FieldContext::new("users", "backup_email");
+    assert_ne!(
+        fields
+            .encrypt_deterministic(&backup_email, b"alice@example.com")
+            .unwrap(),
+        ciphertext
+    );
+    assert!(fields.decrypt(&backup_email, &ciphertext).is_err());
+
+    assert!(matches!(
+        fields.encrypt_deterministic(&email.row("42"), b"alice@example.com"),
+        Err(SecurityModuleError::EncryptionError(_))
+    ));
+}
@@ -0,0 +1,197 @@
+use crate::{
+    common::{
+        crypto::algorithms::{
+            encryption::AsymmetricEncryption,
+            hashes::{Hash, Sha2Bits},
+            KeyBits,
+        },
+        file_encryption::{
+            decrypt_file, decrypt_stream, encrypt_file, encrypt_stream, FileManifest,
+            DEFAULT_CHUNK_SIZE,
+        },
+    },
+    mock::{MockConfig, MockProvider},
+    SecurityModuleError,
+};
+
+const CHUNK_SIZE: usize = 16;
+
+fn rsa_provider() -> MockProvider {
+    MockProvider::with_key(
+        "file_key",
+        MockConfig::new(
+            AsymmetricEncryption::Rsa(KeyBits::Bits2048),
+            Hash::Sha2(Sha2Bits::Sha256),
+        ),
+    )
+}
+
+fn encrypt(provider: &MockProvider, plaintext: &[u8]) -> Vec<u8> {
+    let mut encrypted = Vec::new();
+    encrypt_stream(provider, "file_key", plaintext, &mut encrypted, CHUNK_SIZE).unwrap();
+    encrypted
+}
+
+fn decrypt(provider: &MockProvider, encrypted: &[u8]) -> Result<Vec<u8>, SecurityModuleError> {
+    let mut plaintext = Vec::new();
+    decrypt_stream(provider, encrypted, &mut plaintext)?;
+    Ok(plaintext)
+}
+
+/// Splits an encrypted file into its length-prefixed header and its frames.
+fn split(encrypted: &[u8]) -> (Vec<u8>, Vec<Vec<u8>>) {
+    let header_len = 4 + u32::from_be_bytes(encrypted[..4].try_into().unwrap()) as usize;
+    let mut frames = Vec::new();
+    let mut rest = &encrypted[header_len..];
+    while !rest.is_empty() {
+        let len = 5 + u32::from_be_bytes(rest[1..5].try_into().unwrap()) as usize;
+        frames.push(rest[..len].to_vec());
+        rest = &rest[len..];
+    }
+    (encrypted[..header_len].to_vec(), frames)
+}
+
+fn join(header: &[u8], frames: &[Vec<u8>]) -> Vec<u8> {
+    [header.to_vec(), frames.concat()].concat()
+}
+
+#[test]
+fn test_stream_round_trip() {
+    let provider = rsa_provider();
+    for len in [
+        0,
+        1,
+        CHUNK_SIZE - 1,
+        CHUNK_SIZE,
+        3 * CHUNK_SIZE,
+        3 * CHUNK_SIZE + 5,
+    ] {
+        let plaintext: Vec<u8> = (0..len).map(|i| i as u8).collect();
+        let mut encrypted = Vec::new();
+        let manifest = encrypt_stream(
+            &provider,
+            "file_key",
+            &plaintext[..],
+            &mut encrypted,
+            CHUNK_SIZE,
+        )
+        .unwrap();
+        let chunks = len.div_ceil(CHUNK_SIZE) as u64;
+        assert_eq!(
+            manifest,
+            FileManifest {
+                key_id: "file_key".to_owned(),
+                chunk_size: CHUNK_SIZE,
+                chunks,
+                plaintext_len: len as u64,
+            }
+        );
+        assert_eq!(split(&encrypted).1.len() as u64, chunks + 1);
+
+        let mut decrypted = Vec::new();
+        assert_eq!(
+            decrypt_stream(&provider, &encrypted[..], &mut decrypted).unwrap(),
+            manifest
+        );
+        assert_eq!(decrypted, plaintext, "{} bytes", len);
+    }
+
+    assert!(matches!(
+        encrypt_stream(&provider, "file_key", &b""[..], Vec::new(), 0),
+        Err(SecurityModuleError::EncryptionError(_))
+    ));
+}
+
+#[test]
+fn test_truncation_and_reordering_are_detected() {
+    let provider = rsa_provider();
+    let plaintext = [7; 3 * CHUNK_SIZE + 5];
+    let encrypted = encrypt(&provider, &plaintext);
+    let (header, frames) = split(&encrypted);
+    assert_eq!(frames.len(), 5);
+
+    let mut reordered = frames.clone();
+    reordered.swap(0, 1);
+    let mut without_chunk = frames.clone();
+    without_chunk.remove(1);
+    let mut without_last_chunk = frames.clone();
+    without_last_chunk.remove(3);
+
+    for (case, modified) in [
+        ("reordered", join(&header, &reordered)),
+        ("chunk removed", join(&header, &without_chunk)),
+        ("last chunk removed", join(&header, &without_last_chunk)),
+        ("manifest removed", join(&header, &frames[..4])),
+        ("cut in a frame", encrypted[..encrypted.len() - 3].to_vec()),
+        ("cut in the header", encrypted[..10].to_vec()),
+        ("trailing data", [&encrypted[..], &frames[4]].concat()),
+    ] {
+        assert!(
+            matches!(
+                decrypt(&provider, &modified),
+                Err(SecurityModuleError::DecryptionError(_))
+            ),
+            "{} was accepted",
+            case
+        );
+    }
+
+    // Frames cannot be moved into another file of the same key.
+    let (other_header, other_frames) = split(&encrypt(&provider, &plaintext));
+    assert!(decrypt(&provider, &join(&other_header, &frames)).is_err());
+    let mut mixed = frames.clone();
+    mixed[2] = other_frames[2].clone();
+    assert!(decrypt(&provider, &join(&header, &mixed)).is_err());
+}
+
+#[test]
+fn test_modified_files_are_rejected() {
+    let provider = rsa_provider();
+    let encrypted = encrypt(&provider, &[1; 2 * CHUNK_SIZE]);
+
+    for position in [6, encrypted.len() / 2, encrypted.len() - 1] {
+        let mut modified = encrypted.clone();
+        modified[position] ^= 0x01;
+        assert!(
+            decrypt(&provider, &modified).is_err(),
+            "byte {} was modified",
+            position
+        );
+    }
+
+    // Another key cannot unwrap the file key.
+    assert!(decrypt(&rsa_provider(), &encrypted).is_err());
+}
+
+#[test]
+fn test_encrypt_and_decrypt_file() {
+    let provider = rsa_provider();
+    let dir = std::env::temp_dir().join(format!("file_encryption_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let (input, encrypted, output) = (dir.join("input"), dir.join("input.enc"), dir.join("output"));
+    let plaintext: Vec<u8> = (0..2 * DEFAULT_CHUNK_SIZE + 100)
+        .map(|i| (i % 251) as u8)
+        .collect();
+    std::fs::write(&input, &plaintext).unwrap();
+
+    let manifest = encrypt_file(&provider, "file_key", &input, &encrypted).unwrap();
+    assert_eq!(manifest.chunks, 3);
+    assert_eq!(
+        decrypt_file(&provider, &encrypted, &output).unwrap(),
+        manifest
+    );
+    assert_eq!(std::fs::read(&output).unwrap(), plaintext);
+
+    // A truncated file leaves neither a plaintext nor a temporary file behind.
+    std::fs::remove_file(&output).unwrap();
+    let bytes = std::fs::read(&encrypted).unwrap();
+    std::fs::write(&encrypted, &bytes[..bytes.len() - 100]).unwrap();
+    assert!(decrypt_file(&provider, &encrypted, &output).is_err());
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 2);
+
+    assert!(matches!(
+        encrypt_file(&provider, "file_key", &dir.join("missing"), &output),
+        Err(SecurityModuleError::EncryptionError(_))
+    ));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
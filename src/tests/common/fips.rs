@@ -0,0 +1,176 @@
+use crate::{
+    common::{
+        crypto::{
+            algorithms::{
+                encryption::{
+                    AsymmetricEncryption, BlockCiphers, EccCurves, EccSchemeAlgorithm,
+                    SymmetricMode, TripleDesNumKeys,
+                },
+                hashes::{Hash, Sha2Bits, Sha3Bits},
+                KeyBits,
+            },
+            envelope::AeadAlgorithm,
+            key_spec::{KeyAlgorithm, KeyPurpose, KeySpec},
+        },
+        fips::{self, APPROVED_ALGORITHMS},
+        key_id::ValidatedProvider,
+        sunset::ALGORITHM_NAMES,
+        traits::{key_handle::KeyHandle, module_provider::Provider},
+    },
+    mock::{MockConfig, MockProvider},
+    SecurityModuleError,
+};
+use std::sync::{Arc, Mutex};
+
+const SHA256: Hash = Hash::Sha2(Sha2Bits::Sha256);
+
+fn ecdsa(curve: EccCurves) -> AsymmetricEncryption {
+    AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(curve))
+}
+
+fn refusal(result: Result<(), SecurityModuleError>) -> String {
+    match result {
+        Err(SecurityModuleError::NotFipsApproved(message)) => message,
+        result => panic!("Expected NotFipsApproved, got {:?}", result),
+    }
+}
+
+fn validated(fips_mode: bool) -> ValidatedProvider {
+    let mut mock = MockProvider::new("fips".to_owned());
+    mock.initialize_module().unwrap();
+    ValidatedProvider::new(Arc::new(Mutex::new(mock))).with_fips_mode(fips_mode)
+}
+
+#[test]
+fn test_approved_algorithms() {
+    for name in APPROVED_ALGORITHMS {
+        assert!(ALGORITHM_NAMES.contains(&name), "{}", name);
+    }
+
+    assert!(fips::check(Some(ecdsa(EccCurves::P384)), Some(SHA256)).is_ok());
+    assert!(fips::check(
+        Some(AsymmetricEncryption::Rsa(KeyBits::Bits3072)),
+        Some(Hash::Sha3(Sha3Bits::Sha3_256))
+    )
+    .is_ok());
+    assert_eq!(
+        refusal(fips::check(
+            Some(AsymmetricEncryption::Rsa(KeyBits::Bits1024)),
+            Some(SHA256)
+        )),
+        "rsa1024 is not approved in FIPS mode"
+    );
+    assert_eq!(
+        refusal(fips::check(Some(ecdsa(EccCurves::Curve25519)), None)),
+        "curve25519 is not approved in FIPS mode"
+    );
+    assert_eq!(
+        refusal(fips::check(Some(ecdsa(EccCurves::P256)), Some(Hash::Sha1))),
+        "sha1 is not approved in FIPS mode"
+    );
+    assert!(refusal(fips::check(Some(ecdsa(EccCurves::Secp256k1)), None)).contains("secp256k1"));
+    assert!(refusal(fips::check(None, Some(Hash::Md5))).contains("md5"));
+
+    assert!(fips::check_aead(AeadAlgorithm::Aes256Gcm).is_ok());
+    assert_eq!(
+        refusal(fips::check_aead(AeadAlgorithm::ChaCha20Poly1305)),
+        "chacha20_poly1305 is not approved in FIPS mode"
+    );
+}
+
+#[test]
+fn test_refuses_unapproved_keys() {
+    let mut provider = validated(true);
+
+    assert!(refusal(provider.create_key(
+        "legacy",
+        MockConfig::new(AsymmetricEncryption::Rsa(KeyBits::Bits1024), SHA256),
+    ))
+    .contains("rsa1024"));
+    assert!(refusal(provider.create_key(
+        "x25519",
+        MockConfig::new(ecdsa(EccCurves::Curve25519), SHA256)
+    ))
+    .contains("curve25519"));
+    assert!(refusal(
+        provider.load_key("sha1", MockConfig::new(ecdsa(EccCurves::P256), Hash::Sha1))
+    )
+    .contains("sha1"));
+    let spec = KeySpec::builder()
+        .algorithm(KeyAlgorithm::Rsa2048)
+        .usage(KeyPurpose::Encrypt)
+        .label("imported")
+        .hash(Hash::Sha1)
+        .build()
+        .unwrap();
+    assert!(
+        refusal(provider.import_wrapped_key("imported", b"wrapped", "kek", &spec)).contains("sha1")
+    );
+    assert!(provider.list_keys().unwrap().is_empty());
+
+    provider
+        .create_key("device", MockConfig::new(ecdsa(EccCurves::P256), SHA256))
+        .unwrap();
+    let signature = provider.sign_data(b"data").unwrap();
+    assert!(provider.verify_signature(b"data", &signature).unwrap());
+}
+
+#[test]
+fn test_refuses_unapproved_ciphers_and_unknown_configs() {
+    assert!(
+        fips::check_block_cipher(BlockCiphers::Aes(SymmetricMode::Cbc, KeyBits::Bits128)).is_ok()
+    );
+    assert_eq!(
+        refusal(fips::check_block_cipher(BlockCiphers::Aes(
+            SymmetricMode::Gcm,
+            KeyBits::Bits4096
+        ))),
+        "aes4096 is not approved in FIPS mode"
+    );
+    assert!(refusal(fips::check_block_cipher(BlockCiphers::TripleDes(
+        TripleDesNumKeys::Tdes3
+    )))
+    .contains("tdea"));
+    assert!(refusal(fips::check_block_cipher(BlockCiphers::Camellia(
+        SymmetricMode::Gcm,
+        KeyBits::Bits256
+    )))
+    .contains("camellia256"));
+
+    let aes = KeySpec::builder()
+        .algorithm(KeyAlgorithm::Aes256Gcm)
+        .usage(KeyPurpose::Encrypt)
+        .label("data")
+        .build()
+        .unwrap();
+    assert!(fips::check_key(&aes).is_ok());
+    assert!(refusal(fips::check_key(&"unknown config")).contains("cannot be checked"));
+
+    let mut provider = validated(true);
+    assert!(refusal(provider.create_key("unknown", Box::new(()))).contains("cannot be checked"));
+}
+
+#[cfg(feature = "tpm")]
+#[test]
+fn test_checks_the_cipher_of_tpm_keys() {
+    use crate::tpm::TpmConfig;
+
+    let config = |cipher| TpmConfig::new(ecdsa(EccCurves::P256), cipher, SHA256, Vec::new());
+    assert!(fips::check_key(
+        config(BlockCiphers::Aes(SymmetricMode::Cfb, KeyBits::Bits256)).as_ref()
+    )
+    .is_ok());
+    assert!(refusal(fips::check_key(config(BlockCiphers::Des).as_ref())).contains("des"));
+}
+
+#[test]
+fn test_unapproved_keys_outside_of_fips_mode() {
+    let mut provider = validated(false);
+    provider
+        .create_key(
+            "legacy",
+            MockConfig::new(ecdsa(EccCurves::P256), Hash::Sha1),
+        )
+        .unwrap();
+    assert!(provider.sign_data(b"data").is_ok());
+}
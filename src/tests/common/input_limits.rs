@@ -0,0 +1,130 @@
+use crate::{
+    common::{
+        crypto::envelope::{AeadAlgorithm, Envelope},
+        input_limits::{self, InputLimits, DEFAULT_MAX_FIELD_LEN},
+        key_id::ValidatedProvider,
+        latency::ProviderOperation,
+        traits::{key_handle::KeyHandle, module_provider::Provider},
+    },
+    mock::{MockConfig, MockProvider},
+    SecurityModuleError,
+};
+use std::sync::{Arc, Mutex};
+
+const LIMITS: InputLimits = InputLimits {
+    max_data_len: 64,
+    max_field_len: 16,
+    max_batch_len: 2,
+};
+
+fn envelope() -> Envelope {
+    let mut envelope = Envelope::new(AeadAlgorithm::Aes256Gcm, "key", vec![0; 12]);
+    envelope.ciphertext = vec![0; 16];
+    envelope
+}
+
+#[test]
+fn test_checks() {
+    assert!(LIMITS.check_data("data", 64).is_ok());
+    assert!(LIMITS.check_field("signature", 16).is_ok());
+    assert!(LIMITS.check_batch("batch", 2).is_ok());
+
+    assert!(matches!(
+        LIMITS.check_data("data", 65),
+        Err(SecurityModuleError::InputTooLarge(message))
+            if message == "The data has 65 bytes, at most 64 are allowed"
+    ));
+    assert!(matches!(
+        LIMITS.check_batch("batch", 3),
+        Err(SecurityModuleError::InputTooLarge(message))
+            if message == "The batch has 3 items, at most 2 are allowed"
+    ));
+    assert!(LIMITS.check_field("signature", 17).is_err());
+
+    assert!(LIMITS.validate().is_ok());
+    assert!(InputLimits::default().validate().is_ok());
+    assert!(matches!(
+        InputLimits {
+            max_field_len: 0,
+            ..LIMITS
+        }
+        .validate(),
+        Err(SecurityModuleError::InitializationError(_))
+    ));
+}
+
+#[test]
+fn test_validate_envelope() {
+    let bytes = envelope().to_bytes().unwrap();
+    assert_eq!(
+        input_limits::validate_envelope(&bytes).unwrap().key_id,
+        "key"
+    );
+
+    let mut oversized = envelope();
+    oversized.wrapped_key = Some(vec![0; DEFAULT_MAX_FIELD_LEN + 1]);
+    assert!(matches!(
+        input_limits::validate_envelope(&oversized.to_bytes().unwrap()),
+        Err(SecurityModuleError::InputTooLarge(message))
+            if message.starts_with("The wrapped key of the envelope has")
+    ));
+
+    // A nonce of the wrong length and a ciphertext shorter than the tag are malformed.
+    let nonce_at = bytes
+        .windows(12)
+        .position(|window| window == [0; 12])
+        .unwrap();
+    let mut truncated = bytes.clone();
+    truncated.truncate(bytes.len() - 1);
+    for malformed in [&bytes[..nonce_at], &truncated[..]] {
+        assert!(matches!(
+            input_limits::validate_envelope(malformed),
+            Err(SecurityModuleError::Encoding(_))
+        ));
+    }
+}
+
+#[test]
+fn test_validated_provider_rejects_large_inputs() {
+    let mut mock = MockProvider::new("limited".to_owned());
+    let controller = mock.controller();
+    mock.initialize_module().unwrap();
+    let mut provider = ValidatedProvider::new(Arc::new(Mutex::new(mock))).with_input_limits(LIMITS);
+    provider
+        .create_key("limited", Box::new(MockConfig::default()))
+        .unwrap();
+
+    let signature = provider.sign_data(&[1; 64]).unwrap();
+    assert!(matches!(
+        provider.sign_data(&[1; 65]),
+        Err(SecurityModuleError::InputTooLarge(_))
+    ));
+    assert!(matches!(
+        provider.encrypt_data(&[1; 65]),
+        Err(SecurityModuleError::InputTooLarge(_))
+    ));
+    assert!(matches!(
+        provider.decrypt_data(&[1; 65]),
+        Err(SecurityModuleError::InputTooLarge(_))
+    ));
+    assert!(matches!(
+        provider.verify_signature(&[1; 64], &[0; 17]),
+        Err(SecurityModuleError::InputTooLarge(_))
+    ));
+    assert!(matches!(
+        provider.derive_shared_secret(&[4; 17]),
+        Err(SecurityModuleError::InputTooLarge(_))
+    ));
+    let item: (&[u8], &[u8]) = (&[1; 64], &signature);
+    assert!(matches!(
+        provider.verify_many(&[item, item, item]),
+        Err(SecurityModuleError::InputTooLarge(_))
+    ));
+
+    assert_eq!(controller.calls(ProviderOperation::SignData), 1);
+    assert_eq!(controller.calls(ProviderOperation::EncryptData), 0);
+    assert_eq!(controller.calls(ProviderOperation::DecryptData), 0);
+    assert_eq!(controller.calls(ProviderOperation::VerifySignature), 0);
+    assert_eq!(controller.calls(ProviderOperation::DeriveSharedSecret), 0);
+    assert_eq!(controller.calls(ProviderOperation::VerifyMany), 0);
+}
@@ -0,0 +1,508 @@
+use crate::{
+    common::{
+        crypto::{
+            algorithms::{
+                encryption::{AsymmetricEncryption, EccCurves, EccSchemeAlgorithm},
+                hashes::{Hash, Sha2Bits},
+                KeyBits,
+            },
+            jwk,
+            kdf::Kdf,
+            public_key::PublicKey,
+            secret::SecretBytes,
+        },
+        interop::{
+            age::{self, Recipient},
+            jwe, sealed_box,
+        },
+        traits::{key_handle::KeyHandle, module_provider::Provider},
+    },
+    mock::{MockConfig, MockProvider},
+    SecurityModuleError,
+};
+use base64::{
+    prelude::{BASE64_STANDARD, BASE64_STANDARD_NO_PAD, BASE64_URL_SAFE_NO_PAD},
+    Engine,
+};
+use crypto_layer_core::CoreError;
+use openssl::{
+    aes::{wrap_key, AesKey},
+    bn::{BigNum, BigNumContext},
+    derive::Deriver,
+    ec::{EcGroup, EcKey, EcPoint, PointConversionForm},
+    hash::MessageDigest,
+    nid::Nid,
+    pkey::{HasPublic, Id, PKey, Private},
+    rand::rand_bytes,
+    sha::sha256,
+    sign::Signer,
+    symm::{encrypt, encrypt_aead, Cipher},
+};
+use serde_json::json;
+use std::any::Any;
+
+/// An X25519 key in software, as a security module with X25519 keys would hold it.
+#[derive(Debug)]
+struct X25519Key(PKey<Private>);
+
+impl X25519Key {
+    fn generate() -> Self {
+        Self(PKey::generate_x25519().unwrap())
+    }
+
+    fn public_key(&self) -> [u8; 32] {
+        self.0.raw_public_key().unwrap().try_into().unwrap()
+    }
+}
+
+impl KeyHandle for X25519Key {
+    fn derive_shared_secret(
+        &self,
+        peer_public_key: &[u8],
+    ) -> Result<SecretBytes, SecurityModuleError> {
+        let peer = PKey::public_key_from_raw_bytes(peer_public_key, Id::X25519)
+            .map_err(|_| SecurityModuleError::InvalidPublicKey)?;
+        let mut deriver = Deriver::new(&self.0).unwrap();
+        deriver.set_peer(&peer).unwrap();
+        Ok(deriver.derive_to_vec().unwrap().into())
+    }
+}
+
+fn provider(config: Box<dyn Any>) -> MockProvider {
+    MockProvider::with_key("recipient", config)
+}
+
+fn p256() -> MockProvider {
+    provider(Box::new(MockConfig::default()))
+}
+
+fn random(len: usize) -> Vec<u8> {
+    let mut bytes = vec![0; len];
+    rand_bytes(&mut bytes).unwrap();
+    bytes
+}
+
+fn b64(data: &[u8]) -> String {
+    BASE64_URL_SAFE_NO_PAD.encode(data)
+}
+
+fn hmac(digest: MessageDigest, key: &[u8], data: &[u8]) -> Vec<u8> {
+    let key = PKey::hmac(key).unwrap();
+    let mut signer = Signer::new(digest, &key).unwrap();
+    signer.update(data).unwrap();
+    signer.sign_to_vec().unwrap()
+}
+
+fn chacha20_poly1305(key: &[u8], nonce: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut tag = [0; 16];
+    let mut ciphertext = encrypt_aead(
+        Cipher::chacha20_poly1305(),
+        key,
+        Some(nonce),
+        &[],
+        data,
+        &mut tag,
+    )
+    .unwrap();
+    ciphertext.extend(tag);
+    ciphertext
+}
+
+/// Computes the ECDH shared secret of a fresh P-256 key and the key of `provider`, returning the
+/// ephemeral key and the shared secret.
+fn ecdh_p256(provider: &MockProvider) -> (EcKey<Private>, Vec<u8>) {
+    let der = provider
+        .key_metadata()
+        .unwrap()
+        .public_key()
+        .to_der()
+        .unwrap();
+    let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+    let ephemeral = EcKey::generate(&group).unwrap();
+    let shared_secret = derive(&ephemeral, PKey::public_key_from_der(&der).unwrap());
+    (ephemeral, shared_secret)
+}
+
+fn derive(key: &EcKey<Private>, peer: PKey<impl HasPublic>) -> Vec<u8> {
+    let key = PKey::from_ec_key(key.clone()).unwrap();
+    let mut deriver = Deriver::new(&key).unwrap();
+    deriver.set_peer(&peer).unwrap();
+    deriver.derive_to_vec().unwrap()
+}
+
+fn point(key: &EcKey<Private>, form: PointConversionForm) -> Vec<u8> {
+    key.public_key()
+        .to_bytes(key.group(), form, &mut BigNumContext::new().unwrap())
+        .unwrap()
+}
+
+/// Encodes an age file with the `(type and arguments, body)` stanzas, as the age tool does.
+fn age_file(stanzas: &[(String, Vec<u8>)], file_key: &[u8], plaintext: &[u8]) -> Vec<u8> {
+    let mut header = String::from("age-encryption.org/v1\n");
+    for (args, body) in stanzas {
+        header.push_str(&format!("-> {}\n", args));
+        let body = BASE64_STANDARD_NO_PAD.encode(body);
+        let mut lines: Vec<&str> = body
+            .as_bytes()
+            .chunks(64)
+            .map(|line| std::str::from_utf8(line).unwrap())
+            .collect();
+        if body.len() % 64 == 0 {
+            lines.push("");
+        }
+        for line in lines {
+            header.push_str(line);
+            header.push('\n');
+        }
+    }
+    header.push_str("---");
+    let mac_key = Kdf::HkdfSha256
+        .derive_vec(file_key, None, b"header", 32)
+        .unwrap();
+    let mac = hmac(MessageDigest::sha256(), &mac_key, header.as_bytes());
+    let mut file = format!("{} {}\n", header, BASE64_STANDARD_NO_PAD.encode(mac)).into_bytes();
+
+    let nonce = random(16);
+    let payload_key = Kdf::HkdfSha256
+        .derive_vec(file_key, Some(&nonce), b"payload", 32)
+        .unwrap();
+    file.extend(&nonce);
+    // An empty plaintext is encrypted as one empty chunk.
+    let mut chunks: Vec<&[u8]> = plaintext.chunks(64 * 1024).collect();
+    if chunks.is_empty() {
+        chunks.push(&[]);
+    }
+    for (index, chunk) in chunks.iter().enumerate() {
+        let mut nonce = [0; 12];
+        nonce[3..11].copy_from_slice(&(index as u64).to_be_bytes());
+        nonce[11] = (index == chunks.len() - 1) as u8;
+        file.extend(chacha20_poly1305(&payload_key, &nonce, chunk));
+    }
+    file
+}
+
+fn x25519_stanza(recipient: &[u8; 32], file_key: &[u8]) -> (String, Vec<u8>) {
+    let ephemeral = X25519Key::generate();
+    let shared_secret = ephemeral.derive_shared_secret(recipient).unwrap();
+    let salt = [&ephemeral.public_key()[..], recipient].concat();
+    let wrap_key = Kdf::HkdfSha256
+        .derive_vec(
+            &shared_secret,
+            Some(&salt),
+            b"age-encryption.org/v1/X25519",
+            32,
+        )
+        .unwrap();
+    (
+        format!(
+            "X25519 {}",
+            BASE64_STANDARD_NO_PAD.encode(ephemeral.public_key())
+        ),
+        chacha20_poly1305(&wrap_key, &[0; 12], file_key),
+    )
+}
+
+#[test]
+fn test_age_x25519() {
+    let recipient = X25519Key::generate();
+    let other = X25519Key::generate();
+    let file_key = random(16);
+    let plaintext = random(100_000);
+    let stanzas = [
+        ("scrypt c2FsdA 18".to_owned(), random(32)),
+        x25519_stanza(&other.public_key(), &file_key),
+        x25519_stanza(&recipient.public_key(), &file_key),
+    ];
+    let file = age_file(&stanzas, &file_key, &plaintext);
+    let identity = Recipient::X25519(recipient.public_key());
+    assert_eq!(
+        age::decrypt(&recipient, &identity, &file).unwrap(),
+        plaintext
+    );
+
+    let base64 = BASE64_STANDARD.encode(&file);
+    let lines: Vec<&str> = base64
+        .as_bytes()
+        .chunks(64)
+        .map(|line| std::str::from_utf8(line).unwrap())
+        .collect();
+    let armored = format!(
+        "-----BEGIN AGE ENCRYPTED FILE-----\n{}\n-----END AGE ENCRYPTED FILE-----\n",
+        lines.join("\n")
+    );
+    assert_eq!(
+        age::decrypt(&recipient, &identity, armored.as_bytes()).unwrap(),
+        plaintext
+    );
+
+    // Without a stanza for the key, and with a modified header or payload.
+    let file = age_file(&stanzas[..2], &file_key, &plaintext);
+    assert!(matches!(
+        age::decrypt(&recipient, &identity, &file),
+        Err(SecurityModuleError::DecryptionError(_))
+    ));
+    let mut file = age_file(&stanzas, &file_key, &plaintext);
+    let header_len = file.iter().position(|&byte| byte == b'\n').unwrap();
+    file[header_len - 1] = b'2';
+    assert!(matches!(
+        age::decrypt(&recipient, &identity, &file),
+        Err(SecurityModuleError::Encoding(CoreError::InvalidMagic))
+    ));
+    let mut file = age_file(&stanzas, &file_key, &plaintext);
+    let scrypt = file.windows(6).position(|w| w == b"scrypt").unwrap();
+    file[scrypt] = b'S';
+    assert!(matches!(
+        age::decrypt(&recipient, &identity, &file),
+        Err(SecurityModuleError::DecryptionError(_))
+    ));
+    let file = age_file(&stanzas, &file_key, &plaintext);
+    assert!(matches!(
+        age::decrypt(&recipient, &identity, &file[..file.len() - 64 * 1024]),
+        Err(SecurityModuleError::DecryptionError(_))
+    ));
+}
+
+#[test]
+fn test_age_piv_p256() {
+    let provider = p256();
+    let file_key = random(16);
+    let (ephemeral, shared_secret) = ecdh_p256(&provider);
+    let public_key = provider.key_metadata().unwrap().public_key().clone();
+    let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+    let mut ctx = BigNumContext::new().unwrap();
+    let recipient_point = EcPoint::from_bytes(&group, &public_key.to_ec_point().unwrap(), &mut ctx)
+        .unwrap()
+        .to_bytes(&group, PointConversionForm::COMPRESSED, &mut ctx)
+        .unwrap();
+    let ephemeral_point = point(&ephemeral, PointConversionForm::COMPRESSED);
+    let salt = [&ephemeral_point[..], &recipient_point].concat();
+    let wrap_key = Kdf::HkdfSha256
+        .derive_vec(&shared_secret, Some(&salt), b"piv-p256", 32)
+        .unwrap();
+    let stanza = (
+        format!(
+            "piv-p256 {} {}",
+            BASE64_STANDARD_NO_PAD.encode(&sha256(&recipient_point)[..4]),
+            BASE64_STANDARD_NO_PAD.encode(&ephemeral_point)
+        ),
+        chacha20_poly1305(&wrap_key, &[0; 12], &file_key),
+    );
+
+    let file = age_file(&[stanza], &file_key, b"");
+    let identity = Recipient::PivP256(public_key);
+    assert_eq!(age::decrypt(&provider, &identity, &file).unwrap(), b"");
+    // The tag of the stanza does not match other keys.
+    let other = Recipient::PivP256(p256().key_metadata().unwrap().public_key().clone());
+    assert!(matches!(
+        age::decrypt(&provider, &other, &file),
+        Err(SecurityModuleError::DecryptionError(_))
+    ));
+}
+
+#[test]
+fn test_sealed_box() {
+    use sodiumoxide::crypto::{box_, sealedbox};
+
+    let recipient = X25519Key::generate();
+    let sealed = sealedbox::seal(b"hello", &box_::PublicKey(recipient.public_key()));
+    assert_eq!(
+        sealed_box::open(&recipient, &recipient.public_key(), &sealed).unwrap(),
+        b"hello"
+    );
+
+    let other = X25519Key::generate();
+    assert!(matches!(
+        sealed_box::open(&other, &other.public_key(), &sealed),
+        Err(SecurityModuleError::DecryptionError(_))
+    ));
+    let mut modified = sealed.clone();
+    *modified.last_mut().unwrap() ^= 1;
+    assert!(matches!(
+        sealed_box::open(&recipient, &recipient.public_key(), &modified),
+        Err(SecurityModuleError::DecryptionError(_))
+    ));
+    assert!(matches!(
+        sealed_box::open(&recipient, &recipient.public_key(), &sealed[..40]),
+        Err(SecurityModuleError::Encoding(CoreError::Truncated))
+    ));
+}
+
+#[test]
+fn test_concat_kdf() {
+    // The example of RFC 7518, appendix C.
+    let decode = |data: &str| BASE64_URL_SAFE_NO_PAD.decode(data).unwrap();
+    let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+    let bob_d = BigNum::from_slice(&decode("VEmDZpDXXK8p8N0Cndsxs924q6nS1RXFASRl6BfUqdw")).unwrap();
+    let mut bob_public = EcPoint::new(&group).unwrap();
+    let ctx = BigNumContext::new().unwrap();
+    bob_public.mul_generator(&group, &bob_d, &ctx).unwrap();
+    let bob = EcKey::from_private_components(&group, &bob_d, &bob_public).unwrap();
+    let alice = EcKey::from_public_key_affine_coordinates(
+        &group,
+        &BigNum::from_slice(&decode("gI0GAILBdu7T53akrFmMyGcsF3n5dO7MmwNBHKW5SV0")).unwrap(),
+        &BigNum::from_slice(&decode("SLW_xSffzlPWrHEVI30DHM_4egVwt3NQqeUD7nMFpps")).unwrap(),
+    )
+    .unwrap();
+    let shared_secret = derive(&bob, PKey::from_ec_key(alice).unwrap());
+
+    let key = jwe::concat_kdf(&shared_secret, "A128GCM", b"Alice", b"Bob", 16);
+    assert_eq!(b64(&key), "VqqN6vgjbSBcIijNcacQGg");
+}
+
+#[test]
+fn test_jwe_rsa_oaep() {
+    let provider = provider(MockConfig::new(
+        AsymmetricEncryption::Rsa(KeyBits::Bits2048),
+        Hash::Sha2(Sha2Bits::Sha256),
+    ));
+    let protected = b64(br#"{"alg":"RSA-OAEP","enc":"A256GCM","kid":"device"}"#);
+    let cek = random(32);
+    let iv = random(12);
+    let mut tag = [0; 16];
+    let ciphertext = encrypt_aead(
+        Cipher::aes_256_gcm(),
+        &cek,
+        Some(&iv),
+        protected.as_bytes(),
+        b"The true sign of intelligence",
+        &mut tag,
+    )
+    .unwrap();
+    let compact = [
+        protected,
+        b64(&provider.encrypt_data(&cek).unwrap()),
+        b64(&iv),
+        b64(&ciphertext),
+        b64(&tag),
+    ]
+    .join(".");
+
+    let content = jwe::decrypt(&provider, &compact).unwrap();
+    assert_eq!(content.plaintext, b"The true sign of intelligence");
+    assert_eq!(content.header["kid"], "device");
+
+    let mut parts: Vec<&str> = compact.split('.').collect();
+    let modified_tag = b64(&[0; 16]);
+    parts[4] = &modified_tag;
+    assert!(matches!(
+        jwe::decrypt(&provider, &parts.join(".")),
+        Err(SecurityModuleError::DecryptionError(_))
+    ));
+    assert!(matches!(
+        jwe::decrypt(&provider, &compact[..compact.rfind('.').unwrap()]),
+        Err(SecurityModuleError::Encoding(_))
+    ));
+    let zip = [
+        &b64(br#"{"alg":"RSA-OAEP","enc":"A256GCM","zip":"DEF"}"#),
+        &parts[1..].join(".")[..],
+    ]
+    .join(".");
+    assert!(matches!(
+        jwe::decrypt(&provider, &zip),
+        Err(SecurityModuleError::UnsupportedAlgorithm)
+    ));
+}
+
+#[test]
+fn test_jwe_ecdh_es() {
+    let provider = p256();
+    let (ephemeral, shared_secret) = ecdh_p256(&provider);
+    let epk = jwk::to_jwk(
+        &PublicKey::from_ec_point(
+            &point(&ephemeral, PointConversionForm::UNCOMPRESSED),
+            AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(EccCurves::P256)),
+            Hash::Sha2(Sha2Bits::Sha256),
+        )
+        .unwrap(),
+        None,
+    )
+    .unwrap();
+
+    // ECDH-ES+A128KW and A128CBC-HS256 in the general JSON serialization, with a first recipient
+    // the key cannot decrypt.
+    let kek = jwe::concat_kdf(&shared_secret, "ECDH-ES+A128KW", b"device", b"server", 16);
+    let cek = random(32);
+    let mut encrypted_key = vec![0; 40];
+    wrap_key(
+        &AesKey::new_encrypt(&kek).unwrap(),
+        None,
+        &mut encrypted_key,
+        &cek,
+    )
+    .unwrap();
+    let protected = b64(br#"{"enc":"A128CBC-HS256"}"#);
+    let aad = b64(b"metadata");
+    let iv = random(16);
+    let ciphertext = encrypt(Cipher::aes_128_cbc(), &cek[16..], Some(&iv), b"Live long").unwrap();
+    let authenticated = [
+        format!("{}.{}", protected, aad).as_bytes(),
+        &iv,
+        &ciphertext,
+        &((protected.len() + 1 + aad.len()) as u64 * 8).to_be_bytes(),
+    ]
+    .concat();
+    let tag = hmac(MessageDigest::sha256(), &cek[..16], &authenticated);
+    let general = json!({
+        "protected": protected,
+        "unprotected": {"cty": "text/plain"},
+        "recipients": [
+            {"header": {"alg": "RSA-OAEP"}, "encrypted_key": b64(&random(256))},
+            {
+                "header": {"alg": "ECDH-ES+A128KW", "epk": epk, "apu": b64(b"device"), "apv": b64(b"server")},
+                "encrypted_key": b64(&encrypted_key),
+            },
+        ],
+        "aad": aad,
+        "iv": b64(&iv),
+        "ciphertext": b64(&ciphertext),
+        "tag": b64(&tag[..16]),
+    });
+    let content = jwe::decrypt(&provider, &general.to_string()).unwrap();
+    assert_eq!(content.plaintext, b"Live long");
+    assert_eq!(content.header["cty"], "text/plain");
+
+    let mut modified = general.clone();
+    modified["aad"] = json!(b64(b"other metadata"));
+    assert!(matches!(
+        jwe::decrypt(&provider, &modified.to_string()),
+        Err(SecurityModuleError::DecryptionError(_))
+    ));
+    let mut duplicate = general.clone();
+    duplicate["unprotected"]["enc"] = json!("A256GCM");
+    assert!(matches!(
+        jwe::decrypt(&provider, &duplicate.to_string()),
+        Err(SecurityModuleError::Encoding(_))
+    ));
+
+    // Direct ECDH-ES and A256GCM in the flattened JSON serialization, for another key.
+    let cek = jwe::concat_kdf(&shared_secret, "A256GCM", b"", b"", 32);
+    let protected = b64(json!({"alg": "ECDH-ES", "enc": "A256GCM", "epk": epk})
+        .to_string()
+        .as_bytes());
+    let iv = random(12);
+    let mut tag = [0; 16];
+    let ciphertext = encrypt_aead(
+        Cipher::aes_256_gcm(),
+        &cek,
+        Some(&iv),
+        protected.as_bytes(),
+        b"and prosper",
+        &mut tag,
+    )
+    .unwrap();
+    let flattened = json!({
+        "protected": protected,
+        "iv": b64(&iv),
+        "ciphertext": b64(&ciphertext),
+        "tag": b64(&tag),
+    })
+    .to_string();
+    assert_eq!(
+        jwe::decrypt(&provider, &flattened).unwrap().plaintext,
+        b"and prosper"
+    );
+    assert!(matches!(
+        jwe::decrypt(&p256(), &flattened),
+        Err(SecurityModuleError::DecryptionError(_))
+    ));
+}
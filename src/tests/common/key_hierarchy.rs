@@ -0,0 +1,96 @@
+use crate::{
+    common::{
+        crypto::algorithms::{
+            encryption::AsymmetricEncryption,
+            hashes::{Hash, Sha2Bits},
+            KeyBits,
+        },
+        key_hierarchy::{KeyHierarchy, DEFAULT_SUBKEY_LEN, MAX_DEPTH, MAX_SEGMENT_LEN},
+    },
+    mock::{MockConfig, MockProvider},
+    SecurityModuleError,
+};
+use crypto_layer_core::CoreError;
+
+fn provider() -> MockProvider {
+    MockProvider::with_key(
+        "master_kek",
+        MockConfig::new(
+            AsymmetricEncryption::Rsa(KeyBits::Bits2048),
+            Hash::Sha2(Sha2Bits::Sha256),
+        ),
+    )
+}
+
+#[test]
+fn test_deterministic_subkeys() {
+    let provider = provider();
+    let (hierarchy, wrapped_master) = KeyHierarchy::generate(&provider, "master_kek").unwrap();
+    let payments = hierarchy.derive_subkey("payments/v1").unwrap();
+    assert_eq!(payments.len(), DEFAULT_SUBKEY_LEN);
+    assert_eq!(hierarchy.derive_subkey("payments/v1").unwrap(), payments);
+
+    // The master secret is restored from its encrypted form.
+    let restored = KeyHierarchy::unwrap(&provider, "master_kek", &wrapped_master).unwrap();
+    assert_eq!(restored.key_id(), "master_kek");
+    assert_eq!(restored.derive_subkey("payments/v1").unwrap(), payments);
+    assert!(KeyHierarchy::unwrap(&self::provider(), "master_kek", &wrapped_master).is_err());
+    let (other, _) = KeyHierarchy::generate(&provider, "master_kek").unwrap();
+    assert_ne!(other.derive_subkey("payments/v1").unwrap(), payments);
+
+    // Different paths and lengths give unrelated keys.
+    for path in ["payments/v2", "payments", "v1/payments", "payments/v1/x"] {
+        assert_ne!(hierarchy.derive_subkey(path).unwrap(), payments, "{}", path);
+    }
+    let long = hierarchy.derive_subkey_with_len("payments/v1", 64).unwrap();
+    assert_eq!(long.len(), 64);
+    assert_ne!(long[..32], payments[..]);
+
+    // Subtrees derive the same keys as full paths.
+    let subtree = hierarchy.subtree("payments").unwrap();
+    assert_eq!(subtree.path(), "payments");
+    assert_eq!(subtree.derive_subkey("v1").unwrap(), payments);
+    let nested = hierarchy.subtree("a").unwrap().subtree("b/c").unwrap();
+    assert_eq!(nested.path(), "a/b/c");
+    assert_eq!(
+        nested.derive_subkey("d").unwrap(),
+        hierarchy.derive_subkey("a/b/c/d").unwrap()
+    );
+    assert!(!format!("{:?}", hierarchy).contains("node_key"));
+}
+
+#[test]
+fn test_rejected_paths() {
+    let (hierarchy, _) = KeyHierarchy::generate(&provider(), "master_kek").unwrap();
+    let long_segment = "a".repeat(MAX_SEGMENT_LEN + 1);
+    let too_deep = vec!["a"; MAX_DEPTH + 1].join("/");
+    for path in [
+        "",
+        "/payments",
+        "payments/",
+        "payments//v1",
+        "payments v1",
+        "payments/../v1",
+        ".hidden",
+        long_segment.as_str(),
+        too_deep.as_str(),
+    ] {
+        assert!(
+            matches!(
+                hierarchy.derive_subkey(path),
+                Err(SecurityModuleError::InvalidKeyId(_))
+            ),
+            "{}",
+            path
+        );
+    }
+    let deep = hierarchy.subtree(&vec!["a"; MAX_DEPTH].join("/")).unwrap();
+    assert!(matches!(
+        deep.derive_subkey("b"),
+        Err(SecurityModuleError::InvalidKeyId(_))
+    ));
+    assert!(matches!(
+        hierarchy.derive_subkey_with_len("payments", 0),
+        Err(SecurityModuleError::Encoding(CoreError::InvalidLength))
+    ));
+}
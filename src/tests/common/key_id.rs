@@ -0,0 +1,121 @@
+use crate::{
+    common::{
+        crypto::key_spec::{KeyAlgorithm, KeyPurpose, KeySpec},
+        factory::SecModules,
+        key_id::{KeyId, MAX_KEY_ID_LEN},
+    },
+    SecurityModuleError,
+};
+
+fn rejection(key_id: &str) -> String {
+    match KeyId::new(key_id) {
+        Err(SecurityModuleError::InvalidKeyId(message)) => message,
+        result => panic!("Expected InvalidKeyId, got {:?}", result),
+    }
+}
+
+#[test]
+fn test_valid_key_ids() {
+    for key_id in ["device-identity", "backup_2024.v1", "0", "A"] {
+        let parsed = KeyId::new(key_id).unwrap();
+        assert_eq!(parsed, key_id);
+        assert_eq!(parsed.to_string(), key_id);
+    }
+    assert!(KeyId::new("a".repeat(MAX_KEY_ID_LEN)).is_ok());
+
+    let key_id: KeyId = "device-identity".parse().unwrap();
+    assert_eq!(key_id.len(), "device-identity".len());
+    assert_eq!(String::from(key_id), "device-identity");
+}
+
+#[test]
+fn test_rejects_invalid_key_ids() {
+    assert_eq!(rejection(""), "The key id is empty");
+    assert!(rejection(&"a".repeat(MAX_KEY_ID_LEN + 1)).starts_with("The key id is longer than"));
+    assert_eq!(
+        rejection(".hidden"),
+        "'.hidden' does not start with a letter or digit"
+    );
+    assert_eq!(
+        rejection("tenant/key"),
+        "'tenant/key' contains '/', only ASCII letters, digits, '.', '-' and '_' are allowed"
+    );
+    for key_id in [
+        "-key",
+        "key one",
+        "schlüssel",
+        "key\n",
+        "key\\name",
+        "key:1",
+    ] {
+        assert!(
+            KeyId::try_from(key_id).is_err(),
+            "{:?} was accepted",
+            key_id
+        );
+    }
+}
+
+#[test]
+fn test_key_spec_label_is_key_id() {
+    let spec = KeySpec::builder()
+        .algorithm(KeyAlgorithm::EcP256)
+        .usage(KeyPurpose::Sign)
+        .label("device-identity")
+        .build()
+        .unwrap();
+    assert_eq!(spec.key_id().as_str(), spec.label());
+
+    let result = KeySpec::builder()
+        .algorithm(KeyAlgorithm::EcP256)
+        .usage(KeyPurpose::Sign)
+        .label("device identity")
+        .build();
+    assert!(matches!(
+        result,
+        Err(SecurityModuleError::InvalidKeySpec(message))
+            if message.starts_with("Label is not a valid key id: 'device identity' contains ' '")
+    ));
+}
+
+#[test]
+fn test_factory_rejects_invalid_key_ids() {
+    assert!(matches!(
+        SecModules::get_preferred_instance("../key".to_owned(), None),
+        Err(SecurityModuleError::InvalidKeyId(_))
+    ));
+}
+
+#[cfg(feature = "test-utils")]
+#[test]
+fn test_validated_provider() {
+    use crate::{
+        common::{
+            key_id::ValidatedProvider, latency::ProviderOperation,
+            traits::module_provider::Provider,
+        },
+        mock::{MockConfig, MockProvider},
+    };
+    use std::sync::{Arc, Mutex};
+
+    let mut mock = MockProvider::new("device-identity".to_owned());
+    let controller = mock.controller();
+    mock.initialize_module().unwrap();
+    let mut provider = ValidatedProvider::new(Arc::new(Mutex::new(mock)));
+
+    assert!(matches!(
+        provider.create_key("device identity", Box::new(MockConfig::default())),
+        Err(SecurityModuleError::InvalidKeyId(_))
+    ));
+    assert!(matches!(
+        provider.load_key("", Box::new(MockConfig::default())),
+        Err(SecurityModuleError::InvalidKeyId(_))
+    ));
+    assert_eq!(controller.calls(ProviderOperation::CreateKey), 0);
+    assert_eq!(controller.calls(ProviderOperation::LoadKey), 0);
+
+    provider
+        .create_key("device-identity", Box::new(MockConfig::default()))
+        .unwrap();
+    assert_eq!(provider.list_keys().unwrap(), ["device-identity"]);
+}
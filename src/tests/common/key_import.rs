@@ -0,0 +1,203 @@
+use crate::{
+    common::{
+        crypto::{
+            algorithms::{
+                encryption::{AsymmetricEncryption, EccCurves, EccSchemeAlgorithm},
+                hashes::{Hash, Sha2Bits},
+                KeyBits,
+            },
+            key_spec::{KeyAlgorithm, KeyPurpose, KeySpec},
+            public_key::PublicKey,
+        },
+        key_id::ValidatedProvider,
+        key_import,
+        namespace::{Namespace, NamespacedProvider},
+        sunset::{SunsetAction, SunsetPolicy},
+        traits::{key_handle::KeyHandle, module_provider::Provider},
+    },
+    mock::{MockConfig, MockProvider},
+    SecurityModuleError,
+};
+use crypto_layer_core::CoreError;
+use openssl::{
+    ec::{EcGroup, EcKey},
+    nid::Nid,
+    pkey::PKey,
+};
+use std::sync::{Arc, Mutex};
+
+/// Returns a provider holding the RSA wrapping key `wrapping` and its public key.
+fn provider() -> (MockProvider, PublicKey) {
+    let provider = MockProvider::with_key(
+        "wrapping",
+        MockConfig::new(
+            AsymmetricEncryption::Rsa(KeyBits::Bits2048),
+            Hash::Sha2(Sha2Bits::Sha256),
+        ),
+    );
+    let public_key = provider.key_metadata().unwrap().public_key().clone();
+    (provider, public_key)
+}
+
+/// Returns a new P-256 key as PKCS#8 DER and its DER encoded public key.
+fn p256_key() -> (Vec<u8>, Vec<u8>) {
+    let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+    let key = PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap();
+    (
+        key.private_key_to_pkcs8().unwrap(),
+        key.public_key_to_der().unwrap(),
+    )
+}
+
+fn spec(algorithm: KeyAlgorithm) -> KeySpec {
+    KeySpec::builder()
+        .algorithm(algorithm)
+        .label("imported")
+        .usage(KeyPurpose::Sign)
+        .build()
+        .unwrap()
+}
+
+#[test]
+fn test_import_wrapped_key() {
+    let (mut provider, wrapping_public_key) = provider();
+    let (pkcs8_der, public_key_der) = p256_key();
+    let wrapped_key = key_import::wrap_rsa_aes(&wrapping_public_key, &pkcs8_der).unwrap();
+    // The RSA-OAEP encrypted AES key is followed by the private key padded to 8 bytes and the
+    // 8 byte integrity check value of AES-KWP.
+    assert_eq!(
+        wrapped_key.len(),
+        256 + pkcs8_der.len().next_multiple_of(8) + 8
+    );
+    assert!(!wrapped_key
+        .windows(pkcs8_der.len())
+        .any(|window| window == pkcs8_der));
+
+    provider
+        .import_wrapped_key(
+            "imported",
+            &wrapped_key,
+            "wrapping",
+            &spec(KeyAlgorithm::EcP256),
+        )
+        .unwrap();
+    let metadata = provider.key_metadata().unwrap();
+    assert_eq!(metadata.key_id(), "imported");
+    assert_eq!(metadata.public_key().to_der().unwrap(), public_key_der);
+    let signature = provider.sign_data(b"data").unwrap();
+    assert!(metadata.public_key().verify(b"data", &signature).unwrap());
+    assert_eq!(provider.list_keys().unwrap(), ["imported", "wrapping"]);
+
+    // The wrapping key stays available.
+    provider
+        .load_key("wrapping", Box::new(MockConfig::default()))
+        .unwrap();
+    assert_eq!(
+        provider.key_metadata().unwrap().public_key().to_der().unwrap(),
+        wrapping_public_key.to_der().unwrap()
+    );
+}
+
+#[test]
+fn test_rejected_wrapped_keys() {
+    let (mut provider, wrapping_public_key) = provider();
+    let (pkcs8_der, _) = p256_key();
+    let wrapped_key = key_import::wrap_rsa_aes(&wrapping_public_key, &pkcs8_der).unwrap();
+    let p256 = spec(KeyAlgorithm::EcP256);
+
+    let mut tampered = wrapped_key.clone();
+    *tampered.last_mut().unwrap() ^= 1;
+    assert!(matches!(
+        provider.import_wrapped_key("imported", &tampered, "wrapping", &p256),
+        Err(SecurityModuleError::DecryptionError(_))
+    ));
+    let mut tampered = wrapped_key.clone();
+    tampered[0] ^= 1;
+    assert!(matches!(
+        provider.import_wrapped_key("imported", &tampered, "wrapping", &p256),
+        Err(SecurityModuleError::DecryptionError(_))
+    ));
+    assert!(matches!(
+        provider.import_wrapped_key("imported", &wrapped_key[..256], "wrapping", &p256),
+        Err(SecurityModuleError::Encoding(CoreError::Truncated))
+    ));
+    assert!(matches!(
+        provider.import_wrapped_key("imported", &wrapped_key, "other", &p256),
+        Err(SecurityModuleError::KeyError)
+    ));
+    assert!(matches!(
+        provider.import_wrapped_key(
+            "imported",
+            &wrapped_key,
+            "wrapping",
+            &spec(KeyAlgorithm::EcP384)
+        ),
+        Err(SecurityModuleError::KeyError)
+    ));
+    assert_eq!(provider.list_keys().unwrap(), ["wrapping"]);
+
+    // Only RSA keys wrap keys.
+    let ec_public_key = PublicKey::from_der(
+        &p256_key().1,
+        AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(EccCurves::P256)),
+        Hash::Sha2(Sha2Bits::Sha256),
+    )
+    .unwrap();
+    assert!(matches!(
+        key_import::wrap_rsa_aes(&ec_public_key, &pkcs8_der),
+        Err(SecurityModuleError::UnsupportedAlgorithm)
+    ));
+    assert!(matches!(
+        key_import::wrap_rsa_aes(&wrapping_public_key, &[]),
+        Err(SecurityModuleError::Encoding(CoreError::MissingField(_)))
+    ));
+}
+
+#[test]
+fn test_wrapped_providers() {
+    let (inner, wrapping_public_key) = provider();
+    let inner: Arc<Mutex<dyn Provider>> = Arc::new(Mutex::new(inner));
+    let (pkcs8_der, _) = p256_key();
+    let wrapped_key = key_import::wrap_rsa_aes(&wrapping_public_key, &pkcs8_der).unwrap();
+    let p256 = spec(KeyAlgorithm::EcP256);
+
+    // Both key ids are scoped, so a tenant cannot unwrap keys with the wrapping keys of others.
+    let mut tenant = NamespacedProvider::new(inner.clone(), Namespace::new("tenant-a").unwrap());
+    assert!(matches!(
+        tenant.import_wrapped_key("imported", &wrapped_key, "wrapping", &p256),
+        Err(SecurityModuleError::KeyError)
+    ));
+    tenant
+        .create_key(
+            "wrapping",
+            MockConfig::new(
+                AsymmetricEncryption::Rsa(KeyBits::Bits2048),
+                Hash::Sha2(Sha2Bits::Sha256),
+            ),
+        )
+        .unwrap();
+    let tenant_public_key = tenant.key_metadata().unwrap().public_key().clone();
+    let tenant_wrapped_key = key_import::wrap_rsa_aes(&tenant_public_key, &pkcs8_der).unwrap();
+    tenant
+        .import_wrapped_key("imported", &tenant_wrapped_key, "wrapping", &p256)
+        .unwrap();
+    assert_eq!(tenant.list_keys().unwrap(), ["imported", "wrapping"]);
+    assert_eq!(
+        inner.lock().unwrap().list_keys().unwrap(),
+        ["tenant-a/imported", "tenant-a/wrapping", "wrapping"]
+    );
+
+    let mut validated = ValidatedProvider::new(inner.clone());
+    assert!(matches!(
+        validated.import_wrapped_key("imported", &wrapped_key, "wrap ping", &p256),
+        Err(SecurityModuleError::InvalidKeyId(_))
+    ));
+    let policy = SunsetPolicy::new()
+        .deprecate("p256", SunsetAction::Deny)
+        .unwrap();
+    let mut validated = ValidatedProvider::new(inner).with_sunset_policy(policy);
+    assert!(matches!(
+        validated.import_wrapped_key("imported", &wrapped_key, "wrapping", &p256),
+        Err(SecurityModuleError::DeprecatedAlgorithm(_))
+    ));
+}
@@ -0,0 +1,136 @@
+use crate::{
+    common::{
+        crypto::algorithms::{
+            encryption::{AsymmetricEncryption, EccCurves, EccSchemeAlgorithm},
+            hashes::{Hash, Sha2Bits},
+        },
+        key_inventory::{KeyInventory, INVENTORY_PAYLOAD_TYPE, INVENTORY_VERSION},
+        metadata_store::{KeyRecord, MemoryMetadataStore, MetadataStore},
+        sigstore::DsseEnvelope,
+        traits::module_provider::Provider,
+    },
+    mock::{MockConfig, MockProvider},
+    SecurityModuleError,
+};
+use std::any::Any;
+
+fn config(_key_id: &str) -> Box<dyn Any> {
+    MockConfig::new(
+        AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(EccCurves::P256)),
+        Hash::Sha2(Sha2Bits::Sha256),
+    )
+}
+
+/// Returns a provider with a designated key `identity` and two other keys, of which `records`
+/// has a record in the returned store.
+fn device() -> (MockProvider, MemoryMetadataStore) {
+    let mut provider = MockProvider::new(String::new());
+    provider.initialize_module().unwrap();
+    for key_id in ["backup", "identity", "records"] {
+        provider.create_key(key_id, config(key_id)).unwrap();
+    }
+    let store = MemoryMetadataStore::new();
+    let mut record = KeyRecord::new("records");
+    record
+        .labels
+        .insert("owner".to_owned(), "telemetry".to_owned());
+    store.save(&record).unwrap();
+    (provider, store)
+}
+
+fn sign(provider: &mut MockProvider, inventory: &KeyInventory) -> DsseEnvelope {
+    provider.load_key("identity", config("identity")).unwrap();
+    inventory.sign(provider).unwrap()
+}
+
+#[test]
+fn test_signed_inventory_round_trip() {
+    let (mut provider, store) = device();
+    let inventory =
+        KeyInventory::collect("sensor-17", &mut provider, Some(&store), config).unwrap();
+    assert_eq!(inventory.version, INVENTORY_VERSION);
+    assert_eq!(inventory.device, "sensor-17");
+    assert_eq!(
+        inventory.keys.keys().collect::<Vec<_>>(),
+        ["backup", "identity", "records"]
+    );
+
+    let envelope = sign(&mut provider, &inventory);
+    assert_eq!(envelope.payload_type, INVENTORY_PAYLOAD_TYPE);
+    assert_eq!(envelope.signatures[0].keyid.as_deref(), Some("identity"));
+    let identity = inventory.keys["identity"].metadata.public_key().clone();
+
+    let envelope = DsseEnvelope::from_json(&envelope.to_json()).unwrap();
+    let verified = KeyInventory::verify(&envelope, &identity).unwrap();
+    assert_eq!(verified.created_at, inventory.created_at);
+    for (key_id, entry) in &inventory.keys {
+        assert_eq!(
+            verified.keys[key_id].metadata.public_key_der(),
+            entry.metadata.public_key_der()
+        );
+        assert_eq!(verified.keys[key_id].record, entry.record);
+    }
+    assert_eq!(
+        verified.keys["records"].record.as_ref().unwrap().labels["owner"],
+        "telemetry"
+    );
+    assert!(verified.keys["backup"].record.is_none());
+
+    let fleet_store = MemoryMetadataStore::new();
+    assert_eq!(verified.import(&fleet_store).unwrap(), 3);
+    assert_eq!(
+        fleet_store.key_ids().unwrap(),
+        ["backup", "identity", "records"]
+    );
+    assert_eq!(
+        fleet_store.load("records").unwrap(),
+        store.load("records").unwrap()
+    );
+}
+
+#[test]
+fn test_rejects_manifests_that_are_not_trustworthy() {
+    let (mut provider, _) = device();
+    let inventory = KeyInventory::collect("sensor-17", &mut provider, None, config).unwrap();
+    let identity = inventory.keys["identity"].metadata.public_key().clone();
+    let backup = inventory.keys["backup"].metadata.public_key().clone();
+    let envelope = sign(&mut provider, &inventory);
+
+    // Signed by another key, or modified after signing.
+    assert!(matches!(
+        KeyInventory::verify(&envelope, &backup),
+        Err(SecurityModuleError::InvalidSignature)
+    ));
+    let mut modified = envelope.clone();
+    modified.payload = String::from_utf8(modified.payload)
+        .unwrap()
+        .replace("sensor-17", "sensor-18")
+        .into_bytes();
+    assert!(matches!(
+        KeyInventory::verify(&modified, &identity),
+        Err(SecurityModuleError::InvalidSignature)
+    ));
+    let mut other_type = envelope.clone();
+    other_type.payload_type = "application/vnd.in-toto+json".to_owned();
+    assert!(matches!(
+        KeyInventory::verify(&other_type, &identity),
+        Err(SecurityModuleError::SignatureVerificationError(_))
+    ));
+
+    // Validly signed, but inconsistent or of a newer version.
+    let mut renamed = inventory.clone();
+    let entry = renamed.keys.remove("backup").unwrap();
+    renamed.keys.insert("firmware".to_owned(), entry);
+    let envelope = sign(&mut provider, &renamed);
+    assert!(matches!(
+        KeyInventory::verify(&envelope, &identity),
+        Err(SecurityModuleError::SignatureVerificationError(message)) if message.contains("firmware")
+    ));
+    let mut newer = inventory;
+    newer.version = INVENTORY_VERSION + 1;
+    let envelope = sign(&mut provider, &newer);
+    assert!(matches!(
+        KeyInventory::verify(&envelope, &identity),
+        Err(SecurityModuleError::SignatureVerificationError(_))
+    ));
+}
@@ -0,0 +1,109 @@
+use crate::{
+    common::{
+        key_stats::{all_key_stats, key_stats},
+        latency::{LatencyConfig, ProviderOperation, TimedProvider},
+        traits::{key_handle::KeyHandle, module_provider::Provider},
+    },
+    mock::{MockConfig, MockController, MockProvider},
+    SecurityModuleError,
+};
+use std::{
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+fn provider(key_id: &str) -> (TimedProvider, MockController) {
+    let mock = MockProvider::new(key_id.to_owned());
+    let controller = mock.controller();
+    let mut provider = TimedProvider::new(
+        Arc::new(Mutex::new(mock)),
+        key_id.to_owned(),
+        LatencyConfig::default(),
+    );
+    provider.initialize_module().unwrap();
+    provider
+        .create_key(key_id, Box::new(MockConfig::default()))
+        .unwrap();
+    (provider, controller)
+}
+
+#[test]
+fn test_operations_are_counted() {
+    let before = SystemTime::now();
+    let (provider, controller) = provider("stats_counted_key");
+
+    let signature = provider.sign_data(b"data").unwrap();
+    provider.sign_data(b"data").unwrap();
+    assert!(provider.verify_signature(b"data", &signature).unwrap());
+    controller.fail(ProviderOperation::DecryptData, || {
+        SecurityModuleError::DecryptionError("Device removed".to_owned())
+    });
+    assert!(provider.decrypt_data(b"ciphertext").is_err());
+
+    let stats = key_stats("stats_counted_key").unwrap();
+    assert_eq!(
+        stats.operations.get(&ProviderOperation::CreateKey),
+        Some(&1)
+    );
+    assert_eq!(stats.operations.get(&ProviderOperation::SignData), Some(&2));
+    assert_eq!(
+        stats.operations.get(&ProviderOperation::VerifySignature),
+        Some(&1)
+    );
+    assert_eq!(
+        stats.operations.get(&ProviderOperation::DecryptData),
+        Some(&1)
+    );
+    assert_eq!(
+        stats.operations.get(&ProviderOperation::InitializeModule),
+        None
+    );
+    assert_eq!(stats.total_operations(), 5);
+    assert_eq!(stats.failures, 1);
+    assert!(stats.first_used >= before);
+    assert!(stats.last_used.unwrap() >= stats.first_used);
+}
+
+#[test]
+fn test_failures_do_not_update_last_used() {
+    let (provider, controller) = provider("stats_failed_key");
+    let last_used = key_stats("stats_failed_key").unwrap().last_used;
+
+    controller.fail(ProviderOperation::SignData, || {
+        SecurityModuleError::SigningError("Device removed".to_owned())
+    });
+    assert!(provider.sign_data(b"data").is_err());
+
+    let stats = key_stats("stats_failed_key").unwrap();
+    assert_eq!(stats.last_used, last_used);
+    assert_eq!(stats.failures, 1);
+}
+
+#[test]
+fn test_unused_key_has_no_stats() {
+    assert_eq!(key_stats("stats_unused_key"), None);
+    assert!(all_key_stats()
+        .iter()
+        .all(|stats| stats.key_id != "stats_unused_key"));
+}
+
+#[test]
+fn test_failed_loads_keep_the_current_key() {
+    let (mut provider, _) = provider("stats_current_key");
+
+    assert!(matches!(
+        provider.load_key("stats_missing_key", Box::new(MockConfig::default())),
+        Err(SecurityModuleError::KeyError)
+    ));
+    provider.sign_data(b"data").unwrap();
+
+    let stats = key_stats("stats_current_key").unwrap();
+    assert_eq!(stats.operations.get(&ProviderOperation::SignData), Some(&1));
+    let missing = key_stats("stats_missing_key").unwrap();
+    assert_eq!(
+        missing.operations.get(&ProviderOperation::LoadKey),
+        Some(&1)
+    );
+    assert_eq!(missing.total_operations(), 1);
+    assert_eq!(missing.last_used, None);
+}
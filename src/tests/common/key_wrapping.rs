@@ -0,0 +1,110 @@
+use crate::{
+    common::{
+        crypto::algorithms::{
+            encryption::AsymmetricEncryption,
+            hashes::{Hash, Sha2Bits},
+            KeyBits,
+        },
+        key_wrapping::{KeyWrapper, MAX_KEY_MATERIAL_LEN},
+        traits::module_provider::Provider,
+    },
+    mock::{MockConfig, MockProvider},
+    SecurityModuleError,
+};
+use crypto_layer_core::CoreError;
+use std::{
+    any::Any,
+    sync::{Arc, Mutex},
+};
+
+/// Returns a provider holding the new key `key_id` with `config`.
+fn kek(key_id: &str, config: Box<dyn Any>) -> Arc<Mutex<dyn Provider>> {
+    Arc::new(Mutex::new(MockProvider::with_key(key_id, config)))
+}
+
+fn rsa_config() -> Box<dyn Any> {
+    MockConfig::new(
+        AsymmetricEncryption::Rsa(KeyBits::Bits2048),
+        Hash::Sha2(Sha2Bits::Sha256),
+    )
+}
+
+fn wrapper() -> KeyWrapper {
+    KeyWrapper::new()
+        .with_kek("rsa", kek("rsa", rsa_config()))
+        .with_kek("other_rsa", kek("other_rsa", rsa_config()))
+        .with_kek("ec", kek("ec", Box::new(MockConfig::default())))
+}
+
+#[test]
+fn test_wrap_and_unwrap() {
+    let wrapper = wrapper();
+    assert_eq!(
+        wrapper.kek_ids().collect::<Vec<_>>(),
+        ["ec", "other_rsa", "rsa"]
+    );
+    // Longer than RSA-OAEP could encrypt directly.
+    let key_material = vec![0x42; 1000];
+
+    for (kek_id, tag) in [("rsa", 0x01), ("ec", 0x02)] {
+        let blob = wrapper.wrap(kek_id, &key_material).unwrap();
+        assert_eq!(blob[0], tag);
+        assert!(!blob
+            .windows(key_material.len())
+            .any(|window| window == key_material));
+        assert_eq!(wrapper.unwrap(kek_id, &blob).unwrap(), key_material);
+        // Every blob is encrypted under a fresh key.
+        assert_ne!(wrapper.wrap(kek_id, &key_material).unwrap(), blob);
+    }
+    let blob = wrapper.wrap("rsa", &[1]).unwrap();
+    assert_eq!(wrapper.unwrap("rsa", &blob).unwrap(), [1]);
+}
+
+#[test]
+fn test_rejected_blobs() {
+    let wrapper = wrapper();
+    let blob = wrapper.wrap("rsa", b"software key").unwrap();
+
+    let mut tampered = blob.clone();
+    *tampered.last_mut().unwrap() ^= 1;
+    assert!(matches!(
+        wrapper.unwrap("rsa", &tampered),
+        Err(SecurityModuleError::DecryptionError(_))
+    ));
+    assert!(matches!(
+        wrapper.unwrap("other_rsa", &blob),
+        Err(SecurityModuleError::DecryptionError(_))
+    ));
+    assert!(matches!(
+        wrapper.unwrap("ec", &blob),
+        Err(SecurityModuleError::DecryptionError(_))
+    ));
+    let ec_blob = wrapper.wrap("ec", b"software key").unwrap();
+    assert!(matches!(
+        wrapper.unwrap("rsa", &ec_blob),
+        Err(SecurityModuleError::DecryptionError(_))
+    ));
+    for truncated in [&blob[..0], &blob[..2], &blob[..3 + 256]] {
+        assert!(matches!(
+            wrapper.unwrap("rsa", truncated),
+            Err(SecurityModuleError::DecryptionError(_))
+        ));
+    }
+
+    assert!(matches!(
+        wrapper.wrap("unknown", b"software key"),
+        Err(SecurityModuleError::KeyError)
+    ));
+    assert!(matches!(
+        wrapper.unwrap("unknown", &blob),
+        Err(SecurityModuleError::KeyError)
+    ));
+    assert!(matches!(
+        wrapper.wrap("rsa", &[]),
+        Err(SecurityModuleError::Encoding(CoreError::MissingField(_)))
+    ));
+    assert!(matches!(
+        wrapper.wrap("rsa", &vec![0; MAX_KEY_MATERIAL_LEN + 1]),
+        Err(SecurityModuleError::Encoding(CoreError::InvalidField(_)))
+    ));
+}
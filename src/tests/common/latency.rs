@@ -0,0 +1,202 @@
+use crate::common::{
+    error::SecurityModuleError,
+    latency::{
+        key_id_hash, LatencyConfig, LatencyHistogram, LatencyRecorder, ProviderOperation,
+        TimedProvider,
+    },
+    traits::{key_handle::KeyHandle, module_provider::Provider},
+};
+use std::{
+    any::Any,
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+/// A provider that signs by sleeping for a configurable time.
+#[derive(Debug, Default)]
+struct SleepingProvider {
+    sign_duration: Duration,
+    key_id: String,
+}
+
+impl KeyHandle for SleepingProvider {
+    fn sign_data(&self, data: &[u8]) -> Result<Vec<u8>, SecurityModuleError> {
+        thread::sleep(self.sign_duration);
+        Ok(data.to_vec())
+    }
+}
+
+impl Provider for SleepingProvider {
+    fn create_key(
+        &mut self,
+        key_id: &str,
+        _config: Box<dyn Any>,
+    ) -> Result<(), SecurityModuleError> {
+        self.key_id = key_id.to_owned();
+        Ok(())
+    }
+
+    fn load_key(
+        &mut self,
+        _key_id: &str,
+        _config: Box<dyn Any>,
+    ) -> Result<(), SecurityModuleError> {
+        Err(SecurityModuleError::KeyError)
+    }
+
+    fn initialize_module(&mut self) -> Result<(), SecurityModuleError> {
+        Ok(())
+    }
+}
+
+fn timed_provider(sign_duration: Duration, config: LatencyConfig) -> TimedProvider {
+    let inner = SleepingProvider {
+        sign_duration,
+        ..Default::default()
+    };
+    TimedProvider::new(Arc::new(Mutex::new(inner)), "test_key".to_owned(), config)
+}
+
+#[test]
+fn test_histogram_statistics() {
+    let mut histogram = LatencyHistogram::default();
+    assert_eq!(histogram.mean(), None);
+    assert_eq!(histogram.percentile(50.0), None);
+
+    for micros in [100, 200, 300, 400, 10_000] {
+        histogram.record(Duration::from_micros(micros));
+    }
+
+    assert_eq!(histogram.count(), 5);
+    assert_eq!(histogram.total(), Duration::from_micros(11_000));
+    assert_eq!(histogram.mean(), Some(Duration::from_micros(2_200)));
+    assert_eq!(histogram.min(), Some(Duration::from_micros(100)));
+    assert_eq!(histogram.max(), Some(Duration::from_micros(10_000)));
+    assert_eq!(histogram.percentile(0.0), Some(Duration::from_micros(128)));
+    assert_eq!(histogram.percentile(50.0), Some(Duration::from_micros(512)));
+    assert_eq!(
+        histogram.percentile(100.0),
+        Some(Duration::from_micros(10_000))
+    );
+}
+
+#[test]
+fn test_histogram_buckets() {
+    let mut histogram = LatencyHistogram::default();
+    histogram.record(Duration::ZERO);
+    histogram.record(Duration::from_micros(1));
+    histogram.record(Duration::from_micros(3));
+    histogram.record(Duration::from_secs(100_000));
+
+    let buckets = histogram.buckets();
+    assert_eq!(buckets[0], (Duration::from_micros(1), 1));
+    assert_eq!(buckets[1], (Duration::from_micros(2), 1));
+    assert_eq!(buckets[2], (Duration::from_micros(4), 1));
+    assert_eq!(*buckets.last().unwrap(), (Duration::MAX, 1));
+    assert_eq!(buckets.iter().map(|(_, count)| count).sum::<u64>(), 4);
+}
+
+#[test]
+fn test_recorder_counts_slow_operations() {
+    let recorder = LatencyRecorder::new(LatencyConfig {
+        slow_threshold: Some(Duration::from_millis(10)),
+    });
+
+    recorder.record(ProviderOperation::SignData, "key", Duration::from_millis(1));
+    recorder.record(
+        ProviderOperation::SignData,
+        "key",
+        Duration::from_millis(10),
+    );
+    recorder.record(
+        ProviderOperation::EncryptData,
+        "key",
+        Duration::from_secs(1),
+    );
+
+    let snapshot = recorder.snapshot();
+    assert_eq!(snapshot.slow_operations, 2);
+    assert_eq!(
+        snapshot.get(ProviderOperation::SignData).unwrap().count(),
+        2
+    );
+    assert_eq!(
+        snapshot
+            .get(ProviderOperation::EncryptData)
+            .unwrap()
+            .count(),
+        1
+    );
+    assert!(snapshot.get(ProviderOperation::DecryptData).is_none());
+
+    recorder.reset();
+    assert_eq!(recorder.snapshot(), Default::default());
+}
+
+#[test]
+fn test_recorder_without_threshold() {
+    let recorder = LatencyRecorder::new(LatencyConfig {
+        slow_threshold: None,
+    });
+
+    recorder.record(ProviderOperation::SignData, "key", Duration::from_secs(60));
+
+    assert_eq!(recorder.snapshot().slow_operations, 0);
+}
+
+#[test]
+fn test_timed_provider_records_calls() {
+    let mut provider = timed_provider(
+        Duration::from_millis(20),
+        LatencyConfig {
+            slow_threshold: Some(Duration::from_millis(15)),
+        },
+    );
+
+    provider.initialize_module().unwrap();
+    provider.create_key("other_key", Box::new(())).unwrap();
+    assert_eq!(provider.sign_data(b"data").unwrap(), b"data");
+    assert!(matches!(
+        provider.load_key("missing", Box::new(())),
+        Err(SecurityModuleError::KeyError)
+    ));
+    assert!(provider.encrypt_data(b"data").is_err());
+
+    let snapshot = provider.latency_snapshot().unwrap();
+    for operation in [
+        ProviderOperation::InitializeModule,
+        ProviderOperation::CreateKey,
+        ProviderOperation::SignData,
+        ProviderOperation::LoadKey,
+        ProviderOperation::EncryptData,
+    ] {
+        assert_eq!(snapshot.get(operation).unwrap().count(), 1, "{operation}");
+    }
+    assert!(
+        snapshot
+            .get(ProviderOperation::SignData)
+            .unwrap()
+            .min()
+            .unwrap()
+            >= Duration::from_millis(20)
+    );
+    assert_eq!(snapshot.slow_operations, 1);
+}
+
+#[test]
+fn test_key_id_hash() {
+    let hash = key_id_hash("test_key");
+
+    assert_eq!(hash, key_id_hash("test_key"));
+    assert_ne!(hash, key_id_hash("other_key"));
+    assert_eq!(hash.len(), 16);
+    assert!(hash.chars().all(|c| c.is_ascii_hexdigit()));
+}
+
+#[test]
+fn test_providers_do_not_record_by_default() {
+    let provider = SleepingProvider::default();
+
+    assert!(provider.latency_snapshot().is_none());
+}
@@ -0,0 +1,103 @@
+use crate::common::log_levels::{
+    clear_target_level, levels, set_default_level, set_target_level, ProviderLogFilter,
+};
+#[cfg(feature = "macos")]
+use crate::{
+    common::{factory::SecurityModule, log_levels},
+    tpm::core::instance::TpmType,
+};
+use std::sync::{Arc, Mutex};
+use tracing::{field::Field, level_filters::LevelFilter, Event, Subscriber};
+use tracing_subscriber::{layer::Context, prelude::*, Layer};
+
+/// Collects the messages of all events that pass the filter.
+#[derive(Clone, Default)]
+struct Messages(Arc<Mutex<Vec<String>>>);
+
+impl<S: Subscriber> Layer<S> for Messages {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut visitor = |field: &Field, value: &dyn std::fmt::Debug| {
+            if field.name() == "message" {
+                self.0.lock().unwrap().push(format!("{:?}", value));
+            }
+        };
+        event.record(&mut visitor);
+    }
+}
+
+fn capture(f: impl FnOnce()) -> Vec<String> {
+    let messages = Messages::default();
+    let subscriber = tracing_subscriber::registry()
+        .with(ProviderLogFilter)
+        .with(messages.clone());
+    tracing::subscriber::with_default(subscriber, f);
+    let messages = messages.0.lock().unwrap().clone();
+    messages
+}
+
+#[test]
+fn test_level_changes_apply_immediately() {
+    let messages = capture(|| {
+        set_target_level("log_levels_test::runtime", LevelFilter::INFO);
+        tracing::debug!(target: "log_levels_test::runtime", "hidden");
+        tracing::info!(target: "log_levels_test::runtime", "shown");
+
+        set_target_level("log_levels_test::runtime", LevelFilter::TRACE);
+        tracing::debug!(target: "log_levels_test::runtime", "verbose");
+
+        set_target_level("log_levels_test::runtime", LevelFilter::OFF);
+        tracing::error!(target: "log_levels_test::runtime", "silenced");
+        clear_target_level("log_levels_test::runtime");
+    });
+
+    assert_eq!(messages, ["shown", "verbose"]);
+}
+
+#[test]
+fn test_longest_target_wins() {
+    let messages = capture(|| {
+        set_target_level("log_levels_test::nested", LevelFilter::ERROR);
+        set_target_level("log_levels_test::nested::verbose", LevelFilter::DEBUG);
+        tracing::debug!(target: "log_levels_test::nested::quiet", "quiet");
+        tracing::debug!(target: "log_levels_test::nested::verbose::child", "verbose");
+        // A common prefix that is not a module boundary does not match.
+        tracing::warn!(target: "log_levels_test::nested_other", "other");
+        clear_target_level("log_levels_test::nested");
+        clear_target_level("log_levels_test::nested::verbose");
+    });
+
+    assert_eq!(messages, ["verbose", "other"]);
+}
+
+#[test]
+fn test_default_level() {
+    let messages = capture(|| {
+        set_default_level(LevelFilter::WARN);
+        assert_eq!(levels().0, LevelFilter::WARN);
+        tracing::info!(target: "log_levels_test::default", "hidden");
+        tracing::warn!(target: "log_levels_test::default", "shown");
+        set_default_level(LevelFilter::TRACE);
+    });
+
+    assert_eq!(messages, ["shown"]);
+}
+
+#[cfg(feature = "macos")]
+#[test]
+fn test_provider_level() {
+    let module = SecurityModule::Tpm(TpmType::MacOs);
+
+    let messages = capture(|| {
+        log_levels::set_level(&module, LevelFilter::ERROR);
+        tracing::warn!(target: "crypto_layer::tpm::macos::provider", "hidden");
+        tracing::warn!(target: "crypto_layer::tpm::linux::provider", "shown");
+        assert_eq!(
+            levels().1.get("crypto_layer::tpm::macos"),
+            Some(&LevelFilter::ERROR)
+        );
+        log_levels::clear_level(&module);
+    });
+
+    assert_eq!(messages, ["shown"]);
+    assert_eq!(levels().1.get("crypto_layer::tpm::macos"), None);
+}
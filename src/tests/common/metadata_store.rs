@@ -0,0 +1,187 @@
+use crate::{
+    common::{
+        latency::ProviderOperation,
+        metadata_store::{
+            FileMetadataStore, KeyRecord, MemoryMetadataStore, MetadataProvider, MetadataStore,
+        },
+        traits::{key_handle::KeyHandle, module_provider::Provider},
+    },
+    mock::{MockConfig, MockProvider},
+    SecurityModuleError,
+};
+use std::sync::{Arc, Mutex};
+
+fn metadata_provider(
+    store: Arc<dyn MetadataStore>,
+) -> (MetadataProvider, Arc<Mutex<MockProvider>>) {
+    let mut mock = MockProvider::new("device".to_owned());
+    mock.initialize_module().unwrap();
+    let mock = Arc::new(Mutex::new(mock));
+    let provider = MetadataProvider::new(mock.clone(), "device".to_owned(), store);
+    (provider, mock)
+}
+
+#[test]
+fn test_records_follow_the_key() {
+    let store = Arc::new(MemoryMetadataStore::new());
+    let (mut provider, _) = metadata_provider(store.clone());
+
+    provider
+        .create_key("device", Box::new(MockConfig::default()))
+        .unwrap();
+    let signature = provider.sign_data(b"data").unwrap();
+    assert!(provider.verify_signature(b"data", &signature).unwrap());
+
+    let record = provider.record().unwrap().unwrap();
+    assert_eq!(record.key_id, "device");
+    assert_eq!(record.version, 1);
+    assert_eq!(record.stats.operations[&ProviderOperation::SignData], 1);
+    assert_eq!(
+        record.stats.operations[&ProviderOperation::VerifySignature],
+        1
+    );
+    assert!(record.stats.last_used.is_some());
+
+    // Creating the key again is a new version, loading it is not.
+    provider
+        .create_key("device", Box::new(MockConfig::default()))
+        .unwrap();
+    provider
+        .load_key("device", Box::new(MockConfig::default()))
+        .unwrap();
+    assert_eq!(store.load("device").unwrap().unwrap().version, 2);
+
+    provider.delete_key("device").unwrap();
+    assert_eq!(store.load("device").unwrap(), None);
+    assert!(store.key_ids().unwrap().is_empty());
+}
+
+#[test]
+fn test_enforces_usage_policy() {
+    let store = Arc::new(MemoryMetadataStore::new());
+    let (mut provider, mock) = metadata_provider(store.clone());
+    provider
+        .create_key("device", Box::new(MockConfig::default()))
+        .unwrap();
+    store
+        .update("device", &mut |record| {
+            record.usage = Some([ProviderOperation::SignData].into());
+        })
+        .unwrap();
+
+    assert!(provider.sign_data(b"data").is_ok());
+    assert!(matches!(
+        provider.encrypt_data(b"data"),
+        Err(SecurityModuleError::UnsupportedOperation(message)) if message.contains("encrypt_data")
+    ));
+    assert!(provider.export_private_key().is_err());
+    assert_eq!(
+        mock.lock()
+            .unwrap()
+            .controller()
+            .calls(ProviderOperation::EncryptData),
+        0
+    );
+
+    // Refused operations are not counted, failed ones are.
+    mock.lock()
+        .unwrap()
+        .controller()
+        .fail_times(ProviderOperation::SignData, 1, || {
+            SecurityModuleError::SigningFailed
+        });
+    assert!(provider.sign_data(b"data").is_err());
+    let stats = store.load("device").unwrap().unwrap().stats;
+    assert_eq!(stats.operations.len(), 1);
+    assert_eq!(stats.operations[&ProviderOperation::SignData], 2);
+    assert_eq!(stats.failures, 1);
+}
+
+#[test]
+fn test_failed_loads_keep_the_record_of_the_loaded_key() {
+    let store = Arc::new(MemoryMetadataStore::new());
+    let (mut provider, _) = metadata_provider(store.clone());
+    provider
+        .create_key("device", Box::new(MockConfig::default()))
+        .unwrap();
+    store
+        .update("device", &mut |record| {
+            record.usage = Some([ProviderOperation::VerifySignature].into());
+        })
+        .unwrap();
+    store.save(&KeyRecord::new("other")).unwrap();
+
+    // `device` stays loaded, so its policy applies and its record counts the use.
+    assert!(provider
+        .load_key("other", Box::new(MockConfig::default()))
+        .is_err());
+    assert!(matches!(
+        provider.sign_data(b"data"),
+        Err(SecurityModuleError::UnsupportedOperation(message)) if message.contains("'device'")
+    ));
+    assert_eq!(provider.record().unwrap().unwrap().key_id, "device");
+    assert!(store
+        .load("other")
+        .unwrap()
+        .unwrap()
+        .stats
+        .operations
+        .is_empty());
+}
+
+#[test]
+fn test_file_store_survives_restarts() {
+    let dir = std::env::temp_dir().join(format!("metadata_{}", std::process::id()));
+    let store = FileMetadataStore::new(&dir).unwrap();
+    std::fs::write(dir.join("unrelated.txt"), b"ignored").unwrap();
+
+    let mut record = KeyRecord::new("../escape");
+    record
+        .labels
+        .insert("owner".to_owned(), "telemetry".to_owned());
+    record.usage = Some([ProviderOperation::DecryptData].into());
+    store.save(&record).unwrap();
+    store
+        .update("device", &mut |record| {
+            record.record_use(ProviderOperation::SignData, true)
+        })
+        .unwrap();
+
+    let store = FileMetadataStore::new(&dir).unwrap();
+    assert_eq!(store.key_ids().unwrap(), ["../escape", "device"]);
+    assert_eq!(store.load("../escape").unwrap().unwrap(), record);
+    assert!(!store
+        .load("../escape")
+        .unwrap()
+        .unwrap()
+        .allows(ProviderOperation::SignData));
+    assert_eq!(
+        store.load("device").unwrap().unwrap().stats.operations[&ProviderOperation::SignData],
+        1
+    );
+    assert!(!dir.parent().unwrap().join("escape").exists());
+    assert!(store.delete("../escape").unwrap());
+    assert!(!store.delete("../escape").unwrap());
+    assert_eq!(store.load("missing").unwrap(), None);
+
+    std::fs::write(store.dir().join("6b6579.json"), b"not json").unwrap();
+    assert!(matches!(
+        store.load("key"),
+        Err(SecurityModuleError::SecretStorage(_))
+    ));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_record_serialization() {
+    let record: KeyRecord = serde_json::from_str(r#"{"key_id":"legacy"}"#).unwrap();
+    assert_eq!(record.version, 1);
+    assert!(record.allows(ProviderOperation::DeriveSharedSecret));
+
+    let mut record = KeyRecord::new("device");
+    record.usage = Some([ProviderOperation::SignData].into());
+    let json = serde_json::to_string(&record).unwrap();
+    assert!(json.contains(r#""usage":["sign_data"]"#));
+    assert_eq!(serde_json::from_str::<KeyRecord>(&json).unwrap(), record);
+}
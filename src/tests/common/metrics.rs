@@ -0,0 +1,159 @@
+use crate::{
+    common::{
+        latency::ProviderOperation,
+        metrics::{
+            self, MeteredProvider, FFI_BYTES_TOTAL, FFI_MARSHALLING_DURATION_SECONDS, KEYS,
+            OPERATIONS_TOTAL, OPERATION_DURATION_SECONDS,
+        },
+        traits::{key_handle::KeyHandle, module_provider::Provider},
+    },
+    mock::{MockConfig, MockProvider},
+    SecurityModuleError,
+};
+use ::metrics::{with_local_recorder, Label};
+use metrics_util::{
+    debugging::{DebugValue, DebuggingRecorder, Snapshotter},
+    MetricKind,
+};
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+type Snapshot = Vec<(MetricKind, String, Vec<Label>, DebugValue)>;
+
+/// Returns the recorded metrics with their names and labels.
+fn snapshot(snapshotter: &Snapshotter) -> Snapshot {
+    snapshotter
+        .snapshot()
+        .into_vec()
+        .into_iter()
+        .map(|(key, _, _, value)| {
+            let (kind, key) = key.into_parts();
+            let (name, labels) = key.into_parts();
+            (kind, name.as_str().to_owned(), labels, value)
+        })
+        .collect()
+}
+
+/// Returns the recorded value of the metric with the given name and labels.
+fn value<'a>(
+    snapshot: &'a [(MetricKind, String, Vec<Label>, DebugValue)],
+    name: &str,
+    labels: &[(&str, &str)],
+) -> Option<&'a DebugValue> {
+    snapshot
+        .iter()
+        .find(|(_, key, key_labels, _)| {
+            key == name
+                && labels.iter().all(|(label, value)| {
+                    key_labels
+                        .iter()
+                        .any(|l| l.key() == *label && l.value() == *value)
+                })
+        })
+        .map(|(_, _, _, value)| value)
+}
+
+#[test]
+fn test_operations_are_reported() {
+    let recorder = DebuggingRecorder::new();
+    let snapshotter = recorder.snapshotter();
+
+    with_local_recorder(&recorder, || {
+        let mock = MockProvider::new("metrics_key".to_owned());
+        let controller = mock.controller();
+        let mut provider = MeteredProvider::new(Arc::new(Mutex::new(mock)), "Mock".to_owned());
+
+        provider.initialize_module().unwrap();
+        provider
+            .create_key("metrics_key", Box::new(MockConfig::default()))
+            .unwrap();
+        provider
+            .load_key("metrics_key", Box::new(MockConfig::default()))
+            .unwrap();
+        let signature = provider.sign_data(b"data").unwrap();
+        provider.sign_data(b"data").unwrap();
+        assert!(!provider.verify_signature(b"other", &signature).unwrap());
+        controller.fail(ProviderOperation::EncryptData, || {
+            SecurityModuleError::EncryptionError("Device removed".to_owned())
+        });
+        assert!(provider.encrypt_data(b"data").is_err());
+    });
+
+    let snapshot = snapshot(&snapshotter);
+    let calls = |operation, result| {
+        value(
+            &snapshot,
+            OPERATIONS_TOTAL,
+            &[
+                ("operation", operation),
+                ("provider", "Mock"),
+                ("result", result),
+            ],
+        )
+    };
+    assert_eq!(calls("sign_data", "success"), Some(&DebugValue::Counter(2)));
+    assert_eq!(
+        calls("verify_signature", "rejected"),
+        Some(&DebugValue::Counter(1))
+    );
+    assert_eq!(
+        calls("encrypt_data", "failure"),
+        Some(&DebugValue::Counter(1))
+    );
+    assert_eq!(calls("encrypt_data", "success"), None);
+
+    match value(
+        &snapshot,
+        OPERATION_DURATION_SECONDS,
+        &[("operation", "sign_data")],
+    ) {
+        Some(DebugValue::Histogram(durations)) => assert_eq!(durations.len(), 2),
+        other => panic!("unexpected histogram {:?}", other),
+    }
+    // Loading a key that was created before does not count it twice.
+    assert_eq!(
+        value(&snapshot, KEYS, &[("provider", "Mock")]),
+        Some(&DebugValue::Gauge(1.0.into()))
+    );
+}
+
+#[test]
+fn test_ffi_calls_are_reported() {
+    let recorder = DebuggingRecorder::new();
+    let snapshotter = recorder.snapshotter();
+
+    with_local_recorder(&recorder, || {
+        metrics::record_ffi_call("sign_data", 120, 90, Duration::from_micros(30));
+        metrics::record_ffi_call("sign_data", 80, 90, Duration::from_micros(20));
+        metrics::record_ffi_call("load_key", 60, 10, Duration::from_micros(10));
+    });
+
+    let snapshot = snapshot(&snapshotter);
+    let bytes = |method, direction| {
+        value(
+            &snapshot,
+            FFI_BYTES_TOTAL,
+            &[("method", method), ("direction", direction)],
+        )
+    };
+    assert_eq!(
+        bytes("sign_data", "request"),
+        Some(&DebugValue::Counter(200))
+    );
+    assert_eq!(
+        bytes("sign_data", "response"),
+        Some(&DebugValue::Counter(180))
+    );
+    assert_eq!(bytes("load_key", "request"), Some(&DebugValue::Counter(60)));
+
+    match value(
+        &snapshot,
+        FFI_MARSHALLING_DURATION_SECONDS,
+        &[("method", "sign_data")],
+    ) {
+        Some(DebugValue::Histogram(durations)) => assert_eq!(durations.len(), 2),
+        other => panic!("unexpected histogram {:?}", other),
+    }
+}
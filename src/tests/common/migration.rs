@@ -0,0 +1,180 @@
+use crate::{
+    common::{
+        crypto::{
+            algorithms::{
+                encryption::AsymmetricEncryption,
+                hashes::{Hash, Sha2Bits},
+                KeyBits,
+            },
+            key_spec::{KeyAlgorithm, KeyPurpose, KeySpec},
+            secret::SecretBytes,
+        },
+        migration::{self, MigrationPolicy, MigrationRequest, PayloadKind},
+        proof_of_possession::ProofPolicy,
+        traits::{key_handle::KeyHandle, module_provider::Provider},
+    },
+    mock::{MockConfig, MockProvider},
+    SecurityModuleError,
+};
+use std::{any::Any, collections::BTreeMap, time::Duration};
+
+/// Accepts the unattested keys of the mock provider.
+const POLICY: MigrationPolicy = MigrationPolicy {
+    proof: ProofPolicy {
+        max_age: Duration::from_secs(60),
+        clock_skew: Duration::from_secs(30),
+    },
+    require_attestation: false,
+    require_hardware_key: true,
+};
+
+fn provider(key_id: &str, config: Box<dyn Any>) -> MockProvider {
+    MockProvider::with_key(key_id, config)
+}
+
+fn rsa_transport() -> MockProvider {
+    provider(
+        "transport",
+        MockConfig::new(
+            AsymmetricEncryption::Rsa(KeyBits::Bits2048),
+            Hash::Sha2(Sha2Bits::Sha256),
+        ),
+    )
+}
+
+fn exportable_source() -> MockProvider {
+    let config = MockConfig {
+        exportable: true,
+        ..MockConfig::default()
+    };
+    provider("user_key", Box::new(config))
+}
+
+fn spec() -> KeySpec {
+    KeySpec::builder()
+        .algorithm(KeyAlgorithm::EcP256)
+        .usage(KeyPurpose::Sign)
+        .label("migrated")
+        .build()
+        .unwrap()
+}
+
+#[test]
+fn test_migrate_key() {
+    let source = exportable_source();
+    let mut target = rsa_transport();
+
+    let challenge = migration::challenge().unwrap();
+    let request = MigrationRequest::create(&target, &challenge).unwrap();
+    let json = serde_json::to_string(&request).unwrap();
+    let request: MigrationRequest = serde_json::from_str(&json).unwrap();
+
+    let verified = migration::verify_request(&request, &challenge, &POLICY).unwrap();
+    assert!(verified.attestation().is_empty());
+    let package = migration::export_key(&source, &verified).unwrap();
+    assert_eq!(package.kind, PayloadKind::PrivateKey);
+    migration::import_key(&mut target, &request, &package, "migrated", &spec()).unwrap();
+
+    let signature = source.sign_data(b"data").unwrap();
+    assert!(target.verify_signature(b"data", &signature).unwrap());
+    assert_eq!(target.key_metadata().unwrap().key_id(), "migrated");
+    assert!(!target.key_metadata().unwrap().exportable());
+}
+
+#[test]
+fn test_migrate_data_keys() {
+    let mut target = provider("transport", Box::new(MockConfig::default()));
+    let challenge = migration::challenge().unwrap();
+    let request = MigrationRequest::create(&target, &challenge).unwrap();
+    let verified = migration::verify_request(&request, &challenge, &POLICY).unwrap();
+
+    let keys = BTreeMap::from([
+        ("database".to_owned(), SecretBytes::from(vec![1; 32])),
+        ("photos".to_owned(), SecretBytes::from(vec![2; 16])),
+    ]);
+    let package = migration::export_data_keys(&keys, &verified).unwrap();
+    assert_eq!(package.kind, PayloadKind::DataKeys);
+    assert_eq!(
+        migration::import_data_keys(&target, &request, &package).unwrap(),
+        keys
+    );
+
+    // A package of an earlier request is rejected, even though the transport key is the same.
+    let challenge = migration::challenge().unwrap();
+    let new_request = MigrationRequest::create(&target, &challenge).unwrap();
+    assert!(matches!(
+        migration::import_data_keys(&target, &new_request, &package),
+        Err(SecurityModuleError::InvalidProof(_))
+    ));
+    // Replacing the nonce does not help, it is also encrypted with the keys.
+    let new_verified = migration::verify_request(&new_request, &challenge, &POLICY).unwrap();
+    let mut replayed = package.clone();
+    replayed.request_nonce = migration::export_data_keys(&keys, &new_verified)
+        .unwrap()
+        .request_nonce;
+    assert!(matches!(
+        migration::import_data_keys(&target, &new_request, &replayed),
+        Err(SecurityModuleError::InvalidProof(_))
+    ));
+    assert!(matches!(
+        migration::import_key(&mut target, &request, &package, "migrated", &spec()),
+        Err(SecurityModuleError::InvalidProof(_))
+    ));
+}
+
+#[test]
+fn test_rejected_requests() {
+    let target = rsa_transport();
+    let challenge = migration::challenge().unwrap();
+    let request = MigrationRequest::create(&target, &challenge).unwrap();
+
+    assert!(matches!(
+        migration::verify_request(&request, &migration::challenge().unwrap(), &POLICY),
+        Err(SecurityModuleError::InvalidProof(_))
+    ));
+    // The mock provider does not attest its keys.
+    assert!(matches!(
+        migration::verify_request(&request, &challenge, &MigrationPolicy::default()),
+        Err(SecurityModuleError::InvalidProof(_))
+    ));
+    let mut forged = request.clone();
+    forged.attestation.push("Y2VydGlmaWNhdGU".to_owned());
+    assert!(matches!(
+        migration::verify_request(&forged, &challenge, &POLICY),
+        Err(SecurityModuleError::InvalidProof(_))
+    ));
+    let mut forged = request.clone();
+    forged.transport_key = MigrationRequest::create(&rsa_transport(), &challenge)
+        .unwrap()
+        .transport_key;
+    assert!(matches!(
+        migration::verify_request(&forged, &challenge, &POLICY),
+        Err(SecurityModuleError::InvalidSignature)
+    ));
+
+    // Exportable transport keys could leak the migrated keys.
+    let exportable = exportable_source();
+    let exportable_request = MigrationRequest::create(&exportable, &challenge).unwrap();
+    assert!(matches!(
+        migration::verify_request(&exportable_request, &challenge, &POLICY),
+        Err(SecurityModuleError::InvalidProof(_))
+    ));
+
+    // Only exportable keys are migrated, and only for RSA transport keys.
+    let verified = migration::verify_request(&request, &challenge, &POLICY).unwrap();
+    assert!(matches!(
+        migration::export_key(&target, &verified),
+        Err(SecurityModuleError::UnsupportedOperation(_))
+    ));
+    let ec_target = provider("transport", Box::new(MockConfig::default()));
+    let ec_request = MigrationRequest::create(&ec_target, &challenge).unwrap();
+    let ec_verified = migration::verify_request(&ec_request, &challenge, &POLICY).unwrap();
+    assert!(matches!(
+        migration::export_key(&exportable_source(), &ec_verified),
+        Err(SecurityModuleError::UnsupportedAlgorithm)
+    ));
+    assert!(matches!(
+        migration::export_data_keys(&BTreeMap::new(), &verified),
+        Err(SecurityModuleError::InvalidPublicKey)
+    ));
+}
@@ -1 +1,99 @@
+#[cfg(feature = "test-utils")]
+mod acme;
+#[cfg(feature = "test-utils")]
+mod anomaly;
+#[cfg(feature = "test-utils")]
+mod anti_rollback;
+#[cfg(feature = "test-utils")]
+mod audit;
+#[cfg(feature = "test-utils")]
+mod capability_token;
+mod config;
+mod constant_time;
+pub mod crypto;
+#[cfg(feature = "test-utils")]
+mod csr;
+#[cfg(feature = "test-utils")]
+mod device_identity;
+#[cfg(feature = "test-utils")]
+mod diagnostics;
+#[cfg(feature = "test-utils")]
+mod dnssec;
+#[cfg(feature = "test-utils")]
+mod dual_control;
+#[cfg(feature = "test-utils")]
+mod ecies;
+#[cfg(feature = "test-utils")]
+mod ephemeral_key;
+mod error;
+#[cfg(feature = "test-utils")]
+mod escrow;
+#[cfg(feature = "test-utils")]
+mod events;
+#[cfg(feature = "test-utils")]
+mod field_encryption;
+#[cfg(feature = "test-utils")]
+mod file_encryption;
+#[cfg(feature = "test-utils")]
+mod fips;
+#[cfg(feature = "test-utils")]
+mod input_limits;
+#[cfg(feature = "test-utils")]
+mod interop;
+#[cfg(feature = "test-utils")]
+mod key_hierarchy;
+mod key_id;
+#[cfg(feature = "test-utils")]
+mod key_import;
+#[cfg(all(feature = "serde", feature = "test-utils"))]
+mod key_inventory;
+#[cfg(feature = "test-utils")]
+mod key_stats;
+#[cfg(feature = "test-utils")]
+mod key_wrapping;
+pub mod latency;
+mod log_levels;
+#[cfg(feature = "test-utils")]
+mod metadata_store;
+#[cfg(all(feature = "metrics", feature = "test-utils"))]
+mod metrics;
+#[cfg(feature = "test-utils")]
+mod migration;
+#[cfg(feature = "test-utils")]
+mod namespace;
+#[cfg(feature = "test-utils")]
+mod openpgp;
+#[cfg(feature = "test-utils")]
+mod password;
+#[cfg(feature = "test-utils")]
+mod pkcs12;
+mod plan;
+mod profile;
+#[cfg(feature = "test-utils")]
+mod proof_of_possession;
+mod recovery;
+#[cfg(feature = "test-utils")]
+mod sealed_message;
+#[cfg(feature = "serde")]
+mod serialization;
+#[cfg(not(crypto_layer_loom))]
+pub mod session_pool;
+#[cfg(crypto_layer_loom)]
+mod session_pool_loom;
+#[cfg(feature = "test-utils")]
+mod sigstore;
+#[cfg(all(feature = "sqlite", feature = "test-utils"))]
+mod sqlite_store;
+#[cfg(feature = "test-utils")]
+mod sshsig;
+mod sunset;
+mod telemetry;
 pub mod traits;
+#[cfg(feature = "test-utils")]
+mod transaction;
+#[cfg(feature = "test-utils")]
+mod uniform_errors;
+#[cfg(feature = "test-utils")]
+mod vault;
+#[cfg(feature = "test-utils")]
+mod webauthn;
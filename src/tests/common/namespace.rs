@@ -0,0 +1,110 @@
+use crate::{
+    common::{
+        namespace::{Namespace, NamespacedProvider, MAX_NAMESPACE_LEN},
+        traits::{key_handle::KeyHandle, module_provider::Provider},
+    },
+    mock::{MockConfig, MockProvider},
+    SecurityModuleError,
+};
+use std::sync::{Arc, Mutex};
+
+fn shared_provider() -> Arc<Mutex<dyn Provider>> {
+    let mut provider = MockProvider::new("identity".to_owned());
+    provider.initialize_module().unwrap();
+    Arc::new(Mutex::new(provider))
+}
+
+fn namespaced(inner: &Arc<Mutex<dyn Provider>>, name: &str) -> NamespacedProvider {
+    NamespacedProvider::new(inner.clone(), Namespace::new(name).unwrap())
+}
+
+#[test]
+fn test_namespace_names() {
+    assert_eq!(
+        Namespace::new("com.example.mail").unwrap().name(),
+        "com.example.mail"
+    );
+    assert!(Namespace::new("tenant_42-eu").is_ok());
+    assert!(Namespace::new("a".repeat(MAX_NAMESPACE_LEN)).is_ok());
+
+    for name in ["", "tenant/a", "tenant a", "mandant-ü"] {
+        assert!(
+            matches!(
+                Namespace::new(name),
+                Err(SecurityModuleError::InitializationError(_))
+            ),
+            "{:?} was accepted",
+            name
+        );
+    }
+    assert!(Namespace::new("a".repeat(MAX_NAMESPACE_LEN + 1)).is_err());
+}
+
+#[test]
+fn test_scope_and_unscope() {
+    let namespace = Namespace::new("tenant-a").unwrap();
+
+    assert_eq!(namespace.scope("identity"), "tenant-a/identity");
+    assert_eq!(namespace.unscope("tenant-a/identity"), Some("identity"));
+    assert_eq!(namespace.unscope("tenant-a/nested/key"), Some("nested/key"));
+    assert_eq!(namespace.unscope("tenant-ab/identity"), None);
+    assert_eq!(namespace.unscope("identity"), None);
+}
+
+#[test]
+fn test_namespaces_do_not_collide() {
+    let inner = shared_provider();
+    let mut tenant_a = namespaced(&inner, "tenant-a");
+    let mut tenant_b = namespaced(&inner, "tenant-b");
+
+    tenant_a
+        .create_key("identity", Box::new(MockConfig::default()))
+        .unwrap();
+    let signature = tenant_a.sign_data(b"data").unwrap();
+    tenant_b
+        .create_key("identity", Box::new(MockConfig::default()))
+        .unwrap();
+
+    assert_eq!(
+        inner.lock().unwrap().list_keys().unwrap(),
+        ["tenant-a/identity", "tenant-b/identity"]
+    );
+    assert!(!tenant_b.verify_signature(b"data", &signature).unwrap());
+    tenant_a
+        .load_key("identity", Box::new(MockConfig::default()))
+        .unwrap();
+    assert!(tenant_a.verify_signature(b"data", &signature).unwrap());
+}
+
+#[test]
+fn test_keys_of_other_namespaces_are_hidden() {
+    let inner = shared_provider();
+    let mut tenant_a = namespaced(&inner, "tenant-a");
+    let mut tenant_b = namespaced(&inner, "tenant-b");
+    inner
+        .lock()
+        .unwrap()
+        .create_key("shared", Box::new(MockConfig::default()))
+        .unwrap();
+    tenant_a
+        .create_key("backup", Box::new(MockConfig::default()))
+        .unwrap();
+
+    assert_eq!(tenant_a.list_keys().unwrap(), ["backup"]);
+    assert!(tenant_b.list_keys().unwrap().is_empty());
+    assert!(matches!(
+        tenant_b.delete_key("backup"),
+        Err(SecurityModuleError::KeyError)
+    ));
+    assert!(matches!(
+        tenant_b.load_key("backup", Box::new(MockConfig::default())),
+        Err(SecurityModuleError::KeyError)
+    ));
+    assert!(matches!(
+        tenant_b.load_key("../tenant-a/backup", Box::new(MockConfig::default())),
+        Err(SecurityModuleError::KeyError)
+    ));
+    assert!(tenant_a
+        .load_key("shared", Box::new(MockConfig::default()))
+        .is_err());
+}
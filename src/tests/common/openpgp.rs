@@ -0,0 +1,366 @@
+use crate::{
+    common::{
+        crypto::{
+            algorithms::{
+                encryption::{AsymmetricEncryption, EccCurves, EccSchemeAlgorithm},
+                hashes::{Hash, Sha2Bits},
+                KeyBits,
+            },
+            public_key::PublicKey,
+        },
+        openpgp::{self, PgpKey, PgpSignature, SignatureType},
+        traits::module_provider::Provider,
+    },
+    mock::{MockConfig, MockProvider},
+    SecurityModuleError,
+};
+use openssl::pkey::PKey;
+use std::{
+    fs, io,
+    path::Path,
+    process::{Command, Output},
+    time::{Duration, UNIX_EPOCH},
+};
+
+fn provider(algorithm: AsymmetricEncryption, hash: Sha2Bits) -> MockProvider {
+    MockProvider::with_key("openpgp", MockConfig::new(algorithm, Hash::Sha2(hash)))
+}
+
+fn p256() -> MockProvider {
+    provider(
+        AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(EccCurves::P256)),
+        Sha2Bits::Sha256,
+    )
+}
+
+fn p384() -> MockProvider {
+    provider(
+        AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(EccCurves::P384)),
+        Sha2Bits::Sha384,
+    )
+}
+
+fn rsa() -> MockProvider {
+    provider(
+        AsymmetricEncryption::Rsa(KeyBits::Bits2048),
+        Sha2Bits::Sha256,
+    )
+}
+
+fn key(provider: &MockProvider) -> PgpKey {
+    let created = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+    PgpKey::new(provider.key_metadata().unwrap().public_key(), created).unwrap()
+}
+
+/// Returns the tags of the packets of `bytes`, which have new format headers.
+fn packet_tags(mut bytes: &[u8]) -> Vec<u8> {
+    let mut tags = Vec::new();
+    while let Some((&header, rest)) = bytes.split_first() {
+        tags.push(header & 0x3f);
+        let (len, rest) = match rest[0] {
+            0..=191 => (rest[0] as usize, &rest[1..]),
+            192..=223 => (
+                ((rest[0] as usize - 192) << 8) + rest[1] as usize + 192,
+                &rest[2..],
+            ),
+            _ => (
+                u32::from_be_bytes(rest[1..5].try_into().unwrap()) as usize,
+                &rest[5..],
+            ),
+        };
+        bytes = &rest[len..];
+    }
+    tags
+}
+
+#[test]
+fn test_sign_and_verify() {
+    for provider in [p256(), p384(), rsa()] {
+        let key = key(&provider);
+        let signature =
+            openpgp::sign(&provider, &key, b"app-1.0.tar.gz", SignatureType::Binary).unwrap();
+        openpgp::verify(&signature, &key, b"app-1.0.tar.gz").unwrap();
+        assert_eq!(signature.issuer_key_id(), Some(key.key_id()));
+
+        let armored: PgpSignature = signature.to_string().parse().unwrap();
+        assert_eq!(armored, signature);
+        assert_eq!(
+            PgpSignature::from_bytes(&signature.to_bytes()).unwrap(),
+            signature
+        );
+
+        assert!(matches!(
+            openpgp::verify(&signature, &key, b"app-1.1.tar.gz"),
+            Err(SecurityModuleError::InvalidSignature)
+        ));
+        let other = p256();
+        assert!(matches!(
+            openpgp::verify(&signature, &self::key(&other), b"app-1.0.tar.gz"),
+            Err(SecurityModuleError::InvalidSignature)
+        ));
+        assert!(matches!(
+            openpgp::sign(&other, &key, b"app-1.0.tar.gz", SignatureType::Binary),
+            Err(SecurityModuleError::InvalidPublicKey)
+        ));
+    }
+}
+
+#[test]
+fn test_text_signatures() {
+    let provider = p384();
+    let key = key(&provider);
+    let signature =
+        openpgp::sign(&provider, &key, b"Hello,\nWorld\n", SignatureType::Text).unwrap();
+    openpgp::verify(&signature, &key, b"Hello,\nWorld\n").unwrap();
+    openpgp::verify(&signature, &key, b"Hello,\r\nWorld\r\n").unwrap();
+    assert!(openpgp::verify(&signature, &key, b"Hello, World\n").is_err());
+
+    let binary = openpgp::sign(&provider, &key, b"Hello,\nWorld\n", SignatureType::Binary).unwrap();
+    assert!(openpgp::verify(&binary, &key, b"Hello,\r\nWorld\r\n").is_err());
+}
+
+#[test]
+fn test_keys() {
+    let key = key(&p256());
+    assert_eq!(key.fingerprint()[12..], key.key_id());
+    assert_eq!(key.fingerprint_hex().len(), 40);
+    assert_eq!(
+        key.created(),
+        UNIX_EPOCH + Duration::from_secs(1_700_000_000)
+    );
+    // The creation time is part of the fingerprint.
+    let later = PgpKey::new(
+        key.public_key(),
+        UNIX_EPOCH + Duration::from_secs(1_700_000_001),
+    )
+    .unwrap();
+    assert_ne!(later.fingerprint(), key.fingerprint());
+
+    let unsupported = provider(
+        AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(EccCurves::P256)),
+        Sha2Bits::Sha512,
+    );
+    assert!(matches!(
+        PgpKey::new(unsupported.key_metadata().unwrap().public_key(), UNIX_EPOCH),
+        Err(SecurityModuleError::UnsupportedAlgorithm)
+    ));
+    assert!(matches!(
+        PgpKey::new(key.public_key(), UNIX_EPOCH - Duration::from_secs(1)),
+        Err(SecurityModuleError::SigningError(_))
+    ));
+}
+
+#[test]
+fn test_certificate() {
+    let primary_provider = p256();
+    let primary = key(&primary_provider);
+    let subkey_provider = p384();
+    let subkey = key(&subkey_provider);
+
+    let certificate = openpgp::certificate(
+        &primary_provider,
+        &primary,
+        "Release Signing <release@example.com>",
+        None,
+    )
+    .unwrap();
+    assert_eq!(packet_tags(&certificate.to_bytes()), [6, 13, 2]);
+    let armored = certificate.to_string();
+    assert!(armored.starts_with("-----BEGIN PGP PUBLIC KEY BLOCK-----\n\n"));
+    assert!(armored.ends_with("-----END PGP PUBLIC KEY BLOCK-----\n"));
+
+    let certificate = openpgp::certificate(
+        &primary_provider,
+        &primary,
+        "Release Signing <release@example.com>",
+        Some((&subkey_provider, &subkey)),
+    )
+    .unwrap();
+    assert_eq!(packet_tags(&certificate.to_bytes()), [6, 13, 2, 14, 2]);
+
+    assert!(matches!(
+        openpgp::certificate(&primary_provider, &primary, "", None),
+        Err(SecurityModuleError::SigningError(_))
+    ));
+    assert!(matches!(
+        openpgp::certificate(
+            &primary_provider,
+            &primary,
+            "Release Signing",
+            Some((&primary_provider, &subkey))
+        ),
+        Err(SecurityModuleError::InvalidPublicKey)
+    ));
+}
+
+#[test]
+fn test_encodings() {
+    let provider = p256();
+    let key = key(&provider);
+    let signature = openpgp::sign(&provider, &key, b"data", SignatureType::Binary).unwrap();
+    let armored = signature.to_string();
+
+    // GnuPG writes old format packets and armor headers.
+    let bytes = signature.to_bytes();
+    let old_format = [&[0x88, bytes[1]][..], &bytes[2..]].concat();
+    assert_eq!(PgpSignature::from_bytes(&old_format).unwrap(), signature);
+    let with_header = armored.replacen("-----\n\n", "-----\nComment: release\n\n", 1);
+    assert_eq!(with_header.parse::<PgpSignature>().unwrap(), signature);
+
+    let lines: Vec<&str> = armored.lines().collect();
+    let checksum = lines[lines.len() - 2];
+    let wrong_checksum = armored.replace(checksum, "=AAAA");
+    for malformed in [
+        wrong_checksum.as_str(),
+        "-----BEGIN PGP SIGNATURE-----\n\nAAAA\n-----END PGP SIGNATURE-----",
+        "-----BEGIN PGP MESSAGE-----\n\nAAAA\n-----END PGP MESSAGE-----",
+    ] {
+        assert!(matches!(
+            malformed.parse::<PgpSignature>(),
+            Err(SecurityModuleError::SignatureVerificationError(_))
+        ));
+    }
+    for malformed in [
+        &bytes[..bytes.len() - 1],
+        &[bytes.clone(), bytes.clone()].concat(),
+    ] {
+        assert!(PgpSignature::from_bytes(malformed).is_err());
+    }
+}
+
+/// Signatures of GnuPG 2.2, created with `gpg --detach-sign --armor --digest-algo SHA256` and
+/// `--textmode` for the RSA key. The public keys were converted from the exported key packets.
+#[test]
+fn test_verifies_gpg_signatures() {
+    let p256 = AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(EccCurves::P256));
+    let rsa = AsymmetricEncryption::Rsa(KeyBits::Bits2048);
+    let fixtures = [
+        (
+            p256,
+            include_str!("fixtures/openpgp/gpg-p256.pem"),
+            1_792_030_176,
+            "1D71E9C8357EA8E8F8D5503966C1884CADDC4D8E",
+            &b"app-1.0.tar.gz\n"[..],
+            include_str!("fixtures/openpgp/gpg-p256.sig.asc"),
+        ),
+        (
+            rsa,
+            include_str!("fixtures/openpgp/gpg-rsa2048.pem"),
+            1_792_030_177,
+            "B08B3557A564905F5D0F97DC606485941441D9E4",
+            &b"Release notes\nLine two\n"[..],
+            include_str!("fixtures/openpgp/gpg-rsa2048.sig.asc"),
+        ),
+    ];
+    for (algorithm, pem, created, fingerprint, data, signature) in fixtures {
+        let der = PKey::public_key_from_pem(pem.as_bytes())
+            .and_then(|key| key.public_key_to_der())
+            .unwrap();
+        let public_key =
+            PublicKey::from_der(&der, algorithm, Hash::Sha2(Sha2Bits::Sha256)).unwrap();
+        let key = PgpKey::new(&public_key, UNIX_EPOCH + Duration::from_secs(created)).unwrap();
+        assert_eq!(key.fingerprint_hex(), fingerprint);
+
+        let signature: PgpSignature = signature.parse().unwrap();
+        assert_eq!(signature.issuer_key_id(), Some(key.key_id()));
+        openpgp::verify(&signature, &key, data).unwrap();
+        assert!(matches!(
+            openpgp::verify(&signature, &key, b"app-1.1.tar.gz\n"),
+            Err(SecurityModuleError::InvalidSignature)
+        ));
+    }
+}
+
+/// Runs `gpg` in `home`, or returns `None` if GnuPG is not installed.
+fn gpg(home: &Path, args: &[&str]) -> Option<Output> {
+    match Command::new("gpg")
+        .arg("--homedir")
+        .arg(home)
+        .args(["--batch", "--no-autostart", "--status-fd", "1"])
+        .args(args)
+        .output()
+    {
+        Ok(output) => Some(output),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => panic!("Cannot run gpg: {}", e),
+    }
+}
+
+/// Verifies the detached `signature` of `data` with GnuPG and returns its status lines.
+fn gpg_verify(home: &Path, signature: &PgpSignature, data: &[u8]) -> String {
+    fs::write(home.join("data"), data).unwrap();
+    fs::write(home.join("data.asc"), signature.to_string()).unwrap();
+    let home_str = home.to_str().unwrap();
+    let output = gpg(
+        home,
+        &[
+            "--verify",
+            &format!("{}/data.asc", home_str),
+            &format!("{}/data", home_str),
+        ],
+    )
+    .unwrap();
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn test_gpg_accepts_certificates_and_signatures() {
+    let home = std::env::temp_dir().join(format!("openpgp_gpg_{}", std::process::id()));
+    fs::create_dir_all(&home).unwrap();
+    let primary_provider = p256();
+    let primary = key(&primary_provider);
+    let subkey_provider = p384();
+    let subkey = key(&subkey_provider);
+    let rsa_provider = rsa();
+    let rsa_key = key(&rsa_provider);
+
+    let certificates = [
+        openpgp::certificate(
+            &primary_provider,
+            &primary,
+            "Release Signing <release@example.com>",
+            Some((&subkey_provider, &subkey)),
+        )
+        .unwrap(),
+        openpgp::certificate(&rsa_provider, &rsa_key, "RSA <rsa@example.com>", None).unwrap(),
+    ];
+    for certificate in &certificates {
+        fs::write(home.join("certificate.asc"), certificate.to_string()).unwrap();
+        let certificate_path = home.join("certificate.asc");
+        let Some(output) = gpg(&home, &["--import", certificate_path.to_str().unwrap()]) else {
+            eprintln!("Skipping the GnuPG interoperability, gpg is not installed");
+            fs::remove_dir_all(&home).unwrap();
+            return;
+        };
+        assert!(
+            String::from_utf8_lossy(&output.stdout).contains("IMPORT_OK"),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    // GnuPG drops subkeys whose binding signature is invalid.
+    let listing = gpg(&home, &["--with-colons", "--list-keys"]).unwrap();
+    let listing = String::from_utf8(listing.stdout).unwrap();
+    for key in [&primary, &subkey, &rsa_key] {
+        assert!(listing.contains(&format!("fpr:::::::::{}:", key.fingerprint_hex())));
+    }
+
+    let signatures = [
+        (&primary_provider, &primary, SignatureType::Binary),
+        (&subkey_provider, &subkey, SignatureType::Binary),
+        (&rsa_provider, &rsa_key, SignatureType::Text),
+    ];
+    for (provider, key, signature_type) in signatures {
+        let signature = openpgp::sign(provider, key, b"Hello,\nWorld\n", signature_type).unwrap();
+        let status = gpg_verify(&home, &signature, b"Hello,\nWorld\n");
+        assert!(
+            status.contains(&format!("VALIDSIG {}", key.fingerprint_hex())),
+            "{}",
+            status
+        );
+        let status = gpg_verify(&home, &signature, b"Hello, World\n");
+        assert!(status.contains("BADSIG"), "{}", status);
+    }
+    fs::remove_dir_all(&home).unwrap();
+}
@@ -0,0 +1,128 @@
+use crate::{
+    common::{
+        crypto::algorithms::{
+            encryption::AsymmetricEncryption,
+            hashes::{Hash, Sha2Bits},
+            KeyBits,
+        },
+        password::{harden_password_with, verify_password, PasswordParams, Verifier},
+    },
+    mock::{MockConfig, MockProvider},
+    SecurityModuleError,
+};
+
+/// Keeps the tests fast, real applications use `PasswordParams::INTERACTIVE` or stronger.
+const PARAMS: PasswordParams = PasswordParams {
+    passes: 1,
+    memory_kib: PasswordParams::MIN_MEMORY_KIB,
+};
+
+fn device() -> MockProvider {
+    MockProvider::with_key(
+        "password_key",
+        MockConfig::new(
+            AsymmetricEncryption::Rsa(KeyBits::Bits2048),
+            Hash::Sha2(Sha2Bits::Sha256),
+        ),
+    )
+}
+
+fn harden(provider: &MockProvider, password: &[u8]) -> Verifier {
+    harden_password_with(provider, "password_key", password, PARAMS).unwrap()
+}
+
+#[test]
+fn test_harden_and_verify_password() {
+    let provider = device();
+    let verifier = harden(&provider, b"correct horse");
+    assert_eq!(verifier.key_id(), "password_key");
+    assert_eq!(verifier.params(), PARAMS);
+
+    assert!(verify_password(&provider, &verifier, b"correct horse").unwrap());
+    assert!(!verify_password(&provider, &verifier, b"correct horsf").unwrap());
+    assert!(!verify_password(&provider, &verifier, b"").unwrap());
+
+    // Every verifier uses a fresh salt and HMAC key.
+    let again = harden(&provider, b"correct horse");
+    assert_ne!(again.to_string(), verifier.to_string());
+    assert!(verify_password(&provider, &again, b"correct horse").unwrap());
+
+    // Debug output leaves out the salt, the encrypted key and the tag.
+    let encoded = verifier.to_string();
+    let tag = encoded.rsplit('$').next().unwrap();
+    assert!(!format!("{:?}", verifier).contains(tag));
+}
+
+#[test]
+fn test_verifiers_are_bound_to_the_device() {
+    let verifier = harden(&device(), b"correct horse");
+    // A device with another key, e.g. an attacker with a stolen database, cannot check guesses.
+    assert!(verify_password(&device(), &verifier, b"correct horse").is_err());
+}
+
+#[test]
+fn test_encoding() {
+    let provider = device();
+    let verifier = harden(&provider, b"correct horse");
+    let encoded = verifier.to_string();
+    assert!(encoded.starts_with("$crypto-layer-argon2id$v=1$m=8,t=1$"));
+    assert_eq!(encoded.parse::<Verifier>().unwrap(), verifier);
+
+    // A modified tag rejects the correct password.
+    let fields: Vec<&str> = encoded.split('$').collect();
+    let mut tag = fields[7].as_bytes().to_vec();
+    tag[0] = if tag[0] == b'A' { b'B' } else { b'A' };
+    let modified = [&fields[..7].join("$"), std::str::from_utf8(&tag).unwrap()].join("$");
+    let modified: Verifier = modified.parse().unwrap();
+    assert!(!verify_password(&provider, &modified, b"correct horse").unwrap());
+
+    // Weakened parameters change the Argon2id output.
+    let weakened: Verifier = encoded.replace("m=8,t=1", "m=9,t=1").parse().unwrap();
+    assert!(!verify_password(&provider, &weakened, b"correct horse").unwrap());
+
+    for invalid in [
+        String::new(),
+        "$argon2id$v=19$m=8,t=1$c2FsdA$aGFzaA".to_owned(),
+        encoded.replace("v=1", "v=2"),
+        encoded.replace("m=8", "m=4"),
+        encoded.replace("m=8", "m=99999999"),
+        encoded.replace("t=1", "t=0"),
+        fields[..7].join("$"),
+        format!("{}$", encoded),
+        encoded.replace(fields[5], "c2FsdA"),
+    ] {
+        assert!(
+            matches!(
+                invalid.parse::<Verifier>(),
+                Err(SecurityModuleError::Encoding(_))
+            ),
+            "{:?} was accepted",
+            invalid
+        );
+    }
+}
+
+#[test]
+fn test_invalid_params() {
+    let provider = device();
+    for params in [
+        PasswordParams {
+            passes: 0,
+            ..PARAMS
+        },
+        PasswordParams {
+            memory_kib: PasswordParams::MIN_MEMORY_KIB - 1,
+            ..PARAMS
+        },
+        PasswordParams {
+            memory_kib: PasswordParams::MAX_MEMORY_KIB + 1,
+            ..PARAMS
+        },
+    ] {
+        assert!(matches!(
+            harden_password_with(&provider, "password_key", b"password", params),
+            Err(SecurityModuleError::Encoding(_))
+        ));
+    }
+    assert_eq!(PasswordParams::default(), PasswordParams::INTERACTIVE);
+}
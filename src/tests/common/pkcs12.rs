@@ -0,0 +1,139 @@
+use crate::{
+    common::{
+        pkcs12::{self, Pkcs12Options},
+        traits::module_provider::Provider,
+    },
+    mock::{MockConfig, MockProvider},
+    SecurityModuleError,
+};
+use crypto_layer_core::CoreError;
+use openssl::{
+    asn1::Asn1Time,
+    ec::{EcGroup, EcKey},
+    hash::MessageDigest,
+    nid::Nid,
+    pkcs12::Pkcs12,
+    pkey::{HasPublic, PKey, PKeyRef, Private},
+    x509::{X509Builder, X509NameBuilder},
+};
+
+const PASSWORD: &str = "correct horse battery staple";
+
+fn provider(exportable: bool) -> MockProvider {
+    let config = MockConfig {
+        exportable,
+        ..MockConfig::default()
+    };
+    MockProvider::with_key("identity", Box::new(config))
+}
+
+fn p256() -> PKey<Private> {
+    let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+    PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap()
+}
+
+/// Issues a DER encoded certificate named `name` for `public_key`, signed by `issuer_key`.
+fn certificate(
+    name: &str,
+    public_key: &PKeyRef<impl HasPublic>,
+    issuer_key: &PKey<Private>,
+) -> Vec<u8> {
+    let mut subject = X509NameBuilder::new().unwrap();
+    subject.append_entry_by_nid(Nid::COMMONNAME, name).unwrap();
+    let subject = subject.build();
+    let mut builder = X509Builder::new().unwrap();
+    builder.set_version(2).unwrap();
+    builder.set_subject_name(&subject).unwrap();
+    builder.set_pubkey(public_key).unwrap();
+    builder
+        .set_not_before(&Asn1Time::days_from_now(0).unwrap())
+        .unwrap();
+    builder
+        .set_not_after(&Asn1Time::days_from_now(1).unwrap())
+        .unwrap();
+    builder.sign(issuer_key, MessageDigest::sha256()).unwrap();
+    builder.build().to_der().unwrap()
+}
+
+/// The certificate of the key of `provider` and the certificate of its issuing CA.
+fn chain(provider: &MockProvider) -> Vec<Vec<u8>> {
+    let public_key = provider
+        .key_metadata()
+        .unwrap()
+        .public_key()
+        .to_der()
+        .unwrap();
+    let public_key = PKey::public_key_from_der(&public_key).unwrap();
+    let ca_key = p256();
+    vec![
+        certificate("Device", &public_key, &ca_key),
+        certificate("Device CA", &ca_key, &ca_key),
+    ]
+}
+
+#[test]
+fn test_export_identity() {
+    let provider = provider(true);
+    let chain = chain(&provider);
+    let options = Pkcs12Options::default().friendly_name("Device identity");
+    let der = pkcs12::export_identity(&provider, &chain, PASSWORD, &options).unwrap();
+
+    let parsed = Pkcs12::from_der(&der).unwrap().parse2(PASSWORD).unwrap();
+    let certificate = parsed.cert.unwrap();
+    assert_eq!(certificate.to_der().unwrap(), chain[0]);
+    assert!(parsed
+        .pkey
+        .unwrap()
+        .public_eq(&certificate.public_key().unwrap()));
+    let ca: Vec<Vec<u8>> = parsed
+        .ca
+        .unwrap()
+        .iter()
+        .map(|certificate| certificate.to_der().unwrap())
+        .collect();
+    assert_eq!(ca, chain[1..]);
+    assert!(Pkcs12::from_der(&der).unwrap().parse2("wrong").is_err());
+
+    let legacy = Pkcs12Options::default().legacy();
+    let der = pkcs12::export_identity(&provider, &chain[..1], PASSWORD, &legacy).unwrap();
+    let parsed = Pkcs12::from_der(&der).unwrap().parse2(PASSWORD).unwrap();
+    assert_eq!(parsed.cert.unwrap().to_der().unwrap(), chain[0]);
+    assert!(parsed.ca.is_none_or(|ca| ca.is_empty()));
+}
+
+#[test]
+fn test_rejected_exports() {
+    let options = Pkcs12Options::default();
+    let provider = provider(false);
+    let chain = chain(&provider);
+    assert!(matches!(
+        pkcs12::export_identity(&provider, &chain, PASSWORD, &options),
+        Err(SecurityModuleError::UnsupportedOperation(_))
+    ));
+
+    let provider = self::provider(true);
+    // The certificate chain of another key.
+    assert!(matches!(
+        pkcs12::export_identity(&provider, &chain, PASSWORD, &options),
+        Err(SecurityModuleError::InvalidPublicKey)
+    ));
+    let chain = self::chain(&provider);
+    assert!(matches!(
+        pkcs12::export_identity(&provider, &chain, "", &options),
+        Err(SecurityModuleError::Encoding(CoreError::MissingField(
+            "password"
+        )))
+    ));
+    assert!(matches!(
+        pkcs12::export_identity(&provider, &[], PASSWORD, &options),
+        Err(SecurityModuleError::Encoding(CoreError::MissingField(
+            "certificate"
+        )))
+    ));
+    assert!(matches!(
+        pkcs12::export_identity(&provider, &[b"certificate".to_vec()], PASSWORD, &options),
+        Err(SecurityModuleError::Encoding(CoreError::InvalidField(
+            "certificate"
+        )))
+    ));
+}
@@ -0,0 +1,86 @@
+use crate::common::{
+    config::{CryptoConfig, FallbackPolicy},
+    crypto::key_spec::{KeyAlgorithm, KeyPurpose, KeySpec},
+    error::SecurityModuleError,
+    plan::{InstancePlan, KeyPlan},
+    sunset::SunsetAction,
+};
+
+fn spec(algorithm: KeyAlgorithm) -> KeySpec {
+    KeySpec::builder()
+        .algorithm(algorithm)
+        .usage(KeyPurpose::Sign)
+        .label("identity")
+        .build()
+        .unwrap()
+}
+
+#[test]
+fn test_instance_plan_scopes_key_id() {
+    let config = CryptoConfig::from_toml(
+        r#"
+providers = ["yubikey", "macos"]
+fallback = "next_provider"
+namespace = "com.example.mail"
+"#,
+    )
+    .unwrap();
+    let plan = InstancePlan::for_config(&config, "identity").unwrap();
+
+    assert_eq!(plan.key_id, "identity");
+    assert_eq!(plan.stored_as, "com.example.mail/identity");
+    assert_eq!(plan.namespace.as_deref(), Some("com.example.mail"));
+    assert_eq!(plan.fallback, FallbackPolicy::NextProvider);
+    assert_eq!(plan.providers.len(), config.security_modules().len());
+    for provider in &plan.providers {
+        assert!(provider.storage.contains("'com.example.mail/identity'"));
+    }
+    #[cfg(all(feature = "macos", not(feature = "hsm")))]
+    assert_eq!(plan.providers[0].provider, "macos");
+}
+
+#[test]
+fn test_instance_plan_rejects_invalid_key_id() {
+    let result = InstancePlan::for_config(&CryptoConfig::default(), "../identity");
+
+    assert!(matches!(result, Err(SecurityModuleError::InvalidKeyId(_))));
+}
+
+#[test]
+fn test_key_plan_reports_deprecation() {
+    let mut config = CryptoConfig::default();
+    config
+        .deprecated
+        .insert("rsa2048".to_owned(), SunsetAction::Warn);
+    config
+        .deprecated
+        .insert("p256".to_owned(), SunsetAction::Deny);
+
+    let plan = KeyPlan::for_config(&config, spec(KeyAlgorithm::EcP256)).unwrap();
+    assert_eq!(
+        plan.deprecation,
+        Some(("p256".to_owned(), SunsetAction::Deny))
+    );
+    assert!(!plan.allowed());
+
+    let plan = KeyPlan::for_config(&config, spec(KeyAlgorithm::Rsa2048)).unwrap();
+    assert!(plan.allowed());
+
+    let plan = KeyPlan::for_config(&config, spec(KeyAlgorithm::Rsa4096)).unwrap();
+    assert_eq!(plan.deprecation, None);
+    assert_eq!(plan.instance.stored_as, "identity");
+}
+
+#[test]
+fn test_builder_plan_validates_spec() {
+    let result = KeySpec::builder()
+        .algorithm(KeyAlgorithm::Aes256Gcm)
+        .usage(KeyPurpose::Sign)
+        .label("identity")
+        .plan();
+
+    assert!(matches!(
+        result,
+        Err(SecurityModuleError::InvalidKeySpec(_))
+    ));
+}
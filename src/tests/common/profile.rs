@@ -0,0 +1,77 @@
+use crate::common::{
+    config::{CryptoConfig, FallbackPolicy, PROVIDER_NAMES},
+    crypto::{
+        algorithms::encryption::{AsymmetricEncryption, EccCurves, EccSchemeAlgorithm},
+        key_spec::{AccessControl, KeyPurpose},
+    },
+    profile::Profile,
+};
+
+#[test]
+fn test_high_security_key_spec() {
+    let spec = Profile::HighSecurity
+        .key_spec("device-identity")
+        .build()
+        .unwrap();
+
+    assert_eq!(spec.access(), AccessControl::BiometryCurrentSet);
+    assert!(spec.allows(KeyPurpose::Sign));
+    assert!(matches!(
+        spec.asymmetric_algorithm(),
+        Some(AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(
+            EccCurves::P256
+        )))
+    ));
+}
+
+#[test]
+fn test_compatibility_key_spec_can_be_adjusted() {
+    let spec = Profile::Compatibility
+        .key_spec("backup")
+        .usage(KeyPurpose::Encrypt)
+        .build()
+        .unwrap();
+
+    assert_eq!(spec.access(), AccessControl::None);
+    assert!(matches!(
+        spec.asymmetric_algorithm(),
+        Some(AsymmetricEncryption::Rsa(_))
+    ));
+    assert!(spec.allows(KeyPurpose::Encrypt));
+}
+
+#[test]
+fn test_providers() {
+    assert!(Profile::HighSecurity.requires_hardware());
+    assert!(!Profile::HighSecurity
+        .providers()
+        .contains(&"nks".to_owned()));
+    assert_eq!(Profile::HighSecurity.fallback(), FallbackPolicy::Never);
+
+    assert!(!Profile::Compatibility.requires_hardware());
+    assert_eq!(
+        Profile::Compatibility.providers().len(),
+        PROVIDER_NAMES.len()
+    );
+    assert_eq!(
+        Profile::Compatibility.fallback(),
+        FallbackPolicy::NextProvider
+    );
+}
+
+#[test]
+fn test_apply_keeps_the_rest_of_the_config() {
+    let config = CryptoConfig {
+        namespace: Some("com.example.mail".to_owned()),
+        providers: vec!["nks".to_owned()],
+        ..CryptoConfig::default()
+    };
+    let applied = Profile::HighSecurity.apply(config.clone());
+
+    assert_eq!(applied.providers, Profile::HighSecurity.providers());
+    assert_eq!(applied.fallback, FallbackPolicy::Never);
+    assert_eq!(applied.namespace, config.namespace);
+    assert_eq!(applied.timeouts, config.timeouts);
+    assert!(applied.validate().is_ok());
+    assert!(Profile::Compatibility.apply(config).validate().is_ok());
+}
@@ -0,0 +1,171 @@
+use crate::{
+    common::{
+        crypto::{
+            algorithms::{
+                encryption::{AsymmetricEncryption, EccCurves, EccSchemeAlgorithm},
+                hashes::{Hash, Sha2Bits},
+            },
+            public_key::PublicKey,
+        },
+        proof_of_possession::{create_proof, verify_proof, verify_proof_at, Proof, ProofPolicy},
+        traits::module_provider::Provider,
+    },
+    mock::{MockConfig, MockProvider},
+    SecurityModuleError,
+};
+use base64::{prelude::BASE64_URL_SAFE_NO_PAD, Engine};
+use std::time::{Duration, SystemTime};
+
+const AUDIENCE: &str = "https://api.example.com";
+const CHALLENGE: &[u8; 32] = b"0123456789abcdef0123456789abcdef";
+
+fn device() -> MockProvider {
+    MockProvider::with_key(
+        "device_key",
+        MockConfig::new(
+            AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(EccCurves::P256)),
+            Hash::Sha2(Sha2Bits::Sha256),
+        ),
+    )
+}
+
+fn public_key(provider: &MockProvider) -> PublicKey {
+    provider.key_metadata().unwrap().public_key().clone()
+}
+
+fn verify(proof: &Proof, public_key: &PublicKey) -> Result<(), SecurityModuleError> {
+    verify_proof(
+        proof,
+        public_key,
+        AUDIENCE,
+        CHALLENGE,
+        &ProofPolicy::default(),
+    )
+    .map(|_| ())
+}
+
+#[test]
+fn test_create_and_verify_proof() {
+    let provider = device();
+    let proof = create_proof(&provider, AUDIENCE, CHALLENGE).unwrap();
+
+    let claims = verify_proof(
+        &proof,
+        &public_key(&provider),
+        AUDIENCE,
+        CHALLENGE,
+        &ProofPolicy::default(),
+    )
+    .unwrap();
+    assert_eq!(claims.audience, AUDIENCE);
+    assert_eq!(claims.key_id, "device_key");
+    assert_eq!(claims.key_thumbprint.len(), 64);
+    assert_eq!(claims.attestation, None);
+    assert!(!claims.exportable);
+    assert!(!claims.fips_mode);
+
+    // The encoding round trips and fits into an HTTP header.
+    let encoded = proof.to_string();
+    assert!(encoded
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c)));
+    let parsed: Proof = encoded.parse().unwrap();
+    assert_eq!(parsed, proof);
+    assert!(verify(&parsed, &public_key(&provider)).is_ok());
+
+    // Every proof has a fresh nonce.
+    let again = create_proof(&provider, AUDIENCE, CHALLENGE).unwrap();
+    assert_ne!(again.claims().nonce, proof.claims().nonce);
+}
+
+#[test]
+fn test_proofs_are_bound_to_key_audience_and_challenge() {
+    let provider = device();
+    let proof = create_proof(&provider, AUDIENCE, CHALLENGE).unwrap();
+    let public_key = public_key(&provider);
+
+    assert!(matches!(
+        verify(&proof, &self::public_key(&device())),
+        Err(SecurityModuleError::InvalidSignature)
+    ));
+    assert!(matches!(
+        verify_proof(
+            &proof,
+            &public_key,
+            "https://other.example.com",
+            CHALLENGE,
+            &ProofPolicy::default()
+        ),
+        Err(SecurityModuleError::InvalidProof(_))
+    ));
+    assert!(matches!(
+        verify_proof(
+            &proof,
+            &public_key,
+            AUDIENCE,
+            &[0; 32],
+            &ProofPolicy::default()
+        ),
+        Err(SecurityModuleError::InvalidProof(_))
+    ));
+
+    // Modified claims invalidate the signature.
+    let encoded = proof.to_string();
+    let (claims, signature) = encoded.split_once('.').unwrap();
+    let json = String::from_utf8(BASE64_URL_SAFE_NO_PAD.decode(claims).unwrap()).unwrap();
+    let modified = json.replace(AUDIENCE, "https://api.example.org");
+    let modified: Proof = format!("{}.{}", BASE64_URL_SAFE_NO_PAD.encode(modified), signature)
+        .parse()
+        .unwrap();
+    assert!(matches!(
+        verify_proof(
+            &modified,
+            &public_key,
+            "https://api.example.org",
+            CHALLENGE,
+            &ProofPolicy::default()
+        ),
+        Err(SecurityModuleError::InvalidSignature)
+    ));
+}
+
+#[test]
+fn test_proofs_expire() {
+    let provider = device();
+    let proof = create_proof(&provider, AUDIENCE, CHALLENGE).unwrap();
+    let public_key = public_key(&provider);
+    let policy = ProofPolicy::default();
+    let verify_at = |now| verify_proof_at(&proof, &public_key, AUDIENCE, CHALLENGE, &policy, now);
+
+    let now = SystemTime::now();
+    assert!(verify_at(now + Duration::from_secs(60)).is_ok());
+    assert!(matches!(
+        verify_at(now + Duration::from_secs(10 * 60)),
+        Err(SecurityModuleError::InvalidProof(_))
+    ));
+    assert!(matches!(
+        verify_at(now - Duration::from_secs(10 * 60)),
+        Err(SecurityModuleError::InvalidProof(_))
+    ));
+}
+
+#[test]
+fn test_invalid_challenges_and_encodings() {
+    let provider = device();
+    for challenge in [&[0; 15][..], &[0; 257][..]] {
+        assert!(matches!(
+            create_proof(&provider, AUDIENCE, challenge),
+            Err(SecurityModuleError::InvalidProof(_))
+        ));
+    }
+    for invalid in ["", "no-signature", "!!.AAAA", "e30.AAAA"] {
+        assert!(
+            matches!(
+                invalid.parse::<Proof>(),
+                Err(SecurityModuleError::InvalidProof(_))
+            ),
+            "{:?} was accepted",
+            invalid
+        );
+    }
+}
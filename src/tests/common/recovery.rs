@@ -0,0 +1,90 @@
+use crate::{
+    common::recovery::{self, Share, MAX_SECRET_LEN},
+    SecurityModuleError,
+};
+use crypto_layer_core::CoreError;
+
+const SECRET: &[u8] = b"wrapped master secret of the key hierarchy";
+
+#[test]
+fn test_split_and_combine() {
+    let shares = recovery::split(SECRET, 3, 5).unwrap();
+    assert_eq!(shares.len(), 5);
+    assert_eq!(
+        shares.iter().map(Share::index).collect::<Vec<_>>(),
+        [1, 2, 3, 4, 5]
+    );
+    assert!(shares.iter().all(|share| share.threshold() == 3));
+
+    // Any 3 shares reconstruct the secret, in any order.
+    for indexes in [[0, 1, 2], [4, 2, 0], [1, 3, 4]] {
+        let subset: Vec<Share> = indexes.iter().map(|&i| shares[i].clone()).collect();
+        assert_eq!(recovery::combine(&subset).unwrap(), SECRET);
+    }
+    assert_eq!(recovery::combine(&shares).unwrap(), SECRET);
+
+    // Shares survive their text encoding.
+    let encoded: Vec<String> = shares.iter().map(Share::to_string).collect();
+    let parsed: Vec<Share> = encoded[2..].iter().map(|s| s.parse().unwrap()).collect();
+    assert_eq!(parsed, shares[2..]);
+    assert_eq!(recovery::combine(&parsed).unwrap(), SECRET);
+    assert!(!format!("{:?}", shares[0]).contains("value"));
+
+    // Every split is random.
+    assert_ne!(recovery::split(SECRET, 3, 5).unwrap()[0], shares[0]);
+    let n_of_n = recovery::split(&[0x42], 255, 255).unwrap();
+    assert_eq!(recovery::combine(&n_of_n).unwrap(), [0x42]);
+}
+
+#[test]
+fn test_rejected_shares() {
+    let shares = recovery::split(SECRET, 2, 3).unwrap();
+    let other = recovery::split(SECRET, 2, 3).unwrap();
+    for invalid in [
+        vec![],
+        vec![shares[0].clone()],
+        vec![shares[0].clone(), shares[0].clone()],
+        vec![shares[0].clone(), other[1].clone()],
+    ] {
+        assert!(matches!(
+            recovery::combine(&invalid),
+            Err(SecurityModuleError::DecryptionError(_))
+        ));
+    }
+
+    // A modified value is detected by the checksum of the encoding, or, if the checksum is
+    // recomputed, by the check value of the secret.
+    let mut bytes = shares[1].to_bytes();
+    bytes[45] ^= 1;
+    assert!(matches!(
+        Share::from_bytes(&bytes),
+        Err(SecurityModuleError::Encoding(CoreError::InvalidField(
+            "share checksum"
+        )))
+    ));
+    let content_len = bytes.len() - 4;
+    let checksum = openssl::sha::sha256(&bytes[..content_len]);
+    bytes[content_len..].copy_from_slice(&checksum[..4]);
+    let modified = Share::from_bytes(&bytes).unwrap();
+    assert!(matches!(
+        recovery::combine(&[shares[0].clone(), modified]),
+        Err(SecurityModuleError::DecryptionError(_))
+    ));
+    assert!(matches!(
+        Share::from_bytes(&bytes[..20]),
+        Err(SecurityModuleError::Encoding(CoreError::Truncated))
+    ));
+    assert!("not a share".parse::<Share>().is_err());
+
+    for (secret, threshold, count) in [
+        (&[][..], 2, 3),
+        (&[0; MAX_SECRET_LEN + 1][..], 2, 3),
+        (SECRET, 1, 3),
+        (SECRET, 4, 3),
+    ] {
+        assert!(matches!(
+            recovery::split(secret, threshold, count),
+            Err(SecurityModuleError::Encoding(_))
+        ));
+    }
+}
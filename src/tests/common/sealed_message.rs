@@ -0,0 +1,94 @@
+use crate::{
+    common::{
+        crypto::{
+            algorithms::{
+                encryption::{AsymmetricEncryption, EccCurves, EccSchemeAlgorithm},
+                hashes::{Hash, Sha2Bits},
+                KeyBits,
+            },
+            public_key::PublicKey,
+        },
+        ecies,
+        sealed_message::{open_message, seal_message},
+        traits::module_provider::Provider,
+    },
+    mock::{MockConfig, MockProvider},
+    SecurityModuleError,
+};
+
+fn device(key_algorithm: AsymmetricEncryption) -> MockProvider {
+    MockProvider::with_key(
+        "device_key",
+        MockConfig::new(key_algorithm, Hash::Sha2(Sha2Bits::Sha256)),
+    )
+}
+
+fn p256_device() -> MockProvider {
+    device(AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(
+        EccCurves::P256,
+    )))
+}
+
+fn public_key(provider: &MockProvider) -> PublicKey {
+    provider.key_metadata().unwrap().public_key().clone()
+}
+
+#[test]
+fn test_seal_and_open_message() {
+    let (alice, bob) = (p256_device(), p256_device());
+    let sealed = seal_message(&alice, &public_key(&bob), b"transfer 10 EUR").unwrap();
+    assert_eq!(
+        open_message(&bob, &public_key(&alice), &sealed).unwrap(),
+        b"transfer 10 EUR"
+    );
+    assert!(!sealed.windows(8).any(|window| window == b"transfer"));
+
+    // RSA keys can sign, only the recipient needs an EC key.
+    let rsa = device(AsymmetricEncryption::Rsa(KeyBits::Bits2048));
+    let sealed = seal_message(&rsa, &public_key(&bob), b"").unwrap();
+    assert_eq!(open_message(&bob, &public_key(&rsa), &sealed).unwrap(), b"");
+    assert!(matches!(
+        seal_message(&alice, &public_key(&rsa), b"message"),
+        Err(SecurityModuleError::InvalidPublicKey)
+    ));
+}
+
+#[test]
+fn test_wrong_sender_or_recipient() {
+    let (alice, bob, mallory) = (p256_device(), p256_device(), p256_device());
+    let sealed = seal_message(&alice, &public_key(&bob), b"message").unwrap();
+
+    assert!(matches!(
+        open_message(&bob, &public_key(&mallory), &sealed),
+        Err(SecurityModuleError::InvalidSignature)
+    ));
+    assert!(matches!(
+        open_message(&mallory, &public_key(&alice), &sealed),
+        Err(SecurityModuleError::DecryptionError(_))
+    ));
+
+    let mut modified = sealed.clone();
+    *modified.last_mut().unwrap() ^= 1;
+    assert!(open_message(&bob, &public_key(&alice), &modified).is_err());
+}
+
+#[test]
+fn test_forwarded_messages_are_rejected() {
+    let (alice, bob, carol) = (p256_device(), p256_device(), p256_device());
+    let sealed = seal_message(&alice, &public_key(&bob), b"I love you").unwrap();
+
+    // Bob decrypts the message and encrypts the signed content for Carol.
+    let signed = ecies::decrypt(&bob, &sealed).unwrap();
+    let forwarded = ecies::encrypt_for(&public_key(&carol), &signed).unwrap();
+    assert!(matches!(
+        open_message(&carol, &public_key(&alice), &forwarded),
+        Err(SecurityModuleError::InvalidSignature)
+    ));
+
+    // Truncated plaintexts are rejected.
+    let truncated = ecies::encrypt_for(&public_key(&bob), &[0xff]).unwrap();
+    assert!(matches!(
+        open_message(&bob, &public_key(&alice), &truncated),
+        Err(SecurityModuleError::DecryptionError(_))
+    ));
+}
@@ -0,0 +1,177 @@
+use crate::common::{
+    config::CryptoConfig,
+    crypto::{
+        algorithms::{
+            encryption::{AsymmetricEncryption, EccCurves, EccSchemeAlgorithm},
+            hashes::{Hash, Sha2Bits},
+        },
+        envelope::{AeadAlgorithm, Envelope},
+        kdf::Kdf,
+        key_metadata::KeyMetadata,
+        key_spec::{AccessControl, KeyAlgorithm, KeyPurpose, KeySpec},
+        public_key::PublicKey,
+    },
+    error::{ErrorReport, SecurityModuleError},
+    key_id::KeyId,
+    plan::KeyPlan,
+    session_pool::SessionPoolConfig,
+};
+use crypto_layer_core::CoreError;
+use openssl::{
+    ec::{EcGroup, EcKey},
+    nid::Nid,
+    pkey::PKey,
+};
+use serde_json::json;
+
+#[test]
+fn test_key_spec_round_trip() {
+    let spec = KeySpec::builder()
+        .algorithm(KeyAlgorithm::EcP384)
+        .usage(KeyPurpose::Sign)
+        .access(AccessControl::BiometryCurrentSet)
+        .label("device-identity")
+        .build()
+        .unwrap();
+
+    let json = serde_json::to_value(&spec).unwrap();
+    assert_eq!(json["label"], "device-identity");
+    assert_eq!(json["purposes"], json!(["Sign"]));
+
+    let decoded: KeySpec = serde_json::from_value(json).unwrap();
+    assert_eq!(decoded.algorithm(), KeyAlgorithm::EcP384);
+    assert_eq!(decoded.access(), AccessControl::BiometryCurrentSet);
+    assert!(decoded.allows(KeyPurpose::Sign));
+    assert!(matches!(decoded.hash(), Hash::Sha2(Sha2Bits::Sha384)));
+}
+
+#[test]
+fn test_deserialized_key_spec_is_validated() {
+    let spec: KeySpec = serde_json::from_value(json!({
+        "algorithm": "Rsa2048",
+        "purposes": ["Encrypt"],
+        "label": "backup",
+    }))
+    .unwrap();
+    assert!(matches!(spec.access(), AccessControl::None));
+
+    let err = serde_json::from_value::<KeySpec>(json!({
+        "algorithm": "Aes128Gcm",
+        "purposes": ["Sign"],
+        "label": "backup",
+    }))
+    .unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("Aes128Gcm keys cannot be used for Sign"));
+    assert!(serde_json::from_value::<KeySpec>(json!({ "label": "backup" })).is_err());
+}
+
+#[test]
+fn test_key_ids_are_validated() {
+    let key_id = KeyId::new("device-identity").unwrap();
+    assert_eq!(
+        serde_json::to_value(&key_id).unwrap(),
+        json!("device-identity")
+    );
+    assert_eq!(
+        serde_json::from_value::<KeyId>(json!("device-identity")).unwrap(),
+        key_id
+    );
+    assert!(serde_json::from_value::<KeyId>(json!("../other-key")).is_err());
+}
+
+#[test]
+fn test_key_metadata_round_trip() {
+    let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+    let key = PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap();
+    let public_key = PublicKey::from_der(
+        &key.public_key_to_der().unwrap(),
+        AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(EccCurves::P256)),
+        Hash::Sha2(Sha2Bits::Sha256),
+    )
+    .unwrap();
+    let metadata = KeyMetadata::new("device-identity", public_key)
+        .unwrap()
+        .with_attestation(vec![vec![0x30, 0x82]]);
+
+    let json = serde_json::to_string(&metadata).unwrap();
+    let decoded: KeyMetadata = serde_json::from_str(&json).unwrap();
+    assert_eq!(decoded.key_id(), "device-identity");
+    assert_eq!(decoded.public_key_der(), metadata.public_key_der());
+    assert_eq!(decoded.attestation(), metadata.attestation());
+    assert_eq!(decoded.fetched_at(), metadata.fetched_at());
+
+    let mut json: serde_json::Value = serde_json::from_str(&json).unwrap();
+    json["public_key_der"] = json!([1, 2, 3]);
+    assert!(serde_json::from_value::<KeyMetadata>(json).is_err());
+}
+
+#[test]
+fn test_envelope_round_trip() {
+    let envelope = Envelope {
+        aead: AeadAlgorithm::Aes256Gcm,
+        key_id: "vault".to_owned(),
+        nonce: vec![0; 12],
+        wrapped_key: Some(vec![1; 32]),
+        ephemeral_public_key: None,
+        kdf: Some(Kdf::HkdfSha256),
+        salt: None,
+        extensions: vec![(0x90, b"extension".to_vec())],
+        ciphertext: vec![2; 16],
+    };
+
+    let json = serde_json::to_string(&envelope).unwrap();
+    assert_eq!(serde_json::from_str::<Envelope>(&json).unwrap(), envelope);
+}
+
+#[test]
+fn test_errors_serialize_as_code_and_message() {
+    assert_eq!(
+        serde_json::to_value(SecurityModuleError::UnsupportedAlgorithm).unwrap(),
+        json!({ "code": 7, "message": "Unsupported algorithm" })
+    );
+    assert_eq!(
+        serde_json::to_value(CoreError::Truncated).unwrap(),
+        json!({ "code": 1, "message": "Input is truncated" })
+    );
+
+    let err = SecurityModuleError::InvalidKeySpec("No label given".to_owned());
+    let report: ErrorReport = serde_json::from_value(serde_json::to_value(&err).unwrap()).unwrap();
+    assert_eq!(report, ErrorReport::from(&err));
+    assert_eq!(report.code, 15);
+}
+
+#[test]
+fn test_config_round_trip() {
+    let config = SessionPoolConfig {
+        max_size: 8,
+        ..SessionPoolConfig::default()
+    };
+    let json = serde_json::to_string(&config).unwrap();
+    assert_eq!(
+        serde_json::from_str::<SessionPoolConfig>(&json).unwrap(),
+        config
+    );
+}
+
+#[test]
+fn test_plans_serialize() {
+    let config = CryptoConfig {
+        namespace: Some("com.example.mail".to_owned()),
+        ..CryptoConfig::default()
+    };
+    let spec = KeySpec::builder()
+        .algorithm(KeyAlgorithm::Rsa2048)
+        .usage(KeyPurpose::Sign)
+        .label("identity")
+        .build()
+        .unwrap();
+    let json = serde_json::to_value(KeyPlan::for_config(&config, spec).unwrap()).unwrap();
+
+    assert_eq!(json["spec"]["algorithm"], "Rsa2048");
+    assert_eq!(json["instance"]["key_id"], "identity");
+    assert_eq!(json["instance"]["stored_as"], "com.example.mail/identity");
+    assert_eq!(json["instance"]["fallback"], "never");
+    assert_eq!(json["deprecation"], json!(null));
+}
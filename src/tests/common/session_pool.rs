@@ -0,0 +1,251 @@
+use crate::common::{
+    error::SecurityModuleError,
+    session_pool::{SessionPool, SessionPoolConfig},
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+/// A session that counts how often sessions are opened and closed.
+struct CountedSession {
+    id: usize,
+    closed: Arc<AtomicUsize>,
+}
+
+impl Drop for CountedSession {
+    fn drop(&mut self) {
+        self.closed.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+fn counted_pool(config: SessionPoolConfig) -> (SessionPool<CountedSession>, Arc<AtomicUsize>) {
+    let opened = Arc::new(AtomicUsize::new(0));
+    let closed = Arc::new(AtomicUsize::new(0));
+    let pool_closed = closed.clone();
+    let pool = SessionPool::new(config, move || {
+        Ok(CountedSession {
+            id: opened.fetch_add(1, Ordering::SeqCst),
+            closed: pool_closed.clone(),
+        })
+    });
+    (pool, closed)
+}
+
+fn config(max_size: usize) -> SessionPoolConfig {
+    SessionPoolConfig {
+        max_size,
+        idle_timeout: None,
+        acquire_timeout: Some(Duration::from_millis(50)),
+    }
+}
+
+#[test]
+fn test_reuses_idle_session() {
+    let (pool, closed) = counted_pool(config(2));
+
+    for _ in 0..10 {
+        let session = pool.acquire().unwrap();
+        assert_eq!(session.id, 0);
+    }
+
+    let metrics = pool.metrics();
+    assert_eq!(metrics.opened, 1);
+    assert_eq!(metrics.reused, 9);
+    assert_eq!(metrics.idle, 1);
+    assert_eq!(metrics.in_use, 0);
+    assert_eq!(closed.load(Ordering::SeqCst), 0);
+}
+
+#[test]
+fn test_concurrent_sessions_up_to_max_size() {
+    let (pool, _) = counted_pool(config(3));
+
+    let first = pool.acquire().unwrap();
+    let second = pool.acquire().unwrap();
+    let third = pool.acquire().unwrap();
+    assert_ne!(first.id, second.id);
+    assert_ne!(second.id, third.id);
+
+    let metrics = pool.metrics();
+    assert_eq!(metrics.in_use, 3);
+    assert_eq!(metrics.utilization(), 1.0);
+
+    drop(first);
+    let metrics = pool.metrics();
+    assert_eq!(metrics.in_use, 2);
+    assert_eq!(metrics.idle, 1);
+    assert!((metrics.utilization() - 2.0 / 3.0).abs() < f64::EPSILON);
+}
+
+#[test]
+fn test_acquire_times_out_when_exhausted() {
+    let (pool, _) = counted_pool(config(1));
+    let _session = pool.acquire().unwrap();
+
+    assert!(matches!(
+        pool.acquire(),
+        Err(SecurityModuleError::SessionPoolTimeout)
+    ));
+
+    let metrics = pool.metrics();
+    assert_eq!(metrics.timeouts, 1);
+    assert_eq!(metrics.waited, 1);
+    assert_eq!(metrics.waiting, 0);
+}
+
+#[test]
+fn test_waiter_is_served_on_release() {
+    let (pool, _) = counted_pool(SessionPoolConfig {
+        acquire_timeout: None,
+        ..config(1)
+    });
+    let pool = Arc::new(pool);
+    let session = pool.acquire().unwrap();
+
+    let waiter = {
+        let pool = pool.clone();
+        thread::spawn(move || pool.acquire().map(|session| session.id).ok())
+    };
+    while pool.metrics().waiting == 0 {
+        thread::yield_now();
+    }
+    drop(session);
+
+    assert_eq!(waiter.join().unwrap().unwrap(), 0);
+    assert_eq!(pool.metrics().opened, 1);
+}
+
+#[test]
+fn test_closes_idle_sessions() {
+    let (pool, closed) = counted_pool(SessionPoolConfig {
+        idle_timeout: Some(Duration::ZERO),
+        ..config(1)
+    });
+
+    drop(pool.acquire().unwrap());
+    let session = pool.acquire().unwrap();
+    assert_eq!(session.id, 1);
+    drop(session);
+
+    pool.close_idle();
+    let metrics = pool.metrics();
+    assert_eq!(metrics.closed_idle, 2);
+    assert_eq!(metrics.idle, 0);
+    assert_eq!(closed.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn test_discard_frees_slot() {
+    let (pool, closed) = counted_pool(config(1));
+
+    pool.acquire().unwrap().discard();
+    assert_eq!(closed.load(Ordering::SeqCst), 1);
+
+    let session = pool.acquire().unwrap();
+    assert_eq!(session.id, 1);
+    assert_eq!(pool.metrics().discarded, 1);
+}
+
+#[test]
+fn test_open_error_frees_slot() {
+    let attempts = AtomicUsize::new(0);
+    let pool = SessionPool::new(config(1), move || {
+        match attempts.fetch_add(1, Ordering::SeqCst) {
+            0 => Err(SecurityModuleError::InitializationError(
+                "TPM busy".to_owned(),
+            )),
+            attempt => Ok(attempt),
+        }
+    });
+
+    assert!(matches!(
+        pool.acquire(),
+        Err(SecurityModuleError::InitializationError(_))
+    ));
+    assert_eq!(*pool.acquire().unwrap(), 1);
+    assert_eq!(pool.metrics().opened, 1);
+}
+
+/// A session that keeps track of the number of open sessions and takes a while to close.
+struct LiveSession {
+    open: Arc<AtomicUsize>,
+}
+
+impl Drop for LiveSession {
+    fn drop(&mut self) {
+        thread::sleep(Duration::from_micros(50));
+        self.open.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn test_stress_mixed_traffic() {
+    const THREADS: usize = 8;
+    const ROUNDS: usize = 500;
+    const MAX_SIZE: usize = 3;
+
+    let open = Arc::new(AtomicUsize::new(0));
+    let attempts = AtomicUsize::new(0);
+    let pool_open = open.clone();
+    let config = SessionPoolConfig {
+        max_size: MAX_SIZE,
+        idle_timeout: Some(Duration::from_micros(200)),
+        acquire_timeout: Some(Duration::from_secs(10)),
+    };
+    let pool = SessionPool::new(config, move || {
+        // Every seventh open fails, like a busy TPM.
+        if attempts.fetch_add(1, Ordering::SeqCst) % 7 == 6 {
+            return Err(SecurityModuleError::InitializationError(
+                "TPM busy".to_owned(),
+            ));
+        }
+        let open = pool_open.fetch_add(1, Ordering::SeqCst) + 1;
+        assert!(open <= MAX_SIZE, "{open} sessions open");
+        Ok(LiveSession {
+            open: pool_open.clone(),
+        })
+    });
+
+    thread::scope(|scope| {
+        for thread in 0..THREADS {
+            let pool = &pool;
+            scope.spawn(move || {
+                let mut rng = StdRng::seed_from_u64(thread as u64);
+                for _ in 0..ROUNDS {
+                    match rng.gen_range(0..10) {
+                        0 => pool.close_idle(),
+                        1 | 2 => {
+                            if let Ok(session) = pool.acquire() {
+                                session.discard();
+                            }
+                        }
+                        _ => {
+                            if let Ok(session) = pool.acquire() {
+                                if rng.gen_bool(0.1) {
+                                    thread::sleep(Duration::from_micros(100));
+                                }
+                                drop(session);
+                            }
+                        }
+                    }
+                }
+            });
+        }
+    });
+
+    let metrics = pool.metrics();
+    assert_eq!(metrics.in_use, 0);
+    assert_eq!(metrics.waiting, 0);
+    assert_eq!(metrics.timeouts, 0);
+    assert_eq!(
+        metrics.opened,
+        metrics.idle as u64 + metrics.closed_idle + metrics.discarded
+    );
+    assert_eq!(open.load(Ordering::SeqCst), metrics.idle);
+}
@@ -0,0 +1,151 @@
+//! Model checks of the `SessionPool` state machine.
+//!
+//! Loom runs every model under all possible interleavings of its threads and fails on deadlocks,
+//! so a lost wakeup or a slot that is never freed again shows up deterministically. Run with:
+//!
+//! ```bash
+//! RUSTFLAGS="--cfg crypto_layer_loom" cargo test --release --features test-utils --lib session_pool_loom
+//! ```
+
+use crate::common::{
+    error::SecurityModuleError,
+    session_pool::{SessionPool, SessionPoolConfig},
+};
+use loom::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+};
+use std::time::Duration;
+
+/// A session that keeps track of the number of open sessions.
+struct Session {
+    open: Arc<AtomicUsize>,
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        self.open.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Creates a pool whose `open` fails for the attempts in `failing`, and the number of open
+/// sessions, which is checked against the maximum size on every open.
+fn pool(
+    max_size: usize,
+    idle_timeout: Option<Duration>,
+    failing: &'static [usize],
+) -> (Arc<SessionPool<Session>>, Arc<AtomicUsize>) {
+    let open = Arc::new(AtomicUsize::new(0));
+    let attempts = AtomicUsize::new(0);
+    let pool_open = open.clone();
+    let config = SessionPoolConfig {
+        max_size,
+        idle_timeout,
+        acquire_timeout: None,
+    };
+    let pool = SessionPool::new(config, move || {
+        if failing.contains(&attempts.fetch_add(1, Ordering::SeqCst)) {
+            return Err(SecurityModuleError::InitializationError(
+                "TPM busy".to_owned(),
+            ));
+        }
+        let open = pool_open.fetch_add(1, Ordering::SeqCst) + 1;
+        assert!(
+            open <= max_size,
+            "{open} sessions open, at most {max_size} allowed"
+        );
+        Ok(Session {
+            open: pool_open.clone(),
+        })
+    });
+    (Arc::new(pool), open)
+}
+
+fn spawn_acquire(
+    pool: &Arc<SessionPool<Session>>,
+    discard: bool,
+) -> thread::JoinHandle<Result<(), SecurityModuleError>> {
+    let pool = pool.clone();
+    thread::spawn(move || {
+        let session = pool.acquire()?;
+        if discard {
+            session.discard();
+        }
+        Ok(())
+    })
+}
+
+fn assert_settled(pool: &SessionPool<Session>, open: &AtomicUsize) {
+    let metrics = pool.metrics();
+    assert_eq!(metrics.in_use, 0);
+    assert_eq!(metrics.waiting, 0);
+    assert_eq!(
+        metrics.opened,
+        metrics.idle as u64 + metrics.closed_idle + metrics.discarded
+    );
+    assert_eq!(open.load(Ordering::SeqCst), metrics.idle);
+}
+
+#[test]
+fn loom_waiters_are_served_on_release() {
+    loom::model(|| {
+        let (pool, open) = pool(1, None, &[]);
+        let threads = [spawn_acquire(&pool, false), spawn_acquire(&pool, false)];
+        drop(pool.acquire().unwrap());
+        for thread in threads {
+            thread.join().unwrap().unwrap();
+        }
+
+        assert_settled(&pool, &open);
+        assert_eq!(pool.metrics().opened, 1);
+        assert_eq!(pool.metrics().reused, 2);
+    });
+}
+
+#[test]
+fn loom_discard_frees_slot() {
+    loom::model(|| {
+        let (pool, open) = pool(1, None, &[]);
+        let thread = spawn_acquire(&pool, true);
+        drop(pool.acquire().unwrap());
+        thread.join().unwrap().unwrap();
+
+        assert_settled(&pool, &open);
+        assert_eq!(pool.metrics().discarded, 1);
+    });
+}
+
+#[test]
+fn loom_open_error_wakes_waiter() {
+    loom::model(|| {
+        let (pool, open) = pool(1, None, &[0]);
+        let thread = spawn_acquire(&pool, false);
+        let result = pool.acquire().map(drop);
+        let results = [result, thread.join().unwrap()];
+
+        assert_eq!(results.iter().filter(|result| result.is_err()).count(), 1);
+        assert_settled(&pool, &open);
+    });
+}
+
+#[test]
+fn loom_close_idle_races_acquire() {
+    loom::model(|| {
+        let (pool, open) = pool(1, Some(Duration::ZERO), &[]);
+        let thread = spawn_acquire(&pool, false);
+        let closer = {
+            let pool = pool.clone();
+            thread::spawn(move || pool.close_idle())
+        };
+        drop(pool.acquire().unwrap());
+        thread.join().unwrap().unwrap();
+        closer.join().unwrap();
+
+        pool.close_idle();
+        assert_settled(&pool, &open);
+        assert_eq!(open.load(Ordering::SeqCst), 0);
+    });
+}
@@ -0,0 +1,330 @@
+use crate::{
+    common::{
+        crypto::{
+            algorithms::{
+                encryption::{AsymmetricEncryption, EccCurves, EccSchemeAlgorithm},
+                hashes::{Hash, Sha2Bits},
+                KeyBits,
+            },
+            public_key::PublicKey,
+        },
+        sigstore::{self, DsseEnvelope, DsseSignature, IN_TOTO_PAYLOAD_TYPE},
+        traits::module_provider::Provider,
+    },
+    mock::{MockConfig, MockProvider},
+    SecurityModuleError,
+};
+use ::sigstore::crypto::{CosignVerificationKey, Signature, SigningScheme};
+use base64::{
+    prelude::{BASE64_STANDARD, BASE64_URL_SAFE_NO_PAD},
+    Engine,
+};
+use openssl::{
+    asn1::Asn1Time,
+    ec::{EcGroup, EcKey},
+    hash::MessageDigest,
+    nid::Nid,
+    pkey::{PKey, Public},
+    x509::{X509Builder, X509},
+};
+use serde_json::{json, Value};
+
+fn provider(algorithm: AsymmetricEncryption, hash: Sha2Bits) -> MockProvider {
+    MockProvider::with_key("sigstore", MockConfig::new(algorithm, Hash::Sha2(hash)))
+}
+
+fn p256() -> MockProvider {
+    provider(
+        AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(EccCurves::P256)),
+        Sha2Bits::Sha256,
+    )
+}
+
+fn public_key(provider: &MockProvider) -> PublicKey {
+    provider.key_metadata().unwrap().public_key().clone()
+}
+
+fn identity_token(claims: Value) -> String {
+    let header = BASE64_URL_SAFE_NO_PAD.encode(br#"{"alg":"RS256"}"#);
+    let claims = BASE64_URL_SAFE_NO_PAD.encode(claims.to_string());
+    format!("{}.{}.c2lnbmF0dXJl", header, claims)
+}
+
+/// Issues a certificate for `public_key` as Fulcio would.
+fn certificate(public_key: &PKey<Public>) -> String {
+    let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+    let ca_key = PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap();
+    let mut builder = X509Builder::new().unwrap();
+    builder.set_version(2).unwrap();
+    builder.set_pubkey(public_key).unwrap();
+    builder
+        .set_not_before(&Asn1Time::days_from_now(0).unwrap())
+        .unwrap();
+    builder
+        .set_not_after(&Asn1Time::days_from_now(1).unwrap())
+        .unwrap();
+    builder.sign(&ca_key, MessageDigest::sha256()).unwrap();
+    String::from_utf8(builder.build().to_pem().unwrap()).unwrap()
+}
+
+#[test]
+fn test_pae() {
+    // The example of the DSSE specification.
+    assert_eq!(
+        sigstore::pae("http://example.com/HelloWorld", b"hello world"),
+        b"DSSEv1 29 http://example.com/HelloWorld 11 hello world"
+    );
+    assert_eq!(sigstore::pae("", b""), b"DSSEv1 0  0 ");
+}
+
+#[test]
+fn test_sign_and_verify_envelope() {
+    let provider = p256();
+    let public_key = public_key(&provider);
+    let statement = br#"{"_type":"https://in-toto.io/Statement/v1"}"#;
+    let mut envelope = sigstore::sign_envelope(&provider, IN_TOTO_PAYLOAD_TYPE, statement).unwrap();
+    envelope.verify(&public_key).unwrap();
+
+    // The signature is a DER encoded ECDSA signature over the PAE, as cosign verifies it.
+    let key = PKey::public_key_from_der(&public_key.to_der().unwrap()).unwrap();
+    let mut verifier = openssl::sign::Verifier::new(MessageDigest::sha256(), &key).unwrap();
+    assert!(verifier
+        .verify_oneshot(
+            &envelope.signatures[0].sig,
+            &sigstore::pae(IN_TOTO_PAYLOAD_TYPE, statement)
+        )
+        .unwrap());
+
+    let json: Value = serde_json::from_str(&envelope.to_json()).unwrap();
+    assert_eq!(json["payloadType"], IN_TOTO_PAYLOAD_TYPE);
+    assert_eq!(json["payload"], BASE64_STANDARD.encode(statement));
+    assert!(json["signatures"][0].get("keyid").is_none());
+    assert_eq!(
+        DsseEnvelope::from_json(&envelope.to_json()).unwrap(),
+        envelope
+    );
+
+    // Another signer is added next to the first one.
+    let other = p256();
+    envelope.add_signature(&other, Some("release")).unwrap();
+    envelope.verify(&self::public_key(&other)).unwrap();
+    let parsed = DsseEnvelope::from_json(&envelope.to_json()).unwrap();
+    assert_eq!(parsed.signatures[1].keyid.as_deref(), Some("release"));
+
+    let mut modified = envelope.clone();
+    modified.payload_type = "application/json".to_owned();
+    assert!(matches!(
+        modified.verify(&public_key),
+        Err(SecurityModuleError::InvalidSignature)
+    ));
+    let mut modified = envelope.clone();
+    modified.payload.push(b' ');
+    assert!(matches!(
+        modified.verify(&public_key),
+        Err(SecurityModuleError::InvalidSignature)
+    ));
+    modified.signatures = vec![DsseSignature {
+        keyid: None,
+        sig: vec![0; 8],
+    }];
+    assert!(matches!(
+        modified.verify(&public_key),
+        Err(SecurityModuleError::InvalidSignature)
+    ));
+    assert!(matches!(
+        DsseEnvelope::from_json(r#"{"payloadType":"a","payload":"!","signatures":[]}"#),
+        Err(SecurityModuleError::SignatureVerificationError(_))
+    ));
+}
+
+#[test]
+fn test_key_details() {
+    let ecdsa = |curve, hash| {
+        PublicKey::from_der(
+            &public_key(&p256()).to_der().unwrap(),
+            AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(curve)),
+            Hash::Sha2(hash),
+        )
+    };
+    assert_eq!(
+        sigstore::key_details(&public_key(&p256())).unwrap(),
+        "PKIX_ECDSA_P256_SHA_256"
+    );
+    let rsa = provider(
+        AsymmetricEncryption::Rsa(KeyBits::Bits2048),
+        Sha2Bits::Sha256,
+    );
+    assert_eq!(
+        sigstore::key_details(&public_key(&rsa)).unwrap(),
+        "PKIX_RSA_PKCS1V15_2048_SHA256"
+    );
+    assert!(matches!(
+        sigstore::key_details(&ecdsa(EccCurves::P256, Sha2Bits::Sha512).unwrap()),
+        Err(SecurityModuleError::UnsupportedAlgorithm)
+    ));
+
+    let provider = provider(
+        AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(EccCurves::P256)),
+        Sha2Bits::Sha512,
+    );
+    assert!(matches!(
+        sigstore::sign_envelope(&provider, IN_TOTO_PAYLOAD_TYPE, b"{}"),
+        Err(SecurityModuleError::UnsupportedAlgorithm)
+    ));
+}
+
+#[test]
+fn test_certificate_request() {
+    let provider = p256();
+    let public_key = public_key(&provider);
+    let token = identity_token(json!({
+        "iss": "https://oauth2.sigstore.dev/auth",
+        "sub": "CgYxMjM0NTYSJmh0dHBzOi8vZ2l0aHViLmNvbS9sb2dpbi9vYXV0aA",
+        "email": "alice@example.com",
+    }));
+    let request = sigstore::certificate_request(&provider, &token).unwrap();
+    assert_eq!(request["credentials"]["oidcIdentityToken"], token);
+    let key = &request["publicKeyRequest"]["publicKey"];
+    assert_eq!(key["algorithm"], "ECDSA");
+    let pem = PKey::public_key_from_pem(key["content"].as_str().unwrap().as_bytes()).unwrap();
+    assert_eq!(
+        pem.public_key_to_der().unwrap(),
+        public_key.to_der().unwrap()
+    );
+    let proof = BASE64_STANDARD
+        .decode(
+            request["publicKeyRequest"]["proofOfPossession"]
+                .as_str()
+                .unwrap(),
+        )
+        .unwrap();
+    assert!(public_key.verify(b"alice@example.com", &proof).unwrap());
+
+    // Tokens of workloads have no email, their subject is signed.
+    let token = identity_token(json!({ "sub": "repo:octo/app:ref:refs/heads/main" }));
+    let request = sigstore::certificate_request(&provider, &token).unwrap();
+    let proof = BASE64_STANDARD
+        .decode(
+            request["publicKeyRequest"]["proofOfPossession"]
+                .as_str()
+                .unwrap(),
+        )
+        .unwrap();
+    assert!(public_key
+        .verify(b"repo:octo/app:ref:refs/heads/main", &proof)
+        .unwrap());
+
+    for token in [
+        "not a token".to_owned(),
+        "a.!.c".to_owned(),
+        identity_token(json!({ "iss": "https://issuer.example.com" })),
+    ] {
+        assert!(matches!(
+            sigstore::certificate_request(&provider, &token),
+            Err(SecurityModuleError::InvalidToken(_))
+        ));
+    }
+}
+
+#[test]
+fn test_certificate_chain() {
+    let provider = p256();
+    let public_key = public_key(&provider);
+    let leaf = certificate(&PKey::public_key_from_der(&public_key.to_der().unwrap()).unwrap());
+    let other = certificate(
+        &PKey::public_key_from_der(&self::public_key(&p256()).to_der().unwrap()).unwrap(),
+    );
+
+    let response = json!({
+        "signedCertificateEmbeddedSct": { "chain": { "certificates": [leaf, other] } },
+    });
+    let chain = sigstore::certificate_chain(&response, &public_key).unwrap();
+    assert_eq!(chain.len(), 2);
+    assert_eq!(
+        chain[0],
+        X509::from_pem(leaf.as_bytes()).unwrap().to_der().unwrap()
+    );
+
+    let response = json!({
+        "signedCertificateDetachedSct": {
+            "chain": { "certificates": [leaf] },
+            "signedCertificateTimestamp": "AA==",
+        },
+    });
+    assert_eq!(
+        sigstore::certificate_chain(&response, &public_key)
+            .unwrap()
+            .len(),
+        1
+    );
+
+    for response in [
+        json!({ "signedCertificateEmbeddedSct": { "chain": { "certificates": [other, leaf] } } }),
+        json!({ "signedCertificateEmbeddedSct": { "chain": { "certificates": [] } } }),
+        json!({ "signedCertificateEmbeddedSct": { "chain": { "certificates": ["junk"] } } }),
+        json!({}),
+    ] {
+        assert!(matches!(
+            sigstore::certificate_chain(&response, &public_key),
+            Err(SecurityModuleError::InvalidPublicKey)
+        ));
+    }
+}
+
+/// The signatures verify with the verification keys of `sigstore-rs`, as cosign's do.
+#[test]
+fn test_sigstore_rs_verifies_signatures() {
+    let ecdsa = |curve| AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(curve));
+    let keys = [
+        (
+            ecdsa(EccCurves::P256),
+            Sha2Bits::Sha256,
+            SigningScheme::ECDSA_P256_SHA256_ASN1,
+        ),
+        (
+            ecdsa(EccCurves::P384),
+            Sha2Bits::Sha384,
+            SigningScheme::ECDSA_P384_SHA384_ASN1,
+        ),
+        (
+            AsymmetricEncryption::Rsa(KeyBits::Bits2048),
+            Sha2Bits::Sha256,
+            SigningScheme::RSA_PKCS1_SHA256(2048),
+        ),
+    ];
+    let statement = br#"{"_type":"https://in-toto.io/Statement/v1"}"#;
+    let token = identity_token(json!({ "email": "alice@example.com" }));
+    for (algorithm, hash, scheme) in keys {
+        let provider = provider(algorithm, hash);
+        let public_key = public_key(&provider);
+        let key = CosignVerificationKey::from_der(&public_key.to_der().unwrap(), &scheme).unwrap();
+
+        let envelope = sigstore::sign_envelope(&provider, IN_TOTO_PAYLOAD_TYPE, statement).unwrap();
+        let signed = sigstore::pae(IN_TOTO_PAYLOAD_TYPE, statement);
+        key.verify_signature(Signature::Raw(&envelope.signatures[0].sig), &signed)
+            .unwrap();
+        assert!(key
+            .verify_signature(Signature::Raw(&envelope.signatures[0].sig), statement)
+            .is_err());
+        let encoded = BASE64_STANDARD.encode(&envelope.signatures[0].sig);
+        key.verify_signature(Signature::Base64Encoded(encoded.as_bytes()), &signed)
+            .unwrap();
+
+        // Fulcio checks the proof of possession with the key of the request.
+        let request = sigstore::certificate_request(&provider, &token).unwrap();
+        let request_key = &request["publicKeyRequest"];
+        let key = CosignVerificationKey::try_from_pem(
+            request_key["publicKey"]["content"]
+                .as_str()
+                .unwrap()
+                .as_bytes(),
+        )
+        .unwrap();
+        let proof = request_key["proofOfPossession"].as_str().unwrap();
+        key.verify_signature(
+            Signature::Base64Encoded(proof.as_bytes()),
+            b"alice@example.com",
+        )
+        .unwrap();
+    }
+}
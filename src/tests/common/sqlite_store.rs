@@ -0,0 +1,131 @@
+use crate::{
+    common::{
+        crypto::algorithms::{
+            encryption::AsymmetricEncryption,
+            hashes::{Hash, Sha2Bits},
+            KeyBits,
+        },
+        latency::ProviderOperation,
+        metadata_store::{KeyRecord, MetadataProvider, MetadataStore},
+        sqlite_store::{ciphertext_id, CiphertextEntry, SqliteMetadataStore},
+        traits::{key_handle::KeyHandle, module_provider::Provider},
+    },
+    mock::{MockConfig, MockProvider},
+    SecurityModuleError,
+};
+use std::sync::{Arc, Mutex};
+
+fn entry(ciphertext_id: &str, key_id: &str, version: u32) -> CiphertextEntry {
+    CiphertextEntry {
+        ciphertext_id: ciphertext_id.to_owned(),
+        key_id: key_id.to_owned(),
+        version,
+    }
+}
+
+#[test]
+fn test_finds_ciphertexts_to_reencrypt_after_rotation() {
+    let store = Arc::new(SqliteMetadataStore::open_in_memory().unwrap());
+    let mut mock = MockProvider::new("records".to_owned());
+    mock.initialize_module().unwrap();
+    let mut provider = MetadataProvider::new(
+        Arc::new(Mutex::new(mock)),
+        "records".to_owned(),
+        store.clone(),
+    );
+    let config = || {
+        MockConfig::new(
+            AsymmetricEncryption::Rsa(KeyBits::Bits2048),
+            Hash::Sha2(Sha2Bits::Sha256),
+        )
+    };
+
+    provider.create_key("records", config()).unwrap();
+    let old = provider.encrypt_data(b"old").unwrap();
+    assert_eq!(
+        store
+            .index_current(&ciphertext_id(&old), "records")
+            .unwrap(),
+        1
+    );
+
+    provider.create_key("records", config()).unwrap();
+    let new = provider.encrypt_data(b"new").unwrap();
+    store
+        .index_current(&ciphertext_id(&new), "records")
+        .unwrap();
+
+    let old_entry = entry(&ciphertext_id(&old), "records", 1);
+    assert_eq!(
+        store.lookup_ciphertext(&ciphertext_id(&old)).unwrap(),
+        Some(old_entry.clone())
+    );
+    assert_eq!(store.stale_ciphertexts("records").unwrap(), [old_entry]);
+    assert_eq!(store.ciphertexts("records", None).unwrap().len(), 2);
+    assert_eq!(
+        store.ciphertexts("records", Some(2)).unwrap(),
+        [entry(&ciphertext_id(&new), "records", 2)]
+    );
+
+    // Re-encrypting moves the ciphertext to the current version.
+    assert!(store.remove_ciphertext(&ciphertext_id(&old)).unwrap());
+    assert!(!store.remove_ciphertext(&ciphertext_id(&old)).unwrap());
+    assert!(store.stale_ciphertexts("records").unwrap().is_empty());
+    assert_eq!(store.lookup_ciphertext("unknown").unwrap(), None);
+    assert!(matches!(
+        store.index_current("blob", "unknown"),
+        Err(SecurityModuleError::SecretStorage(_))
+    ));
+}
+
+#[test]
+fn test_database_survives_restarts() {
+    let path = std::env::temp_dir().join(format!("metadata_{}.db", std::process::id()));
+    let store = SqliteMetadataStore::open(&path).unwrap();
+    let mut record = KeyRecord::new("device");
+    record
+        .labels
+        .insert("owner".to_owned(), "telemetry".to_owned());
+    store.save(&record).unwrap();
+    let updated = store
+        .update("backup", &mut |record| {
+            record.record_use(ProviderOperation::DecryptData, false)
+        })
+        .unwrap();
+    store.index_ciphertext("blob", "device", 1).unwrap();
+    drop(store);
+
+    let store = SqliteMetadataStore::open(&path).unwrap();
+    assert_eq!(store.key_ids().unwrap(), ["backup", "device"]);
+    assert_eq!(store.load("device").unwrap(), Some(record));
+    assert_eq!(store.load("backup").unwrap(), Some(updated));
+    assert_eq!(
+        store.lookup_ciphertext("blob").unwrap(),
+        Some(entry("blob", "device", 1))
+    );
+    assert!(store.delete("device").unwrap());
+    assert!(!store.delete("device").unwrap());
+    // The ciphertexts of deleted keys stay in the index.
+    assert!(store.lookup_ciphertext("blob").unwrap().is_some());
+    drop(store);
+
+    // Databases of newer versions of the crate are refused.
+    rusqlite::Connection::open(&path)
+        .unwrap()
+        .pragma_update(None, "user_version", 2)
+        .unwrap();
+    assert!(matches!(
+        SqliteMetadataStore::open(&path),
+        Err(SecurityModuleError::SecretStorage(message)) if message.contains("schema version 2")
+    ));
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_ciphertext_id() {
+    assert_eq!(
+        ciphertext_id(b"abc"),
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+    );
+}
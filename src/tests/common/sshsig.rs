@@ -0,0 +1,133 @@
+use crate::{
+    common::{
+        crypto::{
+            algorithms::{
+                encryption::{AsymmetricEncryption, EccCurves, EccSchemeAlgorithm},
+                hashes::{Hash, Sha2Bits},
+                KeyBits,
+            },
+            public_key::PublicKey,
+        },
+        sshsig::{self, SshSignature, FILE_NAMESPACE, GIT_NAMESPACE},
+        traits::module_provider::Provider,
+    },
+    mock::{MockConfig, MockProvider},
+    SecurityModuleError,
+};
+use base64::{prelude::BASE64_STANDARD, Engine};
+
+/// A key and a signature over `hello\n` in the `git` namespace created with OpenSSH 9.2:
+///
+/// ```text
+/// ssh-keygen -t ecdsa -b 256 -f key && ssh-keygen -Y sign -n git -f key message
+/// ```
+const OPENSSH_PUBLIC_KEY: &str = "ecdsa-sha2-nistp256 AAAAE2VjZHNhLXNoYTItbmlzdHAyNTYAAAAIbmlzdHAyNTYAAABBBMJry89s6rJVKR/AWmVe9Nbsft6AeR0b3XGJAVIEfmP0HVf+Yxlf6gLHOctVd17OUU2niK3tQnnrG8T6j/EGArY=";
+const OPENSSH_SIGNATURE: &str = "-----BEGIN SSH SIGNATURE-----
+U1NIU0lHAAAAAQAAAGgAAAATZWNkc2Etc2hhMi1uaXN0cDI1NgAAAAhuaXN0cDI1NgAAAE
+EEwmvLz2zqslUpH8BaZV701ux+3oB5HRvdcYkBUgR+Y/QdV/5jGV/qAsc5y1V3Xs5RTaeI
+re1CeesbxPqP8QYCtgAAAANnaXQAAAAAAAAABnNoYTUxMgAAAGUAAAATZWNkc2Etc2hhMi
+1uaXN0cDI1NgAAAEoAAAAhAIzaMSfrRw5/aMgNpLtvva3mJxCYui2PAjw3+mTbATSDAAAA
+IQDmTUMAaE+quLzjop1iTADFHwWjavEpbtyPRD+GWO/s2w==
+-----END SSH SIGNATURE-----
+";
+
+fn provider(algorithm: AsymmetricEncryption) -> MockProvider {
+    MockProvider::with_key(
+        "git_key",
+        MockConfig::new(algorithm, Hash::Sha2(Sha2Bits::Sha256)),
+    )
+}
+
+fn p256() -> AsymmetricEncryption {
+    AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(EccCurves::P256))
+}
+
+fn public_key(provider: &MockProvider) -> PublicKey {
+    provider.key_metadata().unwrap().public_key().clone()
+}
+
+#[test]
+fn test_sign_and_verify() {
+    for algorithm in [p256(), AsymmetricEncryption::Rsa(KeyBits::Bits2048)] {
+        let provider = provider(algorithm);
+        let public_key = public_key(&provider);
+        let signature = sshsig::sign(&provider, GIT_NAMESPACE, b"tree 4b825dc\n").unwrap();
+
+        let armored = signature.to_string();
+        assert!(armored.starts_with("-----BEGIN SSH SIGNATURE-----\nU1NIU0lH"));
+        assert!(armored.lines().all(|line| line.len() <= 70));
+        let parsed: SshSignature = armored.parse().unwrap();
+        assert_eq!(parsed, signature);
+        assert_eq!(
+            SshSignature::from_bytes(&signature.to_bytes()).unwrap(),
+            signature
+        );
+        assert_eq!(parsed.namespace(), GIT_NAMESPACE);
+
+        sshsig::verify(&parsed, &public_key, GIT_NAMESPACE, b"tree 4b825dc\n").unwrap();
+        assert!(matches!(
+            sshsig::verify(&parsed, &public_key, FILE_NAMESPACE, b"tree 4b825dc\n"),
+            Err(SecurityModuleError::InvalidSignature)
+        ));
+        assert!(matches!(
+            sshsig::verify(&parsed, &public_key, GIT_NAMESPACE, b"tree 0000000\n"),
+            Err(SecurityModuleError::InvalidSignature)
+        ));
+    }
+
+    let signature = sshsig::sign(&provider(p256()), GIT_NAMESPACE, b"data").unwrap();
+    assert!(matches!(
+        sshsig::verify(
+            &signature,
+            &public_key(&provider(p256())),
+            GIT_NAMESPACE,
+            b"data"
+        ),
+        Err(SecurityModuleError::InvalidSignature)
+    ));
+    assert!(sshsig::sign(&provider(p256()), "", b"data").is_err());
+}
+
+#[test]
+fn test_verify_openssh_signature() {
+    let blob = BASE64_STANDARD
+        .decode(OPENSSH_PUBLIC_KEY.split(' ').nth(1).unwrap())
+        .unwrap();
+    let public_key = PublicKey::from_ec_point(
+        &blob[blob.len() - 65..],
+        p256(),
+        Hash::Sha2(Sha2Bits::Sha256),
+    )
+    .unwrap();
+    assert_eq!(
+        sshsig::public_key_line(&public_key, "").unwrap(),
+        OPENSSH_PUBLIC_KEY
+    );
+
+    let signature: SshSignature = OPENSSH_SIGNATURE.parse().unwrap();
+    assert_eq!(signature.public_key_blob(), blob);
+    sshsig::verify(&signature, &public_key, GIT_NAMESPACE, b"hello\n").unwrap();
+    assert!(sshsig::verify(&signature, &public_key, GIT_NAMESPACE, b"hello").is_err());
+}
+
+#[test]
+fn test_malformed_signatures() {
+    let signature = sshsig::sign(&provider(p256()), GIT_NAMESPACE, b"data").unwrap();
+    let bytes = signature.to_bytes();
+
+    assert!(SshSignature::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+    assert!(SshSignature::from_bytes(&[&bytes[..], &[0]].concat()).is_err());
+    assert!(SshSignature::from_bytes(&bytes[1..]).is_err());
+    let mut version = bytes.clone();
+    version[9] = 2;
+    assert!(SshSignature::from_bytes(&version).is_err());
+    assert!("U1NIU0lH".parse::<SshSignature>().is_err());
+
+    let unsupported = provider(AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(
+        EccCurves::P384,
+    )));
+    assert!(matches!(
+        sshsig::sign(&unsupported, GIT_NAMESPACE, b"data"),
+        Err(SecurityModuleError::UnsupportedAlgorithm)
+    ));
+}
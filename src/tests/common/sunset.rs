@@ -0,0 +1,178 @@
+use crate::{
+    common::{
+        crypto::algorithms::{
+            encryption::{AsymmetricEncryption, EccCurves, EccSchemeAlgorithm},
+            hashes::{Hash, Sha2Bits, Sha3Bits},
+            KeyBits,
+        },
+        sunset::{algorithm_name, hash_name, SunsetAction, SunsetPolicy, ALGORITHM_NAMES},
+    },
+    SecurityModuleError,
+};
+
+const P256: AsymmetricEncryption =
+    AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(EccCurves::P256));
+
+#[test]
+fn test_algorithm_names() {
+    assert_eq!(
+        algorithm_name(AsymmetricEncryption::Rsa(KeyBits::Bits2048)).as_deref(),
+        Some("rsa2048")
+    );
+    assert_eq!(algorithm_name(P256).as_deref(), Some("p256"));
+    assert_eq!(
+        algorithm_name(AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDh(
+            EccCurves::BrainpoolP384r1
+        )))
+        .as_deref(),
+        Some("brainpoolp384r1")
+    );
+    assert_eq!(
+        algorithm_name(AsymmetricEncryption::Ecc(EccSchemeAlgorithm::Null)),
+        None
+    );
+    assert_eq!(hash_name(Hash::Sha1), "sha1");
+    assert_eq!(hash_name(Hash::Sha2(Sha2Bits::Sha512_256)), "sha512_256");
+    assert_eq!(hash_name(Hash::Sha3(Sha3Bits::Sha3_256)), "sha3_256");
+
+    for name in [
+        hash_name(Hash::Md5),
+        hash_name(Hash::Ripemd160),
+        hash_name(Hash::Sha2(Sha2Bits::Sha224)),
+        algorithm_name(AsymmetricEncryption::Rsa(KeyBits::Bits8192)).unwrap(),
+        algorithm_name(AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(
+            EccCurves::Frp256v1,
+        )))
+        .unwrap(),
+    ] {
+        assert!(ALGORITHM_NAMES.contains(&name.as_str()), "{}", name);
+    }
+}
+
+#[test]
+fn test_policy_reports_strictest_action() {
+    let policy = SunsetPolicy::new()
+        .deprecate("p256", SunsetAction::Warn)
+        .unwrap()
+        .deprecate("sha1", SunsetAction::Deny)
+        .unwrap();
+
+    assert_eq!(
+        policy.check(Some(P256), Some(Hash::Sha2(Sha2Bits::Sha256))),
+        Some(("p256", SunsetAction::Warn))
+    );
+    assert_eq!(
+        policy.check(Some(P256), Some(Hash::Sha1)),
+        Some(("sha1", SunsetAction::Deny))
+    );
+    assert_eq!(
+        policy.check(
+            Some(AsymmetricEncryption::Rsa(KeyBits::Bits3072)),
+            Some(Hash::Sha2(Sha2Bits::Sha256))
+        ),
+        None
+    );
+}
+
+#[test]
+fn test_rejects_unknown_algorithms() {
+    assert!(matches!(
+        SunsetPolicy::new().deprecate("rsa1000", SunsetAction::Deny),
+        Err(SecurityModuleError::InitializationError(message))
+            if message == "Unknown algorithm 'rsa1000'"
+    ));
+}
+
+#[cfg(feature = "test-utils")]
+mod provider {
+    use super::P256;
+    use crate::{
+        common::{
+            crypto::algorithms::{
+                encryption::AsymmetricEncryption,
+                hashes::{Hash, Sha2Bits},
+                KeyBits,
+            },
+            key_id::ValidatedProvider,
+            sunset::{KeyMigration, SunsetAction, SunsetPolicy},
+            traits::module_provider::Provider,
+        },
+        mock::{MockConfig, MockProvider},
+        SecurityModuleError,
+    };
+    use std::sync::{Arc, Mutex};
+
+    fn initialized_mock() -> Arc<Mutex<dyn Provider>> {
+        let mut mock = MockProvider::new("legacy".to_owned());
+        mock.initialize_module().unwrap();
+        Arc::new(Mutex::new(mock))
+    }
+
+    #[test]
+    fn test_denied_algorithm_only_blocks_new_keys() {
+        let inner = initialized_mock();
+        inner
+            .lock()
+            .unwrap()
+            .create_key("legacy", Box::new(MockConfig::default()))
+            .unwrap();
+        let policy = SunsetPolicy::new()
+            .deprecate("p256", SunsetAction::Deny)
+            .unwrap();
+        let mut provider = ValidatedProvider::new(inner).with_sunset_policy(policy);
+
+        assert!(matches!(
+            provider.create_key("successor", Box::new(MockConfig::default())),
+            Err(SecurityModuleError::DeprecatedAlgorithm(message))
+                if message == "p256 may no longer be used for new keys"
+        ));
+        provider
+            .load_key("legacy", Box::new(MockConfig::default()))
+            .unwrap();
+        assert_eq!(provider.list_keys().unwrap(), ["legacy"]);
+    }
+
+    #[test]
+    fn test_warned_algorithm_still_creates_keys() {
+        let policy = SunsetPolicy::new()
+            .deprecate("sha256", SunsetAction::Warn)
+            .unwrap();
+        let mut provider = ValidatedProvider::new(initialized_mock()).with_sunset_policy(policy);
+
+        provider
+            .create_key("identity", Box::new(MockConfig::default()))
+            .unwrap();
+        assert_eq!(provider.list_keys().unwrap(), ["identity"]);
+    }
+
+    #[test]
+    fn test_keys_to_migrate() {
+        let inner = initialized_mock();
+        let mut metadata = Vec::new();
+        for (key_id, algorithm) in [
+            ("legacy", P256),
+            ("current", AsymmetricEncryption::Rsa(KeyBits::Bits2048)),
+        ] {
+            let mut provider = inner.lock().unwrap();
+            provider
+                .create_key(
+                    key_id,
+                    MockConfig::new(algorithm, Hash::Sha2(Sha2Bits::Sha256)),
+                )
+                .unwrap();
+            metadata.push(provider.key_metadata().unwrap());
+        }
+        let policy = SunsetPolicy::new()
+            .deprecate("p256", SunsetAction::Warn)
+            .unwrap();
+
+        assert_eq!(
+            policy.keys_to_migrate(&metadata),
+            [KeyMigration {
+                key_id: "legacy".to_owned(),
+                algorithm: "p256".to_owned(),
+                action: SunsetAction::Warn,
+            }]
+        );
+    }
+}
@@ -0,0 +1,47 @@
+use crate::common::telemetry::CorrelationId;
+use std::thread;
+
+#[test]
+fn test_scope_sets_current_id() {
+    assert_eq!(CorrelationId::current(), None);
+
+    let id = CorrelationId::scope(|id| {
+        assert_eq!(CorrelationId::current(), Some(id));
+        id
+    });
+
+    assert_eq!(CorrelationId::current(), None);
+    assert_eq!(id.to_string().len(), 16);
+}
+
+#[test]
+fn test_nested_scopes_keep_outer_id() {
+    CorrelationId::scope(|outer| {
+        CorrelationId::scope(|inner| assert_eq!(inner, outer));
+        assert_eq!(CorrelationId::current(), Some(outer));
+    });
+}
+
+#[test]
+fn test_scopes_get_distinct_ids() {
+    let first = CorrelationId::scope(|id| id);
+    let second = CorrelationId::scope(|id| id);
+
+    assert_ne!(first, second);
+}
+
+#[test]
+fn test_id_is_per_thread() {
+    CorrelationId::scope(|_| {
+        let other = thread::spawn(CorrelationId::current).join().unwrap();
+        assert_eq!(other, None);
+    });
+}
+
+#[test]
+fn test_panic_clears_current_id() {
+    let result = std::panic::catch_unwind(|| CorrelationId::scope(|_| panic!("Operation failed")));
+
+    assert!(result.is_err());
+    assert_eq!(CorrelationId::current(), None);
+}
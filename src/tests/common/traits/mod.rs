@@ -46,19 +46,29 @@ fn setup_security_module(module: SecurityModule) -> Arc<Mutex<dyn Provider>> {
         }
         #[cfg(feature = "tpm")]
         SecurityModule::Tpm(tpm_type) => match tpm_type {
+            #[cfg(feature = "linux")]
             TpmType::Linux => SecModules::get_instance(
                 "test_key".to_owned(),
                 SecurityModule::Tpm(TpmType::Linux),
                 Some(log),
             )
             .unwrap(),
+            #[cfg(feature = "win")]
             TpmType::Windows => SecModules::get_instance(
                 "test_key".to_owned(),
                 SecurityModule::Tpm(TpmType::Windows),
                 Some(log),
             )
             .unwrap(),
+            #[cfg(feature = "macos")]
+            TpmType::MacOs => SecModules::get_instance(
+                "test_key".to_owned(),
+                SecurityModule::Tpm(TpmType::MacOs),
+                Some(log),
+            )
+            .unwrap(),
             TpmType::None => unimplemented!(),
+            #[cfg(feature = "android")]
             TpmType::Android(_) => unimplemented!(),
         },
         #[cfg(feature = "nks")]
@@ -0,0 +1,121 @@
+use crate::{
+    common::{
+        latency::ProviderOperation,
+        traits::{key_handle::KeyHandle, module_provider::Provider},
+        transaction::{self, Transaction},
+    },
+    mock::{MockConfig, MockProvider},
+    SecurityModuleError,
+};
+use std::panic::{self, AssertUnwindSafe};
+
+fn provider() -> MockProvider {
+    let mut provider = MockProvider::new("transaction".to_owned());
+    provider.initialize_module().unwrap();
+    provider
+}
+
+fn device_removed() -> SecurityModuleError {
+    SecurityModuleError::InitializationError("Device removed".to_owned())
+}
+
+#[test]
+fn test_commits_successful_steps() {
+    let mut provider = provider();
+    let key_ids = transaction::run("rotate", &mut provider, |transaction| {
+        transaction.create_key("records.1", Box::new(MockConfig::default()))?;
+        transaction.create_key("records.2", Box::new(MockConfig::default()))?;
+        transaction.target().list_keys()
+    })
+    .unwrap();
+    assert_eq!(key_ids, ["records.1", "records.2"]);
+    assert_eq!(provider.list_keys().unwrap(), key_ids);
+}
+
+#[test]
+fn test_rolls_back_in_reverse_order() {
+    let mut provider = provider();
+    let mut undone = Vec::new();
+    let result: Result<(), _> = transaction::run("rotate", &mut undone, |transaction| {
+        transaction.step(
+            "first",
+            |_| Ok(()),
+            |undone| {
+                undone.push("first");
+                Ok(())
+            },
+        )?;
+        transaction.on_rollback("second", |undone| {
+            undone.push("second");
+            Ok(())
+        });
+        transaction.step(
+            "third",
+            |_| Err(SecurityModuleError::SigningFailed),
+            |undone| {
+                undone.push("third");
+                Ok(())
+            },
+        )
+    });
+    assert!(matches!(result, Err(SecurityModuleError::SigningFailed)));
+    // The failed step is not undone.
+    assert_eq!(undone, ["second", "first"]);
+
+    let result = transaction::run("rotate", &mut provider, |transaction| {
+        transaction.create_key("records.2", Box::new(MockConfig::default()))?;
+        transaction.target().sign_data(b"endorsement")?;
+        Err::<(), _>(SecurityModuleError::SigningFailed)
+    });
+    assert!(result.is_err());
+    assert!(provider.list_keys().unwrap().is_empty());
+}
+
+#[test]
+fn test_failed_undo_steps_do_not_stop_the_rollback() {
+    let mut provider = provider();
+    let controller = provider.controller();
+    let mut transaction = Transaction::begin("rotate", &mut provider);
+    transaction
+        .create_key("records.1", Box::new(MockConfig::default()))
+        .unwrap();
+    transaction
+        .create_key("records.2", Box::new(MockConfig::default()))
+        .unwrap();
+    controller.fail_times(ProviderOperation::DeleteKey, 1, device_removed);
+
+    assert!(matches!(
+        transaction.rollback(),
+        Err(SecurityModuleError::InitializationError(_))
+    ));
+    assert_eq!(controller.calls(ProviderOperation::DeleteKey), 2);
+    assert_eq!(provider.list_keys().unwrap(), ["records.2"]);
+
+    // The error of the failed step is returned, not the one of the rollback.
+    controller.fail_times(ProviderOperation::DeleteKey, 1, device_removed);
+    let result = transaction::run("rotate", &mut provider, |transaction| {
+        transaction.create_key("records.3", Box::new(MockConfig::default()))?;
+        Err::<(), _>(SecurityModuleError::SigningFailed)
+    });
+    assert!(matches!(result, Err(SecurityModuleError::SigningFailed)));
+}
+
+#[test]
+fn test_rolls_back_when_dropped() {
+    let mut provider = provider();
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        transaction::run::<_, ()>("rotate", &mut provider, |transaction| {
+            transaction.create_key("records.1", Box::new(MockConfig::default()))?;
+            panic!("rotation aborted")
+        })
+    }));
+    assert!(result.is_err());
+    assert!(provider.list_keys().unwrap().is_empty());
+
+    let mut transaction = Transaction::begin("rotate", &mut provider);
+    transaction
+        .create_key("records.1", Box::new(MockConfig::default()))
+        .unwrap();
+    transaction.commit();
+    assert_eq!(provider.list_keys().unwrap(), ["records.1"]);
+}
@@ -0,0 +1,187 @@
+use crate::{
+    common::{
+        crypto::{
+            algorithms::{
+                encryption::{AsymmetricEncryption, EccCurves, EccSchemeAlgorithm},
+                hashes::{Hash, Sha2Bits},
+                KeyBits,
+            },
+            envelope::{AeadAlgorithm, Envelope},
+            public_key::PublicKey,
+        },
+        ecies,
+        key_id::ValidatedProvider,
+        traits::{key_handle::KeyHandle, module_provider::Provider},
+        uniform_errors::{self, FailureTiming, DECRYPTION_FAILED, MAX_FAILURE_DELAY},
+    },
+    mock::{MockConfig, MockProvider},
+    SecurityModuleError,
+};
+use crypto_layer_core::CoreError;
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+fn device(key_algorithm: AsymmetricEncryption) -> MockProvider {
+    MockProvider::with_key(
+        "device_key",
+        MockConfig::new(key_algorithm, Hash::Sha2(Sha2Bits::Sha256)),
+    )
+}
+
+fn p256_device() -> MockProvider {
+    device(AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(
+        EccCurves::P256,
+    )))
+}
+
+fn public_key(provider: &MockProvider) -> PublicKey {
+    provider.key_metadata().unwrap().public_key().clone()
+}
+
+fn assert_uniform<T>(result: Result<T, SecurityModuleError>) {
+    match result {
+        Err(SecurityModuleError::DecryptionError(message)) => {
+            assert_eq!(message, DECRYPTION_FAILED)
+        }
+        Err(e) => panic!("expected the uniform error, got {}", e),
+        Ok(_) => panic!("expected the uniform error"),
+    }
+}
+
+fn median(mut durations: Vec<Duration>) -> Duration {
+    durations.sort();
+    durations[durations.len() / 2]
+}
+
+#[test]
+fn test_ecies_failures_are_uniform() {
+    let (alice, bob) = (p256_device(), p256_device());
+    let ciphertext = ecies::encrypt_for(&public_key(&alice), b"message").unwrap();
+
+    let mut tampered = ciphertext.clone();
+    *tampered.last_mut().unwrap() ^= 1;
+    let mut not_ecies = Envelope::new(AeadAlgorithm::Aes256Gcm, "key", vec![0; 12]);
+    not_ecies.ciphertext = vec![0; 16];
+
+    // Parse, header, tag and key failures cannot be told apart.
+    assert_uniform(ecies::decrypt(&alice, b"not an envelope"));
+    assert_uniform(ecies::decrypt(&alice, &not_ecies.to_bytes().unwrap()));
+    assert_uniform(ecies::decrypt(&alice, &tampered));
+    assert_uniform(ecies::decrypt(&bob, &ciphertext));
+    assert_eq!(&*ecies::decrypt(&alice, &ciphertext).unwrap(), b"message");
+}
+
+#[test]
+fn test_validated_provider_failures_are_uniform() {
+    let rsa = device(AsymmetricEncryption::Rsa(KeyBits::Bits2048));
+    let provider = ValidatedProvider::new(Arc::new(Mutex::new(rsa)));
+
+    let mut ciphertext = provider.encrypt_data(b"data key").unwrap();
+    assert_eq!(&*provider.decrypt_data(&ciphertext).unwrap(), b"data key");
+    // A bad OAEP padding and a ciphertext of the wrong length.
+    ciphertext[0] ^= 1;
+    assert_uniform(provider.decrypt_data(&ciphertext));
+    assert_uniform(provider.decrypt_data(&ciphertext[..255]));
+    assert_uniform(provider.decrypt_data(&[1; 512]));
+
+    // A signature that is not even a valid encoding is just invalid.
+    let ec = ValidatedProvider::new(Arc::new(Mutex::new(p256_device())));
+    let signature = ec.sign_data(b"data").unwrap();
+    assert!(ec.verify_signature(b"data", &signature).unwrap());
+    assert!(!ec.verify_signature(b"data", &signature[1..]).unwrap());
+    assert!(!ec.verify_signature(b"data", b"garbage").unwrap());
+}
+
+#[test]
+fn test_passes_through_independent_errors() {
+    let timing = FailureTiming::new();
+    assert!(matches!(
+        uniform_errors::decryption(&timing, || -> Result<(), _> {
+            Err(SecurityModuleError::InputTooLarge("too large".to_owned()))
+        }),
+        Err(SecurityModuleError::InputTooLarge(_))
+    ));
+    assert!(matches!(
+        uniform_errors::decryption(&timing, || -> Result<(), _> {
+            Err(SecurityModuleError::KeyError)
+        }),
+        Err(SecurityModuleError::KeyError)
+    ));
+    assert!(matches!(
+        uniform_errors::decryption(&timing, || -> Result<(), _> {
+            Err(SecurityModuleError::AuthenticationFailed(
+                "locked".to_owned(),
+            ))
+        }),
+        Err(SecurityModuleError::AuthenticationFailed(_))
+    ));
+    assert_eq!(timing.slowest(), Duration::ZERO);
+
+    assert_uniform(uniform_errors::decryption(&timing, || -> Result<(), _> {
+        Err(CoreError::Truncated.into())
+    }));
+    assert_uniform(uniform_errors::decryption(&timing, || -> Result<(), _> {
+        Err(SecurityModuleError::UnsupportedAlgorithm)
+    }));
+    assert!(timing.slowest() > Duration::ZERO);
+    assert!(matches!(
+        uniform_errors::verification(|| Err(SecurityModuleError::KeyError)),
+        Err(SecurityModuleError::KeyError)
+    ));
+}
+
+#[test]
+fn test_failure_timing() {
+    let timing = FailureTiming::new();
+    let slow = || -> Result<(), SecurityModuleError> {
+        std::thread::sleep(Duration::from_millis(20));
+        Err(SecurityModuleError::DecryptionError("bad tag".to_owned()))
+    };
+    let fast = || -> Result<(), SecurityModuleError> { Err(CoreError::InvalidMagic.into()) };
+
+    let start = Instant::now();
+    assert_uniform(uniform_errors::decryption(&timing, slow));
+    assert!(start.elapsed() >= Duration::from_millis(20));
+    let start = Instant::now();
+    assert_uniform(uniform_errors::decryption(&timing, fast));
+    assert!(start.elapsed() >= Duration::from_millis(20));
+
+    // A single stalled call does not delay later failures indefinitely.
+    let stalled = || -> Result<(), SecurityModuleError> {
+        std::thread::sleep(MAX_FAILURE_DELAY * 2);
+        Err(SecurityModuleError::DecryptionError("stalled".to_owned()))
+    };
+    assert_uniform(uniform_errors::decryption(&timing, stalled));
+    assert_eq!(timing.slowest(), MAX_FAILURE_DELAY);
+}
+
+#[test]
+fn test_response_time_distribution() {
+    let alice = p256_device();
+    let ciphertext = ecies::encrypt_for(&public_key(&alice), b"message").unwrap();
+    let mut tampered = ciphertext.clone();
+    *tampered.last_mut().unwrap() ^= 1;
+
+    let measure = |ciphertext: &[u8]| {
+        let start = Instant::now();
+        assert_uniform(ecies::decrypt(&alice, ciphertext));
+        start.elapsed()
+    };
+    // Tag failures run the key agreement and the KDF, parse failures stop at the first byte.
+    let mut tag_failures = Vec::new();
+    let mut parse_failures = Vec::new();
+    for _ in 0..25 {
+        tag_failures.push(measure(&tampered));
+        parse_failures.push(measure(b"not an envelope"));
+    }
+
+    let (tag, parse) = (median(tag_failures), median(parse_failures));
+    assert!(
+        parse.as_secs_f64() >= 0.9 * tag.as_secs_f64(),
+        "parse failures took {:?}, tag failures {:?}",
+        parse,
+        tag
+    );
+}
@@ -0,0 +1,182 @@
+use crate::{
+    common::{
+        crypto::{
+            algorithms::{
+                encryption::AsymmetricEncryption,
+                hashes::{Hash, Sha2Bits},
+                KeyBits,
+            },
+            envelope::EnvelopeRef,
+        },
+        traits::module_provider::Provider,
+        vault::{FileStorage, MemoryStorage, SecretStorage, SecretsVault, MAX_SECRET_NAME_LEN},
+    },
+    mock::{MockConfig, MockProvider},
+    SecurityModuleError,
+};
+use std::sync::{Arc, Mutex};
+
+fn vault_provider(key_id: &str) -> Arc<Mutex<dyn Provider>> {
+    Arc::new(Mutex::new(MockProvider::with_key(
+        key_id,
+        MockConfig::new(
+            AsymmetricEncryption::Rsa(KeyBits::Bits2048),
+            Hash::Sha2(Sha2Bits::Sha256),
+        ),
+    )))
+}
+
+/// Shares the ciphertexts of a `MemoryStorage` between vaults.
+struct SharedStorage(Arc<MemoryStorage>);
+
+impl SecretStorage for SharedStorage {
+    fn read(&self, name: &str) -> Result<Option<Vec<u8>>, SecurityModuleError> {
+        self.0.read(name)
+    }
+
+    fn write(&self, name: &str, ciphertext: &[u8]) -> Result<(), SecurityModuleError> {
+        self.0.write(name, ciphertext)
+    }
+
+    fn delete(&self, name: &str) -> Result<bool, SecurityModuleError> {
+        self.0.delete(name)
+    }
+
+    fn names(&self) -> Result<Vec<String>, SecurityModuleError> {
+        self.0.names()
+    }
+}
+
+#[test]
+fn test_put_and_get_secret() {
+    let storage = Arc::new(MemoryStorage::new());
+    let vault = SecretsVault::new(
+        vault_provider("vault_key"),
+        "vault_key",
+        SharedStorage(storage.clone()),
+    );
+
+    vault.put_secret("db_password", b"hunter2").unwrap();
+    vault.put_secret("api_token", b"").unwrap();
+    assert_eq!(
+        vault.get_secret("db_password").unwrap().unwrap(),
+        b"hunter2"
+    );
+    assert_eq!(vault.get_secret("api_token").unwrap().unwrap(), b"");
+    assert_eq!(vault.get_secret("missing").unwrap(), None);
+    assert_eq!(vault.list_secrets().unwrap(), ["api_token", "db_password"]);
+
+    // The storage only holds envelopes with a wrapped data key.
+    let stored = storage.read("db_password").unwrap().unwrap();
+    let envelope = EnvelopeRef::parse(&stored).unwrap();
+    assert_eq!(envelope.key_id, "vault_key");
+    assert!(envelope.wrapped_key.is_some());
+    assert!(!stored.windows(7).any(|window| window == b"hunter2"));
+
+    // Every write uses a fresh data key.
+    vault.put_secret("db_password", b"hunter2").unwrap();
+    assert_ne!(storage.read("db_password").unwrap().unwrap(), stored);
+
+    assert!(vault.delete_secret("db_password").unwrap());
+    assert!(!vault.delete_secret("db_password").unwrap());
+    assert_eq!(vault.get_secret("db_password").unwrap(), None);
+}
+
+#[test]
+fn test_secrets_are_bound_to_name_and_key() {
+    let storage = Arc::new(MemoryStorage::new());
+    let vault = SecretsVault::new(
+        vault_provider("vault_key"),
+        "vault_key",
+        SharedStorage(storage.clone()),
+    );
+    vault.put_secret("db_password", b"hunter2").unwrap();
+    let stored = storage.read("db_password").unwrap().unwrap();
+
+    // A ciphertext copied to another name cannot be decrypted.
+    storage.write("api_token", &stored).unwrap();
+    assert!(matches!(
+        vault.get_secret("api_token"),
+        Err(SecurityModuleError::DecryptionError(_))
+    ));
+
+    let mut modified = stored.clone();
+    *modified.last_mut().unwrap() ^= 1;
+    storage.write("db_password", &modified).unwrap();
+    assert!(matches!(
+        vault.get_secret("db_password"),
+        Err(SecurityModuleError::DecryptionError(_))
+    ));
+
+    // Another key cannot decrypt the secrets of the vault.
+    storage.write("db_password", &stored).unwrap();
+    let other = SecretsVault::new(
+        vault_provider("other_key"),
+        "other_key",
+        SharedStorage(storage.clone()),
+    );
+    assert!(matches!(
+        other.get_secret("db_password"),
+        Err(SecurityModuleError::DecryptionError(_))
+    ));
+    let impostor = SecretsVault::new(
+        vault_provider("vault_key"),
+        "vault_key",
+        SharedStorage(storage),
+    );
+    assert!(impostor.get_secret("db_password").is_err());
+}
+
+#[test]
+fn test_invalid_names_and_secrets() {
+    let vault = SecretsVault::new(
+        vault_provider("vault_key"),
+        "vault_key",
+        MemoryStorage::new(),
+    );
+
+    for name in [
+        String::new(),
+        "line\nbreak".to_owned(),
+        "a".repeat(MAX_SECRET_NAME_LEN + 1),
+    ] {
+        assert!(
+            matches!(
+                vault.put_secret(&name, b"secret"),
+                Err(SecurityModuleError::SecretStorage(_))
+            ),
+            "{:?} was accepted",
+            name
+        );
+    }
+    assert!(vault
+        .put_secret(&"a".repeat(MAX_SECRET_NAME_LEN), b"secret")
+        .is_ok());
+    assert!(matches!(
+        vault.put_secret("large", &vec![0; 64 * 1024 + 1]),
+        Err(SecurityModuleError::SecretStorage(_))
+    ));
+}
+
+#[test]
+fn test_file_storage() {
+    let dir = std::env::temp_dir().join(format!("vault_{}", std::process::id()));
+    let storage = FileStorage::new(&dir).unwrap();
+    std::fs::write(dir.join("unrelated.txt"), b"ignored").unwrap();
+
+    let vault = SecretsVault::new(vault_provider("vault_key"), "vault_key", storage.clone());
+    vault.put_secret("db_password", b"hunter2").unwrap();
+    vault.put_secret("../escape", b"contained").unwrap();
+
+    assert_eq!(vault.list_secrets().unwrap(), ["../escape", "db_password"]);
+    assert_eq!(
+        vault.get_secret("../escape").unwrap().unwrap(),
+        b"contained"
+    );
+    assert_eq!(storage.read("missing").unwrap(), None);
+    assert!(!dir.parent().unwrap().join("escape").exists());
+    assert!(vault.delete_secret("../escape").unwrap());
+    assert_eq!(vault.list_secrets().unwrap(), ["db_password"]);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
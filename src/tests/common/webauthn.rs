@@ -0,0 +1,283 @@
+use crate::{
+    common::{
+        crypto::algorithms::{
+            encryption::{AsymmetricEncryption, EccCurves, EccSchemeAlgorithm},
+            hashes::{Hash, Sha2Bits},
+            KeyBits,
+        },
+        traits::module_provider::Provider,
+        webauthn::{
+            cbor::Value, AttestationFormat, Authenticator, CredentialStore, GetAssertionRequest,
+            MakeCredentialRequest, MemoryCredentialStore, CREDENTIAL_ID_LEN,
+        },
+    },
+    mock::{MockConfig, MockProvider},
+    SecurityModuleError,
+};
+use openssl::sha::sha256;
+use std::sync::{Arc, Mutex};
+
+const RP_ID: &str = "example.com";
+const AAGUID: [u8; 16] = *b"crypto-layer-tst";
+const CLIENT_DATA_HASH: [u8; 32] = [0x42; 32];
+
+fn authenticator(algorithm: AsymmetricEncryption) -> Authenticator<MemoryCredentialStore> {
+    let mut provider = MockProvider::new(String::new());
+    provider.initialize_module().unwrap();
+    Authenticator::new(
+        Arc::new(Mutex::new(provider)),
+        move || MockConfig::new(algorithm, Hash::Sha2(Sha2Bits::Sha256)),
+        AAGUID,
+        MemoryCredentialStore::new(),
+    )
+}
+
+fn p256() -> AsymmetricEncryption {
+    AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(EccCurves::P256))
+}
+
+fn make_credential_request(attestation: AttestationFormat) -> MakeCredentialRequest<'static> {
+    MakeCredentialRequest {
+        rp_id: RP_ID,
+        user_id: b"user-4711",
+        user_name: "alice@example.com",
+        client_data_hash: &CLIENT_DATA_HASH,
+        user_verified: true,
+        attestation,
+    }
+}
+
+fn get_assertion_request(allow_credentials: &[Vec<u8>]) -> GetAssertionRequest<'_> {
+    GetAssertionRequest {
+        rp_id: RP_ID,
+        client_data_hash: &CLIENT_DATA_HASH,
+        allow_credentials,
+        user_verified: false,
+    }
+}
+
+/// Encodes a CBOR text string of less than 24 bytes.
+fn text(text: &str) -> Vec<u8> {
+    [&[0x60 | text.len() as u8][..], text.as_bytes()].concat()
+}
+
+/// Encodes a CBOR byte string of 256 to 65535 bytes.
+fn bytes(bytes: &[u8]) -> Vec<u8> {
+    [&[0x59][..], &(bytes.len() as u16).to_be_bytes(), bytes].concat()
+}
+
+#[test]
+fn test_make_credential() {
+    let authenticator = authenticator(p256());
+    let attested = authenticator
+        .make_credential(&make_credential_request(AttestationFormat::Packed))
+        .unwrap();
+    let credential = &attested.credential;
+    assert_eq!(credential.credential_id.len(), CREDENTIAL_ID_LEN);
+    assert_eq!(credential.algorithm, -7);
+    assert_eq!(
+        authenticator
+            .store()
+            .get(RP_ID, &credential.credential_id)
+            .unwrap()
+            .as_ref(),
+        Some(credential)
+    );
+
+    // rpIdHash, flags UP, UV and AT, the counter and the attested credential data.
+    let data = &attested.authenticator_data;
+    assert_eq!(data[..32], sha256(RP_ID.as_bytes()));
+    assert_eq!(data[32], 0x45);
+    assert_eq!(data[33..37], [0; 4]);
+    assert_eq!(data[37..53], AAGUID);
+    assert_eq!(data[53..55], [0, CREDENTIAL_ID_LEN as u8]);
+    assert_eq!(data[55..71], credential.credential_id);
+    assert_eq!(data[71..], attested.cose_public_key);
+
+    // {1: 2, 3: -7, -1: 1, -2: x, -3: y}
+    let point = attested.public_key.to_ec_point().unwrap();
+    let cose_key = [
+        &[0xa5, 0x01, 0x02, 0x03, 0x26, 0x20, 0x01, 0x21, 0x58, 0x20][..],
+        &point[1..33],
+        &[0x22, 0x58, 0x20],
+        &point[33..],
+    ]
+    .concat();
+    assert_eq!(attested.cose_public_key, cose_key);
+
+    // {"fmt": "packed", "attStmt": {"alg": -7, "sig": sig}, "authData": data}
+    let object = &attested.attestation_object;
+    let prefix = [
+        &[0xa3][..],
+        &text("fmt"),
+        &text("packed"),
+        &text("attStmt"),
+        &[0xa2],
+        &text("alg"),
+        &[0x26],
+        &text("sig"),
+    ]
+    .concat();
+    assert!(object.starts_with(&prefix));
+    let signature_len = object[prefix.len() + 1] as usize;
+    let signature = &object[prefix.len() + 2..prefix.len() + 2 + signature_len];
+    let suffix = [&text("authData")[..], &[0x58, data.len() as u8], data].concat();
+    assert_eq!(object[prefix.len() + 2 + signature_len..], suffix);
+    assert!(attested
+        .public_key
+        .verify(&[&data[..], &CLIENT_DATA_HASH].concat(), signature)
+        .unwrap());
+}
+
+#[test]
+fn test_attestation_formats() {
+    let attested = authenticator(p256())
+        .make_credential(&make_credential_request(AttestationFormat::None))
+        .unwrap();
+    let expected = [
+        &[0xa3][..],
+        &text("fmt"),
+        &text("none"),
+        &text("attStmt"),
+        &[0xa0],
+        &text("authData"),
+        &[0x58, attested.authenticator_data.len() as u8],
+        &attested.authenticator_data,
+    ]
+    .concat();
+    assert_eq!(attested.attestation_object, expected);
+
+    // {1: 3, 3: -257, -1: n, -2: e}
+    let attested = authenticator(AsymmetricEncryption::Rsa(KeyBits::Bits2048))
+        .make_credential(&make_credential_request(AttestationFormat::None))
+        .unwrap();
+    assert_eq!(attested.credential.algorithm, -257);
+    let der = attested.public_key.to_der().unwrap();
+    let rsa = openssl::pkey::PKey::public_key_from_der(&der)
+        .unwrap()
+        .rsa()
+        .unwrap();
+    let cose_key = [
+        &[0xa4, 0x01, 0x03, 0x03, 0x39, 0x01, 0x00, 0x20][..],
+        &bytes(&rsa.n().to_vec()),
+        &[0x21, 0x43],
+        &rsa.e().to_vec(),
+    ]
+    .concat();
+    assert_eq!(attested.cose_public_key, cose_key);
+}
+
+#[test]
+fn test_get_assertion() {
+    let authenticator = authenticator(p256());
+    let first = authenticator
+        .make_credential(&make_credential_request(AttestationFormat::None))
+        .unwrap();
+    let second = authenticator
+        .make_credential(&make_credential_request(AttestationFormat::None))
+        .unwrap();
+
+    for expected_count in 1..=2u32 {
+        let allowed = [
+            vec![0; CREDENTIAL_ID_LEN],
+            second.credential.credential_id.clone(),
+        ];
+        let assertion = authenticator
+            .get_assertion(&get_assertion_request(&allowed))
+            .unwrap();
+        assert_eq!(assertion.credential_id, second.credential.credential_id);
+        assert_eq!(assertion.user_id, b"user-4711");
+
+        let data = &assertion.authenticator_data;
+        assert_eq!(data.len(), 37);
+        assert_eq!(data[..32], sha256(RP_ID.as_bytes()));
+        assert_eq!(data[32], 0x01);
+        assert_eq!(data[33..], expected_count.to_be_bytes());
+        assert!(second
+            .public_key
+            .verify(
+                &[&data[..], &CLIENT_DATA_HASH].concat(),
+                &assertion.signature
+            )
+            .unwrap());
+        assert!(!first
+            .public_key
+            .verify(
+                &[&data[..], &CLIENT_DATA_HASH].concat(),
+                &assertion.signature
+            )
+            .unwrap());
+    }
+    let stored = authenticator.store().list(RP_ID).unwrap();
+    assert_eq!(stored.len(), 2);
+    assert!(stored.iter().any(|credential| credential.sign_count == 2));
+
+    // Without an allow list, a discoverable credential of the relying party is used.
+    let assertion = authenticator
+        .get_assertion(&get_assertion_request(&[]))
+        .unwrap();
+    assert_eq!(assertion.credential_id, stored[0].credential_id);
+
+    let other_rp = GetAssertionRequest {
+        rp_id: "other.example",
+        ..get_assertion_request(&[])
+    };
+    assert!(matches!(
+        authenticator.get_assertion(&other_rp),
+        Err(SecurityModuleError::KeyError)
+    ));
+    assert!(matches!(
+        authenticator.get_assertion(&get_assertion_request(&[vec![0; CREDENTIAL_ID_LEN]])),
+        Err(SecurityModuleError::KeyError)
+    ));
+
+    assert!(authenticator
+        .store()
+        .delete(RP_ID, &second.credential.credential_id)
+        .unwrap());
+    assert!(authenticator
+        .get_assertion(&get_assertion_request(std::slice::from_ref(
+            &second.credential.credential_id
+        )))
+        .is_err());
+}
+
+#[test]
+fn test_cbor_encoding() {
+    // The examples of Appendix A of RFC 8949.
+    for (value, expected) in [
+        (Value::Int(0), &[0x00][..]),
+        (Value::Int(23), &[0x17]),
+        (Value::Int(24), &[0x18, 0x18]),
+        (Value::Int(1000), &[0x19, 0x03, 0xe8]),
+        (Value::Int(1000000), &[0x1a, 0x00, 0x0f, 0x42, 0x40]),
+        (
+            Value::Int(1 << 32),
+            &[0x1b, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00],
+        ),
+        (Value::Int(-1), &[0x20]),
+        (Value::Int(-1000), &[0x39, 0x03, 0xe7]),
+        (
+            Value::Int(i64::MIN),
+            &[0x3b, 0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff],
+        ),
+        (
+            Value::Bytes(vec![1, 2, 3, 4]),
+            &[0x44, 0x01, 0x02, 0x03, 0x04],
+        ),
+        (
+            Value::Text("IETF".to_owned()),
+            &[0x64, 0x49, 0x45, 0x54, 0x46],
+        ),
+        (Value::Map(vec![]), &[0xa0]),
+        (
+            Value::Map(vec![
+                (Value::Int(1), Value::Int(2)),
+                (Value::Int(3), Value::Int(4)),
+            ]),
+            &[0xa2, 0x01, 0x02, 0x03, 0x04],
+        ),
+    ] {
+        assert_eq!(value.encode(), expected, "{:?}", value);
+    }
+}
@@ -0,0 +1,257 @@
+use crate::{
+    common::{
+        crypto::{
+            algorithms::{
+                encryption::{AsymmetricEncryption, EccCurves, EccSchemeAlgorithm},
+                hashes::{Hash, Sha2Bits},
+            },
+            public_key::PublicKey,
+        },
+        traits::module_provider::Provider,
+    },
+    daemon::{Daemon, MAX_LINE_LEN, SECURITY_MODULE_ERROR},
+    mock::{MockConfig, MockProvider},
+};
+use base64::{prelude::BASE64_STANDARD, Engine};
+use serde_json::{json, Value};
+use std::{
+    io::{self, BufRead, BufReader, Cursor, Write},
+    sync::{Arc, Mutex},
+};
+use test_case::test_case;
+
+fn daemon() -> Daemon {
+    let mut provider = MockProvider::new("daemon".to_owned());
+    provider.initialize_module().unwrap();
+    Daemon::new(Arc::new(Mutex::new(provider)), MockConfig::from_spec)
+}
+
+fn call(daemon: &Daemon, method: &str, params: Value) -> Value {
+    let request = json!({ "jsonrpc": "2.0", "id": 7, "method": method, "params": params });
+    let response: Value =
+        serde_json::from_str(&daemon.handle_line(&request.to_string()).unwrap()).unwrap();
+    assert_eq!(response["jsonrpc"], "2.0");
+    assert_eq!(response["id"], 7);
+    response
+}
+
+fn result(daemon: &Daemon, method: &str, params: Value) -> Value {
+    let response = call(daemon, method, params);
+    assert!(
+        response.get("error").is_none(),
+        "{method} failed: {response}"
+    );
+    response["result"].clone()
+}
+
+fn error_code(daemon: &Daemon, method: &str, params: Value) -> i64 {
+    call(daemon, method, params)["error"]["code"]
+        .as_i64()
+        .unwrap()
+}
+
+fn spec(label: &str, algorithm: &str, purpose: &str) -> Value {
+    json!({ "spec": { "algorithm": algorithm, "purposes": [purpose], "label": label } })
+}
+
+fn base64(data: &[u8]) -> String {
+    BASE64_STANDARD.encode(data)
+}
+
+fn decode(value: &Value) -> Vec<u8> {
+    BASE64_STANDARD.decode(value.as_str().unwrap()).unwrap()
+}
+
+#[test]
+fn test_sign_and_verify() {
+    let daemon = daemon();
+
+    let created = result(&daemon, "createKey", spec("release", "EcP256", "Sign"));
+    assert_eq!(created["keyId"], "release");
+    let public_key = PublicKey::from_der(
+        &decode(&created["publicKey"]),
+        AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(EccCurves::P256)),
+        Hash::Sha2(Sha2Bits::Sha256),
+    )
+    .unwrap();
+
+    let params = json!({ "keyId": "release", "data": base64(b"artifact") });
+    let signature = decode(&result(&daemon, "sign", params)["signature"]);
+    assert!(public_key.verify(b"artifact", &signature).unwrap());
+    for (data, valid) in [(&b"artifact"[..], true), (b"tampered", false)] {
+        let params = json!({
+            "keyId": "release",
+            "data": base64(data),
+            "signature": base64(&signature),
+        });
+        assert_eq!(result(&daemon, "verify", params)["valid"], valid);
+    }
+}
+
+#[test]
+fn test_encrypt_and_decrypt() {
+    let daemon = daemon();
+    result(&daemon, "createKey", spec("backup", "Rsa2048", "Encrypt"));
+
+    let params = json!({ "keyId": "backup", "data": base64(b"secret") });
+    let ciphertext = result(&daemon, "encrypt", params)["ciphertext"].clone();
+    let params = json!({ "keyId": "backup", "data": ciphertext });
+
+    assert_eq!(
+        decode(&result(&daemon, "decrypt", params)["plaintext"]),
+        b"secret"
+    );
+}
+
+#[test]
+fn test_keys() {
+    let other = daemon();
+    let daemon = daemon();
+    let first = result(&daemon, "createKey", spec("first", "EcP256", "Sign"));
+    result(&daemon, "createKey", spec("second", "EcP384", "Sign"));
+
+    // Signing with the first key reloads it into the provider.
+    let params = json!({ "keyId": "first", "data": base64(b"data") });
+    let signature = result(&daemon, "sign", params)["signature"].clone();
+    let params = json!({ "keyId": "first", "data": base64(b"data"), "signature": signature });
+    assert_eq!(result(&daemon, "verify", params)["valid"], true);
+
+    assert_eq!(
+        result(&daemon, "listKeys", json!({}))["keyIds"],
+        json!(["first", "second"])
+    );
+    assert_eq!(
+        error_code(&other, "loadKey", spec("first", "EcP256", "Sign")),
+        SECURITY_MODULE_ERROR
+    );
+    assert_eq!(
+        result(&daemon, "loadKey", spec("first", "EcP256", "Sign"))["publicKey"],
+        first["publicKey"]
+    );
+}
+
+#[test]
+fn test_security_module_errors() {
+    let daemon = daemon();
+
+    let response = call(
+        &daemon,
+        "sign",
+        json!({ "keyId": "missing", "data": base64(b"data") }),
+    );
+
+    assert_eq!(
+        response["error"],
+        json!({
+            "code": SECURITY_MODULE_ERROR,
+            "message": "Key error",
+            "data": { "code": 6, "message": "Key error" },
+        })
+    );
+    // The mock provider has no symmetric keys.
+    let response = call(&daemon, "createKey", spec("aes", "Aes256Gcm", "Encrypt"));
+    assert_eq!(response["error"]["data"]["code"], 7);
+}
+
+#[test_case(r#"{"jsonrpc": "2.0", "id": 1, "method": "sign"#, -32700 ; "parse error")]
+#[test_case(r#"{"jsonrpc": "1.0", "id": 1, "method": "listKeys"}"#, -32600 ; "version")]
+#[test_case(r#"{"jsonrpc": "2.0", "id": 1}"#, -32600 ; "no method")]
+#[test_case(r#"{"jsonrpc": "2.0", "id": [1], "method": "listKeys"}"#, -32600 ; "invalid id")]
+#[test_case(r#"[]"#, -32600 ; "empty batch")]
+#[test_case(r#"{"jsonrpc": "2.0", "id": 1, "method": "export"}"#, -32601 ; "unknown method")]
+#[test_case(r#"{"jsonrpc": "2.0", "id": 1, "method": "listKeys", "params": [1]}"#, -32602 ; "positional params")]
+#[test_case(r#"{"jsonrpc": "2.0", "id": 1, "method": "sign", "params": {"keyId": "k"}}"#, -32602 ; "missing field")]
+#[test_case(r#"{"jsonrpc": "2.0", "id": 1, "method": "sign", "params": {"keyId": "k", "data": "!"}}"#, -32602 ; "invalid base64")]
+#[test_case(r#"{"jsonrpc": "2.0", "id": 1, "method": "createKey", "params": {"spec": {"algorithm": "EcP256", "label": "k"}}}"#, -32602 ; "invalid spec")]
+fn test_invalid_requests(line: &str, code: i64) {
+    let response: Value = serde_json::from_str(&daemon().handle_line(line).unwrap()).unwrap();
+
+    assert_eq!(response["error"]["code"], code, "{response}");
+}
+
+#[test]
+fn test_notifications_and_batches() {
+    let daemon = daemon();
+    let notification =
+        json!({ "jsonrpc": "2.0", "method": "createKey", "params": spec("k", "EcP256", "Sign") });
+
+    assert_eq!(daemon.handle_line(&notification.to_string()), None);
+    let batch = json!([
+        notification,
+        { "jsonrpc": "2.0", "id": "a", "method": "listKeys" },
+        { "jsonrpc": "2.0", "id": "b", "method": "unknown" },
+    ]);
+    let responses: Value =
+        serde_json::from_str(&daemon.handle_line(&batch.to_string()).unwrap()).unwrap();
+    assert_eq!(responses[0]["id"], "a");
+    assert_eq!(responses[0]["result"]["keyIds"], json!(["k"]));
+    assert_eq!(responses[1]["id"], "b");
+    assert_eq!(responses[1]["error"]["code"], -32601);
+    assert_eq!(responses.as_array().unwrap().len(), 2);
+}
+
+#[test]
+fn test_serve() {
+    let daemon = daemon();
+    let input = [
+        json!({ "jsonrpc": "2.0", "id": 1, "method": "createKey", "params": spec("k", "EcP256", "Sign") }).to_string(),
+        String::new(),
+        json!({ "jsonrpc": "2.0", "method": "listKeys" }).to_string(),
+        json!({ "jsonrpc": "2.0", "id": 2, "method": "listKeys" }).to_string(),
+    ]
+    .join("\n");
+    let mut output = Vec::new();
+
+    daemon.serve(Cursor::new(input), &mut output).unwrap();
+
+    let lines: Vec<Value> = output
+        .lines()
+        .map(|line| serde_json::from_str(&line.unwrap()).unwrap())
+        .collect();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0]["result"]["keyId"], "k");
+    assert_eq!(lines[1]["result"]["keyIds"], json!(["k"]));
+
+    let mut output = Vec::new();
+    let long = vec![b' '; MAX_LINE_LEN + 1];
+    let error = daemon.serve(Cursor::new(long), &mut output).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    daemon
+        .serve(Cursor::new(b"\xff\n".to_vec()), &mut output)
+        .unwrap();
+    let response: Value = serde_json::from_slice(&output).unwrap();
+    assert_eq!(response["error"]["code"], -32700);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_listen() {
+    use std::os::unix::net::UnixStream;
+
+    let path =
+        std::env::temp_dir().join(format!("crypto-layer-daemon-{}.sock", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let daemon = Arc::new(daemon());
+    {
+        let path = path.clone();
+        std::thread::spawn(move || daemon.listen(path));
+    }
+    let mut stream = loop {
+        match UnixStream::connect(&path) {
+            Ok(stream) => break stream,
+            Err(_) => std::thread::yield_now(),
+        }
+    };
+
+    writeln!(
+        stream,
+        r#"{{"jsonrpc": "2.0", "id": 1, "method": "listKeys"}}"#
+    )
+    .unwrap();
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line).unwrap();
+
+    let response: Value = serde_json::from_str(&line).unwrap();
+    assert_eq!(response["result"]["keyIds"], json!([]));
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,220 @@
+use crate::{ffi::c_api::*, SecurityModuleError};
+use std::{
+    ffi::{CStr, CString},
+    ptr, slice,
+};
+
+fn open(key_id: &str) -> *mut CryptoLayerProvider {
+    let module = CString::new("MOCK").unwrap();
+    let key_id = CString::new(key_id).unwrap();
+    let mut provider = ptr::null_mut();
+    let status =
+        unsafe { crypto_layer_provider_open(module.as_ptr(), key_id.as_ptr(), &mut provider) };
+    assert_eq!(status, CRYPTO_LAYER_OK);
+    assert!(!provider.is_null());
+    provider
+}
+
+fn spec(algorithm: u32, purposes: u32) -> CryptoLayerKeySpec {
+    CryptoLayerKeySpec {
+        algorithm,
+        purposes,
+        access: CRYPTO_LAYER_ACCESS_NONE,
+        exportable: false,
+    }
+}
+
+fn empty_buffer() -> CryptoLayerBuffer {
+    CryptoLayerBuffer {
+        data: ptr::null_mut(),
+        len: 0,
+    }
+}
+
+fn last_error_message() -> String {
+    unsafe { CStr::from_ptr(crypto_layer_last_error_message()) }
+        .to_str()
+        .unwrap()
+        .to_owned()
+}
+
+#[test]
+fn test_sign_and_verify() {
+    let provider = open("ffi_sign");
+    let key_id = CString::new("ffi_sign").unwrap();
+    let spec = spec(CRYPTO_LAYER_ALGORITHM_EC_P256, CRYPTO_LAYER_PURPOSE_SIGN);
+    let data = b"Hello, C!";
+    unsafe {
+        assert_eq!(
+            crypto_layer_create_key(provider, key_id.as_ptr(), &spec),
+            CRYPTO_LAYER_OK
+        );
+
+        let mut signature = empty_buffer();
+        assert_eq!(
+            crypto_layer_sign(provider, data.as_ptr(), data.len(), &mut signature),
+            CRYPTO_LAYER_OK
+        );
+        assert!(signature.len > 0);
+
+        let mut valid = false;
+        assert_eq!(
+            crypto_layer_verify(
+                provider,
+                data.as_ptr(),
+                data.len(),
+                signature.data,
+                signature.len,
+                &mut valid
+            ),
+            CRYPTO_LAYER_OK
+        );
+        assert!(valid);
+        let status = crypto_layer_verify(
+            provider,
+            b"Hello, Rust!".as_ptr(),
+            12,
+            signature.data,
+            signature.len,
+            &mut valid,
+        );
+        assert!(status != CRYPTO_LAYER_OK || !valid);
+
+        let mut public_key = empty_buffer();
+        assert_eq!(
+            crypto_layer_public_key(provider, &mut public_key),
+            CRYPTO_LAYER_OK
+        );
+        let der = slice::from_raw_parts(public_key.data, public_key.len);
+        assert!(openssl::pkey::PKey::public_key_from_der(der).is_ok());
+
+        crypto_layer_buffer_free(&mut signature);
+        assert!(signature.data.is_null());
+        assert_eq!(signature.len, 0);
+        crypto_layer_buffer_free(&mut signature);
+        crypto_layer_buffer_free(&mut public_key);
+        crypto_layer_provider_free(provider);
+    }
+}
+
+#[test]
+fn test_encrypt_and_decrypt() {
+    let provider = open("ffi_encrypt");
+    let key_id = CString::new("ffi_encrypt").unwrap();
+    let spec = spec(
+        CRYPTO_LAYER_ALGORITHM_RSA_2048,
+        CRYPTO_LAYER_PURPOSE_ENCRYPT,
+    );
+    let data = b"A secret for C";
+    unsafe {
+        assert_eq!(
+            crypto_layer_create_key(provider, key_id.as_ptr(), &spec),
+            CRYPTO_LAYER_OK
+        );
+
+        let mut ciphertext = empty_buffer();
+        assert_eq!(
+            crypto_layer_encrypt(provider, data.as_ptr(), data.len(), &mut ciphertext),
+            CRYPTO_LAYER_OK
+        );
+        let mut plaintext = empty_buffer();
+        assert_eq!(
+            crypto_layer_decrypt(provider, ciphertext.data, ciphertext.len, &mut plaintext),
+            CRYPTO_LAYER_OK
+        );
+        assert_eq!(slice::from_raw_parts(plaintext.data, plaintext.len), data);
+
+        crypto_layer_buffer_free(&mut ciphertext);
+        crypto_layer_buffer_free(&mut plaintext);
+        crypto_layer_provider_free(provider);
+    }
+}
+
+#[test]
+fn test_envelope() {
+    let provider = open("ffi_envelope");
+    let key_id = CString::new("ffi_envelope").unwrap();
+    let spec = spec(
+        CRYPTO_LAYER_ALGORITHM_EC_P256,
+        CRYPTO_LAYER_PURPOSE_KEY_AGREEMENT,
+    );
+    let data = vec![0x42; 100_000];
+    unsafe {
+        assert_eq!(
+            crypto_layer_create_key(provider, key_id.as_ptr(), &spec),
+            CRYPTO_LAYER_OK
+        );
+
+        let mut envelope = empty_buffer();
+        assert_eq!(
+            crypto_layer_envelope_encrypt(provider, data.as_ptr(), data.len(), &mut envelope),
+            CRYPTO_LAYER_OK
+        );
+        let mut plaintext = empty_buffer();
+        assert_eq!(
+            crypto_layer_envelope_decrypt(provider, envelope.data, envelope.len, &mut plaintext),
+            CRYPTO_LAYER_OK
+        );
+        assert_eq!(slice::from_raw_parts(plaintext.data, plaintext.len), data);
+
+        *envelope.data.add(envelope.len - 1) ^= 1;
+        assert_eq!(
+            crypto_layer_envelope_decrypt(provider, envelope.data, envelope.len, &mut plaintext),
+            SecurityModuleError::DecryptionError(String::new()).code() as i32
+        );
+
+        crypto_layer_buffer_free(&mut envelope);
+        crypto_layer_buffer_free(&mut plaintext);
+        crypto_layer_provider_free(provider);
+    }
+}
+
+#[test]
+fn test_errors() {
+    assert_eq!(crypto_layer_abi_version(), CRYPTO_LAYER_ABI_VERSION);
+
+    let module = CString::new("QUANTUM").unwrap();
+    let key_id = CString::new("ffi_errors").unwrap();
+    let mut provider = ptr::null_mut();
+    unsafe {
+        assert_eq!(
+            crypto_layer_provider_open(module.as_ptr(), key_id.as_ptr(), &mut provider),
+            CRYPTO_LAYER_ERROR_UNSUPPORTED_MODULE
+        );
+        assert!(provider.is_null());
+        assert!(last_error_message().contains("QUANTUM"));
+
+        let mut signature = empty_buffer();
+        assert_eq!(
+            crypto_layer_sign(ptr::null(), b"data".as_ptr(), 4, &mut signature),
+            CRYPTO_LAYER_ERROR_NULL_POINTER
+        );
+        assert!(last_error_message().contains("provider"));
+
+        let provider = open("ffi_errors");
+        let unknown = spec(99, CRYPTO_LAYER_PURPOSE_SIGN);
+        assert_eq!(
+            crypto_layer_create_key(provider, key_id.as_ptr(), &unknown),
+            CRYPTO_LAYER_ERROR_INVALID_ARGUMENT
+        );
+        let unknown = spec(CRYPTO_LAYER_ALGORITHM_EC_P256, 1 << 7);
+        assert_eq!(
+            crypto_layer_create_key(provider, key_id.as_ptr(), &unknown),
+            CRYPTO_LAYER_ERROR_INVALID_ARGUMENT
+        );
+        assert_eq!(
+            crypto_layer_sign(provider, ptr::null(), 4, &mut signature),
+            CRYPTO_LAYER_ERROR_NULL_POINTER
+        );
+
+        // No key was created, so signing fails with the code of the provider error.
+        let status = crypto_layer_sign(provider, ptr::null(), 0, &mut signature);
+        assert_ne!(status, CRYPTO_LAYER_OK);
+        assert!(status < CRYPTO_LAYER_ERROR_NULL_POINTER);
+        assert!(signature.data.is_null());
+
+        crypto_layer_buffer_free(ptr::null_mut());
+        crypto_layer_provider_free(provider);
+        crypto_layer_provider_free(ptr::null_mut());
+    }
+}
@@ -0,0 +1,112 @@
+use crate::ffi::mobile::{
+    abi_version, AccessControl, CryptoLayerError, CryptoProvider, KeyAlgorithm, KeyPurpose, KeySpec,
+};
+
+fn spec(algorithm: KeyAlgorithm, purpose: KeyPurpose) -> KeySpec {
+    KeySpec {
+        algorithm,
+        purposes: vec![purpose],
+        access: AccessControl::None,
+        exportable: false,
+    }
+}
+
+#[test]
+fn test_provider() {
+    let provider = CryptoProvider::open("MOCK".into(), "mobile_sign".into()).unwrap();
+    provider
+        .create_key(
+            "mobile_sign".into(),
+            spec(KeyAlgorithm::EcP256, KeyPurpose::Sign),
+        )
+        .unwrap();
+    let signature = provider.sign(b"Hello, Kotlin!".to_vec()).unwrap();
+    assert!(provider
+        .verify(b"Hello, Kotlin!".to_vec(), signature.clone())
+        .unwrap());
+    assert!(!provider
+        .verify(b"Hello, Swift!".to_vec(), signature)
+        .unwrap_or(false));
+    assert!(openssl::pkey::PKey::public_key_from_der(&provider.public_key().unwrap()).is_ok());
+
+    let provider = CryptoProvider::open("MOCK".into(), "mobile_encrypt".into()).unwrap();
+    provider
+        .create_key(
+            "mobile_encrypt".into(),
+            spec(KeyAlgorithm::Rsa2048, KeyPurpose::Encrypt),
+        )
+        .unwrap();
+    let ciphertext = provider.encrypt(b"A secret for Swift".to_vec()).unwrap();
+    assert_eq!(provider.decrypt(ciphertext).unwrap(), b"A secret for Swift");
+}
+
+#[test]
+fn test_errors() {
+    assert!(matches!(
+        CryptoProvider::open("QUANTUM".into(), "mobile_errors".into()),
+        Err(CryptoLayerError::UnsupportedModule { .. })
+    ));
+
+    let provider = CryptoProvider::open("MOCK".into(), "mobile_errors".into()).unwrap();
+    assert!(matches!(
+        provider.create_key(
+            "mobile errors/..".into(),
+            spec(KeyAlgorithm::EcP256, KeyPurpose::Sign)
+        ),
+        Err(CryptoLayerError::InvalidArgument { .. })
+    ));
+    assert!(matches!(
+        provider.create_key(
+            "mobile_errors".into(),
+            spec(KeyAlgorithm::Aes256Gcm, KeyPurpose::Sign)
+        ),
+        Err(CryptoLayerError::InvalidArgument { .. })
+    ));
+    match provider.sign(b"data".to_vec()) {
+        Err(CryptoLayerError::Module { code, .. }) => assert!(code > 0),
+        other => panic!("Signing without a key returned {:?}", other),
+    }
+}
+
+/// The enums of the UDL must list the variants of the Rust enums in the same order.
+#[test]
+fn test_udl_enums() {
+    let udl = include_str!("../../crypto_layer.udl");
+    let variants = |name: &str| -> Vec<String> {
+        let start = udl.find(&format!("enum {} {{", name)).unwrap();
+        let body = &udl[start..start + udl[start..].find('}').unwrap()];
+        body.split('"')
+            .skip(1)
+            .step_by(2)
+            .map(str::to_owned)
+            .collect()
+    };
+    let debug = |values: &[&dyn std::fmt::Debug]| -> Vec<String> {
+        values.iter().map(|value| format!("{:?}", value)).collect()
+    };
+
+    use KeyAlgorithm::*;
+    assert_eq!(
+        variants("KeyAlgorithm"),
+        debug(&[&EcP256, &EcP384, &EcP521, &Rsa2048, &Rsa3072, &Rsa4096, &Aes128Gcm, &Aes256Gcm])
+    );
+    assert_eq!(
+        variants("KeyPurpose"),
+        debug(&[
+            &KeyPurpose::Sign,
+            &KeyPurpose::Encrypt,
+            &KeyPurpose::KeyAgreement
+        ])
+    );
+    assert_eq!(
+        variants("AccessControl"),
+        debug(&[
+            &AccessControl::None,
+            &AccessControl::UserPresence,
+            &AccessControl::BiometryAny,
+            &AccessControl::BiometryCurrentSet,
+            &AccessControl::DevicePasscode
+        ])
+    );
+    assert_eq!(abi_version(), crate::ffi::c_api::CRYPTO_LAYER_ABI_VERSION);
+}
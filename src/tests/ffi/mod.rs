@@ -0,0 +1,3 @@
+mod c_api;
+#[cfg(feature = "uniffi")]
+mod mobile;
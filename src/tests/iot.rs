@@ -0,0 +1,294 @@
+use crate::{
+    common::{
+        crypto::algorithms::{
+            encryption::{AsymmetricEncryption, EccCurves, EccSchemeAlgorithm},
+            hashes::{Hash, Sha2Bits},
+        },
+        device_identity::DeviceIdentity,
+        traits::module_provider::Provider,
+    },
+    iot::{IotDevice, IotHub, MqttConnection, MQTT_PORT},
+    mock::{MockConfig, MockProvider},
+    SecurityModuleError,
+};
+use openssl::{
+    asn1::Asn1Time,
+    bn::BigNum,
+    ec::{EcGroup, EcKey},
+    hash::MessageDigest,
+    nid::Nid,
+    pkey::{PKey, Private},
+    x509::{
+        extension::{BasicConstraints, ExtendedKeyUsage, SubjectAlternativeName},
+        X509Builder, X509NameBuilder, X509Req, X509,
+    },
+};
+use rustls::{
+    crypto::ring,
+    pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer, ServerName},
+    server::WebPkiClientVerifier,
+    ClientConfig, ClientConnection, Connection, RootCertStore, ServerConfig, ServerConnection,
+};
+use std::sync::{Arc, Mutex};
+
+fn identity() -> Arc<DeviceIdentity> {
+    let mut provider = MockProvider::new(String::new());
+    provider.initialize_module().unwrap();
+    let identity = DeviceIdentity::open(Arc::new(Mutex::new(provider)), || {
+        MockConfig::new(
+            AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(EccCurves::P256)),
+            Hash::Sha2(Sha2Bits::Sha256),
+        )
+    })
+    .unwrap();
+    Arc::new(identity)
+}
+
+fn azure_hub() -> IotHub {
+    IotHub::AzureIotHub {
+        host_name: "my-hub.azure-devices.net".to_owned(),
+    }
+}
+
+fn generate_key() -> PKey<Private> {
+    let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+    PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap()
+}
+
+/// A certificate authority issuing the device and server certificates.
+struct Ca {
+    certificate: X509,
+    key: PKey<Private>,
+}
+
+impl Ca {
+    fn new() -> Self {
+        let key = generate_key();
+        let mut builder = Self::builder("Test CA", &key);
+        builder
+            .set_issuer_name(builder_name("Test CA").as_ref())
+            .unwrap();
+        builder
+            .append_extension(BasicConstraints::new().critical().ca().build().unwrap())
+            .unwrap();
+        builder.sign(&key, MessageDigest::sha256()).unwrap();
+        Self {
+            certificate: builder.build(),
+            key,
+        }
+    }
+
+    fn builder(common_name: &str, key: &PKey<impl openssl::pkey::HasPublic>) -> X509Builder {
+        let mut builder = X509Builder::new().unwrap();
+        builder.set_version(2).unwrap();
+        let serial = BigNum::from_u32(rand::random()).unwrap();
+        builder
+            .set_serial_number(serial.to_asn1_integer().unwrap().as_ref())
+            .unwrap();
+        builder
+            .set_subject_name(builder_name(common_name).as_ref())
+            .unwrap();
+        builder.set_pubkey(key).unwrap();
+        builder
+            .set_not_before(Asn1Time::days_from_now(0).unwrap().as_ref())
+            .unwrap();
+        builder
+            .set_not_after(Asn1Time::days_from_now(1).unwrap().as_ref())
+            .unwrap();
+        builder
+    }
+
+    fn issue(&self, builder: &mut X509Builder) -> Vec<u8> {
+        builder
+            .set_issuer_name(self.certificate.subject_name())
+            .unwrap();
+        builder.sign(&self.key, MessageDigest::sha256()).unwrap();
+        std::mem::replace(builder, X509Builder::new().unwrap())
+            .build()
+            .to_der()
+            .unwrap()
+    }
+
+    /// Issues a client certificate for a CSR, as the backend of the fleet would.
+    fn enroll(&self, csr: &[u8]) -> Vec<u8> {
+        let request = X509Req::from_der(csr).unwrap();
+        let public_key = request.public_key().unwrap();
+        assert!(request.verify(&public_key).unwrap());
+        let common_name = request
+            .subject_name()
+            .entries_by_nid(Nid::COMMONNAME)
+            .next()
+            .unwrap()
+            .data()
+            .as_utf8()
+            .unwrap()
+            .to_string();
+        let mut builder = Self::builder(&common_name, &public_key);
+        builder
+            .append_extension(ExtendedKeyUsage::new().client_auth().build().unwrap())
+            .unwrap();
+        self.issue(&mut builder)
+    }
+
+    fn roots(&self) -> Arc<RootCertStore> {
+        let mut roots = RootCertStore::empty();
+        roots
+            .add(CertificateDer::from(self.certificate.to_der().unwrap()))
+            .unwrap();
+        Arc::new(roots)
+    }
+}
+
+fn builder_name(common_name: &str) -> openssl::x509::X509Name {
+    let mut name = X509NameBuilder::new().unwrap();
+    name.append_entry_by_nid(Nid::COMMONNAME, common_name)
+        .unwrap();
+    name.build()
+}
+
+/// Passes the TLS records between both connections until the handshake is complete.
+fn handshake(client: &mut Connection, server: &mut Connection) {
+    fn transfer(from: &mut Connection, to: &mut Connection) {
+        let mut records = Vec::new();
+        while from.wants_write() {
+            from.write_tls(&mut records).unwrap();
+        }
+        let mut records = &records[..];
+        while !records.is_empty() {
+            to.read_tls(&mut records).unwrap();
+        }
+        to.process_new_packets().unwrap();
+    }
+
+    while client.is_handshaking() || server.is_handshaking() {
+        transfer(client, server);
+        transfer(server, client);
+    }
+}
+
+#[test]
+fn test_mutual_tls() {
+    let ca = Ca::new();
+    let identity = identity();
+    let mut device = IotDevice::new(Arc::clone(&identity), "device-42", azure_hub()).unwrap();
+    assert!(matches!(
+        device.certified_key(),
+        Err(SecurityModuleError::InitializationError(_))
+    ));
+
+    let csr = device.enrollment_csr().unwrap();
+    let certificate = ca.enroll(&csr);
+    let pem = X509::from_der(&certificate).unwrap().to_pem().unwrap();
+    device.set_certificate_chain_pem(&pem).unwrap();
+    assert_eq!(
+        device.certificate_chain(),
+        [CertificateDer::from(certificate.clone())]
+    );
+
+    let provider = Arc::new(ring::default_provider());
+    let server_key = generate_key();
+    let mut builder = Ca::builder("my-hub.azure-devices.net", &server_key);
+    let context = builder.x509v3_context(Some(&ca.certificate), None);
+    let subject_alt_name = SubjectAlternativeName::new()
+        .dns("my-hub.azure-devices.net")
+        .build(&context)
+        .unwrap();
+    builder.append_extension(subject_alt_name).unwrap();
+    let server_certificate = ca.issue(&mut builder);
+    let client_verifier =
+        WebPkiClientVerifier::builder_with_provider(ca.roots(), Arc::clone(&provider))
+            .build()
+            .unwrap();
+    let server_config = ServerConfig::builder_with_provider(Arc::clone(&provider))
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .with_client_cert_verifier(client_verifier)
+        .with_single_cert(
+            vec![CertificateDer::from(server_certificate)],
+            PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(
+                server_key.private_key_to_pkcs8().unwrap(),
+            )),
+        )
+        .unwrap();
+
+    let client_config = device
+        .client_config(
+            ClientConfig::builder_with_provider(provider)
+                .with_safe_default_protocol_versions()
+                .unwrap()
+                .with_root_certificates(ca.roots()),
+        )
+        .unwrap();
+    let connection = device.mqtt_connection();
+    let server_name = ServerName::try_from(connection.host).unwrap();
+    let mut client =
+        Connection::from(ClientConnection::new(Arc::new(client_config), server_name).unwrap());
+    let mut server = Connection::from(ServerConnection::new(Arc::new(server_config)).unwrap());
+    handshake(&mut client, &mut server);
+
+    // The server authenticated the device by the certificate of its identity key.
+    assert_eq!(
+        server.peer_certificates().unwrap(),
+        [CertificateDer::from(certificate)]
+    );
+}
+
+#[test]
+fn test_certificate_chain() {
+    let ca = Ca::new();
+    let mut device = IotDevice::new(identity(), "device-42", azure_hub()).unwrap();
+
+    // A certificate for the key of another device is rejected.
+    let other = IotDevice::new(identity(), "device-43", azure_hub()).unwrap();
+    let certificate = ca.enroll(&other.enrollment_csr().unwrap());
+    assert!(matches!(
+        device.set_certificate_chain(vec![certificate]),
+        Err(SecurityModuleError::InvalidPublicKey)
+    ));
+    assert!(matches!(
+        device.set_certificate_chain(Vec::new()),
+        Err(SecurityModuleError::InvalidPublicKey)
+    ));
+    assert!(matches!(
+        device.set_certificate_chain_pem(b"not a certificate"),
+        Err(SecurityModuleError::InvalidPublicKey)
+    ));
+    assert!(device.certificate_chain().is_empty());
+
+    // The chain may contain intermediate certificates after the device certificate.
+    let certificate = ca.enroll(&device.enrollment_csr().unwrap());
+    let chain = vec![certificate, ca.certificate.to_der().unwrap()];
+    device.set_certificate_chain(chain.clone()).unwrap();
+    let chain: Vec<_> = chain.into_iter().map(CertificateDer::from).collect();
+    assert_eq!(device.certificate_chain(), chain);
+    assert_eq!(device.certified_key().unwrap().cert, chain);
+}
+
+#[test]
+fn test_mqtt_connection() {
+    let device = IotDevice::new(identity(), "device-42", azure_hub()).unwrap();
+    assert_eq!(
+        device.mqtt_connection(),
+        MqttConnection {
+            host: "my-hub.azure-devices.net".to_owned(),
+            port: MQTT_PORT,
+            client_id: "device-42".to_owned(),
+            username: Some("my-hub.azure-devices.net/device-42/?api-version=2021-04-12".to_owned()),
+        }
+    );
+
+    let hub = IotHub::AwsIot {
+        endpoint: "a1b2c3d4e5f6g7-ats.iot.eu-central-1.amazonaws.com".to_owned(),
+    };
+    let device = IotDevice::new(identity(), "thing:42", hub).unwrap();
+    let connection = device.mqtt_connection();
+    assert_eq!(connection.client_id, "thing:42");
+    assert_eq!(connection.username, None);
+
+    for device_id in ["", "device 42", "device/42", &"a".repeat(65)] {
+        assert!(matches!(
+            IotDevice::new(identity(), device_id, azure_hub()),
+            Err(SecurityModuleError::InitializationError(_))
+        ));
+    }
+}
@@ -0,0 +1,314 @@
+use crate::{
+    common::{
+        crypto::algorithms::{
+            encryption::{AsymmetricEncryption, EccCurves, EccSchemeAlgorithm},
+            hashes::{Hash, Sha2Bits},
+            KeyBits,
+        },
+        traits::module_provider::Provider,
+    },
+    kms_plugin::{
+        DecryptRequest, DecryptResponse, EncryptRequest, EncryptResponse, KmsPlugin,
+        StatusResponse, API_VERSION,
+    },
+    mock::{MockConfig, MockProvider},
+    SecurityModuleError,
+};
+use bytes::Bytes;
+use crypto_layer_core::CoreError;
+use http::{HeaderMap, Request};
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+
+fn provider(key_id: &str, algorithm: AsymmetricEncryption) -> Arc<Mutex<MockProvider>> {
+    Arc::new(Mutex::new(MockProvider::with_key(
+        key_id,
+        MockConfig::new(algorithm, Hash::Sha2(Sha2Bits::Sha256)),
+    )))
+}
+
+fn rsa(key_id: &str) -> Arc<Mutex<MockProvider>> {
+    provider(key_id, AsymmetricEncryption::Rsa(KeyBits::Bits2048))
+}
+
+fn encrypt_request(plaintext: &[u8]) -> EncryptRequest {
+    EncryptRequest {
+        plaintext: plaintext.to_vec(),
+        uid: "0b7c6ab5-2e3c-4d0e-9c1a-3f0e1d2a4b5c".to_owned(),
+    }
+}
+
+fn decrypt_request(response: &EncryptResponse) -> DecryptRequest {
+    DecryptRequest {
+        ciphertext: response.ciphertext.clone(),
+        uid: "5f2d8e1a-7b9c-4e6f-8a0d-1c3b5e7f9a2d".to_owned(),
+        key_id: response.key_id.clone(),
+        annotations: response.annotations.clone(),
+    }
+}
+
+#[test]
+fn test_encrypt_and_decrypt() {
+    let plugin = KmsPlugin::new(rsa("kms-key")).unwrap();
+    assert!(plugin.key_id().starts_with("kms-key@"));
+    assert_eq!(plugin.key_id().len(), "kms-key@".len() + 32);
+
+    let status = plugin.status();
+    assert_eq!(status.version, API_VERSION);
+    assert_eq!(status.healthz, "ok");
+    assert_eq!(status.key_id, plugin.key_id());
+
+    let seed = [7; 32];
+    let response = plugin.encrypt(&encrypt_request(&seed)).unwrap();
+    assert_eq!(response.key_id, plugin.key_id());
+    assert_eq!(response.ciphertext.len(), 256);
+    assert_eq!(
+        plugin
+            .decrypt(&decrypt_request(&response))
+            .unwrap()
+            .plaintext,
+        seed
+    );
+
+    assert!(matches!(
+        plugin.encrypt(&encrypt_request(b"")),
+        Err(SecurityModuleError::Encoding(CoreError::MissingField(
+            "plaintext"
+        )))
+    ));
+    let mut request = decrypt_request(&response);
+    request.key_id = "kms-key@00000000000000000000000000000000".to_owned();
+    assert!(matches!(
+        plugin.decrypt(&request),
+        Err(SecurityModuleError::KeyError)
+    ));
+    let mut request = decrypt_request(&response);
+    request.ciphertext[0] ^= 1;
+    assert!(matches!(
+        plugin.decrypt(&request),
+        Err(SecurityModuleError::DecryptionError(_))
+    ));
+}
+
+#[test]
+fn test_key_rotation() {
+    let old = rsa("kms-key-1");
+    let new = rsa("kms-key-2");
+    let response = KmsPlugin::new(old.clone())
+        .unwrap()
+        .encrypt(&encrypt_request(b"seed"))
+        .unwrap();
+
+    let mut plugin = KmsPlugin::new(new).unwrap();
+    assert!(matches!(
+        plugin.decrypt(&decrypt_request(&response)),
+        Err(SecurityModuleError::KeyError)
+    ));
+    plugin.add_previous_key(old).unwrap();
+    assert_eq!(
+        plugin
+            .decrypt(&decrypt_request(&response))
+            .unwrap()
+            .plaintext,
+        b"seed"
+    );
+    // New data encryption keys are encrypted under the current key only.
+    let response = plugin.encrypt(&encrypt_request(b"seed")).unwrap();
+    assert_eq!(response.key_id, plugin.key_id());
+    assert!(format!("{:?}", plugin).contains("kms-key-1@"));
+}
+
+#[test]
+fn test_health() {
+    let ec = provider(
+        "kms-key",
+        AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(EccCurves::P256)),
+    );
+    let plugin = KmsPlugin::new(ec).unwrap();
+    let status = plugin.status();
+    assert_ne!(status.healthz, "ok");
+    assert_eq!(status.key_id, plugin.key_id());
+
+    // A key that the provider no longer holds is not used.
+    let provider = rsa("kms-key");
+    let plugin = KmsPlugin::new(provider.clone()).unwrap();
+    provider
+        .lock()
+        .unwrap()
+        .create_key(
+            "other-key",
+            MockConfig::new(
+                AsymmetricEncryption::Rsa(KeyBits::Bits2048),
+                Hash::Sha2(Sha2Bits::Sha256),
+            ),
+        )
+        .unwrap();
+    assert_ne!(plugin.status().healthz, "ok");
+    assert!(matches!(
+        plugin.encrypt(&encrypt_request(b"seed")),
+        Err(SecurityModuleError::KeyError)
+    ));
+}
+
+#[test]
+fn test_messages() {
+    let request = encrypt_request(b"abc");
+    let bytes = request.to_bytes();
+    assert_eq!(bytes[..5], [0x0a, 3, b'a', b'b', b'c']);
+    assert_eq!(bytes[5..7], [0x12, 36]);
+    assert_eq!(EncryptRequest::from_bytes(&bytes).unwrap(), request);
+    assert!(!format!("{:?}", request).contains("97, 98, 99"));
+
+    // Annotations are map entries with the key in field 1 and the value in field 2.
+    let response = EncryptResponse {
+        ciphertext: vec![1, 2],
+        key_id: "k".to_owned(),
+        annotations: BTreeMap::from([("a.example.com".to_owned(), b"v".to_vec())]),
+    };
+    let bytes = response.to_bytes();
+    assert_eq!(bytes[..7], [0x0a, 2, 1, 2, 0x12, 1, b'k']);
+    assert_eq!(bytes[7..10], [0x1a, 18, 0x0a]);
+    assert_eq!(EncryptResponse::from_bytes(&bytes).unwrap(), response);
+    let request = DecryptRequest {
+        ciphertext: response.ciphertext.clone(),
+        uid: String::new(),
+        key_id: response.key_id.clone(),
+        annotations: response.annotations.clone(),
+    };
+    assert_eq!(
+        DecryptRequest::from_bytes(&request.to_bytes()).unwrap(),
+        request
+    );
+
+    // Unknown fields of all wire types are skipped, empty fields have the default value.
+    let unknown = [
+        &[0x28, 0x96, 0x01][..],
+        &[0x31, 0, 0, 0, 0, 0, 0, 0, 0],
+        &[0x3d, 0, 0, 0, 0],
+        &[0x42, 1, 0],
+    ]
+    .concat();
+    let bytes = [&unknown[..], &[0x0a, 1, 9]].concat();
+    assert_eq!(DecryptResponse::from_bytes(&bytes).unwrap().plaintext, [9]);
+    assert_eq!(
+        StatusResponse::from_bytes(&[]).unwrap(),
+        StatusResponse::default()
+    );
+
+    for malformed in [
+        &[0x0a, 3, b'a'][..],
+        &[0x0a],
+        &[0x12, 1, 0xff],
+        &[0x0b],
+        &[
+            0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x01,
+        ],
+    ] {
+        assert!(matches!(
+            EncryptRequest::from_bytes(malformed),
+            Err(SecurityModuleError::Encoding(_))
+        ));
+    }
+}
+
+/// Calls `path` of the plugin over HTTP/2 and returns the message and the `grpc-status`.
+async fn call(
+    client: &h2::client::SendRequest<Bytes>,
+    path: &str,
+    body: Vec<u8>,
+) -> (Vec<u8>, String) {
+    let request = Request::post(format!("http://localhost{}", path))
+        .header("content-type", "application/grpc")
+        .header("te", "trailers")
+        .body(())
+        .unwrap();
+    let (response, mut stream) = client
+        .clone()
+        .ready()
+        .await
+        .unwrap()
+        .send_request(request, false)
+        .unwrap();
+    stream.send_data(body.into(), true).unwrap();
+
+    let response = response.await.unwrap();
+    if let Some(status) = response.headers().get("grpc-status") {
+        return (Vec::new(), status.to_str().unwrap().to_owned());
+    }
+    let mut body = response.into_body();
+    let mut message = Vec::new();
+    while let Some(data) = body.data().await {
+        message.extend_from_slice(&data.unwrap());
+    }
+    let trailers: HeaderMap = body.trailers().await.unwrap().unwrap();
+    let status = trailers["grpc-status"].to_str().unwrap().to_owned();
+    assert_eq!(
+        u32::from_be_bytes(message[1..5].try_into().unwrap()) as usize,
+        message.len() - 5
+    );
+    (message[5..].to_vec(), status)
+}
+
+fn framed(message: &[u8]) -> Vec<u8> {
+    [&[0][..], &(message.len() as u32).to_be_bytes(), message].concat()
+}
+
+#[tokio::test]
+async fn test_grpc() {
+    let plugin = Arc::new(KmsPlugin::new(rsa("kms-key")).unwrap());
+    let (client_io, server_io) = tokio::io::duplex(64 * 1024);
+    tokio::spawn(Arc::clone(&plugin).serve(server_io));
+    let (client, connection) = h2::client::handshake(client_io).await.unwrap();
+    tokio::spawn(connection);
+
+    let (message, status) = call(&client, "/v2.KeyManagementService/Status", framed(&[])).await;
+    assert_eq!(status, "0");
+    let response = StatusResponse::from_bytes(&message).unwrap();
+    assert_eq!(response.healthz, "ok");
+    assert_eq!(response.key_id, plugin.key_id());
+
+    let (message, status) = call(
+        &client,
+        "/v2.KeyManagementService/Encrypt",
+        framed(&encrypt_request(b"seed").to_bytes()),
+    )
+    .await;
+    assert_eq!(status, "0");
+    let response = EncryptResponse::from_bytes(&message).unwrap();
+    let request = decrypt_request(&response);
+    let (message, status) = call(
+        &client,
+        "/v2.KeyManagementService/Decrypt",
+        framed(&request.to_bytes()),
+    )
+    .await;
+    assert_eq!(status, "0");
+    assert_eq!(
+        DecryptResponse::from_bytes(&message).unwrap().plaintext,
+        b"seed"
+    );
+
+    let mut unknown_key = request.clone();
+    unknown_key.key_id = "other".to_owned();
+    for (path, body, expected) in [
+        (
+            "/v2.KeyManagementService/Decrypt",
+            framed(&unknown_key.to_bytes()),
+            "5",
+        ),
+        ("/v2.KeyManagementService/Encrypt", framed(&[0x0a]), "3"),
+        ("/v2.KeyManagementService/Encrypt", framed(&[]), "3"),
+        ("/v2.KeyManagementService/Encrypt", vec![0, 0, 0], "3"),
+        (
+            "/v2.KeyManagementService/Encrypt",
+            [&[1][..], &framed(&[])[1..]].concat(),
+            "12",
+        ),
+        ("/v2.KeyManagementService/Rotate", framed(&[]), "12"),
+        ("/v1beta1.KeyManagementService/Encrypt", framed(&[]), "12"),
+    ] {
+        assert_eq!(call(&client, path, body).await.1, expected, "{}", path);
+    }
+}
@@ -0,0 +1,220 @@
+use crate::{
+    common::{
+        crypto::{
+            algorithms::{
+                encryption::{AsymmetricEncryption, EccCurves, EccSchemeAlgorithm},
+                hashes::{Hash, Sha2Bits},
+            },
+            public_key::PublicKey,
+        },
+        traits::module_provider::Provider,
+    },
+    messaging::{Prekey, PrekeyBundle, Session, SessionInit, MAX_SKIPPED_MESSAGES},
+    mock::{MockConfig, MockProvider},
+    SecurityModuleError,
+};
+
+fn provider(key_id: &str) -> MockProvider {
+    MockProvider::with_key(
+        key_id,
+        MockConfig::new(
+            AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(EccCurves::P256)),
+            Hash::Sha2(Sha2Bits::Sha256),
+        ),
+    )
+}
+
+fn identity(provider: &MockProvider) -> PublicKey {
+    provider.key_metadata().unwrap().public_key().clone()
+}
+
+/// Sets up a session between Alice, the initiator, and Bob, the responder, and delivers the
+/// first message, after which both sides can send.
+fn sessions() -> (Session, Session) {
+    let alice = provider("alice");
+    let bob = provider("bob");
+    let prekey = Prekey::generate(&bob).unwrap();
+    let (mut alice_session, init) =
+        Session::initiate(&alice, prekey.bundle(), &identity(&bob)).unwrap();
+    let mut bob_session = Session::accept(&prekey, &init, &identity(&alice)).unwrap();
+
+    let message = alice_session.encrypt(b"hello", b"chat").unwrap();
+    assert_eq!(bob_session.decrypt(&message, b"chat").unwrap(), b"hello");
+    (alice_session, bob_session)
+}
+
+#[test]
+fn test_conversation() {
+    let (mut alice, mut bob) = sessions();
+    assert!(bob.encrypt(b"", b"").is_ok());
+
+    for round in 0..3u8 {
+        for i in 0..=round {
+            let message = bob.encrypt(&[round, i], b"chat").unwrap();
+            assert_eq!(alice.decrypt(&message, b"chat").unwrap(), [round, i]);
+        }
+        let message = alice.encrypt(&[round], b"chat").unwrap();
+        assert_eq!(bob.decrypt(&message, b"chat").unwrap(), [round]);
+    }
+    // The empty message Bob encrypted first was never delivered.
+    assert_eq!(alice.skipped_messages(), 1);
+    assert_eq!(bob.skipped_messages(), 0);
+
+    let message = alice.encrypt(b"hello", b"chat").unwrap();
+    assert!(matches!(
+        bob.decrypt(&message, b"other chat"),
+        Err(SecurityModuleError::DecryptionError(_))
+    ));
+}
+
+#[test]
+fn test_out_of_order_and_replay() {
+    let (mut alice, mut bob) = sessions();
+    let messages: Vec<_> = (0..4u8)
+        .map(|i| alice.encrypt(&[i], b"").unwrap())
+        .collect();
+
+    assert_eq!(bob.decrypt(&messages[3], b"").unwrap(), [3]);
+    assert_eq!(bob.skipped_messages(), 3);
+    assert_eq!(bob.decrypt(&messages[1], b"").unwrap(), [1]);
+    assert_eq!(bob.decrypt(&messages[0], b"").unwrap(), [0]);
+    assert_eq!(bob.skipped_messages(), 1);
+
+    // Bob replies, starting a new chain, before the last message of the old one arrives.
+    let reply = bob.encrypt(b"reply", b"").unwrap();
+    assert_eq!(alice.decrypt(&reply, b"").unwrap(), b"reply");
+    let message = alice.encrypt(b"next", b"").unwrap();
+    assert_eq!(bob.decrypt(&message, b"").unwrap(), b"next");
+    assert_eq!(bob.decrypt(&messages[2], b"").unwrap(), [2]);
+    assert_eq!(bob.skipped_messages(), 0);
+
+    for message in [&messages[2], &messages[3], &message] {
+        assert!(matches!(
+            bob.decrypt(message, b""),
+            Err(SecurityModuleError::DecryptionError(_))
+        ));
+    }
+}
+
+#[test]
+fn test_too_many_skipped_messages() {
+    let (mut alice, mut bob) = sessions();
+    for _ in 0..=MAX_SKIPPED_MESSAGES {
+        alice.encrypt(b"lost", b"").unwrap();
+    }
+    let message = alice.encrypt(b"late", b"").unwrap();
+    assert!(matches!(
+        bob.decrypt(&message, b""),
+        Err(SecurityModuleError::DecryptionError(_))
+    ));
+    assert_eq!(bob.skipped_messages(), 0);
+}
+
+#[test]
+fn test_tampered_message_leaves_session_unchanged() {
+    let (mut alice, mut bob) = sessions();
+    let first = alice.encrypt(b"first", b"").unwrap();
+    let second = alice.encrypt(b"second", b"").unwrap();
+
+    for i in [1, second.len() / 2, second.len() - 1] {
+        let mut tampered = second.clone();
+        tampered[i] ^= 1;
+        assert!(matches!(
+            bob.decrypt(&tampered, b""),
+            Err(SecurityModuleError::DecryptionError(_))
+        ));
+        assert_eq!(bob.skipped_messages(), 0);
+    }
+    assert!(bob.decrypt(&second[..10], b"").is_err());
+
+    assert_eq!(bob.decrypt(&first, b"").unwrap(), b"first");
+    assert_eq!(bob.decrypt(&second, b"").unwrap(), b"second");
+}
+
+#[test]
+fn test_responder_sends_after_first_message() {
+    let alice = provider("alice");
+    let bob = provider("bob");
+    let prekey = Prekey::generate(&bob).unwrap();
+    let (_, init) = Session::initiate(&alice, prekey.bundle(), &identity(&bob)).unwrap();
+    let mut bob_session = Session::accept(&prekey, &init, &identity(&alice)).unwrap();
+
+    assert!(matches!(
+        bob_session.encrypt(b"hello", b""),
+        Err(SecurityModuleError::EncryptionError(_))
+    ));
+    assert_eq!(
+        bob_session.peer_identity_public_key_der(),
+        identity(&alice).to_der().unwrap()
+    );
+}
+
+#[test]
+fn test_identity_keys_are_verified() {
+    let alice = provider("alice");
+    let bob = provider("bob");
+    let mallory = provider("mallory");
+    let prekey = Prekey::generate(&bob).unwrap();
+
+    // A bundle of Bob is not accepted for another identity.
+    assert!(matches!(
+        Session::initiate(&alice, prekey.bundle(), &identity(&mallory)),
+        Err(SecurityModuleError::InvalidSignature)
+    ));
+    // Nor is the identity key of Bob with the prekey and signature of another identity.
+    let bundle = prekey.bundle().to_bytes();
+    let forged = Prekey::generate(&mallory).unwrap().bundle().to_bytes();
+    let prekey_start = 3 + prekey.bundle().identity_public_key_der().len();
+    let bundle =
+        PrekeyBundle::from_bytes(&[&bundle[..prekey_start], &forged[prekey_start..]].concat())
+            .unwrap();
+    assert!(matches!(
+        Session::initiate(&alice, &bundle, &identity(&bob)),
+        Err(SecurityModuleError::InvalidSignature)
+    ));
+
+    // The handshake of Alice is not accepted for another identity or another prekey.
+    let (_, init) = Session::initiate(&alice, prekey.bundle(), &identity(&bob)).unwrap();
+    assert!(matches!(
+        Session::accept(&prekey, &init, &identity(&mallory)),
+        Err(SecurityModuleError::InvalidSignature)
+    ));
+    let other_prekey = Prekey::generate(&bob).unwrap();
+    assert!(matches!(
+        Session::accept(&other_prekey, &init, &identity(&alice)),
+        Err(SecurityModuleError::InvalidSignature)
+    ));
+}
+
+#[test]
+fn test_encodings() {
+    let alice = provider("alice");
+    let bob = provider("bob");
+    let prekey = Prekey::generate(&bob).unwrap();
+    assert!(format!("{:?}", prekey).ends_with(", .. }"));
+
+    let bundle = PrekeyBundle::from_bytes(&prekey.bundle().to_bytes()).unwrap();
+    assert_eq!(&bundle, prekey.bundle());
+    assert_eq!(
+        bundle.identity_public_key_der(),
+        identity(&bob).to_der().unwrap()
+    );
+    let (mut alice_session, init) = Session::initiate(&alice, &bundle, &identity(&bob)).unwrap();
+    let init = SessionInit::from_bytes(&init.to_bytes()).unwrap();
+    assert_eq!(
+        init.identity_public_key_der(),
+        identity(&alice).to_der().unwrap()
+    );
+    let mut bob_session = Session::accept(&prekey, &init, &identity(&alice)).unwrap();
+    let message = alice_session.encrypt(b"hello", b"").unwrap();
+    assert_eq!(bob_session.decrypt(&message, b"").unwrap(), b"hello");
+
+    for bytes in [&prekey.bundle().to_bytes(), &init.to_bytes()] {
+        let mut unsupported = bytes.clone();
+        unsupported[0] = 2;
+        assert!(PrekeyBundle::from_bytes(&unsupported).is_err());
+        assert!(SessionInit::from_bytes(&unsupported).is_err());
+        assert!(PrekeyBundle::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(SessionInit::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+    }
+}
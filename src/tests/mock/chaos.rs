@@ -0,0 +1,132 @@
+use crate::{
+    common::{
+        latency::ProviderOperation,
+        traits::{key_handle::KeyHandle, module_provider::Provider},
+    },
+    mock::{
+        chaos::{ChaosConfig, ChaosProvider, ChaosStats},
+        MockConfig, MockController, MockProvider,
+    },
+    SecurityModuleError,
+};
+use std::{
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+fn chaos_provider(config: ChaosConfig) -> (ChaosProvider, MockController) {
+    let mock = MockProvider::with_key("test_key", Box::new(MockConfig::default()));
+    let controller = mock.controller();
+    (
+        ChaosProvider::new(Arc::new(Mutex::new(mock)), config),
+        controller,
+    )
+}
+
+#[test]
+fn test_default_config_forwards_calls() {
+    let (provider, controller) = chaos_provider(ChaosConfig::default());
+
+    let signature = provider.sign_data(b"data").unwrap();
+    assert!(provider.verify_signature(b"data", &signature).unwrap());
+    assert_eq!(controller.calls(ProviderOperation::SignData), 1);
+    assert_eq!(
+        provider.stats(),
+        ChaosStats {
+            calls: 2,
+            ..Default::default()
+        }
+    );
+}
+
+#[test]
+fn test_failures_do_not_reach_provider() {
+    let (mut provider, controller) = chaos_provider(ChaosConfig {
+        failure_rate: 1.0,
+        ..Default::default()
+    });
+
+    assert!(matches!(
+        provider.sign_data(b"data"),
+        Err(SecurityModuleError::SigningError(message)) if message == "Injected failure"
+    ));
+    assert!(matches!(
+        provider.verify_many(&[]),
+        Err(SecurityModuleError::SignatureVerificationError(_))
+    ));
+    assert!(matches!(
+        provider.load_key("test_key", Box::new(MockConfig::default())),
+        Err(SecurityModuleError::InitializationError(_))
+    ));
+    assert_eq!(controller.calls(ProviderOperation::SignData), 0);
+    assert_eq!(controller.calls(ProviderOperation::LoadKey), 0);
+    assert_eq!(provider.stats().failures, 3);
+    // Metadata is not an operation of the security module and is never disturbed.
+    assert!(provider.key_metadata().is_ok());
+}
+
+#[test]
+fn test_failure_rate_is_seeded() {
+    let config = ChaosConfig {
+        failure_rate: 0.3,
+        seed: 7,
+        ..Default::default()
+    };
+    let outcomes = || {
+        let (provider, _) = chaos_provider(config);
+        (0..200)
+            .map(|_| provider.sign_data(b"data").is_ok())
+            .collect::<Vec<_>>()
+    };
+
+    let first = outcomes();
+    assert_eq!(first, outcomes());
+    let failures = first.iter().filter(|ok| !**ok).count();
+    assert!((30..90).contains(&failures), "{failures} failures");
+}
+
+#[test]
+fn test_delays_and_reorders() {
+    let max_delay = Duration::from_millis(5);
+    let (provider, _) = chaos_provider(ChaosConfig {
+        delay_rate: 1.0,
+        reorder_rate: 1.0,
+        max_delay,
+        ..Default::default()
+    });
+
+    let start = Instant::now();
+    thread::scope(|scope| {
+        for _ in 0..4 {
+            scope.spawn(|| {
+                for _ in 0..5 {
+                    let signature = provider.sign_data(b"data").unwrap();
+                    assert!(provider.verify_signature(b"data", &signature).unwrap());
+                }
+            });
+        }
+    });
+
+    let stats = provider.stats();
+    assert_eq!(stats.calls, 40);
+    assert_eq!(stats.delays, 40);
+    assert_eq!(stats.reorders, 40);
+    assert_eq!(stats.failures, 0);
+    // Every thread makes ten calls, each delayed and held for at most `max_delay`. The bound is
+    // generous to leave room for slow machines.
+    assert!(start.elapsed() < max_delay * 2 * 10 * 4);
+}
+
+#[test]
+fn test_rates_are_clamped() {
+    let (provider, _) = chaos_provider(ChaosConfig {
+        failure_rate: 2.0,
+        delay_rate: -1.0,
+        ..Default::default()
+    });
+
+    assert_eq!(provider.config().failure_rate, 1.0);
+    assert_eq!(provider.config().delay_rate, 0.0);
+    assert!(provider.sign_data(b"data").is_err());
+}
@@ -0,0 +1,375 @@
+mod chaos;
+
+use crate::{
+    common::{
+        crypto::algorithms::{
+            encryption::{AsymmetricEncryption, EccCurves, EccSchemeAlgorithm},
+            hashes::{Hash, Sha2Bits},
+            KeyBits,
+        },
+        latency::ProviderOperation,
+        traits::{
+            async_key_handle::AsyncKeyHandle, key_handle::KeyHandle, module_provider::Provider,
+        },
+    },
+    mock::{MockConfig, MockProvider},
+    provider_conformance, SecurityModuleError,
+};
+use futures::executor::block_on;
+use openssl::{
+    ec::{EcGroup, EcKey},
+    nid::Nid,
+    pkey::PKey,
+};
+use proptest::{collection::vec, prelude::*};
+use std::{
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
+
+fn provider_with_key(key_algorithm: AsymmetricEncryption) -> MockProvider {
+    MockProvider::with_key(
+        "test_key",
+        MockConfig::new(key_algorithm, Hash::Sha2(Sha2Bits::Sha256)),
+    )
+}
+
+fn ecdsa_provider() -> MockProvider {
+    provider_with_key(AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(
+        EccCurves::P256,
+    )))
+}
+
+#[test]
+fn test_sign_and_verify() {
+    let provider = ecdsa_provider();
+    let signature = provider.sign_data(b"data").unwrap();
+
+    assert!(provider.verify_signature(b"data", &signature).unwrap());
+    assert!(!provider.verify_signature(b"other", &signature).unwrap());
+    assert_eq!(
+        provider
+            .verify_many(&[(b"data", &signature), (b"other", &signature)])
+            .unwrap(),
+        vec![true, false]
+    );
+}
+
+#[test]
+fn test_conformance_ecdsa() {
+    provider_conformance::run_all(&ecdsa_provider());
+}
+
+#[test]
+fn test_conformance_rsa() {
+    provider_conformance::run_all(&provider_with_key(AsymmetricEncryption::Rsa(
+        KeyBits::Bits2048,
+    )));
+}
+
+#[test]
+#[should_panic(expected = "sign_verify_round_trip: verify_signature failed")]
+fn test_conformance_detects_violations() {
+    let provider = ecdsa_provider();
+    provider
+        .controller()
+        .fail(ProviderOperation::VerifySignature, || {
+            SecurityModuleError::VerificationFailed
+        });
+    provider_conformance::sign_verify_round_trip(&provider);
+}
+
+#[test]
+fn test_encrypt_and_decrypt_rsa() {
+    let provider = provider_with_key(AsymmetricEncryption::Rsa(KeyBits::Bits2048));
+    let encrypted = provider.encrypt_data(b"secret").unwrap();
+
+    assert_ne!(encrypted, b"secret");
+    assert_eq!(provider.decrypt_data(&encrypted).unwrap(), b"secret");
+}
+
+#[test]
+fn test_encrypt_ecdsa_is_unsupported() {
+    let provider = ecdsa_provider();
+    assert!(matches!(
+        provider.encrypt_data(b"secret"),
+        Err(SecurityModuleError::UnsupportedAlgorithm)
+    ));
+}
+
+#[test]
+fn test_load_key() {
+    let mut provider = ecdsa_provider();
+    let signature = provider.sign_data(b"data").unwrap();
+    provider
+        .create_key("other_key", Box::new(MockConfig::default()))
+        .unwrap();
+
+    provider
+        .load_key("test_key", Box::new(MockConfig::default()))
+        .unwrap();
+    assert!(provider.verify_signature(b"data", &signature).unwrap());
+    assert_eq!(provider.key_metadata().unwrap().key_id(), "test_key");
+    assert!(matches!(
+        provider.load_key("missing", Box::new(MockConfig::default())),
+        Err(SecurityModuleError::KeyError)
+    ));
+}
+
+#[test]
+fn test_delete_key() {
+    let mut provider = ecdsa_provider();
+    provider
+        .create_key("other_key", Box::new(MockConfig::default()))
+        .unwrap();
+
+    provider.delete_key("test_key").unwrap();
+    assert!(provider.sign_data(b"data").is_ok());
+    provider.delete_key("other_key").unwrap();
+
+    assert!(provider.sign_data(b"data").is_err());
+    assert!(provider.list_keys().unwrap().is_empty());
+    assert!(matches!(
+        provider.delete_key("other_key"),
+        Err(SecurityModuleError::KeyError)
+    ));
+}
+
+#[test]
+fn test_import_key() {
+    let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+    let pem = EcKey::generate(&group)
+        .unwrap()
+        .private_key_to_pem()
+        .unwrap();
+    let mut provider = MockProvider::new("test_key".to_owned());
+    provider.initialize_module().unwrap();
+
+    provider
+        .import_key("imported", MockConfig::default(), &pem)
+        .unwrap();
+    let signature = provider.sign_data(b"data").unwrap();
+    assert!(provider.verify_signature(b"data", &signature).unwrap());
+    assert_eq!(provider.key_metadata().unwrap().key_id(), "imported");
+
+    let p384 = MockConfig {
+        key_algorithm: AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(EccCurves::P384)),
+        hash: Hash::Sha2(Sha2Bits::Sha384),
+        exportable: false,
+    };
+    assert!(matches!(
+        provider.import_key("imported", p384, &pem),
+        Err(SecurityModuleError::KeyError)
+    ));
+    assert!(matches!(
+        provider.import_key("imported", MockConfig::default(), b"not a key"),
+        Err(SecurityModuleError::KeyError)
+    ));
+}
+
+#[test]
+fn test_export_private_key() {
+    let mut provider = ecdsa_provider();
+    assert!(!provider.key_metadata().unwrap().exportable());
+    assert!(matches!(
+        provider.export_private_key(),
+        Err(SecurityModuleError::UnsupportedOperation(_))
+    ));
+
+    let config = MockConfig {
+        exportable: true,
+        ..MockConfig::default()
+    };
+    provider.create_key("exportable", Box::new(config)).unwrap();
+    assert!(provider.key_metadata().unwrap().exportable());
+    let pkcs8_der = provider.export_private_key().unwrap();
+    let private_key = PKey::private_key_from_pkcs8(&pkcs8_der).unwrap();
+    assert_eq!(
+        private_key.public_key_to_der().unwrap(),
+        provider.key_metadata().unwrap().public_key_der()
+    );
+}
+
+#[test]
+fn test_requires_initialization() {
+    let mut provider = MockProvider::new("test_key".to_owned());
+    assert!(matches!(
+        provider.create_key("test_key", Box::new(MockConfig::default())),
+        Err(SecurityModuleError::InitializationError(_))
+    ));
+    assert!(provider.sign_data(b"data").is_err());
+}
+
+#[test]
+fn test_shutdown() {
+    let mut provider = ecdsa_provider();
+
+    provider.shutdown().unwrap();
+    provider.shutdown().unwrap();
+    assert!(provider.sign_data(b"data").is_err());
+    assert!(matches!(
+        provider.load_key("test_key", Box::new(MockConfig::default())),
+        Err(SecurityModuleError::InitializationError(_))
+    ));
+
+    provider.initialize_module().unwrap();
+    provider
+        .load_key("test_key", Box::new(MockConfig::default()))
+        .unwrap();
+    assert!(provider.sign_data(b"data").is_ok());
+}
+
+#[test]
+fn test_async_key_handle() {
+    let provider = provider_with_key(AsymmetricEncryption::Rsa(KeyBits::Bits2048));
+    let signature = block_on(provider.sign_data_async(b"data")).unwrap();
+    assert!(provider.verify_signature(b"data", &signature).unwrap());
+
+    let ciphertext = provider.encrypt_data(b"secret").unwrap();
+    assert_eq!(
+        block_on(provider.decrypt_data_async(&ciphertext)).unwrap(),
+        b"secret"
+    );
+}
+
+#[test]
+fn test_fail() {
+    let provider = ecdsa_provider();
+    let controller = provider.controller();
+    controller.fail(ProviderOperation::SignData, || {
+        SecurityModuleError::SigningError("injected".to_owned())
+    });
+
+    for _ in 0..3 {
+        assert!(matches!(
+            provider.sign_data(b"data"),
+            Err(SecurityModuleError::SigningError(message)) if message == "injected"
+        ));
+    }
+    assert!(provider.encrypt_data(b"data").is_err());
+    assert!(provider.verify_many(&[]).is_ok());
+
+    controller.clear(ProviderOperation::SignData);
+    assert!(provider.sign_data(b"data").is_ok());
+}
+
+#[test]
+fn test_fail_times() {
+    let mut provider = MockProvider::new("test_key".to_owned());
+    let controller = provider.controller();
+    controller.fail_times(ProviderOperation::InitializeModule, 2, || {
+        SecurityModuleError::InitializationError("busy".to_owned())
+    });
+
+    assert!(provider.initialize_module().is_err());
+    assert!(provider.initialize_module().is_err());
+    assert!(provider.initialize_module().is_ok());
+    assert_eq!(controller.calls(ProviderOperation::InitializeModule), 3);
+}
+
+#[test]
+fn test_fail_randomly_is_deterministic() {
+    let provider = ecdsa_provider();
+    let controller = provider.controller();
+    let run = |seed| {
+        controller.seed(seed);
+        controller.fail_randomly(ProviderOperation::SignData, 0.5, || {
+            SecurityModuleError::SigningFailed
+        });
+        (0..64)
+            .map(|_| provider.sign_data(b"data").is_ok())
+            .collect::<Vec<_>>()
+    };
+
+    let first = run(7);
+    assert_eq!(first, run(7));
+    assert!(first.contains(&true) && first.contains(&false));
+}
+
+#[test]
+fn test_set_latency() {
+    let provider = ecdsa_provider();
+    let controller = provider.controller();
+    controller.set_latency(ProviderOperation::SignData, Duration::from_millis(50));
+
+    let start = Instant::now();
+    provider.sign_data(b"data").unwrap();
+    assert!(start.elapsed() >= Duration::from_millis(50));
+}
+
+#[test]
+fn test_reset() {
+    let provider = ecdsa_provider();
+    let controller = provider.controller();
+    controller.fail(ProviderOperation::SignData, || {
+        SecurityModuleError::SigningFailed
+    });
+    assert!(provider.sign_data(b"data").is_err());
+    assert_eq!(controller.calls(ProviderOperation::SignData), 1);
+
+    controller.reset();
+    assert_eq!(controller.calls(ProviderOperation::SignData), 0);
+    assert!(provider.sign_data(b"data").is_ok());
+}
+
+/// Providers with one key per supported algorithm and hash combination, created once since
+/// generating RSA keys is slow.
+fn providers() -> &'static [MockProvider] {
+    static PROVIDERS: OnceLock<Vec<MockProvider>> = OnceLock::new();
+    PROVIDERS.get_or_init(|| {
+        [
+            (
+                AsymmetricEncryption::Rsa(KeyBits::Bits2048),
+                Sha2Bits::Sha256,
+            ),
+            (
+                AsymmetricEncryption::Rsa(KeyBits::Bits3072),
+                Sha2Bits::Sha384,
+            ),
+            (
+                AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(EccCurves::P256)),
+                Sha2Bits::Sha256,
+            ),
+            (
+                AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(EccCurves::P384)),
+                Sha2Bits::Sha384,
+            ),
+            (
+                AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(EccCurves::P521)),
+                Sha2Bits::Sha512,
+            ),
+        ]
+        .into_iter()
+        .map(|(key_algorithm, hash)| {
+            MockProvider::with_key("prop_key", MockConfig::new(key_algorithm, Hash::Sha2(hash)))
+        })
+        .collect()
+    })
+}
+
+/// The longest plaintext RSA-OAEP with SHA-1 can encrypt with a 2048 bit key.
+const MAX_OAEP_PLAINTEXT: usize = 256 - 2 * 20 - 2;
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn prop_sign_verify_round_trip(
+        provider in (0..providers().len()).prop_map(|i| &providers()[i]),
+        data in vec(any::<u8>(), 0..4096),
+    ) {
+        let signature = provider.sign_data(&data).unwrap();
+        prop_assert!(provider.verify_signature(&data, &signature).unwrap());
+    }
+
+    #[test]
+    fn prop_encrypt_decrypt_is_identity(
+        provider in (0..providers().len()).prop_map(|i| &providers()[i]),
+        data in vec(any::<u8>(), 0..=MAX_OAEP_PLAINTEXT),
+    ) {
+        match provider.encrypt_data(&data) {
+            Ok(encrypted) => prop_assert_eq!(provider.decrypt_data(&encrypted).unwrap(), data),
+            Err(e) => prop_assert!(matches!(e, SecurityModuleError::UnsupportedAlgorithm)),
+        }
+    }
+}
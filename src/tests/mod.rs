@@ -1,8 +1,39 @@
+mod allocations;
 pub mod common;
 
 #[cfg(feature = "hsm")]
 pub mod hsm;
 
+#[cfg(all(feature = "cli", feature = "test-utils"))]
+mod cli;
+
+#[cfg(all(feature = "daemon", feature = "test-utils"))]
+mod daemon;
+
+#[cfg(all(feature = "ffi", feature = "test-utils"))]
+mod ffi;
+
+#[cfg(all(feature = "iot", feature = "test-utils"))]
+mod iot;
+
+#[cfg(all(feature = "kms-plugin", feature = "test-utils"))]
+mod kms_plugin;
+
+#[cfg(all(feature = "messaging", feature = "test-utils"))]
+mod messaging;
+
+#[cfg(feature = "test-utils")]
+mod mock;
+
+#[cfg(all(feature = "remote", feature = "test-utils"))]
+mod remote;
+
+#[cfg(all(feature = "ssh-agent", feature = "test-utils"))]
+mod ssh_agent;
+
+#[cfg(feature = "test-utils")]
+mod test_vectors;
+
 #[cfg(feature = "tpm")]
 mod tpm;
 
@@ -88,7 +88,7 @@ fn test_encrypt_and_decrypt_rsa() {
     let decrypted_data = provider
         .decrypt_data(&encrypted_data)
         .expect("Failed to decrypt data");
-    assert_eq!(data, decrypted_data.as_slice())
+    assert_eq!(data, &decrypted_data[..])
 }
 
 #[test]
@@ -115,7 +115,7 @@ fn test_encrypt_and_decrypt_ecdh() {
     let decrypted_data = provider
         .decrypt_data(&encrypted_data)
         .expect("Failed to decrypt data");
-    assert_eq!(data, decrypted_data.as_slice())
+    assert_eq!(data, &decrypted_data[..])
 }
 
 #[test]
@@ -164,6 +164,6 @@ fn test_encrypt_and_decrypt_aes(mode: SymmetricMode, key_sizes: &[KeyBits]) {
         let data = b"Hello, World!";
         let encrypted_data = provider.encrypt_data(data).expect("Failed to encrypt data");
         let decrypted_data = provider.decrypt_data(&encrypted_data).expect("Failed to decrypt data");
-        assert_eq!(data, decrypted_data.as_slice())
+        assert_eq!(data, &decrypted_data[..])
     }
 }
@@ -0,0 +1,337 @@
+use crate::{
+    common::{
+        crypto::key_spec::{KeyAlgorithm, KeyPurpose, KeySpec},
+        latency::ProviderOperation,
+        traits::{key_handle::KeyHandle, module_provider::Provider},
+    },
+    mock::{MockConfig, MockProvider},
+    provider_conformance,
+    remote::{RemoteConfig, RemoteProvider, RemoteServer},
+    SecurityModuleError,
+};
+use std::{
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use test_case::test_case;
+use tokio::{net::TcpSocket, runtime::Runtime};
+
+/// A `RemoteServer` in front of a `MockProvider`, serving on a runtime of its own.
+struct TestServer {
+    provider: Arc<Mutex<MockProvider>>,
+    address: SocketAddr,
+    runtime: Option<Runtime>,
+}
+
+impl TestServer {
+    fn start(token: Option<&str>) -> Self {
+        let mut provider = MockProvider::new("remote".to_owned());
+        provider.initialize_module().unwrap();
+        Self::start_at(
+            Arc::new(Mutex::new(provider)),
+            "127.0.0.1:0".parse().unwrap(),
+            token,
+        )
+    }
+
+    fn start_at(
+        provider: Arc<Mutex<MockProvider>>,
+        address: SocketAddr,
+        token: Option<&str>,
+    ) -> Self {
+        let runtime = Runtime::new().unwrap();
+        let mut server = RemoteServer::new(provider.clone(), MockConfig::from_spec);
+        if let Some(token) = token {
+            server = server.with_token(token);
+        }
+        let server = Arc::new(server);
+        let listener = {
+            let _guard = runtime.enter();
+            let socket = TcpSocket::new_v4().unwrap();
+            socket.set_reuseaddr(true).unwrap();
+            socket.bind(address).unwrap();
+            socket.listen(16).unwrap()
+        };
+        let address = listener.local_addr().unwrap();
+        runtime.spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(Arc::clone(&server).serve(stream));
+            }
+        });
+        Self {
+            provider,
+            address,
+            runtime: Some(runtime),
+        }
+    }
+
+    /// Stops the server and starts a new one on the same address, which has forgotten the keys
+    /// its clients created and loaded.
+    fn restart(&mut self, token: Option<&str>) {
+        self.runtime
+            .take()
+            .unwrap()
+            .shutdown_timeout(Duration::from_secs(5));
+        *self = Self::start_at(self.provider.clone(), self.address, token);
+    }
+
+    fn client(&self) -> RemoteProvider {
+        let mut provider = RemoteProvider::new(self.address.to_string());
+        provider.initialize_module().unwrap();
+        provider
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        if let Some(runtime) = self.runtime.take() {
+            runtime.shutdown_background();
+        }
+    }
+}
+
+fn spec(algorithm: KeyAlgorithm, purpose: KeyPurpose) -> KeySpec {
+    KeySpec::builder()
+        .algorithm(algorithm)
+        .usage(purpose)
+        .label("remote")
+        .build()
+        .unwrap()
+}
+
+#[test_case(KeyAlgorithm::EcP256, KeyPurpose::Sign ; "p256")]
+#[test_case(KeyAlgorithm::EcP384, KeyPurpose::Sign ; "p384")]
+#[test_case(KeyAlgorithm::Rsa2048, KeyPurpose::Encrypt ; "rsa")]
+fn test_conformance(algorithm: KeyAlgorithm, purpose: KeyPurpose) {
+    let server = TestServer::start(None);
+    let mut client = server.client();
+    let spec = spec(algorithm, purpose);
+
+    client
+        .create_key("remote_key", RemoteConfig::from_spec(&spec).unwrap())
+        .unwrap();
+
+    provider_conformance::run_all(&client);
+    let local = server.provider.lock().unwrap().key_metadata().unwrap();
+    let metadata = client.key_metadata().unwrap();
+    assert_eq!(metadata.key_id(), "remote_key");
+    assert_eq!(metadata.public_key_der(), local.public_key_der());
+}
+
+#[test]
+fn test_load_key() {
+    let server = TestServer::start(None);
+    let spec = spec(KeyAlgorithm::EcP256, KeyPurpose::Sign);
+    let mut first = server.client();
+    first
+        .create_key("release", RemoteConfig::from_spec(&spec).unwrap())
+        .unwrap();
+    let signature = first.sign_data(b"artifact").unwrap();
+
+    let mut second = server.client();
+    second
+        .load_key("release", RemoteConfig::from_spec(&spec).unwrap())
+        .unwrap();
+
+    assert!(second.verify_signature(b"artifact", &signature).unwrap());
+    assert_eq!(
+        second.key_metadata().unwrap().public_key_der(),
+        first.key_metadata().unwrap().public_key_der()
+    );
+}
+
+#[test]
+fn test_key_switching() {
+    let server = TestServer::start(None);
+    let spec = spec(KeyAlgorithm::EcP256, KeyPurpose::Sign);
+    let mut first = server.client();
+    let mut second = server.client();
+    first
+        .create_key("first", RemoteConfig::from_spec(&spec).unwrap())
+        .unwrap();
+    second
+        .create_key("second", RemoteConfig::from_spec(&spec).unwrap())
+        .unwrap();
+
+    // Every call uses the key of its client, although the server has a single provider.
+    for _ in 0..3 {
+        let first_signature = first.sign_data(b"data").unwrap();
+        let second_signature = second.sign_data(b"data").unwrap();
+        assert!(first.verify_signature(b"data", &first_signature).unwrap());
+        assert!(!first.verify_signature(b"data", &second_signature).unwrap());
+        assert!(second.verify_signature(b"data", &second_signature).unwrap());
+    }
+}
+
+#[test]
+fn test_errors() {
+    let server = TestServer::start(None);
+    let mut client = server.client();
+    let spec = spec(KeyAlgorithm::EcP256, KeyPurpose::Sign);
+
+    assert!(matches!(
+        client.sign_data(b"data"),
+        Err(SecurityModuleError::KeyError)
+    ));
+    assert!(matches!(
+        client.load_key("missing", RemoteConfig::from_spec(&spec).unwrap()),
+        Err(SecurityModuleError::KeyError)
+    ));
+    // The mock provider has no symmetric keys.
+    let aes = KeySpec::builder()
+        .algorithm(KeyAlgorithm::Aes256Gcm)
+        .usage(KeyPurpose::Encrypt)
+        .label("aes")
+        .build()
+        .unwrap();
+    assert!(matches!(
+        client.create_key("aes", RemoteConfig::from_spec(&aes).unwrap()),
+        Err(SecurityModuleError::UnsupportedAlgorithm)
+    ));
+    assert!(matches!(
+        client.create_key("remote_key", Box::new(MockConfig::default())),
+        Err(SecurityModuleError::InitializationError(_))
+    ));
+
+    client
+        .create_key("remote_key", RemoteConfig::from_spec(&spec).unwrap())
+        .unwrap();
+    let controller = server.provider.lock().unwrap().controller();
+    controller.fail_times(ProviderOperation::SignData, 1, || {
+        SecurityModuleError::SigningError("The key is locked".to_owned())
+    });
+    match client.sign_data(b"data") {
+        Err(SecurityModuleError::SigningError(message)) => {
+            assert_eq!(message, "The key is locked")
+        }
+        result => panic!("unexpected result {:?}", result),
+    }
+    controller.fail_times(ProviderOperation::SignData, 1, || {
+        SecurityModuleError::SessionPoolTimeout
+    });
+    assert!(matches!(
+        client.sign_data(b"data"),
+        Err(SecurityModuleError::SessionPoolTimeout)
+    ));
+    assert!(client.sign_data(b"data").is_ok());
+
+    // A failed load keeps the current key.
+    assert!(matches!(
+        client.load_key("missing", RemoteConfig::from_spec(&spec).unwrap()),
+        Err(SecurityModuleError::KeyError)
+    ));
+    assert_eq!(client.key_metadata().unwrap().key_id(), "remote_key");
+    assert!(client.sign_data(b"data").is_ok());
+
+    let mut uninitialized = RemoteProvider::new(server.address.to_string());
+    assert!(matches!(
+        uninitialized.create_key("remote_key", RemoteConfig::from_spec(&spec).unwrap()),
+        Err(SecurityModuleError::InitializationError(_))
+    ));
+}
+
+#[test]
+fn test_unreachable() {
+    let address = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+
+    let mut client = RemoteProvider::new(address.to_string());
+
+    assert!(matches!(
+        client.initialize_module(),
+        Err(SecurityModuleError::InitializationError(_))
+    ));
+}
+
+#[test]
+fn test_token() {
+    let server = TestServer::start(Some("secret"));
+    let spec = spec(KeyAlgorithm::EcP256, KeyPurpose::Sign);
+
+    for mut client in [
+        RemoteProvider::new(server.address.to_string()),
+        RemoteProvider::new(server.address.to_string()).with_token("wrong"),
+    ] {
+        client.initialize_module().unwrap();
+        assert!(matches!(
+            client.create_key("remote_key", RemoteConfig::from_spec(&spec).unwrap()),
+            Err(SecurityModuleError::AuthenticationFailed(_))
+        ));
+    }
+
+    let mut client = RemoteProvider::new(server.address.to_string()).with_token("secret");
+    client.initialize_module().unwrap();
+    client
+        .create_key("remote_key", RemoteConfig::from_spec(&spec).unwrap())
+        .unwrap();
+    assert!(client.sign_data(b"data").is_ok());
+}
+
+#[test]
+fn test_server_restart() {
+    let mut server = TestServer::start(None);
+    let mut client = server.client();
+    let spec = spec(KeyAlgorithm::EcP256, KeyPurpose::Sign);
+    client
+        .create_key("remote_key", RemoteConfig::from_spec(&spec).unwrap())
+        .unwrap();
+    let signature = client.sign_data(b"before").unwrap();
+
+    server.restart(None);
+
+    // The client reconnects and loads its key again.
+    let after = client.sign_data(b"after").unwrap();
+    assert!(client.verify_signature(b"before", &signature).unwrap());
+    assert!(client.verify_signature(b"after", &after).unwrap());
+}
+
+#[tokio::test]
+async fn test_within_runtime() {
+    let server = TestServer::start(None);
+    let mut client = server.client();
+    let spec = spec(KeyAlgorithm::EcP256, KeyPurpose::Sign);
+
+    client
+        .create_key("remote_key", RemoteConfig::from_spec(&spec).unwrap())
+        .unwrap();
+
+    let signature = client.sign_data(b"data").unwrap();
+    assert!(client.verify_signature(b"data", &signature).unwrap());
+    drop(client);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_unix_socket() {
+    let mut provider = MockProvider::new("remote".to_owned());
+    provider.initialize_module().unwrap();
+    let server = Arc::new(RemoteServer::new(
+        Arc::new(Mutex::new(provider)),
+        MockConfig::from_spec,
+    ));
+    let path =
+        std::env::temp_dir().join(format!("crypto-layer-remote-{}.sock", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let runtime = Runtime::new().unwrap();
+    runtime.spawn(server.listen_unix(path.clone()));
+    while !path.exists() {
+        std::thread::yield_now();
+    }
+
+    let mut client = RemoteProvider::new(format!("unix:{}", path.display()));
+    client.initialize_module().unwrap();
+    client
+        .create_key(
+            "remote_key",
+            RemoteConfig::from_spec(&spec(KeyAlgorithm::EcP256, KeyPurpose::Sign)).unwrap(),
+        )
+        .unwrap();
+    assert!(client.sign_data(b"data").is_ok());
+
+    drop(client);
+    runtime.shutdown_background();
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,189 @@
+use crate::{
+    common::{
+        crypto::{
+            algorithms::{
+                encryption::{AsymmetricEncryption, EccCurves, EccSchemeAlgorithm},
+                hashes::{Hash, Sha2Bits},
+                KeyBits,
+            },
+            public_key::PublicKey,
+            signature_format::SignatureFormat,
+        },
+        traits::module_provider::Provider,
+    },
+    mock::{MockConfig, MockProvider},
+    ssh_agent::SshAgent,
+};
+use base64::{prelude::BASE64_STANDARD, Engine};
+use std::{
+    io::{Read, Write},
+    sync::{Arc, Mutex},
+};
+
+fn provider(key_id: &str, algorithm: AsymmetricEncryption) -> Arc<Mutex<MockProvider>> {
+    Arc::new(Mutex::new(MockProvider::with_key(
+        key_id,
+        MockConfig::new(algorithm, Hash::Sha2(Sha2Bits::Sha256)),
+    )))
+}
+
+fn public_key(provider: &Mutex<MockProvider>) -> PublicKey {
+    provider
+        .lock()
+        .unwrap()
+        .key_metadata()
+        .unwrap()
+        .public_key()
+        .clone()
+}
+
+fn agent() -> (SshAgent, PublicKey, PublicKey) {
+    let ec = provider(
+        "ec_key",
+        AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(EccCurves::P256)),
+    );
+    let rsa = provider("rsa_key", AsymmetricEncryption::Rsa(KeyBits::Bits2048));
+    let (ec_public_key, rsa_public_key) = (public_key(&ec), public_key(&rsa));
+
+    let mut agent = SshAgent::new();
+    agent.add_key(ec, "ec@device").unwrap();
+    agent.add_key(rsa, "rsa@device").unwrap();
+    (agent, ec_public_key, rsa_public_key)
+}
+
+fn string(bytes: &[u8]) -> Vec<u8> {
+    [&(bytes.len() as u32).to_be_bytes()[..], bytes].concat()
+}
+
+fn take_string<'a>(buffer: &mut &'a [u8]) -> &'a [u8] {
+    let len = u32::from_be_bytes(buffer[..4].try_into().unwrap()) as usize;
+    let (field, rest) = buffer[4..].split_at(len);
+    *buffer = rest;
+    field
+}
+
+fn sign_request(blob: &[u8], data: &[u8], flags: u32) -> Vec<u8> {
+    [
+        &[13][..],
+        &string(blob),
+        &string(data),
+        &flags.to_be_bytes(),
+    ]
+    .concat()
+}
+
+fn blob(agent: &SshAgent, index: usize) -> Vec<u8> {
+    let public_key = &agent.public_keys()[index];
+    BASE64_STANDARD
+        .decode(public_key.split(' ').nth(1).unwrap())
+        .unwrap()
+}
+
+#[test]
+fn test_request_identities() {
+    let (agent, ec_public_key, _) = agent();
+    let public_keys = agent.public_keys();
+    assert!(public_keys[0].starts_with("ecdsa-sha2-nistp256 AAAAE2VjZHNhLXNoYTItbmlzdHAyNTY"));
+    assert!(public_keys[0].ends_with(" ec@device"));
+    assert!(public_keys[1].starts_with("ssh-rsa AAAAB3NzaC1yc2E"));
+
+    let response = agent.handle_message(&[11]);
+    assert_eq!(response[0], 12);
+    assert_eq!(u32::from_be_bytes(response[1..5].try_into().unwrap()), 2);
+    let mut body = &response[5..];
+    let ec_blob = take_string(&mut body);
+    assert_eq!(ec_blob, blob(&agent, 0));
+    assert_eq!(take_string(&mut body), b"ec@device");
+
+    let mut fields = ec_blob;
+    assert_eq!(take_string(&mut fields), b"ecdsa-sha2-nistp256");
+    assert_eq!(take_string(&mut fields), b"nistp256");
+    assert_eq!(
+        take_string(&mut fields),
+        ec_public_key.to_ec_point().unwrap()
+    );
+}
+
+#[test]
+fn test_sign_request() {
+    let (agent, ec_public_key, rsa_public_key) = agent();
+
+    let response = agent.handle_message(&sign_request(&blob(&agent, 0), b"session", 0));
+    assert_eq!(response[0], 14);
+    let mut body = &response[1..];
+    let mut signature = take_string(&mut body);
+    assert_eq!(take_string(&mut signature), b"ecdsa-sha2-nistp256");
+    let mut scalars = take_string(&mut signature);
+    let mut raw = Vec::new();
+    for _ in 0..2 {
+        let scalar = take_string(&mut scalars);
+        let scalar = scalar.strip_prefix(&[0]).unwrap_or(scalar);
+        raw.resize(raw.len() + 32 - scalar.len(), 0);
+        raw.extend_from_slice(scalar);
+    }
+    assert!(ec_public_key
+        .verify_with_format(b"session", &raw, SignatureFormat::Raw)
+        .unwrap());
+
+    // RSA keys only sign with the hash they were created with.
+    let response = agent.handle_message(&sign_request(&blob(&agent, 1), b"session", 2));
+    assert_eq!(response[0], 14);
+    let mut body = &response[1..];
+    let mut signature = take_string(&mut body);
+    assert_eq!(take_string(&mut signature), b"rsa-sha2-256");
+    assert!(rsa_public_key
+        .verify(b"session", take_string(&mut signature))
+        .unwrap());
+    for flags in [0, 4] {
+        assert_eq!(
+            agent.handle_message(&sign_request(&blob(&agent, 1), b"session", flags)),
+            [5]
+        );
+    }
+}
+
+#[test]
+fn test_unsupported_requests() {
+    let (agent, _, _) = agent();
+    let mut unknown_blob = blob(&agent, 0);
+    *unknown_blob.last_mut().unwrap() ^= 1;
+
+    for request in [
+        sign_request(&unknown_blob, b"session", 0),
+        sign_request(&blob(&agent, 0), b"session", 0)[..20].to_vec(),
+        [&sign_request(&blob(&agent, 0), b"session", 0)[..], &[0]].concat(),
+        // Adding identities and locking are managed by the application.
+        vec![17],
+        vec![22],
+        vec![11, 0],
+        vec![],
+    ] {
+        assert_eq!(agent.handle_message(&request), [5], "{:?}", request);
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_serve() {
+    use std::os::unix::net::UnixStream;
+
+    let (agent, _, _) = agent();
+    let (mut client, mut server) = UnixStream::pair().unwrap();
+    let expected = agent.handle_message(&[11]);
+    let handle = std::thread::spawn(move || agent.serve(&mut server));
+
+    for _ in 0..2 {
+        client.write_all(&string(&[11])).unwrap();
+        let mut len = [0; 4];
+        client.read_exact(&mut len).unwrap();
+        let mut response = vec![0; u32::from_be_bytes(len) as usize];
+        client.read_exact(&mut response).unwrap();
+        assert_eq!(response, expected);
+    }
+    drop(client);
+    handle.join().unwrap().unwrap();
+
+    let (mut client, mut server) = UnixStream::pair().unwrap();
+    client.write_all(&u32::MAX.to_be_bytes()).unwrap();
+    assert!(SshAgent::new().serve(&mut server).is_err());
+}
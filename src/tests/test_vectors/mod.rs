@@ -0,0 +1,77 @@
+use crate::{
+    test_vectors::{self, TestVectors, FORMAT_VERSION},
+    SecurityModuleError,
+};
+
+fn fixture() -> TestVectors {
+    TestVectors::from_json(include_str!("vectors.json")).unwrap()
+}
+
+#[test]
+fn test_fixture_is_valid() {
+    test_vectors::check(&fixture()).unwrap();
+}
+
+#[test]
+fn test_generated_vectors_are_valid() {
+    let vectors = test_vectors::generate().unwrap();
+    test_vectors::check(&vectors).unwrap();
+    assert_eq!(
+        TestVectors::from_json(&vectors.to_json().unwrap()).unwrap(),
+        vectors
+    );
+}
+
+#[test]
+fn test_generate_reproduces_fixture() {
+    let fixture = fixture();
+    let generated = test_vectors::generate().unwrap();
+
+    assert_eq!(generated.keys, fixture.keys);
+    assert_eq!(generated.envelopes, fixture.envelopes);
+    assert_eq!(generated.signatures.len(), fixture.signatures.len());
+    assert_eq!(generated.ciphertexts.len(), fixture.ciphertexts.len());
+    for (generated, fixture) in generated.signatures.iter().zip(&fixture.signatures) {
+        assert_eq!(generated.message, fixture.message);
+        if fixture.deterministic {
+            assert_eq!(generated.signature, fixture.signature);
+        }
+    }
+}
+
+#[test]
+fn test_check_detects_modifications() {
+    let mut vectors = fixture();
+    vectors.signatures[1].message.push(0);
+    assert!(matches!(
+        test_vectors::check(&vectors),
+        Err(SecurityModuleError::SignatureVerificationError(_))
+    ));
+
+    let mut vectors = fixture();
+    let envelope = vectors.envelopes.last_mut().unwrap();
+    let last = envelope.envelope.len() - 1;
+    envelope.envelope[last] ^= 0x01;
+    assert!(test_vectors::check(&vectors).is_err());
+
+    let mut vectors = fixture();
+    let public_key_der = vectors.keys[0].public_key_der.clone();
+    vectors.keys[1].public_key_der = public_key_der;
+    assert!(matches!(
+        test_vectors::check(&vectors),
+        Err(SecurityModuleError::InitializationError(_))
+    ));
+}
+
+#[test]
+fn test_unsupported_version() {
+    let json = include_str!("vectors.json").replacen(
+        &format!("\"version\": {FORMAT_VERSION}"),
+        "\"version\": 999",
+        1,
+    );
+    assert!(matches!(
+        TestVectors::from_json(&json),
+        Err(SecurityModuleError::InitializationError(_))
+    ));
+}
@@ -14,7 +14,20 @@ use crate::{
         },
         traits::module_provider::Provider,
     },
-    tpm::linux::TpmProvider,
+    tpm::linux::{public_key_from_tpm, TpmProvider},
+};
+use openssl::{
+    bn::{BigNum, BigNumContext},
+    ec::{EcGroup, EcKey},
+    nid::Nid,
+};
+use tss_esapi::{
+    attributes::ObjectAttributesBuilder,
+    interface_types::{algorithm::HashingAlgorithm, ecc::EccCurve},
+    structures::{
+        Digest, EccParameter, EccPoint, EccScheme, HashScheme, KeyDerivationFunctionScheme, Public,
+        PublicEccParameters, SymmetricDefinitionObject,
+    },
 };
 
 #[test]
@@ -150,3 +163,76 @@ fn test_load_ecdh_key() {
         .load_key("test_ecdh_key", config)
         .expect("Failed to load ECDH key");
 }
+
+#[test]
+fn test_create_key_caches_metadata() {
+    let mut provider = TpmProvider::new("test_key".to_string());
+
+    let config = TpmConfig::new(
+        AsymmetricEncryption::Rsa(KeyBits::Bits2048),
+        BlockCiphers::Aes(SymmetricMode::Gcm, KeyBits::Bits512),
+        Hash::Sha2(Sha2Bits::Sha256),
+        vec![KeyUsage::SignEncrypt, KeyUsage::Decrypt],
+    );
+
+    provider
+        .initialize_module()
+        .expect("Failed to initialize module");
+    provider
+        .create_key("test_rsa_metadata_key", config)
+        .expect("Failed to create RSA key");
+
+    let metadata = provider
+        .key_metadata()
+        .expect("Failed to get cached key metadata");
+    assert_eq!(metadata.key_id(), "test_rsa_metadata_key");
+    assert!(metadata.attestation().is_none());
+
+    let refreshed = provider
+        .refresh_key_metadata()
+        .expect("Failed to refresh key metadata");
+    assert_eq!(refreshed.public_key_der(), metadata.public_key_der());
+    assert!(refreshed.fetched_at() >= metadata.fetched_at());
+}
+
+#[test]
+fn test_public_key_from_tpm() {
+    let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+    let ec_key = EcKey::generate(&group).unwrap();
+    let (mut x, mut y) = (BigNum::new().unwrap(), BigNum::new().unwrap());
+    ec_key
+        .public_key()
+        .affine_coordinates(&group, &mut x, &mut y, &mut BigNumContext::new().unwrap())
+        .unwrap();
+
+    let public = Public::Ecc {
+        object_attributes: ObjectAttributesBuilder::new()
+            .with_sign_encrypt(true)
+            .build()
+            .unwrap(),
+        name_hashing_algorithm: HashingAlgorithm::Sha256,
+        auth_policy: Digest::default(),
+        parameters: PublicEccParameters::new(
+            SymmetricDefinitionObject::Null,
+            EccScheme::EcDsa(HashScheme::new(HashingAlgorithm::Sha256)),
+            EccCurve::NistP256,
+            KeyDerivationFunctionScheme::Null,
+        ),
+        unique: EccPoint::new(
+            EccParameter::try_from(x.to_vec()).unwrap(),
+            EccParameter::try_from(y.to_vec()).unwrap(),
+        ),
+    };
+
+    let public_key = public_key_from_tpm(
+        &public,
+        AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(EccCurves::P256)),
+        Hash::Sha2(Sha2Bits::Sha256),
+    )
+    .unwrap();
+
+    assert_eq!(
+        public_key.to_der().unwrap(),
+        ec_key.public_key_to_der().unwrap()
+    );
+}
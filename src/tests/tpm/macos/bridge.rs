@@ -0,0 +1,592 @@
+use crate::{
+    common::{
+        crypto::{
+            algorithms::{
+                encryption::{AsymmetricEncryption, EccCurves, EccSchemeAlgorithm},
+                hashes::{Hash, Sha2Bits},
+            },
+            key_spec::{AccessControl, KeyAlgorithm, KeyPurpose, KeySpec},
+            operation_context::OperationContext,
+        },
+        telemetry::CorrelationId,
+        traits::{
+            async_key_handle::AsyncKeyHandle, key_handle::KeyHandle, module_provider::Provider,
+        },
+    },
+    tpm::macos::{
+        bridge::{Bridge, Cassette, Exchange, Request},
+        response::{SwiftError, SwiftErrorCode, SECURE_ENCLAVE_DOMAIN},
+        SecureEnclaveConfig, SecureEnclaveProvider,
+    },
+    SecurityModuleError,
+};
+use base64::{prelude::BASE64_STANDARD, Engine};
+use futures::executor::block_on;
+use openssl::{
+    bn::BigNumContext,
+    ec::{EcGroup, EcKey, PointConversionForm},
+    hash::MessageDigest,
+    nid::Nid,
+    pkey::{PKey, Private},
+    sign::Signer,
+};
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+};
+use tracing::{
+    field::{Field, Visit},
+    span, Subscriber,
+};
+use tracing_subscriber::{layer::Context, prelude::*, Layer};
+
+const KEY_ID: &str = "replay_key";
+
+fn config() -> SecureEnclaveConfig {
+    SecureEnclaveConfig::new(
+        Some(AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(
+            EccCurves::P256,
+        ))),
+        Some(Hash::Sha2(Sha2Bits::Sha256)),
+    )
+}
+
+fn exchange(request: Request, failed: bool, result: impl Into<String>) -> Exchange {
+    let result = result.into();
+    let response = if failed {
+        Err(SwiftError::new(
+            SwiftErrorCode::Runtime.code(),
+            SECURE_ENCLAVE_DOMAIN,
+            result,
+        ))
+    } else {
+        Ok(result)
+    };
+    Exchange::new(request, response, None)
+}
+
+fn version_exchange() -> Exchange {
+    exchange(
+        Request::BridgeVersion,
+        false,
+        "3;3;access_control,async,sha512,shutdown",
+    )
+}
+
+fn sign_request(data: &[u8]) -> Request {
+    Request::SignData {
+        key_id: KEY_ID.to_owned(),
+        data: data.to_vec(),
+        algorithm: "ECDSA".to_owned(),
+        hash: "SHA256".to_owned(),
+    }
+}
+
+/// The exchanges of initializing the module and creating a P-256 key, answered with `key`.
+fn create_key_exchanges(key: &PKey<Private>) -> Vec<Exchange> {
+    let ec_key = key.ec_key().unwrap();
+    let mut ctx = BigNumContext::new().unwrap();
+    let point = ec_key
+        .public_key()
+        .to_bytes(ec_key.group(), PointConversionForm::UNCOMPRESSED, &mut ctx)
+        .unwrap();
+
+    vec![
+        version_exchange(),
+        exchange(Request::InitializeModule, false, ""),
+        exchange(
+            Request::CreateKey {
+                key_id: KEY_ID.to_owned(),
+                key_type: "ECDSA;256".to_owned(),
+            },
+            false,
+            "",
+        ),
+        exchange(
+            Request::GetPublicKey {
+                key_id: KEY_ID.to_owned(),
+                algorithm: "ECDSA".to_owned(),
+            },
+            false,
+            BASE64_STANDARD.encode(point),
+        ),
+    ]
+}
+
+fn p256_key() -> PKey<Private> {
+    let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+    PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap()
+}
+
+fn replay_provider(exchanges: Vec<Exchange>) -> (SecureEnclaveProvider, Bridge) {
+    let bridge = Bridge::replay(Cassette { exchanges });
+    let provider = SecureEnclaveProvider::with_bridge(KEY_ID.to_owned(), bridge.clone());
+    (provider, bridge)
+}
+
+#[test]
+fn test_cassette_save_load() {
+    let cassette = Cassette {
+        exchanges: vec![
+            exchange(sign_request(&[0x00, 0xff, 0x0a]), false, "c2lnbmF0dXJl"),
+            exchange(Request::InitializeModule, true, ""),
+        ],
+    };
+    let path = std::env::temp_dir().join(format!("cassette_{}.json", std::process::id()));
+
+    cassette.save(&path).unwrap();
+    let json = std::fs::read_to_string(&path).unwrap();
+    assert!(json.contains("\"call\": \"sign_data\""));
+    assert!(json.contains("\"data\": \"AP8K\""));
+    assert_eq!(Cassette::load(&path).unwrap(), cassette);
+
+    std::fs::remove_file(&path).unwrap();
+    assert!(matches!(
+        Cassette::load(&path),
+        Err(SecurityModuleError::InitializationError(_))
+    ));
+}
+
+#[test]
+fn test_replay_sign_verify() {
+    let key = p256_key();
+    let data = b"Hello, World!";
+    let mut signer = Signer::new(MessageDigest::sha256(), &key).unwrap();
+    let signature = BASE64_STANDARD.encode(signer.sign_oneshot_to_vec(data).unwrap());
+
+    let mut exchanges = create_key_exchanges(&key);
+    exchanges.push(exchange(sign_request(data), false, signature.clone()));
+    exchanges.push(exchange(
+        Request::VerifySignature {
+            key_id: KEY_ID.to_owned(),
+            data: data.to_vec(),
+            signature: signature.clone().into_bytes(),
+            algorithm: "ECDSA".to_owned(),
+            hash: "SHA256".to_owned(),
+        },
+        false,
+        "true",
+    ));
+    let (mut provider, bridge) = replay_provider(exchanges);
+
+    provider.initialize_module().unwrap();
+    provider.create_key(KEY_ID, Box::new(config())).unwrap();
+    assert_eq!(
+        provider
+            .key_metadata()
+            .unwrap()
+            .public_key()
+            .to_der()
+            .unwrap(),
+        key.public_key_to_der().unwrap()
+    );
+
+    let signed = provider.sign_data(data).unwrap();
+    assert_eq!(signed, signature.as_bytes());
+    assert!(provider.verify_signature(data, &signed).unwrap());
+    // The replayed signature is real, so it also verifies with the exported public key.
+    let mut context = OperationContext::new();
+    assert!(provider
+        .verify_signature_with(data, &signed, &mut context)
+        .unwrap());
+    assert!(!provider
+        .verify_signature_with(b"Hello, World?", &signed, &mut context)
+        .unwrap());
+
+    assert!(bridge.cassette().unwrap().exchanges.is_empty());
+}
+
+#[test]
+fn test_replay_async_operations() {
+    let data = b"Hello, World!";
+    let plaintext = [0x00, 0xff, 0x00, 0x80];
+    let mut exchanges = create_key_exchanges(&p256_key());
+    exchanges.push(exchange(sign_request(data), false, "c2lnbmF0dXJl"));
+    exchanges.push(exchange(
+        Request::DecryptData {
+            key_id: KEY_ID.to_owned(),
+            data: b"ciphertext".to_vec(),
+            algorithm: "ECDSA".to_owned(),
+            hash: "SHA256".to_owned(),
+        },
+        false,
+        BASE64_STANDARD.encode(plaintext),
+    ));
+    let (mut provider, bridge) = replay_provider(exchanges);
+    let fields = CapturedFields::default();
+    let subscriber = tracing_subscriber::registry().with(fields.clone());
+
+    tracing::subscriber::with_default(subscriber, || {
+        provider.initialize_module().unwrap();
+        provider.create_key(KEY_ID, Box::new(config())).unwrap();
+        assert_eq!(
+            block_on(provider.sign_data_async(data)).unwrap(),
+            b"c2lnbmF0dXJl"
+        );
+        assert_eq!(
+            block_on(provider.decrypt_data_async(b"ciphertext")).unwrap(),
+            plaintext
+        );
+    });
+
+    assert!(bridge.cassette().unwrap().exchanges.is_empty());
+    assert!(fields.get("span").contains(&"sign_data_async".to_owned()));
+    assert!(fields
+        .get("span")
+        .contains(&"decrypt_data_async".to_owned()));
+    assert_eq!(fields.get("rpc.method")[4..], ["sign_data", "decrypt_data"]);
+}
+
+#[test]
+fn test_replay_decrypt_binary_data() {
+    let plaintext = [0x00, 0xff, 0x00, 0x80];
+    let mut exchanges = create_key_exchanges(&p256_key());
+    exchanges.push(exchange(
+        Request::DecryptData {
+            key_id: KEY_ID.to_owned(),
+            data: b"ciphertext".to_vec(),
+            algorithm: "ECDSA".to_owned(),
+            hash: "SHA256".to_owned(),
+        },
+        false,
+        BASE64_STANDARD.encode(plaintext),
+    ));
+    let (mut provider, _) = replay_provider(exchanges);
+
+    provider.initialize_module().unwrap();
+    provider.create_key(KEY_ID, Box::new(config())).unwrap();
+    assert_eq!(provider.decrypt_data(b"ciphertext").unwrap(), plaintext);
+}
+
+#[test]
+fn test_replay_error_mapping() {
+    let (mut provider, _) = replay_provider(vec![
+        version_exchange(),
+        exchange(Request::InitializeModule, true, ""),
+        exchange(
+            Request::CreateKey {
+                key_id: KEY_ID.to_owned(),
+                key_type: "ECDSA;256".to_owned(),
+            },
+            true,
+            "Key generation failed",
+        ),
+    ]);
+
+    assert!(matches!(
+        provider.initialize_module(),
+        Err(SecurityModuleError::InitializationError(_))
+    ));
+    assert!(matches!(
+        provider.create_key(KEY_ID, Box::new(config())),
+        Err(SecurityModuleError::InitializationError(message))
+            if message == "Key generation failed"
+    ));
+}
+
+#[test]
+fn test_swift_exception_fails_only_the_call() {
+    let data = b"Hello, World!";
+    let mut exchanges = create_key_exchanges(&p256_key());
+    // The bindings catch Objective-C exceptions and report them like any other error.
+    exchanges.push(exchange(
+        sign_request(data),
+        true,
+        "Unexpected exception NSInvalidArgumentException: -[__NSCFNumber length]",
+    ));
+    exchanges.push(exchange(sign_request(data), false, "c2lnbmF0dXJl"));
+    let (mut provider, _) = replay_provider(exchanges);
+
+    provider.initialize_module().unwrap();
+    provider.create_key(KEY_ID, Box::new(config())).unwrap();
+    assert!(matches!(
+        provider.sign_data(data),
+        Err(e) if e.to_string().contains("Unexpected exception NSInvalidArgumentException")
+    ));
+    assert_eq!(provider.sign_data(data).unwrap(), b"c2lnbmF0dXJl");
+}
+
+#[test]
+fn test_create_key_from_spec_requests_access_control() {
+    let spec = KeySpec::builder()
+        .algorithm(KeyAlgorithm::EcP256)
+        .usage(KeyPurpose::Sign)
+        .access(AccessControl::BiometryAny)
+        .label(KEY_ID)
+        .build()
+        .unwrap();
+    let (mut provider, _) = replay_provider(vec![exchange(
+        Request::CreateKey {
+            key_id: KEY_ID.to_owned(),
+            key_type: "ECDSA;256;biometryAny".to_owned(),
+        },
+        true,
+        "User interaction is not allowed",
+    )]);
+
+    let config = SecureEnclaveConfig::from_spec(&spec).unwrap();
+    assert!(matches!(
+        provider.create_key(spec.label(), Box::new(config)),
+        Err(SecurityModuleError::InitializationError(message))
+            if message == "User interaction is not allowed"
+    ));
+}
+
+#[test]
+fn test_initialization_belongs_to_the_instance() {
+    let (mut provider, bridge) = replay_provider(vec![
+        version_exchange(),
+        exchange(Request::InitializeModule, false, ""),
+        exchange(Request::ShutdownModule, false, ""),
+        version_exchange(),
+        exchange(Request::InitializeModule, false, ""),
+    ]);
+    let (mut other, _) = replay_provider(vec![
+        version_exchange(),
+        exchange(Request::InitializeModule, true, ""),
+    ]);
+
+    provider.initialize_module().unwrap();
+    provider.initialize_module().unwrap();
+    assert!(other.initialize_module().is_err());
+    assert_eq!(bridge.cassette().unwrap().exchanges.len(), 3);
+
+    provider.shutdown().unwrap();
+    provider.shutdown().unwrap();
+    assert!(matches!(
+        provider.key_metadata(),
+        Err(SecurityModuleError::InitializationError(_))
+    ));
+    provider.initialize_module().unwrap();
+    assert!(bridge.cassette().unwrap().exchanges.is_empty());
+}
+
+#[test]
+fn test_older_bindings_degrade() {
+    let spec = KeySpec::builder()
+        .algorithm(KeyAlgorithm::EcP256)
+        .usage(KeyPurpose::Sign)
+        .access(AccessControl::BiometryAny)
+        .label(KEY_ID)
+        .build()
+        .unwrap();
+    let (mut provider, bridge) = replay_provider(vec![
+        exchange(Request::BridgeVersion, false, "3;3;"),
+        exchange(Request::InitializeModule, false, ""),
+    ]);
+
+    provider.initialize_module().unwrap();
+    assert_eq!(provider.bridge_protocol().unwrap().version, 3);
+    // Bindings without access control are not sent a key type they cannot parse.
+    let config = SecureEnclaveConfig::from_spec(&spec).unwrap();
+    assert!(matches!(
+        provider.create_key(spec.label(), Box::new(config)),
+        Err(SecurityModuleError::InitializationError(message))
+            if message.starts_with("The Swift bindings do not support access control")
+    ));
+    // Bindings without shutdown are not called to shut down.
+    provider.shutdown().unwrap();
+    assert!(provider.bridge_protocol().is_none());
+    assert!(bridge.cassette().unwrap().exchanges.is_empty());
+}
+
+#[test]
+fn test_incompatible_bindings_are_not_initialized() {
+    let (mut provider, bridge) = replay_provider(vec![
+        exchange(Request::BridgeVersion, false, "1;1;"),
+        exchange(Request::InitializeModule, false, ""),
+    ]);
+
+    assert!(matches!(
+        provider.initialize_module(),
+        Err(SecurityModuleError::InitializationError(message))
+            if message.contains("protocol version 1")
+    ));
+    assert_eq!(
+        bridge.cassette().unwrap().exchanges,
+        [exchange(Request::InitializeModule, false, "")]
+    );
+}
+
+#[test]
+fn test_replay_missing_exchange() {
+    let (mut provider, _) = replay_provider(create_key_exchanges(&p256_key()));
+
+    provider.initialize_module().unwrap();
+    provider.create_key(KEY_ID, Box::new(config())).unwrap();
+    assert!(matches!(
+        provider.sign_data(b"Hello, World!"),
+        Err(SecurityModuleError::EncryptionError(message))
+            if message.starts_with("No recorded response for SignData")
+    ));
+}
+
+#[test]
+#[cfg(not(target_os = "macos"))]
+fn test_record_without_secure_enclave() {
+    let bridge = Bridge::recording();
+    let mut provider = SecureEnclaveProvider::with_bridge(KEY_ID.to_owned(), bridge.clone());
+
+    assert!(provider.initialize_module().is_err());
+    let mut exchanges = bridge.cassette().unwrap().exchanges;
+    // The duration of the call is recorded as well.
+    assert!(exchanges[0].duration_us.take().is_some());
+    assert_eq!(
+        exchanges,
+        vec![Exchange::new(
+            Request::BridgeVersion,
+            Err(SwiftError::bridge(
+                "The Secure Enclave is only available on macOS"
+            )),
+            None
+        )]
+    );
+}
+
+/// Collects the name and value of every span field that is recorded.
+#[derive(Clone, Default)]
+struct CapturedFields(Arc<Mutex<Vec<(String, String)>>>);
+
+impl CapturedFields {
+    fn get(&self, name: &str) -> Vec<String> {
+        self.0
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(field, _)| field == name)
+            .map(|(_, value)| value.clone())
+            .collect()
+    }
+}
+
+impl Visit for CapturedFields {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0
+            .lock()
+            .unwrap()
+            .push((field.name().to_owned(), format!("{:?}", value)));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0
+            .lock()
+            .unwrap()
+            .push((field.name().to_owned(), value.to_owned()));
+    }
+}
+
+impl<S: Subscriber> Layer<S> for CapturedFields {
+    fn on_new_span(&self, attrs: &span::Attributes<'_>, _id: &span::Id, _ctx: Context<'_, S>) {
+        self.0
+            .lock()
+            .unwrap()
+            .push(("span".to_owned(), attrs.metadata().name().to_owned()));
+        attrs.record(&mut self.clone());
+    }
+
+    fn on_record(&self, _id: &span::Id, values: &span::Record<'_>, _ctx: Context<'_, S>) {
+        values.record(&mut self.clone());
+    }
+}
+
+#[test]
+fn test_spans_exclude_payloads() {
+    let data = b"secret payload";
+    let mut exchanges = create_key_exchanges(&p256_key());
+    exchanges.push(Exchange {
+        duration_us: Some(1234),
+        ..exchange(sign_request(data), false, "c2lnbmF0dXJl")
+    });
+    exchanges.push(exchange(
+        Request::DecryptData {
+            key_id: KEY_ID.to_owned(),
+            data: data.to_vec(),
+            algorithm: "ECDSA".to_owned(),
+            hash: "SHA256".to_owned(),
+        },
+        true,
+        "Authentication failed",
+    ));
+    let (mut provider, _) = replay_provider(exchanges);
+    let fields = CapturedFields::default();
+    let subscriber = tracing_subscriber::registry().with(fields.clone());
+
+    tracing::subscriber::with_default(subscriber, || {
+        provider.initialize_module().unwrap();
+        provider.create_key(KEY_ID, Box::new(config())).unwrap();
+        provider.sign_data(data).unwrap();
+        assert!(provider.decrypt_data(data).is_err());
+    });
+
+    assert!(fields.get("span").contains(&"sign_data".to_owned()));
+    assert!(fields
+        .get("span")
+        .contains(&"secure_enclave.call".to_owned()));
+    assert_eq!(
+        fields.get("rpc.method"),
+        [
+            "bridge_version",
+            "initialize_module",
+            "create_key",
+            "get_public_key",
+            "sign_data",
+            "decrypt_data"
+        ]
+    );
+    assert_eq!(fields.get("ffi.duration_us"), ["1234"]);
+    assert_eq!(fields.get("otel.status_code"), ["ERROR"]);
+    assert_eq!(
+        fields.get("otel.status_description"),
+        ["Authentication failed"]
+    );
+    assert_eq!(fields.get("crypto.payload.size"), ["14", "14"]);
+    assert!(fields
+        .get("crypto.key_id_hash")
+        .iter()
+        .all(|hash| hash.len() == 16 && hash != KEY_ID));
+
+    let recorded = fields.0.lock().unwrap();
+    assert!(recorded
+        .iter()
+        .all(|(_, value)| !value.contains("secret") && !value.contains(KEY_ID)));
+}
+
+#[test]
+fn test_calls_of_an_operation_share_correlation_id() {
+    let (mut provider, _) = replay_provider(create_key_exchanges(&p256_key()));
+    let fields = CapturedFields::default();
+    let subscriber = tracing_subscriber::registry().with(fields.clone());
+
+    let id = tracing::subscriber::with_default(subscriber, || {
+        provider.initialize_module().unwrap();
+        CorrelationId::scope(|id| {
+            provider.create_key(KEY_ID, Box::new(config())).unwrap();
+            id
+        })
+    });
+
+    let ids = fields.get("crypto.correlation_id");
+    assert_eq!(
+        fields.get("rpc.method")[2..],
+        ["create_key", "get_public_key"]
+    );
+    assert_eq!(ids[2..], [id.to_string(), id.to_string()]);
+    assert!(ids[..2].iter().all(|other| *other != id.to_string()));
+}
+
+#[test]
+fn test_request_debug_redacts_payload() {
+    let request = Request::VerifySignature {
+        key_id: KEY_ID.to_owned(),
+        data: b"secret".to_vec(),
+        signature: vec![0xab; 64],
+        algorithm: "ECDSA".to_owned(),
+        hash: "SHA256".to_owned(),
+    };
+
+    let debug = format!("{:?}", request);
+    assert!(debug.contains("data: <6 bytes"));
+    assert!(debug.contains("signature: <64 bytes"));
+    assert!(!debug.contains("115"));
+    assert_eq!(request.method(), "verify_signature");
+}
@@ -0,0 +1,214 @@
+use crate::{
+    common::telemetry::CorrelationId,
+    tpm::macos::{
+        bridge::Request,
+        dispatch::{self, op_code, Envelope, WireError, WireResponse},
+        protocol::PROTOCOL_VERSION,
+        response::{SwiftError, SwiftErrorCode, BRIDGE_DOMAIN, SECURE_ENCLAVE_DOMAIN},
+    },
+};
+use futures::executor::block_on;
+use std::collections::HashSet;
+
+fn sign_request() -> Request {
+    Request::SignData {
+        key_id: "dispatch_key".to_owned(),
+        data: vec![0x00, 0xff, 0x0a],
+        algorithm: "ECDSA".to_owned(),
+        hash: "SHA256".to_owned(),
+    }
+}
+
+fn all_requests() -> Vec<Request> {
+    let key_id = || "dispatch_key".to_owned();
+    vec![
+        Request::BridgeVersion,
+        Request::InitializeModule,
+        Request::ShutdownModule,
+        Request::CreateKey {
+            key_id: key_id(),
+            key_type: "ECDSA;P256".to_owned(),
+        },
+        Request::LoadKey {
+            key_id: key_id(),
+            key_type: "ECDSA;P256".to_owned(),
+            hash: "SHA256".to_owned(),
+        },
+        sign_request(),
+        Request::VerifySignature {
+            key_id: key_id(),
+            data: vec![1],
+            signature: vec![2],
+            algorithm: "ECDSA".to_owned(),
+            hash: "SHA256".to_owned(),
+        },
+        Request::EncryptData {
+            key_id: key_id(),
+            data: vec![1],
+            algorithm: "ECDSA".to_owned(),
+            hash: "SHA256".to_owned(),
+        },
+        Request::DecryptData {
+            key_id: key_id(),
+            data: vec![1],
+            algorithm: "ECDSA".to_owned(),
+            hash: "SHA256".to_owned(),
+        },
+        Request::GetPublicKey {
+            key_id: key_id(),
+            algorithm: "ECDSA".to_owned(),
+        },
+        Request::DeriveSharedSecret {
+            key_id: key_id(),
+            peer_public_key: vec![4],
+            algorithm: "ECDSA".to_owned(),
+        },
+        Request::ReadMetadata { key_id: key_id() },
+        Request::WriteMetadata {
+            key_id: key_id(),
+            record: "{}".to_owned(),
+        },
+        Request::DeleteMetadata { key_id: key_id() },
+        Request::ListMetadata,
+    ]
+}
+
+#[test]
+fn test_encode_request() {
+    let id = CorrelationId::new();
+    let bytes = dispatch::encode_request(sign_request(), id);
+
+    let json: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+    assert_eq!(json["version"], PROTOCOL_VERSION);
+    assert_eq!(json["correlation_id"], id.to_string());
+    assert_eq!(json["request"]["call"], "sign_data");
+    assert_eq!(json["request"]["key_id"], "dispatch_key");
+    // Binary arguments are base64 encoded.
+    assert_eq!(json["request"]["data"], "AP8K");
+
+    let envelope: Envelope = serde_json::from_slice(&bytes).unwrap();
+    assert_eq!(envelope.request, sign_request());
+}
+
+#[test]
+fn test_op_codes_are_unique() {
+    let requests = all_requests();
+    let op_codes: HashSet<u32> = requests.iter().map(Request::op_code).collect();
+    assert_eq!(op_codes.len(), requests.len());
+    assert_eq!(Request::BridgeVersion.op_code(), op_code::BRIDGE_VERSION);
+    assert_eq!(sign_request().op_code(), op_code::SIGN_DATA);
+}
+
+#[test]
+fn test_decode_response() {
+    assert_eq!(
+        dispatch::decode_response(br#"{"payload":"AP8K"}"#),
+        Ok("AP8K".to_owned())
+    );
+    assert_eq!(
+        dispatch::decode_response(
+            br#"{"payload":"","error":{"code":4,"domain":"SecureEnclaveError","message":"missing"}}"#
+        ),
+        Err(SwiftError::new(
+            SwiftErrorCode::LoadKey.code(),
+            SECURE_ENCLAVE_DOMAIN,
+            "missing"
+        ))
+    );
+
+    let error = dispatch::decode_response(b"Error: not json").unwrap_err();
+    assert_eq!(error.domain, BRIDGE_DOMAIN);
+    assert!(error
+        .message
+        .starts_with("Invalid response of the Swift bindings"));
+}
+
+#[test]
+fn test_wire_response_round_trip() {
+    let failure = Err(SwiftError::new(
+        SwiftErrorCode::Runtime.code(),
+        SECURE_ENCLAVE_DOMAIN,
+        "failed",
+    ));
+    let wire = WireResponse::from(failure.clone());
+    assert_eq!(
+        wire.error,
+        Some(WireError {
+            code: SwiftErrorCode::Runtime.code(),
+            domain: SECURE_ENCLAVE_DOMAIN.to_owned(),
+            message: "failed".to_owned(),
+        })
+    );
+
+    for response in [Ok("payload".to_owned()), failure] {
+        let bytes = serde_json::to_vec(&WireResponse::from(response.clone())).unwrap();
+        assert_eq!(dispatch::decode_response(&bytes), response);
+    }
+}
+
+#[test]
+fn test_call_reports_boundary_stats() {
+    let id = CorrelationId::new();
+    let reply = br#"{"payload":"signature"}"#.to_vec();
+    let mut sent = 0;
+    let (response, stats) = dispatch::call(sign_request(), id, |code, bytes| {
+        assert_eq!(code, op_code::SIGN_DATA);
+        sent = bytes.len();
+        reply.clone()
+    });
+
+    assert_eq!(response, Ok("signature".to_owned()));
+    assert_eq!(stats.request_bytes, sent);
+    assert_eq!(
+        stats.request_bytes,
+        dispatch::encode_request(sign_request(), id).len()
+    );
+    assert_eq!(stats.response_bytes, reply.len());
+
+    let (response, stats) = block_on(dispatch::call_async(sign_request(), id, |_, _| async {
+        b"not json".to_vec()
+    }));
+    assert!(response.is_err());
+    assert_eq!(stats.request_bytes, sent);
+    assert_eq!(stats.response_bytes, 8);
+}
+
+#[test]
+fn test_failing_bindings_are_reported() {
+    let id = CorrelationId::new();
+    for reply in [
+        Vec::new(),
+        br#"{"payload":"trunc"#.to_vec(),
+        vec![0xff, 0xfe, 0x00],
+        br#"{"error":{"code":1}}"#.to_vec(),
+    ] {
+        let (response, stats) = dispatch::call(sign_request(), id, |_, _| reply.clone());
+        let error = response.unwrap_err();
+        assert_eq!(error.domain, BRIDGE_DOMAIN);
+        assert_eq!(stats.response_bytes, reply.len());
+    }
+
+    // A panic of the transport, e.g. in the generated glue code, fails the call instead of
+    // unwinding into the provider.
+    let (response, stats) = dispatch::call(sign_request(), id, |_, _| panic!("glue failed"));
+    let error = response.unwrap_err();
+    assert_eq!(error.domain, BRIDGE_DOMAIN);
+    assert_eq!(
+        error.message,
+        "The call into the Swift bindings panicked: glue failed"
+    );
+    assert_eq!(stats.response_bytes, 0);
+
+    let (response, _) = block_on(dispatch::call_async(
+        sign_request(),
+        id,
+        |code, _| async move { panic!("op code {}", code) },
+    ));
+    assert_eq!(
+        response.unwrap_err().message,
+        format!(
+            "The call into the Swift bindings panicked: op code {}",
+            op_code::SIGN_DATA
+        )
+    );
+}
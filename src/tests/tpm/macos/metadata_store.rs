@@ -0,0 +1,117 @@
+use crate::{
+    common::{
+        latency::ProviderOperation,
+        metadata_store::{KeyRecord, MetadataStore},
+    },
+    tpm::macos::{
+        bridge::{Bridge, Cassette, Exchange, Request},
+        metadata_store::KeychainMetadataStore,
+        response::{SwiftError, SwiftErrorCode, SECURE_ENCLAVE_DOMAIN},
+    },
+    SecurityModuleError,
+};
+
+fn exchange(request: Request, result: Result<&str, &str>) -> Exchange {
+    let response = result.map(str::to_owned).map_err(|message| {
+        SwiftError::new(
+            SwiftErrorCode::Runtime.code(),
+            SECURE_ENCLAVE_DOMAIN,
+            message,
+        )
+    });
+    Exchange::new(request, response, None)
+}
+
+fn replay_store(exchanges: Vec<Exchange>) -> (KeychainMetadataStore, Bridge) {
+    let mut all = vec![exchange(
+        Request::BridgeVersion,
+        Ok("3;3;async,metadata,shutdown"),
+    )];
+    all.extend(exchanges);
+    let bridge = Bridge::replay(Cassette { exchanges: all });
+    let store = KeychainMetadataStore::with_bridge(bridge.clone()).unwrap();
+    (store, bridge)
+}
+
+#[test]
+fn test_keychain_store() {
+    let mut record = KeyRecord::new("device");
+    record.usage = Some([ProviderOperation::SignData].into());
+    let json = serde_json::to_string(&record).unwrap();
+    let read = |key_id: &str| Request::ReadMetadata {
+        key_id: key_id.to_owned(),
+    };
+    let (store, bridge) = replay_store(vec![
+        exchange(
+            Request::WriteMetadata {
+                key_id: "device".to_owned(),
+                record: json.clone(),
+            },
+            Ok(""),
+        ),
+        exchange(read("device"), Ok(&json)),
+        exchange(read("missing"), Ok("")),
+        exchange(Request::ListMetadata, Ok(r#"["device","backup"]"#)),
+        exchange(
+            Request::DeleteMetadata {
+                key_id: "device".to_owned(),
+            },
+            Ok("true"),
+        ),
+    ]);
+
+    store.save(&record).unwrap();
+    assert_eq!(store.load("device").unwrap(), Some(record));
+    assert_eq!(store.load("missing").unwrap(), None);
+    assert_eq!(store.key_ids().unwrap(), ["backup", "device"]);
+    assert!(store.delete("device").unwrap());
+    assert!(bridge.cassette().unwrap().exchanges.is_empty());
+}
+
+#[test]
+fn test_keychain_store_errors() {
+    let (store, _) = replay_store(vec![
+        exchange(
+            Request::ReadMetadata {
+                key_id: "device".to_owned(),
+            },
+            Err("The metadata of 'device' could not be read (-25308)."),
+        ),
+        exchange(
+            Request::ReadMetadata {
+                key_id: "corrupt".to_owned(),
+            },
+            Ok("not json"),
+        ),
+        exchange(
+            Request::DeleteMetadata {
+                key_id: "device".to_owned(),
+            },
+            Ok("maybe"),
+        ),
+    ]);
+
+    assert!(matches!(
+        store.load("device"),
+        Err(SecurityModuleError::SecretStorage(message)) if message.contains("-25308")
+    ));
+    assert!(matches!(
+        store.load("corrupt"),
+        Err(SecurityModuleError::SecretStorage(_))
+    ));
+    assert!(store.delete("device").is_err());
+}
+
+#[test]
+fn test_requires_metadata_capability() {
+    let bridge = Bridge::replay(Cassette {
+        exchanges: vec![exchange(
+            Request::BridgeVersion,
+            Ok("3;3;access_control,async,sha512,shutdown"),
+        )],
+    });
+    assert!(matches!(
+        KeychainMetadataStore::with_bridge(bridge),
+        Err(SecurityModuleError::UnsupportedOperation(_))
+    ));
+}
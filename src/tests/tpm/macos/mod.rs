@@ -0,0 +1,5 @@
+mod bridge;
+mod dispatch;
+mod metadata_store;
+mod protocol;
+mod response;
@@ -0,0 +1,95 @@
+use crate::{
+    tpm::macos::{
+        protocol::{self, capability, BridgeVersion, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION},
+        response::{SwiftError, SwiftErrorCode, SECURE_ENCLAVE_DOMAIN},
+    },
+    SecurityModuleError,
+};
+
+fn bindings(version: u32, min_version: u32, capabilities: &[&str]) -> BridgeVersion {
+    BridgeVersion {
+        version,
+        min_version,
+        capabilities: capabilities.iter().map(|c| c.to_string()).collect(),
+    }
+}
+
+#[test]
+fn test_parse_bridge_version() {
+    let version = BridgeVersion::parse("3;2;shutdown,sha512").unwrap();
+    assert_eq!(version, bindings(3, 2, &["sha512", "shutdown"]));
+    assert_eq!(version.to_payload(), "3;2;sha512,shutdown");
+    assert_eq!(BridgeVersion::parse("2;2;").unwrap(), bindings(2, 2, &[]));
+
+    for payload in [
+        "",
+        "2",
+        "2;2",
+        "2;2;;",
+        "two;2;",
+        "2;3;shutdown",
+        "Error: unknown",
+    ] {
+        assert!(
+            matches!(
+                BridgeVersion::parse(payload),
+                Err(SecurityModuleError::InitializationError(_))
+            ),
+            "{:?}",
+            payload
+        );
+    }
+}
+
+#[test]
+fn test_negotiate_common_version() {
+    let protocol = protocol::negotiate(&bindings(
+        PROTOCOL_VERSION,
+        MIN_PROTOCOL_VERSION,
+        &[capability::SHUTDOWN, "future_feature"],
+    ))
+    .unwrap();
+    assert_eq!(protocol.version, PROTOCOL_VERSION);
+    assert!(protocol.supports(capability::SHUTDOWN));
+    assert!(!protocol.supports(capability::ACCESS_CONTROL));
+    // Capabilities this crate does not know are ignored.
+    assert!(!protocol.supports("future_feature"));
+
+    // Newer bindings that still serve this version are used with it.
+    let protocol =
+        protocol::negotiate(&bindings(PROTOCOL_VERSION + 1, MIN_PROTOCOL_VERSION, &[])).unwrap();
+    assert_eq!(protocol.version, PROTOCOL_VERSION);
+}
+
+#[test]
+fn test_negotiate_incompatible_versions() {
+    assert!(matches!(
+        protocol::negotiate(&bindings(MIN_PROTOCOL_VERSION - 1, 1, &[])),
+        Err(SecurityModuleError::InitializationError(message))
+            if message.ends_with("update the Swift bindings")
+    ));
+    assert!(matches!(
+        protocol::negotiate(&bindings(PROTOCOL_VERSION + 2, PROTOCOL_VERSION + 1, &[])),
+        Err(SecurityModuleError::InitializationError(message))
+            if message.ends_with("update crypto-layer")
+    ));
+}
+
+#[test]
+fn test_negotiate_response() {
+    assert_eq!(
+        protocol::negotiate_response(Ok("3;3;shutdown".to_owned()))
+            .unwrap()
+            .version,
+        3
+    );
+    assert!(matches!(
+        protocol::negotiate_response(Err(SwiftError::new(
+            SwiftErrorCode::Runtime.code(),
+            SECURE_ENCLAVE_DOMAIN,
+            "unavailable"
+        ))),
+        Err(SecurityModuleError::InitializationError(message))
+            if message == "The Swift bindings did not report their protocol version: unavailable"
+    ));
+}
@@ -0,0 +1,156 @@
+use crate::{
+    common::crypto::algorithms::{
+        encryption::{AsymmetricEncryption, EccCurves, EccSchemeAlgorithm},
+        hashes::{Hash, Sha2Bits},
+        KeyBits,
+    },
+    tpm::macos::response::{self, SwiftError, SwiftErrorCode, SECURE_ENCLAVE_DOMAIN},
+    SecurityModuleError,
+};
+use base64::{prelude::BASE64_STANDARD, Engine};
+use openssl::{
+    bn::BigNumContext,
+    ec::{EcGroup, EcKey, PointConversionForm},
+    nid::Nid,
+    rsa::Rsa,
+};
+use proptest::{collection::vec, prelude::*};
+
+const P256: AsymmetricEncryption =
+    AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(EccCurves::P256));
+const SHA256: Hash = Hash::Sha2(Sha2Bits::Sha256);
+
+fn failure(message: impl Into<String>) -> SwiftError {
+    SwiftError::new(
+        SwiftErrorCode::LoadKey.code(),
+        SECURE_ENCLAVE_DOMAIN,
+        message,
+    )
+}
+
+#[test]
+fn test_error_codes() {
+    for code in 0..9 {
+        assert_eq!(SwiftErrorCode::from_code(code).code(), code);
+    }
+    assert_eq!(SwiftErrorCode::from_code(42), SwiftErrorCode::Other);
+
+    let error = SwiftError::new(4, SECURE_ENCLAVE_DOMAIN, "Key not found");
+    assert_eq!(error.code, SwiftErrorCode::LoadKey);
+    assert_eq!(error.to_string(), "Key not found");
+}
+
+#[test]
+fn test_decode_status() {
+    assert!(
+        response::decode_status(Ok(String::new()), SecurityModuleError::InitializationError)
+            .is_ok()
+    );
+    assert!(matches!(
+        response::decode_status(Err(failure("Key not found".to_owned())), SecurityModuleError::InitializationError),
+        Err(SecurityModuleError::InitializationError(message)) if message == "Key not found"
+    ));
+}
+
+#[test]
+fn test_decode_bytes() {
+    assert_eq!(
+        response::decode_bytes(
+            Ok("c2lnbmF0dXJl".to_owned()),
+            SecurityModuleError::SigningError
+        )
+        .unwrap(),
+        b"c2lnbmF0dXJl"
+    );
+    assert!(matches!(
+        response::decode_bytes(Err(failure("Denied".to_owned())), SecurityModuleError::SigningError),
+        Err(SecurityModuleError::SigningError(message)) if message == "Denied"
+    ));
+}
+
+#[test]
+fn test_decode_verification() {
+    assert!(response::decode_verification(Ok("true".to_owned())).unwrap());
+    assert!(!response::decode_verification(Ok("false".to_owned())).unwrap());
+    for result in ["TRUE", "", "true ", "Error: invalid signature"] {
+        assert!(matches!(
+            response::decode_verification(Ok(result.to_owned())),
+            Err(SecurityModuleError::SignatureVerificationError(_))
+        ));
+    }
+    assert!(matches!(
+        response::decode_verification(Err(failure("Key not found"))),
+        Err(SecurityModuleError::SignatureVerificationError(message)) if message == "Key not found"
+    ));
+}
+
+#[test]
+fn test_decode_public_key_ec() {
+    let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+    let key = EcKey::generate(&group).unwrap();
+    let point = key
+        .public_key()
+        .to_bytes(
+            &group,
+            PointConversionForm::UNCOMPRESSED,
+            &mut BigNumContext::new().unwrap(),
+        )
+        .unwrap();
+
+    let public_key =
+        response::decode_public_key(Ok(BASE64_STANDARD.encode(&point)), P256, SHA256).unwrap();
+    assert!(matches!(
+        public_key.algorithm(),
+        AsymmetricEncryption::Ecc(_)
+    ));
+}
+
+#[test]
+fn test_decode_public_key_rsa() {
+    let algorithm = AsymmetricEncryption::Rsa(KeyBits::Bits2048);
+    let pkcs1 = Rsa::generate(2048)
+        .unwrap()
+        .public_key_to_der_pkcs1()
+        .unwrap();
+
+    assert!(
+        response::decode_public_key(Ok(BASE64_STANDARD.encode(pkcs1)), algorithm, SHA256).is_ok()
+    );
+}
+
+#[test]
+fn test_decode_public_key_rejects_malformed_responses() {
+    assert!(matches!(
+        response::decode_public_key(Err(failure("Key not found".to_owned())), P256, SHA256),
+        Err(SecurityModuleError::InitializationError(_))
+    ));
+    assert!(matches!(
+        response::decode_public_key(Ok("not base64!".to_owned()), P256, SHA256),
+        Err(SecurityModuleError::InvalidPublicKey)
+    ));
+    assert!(
+        response::decode_public_key(Ok(BASE64_STANDARD.encode([4, 1, 2, 3])), P256, SHA256)
+            .is_err()
+    );
+}
+
+proptest! {
+    /// Plaintexts are arbitrary bytes, which must survive the String-based bridge unchanged.
+    #[test]
+    fn prop_decode_base64_is_identity(data in vec(any::<u8>(), 0..1024)) {
+        let decoded = response::decode_base64(Ok(BASE64_STANDARD.encode(&data)), SecurityModuleError::EncryptionError).unwrap();
+        prop_assert_eq!(decoded, data);
+    }
+
+    #[test]
+    fn prop_decode_bytes_is_identity(result in "\\PC*") {
+        let decoded = response::decode_bytes(Ok(result.clone()), SecurityModuleError::SigningError).unwrap();
+        prop_assert_eq!(decoded, result.into_bytes());
+    }
+
+    #[test]
+    fn prop_error_message_is_preserved(message in "\\PC*") {
+        let decoded = response::decode_base64(Err(failure(message.clone())), SecurityModuleError::EncryptionError);
+        prop_assert!(matches!(decoded, Err(SecurityModuleError::EncryptionError(m)) if m == message));
+    }
+}
@@ -1,5 +1,7 @@
 #[cfg(feature = "linux")]
 mod linux;
+#[cfg(feature = "macos")]
+mod macos;
 #[cfg(feature = "win")]
 mod win;
 #[cfg(feature = "android")]
@@ -6,8 +6,10 @@ use crate::common::{
     crypto::{
         algorithms::encryption::{AsymmetricEncryption, BlockCiphers},
         algorithms::hashes::Hash,
+        key_spec::{AccessControl, KeySpec},
         KeyUsage,
     },
+    error::SecurityModuleError,
     traits::module_provider_config::ProviderConfig,
 };
 
@@ -20,6 +22,21 @@ pub enum EncryptionMode {
     },
 }
 
+impl EncryptionMode {
+    /// Returns the mode of keys of `spec`, or a `SecurityModuleError::UnsupportedAlgorithm` if
+    /// the spec has neither an asymmetric nor a symmetric algorithm.
+    pub fn from_spec(spec: &KeySpec) -> Result<Self, SecurityModuleError> {
+        match (spec.asymmetric_algorithm(), spec.symmetric_algorithm()) {
+            (Some(algo), _) => Ok(EncryptionMode::ASym {
+                algo,
+                digest: spec.hash(),
+            }),
+            (None, Some(cipher)) => Ok(EncryptionMode::Sym(cipher)),
+            (None, None) => Err(SecurityModuleError::UnsupportedAlgorithm),
+        }
+    }
+}
+
 pub struct AndroidConfig {
     pub mode: EncryptionMode,
     pub key_usages: Vec<KeyUsage>,
@@ -27,6 +44,37 @@ pub struct AndroidConfig {
     pub vm: Option<JavaVM>,
 }
 
+impl AndroidConfig {
+    /// Creates the config of keys of `spec` in the Android Keystore of the running app, for
+    /// callers that only know the spec, e.g. the C API.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the config on success, a `SecurityModuleError::InvalidKeySpec` if
+    /// the spec requires user authentication or an exportable key, which the provider does not
+    /// support, or an error if the process runs no Java VM.
+    pub fn from_spec(spec: &KeySpec) -> Result<Box<dyn Any>, SecurityModuleError> {
+        if spec.access() != AccessControl::None {
+            return Err(SecurityModuleError::InvalidKeySpec(format!(
+                "Access control {:?} is not supported by the Android Keystore provider",
+                spec.access()
+            )));
+        }
+        if spec.exportable() {
+            return Err(SecurityModuleError::InvalidKeySpec(
+                "Exportable keys are not supported by the Android Keystore".to_owned(),
+            ));
+        }
+        Ok(Box::new(Self {
+            mode: EncryptionMode::from_spec(spec)?,
+            key_usages: spec.key_usages(),
+            // StrongBox is missing on most devices, keys are still kept in the TEE.
+            hardware_backed: false,
+            vm: Some(super::wrapper::get_java_vm()?),
+        }))
+    }
+}
+
 impl std::fmt::Debug for AndroidConfig {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("AndroidProvider")
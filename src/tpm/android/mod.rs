@@ -15,8 +15,11 @@ use utils::{
 };
 use wrapper::key_generation::iv_parameter_spec::jni::IvParameterSpec;
 
+use crate::common::crypto::key_spec::KeySpec;
+use crate::common::crypto::secret::SecretBytes;
 use crate::common::crypto::KeyUsage;
 use crate::common::error::SecurityModuleError;
+use crate::common::latency::key_id_hash;
 use crate::common::traits::key_handle::KeyHandle;
 use crate::common::{
     crypto::algorithms::encryption::{AsymmetricEncryption, BlockCiphers},
@@ -25,6 +28,7 @@ use crate::common::{
 use crate::tpm::android::config::AndroidConfig;
 use crate::tpm::android::wrapper::key_store::key_store::jni::KeyStore;
 use crate::tpm::android::wrapper::key_store::signature::jni::Signature;
+use crate::tpm::android::wrapper::key_store::wrapped_key_entry::WrappedKeyEntry;
 use crate::tpm::core::error::ToTpmError;
 use crate::tpm::core::error::TpmError;
 
@@ -55,7 +59,7 @@ impl AndroidProvider {
     /// # Returns
     ///
     /// A new instance of `AndroidProvider` with the specified `key_id`.
-    #[instrument]
+    #[instrument(skip_all, fields(crypto.key_id_hash = %key_id_hash(&key_id)))]
     pub fn new(key_id: String) -> Self {
         Self {
             key_id,
@@ -104,7 +108,7 @@ impl Provider for AndroidProvider {
     /// # Returns
     ///
     /// Returns `Ok(())` if the key generation is successful, otherwise returns an error of type `SecurityModuleError`.
-    #[instrument]
+    #[instrument(skip_all, fields(crypto.key_id_hash = %key_id_hash(key_id)))]
     fn create_key(
         &mut self,
         key_id: &str,
@@ -227,7 +231,7 @@ impl Provider for AndroidProvider {
     /// # Returns
     ///
     /// Returns `Ok(())` if the key loading is successful, otherwise returns an error of type `SecurityModuleError`.
-    #[instrument]
+    #[instrument(skip_all, fields(crypto.key_id_hash = %key_id_hash(key_id)))]
     fn load_key(&mut self, key_id: &str, config: Box<dyn Any>) -> Result<(), SecurityModuleError> {
         key_id.clone_into(&mut self.key_id);
 
@@ -240,6 +244,69 @@ impl Provider for AndroidProvider {
         Ok(())
     }
 
+    /// Imports a key wrapped in a `SecureKeyWrapper` under the key `wrapping_key_id` of the
+    /// Keystore and makes it the current key.
+    ///
+    /// The wrapping key is an RSA key with the purpose `PURPOSE_WRAP_KEY`. The key is unwrapped
+    /// inside the TEE or StrongBox and never reaches the application in plaintext.
+    ///
+    /// # Java Example
+    ///
+    /// ```java
+    /// KeyStore ks = KeyStore.getInstance("AndroidKeyStore");
+    /// ks.load(null);
+    /// AlgorithmParameterSpec spec = new OAEPParameterSpec("SHA-256", "MGF1",
+    ///         MGF1ParameterSpec.SHA1, PSource.PSpecified.DEFAULT);
+    /// ks.setEntry(alias, new WrappedKeyEntry(wrappedKey, wrappingKeyAlias,
+    ///         "RSA/ECB/OAEPPadding", spec), null);
+    /// ```
+    ///
+    /// # Arguments
+    ///
+    /// * `key_id` - The identifier for the imported key.
+    /// * `wrapped_key` - The DER encoded `SecureKeyWrapper`.
+    /// * `wrapping_key_id` - The identifier of the wrapping key.
+    /// * `spec` - The algorithm and usages of the imported key.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` if the key import is successful, otherwise returns an error of type `SecurityModuleError`.
+    #[instrument(skip_all, fields(crypto.key_id_hash = %key_id_hash(key_id)))]
+    fn import_wrapped_key(
+        &mut self,
+        key_id: &str,
+        wrapped_key: &[u8],
+        wrapping_key_id: &str,
+        spec: &KeySpec,
+    ) -> Result<(), SecurityModuleError> {
+        let mode = config::EncryptionMode::from_spec(spec)?;
+        let vm = wrapper::get_java_vm()?;
+
+        {
+            let env = vm.get_env().map_err(|_| {
+                TpmError::InitializationError(
+                    "Could not get java environment, this should never happen".to_owned(),
+                )
+            })?;
+            let entry = WrappedKeyEntry::new(&env, wrapped_key, wrapping_key_id).err_internal()?;
+            let key_store =
+                KeyStore::getInstance(&env, ANDROID_KEYSTORE.to_string()).err_internal()?;
+            key_store.load(&env, None).err_internal()?;
+            key_store
+                .set_entry(&env, key_id, entry.raw.as_obj())
+                .err_internal()?;
+        }
+        debug!("key imported");
+
+        key_id.clone_into(&mut self.key_id);
+        self.apply_config(AndroidConfig {
+            mode,
+            key_usages: spec.key_usages(),
+            hardware_backed: false,
+            vm: Some(vm),
+        })
+    }
+
     /// Initializes the module with the specified parameters.
     ///
     /// # Arguments
@@ -252,7 +319,7 @@ impl Provider for AndroidProvider {
     /// # Returns
     ///
     /// Returns `Ok(())` if the module initialization is successful, otherwise returns an error of type `SecurityModuleError`.
-    #[instrument]
+    #[instrument(skip_all, fields(crypto.key_id_hash = %key_id_hash(&self.key_id)))]
     fn initialize_module(&mut self) -> Result<(), SecurityModuleError> {
         Ok(())
     }
@@ -287,7 +354,7 @@ impl KeyHandle for AndroidProvider {
     /// # Returns
     ///
     /// Returns a `Result` containing the signed data as a `Vec<u8>` if successful, or a `SecurityModuleError` if an error occurs.
-    #[instrument]
+    #[instrument(skip_all, fields(crypto.key_id_hash = %key_id_hash(&self.key_id), crypto.payload.size = data.len()))]
     fn sign_data(&self, data: &[u8]) -> Result<Vec<u8>, SecurityModuleError> {
         // check that signing is allowed
         let config = self
@@ -360,8 +427,8 @@ impl KeyHandle for AndroidProvider {
     /// # Returns
     ///
     /// Returns a `Result` containing the decrypted data as a `Vec<u8>` if successful, or a `SecurityModuleError` if an error occurs.
-    #[instrument]
-    fn decrypt_data(&self, encrypted_data: &[u8]) -> Result<Vec<u8>, SecurityModuleError> {
+    #[instrument(skip_all, fields(crypto.key_id_hash = %key_id_hash(&self.key_id), crypto.payload.size = encrypted_data.len()))]
+    fn decrypt_data(&self, encrypted_data: &[u8]) -> Result<SecretBytes, SecurityModuleError> {
         info!("decrypting data");
 
         let config = self
@@ -390,7 +457,7 @@ impl KeyHandle for AndroidProvider {
         let cipher = wrapper::key_store::cipher::jni::Cipher::getInstance(&env, cipher_mode)
             .err_internal()?;
 
-        let decrypted = match config.mode {
+        let decrypted = SecretBytes::new(match config.mode {
             config::EncryptionMode::Sym(cipher_mode) => {
                 let key = key_store
                     .getKey(&env, self.key_id.to_owned(), JObject::null())
@@ -416,7 +483,7 @@ impl KeyHandle for AndroidProvider {
                     .doFinal(&env, encrypted_data.to_vec())
                     .err_internal()?
             }
-        };
+        });
 
         debug!("decrypted data: {:?}", decrypted);
         Ok(decrypted)
@@ -444,7 +511,7 @@ impl KeyHandle for AndroidProvider {
     /// # Returns
     ///
     /// Returns a `Result` containing the encrypted data as a `Vec<u8>` if successful, or a `SecurityModuleError` if an error occurs.
-    #[instrument]
+    #[instrument(skip_all, fields(crypto.key_id_hash = %key_id_hash(&self.key_id), crypto.payload.size = data.len()))]
     fn encrypt_data(&self, data: &[u8]) -> Result<Vec<u8>, SecurityModuleError> {
         info!("encrypting");
 
@@ -531,7 +598,7 @@ impl KeyHandle for AndroidProvider {
     /// # Returns
     ///
     /// Returns a `Result` containing `true` if the signature is valid, `false` otherwise, or a `SecurityModuleError` if an error occurs.
-    #[instrument]
+    #[instrument(skip_all, fields(crypto.key_id_hash = %key_id_hash(&self.key_id), crypto.payload.size = data.len(), crypto.signature.size = signature.len()))]
     fn verify_signature(&self, data: &[u8], signature: &[u8]) -> Result<bool, SecurityModuleError> {
         info!("verifiying");
 
@@ -99,6 +99,28 @@ pub mod jni {
             )?;
             Ok(())
         }
+
+        /// Saves an entry under the given alias, replacing an existing entry.
+        ///
+        /// # Arguments
+        ///
+        /// * `env` - The JNI environment.
+        /// * `alias` - The alias of the entry.
+        /// * `entry` - The entry, e.g. a `WrappedKeyEntry`.
+        ///
+        /// # Returns
+        ///
+        /// Returns a `JniResult` indicating the success or failure of the operation.
+        pub fn set_entry(&self, env: &JNIEnv, alias: &str, entry: JObject) -> JniResult<()> {
+            let alias = env.new_string(alias)?;
+            env.call_method(
+                self.raw.as_obj(),
+                "setEntry",
+                "(Ljava/lang/String;Ljava/security/KeyStore$Entry;Ljava/security/KeyStore$ProtectionParameter;)V",
+                &[Into::into(alias), Into::into(entry), Into::into(JObject::null())],
+            )?;
+            Ok(())
+        }
     }
 
     /// Represents a Certificate object in Java.
@@ -3,3 +3,4 @@
 pub mod cipher;
 pub mod key_store;
 pub mod signature;
+pub mod wrapped_key_entry;
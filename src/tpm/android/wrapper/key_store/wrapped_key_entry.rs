@@ -0,0 +1,77 @@
+use robusta_jni::jni::errors::Result as JniResult;
+use robusta_jni::jni::objects::{AutoLocal, JObject, JValue};
+use robusta_jni::jni::JNIEnv;
+
+/// The transformation the key wrapped in a `SecureKeyWrapper` is encrypted with.
+const WRAPPING_TRANSFORMATION: &str = "RSA/ECB/OAEPPadding";
+
+/// Wrapper for `android.security.keystore.WrappedKeyEntry`, a key wrapped in a DER encoded
+/// `SecureKeyWrapper` that the Keystore unwraps when the entry is stored with
+/// `KeyStore::set_entry`.
+/// The constructor takes an `OAEPParameterSpec`, which cannot be constructed with `robusta_jni`
+/// because of the static fields it is built from.
+pub struct WrappedKeyEntry<'env: 'borrow, 'borrow> {
+    pub raw: AutoLocal<'env, 'borrow>,
+}
+
+impl<'env: 'borrow, 'borrow> WrappedKeyEntry<'env, 'borrow> {
+    /// Creates a new `WrappedKeyEntry` instance.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The JNI environment.
+    /// * `wrapped_key` - The DER encoded `SecureKeyWrapper`.
+    /// * `wrapping_key_alias` - The alias of the RSA key the key is wrapped under, which has the
+    ///   purpose `PURPOSE_WRAP_KEY`.
+    ///
+    /// # Returns
+    ///
+    /// A `JniResult` containing the new `WrappedKeyEntry` instance.
+    pub fn new(
+        env: &'borrow JNIEnv<'env>,
+        wrapped_key: &[u8],
+        wrapping_key_alias: &str,
+    ) -> JniResult<Self> {
+        // new OAEPParameterSpec("SHA-256", "MGF1", MGF1ParameterSpec.SHA1, PSource.PSpecified.DEFAULT)
+        let mgf1_sha1 = env
+            .get_static_field(
+                "java/security/spec/MGF1ParameterSpec",
+                "SHA1",
+                "Ljava/security/spec/MGF1ParameterSpec;",
+            )?
+            .l()?;
+        let p_source = env
+            .get_static_field(
+                "javax/crypto/spec/PSource$PSpecified",
+                "DEFAULT",
+                "Ljavax/crypto/spec/PSource$PSpecified;",
+            )?
+            .l()?;
+        let oaep_args = [
+            Into::into(env.new_string("SHA-256")?),
+            Into::into(env.new_string("MGF1")?),
+            JValue::from(mgf1_sha1),
+            JValue::from(p_source),
+        ];
+        let oaep_spec = env.new_object(
+            "javax/crypto/spec/OAEPParameterSpec",
+            "(Ljava/lang/String;Ljava/lang/String;Ljava/security/spec/AlgorithmParameterSpec;Ljavax/crypto/spec/PSource;)V",
+            &oaep_args,
+        )?;
+
+        let args = [
+            Into::into(env.byte_array_from_slice(wrapped_key)?),
+            Into::into(env.new_string(wrapping_key_alias)?),
+            Into::into(env.new_string(WRAPPING_TRANSFORMATION)?),
+            JValue::from(oaep_spec),
+        ];
+        let obj = env.new_object(
+            "android/security/keystore/WrappedKeyEntry",
+            "([BLjava/lang/String;Ljava/lang/String;Ljava/security/spec/AlgorithmParameterSpec;)V",
+            &args,
+        )?;
+        Ok(Self {
+            raw: AutoLocal::new(env, Into::<JObject>::into(obj)),
+        })
+    }
+}
@@ -55,7 +55,10 @@ pub(super) fn get_java_vm() -> Result<JavaVM, TpmError> {
     let jvm = unsafe {
         JavaVM::from_raw(buffer[0]).map_err(|e| TpmError::InitializationError(e.to_string()))?
     };
-    jvm.attach_current_thread()
+    // The guard of `attach_current_thread` would detach the thread again before the provider
+    // uses it. Threads of the app are already attached, but threads of native callers, e.g. of
+    // Dart isolates, are not, and stay attached until they exit.
+    jvm.attach_current_thread_permanently()
         .map_err(|e| TpmError::InitializationError(e.to_string()))?;
     Ok(jvm)
 }
@@ -1,6 +1,8 @@
-use crate::{common::traits::module_provider::Provider, tpm::macos::SecureEnclaveProvider};
+use crate::common::traits::module_provider::Provider;
 #[cfg(feature = "linux")]
 use crate::tpm::linux::TpmProvider;
+#[cfg(feature = "macos")]
+use crate::tpm::macos::SecureEnclaveProvider;
 #[cfg(feature = "win")]
 use crate::tpm::win::TpmProvider as WinTpmProvider;
 use std::sync::{Arc, Mutex};
@@ -1,6 +1,8 @@
 use super::TpmProvider;
 use crate::common::{
-    crypto::algorithms::encryption::AsymmetricEncryption, error::SecurityModuleError,
+    crypto::{algorithms::encryption::AsymmetricEncryption, secret::SecretBytes},
+    error::SecurityModuleError,
+    latency::key_id_hash,
     traits::key_handle::KeyHandle,
 };
 use tracing::instrument;
@@ -23,53 +25,40 @@ impl KeyHandle for TpmProvider {
     /// # Returns
     ///
     /// A `Result` containing the signature as a `Vec<u8>` on success, or a `SecurityModuleError` on failure.
-    #[instrument]
+    #[instrument(skip_all, fields(crypto.key_id_hash = %key_id_hash(&self.key_id), crypto.payload.size = data.len()))]
     fn sign_data(&self, data: &[u8]) -> Result<Vec<u8>, SecurityModuleError> {
-        let key_handle = *self.key_handle.as_ref().unwrap().lock().unwrap();
-        let ticket = self
-            .handle
-            .as_ref()
-            .unwrap()
-            .lock()
-            .unwrap()
-            .hash(
-                MaxBuffer::try_from(data).unwrap(),
-                self.hash.unwrap().into(),
-                Hierarchy::Null,
-            )
-            .map_err(|e| SecurityModuleError::SigningError(e.to_string()))?;
-
-        let signature = match self.key_algorithm.as_ref().unwrap() {
-            AsymmetricEncryption::Rsa(_) => self
-                .handle
-                .as_ref()
-                .unwrap()
-                .lock()
-                .unwrap()
-                .sign(
-                    key_handle,
-                    ticket.0,
-                    SignatureScheme::RsaSsa {
-                        hash_scheme: HashScheme::new(self.hash.unwrap().into()),
-                    },
-                    ticket.1,
+        self.with_context(|context, key_handle| {
+            let ticket = context
+                .hash(
+                    MaxBuffer::try_from(data).unwrap(),
+                    self.hash.unwrap().into(),
+                    Hierarchy::Null,
                 )
-                .map_err(|e| SecurityModuleError::SigningError(e.to_string()))?,
-            AsymmetricEncryption::Ecc(ecc_scheme) => {
-                let signature_scheme: SignatureScheme = (*ecc_scheme).into();
-                self.handle
-                    .as_ref()
-                    .unwrap()
-                    .lock()
-                    .unwrap()
-                    .sign(key_handle, ticket.0, signature_scheme, ticket.1)
-                    .map_err(|e| SecurityModuleError::SigningError(e.to_string()))?
-            }
-        };
+                .map_err(|e| SecurityModuleError::SigningError(e.to_string()))?;
+
+            let signature = match self.key_algorithm.as_ref().unwrap() {
+                AsymmetricEncryption::Rsa(_) => context
+                    .sign(
+                        key_handle,
+                        ticket.0,
+                        SignatureScheme::RsaSsa {
+                            hash_scheme: HashScheme::new(self.hash.unwrap().into()),
+                        },
+                        ticket.1,
+                    )
+                    .map_err(|e| SecurityModuleError::SigningError(e.to_string()))?,
+                AsymmetricEncryption::Ecc(ecc_scheme) => {
+                    let signature_scheme: SignatureScheme = (*ecc_scheme).into();
+                    context
+                        .sign(key_handle, ticket.0, signature_scheme, ticket.1)
+                        .map_err(|e| SecurityModuleError::SigningError(e.to_string()))?
+                }
+            };
 
-        signature
-            .marshall()
-            .map_err(|e| SecurityModuleError::SigningError(e.to_string()))
+            signature
+                .marshall()
+                .map_err(|e| SecurityModuleError::SigningError(e.to_string()))
+        })
     }
 
     /// Decrypts the given encrypted data using the cryptographic key managed by the TPM provider.
@@ -81,53 +70,43 @@ impl KeyHandle for TpmProvider {
     /// # Returns
     ///
     /// A `Result` containing the decrypted data as a `Vec<u8>` on success, or a `SecurityModuleError` on failure.
-    #[instrument]
-    fn decrypt_data(&self, encrypted_data: &[u8]) -> Result<Vec<u8>, SecurityModuleError> {
-        let key_handle = *self.key_handle.as_ref().unwrap().lock().unwrap();
-
-        match self.key_algorithm.as_ref().unwrap() {
-            AsymmetricEncryption::Rsa(_) => {
-                let scheme =
-                    RsaDecryptionScheme::Oaep(HashScheme::new(self.hash.unwrap().into()));
-                let pub_key = PublicKeyRsa::try_from(encrypted_data)
-                    .map_err(|e| SecurityModuleError::DecryptionError(e.to_string()))?;
-                let decryption_result = self
-                    .handle
-                    .as_ref()
-                    .unwrap()
-                    .lock()
-                    .unwrap()
-                    .rsa_decrypt(
-                        key_handle,
-                        pub_key,
-                        scheme,
-                        Data::try_from(encrypted_data)
-                            .map_err(|e| SecurityModuleError::DecryptionError(e.to_string()))?,
-                    )
-                    .map_err(|e| SecurityModuleError::DecryptionError(e.to_string()))?;
-                Ok(decryption_result.to_vec())
-            }
-            AsymmetricEncryption::Ecc(_) => {
-                let initial_value = InitialValue::try_from(vec![0u8; 16])
-                    .map_err(|e| SecurityModuleError::DecryptionError(e.to_string()))?;
-                let (decrypted_data, _) = self
-                    .handle
-                    .as_ref()
-                    .unwrap()
-                    .lock()
-                    .unwrap()
-                    .encrypt_decrypt_2(
-                        key_handle,
-                        true,
-                        SymmetricMode::Cfb,
-                        MaxBuffer::try_from(encrypted_data)
-                            .map_err(|e| SecurityModuleError::DecryptionError(e.to_string()))?,
-                        initial_value,
-                    )
-                    .map_err(|e| SecurityModuleError::DecryptionError(e.to_string()))?;
-                Ok(decrypted_data.to_vec())
-            }
-        }
+    #[instrument(skip_all, fields(crypto.key_id_hash = %key_id_hash(&self.key_id), crypto.payload.size = encrypted_data.len()))]
+    fn decrypt_data(&self, encrypted_data: &[u8]) -> Result<SecretBytes, SecurityModuleError> {
+        self.with_context(
+            |context, key_handle| match self.key_algorithm.as_ref().unwrap() {
+                AsymmetricEncryption::Rsa(_) => {
+                    let scheme =
+                        RsaDecryptionScheme::Oaep(HashScheme::new(self.hash.unwrap().into()));
+                    let pub_key = PublicKeyRsa::try_from(encrypted_data)
+                        .map_err(|e| SecurityModuleError::DecryptionError(e.to_string()))?;
+                    let decryption_result = context
+                        .rsa_decrypt(
+                            key_handle,
+                            pub_key,
+                            scheme,
+                            Data::try_from(encrypted_data)
+                                .map_err(|e| SecurityModuleError::DecryptionError(e.to_string()))?,
+                        )
+                        .map_err(|e| SecurityModuleError::DecryptionError(e.to_string()))?;
+                    Ok(decryption_result.to_vec().into())
+                }
+                AsymmetricEncryption::Ecc(_) => {
+                    let initial_value = InitialValue::try_from(vec![0u8; 16])
+                        .map_err(|e| SecurityModuleError::DecryptionError(e.to_string()))?;
+                    let (decrypted_data, _) = context
+                        .encrypt_decrypt_2(
+                            key_handle,
+                            true,
+                            SymmetricMode::Cfb,
+                            MaxBuffer::try_from(encrypted_data)
+                                .map_err(|e| SecurityModuleError::DecryptionError(e.to_string()))?,
+                            initial_value,
+                        )
+                        .map_err(|e| SecurityModuleError::DecryptionError(e.to_string()))?;
+                    Ok(decrypted_data.to_vec().into())
+                }
+            },
+        )
     }
 
     /// Encrypts the given data using the cryptographic key managed by the TPM provider.
@@ -139,53 +118,43 @@ impl KeyHandle for TpmProvider {
     /// # Returns
     ///
     /// A `Result` containing the encrypted data as a `Vec<u8>` on success, or a `SecurityModuleError` on failure.
-    #[instrument]
+    #[instrument(skip_all, fields(crypto.key_id_hash = %key_id_hash(&self.key_id), crypto.payload.size = data.len()))]
     fn encrypt_data(&self, data: &[u8]) -> Result<Vec<u8>, SecurityModuleError> {
-        let key_handle = *self.key_handle.as_ref().unwrap().lock().unwrap();
-
-        match self.key_algorithm.as_ref().unwrap() {
-            AsymmetricEncryption::Rsa(_) => {
-                let scheme =
-                    RsaDecryptionScheme::Oaep(HashScheme::new(self.hash.unwrap().into()));
-                let message = PublicKeyRsa::try_from(data)
-                    .map_err(|e| SecurityModuleError::EncryptionError(e.to_string()))?;
-                let encryption_result = self
-                    .handle
-                    .as_ref()
-                    .unwrap()
-                    .lock()
-                    .unwrap()
-                    .rsa_encrypt(
-                        key_handle,
-                        message,
-                        scheme,
-                        Data::try_from(data)
-                            .map_err(|e| SecurityModuleError::EncryptionError(e.to_string()))?,
-                    )
-                    .map_err(|e| SecurityModuleError::EncryptionError(e.to_string()))?;
-                Ok(encryption_result.value().to_vec())
-            }
-            AsymmetricEncryption::Ecc(_) => {
-                let initial_value = InitialValue::try_from(vec![0u8; 16])
-                    .map_err(|e| SecurityModuleError::EncryptionError(e.to_string()))?;
-                let (encrypted_data, _) = self
-                    .handle
-                    .as_ref()
-                    .unwrap()
-                    .lock()
-                    .unwrap()
-                    .encrypt_decrypt_2(
-                        key_handle,
-                        false,
-                        SymmetricMode::Cfb,
-                        MaxBuffer::try_from(data)
-                            .map_err(|e| SecurityModuleError::EncryptionError(e.to_string()))?,
-                        initial_value,
-                    )
-                    .map_err(|e| SecurityModuleError::EncryptionError(e.to_string()))?;
-                Ok(encrypted_data.to_vec())
-            }
-        }
+        self.with_context(
+            |context, key_handle| match self.key_algorithm.as_ref().unwrap() {
+                AsymmetricEncryption::Rsa(_) => {
+                    let scheme =
+                        RsaDecryptionScheme::Oaep(HashScheme::new(self.hash.unwrap().into()));
+                    let message = PublicKeyRsa::try_from(data)
+                        .map_err(|e| SecurityModuleError::EncryptionError(e.to_string()))?;
+                    let encryption_result = context
+                        .rsa_encrypt(
+                            key_handle,
+                            message,
+                            scheme,
+                            Data::try_from(data)
+                                .map_err(|e| SecurityModuleError::EncryptionError(e.to_string()))?,
+                        )
+                        .map_err(|e| SecurityModuleError::EncryptionError(e.to_string()))?;
+                    Ok(encryption_result.value().to_vec())
+                }
+                AsymmetricEncryption::Ecc(_) => {
+                    let initial_value = InitialValue::try_from(vec![0u8; 16])
+                        .map_err(|e| SecurityModuleError::EncryptionError(e.to_string()))?;
+                    let (encrypted_data, _) = context
+                        .encrypt_decrypt_2(
+                            key_handle,
+                            false,
+                            SymmetricMode::Cfb,
+                            MaxBuffer::try_from(data)
+                                .map_err(|e| SecurityModuleError::EncryptionError(e.to_string()))?,
+                            initial_value,
+                        )
+                        .map_err(|e| SecurityModuleError::EncryptionError(e.to_string()))?;
+                    Ok(encrypted_data.to_vec())
+                }
+            },
+        )
     }
 
     /// Verifies the signature of the given data using the cryptographic key managed by the TPM provider.
@@ -199,79 +168,67 @@ impl KeyHandle for TpmProvider {
     ///
     /// A `Result` containing a boolean indicating whether the signature is valid (`true`) or not (`false`),
     /// or a `SecurityModuleError` on failure.
-    #[instrument]
+    #[instrument(skip_all, fields(crypto.key_id_hash = %key_id_hash(&self.key_id), crypto.payload.size = data.len(), crypto.signature.size = signature.len()))]
     fn verify_signature(&self, data: &[u8], signature: &[u8]) -> Result<bool, SecurityModuleError> {
-        let key_handle = *self.key_handle.as_ref().unwrap().lock().unwrap();
-        let digest = self
-            .handle
-            .as_ref()
-            .unwrap()
-            .lock()
-            .unwrap()
-            .hash(
-                MaxBuffer::try_from(data).unwrap(),
-                self.hash.unwrap().into(),
-                Hierarchy::Null,
-            )
-            .map_err(|e| SecurityModuleError::SignatureVerificationError(e.to_string()))?
-            .0;
+        self.with_context(|context, key_handle| {
+            let digest = context
+                .hash(
+                    MaxBuffer::try_from(data).unwrap(),
+                    self.hash.unwrap().into(),
+                    Hierarchy::Null,
+                )
+                .map_err(|e| SecurityModuleError::SignatureVerificationError(e.to_string()))?
+                .0;
 
-        let verification_result = match self.key_algorithm.as_ref().unwrap() {
-            AsymmetricEncryption::Rsa(_) => {
-                let signature = PublicKeyRsa::try_from(signature)
-                    .map_err(|e| SecurityModuleError::SignatureVerificationError(e.to_string()))?;
-                let rsa_signature =
-                    RsaSignature::create(self.hash.unwrap().into(), signature).map_err(
-                        |e| SecurityModuleError::SignatureVerificationError(e.to_string()),
-                    )?;
-                self.handle
-                    .as_ref()
-                    .unwrap()
-                    .lock()
-                    .unwrap()
-                    .verify_signature(key_handle, digest, Signature::RsaSsa(rsa_signature))
-                    .is_ok()
-            }
-            AsymmetricEncryption::Ecc(ecc_scheme) => {
-                let signature_scheme: SignatureScheme = (*ecc_scheme).into();
-                let (signature_r, signature_s) = match signature.split_at(signature.len() / 2) {
-                    (&[], &[]) => {
-                        return Err(SecurityModuleError::SignatureVerificationError(
-                            "Invalid signature length".to_string(),
-                        ))
-                    }
-                    (r, s) => (
-                        EccParameter::try_from(r).map_err(|e| {
+            let verification_result = match self.key_algorithm.as_ref().unwrap() {
+                AsymmetricEncryption::Rsa(_) => {
+                    let signature = PublicKeyRsa::try_from(signature).map_err(|e| {
+                        SecurityModuleError::SignatureVerificationError(e.to_string())
+                    })?;
+                    let rsa_signature = RsaSignature::create(self.hash.unwrap().into(), signature)
+                        .map_err(|e| {
                             SecurityModuleError::SignatureVerificationError(e.to_string())
-                        })?,
-                        EccParameter::try_from(s).map_err(|e| {
+                        })?;
+                    context
+                        .verify_signature(key_handle, digest, Signature::RsaSsa(rsa_signature))
+                        .is_ok()
+                }
+                AsymmetricEncryption::Ecc(ecc_scheme) => {
+                    let signature_scheme: SignatureScheme = (*ecc_scheme).into();
+                    let (signature_r, signature_s) = match signature.split_at(signature.len() / 2) {
+                        (&[], &[]) => {
+                            return Err(SecurityModuleError::SignatureVerificationError(
+                                "Invalid signature length".to_string(),
+                            ))
+                        }
+                        (r, s) => (
+                            EccParameter::try_from(r).map_err(|e| {
+                                SecurityModuleError::SignatureVerificationError(e.to_string())
+                            })?,
+                            EccParameter::try_from(s).map_err(|e| {
+                                SecurityModuleError::SignatureVerificationError(e.to_string())
+                            })?,
+                        ),
+                    };
+                    let ecc_signature =
+                        EccSignature::create(self.hash.unwrap().into(), signature_r, signature_s)
+                            .map_err(|e| {
                             SecurityModuleError::SignatureVerificationError(e.to_string())
-                        })?,
-                    ),
-                };
-                let ecc_signature = EccSignature::create(
-                    self.hash.unwrap().into(),
-                    signature_r,
-                    signature_s,
-                )
-                .map_err(|e| SecurityModuleError::SignatureVerificationError(e.to_string()))?;
-                let signature = match signature_scheme {
-                    SignatureScheme::EcDsa { .. } => Signature::EcDsa(ecc_signature),
-                    SignatureScheme::EcDaa { .. } => Signature::EcDaa(ecc_signature),
-                    SignatureScheme::Sm2 { .. } => Signature::Sm2(ecc_signature),
-                    SignatureScheme::EcSchnorr { .. } => Signature::EcSchnorr(ecc_signature),
-                    _ => unreachable!(),
-                };
-                self.handle
-                    .as_ref()
-                    .unwrap()
-                    .lock()
-                    .unwrap()
-                    .verify_signature(key_handle, digest, signature)
-                    .is_ok()
-            }
-        };
+                        })?;
+                    let signature = match signature_scheme {
+                        SignatureScheme::EcDsa { .. } => Signature::EcDsa(ecc_signature),
+                        SignatureScheme::EcDaa { .. } => Signature::EcDaa(ecc_signature),
+                        SignatureScheme::Sm2 { .. } => Signature::Sm2(ecc_signature),
+                        SignatureScheme::EcSchnorr { .. } => Signature::EcSchnorr(ecc_signature),
+                        _ => unreachable!(),
+                    };
+                    context
+                        .verify_signature(key_handle, digest, signature)
+                        .is_ok()
+                }
+            };
 
-        Ok(verification_result)
+            Ok(verification_result)
+        })
     }
 }
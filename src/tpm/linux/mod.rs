@@ -1,26 +1,38 @@
-use crate::common::crypto::{
-    algorithms::{
-        encryption::{
-            AsymmetricEncryption, BlockCiphers, EccCurves, EccSchemeAlgorithm, SymmetricMode,
+use crate::common::{
+    crypto::{
+        algorithms::{
+            encryption::{
+                AsymmetricEncryption, BlockCiphers, EccCurves, EccSchemeAlgorithm, SymmetricMode,
+            },
+            hashes::{Hash, Sha2Bits, Sha3Bits},
+            KeyBits,
         },
-        hashes::{Hash, Sha2Bits, Sha3Bits},
-        KeyBits,
+        key_metadata::KeyMetadata,
+        public_key::PublicKey,
+        KeyUsage,
     },
-    KeyUsage,
+    error::SecurityModuleError,
+    session_pool::{SessionPool, SessionPoolConfig},
+};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
 };
-use std::sync::{Arc, Mutex};
 use tss_esapi::{
-    handles::KeyHandle as TssKeyHandle,
+    handles::{KeyHandle as TssKeyHandle, PersistentTpmHandle, TpmHandle},
     interface_types::{
         algorithm::{HashingAlgorithm, PublicAlgorithm, SymmetricMode as TssSymmetricMode},
         ecc::EccCurve,
         key_bits::{AesKeyBits, CamelliaKeyBits, RsaKeyBits},
     },
-    structures::{EcDaaScheme, EccScheme, HashScheme, SignatureScheme, SymmetricDefinitionObject},
-    Context,
+    structures::{
+        EcDaaScheme, EccScheme, HashScheme, Public, SignatureScheme, SymmetricDefinitionObject,
+    },
+    Context, TctiNameConf,
 };
 
 pub mod key_handle;
+pub mod nv_counter;
 pub mod provider;
 
 /// A TPM-based cryptographic provider for managing cryptographic keys and performing
@@ -40,6 +52,42 @@ pub struct TpmProvider {
     pub(super) sym_algorithm: Option<BlockCiphers>,
     pub(super) hash: Option<Hash>,
     pub(super) key_usages: Option<Vec<KeyUsage>>,
+    pub(super) tcti: Option<TctiNameConf>,
+    pub(super) persistent_handle: Option<PersistentTpmHandle>,
+    pub(super) sessions: Option<Arc<SessionPool<TpmSession>>>,
+    /// Metadata of the created or loaded key, computed once so that it is served without
+    /// reading the public area from the TPM.
+    pub(super) metadata: Option<KeyMetadata>,
+}
+
+/// A TPM context handed out by the session pool of a `TpmProvider`.
+///
+/// Object handles are only valid within the context they were created in, so every session
+/// keeps track of the persistent keys it has already resolved.
+pub(super) struct TpmSession {
+    pub(super) context: Context,
+    key_handles: HashMap<u32, TssKeyHandle>,
+}
+
+impl TpmSession {
+    /// Returns the handle of the persistent key within this session's context.
+    fn key_handle(
+        &mut self,
+        persistent_handle: PersistentTpmHandle,
+    ) -> Result<TssKeyHandle, SecurityModuleError> {
+        let tpm_handle = u32::from(persistent_handle);
+        if let Some(key_handle) = self.key_handles.get(&tpm_handle) {
+            return Ok(*key_handle);
+        }
+
+        let key_handle: TssKeyHandle = self
+            .context
+            .tr_from_tpm_public(TpmHandle::Persistent(persistent_handle))
+            .map_err(|e| SecurityModuleError::InitializationError(e.to_string()))?
+            .into();
+        self.key_handles.insert(tpm_handle, key_handle);
+        Ok(key_handle)
+    }
 }
 
 impl TpmProvider {
@@ -57,7 +105,130 @@ impl TpmProvider {
             sym_algorithm: None,
             hash: None,
             key_usages: None,
+            tcti: None,
+            persistent_handle: None,
+            sessions: None,
+            metadata: None,
+        }
+    }
+
+    /// (Re)creates the session pool if its configuration changed.
+    ///
+    /// Must be called after `initialize_module`, which determines the TCTI the sessions connect to.
+    pub(super) fn configure_sessions(&mut self, config: SessionPoolConfig) {
+        if self
+            .sessions
+            .as_ref()
+            .is_some_and(|sessions| *sessions.config() == config)
+        {
+            return;
+        }
+        let Some(tcti) = self.tcti.clone() else {
+            return;
+        };
+
+        self.sessions = Some(Arc::new(SessionPool::new(config, move || {
+            let context = Context::new(tcti.clone())
+                .map_err(|e| SecurityModuleError::InitializationError(e.to_string()))?;
+            Ok(TpmSession {
+                context,
+                key_handles: HashMap::new(),
+            })
+        })));
+    }
+
+    /// Runs `operation` on a TPM context with the key of this provider.
+    ///
+    /// Operations on persistent keys are distributed over the session pool, so that concurrent
+    /// operations do not serialize on a single context. Transient keys are only valid in the
+    /// context that loaded them, so operations on them use the context of the provider.
+    pub(super) fn with_context<T>(
+        &self,
+        operation: impl FnOnce(&mut Context, TssKeyHandle) -> Result<T, SecurityModuleError>,
+    ) -> Result<T, SecurityModuleError> {
+        if let (Some(sessions), Some(persistent_handle)) = (&self.sessions, self.persistent_handle)
+        {
+            let mut session = sessions.acquire()?;
+            let key_handle = session.key_handle(persistent_handle)?;
+            return operation(&mut session.context, key_handle);
+        }
+
+        let key_handle = *self
+            .key_handle
+            .as_ref()
+            .ok_or(SecurityModuleError::KeyError)?
+            .lock()
+            .unwrap();
+        let mut context = self
+            .handle
+            .as_ref()
+            .ok_or(SecurityModuleError::InitializationError(
+                "Module not initialized".to_owned(),
+            ))?
+            .lock()
+            .unwrap();
+        operation(&mut context, key_handle)
+    }
+
+    /// Reads the public area of the key of this provider from the TPM.
+    pub(super) fn read_key_metadata(&self) -> Result<KeyMetadata, SecurityModuleError> {
+        let public = self.with_context(|context, key_handle| {
+            context
+                .read_public(key_handle)
+                .map(|(public, _, _)| public)
+                .map_err(|e| SecurityModuleError::InitializationError(e.to_string()))
+        })?;
+        self.key_metadata_from_public(&public)
+    }
+
+    /// Computes the metadata of the key of this provider from its public area.
+    pub(super) fn key_metadata_from_public(
+        &self,
+        public: &Public,
+    ) -> Result<KeyMetadata, SecurityModuleError> {
+        let algorithm = self
+            .key_algorithm
+            .ok_or(SecurityModuleError::UnsupportedAlgorithm)?;
+        let hash = self.hash.ok_or(SecurityModuleError::UnsupportedAlgorithm)?;
+        KeyMetadata::new(
+            self.key_id.clone(),
+            public_key_from_tpm(public, algorithm, hash)?,
+        )
+    }
+}
+
+/// Converts the public area of a TPM key into a `PublicKey`.
+///
+/// # Arguments
+///
+/// * `public` - The public area as returned by the TPM.
+/// * `algorithm` - The asymmetric algorithm of the key pair.
+/// * `hash` - The hash algorithm used for signing.
+///
+/// # Returns
+///
+/// A `Result` containing the `PublicKey` on success, or a `SecurityModuleError` if the public
+/// area does not describe an RSA or ECC key.
+pub fn public_key_from_tpm(
+    public: &Public,
+    algorithm: AsymmetricEncryption,
+    hash: Hash,
+) -> Result<PublicKey, SecurityModuleError> {
+    match public {
+        Public::Rsa {
+            parameters, unique, ..
+        } => {
+            // The TPM encodes the default exponent 2^16 + 1 as zero.
+            let exponent = match parameters.exponent().value() {
+                0 => 65537u32,
+                exponent => exponent,
+            };
+            PublicKey::from_rsa_components(unique.value(), &exponent.to_be_bytes(), algorithm, hash)
+        }
+        Public::Ecc { unique, .. } => {
+            PublicKey::from_ec_coordinates(unique.x().value(), unique.y().value(), algorithm, hash)
         }
+        _ => Err(SecurityModuleError::UnsupportedAlgorithm),
     }
 }
 
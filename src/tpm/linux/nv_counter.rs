@@ -0,0 +1,123 @@
+use crate::common::{anti_rollback::MonotonicCounter, error::SecurityModuleError};
+use std::{
+    fmt,
+    sync::{Mutex, MutexGuard},
+};
+use tss_esapi::{
+    attributes::NvIndexAttributesBuilder,
+    constants::NvIndexType,
+    handles::{NvIndexHandle, NvIndexTpmHandle, TpmHandle},
+    interface_types::{
+        algorithm::HashingAlgorithm,
+        resource_handles::{NvAuth, Provision},
+        session_handles::AuthSession,
+    },
+    structures::NvPublicBuilder,
+    Context, TctiNameConf,
+};
+
+/// A `MonotonicCounter` kept in an NV counter index of the TPM, which the TPM only allows to be
+/// incremented, see `anti_rollback`.
+///
+/// The index is defined with owner authorization when it does not exist yet. The TPM assigns a
+/// new counter a value at least as large as any counter it held before, so deleting and
+/// redefining the index does not roll it back either.
+pub struct TpmNvCounter {
+    context: Mutex<Context>,
+    index: NvIndexTpmHandle,
+    handle: NvIndexHandle,
+}
+
+impl TpmNvCounter {
+    /// Opens the NV counter at `index`, e.g. `0x0150_0016` in the owner range, defining it if it
+    /// does not exist yet.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the counter on success, or a
+    /// `SecurityModuleError::InitializationError` if the TPM cannot be reached or the index cannot
+    /// be defined.
+    pub fn open(tcti: TctiNameConf, index: u32) -> Result<Self, SecurityModuleError> {
+        let mut context = Context::new(tcti)
+            .map_err(|e| SecurityModuleError::InitializationError(e.to_string()))?;
+        let index = NvIndexTpmHandle::new(index)
+            .map_err(|e| SecurityModuleError::InitializationError(e.to_string()))?;
+        let handle = match context.tr_from_tpm_public(TpmHandle::NvIndex(index)) {
+            Ok(handle) => NvIndexHandle::from(handle),
+            Err(_) => define(&mut context, index)?,
+        };
+        Ok(Self {
+            context: Mutex::new(context),
+            index,
+            handle,
+        })
+    }
+
+    fn context(&self) -> MutexGuard<'_, Context> {
+        self.context.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Defines a counter index of 8 bytes, readable and writable with owner authorization, and
+/// increments it once, as counters cannot be read before.
+fn define(
+    context: &mut Context,
+    index: NvIndexTpmHandle,
+) -> Result<NvIndexHandle, SecurityModuleError> {
+    let attributes = NvIndexAttributesBuilder::new()
+        .with_nv_index_type(NvIndexType::Counter)
+        .with_owner_read(true)
+        .with_owner_write(true)
+        .build()
+        .map_err(|e| SecurityModuleError::InitializationError(e.to_string()))?;
+    let public = NvPublicBuilder::new()
+        .with_nv_index(index)
+        .with_index_name_algorithm(HashingAlgorithm::Sha256)
+        .with_index_attributes(attributes)
+        .with_data_area_size(8)
+        .build()
+        .map_err(|e| SecurityModuleError::InitializationError(e.to_string()))?;
+    context
+        .execute_with_session(Some(AuthSession::Password), |context| {
+            let handle = context.nv_define_space(Provision::Owner, None, public)?;
+            context.nv_increment(NvAuth::Owner, handle)?;
+            Ok(handle)
+        })
+        .map_err(|e: tss_esapi::Error| SecurityModuleError::InitializationError(e.to_string()))
+}
+
+impl MonotonicCounter for TpmNvCounter {
+    fn read(&self) -> Result<u64, SecurityModuleError> {
+        let data = self
+            .context()
+            .execute_with_session(Some(AuthSession::Password), |context| {
+                context.nv_read(NvAuth::Owner, self.handle, 8, 0)
+            })
+            .map_err(|e| counter_error("read", e))?;
+        let value = data.value().try_into().map_err(|_| {
+            SecurityModuleError::SecretStorage("The TPM counter does not hold 8 bytes".to_owned())
+        })?;
+        Ok(u64::from_be_bytes(value))
+    }
+
+    fn increment(&self) -> Result<u64, SecurityModuleError> {
+        self.context()
+            .execute_with_session(Some(AuthSession::Password), |context| {
+                context.nv_increment(NvAuth::Owner, self.handle)
+            })
+            .map_err(|e| counter_error("increment", e))?;
+        self.read()
+    }
+}
+
+impl fmt::Debug for TpmNvCounter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TpmNvCounter")
+            .field("index", &format_args!("{:#010x}", u32::from(self.index)))
+            .finish_non_exhaustive()
+    }
+}
+
+fn counter_error(action: &str, e: tss_esapi::Error) -> SecurityModuleError {
+    SecurityModuleError::SecretStorage(format!("Cannot {} the TPM counter: {}", action, e))
+}
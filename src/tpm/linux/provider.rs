@@ -1,12 +1,18 @@
 use super::TpmProvider;
 use crate::{
     common::{
-        crypto::{algorithms::encryption::AsymmetricEncryption, KeyUsage},
+        crypto::{
+            algorithms::encryption::AsymmetricEncryption, key_metadata::KeyMetadata,
+            key_spec::KeySpec, KeyUsage,
+        },
         error::SecurityModuleError,
+        latency::key_id_hash,
+        session_pool::SessionPoolMetrics,
         traits::module_provider::Provider,
     },
     tpm::TpmConfig,
 };
+use crypto_layer_core::CoreError;
 use std::any::Any;
 use std::sync::{Arc, Mutex};
 use tracing::instrument;
@@ -20,9 +26,11 @@ use tss_esapi::{
         resource_handles::{Hierarchy, Provision},
     },
     structures::{
-        Digest, EccPoint, HashScheme, KeyDerivationFunctionScheme, Private, PublicBuilder,
-        PublicKeyRsa, PublicRsaParameters, RsaExponent, RsaScheme,
+        Digest, EccPoint, EncryptedSecret, HashScheme, KeyDerivationFunctionScheme, Private,
+        Public, PublicBuilder, PublicKeyRsa, PublicRsaParameters, RsaExponent, RsaScheme,
+        SymmetricDefinitionObject,
     },
+    traits::UnMarshall,
     Context, TctiNameConf,
 };
 use tss_esapi::{interface_types::dynamic_handles::Persistent, structures::PublicEccParameters};
@@ -47,7 +55,7 @@ impl Provider for TpmProvider {
     ///
     /// A `Result` that, on success, contains `Ok(())`, indicating that the key was created successfully.
     /// On failure, it returns a `SecurityModuleError`.
-    #[instrument]
+    #[instrument(skip_all, fields(crypto.key_id_hash = %key_id_hash(key_id)))]
     fn create_key(
         &mut self,
         key_id: &str,
@@ -59,6 +67,7 @@ impl Provider for TpmProvider {
         self.sym_algorithm = Some(config.sym_algorithm);
         self.hash = Some(config.hash);
         self.key_usages = Some(config.key_usages.clone());
+        self.configure_sessions(config.session_pool);
 
         let primary_pub = match self.key_algorithm.as_ref().unwrap() {
             AsymmetricEncryption::Rsa(key_bits) => PublicBuilder::new()
@@ -152,8 +161,8 @@ impl Provider for TpmProvider {
             .create_primary(Hierarchy::Owner, primary_pub, None, None, None, None)
             .unwrap();
 
-        let persistent_handle =
-            Persistent::Persistent(PersistentTpmHandle::new(key_handle.key_handle.into()).unwrap());
+        let persistent_tpm_handle = PersistentTpmHandle::new(key_handle.key_handle.into()).unwrap();
+        let persistent_handle = Persistent::Persistent(persistent_tpm_handle);
 
         self.key_handle = Some(Arc::new(Mutex::new(key_handle.key_handle)));
 
@@ -170,8 +179,14 @@ impl Provider for TpmProvider {
             )
             .expect("Failed to make key persistent");
 
+        // Persistent keys can be resolved in every context, so operations use the session pool.
+        self.persistent_handle = Some(persistent_tpm_handle);
         self.key_id = key_id.to_string();
 
+        // The public area is returned on creation, so the metadata is computed without another
+        // round-trip to the TPM.
+        self.metadata = Some(self.key_metadata_from_public(&key_handle.out_public)?);
+
         Ok(())
     }
 
@@ -193,7 +208,7 @@ impl Provider for TpmProvider {
     ///
     /// A `Result` that, on success, contains `Ok(())`, indicating that the key was loaded successfully.
     /// On failure, it returns a `SecurityModuleError`.
-    #[instrument]
+    #[instrument(skip_all, fields(crypto.key_id_hash = %key_id_hash(key_id)))]
     fn load_key(&mut self, key_id: &str, config: Box<dyn Any>) -> Result<(), SecurityModuleError> {
         let config = config.downcast_ref::<TpmConfig>().unwrap();
 
@@ -201,6 +216,7 @@ impl Provider for TpmProvider {
         self.sym_algorithm = Some(config.sym_algorithm);
         self.hash = Some(config.hash);
         self.key_usages = Some(config.key_usages.clone());
+        self.configure_sessions(config.session_pool);
 
         // Start an authorization session
         let session = self
@@ -315,8 +331,112 @@ impl Provider for TpmProvider {
             .load(TssKeyHandle::Null, private, public)
             .unwrap();
 
+        // The loaded key is transient and only valid in the context of this provider.
+        self.key_handle = Some(Arc::new(Mutex::new(key_handle)));
+        self.persistent_handle = None;
+        self.key_id = key_id.to_string();
+
+        self.metadata = match self.read_key_metadata() {
+            Ok(metadata) => Some(metadata),
+            Err(e) => {
+                tracing::warn!(error = %e, "Failed to read the public key of the loaded key");
+                None
+            }
+        };
+
+        Ok(())
+    }
+
+    /// Imports a key that was duplicated for the current key of the provider, e.g. with
+    /// `tpm2_duplicate`, and makes it the current key.
+    ///
+    /// The wrapped key is the `TPM2B_PUBLIC` of the key, followed by the `TPM2B_PRIVATE`
+    /// duplicate and the `TPM2B_ENCRYPTED_SECRET` seed, which is the concatenation of the files
+    /// written by `tpm2_duplicate`. Duplicates with an inner wrapper are not supported. Like
+    /// loaded keys, the imported key is transient and only valid in the context of this provider.
+    ///
+    /// # Arguments
+    ///
+    /// * `key_id` - A string slice that uniquely identifies the imported key.
+    /// * `wrapped_key` - The public area, duplicate and seed of the key.
+    /// * `wrapping_key_id` - The id of the current key, which becomes the parent of the imported key.
+    /// * `spec` - The algorithm, hash and usages of the imported key.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` that, on success, contains `Ok(())`, indicating that the key was imported successfully.
+    /// On failure, it returns a `SecurityModuleError::KeyError` if `wrapping_key_id` is not the
+    /// current key, or a `SecurityModuleError` of the TPM.
+    #[instrument(skip_all, fields(crypto.key_id_hash = %key_id_hash(key_id)))]
+    fn import_wrapped_key(
+        &mut self,
+        key_id: &str,
+        wrapped_key: &[u8],
+        wrapping_key_id: &str,
+        spec: &KeySpec,
+    ) -> Result<(), SecurityModuleError> {
+        if wrapping_key_id != self.key_id {
+            return Err(SecurityModuleError::KeyError);
+        }
+        let parent = *self
+            .key_handle
+            .as_ref()
+            .ok_or(SecurityModuleError::KeyError)?
+            .lock()
+            .unwrap();
+        let config = TpmConfig::from_spec(spec)?
+            .downcast::<TpmConfig>()
+            .map_err(|_| {
+                SecurityModuleError::InitializationError("Failed to initialize config".to_owned())
+            })?;
+
+        let mut data = wrapped_key;
+        let public = Public::unmarshall(tpm2b(&mut data)?)
+            .map_err(|_| SecurityModuleError::Encoding(CoreError::InvalidField("public")))?;
+        let duplicate = Private::try_from(tpm2b(&mut data)?.to_vec())
+            .map_err(|_| SecurityModuleError::Encoding(CoreError::InvalidField("duplicate")))?;
+        let seed = EncryptedSecret::try_from(tpm2b(&mut data)?.to_vec())
+            .map_err(|_| SecurityModuleError::Encoding(CoreError::InvalidField("seed")))?;
+        if !data.is_empty() {
+            return Err(SecurityModuleError::Encoding(CoreError::InvalidField(
+                "wrapped key",
+            )));
+        }
+
+        let key_handle = {
+            let mut context = self
+                .handle
+                .as_ref()
+                .ok_or_else(|| {
+                    SecurityModuleError::InitializationError("Module not initialized".to_owned())
+                })?
+                .lock()
+                .unwrap();
+            let private = context
+                .import(
+                    parent.into(),
+                    None,
+                    public.clone(),
+                    duplicate,
+                    seed,
+                    SymmetricDefinitionObject::Null,
+                )
+                .map_err(|e| SecurityModuleError::InitializationError(e.to_string()))?;
+            context
+                .load(parent, private, public.clone())
+                .map_err(|e| SecurityModuleError::InitializationError(e.to_string()))?
+        };
+
+        self.key_algorithm = Some(config.key_algorithm);
+        self.sym_algorithm = Some(config.sym_algorithm);
+        self.hash = Some(config.hash);
+        self.key_usages = Some(config.key_usages.clone());
+        self.configure_sessions(config.session_pool);
+
         self.key_handle = Some(Arc::new(Mutex::new(key_handle)));
+        self.persistent_handle = None;
         self.key_id = key_id.to_string();
+        self.metadata = Some(self.key_metadata_from_public(&public)?);
 
         Ok(())
     }
@@ -330,15 +450,66 @@ impl Provider for TpmProvider {
     ///
     /// A `Result` that, on success, contains `Ok(())`, indicating that the module was initialized successfully.
     /// On failure, it returns a `SecurityModuleError`.
-    #[instrument]
+    #[instrument(skip_all, fields(crypto.key_id_hash = %key_id_hash(&self.key_id)))]
     fn initialize_module(&mut self) -> Result<(), SecurityModuleError> {
         let tcti = TctiNameConf::from_environment_variable().unwrap();
 
-        let context = Context::new(tcti)
+        let context = Context::new(tcti.clone())
             .map_err(|e| SecurityModuleError::InitializationError(e.to_string()))?;
 
         self.handle = Some(Arc::new(Mutex::new(context)));
+        self.tcti = Some(tcti);
 
         Ok(())
     }
+
+    /// Returns the utilization of the pool of TPM sessions.
+    ///
+    /// # Returns
+    ///
+    /// The current `SessionPoolMetrics`, or `None` if no key has been created or loaded yet.
+    #[instrument(skip_all, fields(crypto.key_id_hash = %key_id_hash(&self.key_id)))]
+    fn session_pool_metrics(&self) -> Option<SessionPoolMetrics> {
+        self.sessions.as_ref().map(|sessions| sessions.metrics())
+    }
+
+    /// Returns the metadata cached when the key was created or loaded.
+    ///
+    /// Keys are not certified by an attestation key, so the metadata contains no attestation data.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `KeyMetadata` on success, or a `SecurityModuleError` if no key has
+    /// been created or loaded.
+    #[instrument(skip_all, fields(crypto.key_id_hash = %key_id_hash(&self.key_id)))]
+    fn key_metadata(&self) -> Result<KeyMetadata, SecurityModuleError> {
+        self.metadata.clone().ok_or(SecurityModuleError::KeyError)
+    }
+
+    /// Reads the public area of the key from the TPM again and updates the cached metadata.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the fetched `KeyMetadata` on success, or a `SecurityModuleError` on failure.
+    #[instrument(skip_all, fields(crypto.key_id_hash = %key_id_hash(&self.key_id)))]
+    fn refresh_key_metadata(&mut self) -> Result<KeyMetadata, SecurityModuleError> {
+        let metadata = self.read_key_metadata()?;
+        self.metadata = Some(metadata.clone());
+        Ok(metadata)
+    }
+}
+
+/// Splits a `TPM2B` structure, a big-endian u16 size followed by the content, off `data` and
+/// returns its content.
+fn tpm2b<'a>(data: &mut &'a [u8]) -> Result<&'a [u8], SecurityModuleError> {
+    let [high, low, rest @ ..] = *data else {
+        return Err(CoreError::Truncated.into());
+    };
+    let size = usize::from(u16::from_be_bytes([*high, *low]));
+    if rest.len() < size {
+        return Err(CoreError::Truncated.into());
+    }
+    let (content, rest) = rest.split_at(size);
+    *data = rest;
+    Ok(content)
 }
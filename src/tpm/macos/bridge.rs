@@ -0,0 +1,595 @@
+//! The boundary between the Secure Enclave provider and the Swift bindings.
+//!
+//! Every call into Swift goes through a `Bridge`. A live bridge calls the bindings, a recording
+//! bridge additionally captures every request and response in a `Cassette`, and a replaying bridge
+//! answers requests from a cassette without calling Swift at all. Cassettes recorded on a Mac can
+//! therefore be replayed on Linux, which regression-tests the Rust side of the provider (error
+//! mapping, response decoding, metadata handling) without Apple hardware:
+//!
+//! ```rust,ignore
+//! // On a Mac:
+//! let bridge = Bridge::recording();
+//! let mut provider = SecureEnclaveProvider::with_bridge("key".to_owned(), bridge.clone());
+//! // ... exercise the provider ...
+//! bridge.cassette().unwrap().save("sign_p256.json")?;
+//!
+//! // Anywhere:
+//! let bridge = Bridge::replay(Cassette::load("sign_p256.json")?);
+//! let mut provider = SecureEnclaveProvider::with_bridge("key".to_owned(), bridge);
+//! ```
+
+use super::{
+    dispatch::{op_code, BoundaryStats},
+    response::{Response, SwiftError},
+};
+use crate::common::{
+    crypto::redact::Redacted, error::SecurityModuleError, telemetry::CorrelationId,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt, fs,
+    path::Path,
+    sync::{Arc, Mutex, MutexGuard},
+    time::Instant,
+};
+use tracing::{field::Empty, Instrument, Span};
+
+/// A call into the Swift bindings with all of its arguments.
+///
+/// The `Debug` representation redacts binary arguments, see `crypto::redact`.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "call", rename_all = "snake_case")]
+pub enum Request {
+    CreateKey {
+        key_id: String,
+        key_type: String,
+    },
+    LoadKey {
+        key_id: String,
+        key_type: String,
+        hash: String,
+    },
+    BridgeVersion,
+    InitializeModule,
+    ShutdownModule,
+    SignData {
+        key_id: String,
+        #[serde(with = "base64_bytes")]
+        data: Vec<u8>,
+        algorithm: String,
+        hash: String,
+    },
+    DecryptData {
+        key_id: String,
+        #[serde(with = "base64_bytes")]
+        data: Vec<u8>,
+        algorithm: String,
+        hash: String,
+    },
+    EncryptData {
+        key_id: String,
+        #[serde(with = "base64_bytes")]
+        data: Vec<u8>,
+        algorithm: String,
+        hash: String,
+    },
+    VerifySignature {
+        key_id: String,
+        #[serde(with = "base64_bytes")]
+        data: Vec<u8>,
+        #[serde(with = "base64_bytes")]
+        signature: Vec<u8>,
+        algorithm: String,
+        hash: String,
+    },
+    GetPublicKey {
+        key_id: String,
+        algorithm: String,
+    },
+    DeriveSharedSecret {
+        key_id: String,
+        #[serde(with = "base64_bytes")]
+        peer_public_key: Vec<u8>,
+        algorithm: String,
+    },
+    ReadMetadata {
+        key_id: String,
+    },
+    /// Stores `record`, a `KeyRecord` serialized as JSON, see `metadata_store`.
+    WriteMetadata {
+        key_id: String,
+        record: String,
+    },
+    DeleteMetadata {
+        key_id: String,
+    },
+    ListMetadata,
+}
+
+impl Request {
+    /// Returns the name of the called function of the bindings, without its prefix.
+    pub fn method(&self) -> &'static str {
+        match self {
+            Request::CreateKey { .. } => "create_key",
+            Request::LoadKey { .. } => "load_key",
+            Request::BridgeVersion => "bridge_version",
+            Request::InitializeModule => "initialize_module",
+            Request::ShutdownModule => "shutdown_module",
+            Request::SignData { .. } => "sign_data",
+            Request::DecryptData { .. } => "decrypt_data",
+            Request::EncryptData { .. } => "encrypt_data",
+            Request::VerifySignature { .. } => "verify_signature",
+            Request::GetPublicKey { .. } => "get_public_key",
+            Request::DeriveSharedSecret { .. } => "derive_shared_secret",
+            Request::ReadMetadata { .. } => "read_metadata",
+            Request::WriteMetadata { .. } => "write_metadata",
+            Request::DeleteMetadata { .. } => "delete_metadata",
+            Request::ListMetadata => "list_metadata",
+        }
+    }
+
+    /// Returns the op code `rustcall_dispatch` selects the operation with, see `dispatch::op_code`.
+    pub fn op_code(&self) -> u32 {
+        match self {
+            Request::BridgeVersion => op_code::BRIDGE_VERSION,
+            Request::InitializeModule => op_code::INITIALIZE_MODULE,
+            Request::ShutdownModule => op_code::SHUTDOWN_MODULE,
+            Request::CreateKey { .. } => op_code::CREATE_KEY,
+            Request::LoadKey { .. } => op_code::LOAD_KEY,
+            Request::SignData { .. } => op_code::SIGN_DATA,
+            Request::VerifySignature { .. } => op_code::VERIFY_SIGNATURE,
+            Request::EncryptData { .. } => op_code::ENCRYPT_DATA,
+            Request::DecryptData { .. } => op_code::DECRYPT_DATA,
+            Request::GetPublicKey { .. } => op_code::GET_PUBLIC_KEY,
+            Request::DeriveSharedSecret { .. } => op_code::DERIVE_SHARED_SECRET,
+            Request::ReadMetadata { .. } => op_code::READ_METADATA,
+            Request::WriteMetadata { .. } => op_code::WRITE_METADATA,
+            Request::DeleteMetadata { .. } => op_code::DELETE_METADATA,
+            Request::ListMetadata => op_code::LIST_METADATA,
+        }
+    }
+}
+
+impl fmt::Debug for Request {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Request::CreateKey { key_id, key_type } => f
+                .debug_struct("CreateKey")
+                .field("key_id", key_id)
+                .field("key_type", key_type)
+                .finish(),
+            Request::LoadKey {
+                key_id,
+                key_type,
+                hash,
+            } => f
+                .debug_struct("LoadKey")
+                .field("key_id", key_id)
+                .field("key_type", key_type)
+                .field("hash", hash)
+                .finish(),
+            Request::BridgeVersion => f.write_str("BridgeVersion"),
+            Request::InitializeModule => f.write_str("InitializeModule"),
+            Request::ShutdownModule => f.write_str("ShutdownModule"),
+            Request::SignData {
+                key_id,
+                data,
+                algorithm,
+                hash,
+            } => debug_payload(f, "SignData", key_id, data, algorithm, hash),
+            Request::DecryptData {
+                key_id,
+                data,
+                algorithm,
+                hash,
+            } => debug_payload(f, "DecryptData", key_id, data, algorithm, hash),
+            Request::EncryptData {
+                key_id,
+                data,
+                algorithm,
+                hash,
+            } => debug_payload(f, "EncryptData", key_id, data, algorithm, hash),
+            Request::VerifySignature {
+                key_id,
+                data,
+                signature,
+                algorithm,
+                hash,
+            } => f
+                .debug_struct("VerifySignature")
+                .field("key_id", key_id)
+                .field("data", &Redacted(data))
+                .field("signature", &Redacted(signature))
+                .field("algorithm", algorithm)
+                .field("hash", hash)
+                .finish(),
+            Request::GetPublicKey { key_id, algorithm } => f
+                .debug_struct("GetPublicKey")
+                .field("key_id", key_id)
+                .field("algorithm", algorithm)
+                .finish(),
+            Request::DeriveSharedSecret {
+                key_id,
+                peer_public_key,
+                algorithm,
+            } => f
+                .debug_struct("DeriveSharedSecret")
+                .field("key_id", key_id)
+                .field("peer_public_key", &Redacted(peer_public_key))
+                .field("algorithm", algorithm)
+                .finish(),
+            Request::ReadMetadata { key_id } => f
+                .debug_struct("ReadMetadata")
+                .field("key_id", key_id)
+                .finish(),
+            Request::WriteMetadata { key_id, record } => f
+                .debug_struct("WriteMetadata")
+                .field("key_id", key_id)
+                .field("record", record)
+                .finish(),
+            Request::DeleteMetadata { key_id } => f
+                .debug_struct("DeleteMetadata")
+                .field("key_id", key_id)
+                .finish(),
+            Request::ListMetadata => f.write_str("ListMetadata"),
+        }
+    }
+}
+
+fn debug_payload(
+    f: &mut fmt::Formatter<'_>,
+    name: &str,
+    key_id: &str,
+    data: &[u8],
+    algorithm: &str,
+    hash: &str,
+) -> fmt::Result {
+    f.debug_struct(name)
+        .field("key_id", &key_id)
+        .field("data", &Redacted(data))
+        .field("algorithm", &algorithm)
+        .field("hash", &hash)
+        .finish()
+}
+
+/// A request together with the response of the Swift bindings.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Exchange {
+    pub request: Request,
+    /// The payload of a successful call, empty if the call failed.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub result: String,
+    /// The error of a failed call.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<SwiftError>,
+    /// The time the Swift bindings took to answer, in microseconds, if it was recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_us: Option<u64>,
+}
+
+impl Exchange {
+    /// Creates an exchange from a request and its response.
+    pub fn new(request: Request, response: Response, duration_us: Option<u64>) -> Self {
+        let (result, error) = match response {
+            Ok(result) => (result, None),
+            Err(e) => (String::new(), Some(e)),
+        };
+        Self {
+            request,
+            result,
+            error,
+            duration_us,
+        }
+    }
+
+    /// Returns the response of the Swift bindings.
+    pub fn response(&self) -> Response {
+        match &self.error {
+            Some(e) => Err(e.clone()),
+            None => Ok(self.result.clone()),
+        }
+    }
+}
+
+/// A sequence of recorded exchanges, stored as JSON.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Cassette {
+    pub exchanges: Vec<Exchange>,
+}
+
+impl Cassette {
+    /// Reads a cassette from a JSON file.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `Cassette`, or a `SecurityModuleError::InitializationError` if
+    /// the file cannot be read or is not a valid cassette.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SecurityModuleError> {
+        let json = fs::read_to_string(path)
+            .map_err(|e| SecurityModuleError::InitializationError(e.to_string()))?;
+        serde_json::from_str(&json)
+            .map_err(|e| SecurityModuleError::InitializationError(e.to_string()))
+    }
+
+    /// Writes the cassette to a JSON file.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` that is `Ok(())` on success, or a `SecurityModuleError::InitializationError`
+    /// if the file cannot be written.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SecurityModuleError> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| SecurityModuleError::InitializationError(e.to_string()))?;
+        fs::write(path, json).map_err(|e| SecurityModuleError::InitializationError(e.to_string()))
+    }
+}
+
+/// Dispatches the calls of a `SecureEnclaveProvider` to the Swift bindings or a cassette.
+#[derive(Clone, Default)]
+pub enum Bridge {
+    /// Calls the Swift bindings. Only available on macOS, elsewhere every call fails.
+    #[default]
+    Live,
+    /// Calls the Swift bindings and appends every exchange to the cassette.
+    Record(Arc<Mutex<Cassette>>),
+    /// Answers every request with the first matching exchange of the cassette that has not been
+    /// replayed yet, without calling the Swift bindings. Requests without a matching exchange fail.
+    Replay(Arc<Mutex<Cassette>>),
+}
+
+impl Bridge {
+    /// Creates a bridge recording into an empty cassette.
+    pub fn recording() -> Self {
+        Bridge::Record(Arc::default())
+    }
+
+    /// Creates a bridge replaying `cassette`.
+    pub fn replay(cassette: Cassette) -> Self {
+        Bridge::Replay(Arc::new(Mutex::new(cassette)))
+    }
+
+    /// Returns the recorded exchanges, or the exchanges that have not been replayed yet.
+    /// A live bridge has no cassette.
+    pub fn cassette(&self) -> Option<Cassette> {
+        match self {
+            Bridge::Live => None,
+            Bridge::Record(cassette) | Bridge::Replay(cassette) => Some(lock(cassette).clone()),
+        }
+    }
+
+    /// Performs `request` and returns the response of the Swift bindings.
+    ///
+    /// Every call is traced in a `secure_enclave.call` span, which records the time the bindings
+    /// took and whether the call failed, see `common::telemetry`. Live calls pass the current
+    /// `CorrelationId`, or a new one, to the bindings, which include it in their `os_log` output.
+    pub fn call(&self, request: Request) -> Response {
+        let correlation_id = CorrelationId::current().unwrap_or_default();
+        let span = call_span(&request, correlation_id);
+        let _entered = span.enter();
+
+        let (response, duration_us) = match self {
+            Bridge::Live => timed_live(request, correlation_id),
+            Bridge::Record(cassette) => {
+                let (response, duration_us) = timed_live(request.clone(), correlation_id);
+                lock(cassette).exchanges.push(Exchange::new(
+                    request,
+                    response.clone(),
+                    duration_us,
+                ));
+                (response, duration_us)
+            }
+            Bridge::Replay(cassette) => replay(cassette, &request),
+        };
+        record_outcome(&span, &response, duration_us);
+        response
+    }
+
+    /// Performs `request` like `call`, but awaits the asynchronous variant of the called function
+    /// of the bindings if there is one.
+    ///
+    /// Signing and decrypting wait for the user if the key requires user authentication. Their
+    /// asynchronous variants show the prompt without blocking the calling thread. All other
+    /// requests are performed synchronously. Recorded and replayed exchanges are the same as
+    /// with `call`.
+    pub async fn call_async(&self, request: Request) -> Response {
+        let correlation_id = CorrelationId::current().unwrap_or_default();
+        let span = call_span(&request, correlation_id);
+
+        let (response, duration_us) = match self {
+            Bridge::Live => {
+                timed_live_async(request, correlation_id)
+                    .instrument(span.clone())
+                    .await
+            }
+            Bridge::Record(cassette) => {
+                let (response, duration_us) = timed_live_async(request.clone(), correlation_id)
+                    .instrument(span.clone())
+                    .await;
+                lock(cassette).exchanges.push(Exchange::new(
+                    request,
+                    response.clone(),
+                    duration_us,
+                ));
+                (response, duration_us)
+            }
+            Bridge::Replay(cassette) => span.in_scope(|| replay(cassette, &request)),
+        };
+        record_outcome(&span, &response, duration_us);
+        response
+    }
+}
+
+fn call_span(request: &Request, correlation_id: CorrelationId) -> Span {
+    tracing::info_span!(
+        "secure_enclave.call",
+        otel.kind = "client",
+        rpc.system = "swift",
+        rpc.method = request.method(),
+        crypto.correlation_id = %correlation_id,
+        ffi.duration_us = Empty,
+        ffi.request.size = Empty,
+        ffi.response.size = Empty,
+        ffi.marshalling_us = Empty,
+        otel.status_code = Empty,
+        otel.status_description = Empty,
+    )
+}
+
+/// Answers `request` with the first matching exchange of `cassette` and removes it.
+fn replay(cassette: &Mutex<Cassette>, request: &Request) -> (Response, Option<u64>) {
+    let mut cassette = lock(cassette);
+    match cassette
+        .exchanges
+        .iter()
+        .position(|exchange| exchange.request == *request)
+    {
+        Some(index) => {
+            let exchange = cassette.exchanges.remove(index);
+            (exchange.response(), exchange.duration_us)
+        }
+        None => (
+            Err(SwiftError::bridge(format!(
+                "No recorded response for {:?}",
+                request
+            ))),
+            None,
+        ),
+    }
+}
+
+fn record_outcome(span: &Span, response: &Response, duration_us: Option<u64>) {
+    if let Some(duration_us) = duration_us {
+        span.record("ffi.duration_us", duration_us);
+    }
+    if let Err(e) = response {
+        span.record("otel.status_code", "ERROR");
+        span.record("otel.status_description", e.message.as_str());
+    }
+}
+
+impl fmt::Debug for Bridge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Bridge::Live => write!(f, "Live"),
+            Bridge::Record(cassette) => {
+                write!(f, "Record({} exchanges)", lock(cassette).exchanges.len())
+            }
+            Bridge::Replay(cassette) => write!(
+                f,
+                "Replay({} exchanges left)",
+                lock(cassette).exchanges.len()
+            ),
+        }
+    }
+}
+
+fn lock(cassette: &Mutex<Cassette>) -> MutexGuard<'_, Cassette> {
+    cassette
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Calls the Swift bindings and measures how long they take.
+fn timed_live(request: Request, correlation_id: CorrelationId) -> (Response, Option<u64>) {
+    let method = request.method();
+    let start = Instant::now();
+    let (response, stats) = live(request, correlation_id);
+    let duration_us = u64::try_from(start.elapsed().as_micros()).unwrap_or(u64::MAX);
+    if let Some(stats) = stats {
+        record_boundary(method, &stats);
+    }
+    (response, Some(duration_us))
+}
+
+/// Calls the asynchronous variant of the Swift bindings and measures how long they take.
+async fn timed_live_async(
+    request: Request,
+    correlation_id: CorrelationId,
+) -> (Response, Option<u64>) {
+    let method = request.method();
+    let start = Instant::now();
+    let (response, stats) = live_async(request, correlation_id).await;
+    let duration_us = u64::try_from(start.elapsed().as_micros()).unwrap_or(u64::MAX);
+    if let Some(stats) = stats {
+        record_boundary(method, &stats);
+    }
+    (response, Some(duration_us))
+}
+
+/// Records the bytes and marshalling time of a call on the current `secure_enclave.call` span and,
+/// with the `metrics` feature, through `common::metrics`.
+fn record_boundary(method: &'static str, stats: &BoundaryStats) {
+    let span = Span::current();
+    span.record("ffi.request.size", stats.request_bytes);
+    span.record("ffi.response.size", stats.response_bytes);
+    span.record(
+        "ffi.marshalling_us",
+        u64::try_from(stats.marshalling.as_micros()).unwrap_or(u64::MAX),
+    );
+    #[cfg(feature = "metrics")]
+    crate::common::metrics::record_ffi_call(
+        method,
+        stats.request_bytes,
+        stats.response_bytes,
+        stats.marshalling,
+    );
+    #[cfg(not(feature = "metrics"))]
+    let _ = method;
+}
+
+#[cfg(target_os = "macos")]
+fn live(request: Request, correlation_id: CorrelationId) -> (Response, Option<BoundaryStats>) {
+    use apple_secure_enclave_bindings::dispatch::rust_crypto_call_dispatch;
+
+    let (response, stats) =
+        super::dispatch::call(request, correlation_id, rust_crypto_call_dispatch);
+    (response, Some(stats))
+}
+
+/// Calls the asynchronous entry point of the Swift bindings, which awaits the authentication of
+/// the user while signing and decrypting and performs every other operation synchronously.
+#[cfg(target_os = "macos")]
+async fn live_async(
+    request: Request,
+    correlation_id: CorrelationId,
+) -> (Response, Option<BoundaryStats>) {
+    use apple_secure_enclave_bindings::dispatch::rust_crypto_call_dispatch_async;
+
+    let (response, stats) =
+        super::dispatch::call_async(request, correlation_id, rust_crypto_call_dispatch_async).await;
+    (response, Some(stats))
+}
+
+/// Nothing crosses the boundary, so there are no `BoundaryStats`.
+#[cfg(not(target_os = "macos"))]
+fn live(_request: Request, _correlation_id: CorrelationId) -> (Response, Option<BoundaryStats>) {
+    (
+        Err(SwiftError::bridge(
+            "The Secure Enclave is only available on macOS",
+        )),
+        None,
+    )
+}
+
+#[cfg(not(target_os = "macos"))]
+async fn live_async(
+    request: Request,
+    correlation_id: CorrelationId,
+) -> (Response, Option<BoundaryStats>) {
+    live(request, correlation_id)
+}
+
+/// Stores binary request arguments base64 encoded, which keeps cassettes readable.
+mod base64_bytes {
+    use base64::{prelude::BASE64_STANDARD, Engine};
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&BASE64_STANDARD.encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        BASE64_STANDARD.decode(encoded).map_err(D::Error::custom)
+    }
+}
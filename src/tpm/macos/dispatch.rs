@@ -0,0 +1,221 @@
+//! The wire format of the single entry point of the Swift bindings.
+//!
+//! The bindings export one function, `rustcall_dispatch(op_code, request) -> response`, and its
+//! asynchronous variant `rustcall_dispatch_async`, instead of one function per operation. Adding an
+//! operation therefore only needs a new `Request` variant and op code, and the Swift side can be
+//! tested in isolation by feeding it request bytes.
+//!
+//! - `op_code` selects the operation, see `op_code` and `Request::op_code`.
+//! - `request` is a JSON `Envelope` holding the protocol version, the `CorrelationId` and the
+//!   request in the format of cassettes, e.g.
+//!   `{"version":3,"correlation_id":"…","request":{"call":"sign_data","key_id":"…","data":"AP8K",…}}`.
+//!   Binary arguments are base64 encoded.
+//! - `response` is a JSON `WireResponse`, `{"payload":"…"}` on success or
+//!   `{"payload":"","error":{"code":4,"domain":"SecureEnclaveError","message":"…"}}` on failure.
+//!
+//! JSON is used instead of a binary format like CBOR because both Foundation and this crate
+//! already support it, so the bindings need no additional dependency.
+
+use super::{
+    bridge::Request,
+    protocol::PROTOCOL_VERSION,
+    response::{Response, SwiftError},
+};
+use crate::common::telemetry::CorrelationId;
+use futures::FutureExt;
+use serde::{Deserialize, Serialize};
+use std::{
+    any::Any,
+    future::Future,
+    panic::{self, AssertUnwindSafe},
+    time::{Duration, Instant},
+};
+
+/// The op codes of the operations, passed as the first argument of `rustcall_dispatch`.
+pub mod op_code {
+    pub const BRIDGE_VERSION: u32 = 0;
+    pub const INITIALIZE_MODULE: u32 = 1;
+    pub const SHUTDOWN_MODULE: u32 = 2;
+    pub const CREATE_KEY: u32 = 3;
+    pub const LOAD_KEY: u32 = 4;
+    pub const SIGN_DATA: u32 = 5;
+    pub const VERIFY_SIGNATURE: u32 = 6;
+    pub const ENCRYPT_DATA: u32 = 7;
+    pub const DECRYPT_DATA: u32 = 8;
+    pub const GET_PUBLIC_KEY: u32 = 9;
+    pub const DERIVE_SHARED_SECRET: u32 = 10;
+    pub const READ_METADATA: u32 = 11;
+    pub const WRITE_METADATA: u32 = 12;
+    pub const DELETE_METADATA: u32 = 13;
+    pub const LIST_METADATA: u32 = 14;
+}
+
+/// The request bytes passed to `rustcall_dispatch`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Envelope {
+    /// The protocol version the request is encoded with, see `protocol`.
+    pub version: u32,
+    /// The correlation id the bindings include in their `os_log` messages.
+    pub correlation_id: String,
+    pub request: Request,
+}
+
+/// The response bytes returned by `rustcall_dispatch`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WireResponse {
+    #[serde(default)]
+    pub payload: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<WireError>,
+}
+
+/// The error of a failed call, see `response::SwiftError`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WireError {
+    pub code: u32,
+    pub domain: String,
+    pub message: String,
+}
+
+impl From<Response> for WireResponse {
+    fn from(response: Response) -> Self {
+        match response {
+            Ok(payload) => Self {
+                payload,
+                error: None,
+            },
+            Err(e) => Self {
+                payload: String::new(),
+                error: Some(WireError {
+                    code: e.code.code(),
+                    domain: e.domain,
+                    message: e.message,
+                }),
+            },
+        }
+    }
+}
+
+impl From<WireResponse> for Response {
+    fn from(response: WireResponse) -> Self {
+        match response.error {
+            None => Ok(response.payload),
+            Some(e) => Err(SwiftError::new(e.code, e.domain, e.message)),
+        }
+    }
+}
+
+/// Encodes `request` as the request bytes of `rustcall_dispatch`.
+pub fn encode_request(request: Request, correlation_id: CorrelationId) -> Vec<u8> {
+    let envelope = Envelope {
+        version: PROTOCOL_VERSION,
+        correlation_id: correlation_id.to_string(),
+        request,
+    };
+    // A `Request` only holds strings and bytes, which always serialize.
+    serde_json::to_vec(&envelope).expect("A request serializes to JSON")
+}
+
+/// Decodes the response bytes of `rustcall_dispatch`.
+///
+/// Bytes that are not a valid `WireResponse` are reported as an error of the bridge instead of
+/// being passed on as a payload.
+pub fn decode_response(bytes: &[u8]) -> Response {
+    match serde_json::from_slice::<WireResponse>(bytes) {
+        Ok(response) => response.into(),
+        Err(e) => Err(SwiftError::bridge(format!(
+            "Invalid response of the Swift bindings: {}",
+            e
+        ))),
+    }
+}
+
+/// The cost of one call across the boundary to the Swift bindings.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BoundaryStats {
+    /// The length of the request bytes passed to the bindings.
+    pub request_bytes: usize,
+    /// The length of the response bytes returned by the bindings.
+    pub response_bytes: usize,
+    /// The time spent encoding the request and decoding the response, without the time the
+    /// bindings took.
+    pub marshalling: Duration,
+}
+
+/// Encodes `request`, passes it to `transport` and decodes the returned response bytes.
+///
+/// `transport` is the entry point of the bindings, `rust_crypto_call_dispatch` on macOS. If it
+/// panics, e.g. in the generated glue code of `swift-bridge`, the call fails with an error of the
+/// bridge instead of unwinding into the provider. The Swift bindings themselves never trap on
+/// failures, they report every error, including Objective-C exceptions, in the response.
+pub fn call(
+    request: Request,
+    correlation_id: CorrelationId,
+    transport: impl FnOnce(u32, Vec<u8>) -> Vec<u8>,
+) -> (Response, BoundaryStats) {
+    let op_code = request.op_code();
+    let start = Instant::now();
+    let bytes = encode_request(request, correlation_id);
+    let request_bytes = bytes.len();
+    let marshalling = start.elapsed();
+
+    let bytes = panic::catch_unwind(AssertUnwindSafe(|| transport(op_code, bytes)));
+    finish(bytes, request_bytes, marshalling)
+}
+
+/// Performs the call like `call`, but awaits the response bytes of an asynchronous `transport`.
+pub async fn call_async<F>(
+    request: Request,
+    correlation_id: CorrelationId,
+    transport: impl FnOnce(u32, Vec<u8>) -> F,
+) -> (Response, BoundaryStats)
+where
+    F: Future<Output = Vec<u8>>,
+{
+    let op_code = request.op_code();
+    let start = Instant::now();
+    let bytes = encode_request(request, correlation_id);
+    let request_bytes = bytes.len();
+    let marshalling = start.elapsed();
+
+    // Creating the future may panic as well as polling it.
+    let bytes = AssertUnwindSafe(async move { transport(op_code, bytes).await })
+        .catch_unwind()
+        .await;
+    finish(bytes, request_bytes, marshalling)
+}
+
+/// Decodes the response bytes returned by a transport, or reports its panic.
+fn finish(
+    bytes: Result<Vec<u8>, Box<dyn Any + Send>>,
+    request_bytes: usize,
+    mut marshalling: Duration,
+) -> (Response, BoundaryStats) {
+    let (response, response_bytes) = match bytes {
+        Ok(bytes) => {
+            let start = Instant::now();
+            let response = decode_response(&bytes);
+            marshalling += start.elapsed();
+            (response, bytes.len())
+        }
+        Err(panic) => (Err(transport_panicked(panic)), 0),
+    };
+    let stats = BoundaryStats {
+        request_bytes,
+        response_bytes,
+        marshalling,
+    };
+    (response, stats)
+}
+
+fn transport_panicked(panic: Box<dyn Any + Send>) -> SwiftError {
+    let message = panic
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown panic");
+    SwiftError::bridge(format!(
+        "The call into the Swift bindings panicked: {}",
+        message
+    ))
+}
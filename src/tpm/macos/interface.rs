@@ -0,0 +1,7 @@
+//! The string identifiers passed to the Swift bindings.
+//!
+//! The constants are generated by `build.rs` from `swift_interface.txt`, which fails the build if the
+//! Swift bindings do not handle one of them. Use them instead of string literals, so that an
+//! identifier removed from the interface does not compile.
+
+include!(concat!(env!("OUT_DIR"), "/swift_interface.rs"));
@@ -1,7 +1,35 @@
-extern crate apple_secure_enclave_bindings;
-use super::{provider::{convert_algorithms, convert_hash}, SecureEnclaveProvider};
-use crate::common::{error::SecurityModuleError, traits::key_handle::KeyHandle};
-use tracing::instrument;
+use super::{bridge::Request, protocol::capability, provider::{convert_algorithms, convert_hash}, response, SecureEnclaveProvider};
+use crate::common::{crypto::{operation_context::OperationContext, secret::SecretBytes}, error::SecurityModuleError, latency::key_id_hash, traits::{async_key_handle::AsyncKeyHandle, key_handle::KeyHandle}};
+use base64::{prelude::BASE64_STANDARD, Engine};
+use futures::future::BoxFuture;
+use tracing::{instrument, Instrument};
+
+impl SecureEnclaveProvider {
+    /// Builds the request signing `data` with the loaded key.
+    fn sign_request(&self, data: &[u8]) -> Result<Request, SecurityModuleError> {
+        let config = self.config.as_ref().ok_or(SecurityModuleError::InitializationError(("Failed to initialize config").to_owned()))?;
+        let algorithm = convert_algorithms(config.clone());
+        let hash = convert_hash(config.hash.expect("No Hash given"));
+        Ok(Request::SignData { key_id: self.key_id.clone(), data: data.to_vec(), algorithm, hash })
+    }
+
+    /// Builds the request decrypting `encrypted_data` with the loaded key.
+    fn decrypt_request(&self, encrypted_data: &[u8]) -> Result<Request, SecurityModuleError> {
+        let config = self.config.as_ref().ok_or(SecurityModuleError::InitializationError(("Failed to initialize config").to_owned()))?;
+        let algorithm = convert_algorithms(config.clone());
+        let hash = convert_hash(config.hash.expect("No Hash given"));
+        Ok(Request::DecryptData { key_id: self.key_id.clone(), data: encrypted_data.to_vec(), algorithm, hash })
+    }
+
+    /// Performs `request` with the asynchronous functions of the Swift bindings, unless the negotiated protocol
+    /// does not include them.
+    async fn call_async(&self, request: Request) -> response::Response {
+        match &self.protocol {
+            Some(protocol) if !protocol.supports(capability::ASYNC) => self.bridge.call(request),
+            _ => self.bridge.call_async(request).await,
+        }
+    }
+}
 
 
 /// Provides cryptographic operations for asymmetric keys on macOS,
@@ -9,7 +37,7 @@ use tracing::instrument;
 impl KeyHandle for SecureEnclaveProvider {
     /// Signs the given data using the cryptographic key managed by the Secure Enclave provider.
     /// 
-    /// Uses the rust_crypto_call_sign_data function from the Swift Secure Enclave bindings.
+    /// Uses the rust_crypto_call_dispatch function from the Swift Secure Enclave bindings.
     ///
     /// # Arguments
     ///
@@ -18,28 +46,17 @@ impl KeyHandle for SecureEnclaveProvider {
     /// # Returns
     ///
     /// A `Result` containing the signature as a `Vec<u8>` on success, or a `SecurityModuleError` on failure.
-    #[instrument]
+    #[instrument(skip_all, fields(crypto.key_id_hash = %key_id_hash(&self.key_id), crypto.payload.size = data.len()))]
     fn sign_data(&self, data: &[u8]) -> Result<Vec<u8>, SecurityModuleError> {
-        
-        let data_vec = data.to_vec(); 
-        let key_id = &self.key_id; 
-        let config = self.config.as_ref().ok_or(SecurityModuleError::InitializationError(("Failed to initialize config").to_owned()))?;
-        let algo = convert_algorithms(config.clone());
-        let hash = convert_hash(config.hash.expect("No Hash given"));
-
-        let signed_data = apple_secure_enclave_bindings::keyhandle::rust_crypto_call_sign_data(key_id.clone(), data_vec, algo, hash);
+        let signed_data = self.bridge.call(self.sign_request(data)?);
 
-        if signed_data.0 {
-            Err(SecurityModuleError::EncryptionError(signed_data.1.to_string()))
-        } else {
-            Ok(signed_data.1.into_bytes())
-        }
+        response::decode_bytes(signed_data, SecurityModuleError::EncryptionError)
     }
 
 
     /// Decrypts the given encrypted data using the cryptographic key managed by the Secure Enclave provider.
     /// 
-    /// Uses the rust_crypto_call_decrypt_data function from the Swift Secure Enclave bindings.
+    /// Uses the rust_crypto_call_dispatch function from the Swift Secure Enclave bindings.
     ///
     /// # Arguments
     ///
@@ -48,26 +65,16 @@ impl KeyHandle for SecureEnclaveProvider {
     /// # Returns
     ///
     /// A `Result` containing the decrypted data as a `Vec<u8>` on success, or a `SecurityModuleError` on failure.
-    #[instrument]
-    fn decrypt_data(&self, encrypted_data: &[u8]) -> Result<Vec<u8>, SecurityModuleError> {
-        let encrypted_data_vec = encrypted_data.to_vec(); 
-        let config = self.config.as_ref().ok_or(SecurityModuleError::InitializationError(("Failed to initialize config").to_owned()))?;
-        let algorithm = convert_algorithms(config.clone()); 
-        let hash = convert_hash(config.hash.expect("No Hash given"));
-
-        let decrypted_data =
-            apple_secure_enclave_bindings::keyhandle::rust_crypto_call_decrypt_data(self.key_id.to_string(), encrypted_data_vec, algorithm, hash);
-        if decrypted_data.0 {
-            Err(SecurityModuleError::EncryptionError(decrypted_data.1.to_string()))
-        } else {
-            Ok(decrypted_data.1.into_bytes())
-        }
+    #[instrument(skip_all, fields(crypto.key_id_hash = %key_id_hash(&self.key_id), crypto.payload.size = encrypted_data.len()))]
+    fn decrypt_data(&self, encrypted_data: &[u8]) -> Result<SecretBytes, SecurityModuleError> {
+        let decrypted_data = self.bridge.call(self.decrypt_request(encrypted_data)?);
+        response::decode_base64(decrypted_data, SecurityModuleError::EncryptionError).map(SecretBytes::new)
     }
 
 
     /// Encrypts data with the cryptographic key.
     ///
-    /// Uses the rust_crypto_call_encrypt_data function from the Swift Secure Enclave bindings.
+    /// Uses the rust_crypto_call_dispatch function from the Swift Secure Enclave bindings.
     ///
     /// # Arguments
     ///
@@ -76,7 +83,7 @@ impl KeyHandle for SecureEnclaveProvider {
     /// # Returns
     ///
     /// A `Result` containing the encrypted data as a `Vec<u8>` on success, or a `SecurityModuleError` on failure.
-    #[instrument]
+    #[instrument(skip_all, fields(crypto.key_id_hash = %key_id_hash(&self.key_id), crypto.payload.size = data.len()))]
     fn encrypt_data(&self, data: &[u8]) -> Result<Vec<u8>, SecurityModuleError> {
         // let string_data = String::from_utf8(data.to_vec()).map_err(|_| {
         //     SecurityModuleError::EncryptionError("Data conversion error".to_string())
@@ -88,21 +95,15 @@ impl KeyHandle for SecureEnclaveProvider {
         let hash = convert_hash(config.hash.expect("No Hash given"));
 
         let encrypted_data =
-            apple_secure_enclave_bindings::keyhandle::rust_crypto_call_encrypt_data(key_id.to_string(), data_vec, algorithm, hash);
-
-        if encrypted_data.0 {
-            Err(SecurityModuleError::EncryptionError(
-                encrypted_data.1.to_string(),
-                ))
-        } else {
-            Ok(encrypted_data.1.into_bytes())
-        }
+            self.bridge.call(Request::EncryptData { key_id: key_id.to_string(), data: data_vec, algorithm, hash });
+
+        response::decode_bytes(encrypted_data, SecurityModuleError::EncryptionError)
     }
 
 
     /// Verifies the signature of the given data using the cryptographic key managed by the Secure Enclave provider.
     /// 
-    /// Uses the rust_crypto_call_verify_signature function from the Swift Secure Enclave bindings.
+    /// Uses the rust_crypto_call_dispatch function from the Swift Secure Enclave bindings.
     ///
     /// # Arguments
     ///
@@ -113,7 +114,7 @@ impl KeyHandle for SecureEnclaveProvider {
     ///
     /// A `Result` containing a boolean indicating whether the signature is valid (`true`) or not (`false`),
     /// or a `SecurityModuleError` on failure.
-    #[instrument]
+    #[instrument(skip_all, fields(crypto.key_id_hash = %key_id_hash(&self.key_id), crypto.payload.size = data.len(), crypto.signature.size = signature.len()))]
     fn verify_signature(&self, data: &[u8], signature: &[u8]) -> Result<bool, SecurityModuleError> {
         let data_vec = data.to_vec();
         let signature_vec = signature.to_vec(); 
@@ -123,17 +124,123 @@ impl KeyHandle for SecureEnclaveProvider {
         let hash = convert_hash(config.hash.expect("No Hash given"));
 
         let verification_result =
-            apple_secure_enclave_bindings::keyhandle::rust_crypto_call_verify_signature(key_id.clone(), data_vec, signature_vec, algo, hash);
-
-        // The FFI bridge always returns strings by design.
-        // If not "true" or "false" is found, an error from the function is expected
-        match verification_result.1.as_str() {
-            "true" => Ok(true),
-            "false" => Ok(false),
-            _ => Err(SecurityModuleError::SignatureVerificationError(
-                verification_result.1,
-            ))
+            self.bridge.call(Request::VerifySignature { key_id: key_id.clone(), data: data_vec, signature: signature_vec, algorithm: algo, hash });
+
+        response::decode_verification(verification_result)
+    }
+
+
+    /// Verifies the signature of the given data using the public key exported on `create_key` or `load_key`.
+    /// 
+    /// The base64 encoded signature is decoded into the scratch buffer of `context` and verified in pure Rust,
+    /// so no allocations are needed once the context has warmed up. Falls back to `verify_signature` if no
+    /// public key is available.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - A byte slice representing the data whose signature is to be verified.
+    /// * `signature` - The signature (base64 encoded, as returned by `sign_data`) to be verified against the data.
+    /// * `context` - The `OperationContext` whose buffers are reused.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing a boolean indicating whether the signature is valid (`true`) or not (`false`),
+    /// or a `SecurityModuleError` on failure.
+    #[instrument(skip_all, fields(crypto.key_id_hash = %key_id_hash(&self.key_id), crypto.payload.size = data.len(), crypto.signature.size = signature.len()))]
+    fn verify_signature_with(&self, data: &[u8], signature: &[u8], context: &mut OperationContext) -> Result<bool, SecurityModuleError> {
+        match self.metadata.as_ref() {
+            Some(metadata) => metadata.public_key().verify(data, context.decode_base64(signature)?),
+            None => self.verify_signature(data, signature),
+        }
+    }
+
+
+    /// Verifies a batch of signatures in parallel using the public key exported on `create_key` or `load_key`.
+    /// 
+    /// The verification is done in pure Rust, the Swift Secure Enclave bindings are not involved.
+    ///
+    /// # Arguments
+    ///
+    /// * `items` - Pairs of data and the signature (base64 encoded, as returned by `sign_data`) to be verified.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing one boolean per item indicating whether the respective signature is valid,
+    /// or a `SecurityModuleError` if no key has been loaded.
+    #[instrument(skip_all, fields(crypto.key_id_hash = %key_id_hash(&self.key_id), crypto.batch.size = items.len()))]
+    fn verify_many(&self, items: &[(&[u8], &[u8])]) -> Result<Vec<bool>, SecurityModuleError> {
+        let public_key = self.metadata.as_ref().ok_or(SecurityModuleError::InitializationError(("No key loaded").to_owned()))?.public_key();
+
+        // Malformed signatures are kept as empty signature, which is reported as invalid.
+        let signatures: Vec<Vec<u8>> = items
+            .iter()
+            .map(|(_, signature)| BASE64_STANDARD.decode(signature).unwrap_or_default())
+            .collect();
+        let decoded_items: Vec<(&[u8], &[u8])> = items
+            .iter()
+            .zip(signatures.iter())
+            .map(|((data, _), signature)| (*data, signature.as_slice()))
+            .collect();
+
+        Ok(public_key.verify_many(&decoded_items))
+    }
+
+
+    /// Derives a shared secret with ECDH from the private key in the Secure Enclave and the public key of another party.
+    ///
+    /// Uses the rust_crypto_call_dispatch function from the Swift Secure Enclave bindings.
+    ///
+    /// # Arguments
+    ///
+    /// * `peer_public_key` - The public key of the other party as an uncompressed P-256 point (ANSI X9.63).
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the shared secret on success, or a `SecurityModuleError::UnsupportedAlgorithm` if
+    /// the Swift bindings do not support key agreement.
+    #[instrument(skip_all, fields(crypto.key_id_hash = %key_id_hash(&self.key_id)))]
+    fn derive_shared_secret(&self, peer_public_key: &[u8]) -> Result<SecretBytes, SecurityModuleError> {
+        if self.protocol.as_ref().is_some_and(|protocol| !protocol.supports(capability::KEY_AGREEMENT)) {
+            return Err(SecurityModuleError::UnsupportedAlgorithm);
         }
+        let config = self.config.as_ref().ok_or(SecurityModuleError::InitializationError(("Failed to initialize config").to_owned()))?;
+        let algorithm = convert_algorithms(config.clone());
+
+        let shared_secret = self.bridge.call(Request::DeriveSharedSecret { key_id: self.key_id.clone(), peer_public_key: peer_public_key.to_vec(), algorithm });
+        response::decode_base64(shared_secret, SecurityModuleError::DecryptionError).map(SecretBytes::new)
     }
 }
 
+
+/// Awaits the asynchronous functions of the Swift bindings, so that a Touch ID prompt does not block the calling thread.
+impl AsyncKeyHandle for SecureEnclaveProvider {
+    /// Signs the given data like `sign_data`, without blocking while the user authenticates.
+    ///
+    /// Uses the rust_crypto_call_dispatch_async function from the Swift Secure Enclave bindings.
+    fn sign_data_async<'a>(&'a self, data: &'a [u8]) -> BoxFuture<'a, Result<Vec<u8>, SecurityModuleError>> {
+        let span = tracing::info_span!("sign_data_async", crypto.key_id_hash = %key_id_hash(&self.key_id), crypto.payload.size = data.len());
+        Box::pin(
+            async move {
+                let request = self.sign_request(data)?;
+                let signed_data = self.call_async(request).await;
+                response::decode_bytes(signed_data, SecurityModuleError::EncryptionError)
+            }
+            .instrument(span),
+        )
+    }
+
+    /// Decrypts the given data like `decrypt_data`, without blocking while the user authenticates.
+    ///
+    /// Uses the rust_crypto_call_dispatch_async function from the Swift Secure Enclave bindings.
+    fn decrypt_data_async<'a>(&'a self, encrypted_data: &'a [u8]) -> BoxFuture<'a, Result<SecretBytes, SecurityModuleError>> {
+        let span = tracing::info_span!("decrypt_data_async", crypto.key_id_hash = %key_id_hash(&self.key_id), crypto.payload.size = encrypted_data.len());
+        Box::pin(
+            async move {
+                let request = self.decrypt_request(encrypted_data)?;
+                let decrypted_data = self.call_async(request).await;
+                response::decode_base64(decrypted_data, SecurityModuleError::EncryptionError).map(SecretBytes::new)
+            }
+            .instrument(span),
+        )
+    }
+}
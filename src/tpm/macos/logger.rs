@@ -1,7 +1,7 @@
 use tracing::Level;
 use tracing_appender::rolling;
-use tracing_subscriber::FmtSubscriber;
-use crate::common::traits::log_config::LogConfig;
+use tracing_subscriber::{layer::SubscriberExt, FmtSubscriber};
+use crate::common::{log_levels::ProviderLogFilter, traits::log_config::LogConfig};
 
 
 #[derive(Debug, Clone, Copy)]
@@ -22,7 +22,8 @@ impl LogConfig for Logger{
         let subscriber = FmtSubscriber::builder()
             .with_max_level(Level::TRACE)
             .with_writer(non_blocking)
-            .finish();
+            .finish()
+            .with(ProviderLogFilter);
         tracing::subscriber::set_global_default(subscriber)
             .expect("setting default subscriber failed");
     }
@@ -0,0 +1,108 @@
+//! A `MetadataStore` keeping the metadata of keys in the keychain.
+//!
+//! Every `KeyRecord` is stored as JSON in the generic attribute of a generic password item of the
+//! service `crypto-layer.metadata`, whose account is the key id. The item holds no secret, the
+//! keychain is only used because it is where the keys of the Secure Enclave live, so the
+//! metadata is backed up, migrated and deleted together with them. Bindings without the
+//! `metadata` capability cannot store records, see `protocol`.
+
+use super::{
+    bridge::{Bridge, Request},
+    protocol::{self, capability},
+    response::{decode_status, Response},
+};
+use crate::common::{
+    error::SecurityModuleError,
+    metadata_store::{KeyRecord, MetadataStore},
+};
+
+/// A `MetadataStore` keeping the records in the keychain, see the module documentation.
+#[derive(Debug, Clone)]
+pub struct KeychainMetadataStore {
+    bridge: Bridge,
+}
+
+impl KeychainMetadataStore {
+    /// Creates a store calling the Swift bindings.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `KeychainMetadataStore` on success, or a
+    /// `SecurityModuleError::UnsupportedOperation` if the bindings cannot store metadata.
+    pub fn new() -> Result<Self, SecurityModuleError> {
+        Self::with_bridge(Bridge::Live)
+    }
+
+    /// Creates a store whose calls to the Swift bindings go through `bridge`, see `new`.
+    pub fn with_bridge(bridge: Bridge) -> Result<Self, SecurityModuleError> {
+        let protocol = protocol::negotiate_response(bridge.call(Request::BridgeVersion))?;
+        if !protocol.supports(capability::METADATA) {
+            return Err(SecurityModuleError::UnsupportedOperation(
+                "The Swift bindings cannot store key metadata; update the Swift bindings"
+                    .to_owned(),
+            ));
+        }
+        Ok(Self { bridge })
+    }
+}
+
+impl MetadataStore for KeychainMetadataStore {
+    fn load(&self, key_id: &str) -> Result<Option<KeyRecord>, SecurityModuleError> {
+        let json = decode(self.bridge.call(Request::ReadMetadata {
+            key_id: key_id.to_owned(),
+        }))?;
+        // The bindings answer with an empty payload if there is no item.
+        if json.is_empty() {
+            return Ok(None);
+        }
+        serde_json::from_str(&json).map(Some).map_err(|e| {
+            SecurityModuleError::SecretStorage(format!(
+                "The keychain metadata of '{}' is invalid: {}",
+                key_id, e
+            ))
+        })
+    }
+
+    fn save(&self, record: &KeyRecord) -> Result<(), SecurityModuleError> {
+        // A `KeyRecord` only holds strings, numbers and maps with string keys.
+        let json = serde_json::to_string(record).expect("A key record serializes to JSON");
+        decode_status(
+            self.bridge.call(Request::WriteMetadata {
+                key_id: record.key_id.clone(),
+                record: json,
+            }),
+            keychain_error,
+        )
+    }
+
+    fn delete(&self, key_id: &str) -> Result<bool, SecurityModuleError> {
+        match decode(self.bridge.call(Request::DeleteMetadata {
+            key_id: key_id.to_owned(),
+        }))?
+        .as_str()
+        {
+            "true" => Ok(true),
+            "false" => Ok(false),
+            payload => Err(keychain_error(format!(
+                "Invalid result of deleting metadata: '{}'",
+                payload.escape_debug()
+            ))),
+        }
+    }
+
+    fn key_ids(&self) -> Result<Vec<String>, SecurityModuleError> {
+        let json = decode(self.bridge.call(Request::ListMetadata))?;
+        let mut key_ids: Vec<String> = serde_json::from_str(&json)
+            .map_err(|e| keychain_error(format!("Invalid list of metadata items: {}", e)))?;
+        key_ids.sort();
+        Ok(key_ids)
+    }
+}
+
+fn decode(response: Response) -> Result<String, SecurityModuleError> {
+    response.map_err(|e| keychain_error(e.message))
+}
+
+fn keychain_error(message: String) -> SecurityModuleError {
+    SecurityModuleError::SecretStorage(format!("Keychain metadata: {}", message))
+}
@@ -1,18 +1,33 @@
-use crate::{common::{crypto::algorithms::{encryption::AsymmetricEncryption, hashes::Hash}, traits::module_provider_config::ProviderConfig}, SecurityModuleError};
+use crate::{common::{crypto::{algorithms::{encryption::AsymmetricEncryption, hashes::Hash}, key_metadata::KeyMetadata, key_spec::{AccessControl, KeySpec}}, traits::module_provider_config::ProviderConfig}, SecurityModuleError};
 use anyhow::Result;
 use std::fmt::{Debug, Formatter};
 use std::any::Any;
+use bridge::Bridge;
+use protocol::BridgeProtocol;
 
+pub mod bridge;
+pub mod dispatch;
+pub mod interface;
 pub mod key_handle;
+pub mod metadata_store;
+pub mod protocol;
 pub mod provider;
 pub mod logger;
+pub mod response;
 
 // Provider Setup - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - 
 #[derive(Clone, Debug)]
 #[repr(C)]
 pub struct SecureEnclaveProvider {
     pub(super) key_id: String, 
-    config: Option<SecureEnclaveConfig>
+    config: Option<SecureEnclaveConfig>,
+    /// Metadata of the created or loaded key pair. Its public key is used to verify signatures without the Secure Enclave.
+    pub(super) metadata: Option<KeyMetadata>,
+    /// Dispatches the calls to the Swift bindings, or records and replays them.
+    pub(super) bridge: Bridge,
+    /// The protocol negotiated with the Swift bindings by `initialize_module`, or `None` if the provider
+    /// has not been initialized since it was created or shut down.
+    pub(super) protocol: Option<BridgeProtocol>,
 }
 
 impl SecureEnclaveProvider {
@@ -27,12 +42,30 @@ impl SecureEnclaveProvider {
     ///
     /// A new instance of `SecureEnclaveProvider` with the specified `key_id`.
     pub fn new(key_id: String) -> Self {
+        Self::with_bridge(key_id, Bridge::Live)
+    }
+
+    /// Constructs a new `SecureEnclaveProvider` whose calls to the Swift bindings go through `bridge`.
+    ///
+    /// # Arguments
+    ///
+    /// * `key_id` - A string identifier for the cryptographic key to be managed by this provider.
+    /// * `bridge` - Calls, records or replays the Swift bindings.
+    pub fn with_bridge(key_id: String, bridge: Bridge) -> Self {
         Self {
             key_id,
-            config: None
+            config: None,
+            metadata: None,
+            bridge,
+            protocol: None,
         }
     }
 
+    /// Returns the protocol negotiated with the Swift bindings, or `None` if the provider is not initialized.
+    pub fn bridge_protocol(&self) -> Option<&BridgeProtocol> {
+        self.protocol.as_ref()
+    }
+
     pub fn set_config (&mut self, config: SecureEnclaveConfig) -> Result <(), SecurityModuleError> {
         self.config = Some(config);
         Ok(())
@@ -42,9 +75,12 @@ impl SecureEnclaveProvider {
 // Config Setup - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - 
 
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SecureEnclaveConfig {
     pub asym_algorithm: Option<AsymmetricEncryption>,
-    pub hash: Option<Hash>
+    pub hash: Option<Hash>,
+    /// The user authentication the Secure Enclave requires before it uses the private key.
+    pub access: AccessControl,
 }
 
 impl SecureEnclaveConfig{
@@ -66,7 +102,31 @@ impl SecureEnclaveConfig{
         Self {
             asym_algorithm, 
             hash, 
+            access: AccessControl::None,
+        }
+    }
+
+    /// Constructs a `SecureEnclaveConfig` from a validated `KeySpec`.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `spec` - The specification of the key, whose label is used as the key id.
+    /// 
+    /// # Returns
+    /// 
+    /// A `Result` containing the `SecureEnclaveConfig` on success, or a
+    /// `SecurityModuleError::UnsupportedAlgorithm` for symmetric keys, which the Secure Enclave cannot store,
+    /// or a `SecurityModuleError::InvalidKeySpec` for exportable keys.
+    pub fn from_spec(spec: &KeySpec) -> Result<SecureEnclaveConfig, SecurityModuleError> {
+        if spec.exportable() {
+            return Err(SecurityModuleError::InvalidKeySpec("The Secure Enclave cannot export keys".to_owned()));
         }
+        let asym_algorithm = spec.asymmetric_algorithm().ok_or(SecurityModuleError::UnsupportedAlgorithm)?;
+        Ok(Self {
+            asym_algorithm: Some(asym_algorithm),
+            hash: Some(spec.hash()),
+            access: spec.access(),
+        })
     }
 }
 
@@ -87,6 +147,7 @@ impl Debug for SecureEnclaveConfig {
             .field("asym_algorithm", &self.asym_algorithm)
             // .field("sym_algorithm", &self.sym_algorithm) // Not supported by Secure Enclave
             .field("hash", &self.hash)
+            .field("access", &self.access)
             .finish()
     }
 }
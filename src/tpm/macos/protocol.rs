@@ -0,0 +1,168 @@
+//! Versioning of the interface between the Secure Enclave provider and the Swift bindings.
+//!
+//! The Swift bindings are embedded in the application, so a newer version of this crate can end
+//! up linked against older bindings or vice versa. Before initializing the module,
+//! `SecureEnclaveProvider::initialize_module` asks the bindings for their version with a
+//! `bridge_version` call, which answers with `version;min_version;capabilities`, e.g.
+//! `3;3;access_control,async,key_agreement,metadata,sha512,shutdown`:
+//!
+//! - `version` is the newest protocol version the bindings implement and `min_version` the oldest
+//!   one they still serve. Both sides use the newest version they have in common, and the
+//!   initialization fails with a message naming the side to update if there is none.
+//! - `capabilities` lists the optional features of the bindings. The provider only uses a feature
+//!   both sides know, e.g. it does not request access control from bindings without
+//!   `access_control`, instead of sending them a request they cannot parse.
+//!
+//! Version 1 was the protocol of plain strings prefixed with `Error: `, version 2 returned the
+//! structured errors of `response::SwiftError` from one function per operation, and version 3
+//! sends every call through the single entry point of `dispatch`.
+
+use super::response::Response;
+use crate::common::error::SecurityModuleError;
+use std::collections::BTreeSet;
+
+/// The newest protocol version this crate implements.
+pub const PROTOCOL_VERSION: u32 = 3;
+
+/// The oldest protocol version this crate still implements.
+pub const MIN_PROTOCOL_VERSION: u32 = 3;
+
+/// The optional features of the Swift bindings.
+pub mod capability {
+    /// Keys can be created with the access control of `KeySpec::access`.
+    pub const ACCESS_CONTROL: &str = "access_control";
+    /// Signing and decrypting have asynchronous variants that do not block while the user
+    /// authenticates.
+    pub const ASYNC: &str = "async";
+    /// Signatures and encryption can use SHA-512.
+    pub const SHA512: &str = "sha512";
+    /// `shutdown_module` releases the resources held for a provider.
+    pub const SHUTDOWN: &str = "shutdown";
+    /// Shared secrets can be derived with ECDH, see `KeyHandle::derive_shared_secret`.
+    pub const KEY_AGREEMENT: &str = "key_agreement";
+    /// Key metadata can be kept in keychain items, see `metadata_store`.
+    pub const METADATA: &str = "metadata";
+}
+
+/// The capabilities this crate knows, see `capability`.
+pub const CAPABILITIES: [&str; 6] = [
+    capability::ACCESS_CONTROL,
+    capability::ASYNC,
+    capability::KEY_AGREEMENT,
+    capability::METADATA,
+    capability::SHA512,
+    capability::SHUTDOWN,
+];
+
+/// The answer of the Swift bindings to a `bridge_version` call.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BridgeVersion {
+    pub version: u32,
+    pub min_version: u32,
+    pub capabilities: BTreeSet<String>,
+}
+
+impl BridgeVersion {
+    /// Parses the payload of a `bridge_version` call, see the module documentation.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `BridgeVersion` on success, or a
+    /// `SecurityModuleError::InitializationError` if the payload is malformed.
+    pub fn parse(payload: &str) -> Result<Self, SecurityModuleError> {
+        let invalid = || {
+            SecurityModuleError::InitializationError(format!(
+                "Invalid bridge version '{}', expected version;min_version;capabilities",
+                payload.escape_debug()
+            ))
+        };
+        let mut fields = payload.split(';');
+        let (Some(version), Some(min_version), Some(capabilities), None) =
+            (fields.next(), fields.next(), fields.next(), fields.next())
+        else {
+            return Err(invalid());
+        };
+        let version = version.parse().map_err(|_| invalid())?;
+        let min_version = min_version.parse().map_err(|_| invalid())?;
+        if min_version > version {
+            return Err(invalid());
+        }
+        Ok(Self {
+            version,
+            min_version,
+            capabilities: capabilities
+                .split(',')
+                .filter(|capability| !capability.is_empty())
+                .map(str::to_owned)
+                .collect(),
+        })
+    }
+
+    /// Returns the payload the Swift bindings answer a `bridge_version` call with.
+    pub fn to_payload(&self) -> String {
+        let capabilities: Vec<&str> = self.capabilities.iter().map(String::as_str).collect();
+        format!(
+            "{};{};{}",
+            self.version,
+            self.min_version,
+            capabilities.join(",")
+        )
+    }
+}
+
+/// The protocol both sides agreed on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BridgeProtocol {
+    pub version: u32,
+    /// The capabilities of the Swift bindings that this crate knows as well.
+    pub capabilities: BTreeSet<String>,
+}
+
+impl BridgeProtocol {
+    /// Returns whether both sides support `capability`, see `capability`.
+    pub fn supports(&self, capability: &str) -> bool {
+        self.capabilities.contains(capability)
+    }
+}
+
+/// Agrees on the newest protocol version both this crate and `bindings` implement.
+///
+/// # Returns
+///
+/// A `Result` containing the `BridgeProtocol` on success, or a
+/// `SecurityModuleError::InitializationError` naming the side that needs to be updated if the
+/// versions do not overlap.
+pub fn negotiate(bindings: &BridgeVersion) -> Result<BridgeProtocol, SecurityModuleError> {
+    if bindings.version < MIN_PROTOCOL_VERSION {
+        return Err(SecurityModuleError::InitializationError(format!(
+            "The Swift bindings implement protocol version {}, but crypto-layer requires at least version {}; update the Swift bindings",
+            bindings.version, MIN_PROTOCOL_VERSION
+        )));
+    }
+    if bindings.min_version > PROTOCOL_VERSION {
+        return Err(SecurityModuleError::InitializationError(format!(
+            "The Swift bindings require at least protocol version {}, but crypto-layer implements version {}; update crypto-layer",
+            bindings.min_version, PROTOCOL_VERSION
+        )));
+    }
+    Ok(BridgeProtocol {
+        version: bindings.version.min(PROTOCOL_VERSION),
+        capabilities: bindings
+            .capabilities
+            .iter()
+            .filter(|capability| CAPABILITIES.contains(&capability.as_str()))
+            .cloned()
+            .collect(),
+    })
+}
+
+/// Negotiates the protocol from the response to a `bridge_version` call.
+pub fn negotiate_response(response: Response) -> Result<BridgeProtocol, SecurityModuleError> {
+    let payload = response.map_err(|e| {
+        SecurityModuleError::InitializationError(format!(
+            "The Swift bindings did not report their protocol version: {}",
+            e.message
+        ))
+    })?;
+    negotiate(&BridgeVersion::parse(&payload)?)
+}
@@ -1,9 +1,14 @@
-use super::{SecureEnclaveConfig, SecureEnclaveProvider};
-extern crate apple_secure_enclave_bindings;
+use super::{bridge::{Bridge, Request}, interface, protocol::{self, capability}, response, SecureEnclaveConfig, SecureEnclaveProvider};
 use crate::
     common::{
-        crypto::algorithms::{encryption::{AsymmetricEncryption, EccCurves, EccSchemeAlgorithm}, hashes::Hash, KeyBits},
+        crypto::{
+            algorithms::{encryption::{AsymmetricEncryption, EccCurves, EccSchemeAlgorithm}, hashes::Hash, KeyBits},
+            key_metadata::KeyMetadata,
+            key_spec::{AccessControl, KeySpec},
+            public_key::PublicKey,
+        },
         error::SecurityModuleError,
+        latency::key_id_hash,
         traits::module_provider::Provider,
     };
 use crate::common::crypto::algorithms::hashes::*; 
@@ -21,7 +26,7 @@ impl Provider for SecureEnclaveProvider {
     /// and identifier, making it retrievable for future operations. The key is created
     /// with the specified key usages and stored in the Secure Enclave.
     /// 
-    /// Uses the rust_crypto_call_create_key function from the Swift Secure Enclave bindings.
+    /// Uses the rust_crypto_call_dispatch function from the Swift Secure Enclave bindings.
     ///
     /// # Arguments
     ///
@@ -33,7 +38,7 @@ impl Provider for SecureEnclaveProvider {
     ///
     /// A `Result` that, on success, contains `Ok(())`, indicating that the key was created successfully.
     /// On failure, it returns a `SecurityModuleError`.
-    #[instrument]
+    #[instrument(skip_all, fields(crypto.key_id_hash = %key_id_hash(&self.key_id)))]
     fn create_key(
         &mut self,
         _key_id: &str,
@@ -47,9 +52,9 @@ impl Provider for SecureEnclaveProvider {
                 AsymmetricEncryption::Rsa(keybits) => {
                     match keybits {
                         //Works only in combination with SHA1, SHA224
-                        KeyBits::Bits512 => "RSA;512".to_string(),
+                        KeyBits::Bits512 => interface::key_type::RSA_512.to_string(),
                         //Works only in combination with SHA256, SHA384
-                        KeyBits::Bits1024 => "RSA;1024".to_string(),
+                        KeyBits::Bits1024 => interface::key_type::RSA_1024.to_string(),
                         _ => unimplemented!("With RSA only Keysize of 512 and 1024 are supported"),
                     }
                 }
@@ -57,8 +62,8 @@ impl Provider for SecureEnclaveProvider {
                     match ecc_scheme_algo {
                         EccSchemeAlgorithm::EcDsa(ecc_curve) => {
                             match ecc_curve{
-                                EccCurves::P256 => "ECDSA;256".to_string(),
-                                EccCurves::P384 => "ECDSA;384".to_string(),
+                                EccCurves::P256 => interface::key_type::ECDSA_256.to_string(),
+                                EccCurves::P384 => interface::key_type::ECDSA_384.to_string(),
                                 // EccCurves::P521 => "ECDSA;521".to_string(), Not supported by Secure Enclave
                                 _ => {return Err(InitializationError("Ecc-Curve is not supported. Only P256 and P384 are supported.".to_string()))}
                             }
@@ -68,13 +73,25 @@ impl Provider for SecureEnclaveProvider {
                 }
             };
 
-            let keypair = apple_secure_enclave_bindings::provider::rust_crypto_call_create_key(self.key_id.clone(), key_algorithm_type);
+            // The access control is appended as a third field, so that keys without one are created with the same request as before.
+            let key_algorithm_type = match access_control_flag(config.access) {
+                Some(_) if self.protocol.as_ref().is_some_and(|protocol| !protocol.supports(capability::ACCESS_CONTROL)) => {
+                    return Err(InitializationError("The Swift bindings do not support access control, update them to create keys requiring user authentication".to_string()))
+                }
+                Some(flag) => format!("{};{}", key_algorithm_type, flag),
+                None => key_algorithm_type,
+            };
 
-            if keypair.0 {
-                Err(SecurityModuleError::InitializationError(keypair.1.to_string()))
-            } else {
-                Ok(())
+            let keypair = self.bridge.call(Request::CreateKey { key_id: self.key_id.clone(), key_type: key_algorithm_type });
+            response::decode_status(keypair, InitializationError)?;
+
+            // The public key is needed for every verification, so it is exported once while creating the key.
+            // Without a hash algorithm signatures cannot be verified, and the metadata is fetched on `load_key`.
+            if config.hash.is_some() {
+                self.metadata = Some(fetch_key_metadata(&self.bridge, &self.key_id, &config)?);
+                let _ = self.set_config(config);
             }
+            Ok(())
         }else{
             return Err(InitializationError("Algorithm is not supported".to_string()))
         }
@@ -87,7 +104,7 @@ impl Provider for SecureEnclaveProvider {
     /// If successful, it sets the key usages and returns a handle to the key for further
     /// cryptographic operations.
     /// 
-    /// Uses the rust_crypto_call_load_key function from the Swift Secure Enclave bindings.
+    /// Uses the rust_crypto_call_dispatch function from the Swift Secure Enclave bindings.
     ///
     /// # Arguments
     ///
@@ -99,7 +116,7 @@ impl Provider for SecureEnclaveProvider {
     ///
     /// A `Result` that, on success, contains `Ok(())`, indicating that the key was loaded successfully.
     /// On failure, it returns a `SecurityModuleError`.
-    #[instrument]
+    #[instrument(skip_all, fields(crypto.key_id_hash = %key_id_hash(&self.key_id)))]
     fn load_key(
         &mut self,
         _key_id: &str,
@@ -110,11 +127,10 @@ impl Provider for SecureEnclaveProvider {
         let algorithm = convert_algorithms(config.clone()); 
         let hash = convert_hash(config.hash.expect("No Hash given"));
 
-        let load_key = apple_secure_enclave_bindings::provider::rust_crypto_call_load_key(_key_id.to_string(), algorithm, hash);
+        let load_key = self.bridge.call(Request::LoadKey { key_id: _key_id.to_string(), key_type: algorithm.clone(), hash });
+        response::decode_status(load_key, InitializationError)?;
 
-        if load_key.0 {
-            return Err(SecurityModuleError::InitializationError(load_key.1.to_string()))
-        }
+        self.metadata = Some(fetch_key_metadata(&self.bridge, _key_id, &config)?);
         return Ok(())
     }
 
@@ -124,24 +140,146 @@ impl Provider for SecureEnclaveProvider {
     /// This method initializes the Secure Enclave context and prepares it for use. It should be called
     /// before performing any other operations with the Secure Enclave.
     /// 
-    /// Uses the rust_crypto_call_sign_data function from the Swift Secure Enclave bindings.
+    /// Uses the rust_crypto_call_dispatch function from the Swift Secure Enclave bindings.
     ///
     /// # Returns
     ///
     /// A `Result` that, on success, contains `Ok(())`, indicating that the module was initialized successfully.
     /// On failure, it returns a `SecurityModuleError`.
-    #[instrument]
+    /// The state of the initialization belongs to this provider: once it succeeded, further calls return
+    /// `Ok(())` without calling the Swift bindings again, until the provider is shut down.
+    /// Before initializing, the protocol is negotiated with the bindings, see `protocol`; if this crate and the
+    /// bindings have no protocol version in common, a `SecurityModuleError::InitializationError` names the side
+    /// to update.
+    #[instrument(skip_all, fields(crypto.key_id_hash = %key_id_hash(&self.key_id)))]
     fn initialize_module(&mut self) -> Result<(), SecurityModuleError> {
-        let initialization_result =
-            apple_secure_enclave_bindings::provider::rust_crypto_call_initialize_module();
+        if self.protocol.is_some() {
+            return Ok(());
+        }
+        // Agree on a protocol first, so that mismatched Swift bindings fail with a clear message.
+        let protocol = protocol::negotiate_response(self.bridge.call(Request::BridgeVersion))?;
+        let initialization_result = self.bridge.call(Request::InitializeModule);
 
         match initialization_result {
-            true => Ok(()),
-            false => Err(SecurityModuleError::InitializationError(
+            Ok(_) => {
+                self.protocol = Some(protocol);
+                Ok(())
+            }
+            Err(_) => Err(SecurityModuleError::InitializationError(
                 "Failed to initialize module".to_string(),
             )),
         }
     }
+
+    /// Releases the resources the Swift bindings hold for this provider and forgets the loaded key.
+    ///
+    /// Does nothing if the provider is not initialized, so repeated calls only call the Swift bindings once.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` that is `Ok(())` on success, or a `SecurityModuleError::InitializationError` if the
+    /// Swift bindings failed to release their resources. The provider is shut down either way.
+    #[instrument(skip_all, fields(crypto.key_id_hash = %key_id_hash(&self.key_id)))]
+    fn shutdown(&mut self) -> Result<(), SecurityModuleError> {
+        let Some(protocol) = self.protocol.take() else {
+            return Ok(());
+        };
+        self.config = None;
+        self.metadata = None;
+        // Older Swift bindings hold no resources for a provider.
+        if !protocol.supports(capability::SHUTDOWN) {
+            return Ok(());
+        }
+
+        match self.bridge.call(Request::ShutdownModule) {
+            Ok(_) => Ok(()),
+            Err(_) => Err(SecurityModuleError::InitializationError(
+                "Failed to shut down module".to_string(),
+            )),
+        }
+    }
+
+    /// Returns the metadata cached when the key was created or loaded.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `KeyMetadata` on success, or a `SecurityModuleError` if no key has been
+    /// created or loaded. The Secure Enclave does not attest its keys, so the metadata never contains attestation data.
+    #[instrument(skip_all, fields(crypto.key_id_hash = %key_id_hash(&self.key_id)))]
+    fn key_metadata(&self) -> Result<KeyMetadata, SecurityModuleError> {
+        self.metadata.clone().ok_or(InitializationError("No key loaded".to_owned()))
+    }
+
+    /// Exports the public key from the Secure Enclave again and updates the cached metadata.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the fetched `KeyMetadata` on success, or a `SecurityModuleError` on failure.
+    #[instrument(skip_all, fields(crypto.key_id_hash = %key_id_hash(&self.key_id)))]
+    fn refresh_key_metadata(&mut self) -> Result<KeyMetadata, SecurityModuleError> {
+        let config = self.config.clone().ok_or(InitializationError("No key loaded".to_owned()))?;
+        let key_id = self.metadata.as_ref().map_or(self.key_id.clone(), |metadata| metadata.key_id().to_owned());
+
+        let metadata = fetch_key_metadata(&self.bridge, &key_id, &config)?;
+        self.metadata = Some(metadata.clone());
+        Ok(metadata)
+    }
+
+    /// Always fails, keys of the Secure Enclave are generated inside it and cannot be imported.
+    ///
+    /// # Returns
+    ///
+    /// A `SecurityModuleError::UnsupportedOperation`, regardless of the arguments.
+    #[instrument(skip_all, fields(crypto.key_id_hash = %key_id_hash(_key_id)))]
+    fn import_wrapped_key(
+        &mut self,
+        _key_id: &str,
+        _wrapped_key: &[u8],
+        _wrapping_key_id: &str,
+        _spec: &KeySpec,
+    ) -> Result<(), SecurityModuleError> {
+        Err(SecurityModuleError::UnsupportedOperation(
+            "The Secure Enclave only uses keys generated inside it and cannot import keys".to_owned(),
+        ))
+    }
+}
+
+/// Exports the public key of a key pair and wraps it in `KeyMetadata`.
+///
+/// # Arguments
+///
+/// * `bridge` - The bridge to the Swift bindings.
+/// * `key_id` - A string slice that uniquely identifies the key pair.
+/// * `config` - A `SecureEnclaveConfig` object containing the configuration for the key.
+///
+/// # Returns
+///
+/// A `Result` containing the `KeyMetadata` on success, or a `SecurityModuleError` on failure.
+fn fetch_key_metadata(bridge: &Bridge, key_id: &str, config: &SecureEnclaveConfig) -> Result<KeyMetadata, SecurityModuleError> {
+    let algorithm = convert_algorithms(config.clone());
+    KeyMetadata::new(key_id, export_public_key(bridge, key_id, algorithm, config)?)
+}
+
+/// Exports the public key of a key pair from the Secure Enclave.
+/// 
+/// Uses the rust_crypto_call_dispatch function from the Swift Secure Enclave bindings.
+/// 
+/// # Arguments
+/// 
+/// * `bridge` - The bridge to the Swift bindings.
+/// * `key_id` - A string slice that uniquely identifies the key pair.
+/// * `algorithm` - The algorithm type as returned by `convert_algorithms`.
+/// * `config` - A `SecureEnclaveConfig` object containing the configuration for the key.
+/// 
+/// # Returns
+/// 
+/// A `Result` containing the `PublicKey` on success, or a `SecurityModuleError` on failure.
+fn export_public_key(bridge: &Bridge, key_id: &str, algorithm: String, config: &SecureEnclaveConfig) -> Result<PublicKey, SecurityModuleError> {
+    let asym_algorithm = config.asym_algorithm.ok_or(SecurityModuleError::UnsupportedAlgorithm)?;
+    let hash = config.hash.ok_or(SecurityModuleError::UnsupportedAlgorithm)?;
+
+    let public_key = bridge.call(Request::GetPublicKey { key_id: key_id.to_string(), algorithm });
+    response::decode_public_key(public_key, asym_algorithm, hash)
 }
 
 /// Converts the algorithm type to a String.
@@ -156,8 +294,8 @@ impl Provider for SecureEnclaveProvider {
 pub fn convert_algorithms(config: SecureEnclaveConfig) -> String {
     let asym_algorithm_type = match config.asym_algorithm.expect("Invalid config") {
         // Is only Asymmetric-Algorithm which is working at that time
-        AsymmetricEncryption::Rsa(_) => "RSA".to_string(),
-        AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(_)) => "ECDSA".to_string(), 
+        AsymmetricEncryption::Rsa(_) => interface::algorithm::RSA.to_string(),
+        AsymmetricEncryption::Ecc(EccSchemeAlgorithm::EcDsa(_)) => interface::algorithm::ECDSA.to_string(), 
         _ => unimplemented!("Only RSA and ECDSA supported") ,
     };
 
@@ -178,13 +316,24 @@ pub fn convert_hash(hash: Hash) -> String {
     match hash {
         Hash::Sha2(sha2_bits) =>{
             match sha2_bits {
-                Sha2Bits::Sha224 => "SHA224".to_string(),
-                Sha2Bits::Sha256 => "SHA256".to_string(),
-                Sha2Bits::Sha384 => "SHA384".to_string(),
-                Sha2Bits::Sha512 => "SHA512".to_string(),
+                Sha2Bits::Sha224 => interface::hash::SHA224.to_string(),
+                Sha2Bits::Sha256 => interface::hash::SHA256.to_string(),
+                Sha2Bits::Sha384 => interface::hash::SHA384.to_string(),
+                Sha2Bits::Sha512 => interface::hash::SHA512.to_string(),
                 _ => unimplemented!("Only SHA224, SHA256, SHA384, SHA512 supported."),            
             }
         }, 
         _ => unimplemented!("Only SHA1 and Sha2Bits supported."), 
     }
 }
+
+/// Returns the name of the `SecAccessControlCreateFlags` the Swift bindings add to `.privateKeyUsage`, or `None` if the key needs no user authentication.
+fn access_control_flag(access: AccessControl) -> Option<&'static str> {
+    match access {
+        AccessControl::None => None,
+        AccessControl::UserPresence => Some(interface::access::USER_PRESENCE),
+        AccessControl::BiometryAny => Some(interface::access::BIOMETRY_ANY),
+        AccessControl::BiometryCurrentSet => Some(interface::access::BIOMETRY_CURRENT_SET),
+        AccessControl::DevicePasscode => Some(interface::access::DEVICE_PASSCODE),
+    }
+}
@@ -0,0 +1,204 @@
+//! Decoding of the responses returned by the Swift Secure Enclave bindings.
+//!
+//! The bindings return every result as an `FfiResponse`, which holds either the payload or an
+//! `FfiError` with a code, a domain and a message. A `Response` is its Rust counterpart. The
+//! decoding is kept free of FFI calls, so that it can be tested and fuzzed without a Secure Enclave.
+
+use crate::common::{
+    crypto::{
+        algorithms::{encryption::AsymmetricEncryption, hashes::Hash},
+        public_key::{PublicKey, RsaSignaturePadding},
+    },
+    error::SecurityModuleError,
+};
+use base64::{prelude::BASE64_STANDARD, Engine};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// The domain of the errors thrown as `SecureEnclaveError` by the Swift bindings.
+pub const SECURE_ENCLAVE_DOMAIN: &str = "SecureEnclaveError";
+
+/// The domain of the errors raised on the Rust side of the bridge, e.g. by a replaying `Bridge`.
+pub const BRIDGE_DOMAIN: &str = "crypto-layer.bridge";
+
+/// A response of the Swift bindings: the payload, or the error the call failed with.
+pub type Response = Result<String, SwiftError>;
+
+/// The kind of an error reported by the Swift bindings, transferred as the `code` of an `FfiError`.
+///
+/// All cases but `Other` correspond to the cases of `SecureEnclaveError` in the bindings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SwiftErrorCode {
+    /// An error that is not a `SecureEnclaveError`, e.g. an `NSError` of the Security framework,
+    /// or a code unknown to this version of the crate.
+    Other,
+    Runtime,
+    Initialization,
+    CreateKey,
+    LoadKey,
+    Signing,
+    SignatureVerification,
+    Encryption,
+    Decryption,
+}
+
+impl SwiftErrorCode {
+    /// Returns the code of an `FfiError` with this kind.
+    pub fn code(self) -> u32 {
+        match self {
+            SwiftErrorCode::Other => 0,
+            SwiftErrorCode::Runtime => 1,
+            SwiftErrorCode::Initialization => 2,
+            SwiftErrorCode::CreateKey => 3,
+            SwiftErrorCode::LoadKey => 4,
+            SwiftErrorCode::Signing => 5,
+            SwiftErrorCode::SignatureVerification => 6,
+            SwiftErrorCode::Encryption => 7,
+            SwiftErrorCode::Decryption => 8,
+        }
+    }
+
+    /// Returns the kind of the code of an `FfiError`. Unknown codes are `Other`.
+    pub fn from_code(code: u32) -> Self {
+        match code {
+            1 => SwiftErrorCode::Runtime,
+            2 => SwiftErrorCode::Initialization,
+            3 => SwiftErrorCode::CreateKey,
+            4 => SwiftErrorCode::LoadKey,
+            5 => SwiftErrorCode::Signing,
+            6 => SwiftErrorCode::SignatureVerification,
+            7 => SwiftErrorCode::Encryption,
+            8 => SwiftErrorCode::Decryption,
+            _ => SwiftErrorCode::Other,
+        }
+    }
+}
+
+/// An error reported by the Swift bindings, the Rust counterpart of their `FfiError`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SwiftError {
+    pub code: SwiftErrorCode,
+    /// `SECURE_ENCLAVE_DOMAIN`, the domain of an `NSError`, or `BRIDGE_DOMAIN`.
+    pub domain: String,
+    pub message: String,
+}
+
+impl SwiftError {
+    /// Creates an error with the fields of an `FfiError`.
+    pub fn new(code: u32, domain: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            code: SwiftErrorCode::from_code(code),
+            domain: domain.into(),
+            message: message.into(),
+        }
+    }
+
+    /// Creates an error raised on the Rust side of the bridge.
+    pub fn bridge(message: impl Into<String>) -> Self {
+        Self {
+            code: SwiftErrorCode::Other,
+            domain: BRIDGE_DOMAIN.to_owned(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for SwiftError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// Decodes a response without a result, e.g. of creating or loading a key.
+///
+/// # Arguments
+///
+/// * `response` - The response of the Swift bindings.
+/// * `error` - Converts the error message into a `SecurityModuleError`.
+pub fn decode_status(
+    response: Response,
+    error: fn(String) -> SecurityModuleError,
+) -> Result<(), SecurityModuleError> {
+    response.map(|_| ()).map_err(|e| error(e.message))
+}
+
+/// Decodes a response whose result is passed on as bytes, e.g. a signature or ciphertext.
+///
+/// # Arguments
+///
+/// * `response` - The response of the Swift bindings.
+/// * `error` - Converts the error message into a `SecurityModuleError`.
+pub fn decode_bytes(
+    response: Response,
+    error: fn(String) -> SecurityModuleError,
+) -> Result<Vec<u8>, SecurityModuleError> {
+    response
+        .map(String::into_bytes)
+        .map_err(|e| error(e.message))
+}
+
+/// Decodes a response whose result is base64 encoded binary data, e.g. a decrypted plaintext.
+///
+/// # Arguments
+///
+/// * `response` - The response of the Swift bindings.
+/// * `error` - Converts the error message, or the message of an invalid encoding, into a
+///   `SecurityModuleError`.
+pub fn decode_base64(
+    response: Response,
+    error: fn(String) -> SecurityModuleError,
+) -> Result<Vec<u8>, SecurityModuleError> {
+    let result = response.map_err(|e| error(e.message))?;
+    BASE64_STANDARD
+        .decode(result)
+        .map_err(|e| error(e.to_string()))
+}
+
+/// Decodes the response of a signature verification.
+///
+/// The bindings report the result as `"true"` or `"false"`, any other payload is invalid.
+pub fn decode_verification(response: Response) -> Result<bool, SecurityModuleError> {
+    match response {
+        Ok(result) if result == "true" => Ok(true),
+        Ok(result) if result == "false" => Ok(false),
+        Ok(result) => Err(SecurityModuleError::SignatureVerificationError(format!(
+            "Invalid verification result '{}'",
+            result.escape_debug()
+        ))),
+        Err(e) => Err(SecurityModuleError::SignatureVerificationError(e.message)),
+    }
+}
+
+/// Decodes an exported public key.
+///
+/// The Security framework exports EC keys as X9.63 points and RSA keys in PKCS#1 format, the
+/// bindings encode them in base64.
+///
+/// # Arguments
+///
+/// * `response` - The response of the Swift bindings.
+/// * `algorithm` - The asymmetric algorithm of the key pair.
+/// * `hash` - The hash algorithm used for signing.
+pub fn decode_public_key(
+    response: Response,
+    algorithm: AsymmetricEncryption,
+    hash: Hash,
+) -> Result<PublicKey, SecurityModuleError> {
+    let result = response.map_err(|e| SecurityModuleError::InitializationError(e.message))?;
+    let public_key_bytes = BASE64_STANDARD
+        .decode(result)
+        .map_err(|_| SecurityModuleError::InvalidPublicKey)?;
+
+    match algorithm {
+        AsymmetricEncryption::Rsa(_) => {
+            Ok(
+                PublicKey::from_pkcs1_der(&public_key_bytes, algorithm, hash)?
+                    .with_rsa_padding(RsaSignaturePadding::Pss),
+            )
+        }
+        AsymmetricEncryption::Ecc(_) => {
+            PublicKey::from_ec_point(&public_key_bytes, algorithm, hash)
+        }
+    }
+}
@@ -1,28 +1,75 @@
-use std::{path::PathBuf, process::Command, env};
+use std::{
+    env,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// Path of a prebuilt Swift library, either a static library (`.a`) or an `.xcframework` holding one
+/// per platform. If it is set, the Swift package is not built and no Swift toolchain is needed.
+const PREBUILT_ENV: &str = "SECURE_ENCLAVE_BINDINGS_PREBUILT";
+/// If set to `1` or `true`, the Swift package is built without network access and only with the
+/// dependency versions pinned in `swift-library/Package.resolved`. `CARGO_NET_OFFLINE` enables it
+/// as well.
+const OFFLINE_ENV: &str = "SECURE_ENCLAVE_BINDINGS_OFFLINE";
+/// If set to `1` or `true`, the build additionally packages the Swift library as
+/// `swift-library/.build/SecureEnclaveBindings.xcframework` for macOS, iOS and the iOS simulator.
+const XCFRAMEWORK_ENV: &str = "SECURE_ENCLAVE_BINDINGS_XCFRAMEWORK";
+
 fn main() {
-    
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=src/lib.rs");
+    println!("cargo:rerun-if-changed=swift-library/compile.sh");
+    println!("cargo:rerun-if-changed=swift-library/Package.swift");
+    println!("cargo:rerun-if-changed=swift-library/Package.resolved");
+    println!(
+        "cargo:rerun-if-changed={}",
+        swift_source_dir()
+            .join("SecureEnclaveManager.swift")
+            .display()
+    );
+    println!("cargo:rerun-if-env-changed={}", PREBUILT_ENV);
+    println!("cargo:rerun-if-env-changed={}", OFFLINE_ENV);
+    println!("cargo:rerun-if-env-changed=CARGO_NET_OFFLINE");
+    println!("cargo:rerun-if-env-changed={}", XCFRAMEWORK_ENV);
+
     let mut ios = false;
     let mut sdk_name = "macosx";
-    let target = String::from(env::var("TARGET").unwrap());
+    let target = env::var("TARGET").unwrap();
+    // x86_64 iOS targets always run in the simulator.
+    let simulator = target.ends_with("-ios-sim") || target == "x86_64-apple-ios";
 
     if target.contains("ios") {
         ios = true;
-        sdk_name = "iphoneos"; 
+        sdk_name = if simulator {
+            "iphonesimulator"
+        } else {
+            "iphoneos"
+        };
     }
-    
-    // 1. Use `swift-bridge-build` to generate Swift/C FFI glue.
-    //    You can also use the `swift-bridge` CLI.
-    let bridge_files = vec!["src/lib.rs"];
-    swift_bridge_build::parse_bridges(bridge_files)
-        .write_all_concatenated(swift_bridge_out_dir(), "rust-calls-swift");
 
-    // 2. Compile Swift library and set name for linking library
-    compile_swift(ios);
+    match env::var_os(PREBUILT_ENV) {
+        // The prebuilt library already contains the generated Swift FFI glue.
+        Some(prebuilt) => link_prebuilt(Path::new(&prebuilt), ios, simulator),
+        None => {
+            // 1. Use `swift-bridge-build` to generate Swift/C FFI glue.
+            //    You can also use the `swift-bridge` CLI.
+            let bridge_files = vec!["src/lib.rs"];
+            swift_bridge_build::parse_bridges(bridge_files)
+                .write_all_concatenated(swift_bridge_out_dir(), "rust-calls-swift");
 
-    println!(
-        "cargo:rustc-link-search={}",
-        swift_library_static_lib_dir(ios).to_str().unwrap()
-    );
+            // 2. Compile Swift library and set name for linking library
+            check_toolchain(ios);
+            compile_swift(ios);
+            if is_enabled(XCFRAMEWORK_ENV) {
+                package_xcframework();
+            }
+
+            println!(
+                "cargo:rustc-link-search={}",
+                swift_library_static_lib_dir(ios).to_str().unwrap()
+            );
+        }
+    }
 
     // Without this we will get warnings about not being able to find dynamic libraries, and then
     // we won't be able to compile since the Swift static libraries depend on them:
@@ -31,52 +78,192 @@ fn main() {
     // ld: warning: Could not find or use auto-linked library 'swiftCompatibility50'
     // ld: warning: Could not find or use auto-linked library 'swiftCompatibilityDynamicReplacements'
     // ld: warning: Could not find or use auto-linked library 'swiftCompatibilityConcurrency'
-    let xcode_path = if let Ok(output) = std::process::Command::new("xcode-select")
-        .arg("--print-path")
-        .output()
-    {
-        String::from_utf8(output.stdout.as_slice().into())
-            .unwrap()
-            .trim()
-            .to_string()
-    } else {
-        "/Applications/Xcode.app/Contents/Developer".to_string()
+    let xcode_path = match Command::new("xcode-select").arg("--print-path").output() {
+        Ok(output) if output.status.success() => {
+            String::from_utf8_lossy(&output.stdout).trim().to_string()
+        }
+        _ => "/Applications/Xcode.app/Contents/Developer".to_string(),
     };
-        
+
     println!(
         "cargo:rustc-link-search={}/Toolchains/XcodeDefault.xctoolchain/usr/lib/swift/{}/",
         &xcode_path, &sdk_name
     );
-    
-    println!("cargo:rustc-link-search={}", "/usr/lib/swift");
+
+    println!("cargo:rustc-link-search=/usr/lib/swift");
+}
+
+/// Links the static library at `path`, or the library of the matching platform of an `.xcframework`.
+fn link_prebuilt(path: &Path, ios: bool, simulator: bool) {
+    println!("cargo:rerun-if-changed={}", path.display());
+    let library = if path
+        .extension()
+        .is_some_and(|extension| extension == "xcframework")
+    {
+        xcframework_library(path, ios, simulator)
+    } else {
+        path.to_path_buf()
+    };
+
+    let name = library
+        .file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| name.strip_prefix("lib"))
+        .and_then(|name| name.strip_suffix(".a"))
+        .unwrap_or_else(|| {
+            fail(&format!(
+                "{} must point to a static library named 'lib<name>.a' or to an .xcframework, but is '{}'.",
+                PREBUILT_ENV,
+                library.display()
+            ))
+        });
+    if !library.is_file() {
+        fail(&format!(
+            "The prebuilt Swift library '{}' of {} does not exist.",
+            library.display(),
+            PREBUILT_ENV
+        ));
+    }
+
+    println!(
+        "cargo:rustc-link-search=native={}",
+        library.parent().unwrap().display()
+    );
+    println!("cargo:rustc-link-lib=static={}", name);
+}
+
+/// Returns the static library of the slice of `xcframework` for macOS, iOS or the iOS simulator, e.g.
+/// `macos-arm64_x86_64/libswift-library.a`.
+fn xcframework_library(xcframework: &Path, ios: bool, simulator: bool) -> PathBuf {
+    let platform = if ios { "ios-" } else { "macos-" };
+    let entries = std::fs::read_dir(xcframework).unwrap_or_else(|e| {
+        fail(&format!(
+            "Cannot read the .xcframework '{}' of {}: {}",
+            xcframework.display(),
+            PREBUILT_ENV,
+            e
+        ))
+    });
+
+    entries
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|slice| {
+            slice
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| {
+                    name.starts_with(platform) && name.ends_with("-simulator") == simulator
+                })
+        })
+        .filter_map(|slice| std::fs::read_dir(slice).ok())
+        .flatten()
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .find(|library| {
+            library
+                .extension()
+                .is_some_and(|extension| extension == "a")
+        })
+        .unwrap_or_else(|| {
+            fail(&format!(
+                "The .xcframework '{}' of {} has no static library for {}.",
+                xcframework.display(),
+                PREBUILT_ENV,
+                match (ios, simulator) {
+                    (false, _) => "macOS",
+                    (true, false) => "iOS",
+                    (true, true) => "the iOS simulator",
+                }
+            ))
+        })
+}
+
+/// Fails the build with a hint if the Swift toolchain needed to build the Swift package is missing.
+fn check_toolchain(ios: bool) {
+    let tool = if ios { "swiftc" } else { "swift" };
+    let found = Command::new("xcrun")
+        .args(["--find", tool])
+        .output()
+        .is_ok_and(|output| output.status.success());
+    if !found {
+        fail(&format!(
+            "Cannot find the Swift toolchain ('xcrun --find {}' failed). Install Xcode or the Xcode \
+             Command Line Tools with 'xcode-select --install' and select them with 'xcode-select --switch', \
+             or set {} to a prebuilt libswift-library.a or .xcframework to link without building the Swift package.",
+            tool, PREBUILT_ENV
+        ));
+    }
+}
+
+/// Returns whether the environment variable `name` is set to `1` or `true`.
+fn is_enabled(name: &str) -> bool {
+    env::var(name).is_ok_and(|value| value == "1" || value.eq_ignore_ascii_case("true"))
+}
+
+/// Returns whether the Swift package must be built without network access.
+fn is_offline() -> bool {
+    is_enabled(OFFLINE_ENV) || is_enabled("CARGO_NET_OFFLINE")
 }
 
 fn compile_swift(ios: bool) {
-    let swift_package_dir = manifest_dir().join("swift-library");
-    let mut bash_cmd = Command::new("bash"); 
-    if ios{
-        bash_cmd.current_dir(swift_package_dir).args(&["./compile.sh", "ios"]);
+    if ios {
+        run_compile_script("ios");
         println!("cargo:rustc-link-lib=static=swift-library_ios");
-               
-    }else{
-        bash_cmd.current_dir(swift_package_dir).args(&["./compile.sh", "macos"]);
+    } else {
+        run_compile_script("macos");
         println!("cargo:rustc-link-lib=static=swift-library");
     }
+}
 
-    let exit_status = bash_cmd.spawn().unwrap().wait_with_output().unwrap();
+/// Packages the Swift library as an XCFramework with a macOS, an iOS and an iOS simulator slice, which
+/// app projects can add with Xcode or as a `binaryTarget` of a Swift package.
+fn package_xcframework() {
+    run_compile_script("xcframework");
+    println!(
+        "cargo:warning=Packaged {}",
+        manifest_dir()
+            .join("swift-library/.build/SecureEnclaveBindings.xcframework")
+            .display()
+    );
+}
+
+/// Runs `swift-library/compile.sh` for `target` and fails the build if it fails.
+fn run_compile_script(target: &str) {
+    let swift_package_dir = manifest_dir().join("swift-library");
+    let mut bash_cmd = Command::new("bash");
+    bash_cmd
+        .current_dir(swift_package_dir)
+        .args(["./compile.sh", target]);
+    if is_offline() {
+        // Never resolves or fetches dependencies, so the build fails instead of using versions that
+        // are not pinned in Package.resolved.
+        bash_cmd.env("SWIFT_BUILD_FLAGS", "--disable-automatic-resolution");
+    }
 
-    if !exit_status.status.success() {
-        panic!(
-            r#"
+    let output = bash_cmd
+        .output()
+        .unwrap_or_else(|e| fail(&format!("Cannot run swift-library/compile.sh: {}", e)));
+
+    if !output.status.success() {
+        fail(&format!(
+            r#"Building the Swift package for {} failed.
 Stderr: {}
 Stdout: {}
 "#,
-            String::from_utf8(exit_status.stderr).unwrap(),
-            String::from_utf8(exit_status.stdout).unwrap(),
-        )
+            target,
+            String::from_utf8_lossy(&output.stderr),
+            String::from_utf8_lossy(&output.stdout),
+        ))
     }
 }
 
+/// Fails the build with `message`, which cargo shows without a backtrace of the build script.
+fn fail(message: &str) -> ! {
+    eprintln!("error: {}", message);
+    std::process::exit(1)
+}
+
 fn swift_bridge_out_dir() -> PathBuf {
     generated_code_dir()
 }
@@ -105,10 +292,9 @@ fn swift_library_static_lib_dir(ios: bool) -> PathBuf {
         "debug"
     };
 
-    if ios{
-        manifest_dir().join(format!("swift-library/.build/"))
-    }else{
+    if ios {
+        manifest_dir().join("swift-library/.build/")
+    } else {
         manifest_dir().join(format!("swift-library/.build/{}", debug_or_release))
-
     }
 }
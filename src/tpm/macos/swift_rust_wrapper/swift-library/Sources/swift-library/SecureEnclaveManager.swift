@@ -2,6 +2,157 @@ import Foundation
 import LocalAuthentication
 import Security
 import CryptoKit
+import os.log
+
+    /// The log of the calls from the rust-side, shown by `log stream --predicate 'subsystem == "crypto-layer"'`.
+    let secure_enclave_log = OSLog(subsystem: "crypto-layer", category: "SecureEnclave")
+    let correlation_id_key = "crypto-layer.correlation_id"
+
+    /**
+    Sets the correlation id of the operation the following calls on the current thread belong to.
+
+    - Parameter correlation_id: A String holding the correlation id of the rust-side operation.
+    */
+    func set_correlation_id(_ correlation_id: String) {
+        Thread.current.threadDictionary[correlation_id_key] = correlation_id
+    }
+
+    /**
+    Logs a call from the rust-side together with its correlation id.
+
+    - Parameter method: A String naming the called function.
+    - Parameter correlation_id: The correlation id passed by an asynchronous call, which may run on another thread. Defaults to the one of the current thread.
+    */
+    func log_call(_ method: String, correlation_id: String? = nil) {
+        os_log("[%{public}@] %{public}@", log: secure_enclave_log, type: .debug, correlation_id ?? current_correlation_id(), method)
+    }
+
+    /**
+    Logs a failed call from the rust-side together with its correlation id.
+
+    - Parameter method: A String naming the called function.
+    - Parameter error: The error the call failed with.
+    - Parameter correlation_id: The correlation id passed by an asynchronous call. Defaults to the one of the current thread.
+    */
+    func log_failure(_ method: String, _ error: Error, correlation_id: String? = nil) {
+        os_log("[%{public}@] %{public}@ failed: %{public}@", log: secure_enclave_log, type: .error, correlation_id ?? current_correlation_id(), method, String(describing: error))
+    }
+
+    /// Returns the correlation id set by the rust-side for the current thread, or "-" if there is none.
+    func current_correlation_id() -> String {
+        return Thread.current.threadDictionary[correlation_id_key] as? String ?? "-"
+    }
+
+    let security_queue_key = DispatchSpecificKey<Bool>()
+
+    /// The serial queue every Security.framework and LocalAuthentication call runs on. The rust-side calls from arbitrary threads, possibly at the same time, but a 'LAContext' and the keychain items queried with it must be used from one queue, otherwise the calls fail intermittently.
+    let security_queue: DispatchQueue = {
+        let queue = DispatchQueue(label: "crypto-layer.secure-enclave", qos: .userInitiated)
+        queue.setSpecific(key: security_queue_key, value: true)
+        return queue
+    }()
+
+    /**
+    Runs 'work' and turns an Objective-C exception it raises into a thrown error. Swift cannot catch these exceptions, e.g. of Foundation or the keychain, which would otherwise terminate the whole process of the rust-side.
+
+    - Parameter work: The calls to perform.
+    - Throws: The error thrown by 'work', or 'SecureEnclaveError.runtimeError' if it raised an exception.
+    - Returns: The result of 'work'.
+    */
+    func catching_exceptions<T>(_ work: () throws -> T) throws -> T {
+        var result: Result<T, Error>?
+        if let exception = crypto_layer_catch_exception({ result = Result { try work() } }) {
+            throw SecureEnclaveError.runtimeError("Unexpected exception \(exception.name.rawValue): \(exception.reason ?? "no reason")")
+        }
+        guard let result = result else {
+            throw SecureEnclaveError.runtimeError("The operation did not return.")
+        }
+        return try result.get()
+    }
+
+    /**
+    Runs 'work' on the security queue and waits for its result. Runs it directly if the current thread already runs on the queue, so nested calls do not deadlock.
+
+    - Parameter work: The Security.framework calls to perform.
+    - Throws: The error thrown by 'work', or 'SecureEnclaveError.runtimeError' if it raised an exception.
+    - Returns: The result of 'work'.
+    */
+    func on_security_queue<T>(_ work: () throws -> T) throws -> T {
+        if DispatchQueue.getSpecific(key: security_queue_key) == true {
+            return try catching_exceptions(work)
+        }
+        return try security_queue.sync { try catching_exceptions(work) }
+    }
+
+    /**
+    Asynchronous variant of @on_security_queue(): suspends the calling task instead of blocking its thread until 'work' has run on the security queue.
+
+    - Parameter work: The Security.framework calls to perform.
+    - Throws: The error thrown by 'work', or 'SecureEnclaveError.runtimeError' if it raised an exception.
+    - Returns: The result of 'work'.
+    */
+    func on_security_queue_async<T>(_ work: @escaping () throws -> T) async throws -> T {
+        return try await withCheckedThrowingContinuation { continuation in
+            security_queue.async {
+                continuation.resume(with: Result { try catching_exceptions(work) })
+            }
+        }
+    }
+
+    /**
+    Returns 'value', or throws 'error' if it is nil. Used instead of force unwrapping, which would terminate the whole process of the rust-side instead of failing the call.
+
+    - Parameter value: The optional result of a call.
+    - Parameter error: The error to throw if there is no result.
+    - Throws: 'error' if 'value' is nil.
+    - Returns: The unwrapped 'value'.
+    */
+    func required<T>(_ value: T?, _ error: @autoclosure () -> SecureEnclaveError) throws -> T {
+        guard let value = value else {
+            throw error()
+        }
+        return value
+    }
+
+    /// An error thrown while handling a call. 'code' is 0 for errors that are no 'SecureEnclaveError', whose domain is then the domain of the 'NSError'.
+    struct FfiError: Codable {
+        let code: UInt32
+        let domain: String
+        let message: String
+    }
+
+    /// The result of a call: the payload, or the error the call failed with. Encoded as JSON, it is the response of 'rustcall_dispatch'.
+    struct FfiResponse: Codable {
+        let payload: String
+        let error: FfiError?
+    }
+
+    /**
+    Wraps the payload of a successful call for the rust-side.
+
+    - Parameter payload: A String holding the result of the call.
+    - Returns: A 'FfiResponse' without an error.
+    */
+    func ffi_success(_ payload: String) -> FfiResponse {
+        return FfiResponse(payload: payload, error: nil)
+    }
+
+    /**
+    Wraps the error of a failed call for the rust-side. A 'SecureEnclaveError' keeps its case as code, any other error is passed on with code 0 and the domain of its 'NSError'.
+
+    - Parameter error: The error the call failed with.
+    - Returns: A 'FfiResponse' holding the error as 'FfiError'.
+    */
+    func ffi_failure(_ error: Error) -> FfiResponse {
+        let ffi_error: FfiError
+        if let error = error as? SecureEnclaveError {
+            ffi_error = FfiError(code: error.code, domain: "SecureEnclaveError", message: error.message)
+        } else {
+            let error = error as NSError
+            ffi_error = FfiError(code: 0, domain: error.domain, message: error.localizedDescription)
+        }
+        return FfiResponse(payload: "", error: ffi_error)
+    }
     
     /**
     Creates a new cryptographic key pair in the Secure Enclave.
@@ -9,11 +160,12 @@ import CryptoKit
     - Parameter keyID: A String used to identify the private key.
     - Parameter algorithm: A 'CFString' data type representing the algorithm used to create the key pair.
     - Parameter keySize: A String representing the size of the key.
+    - Parameter access: The name of the user authentication required to use the private key, or nil if none is required.
     - Throws: 'SecureEnclaveError.CreateKeyError' if a new public-private key pair could not be generated.
     - Returns: A 'SEKeyPair' containing the public and private key on success, or a 'SecureEnclaveError' on failure.
     */
-    func create_key(key_id: String, algorithm: CFString, key_size: String ) throws -> SEKeyPair? {
-        let accessControl = create_access_control_object()
+    func create_key(key_id: String, algorithm: CFString, key_size: String, access: String? = nil) throws -> SEKeyPair? {
+        let accessControl = try create_access_control_object(access: access)
         let params: [String: Any]; 
         if algorithm == kSecAttrKeyTypeRSA{ // Asymmetric Encryption
             params =
@@ -62,20 +214,27 @@ import CryptoKit
     /** 
     Optimized method of @create_key() to communicate with the rust-side abstraction-layer.
 
-    - Parameter key_id: A 'RustString' data type used to identify the private key.
-    - Parameter key_type - A 'RustString' data type used to represent the algorithm that is used to create the key pair.
+    - Parameter key_id: A String used to identify the private key.
+    - Parameter key_type - A String used to represent the algorithm that is used to create the key pair, optionally followed by the required user authentication.
     - Returns: A boolean representing if a error occured and a String representing the private and public key, or an error as a String on failure.
     */
-    func rustcall_create_key(key_id: RustString, key_type: RustString) -> (Bool, String) {
+    func handle_create_key(key_id: String, key_type: String) -> FfiResponse {
+        log_call("create_key")
         // For Secure Enclave is only ECC supported
-        let algorithm = String(key_type.toString().split(separator: ";")[0])
-        let keySize = String(key_type.toString().split(separator:";")[1])
+        let fields = key_type.split(separator: ";")
+        let access = fields.count > 2 ? String(fields[2]) : nil
         do{
-            let algorithm = try get_key_type(key_type: algorithm);
-            let keyPair = try create_key(key_id: key_id.toString(), algorithm: algorithm, key_size: keySize)
-            return (false,("Private Key: "+String((keyPair?.privateKey.hashValue)!) + "\nPublic Key: " + String((keyPair?.publicKey.hashValue)!)))
+            guard fields.count >= 2 else {
+                throw SecureEnclaveError.CreateKeyError("Invalid key type '\(key_type)', expected '<algorithm>;<key size>'.")
+            }
+            let algorithm = try get_key_type(key_type: String(fields[0]));
+            let keySize = String(fields[1])
+            let keyPair = try required(create_key(key_id: key_id, algorithm: algorithm, key_size: keySize, access: access),
+                                       SecureEnclaveError.CreateKeyError("The key pair could not be created."))
+            return ffi_success(("Private Key: "+String(keyPair.privateKey.hashValue) + "\nPublic Key: " + String(keyPair.publicKey.hashValue)))
         }catch{
-            return (true,"Error: \(String(describing: error))")
+            log_failure("create_key", error)
+            return ffi_failure(error)
         }
     }
     
@@ -83,14 +242,28 @@ import CryptoKit
     /**
     Creates an access control object for a cryptographic operation.
      
+    - Parameter access: The name of the user authentication required in addition, or nil if none is required.
+    - Throws: 'SecureEnclaveError.CreateKeyError' if the user authentication is not supported.
     - Returns: A 'SecAccessControl' configured for private key usage.
     */
-    func create_access_control_object() -> SecAccessControl {
-            let access = SecAccessControlCreateWithFlags(
+    func create_access_control_object(access: String? = nil) throws -> SecAccessControl {
+            var flags: SecAccessControlCreateFlags = .privateKeyUsage
+            switch access {
+                case nil: break
+                case "userPresence"?: flags.insert(.userPresence)
+                case "biometryAny"?: flags.insert(.biometryAny)
+                case "biometryCurrentSet"?: flags.insert(.biometryCurrentSet)
+                case "devicePasscode"?: flags.insert(.devicePasscode)
+                default:
+                    throw SecureEnclaveError.CreateKeyError("Access control is not supported.")
+            }
+            guard let access = SecAccessControlCreateWithFlags(
                 kCFAllocatorDefault,
                 kSecAttrAccessibleWhenUnlockedThisDeviceOnly, 
-                .privateKeyUsage, 
-                nil)!
+                flags, 
+                nil) else {
+                throw SecureEnclaveError.CreateKeyError("The access control object could not be created.")
+            }
             
             return access
     }
@@ -121,26 +294,30 @@ import CryptoKit
     /** 
     Optimized method of @encrypt_data() to communicate with the rust-side abstraction-layer.
 
-    - Parameter key_id: A 'RustString' data type used to identify the private key.
-    - Parameter data: A 'RustVec<UInt8>' data type used to represent the data that has to be encrypted as a Rust-Vector.
-    - Parameter algorithm: A 'RustString' data type used to represent the algorithm that is used to encrypt the data.
-    - Parameter hash: A 'RustString' data type used to represent the hash that is used.
+    - Parameter key_id: A String used to identify the private key.
+    - Parameter data: A 'Data' value used to represent the data that has to be encrypted.
+    - Parameter algorithm: A String used to represent the algorithm that is used to encrypt the data.
+    - Parameter hash: A String used to represent the hash that is used.
     - Returns: A boolean representing if a error occured and a String representing the encrypted data, or an error as a String on failure.
     */
-    func rustcall_encrypt_data(key_id: RustString, data: RustVec<UInt8>, algorithm: RustString, hash: RustString) -> (Bool, String) {
+    func handle_encrypt_data(key_id: String, data: Data, algorithm: String, hash: String) -> FfiResponse {
+        log_call("encrypt_data")
         do{
-            let key_type = try get_key_type(key_type: algorithm.toString())
-            let privateKey: SecKey = try load_key(key_id: key_id.toString(), algorithm: key_type)!
-            let publicKey = get_public_key_from_private_key(private_key: privateKey)
-            let algorithm = try get_encrypt_algorithm(algorithm: algorithm.toString(), hash: hash.toString())
-            try check_algorithm_support(key: get_public_key_from_private_key(private_key: publicKey!)!, operation: SecKeyOperationType.encrypt, algorithm: algorithm)
+            let key_type = try get_key_type(key_type: algorithm)
+            let privateKey: SecKey = try required(load_key(key_id: key_id, algorithm: key_type), SecureEnclaveError.LoadKeyError("Key '\(key_id)' could not be found."))
+            let publicKey = try required(get_public_key_from_private_key(private_key: privateKey),
+                                         SecureEnclaveError.EncryptionError("Public key could not be received from the private key."))
+            let seckey_algorithm = try get_encrypt_algorithm(algorithm: algorithm, hash: hash)
+            try check_algorithm_support(key: publicKey, operation: SecKeyOperationType.encrypt, algorithm: seckey_algorithm)
 
-            let encryptedData: Data = try encrypt_data(data: Data(data) as CFData, public_key: publicKey!, algorithm: algorithm)! as Data
+            let encryptedData: Data = try required(encrypt_data(data: data as CFData, public_key: publicKey, algorithm: seckey_algorithm),
+                                                   SecureEnclaveError.EncryptionError("Data could not be encrypted.")) as Data
 
             let encryptedData_string = encryptedData.base64EncodedString(options: [])
-            return (false, encryptedData_string)
+            return ffi_success(encryptedData_string)
         }catch{
-            return (true, "Error: \(String(describing: error))")
+            log_failure("encrypt_data", error)
+            return ffi_failure(error)
         }
     }
     
@@ -165,29 +342,141 @@ import CryptoKit
     /** 
     Optimized method of @decrypt_data() to communicate with the rust-side abstraction-layer.
 
-    - Parameter key_id: A 'RustString' data type used to identify the private key.
-    - Parameter data: A 'RustVec<UInt8>' data type used to represent the data that has to be decrypted as a Rust-Vector.
-    - Parameter algorithm: A 'RustString' data type used to represent the algorithm that is used to decrypt the data.
-    - Parameter hash: A 'RustString' data type used to represent the hash that is used.
-    - Returns: A boolean representing if a error occured and a String representing the decrypted data, or an error as a String on failure.
+    - Parameter key_id: A String used to identify the private key.
+    - Parameter data: A 'Data' value used to represent the data that has to be decrypted.
+    - Parameter algorithm: A String used to represent the algorithm that is used to decrypt the data.
+    - Parameter hash: A String used to represent the hash that is used.
+    - Returns: A boolean representing if a error occured and a String representing the base64 encoded decrypted data, or an error as a String on failure.
     */
-    func rustcall_decrypt_data(key_id: RustString, data: RustVec<UInt8>, algorithm: RustString, hash: RustString) -> (Bool, String) {
+    func handle_decrypt_data(key_id: String, data: Data, algorithm: String, hash: String) -> FfiResponse {
+        log_call("decrypt_data")
         do{
-            let seckey_algorithm_enum = try get_encrypt_algorithm(algorithm: algorithm.toString(), hash: hash.toString())
-            let key_type = try get_key_type(key_type: algorithm.toString())
-            let data_cfdata = Data(base64Encoded: Data(data), options: [])! as CFData
-            let private_key = try load_key(key_id: key_id.toString(), algorithm: key_type)!
+            let seckey_algorithm_enum = try get_encrypt_algorithm(algorithm: algorithm, hash: hash)
+            let key_type = try get_key_type(key_type: algorithm)
+            let data_cfdata = try required(Data(base64Encoded: data, options: []), SecureEnclaveError.DecryptionError("The encrypted data is not base64 encoded.")) as CFData
+            let private_key = try required(load_key(key_id: key_id, algorithm: key_type), SecureEnclaveError.LoadKeyError("Key '\(key_id)' could not be found."))
             try check_algorithm_support(key: private_key, operation: SecKeyOperationType.decrypt, algorithm: seckey_algorithm_enum)
 
-            let decrypted_data = try (decrypt_data(data: data_cfdata, private_key: private_key, algorithm: seckey_algorithm_enum))! as Data
+            let decrypted_data = try required(decrypt_data(data: data_cfdata, private_key: private_key, algorithm: seckey_algorithm_enum),
+                                              SecureEnclaveError.DecryptionError("Data could not be decrypted.")) as Data
 
-            return (false, String(data: decrypted_data, encoding: String.Encoding.ascii)!)
+            // The plaintext is arbitrary binary data, which only survives the String-based bridge base64 encoded.
+            return ffi_success(decrypted_data.base64EncodedString(options: []))
         } catch {
-            return (true, "Error: \(String(describing: error))")
+            log_failure("decrypt_data", error)
+            return ffi_failure(error)
         }
     }
     
     
+    /**
+    Authenticates the user for an operation with a private key without blocking a thread, if the access control of the key requires it.
+
+    - Parameter key_id: A String used to identify the private key.
+    - Parameter operation: The 'LAAccessControlOperation' that is going to be performed with the key.
+    - Parameter reason: The reason shown in the Touch ID prompt.
+    - Throws: 'SecureEnclaveError.runtimeError' if the user could not be authenticated.
+    - Returns: An 'LAContext' to load the key with, which does not prompt the user again.
+    */
+    @available(macOS 12.0, iOS 15.0, *)
+    func authenticate(key_id: String, operation: LAAccessControlOperation, reason: String) async throws -> LAContext {
+        let context = LAContext()
+        let access = try await on_security_queue_async { () -> SecAccessControl? in
+            let query: [String: Any] = [
+                kSecClass as String                  : kSecClassKey,
+                kSecAttrApplicationTag as String    : key_id,
+                kSecReturnAttributes as String      : true,
+                kSecUseAuthenticationContext as String : context,
+                kSecUseAuthenticationUI as String   : kSecUseAuthenticationUISkip
+            ]
+            var item: CFTypeRef?
+            guard SecItemCopyMatching(query as CFDictionary, &item) == errSecSuccess,
+                  let attributes = item as? [String: Any],
+                  let access = attributes[kSecAttrAccessControl as String] else {
+                return nil
+            }
+            return (access as! SecAccessControl)
+        }
+        guard let access = access else {
+            // Keys without access control are used without prompting.
+            return context
+        }
+        do {
+            // Waits for the user without occupying the security queue.
+            _ = try await context.evaluateAccessControl(access, operation: operation, localizedReason: reason)
+        } catch {
+            throw SecureEnclaveError.runtimeError("User authentication failed: \(error.localizedDescription)")
+        }
+        return context
+    }
+
+    /**
+    Asynchronous variant of @handle_sign_data() that awaits the authentication of the user instead of blocking the calling thread.
+
+    - Parameter key_id: A String used to identify the private key.
+    - Parameter data: A 'Data' value used to represent the data that has to be signed.
+    - Parameter algorithm: A String used to represent the algorithm that is used to sign the data.
+    - Parameter hash: A String used to represent the hash that is used.
+    - Parameter correlation_id: A String holding the correlation id of the rust-side operation.
+    - Returns: A 'FfiResponse' holding the base64 encoded signature, or the error the call failed with.
+    */
+    func handle_sign_data_async(key_id: String, data: Data, algorithm: String, hash: String, correlation_id: String) async -> FfiResponse {
+        log_call("sign_data_async", correlation_id: correlation_id)
+        let data_cfdata = data as CFData
+        do {
+            guard #available(macOS 12.0, iOS 15.0, *) else {
+                throw SecureEnclaveError.runtimeError("Asynchronous signing requires macOS 12.0 or iOS 15.0")
+            }
+            let context = try await authenticate(key_id: key_id, operation: .useKeySign, reason: "sign data")
+            let signed_data = try await on_security_queue_async { () throws -> Data in
+                let seckey_algorithm_enum = try get_sign_algorithm(algorithm: algorithm, hash: hash)
+                let key_type = try get_key_type(key_type: algorithm) as CFString
+                let private_key = try required(load_key(key_id: key_id, algorithm: key_type, context: context), SecureEnclaveError.LoadKeyError("Key '\(key_id)' could not be found."))
+                try check_algorithm_support(key: private_key, operation: SecKeyOperationType.sign, algorithm: seckey_algorithm_enum)
+                return try required(sign_data(data: data_cfdata, privateKey: private_key, algorithm: seckey_algorithm_enum),
+                                    SecureEnclaveError.SigningError("Data could not be signed.")) as Data
+            }
+            return ffi_success(signed_data.base64EncodedString(options: []))
+        } catch {
+            log_failure("sign_data_async", error, correlation_id: correlation_id)
+            return ffi_failure(error)
+        }
+    }
+
+    /**
+    Asynchronous variant of @handle_decrypt_data() that awaits the authentication of the user instead of blocking the calling thread.
+
+    - Parameter key_id: A String used to identify the private key.
+    - Parameter data: A 'Data' value used to represent the data that has to be decrypted.
+    - Parameter algorithm: A String used to represent the algorithm that is used to decrypt the data.
+    - Parameter hash: A String used to represent the hash that is used.
+    - Parameter correlation_id: A String holding the correlation id of the rust-side operation.
+    - Returns: A 'FfiResponse' holding the base64 encoded decrypted data, or the error the call failed with.
+    */
+    func handle_decrypt_data_async(key_id: String, data: Data, algorithm: String, hash: String, correlation_id: String) async -> FfiResponse {
+        log_call("decrypt_data_async", correlation_id: correlation_id)
+        do {
+            guard #available(macOS 12.0, iOS 15.0, *) else {
+                throw SecureEnclaveError.runtimeError("Asynchronous decryption requires macOS 12.0 or iOS 15.0")
+            }
+            let context = try await authenticate(key_id: key_id, operation: .useKeyDecrypt, reason: "decrypt data")
+            let decrypted_data = try await on_security_queue_async { () throws -> Data in
+                let seckey_algorithm_enum = try get_encrypt_algorithm(algorithm: algorithm, hash: hash)
+                let key_type = try get_key_type(key_type: algorithm)
+                let data_cfdata = try required(Data(base64Encoded: data, options: []), SecureEnclaveError.DecryptionError("The encrypted data is not base64 encoded.")) as CFData
+                let private_key = try required(load_key(key_id: key_id, algorithm: key_type, context: context), SecureEnclaveError.LoadKeyError("Key '\(key_id)' could not be found."))
+                try check_algorithm_support(key: private_key, operation: SecKeyOperationType.decrypt, algorithm: seckey_algorithm_enum)
+                return try required(decrypt_data(data: data_cfdata, private_key: private_key, algorithm: seckey_algorithm_enum),
+                                    SecureEnclaveError.DecryptionError("Data could not be decrypted.")) as Data
+            }
+            return ffi_success(decrypted_data.base64EncodedString(options: []))
+        } catch {
+            log_failure("decrypt_data_async", error, correlation_id: correlation_id)
+            return ffi_failure(error)
+        }
+    }
+
+
     /**
     Retrieves the public key associated with a given private key.
 
@@ -222,26 +511,30 @@ import CryptoKit
     /** 
     Optimized method of @sign_data() to communicate with the rust-side abstraction-layer.
 
-    - Parameter key_id: A 'RustString' data type used to identify the private key.
-    - Parameter data: A 'RustVec<UInt8>' data type used to represent the data that has to be signed as a Rust-Vector.
-    - Parameter algorithm: A 'RustString' data type used to represent the algorithm that is used to sign the data.
-    - Parameter hash: A 'RustString' data type used to represent the hash that is used.
+    - Parameter key_id: A String used to identify the private key.
+    - Parameter data: A 'Data' value used to represent the data that has to be signed.
+    - Parameter algorithm: A String used to represent the algorithm that is used to sign the data.
+    - Parameter hash: A String used to represent the hash that is used.
     - Returns: A boolean representing if a error occured and a String representing the signed data, or an error as a String on failure.
     */
-    func rustcall_sign_data(key_id: RustString, data: RustVec<UInt8>, algorithm: RustString, hash: RustString) -> (Bool, String){
-        let privateKeyName_string = key_id.toString()
+    func handle_sign_data(key_id: String, data: Data, algorithm: String, hash: String) -> FfiResponse{
+        log_call("sign_data")
+        let privateKeyName_string = key_id
         // let data_cfdata = data.toString().data(using: String.Encoding.utf8)! as CFData
-        let data_cfdata = Data(data) as CFData; 
+        let data_cfdata = data as CFData; 
 
         do {
-            let seckey_algorithm_enum = try get_sign_algorithm(algorithm: algorithm.toString(), hash: hash.toString())
-            let key_type = try get_key_type(key_type: algorithm.toString()) as CFString
-            let privateKeyReference = try load_key(key_id: privateKeyName_string, algorithm: key_type)!
+            let seckey_algorithm_enum = try get_sign_algorithm(algorithm: algorithm, hash: hash)
+            let key_type = try get_key_type(key_type: algorithm) as CFString
+            let privateKeyReference = try required(load_key(key_id: privateKeyName_string, algorithm: key_type),
+                                                   SecureEnclaveError.LoadKeyError("Key '\(privateKeyName_string)' could not be found."))
             try check_algorithm_support(key: privateKeyReference, operation: SecKeyOperationType.sign, algorithm: seckey_algorithm_enum)
-            let signed_data = try ((sign_data(data: data_cfdata, privateKey: privateKeyReference, algorithm: seckey_algorithm_enum))! as Data) 
-            return (false, signed_data.base64EncodedString(options: []))
+            let signed_data = try required(sign_data(data: data_cfdata, privateKey: privateKeyReference, algorithm: seckey_algorithm_enum),
+                                           SecureEnclaveError.SigningError("Data could not be signed.")) as Data
+            return ffi_success(signed_data.base64EncodedString(options: []))
         }catch{
-            return (true, "Error:  \(String(describing: error))")
+            log_failure("sign_data", error)
+            return ffi_failure(error)
         }
     }
     
@@ -271,28 +564,30 @@ import CryptoKit
     /** 
     Optimized method of @verify_data() to communicate with the rust-side abstraction-layer.
 
-    - Parameter key_id: A 'RustString' data type used to identify the public key.
-    - Parameter data: A 'RustVec<UInt8>' data type used to represent the data that has to be verified with the signature as a Rust-Vector.
-    - Parameter signature: A 'RustVec<UInt8>' data type used to represent the signature of the signed data as a Rust-Vector.
-    - Parameter algorithm: A 'RustString' data type used to represent the algorithm that is used to verify the signature.
-    - Parameter hash: A 'RustString' data type used to represent the hash that is used.
+    - Parameter key_id: A String used to identify the public key.
+    - Parameter data: A 'Data' value used to represent the data that has to be verified with the signature.
+    - Parameter signature: A 'Data' value used to represent the signature of the signed data.
+    - Parameter algorithm: A String used to represent the algorithm that is used to verify the signature.
+    - Parameter hash: A String used to represent the hash that is used.
     - Returns: A boolean representing if a error occured and a String representing the verify status, or an error as a String on failure.
     */
-    func rustcall_verify_signature(key_id: RustString, data: RustVec<UInt8>, signature: RustVec<UInt8>, algorithm: RustString, hash: RustString) -> (Bool, String) {
+    func handle_verify_signature(key_id: String, data: Data, signature: Data, algorithm: String, hash: String) -> FfiResponse {
+        log_call("verify_signature")
         do{
-            let publicKeyName_string = key_id.toString()
-            let data_cfdata = Data(data) as CFData;
-            let signature_cfdata = Data(base64Encoded: Data(signature), options: [])! as CFData
-
-            guard Data(base64Encoded: Data(signature)) != nil else{
+            let publicKeyName_string = key_id
+            let data_cfdata = data as CFData;
+            guard let signature_data = Data(base64Encoded: signature, options: []) else{
                 throw SecureEnclaveError.SignatureVerificationError("Invalid message to verify.)")
             }
+            let signature_cfdata = signature_data as CFData
 
             //Get Algorithm enums
-            let seckey_algorithm_enum = try get_sign_algorithm(algorithm: algorithm.toString(), hash: hash.toString())
-            let key_type = try get_key_type(key_type: algorithm.toString())
+            let seckey_algorithm_enum = try get_sign_algorithm(algorithm: algorithm, hash: hash)
+            let key_type = try get_key_type(key_type: algorithm)
 
-            guard let publicKey = get_public_key_from_private_key(private_key: try load_key(key_id: publicKeyName_string, algorithm: key_type)!)else{
+            let privateKey = try required(load_key(key_id: publicKeyName_string, algorithm: key_type),
+                                          SecureEnclaveError.LoadKeyError("Key '\(publicKeyName_string)' could not be found."))
+            guard let publicKey = get_public_key_from_private_key(private_key: privateKey)else{
                 throw SecureEnclaveError.SignatureVerificationError("Public key could not be received from the private key.)")
             }
 
@@ -300,16 +595,203 @@ import CryptoKit
             let status = try verify_signature(public_key: publicKey, data: data_cfdata, signature: signature_cfdata, sign_algorithm: seckey_algorithm_enum)
             
             if status == true{
-                return (false,"true")
+                return ffi_success("true")
             }else{
-                return (false,"false")
+                return ffi_success("false")
             }
         }catch{
-            return (true,"Error: \(String(describing: error))")
+            log_failure("verify_signature", error)
+            return ffi_failure(error)
         }
     }
     
     
+    /** 
+    Exports the public key of a key pair to communicate with the rust-side abstraction-layer.
+
+    - Parameter key_id: A String used to identify the private key.
+    - Parameter algorithm: A String used to represent the algorithm of the key pair.
+    - Returns: A boolean representing if a error occured and a String representing the base64 encoded public key 
+    (ANSI X9.63 for ECDSA, PKCS#1 for RSA), or an error as a String on failure.
+    */
+    func handle_get_public_key(key_id: String, algorithm: String) -> FfiResponse {
+        log_call("get_public_key")
+        do{
+            let key_type = try get_key_type(key_type: algorithm)
+            let privateKey = try required(load_key(key_id: key_id, algorithm: key_type), SecureEnclaveError.LoadKeyError("Key '\(key_id)' could not be found."))
+
+            guard let publicKey = get_public_key_from_private_key(private_key: privateKey) else{
+                throw SecureEnclaveError.LoadKeyError("Public key could not be received from the private key.")
+            }
+
+            var error: Unmanaged<CFError>?
+            guard let publicKeyData = SecKeyCopyExternalRepresentation(publicKey, &error) else{
+                throw SecureEnclaveError.LoadKeyError("Public key could not be exported. \(String(describing: error))")
+            }
+            return ffi_success((publicKeyData as Data).base64EncodedString(options: []))
+        }catch{
+            log_failure("get_public_key", error)
+            return ffi_failure(error)
+        }
+    }
+
+
+    /**
+    Derives a shared secret with ECDH from a private key and the public key of another party, e.g. to decrypt an ECIES message of the rust-side.
+
+    - Parameter key_id: A String used to identify the private key.
+    - Parameter peer_public_key: A 'Data' value holding the public key of the other party as an uncompressed ANSI X9.63 point.
+    - Parameter algorithm: A String used to represent the algorithm of the key pair.
+    - Returns: A 'FfiResponse' whose payload is the base64 encoded shared secret, or an error on failure.
+    */
+    func handle_derive_shared_secret(key_id: String, peer_public_key: Data, algorithm: String) -> FfiResponse {
+        log_call("derive_shared_secret")
+        do{
+            let key_type = try get_key_type(key_type: algorithm)
+            let private_key = try required(load_key(key_id: key_id, algorithm: key_type), SecureEnclaveError.LoadKeyError("Key '\(key_id)' could not be found."))
+            try check_algorithm_support(key: private_key, operation: SecKeyOperationType.keyExchange, algorithm: .ecdhKeyExchangeStandard)
+
+            let attributes: [String: Any] = [
+                kSecAttrKeyType as String: kSecAttrKeyTypeECSECPrimeRandom,
+                kSecAttrKeyClass as String: kSecAttrKeyClassPublic,
+            ]
+            var error: Unmanaged<CFError>?
+            guard let peer_key = SecKeyCreateWithData(peer_public_key as CFData, attributes as CFDictionary, &error) else {
+                throw SecureEnclaveError.DecryptionError("The public key of the other party is invalid. \(String(describing: error))")
+            }
+            guard let shared_secret = SecKeyCopyKeyExchangeResult(private_key, .ecdhKeyExchangeStandard, peer_key, [:] as CFDictionary, &error) else {
+                throw SecureEnclaveError.DecryptionError("The shared secret could not be derived. \(String(describing: error))")
+            }
+            return ffi_success((shared_secret as Data).base64EncodedString(options: []))
+        }catch{
+            log_failure("derive_shared_secret", error)
+            return ffi_failure(error)
+        }
+    }
+
+    /// The service of the generic password items holding the metadata of keys, see 'tpm::macos::metadata_store' of crypto-layer.
+    let metadata_service = "crypto-layer.metadata"
+
+    /// Returns the query matching the metadata item of 'key_id', or the items of all keys if 'key_id' is nil.
+    func metadata_query(key_id: String?) -> [String: Any] {
+        var query: [String: Any] = [
+            kSecClass as String: kSecClassGenericPassword,
+            kSecAttrService as String: metadata_service,
+        ]
+        if let key_id = key_id {
+            query[kSecAttrAccount as String] = key_id
+        }
+        return query
+    }
+
+    /**
+    Reads the metadata of a key, stored in the generic attribute of its metadata item.
+    Optimized to communicate with the rust-side abstraction-layer.
+
+    - Parameter key_id: A 'String' used as identifier for the key.
+    - Returns: A 'FfiResponse' whose payload is the JSON encoded 'KeyRecord', empty if the key has no metadata, or a 'SecureEnclaveError' on failure.
+    */
+    func handle_read_metadata(key_id: String) -> FfiResponse {
+        log_call("read_metadata")
+        var query = metadata_query(key_id: key_id)
+        query[kSecReturnAttributes as String] = true
+        query[kSecMatchLimit as String] = kSecMatchLimitOne
+        var item: CFTypeRef?
+        let status = SecItemCopyMatching(query as CFDictionary, &item)
+        if status == errSecItemNotFound {
+            return ffi_success("")
+        }
+        guard status == errSecSuccess,
+              let attributes = item as? [String: Any],
+              let record = attributes[kSecAttrGeneric as String] as? Data,
+              let json = String(data: record, encoding: .utf8) else {
+            let error = SecureEnclaveError.runtimeError("The metadata of '\(key_id)' could not be read (\(status)).")
+            log_failure("read_metadata", error)
+            return ffi_failure(error)
+        }
+        return ffi_success(json)
+    }
+
+    /**
+    Stores the metadata of a key in the generic attribute of its metadata item, creating the item if there is none.
+    Optimized to communicate with the rust-side abstraction-layer.
+
+    - Parameter key_id: A 'String' used as identifier for the key.
+    - Parameter record: The JSON encoded 'KeyRecord'.
+    - Returns: A 'FfiResponse' with an empty payload on success, or a 'SecureEnclaveError' on failure.
+    */
+    func handle_write_metadata(key_id: String, record: String) -> FfiResponse {
+        log_call("write_metadata")
+        let attributes: [String: Any] = [kSecAttrGeneric as String: Data(record.utf8)]
+        var status = SecItemUpdate(metadata_query(key_id: key_id) as CFDictionary, attributes as CFDictionary)
+        if status == errSecItemNotFound {
+            let query = metadata_query(key_id: key_id).merging(attributes) { _, new in new }
+            status = SecItemAdd(query as CFDictionary, nil)
+        }
+        guard status == errSecSuccess else {
+            let error = SecureEnclaveError.runtimeError("The metadata of '\(key_id)' could not be stored (\(status)).")
+            log_failure("write_metadata", error)
+            return ffi_failure(error)
+        }
+        return ffi_success("")
+    }
+
+    /**
+    Deletes the metadata item of a key.
+    Optimized to communicate with the rust-side abstraction-layer.
+
+    - Parameter key_id: A 'String' used as identifier for the key.
+    - Returns: A 'FfiResponse' whose payload is 'true' if there was an item and 'false' if not, or a 'SecureEnclaveError' on failure.
+    */
+    func handle_delete_metadata(key_id: String) -> FfiResponse {
+        log_call("delete_metadata")
+        let status = SecItemDelete(metadata_query(key_id: key_id) as CFDictionary)
+        switch status {
+            case errSecSuccess:
+                return ffi_success("true")
+            case errSecItemNotFound:
+                return ffi_success("false")
+            default:
+                let error = SecureEnclaveError.runtimeError("The metadata of '\(key_id)' could not be deleted (\(status)).")
+                log_failure("delete_metadata", error)
+                return ffi_failure(error)
+        }
+    }
+
+    /**
+    Lists the keys that have a metadata item.
+    Optimized to communicate with the rust-side abstraction-layer.
+
+    - Returns: A 'FfiResponse' whose payload is a JSON array of the key ids, or a 'SecureEnclaveError' on failure.
+    */
+    func handle_list_metadata() -> FfiResponse {
+        log_call("list_metadata")
+        var query = metadata_query(key_id: nil)
+        query[kSecReturnAttributes as String] = true
+        query[kSecMatchLimit as String] = kSecMatchLimitAll
+        var items: CFTypeRef?
+        let status = SecItemCopyMatching(query as CFDictionary, &items)
+        var key_ids: [String] = []
+        switch status {
+            case errSecSuccess:
+                for attributes in (items as? [[String: Any]]) ?? [] {
+                    if let key_id = attributes[kSecAttrAccount as String] as? String {
+                        key_ids.append(key_id)
+                    }
+                }
+            case errSecItemNotFound:
+                break
+            default:
+                let error = SecureEnclaveError.runtimeError("The metadata items could not be listed (\(status)).")
+                log_failure("list_metadata", error)
+                return ffi_failure(error)
+        }
+        // An array of strings always encodes.
+        let json = (try? JSONEncoder().encode(key_ids)).flatMap { String(data: $0, encoding: .utf8) } ?? "[]"
+        return ffi_success(json)
+    }
+    
+    
     /// Represents errors that can occur within 'SecureEnclaveManager'.
     enum SecureEnclaveError: Error {
         case runtimeError(String)
@@ -321,6 +803,32 @@ import CryptoKit
         case CreateKeyError(String)
         case LoadKeyError(String)
     }
+
+    extension SecureEnclaveError {
+        /// The code of the error in a 'FfiError', which the rust-side matches as 'SwiftErrorCode'.
+        var code: UInt32 {
+            switch self {
+                case .runtimeError: return 1
+                case .InitializationError: return 2
+                case .CreateKeyError: return 3
+                case .LoadKeyError: return 4
+                case .SigningError: return 5
+                case .SignatureVerificationError: return 6
+                case .EncryptionError: return 7
+                case .DecryptionError: return 8
+            }
+        }
+
+        /// The message the error was thrown with.
+        var message: String {
+            switch self {
+                case .runtimeError(let message), .SigningError(let message), .DecryptionError(let message),
+                     .EncryptionError(let message), .SignatureVerificationError(let message),
+                     .InitializationError(let message), .CreateKeyError(let message), .LoadKeyError(let message):
+                    return message
+            }
+        }
+    }
     
     /// Represents a pair of cryptographic keys, both the public key and the private key are objects of the data type 'SecKey'.
     struct SEKeyPair {
@@ -334,23 +842,28 @@ import CryptoKit
 
     - Parameter key_id: A String used as the identifier for the key.
     - Parameter algo: A 'CFString' data type representing the algorithm used to create the key pair.
+    - Parameter context: An already authenticated 'LAContext', so that using the key does not prompt the user again, or nil.
     - Throws: 'SecureEnclaveError.LoadKeyError' if the key could not be found.
     - Returns: Optionally the key as a SecKey data type on success, or a nil on failure.
     */
-    func load_key(key_id: String, algorithm: CFString) throws -> SecKey? {
+    func load_key(key_id: String, algorithm: CFString, context: LAContext? = nil) throws -> SecKey? {
         let tag = key_id
-        let query: [String: Any] = [
+        var query: [String: Any] = [
             kSecClass as String                  : kSecClassKey,
             kSecAttrApplicationTag as String    : tag,
             kSecAttrKeyType as String           : algorithm,
             kSecReturnRef as String             : true
         ]
+        if let context = context {
+            query[kSecUseAuthenticationContext as String] = context
+        }
 
         var item: CFTypeRef?
         let status = SecItemCopyMatching(query as CFDictionary, &item)
-        guard status == errSecSuccess else {
+        guard status == errSecSuccess, let item = item else {
             throw SecureEnclaveError.LoadKeyError("Key could not be found.)")
         }
+        // A query for kSecClassKey with kSecReturnRef only returns keys.
         return (item as! SecKey)
     }
 
@@ -358,22 +871,24 @@ import CryptoKit
     /** 
     Optimized method of @load_key() to communicate with the rust-side abstraction-layer.
 
-    - Parameter key_id: A 'RustString' data type used to identify the private key.
-    - Parameter key_type - A 'RustString' data type used to represent the algorithm that is used to create the key pair.
-    - Parameter hash - A 'RustString' data type used to represent the hash that is used.
+    - Parameter key_id: A String used to identify the private key.
+    - Parameter key_type - A String used to represent the algorithm that is used to create the key pair.
+    - Parameter hash - A String used to represent the hash that is used.
     - Returns: A boolean representing if a error occured and a String representing the private key, or an error as a String on failure.
     */
-    func rustcall_load_key(key_id: RustString, key_type: RustString, hash: RustString) -> (Bool, String) {
+    func handle_load_key(key_id: String, key_type: String, hash: String) -> FfiResponse {
+        log_call("load_key")
         do {
-            let key_algorithm = try get_key_type(key_type: key_type.toString())
+            let key_algorithm = try get_key_type(key_type: key_type)
 
-            guard let key = try load_key(key_id: key_id.toString(), algorithm: key_algorithm) else {
-                return (true,"Key with KeyID \(key_id) could not be found.")
+            guard let key = try load_key(key_id: key_id, algorithm: key_algorithm) else {
+                return ffi_failure(SecureEnclaveError.LoadKeyError("Key with KeyID \(key_id) could not be found."))
             }
 
-            return (false,"\(key.hashValue)")
+            return ffi_success("\(key.hashValue)")
         } catch {
-            return (true,"Error: \(key_type.toString()) + \(String(describing: error))")
+            log_failure("load_key", error)
+            return ffi_failure(error)
         }
     }
     
@@ -387,7 +902,7 @@ import CryptoKit
     */
     func storeKey_Keychain(_ name: String, _ private_key: SecKey) throws {
         let key = private_key
-        let tag = name.data(using: .utf8)!
+        let tag = Data(name.utf8)
         let addquery: [String: Any] = [kSecClass as String: kSecClassKey,
                                        kSecAttrApplicationTag as String: tag,
                                        kSecValueRef as String: key]
@@ -399,6 +914,23 @@ import CryptoKit
         }
     }
     
+    /// The newest and the oldest protocol version the rust-side may use, see 'tpm::macos::protocol' of crypto-layer.
+    let bridge_version = 3
+    let min_bridge_version = 3
+    /// The optional features of these bindings. Capabilities unknown to the rust-side are ignored.
+    let bridge_capabilities = ["access_control", "async", "key_agreement", "metadata", "sha512", "shutdown"]
+
+    /**
+    Reports the protocol versions and capabilities of these bindings, so that the rust-side can check whether it is compatible before initializing the module.
+    Optimized to communicate with the rust-side abstraction-layer.
+
+    - Returns: A 'FfiResponse' whose payload is 'version;min_version;capabilities', e.g. '3;3;access_control,async,key_agreement,metadata,sha512,shutdown'.
+    */
+    func handle_bridge_version() -> FfiResponse {
+        log_call("bridge_version")
+        return ffi_success("\(bridge_version);\(min_bridge_version);" + bridge_capabilities.joined(separator: ","))
+    }
+
     /**
     Initializes a module by creating a private key and the associated private key. 
     Optimized to communicate with the rust-side abstraction-layer.
@@ -406,6 +938,7 @@ import CryptoKit
     - Returns: A boolean if the module has been inizializes correctly on success ('true') or not ('false'), or a 'SecureEnclaveError' on failure.
     */
     func initialize_module() -> Bool  {
+        log_call("initialize_module")
         do {
             if #available(macOS 10.15, iOS 14.0, *)  {
                 guard SecureEnclave.isAvailable else {
@@ -416,10 +949,24 @@ import CryptoKit
                 return false
             }
         }catch{
+            log_failure("initialize_module", error)
             return false
         }
     }
 
+    /**
+    Releases the resources held for the calling rust-side provider, i.e. the correlation id of the current thread.
+    Keys are looked up in the keychain on every call, so no key references are kept between calls.
+    Optimized to communicate with the rust-side abstraction-layer.
+
+    - Returns: A boolean if the resources have been released ('true') or not ('false').
+    */
+    func shutdown_module() -> Bool {
+        log_call("shutdown_module")
+        Thread.current.threadDictionary.removeObject(forKey: correlation_id_key)
+        return true
+    }
+
     /**
     Checks if the algorithm is supported.
 
@@ -485,6 +1032,8 @@ import CryptoKit
                     apple_algorithm_enum = SecKeyAlgorithm.rsaSignatureMessagePSSSHA256
                 case "SHA384":
                     apple_algorithm_enum = SecKeyAlgorithm.rsaSignatureMessagePSSSHA384
+                case "SHA512":
+                    apple_algorithm_enum = SecKeyAlgorithm.rsaSignatureMessagePSSSHA512
                 default: 
                     throw SecureEnclaveError.SigningError("Hash for asymmetric signing with RSA is not supported.)")
             }
@@ -497,6 +1046,8 @@ import CryptoKit
                     apple_algorithm_enum = SecKeyAlgorithm.ecdsaSignatureMessageX962SHA256
                 case "SHA384":
                     apple_algorithm_enum = SecKeyAlgorithm.ecdsaSignatureMessageX962SHA384
+                case "SHA512":
+                    apple_algorithm_enum = SecKeyAlgorithm.ecdsaSignatureMessageX962SHA512
                 default: 
                     throw SecureEnclaveError.SigningError("Hash for asymmetric signing with ECDSA is not supported.)")
             }
@@ -529,6 +1080,8 @@ import CryptoKit
                     apple_algorithm_enum = SecKeyAlgorithm.rsaEncryptionOAEPSHA256
                 case "SHA384":
                     apple_algorithm_enum = SecKeyAlgorithm.rsaEncryptionOAEPSHA384
+                case "SHA512":
+                    apple_algorithm_enum = SecKeyAlgorithm.rsaEncryptionOAEPSHA512
                 default: 
                     throw SecureEnclaveError.EncryptionError("Hash for Encryption/Decryption is not supported.)")
             }
@@ -537,3 +1090,173 @@ import CryptoKit
             throw SecureEnclaveError.EncryptionError("Algorithm for Encryption/Decryption not supported.))")
         }
     }
+
+
+    /// The operations of 'rustcall_dispatch', see 'tpm::macos::dispatch::op_code' of crypto-layer.
+    enum OpCode: UInt32 {
+        case bridgeVersion = 0
+        case initializeModule = 1
+        case shutdownModule = 2
+        case createKey = 3
+        case loadKey = 4
+        case signData = 5
+        case verifySignature = 6
+        case encryptData = 7
+        case decryptData = 8
+        case getPublicKey = 9
+        case deriveSharedSecret = 10
+        case readMetadata = 11
+        case writeMetadata = 12
+        case deleteMetadata = 13
+        case listMetadata = 14
+    }
+
+    /// The arguments of a call, decoded from the JSON request of the rust-side.
+    struct DispatchRequest {
+        let correlation_id: String
+        let fields: [String: Any]
+
+        /**
+        Decodes the JSON envelope '{"version": 3, "correlation_id": "...", "request": {...}}' of the rust-side.
+
+        - Parameter request: The request bytes passed to 'rustcall_dispatch'.
+        - Throws: 'SecureEnclaveError.runtimeError' if the request is not a valid envelope.
+        */
+        init(_ request: Data) throws {
+            guard let envelope = (try? JSONSerialization.jsonObject(with: request)) as? [String: Any],
+                  let correlation_id = envelope["correlation_id"] as? String,
+                  let fields = envelope["request"] as? [String: Any] else {
+                throw SecureEnclaveError.runtimeError("The request is not a valid JSON envelope.")
+            }
+            self.correlation_id = correlation_id
+            self.fields = fields
+        }
+
+        /// Returns the string argument 'name', or throws a 'SecureEnclaveError.runtimeError' if it is missing.
+        func string(_ name: String) throws -> String {
+            guard let value = fields[name] as? String else {
+                throw SecureEnclaveError.runtimeError("The request has no argument '\(name)'.")
+            }
+            return value
+        }
+
+        /// Returns the base64 encoded binary argument 'name', or throws a 'SecureEnclaveError.runtimeError' if it is missing.
+        func bytes(_ name: String) throws -> Data {
+            guard let value = Data(base64Encoded: try string(name)) else {
+                throw SecureEnclaveError.runtimeError("The argument '\(name)' is not base64 encoded.")
+            }
+            return value
+        }
+    }
+
+    /**
+    Performs the operation selected by 'op_code' synchronously.
+
+    - Parameter op_code: The 'OpCode' of the operation.
+    - Parameter request: The decoded arguments of the operation.
+    - Throws: 'SecureEnclaveError.runtimeError' if the op code is unknown or an argument is missing.
+    - Returns: A 'FfiResponse' holding the result of the operation.
+    */
+    func dispatch(op_code: UInt32, request: DispatchRequest) throws -> FfiResponse {
+        guard let operation = OpCode(rawValue: op_code) else {
+            throw SecureEnclaveError.runtimeError("Unknown op code \(op_code).")
+        }
+        switch operation {
+            case .bridgeVersion:
+                return handle_bridge_version()
+            case .initializeModule:
+                return initialize_module() ? ffi_success("") : ffi_failure(SecureEnclaveError.InitializationError("initialize_module failed"))
+            case .shutdownModule:
+                return shutdown_module() ? ffi_success("") : ffi_failure(SecureEnclaveError.InitializationError("shutdown_module failed"))
+            case .createKey:
+                return handle_create_key(key_id: try request.string("key_id"), key_type: try request.string("key_type"))
+            case .loadKey:
+                return handle_load_key(key_id: try request.string("key_id"), key_type: try request.string("key_type"), hash: try request.string("hash"))
+            case .signData:
+                return handle_sign_data(key_id: try request.string("key_id"), data: try request.bytes("data"), algorithm: try request.string("algorithm"), hash: try request.string("hash"))
+            case .verifySignature:
+                return handle_verify_signature(key_id: try request.string("key_id"), data: try request.bytes("data"), signature: try request.bytes("signature"), algorithm: try request.string("algorithm"), hash: try request.string("hash"))
+            case .encryptData:
+                return handle_encrypt_data(key_id: try request.string("key_id"), data: try request.bytes("data"), algorithm: try request.string("algorithm"), hash: try request.string("hash"))
+            case .decryptData:
+                return handle_decrypt_data(key_id: try request.string("key_id"), data: try request.bytes("data"), algorithm: try request.string("algorithm"), hash: try request.string("hash"))
+            case .getPublicKey:
+                return handle_get_public_key(key_id: try request.string("key_id"), algorithm: try request.string("algorithm"))
+            case .deriveSharedSecret:
+                return handle_derive_shared_secret(key_id: try request.string("key_id"), peer_public_key: try request.bytes("peer_public_key"), algorithm: try request.string("algorithm"))
+            case .readMetadata:
+                return handle_read_metadata(key_id: try request.string("key_id"))
+            case .writeMetadata:
+                return handle_write_metadata(key_id: try request.string("key_id"), record: try request.string("record"))
+            case .deleteMetadata:
+                return handle_delete_metadata(key_id: try request.string("key_id"))
+            case .listMetadata:
+                return handle_list_metadata()
+        }
+    }
+
+    /**
+    Encodes a 'FfiResponse' as the JSON response bytes of 'rustcall_dispatch'.
+
+    - Parameter response: The result of the operation.
+    - Returns: A 'RustVec<UInt8>' holding the JSON encoded response.
+    */
+    func encode_response(_ response: FfiResponse) -> RustVec<UInt8> {
+        let bytes = RustVec<UInt8>()
+        // A response only holds strings and integers, which always encode. Should it fail anyway,
+        // the empty response is reported as invalid by the rust-side.
+        for byte in (try? JSONEncoder().encode(response)) ?? Data() {
+            bytes.push(value: byte)
+        }
+        return bytes
+    }
+
+    /**
+    The single entry point of the rust-side: performs the operation selected by 'op_code' on the security queue, whichever thread it is called from.
+
+    - Parameter op_code: The 'OpCode' of the operation.
+    - Parameter request: A 'RustVec<UInt8>' holding the JSON envelope with the arguments.
+    - Returns: A 'RustVec<UInt8>' holding the JSON encoded 'FfiResponse'.
+    */
+    func rustcall_dispatch(op_code: UInt32, request: RustVec<UInt8>) -> RustVec<UInt8> {
+        let request = Data(request)
+        let response: FfiResponse
+        do {
+            response = try on_security_queue { () throws -> FfiResponse in
+                let request = try DispatchRequest(request)
+                set_correlation_id(request.correlation_id)
+                return try dispatch(op_code: op_code, request: request)
+            }
+        } catch {
+            log_failure("dispatch", error)
+            response = ffi_failure(error)
+        }
+        return encode_response(response)
+    }
+
+    /**
+    Asynchronous variant of @rustcall_dispatch(): signing and decrypting await the authentication of the user instead of blocking the calling thread, every other operation is performed on the security queue.
+
+    - Parameter op_code: The 'OpCode' of the operation.
+    - Parameter request: A 'RustVec<UInt8>' holding the JSON envelope with the arguments.
+    - Returns: A 'RustVec<UInt8>' holding the JSON encoded 'FfiResponse'.
+    */
+    func rustcall_dispatch_async(op_code: UInt32, request: RustVec<UInt8>) async -> RustVec<UInt8> {
+        do {
+            let request = try DispatchRequest(Data(request))
+            switch OpCode(rawValue: op_code) {
+                case .signData:
+                    return encode_response(await handle_sign_data_async(key_id: try request.string("key_id"), data: try request.bytes("data"), algorithm: try request.string("algorithm"), hash: try request.string("hash"), correlation_id: request.correlation_id))
+                case .decryptData:
+                    return encode_response(await handle_decrypt_data_async(key_id: try request.string("key_id"), data: try request.bytes("data"), algorithm: try request.string("algorithm"), hash: try request.string("hash"), correlation_id: request.correlation_id))
+                default:
+                    return encode_response(try await on_security_queue_async { () throws -> FfiResponse in
+                        set_correlation_id(request.correlation_id)
+                        return try dispatch(op_code: op_code, request: request)
+                    })
+            }
+        } catch {
+            log_failure("dispatch_async", error)
+            return encode_response(ffi_failure(error))
+        }
+    }
@@ -1,7 +1,19 @@
 #ifndef BRIDGING_HEADER_H
 #define BRIDGING_HEADER_H
 
+#import <Foundation/Foundation.h>
 #import "./generated/SwiftBridgeCore.h"
 #import "./generated/rust-calls-swift/rust-calls-swift.h"
 
+// Runs `block` and returns the Objective-C exception it raised, or nil if it returned normally.
+// Swift cannot catch Objective-C exceptions, which would otherwise terminate the process.
+static inline NSException *_Nullable crypto_layer_catch_exception(void (NS_NOESCAPE ^_Nonnull block)(void)) {
+    @try {
+        block();
+        return nil;
+    } @catch (NSException *exception) {
+        return exception;
+    }
+}
+
 #endif BRIDGING_HEADER_H
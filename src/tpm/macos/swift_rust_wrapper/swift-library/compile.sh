@@ -6,6 +6,9 @@ regex_file_path='\.build/libswift-library_ios\.a (Sources/[a-zA-Z_/-]+\.swift )+
 regex_header_path='^Sources\/[a-zA-Z0-9_-]+\/bridging-header\.h$'
 
 TARGET=$1
+# Additional flags of 'swift build', e.g. '--disable-automatic-resolution' to build offline with the
+# dependency versions pinned in Package.resolved. Set by build.rs.
+SWIFT_BUILD_FLAGS=${SWIFT_BUILD_FLAGS:-}
 
 file_path_valid=true
 is_valid_file_path() {
@@ -34,7 +37,7 @@ is_valid_header_path
 
 if $file_path_valid && $headder_path_valid; then
     if [ $# -eq 0 ]; then
-        echo "No target as argument given. Please provide 'ios' or 'macos' as target."
+        echo "No target as argument given. Please provide 'ios', 'macos' or 'xcframework' as target."
         exit 1
     fi
 
@@ -44,6 +47,7 @@ if $file_path_valid && $headder_path_valid; then
         -emit-library -static -F /swift-library \
         -o $compile_files_path  \
         -import-objc-header $compile_header_path \
+        || exit 1
 
         echo "Compiled for: $TARGET"
 
@@ -52,11 +56,57 @@ if $file_path_valid && $headder_path_valid; then
         -Xswiftc -static \
         -Xswiftc -import-objc-header \
         -Xswiftc $compile_header_path \
-        
+        $SWIFT_BUILD_FLAGS \
+        || exit 1
+
+        echo "Compiled for: $TARGET"
+
+    elif [ "$TARGET" == "xcframework" ]; then
+        # Distributable SecureEnclaveBindings.xcframework with a universal macOS slice, an iOS device
+        # slice and a universal iOS simulator slice. Each slice holds libswift-library.a.
+        xcframework_dir='.build/xcframework'
+        swift_sources=${compile_files_path#* }
+        rm -rf $xcframework_dir .build/SecureEnclaveBindings.xcframework
+        mkdir -p $xcframework_dir/headers/generated/rust-calls-swift
+
+        # compile_slice <target triple> <sdk>
+        compile_slice() {
+            mkdir -p $xcframework_dir/$1
+            swiftc -target $1 \
+            -sdk $(xcrun --sdk $2 --show-sdk-path) \
+            -emit-library -static -module-name swift_library -parse-as-library -O \
+            -o $xcframework_dir/$1/libswift-library.a $swift_sources \
+            -import-objc-header $compile_header_path \
+            || exit 1
+        }
+        compile_slice arm64-apple-macos11.0 macosx
+        compile_slice x86_64-apple-macos11.0 macosx
+        compile_slice arm64-apple-ios14.0 iphoneos
+        compile_slice arm64-apple-ios14.0-simulator iphonesimulator
+        compile_slice x86_64-apple-ios14.0-simulator iphonesimulator
+
+        mkdir -p $xcframework_dir/macos $xcframework_dir/ios-simulator
+        lipo -create $xcframework_dir/{arm64,x86_64}-apple-macos11.0/libswift-library.a \
+        -output $xcframework_dir/macos/libswift-library.a || exit 1
+        lipo -create $xcframework_dir/{arm64,x86_64}-apple-ios14.0-simulator/libswift-library.a \
+        -output $xcframework_dir/ios-simulator/libswift-library.a || exit 1
+
+        cp $compile_header_path $xcframework_dir/headers/
+        cp Sources/swift-library/generated/SwiftBridgeCore.h $xcframework_dir/headers/generated/
+        cp Sources/swift-library/generated/rust-calls-swift/rust-calls-swift.h $xcframework_dir/headers/generated/rust-calls-swift/
+
+        xcodebuild -create-xcframework \
+        -library $xcframework_dir/macos/libswift-library.a -headers $xcframework_dir/headers \
+        -library $xcframework_dir/arm64-apple-ios14.0/libswift-library.a -headers $xcframework_dir/headers \
+        -library $xcframework_dir/ios-simulator/libswift-library.a -headers $xcframework_dir/headers \
+        -output .build/SecureEnclaveBindings.xcframework \
+        || exit 1
+
         echo "Compiled for: $TARGET"
 
     else
-        echo "No valid target as argument. Please choose 'ios' or 'macos' as targets."
+        echo "No valid target as argument. Please choose 'ios', 'macos' or 'xcframework' as targets."
+        exit 1
     fi
 else
     echo "Error. Minimum one parameter is unvalid."
@@ -4,8 +4,12 @@ use crate::common::{
             encryption::{AsymmetricEncryption, BlockCiphers},
             hashes::Hash,
         },
+        key_spec::{AccessControl, KeySpec},
         KeyUsage,
     },
+    error::SecurityModuleError,
+    factory::SecModules,
+    session_pool::SessionPoolConfig,
     traits::module_provider_config::ProviderConfig,
 };
 use std::any::Any;
@@ -21,11 +25,14 @@ pub mod macos;
 pub mod win;
 
 #[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TpmConfig {
     pub key_algorithm: AsymmetricEncryption,
     pub sym_algorithm: BlockCiphers,
     pub hash: Hash,
     pub key_usages: Vec<KeyUsage>,
+    /// Size and timeouts of the pool of TPM sessions used for operations on persistent keys.
+    pub session_pool: SessionPoolConfig,
 }
 
 impl ProviderConfig for TpmConfig {
@@ -41,12 +48,62 @@ impl TpmConfig {
         sym_algorithm: BlockCiphers,
         hash: Hash,
         key_usages: Vec<KeyUsage>,
+    ) -> Box<dyn Any> {
+        Self::new_with_session_pool(
+            key_algorithm,
+            sym_algorithm,
+            hash,
+            key_usages,
+            SessionPoolConfig::default(),
+        )
+    }
+
+    /// Creates a boxed `TpmConfig` from a validated `KeySpec`, to be passed to `create_key`
+    /// together with `spec.label()`. The session pool uses the acquire timeout of
+    /// `SecModules::config`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the config on success, or a `SecurityModuleError::InvalidKeySpec`
+    /// if the spec requires user authentication, which the TPM providers cannot enforce, or an
+    /// exportable key, since the TPM providers keep private keys in the TPM.
+    pub fn from_spec(spec: &KeySpec) -> Result<Box<dyn Any>, SecurityModuleError> {
+        if spec.access() != AccessControl::None {
+            return Err(SecurityModuleError::InvalidKeySpec(format!(
+                "Access control {:?} is not supported by the TPM",
+                spec.access()
+            )));
+        }
+        if spec.exportable() {
+            return Err(SecurityModuleError::InvalidKeySpec(
+                "Exportable keys are not supported by the TPM".to_owned(),
+            ));
+        }
+        let defaults = Self::default();
+        Ok(Self::new_with_session_pool(
+            spec.asymmetric_algorithm()
+                .unwrap_or(defaults.key_algorithm),
+            spec.symmetric_algorithm().unwrap_or(defaults.sym_algorithm),
+            spec.hash(),
+            spec.key_usages(),
+            SecModules::config().session_pool_config(),
+        ))
+    }
+
+    /// Like `new`, but additionally configures the pool of TPM sessions.
+    pub fn new_with_session_pool(
+        key_algorithm: AsymmetricEncryption,
+        sym_algorithm: BlockCiphers,
+        hash: Hash,
+        key_usages: Vec<KeyUsage>,
+        session_pool: SessionPoolConfig,
     ) -> Box<dyn Any> {
         Box::new(Self {
             key_algorithm,
             sym_algorithm,
             hash,
             key_usages,
+            session_pool,
         })
     }
 }
@@ -1,6 +1,9 @@
 use super::TpmProvider;
 use crate::{
-    common::{error::SecurityModuleError, traits::key_handle::KeyHandle},
+    common::{
+        crypto::secret::SecretBytes, error::SecurityModuleError, latency::key_id_hash,
+        traits::key_handle::KeyHandle,
+    },
     tpm::core::error::TpmError,
 };
 use tracing::instrument;
@@ -30,7 +33,7 @@ impl KeyHandle for TpmProvider {
     /// # Returns
     ///
     /// A `Result` containing the signature as a `Vec<u8>` on success, or a `SecurityModuleError` on failure.
-    #[instrument]
+    #[instrument(skip_all, fields(crypto.key_id_hash = %key_id_hash(&self.key_id), crypto.payload.size = data.len()))]
     fn sign_data(&self, data: &[u8]) -> Result<Vec<u8>, SecurityModuleError> {
         // Open an algorithm provider for SHA-512
         let mut alg_handle: BCRYPT_ALG_HANDLE = self.hash.unwrap().into();
@@ -154,8 +157,8 @@ impl KeyHandle for TpmProvider {
     /// # Returns
     ///
     /// A `Result` containing the decrypted data as a `Vec<u8>` on success, or a `SecurityModuleError` on failure.
-    #[instrument]
-    fn decrypt_data(&self, encrypted_data: &[u8]) -> Result<Vec<u8>, SecurityModuleError> {
+    #[instrument(skip_all, fields(crypto.key_id_hash = %key_id_hash(&self.key_id), crypto.payload.size = encrypted_data.len()))]
+    fn decrypt_data(&self, encrypted_data: &[u8]) -> Result<SecretBytes, SecurityModuleError> {
         let mut decrypted_data_len: u32 = 0;
 
         // First, determine the size of the decrypted data without actually decrypting
@@ -174,8 +177,8 @@ impl KeyHandle for TpmProvider {
             return Err(TpmError::Win(windows::core::Error::from_win32()).into());
         }
 
-        // Allocate a buffer for the decrypted data
-        let mut decrypted_data = vec![0u8; decrypted_data_len as usize];
+        // Allocate a buffer for the decrypted data, zeroed again when dropped
+        let mut decrypted_data = SecretBytes::zeroed(decrypted_data_len as usize);
 
         // Perform the actual decryption
         if unsafe {
@@ -193,8 +196,8 @@ impl KeyHandle for TpmProvider {
             return Err(TpmError::Win(windows::core::Error::from_win32()).into());
         }
 
-        // Resize the buffer to match the actual decrypted data length
-        decrypted_data.resize(decrypted_data_len as usize, 0);
+        // Shrink the buffer to match the actual decrypted data length
+        decrypted_data.truncate(decrypted_data_len as usize);
 
         Ok(decrypted_data)
     }
@@ -210,7 +213,7 @@ impl KeyHandle for TpmProvider {
     /// # Returns
     ///
     /// A `Result` containing the encrypted data as a `Vec<u8>` on success, or a `SecurityModuleError` on failure.
-    #[instrument]
+    #[instrument(skip_all, fields(crypto.key_id_hash = %key_id_hash(&self.key_id), crypto.payload.size = data.len()))]
     fn encrypt_data(&self, data: &[u8]) -> Result<Vec<u8>, SecurityModuleError> {
         // First call to determine the size of the encrypted data
         let mut encrypted_data_len: u32 = 0;
@@ -268,7 +271,7 @@ impl KeyHandle for TpmProvider {
     ///
     /// A `Result` indicating whether the signature is valid (`true`) or not (`false`),
     /// or a `SecurityModuleError` on failure.
-    #[instrument]
+    #[instrument(skip_all, fields(crypto.key_id_hash = %key_id_hash(&self.key_id), crypto.payload.size = data.len(), crypto.signature.size = signature.len()))]
     fn verify_signature(&self, data: &[u8], signature: &[u8]) -> Result<bool, SecurityModuleError> {
         // Open an algorithm provider for SHA-256, just like in sign_data
         let mut alg_handle = BCRYPT_ALG_HANDLE::default();
@@ -5,6 +5,7 @@ use crate::common::crypto::{
     },
     KeyUsage,
 };
+use crate::common::latency::key_id_hash;
 use tracing::instrument;
 use windows::{
     core::PCWSTR,
@@ -50,7 +51,7 @@ impl TpmProvider {
     /// # Returns
     ///
     /// A new instance of `TpmProvider` with the specified `key_id`.
-    #[instrument]
+    #[instrument(skip_all, fields(crypto.key_id_hash = %key_id_hash(&key_id)))]
     pub fn new(key_id: String) -> Self {
         Self {
             key_id,
@@ -6,6 +6,7 @@ use crate::{
             KeyUsage,
         },
         error::SecurityModuleError,
+        latency::key_id_hash,
         traits::module_provider::Provider,
     },
     tpm::{core::error::TpmError, TpmConfig},
@@ -48,7 +49,7 @@ impl Provider for TpmProvider {
     ///
     /// A `Result` that, on success, contains `Ok(())`, indicating that the key was created successfully.
     /// On failure, it returns a `SecurityModuleError`.
-    #[instrument]
+    #[instrument(skip_all, fields(crypto.key_id_hash = %key_id_hash(key_id)))]
     fn create_key(
         &mut self,
         key_id: &str,
@@ -198,7 +199,7 @@ impl Provider for TpmProvider {
     ///
     /// A `Result` that, on success, contains `Ok(())`, indicating that the key was loaded successfully.
     /// On failure, it returns a `SecurityModuleError`.
-    #[instrument]
+    #[instrument(skip_all, fields(crypto.key_id_hash = %key_id_hash(key_id)))]
     fn load_key(&mut self, key_id: &str, config: Box<dyn Any>) -> Result<(), SecurityModuleError> {
         let config = config.downcast_ref::<TpmConfig>().unwrap();
 
@@ -300,7 +301,7 @@ impl Provider for TpmProvider {
     ///
     /// A `Result` that, on success, contains `Ok(())`, indicating that the module was initialized successfully.
     /// On failure, it returns a `SecurityModuleError`.
-    #[instrument]
+    #[instrument(skip_all, fields(crypto.key_id_hash = %key_id_hash(&self.key_id)))]
     fn initialize_module(&mut self) -> Result<(), SecurityModuleError> {
         let mut handle = NCRYPT_PROV_HANDLE::default();
 
@@ -0,0 +1,48 @@
+# Drives the mock provider through the Python bindings generated by UniFFI, see
+# tests/uniffi_bindings.rs.
+
+from crypto_layer import (
+    AccessControl,
+    CryptoLayerError,
+    CryptoProvider,
+    KeyAlgorithm,
+    KeyPurpose,
+    KeySpec,
+    abi_version,
+)
+
+assert abi_version() == 1
+
+provider = CryptoProvider.open("MOCK", "uniffi_sign")
+provider.create_key(
+    "uniffi_sign", KeySpec(algorithm=KeyAlgorithm.EC_P256, purposes=[KeyPurpose.SIGN])
+)
+signature = provider.sign(b"Hello, UniFFI!")
+assert provider.verify(b"Hello, UniFFI!", signature)
+assert not provider.verify(b"Hello, Kotlin!", signature)
+assert len(provider.public_key()) == 91
+
+provider = CryptoProvider.open("MOCK", "uniffi_encrypt")
+provider.create_key(
+    "uniffi_encrypt",
+    KeySpec(
+        algorithm=KeyAlgorithm.RSA2048,
+        purposes=[KeyPurpose.ENCRYPT],
+        access=AccessControl.NONE,
+        exportable=False,
+    ),
+)
+ciphertext = provider.encrypt(b"secret")
+assert provider.decrypt(ciphertext) == b"secret"
+
+try:
+    CryptoProvider.open("QUANTUM", "uniffi_errors")
+    raise AssertionError("opened an unknown module")
+except CryptoLayerError.UnsupportedModule:
+    pass
+
+try:
+    provider.decrypt(b"garbage")
+    raise AssertionError("decrypted garbage")
+except CryptoLayerError.Module as e:
+    assert e.code > 0
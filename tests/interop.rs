@@ -0,0 +1,379 @@
+//! Interop tests between the Rust API and the clients of the C API in other languages.
+//!
+//! Every client in `tests/interop` drives the mock provider of the dynamic library through its
+//! binding, the C API itself, the Python module or the Node.js package, and answers requests
+//! on stdin, one per line:
+//!
+//! - `open <key id>` opens the mock provider.
+//! - `create <key id> <algorithm> <purposes>` creates a key with the `CRYPTO_LAYER_ALGORITHM_*`
+//!   constant and `CRYPTO_LAYER_PURPOSE_*` flags.
+//! - `public_key` returns the public key of the current key.
+//! - `<operation> <data>` with `sign`, `encrypt`, `decrypt`, `envelope_encrypt` or
+//!   `envelope_decrypt` returns the result of the operation on the base64 encoded data.
+//!
+//! The client responds with `ok` followed by the base64 encoded result, or with `error` followed
+//! by the error code of the C API. The tests verify the signatures and envelopes of the clients
+//! with the Rust API, and have the clients decrypt envelopes of the Rust API, so that the
+//! formats of all languages stay the same.
+//!
+//! Run the tests with `cargo test --features ffi,test-utils,macos --test interop`, and add the
+//! `python` feature for the Python client and the `node` feature for the Node.js client. Clients
+//! whose compiler or interpreter is not installed are skipped.
+
+use base64::{prelude::BASE64_STANDARD, Engine};
+use crypto_layer::{
+    common::{
+        crypto::{
+            envelope::{Envelope, EnvelopeRef},
+            key_spec::{KeyAlgorithm, KeyPurpose, KeySpec},
+            public_key::PublicKey,
+        },
+        ecies,
+    },
+    ffi::c_api::*,
+    SecurityModuleError,
+};
+use openssl::{pkey::PKey, rsa::Padding};
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    process::{Child, ChildStdin, ChildStdout, Command, Stdio},
+};
+
+/// A client process speaking the protocol of the module documentation.
+struct Client {
+    name: &'static str,
+    child: Child,
+    stdin: Option<ChildStdin>,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl Client {
+    /// Starts the client, or returns `None` if its program is not installed.
+    fn spawn(name: &'static str, mut command: Command) -> Option<Self> {
+        let mut child = match command.stdin(Stdio::piped()).stdout(Stdio::piped()).spawn() {
+            Ok(child) => child,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                eprintln!(
+                    "Skipping the {} client, {:?} is not installed",
+                    name, command
+                );
+                return None;
+            }
+            Err(e) => panic!("Cannot start the {} client: {}", name, e),
+        };
+        Some(Self {
+            name,
+            stdin: child.stdin.take(),
+            stdout: BufReader::new(child.stdout.take().unwrap()),
+            child,
+        })
+    }
+
+    /// Sends `request` and returns the result, or the error code of the C API.
+    fn call(&mut self, request: &str) -> Result<Vec<u8>, i32> {
+        let stdin = self.stdin.as_mut().unwrap();
+        writeln!(stdin, "{}", request).unwrap();
+        stdin.flush().unwrap();
+        let mut line = String::new();
+        self.stdout.read_line(&mut line).unwrap();
+        let mut fields = line.split_whitespace();
+        match (fields.next(), fields.next()) {
+            (Some("ok"), result) => Ok(BASE64_STANDARD.decode(result.unwrap_or("")).unwrap()),
+            (Some("error"), Some(code)) => Err(code.parse().unwrap()),
+            _ => panic!(
+                "The {} client responded {:?} to {}",
+                self.name, line, request
+            ),
+        }
+    }
+
+    fn ok(&mut self, request: &str) -> Vec<u8> {
+        self.call(request).unwrap_or_else(|code| {
+            panic!("{} failed in the {} client: {}", request, self.name, code)
+        })
+    }
+
+    fn operation(&mut self, operation: &str, data: &[u8]) -> Result<Vec<u8>, i32> {
+        self.call(&format!("{} {}", operation, BASE64_STANDARD.encode(data)))
+    }
+
+    /// Opens the mock provider with a new key and returns the spec of the key.
+    fn create_key(
+        &mut self,
+        key_id: &str,
+        algorithm: KeyAlgorithm,
+        purpose: KeyPurpose,
+    ) -> KeySpec {
+        let spec = KeySpec::builder()
+            .algorithm(algorithm)
+            .usage(purpose)
+            .label(key_id)
+            .build()
+            .unwrap();
+        self.ok(&format!("open {}", key_id));
+        self.ok(&format!(
+            "create {} {} {}",
+            key_id,
+            algorithm_constant(algorithm),
+            purpose_flag(purpose)
+        ));
+        spec
+    }
+
+    fn public_key(&mut self, spec: &KeySpec) -> PublicKey {
+        PublicKey::from_der(
+            &self.ok("public_key"),
+            spec.asymmetric_algorithm().unwrap(),
+            spec.hash(),
+        )
+        .unwrap()
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        // Closing stdin ends the client.
+        drop(self.stdin.take());
+        let status = self.child.wait().unwrap();
+        if !std::thread::panicking() {
+            assert!(
+                status.success(),
+                "The {} client failed: {}",
+                self.name,
+                status
+            );
+        }
+    }
+}
+
+fn algorithm_constant(algorithm: KeyAlgorithm) -> u32 {
+    match algorithm {
+        KeyAlgorithm::EcP256 => CRYPTO_LAYER_ALGORITHM_EC_P256,
+        KeyAlgorithm::EcP384 => CRYPTO_LAYER_ALGORITHM_EC_P384,
+        KeyAlgorithm::EcP521 => CRYPTO_LAYER_ALGORITHM_EC_P521,
+        KeyAlgorithm::Rsa2048 => CRYPTO_LAYER_ALGORITHM_RSA_2048,
+        KeyAlgorithm::Rsa3072 => CRYPTO_LAYER_ALGORITHM_RSA_3072,
+        KeyAlgorithm::Rsa4096 => CRYPTO_LAYER_ALGORITHM_RSA_4096,
+        KeyAlgorithm::Aes128Gcm => CRYPTO_LAYER_ALGORITHM_AES_128_GCM,
+        KeyAlgorithm::Aes256Gcm => CRYPTO_LAYER_ALGORITHM_AES_256_GCM,
+        _ => panic!("The C API has no constant for {:?} keys", algorithm),
+    }
+}
+
+fn purpose_flag(purpose: KeyPurpose) -> u32 {
+    match purpose {
+        KeyPurpose::Sign => CRYPTO_LAYER_PURPOSE_SIGN,
+        KeyPurpose::Encrypt => CRYPTO_LAYER_PURPOSE_ENCRYPT,
+        KeyPurpose::KeyAgreement => CRYPTO_LAYER_PURPOSE_KEY_AGREEMENT,
+        _ => panic!("The C API has no flag for the purpose {:?}", purpose),
+    }
+}
+
+/// Returns the directory of the dynamic library. Cargo builds it into the directory of the
+/// tests, `target/debug/deps`, and copies it to `target/debug` only for `cargo build`.
+fn library_dir() -> PathBuf {
+    let exe = std::env::current_exe().unwrap();
+    exe.parent().unwrap().to_owned()
+}
+
+fn library() -> PathBuf {
+    let name = if cfg!(target_os = "macos") {
+        "libcrypto_layer.dylib"
+    } else {
+        "libcrypto_layer.so"
+    };
+    let library = library_dir().join(name);
+    assert!(library.exists(), "{} was not built", library.display());
+    library
+}
+
+fn interop_dir() -> &'static Path {
+    Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/interop"))
+}
+
+/// Runs the checks of all artifacts against `client`.
+fn check(client: &mut Client) {
+    check_signatures(client);
+    check_envelopes(client);
+    check_rsa_encryption(client);
+    check_errors(client);
+}
+
+fn check_signatures(client: &mut Client) {
+    for algorithm in [
+        KeyAlgorithm::EcP256,
+        KeyAlgorithm::EcP384,
+        KeyAlgorithm::Rsa2048,
+    ] {
+        let key_id = format!("interop_sign_{:?}", algorithm);
+        let spec = client.create_key(&key_id, algorithm, KeyPurpose::Sign);
+        let public_key = client.public_key(&spec);
+
+        let signature = client.operation("sign", b"release.tar.gz").unwrap();
+
+        assert!(
+            public_key.verify(b"release.tar.gz", &signature).unwrap(),
+            "{:?} signature of the {} client",
+            algorithm,
+            client.name
+        );
+        assert!(!public_key.verify(b"tampered.tar.gz", &signature).unwrap());
+    }
+}
+
+fn check_envelopes(client: &mut Client) {
+    for algorithm in [KeyAlgorithm::EcP256, KeyAlgorithm::EcP384] {
+        let key_id = format!("interop_envelope_{:?}", algorithm);
+        let spec = client.create_key(&key_id, algorithm, KeyPurpose::KeyAgreement);
+        let public_key = client.public_key(&spec);
+        let secret: Vec<u8> = (0..50_000).map(|i| (i % 251) as u8).collect();
+
+        // Rust encrypts, the client decrypts.
+        let envelope = ecies::encrypt_for(&public_key, &secret).unwrap();
+        assert_eq!(
+            client.operation("envelope_decrypt", &envelope),
+            Ok(secret.clone())
+        );
+        let mut tampered = envelope.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert_eq!(
+            client.operation("envelope_decrypt", &tampered),
+            Err(SecurityModuleError::DecryptionError(String::new()).code() as i32)
+        );
+
+        // The client encrypts, Rust parses the envelope and encodes it again.
+        let envelope = client.operation("envelope_encrypt", &secret).unwrap();
+        let parsed = EnvelopeRef::parse(&envelope).unwrap();
+        assert_eq!(parsed.aead, ecies::DEFAULT_AEAD);
+        assert_eq!(parsed.kdf, Some(ecies::DEFAULT_KDF));
+        assert_eq!(parsed.key_id, ecies::recipient_id(&public_key).unwrap());
+        PublicKey::from_ec_point(
+            parsed.ephemeral_public_key.unwrap(),
+            spec.asymmetric_algorithm().unwrap(),
+            spec.hash(),
+        )
+        .unwrap();
+        assert_eq!(
+            parsed.ciphertext.len(),
+            secret.len() + parsed.aead.tag_len()
+        );
+        let encoded = Envelope::from_bytes(&envelope).unwrap().to_bytes().unwrap();
+        assert_eq!(encoded, envelope);
+        assert_eq!(client.operation("envelope_decrypt", &encoded), Ok(secret));
+    }
+}
+
+fn check_rsa_encryption(client: &mut Client) {
+    let spec = client.create_key("interop_rsa", KeyAlgorithm::Rsa2048, KeyPurpose::Encrypt);
+    let public_key = PKey::public_key_from_der(&client.ok("public_key")).unwrap();
+    assert_eq!(spec.algorithm(), KeyAlgorithm::Rsa2048);
+
+    let rsa = public_key.rsa().unwrap();
+    let mut ciphertext = vec![0; rsa.size() as usize];
+    let len = rsa
+        .public_encrypt(b"database password", &mut ciphertext, Padding::PKCS1_OAEP)
+        .unwrap();
+    ciphertext.truncate(len);
+
+    assert_eq!(
+        client.operation("decrypt", &ciphertext),
+        Ok(b"database password".to_vec())
+    );
+    let ciphertext = client.operation("encrypt", b"backup key").unwrap();
+    assert_eq!(ciphertext.len(), 256);
+}
+
+fn check_errors(client: &mut Client) {
+    client.ok("open interop_errors");
+
+    assert_eq!(
+        client.call("create interop_errors 99 1"),
+        Err(CRYPTO_LAYER_ERROR_INVALID_ARGUMENT)
+    );
+    assert_eq!(
+        client.operation("sign", b"no key"),
+        Err(SecurityModuleError::KeyError.code() as i32)
+    );
+}
+
+#[test]
+fn test_c_client() {
+    let program = std::env::temp_dir().join(format!("crypto-layer-interop-{}", std::process::id()));
+    let lib_dir = library_dir();
+    library();
+    let mut cc = Command::new(std::env::var("CC").unwrap_or_else(|_| "cc".to_owned()));
+    cc.arg(interop_dir().join("client.c"))
+        .arg("-I")
+        .arg(concat!(env!("CARGO_MANIFEST_DIR"), "/include"))
+        .arg("-L")
+        .arg(&lib_dir)
+        .arg(format!("-Wl,-rpath,{}", lib_dir.display()))
+        .arg("-lcrypto_layer")
+        .arg("-o")
+        .arg(&program);
+    let status = match cc.status() {
+        Ok(status) => status,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            eprintln!("Skipping the C client, {:?} is not installed", cc);
+            return;
+        }
+        Err(e) => panic!("Cannot compile the C client: {}", e),
+    };
+    assert!(status.success(), "The C client does not compile");
+
+    // The search path of the loader takes precedence over the RUNPATH of the client, and cargo
+    // puts `target/debug` on it, which may hold a library of an earlier build without `ffi`.
+    let mut command = Command::new(&program);
+    let library_path = if cfg!(target_os = "macos") {
+        "DYLD_LIBRARY_PATH"
+    } else {
+        "LD_LIBRARY_PATH"
+    };
+    command.env(library_path, &lib_dir);
+    if let Some(mut client) = Client::spawn("C", command) {
+        check(&mut client);
+    }
+    std::fs::remove_file(program).unwrap();
+}
+
+/// Returns the directory of the Python package, whose extension module is a copy of the library.
+#[cfg(feature = "python")]
+fn python_package() -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("interop-python");
+    let package = dir.join("crypto_layer");
+    std::fs::create_dir_all(&package).unwrap();
+    std::fs::copy(
+        concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/python/crypto_layer/__init__.py"
+        ),
+        package.join("__init__.py"),
+    )
+    .unwrap();
+    std::fs::copy(library(), package.join("_crypto_layer.abi3.so")).unwrap();
+    dir
+}
+
+#[cfg(feature = "python")]
+#[test]
+fn test_python_client() {
+    let mut python = Command::new("python3");
+    python
+        .arg(interop_dir().join("client.py"))
+        .env("PYTHONPATH", python_package());
+    if let Some(mut client) = Client::spawn("Python", python) {
+        check(&mut client);
+    }
+}
+
+#[cfg(feature = "node")]
+#[test]
+fn test_node_client() {
+    let mut node = Command::new("node");
+    node.arg(interop_dir().join("client.js"))
+        .env("CRYPTO_LAYER_ADDON", library());
+    if let Some(mut client) = Client::spawn("Node.js", node) {
+        check(&mut client);
+    }
+}
@@ -0,0 +1,126 @@
+/* The interop client of tests/interop.rs for the C API, see the protocol there. */
+
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#include "crypto_layer.h"
+
+static const char ALPHABET[] =
+    "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/* Decodes the base64 `text` into `out`, which must hold 3 / 4 of its length, and returns the
+ * number of bytes or -1. */
+static long decode(const char *text, uint8_t *out) {
+    size_t len = strlen(text), n = 0;
+    uint32_t bits = 0;
+    int count = 0;
+    for (size_t i = 0; i < len && text[i] != '='; i++) {
+        const char *c = strchr(ALPHABET, text[i]);
+        if (c == NULL) {
+            return -1;
+        }
+        bits = (bits << 6) | (uint32_t)(c - ALPHABET);
+        if (++count == 4) {
+            out[n++] = bits >> 16;
+            out[n++] = bits >> 8;
+            out[n++] = bits;
+            bits = 0;
+            count = 0;
+        }
+    }
+    if (count == 3) {
+        out[n++] = bits >> 10;
+        out[n++] = bits >> 2;
+    } else if (count == 2) {
+        out[n++] = bits >> 4;
+    } else if (count == 1) {
+        return -1;
+    }
+    return (long)n;
+}
+
+static void print_ok(const uint8_t *data, size_t len) {
+    fputs("ok ", stdout);
+    for (size_t i = 0; i < len; i += 3) {
+        uint32_t bits = (uint32_t)data[i] << 16;
+        if (i + 1 < len) {
+            bits |= (uint32_t)data[i + 1] << 8;
+        }
+        if (i + 2 < len) {
+            bits |= data[i + 2];
+        }
+        putchar(ALPHABET[bits >> 18]);
+        putchar(ALPHABET[(bits >> 12) & 63]);
+        putchar(i + 1 < len ? ALPHABET[(bits >> 6) & 63] : '=');
+        putchar(i + 2 < len ? ALPHABET[bits & 63] : '=');
+    }
+    putchar('\n');
+}
+
+typedef int32_t (*transform)(const CryptoLayerProvider *, const uint8_t *, size_t,
+                             CryptoLayerBuffer *);
+
+static transform find(const char *command) {
+    if (strcmp(command, "sign") == 0) {
+        return crypto_layer_sign;
+    } else if (strcmp(command, "encrypt") == 0) {
+        return crypto_layer_encrypt;
+    } else if (strcmp(command, "decrypt") == 0) {
+        return crypto_layer_decrypt;
+    } else if (strcmp(command, "envelope_encrypt") == 0) {
+        return crypto_layer_envelope_encrypt;
+    } else if (strcmp(command, "envelope_decrypt") == 0) {
+        return crypto_layer_envelope_decrypt;
+    }
+    return NULL;
+}
+
+int main(void) {
+    if (crypto_layer_abi_version() != CRYPTO_LAYER_ABI_VERSION) {
+        fprintf(stderr, "The library has another ABI version\n");
+        return 1;
+    }
+    static char line[1 << 20];
+    static uint8_t data[sizeof(line)];
+    CryptoLayerProvider *provider = NULL;
+    while (fgets(line, sizeof(line), stdin) != NULL) {
+        line[strcspn(line, "\n")] = '\0';
+        char *command = strtok(line, " ");
+        char *arg = strtok(NULL, " ");
+        CryptoLayerBuffer out = {0};
+        int32_t status = CRYPTO_LAYER_ERROR_INVALID_ARGUMENT;
+        if (command == NULL) {
+            /* An empty line is an invalid request. */
+        } else if (strcmp(command, "open") == 0) {
+            crypto_layer_provider_free(provider);
+            provider = NULL;
+            status = crypto_layer_provider_open("MOCK", arg, &provider);
+        } else if (strcmp(command, "create") == 0) {
+            char *algorithm = strtok(NULL, " ");
+            char *purposes = strtok(NULL, " ");
+            if (algorithm != NULL && purposes != NULL) {
+                CryptoLayerKeySpec spec = {(uint32_t)atoi(algorithm), (uint32_t)atoi(purposes),
+                                           CRYPTO_LAYER_ACCESS_NONE, false};
+                status = crypto_layer_create_key(provider, arg, &spec);
+            }
+        } else if (strcmp(command, "public_key") == 0) {
+            status = crypto_layer_public_key(provider, &out);
+        } else if (find(command) != NULL && arg != NULL) {
+            long len = decode(arg, data);
+            if (len >= 0) {
+                status = find(command)(provider, data, (size_t)len, &out);
+            }
+        }
+        if (status == CRYPTO_LAYER_OK) {
+            print_ok(out.data, out.len);
+        } else {
+            printf("error %d\n", status);
+        }
+        fflush(stdout);
+        crypto_layer_buffer_free(&out);
+    }
+    crypto_layer_provider_free(provider);
+    return 0;
+}
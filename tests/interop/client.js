@@ -0,0 +1,58 @@
+'use strict';
+
+// The interop client of tests/interop.rs for the Node.js package, see the protocol there.
+
+const readline = require('readline');
+const { Provider } = require('../../node');
+
+const ALGORITHMS = ['', 'EcP256', 'EcP384', 'EcP521', 'Rsa2048', 'Rsa3072', 'Rsa4096'];
+const PURPOSES = [
+  [1, 'Sign'],
+  [2, 'Encrypt'],
+  [4, 'KeyAgreement'],
+];
+
+async function handle(state, command, args) {
+  switch (command) {
+    case 'open':
+      state.provider = await Provider.open('MOCK', args[0]);
+      return Buffer.alloc(0);
+    case 'create':
+      await state.provider.createKey(args[0], {
+        algorithm: ALGORITHMS[Number(args[1])],
+        purposes: PURPOSES.filter(([flag]) => Number(args[2]) & flag).map(([, name]) => name),
+      });
+      return Buffer.alloc(0);
+    case 'public_key':
+      return state.provider.publicKey();
+    default: {
+      const method = {
+        sign: 'sign',
+        encrypt: 'encrypt',
+        decrypt: 'decrypt',
+        envelope_encrypt: 'envelopeEncrypt',
+        envelope_decrypt: 'envelopeDecrypt',
+      }[command];
+      return state.provider[method](Buffer.from(args[0], 'base64'));
+    }
+  }
+}
+
+async function main() {
+  const state = {};
+  const lines = readline.createInterface({ input: process.stdin });
+  for await (const line of lines) {
+    const [command, ...args] = line.split(' ');
+    try {
+      const result = await handle(state, command, args);
+      process.stdout.write(`ok ${Buffer.from(result).toString('base64')}\n`);
+    } catch (error) {
+      process.stdout.write(`error ${error.code}\n`);
+    }
+  }
+  if (state.provider) {
+    state.provider.close();
+  }
+}
+
+main();
@@ -0,0 +1,37 @@
+"""The interop client of tests/interop.rs for the Python module, see the protocol there."""
+
+import base64
+import sys
+
+from crypto_layer import CryptoLayerError, Provider
+
+
+def main():
+    provider = None
+    for line in sys.stdin:
+        command, *args = line.split()
+        try:
+            if command == "open":
+                provider = Provider("MOCK", args[0])
+                result = b""
+            elif command == "create":
+                provider.create_key(args[0], int(args[1]), int(args[2]))
+                result = b""
+            elif command == "public_key":
+                result = provider.public_key()
+            else:
+                function = {
+                    "sign": provider.sign,
+                    "encrypt": provider.encrypt,
+                    "decrypt": provider.decrypt,
+                    "envelope_encrypt": provider.envelope_encrypt,
+                    "envelope_decrypt": provider.envelope_decrypt,
+                }[command]
+                result = function(base64.b64decode(args[0]))
+            print("ok", base64.b64encode(result).decode(), flush=True)
+        except CryptoLayerError as error:
+            print("error", error.code, flush=True)
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,68 @@
+//! Runs the tests of the Python module in `python/tests` against the mock provider.
+//!
+//! The test assembles the package `python/crypto_layer` with a copy of the dynamic library as its
+//! extension module, as maturin does, and runs the tests with it. Run it with
+//! `cargo test --features python,test-utils,macos --test python_module`. It is skipped if
+//! `python3` is not installed.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+fn manifest_dir() -> &'static Path {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+}
+
+fn library() -> PathBuf {
+    let name = if cfg!(target_os = "macos") {
+        "libcrypto_layer.dylib"
+    } else if cfg!(windows) {
+        "crypto_layer.dll"
+    } else {
+        "libcrypto_layer.so"
+    };
+    let exe = std::env::current_exe().unwrap();
+    let library = exe.parent().unwrap().join(name);
+    assert!(library.exists(), "{} was not built", library.display());
+    library
+}
+
+/// Returns the directory of the package, whose extension module is a copy of the library.
+fn python_package() -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("python");
+    let package = dir.join("crypto_layer");
+    fs::create_dir_all(&package).unwrap();
+    fs::copy(
+        manifest_dir().join("python/crypto_layer/__init__.py"),
+        package.join("__init__.py"),
+    )
+    .unwrap();
+    let extension = if cfg!(windows) {
+        "_crypto_layer.pyd"
+    } else {
+        "_crypto_layer.abi3.so"
+    };
+    fs::copy(library(), package.join(extension)).unwrap();
+    dir
+}
+
+#[test]
+fn test_python_module() {
+    let tests = manifest_dir().join("python/tests");
+    let status = match Command::new("python3")
+        .args(["-m", "unittest", "discover", "-s"])
+        .arg(&tests)
+        .env("PYTHONPATH", python_package())
+        .status()
+    {
+        Ok(status) => status,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            eprintln!("Skipping the Python module, python3 is not installed");
+            return;
+        }
+        Err(e) => panic!("Cannot run the tests in {}: {}", tests.display(), e),
+    };
+    assert!(status.success(), "The tests in {} failed", tests.display());
+}
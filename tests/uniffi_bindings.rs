@@ -0,0 +1,60 @@
+//! Smoke test of the UniFFI bindings of `src/crypto_layer.udl`.
+//!
+//! The test generates the Python bindings with UniFFI next to a copy of the dynamic library and
+//! runs `tests/bindings/test_crypto_provider.py` with them against the mock provider. Run it with
+//! `cargo test --features uniffi,test-utils,macos --test uniffi_bindings`. It is skipped if
+//! `python3` is not installed.
+
+use camino::{Utf8Path, Utf8PathBuf};
+use std::{fs, io, process::Command};
+use uniffi::PythonBindingGenerator;
+
+fn manifest_dir() -> &'static Utf8Path {
+    Utf8Path::new(env!("CARGO_MANIFEST_DIR"))
+}
+
+fn library() -> Utf8PathBuf {
+    let name = if cfg!(target_os = "macos") {
+        "libcrypto_layer.dylib"
+    } else {
+        "libcrypto_layer.so"
+    };
+    let exe = Utf8PathBuf::try_from(std::env::current_exe().unwrap()).unwrap();
+    let library = exe.parent().unwrap().join(name);
+    assert!(library.exists(), "{} was not built", library);
+    library
+}
+
+#[test]
+fn test_python_bindings() {
+    let out_dir = Utf8Path::new(env!("CARGO_TARGET_TMPDIR")).join("uniffi-python");
+    fs::create_dir_all(&out_dir).unwrap();
+    let library = library();
+    uniffi::generate_bindings(
+        &manifest_dir().join("src/crypto_layer.udl"),
+        None,
+        PythonBindingGenerator,
+        Some(&out_dir),
+        Some(&library),
+        None,
+        false,
+    )
+    .unwrap();
+    // The bindings load the library from their own directory.
+    fs::copy(&library, out_dir.join(library.file_name().unwrap())).unwrap();
+
+    let script = manifest_dir().join("tests/bindings/test_crypto_provider.py");
+    let status = match Command::new("python3")
+        .arg(&script)
+        .env("PYTHONPATH", &out_dir)
+        .status()
+    {
+        Ok(status) => status,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            eprintln!("Skipping the Python bindings, python3 is not installed");
+            return;
+        }
+        Err(e) => panic!("Cannot run {}: {}", script, e),
+    };
+    assert!(status.success(), "{} failed", script);
+}